@@ -0,0 +1,32 @@
+-- Asynchronous TAXII 1.x result set preparation
+-- Result sets created for asynchronous polls start out PENDING and are
+-- materialized by a background worker before Poll Fulfillment serves them.
+-- Compatible with PostgreSQL 9.4+
+
+-- ============================================
+-- Result Set Status
+-- ============================================
+
+ALTER TABLE result_sets ADD COLUMN IF NOT EXISTS status VARCHAR(20) NOT NULL DEFAULT 'READY';
+ALTER TABLE result_sets ADD COLUMN IF NOT EXISTS content_block_count INTEGER;
+
+DO $$ BEGIN
+    CREATE INDEX ix_result_sets_status ON result_sets(status);
+EXCEPTION WHEN duplicate_table THEN NULL;
+END $$;
+
+-- ============================================
+-- Materialized Result Set Content
+-- ============================================
+
+CREATE TABLE IF NOT EXISTS result_set_content_blocks (
+    result_set_id VARCHAR(150) REFERENCES result_sets(id) ON DELETE CASCADE,
+    content_block_id INTEGER REFERENCES content_blocks(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    PRIMARY KEY (result_set_id, content_block_id)
+);
+
+DO $$ BEGIN
+    CREATE INDEX ix_result_set_content_blocks_position ON result_set_content_blocks(result_set_id, position);
+EXCEPTION WHEN duplicate_table THEN NULL;
+END $$;
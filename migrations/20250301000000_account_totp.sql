@@ -0,0 +1,32 @@
+-- TOTP two-factor authentication for password logins
+-- This migration is backward compatible - does not modify existing tables
+-- Compatible with PostgreSQL 9.4+
+
+-- ============================================
+-- Account TOTP Table
+-- ============================================
+
+-- One row per account with two-factor authentication enabled.
+-- The secret is encrypted with a key derived from the auth secret.
+CREATE TABLE IF NOT EXISTS account_totp (
+    account_id INT PRIMARY KEY REFERENCES accounts(id) ON DELETE CASCADE,
+    secret_encrypted TEXT NOT NULL,
+    last_used_step BIGINT,  -- last accepted time step, for replay prevention
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- ============================================
+-- Recovery Codes Table
+-- ============================================
+
+CREATE TABLE IF NOT EXISTS account_recovery_codes (
+    id SERIAL PRIMARY KEY,
+    account_id INT NOT NULL REFERENCES account_totp(account_id) ON DELETE CASCADE,
+    code_hash VARCHAR(64) NOT NULL,  -- hex-encoded SHA-256 of the code
+    used_at TIMESTAMPTZ
+);
+
+DO $$ BEGIN
+    CREATE INDEX ix_account_recovery_codes_account_id ON account_recovery_codes(account_id);
+EXCEPTION WHEN duplicate_table THEN NULL;
+END $$;
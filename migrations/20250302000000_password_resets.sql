@@ -0,0 +1,29 @@
+-- Password reset tokens issued and redeemed out of band
+-- This migration is backward compatible - does not modify existing tables
+-- Compatible with PostgreSQL 9.4+
+
+-- ============================================
+-- Password Resets Table
+-- ============================================
+
+-- Tokens are single-use and time-limited. Only a hash of the token is
+-- stored; the token itself is handed to the user out of band.
+CREATE TABLE IF NOT EXISTS password_resets (
+    id SERIAL PRIMARY KEY,
+    account_id INT NOT NULL REFERENCES accounts(id) ON DELETE CASCADE,
+    token_hash VARCHAR(64) UNIQUE NOT NULL,  -- hex-encoded SHA-256 of the token
+    expires_at TIMESTAMPTZ NOT NULL,
+    used_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+DO $$ BEGIN
+    CREATE INDEX ix_password_resets_account_id ON password_resets(account_id);
+EXCEPTION WHEN duplicate_table THEN NULL;
+END $$;
+
+-- Index for cleanup of expired tokens
+DO $$ BEGIN
+    CREATE INDEX ix_password_resets_expires_at ON password_resets(expires_at);
+EXCEPTION WHEN duplicate_table THEN NULL;
+END $$;
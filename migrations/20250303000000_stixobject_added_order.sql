@@ -0,0 +1,11 @@
+-- Index for "recently added" pagination of TAXII 2.x objects
+-- This migration is backward compatible - does not modify existing tables
+-- Compatible with PostgreSQL 9.4+
+
+-- Objects are paged per collection by (date_added, id). The index serves
+-- both the page queries and the latest date_added lookup on insert.
+DO $$ BEGIN
+    CREATE INDEX ix_opentaxii_stixobject_collection_added
+        ON opentaxii_stixobject(collection_id, date_added, id);
+EXCEPTION WHEN duplicate_table THEN NULL;
+END $$;
@@ -0,0 +1,18 @@
+-- Soft delete (tombstones) for TAXII 2.x objects
+-- This migration is backward compatible - only adds nullable columns
+-- Compatible with PostgreSQL 9.6+
+
+-- Deleted objects keep their row with the time and account of the
+-- deletion, so removals can be audited and propagated. Tombstones are
+-- removed for good by the periodic purge.
+ALTER TABLE opentaxii_stixobject
+    ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ,
+    ADD COLUMN IF NOT EXISTS deleted_by INT REFERENCES accounts(id) ON DELETE SET NULL;
+
+-- Index for listing deletions of a collection and purging old tombstones
+DO $$ BEGIN
+    CREATE INDEX ix_opentaxii_stixobject_deleted
+        ON opentaxii_stixobject(collection_id, deleted_at)
+        WHERE deleted_at IS NOT NULL;
+EXCEPTION WHEN duplicate_table THEN NULL;
+END $$;
@@ -0,0 +1,34 @@
+-- Per-collection retention policies
+-- This migration is backward compatible - only adds nullable columns and a new table
+-- Compatible with PostgreSQL 9.4+
+
+-- ============================================
+-- Retention Policies
+-- ============================================
+
+-- Days content is kept in a collection; NULL keeps it forever
+ALTER TABLE opentaxii_collection
+    ADD COLUMN IF NOT EXISTS retention_days INTEGER CHECK (retention_days > 0);
+
+ALTER TABLE data_collections
+    ADD COLUMN IF NOT EXISTS retention_days INTEGER CHECK (retention_days > 0);
+
+-- ============================================
+-- Retention Purge Log
+-- ============================================
+
+-- One row per purge that removed (or retained) anything, for auditing
+CREATE TABLE IF NOT EXISTS retention_purges (
+    id BIGSERIAL PRIMARY KEY,
+    protocol VARCHAR(8) NOT NULL,  -- 'taxii1', 'taxii2'
+    collection_id VARCHAR(150) NOT NULL,
+    cutoff TIMESTAMPTZ NOT NULL,
+    purged BIGINT NOT NULL,
+    retained BIGINT NOT NULL,
+    purged_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+DO $$ BEGIN
+    CREATE INDEX ix_retention_purges_collection ON retention_purges(protocol, collection_id, purged_at);
+EXCEPTION WHEN duplicate_table THEN NULL;
+END $$;
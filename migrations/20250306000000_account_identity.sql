@@ -0,0 +1,13 @@
+-- STIX identity of accounts, for provenance of ingested objects
+-- This migration is backward compatible - only adds a nullable column
+-- Compatible with PostgreSQL 9.4+
+
+-- ============================================
+-- Account Identity
+-- ============================================
+
+-- STIX identity (identity--<uuid>) objects posted by the account are
+-- attributed to through created_by_ref; NULL if none is associated
+ALTER TABLE accounts
+    ADD COLUMN IF NOT EXISTS identity_id VARCHAR(100)
+    CHECK (identity_id LIKE 'identity--%');
@@ -0,0 +1,40 @@
+-- Full-text search over stored STIX object content
+-- This migration is backward compatible - only adds functions and an index
+-- Compatible with PostgreSQL 9.6+ (searching uses websearch_to_tsquery, PostgreSQL 11+)
+
+-- ============================================
+-- Search Vector
+-- ============================================
+
+-- Searchable text of an object, weighted by property:
+--   A: name, value (SCOs)
+--   B: labels
+--   C: description, pattern
+-- The text search configuration is fixed so the function is immutable and
+-- can back an expression index.
+CREATE OR REPLACE FUNCTION stix_search_vector(data JSON) RETURNS tsvector AS $$
+    SELECT setweight(to_tsvector('english'::regconfig,
+               coalesce(data->>'name', '') || ' ' || coalesce(data->>'value', '')), 'A')
+        || setweight(to_tsvector('english'::regconfig, coalesce(data->>'labels', '')), 'B')
+        || setweight(to_tsvector('english'::regconfig,
+               coalesce(data->>'description', '') || ' ' || coalesce(data->>'pattern', '')), 'C')
+$$ LANGUAGE SQL IMMUTABLE;
+
+-- Relevance tier of a matching object: 0 if the query matches its name or
+-- value alone, 1 if it matches with labels, 2 otherwise. Results are
+-- ordered by tier, so name matches come before description matches.
+CREATE OR REPLACE FUNCTION stix_search_rank(data JSON, query tsquery) RETURNS INTEGER AS $$
+    SELECT CASE
+        WHEN ts_filter(stix_search_vector(data), '{a}') @@ query THEN 0
+        WHEN ts_filter(stix_search_vector(data), '{a,b}') @@ query THEN 1
+        ELSE 2
+    END
+$$ LANGUAGE SQL IMMUTABLE;
+
+-- ============================================
+-- Search Index
+-- ============================================
+
+-- Queries must use stix_search_vector(serialized_data) to use the index
+CREATE INDEX IF NOT EXISTS ix_opentaxii_stixobject_search
+    ON opentaxii_stixobject USING GIN (stix_search_vector(serialized_data));
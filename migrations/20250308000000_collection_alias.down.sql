@@ -0,0 +1,5 @@
+-- Revert: path-safe TAXII 2.x collection aliases
+-- Aliases cleared by the migration are not restored
+-- Compatible with PostgreSQL 9.4+
+
+ALTER TABLE opentaxii_collection DROP CONSTRAINT IF EXISTS ck_opentaxii_collection_alias;
@@ -0,0 +1,26 @@
+-- Path-safe TAXII 2.x collection aliases
+-- This migration is backward compatible - existing aliases that are not
+-- path-safe are left in place (the constraint is not validated for them)
+-- Compatible with PostgreSQL 9.4+
+
+-- ============================================
+-- Collection Alias
+-- ============================================
+
+-- An empty alias can never be addressed; treat it as no alias
+UPDATE opentaxii_collection SET alias = NULL WHERE alias = '';
+
+-- Aliases are used as a URL path segment in place of the collection ID.
+-- An alias that looks like a UUID is allowed, but a collection whose ID is
+-- that UUID takes precedence when resolving the path.
+DO $$
+BEGIN
+    IF NOT EXISTS (
+        SELECT 1 FROM pg_constraint WHERE conname = 'ck_opentaxii_collection_alias'
+    ) THEN
+        ALTER TABLE opentaxii_collection
+            ADD CONSTRAINT ck_opentaxii_collection_alias
+            CHECK (alias <> '' AND strpos(alias, '/') = 0) NOT VALID;
+    END IF;
+END
+$$;
@@ -0,0 +1,59 @@
+-- Revert: store STIX object content as JSON again
+-- Drops the property filter indexes and restores the JSON search functions
+-- Compatible with PostgreSQL 9.6+
+
+-- ============================================
+-- Property Filter Indexes
+-- ============================================
+
+DROP INDEX IF EXISTS ix_opentaxii_stixobject_object_marking_refs;
+DROP INDEX IF EXISTS ix_opentaxii_stixobject_created_by_ref;
+DROP INDEX IF EXISTS ix_opentaxii_stixobject_pattern_type;
+DROP INDEX IF EXISTS ix_opentaxii_stixobject_labels;
+
+-- ============================================
+-- JSON Content
+-- ============================================
+
+DROP INDEX IF EXISTS ix_opentaxii_stixobject_search;
+
+-- Key order and whitespace of the original documents are not restored
+DO $$
+BEGIN
+    IF EXISTS (
+        SELECT 1 FROM information_schema.columns
+        WHERE table_name = 'opentaxii_stixobject'
+          AND column_name = 'serialized_data'
+          AND data_type = 'jsonb'
+    ) THEN
+        ALTER TABLE opentaxii_stixobject
+            ALTER COLUMN serialized_data TYPE JSON USING serialized_data::json;
+    END IF;
+END
+$$;
+
+-- ============================================
+-- Search Vector (JSON)
+-- ============================================
+
+CREATE OR REPLACE FUNCTION stix_search_vector(data JSON) RETURNS tsvector AS $$
+    SELECT setweight(to_tsvector('english'::regconfig,
+               coalesce(data->>'name', '') || ' ' || coalesce(data->>'value', '')), 'A')
+        || setweight(to_tsvector('english'::regconfig, coalesce(data->>'labels', '')), 'B')
+        || setweight(to_tsvector('english'::regconfig,
+               coalesce(data->>'description', '') || ' ' || coalesce(data->>'pattern', '')), 'C')
+$$ LANGUAGE SQL IMMUTABLE;
+
+CREATE OR REPLACE FUNCTION stix_search_rank(data JSON, query tsquery) RETURNS INTEGER AS $$
+    SELECT CASE
+        WHEN ts_filter(stix_search_vector(data), '{a}') @@ query THEN 0
+        WHEN ts_filter(stix_search_vector(data), '{a,b}') @@ query THEN 1
+        ELSE 2
+    END
+$$ LANGUAGE SQL IMMUTABLE;
+
+DROP FUNCTION IF EXISTS stix_search_rank(JSONB, tsquery);
+DROP FUNCTION IF EXISTS stix_search_vector(JSONB);
+
+CREATE INDEX IF NOT EXISTS ix_opentaxii_stixobject_search
+    ON opentaxii_stixobject USING GIN (stix_search_vector(serialized_data));
@@ -0,0 +1,78 @@
+-- Store STIX object content as JSONB, with indexes for property filters
+-- This migration is backward compatible - the content is converted in place
+-- and reads return the same documents
+-- Compatible with PostgreSQL 9.6+ (searching uses websearch_to_tsquery, PostgreSQL 11+)
+
+-- ============================================
+-- JSONB Content
+-- ============================================
+
+-- The search index depends on the JSON signature of stix_search_vector and
+-- is rebuilt below
+DROP INDEX IF EXISTS ix_opentaxii_stixobject_search;
+
+-- Converting parses every document once; JSONB drops insignificant
+-- whitespace and key order, which STIX does not depend on. Rewrites the
+-- table, so large installations should plan for the lock.
+DO $$
+BEGIN
+    IF EXISTS (
+        SELECT 1 FROM information_schema.columns
+        WHERE table_name = 'opentaxii_stixobject'
+          AND column_name = 'serialized_data'
+          AND data_type = 'json'
+    ) THEN
+        ALTER TABLE opentaxii_stixobject
+            ALTER COLUMN serialized_data TYPE JSONB USING serialized_data::jsonb;
+    END IF;
+END
+$$;
+
+-- ============================================
+-- Search Vector (JSONB)
+-- ============================================
+
+-- Same weighting as the JSON versions they replace
+CREATE OR REPLACE FUNCTION stix_search_vector(data JSONB) RETURNS tsvector AS $$
+    SELECT setweight(to_tsvector('english'::regconfig,
+               coalesce(data->>'name', '') || ' ' || coalesce(data->>'value', '')), 'A')
+        || setweight(to_tsvector('english'::regconfig, coalesce(data->>'labels', '')), 'B')
+        || setweight(to_tsvector('english'::regconfig,
+               coalesce(data->>'description', '') || ' ' || coalesce(data->>'pattern', '')), 'C')
+$$ LANGUAGE SQL IMMUTABLE;
+
+CREATE OR REPLACE FUNCTION stix_search_rank(data JSONB, query tsquery) RETURNS INTEGER AS $$
+    SELECT CASE
+        WHEN ts_filter(stix_search_vector(data), '{a}') @@ query THEN 0
+        WHEN ts_filter(stix_search_vector(data), '{a,b}') @@ query THEN 1
+        ELSE 2
+    END
+$$ LANGUAGE SQL IMMUTABLE;
+
+DROP FUNCTION IF EXISTS stix_search_rank(JSON, tsquery);
+DROP FUNCTION IF EXISTS stix_search_vector(JSON);
+
+CREATE INDEX IF NOT EXISTS ix_opentaxii_stixobject_search
+    ON opentaxii_stixobject USING GIN (stix_search_vector(serialized_data));
+
+-- ============================================
+-- Property Filter Indexes
+-- ============================================
+
+-- match[<property>] filters are evaluated as
+--   serialized_data -> '<property>' @> ANY(<values>)
+-- which these indexes serve for the common filter properties. Other
+-- allowed properties are filtered without an index. The object type is a
+-- column with its own index.
+CREATE INDEX IF NOT EXISTS ix_opentaxii_stixobject_labels
+    ON opentaxii_stixobject USING GIN ((serialized_data -> 'labels') jsonb_path_ops);
+
+CREATE INDEX IF NOT EXISTS ix_opentaxii_stixobject_pattern_type
+    ON opentaxii_stixobject USING GIN ((serialized_data -> 'pattern_type') jsonb_path_ops);
+
+CREATE INDEX IF NOT EXISTS ix_opentaxii_stixobject_created_by_ref
+    ON opentaxii_stixobject USING GIN ((serialized_data -> 'created_by_ref') jsonb_path_ops);
+
+-- match[tlp] filters on marking definition references
+CREATE INDEX IF NOT EXISTS ix_opentaxii_stixobject_object_marking_refs
+    ON opentaxii_stixobject USING GIN ((serialized_data -> 'object_marking_refs') jsonb_path_ops);
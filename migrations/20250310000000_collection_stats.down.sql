@@ -0,0 +1,5 @@
+-- Revert: collection statistics
+-- Compatible with PostgreSQL 9.4+
+
+DROP TABLE IF EXISTS collection_stats;
+DROP INDEX IF EXISTS ix_opentaxii_stixobject_collection_stats;
@@ -0,0 +1,29 @@
+-- Collection statistics
+-- This migration is backward compatible - only adds an index and a new table
+-- Compatible with PostgreSQL 9.5+
+
+-- ============================================
+-- Statistics Index
+-- ============================================
+
+-- Covers the per-type statistics of a collection (counts, distinct IDs and
+-- latest date_added), so they are answered by an index-only scan
+CREATE INDEX IF NOT EXISTS ix_opentaxii_stixobject_collection_stats
+    ON opentaxii_stixobject (collection_id, type, id, date_added)
+    WHERE deleted_at IS NULL;
+
+-- ============================================
+-- Statistics Cache
+-- ============================================
+
+-- Statistics of very large collections, refreshed periodically by the server
+CREATE TABLE IF NOT EXISTS collection_stats (
+    protocol VARCHAR(8) NOT NULL,  -- 'taxii1', 'taxii2'
+    collection_id VARCHAR(150) NOT NULL,
+    object_count BIGINT NOT NULL,
+    distinct_ids BIGINT NOT NULL,
+    latest_added TIMESTAMPTZ,
+    by_type JSONB NOT NULL,
+    computed_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (protocol, collection_id)
+);
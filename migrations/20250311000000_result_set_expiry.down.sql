@@ -0,0 +1,5 @@
+-- Revert: TAXII 1.x result set expiry
+-- Compatible with PostgreSQL 9.4+
+
+DROP INDEX IF EXISTS ix_result_sets_expires_at;
+ALTER TABLE result_sets DROP COLUMN IF EXISTS expires_at;
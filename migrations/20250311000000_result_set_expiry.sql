@@ -0,0 +1,19 @@
+-- TAXII 1.x result set expiry
+-- This migration is backward compatible - only adds a nullable column and an index
+-- Compatible with PostgreSQL 9.5+
+
+-- ============================================
+-- Result Set Expiry
+-- ============================================
+
+-- Result sets are served by Poll Fulfillment until they expire and are then
+-- purged periodically. NULL never expires.
+ALTER TABLE result_sets ADD COLUMN IF NOT EXISTS expires_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS ix_result_sets_expires_at
+    ON result_sets (expires_at)
+    WHERE expires_at IS NOT NULL;
+
+-- Existing result sets get the default TTL of one day
+UPDATE result_sets SET expires_at = date_created + INTERVAL '1 day'
+WHERE expires_at IS NULL;
@@ -0,0 +1,8 @@
+-- Revert: TAXII 1.x content block deduplication
+-- Compatible with PostgreSQL 9.4+
+
+DROP TABLE IF EXISTS content_block_inbox_messages;
+ALTER TABLE data_collections DROP COLUMN IF EXISTS deduplicate_content;
+DROP INDEX IF EXISTS ix_content_blocks_missing_sha256;
+DROP INDEX IF EXISTS ix_content_blocks_content_sha256;
+ALTER TABLE content_blocks DROP COLUMN IF EXISTS content_sha256;
@@ -0,0 +1,44 @@
+-- TAXII 1.x content block deduplication
+-- This migration is backward compatible - only adds nullable/defaulted columns, an index and a new table
+-- Compatible with PostgreSQL 9.5+
+
+-- ============================================
+-- Content Digest
+-- ============================================
+
+-- SHA-256 of the content (hex). Existing blocks are backfilled lazily: only
+-- blocks added within the deduplication window are matched.
+ALTER TABLE content_blocks ADD COLUMN IF NOT EXISTS content_sha256 VARCHAR(64);
+
+CREATE INDEX IF NOT EXISTS ix_content_blocks_content_sha256
+    ON content_blocks (content_sha256)
+    WHERE content_sha256 IS NOT NULL;
+
+-- Blocks added before this migration get their digest when a deduplicating
+-- collection next looks for duplicates; once done this index stays empty
+-- within the window.
+CREATE INDEX IF NOT EXISTS ix_content_blocks_missing_sha256
+    ON content_blocks (date_created)
+    WHERE content_sha256 IS NULL;
+
+-- ============================================
+-- Collection Flag
+-- ============================================
+
+ALTER TABLE data_collections ADD COLUMN IF NOT EXISTS deduplicate_content BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- ============================================
+-- Inbox Message References
+-- ============================================
+
+-- Every inbox message that delivered a content block. A deduplicated block
+-- keeps the inbox_message_id of its first delivery.
+CREATE TABLE IF NOT EXISTS content_block_inbox_messages (
+    content_block_id INTEGER REFERENCES content_blocks(id) ON DELETE CASCADE,
+    inbox_message_id INTEGER REFERENCES inbox_messages(id) ON DELETE CASCADE,
+    PRIMARY KEY (content_block_id, inbox_message_id)
+);
+
+INSERT INTO content_block_inbox_messages (content_block_id, inbox_message_id)
+SELECT id, inbox_message_id FROM content_blocks WHERE inbox_message_id IS NOT NULL
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,5 @@
+-- Revert: Account activity keyset pagination indexes
+-- Compatible with PostgreSQL 9.4+
+
+DROP INDEX IF EXISTS ix_account_activity_event_created;
+DROP INDEX IF EXISTS ix_account_activity_account_created;
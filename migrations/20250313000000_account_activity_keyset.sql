@@ -0,0 +1,19 @@
+-- Account activity keyset pagination indexes
+-- This migration is backward compatible - only adds indexes
+-- Compatible with PostgreSQL 9.4+
+
+-- ============================================
+-- Keyset Pagination
+-- ============================================
+
+-- Activity is paged newest first on (created_at, id); these indexes serve
+-- pages of one account and of one event type without sorting
+DO $$ BEGIN
+    CREATE INDEX ix_account_activity_account_created ON account_activity(account_id, created_at DESC, id DESC);
+EXCEPTION WHEN duplicate_table THEN NULL;
+END $$;
+
+DO $$ BEGIN
+    CREATE INDEX ix_account_activity_event_created ON account_activity(event_type, created_at DESC, id DESC);
+EXCEPTION WHEN duplicate_table THEN NULL;
+END $$;
@@ -0,0 +1,4 @@
+-- Revert: Per-collection write validation rules
+-- Compatible with PostgreSQL 9.4+
+
+ALTER TABLE opentaxii_collection DROP COLUMN IF EXISTS validation_rules;
@@ -0,0 +1,11 @@
+-- Per-collection write validation rules
+-- This migration is backward compatible - only adds a column with a default
+-- Compatible with PostgreSQL 9.4+
+
+-- ============================================
+-- Validation Rules
+-- ============================================
+
+-- Names of built-in rules objects posted to a TAXII 2.x collection must pass
+ALTER TABLE opentaxii_collection
+    ADD COLUMN IF NOT EXISTS validation_rules TEXT[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,7 @@
+-- Revert: Push delivery status of TAXII 1.x subscriptions
+-- Compatible with PostgreSQL 9.4+
+
+ALTER TABLE subscriptions
+    DROP COLUMN IF EXISTS last_delivery_error,
+    DROP COLUMN IF EXISTS delivery_failures,
+    DROP COLUMN IF EXISTS last_delivery_at;
@@ -0,0 +1,14 @@
+-- Push delivery status of TAXII 1.x subscriptions
+-- This migration is backward compatible - only adds nullable columns and a column with a default
+-- Compatible with PostgreSQL 9.4+
+
+-- ============================================
+-- Delivery Status
+-- ============================================
+
+-- Last successful delivery, failed deliveries since then and the last error;
+-- subscriptions are paused after repeated failures
+ALTER TABLE subscriptions
+    ADD COLUMN IF NOT EXISTS last_delivery_at TIMESTAMPTZ,
+    ADD COLUMN IF NOT EXISTS delivery_failures INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS last_delivery_error TEXT;
@@ -0,0 +1,4 @@
+-- Revert: Index for the version order of TAXII 2.x objects
+-- Compatible with PostgreSQL 9.4+
+
+DROP INDEX IF EXISTS ix_opentaxii_stixobject_version_order;
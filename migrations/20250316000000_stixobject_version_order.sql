@@ -0,0 +1,16 @@
+-- Index for the version order of TAXII 2.x objects
+-- This migration is backward compatible - only adds an index
+-- Compatible with PostgreSQL 9.4+
+
+-- ============================================
+-- Version Order
+-- ============================================
+
+-- Versions of an object are totally ordered by (version, date_added, pk), so
+-- rows sharing a version resolve the same way every time. The index serves
+-- first/last version selection and the versions listing.
+DO $$ BEGIN
+    CREATE INDEX ix_opentaxii_stixobject_version_order
+        ON opentaxii_stixobject(collection_id, id, version, date_added, pk);
+EXCEPTION WHEN duplicate_table THEN NULL;
+END $$;
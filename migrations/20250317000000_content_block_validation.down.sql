@@ -0,0 +1,6 @@
+-- Revert: TAXII 1.x inbound content validation
+-- Compatible with PostgreSQL 9.4+
+
+DROP INDEX IF EXISTS ix_content_blocks_validation_error;
+ALTER TABLE content_blocks DROP COLUMN IF EXISTS validation_error;
+ALTER TABLE data_collections DROP COLUMN IF EXISTS validation_mode;
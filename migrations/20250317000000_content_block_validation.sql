@@ -0,0 +1,24 @@
+-- TAXII 1.x inbound content validation
+-- This migration is backward compatible - only adds nullable/defaulted columns
+-- Compatible with PostgreSQL 9.4+
+
+-- ============================================
+-- Collection Validation Mode
+-- ============================================
+
+-- How the Inbox service treats content claiming a STIX XML binding that is
+-- not valid STIX: 'off' (no validation), 'reject' (refuse the inbox
+-- message) or 'flag' (store it with the validation error).
+ALTER TABLE data_collections ADD COLUMN IF NOT EXISTS validation_mode VARCHAR(16) NOT NULL DEFAULT 'off';
+
+-- ============================================
+-- Content Block Validation Result
+-- ============================================
+
+-- Why a block stored in a 'flag' collection failed validation. NULL for
+-- valid blocks and blocks that were not validated.
+ALTER TABLE content_blocks ADD COLUMN IF NOT EXISTS validation_error TEXT;
+
+CREATE INDEX IF NOT EXISTS ix_content_blocks_validation_error
+    ON content_blocks (date_created)
+    WHERE validation_error IS NOT NULL;
@@ -0,0 +1,9 @@
+-- Revert: Push delivery of new content to TAXII 1.x subscribers
+-- Compatible with PostgreSQL 9.4+
+
+DROP TABLE IF EXISTS subscription_deliveries;
+
+ALTER TABLE subscriptions
+    DROP COLUMN IF EXISTS push_message_binding,
+    DROP COLUMN IF EXISTS push_address,
+    DROP COLUMN IF EXISTS push_protocol_binding;
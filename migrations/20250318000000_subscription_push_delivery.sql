@@ -0,0 +1,35 @@
+-- Push delivery of new content to TAXII 1.x subscribers
+-- This migration is backward compatible - only adds nullable columns and a new table
+-- Compatible with PostgreSQL 9.4+
+
+-- ============================================
+-- Push Parameters
+-- ============================================
+
+-- Inbox of the subscriber that new content is pushed to; NULL for
+-- subscriptions that are only polled
+ALTER TABLE subscriptions
+    ADD COLUMN IF NOT EXISTS push_protocol_binding VARCHAR(255),
+    ADD COLUMN IF NOT EXISTS push_address TEXT,
+    ADD COLUMN IF NOT EXISTS push_message_binding VARCHAR(255);
+
+-- ============================================
+-- Delivery Log
+-- ============================================
+
+-- One row per content block and push subscription: queued as PENDING and
+-- recorded as DELIVERED or FAILED once pushed
+CREATE TABLE IF NOT EXISTS subscription_deliveries (
+    id SERIAL PRIMARY KEY,
+    subscription_id VARCHAR(150) NOT NULL REFERENCES subscriptions(id) ON UPDATE CASCADE ON DELETE CASCADE,
+    content_block_id INTEGER NOT NULL REFERENCES content_blocks(id) ON DELETE CASCADE,
+    status VARCHAR(16) NOT NULL DEFAULT 'PENDING',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    date_created TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_attempt_at TIMESTAMPTZ,
+    UNIQUE (subscription_id, content_block_id)
+);
+
+CREATE INDEX IF NOT EXISTS ix_subscription_deliveries_status
+    ON subscription_deliveries (status, id);
@@ -0,0 +1,4 @@
+-- Revert: Per-collection default marking of posted objects
+-- Compatible with PostgreSQL 9.4+
+
+ALTER TABLE opentaxii_collection DROP COLUMN IF EXISTS default_marking;
@@ -0,0 +1,12 @@
+-- Per-collection default marking of posted objects
+-- This migration is backward compatible - only adds a nullable column
+-- Compatible with PostgreSQL 9.4+
+
+-- ============================================
+-- Default Marking
+-- ============================================
+
+-- Marking definition applied to objects posted to a TAXII 2.x collection
+-- without object_marking_refs
+ALTER TABLE opentaxii_collection
+    ADD COLUMN IF NOT EXISTS default_marking TEXT;
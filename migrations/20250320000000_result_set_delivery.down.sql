@@ -0,0 +1,10 @@
+-- Revert: TAXII 1.x asynchronous poll delivery
+-- Compatible with PostgreSQL 9.4+
+
+DROP INDEX IF EXISTS ix_result_sets_delivery_status;
+ALTER TABLE result_sets DROP COLUMN IF EXISTS delivery_error;
+ALTER TABLE result_sets DROP COLUMN IF EXISTS delivery_attempts;
+ALTER TABLE result_sets DROP COLUMN IF EXISTS delivery_status;
+ALTER TABLE result_sets DROP COLUMN IF EXISTS delivery_message_binding;
+ALTER TABLE result_sets DROP COLUMN IF EXISTS delivery_address;
+ALTER TABLE result_sets DROP COLUMN IF EXISTS delivery_protocol_binding;
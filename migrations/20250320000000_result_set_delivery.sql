@@ -0,0 +1,23 @@
+-- TAXII 1.x asynchronous poll delivery
+-- This migration is backward compatible - only adds nullable columns and an index
+-- Compatible with PostgreSQL 9.5+
+
+-- ============================================
+-- Result Set Delivery
+-- ============================================
+
+-- An asynchronous poll with delivery parameters has its results pushed to
+-- the client's inbox once the result set is prepared. NULL delivery columns
+-- leave the results to Poll Fulfillment.
+ALTER TABLE result_sets ADD COLUMN IF NOT EXISTS delivery_protocol_binding TEXT;
+ALTER TABLE result_sets ADD COLUMN IF NOT EXISTS delivery_address TEXT;
+ALTER TABLE result_sets ADD COLUMN IF NOT EXISTS delivery_message_binding TEXT;
+
+-- PENDING until pushed, then DELIVERED or FAILED
+ALTER TABLE result_sets ADD COLUMN IF NOT EXISTS delivery_status VARCHAR(16);
+ALTER TABLE result_sets ADD COLUMN IF NOT EXISTS delivery_attempts INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE result_sets ADD COLUMN IF NOT EXISTS delivery_error TEXT;
+
+CREATE INDEX IF NOT EXISTS ix_result_sets_delivery_status
+    ON result_sets (delivery_status)
+    WHERE delivery_status IS NOT NULL;
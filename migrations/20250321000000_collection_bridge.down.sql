@@ -0,0 +1,4 @@
+-- Revert: Conversion of STIX 1.x content into TAXII 2.x collections
+-- Compatible with PostgreSQL 9.4+
+
+DROP TABLE IF EXISTS collection_bridges;
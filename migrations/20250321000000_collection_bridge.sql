@@ -0,0 +1,17 @@
+-- Conversion of STIX 1.x content received by TAXII 1.x collections into
+-- TAXII 2.x collections
+-- This migration is backward compatible - only adds a new table
+-- Compatible with PostgreSQL 9.4+
+
+-- ============================================
+-- Collection Bridges
+-- ============================================
+
+-- TAXII 2.x collection that STIX packages received into a TAXII 1.x
+-- collection are converted into; at most one per TAXII 1.x collection
+CREATE TABLE IF NOT EXISTS collection_bridges (
+    data_collection_id INTEGER PRIMARY KEY REFERENCES data_collections(id) ON DELETE CASCADE,
+    api_root_id UUID NOT NULL REFERENCES opentaxii_api_root(id) ON DELETE CASCADE,
+    collection_id UUID NOT NULL REFERENCES opentaxii_collection(id) ON DELETE CASCADE,
+    date_created TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
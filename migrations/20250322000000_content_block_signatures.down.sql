@@ -0,0 +1,7 @@
+-- Revert: TAXII 1.x inbound content signature verification
+-- Compatible with PostgreSQL 9.4+
+
+ALTER TABLE content_blocks DROP COLUMN IF EXISTS signer_fingerprint;
+ALTER TABLE content_blocks DROP COLUMN IF EXISTS signature_status;
+ALTER TABLE data_collections DROP COLUMN IF EXISTS trusted_certificates;
+ALTER TABLE data_collections DROP COLUMN IF EXISTS signature_policy;
@@ -0,0 +1,26 @@
+-- TAXII 1.x inbound content signature verification
+-- This migration is backward compatible - only adds nullable/defaulted columns
+-- Compatible with PostgreSQL 9.4+
+
+-- ============================================
+-- Collection Signature Policy
+-- ============================================
+
+-- How the Inbox service treats XML signatures of content: 'off' (not
+-- verified), 'verify' (signed content must verify, unsigned content is
+-- accepted) or 'require' (content must be signed and verify).
+ALTER TABLE data_collections ADD COLUMN IF NOT EXISTS signature_policy VARCHAR(16) NOT NULL DEFAULT 'off';
+
+-- PEM bundle of the certificates trusted to sign content
+ALTER TABLE data_collections ADD COLUMN IF NOT EXISTS trusted_certificates TEXT;
+
+-- ============================================
+-- Content Block Signature Result
+-- ============================================
+
+-- Outcome of signature verification ('valid' or 'unsigned'). NULL for
+-- blocks that were not verified.
+ALTER TABLE content_blocks ADD COLUMN IF NOT EXISTS signature_status VARCHAR(16);
+
+-- SHA-256 fingerprint (hex) of the certificate that signed the content
+ALTER TABLE content_blocks ADD COLUMN IF NOT EXISTS signer_fingerprint VARCHAR(64);
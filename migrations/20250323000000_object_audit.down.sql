@@ -0,0 +1,5 @@
+-- Revert: TAXII 2.x object audit log
+-- Compatible with PostgreSQL 9.4+
+
+DROP TABLE IF EXISTS object_audit;
+DROP FUNCTION IF EXISTS object_audit_immutable();
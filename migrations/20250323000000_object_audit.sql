@@ -0,0 +1,60 @@
+-- TAXII 2.x object audit log
+-- This migration is backward compatible - only adds a table
+-- Compatible with PostgreSQL 9.4+
+
+-- ============================================
+-- Object Audit Table
+-- ============================================
+
+-- One row per object version created, updated (overwritten) or deleted,
+-- written in the transaction of the change. Accounts and collections are
+-- not foreign keys so that entries outlive them.
+CREATE TABLE IF NOT EXISTS object_audit (
+    id BIGSERIAL PRIMARY KEY,
+    account_id INT,
+    operation VARCHAR(16) NOT NULL,  -- 'create', 'update', 'delete'
+    collection_id UUID NOT NULL,
+    object_id VARCHAR(100) NOT NULL,
+    object_version TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Entries are paged newest first on (created_at, id), by collection, by
+-- account or by object
+DO $$ BEGIN
+    CREATE INDEX ix_object_audit_created ON object_audit(created_at DESC, id DESC);
+EXCEPTION WHEN duplicate_table THEN NULL;
+END $$;
+
+DO $$ BEGIN
+    CREATE INDEX ix_object_audit_collection_created ON object_audit(collection_id, created_at DESC, id DESC);
+EXCEPTION WHEN duplicate_table THEN NULL;
+END $$;
+
+DO $$ BEGIN
+    CREATE INDEX ix_object_audit_account_created ON object_audit(account_id, created_at DESC, id DESC);
+EXCEPTION WHEN duplicate_table THEN NULL;
+END $$;
+
+DO $$ BEGIN
+    CREATE INDEX ix_object_audit_object ON object_audit(object_id);
+EXCEPTION WHEN duplicate_table THEN NULL;
+END $$;
+
+-- ============================================
+-- Immutability
+-- ============================================
+
+-- Entries cannot be changed or removed once written
+CREATE OR REPLACE FUNCTION object_audit_immutable() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'object_audit entries are immutable';
+END;
+$$ LANGUAGE plpgsql;
+
+DO $$ BEGIN
+    CREATE TRIGGER object_audit_immutable
+        BEFORE UPDATE OR DELETE ON object_audit
+        FOR EACH ROW EXECUTE PROCEDURE object_audit_immutable();
+EXCEPTION WHEN duplicate_object THEN NULL;
+END $$;
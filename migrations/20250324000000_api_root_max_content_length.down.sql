@@ -0,0 +1,4 @@
+-- Revert: Per-API root limit on the size of TAXII 2.x POST bodies
+-- Compatible with PostgreSQL 9.4+
+
+ALTER TABLE opentaxii_api_root DROP COLUMN IF EXISTS max_content_length;
@@ -0,0 +1,11 @@
+-- Per-API root limit on the size of TAXII 2.x POST bodies
+-- This migration is backward compatible - only adds a nullable column
+-- Compatible with PostgreSQL 9.4+
+
+-- ============================================
+-- Max Content Length
+-- ============================================
+
+-- Largest request body (bytes) the API root accepts; NULL uses the
+-- server's taxii2.max_content_length
+ALTER TABLE opentaxii_api_root ADD COLUMN IF NOT EXISTS max_content_length BIGINT;
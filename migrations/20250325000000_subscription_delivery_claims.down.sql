@@ -0,0 +1,4 @@
+-- Revert: Claims on queued TAXII 1.x push deliveries
+-- Compatible with PostgreSQL 9.4+
+
+ALTER TABLE subscription_deliveries DROP COLUMN IF EXISTS claimed_until;
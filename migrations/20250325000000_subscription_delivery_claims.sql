@@ -0,0 +1,12 @@
+-- Claims on queued TAXII 1.x push deliveries
+-- This migration is backward compatible - only adds a nullable column
+-- Compatible with PostgreSQL 9.5+
+
+-- ============================================
+-- Delivery Claims
+-- ============================================
+
+-- A push delivery worker claims the pending deliveries it pushes until
+-- this time, so that the workers of other server instances skip them;
+-- NULL when unclaimed. Recording the outcome releases the claim.
+ALTER TABLE subscription_deliveries ADD COLUMN IF NOT EXISTS claimed_until TIMESTAMPTZ;
@@ -0,0 +1,4 @@
+-- Revert: Claims on TAXII 1.x result sets whose results are pushed
+-- Compatible with PostgreSQL 9.4+
+
+ALTER TABLE result_sets DROP COLUMN IF EXISTS claimed_until;
@@ -0,0 +1,12 @@
+-- Claims on TAXII 1.x result sets whose results are pushed
+-- This migration is backward compatible - only adds a nullable column
+-- Compatible with PostgreSQL 9.5+
+
+-- ============================================
+-- Result Set Delivery Claims
+-- ============================================
+
+-- A push delivery worker claims the result sets whose results it pushes
+-- until this time, so that the workers of other server instances skip
+-- them; NULL when unclaimed. Recording the outcome releases the claim.
+ALTER TABLE result_sets ADD COLUMN IF NOT EXISTS claimed_until TIMESTAMPTZ;
@@ -0,0 +1,5 @@
+-- Revert: Owners of TAXII 1.x subscriptions
+-- Compatible with PostgreSQL 9.4+
+
+DROP INDEX IF EXISTS ix_subscriptions_account_id;
+ALTER TABLE subscriptions DROP COLUMN IF EXISTS account_id;
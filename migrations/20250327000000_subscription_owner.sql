@@ -0,0 +1,15 @@
+-- Owners of TAXII 1.x subscriptions
+-- This migration is backward compatible - only adds a nullable column
+-- Compatible with PostgreSQL 9.4+
+
+-- ============================================
+-- Subscription Owner
+-- ============================================
+
+-- Account that created the subscription; only it can list and manage the
+-- subscription. NULL for subscriptions created without authentication,
+-- or before owners were recorded.
+ALTER TABLE subscriptions
+    ADD COLUMN IF NOT EXISTS account_id INT REFERENCES accounts(id) ON DELETE CASCADE;
+
+CREATE INDEX IF NOT EXISTS ix_subscriptions_account_id ON subscriptions(account_id);
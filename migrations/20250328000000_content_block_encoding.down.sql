@@ -0,0 +1,4 @@
+-- Revert: TAXII 1.x content block encodings
+-- Compatible with PostgreSQL 9.4+
+
+ALTER TABLE content_blocks DROP COLUMN IF EXISTS content_encoding;
@@ -0,0 +1,13 @@
+-- TAXII 1.x content block encodings
+-- This migration is backward compatible - only adds a defaulted column
+-- Compatible with PostgreSQL 9.4+
+
+-- ============================================
+-- Content Block Encoding
+-- ============================================
+
+-- How the content is carried in messages: 'text' (UTF-8 text, sent as is)
+-- or 'base64' (binary content of a binary binding such as S/MIME, base64
+-- encoded in messages). Blocks stored before this migration hold their
+-- content as it was received, S/MIME content included, so they are text.
+ALTER TABLE content_blocks ADD COLUMN IF NOT EXISTS content_encoding VARCHAR(16) NOT NULL DEFAULT 'text';
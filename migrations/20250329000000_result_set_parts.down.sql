@@ -0,0 +1,4 @@
+-- Revert: TAXII 1.x result set parts
+-- Compatible with PostgreSQL 9.4+
+
+ALTER TABLE result_sets DROP COLUMN IF EXISTS part_sizes;
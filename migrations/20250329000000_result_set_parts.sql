@@ -0,0 +1,12 @@
+-- TAXII 1.x result set parts
+-- This migration is backward compatible - only adds a nullable column
+-- Compatible with PostgreSQL 9.4+
+
+-- ============================================
+-- Result Set Parts
+-- ============================================
+
+-- Number of content blocks in each result part, recorded when the result
+-- set is first fulfilled so that later parts are served without sizing
+-- every block again. NULL until then.
+ALTER TABLE result_sets ADD COLUMN IF NOT EXISTS part_sizes BIGINT[];
@@ -0,0 +1,4 @@
+-- Revert: Account token generation
+-- Compatible with PostgreSQL 9.4+
+
+ALTER TABLE accounts DROP COLUMN IF EXISTS token_generation;
@@ -0,0 +1,11 @@
+-- Account token generation
+-- This migration is backward compatible - only adds a column with a default
+-- Compatible with PostgreSQL 9.4+
+
+-- ============================================
+-- Account Token Generation
+-- ============================================
+
+-- Embedded in every token issued for the account. Redeeming a password
+-- reset increments it, so tokens issued before the reset are refused.
+ALTER TABLE accounts ADD COLUMN IF NOT EXISTS token_generation INTEGER NOT NULL DEFAULT 0;
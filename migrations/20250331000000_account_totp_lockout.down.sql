@@ -0,0 +1,5 @@
+-- Revert: Lockout after failed two-factor attempts
+-- Compatible with PostgreSQL 9.4+
+
+ALTER TABLE account_totp DROP COLUMN IF EXISTS locked_until;
+ALTER TABLE account_totp DROP COLUMN IF EXISTS failed_attempts;
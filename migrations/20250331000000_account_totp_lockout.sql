@@ -0,0 +1,12 @@
+-- Lockout after failed two-factor attempts
+-- This migration is backward compatible - only adds columns
+-- Compatible with PostgreSQL 9.4+
+
+-- ============================================
+-- Account TOTP Lockout
+-- ============================================
+
+-- Failed TOTP and recovery code attempts since the last success or lockout.
+-- Too many lock the second factor until locked_until.
+ALTER TABLE account_totp ADD COLUMN IF NOT EXISTS failed_attempts INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE account_totp ADD COLUMN IF NOT EXISTS locked_until TIMESTAMPTZ;
@@ -27,8 +27,45 @@ use crate::core::error::{Error, Result};
 /// assert_eq!(canonical, r#"{"a":1,"b":2}"#);
 /// ```
 pub fn canonicalize(value: &Value) -> Result<String> {
+    canonicalize_with(value, CanonicalizationOptions::default())
+}
+
+/// How canonicalization writes numbers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NumberFormat {
+    /// Integers are written exactly; other numbers as IEEE-754 doubles in
+    /// their shortest round-trippable form (`1.0` and `1` are both `1`).
+    ///
+    /// This is what python-stix2 does, so deterministic SCO IDs match.
+    #[default]
+    PreserveIntegers,
+    /// Every number is written as an IEEE-754 double, as RFC 8785 requires.
+    /// Integers beyond 2^53 are rounded.
+    Ieee754,
+}
+
+/// Canonicalization options.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CanonicalizationOptions {
+    /// How numbers are written.
+    pub numbers: NumberFormat,
+}
+
+/// Canonicalize a JSON value with the given options.
+///
+/// # Example
+///
+/// ```rust
+/// use stix2::canonicalization::{CanonicalizationOptions, NumberFormat, canonicalize_with};
+/// use serde_json::json;
+///
+/// let options = CanonicalizationOptions { numbers: NumberFormat::Ieee754 };
+/// let canonical = canonicalize_with(&json!({"n": 9007199254740993u64}), options).unwrap();
+/// assert_eq!(canonical, r#"{"n":9007199254740992}"#);
+/// ```
+pub fn canonicalize_with(value: &Value, options: CanonicalizationOptions) -> Result<String> {
     let mut buffer = Vec::new();
-    write_canonical(&mut buffer, value)
+    write_canonical(&mut buffer, value, options)
         .map_err(|e| Error::Custom(format!("Canonicalization error: {e}")))?;
     String::from_utf8(buffer).map_err(|e| Error::Custom(format!("UTF-8 error: {e}")))
 }
@@ -41,7 +78,11 @@ pub fn canonicalize_object<T: serde::Serialize>(obj: &T) -> Result<String> {
 }
 
 /// Write canonical JSON to a writer.
-fn write_canonical<W: Write>(writer: &mut W, value: &Value) -> io::Result<()> {
+fn write_canonical<W: Write>(
+    writer: &mut W,
+    value: &Value,
+    options: CanonicalizationOptions,
+) -> io::Result<()> {
     match value {
         Value::Null => writer.write_all(b"null"),
         Value::Bool(b) => {
@@ -51,7 +92,7 @@ fn write_canonical<W: Write>(writer: &mut W, value: &Value) -> io::Result<()> {
                 writer.write_all(b"false")
             }
         }
-        Value::Number(n) => write_canonical_number(writer, n),
+        Value::Number(n) => write_canonical_number(writer, n, options.numbers),
         Value::String(s) => write_canonical_string(writer, s),
         Value::Array(arr) => {
             writer.write_all(b"[")?;
@@ -61,7 +102,7 @@ fn write_canonical<W: Write>(writer: &mut W, value: &Value) -> io::Result<()> {
                     writer.write_all(b",")?;
                 }
                 first = false;
-                write_canonical(writer, item)?;
+                write_canonical(writer, item, options)?;
             }
             writer.write_all(b"]")
         }
@@ -81,7 +122,7 @@ fn write_canonical<W: Write>(writer: &mut W, value: &Value) -> io::Result<()> {
                 write_canonical_string(writer, key)?;
                 writer.write_all(b":")?;
                 if let Some(val) = obj.get(key) {
-                    write_canonical(writer, val)?;
+                    write_canonical(writer, val, options)?;
                 }
             }
             writer.write_all(b"}")
@@ -99,28 +140,29 @@ fn compare_strings_utf16(a: &str, b: &str) -> std::cmp::Ordering {
 /// Write a canonical number according to RFC 8785.
 ///
 /// Numbers must be serialized according to ECMAScript number-to-string rules.
-fn write_canonical_number<W: Write>(writer: &mut W, n: &Number) -> io::Result<()> {
-    if let Some(i) = n.as_i64() {
-        // Integer - write directly
-        write!(writer, "{i}")
-    } else if let Some(u) = n.as_u64() {
-        // Unsigned integer
-        write!(writer, "{u}")
-    } else if let Some(f) = n.as_f64() {
-        // Floating point - use ECMAScript formatting
-        write_ecmascript_number(writer, f)
-    } else {
-        // Fallback
-        writer.write_all(n.to_string().as_bytes())
+fn write_canonical_number<W: Write>(
+    writer: &mut W,
+    n: &Number,
+    format: NumberFormat,
+) -> io::Result<()> {
+    if format == NumberFormat::PreserveIntegers {
+        if let Some(i) = n.as_i64() {
+            return write!(writer, "{i}");
+        }
+        if let Some(u) = n.as_u64() {
+            return write!(writer, "{u}");
+        }
+    }
+
+    match n.as_f64() {
+        Some(f) => write_ecmascript_number(writer, f),
+        None => writer.write_all(n.to_string().as_bytes()),
     }
 }
 
 /// Write a floating point number according to ECMAScript Number.toString() rules.
 fn write_ecmascript_number<W: Write>(writer: &mut W, f: f64) -> io::Result<()> {
-    if f.is_nan() {
-        return writer.write_all(b"null");
-    }
-    if f.is_infinite() {
+    if !f.is_finite() {
         return writer.write_all(b"null");
     }
     if f == 0.0 {
@@ -128,69 +170,51 @@ fn write_ecmascript_number<W: Write>(writer: &mut W, f: f64) -> io::Result<()> {
         return writer.write_all(b"0");
     }
 
-    // Check if it's an integer
-    if f.trunc() == f && f.abs() < 1e21 {
-        write!(writer, "{}", f as i64)
-    } else {
-        // Use shortest representation
-        let s = format_shortest_float(f);
-        writer.write_all(s.as_bytes())
-    }
+    writer.write_all(format_ecmascript_number(f).as_bytes())
 }
 
-/// Format a float to its shortest decimal representation.
-fn format_shortest_float(f: f64) -> String {
-    // Try different precisions and pick the shortest that round-trips
-    let abs_f = f.abs();
-
-    if !(1e-6..1e21).contains(&abs_f) {
-        // Use exponential notation
-        let s = format!("{f:e}");
-        normalize_exponential(&s)
-    } else {
-        // Use decimal notation
-        for precision in 0..17 {
-            let s = format!("{f:.precision$}");
-            if let Ok(parsed) = s.parse::<f64>()
-                && parsed == f
-            {
-                // Remove trailing zeros after decimal point
-                return normalize_decimal(&s);
-            }
-        }
-        format!("{f}")
-    }
-}
-
-/// Normalize decimal representation (remove trailing zeros).
-fn normalize_decimal(s: &str) -> String {
-    if !s.contains('.') {
-        return s.to_string();
+/// Format a finite, non-zero float like ECMAScript's Number.toString()
+/// (RFC 8785 section 3.2.2.3).
+fn format_ecmascript_number(f: f64) -> String {
+    // `{:e}` gives the shortest digits that round-trip, e.g. "1.2345e-7"
+    let exponential = format!("{:e}", f.abs());
+    let (mantissa, exponent) = exponential
+        .split_once('e')
+        .unwrap_or((exponential.as_str(), "0"));
+    let digits: String = mantissa.chars().filter(|c| *c != '.').collect();
+
+    // The value is 0.<digits> * 10^n
+    let k = digits.len() as i32;
+    let n = exponent.parse::<i32>().unwrap_or(0) + 1;
+
+    let mut out = String::new();
+    if f < 0.0 {
+        out.push('-');
     }
-
-    let s = s.trim_end_matches('0');
-    s.strip_suffix('.').unwrap_or(s).to_string()
-}
-
-/// Normalize exponential notation to ECMAScript format.
-fn normalize_exponential(s: &str) -> String {
-    // Convert "1.5e-7" to "1.5e-7" (already correct)
-    // Convert "1.500000e-7" to "1.5e-7"
-    if let Some(pos) = s.find('e') {
-        let mantissa = normalize_decimal(&s[..pos]);
-        let exponent = &s[pos + 1..];
-
-        // Remove leading + from exponent
-        let exp_num: i32 = exponent.parse().unwrap_or(0);
-        format!(
-            "{}e{}{}",
-            mantissa,
-            if exp_num < 0 { "-" } else { "+" },
-            exp_num.abs()
-        )
+    if k <= n && n <= 21 {
+        out.push_str(&digits);
+        out.push_str(&"0".repeat((n - k) as usize));
+    } else if 0 < n && n <= 21 {
+        let (int, frac) = digits.split_at(n as usize);
+        out.push_str(int);
+        out.push('.');
+        out.push_str(frac);
+    } else if -6 < n && n <= 0 {
+        out.push_str("0.");
+        out.push_str(&"0".repeat(n.unsigned_abs() as usize));
+        out.push_str(&digits);
     } else {
-        s.to_string()
+        let (first, rest) = digits.split_at(1);
+        out.push_str(first);
+        if !rest.is_empty() {
+            out.push('.');
+            out.push_str(rest);
+        }
+        out.push('e');
+        out.push(if n > 0 { '+' } else { '-' });
+        out.push_str(&(n - 1).unsigned_abs().to_string());
     }
+    out
 }
 
 /// Write a canonical string with proper escaping.
@@ -224,9 +248,15 @@ fn write_canonical_string<W: Write>(writer: &mut W, s: &str) -> io::Result<()> {
 
 /// Create a deterministic hash of a canonicalized JSON object.
 pub fn canonical_hash(value: &Value) -> Result<String> {
+    canonical_hash_with(value, CanonicalizationOptions::default())
+}
+
+/// Create a deterministic hash of a JSON object canonicalized with the
+/// given options.
+pub fn canonical_hash_with(value: &Value, options: CanonicalizationOptions) -> Result<String> {
     use sha2::{Digest, Sha256};
 
-    let canonical = canonicalize(value)?;
+    let canonical = canonicalize_with(value, options)?;
     let mut hasher = Sha256::new();
     hasher.update(canonical.as_bytes());
     let result = hasher.finalize();
@@ -305,6 +335,100 @@ mod tests {
         assert_eq!(canonicalize(&json!(1.5)).unwrap(), "1.5");
     }
 
+    #[test]
+    fn test_canonicalize_floats() {
+        // Examples from RFC 8785 appendix B
+        let cases = [
+            (0.0, "0"),
+            (-0.0, "0"),
+            (1.0, "1"),
+            (4.50, "4.5"),
+            (2e-3, "0.002"),
+            (0.000001, "0.000001"),
+            (1e-7, "1e-7"),
+            (-1e-7, "-1e-7"),
+            (1e20, "100000000000000000000"),
+            (1e21, "1e+21"),
+            (1e23, "1e+23"),
+            (9007199254740992.0, "9007199254740992"),
+            (295147905179352830000.0, "295147905179352830000"),
+            (333333333.3333333, "333333333.3333333"),
+            (0.1 + 0.2, "0.30000000000000004"),
+            (5e-324, "5e-324"),
+            (1.7976931348623157e308, "1.7976931348623157e+308"),
+        ];
+        for (f, expected) in cases {
+            assert_eq!(canonicalize(&json!(f)).unwrap(), expected, "{f:e}");
+        }
+        assert_eq!(canonicalize(&json!(f64::NAN)).unwrap(), "null");
+    }
+
+    #[test]
+    fn test_number_format() {
+        let large = json!({"n": 9007199254740993u64, "f": 2.50});
+        assert_eq!(
+            canonicalize(&large).unwrap(),
+            r#"{"f":2.5,"n":9007199254740993}"#
+        );
+
+        let ieee754 = CanonicalizationOptions {
+            numbers: NumberFormat::Ieee754,
+        };
+        assert_eq!(
+            canonicalize_with(&large, ieee754).unwrap(),
+            r#"{"f":2.5,"n":9007199254740992}"#
+        );
+        assert_eq!(
+            canonical_hash_with(&json!({"n": 1}), ieee754).unwrap(),
+            canonical_hash(&json!({"n": 1.0})).unwrap()
+        );
+    }
+
+    /// Objects with float properties hash the same whether floats were
+    /// written with or without fractional digits.
+    #[test]
+    fn test_float_properties_hash_identically() {
+        use crate::extensions::WindowsPeSection;
+        use crate::observables::generate_sco_id;
+
+        let section = WindowsPeSection {
+            name: ".text".to_string(),
+            size: Some(4096),
+            entropy: Some(7.0),
+            hashes: IndexMap::new(),
+        };
+        let typed = json!({
+            "type": "file",
+            "name": "sample.exe",
+            "extensions": {"windows-pebinary-ext": {"pe_type": "exe", "sections": [section]}}
+        });
+        for text in ["7", "7.0", "7.000", "0.7e1", "70e-1"] {
+            let parsed: Value = serde_json::from_str(&format!(
+                r#"{{"type": "file", "name": "sample.exe", "extensions": {{"windows-pebinary-ext":
+                    {{"pe_type": "exe", "sections": [{{"name": ".text", "size": 4096, "entropy": {text}}}]}}}}}}"#
+            ))
+            .unwrap();
+            assert_eq!(
+                canonical_hash(&parsed).unwrap(),
+                canonical_hash(&typed).unwrap(),
+                "{text}"
+            );
+        }
+
+        let location = json!({"latitude": 48.8566, "longitude": 2.0});
+        let parsed: Value =
+            serde_json::from_str(r#"{"longitude": 2, "latitude": 48.85660}"#).unwrap();
+        assert_eq!(
+            canonical_hash(&location).unwrap(),
+            canonical_hash(&parsed).unwrap()
+        );
+
+        // Deterministic SCO IDs do not depend on how a float was written
+        let id = generate_sco_id("x-sensor", &json!({"reading": 1.0})).unwrap();
+        let parsed: Value = serde_json::from_str(r#"{"reading": 1.00}"#).unwrap();
+        assert_eq!(generate_sco_id("x-sensor", &parsed).unwrap(), id);
+    }
+
     #[test]
     fn test_canonicalize_array() {
         let value = json!([3, 1, 2]);
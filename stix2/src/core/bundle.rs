@@ -6,7 +6,13 @@
 use crate::core::error::{Error, Result};
 use crate::core::id::Identifier;
 use crate::core::stix_object::StixObject;
+use crate::core::timestamp::{Precision, Timestamp, format_datetime};
+use crate::validation::{ValidationProfile, validate_object};
+use serde::de::{self, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cell::Cell;
+use std::io::Write;
 
 /// A STIX Bundle containing multiple STIX objects.
 ///
@@ -154,6 +160,12 @@ impl Bundle {
     /// When multiple versions of the same object exist, only the
     /// most recently modified version is kept.
     pub fn deduplicate(&mut self) {
+        self.remove_duplicates();
+    }
+
+    /// Deduplicate objects like [`Self::deduplicate`], returning the
+    /// removed objects.
+    fn remove_duplicates(&mut self) -> Vec<StixObject> {
         use std::collections::HashMap;
 
         let mut seen: HashMap<String, (usize, Option<chrono::DateTime<chrono::Utc>>)> =
@@ -188,9 +200,86 @@ impl Bundle {
         // Remove duplicates in reverse order to preserve indices
         to_remove.sort_unstable();
         to_remove.reverse();
-        for idx in to_remove {
-            self.objects.remove(idx);
+        let mut removed: Vec<StixObject> = to_remove
+            .into_iter()
+            .map(|idx| self.objects.remove(idx))
+            .collect();
+        removed.reverse();
+        removed
+    }
+
+    /// Validate and normalize the bundle in one pass.
+    ///
+    /// Every object goes through the same pipeline:
+    ///
+    /// 1. With [`NormalizeOptions::repair_ids`], malformed identifiers in
+    ///    `id`, `*_ref` and `*_refs` properties are repaired (see
+    ///    [`Identifier::repair`]). Typed objects already have well-formed
+    ///    identifiers, so this mostly fixes references in custom objects
+    ///    and custom properties.
+    /// 2. `created` and `modified` timestamps with second precision are
+    ///    normalized to the millisecond precision STIX 2.1 requires.
+    /// 3. The object is validated with [`validate_object`], enforcing
+    ///    [`NormalizeOptions::profile`] if set. Invalid objects are removed.
+    ///
+    /// Finally, duplicate versions are removed as by [`Self::deduplicate`].
+    ///
+    /// Returns the clean bundle, with the same ID, and a finding for every
+    /// change: [`FindingSeverity::Warning`] for corrected objects and
+    /// removed duplicates, [`FindingSeverity::Error`] for removed invalid
+    /// objects.
+    pub fn normalize(self, options: &NormalizeOptions) -> (Bundle, Vec<ValidationFinding>) {
+        let mut findings = Vec::new();
+        let mut bundle = Bundle {
+            type_: self.type_,
+            id: self.id,
+            objects: Vec::with_capacity(self.objects.len()),
+        };
+
+        for object in self.objects {
+            let object_id = object.id().clone();
+            let mut warnings = Vec::new();
+            let normalized =
+                serde_json::to_value(&object)
+                    .map_err(Error::from)
+                    .and_then(|mut value| {
+                        if options.repair_ids {
+                            crate::environment::repair_ids(&mut value, false, &mut warnings);
+                        }
+                        normalize_timestamps(&mut value, &mut warnings);
+                        validate_object(&value, options.profile.as_ref())
+                    });
+
+            findings.extend(warnings.into_iter().map(|message| ValidationFinding {
+                object_id: object_id.clone(),
+                severity: FindingSeverity::Warning,
+                message,
+            }));
+            match normalized {
+                Ok(object) => bundle.objects.push(object),
+                Err(e) => findings.push(ValidationFinding {
+                    object_id,
+                    severity: FindingSeverity::Error,
+                    message: format!("Removed invalid object: {e}"),
+                }),
+            }
         }
+
+        for duplicate in bundle.remove_duplicates() {
+            findings.push(ValidationFinding {
+                object_id: duplicate.id().clone(),
+                severity: FindingSeverity::Warning,
+                message: match duplicate.modified() {
+                    Some(modified) => format!(
+                        "Removed duplicate version modified {}",
+                        format_datetime(&modified, Precision::Millisecond)
+                    ),
+                    None => "Removed duplicate object".to_string(),
+                },
+            });
+        }
+
+        (bundle, findings)
     }
 
     /// Serialize the bundle to JSON.
@@ -209,6 +298,57 @@ impl Bundle {
     }
 }
 
+/// Normalize `created` and `modified` timestamps of an object to
+/// millisecond precision.
+fn normalize_timestamps(value: &mut Value, warnings: &mut Vec<String>) {
+    for property in ["created", "modified"] {
+        let Some(Value::String(s)) = value.get_mut(property) else {
+            continue;
+        };
+        let Ok(timestamp) = s.parse::<Timestamp>() else {
+            continue;
+        };
+        if timestamp.precision() == Precision::Second {
+            let normalized = format_datetime(&timestamp.datetime(), Precision::Millisecond);
+            warnings.push(format!(
+                "Normalized {property} '{s}' to millisecond precision"
+            ));
+            *s = normalized;
+        }
+    }
+}
+
+/// Options of [`Bundle::normalize`].
+#[derive(Debug, Clone, Default)]
+pub struct NormalizeOptions {
+    /// Repair malformed identifiers in `id`, `*_ref` and `*_refs`
+    /// properties.
+    pub repair_ids: bool,
+
+    /// Profile every object must satisfy.
+    pub profile: Option<ValidationProfile>,
+}
+
+/// How serious a [`ValidationFinding`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FindingSeverity {
+    /// The object was corrected, or removed as a duplicate.
+    Warning,
+    /// The object was invalid and removed.
+    Error,
+}
+
+/// A change made to a bundle by [`Bundle::normalize`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationFinding {
+    /// ID of the object concerned.
+    pub object_id: Identifier,
+    /// How serious the finding is.
+    pub severity: FindingSeverity,
+    /// What was found and done about it.
+    pub message: String,
+}
+
 impl Default for Bundle {
     fn default() -> Self {
         Self::new()
@@ -239,9 +379,215 @@ impl Extend<StixObject> for Bundle {
     }
 }
 
+/// Deserialize a bundle holding at most `max_objects` objects.
+///
+/// Objects are counted as they are read: the bundle is refused with
+/// [`Error::TooManyObjects`] at the first object past the limit, which is
+/// skipped rather than built, and nothing after it is read.
+pub(crate) fn from_json_bounded<'de, R: serde_json::de::Read<'de>>(
+    read: R,
+    max_objects: usize,
+) -> Result<Bundle> {
+    let exceeded = Cell::new(false);
+    let mut deserializer = serde_json::Deserializer::new(read);
+    let seed = BoundedBundle {
+        max_objects,
+        exceeded: &exceeded,
+    };
+    seed.deserialize(&mut deserializer)
+        .and_then(|bundle| deserializer.end().map(|()| bundle))
+        .map_err(|e| {
+            if exceeded.get() {
+                Error::TooManyObjects(max_objects)
+            } else {
+                Error::Json(e)
+            }
+        })
+}
+
+/// Seed deserializing a [`Bundle`] whose objects are bounded.
+struct BoundedBundle<'a> {
+    max_objects: usize,
+    exceeded: &'a Cell<bool>,
+}
+
+impl<'de> DeserializeSeed<'de> for BoundedBundle<'_> {
+    type Value = Bundle;
+
+    fn deserialize<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> std::result::Result<Bundle, D::Error> {
+        deserializer.deserialize_struct("Bundle", &["type", "id", "objects"], self)
+    }
+}
+
+impl<'de> Visitor<'de> for BoundedBundle<'_> {
+    type Value = Bundle;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("a STIX bundle")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> std::result::Result<Bundle, A::Error> {
+        let mut type_ = None;
+        let mut id = None;
+        let mut objects = None;
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "type" if type_.is_none() => type_ = Some(map.next_value()?),
+                "id" if id.is_none() => id = Some(map.next_value()?),
+                "objects" if objects.is_none() => {
+                    objects = Some(map.next_value_seed(BoundedObjects {
+                        max_objects: self.max_objects,
+                        exceeded: self.exceeded,
+                    })?);
+                }
+                "type" | "id" | "objects" => {
+                    return Err(de::Error::custom(format!("duplicate field `{key}`")));
+                }
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        Ok(Bundle {
+            type_: type_.ok_or_else(|| de::Error::missing_field("type"))?,
+            id: id.ok_or_else(|| de::Error::missing_field("id"))?,
+            objects: objects.unwrap_or_default(),
+        })
+    }
+}
+
+/// Seed deserializing the `objects` of a bundle up to a limit.
+struct BoundedObjects<'a> {
+    max_objects: usize,
+    exceeded: &'a Cell<bool>,
+}
+
+impl<'de> DeserializeSeed<'de> for BoundedObjects<'_> {
+    type Value = Vec<StixObject>;
+
+    fn deserialize<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> std::result::Result<Vec<StixObject>, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for BoundedObjects<'_> {
+    type Value = Vec<StixObject>;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("an array of STIX objects")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(
+        self,
+        mut seq: A,
+    ) -> std::result::Result<Vec<StixObject>, A::Error> {
+        // The length the input claims is not trusted for preallocation
+        let mut objects = Vec::new();
+        loop {
+            if objects.len() == self.max_objects {
+                if seq.next_element::<IgnoredAny>()?.is_some() {
+                    self.exceeded.set(true);
+                    return Err(de::Error::custom(format!(
+                        "bundle contains more than {} objects",
+                        self.max_objects
+                    )));
+                }
+                return Ok(objects);
+            }
+            match seq.next_element()? {
+                Some(object) => objects.push(object),
+                None => return Ok(objects),
+            }
+        }
+    }
+}
+
+/// Writes a bundle object by object, without holding the objects in memory.
+///
+/// The bundle header is written on creation, each object as it is added,
+/// and the closing brackets by [`BundleWriter::finish`]. The output parses
+/// as a [`Bundle`].
+///
+/// # Example
+///
+/// ```rust
+/// use stix2::BundleWriter;
+/// use serde_json::json;
+///
+/// fn main() -> stix2::Result<()> {
+///     let mut writer = BundleWriter::new(Vec::new())?;
+///     writer.write_object(&json!({"type": "identity", "id": "identity--0b5f2a5e-5d4e-4e1a-8b5c-1c2d3e4f5a6b"}))?;
+///     let output = writer.finish()?;
+///     assert!(String::from_utf8_lossy(&output).starts_with("{\"type\":\"bundle\""));
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug)]
+pub struct BundleWriter<W: Write> {
+    writer: W,
+    count: usize,
+}
+
+impl<W: Write> BundleWriter<W> {
+    /// Start a bundle with a random ID.
+    pub fn new(writer: W) -> Result<Self> {
+        Self::with_id(writer, &Identifier::new_bundle())
+    }
+
+    /// Start a bundle with a specific ID.
+    pub fn with_id(mut writer: W, id: &Identifier) -> Result<Self> {
+        if id.object_type() != "bundle" {
+            return Err(Error::InvalidType(format!(
+                "Expected bundle identifier, got: {}",
+                id.object_type()
+            )));
+        }
+        write!(
+            writer,
+            "{{\"type\":\"bundle\",\"id\":{},\"objects\":[",
+            serde_json::to_string(id)?
+        )?;
+        Ok(Self { writer, count: 0 })
+    }
+
+    /// Append an object to the bundle.
+    pub fn write_object<T: Serialize + ?Sized>(&mut self, object: &T) -> Result<()> {
+        if self.count > 0 {
+            self.writer.write_all(b",")?;
+        }
+        serde_json::to_writer(&mut self.writer, object)?;
+        self.count += 1;
+        Ok(())
+    }
+
+    /// Number of objects written so far.
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    /// Whether no object was written yet.
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Close the bundle and return the underlying writer.
+    pub fn finish(mut self) -> Result<W> {
+        self.writer.write_all(b"]}")?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use serde_json::json;
 
     #[test]
     fn test_new_bundle() {
@@ -261,6 +607,102 @@ mod tests {
         assert_eq!(bundle.id, parsed.id);
     }
 
+    #[test]
+    fn test_normalize_messy_bundle() {
+        let json = r#"{
+            "type": "bundle",
+            "id": "bundle--44af6c39-c09b-49c5-9de2-394224b04982",
+            "objects": [
+                {
+                    "type": "indicator",
+                    "spec_version": "2.1",
+                    "id": "indicator--12345678-1234-4234-8234-123456789012",
+                    "created": "2023-01-01T00:00:00Z",
+                    "modified": "2023-01-01T00:00:00Z",
+                    "pattern": "[file:name = 'test.exe']",
+                    "pattern_type": "stix",
+                    "valid_from": "2023-01-01T00:00:00Z",
+                    "indicator_types": ["malicious-activity"],
+                    "confidence": 80
+                },
+                {
+                    "type": "indicator",
+                    "spec_version": "2.1",
+                    "id": "indicator--12345678-1234-4234-8234-123456789012",
+                    "created": "2023-01-01T00:00:00.000Z",
+                    "modified": "2023-02-01T00:00:00.000Z",
+                    "pattern": "[file:name = 'test.exe']",
+                    "pattern_type": "stix",
+                    "valid_from": "2023-01-01T00:00:00Z",
+                    "indicator_types": ["malicious-activity"],
+                    "confidence": 90
+                },
+                {
+                    "type": "indicator",
+                    "spec_version": "2.1",
+                    "id": "indicator--22345678-1234-4234-8234-123456789012",
+                    "created": "2023-01-01T00:00:00.000Z",
+                    "modified": "2023-01-01T00:00:00.000Z",
+                    "pattern": "[file:name = 'other.exe']",
+                    "pattern_type": "stix",
+                    "valid_from": "2023-01-01T00:00:00Z",
+                    "indicator_types": ["malicious-activity"]
+                },
+                {
+                    "type": "x-acme-widget",
+                    "spec_version": "2.1",
+                    "id": "x-acme-widget--32345678-1234-4234-8234-123456789012",
+                    "created": "2023-01-01T00:00:00.000Z",
+                    "modified": "2023-01-01T00:00:00.000Z",
+                    "target_ref": "Indicator-12345678-1234-4234-8234-123456789012"
+                }
+            ]
+        }"#;
+        let bundle = Bundle::from_json(json).unwrap();
+        let options = NormalizeOptions {
+            repair_ids: true,
+            profile: Some(ValidationProfile::indicator_sharing()),
+        };
+
+        let (clean, findings) = bundle.normalize(&options);
+        assert_eq!(clean.len(), 2, "{findings:?}");
+        assert_eq!(
+            clean.id.to_string(),
+            "bundle--44af6c39-c09b-49c5-9de2-394224b04982"
+        );
+        assert_eq!(clean.objects[0].confidence(), Some(90));
+        let widget = serde_json::to_value(&clean.objects[1]).unwrap();
+        assert_eq!(
+            widget["target_ref"],
+            "indicator--12345678-1234-4234-8234-123456789012"
+        );
+
+        assert_eq!(findings.len(), 5, "{findings:?}");
+        let count = |severity: FindingSeverity, prefix: &str| {
+            findings
+                .iter()
+                .filter(|f| f.severity == severity && f.message.starts_with(prefix))
+                .count()
+        };
+        assert_eq!(count(FindingSeverity::Warning, "Normalized "), 2);
+        assert_eq!(
+            count(FindingSeverity::Warning, "Repaired malformed identifier"),
+            1
+        );
+        assert_eq!(
+            count(FindingSeverity::Warning, "Removed duplicate version"),
+            1
+        );
+        assert_eq!(count(FindingSeverity::Error, "Removed invalid object"), 1);
+        assert!(findings.iter().any(|f| f.severity == FindingSeverity::Error
+            && f.object_id.to_string() == "indicator--22345678-1234-4234-8234-123456789012"));
+
+        // A clean bundle comes through unchanged
+        let (again, findings) = clean.clone().normalize(&options);
+        assert_eq!(again, clean);
+        assert!(findings.is_empty());
+    }
+
     #[test]
     fn test_find_by_type() {
         let bundle = Bundle::new();
@@ -268,4 +710,36 @@ mod tests {
         let results = bundle.find_by_type("indicator");
         assert!(results.is_empty());
     }
+
+    #[test]
+    fn test_bundle_writer() {
+        let identity = json!({
+            "type": "identity",
+            "id": "identity--0b5f2a5e-5d4e-4e1a-8b5c-1c2d3e4f5a6b",
+            "spec_version": "2.1",
+            "created": "2024-01-01T00:00:00.000Z",
+            "modified": "2024-01-01T00:00:00.000Z",
+            "name": "ACME"
+        });
+
+        let writer = BundleWriter::new(Vec::new()).unwrap();
+        assert!(writer.is_empty());
+        let empty = Bundle::from_json(&String::from_utf8(writer.finish().unwrap()).unwrap());
+        assert!(empty.unwrap().is_empty());
+
+        let id = Identifier::new_bundle();
+        let mut writer = BundleWriter::with_id(Vec::new(), &id).unwrap();
+        writer.write_object(&identity).unwrap();
+        writer.write_object(&identity).unwrap();
+        assert_eq!(writer.len(), 2);
+        let output = String::from_utf8(writer.finish().unwrap()).unwrap();
+        let bundle = Bundle::from_json(&output).unwrap();
+        assert_eq!(bundle.id, id);
+        assert_eq!(bundle.len(), 2);
+
+        let not_bundle: Identifier = "identity--0b5f2a5e-5d4e-4e1a-8b5c-1c2d3e4f5a6b"
+            .parse()
+            .unwrap();
+        assert!(BundleWriter::with_id(Vec::new(), &not_bundle).is_err());
+    }
 }
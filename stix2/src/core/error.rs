@@ -70,6 +70,10 @@ pub enum Error {
     #[error("Validation error: {0}")]
     Validation(String),
 
+    /// A bundle holds more objects than allowed.
+    #[error("Bundle contains more than {0} objects")]
+    TooManyObjects(usize),
+
     /// Pattern parsing error.
     #[error("Pattern parsing error: {0}")]
     PatternParse(String),
@@ -78,6 +82,10 @@ pub enum Error {
     #[error("Pattern validation error: {0}")]
     PatternValidation(String),
 
+    /// Pattern construct the matcher cannot evaluate.
+    #[error("Pattern construct not supported by the matcher: {0}")]
+    PatternUnsupported(String),
+
     /// Invalid hash format.
     #[error("Invalid hash format for algorithm '{algorithm}': {message}")]
     InvalidHash {
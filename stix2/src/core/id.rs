@@ -158,6 +158,26 @@ impl Identifier {
         self.uuid
     }
 
+    /// Repair common malformations of an identifier string.
+    ///
+    /// Uppercase characters are lowercased and a single dash between type
+    /// and UUID (`indicator-<uuid>`) is doubled. Returns the repaired
+    /// identifier, or `None` if the string is already well-formed or cannot
+    /// be repaired.
+    #[must_use]
+    pub fn repair(s: &str) -> Option<String> {
+        let lower = s.to_ascii_lowercase();
+        let candidate = if lower.contains("--") {
+            lower
+        } else {
+            let split = lower.len().checked_sub(36)?;
+            let object_type = lower.get(..split)?.strip_suffix('-')?;
+            format!("{object_type}--{}", lower.get(split..)?)
+        };
+
+        (candidate != s && candidate.parse::<Self>().is_ok()).then_some(candidate)
+    }
+
     /// Validate the object type format.
     fn validate_type(object_type: &str) -> Result<()> {
         if !TYPE_REGEX.is_match(object_type) {
@@ -288,6 +308,22 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_repair_identifier() {
+        let uuid = "8e2e2d2b-17d4-4cbf-938f-98ee46b3cd3f";
+        assert_eq!(
+            Identifier::repair(&format!("indicator-{uuid}")),
+            Some(format!("indicator--{uuid}"))
+        );
+        assert_eq!(
+            Identifier::repair(&format!("Indicator--{}", uuid.to_uppercase())),
+            Some(format!("indicator--{uuid}"))
+        );
+        assert_eq!(Identifier::repair(&format!("indicator--{uuid}")), None);
+        assert_eq!(Identifier::repair("indicator-not-a-uuid"), None);
+        assert_eq!(Identifier::repair(uuid), None);
+    }
+
     #[test]
     fn test_invalid_type() {
         let result = Identifier::new("INVALID_TYPE");
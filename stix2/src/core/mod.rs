@@ -20,7 +20,7 @@ pub mod stix_object;
 pub mod timestamp;
 pub mod traits;
 
-pub use bundle::Bundle;
+pub use bundle::{Bundle, BundleWriter, FindingSeverity, NormalizeOptions, ValidationFinding};
 pub use common::*;
 pub use error::{Error, Result};
 pub use external_reference::ExternalReference;
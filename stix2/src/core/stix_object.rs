@@ -3,7 +3,10 @@
 //! This module provides a comprehensive enum that can represent any STIX object,
 //! enabling heterogeneous collections and dynamic dispatch.
 
+use crate::core::common::CommonProperties;
+use crate::core::error::{Error, Result};
 use crate::core::id::Identifier;
+use crate::validation::Constrained;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde_json::Value;
@@ -223,37 +226,220 @@ impl StixObject {
         }
     }
 
+    /// Get the common properties of SDOs, SROs and Language Content.
+    ///
+    /// Returns `None` for SCOs, marking definitions and custom objects,
+    /// which do not share [`CommonProperties`].
+    pub fn common(&self) -> Option<&CommonProperties> {
+        match self {
+            StixObject::AttackPattern(o) => Some(&o.common),
+            StixObject::Campaign(o) => Some(&o.common),
+            StixObject::CourseOfAction(o) => Some(&o.common),
+            StixObject::Grouping(o) => Some(&o.common),
+            StixObject::Identity(o) => Some(&o.common),
+            StixObject::Incident(o) => Some(&o.common),
+            StixObject::Indicator(o) => Some(&o.common),
+            StixObject::Infrastructure(o) => Some(&o.common),
+            StixObject::IntrusionSet(o) => Some(&o.common),
+            StixObject::Location(o) => Some(&o.common),
+            StixObject::Malware(o) => Some(&o.common),
+            StixObject::MalwareAnalysis(o) => Some(&o.common),
+            StixObject::Note(o) => Some(&o.common),
+            StixObject::ObservedData(o) => Some(&o.common),
+            StixObject::Opinion(o) => Some(&o.common),
+            StixObject::Report(o) => Some(&o.common),
+            StixObject::ThreatActor(o) => Some(&o.common),
+            StixObject::Tool(o) => Some(&o.common),
+            StixObject::Vulnerability(o) => Some(&o.common),
+            StixObject::Relationship(o) => Some(&o.common),
+            StixObject::Sighting(o) => Some(&o.common),
+            StixObject::LanguageContent(o) => Some(&o.common),
+            _ => None,
+        }
+    }
+
     /// Get the modified timestamp if available.
     pub fn modified(&self) -> Option<DateTime<Utc>> {
         match self {
-            StixObject::AttackPattern(o) => Some(o.common.modified.datetime()),
-            StixObject::Campaign(o) => Some(o.common.modified.datetime()),
-            StixObject::CourseOfAction(o) => Some(o.common.modified.datetime()),
-            StixObject::Grouping(o) => Some(o.common.modified.datetime()),
-            StixObject::Identity(o) => Some(o.common.modified.datetime()),
-            StixObject::Incident(o) => Some(o.common.modified.datetime()),
-            StixObject::Indicator(o) => Some(o.common.modified.datetime()),
-            StixObject::Infrastructure(o) => Some(o.common.modified.datetime()),
-            StixObject::IntrusionSet(o) => Some(o.common.modified.datetime()),
-            StixObject::Location(o) => Some(o.common.modified.datetime()),
-            StixObject::Malware(o) => Some(o.common.modified.datetime()),
-            StixObject::MalwareAnalysis(o) => Some(o.common.modified.datetime()),
-            StixObject::Note(o) => Some(o.common.modified.datetime()),
-            StixObject::ObservedData(o) => Some(o.common.modified.datetime()),
-            StixObject::Opinion(o) => Some(o.common.modified.datetime()),
-            StixObject::Report(o) => Some(o.common.modified.datetime()),
-            StixObject::ThreatActor(o) => Some(o.common.modified.datetime()),
-            StixObject::Tool(o) => Some(o.common.modified.datetime()),
-            StixObject::Vulnerability(o) => Some(o.common.modified.datetime()),
-            StixObject::Relationship(o) => Some(o.common.modified.datetime()),
-            StixObject::Sighting(o) => Some(o.common.modified.datetime()),
             StixObject::MarkingDefinition(o) => Some(o.created.datetime()),
-            StixObject::LanguageContent(o) => Some(o.common.modified.datetime()),
             // SCOs don't have modified timestamps
+            _ => self.common().map(|c| c.modified.datetime()),
+        }
+    }
+
+    /// Get the created timestamp if available.
+    ///
+    /// Returns `None` for SCOs and custom objects.
+    pub fn created(&self) -> Option<DateTime<Utc>> {
+        match self {
+            StixObject::MarkingDefinition(o) => Some(o.created.datetime()),
+            _ => self.common().map(|c| c.created.datetime()),
+        }
+    }
+
+    /// Get the identity that created this object, if any.
+    pub fn created_by_ref(&self) -> Option<&Identifier> {
+        match self {
+            StixObject::MarkingDefinition(o) => o.created_by_ref.as_ref(),
+            _ => self.common().and_then(|c| c.created_by_ref.as_ref()),
+        }
+    }
+
+    /// Get the labels of this object.
+    ///
+    /// Empty for objects without labels.
+    pub fn labels(&self) -> &[String] {
+        self.common().map(|c| c.labels.as_slice()).unwrap_or(&[])
+    }
+
+    /// Get the confidence of this object, if any.
+    pub fn confidence(&self) -> Option<u8> {
+        self.common().and_then(|c| c.confidence)
+    }
+
+    /// Get the object marking references of this object.
+    ///
+    /// Empty for custom objects.
+    pub fn object_marking_refs(&self) -> &[Identifier] {
+        if let Some(common) = self.common() {
+            return &common.object_marking_refs;
+        }
+        match self {
+            StixObject::Artifact(o) => &o.object_marking_refs,
+            StixObject::AutonomousSystem(o) => &o.object_marking_refs,
+            StixObject::Directory(o) => &o.object_marking_refs,
+            StixObject::DomainName(o) => &o.object_marking_refs,
+            StixObject::EmailAddress(o) => &o.object_marking_refs,
+            StixObject::EmailMessage(o) => &o.object_marking_refs,
+            StixObject::File(o) => &o.object_marking_refs,
+            StixObject::IPv4Address(o) => &o.object_marking_refs,
+            StixObject::IPv6Address(o) => &o.object_marking_refs,
+            StixObject::MacAddress(o) => &o.object_marking_refs,
+            StixObject::Mutex(o) => &o.object_marking_refs,
+            StixObject::NetworkTraffic(o) => &o.object_marking_refs,
+            StixObject::Process(o) => &o.object_marking_refs,
+            StixObject::Software(o) => &o.object_marking_refs,
+            StixObject::Url(o) => &o.object_marking_refs,
+            StixObject::UserAccount(o) => &o.object_marking_refs,
+            StixObject::WindowsRegistryKey(o) => &o.object_marking_refs,
+            StixObject::X509Certificate(o) => &o.object_marking_refs,
+            StixObject::MarkingDefinition(o) => &o.object_marking_refs,
+            _ => &[],
+        }
+    }
+
+    /// Get mutable access to the common properties of SDOs, SROs and
+    /// Language Content.
+    pub fn common_mut(&mut self) -> Option<&mut CommonProperties> {
+        match self {
+            StixObject::AttackPattern(o) => Some(&mut o.common),
+            StixObject::Campaign(o) => Some(&mut o.common),
+            StixObject::CourseOfAction(o) => Some(&mut o.common),
+            StixObject::Grouping(o) => Some(&mut o.common),
+            StixObject::Identity(o) => Some(&mut o.common),
+            StixObject::Incident(o) => Some(&mut o.common),
+            StixObject::Indicator(o) => Some(&mut o.common),
+            StixObject::Infrastructure(o) => Some(&mut o.common),
+            StixObject::IntrusionSet(o) => Some(&mut o.common),
+            StixObject::Location(o) => Some(&mut o.common),
+            StixObject::Malware(o) => Some(&mut o.common),
+            StixObject::MalwareAnalysis(o) => Some(&mut o.common),
+            StixObject::Note(o) => Some(&mut o.common),
+            StixObject::ObservedData(o) => Some(&mut o.common),
+            StixObject::Opinion(o) => Some(&mut o.common),
+            StixObject::Report(o) => Some(&mut o.common),
+            StixObject::ThreatActor(o) => Some(&mut o.common),
+            StixObject::Tool(o) => Some(&mut o.common),
+            StixObject::Vulnerability(o) => Some(&mut o.common),
+            StixObject::Relationship(o) => Some(&mut o.common),
+            StixObject::Sighting(o) => Some(&mut o.common),
+            StixObject::LanguageContent(o) => Some(&mut o.common),
+            _ => None,
+        }
+    }
+
+    /// Get mutable access to the typed object marking references.
+    ///
+    /// Returns `None` for custom objects, whose properties are raw JSON.
+    fn object_marking_refs_mut(&mut self) -> Option<&mut Vec<Identifier>> {
+        if self.common().is_some() {
+            return self.common_mut().map(|c| &mut c.object_marking_refs);
+        }
+        match self {
+            StixObject::Artifact(o) => Some(&mut o.object_marking_refs),
+            StixObject::AutonomousSystem(o) => Some(&mut o.object_marking_refs),
+            StixObject::Directory(o) => Some(&mut o.object_marking_refs),
+            StixObject::DomainName(o) => Some(&mut o.object_marking_refs),
+            StixObject::EmailAddress(o) => Some(&mut o.object_marking_refs),
+            StixObject::EmailMessage(o) => Some(&mut o.object_marking_refs),
+            StixObject::File(o) => Some(&mut o.object_marking_refs),
+            StixObject::IPv4Address(o) => Some(&mut o.object_marking_refs),
+            StixObject::IPv6Address(o) => Some(&mut o.object_marking_refs),
+            StixObject::MacAddress(o) => Some(&mut o.object_marking_refs),
+            StixObject::Mutex(o) => Some(&mut o.object_marking_refs),
+            StixObject::NetworkTraffic(o) => Some(&mut o.object_marking_refs),
+            StixObject::Process(o) => Some(&mut o.object_marking_refs),
+            StixObject::Software(o) => Some(&mut o.object_marking_refs),
+            StixObject::Url(o) => Some(&mut o.object_marking_refs),
+            StixObject::UserAccount(o) => Some(&mut o.object_marking_refs),
+            StixObject::WindowsRegistryKey(o) => Some(&mut o.object_marking_refs),
+            StixObject::X509Certificate(o) => Some(&mut o.object_marking_refs),
+            StixObject::MarkingDefinition(o) => Some(&mut o.object_marking_refs),
             _ => None,
         }
     }
 
+    /// Add an object-level marking to this object.
+    ///
+    /// The marking must reference a marking definition. Markings that are
+    /// already applied are not added again.
+    pub fn add_marking(&mut self, marking: Identifier) -> Result<()> {
+        if !marking.is_type("marking-definition") {
+            return Err(Error::InvalidMarking(format!(
+                "{marking} is not a marking-definition identifier"
+            )));
+        }
+
+        if let StixObject::Custom(o) = self {
+            return push_unique(
+                &mut o.properties,
+                "object_marking_refs",
+                marking.to_string(),
+            );
+        }
+
+        if let Some(refs) = self.object_marking_refs_mut()
+            && !refs.contains(&marking)
+        {
+            refs.push(marking);
+        }
+        Ok(())
+    }
+
+    /// Add a label to this object.
+    ///
+    /// Labels that are already present are not added again. Fails for SCOs
+    /// and marking definitions, which have no `labels` property.
+    pub fn add_label(&mut self, label: impl Into<String>) -> Result<()> {
+        let label = label.into();
+
+        if let StixObject::Custom(o) = self {
+            return push_unique(&mut o.properties, "labels", label);
+        }
+
+        let type_name = self.type_name().to_string();
+        let common = self.common_mut().ok_or_else(|| {
+            Error::invalid_property_value(
+                "labels".to_string(),
+                format!("{type_name} objects have no labels"),
+            )
+        })?;
+        if !common.labels.contains(&label) {
+            common.labels.push(label);
+        }
+        Ok(())
+    }
+
     /// Check if this is a domain object (SDO).
     pub fn is_domain_object(&self) -> bool {
         matches!(
@@ -404,6 +590,8 @@ impl<'de> Deserialize<'de> for StixObject {
             .and_then(|v| v.as_str())
             .ok_or_else(|| serde::de::Error::custom("missing 'type' field"))?;
 
+        crate::extensions::check_toplevel_properties(&value).map_err(serde::de::Error::custom)?;
+
         let result = match type_str {
             "attack-pattern" => serde_json::from_value(value).map(StixObject::AttackPattern),
             "campaign" => serde_json::from_value(value).map(StixObject::Campaign),
@@ -414,7 +602,14 @@ impl<'de> Deserialize<'de> for StixObject {
             "indicator" => serde_json::from_value(value).map(StixObject::Indicator),
             "infrastructure" => serde_json::from_value(value).map(StixObject::Infrastructure),
             "intrusion-set" => serde_json::from_value(value).map(StixObject::IntrusionSet),
-            "location" => serde_json::from_value(value).map(StixObject::Location),
+            "location" => {
+                serde_json::from_value(value).and_then(|location: crate::objects::Location| {
+                    location
+                        .validate_constraints()
+                        .map_err(serde::de::Error::custom)?;
+                    Ok(StixObject::Location(location))
+                })
+            }
             "malware" => serde_json::from_value(value).map(StixObject::Malware),
             "malware-analysis" => serde_json::from_value(value).map(StixObject::MalwareAnalysis),
             "note" => serde_json::from_value(value).map(StixObject::Note),
@@ -513,11 +708,231 @@ impl_from_stix_object!(X509Certificate, crate::observables::X509Certificate);
 impl_from_stix_object!(MarkingDefinition, crate::markings::MarkingDefinition);
 impl_from_stix_object!(LanguageContent, crate::objects::LanguageContent);
 
+/// Append a string to an array property of a custom object, unless present.
+fn push_unique(properties: &mut Value, property: &str, value: String) -> Result<()> {
+    let Value::Object(map) = properties else {
+        return Err(Error::invalid_property_value(
+            property.to_string(),
+            "custom object properties are not a JSON object".to_string(),
+        ));
+    };
+    let Value::Array(values) = map
+        .entry(property)
+        .or_insert_with(|| Value::Array(Vec::new()))
+    else {
+        return Err(Error::invalid_property_value(
+            property.to_string(),
+            "expected a list".to_string(),
+        ));
+    };
+    if !values.iter().any(|v| v.as_str() == Some(value.as_str())) {
+        values.push(Value::String(value));
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
+    use super::*;
+
+    fn parse(json: serde_json::Value) -> StixObject {
+        serde_json::from_value(json).unwrap()
+    }
 
     #[test]
     fn test_stix_object_type_name() {
         // Tests will be added once object types are implemented
     }
+
+    #[test]
+    fn test_common_accessors_sdo() {
+        let indicator = parse(serde_json::json!({
+            "type": "indicator",
+            "spec_version": "2.1",
+            "id": "indicator--8e2e2d2b-17d4-4cbf-938f-98ee46b3cd3f",
+            "created_by_ref": "identity--f431f809-377b-45e0-aa1c-6a4751cae5ff",
+            "created": "2016-04-06T20:03:48.000Z",
+            "modified": "2016-04-07T20:03:48.000Z",
+            "labels": ["malicious-activity"],
+            "confidence": 85,
+            "object_marking_refs": ["marking-definition--f88d31f6-486f-44da-b317-01333bde0b82"],
+            "pattern": "[ipv4-addr:value = '198.51.100.3']",
+            "pattern_type": "stix",
+            "valid_from": "2016-01-01T00:00:00Z"
+        }));
+
+        assert_eq!(
+            indicator.created().unwrap().to_rfc3339(),
+            "2016-04-06T20:03:48+00:00"
+        );
+        assert!(indicator.modified().unwrap() > indicator.created().unwrap());
+        assert_eq!(
+            indicator.created_by_ref().unwrap().to_string(),
+            "identity--f431f809-377b-45e0-aa1c-6a4751cae5ff"
+        );
+        assert_eq!(indicator.labels(), ["malicious-activity".to_string()]);
+        assert_eq!(indicator.confidence(), Some(85));
+        assert_eq!(indicator.object_marking_refs().len(), 1);
+    }
+
+    #[test]
+    fn test_common_accessors_sro() {
+        let relationship = parse(serde_json::json!({
+            "type": "relationship",
+            "spec_version": "2.1",
+            "id": "relationship--57b56a43-b8b0-4cba-9deb-34e3e1faed9e",
+            "created": "2016-04-06T20:06:37.000Z",
+            "modified": "2016-04-06T20:06:37.000Z",
+            "relationship_type": "indicates",
+            "source_ref": "indicator--8e2e2d2b-17d4-4cbf-938f-98ee46b3cd3f",
+            "target_ref": "malware--31b940d4-6f7f-459a-80ea-9c1f17b5891b"
+        }));
+
+        assert!(relationship.created().is_some());
+        assert!(relationship.created_by_ref().is_none());
+        assert!(relationship.labels().is_empty());
+        assert_eq!(relationship.confidence(), None);
+    }
+
+    #[test]
+    fn test_common_accessors_sco() {
+        let ipv4 = parse(serde_json::json!({
+            "type": "ipv4-addr",
+            "spec_version": "2.1",
+            "id": "ipv4-addr--ff26c055-6336-5bc5-b98d-13d6226742dd",
+            "value": "198.51.100.3",
+            "object_marking_refs": ["marking-definition--f88d31f6-486f-44da-b317-01333bde0b82"]
+        }));
+
+        assert!(ipv4.common().is_none());
+        assert!(ipv4.created().is_none());
+        assert!(ipv4.modified().is_none());
+        assert!(ipv4.created_by_ref().is_none());
+        assert!(ipv4.labels().is_empty());
+        assert_eq!(ipv4.confidence(), None);
+        assert_eq!(ipv4.object_marking_refs().len(), 1);
+    }
+
+    #[test]
+    fn test_common_accessors_marking_definition() {
+        let marking = parse(serde_json::json!({
+            "type": "marking-definition",
+            "spec_version": "2.1",
+            "id": "marking-definition--34098fce-860f-48ae-8e50-ebd3cc5e41da",
+            "created": "2017-01-20T00:00:00.000Z",
+            "created_by_ref": "identity--f431f809-377b-45e0-aa1c-6a4751cae5ff",
+            "definition_type": "statement",
+            "definition": {"statement": "Copyright 2017, Example Corp"}
+        }));
+
+        assert_eq!(marking.created(), marking.modified());
+        assert!(marking.created_by_ref().is_some());
+        assert!(marking.labels().is_empty());
+    }
+
+    const TLP_GREEN: &str = "marking-definition--34098fce-860f-48ae-8e50-ebd3cc5e41da";
+
+    #[test]
+    fn test_add_marking_across_variants() {
+        let marking: Identifier = TLP_GREEN.parse().unwrap();
+        let mut objects = vec![
+            parse(serde_json::json!({
+                "type": "malware",
+                "spec_version": "2.1",
+                "id": "malware--31b940d4-6f7f-459a-80ea-9c1f17b5891b",
+                "created": "2016-04-06T20:07:09.000Z",
+                "modified": "2016-04-06T20:07:09.000Z",
+                "name": "Poison Ivy",
+                "is_family": false
+            })),
+            parse(serde_json::json!({
+                "type": "sighting",
+                "spec_version": "2.1",
+                "id": "sighting--ee20065d-2555-424f-ad9e-0f8428623c75",
+                "created": "2016-04-06T20:08:31.000Z",
+                "modified": "2016-04-06T20:08:31.000Z",
+                "sighting_of_ref": "indicator--8e2e2d2b-17d4-4cbf-938f-98ee46b3cd3f"
+            })),
+            parse(serde_json::json!({
+                "type": "domain-name",
+                "spec_version": "2.1",
+                "id": "domain-name--3c10e93f-798e-5a26-a0c1-08156efab7f5",
+                "value": "example.com"
+            })),
+            parse(serde_json::json!({
+                "type": "x-custom-object",
+                "spec_version": "2.1",
+                "id": "x-custom-object--8e2e2d2b-17d4-4cbf-938f-98ee46b3cd3f",
+                "name": "custom"
+            })),
+        ];
+
+        for object in &mut objects {
+            object.add_marking(marking.clone()).unwrap();
+            object.add_marking(marking.clone()).unwrap();
+            let value = serde_json::to_value(&*object).unwrap();
+            assert_eq!(
+                value["object_marking_refs"],
+                serde_json::json!([TLP_GREEN]),
+                "{}",
+                object.type_name()
+            );
+        }
+        assert_eq!(objects[0].object_marking_refs(), [marking.clone()]);
+        assert_eq!(objects[2].object_marking_refs(), [marking]);
+    }
+
+    #[test]
+    fn test_add_marking_rejects_non_marking_ids() {
+        let mut ipv4 = parse(serde_json::json!({
+            "type": "ipv4-addr",
+            "spec_version": "2.1",
+            "id": "ipv4-addr--ff26c055-6336-5bc5-b98d-13d6226742dd",
+            "value": "198.51.100.3"
+        }));
+        let identity: Identifier = "identity--f431f809-377b-45e0-aa1c-6a4751cae5ff"
+            .parse()
+            .unwrap();
+
+        assert!(matches!(
+            ipv4.add_marking(identity),
+            Err(Error::InvalidMarking(_))
+        ));
+        assert!(ipv4.object_marking_refs().is_empty());
+    }
+
+    #[test]
+    fn test_add_label() {
+        let mut tool = parse(serde_json::json!({
+            "type": "tool",
+            "spec_version": "2.1",
+            "id": "tool--8e2e2d2b-17d4-4cbf-938f-98ee46b3cd3f",
+            "created": "2016-04-06T20:03:48.000Z",
+            "modified": "2016-04-06T20:03:48.000Z",
+            "name": "VNC",
+            "labels": ["remote-access"]
+        }));
+        tool.add_label("remote-access").unwrap();
+        tool.add_label("reviewed").unwrap();
+        assert_eq!(tool.labels(), ["remote-access", "reviewed"]);
+
+        let mut custom = parse(serde_json::json!({
+            "type": "x-custom-object",
+            "spec_version": "2.1",
+            "id": "x-custom-object--8e2e2d2b-17d4-4cbf-938f-98ee46b3cd3f"
+        }));
+        custom.add_label("reviewed").unwrap();
+        assert_eq!(
+            serde_json::to_value(&custom).unwrap()["labels"],
+            serde_json::json!(["reviewed"])
+        );
+
+        let mut url = parse(serde_json::json!({
+            "type": "url",
+            "spec_version": "2.1",
+            "id": "url--c1477287-23ac-5971-a010-5c287877fa60",
+            "value": "https://example.com/"
+        }));
+        assert!(url.add_label("reviewed").is_err());
+    }
 }
@@ -44,6 +44,7 @@
 use crate::core::error::{Error, Result};
 use crate::registry::{
     CustomTypeOptions, ObjectCategory, SpecVersion, class_for_type, register_custom_type,
+    toplevel_property_extension,
 };
 
 /// Validates that a custom type name follows STIX conventions.
@@ -162,6 +163,8 @@ pub fn register_custom_sdo(
             parser: None,
             id_contributing_props: None,
             validator,
+            toplevel_properties: None,
+            properties: None,
         }),
     )
 }
@@ -211,6 +214,54 @@ pub fn register_custom_sco(
             parser: None,
             id_contributing_props,
             validator,
+            toplevel_properties: None,
+            properties: None,
+        }),
+    )
+}
+
+/// Register a custom SCO type with its property schema.
+///
+/// Like [`register_custom_sco`], but also records the properties the
+/// observable declares, so that pattern matching can resolve object paths
+/// against instances of the type (see [`crate::patterns::matches`]).
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use stix2::custom::register_custom_sco_with_properties;
+/// use stix2::registry::SpecVersion;
+///
+/// register_custom_sco_with_properties(
+///     "x-network-sensor",
+///     vec![SpecVersion::V21],
+///     Some(vec!["sensor_id".to_string()]),
+///     vec!["sensor_id".to_string(), "reading".to_string()],
+///     None,
+/// )?;
+/// ```
+pub fn register_custom_sco_with_properties(
+    type_name: &str,
+    versions: Vec<SpecVersion>,
+    id_contributing_props: Option<Vec<String>>,
+    properties: Vec<String>,
+    validator: Option<fn(&serde_json::Value) -> Result<()>>,
+) -> Result<()> {
+    validate_custom_type_name(type_name)?;
+
+    for version in &versions {
+        check_not_registered(type_name, *version)?;
+    }
+
+    register_custom_type(
+        type_name,
+        ObjectCategory::Observable,
+        versions,
+        Some(CustomTypeOptions {
+            id_contributing_props,
+            validator,
+            properties: Some(properties),
+            ..Default::default()
         }),
     )
 }
@@ -241,10 +292,101 @@ pub fn register_custom_extension(
             parser: None,
             id_contributing_props: None,
             validator,
+            toplevel_properties: None,
+            properties: None,
+        }),
+    )
+}
+
+/// Register a `toplevel-property-extension`.
+///
+/// Records the top-level properties the extension introduces. Objects may
+/// only carry these properties when they reference the extension in their
+/// `extensions` property (see
+/// [`check_toplevel_properties`](crate::extensions::check_toplevel_properties)).
+///
+/// # Arguments
+///
+/// * `type_name` - The extension type name (usually `extension-definition--<UUID>`)
+/// * `versions` - Which STIX versions to register for
+/// * `properties` - The top-level properties the extension introduces
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use stix2::custom::register_toplevel_property_extension;
+/// use stix2::registry::SpecVersion;
+///
+/// register_toplevel_property_extension(
+///     "extension-definition--71736db5-10db-43d3-b0e3-65cf81601fe1",
+///     vec![SpecVersion::V21],
+///     vec!["rank".to_string(), "toxicity".to_string()],
+/// )?;
+/// ```
+pub fn register_toplevel_property_extension(
+    type_name: &str,
+    versions: Vec<SpecVersion>,
+    properties: Vec<String>,
+) -> Result<()> {
+    validate_extension_type_name(type_name)?;
+
+    if properties.is_empty() {
+        return Err(Error::InvalidPropertyValue {
+            property: "properties".to_string(),
+            message: format!("Extension '{type_name}' must introduce at least one property"),
+        });
+    }
+
+    for property in &properties {
+        validate_toplevel_property_name(property)?;
+    }
+
+    for version in &versions {
+        check_not_registered(type_name, *version)?;
+        for property in &properties {
+            if let Some(owner) = toplevel_property_extension(property, *version) {
+                return Err(Error::DuplicateType(format!(
+                    "Property '{property}' is already introduced by extension '{owner}' for STIX {}",
+                    version.as_str()
+                )));
+            }
+        }
+    }
+
+    register_custom_type(
+        type_name,
+        ObjectCategory::Extension,
+        versions,
+        Some(CustomTypeOptions {
+            toplevel_properties: Some(properties),
+            ..Default::default()
         }),
     )
 }
 
+/// Validates a top-level property name introduced by an extension.
+///
+/// STIX 2.1 property names contain only lowercase letters, digits and
+/// underscores, start with a letter, and are 3 to 250 characters long.
+fn validate_toplevel_property_name(property: &str) -> Result<()> {
+    let valid = (3..=250).contains(&property.len())
+        && property.starts_with(|c: char| c.is_ascii_lowercase())
+        && property
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+
+    if !valid {
+        return Err(Error::InvalidPropertyValue {
+            property: property.to_string(),
+            message: "Top-level property names must be 3-250 lowercase letters, digits or \
+                      underscores, starting with a letter"
+                .to_string(),
+        });
+    }
+
+    Ok(())
+}
+
 /// Register a custom marking definition type.
 ///
 /// # Arguments
@@ -271,6 +413,8 @@ pub fn register_custom_marking(
             parser: None,
             id_contributing_props: None,
             validator,
+            toplevel_properties: None,
+            properties: None,
         }),
     )
 }
@@ -447,10 +591,11 @@ macro_rules! define_custom_observable {
 
             /// Register this custom type with the global registry.
             pub fn register() -> $crate::core::error::Result<()> {
-                $crate::custom::register_custom_sco(
+                $crate::custom::register_custom_sco_with_properties(
                     $type_str,
                     vec![$crate::registry::SpecVersion::V21],
                     Some(vec![$(stringify!($id_prop).to_string()),*]),
+                    vec![$(stringify!($field).to_string()),*],
                     None,
                 )
             }
@@ -622,6 +767,46 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_register_toplevel_property_extension() {
+        let ext = "extension-definition--0f9a4c4e-1c57-4b5e-9d1a-2a7a2e0c1b11";
+        let properties = vec!["test_rank".to_string(), "test_toxicity".to_string()];
+
+        assert!(
+            register_toplevel_property_extension(ext, vec![SpecVersion::V21], properties).is_ok()
+        );
+        assert_eq!(
+            toplevel_property_extension("test_rank", SpecVersion::V21),
+            Some(ext.to_string())
+        );
+        assert_eq!(
+            toplevel_property_extension("test_rank", SpecVersion::V20),
+            None
+        );
+
+        // A property can only be introduced by one extension
+        let other = "extension-definition--6d0f2c1b-7e1e-4f7b-8f3e-3b2d8e6c9a22";
+        let result = register_toplevel_property_extension(
+            other,
+            vec![SpecVersion::V21],
+            vec!["test_rank".to_string()],
+        );
+        assert!(result.is_err());
+
+        // Property names follow STIX naming rules
+        for invalid in ["Rank", "x-rank", "rk", "9lives"] {
+            let result = register_toplevel_property_extension(
+                other,
+                vec![SpecVersion::V21],
+                vec![invalid.to_string()],
+            );
+            assert!(result.is_err(), "{invalid}");
+        }
+        assert!(
+            register_toplevel_property_extension(other, vec![SpecVersion::V21], vec![]).is_err()
+        );
+    }
+
     // Test the macros
     define_custom_object! {
         /// Test custom object for unit tests.
@@ -93,7 +93,11 @@ impl DataSource for CompositeDataSource {
             }
         }
 
-        Ok(deduplicate(all_data))
+        // Versions are distinct by modified timestamp; the same version may
+        // come from several sources
+        all_data.sort_by_key(get_modified_opt);
+        all_data.dedup_by(|a, b| get_modified_opt(a) == get_modified_opt(b));
+        Ok(all_data)
     }
 
     fn query(&self, filters: &[Filter]) -> Result<Vec<StixObject>> {
@@ -20,7 +20,7 @@ pub enum FilterOperator {
     GreaterThanOrEqual,
     /// Value is in a set.
     In,
-    /// Value contains substring.
+    /// Value contains substring, or list value contains an element.
     Contains,
 }
 
@@ -111,6 +111,8 @@ impl Filter {
             (FilterOperator::Contains, FilterValue::String(s)) => {
                 if let Some(obj_str) = obj_value.as_str() {
                     obj_str.contains(s.as_str())
+                } else if let Some(items) = obj_value.as_array() {
+                    items.iter().any(|item| item.as_str() == Some(s.as_str()))
                 } else {
                     false
                 }
@@ -197,4 +199,14 @@ mod tests {
         let obj = serde_json::json!({"type": "malware"});
         assert!(!filter.matches(&obj));
     }
+
+    #[test]
+    fn test_filter_contains_list_element() {
+        let filter = Filter::new("object_refs", FilterOperator::Contains, "indicator--1");
+        let obj = serde_json::json!({"object_refs": ["malware--2", "indicator--1"]});
+        assert!(filter.matches(&obj));
+
+        let obj = serde_json::json!({"object_refs": ["malware--2", "indicator--10"]});
+        assert!(!filter.matches(&obj));
+    }
 }
@@ -86,6 +86,22 @@ impl DataSource for MemoryStore {
         }))
     }
 
+    fn get_many(&self, ids: &[Identifier]) -> Result<Vec<(Identifier, Option<StixObject>)>> {
+        let guard = self
+            .objects
+            .read()
+            .map_err(|_| Error::read_lock("MemoryStore::get_many"))?;
+        Ok(ids
+            .iter()
+            .map(|id| {
+                let object = guard
+                    .get(&id.to_string())
+                    .and_then(|versions| versions.last().cloned());
+                (id.clone(), object)
+            })
+            .collect())
+    }
+
     fn all_versions(&self, id: &Identifier) -> Result<Vec<StixObject>> {
         let key = id.to_string();
         let guard = self
@@ -255,6 +271,32 @@ mod tests {
         assert!(retrieved.is_some());
     }
 
+    #[test]
+    fn test_memory_store_get_many() {
+        let mut store = MemoryStore::new();
+        let mut ids = Vec::new();
+        for address in ["10.0.0.1", "10.0.0.2"] {
+            let indicator = Indicator::builder()
+                .name("Test Indicator")
+                .pattern(format!("[ipv4-addr:value = '{address}']"))
+                .pattern_type(PatternType::Stix)
+                .valid_from_now()
+                .build()
+                .unwrap();
+            ids.push(indicator.id.clone());
+            store.add(StixObject::Indicator(indicator)).unwrap();
+        }
+        let missing = Identifier::new("indicator").unwrap();
+        ids.insert(1, missing.clone());
+
+        let results = store.get_many(&ids).unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(&results[0].0, &ids[0]);
+        assert_eq!(results[0].1.as_ref().map(|o| o.id()), Some(&ids[0]));
+        assert_eq!(results[1], (missing, None));
+        assert_eq!(results[2].1.as_ref().map(|o| o.id()), Some(&ids[2]));
+    }
+
     #[test]
     fn test_memory_store_query() {
         let mut store = MemoryStore::new();
@@ -27,6 +27,15 @@ pub trait DataSource {
     /// Get an object by ID.
     fn get(&self, id: &Identifier) -> Result<Option<StixObject>>;
 
+    /// Get several objects by ID.
+    ///
+    /// Returns each requested ID paired with its object, in request order.
+    fn get_many(&self, ids: &[Identifier]) -> Result<Vec<(Identifier, Option<StixObject>)>> {
+        ids.iter()
+            .map(|id| Ok((id.clone(), self.get(id)?)))
+            .collect()
+    }
+
     /// Get all versions of an object.
     fn all_versions(&self, id: &Identifier) -> Result<Vec<StixObject>>;
 
@@ -114,15 +123,102 @@ pub trait DataStore: DataSource + DataSink {
         ];
         self.query(&filters)
     }
+
+    /// Get notes that reference an object in their `object_refs`.
+    fn notes_for(&self, id: &Identifier) -> Result<Vec<StixObject>> {
+        let filters = vec![
+            Filter::new("type", FilterOperator::Equal, "note"),
+            Filter::new("object_refs", FilterOperator::Contains, id),
+        ];
+        self.query(&filters)
+    }
+
+    /// Get opinions that reference an object in their `object_refs`.
+    fn opinions_for(&self, id: &Identifier) -> Result<Vec<StixObject>> {
+        let filters = vec![
+            Filter::new("type", FilterOperator::Equal, "opinion"),
+            Filter::new("object_refs", FilterOperator::Contains, id),
+        ];
+        self.query(&filters)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use crate::objects::{Indicator, Malware, Note, Opinion};
+    use crate::vocab::{OpinionValue, PatternType};
+
     #[test]
     fn test_memory_store_basic() {
         let store = MemoryStore::new();
         assert!(store.get_all().unwrap().is_empty());
     }
+
+    fn indicator() -> Indicator {
+        Indicator::builder()
+            .name("Test Indicator")
+            .pattern("[ipv4-addr:value = '10.0.0.1']")
+            .pattern_type(PatternType::Stix)
+            .valid_from_now()
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_notes_for() {
+        let mut store = MemoryStore::new();
+        let indicator = indicator();
+        let malware = Malware::builder()
+            .name("Test Malware")
+            .is_family(false)
+            .build()
+            .unwrap();
+
+        let note = Note::builder()
+            .content("Seen in phishing campaign")
+            .object_ref(indicator.id.clone())
+            .build()
+            .unwrap();
+        let other_note = Note::builder()
+            .content("Unrelated")
+            .object_ref(malware.id.clone())
+            .build()
+            .unwrap();
+        let note_id = note.id.clone();
+
+        store.add(StixObject::Indicator(indicator.clone())).unwrap();
+        store.add(StixObject::Malware(malware)).unwrap();
+        store.add(StixObject::Note(note)).unwrap();
+        store.add(StixObject::Note(other_note)).unwrap();
+
+        let notes = store.notes_for(&indicator.id).unwrap();
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes[0].id(), &note_id);
+
+        assert!(store.opinions_for(&indicator.id).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_opinions_for() {
+        let mut store = MemoryStore::new();
+        let indicator = indicator();
+
+        let opinion = Opinion::builder()
+            .opinion(OpinionValue::Agree)
+            .object_ref(indicator.id.clone())
+            .build()
+            .unwrap();
+        let opinion_id = opinion.id.clone();
+
+        store.add(StixObject::Indicator(indicator.clone())).unwrap();
+        store.add(StixObject::Opinion(opinion)).unwrap();
+
+        let opinions = store.opinions_for(&indicator.id).unwrap();
+        assert_eq!(opinions.len(), 1);
+        assert_eq!(opinions[0].id(), &opinion_id);
+
+        assert!(store.notes_for(&indicator.id).unwrap().is_empty());
+    }
 }
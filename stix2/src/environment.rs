@@ -4,6 +4,7 @@
 //! for working with STIX objects with common defaults.
 
 use chrono::{DateTime, Utc};
+use serde_json::Value;
 
 use crate::core::bundle::Bundle;
 use crate::core::error::{Error, Result};
@@ -13,6 +14,9 @@ use crate::core::stix_object::StixObject;
 use crate::datastore::{CompositeDataSource, DataSink, DataSource, Filter, MemoryStore};
 use crate::equivalence::{object_equivalence, object_similarity};
 use crate::graph::{StixGraph, graph_similarity};
+use crate::registry::SpecVersion;
+use crate::v20::STIX_21_ONLY_TYPES;
+use crate::validation::{validate_id, validate_type};
 
 /// Factory for creating STIX objects with default values.
 ///
@@ -26,6 +30,7 @@ use crate::graph::{StixGraph, graph_similarity};
 ///     .with_created_by_ref("identity--12345678-1234-1234-1234-123456789012".parse().unwrap());
 ///
 /// // All objects created will have this creator
+/// let indicator = factory.create(Indicator::builder()./* ... */build()?)?;
 /// ```
 #[derive(Debug, Clone, Default)]
 pub struct ObjectFactory {
@@ -39,6 +44,8 @@ pub struct ObjectFactory {
     object_marking_refs: Option<Vec<Identifier>>,
     /// Whether to append to list properties or replace them
     list_append: bool,
+    /// Spec version of created objects (2.1 if unset)
+    spec_version: Option<SpecVersion>,
 }
 
 impl ObjectFactory {
@@ -80,6 +87,12 @@ impl ObjectFactory {
         self
     }
 
+    /// Set the spec version of created objects.
+    pub fn with_spec_version(mut self, version: SpecVersion) -> Self {
+        self.spec_version = Some(version);
+        self
+    }
+
     /// Get the default created_by_ref.
     pub fn created_by_ref(&self) -> Option<&Identifier> {
         self.created_by_ref.as_ref()
@@ -100,6 +113,11 @@ impl ObjectFactory {
         self.object_marking_refs.as_ref()
     }
 
+    /// Get the spec version of created objects.
+    pub fn spec_version(&self) -> SpecVersion {
+        self.spec_version.unwrap_or(SpecVersion::V21)
+    }
+
     /// Set the default creator (mutable).
     pub fn set_default_creator(&mut self, creator: Option<Identifier>) {
         self.created_by_ref = creator;
@@ -119,6 +137,119 @@ impl ObjectFactory {
     pub fn set_default_object_marking_refs(&mut self, refs: Option<Vec<Identifier>>) {
         self.object_marking_refs = refs;
     }
+
+    /// Set the spec version of created objects (mutable).
+    pub fn set_default_spec_version(&mut self, version: Option<SpecVersion>) {
+        self.spec_version = version;
+    }
+
+    /// Create an object with the factory's defaults.
+    ///
+    /// A default creator only applies to objects without one; default
+    /// external references and markings are appended to (or, without
+    /// `list_append`, replace) the object's own. The object gets the
+    /// factory's spec version and must be valid for it: STIX 2.0 has no
+    /// 2.1-only types, top-level SCOs, `confidence` or `lang`, and requires
+    /// UUIDv4 identifiers.
+    pub fn create(&self, object: impl Into<StixObject>) -> Result<StixObject> {
+        let mut object = object.into();
+        let version = self.spec_version();
+
+        if let Some(common) = object.common_mut() {
+            if common.created_by_ref.is_none() {
+                common.created_by_ref = self.created_by_ref.clone();
+            }
+            if let Some(created) = self.created {
+                // A new object's modified timestamp follows its created one
+                if common.modified == common.created {
+                    common.modified = created.into();
+                }
+                common.created = created.into();
+            }
+            if let Some(refs) = &self.external_references {
+                self.apply_list(&mut common.external_references, refs);
+            }
+            if let Some(refs) = &self.object_marking_refs {
+                self.apply_list(&mut common.object_marking_refs, refs);
+            }
+        }
+
+        set_spec_version(&mut object, version);
+        check_spec_version(&object, version)?;
+
+        Ok(object)
+    }
+
+    fn apply_list<T: Clone + PartialEq>(&self, list: &mut Vec<T>, defaults: &[T]) {
+        if !self.list_append {
+            *list = defaults.to_vec();
+            return;
+        }
+        for item in defaults {
+            if !list.contains(item) {
+                list.push(item.clone());
+            }
+        }
+    }
+}
+
+/// Set the `spec_version` property of an object.
+fn set_spec_version(object: &mut StixObject, version: SpecVersion) {
+    let spec_version = version.as_str().to_string();
+
+    if let Some(common) = object.common_mut() {
+        common.spec_version = spec_version;
+        return;
+    }
+
+    match object {
+        StixObject::MarkingDefinition(o) => o.spec_version = spec_version,
+        StixObject::Custom(o) => {
+            if let Some(properties) = o.properties.as_object_mut() {
+                properties.insert("spec_version".to_string(), spec_version.into());
+            }
+        }
+        // Top-level SCOs only exist in 2.1, which they default to
+        _ => {}
+    }
+}
+
+/// Check an object against the rules of a spec version.
+fn check_spec_version(object: &StixObject, version: SpecVersion) -> Result<()> {
+    let type_name = object.type_name();
+    validate_type(type_name, version)?;
+    validate_id(&object.id().to_string(), version, None, false)?;
+
+    if version == SpecVersion::V21 {
+        return Ok(());
+    }
+
+    if STIX_21_ONLY_TYPES.contains(&type_name) {
+        return Err(Error::InvalidType(format!(
+            "Object type '{type_name}' does not exist in STIX 2.0"
+        )));
+    }
+    if object.is_cyber_observable() {
+        return Err(Error::InvalidType(format!(
+            "'{type_name}' is not a top-level object in STIX 2.0"
+        )));
+    }
+    if let Some(common) = object.common() {
+        if common.confidence.is_some() {
+            return Err(Error::InvalidPropertyValue {
+                property: "confidence".to_string(),
+                message: "not defined in STIX 2.0".to_string(),
+            });
+        }
+        if common.lang.is_some() {
+            return Err(Error::InvalidPropertyValue {
+                property: "lang".to_string(),
+                message: "not defined in STIX 2.0".to_string(),
+            });
+        }
+    }
+
+    Ok(())
 }
 
 /// STIX Environment for managing objects and data sources.
@@ -147,6 +278,8 @@ pub struct Environment {
     source: CompositeDataSource,
     /// Data sink for storing
     sink: Option<Box<dyn DataSinkWrapper>>,
+    /// Repair malformed identifiers when parsing
+    lenient_ids: bool,
 }
 
 // Wrapper trait to allow storing any DataSink
@@ -184,6 +317,7 @@ impl Environment {
             factory: ObjectFactory::new(),
             source: CompositeDataSource::new(),
             sink: None,
+            lenient_ids: false,
         }
     }
 
@@ -222,6 +356,33 @@ impl Environment {
         &mut self.factory
     }
 
+    /// Set the spec version of objects created through the environment.
+    pub fn with_spec_version(mut self, version: SpecVersion) -> Self {
+        self.factory.set_default_spec_version(Some(version));
+        self
+    }
+
+    /// Repair malformed identifiers when parsing instead of rejecting them.
+    ///
+    /// Applies to [`Self::parse_with_warnings`] and
+    /// [`Self::parse_bundle_with_warnings`]; see [`Identifier::repair`].
+    pub fn with_lenient_ids(mut self, lenient: bool) -> Self {
+        self.lenient_ids = lenient;
+        self
+    }
+
+    /// Whether malformed identifiers are repaired when parsing.
+    pub fn lenient_ids(&self) -> bool {
+        self.lenient_ids
+    }
+
+    /// Create an object with the factory's defaults.
+    ///
+    /// See [`ObjectFactory::create`].
+    pub fn create(&self, object: impl Into<StixObject>) -> Result<StixObject> {
+        self.factory.create(object)
+    }
+
     // DataSource methods
 
     /// Get an object by ID.
@@ -392,6 +553,66 @@ impl Environment {
     pub fn parse_bundle(json: &str) -> Result<Bundle> {
         crate::parse_bundle(json)
     }
+
+    /// Parse a STIX JSON string, repairing malformed identifiers in
+    /// lenient mode.
+    ///
+    /// Returns the object and a warning for every repaired identifier. In
+    /// strict mode (the default) this is [`Self::parse`] without warnings.
+    pub fn parse_with_warnings(&self, json: &str) -> Result<(StixObject, Vec<String>)> {
+        if !self.lenient_ids {
+            return Ok((crate::parse(json)?, Vec::new()));
+        }
+
+        let (value, warnings) = repaired_value(json)?;
+        Ok((serde_json::from_value(value)?, warnings))
+    }
+
+    /// Parse a STIX bundle, repairing malformed identifiers in lenient mode.
+    ///
+    /// See [`Self::parse_with_warnings`].
+    pub fn parse_bundle_with_warnings(&self, json: &str) -> Result<(Bundle, Vec<String>)> {
+        if !self.lenient_ids {
+            return Ok((crate::parse_bundle(json)?, Vec::new()));
+        }
+
+        let (value, warnings) = repaired_value(json)?;
+        Ok((serde_json::from_value(value)?, warnings))
+    }
+}
+
+/// Parse JSON and repair the identifiers in it.
+fn repaired_value(json: &str) -> Result<(Value, Vec<String>)> {
+    let mut value: Value = serde_json::from_str(json)?;
+    let mut warnings = Vec::new();
+    repair_ids(&mut value, false, &mut warnings);
+    Ok((value, warnings))
+}
+
+/// Repair identifiers in `id`, `*_ref` and `*_refs` properties at any depth.
+pub(crate) fn repair_ids(value: &mut Value, is_id: bool, warnings: &mut Vec<String>) {
+    match value {
+        Value::String(s) if is_id => {
+            if let Some(repaired) = Identifier::repair(s) {
+                warnings.push(format!(
+                    "Repaired malformed identifier '{s}' to '{repaired}'"
+                ));
+                *s = repaired;
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                repair_ids(item, is_id, warnings);
+            }
+        }
+        Value::Object(map) => {
+            for (key, item) in map.iter_mut() {
+                let is_id = key == "id" || key.ends_with("_ref") || key.ends_with("_refs");
+                repair_ids(item, is_id, warnings);
+            }
+        }
+        _ => {}
+    }
 }
 
 impl Default for Environment {
@@ -438,6 +659,44 @@ mod tests {
         assert!(results.is_empty());
     }
 
+    #[test]
+    fn test_environment_creates_v20_objects() {
+        use crate::objects::{Identity, Location, Malware};
+
+        let creator: Identifier = "identity--12345678-1234-4234-8234-123456789012"
+            .parse()
+            .unwrap();
+        let env = Environment::new().with_factory(
+            ObjectFactory::new()
+                .with_created_by_ref(creator.clone())
+                .with_spec_version(SpecVersion::V20),
+        );
+        assert_eq!(env.factory().spec_version(), SpecVersion::V20);
+
+        let identity = Identity::builder().name("Producer").build().unwrap();
+        let created = env.create(identity).unwrap();
+        assert_eq!(created.created_by_ref(), Some(&creator));
+
+        let json = serde_json::to_value(&created).unwrap();
+        assert_eq!(json["spec_version"], "2.0");
+
+        // 2.1-only types and properties are rejected
+        let location = Location::builder().country("RU").build().unwrap();
+        assert!(env.create(location).is_err());
+        let malware = Malware::builder()
+            .name("Emotet")
+            .is_family(true)
+            .confidence(80)
+            .build()
+            .unwrap();
+        assert!(env.create(malware).is_err());
+
+        // Without a configured version, objects are 2.1
+        let identity = Identity::builder().name("Producer").build().unwrap();
+        let created = Environment::new().create(identity).unwrap();
+        assert_eq!(created.common().unwrap().spec_version, "2.1");
+    }
+
     #[test]
     fn test_environment_parse() {
         let json = r#"{
@@ -454,4 +713,57 @@ mod tests {
         let obj = Environment::parse(json).unwrap();
         assert_eq!(obj.type_name(), "indicator");
     }
+
+    #[test]
+    fn test_lenient_ids_repair_malformed_identifiers() {
+        let json = r#"{
+            "type": "bundle",
+            "id": "bundle--44af6c39-c09b-49c5-9de2-394224b04982",
+            "objects": [
+                {
+                    "type": "indicator",
+                    "spec_version": "2.1",
+                    "id": "indicator-12345678-1234-4234-8234-123456789012",
+                    "created": "2023-01-01T00:00:00.000Z",
+                    "modified": "2023-01-01T00:00:00.000Z",
+                    "pattern": "[file:name = 'test.exe']",
+                    "pattern_type": "stix",
+                    "valid_from": "2023-01-01T00:00:00.000Z",
+                    "created_by_ref": "identity--ABCDEF01-1234-4234-8234-123456789012"
+                }
+            ]
+        }"#;
+
+        // Strict mode keeps rejecting the single-dash id
+        let strict = Environment::new();
+        assert!(!strict.lenient_ids());
+        assert!(strict.parse_bundle_with_warnings(json).is_err());
+
+        let env = Environment::new().with_lenient_ids(true);
+        let (bundle, warnings) = env.parse_bundle_with_warnings(json).unwrap();
+        let indicator = &bundle.objects[0];
+        assert_eq!(
+            indicator.id().to_string(),
+            "indicator--12345678-1234-4234-8234-123456789012"
+        );
+        assert_eq!(
+            indicator.created_by_ref().map(ToString::to_string),
+            Some("identity--abcdef01-1234-4234-8234-123456789012".to_string())
+        );
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings[0].contains("indicator-12345678"));
+
+        // Well-formed input parses without warnings
+        let clean = r#"{
+            "type": "identity",
+            "spec_version": "2.1",
+            "id": "identity--abcdef01-1234-4234-8234-123456789012",
+            "created": "2023-01-01T00:00:00.000Z",
+            "modified": "2023-01-01T00:00:00.000Z",
+            "name": "Producer"
+        }"#;
+        let (identity, warnings) = env.parse_with_warnings(clean).unwrap();
+        assert_eq!(identity.type_name(), "identity");
+        assert!(warnings.is_empty());
+    }
 }
@@ -68,14 +68,27 @@ pub struct WindowsPeSection {
 /// Contains information about archive files (e.g., ZIP, TAR).
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ArchiveExt {
-    /// References to file objects contained in the archive (required).
-    /// Each reference must be a file--<uuid> identifier.
+    /// References to objects contained in the archive (required).
+    /// Each reference must be a file or directory identifier.
     pub contains_refs: Vec<Identifier>,
     /// A comment included as part of the archive file.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub comment: Option<String>,
 }
 
+impl Constrained for ArchiveExt {
+    /// Validate ArchiveExt constraints.
+    ///
+    /// - `contains_refs` must reference only `file` or `directory` types.
+    fn validate_constraints(&self) -> Result<()> {
+        use crate::validation::check_refs_type;
+
+        check_refs_type(&self.contains_refs, "contains_refs", &["file", "directory"])?;
+
+        Ok(())
+    }
+}
+
 /// PDF extension.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PdfExt {
@@ -596,6 +609,183 @@ fn default_spec_version() -> String {
     "2.1".to_string()
 }
 
+/// Validate an SCO extension against the type defined for its key.
+///
+/// The value under a predefined extension key (e.g. `archive-ext`) must be
+/// an object of the matching extension type, without properties of another
+/// extension, and must satisfy that type's constraints. Other keys (custom
+/// and `extension-definition--` extensions) are not checked here.
+pub fn validate_extension(key: &str, value: &Value) -> Result<()> {
+    match key {
+        "archive-ext" => parse_extension::<ArchiveExt>(key, value)?.validate_constraints(),
+        "ntfs-ext" => parse_extension::<NtfsExt>(key, value).map(drop),
+        "pdf-ext" => parse_extension::<PdfExt>(key, value).map(drop),
+        "raster-image-ext" => parse_extension::<RasterImageExt>(key, value).map(drop),
+        "windows-pebinary-ext" => {
+            let ext = parse_extension::<WindowsPeBinaryExt>(key, value)?;
+            match &ext.optional_header {
+                Some(header) => header.validate_constraints(),
+                None => Ok(()),
+            }
+        }
+        "http-request-ext" => parse_extension::<HttpRequestExt>(key, value)?.validate_constraints(),
+        "icmp-ext" => parse_extension::<IcmpExt>(key, value).map(drop),
+        "socket-ext" => parse_extension::<SocketExt>(key, value)?.validate_constraints(),
+        "tcp-ext" => parse_extension::<TcpExt>(key, value).map(drop),
+        "windows-process-ext" => parse_extension::<WindowsProcessExt>(key, value).map(drop),
+        "windows-service-ext" => parse_extension::<WindowsServiceExt>(key, value).map(drop),
+        "unix-account-ext" => parse_extension::<UnixAccountExt>(key, value).map(drop),
+        _ => Ok(()),
+    }
+}
+
+/// Validate all extensions of an SCO.
+///
+/// Each extension is checked with [`validate_extension`]. When the current
+/// validation context has `strict_extensions` set, the SCO may also only
+/// carry predefined extensions of its own type (see
+/// [`check_extensions_allowed`]).
+pub fn validate_extensions(sco_type: &str, extensions: &IndexMap<String, Value>) -> Result<()> {
+    let strict = crate::validation::current_context().strict_extensions;
+    check_extensions_allowed(sco_type, extensions, strict)?;
+
+    for (key, value) in extensions {
+        validate_extension(key, value)?;
+    }
+    Ok(())
+}
+
+/// Check that an SCO only carries predefined extensions valid for its type.
+///
+/// In non-strict mode every extension is allowed. In strict mode a
+/// predefined extension of another SCO type (e.g. `pdf-ext` on a
+/// `domain-name`) is rejected. Custom and `extension-definition--`
+/// extensions are allowed in both modes.
+pub fn check_extensions_allowed(
+    sco_type: &str,
+    extensions: &IndexMap<String, Value>,
+    strict: bool,
+) -> Result<()> {
+    use crate::registry::{extension_owner, valid_extensions_for};
+
+    if !strict {
+        return Ok(());
+    }
+
+    let valid = valid_extensions_for(sco_type);
+    let misplaced = extensions
+        .keys()
+        .find(|key| extension_owner(key).is_some() && !valid.contains(&key.as_str()));
+
+    if let Some(key) = misplaced {
+        let message = if valid.is_empty() {
+            format!("'{key}' is not valid on '{sco_type}', which has no predefined extensions")
+        } else {
+            format!(
+                "'{key}' is not valid on '{sco_type}'; expected one of: {}",
+                valid.join(", ")
+            )
+        };
+        return Err(Error::InvalidPropertyValue {
+            property: "extensions".to_string(),
+            message,
+        });
+    }
+    Ok(())
+}
+
+/// Check that top-level properties introduced by registered
+/// `toplevel-property-extension`s only appear on objects that reference
+/// the extension.
+///
+/// Each such property requires an `extensions` entry for its extension.
+/// When the entry declares an `extension_type`, it must be
+/// `toplevel-property-extension`.
+pub fn check_toplevel_properties(object: &Value) -> Result<()> {
+    use crate::registry::{SpecVersion, toplevel_property_extension};
+
+    let Value::Object(properties) = object else {
+        return Ok(());
+    };
+
+    let version = properties
+        .get("spec_version")
+        .and_then(Value::as_str)
+        .and_then(|v| v.parse::<SpecVersion>().ok())
+        .unwrap_or(SpecVersion::V21);
+    let extensions = properties.get("extensions").and_then(Value::as_object);
+
+    for name in properties.keys() {
+        let Some(extension) = toplevel_property_extension(name, version) else {
+            continue;
+        };
+
+        let invalid = |message: String| Error::InvalidPropertyValue {
+            property: name.clone(),
+            message,
+        };
+        let Some(entry) = extensions.and_then(|e| e.get(&extension)) else {
+            return Err(invalid(format!(
+                "property is introduced by extension '{extension}', which the object does not reference"
+            )));
+        };
+        match entry.get("extension_type").and_then(Value::as_str) {
+            None | Some("toplevel-property-extension") => {}
+            Some(other) => {
+                return Err(invalid(format!(
+                    "extension '{extension}' is referenced as '{other}', expected 'toplevel-property-extension'"
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Deserialize an extension value into its extension type.
+///
+/// Serde ignores unknown fields, so an extension of one type stored under
+/// another type's key could deserialize into a mostly empty struct. Any
+/// non-empty property that does not survive the round trip is therefore
+/// rejected, except custom (`x_`) properties.
+fn parse_extension<T>(key: &str, value: &Value) -> Result<T>
+where
+    T: serde::de::DeserializeOwned + Serialize,
+{
+    let invalid = |message: String| Error::InvalidPropertyValue {
+        property: format!("extensions.{key}"),
+        message,
+    };
+
+    let Value::Object(properties) = value else {
+        return Err(invalid("extension must be an object".to_string()));
+    };
+
+    let ext: T = serde_json::from_value(value.clone())
+        .map_err(|e| invalid(format!("not a valid '{key}' extension: {e}")))?;
+
+    let known = serde_json::to_value(&ext)?;
+    let unexpected = properties.iter().find(|(name, property)| {
+        !name.starts_with("x_") && known.get(name.as_str()).is_none() && !is_empty_value(property)
+    });
+    if let Some((name, _)) = unexpected {
+        return Err(invalid(format!(
+            "property '{name}' is not defined for the '{key}' extension"
+        )));
+    }
+
+    Ok(ext)
+}
+
+/// Whether a JSON value carries no data (dropped by serialization defaults).
+fn is_empty_value(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::Array(items) => items.is_empty(),
+        Value::Object(map) => map.is_empty(),
+        _ => false,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -645,4 +835,156 @@ mod tests {
         let json = serde_json::to_string(&ext).unwrap();
         assert!(json.contains("CA:TRUE"));
     }
+
+    #[test]
+    fn test_validate_correctly_keyed_extension() {
+        let archive = serde_json::json!({
+            "contains_refs": ["file--019fde1c-94ab-4b1a-9f34-fbb2d2e1da1a"],
+            "comment": "quarterly report"
+        });
+        assert!(validate_extension("archive-ext", &archive).is_ok());
+
+        let pdf = serde_json::json!({"version": "1.7", "x_producer": "scanner"});
+        assert!(validate_extension("pdf-ext", &pdf).is_ok());
+
+        let http = serde_json::json!({"request_method": "get", "request_value": "/"});
+        assert!(validate_extension("http-request-ext", &http).is_ok());
+    }
+
+    #[test]
+    fn test_validate_mismatched_extension() {
+        // A PDF extension under the archive key is missing contains_refs
+        let pdf = serde_json::json!({"version": "1.7", "is_optimized": false});
+        assert!(validate_extension("archive-ext", &pdf).is_err());
+
+        // An archive extension under the NTFS key has unknown properties
+        let archive = serde_json::json!({
+            "contains_refs": ["file--019fde1c-94ab-4b1a-9f34-fbb2d2e1da1a"]
+        });
+        let err = validate_extension("ntfs-ext", &archive).unwrap_err();
+        assert!(err.to_string().contains("contains_refs"));
+
+        assert!(validate_extension("tcp-ext", &serde_json::json!(["a"])).is_err());
+    }
+
+    #[test]
+    fn test_validate_archive_contains_refs_type() {
+        let archive = serde_json::json!({
+            "contains_refs": ["ipv4-addr--019fde1c-94ab-4b1a-9f34-fbb2d2e1da1a"]
+        });
+        assert!(validate_extension("archive-ext", &archive).is_err());
+    }
+
+    #[test]
+    fn test_validate_unknown_extension_keys_ignored() {
+        let mut extensions = IndexMap::new();
+        extensions.insert(
+            "extension-definition--d83fce45-ef58-4c6c-a3f4-1fbc32e98c6e".to_string(),
+            serde_json::json!({"extension_type": "property-extension", "rank": 5}),
+        );
+        extensions.insert("x-acme-ext".to_string(), serde_json::json!({"a": 1}));
+        assert!(validate_extensions("file", &extensions).is_ok());
+
+        extensions.insert(
+            "icmp-ext".to_string(),
+            serde_json::json!({"icmp_type_hex": "08"}),
+        );
+        assert!(validate_extensions("network-traffic", &extensions).is_err());
+    }
+
+    #[test]
+    fn test_extensions_allowed_for_type() {
+        let mut extensions = IndexMap::new();
+        extensions.insert("pdf-ext".to_string(), serde_json::json!({"version": "1.7"}));
+
+        assert!(check_extensions_allowed("file", &extensions, true).is_ok());
+        assert!(check_extensions_allowed("domain-name", &extensions, true).is_err());
+        assert!(check_extensions_allowed("domain-name", &extensions, false).is_ok());
+
+        extensions.insert("windows-process-ext".to_string(), serde_json::json!({}));
+        assert!(check_extensions_allowed("file", &extensions, true).is_err());
+    }
+
+    #[test]
+    fn test_custom_extensions_allowed_in_strict_mode() {
+        let mut extensions = IndexMap::new();
+        extensions.insert(
+            "extension-definition--d83fce45-ef58-4c6c-a3f4-1fbc32e98c6e".to_string(),
+            serde_json::json!({"extension_type": "property-extension"}),
+        );
+        extensions.insert("x-acme-ext".to_string(), serde_json::json!({"a": 1}));
+
+        assert!(check_extensions_allowed("domain-name", &extensions, true).is_ok());
+    }
+
+    #[test]
+    fn test_toplevel_property_extension() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        use crate::registry::SpecVersion;
+
+        let ext = "extension-definition--3a4b5c6d-7e8f-4a0b-9c1d-2e3f4a5b6c7d";
+        crate::custom::register_toplevel_property_extension(
+            ext,
+            vec![SpecVersion::V21],
+            vec!["toplevel_test_rank".to_string()],
+        )?;
+
+        let indicator = |extensions: Value| {
+            serde_json::json!({
+                "type": "indicator",
+                "spec_version": "2.1",
+                "id": "indicator--8e2e2d2b-17d4-4cbf-938f-98ee46b3cd3f",
+                "created": "2024-01-01T00:00:00.000Z",
+                "modified": "2024-01-01T00:00:00.000Z",
+                "pattern": "[ipv4-addr:value = '10.0.0.1']",
+                "pattern_type": "stix",
+                "valid_from": "2024-01-01T00:00:00Z",
+                "toplevel_test_rank": 5,
+                "extensions": extensions
+            })
+        };
+
+        let referenced = indicator(serde_json::json!({
+            ext: {"extension_type": "toplevel-property-extension"}
+        }));
+        assert!(check_toplevel_properties(&referenced).is_ok());
+        let parsed = crate::parse(&referenced.to_string())?;
+        assert_eq!(
+            serde_json::to_value(&parsed)?["toplevel_test_rank"],
+            serde_json::json!(5)
+        );
+
+        // The property requires the extension to be referenced
+        let unreferenced = indicator(serde_json::json!({}));
+        assert!(check_toplevel_properties(&unreferenced).is_err());
+        assert!(crate::parse(&unreferenced.to_string()).is_err());
+
+        // ... as a toplevel-property-extension
+        let wrong_type = indicator(serde_json::json!({
+            ext: {"extension_type": "property-extension"}
+        }));
+        assert!(check_toplevel_properties(&wrong_type).is_err());
+
+        // Unregistered properties are not affected
+        let mut other = unreferenced;
+        if let Some(properties) = other.as_object_mut() {
+            properties.remove("toplevel_test_rank");
+        }
+        assert!(check_toplevel_properties(&other).is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_strict_context_rejects_misplaced_extension() {
+        use crate::validation::{ValidationContext, with_context};
+
+        let mut extensions = IndexMap::new();
+        extensions.insert("pdf-ext".to_string(), serde_json::json!({"version": "1.7"}));
+
+        assert!(validate_extensions("domain-name", &extensions).is_ok());
+        let strict = with_context(ValidationContext::new().strict_extensions(true), || {
+            validate_extensions("domain-name", &extensions)
+        });
+        assert!(strict.is_err());
+    }
 }
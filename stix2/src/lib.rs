@@ -47,6 +47,7 @@
 //! - [`observables`]: STIX Cyber Observable Objects (SCOs)
 //! - [`extensions`]: Observable and object extensions
 //! - [`markings`]: Data marking definitions
+//! - [`policy`]: Sharing policy helpers
 //! - [`patterns`]: STIX pattern language parser
 //! - [`datastore`]: DataStore abstractions
 //! - [`vocab`]: STIX vocabularies
@@ -76,6 +77,7 @@ pub mod objects;
 pub mod observables;
 pub mod pattern_equivalence;
 pub mod patterns;
+pub mod policy;
 pub mod registry;
 pub mod relationship;
 pub mod utils;
@@ -86,7 +88,9 @@ pub mod vocab;
 pub mod workbench;
 
 // Re-export commonly used types
-pub use crate::core::bundle::Bundle;
+pub use crate::core::bundle::{
+    Bundle, BundleWriter, FindingSeverity, NormalizeOptions, ValidationFinding,
+};
 pub use crate::core::error::{Error, Result};
 pub use crate::core::id::Identifier;
 pub use crate::core::stix_object::StixObject;
@@ -115,7 +119,7 @@ pub use crate::markings::{
 };
 
 // Re-export patterns
-pub use crate::patterns::{Pattern, PatternExpression};
+pub use crate::patterns::{Pattern, PatternExpression, PatternLimits};
 
 // Re-export datastore
 pub use crate::datastore::{
@@ -124,8 +128,9 @@ pub use crate::datastore::{
 
 // Re-export versioning
 pub use crate::versioning::{
-    UNMODIFIABLE_PROPERTIES, VersionBuilder, is_versionable, new_version, new_version_with_changes,
-    remove_custom_properties, revoke,
+    RevokeReport, SkippedRevocation, UNMODIFIABLE_PROPERTIES, VersionBuilder, is_versionable,
+    new_version, new_version_with_changes, remove_custom_properties, revoke, revoke_all,
+    revoke_all_with_report,
 };
 
 // Re-export equivalence
@@ -135,25 +140,30 @@ pub use crate::equivalence::{object_equivalence, object_similarity};
 pub use crate::graph::{StixGraph, graph_equivalence, graph_similarity, graphs_equivalent};
 
 // Re-export canonicalization
-pub use crate::canonicalization::{canonical_hash, canonicalize};
+pub use crate::canonicalization::{
+    CanonicalizationOptions, NumberFormat, canonical_hash, canonical_hash_with, canonicalize,
+    canonicalize_with,
+};
 
 // Re-export v20 compatibility
 pub use crate::v20::{StixVersion, detect_version, parse_any_version};
 
 // Re-export pattern equivalence
 pub use crate::pattern_equivalence::{
-    equivalent_patterns, find_equivalent_patterns, pattern_similarity,
+    equivalent_patterns, find_equivalent_patterns, pattern_similarity, simplify_pattern,
 };
 
 // Re-export registry
 pub use crate::registry::{
-    CustomTypeOptions, ObjectCategory, SpecVersion, class_for_type, get_sco_types, get_sdo_types,
-    get_sro_types, is_registered_type, register_custom_type,
+    CustomTypeOptions, ObjectCategory, SpecVersion, class_for_type, extension_owner, get_sco_types,
+    get_sdo_types, get_sro_types, is_registered_type, register_custom_type,
+    toplevel_property_extension, valid_extensions_for,
 };
 
 // Re-export custom object functions
 pub use crate::custom::{
-    register_custom_extension, register_custom_marking, register_custom_sco, register_custom_sdo,
+    register_custom_extension, register_custom_marking, register_custom_sco,
+    register_custom_sco_with_properties, register_custom_sdo, register_toplevel_property_extension,
     validate_custom_type_name, validate_extension_type_name,
 };
 
@@ -222,7 +232,10 @@ pub mod prelude {
     pub use chrono::{DateTime, Utc};
     pub use uuid::Uuid;
 
-    pub use crate::{parse, parse_bundle};
+    pub use crate::{
+        parse, parse_bundle, parse_bundle_reader, parse_bundle_reader_with_limit,
+        parse_bundle_with_limit, parse_reader,
+    };
 }
 
 /// Parse a STIX JSON string into a StixObject
@@ -260,6 +273,69 @@ pub fn parse_bundle(json: &str) -> Result<Bundle> {
     serde_json::from_str(json).map_err(Error::from)
 }
 
+/// Parse a STIX object from a reader
+///
+/// The JSON is deserialized as it is read, so the input does not have to be
+/// loaded into a `String` first. The reader is buffered internally.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use stix2::parse_reader;
+///
+/// let obj = parse_reader(std::fs::File::open("indicator.json")?)?;
+/// ```
+pub fn parse_reader<R: std::io::Read>(reader: R) -> Result<StixObject> {
+    serde_json::from_reader(std::io::BufReader::new(reader)).map_err(Error::from)
+}
+
+/// Parse a STIX Bundle from a reader
+///
+/// Like [`parse_reader`], this avoids buffering the whole bundle as a
+/// string, which reduces peak memory for large files.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use stix2::parse_bundle_reader;
+///
+/// let bundle = parse_bundle_reader(std::fs::File::open("bundle.json")?)?;
+/// ```
+pub fn parse_bundle_reader<R: std::io::Read>(reader: R) -> Result<Bundle> {
+    serde_json::from_reader(std::io::BufReader::new(reader)).map_err(Error::from)
+}
+
+/// Parse a STIX Bundle JSON string holding at most `max_objects` objects
+///
+/// Objects are counted while parsing, so a bundle claiming more objects is
+/// refused with [`Error::TooManyObjects`] without deserializing the objects
+/// past the limit.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use stix2::parse_bundle_with_limit;
+///
+/// let bundle = parse_bundle_with_limit(json, 10_000)?;
+/// ```
+pub fn parse_bundle_with_limit(json: &str, max_objects: usize) -> Result<Bundle> {
+    crate::core::bundle::from_json_bounded(serde_json::de::StrRead::new(json), max_objects)
+}
+
+/// Parse a STIX Bundle from a reader, holding at most `max_objects` objects
+///
+/// The streaming counterpart of [`parse_bundle_with_limit`]: nothing after
+/// the first object past the limit is read.
+pub fn parse_bundle_reader_with_limit<R: std::io::Read>(
+    reader: R,
+    max_objects: usize,
+) -> Result<Bundle> {
+    crate::core::bundle::from_json_bounded(
+        serde_json::de::IoRead::new(std::io::BufReader::new(reader)),
+        max_objects,
+    )
+}
+
 /// Serialize a STIX object to JSON string
 ///
 /// # Arguments
@@ -288,4 +364,86 @@ mod tests {
         let _: fn() -> Bundle = Bundle::new;
         let _: fn(&str) -> Result<Identifier> = Identifier::new;
     }
+
+    const BUNDLE_JSON: &str = r#"{
+        "type": "bundle",
+        "id": "bundle--5d0092c5-5f74-4287-9642-33f4c354e56d",
+        "objects": [
+            {
+                "type": "indicator",
+                "spec_version": "2.1",
+                "id": "indicator--8e2e2d2b-17d4-4cbf-938f-98ee46b3cd3f",
+                "created": "2016-04-06T20:03:48.000Z",
+                "modified": "2016-04-06T20:03:48.000Z",
+                "name": "Poison Ivy Malware",
+                "pattern": "[file:hashes.'SHA-256' = 'ef537f25c895bfa782526529a9b63d97aa631564d5d789c2b765448c8635fb6c']",
+                "pattern_type": "stix",
+                "valid_from": "2016-01-01T00:00:00Z"
+            },
+            {
+                "type": "ipv4-addr",
+                "spec_version": "2.1",
+                "id": "ipv4-addr--ff26c055-6336-5bc5-b98d-13d6226742dd",
+                "value": "198.51.100.3"
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn test_parse_bundle_reader() {
+        let bundle = parse_bundle_reader(std::io::Cursor::new(BUNDLE_JSON.as_bytes())).unwrap();
+
+        assert_eq!(bundle, parse_bundle(BUNDLE_JSON).unwrap());
+        assert_eq!(bundle.objects.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_bundle_with_limit() {
+        let bundle = parse_bundle_with_limit(BUNDLE_JSON, 2).unwrap();
+        assert_eq!(bundle, parse_bundle(BUNDLE_JSON).unwrap());
+
+        let reader = std::io::Cursor::new(BUNDLE_JSON.as_bytes());
+        assert_eq!(parse_bundle_reader_with_limit(reader, 2).unwrap(), bundle);
+
+        // Malformed input is still reported as such
+        let result = parse_bundle_with_limit(r#"{"type": "bundle"}"#, 2);
+        assert!(matches!(result, Err(Error::Json(_))));
+    }
+
+    #[test]
+    fn test_parse_bundle_over_limit() {
+        // Objects past the limit are never deserialized: the third object
+        // is not valid STIX, yet the count is what gets reported
+        let json = r#"{
+            "type": "bundle",
+            "id": "bundle--5d0092c5-5f74-4287-9642-33f4c354e56d",
+            "objects": [
+                {"type": "ipv4-addr", "spec_version": "2.1", "id": "ipv4-addr--ff26c055-6336-5bc5-b98d-13d6226742dd", "value": "198.51.100.3"},
+                {"type": "ipv4-addr", "spec_version": "2.1", "id": "ipv4-addr--ff26c055-6336-5bc5-b98d-13d6226742de", "value": "198.51.100.4"},
+                {"type": "indicator", "id": "not-an-id"}
+            ]
+        }"#;
+
+        let result = parse_bundle_with_limit(json, 2);
+        assert!(matches!(result, Err(Error::TooManyObjects(2))));
+
+        let result = parse_bundle_reader_with_limit(std::io::Cursor::new(json.as_bytes()), 2);
+        assert!(matches!(result, Err(Error::TooManyObjects(2))));
+
+        assert!(matches!(parse_bundle(json), Err(Error::Json(_))));
+    }
+
+    #[test]
+    fn test_parse_reader() {
+        let json = r#"{"type": "ipv4-addr", "spec_version": "2.1", "id": "ipv4-addr--ff26c055-6336-5bc5-b98d-13d6226742dd", "value": "198.51.100.3"}"#;
+        let obj = parse_reader(json.as_bytes()).unwrap();
+
+        assert_eq!(obj.type_name(), "ipv4-addr");
+    }
+
+    #[test]
+    fn test_parse_reader_invalid_json() {
+        let result = parse_bundle_reader(std::io::Cursor::new(b"{\"type\": \"bundle\"".to_vec()));
+        assert!(matches!(result, Err(Error::Json(_))));
+    }
 }
@@ -80,6 +80,31 @@ impl TlpLevel {
         };
         Identifier::marking_definition(uuid)
     }
+
+    /// Get the TLP level of a standard TLP marking definition ID.
+    pub fn from_marking_definition_id(id: &Identifier) -> Option<Self> {
+        [
+            TlpLevel::Clear,
+            TlpLevel::White,
+            TlpLevel::Green,
+            TlpLevel::Amber,
+            TlpLevel::AmberStrict,
+            TlpLevel::Red,
+        ]
+        .into_iter()
+        .find(|level| level.marking_definition_id() == *id)
+    }
+
+    /// Get how restrictive the level is, from 0 (CLEAR or WHITE) to 4 (RED).
+    pub fn rank(&self) -> u8 {
+        match self {
+            TlpLevel::Clear | TlpLevel::White => 0,
+            TlpLevel::Green => 1,
+            TlpLevel::Amber => 2,
+            TlpLevel::AmberStrict => 3,
+            TlpLevel::Red => 4,
+        }
+    }
 }
 
 /// TLP Marking definition type.
@@ -3,12 +3,14 @@
 //! This module provides functions for manipulating object-level and granular markings
 //! on STIX objects.
 
-use super::GranularMarking;
+use super::{GranularMarking, MarkingType, TlpLevel};
+use crate::core::bundle::Bundle;
 use crate::core::error::{Error, Result};
 use crate::core::id::Identifier;
+use crate::core::stix_object::StixObject;
 use indexmap::IndexMap;
 use serde_json::Value;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 // ============================================================================
 // Object Marking Operations
@@ -396,6 +398,171 @@ pub fn validate_selector(obj: &Value, selector: &str) -> Result<()> {
     Ok(())
 }
 
+// ============================================================================
+// Sharing Operations
+// ============================================================================
+
+/// Reference properties an object is invalid without, by object type.
+const REQUIRED_REFS: &[(&str, &str)] = &[
+    ("relationship", "source_ref"),
+    ("relationship", "target_ref"),
+    ("sighting", "sighting_of_ref"),
+    ("language-content", "object_ref"),
+    ("report", "object_refs"),
+    ("grouping", "object_refs"),
+    ("note", "object_refs"),
+    ("opinion", "object_refs"),
+    ("observed-data", "object_refs"),
+];
+
+/// Produce the view of a bundle that can be shared at a TLP ceiling.
+///
+/// Objects marked (at the object level or granularly) with a TLP level
+/// more restrictive than `ceiling` are dropped, as are the TLP marking
+/// definitions above it. References to dropped objects are pruned from
+/// the remaining objects; objects that cannot exist without them (e.g. a
+/// relationship whose target was dropped, or a report left without
+/// `object_refs`) are dropped too.
+///
+/// TLP levels are taken from the standard TLP marking definitions and from
+/// TLP marking definitions in the bundle. Returns the sanitized bundle and
+/// the IDs of the dropped objects, in bundle order.
+pub fn sanitize_for_tlp(bundle: &Bundle, ceiling: TlpLevel) -> (Bundle, Vec<Identifier>) {
+    let mut levels: HashMap<String, TlpLevel> = HashMap::new();
+    for object in bundle.iter() {
+        if let StixObject::MarkingDefinition(definition) = object
+            && let MarkingType::Tlp(tlp) = &definition.marking_type
+        {
+            levels.insert(definition.id.to_string(), tlp.tlp);
+        }
+    }
+    let exceeds = |marking_ref: &str| {
+        let level = levels.get(marking_ref).copied().or_else(|| {
+            marking_ref
+                .parse()
+                .ok()
+                .and_then(|id| TlpLevel::from_marking_definition_id(&id))
+        });
+        level.is_some_and(|level| level.rank() > ceiling.rank())
+    };
+
+    let mut values = Vec::new();
+    let mut dropped: HashSet<String> = HashSet::new();
+    for object in bundle.iter() {
+        let id = object.id().to_string();
+        let Ok(value) = serde_json::to_value(object) else {
+            dropped.insert(id);
+            continue;
+        };
+        if object.is_marking_definition() {
+            if exceeds(&id) {
+                continue;
+            }
+        } else if marking_refs(&value).any(exceeds) {
+            dropped.insert(id.clone());
+        }
+        values.push((id, value));
+    }
+
+    // Pruning can invalidate objects, whose references are pruned in turn
+    loop {
+        let mut invalidated = Vec::new();
+        for (id, value) in values.iter_mut() {
+            if dropped.contains(id.as_str()) {
+                continue;
+            }
+            prune_refs(value, &dropped);
+            let type_name = value.get("type").and_then(Value::as_str).unwrap_or("");
+            let missing = REQUIRED_REFS
+                .iter()
+                .any(|(t, property)| *t == type_name && value.get(*property).is_none());
+            if missing {
+                invalidated.push(id.clone());
+            }
+        }
+        if invalidated.is_empty() {
+            break;
+        }
+        dropped.extend(invalidated);
+    }
+
+    let mut objects = Vec::new();
+    for (id, value) in values {
+        if dropped.contains(&id) {
+            continue;
+        }
+        match serde_json::from_value::<StixObject>(value) {
+            Ok(object) => objects.push(object),
+            // Invalid without a pruned reference
+            Err(_) => {
+                dropped.insert(id);
+            }
+        }
+    }
+
+    let dropped_ids = bundle
+        .iter()
+        .filter(|object| dropped.contains(&object.id().to_string()))
+        .map(|object| object.id().clone())
+        .collect();
+    let sanitized = Bundle {
+        type_: bundle.type_.clone(),
+        id: bundle.id.clone(),
+        objects,
+    };
+
+    (sanitized, dropped_ids)
+}
+
+/// Marking definition references of an object, object-level and granular.
+fn marking_refs(value: &Value) -> impl Iterator<Item = &str> {
+    let object_level = value
+        .get("object_marking_refs")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str);
+    let granular = value
+        .get("granular_markings")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|marking| marking.get("marking_ref").and_then(Value::as_str));
+    object_level.chain(granular)
+}
+
+/// Remove references to `dropped` objects from `_ref` and `_refs`
+/// properties, at any depth.
+///
+/// Lists left empty are removed.
+fn prune_refs(value: &mut Value, dropped: &HashSet<String>) {
+    match value {
+        Value::Object(map) => {
+            map.retain(|key, value| {
+                if key.ends_with("_ref") {
+                    return !value.as_str().is_some_and(|id| dropped.contains(id));
+                }
+                if key.ends_with("_refs")
+                    && let Value::Array(refs) = value
+                {
+                    refs.retain(|r| !r.as_str().is_some_and(|id| dropped.contains(id)));
+                    return !refs.is_empty();
+                }
+                true
+            });
+            for value in map.values_mut() {
+                prune_refs(value, dropped);
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                prune_refs(item, dropped);
+            }
+        }
+        _ => {}
+    }
+}
+
 /// Walk an object tree yielding (path, value) tuples.
 pub fn iter_path(obj: &Value) -> Vec<(String, &Value)> {
     let mut result = Vec::new();
@@ -597,4 +764,143 @@ mod tests {
         assert!(path_strings.contains(&"labels[0]"));
         assert!(path_strings.contains(&"labels[1]"));
     }
+    #[test]
+    fn test_sanitize_for_tlp() {
+        let green = TlpLevel::Green.marking_definition_id().to_string();
+        let amber = TlpLevel::Amber.marking_definition_id().to_string();
+        let red = TlpLevel::Red.marking_definition_id().to_string();
+        let sdo = |id: &str, marking: &str, extra: Value| {
+            let mut value = serde_json::json!({
+                "type": id.split("--").next().unwrap(),
+                "spec_version": "2.1",
+                "id": id,
+                "created": "2024-01-01T00:00:00.000Z",
+                "modified": "2024-01-01T00:00:00.000Z",
+                "object_marking_refs": [marking]
+            });
+            for (key, v) in extra.as_object().unwrap() {
+                value[key] = v.clone();
+            }
+            value
+        };
+
+        let identity = "identity--11111111-1111-4111-8111-111111111111";
+        let shared = "indicator--22222222-2222-4222-8222-222222222222";
+        let restricted = "indicator--33333333-3333-4333-8333-333333333333";
+        let amber_malware = "malware--44444444-4444-4444-8444-444444444444";
+        let indicator = serde_json::json!({
+            "pattern": "[ipv4-addr:value = '198.51.100.1']",
+            "pattern_type": "stix",
+            "valid_from": "2024-01-01T00:00:00Z",
+            "created_by_ref": identity
+        });
+        let objects = vec![
+            sdo(identity, &green, serde_json::json!({"name": "Producer"})),
+            sdo(shared, &green, indicator.clone()),
+            sdo(restricted, &red, indicator),
+            sdo(
+                amber_malware,
+                &amber,
+                serde_json::json!({"name": "Emotet", "is_family": true}),
+            ),
+            // Relationship to a RED object cannot be shared
+            sdo(
+                "relationship--55555555-5555-4555-8555-555555555555",
+                &green,
+                serde_json::json!({
+                    "relationship_type": "indicates",
+                    "source_ref": restricted,
+                    "target_ref": amber_malware
+                }),
+            ),
+            // Report keeps its shareable references
+            sdo(
+                "report--66666666-6666-4666-8666-666666666666",
+                &green,
+                serde_json::json!({
+                    "name": "Campaign",
+                    "published": "2024-01-01T00:00:00Z",
+                    "object_refs": [shared, restricted, amber_malware]
+                }),
+            ),
+            // Note about AMBER content only
+            sdo(
+                "note--77777777-7777-4777-8777-777777777777",
+                &green,
+                serde_json::json!({"content": "Seen", "object_refs": [amber_malware]}),
+            ),
+            // GREEN object with an AMBER property
+            sdo(
+                "identity--88888888-8888-4888-8888-888888888888",
+                &green,
+                serde_json::json!({
+                    "name": "Victim",
+                    "granular_markings": [{"marking_ref": amber, "selectors": ["name"]}]
+                }),
+            ),
+            serde_json::json!({
+                "type": "marking-definition",
+                "spec_version": "2.1",
+                "id": red,
+                "created": "2017-01-20T00:00:00.000Z",
+                "definition_type": "tlp",
+                "name": "TLP:RED",
+                "definition": {"tlp": "red"}
+            }),
+        ];
+        let bundle: Bundle = serde_json::from_value(serde_json::json!({
+            "type": "bundle",
+            "id": "bundle--99999999-9999-4999-8999-999999999999",
+            "objects": objects
+        }))
+        .unwrap();
+
+        let (sanitized, dropped) = sanitize_for_tlp(&bundle, TlpLevel::Green);
+
+        let dropped: Vec<String> = dropped.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            dropped,
+            vec![
+                restricted,
+                amber_malware,
+                "relationship--55555555-5555-4555-8555-555555555555",
+                "note--77777777-7777-4777-8777-777777777777",
+                "identity--88888888-8888-4888-8888-888888888888",
+            ]
+        );
+        let kept: Vec<String> = sanitized.iter().map(|o| o.id().to_string()).collect();
+        assert_eq!(
+            kept,
+            vec![
+                identity,
+                shared,
+                "report--66666666-6666-4666-8666-666666666666"
+            ]
+        );
+        assert_eq!(sanitized.id, bundle.id);
+
+        let report: Identifier = "report--66666666-6666-4666-8666-666666666666"
+            .parse()
+            .unwrap();
+        match sanitized.find_by_id(&report) {
+            Some(StixObject::Report(report)) => {
+                let refs: Vec<String> =
+                    report.object_refs.iter().map(ToString::to_string).collect();
+                assert_eq!(refs, vec![shared]);
+            }
+            other => panic!("expected the report, got {other:?}"),
+        }
+
+        // An AMBER ceiling keeps the AMBER objects but not the RED ones
+        let (sanitized, dropped) = sanitize_for_tlp(&bundle, TlpLevel::Amber);
+        let dropped: Vec<String> = dropped.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            dropped,
+            vec![
+                restricted,
+                "relationship--55555555-5555-4555-8555-555555555555"
+            ]
+        );
+        assert_eq!(sanitized.len(), 6);
+    }
 }
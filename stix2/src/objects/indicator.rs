@@ -9,7 +9,7 @@ use crate::core::id::Identifier;
 use crate::core::kill_chain_phase::KillChainPhase;
 use crate::core::timestamp::Timestamp;
 use crate::impl_sdo_traits;
-use crate::patterns::parse_pattern;
+use crate::patterns::{PatternLimits, check_pattern_length, check_pattern_limits, parse_pattern};
 use crate::validation::{Constrained, check_timestamp_order_strict};
 use crate::vocab::{IndicatorType, PatternType};
 use serde::{Deserialize, Serialize};
@@ -74,6 +74,18 @@ impl Indicator {
     pub fn builder() -> IndicatorBuilder {
         IndicatorBuilder::new()
     }
+
+    /// Check the pattern against size limits.
+    ///
+    /// The length limit applies to patterns of any type, the clause limit
+    /// to STIX patterns only.
+    pub fn check_pattern_limits(&self, limits: &PatternLimits) -> Result<()> {
+        if self.pattern_type == PatternType::Stix {
+            check_pattern_limits(&self.pattern, limits)
+        } else {
+            check_pattern_length(&self.pattern, limits)
+        }
+    }
 }
 
 impl_sdo_traits!(Indicator, "indicator");
@@ -48,6 +48,51 @@ impl Location {
     pub fn builder() -> LocationBuilder {
         LocationBuilder::new()
     }
+
+    /// Get warnings for properties that do not follow the spec's
+    /// recommendations.
+    ///
+    /// Unlike [`validate_constraints`](Constrained::validate_constraints)
+    /// violations, these do not make the object invalid:
+    ///
+    /// - `administrative_area` should be an ISO 3166-2 code (e.g. `US-CA`)
+    /// - the `administrative_area` code should be within `country`
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+
+        if let Some(area) = &self.administrative_area {
+            if !is_iso3166_2(area) {
+                warnings.push(format!(
+                    "administrative_area '{area}' is not an ISO 3166-2 code"
+                ));
+            } else if let Some(country) = &self.country
+                && !area
+                    .get(..2)
+                    .is_some_and(|prefix| prefix.eq_ignore_ascii_case(country))
+            {
+                warnings.push(format!(
+                    "administrative_area '{area}' is not within country '{country}'"
+                ));
+            }
+        }
+
+        warnings
+    }
+}
+
+/// Whether a value has the ISO 3166-2 subdivision code format: an
+/// ISO 3166-1 alpha-2 country code, a hyphen and 1 to 3 letters or digits.
+fn is_iso3166_2(value: &str) -> bool {
+    let Some((country, subdivision)) = value.split_once('-') else {
+        return false;
+    };
+
+    country.len() == 2
+        && country.chars().all(|c| c.is_ascii_uppercase())
+        && (1..=3).contains(&subdivision.len())
+        && subdivision
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
 }
 
 impl_sdo_traits!(Location, "location");
@@ -60,6 +105,8 @@ impl Constrained for Location {
     /// - `longitude` must be between -180 and 180
     /// - `precision` must be >= 0
     /// - `latitude` and `longitude` must both be present or both absent
+    ///
+    /// Also enforced when parsing a Location.
     fn validate_constraints(&self) -> Result<()> {
         // Validate latitude range (-90 to 90)
         if let Some(lat) = self.latitude
@@ -256,6 +303,72 @@ mod tests {
         assert_eq!(loc.country, Some("RU".to_string()));
     }
 
+    #[test]
+    fn test_location_precision() {
+        let loc = Location::builder()
+            .coordinates(55.7558, 37.6173)
+            .precision(1000.0)
+            .build()
+            .unwrap();
+        assert_eq!(loc.precision, Some(1000.0));
+
+        let negative = Location::builder()
+            .coordinates(55.7558, 37.6173)
+            .precision(-1.0)
+            .build();
+        assert!(negative.is_err());
+    }
+
+    #[test]
+    fn test_location_precision_requires_coordinates() {
+        let result = Location::builder().country("RU").precision(1000.0).build();
+        assert!(matches!(result, Err(Error::PropertyDependency { .. })));
+
+        // Also enforced when parsing
+        let json = r#"{
+            "type": "location",
+            "spec_version": "2.1",
+            "id": "location--a6e9345f-5a15-4c29-8bb3-7dcc5d168d64",
+            "created": "2024-01-01T00:00:00.000Z",
+            "modified": "2024-01-01T00:00:00.000Z",
+            "country": "RU",
+            "precision": 1000.0
+        }"#;
+        assert!(crate::parse(json).is_err());
+    }
+
+    #[test]
+    fn test_location_administrative_area_warnings() {
+        let valid = Location::builder()
+            .country("US")
+            .administrative_area("US-CA")
+            .build()
+            .unwrap();
+        assert!(valid.warnings().is_empty());
+
+        // Warnings do not make the object invalid
+        let free_text = Location::builder()
+            .country("US")
+            .administrative_area("California")
+            .build()
+            .unwrap();
+        assert_eq!(free_text.warnings().len(), 1);
+
+        let elsewhere = Location::builder()
+            .country("FR")
+            .administrative_area("US-CA")
+            .build()
+            .unwrap();
+        assert_eq!(elsewhere.warnings().len(), 1);
+
+        for code in ["GB-ENG", "FR-75", "CN-BJ"] {
+            assert!(is_iso3166_2(code), "{code}");
+        }
+        for code in ["us-ca", "USA-CA", "US-", "US-CALI", "US_CA", "US"] {
+            assert!(!is_iso3166_2(code), "{code}");
+        }
+    }
+
     #[test]
     fn test_location_with_coordinates() {
         let loc = Location::builder()
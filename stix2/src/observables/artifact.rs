@@ -116,6 +116,8 @@ impl Constrained for Artifact {
             });
         }
 
+        crate::extensions::validate_extensions(Self::TYPE, &self.extensions)?;
+
         Ok(())
     }
 }
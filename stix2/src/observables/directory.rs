@@ -82,6 +82,8 @@ impl crate::validation::Constrained for Directory {
 
         check_refs_type(&self.contains_refs, "contains_refs", &["file", "directory"])?;
 
+        crate::extensions::validate_extensions(Self::TYPE, &self.extensions)?;
+
         Ok(())
     }
 }
@@ -151,6 +151,8 @@ impl crate::validation::Constrained for DomainName {
             Self::VALID_RESOLVES_TO_TYPES,
         )?;
 
+        crate::extensions::validate_extensions(Self::TYPE, &self.extensions)?;
+
         Ok(())
     }
 }
@@ -76,6 +76,8 @@ impl crate::validation::Constrained for EmailAddress {
             &["user-account"],
         )?;
 
+        crate::extensions::validate_extensions(Self::TYPE, &self.extensions)?;
+
         Ok(())
     }
 }
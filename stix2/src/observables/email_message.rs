@@ -138,6 +138,8 @@ impl Constrained for EmailMessage {
         check_refs_type(&self.bcc_refs, "bcc_refs", &["email-addr"])?;
         check_optional_ref_type(self.raw_email_ref.as_ref(), "raw_email_ref", &["artifact"])?;
 
+        crate::extensions::validate_extensions(Self::TYPE, &self.extensions)?;
+
         Ok(())
     }
 }
@@ -84,6 +84,7 @@ impl Constrained for File {
     /// Validate File constraints.
     ///
     /// - At least one of `hashes` or `name` must be present
+    /// - Extensions must match their keys and be valid for the type
     fn validate_constraints(&self) -> Result<()> {
         use crate::validation::check_optional_ref_type;
 
@@ -104,6 +105,8 @@ impl Constrained for File {
         check_optional_ref_type(self.content_ref.as_ref(), "content_ref", &["artifact"])?;
         // contains_refs can be any SCO type per the spec (embedded files)
 
+        crate::extensions::validate_extensions(Self::TYPE, &self.extensions)?;
+
         Ok(())
     }
 }
@@ -241,6 +244,22 @@ mod tests {
         assert_eq!(file.name, Some("malware.exe".to_string()));
     }
 
+    #[test]
+    fn test_extensions_validated() {
+        let mut file = File::builder().name("bundle.zip").build().unwrap();
+        file.extensions.insert(
+            "archive-ext".to_string(),
+            serde_json::json!({"contains_refs": ["file--019fde1c-94ab-4b1a-9f34-fbb2d2e1da1a"]}),
+        );
+        assert!(file.validate_constraints().is_ok());
+
+        file.extensions.insert(
+            "pdf-ext".to_string(),
+            serde_json::json!({"contains_refs": ["file--019fde1c-94ab-4b1a-9f34-fbb2d2e1da1a"]}),
+        );
+        assert!(file.validate_constraints().is_err());
+    }
+
     #[test]
     fn test_serialization() {
         let file = File::builder().name("test.txt").build().unwrap();
@@ -177,6 +177,8 @@ impl crate::validation::Constrained for IPv4Address {
             &["autonomous-system"],
         )?;
 
+        crate::extensions::validate_extensions(Self::TYPE, &self.extensions)?;
+
         Ok(())
     }
 }
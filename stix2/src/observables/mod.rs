@@ -45,5 +45,5 @@ pub use process::Process;
 pub use software::Software;
 pub use url::Url;
 pub use user_account::UserAccount;
-pub use windows_registry_key::WindowsRegistryKey;
+pub use windows_registry_key::{WindowsRegistryKey, expand_registry_hive};
 pub use x509_certificate::X509Certificate;
@@ -122,6 +122,7 @@ impl Constrained for NetworkTraffic {
     /// - At least one of `src_ref` or `dst_ref` must be present
     /// - If both `start` and `end` are present, `end` must be >= `start`
     /// - If `end` is present, `is_active` must be false
+    /// - Extensions must match their keys and be valid for the type
     fn validate_constraints(&self) -> Result<()> {
         use crate::validation::{check_optional_ref_type, check_refs_type};
 
@@ -169,6 +170,8 @@ impl Constrained for NetworkTraffic {
             &["network-traffic"],
         )?;
 
+        crate::extensions::validate_extensions(Self::TYPE, &self.extensions)?;
+
         Ok(())
     }
 }
@@ -95,6 +95,7 @@ impl Constrained for Process {
     ///
     /// - At least one property (besides type, id, spec_version, defanged, extensions) must be present
     /// - pid must be non-negative if present
+    /// - Extensions must match their keys and be valid for the type
     fn validate_constraints(&self) -> Result<()> {
         use crate::validation::{check_non_negative, check_optional_ref_type, check_refs_type};
 
@@ -147,6 +148,8 @@ impl Constrained for Process {
         check_optional_ref_type(self.parent_ref.as_ref(), "parent_ref", &["process"])?;
         check_refs_type(&self.child_refs, "child_refs", &["process"])?;
 
+        crate::extensions::validate_extensions(Self::TYPE, &self.extensions)?;
+
         Ok(())
     }
 }
@@ -5,6 +5,7 @@ use crate::core::id::Identifier;
 use crate::core::timestamp::Timestamp;
 use crate::impl_sco_traits;
 use crate::markings::GranularMarking;
+use crate::validation::Constrained;
 use crate::vocab::AccountType;
 use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
@@ -98,3 +99,12 @@ impl crate::observables::IdContributing for UserAccount {
     const ID_CONTRIBUTING_PROPERTIES: &'static [&'static str] =
         &["account_type", "user_id", "account_login"];
 }
+
+impl Constrained for UserAccount {
+    /// Validate UserAccount constraints.
+    ///
+    /// - Extensions must match their keys and be valid for the type
+    fn validate_constraints(&self) -> Result<()> {
+        crate::extensions::validate_extensions(Self::TYPE, &self.extensions)
+    }
+}
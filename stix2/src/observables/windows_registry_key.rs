@@ -1,5 +1,6 @@
 //! Windows Registry Key SCO
 
+use super::common::generate_sco_id;
 use crate::core::error::Result;
 use crate::core::id::Identifier;
 use crate::core::timestamp::Timestamp;
@@ -8,7 +9,49 @@ use crate::markings::GranularMarking;
 use crate::vocab::WindowsRegistryDatatype;
 use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{Value, json};
+
+/// Registry hive abbreviations and their full names.
+const HIVE_ABBREVIATIONS: &[(&str, &str)] = &[
+    ("HKCR", "HKEY_CLASSES_ROOT"),
+    ("HKCU", "HKEY_CURRENT_USER"),
+    ("HKLM", "HKEY_LOCAL_MACHINE"),
+    ("HKU", "HKEY_USERS"),
+    ("HKCC", "HKEY_CURRENT_CONFIG"),
+    ("HKPD", "HKEY_PERFORMANCE_DATA"),
+    ("HKDD", "HKEY_DYN_DATA"),
+];
+
+/// Expand the hive of a registry key to its full, upper-case name.
+///
+/// STIX 2.1 requires the hive to be fully expanded (`HKEY_LOCAL_MACHINE`
+/// rather than `HKLM`). The rest of the key is left as is. Keys that do not
+/// start with a known hive are returned unchanged.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// assert_eq!(expand_registry_hive("hklm\\Software"), "HKEY_LOCAL_MACHINE\\Software");
+/// ```
+pub fn expand_registry_hive(key: &str) -> String {
+    let (hive, rest) = match key.split_once('\\') {
+        Some((hive, rest)) => (hive, Some(rest)),
+        None => (key, None),
+    };
+
+    let expanded = HIVE_ABBREVIATIONS
+        .iter()
+        .find(|(abbreviation, full)| {
+            hive.eq_ignore_ascii_case(abbreviation) || hive.eq_ignore_ascii_case(full)
+        })
+        .map(|(_, full)| *full);
+
+    match (expanded, rest) {
+        (Some(full), Some(rest)) => format!("{full}\\{rest}"),
+        (Some(full), None) => full.to_string(),
+        (None, _) => key.to_string(),
+    }
+}
 
 /// Windows Registry Value Type.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -60,13 +103,22 @@ fn default_spec_version() -> String {
 impl WindowsRegistryKey {
     pub const TYPE: &'static str = "windows-registry-key";
 
+    /// Create a new Windows Registry Key with a deterministic ID.
+    ///
+    /// The hive of `key` is expanded (see [`expand_registry_hive`]). The ID
+    /// is generated from the case-folded key, so the same key reported as
+    /// `HKLM\Software\Foo` and `HKEY_LOCAL_MACHINE\SOFTWARE\foo` by
+    /// different sources gets the same ID.
     pub fn new(key: impl Into<String>) -> Result<Self> {
+        let key = expand_registry_hive(&key.into());
+        let id = Self::id_for_key(&key)?;
+
         Ok(Self {
             type_: Self::TYPE.to_string(),
-            id: Identifier::new(Self::TYPE)?,
+            id,
             spec_version: default_spec_version(),
             defanged: false,
-            key: Some(key.into()),
+            key: Some(key),
             values: Vec::new(),
             modified_time: None,
             creator_user_ref: None,
@@ -76,6 +128,16 @@ impl WindowsRegistryKey {
             extensions: IndexMap::new(),
         })
     }
+
+    /// Generate the deterministic ID for a registry key.
+    fn id_for_key(key: &str) -> Result<Identifier> {
+        use crate::pattern_equivalence::specials::canonicalize_windows_registry_key;
+
+        generate_sco_id(
+            Self::TYPE,
+            &json!({"key": canonicalize_windows_registry_key(key)}),
+        )
+    }
 }
 
 impl_sco_traits!(WindowsRegistryKey, "windows-registry-key");
@@ -96,6 +158,50 @@ impl crate::validation::Constrained for WindowsRegistryKey {
             &["user-account"],
         )?;
 
+        crate::extensions::validate_extensions(Self::TYPE, &self.extensions)?;
+
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_registry_hive() {
+        assert_eq!(
+            expand_registry_hive("HKLM\\Software\\Microsoft"),
+            "HKEY_LOCAL_MACHINE\\Software\\Microsoft"
+        );
+        assert_eq!(
+            expand_registry_hive("hkcu\\Environment"),
+            "HKEY_CURRENT_USER\\Environment"
+        );
+        assert_eq!(
+            expand_registry_hive("hkey_users\\.DEFAULT"),
+            "HKEY_USERS\\.DEFAULT"
+        );
+        assert_eq!(expand_registry_hive("HKCR"), "HKEY_CLASSES_ROOT");
+        assert_eq!(expand_registry_hive("Software\\Foo"), "Software\\Foo");
+    }
+
+    #[test]
+    fn test_new_expands_hive() {
+        let key = WindowsRegistryKey::new("HKLM\\Software\\Run").unwrap();
+        assert_eq!(
+            key.key.as_deref(),
+            Some("HKEY_LOCAL_MACHINE\\Software\\Run")
+        );
+    }
+
+    #[test]
+    fn test_id_ignores_hive_form_and_case() {
+        let short = WindowsRegistryKey::new("HKLM\\Software\\Foo").unwrap();
+        let long = WindowsRegistryKey::new("HKEY_LOCAL_MACHINE\\SOFTWARE\\foo").unwrap();
+        let other = WindowsRegistryKey::new("HKCU\\Software\\Foo").unwrap();
+
+        assert_eq!(short.id, long.id);
+        assert_ne!(short.id, other.id);
+    }
+}
@@ -137,6 +137,8 @@ impl Constrained for X509Certificate {
             ]));
         }
 
+        crate::extensions::validate_extensions(Self::TYPE, &self.extensions)?;
+
         Ok(())
     }
 }
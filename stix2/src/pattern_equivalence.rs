@@ -10,20 +10,27 @@
 //! 3. Converting to DNF (Disjunctive Normal Form)
 //! 4. Applying special value canonicalization (IPv4/IPv6 CIDR, Windows registry)
 //! 5. Comparing the normalized forms
+//!
+//! The same flattening and absorption rules simplify patterns with
+//! [`simplify`], removing repeated and absorbed clauses. [`canonical_form`]
+//! writes the normalized form out as a string, for indexing patterns by
+//! equivalence.
 
 use std::cmp::Ordering;
-use std::collections::BTreeSet;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::{self, Write as _};
 
 use crate::core::error::Result;
 use crate::patterns::{
-    ComparisonExpression, ComparisonOperator, PatternExpression, PatternValue, parse_pattern,
+    ComparisonExpression, ComparisonOperator, PatternExpression, PatternValue, Qualifier,
+    parse_pattern,
 };
 
 // ============================================================================
 // Special Value Canonicalization
 // ============================================================================
 
-mod specials {
+pub(crate) mod specials {
     /// Canonicalize an IPv4 address value.
     ///
     /// Normalizes CIDR notation by applying the mask to the address.
@@ -71,9 +78,10 @@ mod specials {
 
     /// Canonicalize a Windows registry key value.
     ///
-    /// Lowercases the key for case-insensitive comparison.
+    /// Expands an abbreviated hive (e.g. `HKLM`) and lowercases the key for
+    /// case-insensitive comparison.
     pub fn canonicalize_windows_registry_key(value: &str) -> String {
-        value.to_lowercase()
+        crate::observables::expand_registry_hive(value).to_lowercase()
     }
 
     #[cfg(test)]
@@ -99,6 +107,10 @@ mod specials {
                 canonicalize_windows_registry_key("HKEY_LOCAL_MACHINE\\SOFTWARE"),
                 "hkey_local_machine\\software"
             );
+            assert_eq!(
+                canonicalize_windows_registry_key("HKLM\\Software"),
+                "hkey_local_machine\\software"
+            );
         }
     }
 }
@@ -345,6 +357,163 @@ impl PatternStructure {
     fn normalize(self) -> Self {
         self.flatten().into_dnf().settle()
     }
+
+    /// Remove repeated operands of AND and OR, keeping the first.
+    ///
+    /// A AND A -> A
+    /// A OR A -> A
+    fn dedup(self) -> Self {
+        fn unique(children: Vec<PatternStructure>) -> Vec<PatternStructure> {
+            let mut kept: Vec<PatternStructure> = Vec::with_capacity(children.len());
+            for child in children.into_iter().map(PatternStructure::dedup) {
+                if !kept.contains(&child) {
+                    kept.push(child);
+                }
+            }
+            kept
+        }
+
+        match self {
+            PatternStructure::And(children) => {
+                Self::simplify_single(unique(children), PatternStructure::And)
+            }
+            PatternStructure::Or(children) => {
+                Self::simplify_single(unique(children), PatternStructure::Or)
+            }
+            PatternStructure::FollowedBy(children) => {
+                PatternStructure::FollowedBy(children.into_iter().map(|c| c.dedup()).collect())
+            }
+            PatternStructure::Qualified(inner, q) => {
+                PatternStructure::Qualified(Box::new(inner.dedup()), q)
+            }
+            other => other,
+        }
+    }
+
+    /// Simplification: flatten, remove repeated operands and absorb until
+    /// no changes. Unlike normalization, AND is not distributed over OR, so
+    /// the pattern never grows.
+    fn simplify(self) -> Self {
+        let mut current = self;
+        loop {
+            let simplified = current.clone().flatten().dedup().absorb();
+            if simplified == current {
+                return current;
+            }
+            current = simplified;
+        }
+    }
+}
+
+// ============================================================================
+// Simplification
+// ============================================================================
+
+/// Comparisons and qualifiers of a pattern being simplified, referred to by
+/// its structure.
+#[derive(Default)]
+struct SimplifyContext {
+    /// Each distinct comparison (after canonicalization), as first written.
+    comparisons: Vec<(NormalizedComparison, ComparisonExpression)>,
+    /// Qualifiers by their string form.
+    qualifiers: HashMap<String, Qualifier>,
+}
+
+impl SimplifyContext {
+    /// Build the structure of an expression, with comparisons that only
+    /// differ in how they are written sharing a leaf.
+    fn structure(&mut self, expr: &PatternExpression) -> PatternStructure {
+        match expr {
+            PatternExpression::Comparison(comp) => {
+                let normalized = normalize_comparison(comp);
+                let index = match self.comparisons.iter().position(|(n, _)| *n == normalized) {
+                    Some(index) => index,
+                    None => {
+                        self.comparisons.push((normalized, comp.clone()));
+                        self.comparisons.len() - 1
+                    }
+                };
+                PatternStructure::Leaf(index)
+            }
+            PatternExpression::And(left, right) => {
+                PatternStructure::And(vec![self.structure(left), self.structure(right)])
+            }
+            PatternExpression::Or(left, right) => {
+                PatternStructure::Or(vec![self.structure(left), self.structure(right)])
+            }
+            PatternExpression::FollowedBy(left, right) => {
+                PatternStructure::FollowedBy(vec![self.structure(left), self.structure(right)])
+            }
+            PatternExpression::Qualified(inner, qualifier) => {
+                let key = qualifier.to_string();
+                self.qualifiers
+                    .entry(key.clone())
+                    .or_insert_with(|| qualifier.clone());
+                PatternStructure::Qualified(Box::new(self.structure(inner)), key)
+            }
+        }
+    }
+
+    /// Rebuild an expression from a structure built by this context.
+    fn expression(&self, structure: &PatternStructure) -> Option<PatternExpression> {
+        let fold =
+            |children: &[PatternStructure],
+             combine: fn(PatternExpression, PatternExpression) -> PatternExpression| {
+                let mut operands = children.iter().map(|c| self.expression(c));
+                let first = operands.next()??;
+                operands.try_fold(first, |acc, operand| Some(combine(acc, operand?)))
+            };
+
+        match structure {
+            PatternStructure::Leaf(index) => self
+                .comparisons
+                .get(*index)
+                .map(|(_, comp)| PatternExpression::Comparison(comp.clone())),
+            PatternStructure::And(children) => fold(children, PatternExpression::and),
+            PatternStructure::Or(children) => fold(children, PatternExpression::or),
+            PatternStructure::FollowedBy(children) => fold(children, |a, b| {
+                PatternExpression::FollowedBy(Box::new(a), Box::new(b))
+            }),
+            PatternStructure::Qualified(inner, key) => Some(PatternExpression::Qualified(
+                Box::new(self.expression(inner)?),
+                self.qualifiers.get(key)?.clone(),
+            )),
+            PatternStructure::Single => None,
+        }
+    }
+}
+
+/// Simplify a pattern expression by removing redundant clauses.
+///
+/// Nested AND and OR expressions are flattened, repeated operands removed
+/// (`[a] OR [a]` becomes `[a]`) and absorbed operands dropped
+/// (`[a] AND ([a] OR [b])` becomes `[a]`). Comparisons are compared after
+/// canonicalization, so `[ipv4-addr:value = '10.0.0.1/32']` repeats
+/// `[ipv4-addr:value = '10.0.0.1']`. The simplified expression matches the
+/// same observations, and keeps the order and the first spelling of the
+/// remaining clauses.
+///
+/// # Example
+///
+/// ```rust
+/// use stix2::patterns::{parse_pattern, simplify};
+///
+/// let expr = parse_pattern("[file:name = 'a'] OR [file:name = 'a']").unwrap();
+/// assert_eq!(simplify(&expr).to_string(), "[file:name = 'a']");
+/// ```
+pub fn simplify(expr: &PatternExpression) -> PatternExpression {
+    let mut context = SimplifyContext::default();
+    let structure = context.structure(expr).simplify();
+    context
+        .expression(&structure)
+        .unwrap_or_else(|| expr.clone())
+}
+
+/// Simplify a STIX pattern string, returning the simplified pattern.
+///
+/// See [`simplify`].
+pub fn simplify_pattern(pattern: &str) -> Result<String> {
+    Ok(simplify(&parse_pattern(pattern)?).to_string())
 }
 
 /// Check if two STIX patterns are semantically equivalent.
@@ -395,6 +564,28 @@ where
     Ok(results)
 }
 
+/// Get the canonical form of a STIX pattern.
+///
+/// Two patterns have the same canonical form exactly when
+/// [`equivalent_patterns`] considers them equivalent, so the canonical form
+/// can key a map of indicators by pattern without comparing patterns pairwise.
+/// The form is meant for comparison only; it is not a STIX pattern.
+///
+/// # Example
+///
+/// ```rust
+/// use stix2::patterns::canonical_form;
+///
+/// assert_eq!(
+///     canonical_form("[ipv4-addr:value = '10.0.0.1/32']").unwrap(),
+///     canonical_form("[ipv4-addr:value='10.0.0.1']").unwrap()
+/// );
+/// ```
+pub fn canonical_form(pattern: &str) -> Result<String> {
+    let ast = parse_pattern(pattern)?;
+    Ok(normalize_expression(&ast).to_string())
+}
+
 /// Calculate similarity score between two patterns (0-100).
 pub fn pattern_similarity(pattern1: &str, pattern2: &str) -> Result<f64> {
     let ast1 = parse_pattern(pattern1)?;
@@ -418,6 +609,8 @@ pub struct NormalizedPattern {
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 enum PatternStructure {
     Single,
+    /// A comparison, by its index in a [`SimplifyContext`].
+    Leaf(usize),
     And(Vec<PatternStructure>),
     Or(Vec<PatternStructure>),
     FollowedBy(Vec<PatternStructure>),
@@ -465,6 +658,81 @@ enum NormalizedValue {
     List(Vec<NormalizedValue>),
 }
 
+// The canonical form writes out every field compared by `compare_patterns`,
+// quoting strings so distinct normalized patterns never share a form.
+
+impl fmt::Display for NormalizedPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_char('{')?;
+        for (i, comparison) in self.comparisons.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{comparison}")?;
+        }
+        write!(f, "}} {}", self.structure)
+    }
+}
+
+impl fmt::Display for NormalizedComparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let neg = if self.negated { "NOT " } else { "" };
+        write!(
+            f,
+            "{neg}{}:{} {:?} {}",
+            self.object_type, self.property_path, self.operator, self.value
+        )
+    }
+}
+
+impl fmt::Display for NormalizedValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NormalizedValue::String(s) => write!(f, "{s:?}"),
+            NormalizedValue::Integer(i) => write!(f, "{i}"),
+            // Adding zero folds -0.0 into 0.0, which compare equal
+            NormalizedValue::Float(OrderedFloat(v)) => write!(f, "{:?}", v + 0.0),
+            NormalizedValue::Boolean(b) => write!(f, "{b}"),
+            NormalizedValue::Timestamp(t) => write!(f, "t{t:?}"),
+            NormalizedValue::Binary(b) => write!(f, "b'{}'", hex::encode(b)),
+            NormalizedValue::Hex(h) => write!(f, "h{h:?}"),
+            NormalizedValue::List(items) => {
+                f.write_char('(')?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                f.write_char(')')
+            }
+        }
+    }
+}
+
+impl fmt::Display for PatternStructure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (name, children) = match self {
+            PatternStructure::Single => return f.write_char('_'),
+            PatternStructure::Leaf(index) => return write!(f, "#{index}"),
+            PatternStructure::Qualified(inner, qualifier) => {
+                return write!(f, "({inner}) {qualifier}");
+            }
+            PatternStructure::And(children) => ("AND", children),
+            PatternStructure::Or(children) => ("OR", children),
+            PatternStructure::FollowedBy(children) => ("FOLLOWEDBY", children),
+        };
+        write!(f, "{name}(")?;
+        for (i, child) in children.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{child}")?;
+        }
+        f.write_char(')')
+    }
+}
+
 /// Wrapper for f64 that implements Ord (for sorting purposes)
 #[derive(Debug, Clone, PartialEq)]
 struct OrderedFloat(f64);
@@ -673,6 +941,12 @@ mod tests {
         assert!(!result.unwrap());
     }
 
+    #[test]
+    fn test_not_equivalent_different_value_types() {
+        let result = equivalent_patterns("[file:size = 100]", "[file:size = '100']");
+        assert!(!result.unwrap());
+    }
+
     #[test]
     fn test_pattern_similarity_identical() {
         let result = pattern_similarity("[file:name = 'test.exe']", "[file:name = 'test.exe']");
@@ -873,4 +1147,138 @@ mod tests {
             panic!("Expected And structure, got {absorbed:?}");
         }
     }
+
+    #[test]
+    fn test_simplify_duplicates() {
+        assert_eq!(
+            simplify_pattern("[file:name = 'a'] OR [file:name = 'a']").unwrap(),
+            "[file:name = 'a']"
+        );
+        assert_eq!(
+            simplify_pattern("[file:name = 'a'] AND [file:name = 'a']").unwrap(),
+            "[file:name = 'a']"
+        );
+        // Flattened, so repeats are found across nesting levels
+        assert_eq!(
+            simplify_pattern("[file:name = 'a'] OR ([file:name = 'b'] OR [file:name = 'a'])")
+                .unwrap(),
+            "[file:name = 'a'] OR [file:name = 'b']"
+        );
+        // Comparisons are compared after canonicalization
+        assert_eq!(
+            simplify_pattern("[ipv4-addr:value = '10.0.0.1'] OR [ipv4-addr:value = '10.0.0.1/32']")
+                .unwrap(),
+            "[ipv4-addr:value = '10.0.0.1']"
+        );
+    }
+
+    #[test]
+    fn test_simplify_absorption() {
+        assert_eq!(
+            simplify_pattern("[file:name = 'a'] AND ([file:name = 'a'] OR [file:name = 'b'])")
+                .unwrap(),
+            "[file:name = 'a']"
+        );
+        assert_eq!(
+            simplify_pattern("([file:name = 'a'] AND [file:name = 'b']) OR [file:name = 'a']")
+                .unwrap(),
+            "[file:name = 'a']"
+        );
+        assert_eq!(
+            simplify_pattern(
+                "([file:name = 'a'] OR [file:name = 'b']) AND \
+                 ([file:name = 'b'] OR [file:name = 'a'] OR [file:name = 'c'])"
+            )
+            .unwrap(),
+            "[file:name = 'a'] OR [file:name = 'b']"
+        );
+    }
+
+    #[test]
+    fn test_simplify_keeps_order_and_qualifiers() {
+        // FOLLOWEDBY operands are ordered and never removed
+        let pattern = "[file:name = 'a'] FOLLOWEDBY [file:name = 'a']";
+        assert_eq!(simplify_pattern(pattern).unwrap(), pattern);
+
+        assert_eq!(
+            simplify_pattern(
+                "([file:name = 'a'] OR [file:name = 'a']) WITHIN 5 SECONDS \
+                 AND [file:name = 'b']"
+            )
+            .unwrap(),
+            "[file:name = 'a'] WITHIN 5 SECONDS AND [file:name = 'b']"
+        );
+        // Differently qualified clauses are not repeats
+        let pattern = "[file:name = 'a'] WITHIN 5 SECONDS OR [file:name = 'a'] REPEATS 2 TIMES";
+        assert_eq!(simplify_pattern(pattern).unwrap(), pattern);
+    }
+
+    #[test]
+    fn test_simplified_pattern_equivalent() {
+        for pattern in [
+            "[file:name = 'a'] OR [file:name = 'a'] OR [file:name = 'b']",
+            "[file:name = 'a'] AND ([file:name = 'b'] OR [file:name = 'a'])",
+            "([file:name = 'a'] AND [file:name = 'b']) FOLLOWEDBY [file:name = 'c']",
+        ] {
+            let simplified = simplify_pattern(pattern).unwrap();
+            assert!(parse_pattern(&simplified).is_ok());
+            let expr = parse_pattern(pattern).unwrap();
+            assert_eq!(simplify(&simplify(&expr)), simplify(&expr));
+        }
+    }
+
+    #[test]
+    fn test_canonical_form_equivalent_patterns() {
+        let pairs = [
+            ("[file:name = 'test.exe']", "[file:name='test.exe']"),
+            (
+                "[ipv4-addr:value = '192.168.1.100/24']",
+                "[ipv4-addr:value = '192.168.1.0/24']",
+            ),
+            (
+                "[windows-registry-key:key = 'HKLM']",
+                "[windows-registry-key:key = 'hkey_local_machine']",
+            ),
+            ("[file:size IN (2, 1)]", "[file:size IN (1, 2)]"),
+            (
+                "[file:name = 'a'] AND [file:size = 1]",
+                "[file:size = 1] AND [file:name = 'a']",
+            ),
+        ];
+        for (a, b) in pairs {
+            assert!(equivalent_patterns(a, b).unwrap(), "{a} / {b}");
+            assert_eq!(canonical_form(a).unwrap(), canonical_form(b).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_canonical_form_distinct_patterns() {
+        let patterns = [
+            "[file:name = 'a']",
+            "[file:name = 'b']",
+            "[file:name != 'a']",
+            "[file:name = 'a'] AND [file:size = 1]",
+            "[file:name = 'a'] OR [file:size = 1]",
+            "[file:name = 'a'] FOLLOWEDBY [file:size = 1]",
+            "[file:name = 'a'] WITHIN 5 SECONDS",
+            "[file:size = 1]",
+            "[file:size = '1']",
+            "[file:name = 'a, b']",
+        ];
+        let forms: BTreeSet<String> = patterns
+            .iter()
+            .map(|p| canonical_form(p).unwrap())
+            .collect();
+        assert_eq!(forms.len(), patterns.len());
+        for a in patterns {
+            for b in patterns {
+                assert_eq!(
+                    equivalent_patterns(a, b).unwrap(),
+                    canonical_form(a).unwrap() == canonical_form(b).unwrap(),
+                    "{a} / {b}"
+                );
+            }
+        }
+        assert!(canonical_form("[file:name = ").is_err());
+    }
 }
@@ -0,0 +1,156 @@
+//! STIX Pattern Size Limits
+//!
+//! Limits on the length and number of clauses of a pattern, checked before
+//! a pattern is parsed or matched. Both checks are linear scans of the
+//! pattern text, so they are safe to run on untrusted input.
+
+use crate::core::error::{Error, Result};
+
+/// Default maximum pattern length, in bytes.
+pub const DEFAULT_MAX_PATTERN_LENGTH: usize = 16 * 1024;
+
+/// Default maximum number of clauses in a pattern.
+pub const DEFAULT_MAX_PATTERN_CLAUSES: usize = 256;
+
+/// Keywords joining the clauses of a pattern.
+const CLAUSE_OPERATORS: &[&str] = &["AND", "OR", "FOLLOWEDBY"];
+
+/// Limits on the size of a pattern.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PatternLimits {
+    /// Maximum pattern length, in bytes.
+    pub max_length: usize,
+    /// Maximum number of clauses, see [`pattern_clause_count`].
+    pub max_clauses: usize,
+}
+
+impl Default for PatternLimits {
+    fn default() -> Self {
+        Self {
+            max_length: DEFAULT_MAX_PATTERN_LENGTH,
+            max_clauses: DEFAULT_MAX_PATTERN_CLAUSES,
+        }
+    }
+}
+
+/// Count the clauses of a STIX pattern.
+///
+/// Clauses are the comparison and observation expressions joined by
+/// `AND`, `OR` and `FOLLOWEDBY`. The count does not need the pattern to
+/// parse: keywords inside string literals are ignored.
+pub fn pattern_clause_count(pattern: &str) -> usize {
+    let mut operators = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut word = String::new();
+
+    for c in pattern.chars() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '\'' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        if c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | ':' | '.') {
+            word.push(c);
+            continue;
+        }
+        if is_clause_operator(&word) {
+            operators += 1;
+        }
+        word.clear();
+        in_string = c == '\'';
+    }
+    if is_clause_operator(&word) {
+        operators += 1;
+    }
+
+    operators + 1
+}
+
+fn is_clause_operator(word: &str) -> bool {
+    CLAUSE_OPERATORS
+        .iter()
+        .any(|op| word.eq_ignore_ascii_case(op))
+}
+
+/// Check that a pattern is within the length limit.
+pub fn check_pattern_length(pattern: &str, limits: &PatternLimits) -> Result<()> {
+    if pattern.len() > limits.max_length {
+        return Err(Error::PatternValidation(format!(
+            "pattern of {} bytes exceeds the maximum length of {} bytes",
+            pattern.len(),
+            limits.max_length
+        )));
+    }
+    Ok(())
+}
+
+/// Check that a STIX pattern is within the length and clause limits.
+///
+/// The length is checked first, so an over-length pattern is never
+/// scanned for clauses.
+pub fn check_pattern_limits(pattern: &str, limits: &PatternLimits) -> Result<()> {
+    check_pattern_length(pattern, limits)?;
+
+    let clauses = pattern_clause_count(pattern);
+    if clauses > limits.max_clauses {
+        return Err(Error::PatternValidation(format!(
+            "pattern has {clauses} clauses, more than the maximum of {}",
+            limits.max_clauses
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clause_count() {
+        assert_eq!(pattern_clause_count("[ipv4-addr:value = '10.0.0.1']"), 1);
+        assert_eq!(
+            pattern_clause_count("[a:b = 1 AND a:c = 2] OR [d:e = 3] FOLLOWEDBY [f:g = 4]"),
+            4
+        );
+        assert_eq!(pattern_clause_count("[a:b = 1 and a:c = 2]"), 2);
+        // Keywords in strings and names are not operators
+        assert_eq!(pattern_clause_count("[a:b = 'x AND y \\' OR z']"), 1);
+        assert_eq!(pattern_clause_count("[x-or-type:or_value = 1]"), 1);
+    }
+
+    #[test]
+    fn test_over_length_pattern() {
+        let limits = PatternLimits {
+            max_length: 64,
+            ..PatternLimits::default()
+        };
+        let pattern = format!("[url:value = '{}']", "a".repeat(64));
+        assert!(matches!(
+            check_pattern_limits(&pattern, &limits),
+            Err(Error::PatternValidation(_))
+        ));
+        assert!(check_pattern_limits("[url:value = 'a']", &limits).is_ok());
+    }
+
+    #[test]
+    fn test_over_complex_pattern() {
+        let limits = PatternLimits {
+            max_clauses: 10,
+            ..PatternLimits::default()
+        };
+        let clause = |i: usize| format!("[ipv4-addr:value = '10.0.0.{i}']");
+        let within = (0..10).map(clause).collect::<Vec<_>>().join(" OR ");
+        let over = (0..11).map(clause).collect::<Vec<_>>().join(" OR ");
+
+        assert!(check_pattern_limits(&within, &limits).is_ok());
+        assert!(matches!(
+            check_pattern_limits(&over, &limits),
+            Err(Error::PatternValidation(_))
+        ));
+    }
+}
@@ -0,0 +1,530 @@
+//! STIX Pattern Matching
+//!
+//! This module evaluates parsed STIX patterns against cyber observable
+//! objects (SCOs). The object type of every comparison is dispatched through
+//! the type [`registry`](crate::registry): built-in observables resolve object
+//! paths against whatever properties an instance carries, while custom SCOs
+//! registered with a property schema (see
+//! [`register_custom_sco_with_properties`](crate::custom::register_custom_sco_with_properties))
+//! only resolve paths that start with a declared property.
+//!
+//! All objects are treated as one observation, so `AND` and `OR` combine the
+//! results of their operands. The objects carry no observation times or
+//! counts, so patterns using `FOLLOWEDBY` or a qualifier (`WITHIN`,
+//! `REPEATS`, `START`/`STOP`) fail with [`Error::PatternUnsupported`] rather
+//! than matching on their comparisons alone.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use chrono::DateTime;
+use ipnetwork::IpNetwork;
+use regex::Regex;
+use serde_json::Value;
+
+use super::{ComparisonExpression, ComparisonOperator, PatternExpression, PatternValue};
+use crate::core::error::{Error, Result};
+use crate::registry::{ObjectCategory, SpecVersion, class_for_type};
+
+/// Properties every SCO may carry besides its declared ones.
+const COMMON_SCO_PROPERTIES: &[&str] = &[
+    "type",
+    "id",
+    "spec_version",
+    "object_marking_refs",
+    "granular_markings",
+    "defanged",
+    "extensions",
+];
+
+/// Check whether a pattern matches a set of observed objects.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use stix2::patterns::match_pattern;
+/// use serde_json::json;
+///
+/// let objects = vec![json!({"type": "ipv4-addr", "value": "10.0.0.1"})];
+/// assert!(match_pattern("[ipv4-addr:value = '10.0.0.1']", &objects)?);
+/// ```
+pub fn match_pattern(pattern: &str, objects: &[Value]) -> Result<bool> {
+    matches(&super::parse_pattern(pattern)?, objects)
+}
+
+/// Check whether a parsed pattern matches a set of observed objects.
+///
+/// Fails if a comparison refers to an object type that is not a registered
+/// SCO, or to a property a custom SCO does not declare, and if the pattern
+/// uses `FOLLOWEDBY` or a qualifier.
+pub fn matches(expr: &PatternExpression, objects: &[Value]) -> Result<bool> {
+    check_supported(expr)?;
+
+    let by_id: HashMap<&str, &Value> = objects
+        .iter()
+        .filter_map(|obj| Some((obj.get("id")?.as_str()?, obj)))
+        .collect();
+
+    evaluate(expr, objects, &by_id)
+}
+
+/// Reject the constructs that need observation times or counts.
+fn check_supported(expr: &PatternExpression) -> Result<()> {
+    match expr {
+        PatternExpression::Comparison(_) => Ok(()),
+        PatternExpression::And(a, b) | PatternExpression::Or(a, b) => {
+            check_supported(a)?;
+            check_supported(b)
+        }
+        PatternExpression::FollowedBy(..) | PatternExpression::Qualified(..) => {
+            Err(unsupported(expr))
+        }
+    }
+}
+
+fn unsupported(expr: &PatternExpression) -> Error {
+    Error::PatternUnsupported(match expr {
+        PatternExpression::Qualified(_, qualifier) => qualifier.to_string(),
+        PatternExpression::FollowedBy(..) => "FOLLOWEDBY".to_string(),
+        other => other.to_string(),
+    })
+}
+
+fn evaluate(
+    expr: &PatternExpression,
+    objects: &[Value],
+    by_id: &HashMap<&str, &Value>,
+) -> Result<bool> {
+    match expr {
+        PatternExpression::Comparison(comparison) => {
+            evaluate_comparison(comparison, objects, by_id)
+        }
+        PatternExpression::And(a, b) => {
+            Ok(evaluate(a, objects, by_id)? && evaluate(b, objects, by_id)?)
+        }
+        PatternExpression::Or(a, b) => {
+            Ok(evaluate(a, objects, by_id)? || evaluate(b, objects, by_id)?)
+        }
+        PatternExpression::FollowedBy(..) | PatternExpression::Qualified(..) => {
+            Err(unsupported(expr))
+        }
+    }
+}
+
+fn evaluate_comparison(
+    comparison: &ComparisonExpression,
+    objects: &[Value],
+    by_id: &HashMap<&str, &Value>,
+) -> Result<bool> {
+    let steps = parse_path(&comparison.object_path)?;
+    check_path(&comparison.object_type, &steps)?;
+    let expected = unescape(&comparison.value);
+    // Compiled once for every value the path resolves to
+    let regex = match (comparison.operator, &expected) {
+        (ComparisonOperator::Matches, PatternValue::String(regex)) => {
+            Some(Regex::new(regex).map_err(|e| {
+                Error::PatternValidation(format!("Invalid regular expression: {e}"))
+            })?)
+        }
+        (ComparisonOperator::Like, PatternValue::String(like)) => Some(like_regex(like)?),
+        _ => None,
+    };
+
+    for obj in objects
+        .iter()
+        .filter(|obj| obj.get("type").and_then(Value::as_str) == Some(&comparison.object_type))
+    {
+        for value in resolve(obj, &steps, by_id) {
+            if compare(value, comparison.operator, &expected, regex.as_ref()) != comparison.negated
+            {
+                return Ok(true);
+            }
+        }
+    }
+
+    Ok(false)
+}
+
+/// Check an object path against the registered schema of its object type.
+fn check_path(object_type: &str, steps: &[PathStep]) -> Result<()> {
+    let info = class_for_type(object_type, SpecVersion::V21)
+        .or_else(|| class_for_type(object_type, SpecVersion::V20))
+        .ok_or_else(|| Error::PatternValidation(format!("Unknown object type '{object_type}'")))?;
+
+    if info.category != ObjectCategory::Observable {
+        return Err(Error::PatternValidation(format!(
+            "'{object_type}' is not a cyber observable type"
+        )));
+    }
+
+    // Built-in SCOs and custom ones registered without a schema are open
+    let (Some(properties), Some(PathStep::Key(first))) = (&info.properties, steps.first()) else {
+        return Ok(());
+    };
+    if properties.iter().any(|p| p == first) || COMMON_SCO_PROPERTIES.contains(&first.as_str()) {
+        Ok(())
+    } else {
+        Err(Error::PatternValidation(format!(
+            "'{object_type}' has no property '{first}'"
+        )))
+    }
+}
+
+/// One step of an object path.
+#[derive(Debug, Clone, PartialEq)]
+enum PathStep {
+    /// Dictionary key or property name.
+    Key(String),
+    /// List index.
+    Index(usize),
+    /// Every list element (`[*]`).
+    Any,
+}
+
+/// Split an object path (e.g. `hashes.'SHA-256'` or `values[*].name`).
+fn parse_path(path: &str) -> Result<Vec<PathStep>> {
+    let invalid = || Error::PatternValidation(format!("Invalid object path '{path}'"));
+
+    let mut steps = Vec::new();
+    let mut rest = path;
+    let mut first = true;
+
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('[') {
+            let end = after.find(']').ok_or_else(invalid)?;
+            let index = &after[..end];
+            steps.push(if index == "*" {
+                PathStep::Any
+            } else {
+                PathStep::Index(index.parse().map_err(|_| invalid())?)
+            });
+            rest = &after[end + 1..];
+            continue;
+        }
+
+        let component = if first {
+            rest
+        } else {
+            rest.strip_prefix('.').ok_or_else(invalid)?
+        };
+        first = false;
+
+        if let Some(quoted) = component.strip_prefix('\'') {
+            let end = quoted.find('\'').ok_or_else(invalid)?;
+            steps.push(PathStep::Key(quoted[..end].to_string()));
+            rest = &quoted[end + 1..];
+        } else {
+            let end = component.find(['.', '[']).unwrap_or(component.len());
+            if end == 0 {
+                return Err(invalid());
+            }
+            steps.push(PathStep::Key(component[..end].to_string()));
+            rest = &component[end..];
+        }
+    }
+
+    if steps.is_empty() {
+        return Err(invalid());
+    }
+    Ok(steps)
+}
+
+/// Resolve an object path to the values it refers to.
+///
+/// Object references (`*_ref` and the elements of `*_refs`) are followed to
+/// the referenced object when a path continues past them.
+fn resolve<'a>(
+    obj: &'a Value,
+    steps: &[PathStep],
+    by_id: &HashMap<&str, &'a Value>,
+) -> Vec<&'a Value> {
+    let mut current = vec![obj];
+
+    for step in steps {
+        current = current
+            .into_iter()
+            .flat_map(|value| {
+                let value = match (value, step) {
+                    (Value::String(id), PathStep::Key(_)) => {
+                        by_id.get(id.as_str()).copied().unwrap_or(value)
+                    }
+                    _ => value,
+                };
+                match (value, step) {
+                    (Value::Object(map), PathStep::Key(key)) => map.get(key).into_iter().collect(),
+                    (Value::Array(items), PathStep::Index(i)) => {
+                        items.get(*i).into_iter().collect()
+                    }
+                    (Value::Array(items), PathStep::Any) => items.iter().collect(),
+                    _ => Vec::new(),
+                }
+            })
+            .collect();
+    }
+
+    current
+}
+
+/// Unescape the string literals of a pattern value (`\'`, `\\`, `\n`, `\r`
+/// and `\t`), which the parser keeps as written.
+fn unescape(value: &PatternValue) -> PatternValue {
+    match value {
+        PatternValue::String(s) => {
+            let mut unescaped = String::with_capacity(s.len());
+            let mut chars = s.chars();
+            while let Some(c) = chars.next() {
+                if c != '\\' {
+                    unescaped.push(c);
+                    continue;
+                }
+                match chars.next() {
+                    Some('n') => unescaped.push('\n'),
+                    Some('r') => unescaped.push('\r'),
+                    Some('t') => unescaped.push('\t'),
+                    Some(escaped) => unescaped.push(escaped),
+                    None => unescaped.push('\\'),
+                }
+            }
+            PatternValue::String(unescaped)
+        }
+        PatternValue::List(items) => PatternValue::List(items.iter().map(unescape).collect()),
+        other => other.clone(),
+    }
+}
+
+/// Compare an object value with a pattern value, or with its compiled regex
+/// for `MATCHES` and `LIKE`.
+fn compare(
+    value: &Value,
+    operator: ComparisonOperator,
+    expected: &PatternValue,
+    regex: Option<&Regex>,
+) -> bool {
+    match operator {
+        ComparisonOperator::Equal => equals(value, expected),
+        ComparisonOperator::NotEqual => !equals(value, expected),
+        ComparisonOperator::LessThan => order(value, expected) == Some(Ordering::Less),
+        ComparisonOperator::LessThanOrEqual => matches!(
+            order(value, expected),
+            Some(Ordering::Less | Ordering::Equal)
+        ),
+        ComparisonOperator::GreaterThan => order(value, expected) == Some(Ordering::Greater),
+        ComparisonOperator::GreaterThanOrEqual => matches!(
+            order(value, expected),
+            Some(Ordering::Greater | Ordering::Equal)
+        ),
+        ComparisonOperator::In => match expected {
+            PatternValue::List(items) => items.iter().any(|item| equals(value, item)),
+            other => equals(value, other),
+        },
+        ComparisonOperator::Matches | ComparisonOperator::Like => match (value.as_str(), regex) {
+            (Some(s), Some(regex)) => regex.is_match(s),
+            _ => false,
+        },
+        ComparisonOperator::IsSubset => networks(value, expected)
+            .is_some_and(|(value, expected)| network_contains(expected, value)),
+        ComparisonOperator::IsSuperset => networks(value, expected)
+            .is_some_and(|(value, expected)| network_contains(value, expected)),
+    }
+}
+
+fn equals(value: &Value, expected: &PatternValue) -> bool {
+    match expected {
+        PatternValue::String(s) => value.as_str() == Some(s),
+        PatternValue::Boolean(b) => value.as_bool() == Some(*b),
+        PatternValue::Integer(_) | PatternValue::Float(_) | PatternValue::Timestamp(_) => {
+            order(value, expected) == Some(Ordering::Equal)
+        }
+        PatternValue::Binary(bytes) => binary(value).as_ref() == Some(bytes),
+        PatternValue::Hex(h) => hex::decode(h)
+            .ok()
+            .is_some_and(|bytes| binary(value) == Some(bytes)),
+        PatternValue::List(_) => false,
+    }
+}
+
+fn order(value: &Value, expected: &PatternValue) -> Option<Ordering> {
+    match expected {
+        PatternValue::Integer(i) => value.as_f64()?.partial_cmp(&(*i as f64)),
+        PatternValue::Float(f) => value.as_f64()?.partial_cmp(f),
+        PatternValue::Timestamp(t) => {
+            let value = DateTime::parse_from_rfc3339(value.as_str()?).ok()?;
+            let expected = DateTime::parse_from_rfc3339(t).ok()?;
+            Some(value.cmp(&expected))
+        }
+        PatternValue::String(s) => Some(value.as_str()?.cmp(s.as_str())),
+        _ => None,
+    }
+}
+
+/// Decode a binary property, which STIX serializes as base64.
+fn binary(value: &Value) -> Option<Vec<u8>> {
+    BASE64.decode(value.as_str()?).ok()
+}
+
+/// Translate a `LIKE` pattern (`%` and `_` wildcards) to a regex.
+fn like_regex(like: &str) -> Result<Regex> {
+    let mut regex = String::from("^");
+    for c in like.chars() {
+        match c {
+            '%' => regex.push_str(".*"),
+            '_' => regex.push('.'),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+
+    Regex::new(&regex).map_err(|e| Error::PatternValidation(format!("Invalid LIKE pattern: {e}")))
+}
+
+fn networks(value: &Value, expected: &PatternValue) -> Option<(IpNetwork, IpNetwork)> {
+    let PatternValue::String(expected) = expected else {
+        return None;
+    };
+    Some((value.as_str()?.parse().ok()?, expected.parse().ok()?))
+}
+
+/// Whether `outer` contains every address of `inner`.
+fn network_contains(outer: IpNetwork, inner: IpNetwork) -> bool {
+    outer.prefix() <= inner.prefix() && outer.contains(inner.network())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::custom::register_custom_sco_with_properties;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_path() {
+        assert_eq!(
+            parse_path("hashes.'SHA-256'").unwrap(),
+            vec![
+                PathStep::Key("hashes".to_string()),
+                PathStep::Key("SHA-256".to_string())
+            ]
+        );
+        assert_eq!(
+            parse_path("values[*].name").unwrap(),
+            vec![
+                PathStep::Key("values".to_string()),
+                PathStep::Any,
+                PathStep::Key("name".to_string())
+            ]
+        );
+        assert_eq!(
+            parse_path("extensions.'x-ext'.items[1]").unwrap().last(),
+            Some(&PathStep::Index(1))
+        );
+        assert!(parse_path("values[x]").is_err());
+        assert!(parse_path("a..b").is_err());
+    }
+
+    #[test]
+    fn test_builtin_observables() {
+        let objects = vec![
+            json!({"type": "file", "id": "file--1", "name": "evil.exe",
+                   "hashes": {"SHA-256": "abc"}, "size": 1024, "parent_directory_ref": "directory--1"}),
+            json!({"type": "directory", "id": "directory--1", "path": "C:\\Temp"}),
+            json!({"type": "ipv4-addr", "value": "10.0.0.1"}),
+        ];
+
+        let cases = [
+            ("[file:hashes.'SHA-256' = 'abc']", true),
+            ("[file:hashes.'SHA-256' = 'def']", false),
+            ("[file:size > 1000] AND [file:name LIKE 'evil%']", true),
+            ("[file:name MATCHES '^evil\\\\.']", true),
+            ("[file:parent_directory_ref.path = 'C:\\\\Temp']", true),
+            ("[ipv4-addr:value ISSUBSET '10.0.0.0/8']", true),
+            ("[ipv4-addr:value IN ('10.0.0.2', '10.0.0.3')]", false),
+            ("[file:size < 10] OR [ipv4-addr:value = '10.0.0.1']", true),
+            ("[file:missing = 'x']", false),
+        ];
+        for (pattern, expected) in cases {
+            assert_eq!(
+                match_pattern(pattern, &objects).unwrap(),
+                expected,
+                "{pattern}"
+            );
+        }
+
+        // Invalid regexes fail even when no value is compared
+        assert!(match_pattern("[file:missing MATCHES '(']", &objects).is_err());
+
+        // Object types must be registered observables
+        assert!(match_pattern("[x-unregistered:value = 'x']", &objects).is_err());
+        assert!(match_pattern("[indicator:name = 'x']", &objects).is_err());
+    }
+
+    #[test]
+    fn test_custom_sco() {
+        register_custom_sco_with_properties(
+            "x-test-matcher-sensor",
+            vec![SpecVersion::V21],
+            Some(vec!["sensor_id".to_string()]),
+            vec!["sensor_id".to_string(), "readings".to_string()],
+            None,
+        )
+        .unwrap();
+
+        let objects = vec![json!({
+            "type": "x-test-matcher-sensor",
+            "id": "x-test-matcher-sensor--4b1e7b54-5d2a-4f6e-9b1f-6a0b4a7b8f10",
+            "sensor_id": "s-42",
+            "readings": [{"kind": "bandwidth", "value": 950}],
+        })];
+
+        assert!(match_pattern("[x-test-matcher-sensor:sensor_id = 's-42']", &objects).unwrap());
+        assert!(
+            match_pattern("[x-test-matcher-sensor:readings[*].value >= 900]", &objects).unwrap()
+        );
+        assert!(
+            !match_pattern(
+                "[x-test-matcher-sensor:readings[0].kind = 'temperature']",
+                &objects
+            )
+            .unwrap()
+        );
+        assert!(
+            match_pattern(
+                "[x-test-matcher-sensor:id = 'x-test-matcher-sensor--4b1e7b54-5d2a-4f6e-9b1f-6a0b4a7b8f10']",
+                &objects
+            )
+            .unwrap()
+        );
+
+        // Paths are resolved against the registered schema
+        assert!(match_pattern("[x-test-matcher-sensor:location = 'x']", &objects).is_err());
+    }
+
+    #[test]
+    fn test_unsupported() {
+        let objects = vec![json!({"type": "ipv4-addr", "value": "10.0.0.1"})];
+
+        for (pattern, construct) in [
+            (
+                "[ipv4-addr:value = '10.0.0.1'] FOLLOWEDBY [ipv4-addr:value = '10.0.0.1']",
+                "FOLLOWEDBY",
+            ),
+            (
+                "[ipv4-addr:value = '10.0.0.1'] WITHIN 60 SECONDS",
+                "WITHIN 60 SECONDS",
+            ),
+            (
+                "[ipv4-addr:value = '10.0.0.1'] REPEATS 2 TIMES",
+                "REPEATS 2 TIMES",
+            ),
+            // Not hidden by an operand that already decides the result
+            (
+                "[ipv4-addr:value = '10.0.0.1'] OR ([ipv4-addr:value = 'x'] WITHIN 5 SECONDS)",
+                "WITHIN 5 SECONDS",
+            ),
+        ] {
+            match match_pattern(pattern, &objects) {
+                Err(Error::PatternUnsupported(found)) => assert_eq!(found, construct, "{pattern}"),
+                other => panic!("{pattern}: {other:?}"),
+            }
+        }
+    }
+}
@@ -1,12 +1,20 @@
 //! STIX Pattern Language
 //!
-//! This module provides parsing and manipulation of STIX patterns.
+//! This module provides parsing, manipulation and matching of STIX patterns.
 //! STIX patterns are used in Indicators to describe observable patterns
 //! that might be seen in cyber threat activity.
 
+mod limits;
+mod matcher;
 mod parser;
 mod types;
 
+pub use crate::pattern_equivalence::{canonical_form, simplify, simplify_pattern};
+pub use limits::{
+    DEFAULT_MAX_PATTERN_CLAUSES, DEFAULT_MAX_PATTERN_LENGTH, PatternLimits, check_pattern_length,
+    check_pattern_limits, pattern_clause_count,
+};
+pub use matcher::{match_pattern, matches};
 pub use parser::{PatternParser, parse_pattern};
 pub use types::*;
 
@@ -52,14 +60,48 @@ impl PatternExpression {
     }
 }
 
+impl PatternExpression {
+    /// How tightly the expression's operator binds: OR binds loosest, then
+    /// AND, FOLLOWEDBY and qualifiers.
+    fn precedence(&self) -> u8 {
+        match self {
+            PatternExpression::Or(..) => 0,
+            PatternExpression::And(..) => 1,
+            PatternExpression::FollowedBy(..) => 2,
+            PatternExpression::Qualified(..) => 3,
+            PatternExpression::Comparison(_) => 4,
+        }
+    }
+
+    /// Display as the operand of an operator of the given precedence.
+    fn operand(&self, precedence: u8) -> Operand<'_> {
+        Operand(self, precedence)
+    }
+}
+
+/// An operand, parenthesized when it binds looser than its operator.
+struct Operand<'a>(&'a PatternExpression, u8);
+
+impl fmt::Display for Operand<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.precedence() < self.1 {
+            write!(f, "({})", self.0)
+        } else {
+            write!(f, "{}", self.0)
+        }
+    }
+}
+
 impl fmt::Display for PatternExpression {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             PatternExpression::Comparison(c) => write!(f, "[{c}]"),
-            PatternExpression::And(a, b) => write!(f, "{a} AND {b}"),
+            PatternExpression::And(a, b) => write!(f, "{} AND {}", a.operand(1), b.operand(1)),
             PatternExpression::Or(a, b) => write!(f, "{a} OR {b}"),
-            PatternExpression::FollowedBy(a, b) => write!(f, "{a} FOLLOWEDBY {b}"),
-            PatternExpression::Qualified(expr, qual) => write!(f, "{expr} {qual}"),
+            PatternExpression::FollowedBy(a, b) => {
+                write!(f, "{} FOLLOWEDBY {}", a.operand(2), b.operand(2))
+            }
+            PatternExpression::Qualified(expr, qual) => write!(f, "{} {qual}", expr.operand(4)),
         }
     }
 }
@@ -366,4 +408,18 @@ mod tests {
         );
         assert_eq!(expr.object_type, "ipv4-addr");
     }
+
+    #[test]
+    fn test_expression_display_round_trips() {
+        for pattern in [
+            "([a:b = 1] OR [c:d = 2]) AND [e:f = 3]",
+            "([a:b = 1] AND [c:d = 2]) FOLLOWEDBY [e:f = 3]",
+            "([a:b = 1] OR [c:d = 2]) WITHIN 5 SECONDS",
+            "[a:b = 1] OR [c:d = 2] AND [e:f = 3]",
+        ] {
+            let expr = parse_pattern(pattern).unwrap();
+            assert_eq!(expr.to_string(), pattern);
+            assert_eq!(parse_pattern(&expr.to_string()).unwrap(), expr);
+        }
+    }
 }
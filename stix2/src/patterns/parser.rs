@@ -5,11 +5,12 @@
 use super::{ComparisonExpression, ComparisonOperator, PatternExpression, PatternValue, Qualifier};
 use crate::core::error::{Error, Result};
 use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use chrono::DateTime;
 use nom::{
     IResult, Parser,
     branch::alt,
     bytes::complete::{escaped, tag, tag_no_case, take_while, take_while1},
-    character::complete::{char, digit1, multispace0, none_of, one_of},
+    character::complete::{char, digit0, digit1, multispace0, none_of, one_of},
     combinator::{map_res, opt, recognize, value},
     multi::{many0, separated_list0},
     sequence::{delimited, pair, preceded, terminated},
@@ -197,7 +198,9 @@ fn parse_comparison_operator(input: &str) -> IResult<&str, ComparisonOperator> {
     .parse(input)
 }
 
-// Parse a value
+// Parse a value. The literal syntax decides its type: quotes make a string,
+// bare digits an integer (a float with a decimal point) and `t'...'` a
+// timestamp, so `100` and `'100'` never compare equal.
 fn parse_value(input: &str) -> IResult<&str, PatternValue> {
     alt((
         parse_string_value,
@@ -232,7 +235,16 @@ fn parse_boolean_value(input: &str) -> IResult<&str, PatternValue> {
 
 fn parse_timestamp_value(input: &str) -> IResult<&str, PatternValue> {
     let (input, _) = tag("t'")(input)?;
-    let (input, ts) = take_while(|c| c != '\'')(input)?;
+    // STIX timestamps are RFC 3339 in UTC
+    let (input, ts) =
+        map_res(
+            take_while(|c| c != '\''),
+            |ts: &str| match DateTime::parse_from_rfc3339(ts) {
+                Ok(_) if ts.ends_with('Z') => Ok(ts),
+                _ => Err("invalid timestamp"),
+            },
+        )
+        .parse(input)?;
     let (input, _) = char('\'')(input)?;
     Ok((input, PatternValue::Timestamp(ts.to_string())))
 }
@@ -257,20 +269,20 @@ fn parse_binary_value(input: &str) -> IResult<&str, PatternValue> {
 }
 
 fn parse_integer_value(input: &str) -> IResult<&str, PatternValue> {
-    let (input, neg) = opt(char('-')).parse(input)?;
-    let (input, value) = map_res(digit1, |digits: &str| digits.parse::<i64>()).parse(input)?;
-    let value = if neg.is_some() { -value } else { value };
+    let (input, value) = map_res(recognize((opt(one_of("+-")), digit1)), |s: &str| {
+        s.parse::<i64>()
+    })
+    .parse(input)?;
 
     Ok((input, PatternValue::Integer(value)))
 }
 
 fn parse_float_value(input: &str) -> IResult<&str, PatternValue> {
-    let (input, neg) = opt(char('-')).parse(input)?;
-    let (input, value) = map_res(recognize((digit1, char('.'), digit1)), |s: &str| {
-        s.parse::<f64>()
-    })
+    let (input, value) = map_res(
+        recognize((opt(one_of("+-")), digit0, char('.'), digit1)),
+        |s: &str| s.parse::<f64>(),
+    )
     .parse(input)?;
-    let value = if neg.is_some() { -value } else { value };
 
     Ok((input, PatternValue::Float(value)))
 }
@@ -349,4 +361,45 @@ mod tests {
         let result = parse_pattern(pattern);
         assert!(result.is_ok());
     }
+
+    fn comparison_value(pattern: &str) -> PatternValue {
+        match parse_pattern(pattern) {
+            Ok(PatternExpression::Comparison(comparison)) => comparison.value,
+            other => panic!("expected a single comparison, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_value_types() {
+        assert_eq!(
+            comparison_value("[file:size = 100]"),
+            PatternValue::Integer(100)
+        );
+        assert_eq!(
+            comparison_value("[file:name = '100']"),
+            PatternValue::String("100".to_string())
+        );
+        assert_eq!(
+            comparison_value("[file:size > +100]"),
+            PatternValue::Integer(100)
+        );
+        assert_eq!(
+            comparison_value("[file:size > -9223372036854775808]"),
+            PatternValue::Integer(i64::MIN)
+        );
+        assert_eq!(
+            comparison_value("[x-custom:ratio < -.5]"),
+            PatternValue::Float(-0.5)
+        );
+        assert_eq!(
+            comparison_value("[file:created = t'2024-01-15T10:00:00.000Z']"),
+            PatternValue::Timestamp("2024-01-15T10:00:00.000Z".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_timestamp() {
+        assert!(parse_pattern("[file:created = t'yesterday']").is_err());
+        assert!(parse_pattern("[file:created = t'2024-01-15T10:00:00+02:00']").is_err());
+    }
 }
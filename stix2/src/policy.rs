@@ -0,0 +1,126 @@
+//! Sharing Policy Helpers
+//!
+//! This module provides helpers for deriving handling guidance from object
+//! properties, for use when auto-marking producer output.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! use stix2::policy::{suggested_tlp, DEFAULT_CONFIDENCE_TLP_RULES};
+//! use stix2::markings::TlpLevel;
+//!
+//! // Low-confidence intelligence stays restricted
+//! assert_eq!(suggested_tlp(20, DEFAULT_CONFIDENCE_TLP_RULES), TlpLevel::Red);
+//!
+//! // Custom rules: share widely above 90
+//! let rules = [(0, TlpLevel::Amber), (90, TlpLevel::Clear)];
+//! assert_eq!(suggested_tlp(95, &rules), TlpLevel::Clear);
+//! ```
+
+use crate::core::id::Identifier;
+use crate::markings::TlpLevel;
+
+/// Default confidence-to-TLP rules.
+///
+/// Thresholds follow the None/Low/Med/High confidence scale
+/// (see [`crate::utils::confidence::to_nlmh`]): None and Low confidence
+/// stay TLP:RED, Medium is TLP:AMBER and High is TLP:GREEN.
+pub const DEFAULT_CONFIDENCE_TLP_RULES: &[(u8, TlpLevel)] = &[
+    (0, TlpLevel::Red),
+    (31, TlpLevel::Amber),
+    (71, TlpLevel::Green),
+];
+
+/// Maximum STIX confidence value.
+const MAX_CONFIDENCE: u8 = 100;
+
+/// Suggest a TLP level for a confidence value.
+///
+/// Each rule is a `(minimum confidence, level)` pair. The rule with the
+/// highest minimum that does not exceed `confidence` applies, regardless
+/// of the order of `rules`. Confidence values above 100 are treated as 100.
+///
+/// When no rule applies (no rules, or `confidence` is below every minimum),
+/// the most restrictive level, TLP:RED, is suggested.
+pub fn suggested_tlp(confidence: u8, rules: &[(u8, TlpLevel)]) -> TlpLevel {
+    let confidence = confidence.min(MAX_CONFIDENCE);
+
+    rules
+        .iter()
+        .filter(|(minimum, _)| *minimum <= confidence)
+        .max_by_key(|(minimum, _)| *minimum)
+        .map(|(_, level)| *level)
+        .unwrap_or(TlpLevel::Red)
+}
+
+/// Suggest a TLP marking definition for a confidence value.
+///
+/// Returns the standard marking definition ID of [`suggested_tlp`], ready
+/// to be added to `object_marking_refs`.
+pub fn suggested_tlp_marking(confidence: u8, rules: &[(u8, TlpLevel)]) -> Identifier {
+    suggested_tlp(confidence, rules).marking_definition_id()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_rule_boundaries() {
+        let rules = DEFAULT_CONFIDENCE_TLP_RULES;
+
+        assert_eq!(suggested_tlp(0, rules), TlpLevel::Red);
+        assert_eq!(suggested_tlp(30, rules), TlpLevel::Red);
+        assert_eq!(suggested_tlp(31, rules), TlpLevel::Amber);
+        assert_eq!(suggested_tlp(70, rules), TlpLevel::Amber);
+        assert_eq!(suggested_tlp(71, rules), TlpLevel::Green);
+        assert_eq!(suggested_tlp(100, rules), TlpLevel::Green);
+    }
+
+    #[test]
+    fn test_rules_order_independent() {
+        let rules = [
+            (90, TlpLevel::Clear),
+            (0, TlpLevel::Red),
+            (50, TlpLevel::Amber),
+        ];
+
+        assert_eq!(suggested_tlp(49, &rules), TlpLevel::Red);
+        assert_eq!(suggested_tlp(50, &rules), TlpLevel::Amber);
+        assert_eq!(suggested_tlp(89, &rules), TlpLevel::Amber);
+        assert_eq!(suggested_tlp(90, &rules), TlpLevel::Clear);
+    }
+
+    #[test]
+    fn test_below_all_rules_is_red() {
+        let rules = [(50, TlpLevel::Green)];
+
+        assert_eq!(suggested_tlp(49, &rules), TlpLevel::Red);
+        assert_eq!(suggested_tlp(50, &rules), TlpLevel::Green);
+        assert_eq!(suggested_tlp(80, &[]), TlpLevel::Red);
+    }
+
+    #[test]
+    fn test_confidence_above_max_is_clamped() {
+        let rules = [
+            (0, TlpLevel::Amber),
+            (100, TlpLevel::Green),
+            (150, TlpLevel::Clear),
+        ];
+
+        assert_eq!(suggested_tlp(100, &rules), TlpLevel::Green);
+        assert_eq!(suggested_tlp(200, &rules), TlpLevel::Green);
+    }
+
+    #[test]
+    fn test_suggested_tlp_marking() {
+        assert_eq!(
+            suggested_tlp_marking(80, DEFAULT_CONFIDENCE_TLP_RULES),
+            TlpLevel::Green.marking_definition_id()
+        );
+        assert_eq!(
+            suggested_tlp_marking(10, DEFAULT_CONFIDENCE_TLP_RULES),
+            TlpLevel::Red.marking_definition_id()
+        );
+    }
+}
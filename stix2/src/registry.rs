@@ -81,6 +81,10 @@ pub struct TypeInfo {
     pub id_contributing_props: Option<Vec<String>>,
     /// Custom validator function
     pub validator: Option<ObjectValidator>,
+    /// Top-level properties introduced by a `toplevel-property-extension`
+    pub toplevel_properties: Option<Vec<String>>,
+    /// Declared properties of custom SCOs, used to resolve pattern object paths
+    pub properties: Option<Vec<String>>,
 }
 
 /// Global type registry
@@ -96,6 +100,8 @@ pub struct TypeRegistry {
     types: HashMap<(String, SpecVersion), TypeInfo>,
     /// Custom type parsers
     custom_parsers: HashMap<String, ObjectParser>,
+    /// Extensions by the top-level property they introduce
+    toplevel_properties: HashMap<(String, SpecVersion), String>,
 }
 
 impl TypeRegistry {
@@ -104,6 +110,7 @@ impl TypeRegistry {
         Self {
             types: HashMap::new(),
             custom_parsers: HashMap::new(),
+            toplevel_properties: HashMap::new(),
         }
     }
 
@@ -134,6 +141,8 @@ impl TypeRegistry {
                 is_custom: false,
                 id_contributing_props: None,
                 validator: None,
+                toplevel_properties: None,
+                properties: None,
             });
         }
 
@@ -158,6 +167,8 @@ impl TypeRegistry {
                 is_custom: false,
                 id_contributing_props: None,
                 validator: None,
+                toplevel_properties: None,
+                properties: None,
             });
         }
 
@@ -172,6 +183,8 @@ impl TypeRegistry {
                 is_custom: false,
                 id_contributing_props: None,
                 validator: None,
+                toplevel_properties: None,
+                properties: None,
             });
         }
 
@@ -206,6 +219,8 @@ impl TypeRegistry {
                 is_custom: false,
                 id_contributing_props: None,
                 validator: None,
+                toplevel_properties: None,
+                properties: None,
             });
         }
 
@@ -218,17 +233,34 @@ impl TypeRegistry {
             is_custom: false,
             id_contributing_props: None,
             validator: None,
+            toplevel_properties: None,
+            properties: None,
         });
     }
 
     /// Register a type
     pub fn register_type(&mut self, info: TypeInfo) {
         for version in &info.spec_versions {
+            for property in info.toplevel_properties.iter().flatten() {
+                self.toplevel_properties
+                    .insert((property.clone(), *version), info.type_name.clone());
+            }
             self.types
                 .insert((info.type_name.clone(), *version), info.clone());
         }
     }
 
+    /// Get the extension that introduces a top-level property
+    pub fn toplevel_property_extension(
+        &self,
+        property: &str,
+        version: SpecVersion,
+    ) -> Option<&str> {
+        self.toplevel_properties
+            .get(&(property.to_string(), version))
+            .map(String::as_str)
+    }
+
     /// Get type info
     pub fn get_type(&self, type_name: &str, version: SpecVersion) -> Option<&TypeInfo> {
         self.types.get(&(type_name.to_string(), version))
@@ -289,6 +321,10 @@ pub struct CustomTypeOptions {
     pub id_contributing_props: Option<Vec<String>>,
     /// Custom validator function
     pub validator: Option<ObjectValidator>,
+    /// Top-level properties introduced by a `toplevel-property-extension`
+    pub toplevel_properties: Option<Vec<String>>,
+    /// Declared properties of a custom SCO
+    pub properties: Option<Vec<String>>,
 }
 
 /// Register a custom STIX type.
@@ -325,6 +361,8 @@ pub fn register_custom_type(
         is_custom: true,
         id_contributing_props: opts.id_contributing_props,
         validator: opts.validator,
+        toplevel_properties: opts.toplevel_properties,
+        properties: opts.properties,
     });
 
     Ok(())
@@ -355,6 +393,17 @@ pub fn is_registered_type(type_name: &str, version: SpecVersion) -> bool {
     }
 }
 
+/// Get the registered `toplevel-property-extension` that introduces a
+/// top-level property.
+///
+/// Returns `None` for properties not introduced by an extension.
+pub fn toplevel_property_extension(property: &str, version: SpecVersion) -> Option<String> {
+    let registry = REGISTRY.read().ok()?;
+    registry
+        .toplevel_property_extension(property, version)
+        .map(str::to_string)
+}
+
 /// Get all registered SDO types.
 pub fn get_sdo_types(version: SpecVersion) -> Vec<String> {
     if let Ok(registry) = REGISTRY.read() {
@@ -394,6 +443,47 @@ pub fn get_sco_types(version: SpecVersion) -> Vec<String> {
     }
 }
 
+/// Predefined SCO extensions (STIX 2.1) by the SCO type they belong to.
+const SCO_EXTENSIONS: &[(&str, &[&str])] = &[
+    (
+        "file",
+        &[
+            "archive-ext",
+            "ntfs-ext",
+            "pdf-ext",
+            "raster-image-ext",
+            "windows-pebinary-ext",
+        ],
+    ),
+    (
+        "network-traffic",
+        &["http-request-ext", "icmp-ext", "socket-ext", "tcp-ext"],
+    ),
+    ("process", &["windows-process-ext", "windows-service-ext"]),
+    ("user-account", &["unix-account-ext"]),
+];
+
+/// Get the predefined extensions that are valid on an SCO type.
+///
+/// Returns an empty slice for types without predefined extensions.
+pub fn valid_extensions_for(sco_type: &str) -> &'static [&'static str] {
+    SCO_EXTENSIONS
+        .iter()
+        .find(|(type_name, _)| *type_name == sco_type)
+        .map(|(_, extensions)| *extensions)
+        .unwrap_or(&[])
+}
+
+/// Get the SCO type a predefined extension belongs to.
+///
+/// Returns `None` for custom and `extension-definition--` extension keys.
+pub fn extension_owner(extension: &str) -> Option<&'static str> {
+    SCO_EXTENSIONS
+        .iter()
+        .find(|(_, extensions)| extensions.contains(&extension))
+        .map(|(type_name, _)| *type_name)
+}
+
 /// Get all registered types for a version.
 pub fn get_all_types(version: SpecVersion) -> Vec<String> {
     if let Ok(registry) = REGISTRY.read() {
@@ -458,4 +548,19 @@ mod tests {
         assert_eq!(info.type_name, "indicator");
         assert_eq!(info.category, ObjectCategory::DomainObject);
     }
+
+    #[test]
+    fn test_valid_extensions_for() {
+        assert!(valid_extensions_for("file").contains(&"pdf-ext"));
+        assert!(valid_extensions_for("network-traffic").contains(&"http-request-ext"));
+        assert!(valid_extensions_for("domain-name").is_empty());
+        assert!(valid_extensions_for("x-custom").is_empty());
+    }
+
+    #[test]
+    fn test_extension_owner() {
+        assert_eq!(extension_owner("pdf-ext"), Some("file"));
+        assert_eq!(extension_owner("unix-account-ext"), Some("user-account"));
+        assert_eq!(extension_owner("x-acme-ext"), None);
+    }
 }
@@ -4,6 +4,7 @@
 
 use crate::core::id::Identifier;
 use crate::core::stix_object::StixObject;
+use chrono::{DateTime, Duration, Utc};
 use std::collections::HashMap;
 
 /// Get the STIX type from an identifier.
@@ -146,6 +147,28 @@ pub fn deduplicate(objects: Vec<StixObject>) -> Vec<StixObject> {
     seen.into_values().collect()
 }
 
+/// Age of an object at `now`, from its `modified` timestamp.
+///
+/// Returns `None` for objects without one (SCOs). An object modified after
+/// `now` has age zero.
+pub fn age(obj: &StixObject, now: DateTime<Utc>) -> Option<Duration> {
+    obj.modified()
+        .map(|modified| (now - modified).max(Duration::zero()))
+}
+
+/// Check if an object is stale at `now`.
+///
+/// An indicator is stale from its `valid_until` on, whatever its age.
+/// Otherwise an object is stale when older than `max_age`; objects without
+/// a `modified` timestamp never are.
+pub fn is_stale(obj: &StixObject, now: DateTime<Utc>, max_age: Duration) -> bool {
+    let expired = obj
+        .as_indicator()
+        .and_then(|indicator| indicator.valid_until.as_ref())
+        .is_some_and(|valid_until| valid_until.datetime() <= now);
+    expired || age(obj, now).is_some_and(|age| age > max_age)
+}
+
 /// Confidence scale conversion utilities.
 pub mod confidence {
     /// Convert from None/Low/Med/High scale to 0-100.
@@ -380,6 +403,65 @@ mod tests {
         assert!(!is_valid_stix_type("invalid-type"));
     }
 
+    fn indicator(modified: &str, valid_until: Option<&str>) -> crate::Result<StixObject> {
+        let mut json = serde_json::json!({
+            "type": "indicator",
+            "spec_version": "2.1",
+            "id": "indicator--8e2e2d2b-17d4-4cbf-938f-98ee46b3cd3f",
+            "created": "2024-01-01T00:00:00.000Z",
+            "modified": modified,
+            "pattern": "[ipv4-addr:value = '198.51.100.1']",
+            "pattern_type": "stix",
+            "valid_from": "2024-01-01T00:00:00Z"
+        });
+        if let Some(valid_until) = valid_until {
+            json["valid_until"] = valid_until.into();
+        }
+        crate::parse(&json.to_string())
+    }
+
+    fn at(timestamp: &str) -> DateTime<Utc> {
+        timestamp.parse().unwrap_or_default()
+    }
+
+    #[test]
+    fn test_age_and_staleness() -> crate::Result<()> {
+        let now = at("2024-03-01T00:00:00Z");
+        let max_age = Duration::days(30);
+
+        let fresh = indicator("2024-02-20T00:00:00.000Z", None)?;
+        assert_eq!(age(&fresh, now), Some(Duration::days(10)));
+        assert!(!is_stale(&fresh, now, max_age));
+
+        let stale = indicator("2024-01-15T00:00:00.000Z", None)?;
+        assert_eq!(age(&stale, now), Some(Duration::days(46)));
+        assert!(is_stale(&stale, now, max_age));
+
+        // Past valid_until, even when recently modified
+        let expired = indicator("2024-02-28T00:00:00.000Z", Some("2024-02-29T00:00:00Z"))?;
+        assert!(is_stale(&expired, now, max_age));
+        let valid = indicator("2024-02-28T00:00:00.000Z", Some("2024-06-01T00:00:00Z"))?;
+        assert!(!is_stale(&valid, now, max_age));
+
+        // Modified after now
+        assert_eq!(
+            age(&fresh, at("2024-01-01T00:00:00Z")),
+            Some(Duration::zero())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_age_without_modified() -> crate::Result<()> {
+        let ip = crate::parse(
+            r#"{"type": "ipv4-addr", "spec_version": "2.1", "id": "ipv4-addr--ff26c055-6336-5bc5-b98d-13d6226742dd", "value": "198.51.100.3"}"#,
+        )?;
+        let now = at("2024-03-01T00:00:00Z");
+        assert_eq!(age(&ip, now), None);
+        assert!(!is_stale(&ip, now, Duration::zero()));
+        Ok(())
+    }
+
     #[test]
     fn test_confidence_conversion() {
         assert_eq!(confidence::from_nlmh("high"), 85);
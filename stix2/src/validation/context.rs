@@ -45,6 +45,12 @@ pub struct ValidationContext {
     /// - STIX 2.1: Dictionary keys must be 1-250 chars
     /// - STIX 2.1: Property names must start with alpha char
     pub spec_version: SpecVersion,
+
+    /// Whether SCOs may only carry predefined extensions of their own type.
+    ///
+    /// When `true`, e.g. a `pdf-ext` on a `domain-name` is rejected.
+    /// Custom and `extension-definition--` extensions are always accepted.
+    pub strict_extensions: bool,
 }
 
 impl Default for ValidationContext {
@@ -55,6 +61,7 @@ impl Default for ValidationContext {
             allow_custom: true,
             interoperability: false,
             spec_version: SpecVersion::V21,
+            strict_extensions: false,
         }
     }
 }
@@ -65,12 +72,14 @@ impl ValidationContext {
         Self::default()
     }
 
-    /// Create a strict validation context that rejects custom content.
+    /// Create a strict validation context that rejects custom content and
+    /// SCO extensions that are not valid for the SCO type.
     pub fn strict() -> Self {
         Self {
             allow_custom: false,
             interoperability: false,
             spec_version: SpecVersion::V21,
+            strict_extensions: true,
         }
     }
 
@@ -86,6 +95,12 @@ impl ValidationContext {
         self
     }
 
+    /// Set whether SCO extensions must be valid for the SCO type.
+    pub fn strict_extensions(mut self, strict: bool) -> Self {
+        self.strict_extensions = strict;
+        self
+    }
+
     /// Set the STIX specification version.
     pub fn spec_version(mut self, version: SpecVersion) -> Self {
         self.spec_version = version;
@@ -98,6 +113,7 @@ impl ValidationContext {
             allow_custom: true,
             interoperability: false,
             spec_version: SpecVersion::V20,
+            strict_extensions: false,
         }
     }
 
@@ -107,6 +123,7 @@ impl ValidationContext {
             allow_custom: true,
             interoperability: false,
             spec_version: SpecVersion::V21,
+            strict_extensions: false,
         }
     }
 }
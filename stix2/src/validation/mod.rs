@@ -9,15 +9,18 @@
 //! - Inter-property constraint checking
 //! - Custom property (x_*) handling via `allow_custom`
 //! - Relaxed UUID validation via `interoperability` mode
+//! - Community requirements on optional properties via validation profiles
 
 pub mod constraints;
 pub mod context;
 #[macro_use]
 pub mod macros;
+pub mod profiles;
 pub mod properties;
 
 pub use constraints::*;
 pub use context::*;
+pub use profiles::*;
 pub use properties::*;
 
 use crate::core::error::Result;
@@ -0,0 +1,198 @@
+//! Validation Profiles
+//!
+//! Sharing communities often require properties that the STIX specification
+//! leaves optional. A `ValidationProfile` declares these per object type, and
+//! [`validate_object`] rejects objects that lack them.
+
+use crate::core::error::{Error, Result};
+use crate::core::stix_object::StixObject;
+use crate::registry::{SpecVersion, get_sdo_types, get_sro_types};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Properties required of objects, by object type.
+///
+/// A required property is missing when it is absent, `null`, an empty
+/// string or an empty list, so requiring a list property requires at least
+/// one entry.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use stix2::validation::{ValidationProfile, validate_object};
+///
+/// let profile = ValidationProfile::new("isac")
+///     .require("indicator", ["confidence", "indicator_types"]);
+/// validate_object(&object, Some(&profile))?;
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValidationProfile {
+    /// Name of the profile, used in error messages.
+    pub name: String,
+
+    /// Required properties by object type.
+    #[serde(default)]
+    pub required: BTreeMap<String, Vec<String>>,
+}
+
+impl ValidationProfile {
+    /// Create an empty profile.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            required: BTreeMap::new(),
+        }
+    }
+
+    /// Require properties of objects of a type.
+    pub fn require<I, S>(mut self, object_type: impl Into<String>, properties: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let required = self.required.entry(object_type.into()).or_default();
+        for property in properties {
+            let property = property.into();
+            if !required.contains(&property) {
+                required.push(property);
+            }
+        }
+        self
+    }
+
+    /// Indicator sharing profile.
+    ///
+    /// Indicators must have a `confidence` and at least one
+    /// `indicator_types` entry, and sightings a `confidence`.
+    pub fn indicator_sharing() -> Self {
+        Self::new("indicator-sharing")
+            .require("indicator", ["confidence", "indicator_types"])
+            .require("sighting", ["confidence"])
+    }
+
+    /// Provenance profile.
+    ///
+    /// SDOs and SROs must name their creator in `created_by_ref`.
+    pub fn provenance() -> Self {
+        get_sdo_types(SpecVersion::V21)
+            .into_iter()
+            .chain(get_sro_types(SpecVersion::V21))
+            .fold(Self::new("provenance"), |profile, object_type| {
+                profile.require(object_type, ["created_by_ref"])
+            })
+    }
+
+    /// Get a built-in profile by name.
+    pub fn builtin(name: &str) -> Option<Self> {
+        match name {
+            "indicator-sharing" => Some(Self::indicator_sharing()),
+            "provenance" => Some(Self::provenance()),
+            _ => None,
+        }
+    }
+
+    /// Check that an object has the properties the profile requires of its
+    /// type.
+    ///
+    /// Returns `Error::MissingProperty` naming the first missing property.
+    pub fn check(&self, object: &Value) -> Result<()> {
+        let Some(required) = object["type"]
+            .as_str()
+            .and_then(|object_type| self.required.get(object_type))
+        else {
+            return Ok(());
+        };
+
+        let missing = required
+            .iter()
+            .find(|property| match &object[property.as_str()] {
+                Value::Null => true,
+                Value::String(s) => s.is_empty(),
+                Value::Array(a) => a.is_empty(),
+                _ => false,
+            });
+        match missing {
+            Some(property) => Err(Error::MissingProperty(format!(
+                "{property} (required by profile '{}')",
+                self.name
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Validate a STIX object, enforcing a profile if given.
+///
+/// The object must first be a valid STIX object; see [`crate::parse`].
+pub fn validate_object(object: &Value, profile: Option<&ValidationProfile>) -> Result<StixObject> {
+    let parsed = serde_json::from_value(object.clone())?;
+    if let Some(profile) = profile {
+        profile.check(object)?;
+    }
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn indicator() -> Value {
+        json!({
+            "type": "indicator",
+            "spec_version": "2.1",
+            "id": "indicator--8e2e2d2b-17d4-4cbf-938f-98ee46b3cd3f",
+            "created": "2016-04-06T20:03:48.000Z",
+            "modified": "2016-04-06T20:03:48.000Z",
+            "pattern": "[file:hashes.'SHA-256' = 'ef537f25c895bfa782526529a9b63d97aa631564d5d789c2b765448c8635fb6c']",
+            "pattern_type": "stix",
+            "valid_from": "2016-01-01T00:00:00Z",
+            "indicator_types": ["malicious-activity"]
+        })
+    }
+
+    #[test]
+    fn test_profile_flags_missing_confidence() {
+        let profile = ValidationProfile::indicator_sharing();
+        let mut object = indicator();
+
+        assert!(validate_object(&object, None).is_ok());
+        let err = validate_object(&object, Some(&profile)).unwrap_err();
+        assert!(matches!(&err, Error::MissingProperty(p) if p.starts_with("confidence")));
+
+        object["confidence"] = json!(80);
+        assert!(validate_object(&object, Some(&profile)).is_ok());
+
+        object["indicator_types"] = json!([]);
+        let err = validate_object(&object, Some(&profile)).unwrap_err();
+        assert!(matches!(&err, Error::MissingProperty(p) if p.starts_with("indicator_types")));
+    }
+
+    #[test]
+    fn test_profile_ignores_other_types() {
+        let profile = ValidationProfile::new("custom").require("malware", ["description"]);
+        assert!(profile.check(&indicator()).is_ok());
+        assert!(profile.check(&json!({"type": "malware"})).is_err());
+    }
+
+    #[test]
+    fn test_invalid_object_fails_before_profile() {
+        let mut object = indicator();
+        object["pattern_type"] = Value::Null;
+        assert!(validate_object(&object, None).is_err());
+    }
+
+    #[test]
+    fn test_builtin_profiles() {
+        assert_eq!(
+            ValidationProfile::builtin("indicator-sharing"),
+            Some(ValidationProfile::indicator_sharing())
+        );
+        assert!(ValidationProfile::builtin("unknown").is_none());
+
+        let provenance = ValidationProfile::provenance();
+        assert!(provenance.check(&indicator()).is_err());
+        assert!(provenance.check(&json!({"type": "ipv4-addr"})).is_ok());
+    }
+}
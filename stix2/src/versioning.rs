@@ -7,7 +7,8 @@
 //!
 //! - Create new versions with updated timestamps
 //! - Apply property changes when creating new versions
-//! - Revoke objects
+//! - Apply JSON merge patches (RFC 7396) when creating new versions
+//! - Revoke objects, one at a time or in bulk
 //! - Prevent modification of immutable properties
 //!
 //! ## Example
@@ -249,6 +250,51 @@ pub fn revoke(obj: &StixObject) -> Result<StixObject> {
     Ok(new_obj)
 }
 
+/// An object left untouched by [`revoke_all_with_report`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedRevocation {
+    /// ID of the object.
+    pub id: String,
+    /// Why the object was not revoked.
+    pub reason: String,
+}
+
+/// Outcome of revoking many objects at once.
+#[derive(Debug, Clone, Default)]
+pub struct RevokeReport {
+    /// Revoked versions of the objects, in input order.
+    pub revoked: Vec<StixObject>,
+    /// Objects that could not be revoked, in input order.
+    pub skipped: Vec<SkippedRevocation>,
+}
+
+/// Revoke many STIX objects at once.
+///
+/// Applies [`revoke`] to each object: the returned versions have a bumped
+/// `modified` and `revoked` set to `true`, and keep every other property,
+/// including markings. Objects that cannot be revoked (SCOs, already revoked
+/// objects) are left out; use [`revoke_all_with_report`] to know which.
+pub fn revoke_all(objects: Vec<StixObject>) -> Vec<StixObject> {
+    revoke_all_with_report(objects).revoked
+}
+
+/// Revoke many STIX objects at once, reporting the objects skipped.
+///
+/// See [`revoke_all`].
+pub fn revoke_all_with_report(objects: Vec<StixObject>) -> RevokeReport {
+    let mut report = RevokeReport::default();
+    for obj in objects {
+        match revoke(&obj) {
+            Ok(revoked) => report.revoked.push(revoked),
+            Err(e) => report.skipped.push(SkippedRevocation {
+                id: obj.id().to_string(),
+                reason: e.to_string(),
+            }),
+        }
+    }
+    report
+}
+
 fn set_modified(obj: &mut StixObject, modified: Timestamp) {
     match obj {
         StixObject::AttackPattern(o) => o.common.modified = modified,
@@ -424,6 +470,74 @@ pub fn new_version_with_changes(
         .map_err(|e| Error::custom(format!("Failed to deserialize updated object: {e}")))
 }
 
+/// Apply a JSON merge patch (RFC 7396) to a value.
+///
+/// Object members of the patch are merged recursively, `null` members
+/// remove the property, and any other patch value replaces the target.
+pub fn merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(patch_map) = patch else {
+        *target = patch.clone();
+        return;
+    };
+
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    if let Value::Object(target_map) = target {
+        for (key, value) in patch_map {
+            if value.is_null() {
+                target_map.remove(key);
+            } else {
+                merge_patch(target_map.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+    }
+}
+
+/// Create a new version of a STIX object by applying a JSON merge patch
+/// (RFC 7396).
+///
+/// Properties the patch leaves unchanged are not part of the change set, so
+/// a patch may repeat unmodifiable properties such as `id` as long as their
+/// values stay the same.
+///
+/// # Errors
+///
+/// Returns an error if the patch is not a JSON object, changes an
+/// unmodifiable property, or [`new_version_with_changes`] fails.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use stix2::versioning::new_version_with_patch;
+/// use serde_json::json;
+///
+/// let new_indicator = new_version_with_patch(
+///     &indicator,
+///     &json!({"description": "New description", "labels": null}),
+/// )?;
+/// ```
+pub fn new_version_with_patch(obj: &StixObject, patch: &Value) -> Result<StixObject> {
+    let Value::Object(patch_map) = patch else {
+        return Err(Error::validation("Merge patch must be a JSON object"));
+    };
+
+    let original = serde_json::to_value(obj)
+        .map_err(|e| Error::custom(format!("Failed to serialize object: {e}")))?;
+    let mut patched = original.clone();
+    merge_patch(&mut patched, patch);
+
+    let changes: Map<String, Value> = patch_map
+        .keys()
+        .filter_map(|key| {
+            let new = patched.get(key);
+            (new != original.get(key)).then(|| (key.clone(), new.cloned().unwrap_or(Value::Null)))
+        })
+        .collect();
+
+    new_version_with_changes(obj, &changes)
+}
+
 /// Builder for creating new object versions with a fluent API.
 ///
 /// This provides a convenient way to apply multiple property changes
@@ -653,6 +767,59 @@ mod tests {
         assert!(revoke(&revoked_obj).is_err());
     }
 
+    #[test]
+    fn test_revoke_all() {
+        let indicator = Indicator::builder()
+            .name("Test")
+            .pattern("[ipv4-addr:value = '10.0.0.1']")
+            .pattern_type(PatternType::Stix)
+            .valid_from_now()
+            .object_marking_ref(crate::markings::tlp::amber().id)
+            .build()
+            .unwrap();
+        let indicator = StixObject::Indicator(indicator);
+        let address: StixObject = serde_json::from_value(serde_json::json!({
+            "type": "ipv4-addr",
+            "spec_version": "2.1",
+            "id": "ipv4-addr--ff26c055-6336-5bc5-b98d-13d6226742dd",
+            "value": "10.0.0.1"
+        }))
+        .unwrap();
+        let revoked_before = revoke(&indicator).unwrap();
+
+        let report = revoke_all_with_report(vec![
+            indicator.clone(),
+            address.clone(),
+            revoked_before.clone(),
+        ]);
+
+        assert_eq!(report.revoked.len(), 1);
+        let revoked = &report.revoked[0];
+        assert!(is_revoked(revoked));
+        assert_eq!(revoked.id(), indicator.id());
+        assert!(
+            get_modified(revoked).unwrap().datetime()
+                > get_modified(&indicator).unwrap().datetime()
+        );
+        let StixObject::Indicator(revoked_indicator) = revoked else {
+            panic!("expected an indicator");
+        };
+        assert_eq!(
+            revoked_indicator.common.object_marking_refs,
+            vec![crate::markings::tlp::amber().id]
+        );
+        assert_eq!(revoked_indicator.name.as_deref(), Some("Test"));
+
+        let skipped: Vec<_> = report.skipped.iter().map(|s| s.id.clone()).collect();
+        assert_eq!(
+            skipped,
+            vec![address.id().to_string(), revoked_before.id().to_string()]
+        );
+        assert!(report.skipped[0].reason.contains("SCO"));
+
+        assert_eq!(revoke_all(vec![address, indicator]).len(), 1);
+    }
+
     #[test]
     fn test_new_version_with_changes() {
         let indicator = Indicator::builder()
@@ -773,4 +940,21 @@ mod tests {
             panic!("Expected Indicator");
         }
     }
+
+    #[test]
+    fn test_merge_patch() {
+        let mut target = serde_json::json!({
+            "a": "b",
+            "c": {"d": "e", "f": "g"},
+            "list": [1, 2]
+        });
+        merge_patch(
+            &mut target,
+            &serde_json::json!({"a": "z", "c": {"f": null, "h": 1}, "list": [3]}),
+        );
+        assert_eq!(
+            target,
+            serde_json::json!({"a": "z", "c": {"d": "e", "h": 1}, "list": [3]})
+        );
+    }
 }
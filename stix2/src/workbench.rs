@@ -6,6 +6,7 @@
 use std::sync::RwLock;
 
 use once_cell::sync::Lazy;
+use serde_json::Value;
 
 use crate::core::bundle::Bundle;
 use crate::core::error::{Error, Result};
@@ -20,6 +21,7 @@ use crate::objects::{
     Report, ThreatActor, Tool, Vulnerability,
 };
 use crate::relationship::{Relationship, Sighting};
+use crate::versioning::new_version_with_patch;
 
 // Global workbench environment
 static WORKBENCH: Lazy<RwLock<Workbench>> = Lazy::new(|| RwLock::new(Workbench::new()));
@@ -111,6 +113,25 @@ pub fn save(object: StixObject) -> Result<()> {
     wb.env.add(object)
 }
 
+/// Apply a JSON merge patch (RFC 7396) to the latest version of an object
+/// and save the result as a new version.
+///
+/// The `modified` timestamp is bumped unless the patch sets a later one;
+/// unmodifiable properties (`id`, `type`, `created`, `created_by_ref`)
+/// cannot be changed. Returns the new version.
+pub fn patch(id: &Identifier, patch: Value) -> Result<StixObject> {
+    let mut wb = WORKBENCH
+        .write()
+        .map_err(|_| Error::Custom("Failed to acquire workbench lock".to_string()))?;
+    let latest = wb
+        .env
+        .get(id)?
+        .ok_or_else(|| Error::not_found(id.to_string()))?;
+    let new_version = new_version_with_patch(&latest, &patch)?;
+    wb.env.add(new_version.clone())?;
+    Ok(new_version)
+}
+
 /// Parse a STIX JSON string.
 pub fn parse(json: &str) -> Result<StixObject> {
     crate::parse(json)
@@ -514,4 +535,57 @@ mod tests {
 
         clear().unwrap();
     }
+
+    #[test]
+    fn test_workbench_patch() {
+        let _lock = TEST_MUTEX.lock().unwrap();
+
+        clear().unwrap();
+
+        let indicator = Indicator::builder()
+            .name("Test")
+            .description("Original")
+            .pattern("[file:name = 'test.exe']")
+            .pattern_type(PatternType::Stix)
+            .valid_from_now()
+            .build()
+            .unwrap();
+
+        let id = indicator.id.clone();
+        let created = indicator.common.created;
+        save(StixObject::Indicator(indicator)).unwrap();
+
+        // Small delay to ensure timestamp difference
+        std::thread::sleep(std::time::Duration::from_millis(10));
+
+        let patched = patch(
+            &id,
+            serde_json::json!({"id": id.to_string(), "description": "Patched", "name": null}),
+        )
+        .unwrap();
+
+        let versions = all_versions(&id).unwrap();
+        assert_eq!(versions.len(), 2);
+
+        let StixObject::Indicator(latest) = get(&id).unwrap().unwrap() else {
+            panic!("expected an indicator");
+        };
+        assert_eq!(latest.description.as_deref(), Some("Patched"));
+        assert_eq!(latest.name, None);
+        assert_eq!(latest.common.created.to_string(), created.to_string());
+        assert!(latest.common.modified > created);
+        assert_eq!(StixObject::Indicator(latest), patched);
+
+        // Unmodifiable properties cannot be changed
+        assert!(patch(&id, serde_json::json!({"created": "2020-01-01T00:00:00Z"})).is_err());
+        assert!(patch(&id, serde_json::json!(["not", "an", "object"])).is_err());
+
+        let unknown = Identifier::new("indicator").unwrap();
+        assert!(matches!(
+            patch(&unknown, serde_json::json!({})),
+            Err(Error::NotFound(_))
+        ));
+
+        clear().unwrap();
+    }
 }
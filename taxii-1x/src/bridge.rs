@@ -0,0 +1,997 @@
+//! Bridge of inbound STIX 1.x content into TAXII 2.x collections.
+//!
+//! A TAXII 1.x collection may be linked to a TAXII 2.x collection (see
+//! [`taxii_core::CollectionBridgeEntity`]). STIX content received by the
+//! inbox service for a linked collection is converted to STIX 2.1 and added
+//! to the TAXII 2.x collection by [`BridgeWorker`] in the background, once
+//! the inbox message is stored.
+//!
+//! The conversion covers:
+//!
+//! - Observables of IPv4 and IPv6 addresses, domain names, URLs and files
+//!   with hashes, which become cyber observables.
+//! - Indicators whose observable is one of those, or an
+//!   `Observable_Composition` of them, tested for equality. They become
+//!   STIX pattern indicators.
+//!
+//! Every other construct (other object types, other conditions, composite
+//! indicators, TTPs, incidents, ...) is counted as unconverted and logged.
+//! Package header metadata is not carried over.
+//!
+//! Converted objects carry their origin in the [`PROVENANCE_PROPERTY`]
+//! property. Indicator IDs are derived from the STIX 1.x IDs, so
+//! converting a package again yields the same objects.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use quick_xml::Reader;
+use quick_xml::escape::resolve_predefined_entity;
+use quick_xml::events::{BytesStart, Event};
+use serde_json::{Value, json};
+use stix2::observables::generate_sco_id;
+use stix2::vocab::PatternType;
+use stix2::{DomainName, File, IPv4Address, IPv6Address, Identifier, Indicator, Timestamp, Url};
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use taxii_core::{CollectionBridgeEntity, CollisionPolicy, ContentBlockCreatedEvent, SignalEvent};
+use taxii_db::{Taxii1Repository, Taxii2Repository};
+
+use crate::validation::{ContentValidationError, STIX_PACKAGE, is_stix_xml_binding};
+
+/// Custom property recording where a converted object came from.
+pub const PROVENANCE_PROPERTY: &str = "x_taxii1_bridge";
+
+/// Namespace of the indicator IDs derived from STIX 1.x IDs.
+const INDICATOR_NAMESPACE: Uuid = uuid::uuid!("6e2f4c1a-3b8d-5f7e-9a0c-1d2e3f4a5b6c");
+
+/// Depth limit of observable compositions and references.
+const MAX_OBSERVABLE_DEPTH: usize = 16;
+
+/// Where converted content came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Provenance {
+    /// TAXII 1.x collection the content was received for.
+    pub collection_id: i32,
+
+    /// Content block holding the content.
+    pub content_block_id: Option<i32>,
+}
+
+/// STIX 2.1 objects converted from a STIX 1.x package.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Conversion {
+    /// Converted objects, each once.
+    pub objects: Vec<Value>,
+
+    /// Number of constructs that could not be converted, by reason.
+    pub unconverted: BTreeMap<String, usize>,
+}
+
+impl Conversion {
+    /// Total number of constructs that could not be converted.
+    pub fn unconverted_count(&self) -> usize {
+        self.unconverted.values().sum()
+    }
+
+    fn skip(&mut self, reason: impl Into<String>) {
+        *self.unconverted.entry(reason.into()).or_default() += 1;
+    }
+}
+
+/// Convert a STIX 1.x package to STIX 2.1 objects.
+///
+/// Fails only if the content is not a STIX package; constructs that cannot
+/// be converted are reported in [`Conversion::unconverted`].
+pub fn convert_package(
+    content: &[u8],
+    provenance: &Provenance,
+) -> Result<Conversion, ContentValidationError> {
+    let package = parse_element(content)?;
+    if package.name != STIX_PACKAGE {
+        return Err(ContentValidationError::NotStix(format!(
+            "root element is {}",
+            package.name
+        )));
+    }
+
+    let mut converter = Converter::new(&package, provenance);
+    for section in &package.children {
+        match section.name.as_str() {
+            "STIX_Header" => {}
+            "Observables" => {
+                for observable in section.children_named("Observable") {
+                    converter.convert_observable(observable);
+                }
+            }
+            "Indicators" => {
+                for indicator in section.children_named("Indicator") {
+                    converter.convert_indicator(indicator);
+                }
+            }
+            _ if section.children.is_empty() => converter.conversion.skip(section.name.clone()),
+            _ => {
+                for construct in &section.children {
+                    converter.conversion.skip(construct.name.clone());
+                }
+            }
+        }
+    }
+    Ok(converter.conversion)
+}
+
+/// Worker converting the STIX content of new content blocks into the TAXII
+/// 2.x collections their collections are linked to.
+///
+/// Bridged objects are added like objects posted to the TAXII 2.x API: they
+/// get the collection's default marking, objects failing its validation
+/// rules are rejected, and versions colliding with stored ones are handled
+/// by the collision policy. Failures are logged; the content stays stored in
+/// TAXII 1.x either way.
+///
+/// # Example
+///
+/// ```ignore
+/// let worker = BridgeWorker::new(DbTaxii1Repository::new(pool.clone()), DbTaxii2Repository::new(pool));
+/// tokio::spawn(worker.listen(hooks.subscribe()));
+/// ```
+pub struct BridgeWorker<R1, R2> {
+    taxii1: R1,
+    taxii2: R2,
+    policy: CollisionPolicy,
+}
+
+impl<R1: Taxii1Repository, R2: Taxii2Repository> BridgeWorker<R1, R2> {
+    /// Create a worker rejecting objects that collide with stored versions.
+    pub fn new(taxii1: R1, taxii2: R2) -> Self {
+        Self {
+            taxii1,
+            taxii2,
+            policy: CollisionPolicy::Reject,
+        }
+    }
+
+    /// Set how bridged objects colliding with stored versions are handled.
+    #[must_use]
+    pub fn with_collision_policy(mut self, policy: CollisionPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Bridge content blocks created until the hook registry is dropped.
+    ///
+    /// Blocks whose events are missed because the receiver lagged behind
+    /// are not bridged.
+    pub async fn listen(self, mut events: broadcast::Receiver<SignalEvent>) {
+        loop {
+            let event = match events.recv().await {
+                Ok(SignalEvent::ContentBlockCreated(event)) => event,
+                Ok(_) => continue,
+                Err(RecvError::Lagged(missed)) => {
+                    warn!(missed, "Bridge missed content block events");
+                    continue;
+                }
+                Err(RecvError::Closed) => return,
+            };
+            self.bridge(std::slice::from_ref(&event)).await;
+        }
+    }
+
+    /// Convert the STIX content of stored blocks into the TAXII 2.x
+    /// collections their collections are linked to.
+    pub async fn bridge(&self, events: &[ContentBlockCreatedEvent]) {
+        let collection_ids: Vec<i32> = events
+            .iter()
+            .flat_map(|e| e.collection_ids.iter().copied())
+            .collect();
+        if collection_ids.is_empty() {
+            return;
+        }
+
+        let bridges = match self.taxii1.get_collection_bridges(&collection_ids).await {
+            Ok(bridges) => bridges,
+            Err(e) => {
+                warn!(error = %e, "Failed to look up collection bridges");
+                return;
+            }
+        };
+
+        for bridge in &bridges {
+            let objects = convert_blocks(bridge, events);
+            if objects.is_empty() {
+                continue;
+            }
+            if let Err(e) = self.add_objects(bridge, objects).await {
+                warn!(
+                    collection = bridge.data_collection_id,
+                    taxii2_collection = %bridge.collection_id,
+                    error = %e,
+                    "Failed to add bridged objects"
+                );
+            }
+        }
+    }
+
+    /// Add converted objects the way the TAXII 2.x API adds posted ones.
+    async fn add_objects(
+        &self,
+        bridge: &CollectionBridgeEntity,
+        mut objects: Vec<Value>,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let collection = self
+            .taxii2
+            .get_collection(&bridge.api_root_id, &bridge.collection_id)
+            .await?
+            .ok_or("linked collection not found")?;
+
+        for object in &mut objects {
+            collection.apply_default_marking(object)?;
+        }
+        let rejected: BTreeMap<usize, String> = objects
+            .iter()
+            .enumerate()
+            .filter_map(|(index, obj)| collection.check_object(obj).err().map(|msg| (index, msg)))
+            .collect();
+
+        let report = self
+            .taxii2
+            .add_objects_bulk_with_rejections(
+                &bridge.api_root_id,
+                &collection.id,
+                &objects,
+                &rejected,
+                self.policy,
+                None,
+            )
+            .await?;
+        if report.job.failure_count > 0 {
+            warn!(
+                collection = bridge.data_collection_id,
+                taxii2_collection = %bridge.collection_id,
+                job = %report.job.id,
+                failures = report.job.failure_count,
+                "Bridged objects rejected"
+            );
+        }
+        debug!(
+            collection = bridge.data_collection_id,
+            taxii2_collection = %bridge.collection_id,
+            job = %report.job.id,
+            objects = report.job.success_count,
+            "Bridged STIX 1.x content"
+        );
+        Ok(())
+    }
+}
+
+/// Convert the STIX content of the blocks created for a linked collection.
+fn convert_blocks(
+    bridge: &CollectionBridgeEntity,
+    events: &[ContentBlockCreatedEvent],
+) -> Vec<Value> {
+    let mut objects = Vec::new();
+    for event in events
+        .iter()
+        .filter(|e| e.collection_ids.contains(&bridge.data_collection_id))
+    {
+        let block = &event.content_block;
+        let provenance = Provenance {
+            collection_id: bridge.data_collection_id,
+            content_block_id: block.id,
+        };
+        let conversion = match &block.content_binding {
+            Some(binding) if is_stix_xml_binding(&binding.binding) => {
+                convert_package(&block.content, &provenance)
+            }
+            Some(binding) => Err(ContentValidationError::NotStix(format!(
+                "content binding {}",
+                binding.binding
+            ))),
+            None => Err(ContentValidationError::NotStix(
+                "no content binding".to_string(),
+            )),
+        };
+
+        match conversion {
+            Ok(conversion) => {
+                if conversion.unconverted_count() > 0 {
+                    warn!(
+                        collection = bridge.data_collection_id,
+                        content_block = ?block.id,
+                        unconverted = conversion.unconverted_count(),
+                        constructs = ?conversion.unconverted,
+                        "STIX 1.x constructs not bridged"
+                    );
+                }
+                objects.extend(conversion.objects);
+            }
+            Err(e) => warn!(
+                collection = bridge.data_collection_id,
+                content_block = ?block.id,
+                error = %e,
+                "Content block not bridged"
+            ),
+        }
+    }
+    objects
+}
+
+// ============================================================================
+// Conversion
+// ============================================================================
+
+/// A value of a supported observable object.
+#[derive(Debug, Clone, PartialEq)]
+enum Observed {
+    Ipv4(String),
+    Ipv6(String),
+    Domain(String),
+    Url(String),
+    File {
+        name: Option<String>,
+        hashes: Vec<(String, String)>,
+    },
+}
+
+impl Observed {
+    /// STIX 2.1 cyber observable of the value.
+    fn to_sco(&self) -> stix2::Result<Value> {
+        let value = match self {
+            Self::Ipv4(v) => serde_json::to_value(IPv4Address::new(v)?)?,
+            Self::Ipv6(v) => serde_json::to_value(IPv6Address::new(v)?)?,
+            Self::Domain(v) => serde_json::to_value(DomainName::new(v)?)?,
+            Self::Url(v) => serde_json::to_value(Url::new(v)?)?,
+            Self::File { name, hashes } => {
+                let mut builder = File::builder();
+                for (algorithm, hash) in hashes {
+                    builder = builder.hash(algorithm, hash);
+                }
+                if let Some(name) = name {
+                    builder = builder.name(name);
+                }
+                let mut file = builder.build()?;
+                file.id = generate_sco_id(
+                    File::TYPE,
+                    &json!({ "hashes": file.hashes, "name": file.name }),
+                )?;
+                serde_json::to_value(file)?
+            }
+        };
+        Ok(value)
+    }
+
+    /// STIX 2.1 comparison expressions matching the value, any of which
+    /// matches.
+    fn comparisons(&self) -> Vec<String> {
+        match self {
+            Self::Ipv4(v) => vec![format!("ipv4-addr:value = '{}'", quote(v))],
+            Self::Ipv6(v) => vec![format!("ipv6-addr:value = '{}'", quote(v))],
+            Self::Domain(v) => vec![format!("domain-name:value = '{}'", quote(v))],
+            Self::Url(v) => vec![format!("url:value = '{}'", quote(v))],
+            Self::File { name, hashes } => match name {
+                Some(name) if hashes.is_empty() => vec![format!("file:name = '{}'", quote(name))],
+                _ => hashes
+                    .iter()
+                    .map(|(algorithm, hash)| match algorithm.contains('-') {
+                        true => format!("file:hashes.'{algorithm}' = '{}'", quote(hash)),
+                        false => format!("file:hashes.{algorithm} = '{}'", quote(hash)),
+                    })
+                    .collect(),
+            },
+        }
+    }
+}
+
+/// Escape a string for a STIX pattern literal.
+fn quote(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+struct Converter<'a> {
+    /// Package observables by ID, for references.
+    observables: HashMap<&'a str, &'a Element>,
+    /// Package creation time, for indicators without one.
+    package_timestamp: Option<&'a str>,
+    provenance: &'a Provenance,
+    seen: HashSet<String>,
+    conversion: Conversion,
+}
+
+impl<'a> Converter<'a> {
+    fn new(package: &'a Element, provenance: &'a Provenance) -> Self {
+        let observables = package
+            .children_named("Observables")
+            .flat_map(|section| section.children_named("Observable"))
+            .filter_map(|o| o.attribute("id").map(|id| (id, o)))
+            .collect();
+        Self {
+            observables,
+            package_timestamp: package.attribute("timestamp"),
+            provenance,
+            seen: HashSet::new(),
+            conversion: Conversion::default(),
+        }
+    }
+
+    /// Add a converted object, once.
+    fn push(&mut self, mut object: Value, stix1_id: Option<&str>) {
+        let Some(id) = object["id"].as_str().map(str::to_string) else {
+            return;
+        };
+        if !self.seen.insert(id) {
+            return;
+        }
+        object[PROVENANCE_PROPERTY] = json!({
+            "collection_id": self.provenance.collection_id,
+            "content_block_id": self.provenance.content_block_id,
+            "stix1_id": stix1_id,
+        });
+        self.conversion.objects.push(object);
+    }
+
+    /// Convert a package observable to cyber observables.
+    fn convert_observable(&mut self, observable: &Element) {
+        let observed = match observable.child("Object") {
+            Some(object) => observed_values(object),
+            None if observable.attribute("idref").is_some() => return,
+            None => Err(observable
+                .children
+                .first()
+                .map_or("empty", |c| c.name.as_str())
+                .to_string()),
+        };
+
+        let scos = observed.and_then(|values| {
+            values
+                .iter()
+                .map(|v| v.to_sco().map_err(|e| e.to_string()))
+                .collect::<Result<Vec<_>, _>>()
+        });
+        match scos {
+            Ok(scos) => {
+                for sco in scos {
+                    self.push(sco, observable.attribute("id"));
+                }
+            }
+            Err(reason) => self.conversion.skip(format!("Observable: {reason}")),
+        }
+    }
+
+    /// Convert an indicator to a pattern indicator.
+    fn convert_indicator(&mut self, indicator: &Element) {
+        match self.build_indicator(indicator) {
+            Ok(Some(object)) => self.push(object, indicator.attribute("id")),
+            Ok(None) => {}
+            Err(reason) => self.conversion.skip(format!("Indicator: {reason}")),
+        }
+    }
+
+    fn build_indicator(&self, indicator: &Element) -> Result<Option<Value>, String> {
+        let Some(stix1_id) = indicator.attribute("id") else {
+            return match indicator.attribute("idref") {
+                Some(_) => Ok(None),
+                None => Err("no id".to_string()),
+            };
+        };
+        if indicator.child("Composite_Indicator_Expression").is_some() {
+            return Err("composite indicator expression".to_string());
+        }
+        let observable = indicator
+            .child("Observable")
+            .ok_or_else(|| "no observable".to_string())?;
+        let pattern = self.pattern(observable, 0)?.text;
+
+        let timestamp = indicator.attribute("timestamp").or(self.package_timestamp);
+        let created = timestamp.and_then(|t| t.parse::<Timestamp>().ok());
+        let valid_time = indicator.child("Valid_Time_Position");
+        let valid_from = valid_time
+            .and_then(|v| v.child("Start_Time"))
+            .and_then(|t| t.text.trim().parse::<Timestamp>().ok())
+            .or(created)
+            .unwrap_or_else(Timestamp::now);
+
+        let mut builder = Indicator::builder()
+            .pattern(pattern)
+            .pattern_type(PatternType::Stix)
+            .valid_from(valid_from);
+        if let Some(valid_until) = valid_time
+            .and_then(|v| v.child("End_Time"))
+            .and_then(|t| t.text.trim().parse::<Timestamp>().ok())
+        {
+            builder = builder.valid_until(valid_until);
+        }
+        if let Some(title) = indicator.child_text("Title") {
+            builder = builder.name(title);
+        }
+        if let Some(description) = indicator.child_text("Description") {
+            builder = builder.description(description);
+        }
+
+        let mut built = builder.build().map_err(|e| e.to_string())?;
+        built.id = Identifier::deterministic(Indicator::TYPE, INDICATOR_NAMESPACE, stix1_id)
+            .map_err(|e| e.to_string())?;
+        if let Some(created) = created {
+            built.common.created = created;
+            built.common.modified = created;
+        }
+        serde_json::to_value(built)
+            .map(Some)
+            .map_err(|e| e.to_string())
+    }
+
+    /// STIX 2.1 pattern of an indicator observable.
+    ///
+    /// Each comparison is an observation of its own, alternatives joined
+    /// with `OR`.
+    fn pattern(&self, observable: &Element, depth: usize) -> Result<Expression, String> {
+        if depth > MAX_OBSERVABLE_DEPTH {
+            return Err("observable nested too deeply".to_string());
+        }
+        if let Some(idref) = observable.attribute("idref") {
+            let referenced = self
+                .observables
+                .get(idref)
+                .ok_or_else(|| format!("unresolved observable reference {idref}"))?;
+            return self.pattern(referenced, depth + 1);
+        }
+        if let Some(composition) = observable.child("Observable_Composition") {
+            let operator = match composition.attribute("operator") {
+                Some("AND") => "AND",
+                Some("OR") => "OR",
+                other => return Err(format!("composition operator {other:?}")),
+            };
+            let operands = composition
+                .children_named("Observable")
+                .map(|o| self.pattern(o, depth + 1))
+                .collect::<Result<Vec<_>, _>>()?;
+            if operands.is_empty() {
+                return Err("empty composition".to_string());
+            }
+            return Ok(Expression::join(operands, operator));
+        }
+
+        let object = observable
+            .child("Object")
+            .ok_or_else(|| "observable without object".to_string())?;
+        let observations = observed_values(object)?
+            .iter()
+            .flat_map(Observed::comparisons)
+            .map(|c| Expression {
+                text: format!("[{c}]"),
+                compound: false,
+            })
+            .collect();
+        Ok(Expression::join(observations, "OR"))
+    }
+}
+
+/// Observation expression of a pattern.
+struct Expression {
+    text: String,
+    /// Whether it joins several observations.
+    compound: bool,
+}
+
+impl Expression {
+    /// Join expressions, grouping compound ones.
+    fn join(mut expressions: Vec<Expression>, operator: &str) -> Self {
+        if expressions.len() == 1
+            && let Some(expression) = expressions.pop()
+        {
+            return expression;
+        }
+        let text = expressions
+            .iter()
+            .map(|e| match e.compound {
+                true => format!("({})", e.text),
+                false => e.text.clone(),
+            })
+            .collect::<Vec<_>>()
+            .join(&format!(" {operator} "));
+        Self {
+            text,
+            compound: true,
+        }
+    }
+}
+
+/// Values of a supported observable object.
+///
+/// A value may list several alternatives separated by `##comma##`.
+fn observed_values(object: &Element) -> Result<Vec<Observed>, String> {
+    let properties = object
+        .child("Properties")
+        .ok_or_else(|| "object without properties".to_string())?;
+    let object_type = properties.xsi_type().unwrap_or("untyped object");
+
+    let values = |name: &str| -> Result<Vec<String>, String> {
+        let value = properties
+            .child(name)
+            .ok_or_else(|| format!("{object_type} without {name}"))?;
+        check_condition(value)?;
+        Ok(split_values(&value.text))
+    };
+
+    let observed = match object_type {
+        "AddressObjectType" => match properties.attribute("category").unwrap_or("ipv4-addr") {
+            "ipv4-addr" => values("Address_Value")?
+                .into_iter()
+                .map(Observed::Ipv4)
+                .collect(),
+            "ipv6-addr" => values("Address_Value")?
+                .into_iter()
+                .map(Observed::Ipv6)
+                .collect(),
+            category => return Err(format!("{object_type} category {category}")),
+        },
+        "DomainNameObjectType" => values("Value")?.into_iter().map(Observed::Domain).collect(),
+        "URIObjectType" => match properties.attribute("type").unwrap_or("URL") {
+            "URL" => values("Value")?.into_iter().map(Observed::Url).collect(),
+            uri_type => return Err(format!("{object_type} type {uri_type}")),
+        },
+        "FileObjectType" => vec![file_value(properties)?],
+        _ => return Err(object_type.to_string()),
+    };
+    if observed.is_empty() {
+        return Err(format!("{object_type} without value"));
+    }
+    Ok(observed)
+}
+
+/// Name and hashes of a file object.
+fn file_value(properties: &Element) -> Result<Observed, String> {
+    let name = match properties.child("File_Name") {
+        Some(name) => {
+            check_condition(name)?;
+            Some(name.text.trim().to_string()).filter(|n| !n.is_empty())
+        }
+        None => None,
+    };
+
+    let mut hashes = Vec::new();
+    for hash in properties
+        .children_named("Hashes")
+        .flat_map(|h| h.children_named("Hash"))
+    {
+        let hash_type = hash.child_text("Type").unwrap_or_default();
+        let algorithm = match hash_type.to_ascii_uppercase().replace('-', "").as_str() {
+            "MD5" => "MD5",
+            "SHA1" => "SHA-1",
+            "SHA224" => "SHA-224",
+            "SHA256" => "SHA-256",
+            "SHA384" => "SHA-384",
+            "SHA512" => "SHA-512",
+            "SSDEEP" => "SSDEEP",
+            _ => return Err(format!("FileObjectType hash type {hash_type}")),
+        };
+        let value = hash
+            .child("Simple_Hash_Value")
+            .ok_or_else(|| "FileObjectType hash without Simple_Hash_Value".to_string())?;
+        check_condition(value)?;
+        hashes.push((algorithm.to_string(), value.text.trim().to_string()));
+    }
+
+    if name.is_none() && hashes.is_empty() {
+        return Err("FileObjectType without name or hashes".to_string());
+    }
+    Ok(Observed::File { name, hashes })
+}
+
+/// Only equality, the default, converts to STIX 2.1 as is.
+fn check_condition(value: &Element) -> Result<(), String> {
+    match value.attribute("condition") {
+        None | Some("Equals") => Ok(()),
+        Some(condition) => Err(format!("condition {condition}")),
+    }
+}
+
+fn split_values(text: &str) -> Vec<String> {
+    text.split("##comma##")
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+// ============================================================================
+// XML
+// ============================================================================
+
+/// Element of a parsed document.
+///
+/// Elements are named by local name, attributes by qualified name.
+#[derive(Debug, Default)]
+struct Element {
+    name: String,
+    attributes: Vec<(String, String)>,
+    text: String,
+    children: Vec<Element>,
+}
+
+impl Element {
+    fn from_start(start: &BytesStart<'_>) -> Result<Self, ContentValidationError> {
+        let malformed =
+            |e: &dyn std::fmt::Display| ContentValidationError::Malformed(e.to_string());
+        let mut attributes = Vec::new();
+        for attribute in start.attributes() {
+            let attribute = attribute.map_err(|e| malformed(&e))?;
+            let name = String::from_utf8_lossy(attribute.key.as_ref()).into_owned();
+            let value = attribute.unescape_value().map_err(|e| malformed(&e))?;
+            attributes.push((name, value.into_owned()));
+        }
+        Ok(Self {
+            name: String::from_utf8_lossy(start.local_name().as_ref()).into_owned(),
+            attributes,
+            ..Self::default()
+        })
+    }
+
+    /// Unprefixed attribute.
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Local part of the `xsi:type` attribute.
+    fn xsi_type(&self) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| n.ends_with(":type"))
+            .map(|(_, t)| t.rsplit_once(':').map_or(t.as_str(), |(_, local)| local))
+    }
+
+    fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|c| c.name == name)
+    }
+
+    fn children_named<'s>(&'s self, name: &'s str) -> impl Iterator<Item = &'s Element> {
+        self.children.iter().filter(move |c| c.name == name)
+    }
+
+    fn child_text(&self, name: &str) -> Option<String> {
+        self.child(name)
+            .map(|c| c.text.trim().to_string())
+            .filter(|t| !t.is_empty())
+    }
+}
+
+/// Parse a document into its root element.
+fn parse_element(content: &[u8]) -> Result<Element, ContentValidationError> {
+    let malformed = |e: &dyn std::fmt::Display| ContentValidationError::Malformed(e.to_string());
+    let xml = std::str::from_utf8(content)
+        .map_err(|e| ContentValidationError::Malformed(format!("invalid UTF-8: {e}")))?;
+
+    let mut reader = Reader::from_str(xml);
+    let mut stack: Vec<Element> = Vec::new();
+    loop {
+        match reader.read_event().map_err(|e| malformed(&e))? {
+            Event::Start(start) => stack.push(Element::from_start(&start)?),
+            Event::Empty(start) => {
+                let element = Element::from_start(&start)?;
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(element),
+                    None => return Ok(element),
+                }
+            }
+            Event::End(_) => {
+                let element = stack
+                    .pop()
+                    .ok_or_else(|| malformed(&"unbalanced end tag"))?;
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(element),
+                    None => return Ok(element),
+                }
+            }
+            Event::Text(text) => {
+                if let Some(element) = stack.last_mut() {
+                    element
+                        .text
+                        .push_str(&text.decode().map_err(|e| malformed(&e))?);
+                }
+            }
+            Event::CData(data) => {
+                if let Some(element) = stack.last_mut() {
+                    element
+                        .text
+                        .push_str(&data.decode().map_err(|e| malformed(&e))?);
+                }
+            }
+            Event::GeneralRef(reference) => {
+                let Some(element) = stack.last_mut() else {
+                    continue;
+                };
+                if let Some(c) = reference.resolve_char_ref().map_err(|e| malformed(&e))? {
+                    element.text.push(c);
+                } else {
+                    let name = reference.decode().map_err(|e| malformed(&e))?;
+                    let resolved = resolve_predefined_entity(&name)
+                        .ok_or_else(|| malformed(&format!("unknown entity &{name};")))?;
+                    element.text.push_str(resolved);
+                }
+            }
+            Event::Eof => return Err(malformed(&"no root element")),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PACKAGE: &str = include_str!("../tests/fixtures/stix_package_111.xml");
+
+    type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+    const PROVENANCE: Provenance = Provenance {
+        collection_id: 7,
+        content_block_id: Some(42),
+    };
+
+    fn by_type<'a>(conversion: &'a Conversion, object_type: &str) -> Vec<&'a Value> {
+        conversion
+            .objects
+            .iter()
+            .filter(|o| o["type"] == object_type)
+            .collect()
+    }
+
+    #[test]
+    fn test_convert_package_observables() -> TestResult {
+        let conversion = convert_package(PACKAGE.as_bytes(), &PROVENANCE)?;
+
+        let ipv4: Vec<&Value> = by_type(&conversion, "ipv4-addr")
+            .into_iter()
+            .map(|o| &o["value"])
+            .collect();
+        assert_eq!(ipv4, [&json!("198.51.100.17"), &json!("198.51.100.18")]);
+        let ipv6 = by_type(&conversion, "ipv6-addr");
+        assert_eq!(ipv6.len(), 1);
+        assert_eq!(ipv6[0]["value"], "2001:db8::dead:beef");
+
+        let files = by_type(&conversion, "file");
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0]["name"], "invoice_0515.exe");
+        assert_eq!(
+            files[0]["hashes"]["MD5"],
+            "4d2a1b0b8b1d6f0c2e5a7b9c3d4e5f60"
+        );
+        assert_eq!(
+            files[0]["hashes"]["SHA-256"],
+            "ef537f25c895bfa782526529a9b63d97aa631564d5d789c2b765448c8635fb6c"
+        );
+        assert_eq!(
+            files[0][PROVENANCE_PROPERTY],
+            json!({
+                "collection_id": 7,
+                "content_block_id": 42,
+                "stix1_id": "opensource:Observable-2b3c4d5e-6f70-4b81-9ca2-b3c4d5e6f708"
+            })
+        );
+
+        for object in &conversion.objects {
+            stix2::parse(&object.to_string())?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_package_indicators() -> TestResult {
+        let conversion = convert_package(PACKAGE.as_bytes(), &PROVENANCE)?;
+        let indicators = by_type(&conversion, "indicator");
+        let patterns: Vec<&str> = indicators
+            .iter()
+            .filter_map(|i| i["pattern"].as_str())
+            .collect();
+        assert_eq!(
+            patterns,
+            [
+                "[ipv4-addr:value = '203.0.113.5']",
+                "[file:hashes.MD5 = '4d2a1b0b8b1d6f0c2e5a7b9c3d4e5f60'] OR [file:hashes.'SHA-256' = 'ef537f25c895bfa782526529a9b63d97aa631564d5d789c2b765448c8635fb6c']",
+                "[domain-name:value = 'invoice-portal.example'] OR [url:value = 'http://invoice-portal.example/login.php?id=0515']",
+            ]
+        );
+
+        let c2 = indicators[0];
+        assert_eq!(c2["name"], "C2 server: 203.0.113.5");
+        assert_eq!(
+            c2["description"],
+            "Command and control server contacted by the dropper."
+        );
+        assert_eq!(c2["created"], "2015-05-15T09:00:00.000000Z");
+        assert_eq!(c2["valid_from"], c2["created"]);
+        assert_eq!(
+            c2[PROVENANCE_PROPERTY]["stix1_id"],
+            "opensource:indicator-5e6f7081-92a3-4eb4-8fd5-e6f708192a3b"
+        );
+
+        let dropper = indicators[1];
+        assert_eq!(dropper["valid_from"], "2015-05-01T00:00:00Z");
+        assert_eq!(dropper["valid_until"], "2015-08-01T00:00:00Z");
+        assert_eq!(indicators[2]["name"], "Phishing landing domains & URL");
+
+        let again = convert_package(PACKAGE.as_bytes(), &PROVENANCE)?;
+        assert_eq!(again.objects, conversion.objects);
+        for indicator in indicators {
+            stix2::parse(&indicator.to_string())?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_package_counts_unconverted() -> TestResult {
+        let conversion = convert_package(PACKAGE.as_bytes(), &PROVENANCE)?;
+        assert_eq!(
+            conversion.unconverted,
+            BTreeMap::from([
+                ("Indicator: composite indicator expression".to_string(), 1),
+                ("Indicator: condition Contains".to_string(), 1),
+                (
+                    "Observable: AddressObjectType category e-mail".to_string(),
+                    1
+                ),
+                ("Observable: EmailMessageObjectType".to_string(), 1),
+                ("TTP".to_string(), 1),
+            ])
+        );
+        assert_eq!(conversion.unconverted_count(), 5);
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_groups_compositions() -> TestResult {
+        let package = r#"<stix:STIX_Package xmlns:stix="http://stix.mitre.org/stix-1"
+            xmlns:cybox="http://cybox.mitre.org/cybox-2"
+            xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" id="example:Package-1">
+          <stix:Indicators>
+            <stix:Indicator id="example:indicator-1" timestamp="2015-05-15T09:00:00Z">
+              <indicator:Observable xmlns:indicator="http://stix.mitre.org/Indicator-2">
+                <cybox:Observable_Composition operator="AND">
+                  <cybox:Observable><cybox:Object>
+                    <cybox:Properties xsi:type="FileObj:FileObjectType">
+                      <FileObj:Hashes>
+                        <cyboxCommon:Hash><cyboxCommon:Type>MD5</cyboxCommon:Type>
+                          <cyboxCommon:Simple_Hash_Value>aa</cyboxCommon:Simple_Hash_Value>
+                        </cyboxCommon:Hash>
+                        <cyboxCommon:Hash><cyboxCommon:Type>SHA1</cyboxCommon:Type>
+                          <cyboxCommon:Simple_Hash_Value>bb</cyboxCommon:Simple_Hash_Value>
+                        </cyboxCommon:Hash>
+                      </FileObj:Hashes>
+                    </cybox:Properties>
+                  </cybox:Object></cybox:Observable>
+                  <cybox:Observable><cybox:Object>
+                    <cybox:Properties xsi:type="AddressObj:AddressObjectType">
+                      <AddressObj:Address_Value>10.0.0.1</AddressObj:Address_Value>
+                    </cybox:Properties>
+                  </cybox:Object></cybox:Observable>
+                </cybox:Observable_Composition>
+              </indicator:Observable>
+            </stix:Indicator>
+          </stix:Indicators>
+        </stix:STIX_Package>"#;
+
+        let conversion = convert_package(package.as_bytes(), &PROVENANCE)?;
+        assert_eq!(conversion.unconverted_count(), 0);
+        assert_eq!(
+            conversion.objects[0]["pattern"],
+            "([file:hashes.MD5 = 'aa'] OR [file:hashes.'SHA-1' = 'bb']) AND [ipv4-addr:value = '10.0.0.1']"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_rejects_non_packages() {
+        assert!(matches!(
+            convert_package(b"<Other/>", &PROVENANCE),
+            Err(ContentValidationError::NotStix(_))
+        ));
+        assert!(matches!(
+            convert_package(b"<STIX_Package>", &PROVENANCE),
+            Err(ContentValidationError::Malformed(_))
+        ));
+    }
+}
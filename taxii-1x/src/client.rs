@@ -0,0 +1,1380 @@
+//! TAXII 1.x client for remote services.
+//!
+//! [`Taxii1Client`] talks to the services of remote TAXII 1.x servers over
+//! HTTP or HTTPS: it discovers their services, polls their collections and
+//! pushes content to their inboxes. Requests built by the client use its
+//! message binding, TAXII 1.1 unless configured otherwise, and are sent with
+//! the TAXII headers of that binding. Poll results split in parts are
+//! fetched with Poll Fulfillment by [`Taxii1Client::poll_all`] (TAXII 1.1
+//! only).
+//!
+//! A Status Message other than `SUCCESS` from the remote service is
+//! returned as [`Taxii1xError::StatusMessage`], whatever the HTTP status of
+//! the response. HTTPS services requiring client authentication are
+//! supported with [`Taxii1ClientBuilder::client_certificate`]. Redirects
+//! are not followed, responses are read up to
+//! [`Taxii1ClientBuilder::max_response_size`] bytes and
+//! [`Taxii1Client::poll_all`] fetches up to
+//! [`Taxii1ClientBuilder::max_poll_parts`] parts. A client
+//! built with [`Taxii1ClientBuilder::public_addresses_only`] refuses to
+//! connect to anything but public addresses (see [`is_public_address`]),
+//! however the host names it is given resolve.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let client = Taxii1Client::builder()
+//!     .client_certificate(std::fs::read("client.pem")?)
+//!     .build()?;
+//! let blocks = client
+//!     .poll_all("https://taxii.example.com/services/poll/", "collection-a", &PollOptions::default())
+//!     .await?;
+//! ```
+
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use reqwest::{Certificate, Identity};
+use taxii_core::{ContentBindingEntity, ContentBlockEntity, format_timestamp_label};
+
+use crate::constants::{RT_FULL, StatusType, VID_TAXII_XML_10, VID_TAXII_XML_11};
+use crate::content::{binding_encoding, decode_content, encode_content};
+use crate::error::{Taxii1xError, Taxii1xResult};
+use crate::handlers::generate_id;
+use crate::http::{
+    HTTP_X_TAXII_ACCEPT, HTTP_X_TAXII_CONTENT_TYPE, SUPPORTED_MESSAGE_BINDINGS, get_http_headers,
+};
+use crate::messages::{NS_TAXII_10, NS_TAXII_11, TaxiiMessage, get_message_from_xml, tm10, tm11};
+
+/// Default timeout of a request to a remote service.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Default maximum size of a response of a remote service, in bytes.
+pub const DEFAULT_MAX_RESPONSE_SIZE: usize = 100 * 1024 * 1024;
+
+/// Default maximum number of parts of poll results fetched by
+/// [`Taxii1Client::poll_all`].
+pub const DEFAULT_MAX_POLL_PARTS: usize = 1000;
+
+/// Client for remote TAXII 1.x services.
+///
+/// Cheap to clone; clones share their connection pool.
+#[derive(Debug, Clone)]
+pub struct Taxii1Client {
+    http: reqwest::Client,
+    message_binding: &'static str,
+    timeout: Duration,
+    credentials: Option<(String, String)>,
+    public_only: bool,
+    max_response_size: usize,
+    max_poll_parts: usize,
+}
+
+impl Default for Taxii1Client {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Taxii1Client {
+    /// Create a TAXII 1.1 client with the default settings.
+    #[must_use]
+    pub fn new() -> Self {
+        // Only fails where reqwest::Client::new panics too
+        let http = http_client()
+            .build()
+            .unwrap_or_else(|_| reqwest::Client::new());
+        Self {
+            http,
+            message_binding: VID_TAXII_XML_11,
+            timeout: DEFAULT_TIMEOUT,
+            credentials: None,
+            public_only: false,
+            max_response_size: DEFAULT_MAX_RESPONSE_SIZE,
+            max_poll_parts: DEFAULT_MAX_POLL_PARTS,
+        }
+    }
+
+    /// Create a builder for a configured client.
+    #[must_use]
+    pub fn builder() -> Taxii1ClientBuilder {
+        Taxii1ClientBuilder::default()
+    }
+
+    /// Message binding of the requests built by the client.
+    pub fn message_binding(&self) -> &'static str {
+        self.message_binding
+    }
+
+    /// A client sharing this one's connection pool and settings, building
+    /// requests in another message binding.
+    ///
+    /// Fails if the message binding is unknown.
+    pub fn with_message_binding(&self, message_binding: &str) -> Taxii1xResult<Self> {
+        Ok(Self {
+            message_binding: known_binding(Some(message_binding))?,
+            ..self.clone()
+        })
+    }
+
+    fn is_taxii_10(&self) -> bool {
+        self.message_binding == VID_TAXII_XML_10
+    }
+
+    /// Send a message to a remote service, returning its response.
+    ///
+    /// The message is sent with the headers of its own message binding.
+    /// Status Messages other than `SUCCESS` are returned as
+    /// [`Taxii1xError::StatusMessage`]. Redirects are not followed, and
+    /// responses larger than the maximum response size are refused.
+    pub async fn send(&self, address: &str, message: &TaxiiMessage) -> Taxii1xResult<TaxiiMessage> {
+        // Host names are checked by the resolver, literal addresses here
+        if self.public_only {
+            let literal = reqwest::Url::parse(address)
+                .ok()
+                .and_then(|url| url.host_str().map(str::to_string))
+                .and_then(|host| {
+                    host.trim_start_matches('[')
+                        .trim_end_matches(']')
+                        .parse::<IpAddr>()
+                        .ok()
+                });
+            if let Some(ip) = literal.filter(|ip| !is_public_address(*ip)) {
+                return Err(Taxii1xError::Http(format!(
+                    "Refusing to connect to the non-public address {ip}"
+                )));
+            }
+        }
+
+        let version = message.version();
+        let headers = get_http_headers(version, address.starts_with("https://"))?;
+        let body = message.to_xml()?;
+
+        let mut request = self
+            .http
+            .post(address)
+            .timeout(self.timeout)
+            .header(HTTP_X_TAXII_ACCEPT, version)
+            .body(body);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        if let Some((username, password)) = &self.credentials {
+            request = request.basic_auth(username, Some(password));
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| Taxii1xError::Http(e.to_string()))?;
+        let status = response.status();
+        if let Some(content_type) = response
+            .headers()
+            .get(HTTP_X_TAXII_CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+        {
+            if status.is_success() && !SUPPORTED_MESSAGE_BINDINGS.contains(&content_type) {
+                return Err(Taxii1xError::UnsupportedContentType(
+                    content_type.to_string(),
+                ));
+            }
+        }
+        let body = read_body(response, self.max_response_size).await?;
+
+        // Error responses usually carry a Status Message explaining them
+        let response = match get_message_from_xml(&body) {
+            Ok(response) => response,
+            Err(_) if !status.is_success() => return Err(Taxii1xError::HttpStatus(status)),
+            Err(e) => return Err(e),
+        };
+        if let Some(error) = status_error(&response) {
+            return Err(error);
+        }
+        if !status.is_success() {
+            return Err(Taxii1xError::HttpStatus(status));
+        }
+        Ok(response)
+    }
+
+    /// Discover the services of a remote Discovery Service.
+    pub async fn discover(&self, address: &str) -> Taxii1xResult<Vec<DiscoveredService>> {
+        let request = if self.is_taxii_10() {
+            TaxiiMessage::V10(tm10::Taxii10Message::DiscoveryRequest(
+                tm10::DiscoveryRequest::new(generate_id()),
+            ))
+        } else {
+            TaxiiMessage::V11(tm11::Taxii11Message::DiscoveryRequest(
+                tm11::DiscoveryRequest::new(generate_id()),
+            ))
+        };
+
+        match self.send(address, &request).await? {
+            TaxiiMessage::V11(tm11::Taxii11Message::DiscoveryResponse(response)) => Ok(response
+                .service_instances
+                .into_iter()
+                .map(|instance| DiscoveredService {
+                    service_type: instance.service_type,
+                    services_version: instance.services_version,
+                    available: instance.available,
+                    protocol_binding: instance.protocol_binding,
+                    address: instance.service_address,
+                    message_bindings: instance.message_bindings,
+                    content_bindings: instance
+                        .inbox_service_accepted_content
+                        .into_iter()
+                        .map(|cb| cb.binding_id)
+                        .collect(),
+                    message: instance.message,
+                })
+                .collect()),
+            TaxiiMessage::V10(tm10::Taxii10Message::DiscoveryResponse(response)) => Ok(response
+                .service_instances
+                .into_iter()
+                .map(|instance| DiscoveredService {
+                    service_type: instance.service_type,
+                    services_version: instance.services_version,
+                    available: instance.available,
+                    protocol_binding: instance.protocol_binding,
+                    address: instance.service_address,
+                    message_bindings: instance.message_bindings,
+                    content_bindings: instance.inbox_service_accepted_content,
+                    message: instance.message,
+                })
+                .collect()),
+            other => Err(unexpected("Discovery Response", &other)),
+        }
+    }
+
+    /// Poll a collection (a feed in TAXII 1.0) of a remote Poll Service,
+    /// returning the first part of the results.
+    ///
+    /// See [`Self::poll_all`] to fetch every part.
+    pub async fn poll(
+        &self,
+        address: &str,
+        collection: &str,
+        options: &PollOptions,
+    ) -> Taxii1xResult<PollResult> {
+        let begin = options.exclusive_begin.map(|t| format_timestamp_label(&t));
+        let end = options.inclusive_end.map(|t| format_timestamp_label(&t));
+
+        let request = if self.is_taxii_10() {
+            TaxiiMessage::V10(tm10::Taxii10Message::PollRequest(tm10::PollRequest {
+                xmlns: NS_TAXII_10.to_string(),
+                message_id: generate_id(),
+                feed_name: collection.to_string(),
+                extended_headers: None,
+                subscription_id: options.subscription_id.clone(),
+                exclusive_begin_timestamp_label: begin,
+                inclusive_end_timestamp_label: end,
+                content_bindings: options
+                    .content_bindings
+                    .iter()
+                    .map(|cb| cb.binding.clone())
+                    .collect(),
+            }))
+        } else {
+            // Poll parameters and a subscription are mutually exclusive
+            let poll_parameters = options
+                .subscription_id
+                .is_none()
+                .then(|| tm11::PollParameters {
+                    allow_asynch: Some(false),
+                    response_type: Some(RT_FULL.to_string()),
+                    content_bindings: options
+                        .content_bindings
+                        .iter()
+                        .map(|cb| {
+                            tm11::ContentBinding::with_subtypes(&cb.binding, cb.subtypes.clone())
+                        })
+                        .collect(),
+                    delivery_parameters: None,
+                });
+            TaxiiMessage::V11(tm11::Taxii11Message::PollRequest(tm11::PollRequest {
+                xmlns: NS_TAXII_11.to_string(),
+                message_id: generate_id(),
+                collection_name: collection.to_string(),
+                extended_headers: None,
+                exclusive_begin_timestamp_label: begin,
+                inclusive_end_timestamp_label: end,
+                subscription_id: options.subscription_id.clone(),
+                poll_parameters,
+            }))
+        };
+
+        poll_result(self.send(address, &request).await?)
+    }
+
+    /// Fetch a part of the results of a poll from a remote Poll Service
+    /// (TAXII 1.1 only).
+    pub async fn fulfill(
+        &self,
+        address: &str,
+        collection: &str,
+        result_id: &str,
+        result_part_number: i32,
+    ) -> Taxii1xResult<PollResult> {
+        if self.is_taxii_10() {
+            return Err(Taxii1xError::UnsupportedVersion(
+                "Poll Fulfillment is not supported in TAXII 1.0".to_string(),
+            ));
+        }
+
+        let request = TaxiiMessage::V11(tm11::Taxii11Message::PollFulfillmentRequest(
+            tm11::PollFulfillmentRequest {
+                xmlns: NS_TAXII_11.to_string(),
+                message_id: generate_id(),
+                collection_name: collection.to_string(),
+                result_id: result_id.to_string(),
+                result_part_number: Some(result_part_number),
+                extended_headers: None,
+            },
+        ));
+
+        poll_result(self.send(address, &request).await?)
+    }
+
+    /// Poll a collection of a remote Poll Service, following Poll
+    /// Fulfillment until every part of the results is fetched.
+    ///
+    /// Fails if the results have more parts than the client's maximum.
+    pub async fn poll_all(
+        &self,
+        address: &str,
+        collection: &str,
+        options: &PollOptions,
+    ) -> Taxii1xResult<Vec<ContentBlockEntity>> {
+        let mut result = self.poll(address, collection, options).await?;
+        let mut blocks = std::mem::take(&mut result.content_blocks);
+        let mut part = result.result_part_number.unwrap_or(1);
+        let mut parts = 1;
+
+        while result.more {
+            if parts >= self.max_poll_parts {
+                return Err(Taxii1xError::UnexpectedResponse(format!(
+                    "Poll results have more than {} parts",
+                    self.max_poll_parts
+                )));
+            }
+            parts += 1;
+            let Some(result_id) = result.result_id.take() else {
+                return Err(Taxii1xError::UnexpectedResponse(
+                    "Poll Response has more parts but no result ID".to_string(),
+                ));
+            };
+            part += 1;
+            result = self.fulfill(address, collection, &result_id, part).await?;
+            blocks.append(&mut result.content_blocks);
+            result.result_id.get_or_insert(result_id);
+        }
+
+        Ok(blocks)
+    }
+
+    /// Push content blocks to a remote Inbox Service.
+    pub async fn inbox(
+        &self,
+        address: &str,
+        blocks: &[ContentBlockEntity],
+        options: &InboxOptions,
+    ) -> Taxii1xResult<()> {
+        self.push(address, &self.inbox_message(blocks, options))
+            .await
+    }
+
+    /// Build the Inbox Message pushing content blocks, in the client's
+    /// message binding.
+    ///
+    /// Destinations and result sets are left out in TAXII 1.0, which has
+    /// neither.
+    pub fn inbox_message(
+        &self,
+        blocks: &[ContentBlockEntity],
+        options: &InboxOptions,
+    ) -> TaxiiMessage {
+        let content =
+            |block: &ContentBlockEntity| encode_content(&block.content_encoding, &block.content);
+        let begin = blocks
+            .first()
+            .map(|b| format_timestamp_label(&b.timestamp_label));
+        let end = blocks
+            .last()
+            .map(|b| format_timestamp_label(&b.timestamp_label));
+
+        if self.is_taxii_10() {
+            return TaxiiMessage::V10(tm10::Taxii10Message::InboxMessage(tm10::InboxMessage {
+                xmlns: NS_TAXII_10.to_string(),
+                message_id: generate_id(),
+                extended_headers: None,
+                message: None,
+                subscription_information: options.subscription.as_ref().map(|subscription| {
+                    tm10::SubscriptionInformation10 {
+                        feed_name: subscription.collection_name.clone(),
+                        subscription_id: subscription.subscription_id.clone(),
+                        inclusive_begin_timestamp_label: begin,
+                        inclusive_end_timestamp_label: end,
+                    }
+                }),
+                content_blocks: blocks
+                    .iter()
+                    .map(|block| tm10::ContentBlock {
+                        content_binding: block
+                            .content_binding
+                            .as_ref()
+                            .map(|cb| cb.binding.clone())
+                            .unwrap_or_default(),
+                        content: content(block),
+                        timestamp_label: Some(format_timestamp_label(&block.timestamp_label)),
+                        padding: None,
+                    })
+                    .collect(),
+            }));
+        }
+
+        TaxiiMessage::V11(tm11::Taxii11Message::InboxMessage(tm11::InboxMessage {
+            xmlns: NS_TAXII_11.to_string(),
+            message_id: generate_id(),
+            result_id: options.result_id.clone(),
+            extended_headers: None,
+            destination_collection_names: options.destination_collections.clone(),
+            message: None,
+            subscription_information: options.subscription.as_ref().map(|subscription| {
+                tm11::SubscriptionInformation {
+                    collection_name: subscription.collection_name.clone(),
+                    subscription_id: subscription.subscription_id.clone(),
+                    exclusive_begin_timestamp_label: None,
+                    inclusive_end_timestamp_label: end,
+                }
+            }),
+            record_count: options.record_count.map(|record_count| tm11::RecordCount {
+                partial_count: false,
+                record_count,
+            }),
+            content_blocks: blocks
+                .iter()
+                .map(|block| tm11::ContentBlock {
+                    content_binding: block
+                        .content_binding
+                        .as_ref()
+                        .map(|cb| {
+                            tm11::ContentBinding::with_subtypes(&cb.binding, cb.subtypes.clone())
+                        })
+                        .unwrap_or_else(|| tm11::ContentBinding::new("")),
+                    content: content(block),
+                    timestamp_label: Some(format_timestamp_label(&block.timestamp_label)),
+                    message: block.message.clone(),
+                    padding: None,
+                })
+                .collect(),
+        }))
+    }
+
+    /// Send an Inbox Message to a remote Inbox Service, expecting a
+    /// `SUCCESS` Status Message in response.
+    pub async fn push(&self, address: &str, message: &TaxiiMessage) -> Taxii1xResult<()> {
+        match self.send(address, message).await? {
+            TaxiiMessage::V11(tm11::Taxii11Message::StatusMessage(_))
+            | TaxiiMessage::V10(tm10::Taxii10Message::StatusMessage(_)) => Ok(()),
+            other => Err(unexpected("Status Message", &other)),
+        }
+    }
+}
+
+/// Builder of a configured [`Taxii1Client`].
+#[derive(Debug, Default)]
+pub struct Taxii1ClientBuilder {
+    message_binding: Option<String>,
+    timeout: Option<Duration>,
+    client_certificate: Option<Vec<u8>>,
+    root_certificates: Vec<Vec<u8>>,
+    credentials: Option<(String, String)>,
+    public_only: bool,
+    max_response_size: Option<usize>,
+    max_poll_parts: Option<usize>,
+}
+
+impl Taxii1ClientBuilder {
+    /// Set the message binding of the requests built by the client
+    /// ([`VID_TAXII_XML_10`] or [`VID_TAXII_XML_11`]).
+    #[must_use]
+    pub fn message_binding(mut self, message_binding: impl Into<String>) -> Self {
+        self.message_binding = Some(message_binding.into());
+        self
+    }
+
+    /// Set the timeout of a request.
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Set the maximum size of a response, in bytes.
+    #[must_use]
+    pub fn max_response_size(mut self, max_response_size: usize) -> Self {
+        self.max_response_size = Some(max_response_size);
+        self
+    }
+
+    /// Set the maximum number of parts of poll results fetched by
+    /// [`Taxii1Client::poll_all`].
+    #[must_use]
+    pub fn max_poll_parts(mut self, max_poll_parts: usize) -> Self {
+        self.max_poll_parts = Some(max_poll_parts);
+        self
+    }
+
+    /// Authenticate to HTTPS services with a client certificate.
+    ///
+    /// `pem` holds the PEM-encoded certificate chain and private key.
+    #[must_use]
+    pub fn client_certificate(mut self, pem: impl Into<Vec<u8>>) -> Self {
+        self.client_certificate = Some(pem.into());
+        self
+    }
+
+    /// Trust a PEM-encoded root certificate, in addition to the system
+    /// roots.
+    #[must_use]
+    pub fn root_certificate(mut self, pem: impl Into<Vec<u8>>) -> Self {
+        self.root_certificates.push(pem.into());
+        self
+    }
+
+    /// Authenticate with HTTP basic authentication.
+    #[must_use]
+    pub fn basic_auth(mut self, username: impl Into<String>, password: impl Into<String>) -> Self {
+        self.credentials = Some((username.into(), password.into()));
+        self
+    }
+
+    /// Only connect to public addresses.
+    ///
+    /// Host names are resolved to their public addresses only, and
+    /// addresses that are not public are refused, e.g. for addresses chosen
+    /// by clients of the server.
+    #[must_use]
+    pub fn public_addresses_only(mut self) -> Self {
+        self.public_only = true;
+        self
+    }
+
+    /// Build the client.
+    ///
+    /// Fails if the message binding is unknown or a certificate is invalid.
+    pub fn build(self) -> Taxii1xResult<Taxii1Client> {
+        let message_binding = known_binding(self.message_binding.as_deref())?;
+
+        let mut http = http_client();
+        if let Some(pem) = self.client_certificate {
+            let identity = Identity::from_pem(&pem)
+                .map_err(|e| Taxii1xError::Http(format!("Invalid client certificate: {e}")))?;
+            http = http.identity(identity);
+        }
+        for pem in self.root_certificates {
+            let certificate = Certificate::from_pem(&pem)
+                .map_err(|e| Taxii1xError::Http(format!("Invalid root certificate: {e}")))?;
+            http = http.add_root_certificate(certificate);
+        }
+        if self.public_only {
+            http = http.dns_resolver(Arc::new(PublicResolver));
+        }
+        let http = http
+            .build()
+            .map_err(|e| Taxii1xError::Http(format!("Failed to create HTTP client: {e}")))?;
+
+        Ok(Taxii1Client {
+            http,
+            message_binding,
+            timeout: self.timeout.unwrap_or(DEFAULT_TIMEOUT),
+            credentials: self.credentials,
+            public_only: self.public_only,
+            max_response_size: self.max_response_size.unwrap_or(DEFAULT_MAX_RESPONSE_SIZE),
+            max_poll_parts: self.max_poll_parts.unwrap_or(DEFAULT_MAX_POLL_PARTS),
+        })
+    }
+}
+
+/// Builder of the HTTP client, which does not follow redirects.
+fn http_client() -> reqwest::ClientBuilder {
+    reqwest::Client::builder().redirect(reqwest::redirect::Policy::none())
+}
+
+/// Message binding of requests, TAXII 1.1 by default.
+fn known_binding(message_binding: Option<&str>) -> Taxii1xResult<&'static str> {
+    match message_binding {
+        None | Some(VID_TAXII_XML_11) => Ok(VID_TAXII_XML_11),
+        Some(VID_TAXII_XML_10) => Ok(VID_TAXII_XML_10),
+        Some(other) => Err(Taxii1xError::UnsupportedVersion(other.to_string())),
+    }
+}
+
+/// Read the body of a response, refusing bodies larger than `max_size`.
+async fn read_body(mut response: reqwest::Response, max_size: usize) -> Taxii1xResult<String> {
+    let too_large = || {
+        Taxii1xError::Http(format!(
+            "Response exceeds the maximum size of {max_size} bytes"
+        ))
+    };
+    if response
+        .content_length()
+        .is_some_and(|length| length > max_size as u64)
+    {
+        return Err(too_large());
+    }
+
+    let mut body = Vec::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| Taxii1xError::Http(e.to_string()))?
+    {
+        if body.len() + chunk.len() > max_size {
+            return Err(too_large());
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(String::from_utf8_lossy(&body).into_owned())
+}
+
+/// Whether an address is public, i.e. none of loopback, private,
+/// link-local (including cloud metadata services), shared, unspecified,
+/// broadcast, multicast, documentation or reserved.
+///
+/// IPv4-mapped IPv6 addresses are judged by their IPv4 address; IPv6
+/// unique local, site-local and NAT64 addresses are not public.
+pub fn is_public_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return is_public_ipv4(ip);
+            }
+            let segments = ip.segments();
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                || segments[..6] == [0; 6]
+                || (segments[0] & 0xfe00) == 0xfc00
+                || (segments[0] & 0xffc0) == 0xfe80
+                || (segments[0] & 0xffc0) == 0xfec0
+                || (segments[0] == 0x2001 && segments[1] == 0x0db8)
+                || (segments[0] == 0x0064 && segments[1] == 0xff9b))
+        }
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        || a == 0
+        || (a == 100 && (b & 0xc0) == 64)
+        || (a == 192 && b == 0 && c == 0)
+        || (a == 198 && (b & 0xfe) == 18)
+        || a >= 240)
+}
+
+/// Resolver of host names to their public addresses only.
+struct PublicResolver;
+
+impl reqwest::dns::Resolve for PublicResolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|addr| is_public_address(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!("{} has no public address", name.as_str()).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as reqwest::dns::Addrs)
+        })
+    }
+}
+
+/// Service listed in a Discovery Response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscoveredService {
+    /// Service type, e.g. `INBOX` or `POLL`.
+    pub service_type: String,
+    /// Services binding of the service.
+    pub services_version: String,
+    /// Whether the service is available to the requester.
+    pub available: Option<bool>,
+    /// Protocol binding of the service.
+    pub protocol_binding: String,
+    /// Address of the service.
+    pub address: String,
+    /// Message bindings of the service.
+    pub message_bindings: Vec<String>,
+    /// Content bindings accepted by an Inbox Service.
+    pub content_bindings: Vec<String>,
+    /// Message about the service.
+    pub message: Option<String>,
+}
+
+/// Parameters of a poll.
+#[derive(Debug, Clone, Default)]
+pub struct PollOptions {
+    /// Only poll content with a timestamp label after this time.
+    pub exclusive_begin: Option<DateTime<Utc>>,
+    /// Only poll content with a timestamp label up to this time.
+    pub inclusive_end: Option<DateTime<Utc>>,
+    /// Only poll content of these bindings; all content when empty.
+    ///
+    /// Ignored when polling for a subscription, whose own bindings apply.
+    pub content_bindings: Vec<ContentBindingEntity>,
+    /// Poll for a subscription.
+    pub subscription_id: Option<String>,
+}
+
+/// Parameters of an Inbox Message.
+#[derive(Debug, Clone, Default)]
+pub struct InboxOptions {
+    /// Collections to store the blocks in (TAXII 1.1 only).
+    pub destination_collections: Vec<String>,
+    /// Subscription the blocks are pushed for.
+    pub subscription: Option<InboxSubscription>,
+    /// Result set of an asynchronous poll the blocks are part of (TAXII 1.1
+    /// only).
+    pub result_id: Option<String>,
+    /// Number of blocks in the whole result set (TAXII 1.1 only).
+    pub record_count: Option<i64>,
+}
+
+/// Subscription content is pushed for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InboxSubscription {
+    /// Collection (feed in TAXII 1.0) subscribed to.
+    pub collection_name: String,
+    /// ID of the subscription.
+    pub subscription_id: String,
+}
+
+/// One part of the results of a poll.
+#[derive(Debug, Clone)]
+pub struct PollResult {
+    /// ID of the result set, to fetch further parts.
+    pub result_id: Option<String>,
+    /// Number of the part.
+    pub result_part_number: Option<i32>,
+    /// Whether more parts follow.
+    pub more: bool,
+    /// Timestamp label of the latest content of the results, to poll from
+    /// next time.
+    pub inclusive_end: Option<DateTime<Utc>>,
+    /// The content blocks of the part.
+    ///
+    /// Blocks without a timestamp label are labeled with the time they were
+    /// received.
+    pub content_blocks: Vec<ContentBlockEntity>,
+}
+
+/// Error for a Status Message other than `SUCCESS`.
+fn status_error(message: &TaxiiMessage) -> Option<Taxii1xError> {
+    let (status_type, message, in_response_to, status_detail) = match message {
+        TaxiiMessage::V11(tm11::Taxii11Message::StatusMessage(status)) => {
+            let details: Vec<&str> = status
+                .status_details
+                .iter()
+                .filter_map(|detail| detail.value.as_deref())
+                .collect();
+            (
+                &status.status_type,
+                &status.message,
+                &status.in_response_to,
+                (!details.is_empty()).then(|| details.join(", ")),
+            )
+        }
+        TaxiiMessage::V10(tm10::Taxii10Message::StatusMessage(status)) => (
+            &status.status_type,
+            &status.message,
+            &status.in_response_to,
+            status.status_detail.clone(),
+        ),
+        _ => return None,
+    };
+
+    let (status_type, message) = match status_type.parse::<StatusType>() {
+        Ok(StatusType::Success) => return None,
+        Ok(parsed) => (parsed, message.clone().unwrap_or_default()),
+        // Keep unknown status types readable
+        Err(()) => (
+            StatusType::Failure,
+            match message {
+                Some(m) => format!("{status_type}: {m}"),
+                None => status_type.clone(),
+            },
+        ),
+    };
+    Some(Taxii1xError::StatusMessage {
+        message,
+        in_response_to: in_response_to.clone(),
+        status_type,
+        status_detail,
+    })
+}
+
+/// Error for a response of an unexpected message type.
+fn unexpected(expected: &str, message: &TaxiiMessage) -> Taxii1xError {
+    Taxii1xError::UnexpectedResponse(format!(
+        "Expected a {expected}, got {}",
+        message.message_type()
+    ))
+}
+
+/// Read a Poll Response.
+fn poll_result(message: TaxiiMessage) -> Taxii1xResult<PollResult> {
+    match message {
+        TaxiiMessage::V11(tm11::Taxii11Message::PollResponse(response)) => Ok(PollResult {
+            result_id: response.result_id,
+            result_part_number: response.result_part_number,
+            more: response.more.unwrap_or(false),
+            inclusive_end: parse_label(response.inclusive_end_timestamp_label.as_deref()),
+            content_blocks: response
+                .content_blocks
+                .into_iter()
+                .map(|block| {
+                    let binding = ContentBindingEntity::with_subtypes(
+                        block.content_binding.binding_id,
+                        block
+                            .content_binding
+                            .subtype_ids
+                            .into_iter()
+                            .map(|s| s.subtype_id)
+                            .collect(),
+                    );
+                    content_block(
+                        binding,
+                        &block.content,
+                        block.timestamp_label.as_deref(),
+                        block.message,
+                    )
+                })
+                .collect::<Taxii1xResult<_>>()?,
+        }),
+        TaxiiMessage::V10(tm10::Taxii10Message::PollResponse(response)) => Ok(PollResult {
+            result_id: None,
+            result_part_number: None,
+            more: false,
+            inclusive_end: parse_label(response.inclusive_end_timestamp_label.as_deref()),
+            content_blocks: response
+                .content_blocks
+                .into_iter()
+                .map(|block| {
+                    content_block(
+                        ContentBindingEntity::new(block.content_binding),
+                        &block.content,
+                        block.timestamp_label.as_deref(),
+                        None,
+                    )
+                })
+                .collect::<Taxii1xResult<_>>()?,
+        }),
+        other => Err(unexpected("Poll Response", &other)),
+    }
+}
+
+/// Convert a polled content block, decoding its content.
+fn content_block(
+    binding: ContentBindingEntity,
+    content: &str,
+    timestamp_label: Option<&str>,
+    message: Option<String>,
+) -> Taxii1xResult<ContentBlockEntity> {
+    let content = decode_content(&binding.binding, content)
+        .map_err(|e| Taxii1xError::UnexpectedResponse(e.to_string()))?;
+    let content_encoding = binding_encoding(&binding.binding).to_string();
+    Ok(ContentBlockEntity {
+        id: None,
+        content,
+        timestamp_label: parse_label(timestamp_label).unwrap_or_else(Utc::now),
+        content_binding: Some(binding),
+        message,
+        inbox_message_id: None,
+        validation_error: None,
+        signature_status: None,
+        signer_fingerprint: None,
+        content_encoding,
+    })
+}
+
+fn parse_label(label: Option<&str>) -> Option<DateTime<Utc>> {
+    label
+        .and_then(|l| DateTime::parse_from_rfc3339(l).ok())
+        .map(|t| t.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{
+        CB_SMIME, CB_STIX_XML_111, VID_TAXII_HTTP_10, VID_TAXII_SERVICES_10, VID_TAXII_SERVICES_11,
+    };
+    use crate::handlers::{HandlerContext, HandlerRegistry, ServiceInfo, TaxiiHeaders};
+    use axum::Router;
+    use axum::extract::{Path, State};
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::routing::post;
+    use sqlx::PgPool;
+    use std::sync::Arc;
+    use taxii_core::{CollectionEntity, ServiceEntity};
+    use taxii_db::{DbTaxii1Repository, Taxii1Repository, TaxiiPool};
+
+    type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+    const COLLECTION_NAME: &str = "collection-a";
+
+    struct Server {
+        persistence: Arc<DbTaxii1Repository>,
+        registry: HandlerRegistry,
+    }
+
+    /// Handle a message to a service with this crate's handlers.
+    async fn service(
+        State(server): State<Arc<Server>>,
+        Path(service_id): Path<String>,
+        headers: HeaderMap,
+        body: String,
+    ) -> (StatusCode, [(&'static str, &'static str); 2], String) {
+        let message = match get_message_from_xml(&body) {
+            Ok(message) => message,
+            Err(_) => return (StatusCode::BAD_REQUEST, Default::default(), String::new()),
+        };
+        let (version, services) = match message {
+            TaxiiMessage::V10(_) => (VID_TAXII_XML_10, VID_TAXII_SERVICES_10),
+            TaxiiMessage::V11(_) => (VID_TAXII_XML_11, VID_TAXII_SERVICES_11),
+        };
+        let response_headers = [
+            (HTTP_X_TAXII_CONTENT_TYPE, version),
+            (crate::http::HTTP_X_TAXII_SERVICES, services),
+        ];
+
+        let entity = server.persistence.get_service(&service_id).await;
+        let (Ok(Some(entity)), Some(handler)) =
+            (entity, server.registry.get(version, message.message_type()))
+        else {
+            return (StatusCode::NOT_FOUND, response_headers, String::new());
+        };
+        let ctx = HandlerContext {
+            account: None,
+            persistence: server.persistence.clone(),
+            service: ServiceInfo {
+                id: service_id.clone(),
+                service_type: entity.service_type,
+                address: format!("/services/{service_id}/"),
+                description: None,
+                protocol_bindings: vec![VID_TAXII_HTTP_10.to_string()],
+                message_bindings: vec![VID_TAXII_XML_10.to_string(), VID_TAXII_XML_11.to_string()],
+                available: true,
+                authentication_required: false,
+                properties: entity.properties,
+            },
+            domain: None,
+            limits: Default::default(),
+            push: Default::default(),
+            hooks: None,
+        };
+
+        let headers = match TaxiiHeaders::from_header_map(&headers)
+            .and_then(|h| h.validate(version, Some(message.message_id())).map(|()| h))
+        {
+            Ok(headers) => headers,
+            Err(e) => {
+                let xml = match message {
+                    TaxiiMessage::V10(_) => {
+                        tm10::Taxii10Message::StatusMessage(e.to_status_message_10(generate_id()))
+                            .to_xml()
+                    }
+                    TaxiiMessage::V11(_) => {
+                        tm11::Taxii11Message::StatusMessage(e.to_status_message_11(generate_id()))
+                            .to_xml()
+                    }
+                };
+                return (
+                    StatusCode::BAD_REQUEST,
+                    response_headers,
+                    xml.unwrap_or_default(),
+                );
+            }
+        };
+        let xml = match &message {
+            TaxiiMessage::V10(m) => match handler.handle_10(&ctx, &headers, m).await {
+                Ok(response) => response.to_xml(),
+                Err(e) => {
+                    tm10::Taxii10Message::StatusMessage(e.to_status_message_10(generate_id()))
+                        .to_xml()
+                }
+            },
+            TaxiiMessage::V11(m) => match handler.handle_11(&ctx, &headers, m).await {
+                Ok(response) => response.to_xml(),
+                Err(e) => {
+                    tm11::Taxii11Message::StatusMessage(e.to_status_message_11(generate_id()))
+                        .to_xml()
+                }
+            },
+        };
+        (StatusCode::OK, response_headers, xml.unwrap_or_default())
+    }
+
+    /// Serve a discovery service advertising an inbox and a poll service
+    /// with results of two blocks per part, both of one collection.
+    /// Returns the address of the services.
+    async fn serve(pool: PgPool) -> Result<String, Box<dyn std::error::Error>> {
+        let persistence = Arc::new(DbTaxii1Repository::new(TaxiiPool::new(pool)));
+        for (id, service_type, properties) in [
+            (
+                "discovery",
+                "DISCOVERY",
+                serde_json::json!({"advertised_services": ["inbox", "poll"]}),
+            ),
+            (
+                "inbox",
+                "INBOX",
+                serde_json::json!({"destination_collection_names": [COLLECTION_NAME]}),
+            ),
+            ("poll", "POLL", serde_json::json!({"max_result_size": 2})),
+        ] {
+            persistence
+                .update_service(&ServiceEntity {
+                    id: Some(id.to_string()),
+                    service_type: service_type.to_string(),
+                    properties,
+                })
+                .await?;
+        }
+        let collection = persistence
+            .create_collection(&CollectionEntity::new(COLLECTION_NAME))
+            .await?;
+        persistence
+            .set_collection_services(
+                collection.id.ok_or("collection has no id")?,
+                &["inbox".to_string(), "poll".to_string()],
+            )
+            .await?;
+
+        let server = Arc::new(Server {
+            persistence,
+            registry: HandlerRegistry::new(),
+        });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let address = format!("http://{}/services", listener.local_addr()?);
+        let app = Router::new()
+            .route("/services/{service_id}/", post(service))
+            .with_state(server);
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        Ok(address)
+    }
+
+    fn block(binding: &str, content: &[u8]) -> ContentBlockEntity {
+        ContentBlockEntity {
+            content_encoding: binding_encoding(binding).to_string(),
+            ..ContentBlockEntity::new(content, Some(ContentBindingEntity::new(binding)))
+        }
+    }
+
+    #[test]
+    fn test_builder_rejects_unknown_binding() {
+        assert!(
+            Taxii1Client::builder()
+                .message_binding(VID_TAXII_XML_10)
+                .build()
+                .is_ok()
+        );
+        assert!(matches!(
+            Taxii1Client::builder()
+                .message_binding("urn:example.com:xml:2.0")
+                .build(),
+            Err(Taxii1xError::UnsupportedVersion(_))
+        ));
+        assert!(matches!(
+            Taxii1Client::builder()
+                .client_certificate("not a certificate")
+                .build(),
+            Err(Taxii1xError::Http(_))
+        ));
+    }
+
+    #[test]
+    fn test_status_error() -> TestResult {
+        let status = |status_type: &str| {
+            let mut status = tm11::StatusMessage::failure("1", Some("2".to_string()), None);
+            status.status_type = status_type.to_string();
+            status.status_details = vec![
+                crate::messages::common::StatusDetail {
+                    name: "SUPPORTED_CONTENT".to_string(),
+                    value: Some("a".to_string()),
+                },
+                crate::messages::common::StatusDetail {
+                    name: "SUPPORTED_CONTENT".to_string(),
+                    value: Some("b".to_string()),
+                },
+            ];
+            TaxiiMessage::V11(tm11::Taxii11Message::StatusMessage(status))
+        };
+
+        assert!(status_error(&status("SUCCESS")).is_none());
+        let Some(Taxii1xError::StatusMessage {
+            status_type,
+            in_response_to,
+            status_detail,
+            ..
+        }) = status_error(&status("UNSUPPORTED_CONTENT"))
+        else {
+            return Err("expected a status error".into());
+        };
+        assert_eq!(status_type, StatusType::UnsupportedContentBinding);
+        assert_eq!(in_response_to.as_deref(), Some("2"));
+        assert_eq!(status_detail.as_deref(), Some("a, b"));
+
+        let Some(Taxii1xError::StatusMessage {
+            status_type,
+            message,
+            ..
+        }) = status_error(&status("CUSTOM"))
+        else {
+            return Err("expected a status error".into());
+        };
+        assert_eq!(status_type, StatusType::Failure);
+        assert_eq!(message, "CUSTOM");
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_discover_push_and_poll_11(pool: PgPool) -> TestResult {
+        let address = serve(pool).await?;
+        let client = Taxii1Client::new();
+
+        let services = client.discover(&format!("{address}/discovery/")).await?;
+        let types: Vec<&str> = services.iter().map(|s| s.service_type.as_str()).collect();
+        assert!(types.contains(&"INBOX"));
+        assert!(types.contains(&"POLL"));
+
+        let blocks: Vec<ContentBlockEntity> = (0..5)
+            .map(|i| {
+                block(
+                    CB_STIX_XML_111,
+                    format!("<package>{i}</package>").as_bytes(),
+                )
+            })
+            .chain([block(CB_SMIME, &[0, 159, 146, 150])])
+            .collect();
+        client
+            .inbox(
+                &format!("{address}/inbox/"),
+                &blocks,
+                &InboxOptions::default(),
+            )
+            .await?;
+
+        // Six blocks come in three parts, fetched with Poll Fulfillment
+        let poll = format!("{address}/poll/");
+        let first = client
+            .poll(&poll, COLLECTION_NAME, &PollOptions::default())
+            .await?;
+        assert!(first.more);
+        assert_eq!(first.content_blocks.len(), 2);
+
+        let mut polled = client
+            .poll_all(&poll, COLLECTION_NAME, &PollOptions::default())
+            .await?;
+        polled.sort_by(|a, b| a.content.cmp(&b.content));
+        let mut expected: Vec<Vec<u8>> = blocks.iter().map(|b| b.content.clone()).collect();
+        expected.sort();
+        assert_eq!(
+            polled.into_iter().map(|b| b.content).collect::<Vec<_>>(),
+            expected
+        );
+
+        // Only the requested bindings are polled
+        let options = PollOptions {
+            content_bindings: vec![ContentBindingEntity::new(CB_SMIME)],
+            ..Default::default()
+        };
+        let polled = client.poll_all(&poll, COLLECTION_NAME, &options).await?;
+        assert_eq!(polled.len(), 1);
+
+        // Failures of the remote service are status errors
+        let result = client
+            .poll(&poll, "no-such-collection", &PollOptions::default())
+            .await;
+        assert!(
+            matches!(result, Err(Taxii1xError::StatusMessage { status_type, .. }) if status_type != StatusType::Success)
+        );
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_discover_push_and_poll_10(pool: PgPool) -> TestResult {
+        let address = serve(pool).await?;
+        let client = Taxii1Client::builder()
+            .message_binding(VID_TAXII_XML_10)
+            .build()?;
+
+        let services = client.discover(&format!("{address}/discovery/")).await?;
+        assert!(services.iter().any(|s| s.service_type == "INBOX"));
+
+        client
+            .inbox(
+                &format!("{address}/inbox/"),
+                &[block(CB_STIX_XML_111, b"<package>1</package>")],
+                &InboxOptions::default(),
+            )
+            .await?;
+        let result = client
+            .poll(
+                &format!("{address}/poll/"),
+                COLLECTION_NAME,
+                &PollOptions::default(),
+            )
+            .await?;
+        assert_eq!(result.content_blocks.len(), 1);
+        assert_eq!(result.content_blocks[0].content, b"<package>1</package>");
+        assert!(matches!(
+            client
+                .fulfill(&format!("{address}/poll/"), COLLECTION_NAME, "1", 2)
+                .await,
+            Err(Taxii1xError::UnsupportedVersion(_))
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_http_error_without_status_message() -> TestResult {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let address = format!("http://{}/inbox/", listener.local_addr()?);
+        let app = Router::new().route(
+            "/inbox/",
+            post(|| async { (StatusCode::SERVICE_UNAVAILABLE, String::new()) }),
+        );
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let result = Taxii1Client::new()
+            .inbox(&address, &[], &InboxOptions::default())
+            .await;
+        assert!(matches!(
+            result,
+            Err(Taxii1xError::HttpStatus(StatusCode::SERVICE_UNAVAILABLE))
+        ));
+        Ok(())
+    }
+
+    /// Serve a fixed response to every POST request.
+    async fn serve_fixed(
+        response: impl axum::response::IntoResponse + Clone + Send + Sync + 'static,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let address = format!("http://{}/service/", listener.local_addr()?);
+        let app = Router::new().route("/service/", post(move || async move { response }));
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        Ok(address)
+    }
+
+    #[tokio::test]
+    async fn test_redirects_not_followed() -> TestResult {
+        let address = serve_fixed((
+            StatusCode::FOUND,
+            [(axum::http::header::LOCATION, "http://169.254.169.254/")],
+        ))
+        .await?;
+
+        let result = Taxii1Client::new()
+            .inbox(&address, &[], &InboxOptions::default())
+            .await;
+        assert!(matches!(
+            result,
+            Err(Taxii1xError::HttpStatus(StatusCode::FOUND))
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_response_size_limited() -> TestResult {
+        let status = format!(
+            r#"<Status_Message xmlns="{NS_TAXII_11}" message_id="1" in_response_to="2" status_type="SUCCESS"/>"#
+        );
+        let address = serve_fixed(status.clone()).await?;
+
+        let client = Taxii1Client::builder()
+            .max_response_size(status.len())
+            .build()?;
+        client
+            .inbox(&address, &[], &InboxOptions::default())
+            .await?;
+
+        let client = Taxii1Client::builder()
+            .max_response_size(status.len() - 1)
+            .build()?;
+        let result = client.inbox(&address, &[], &InboxOptions::default()).await;
+        assert!(
+            matches!(&result, Err(Taxii1xError::Http(message)) if message.contains("maximum size")),
+            "{result:?}"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_poll_parts_limited() -> TestResult {
+        // Every part claims more parts follow
+        let address = serve_fixed(format!(
+            r#"<Poll_Response xmlns="{NS_TAXII_11}" message_id="1" in_response_to="2" collection_name="{COLLECTION_NAME}" more="true" result_id="r" result_part_number="1"/>"#
+        ))
+        .await?;
+
+        let client = Taxii1Client::builder().max_poll_parts(3).build()?;
+        let result = client
+            .poll_all(&address, COLLECTION_NAME, &PollOptions::default())
+            .await;
+        assert!(
+            matches!(&result, Err(Taxii1xError::UnexpectedResponse(message)) if message.contains("more than 3 parts")),
+            "{result:?}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_inbox_message() -> TestResult {
+        let blocks = [block(CB_STIX_XML_111, b"<package>1</package>")];
+        let options = InboxOptions {
+            subscription: Some(InboxSubscription {
+                collection_name: COLLECTION_NAME.to_string(),
+                subscription_id: "s-1".to_string(),
+            }),
+            ..Default::default()
+        };
+
+        let TaxiiMessage::V11(tm11::Taxii11Message::InboxMessage(message)) =
+            Taxii1Client::new().inbox_message(&blocks, &options)
+        else {
+            return Err("expected a TAXII 1.1 Inbox Message".into());
+        };
+        let subscription = message
+            .subscription_information
+            .ok_or("no subscription information")?;
+        assert_eq!(subscription.subscription_id, "s-1");
+        assert_eq!(message.content_blocks.len(), 1);
+
+        let TaxiiMessage::V10(tm10::Taxii10Message::InboxMessage(message)) = Taxii1Client::new()
+            .with_message_binding(VID_TAXII_XML_10)?
+            .inbox_message(&blocks, &options)
+        else {
+            return Err("expected a TAXII 1.0 Inbox Message".into());
+        };
+        let subscription = message
+            .subscription_information
+            .ok_or("no subscription information")?;
+        assert_eq!(subscription.feed_name, COLLECTION_NAME);
+        assert!(subscription.inclusive_begin_timestamp_label.is_some());
+        Ok(())
+    }
+}
@@ -417,6 +417,8 @@ pub const SD_WILL_PUSH: &str = "WILL_PUSH";
 pub const SD_SUPPORTED_CONTENT: &str = "SUPPORTED_CONTENT";
 /// Acceptable destination collection names.
 pub const SD_ACCEPTABLE_DESTINATION: &str = "ACCEPTABLE_DESTINATION";
+/// Destination collection names that were not recognized.
+pub const SD_DESTINATION_COLLECTION_NAME: &str = "DESTINATION_COLLECTION_NAME";
 /// Maximum part number for poll fulfillment.
 pub const SD_MAX_PART_NUMBER: &str = "MAX_PART_NUMBER";
 /// Item field for status details.
@@ -0,0 +1,155 @@
+//! Encoding of content block bytes in TAXII 1.x messages.
+//!
+//! Content blocks are stored as bytes, while message content is text. Each
+//! block records how its content is carried in messages (see
+//! [`content_encoding`]). Content of a known binary binding (S/MIME) is
+//! base64 decoded when received and stored as bytes, with the `base64`
+//! encoding, so it is base64 encoded again when sent. Content of any other
+//! binding is stored as the text it was received as, with the `text`
+//! encoding, and sent as is.
+//!
+//! Blocks stored before encodings were recorded have the `text` encoding:
+//! they hold S/MIME content as the base64 text it was received as, and it
+//! is sent as such.
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use taxii_core::content_encoding;
+use thiserror::Error;
+
+use crate::constants::{
+    CB_CAP_11, CB_SMIME, CB_STIX_XML_10, CB_STIX_XML_11, CB_STIX_XML_12, CB_STIX_XML_101,
+    CB_STIX_XML_111, CB_XENC_122002,
+};
+
+/// Content bindings whose content is UTF-8 text.
+const TEXT_BINDINGS: &[&str] = &[
+    CB_STIX_XML_10,
+    CB_STIX_XML_101,
+    CB_STIX_XML_11,
+    CB_STIX_XML_111,
+    CB_STIX_XML_12,
+    CB_CAP_11,
+    CB_XENC_122002,
+];
+
+/// Content bindings whose content is binary.
+const BINARY_BINDINGS: &[&str] = &[CB_SMIME];
+
+/// Message content that cannot be decoded for its binding.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("Content of binding {binding} is not valid base64: {reason}")]
+pub struct ContentDecodeError {
+    /// Content binding of the content.
+    pub binding: String,
+    /// Why the content is not valid base64.
+    pub reason: String,
+}
+
+/// Whether content of a binding is UTF-8 text.
+pub fn is_text_binding(binding: &str) -> bool {
+    TEXT_BINDINGS.contains(&binding)
+}
+
+/// Whether content of a binding is binary, and base64 encoded in messages.
+pub fn is_binary_binding(binding: &str) -> bool {
+    BINARY_BINDINGS.contains(&binding)
+}
+
+/// Encoding of the content of a binding received in a message and stored
+/// as decoded by [`decode_content`].
+pub fn binding_encoding(binding: &str) -> &'static str {
+    if is_binary_binding(binding) {
+        content_encoding::BASE64
+    } else {
+        content_encoding::TEXT
+    }
+}
+
+/// Encode stored content block bytes as message content, by the block's
+/// encoding.
+pub fn encode_content(encoding: &str, content: &[u8]) -> String {
+    if encoding == content_encoding::BASE64 {
+        return STANDARD.encode(content);
+    }
+
+    match std::str::from_utf8(content) {
+        Ok(text) => text.to_string(),
+        Err(e) => {
+            // Text content cannot be base64 encoded without changing its
+            // meaning for the recipient
+            tracing::warn!(
+                error = %e,
+                "Text content is not valid UTF-8, invalid bytes are replaced"
+            );
+            String::from_utf8_lossy(content).into_owned()
+        }
+    }
+}
+
+/// Decode message content to the content block bytes to store, with the
+/// encoding given by [`binding_encoding`].
+///
+/// Content of a binary binding is base64 decoded, ignoring whitespace.
+pub fn decode_content(binding: &str, content: &str) -> Result<Vec<u8>, ContentDecodeError> {
+    if !is_binary_binding(binding) {
+        return Ok(content.as_bytes().to_vec());
+    }
+
+    let encoded: String = content
+        .chars()
+        .filter(|c| !c.is_ascii_whitespace())
+        .collect();
+    STANDARD.decode(encoded).map_err(|e| ContentDecodeError {
+        binding: binding.to_string(),
+        reason: e.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+    const NOT_UTF8: &[u8] = &[0x30, 0x82, 0xff, 0xfe, 0x00, 0x80];
+
+    #[test]
+    fn test_binary_binding_round_trip() -> TestResult {
+        assert_eq!(binding_encoding(CB_SMIME), content_encoding::BASE64);
+        let encoded = encode_content(content_encoding::BASE64, NOT_UTF8);
+        assert_eq!(encoded, "MIL//gCA");
+        assert_eq!(decode_content(CB_SMIME, &encoded)?, NOT_UTF8);
+        assert_eq!(decode_content(CB_SMIME, "MIL/\n/gCA\n")?, NOT_UTF8);
+        assert!(decode_content(CB_SMIME, "not base64!").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_text_binding() -> TestResult {
+        let xml = "<stix:STIX_Package/>";
+        assert_eq!(binding_encoding(CB_STIX_XML_12), content_encoding::TEXT);
+        assert_eq!(encode_content(content_encoding::TEXT, xml.as_bytes()), xml);
+        assert_eq!(decode_content(CB_STIX_XML_12, xml)?, xml.as_bytes());
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_binding() -> TestResult {
+        // Content is stored and sent as the text it was received as
+        assert_eq!(binding_encoding("urn:custom:csv"), content_encoding::TEXT);
+        assert_eq!(decode_content("urn:custom:csv", "a,b")?, b"a,b");
+        assert_eq!(encode_content(content_encoding::TEXT, b"a,b"), "a,b");
+        Ok(())
+    }
+
+    #[test]
+    fn test_text_encoding_of_binary_binding() {
+        // S/MIME blocks stored before encodings were recorded hold the
+        // base64 text they were received as
+        assert_eq!(
+            encode_content(content_encoding::TEXT, b"MIL//gCA"),
+            "MIL//gCA"
+        );
+    }
+}
@@ -2,7 +2,12 @@
 
 use thiserror::Error;
 
-use crate::constants::StatusType;
+use crate::constants::{
+    SD_SUPPORTED_BINDING, SD_SUPPORTED_CONTENT, SD_SUPPORTED_PROTOCOL, SD_SUPPORTED_QUERY,
+    StatusType,
+};
+use crate::messages::common::StatusDetail;
+use crate::messages::{messages_10 as tm10, messages_11 as tm11};
 
 /// TAXII 1.x result type.
 pub type Taxii1xResult<T> = Result<T, Taxii1xError>;
@@ -67,6 +72,35 @@ pub enum Taxii1xError {
     /// Database error.
     #[error("Database error: {0}")]
     Database(#[from] taxii_db::DatabaseError),
+
+    /// I/O error, e.g. reading a spooled message.
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// HTTP error talking to a remote service.
+    #[error("HTTP error: {0}")]
+    Http(String),
+
+    /// Remote service responded with an HTTP error status and no Status
+    /// Message.
+    #[error("Remote service responded with HTTP {0}")]
+    HttpStatus(reqwest::StatusCode),
+
+    /// Remote service responded with an unexpected message.
+    #[error("Unexpected response: {0}")]
+    UnexpectedResponse(String),
+}
+
+/// Status detail name listing the supported values of an `UNSUPPORTED_*`
+/// status.
+fn supported_values_detail(status_type: StatusType) -> Option<&'static str> {
+    match status_type {
+        StatusType::UnsupportedMessageBinding => Some(SD_SUPPORTED_BINDING),
+        StatusType::UnsupportedContentBinding => Some(SD_SUPPORTED_CONTENT),
+        StatusType::UnsupportedProtocol => Some(SD_SUPPORTED_PROTOCOL),
+        StatusType::UnsupportedQuery => Some(SD_SUPPORTED_QUERY),
+        _ => None,
+    }
 }
 
 /// Format XML error context for display.
@@ -133,6 +167,59 @@ impl Taxii1xError {
         }
     }
 
+    /// TAXII 1.1 Status Message reporting this error.
+    ///
+    /// Errors other than status messages are reported as `FAILURE`. The
+    /// detail of an `UNSUPPORTED_*` status lists the supported values,
+    /// separated by `", "`; each becomes a `Status_Detail` entry under the
+    /// name the specification gives it. Other details are left out.
+    pub fn to_status_message_11(&self, message_id: impl Into<String>) -> tm11::StatusMessage {
+        let Self::StatusMessage {
+            message,
+            in_response_to,
+            status_type,
+            status_detail,
+        } = self
+        else {
+            return tm11::StatusMessage::failure(message_id, None, Some(self.to_string()));
+        };
+
+        let mut status =
+            tm11::StatusMessage::failure(message_id, in_response_to.clone(), Some(message.clone()));
+        status.status_type = status_type.as_str().to_string();
+        if let (Some(name), Some(detail)) = (supported_values_detail(*status_type), status_detail) {
+            status.status_details = detail
+                .split(", ")
+                .map(|value| StatusDetail {
+                    name: name.to_string(),
+                    value: Some(value.to_string()),
+                })
+                .collect();
+        }
+        status
+    }
+
+    /// TAXII 1.0 Status Message reporting this error.
+    ///
+    /// Errors other than status messages are reported as `FAILURE`.
+    pub fn to_status_message_10(&self, message_id: impl Into<String>) -> tm10::StatusMessage {
+        let Self::StatusMessage {
+            message,
+            in_response_to,
+            status_type,
+            status_detail,
+        } = self
+        else {
+            return tm10::StatusMessage::failure(message_id, None, Some(self.to_string()));
+        };
+
+        let mut status =
+            tm10::StatusMessage::failure(message_id, in_response_to.clone(), Some(message.clone()));
+        status.status_type = status_type.as_str().to_string();
+        status.status_detail = status_detail.clone();
+        status
+    }
+
     /// Create an XML parsing error from a quick-xml deserialization error.
     ///
     /// The error message includes position information when available
@@ -0,0 +1,141 @@
+//! Services as advertised to clients.
+//!
+//! Discovery, Collection Information and subscription responses describe
+//! services by protocol binding, address and message bindings. These come
+//! from the service properties:
+//!
+//! | Property | Default |
+//! |----------|---------|
+//! | `protocol_binding` | Protocol binding of the request |
+//! | `address` | `/services/{id}/` |
+//! | `message_bindings` | XML message binding of the response's TAXII version |
+//! | `available` | `true` |
+//!
+//! A relative address is made absolute with the scheme of the protocol
+//! binding and the service's `domain` property, or the server domain.
+
+use taxii_core::ServiceEntity;
+
+use crate::constants::{VID_TAXII_HTTP_10, VID_TAXII_HTTPS_10};
+
+use super::base::HandlerContext;
+
+/// A service as advertised to clients.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdvertisedService {
+    /// Service type, e.g. `POLL`.
+    pub service_type: String,
+    /// Protocol binding id (HTTP or HTTPS).
+    pub protocol_binding: String,
+    /// Address of the service.
+    pub address: String,
+    /// Supported message binding ids.
+    pub message_bindings: Vec<String>,
+    /// Whether the service is available to the requester.
+    pub available: bool,
+}
+
+impl AdvertisedService {
+    /// Describe a service, with `message_binding` as the default message
+    /// binding.
+    pub fn new<R>(ctx: &HandlerContext<R>, service: &ServiceEntity, message_binding: &str) -> Self {
+        let properties = &service.properties;
+        let protocol_binding = properties
+            .get("protocol_binding")
+            .and_then(|v| v.as_str())
+            .or_else(|| ctx.service.protocol_bindings.first().map(String::as_str))
+            .unwrap_or(VID_TAXII_HTTP_10)
+            .to_string();
+
+        let address = match properties.get("address").and_then(|v| v.as_str()) {
+            Some(address) => address.to_string(),
+            None => format!("/services/{}/", service.id.as_deref().unwrap_or_default()),
+        };
+        let domain = properties
+            .get("domain")
+            .and_then(|v| v.as_str())
+            .or(ctx.domain.as_deref());
+        let address = absolute_address(address, &protocol_binding, domain);
+
+        let message_bindings = properties
+            .get("message_bindings")
+            .and_then(|v| v.as_array())
+            .map(|arr| {
+                arr.iter()
+                    .filter_map(|v| v.as_str().map(String::from))
+                    .collect()
+            })
+            .unwrap_or_else(|| vec![message_binding.to_string()]);
+
+        let available = properties
+            .get("available")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(true);
+
+        Self {
+            service_type: service.service_type.clone(),
+            protocol_binding,
+            address,
+            message_bindings,
+            available,
+        }
+    }
+}
+
+/// Make a relative service address absolute.
+///
+/// Absolute addresses, and addresses without a known domain, are kept.
+fn absolute_address(address: String, protocol_binding: &str, domain: Option<&str>) -> String {
+    if address.contains("://") {
+        return address;
+    }
+    let Some(domain) = domain else {
+        return address;
+    };
+
+    let scheme = if protocol_binding == VID_TAXII_HTTPS_10 {
+        "https"
+    } else {
+        "http"
+    };
+    let domain = domain.trim_end_matches('/');
+    let path = address.trim_start_matches('/');
+    format!("{scheme}://{domain}/{path}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_absolute_address() {
+        assert_eq!(
+            absolute_address(
+                "/services/poll/".to_string(),
+                VID_TAXII_HTTPS_10,
+                Some("taxii.example.com")
+            ),
+            "https://taxii.example.com/services/poll/"
+        );
+        assert_eq!(
+            absolute_address(
+                "services/poll/".to_string(),
+                VID_TAXII_HTTP_10,
+                Some("localhost:9000/")
+            ),
+            "http://localhost:9000/services/poll/"
+        );
+        assert_eq!(
+            absolute_address(
+                "https://other.example.com/poll/".to_string(),
+                VID_TAXII_HTTP_10,
+                Some("taxii.example.com")
+            ),
+            "https://other.example.com/poll/"
+        );
+        assert_eq!(
+            absolute_address("/services/poll/".to_string(), VID_TAXII_HTTP_10, None),
+            "/services/poll/"
+        );
+    }
+}
@@ -14,15 +14,26 @@
 //!
 //! - `X-TAXII-Content-Type`: The message binding (e.g., `urn:taxii.mitre.org:message:xml:1.1`)
 //! - `X-TAXII-Services`: The services binding (e.g., `urn:taxii.mitre.org:services:1.1`)
+//! - `X-TAXII-Protocol`: (Optional) The protocol binding (HTTP or HTTPS)
 //! - `X-TAXII-Accept`: (Optional) Preferred response format
 
 use std::sync::Arc;
 
+use axum::http::HeaderMap;
+use taxii_db::DbTaxii1Repository;
+
 use crate::constants::{
-    VID_TAXII_SERVICES_10, VID_TAXII_SERVICES_11, VID_TAXII_XML_10, VID_TAXII_XML_11,
+    StatusType, VID_TAXII_SERVICES_10, VID_TAXII_SERVICES_11, VID_TAXII_XML_10,
 };
 use crate::error::{Taxii1xError, Taxii1xResult};
+use crate::http::{
+    HTTP_CONTENT_TYPE, HTTP_CONTENT_XML, HTTP_X_TAXII_ACCEPT, HTTP_X_TAXII_CONTENT_TYPE,
+    HTTP_X_TAXII_PROTOCOL, HTTP_X_TAXII_SERVICES, PROTOCOL_BINDINGS, SERVICE_BINDINGS,
+    SUPPORTED_MESSAGE_BINDINGS,
+};
+use crate::limits::MessageLimits;
 use crate::messages::common::generate_message_id;
+use crate::push::PushPolicy;
 
 /// TAXII-specific HTTP headers extracted from a request.
 ///
@@ -40,15 +51,24 @@ use crate::messages::common::generate_message_id;
 /// - TAXII 1.0: `urn:taxii.mitre.org:message:xml:1.0`
 /// - TAXII 1.1: `urn:taxii.mitre.org:message:xml:1.1`
 ///
+/// # Validation
+///
+/// [`TaxiiHeaders::from_header_map`] rejects requests missing a mandatory
+/// header, and [`TaxiiHeaders::validate`] checks the values against the
+/// parsed message. Violations are status errors of the type the TAXII HTTP
+/// binding prescribes, with the supported values as status detail:
+///
+/// | Violation | Status |
+/// |-----------|--------|
+/// | Missing `Content-Type`, `X-TAXII-Content-Type` or `X-TAXII-Services`, or a non-XML `Content-Type` | `BAD_MESSAGE` |
+/// | Unknown `X-TAXII-Protocol` | `UNSUPPORTED_PROTOCOL` |
+/// | `X-TAXII-Content-Type`, `X-TAXII-Services` or `X-TAXII-Accept` not matching the message | `UNSUPPORTED_MESSAGE` |
+///
 /// # Example
 ///
 /// ```ignore
-/// let headers = TaxiiHeaders {
-///     content_type: "urn:taxii.mitre.org:message:xml:1.1".to_string(),
-///     services: "urn:taxii.mitre.org:services:1.1".to_string(),
-///     accept: Some("urn:taxii.mitre.org:message:xml:1.1".to_string()),
-/// };
-/// headers.validate_11(Some("msg-123"))?;
+/// let headers = TaxiiHeaders::from_header_map(&request_headers)?;
+/// headers.validate(message.version(), Some(message.message_id()))?;
 /// ```
 #[derive(Debug, Clone)]
 pub struct TaxiiHeaders {
@@ -67,64 +87,130 @@ pub struct TaxiiHeaders {
     /// When present, indicates the client's preferred response format.
     /// If absent, the server typically responds using the same binding as the request.
     pub accept: Option<String>,
+
+    /// The optional `X-TAXII-Protocol` header value naming the protocol
+    /// binding the client used.
+    pub protocol: Option<String>,
 }
 
 impl TaxiiHeaders {
-    /// Validate headers for TAXII 1.0.
-    pub fn validate_10(&self, in_response_to: Option<&str>) -> Taxii1xResult<()> {
-        if self.services != VID_TAXII_SERVICES_10 {
-            return Err(Taxii1xError::failure(
+    /// Read the TAXII headers of a request.
+    ///
+    /// Returns a `BAD_MESSAGE` status error when `Content-Type`,
+    /// `X-TAXII-Content-Type` or `X-TAXII-Services` is missing, or when
+    /// `Content-Type` is not `application/xml`.
+    pub fn from_header_map(headers: &HeaderMap) -> Taxii1xResult<Self> {
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+        };
+        let required = |name: &'static str| {
+            header(name).ok_or_else(|| {
+                Taxii1xError::status(
+                    StatusType::BadMessage,
+                    format!("Header {name} was not specified"),
+                    None,
+                )
+            })
+        };
+
+        let http_content_type = required(HTTP_CONTENT_TYPE)?;
+        let media_type = http_content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim();
+        if !media_type.eq_ignore_ascii_case(HTTP_CONTENT_XML) {
+            return Err(Taxii1xError::status(
+                StatusType::BadMessage,
                 format!(
-                    "The specified value of X-TAXII-Services is not supported: {}",
-                    self.services
+                    "The specified value of {HTTP_CONTENT_TYPE} is not supported: {http_content_type}, expected {HTTP_CONTENT_XML}"
                 ),
-                in_response_to.map(String::from),
+                None,
             ));
         }
 
-        if self.content_type != VID_TAXII_XML_10 {
-            return Err(Taxii1xError::failure(
-                "The specified value of X-TAXII-Content-Type is not supported",
-                in_response_to.map(String::from),
-            ));
-        }
+        Ok(Self {
+            content_type: required(HTTP_X_TAXII_CONTENT_TYPE)?.to_string(),
+            services: required(HTTP_X_TAXII_SERVICES)?.to_string(),
+            accept: header(HTTP_X_TAXII_ACCEPT).map(String::from),
+            protocol: header(HTTP_X_TAXII_PROTOCOL).map(String::from),
+        })
+    }
 
-        if let Some(ref accept) = self.accept {
-            if accept != VID_TAXII_XML_10 {
-                return Err(Taxii1xError::failure(
-                    "The specified value of X-TAXII-Accept is not supported",
-                    in_response_to.map(String::from),
+    /// Validate the headers against a parsed message of `message_binding`
+    /// (e.g. [`crate::constants::VID_TAXII_XML_11`]).
+    ///
+    /// The message binding of the response is always the one of the
+    /// request, so `X-TAXII-Accept` must name it when present.
+    pub fn validate(
+        &self,
+        message_binding: &str,
+        in_response_to: Option<&str>,
+    ) -> Taxii1xResult<()> {
+        let in_response_to = in_response_to.map(String::from);
+
+        if let Some(ref protocol) = self.protocol {
+            if !PROTOCOL_BINDINGS.contains(&protocol.as_str()) {
+                return Err(Taxii1xError::status_with_detail(
+                    StatusType::UnsupportedProtocol,
+                    format!(
+                        "The specified value of {HTTP_X_TAXII_PROTOCOL} is not supported: {protocol}"
+                    ),
+                    in_response_to,
+                    PROTOCOL_BINDINGS.join(", "),
                 ));
             }
         }
 
-        Ok(())
-    }
-
-    /// Validate headers for TAXII 1.1.
-    pub fn validate_11(&self, in_response_to: Option<&str>) -> Taxii1xResult<()> {
-        if self.services != VID_TAXII_SERVICES_11 {
-            return Err(Taxii1xError::failure(
+        if !SUPPORTED_MESSAGE_BINDINGS.contains(&self.content_type.as_str()) {
+            return Err(Taxii1xError::status_with_detail(
+                StatusType::UnsupportedMessageBinding,
                 format!(
-                    "The specified value of X-TAXII-Services is not supported: {}",
-                    self.services
+                    "The specified value of {HTTP_X_TAXII_CONTENT_TYPE} is not supported: {}",
+                    self.content_type
                 ),
-                in_response_to.map(String::from),
+                in_response_to,
+                SUPPORTED_MESSAGE_BINDINGS.join(", "),
+            ));
+        }
+        if self.content_type != message_binding {
+            return Err(Taxii1xError::status_with_detail(
+                StatusType::UnsupportedMessageBinding,
+                format!(
+                    "{HTTP_X_TAXII_CONTENT_TYPE} {} does not match the message binding of the message: {message_binding}",
+                    self.content_type
+                ),
+                in_response_to,
+                message_binding,
             ));
         }
 
-        if self.content_type != VID_TAXII_XML_11 {
-            return Err(Taxii1xError::failure(
-                "The specified value of X-TAXII-Content-Type is not supported",
-                in_response_to.map(String::from),
+        let services = services_binding(message_binding);
+        if !SERVICE_BINDINGS.contains(&self.services.as_str()) || self.services != services {
+            return Err(Taxii1xError::status_with_detail(
+                StatusType::UnsupportedMessageBinding,
+                format!(
+                    "The specified value of {HTTP_X_TAXII_SERVICES} is not supported with {message_binding}: {}",
+                    self.services
+                ),
+                in_response_to,
+                services,
             ));
         }
 
         if let Some(ref accept) = self.accept {
-            if accept != VID_TAXII_XML_11 {
-                return Err(Taxii1xError::failure(
-                    "The specified value of X-TAXII-Accept is not supported",
-                    in_response_to.map(String::from),
+            if accept != message_binding {
+                return Err(Taxii1xError::status_with_detail(
+                    StatusType::UnsupportedMessageBinding,
+                    format!(
+                        "The specified value of {HTTP_X_TAXII_ACCEPT} is not supported with {message_binding}: {accept}"
+                    ),
+                    in_response_to,
+                    message_binding,
                 ));
             }
         }
@@ -133,6 +219,15 @@ impl TaxiiHeaders {
     }
 }
 
+/// Services binding matching a message binding.
+fn services_binding(message_binding: &str) -> &'static str {
+    if message_binding == VID_TAXII_XML_10 {
+        VID_TAXII_SERVICES_10
+    } else {
+        VID_TAXII_SERVICES_11
+    }
+}
+
 /// Execution context for TAXII 1.x message handlers.
 ///
 /// This struct provides handlers with all the resources needed to process
@@ -145,7 +240,8 @@ impl TaxiiHeaders {
 ///   authorization checks on collections and services.
 ///
 /// - **Persistence**: Database access layer for collections, content blocks,
-///   subscriptions, and other TAXII entities.
+///   subscriptions, and other TAXII entities. Any
+///   [`taxii_db::Taxii1Repository`] works, e.g. the in-memory one in tests.
 ///
 /// - **Service**: Configuration for the specific TAXII service endpoint handling
 ///   this request (Discovery, Poll, Inbox, etc.).
@@ -157,8 +253,7 @@ impl TaxiiHeaders {
 ///
 /// The context uses `Arc` for shared ownership of the persistence layer,
 /// allowing concurrent request handling.
-#[derive(Clone)]
-pub struct HandlerContext {
+pub struct HandlerContext<R = DbTaxii1Repository> {
     /// The authenticated account making this request, if any.
     ///
     /// When `None`, the request is anonymous. Handlers should check this
@@ -169,7 +264,7 @@ pub struct HandlerContext {
     ///
     /// Provides access to collections, content blocks, subscriptions,
     /// inbox messages, and result sets.
-    pub persistence: Arc<taxii_db::DbTaxii1Repository>,
+    pub persistence: Arc<R>,
 
     /// Configuration and metadata for the TAXII service handling this request.
     ///
@@ -177,6 +272,23 @@ pub struct HandlerContext {
     /// have different configurations and capabilities.
     pub service: ServiceInfo,
 
+    /// External domain of the server (host and optional port).
+    ///
+    /// Used to make the addresses of advertised services absolute; see
+    /// [`super::advertised`].
+    pub domain: Option<String>,
+
+    /// Size limits of the service's messages.
+    ///
+    /// The server defaults overridden by the service properties; see
+    /// [`crate::limits`].
+    pub limits: MessageLimits,
+
+    /// Whether, and to which addresses, content may be pushed.
+    ///
+    /// Push is off unless the server enables it; see [`PushPolicy`].
+    pub push: PushPolicy,
+
     /// Optional hook registry for emitting events.
     ///
     /// Used to notify external systems when content blocks are created,
@@ -184,11 +296,45 @@ pub struct HandlerContext {
     pub hooks: Option<taxii_core::SharedHookRegistry>,
 }
 
-impl std::fmt::Debug for HandlerContext {
+impl<R> HandlerContext<R> {
+    /// ID of the account making the request, `None` if anonymous.
+    pub fn account_id(&self) -> Option<i32> {
+        self.account.as_ref().map(|account| account.id)
+    }
+
+    /// Check if a subscription was created by the account making the
+    /// request.
+    ///
+    /// Clients only see and manage their own subscriptions: anonymous
+    /// requests those created anonymously, and authenticated requests
+    /// those created by the same account.
+    pub fn owns(&self, subscription: &taxii_core::SubscriptionEntity) -> bool {
+        subscription.account_id == self.account_id()
+    }
+}
+
+impl<R> Clone for HandlerContext<R> {
+    fn clone(&self) -> Self {
+        Self {
+            account: self.account.clone(),
+            persistence: Arc::clone(&self.persistence),
+            service: self.service.clone(),
+            domain: self.domain.clone(),
+            limits: self.limits,
+            push: self.push,
+            hooks: self.hooks.clone(),
+        }
+    }
+}
+
+impl<R> std::fmt::Debug for HandlerContext<R> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("HandlerContext")
             .field("account", &self.account)
             .field("service", &self.service)
+            .field("domain", &self.domain)
+            .field("limits", &self.limits)
+            .field("push", &self.push)
             .finish_non_exhaustive()
     }
 }
@@ -220,6 +366,7 @@ impl std::fmt::Debug for HandlerContext {
 /// Service-specific configuration is stored in `properties` as JSON. Common
 /// properties include:
 /// - `max_result_size`: Maximum content blocks per poll response
+/// - `max_result_bytes`: Maximum content bytes per poll response (see [`crate::limits`])
 /// - `subscription_required`: Whether polling requires a subscription
 /// - `destination_collection_required`: Whether inbox requires explicit destinations
 #[derive(Debug, Clone)]
@@ -276,6 +423,245 @@ impl ServiceInfo {
 // See handlers/mod.rs for the Handler enum implementation
 
 /// Generate a unique message ID.
+///
+/// Used for response message IDs and result set IDs; see
+/// [`generate_message_id`] for why concurrent polls cannot collide.
 pub fn generate_id() -> String {
     generate_message_id()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{VID_TAXII_HTTP_10, VID_TAXII_HTTPS_10, VID_TAXII_XML_11};
+    use axum::http::HeaderValue;
+
+    type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+    const BINDINGS: [(&str, &str); 2] = [
+        (VID_TAXII_XML_10, VID_TAXII_SERVICES_10),
+        (VID_TAXII_XML_11, VID_TAXII_SERVICES_11),
+    ];
+
+    fn header_map(
+        content_type: &str,
+        services: &str,
+    ) -> Result<HeaderMap, Box<dyn std::error::Error>> {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            HTTP_CONTENT_TYPE,
+            HeaderValue::from_static(HTTP_CONTENT_XML),
+        );
+        headers.insert(
+            HTTP_X_TAXII_CONTENT_TYPE,
+            HeaderValue::from_str(content_type)?,
+        );
+        headers.insert(HTTP_X_TAXII_SERVICES, HeaderValue::from_str(services)?);
+        Ok(headers)
+    }
+
+    /// Status type and detail of the error `result` failed with.
+    fn status_of<T>(result: Taxii1xResult<T>) -> Option<(StatusType, Option<String>)> {
+        match result {
+            Err(Taxii1xError::StatusMessage {
+                status_type,
+                status_detail,
+                ..
+            }) => Some((status_type, status_detail)),
+            _ => None,
+        }
+    }
+
+    fn validate(headers: &HeaderMap, message_binding: &str) -> Taxii1xResult<()> {
+        TaxiiHeaders::from_header_map(headers)?.validate(message_binding, Some("1"))
+    }
+
+    #[test]
+    fn test_valid_headers() -> TestResult {
+        for (binding, services) in BINDINGS {
+            let mut headers = header_map(binding, services)?;
+            headers.insert(
+                HTTP_CONTENT_TYPE,
+                HeaderValue::from_static("application/xml; charset=utf-8"),
+            );
+            headers.insert(
+                HTTP_X_TAXII_PROTOCOL,
+                HeaderValue::from_static(VID_TAXII_HTTPS_10),
+            );
+            headers.insert(HTTP_X_TAXII_ACCEPT, HeaderValue::from_str(binding)?);
+
+            let taxii_headers = TaxiiHeaders::from_header_map(&headers)?;
+            assert_eq!(taxii_headers.protocol.as_deref(), Some(VID_TAXII_HTTPS_10));
+            taxii_headers.validate(binding, Some("1"))?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_mandatory_header() -> TestResult {
+        for (binding, services) in BINDINGS {
+            for missing in [
+                HTTP_CONTENT_TYPE,
+                HTTP_X_TAXII_CONTENT_TYPE,
+                HTTP_X_TAXII_SERVICES,
+            ] {
+                let mut headers = header_map(binding, services)?;
+                headers.remove(missing);
+
+                let result = TaxiiHeaders::from_header_map(&headers);
+                assert!(matches!(&result, Err(e) if e.to_string().contains(missing)));
+                assert_eq!(status_of(result), Some((StatusType::BadMessage, None)));
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_non_xml_content_type() -> TestResult {
+        let mut headers = header_map(VID_TAXII_XML_11, VID_TAXII_SERVICES_11)?;
+        headers.insert(
+            HTTP_CONTENT_TYPE,
+            HeaderValue::from_static("application/json"),
+        );
+
+        assert_eq!(
+            status_of(TaxiiHeaders::from_header_map(&headers)),
+            Some((StatusType::BadMessage, None))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_unsupported_protocol() -> TestResult {
+        for (binding, services) in BINDINGS {
+            let mut headers = header_map(binding, services)?;
+            headers.insert(
+                HTTP_X_TAXII_PROTOCOL,
+                HeaderValue::from_static("urn:taxii.mitre.org:protocol:ftp:1.0"),
+            );
+
+            assert_eq!(
+                status_of(validate(&headers, binding)),
+                Some((
+                    StatusType::UnsupportedProtocol,
+                    Some(format!("{VID_TAXII_HTTP_10}, {VID_TAXII_HTTPS_10}"))
+                ))
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_unsupported_message_binding() -> TestResult {
+        let headers = header_map(
+            "urn:taxii.mitre.org:message:json:1.1",
+            VID_TAXII_SERVICES_11,
+        )?;
+
+        assert_eq!(
+            status_of(validate(&headers, VID_TAXII_XML_11)),
+            Some((
+                StatusType::UnsupportedMessageBinding,
+                Some(format!("{VID_TAXII_XML_10}, {VID_TAXII_XML_11}"))
+            ))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_content_type_not_matching_message() -> TestResult {
+        for ((declared, services), (detected, _)) in
+            [(BINDINGS[0], BINDINGS[1]), (BINDINGS[1], BINDINGS[0])]
+        {
+            assert_eq!(
+                status_of(validate(&header_map(declared, services)?, detected)),
+                Some((
+                    StatusType::UnsupportedMessageBinding,
+                    Some(detected.to_string())
+                ))
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_services_not_matching_message() -> TestResult {
+        for (binding, services, expected) in [
+            (
+                VID_TAXII_XML_10,
+                VID_TAXII_SERVICES_11,
+                VID_TAXII_SERVICES_10,
+            ),
+            (
+                VID_TAXII_XML_11,
+                VID_TAXII_SERVICES_10,
+                VID_TAXII_SERVICES_11,
+            ),
+            (
+                VID_TAXII_XML_11,
+                "urn:taxii.mitre.org:services:2.0",
+                VID_TAXII_SERVICES_11,
+            ),
+        ] {
+            assert_eq!(
+                status_of(validate(&header_map(binding, services)?, binding)),
+                Some((
+                    StatusType::UnsupportedMessageBinding,
+                    Some(expected.to_string())
+                ))
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_accept_not_matching_message() -> TestResult {
+        for ((binding, services), (other, _)) in
+            [(BINDINGS[0], BINDINGS[1]), (BINDINGS[1], BINDINGS[0])]
+        {
+            let mut headers = header_map(binding, services)?;
+            headers.insert(HTTP_X_TAXII_ACCEPT, HeaderValue::from_str(other)?);
+
+            assert_eq!(
+                status_of(validate(&headers, binding)),
+                Some((
+                    StatusType::UnsupportedMessageBinding,
+                    Some(binding.to_string())
+                ))
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_status_message_lists_supported_values() {
+        let error = Taxii1xError::status_with_detail(
+            StatusType::UnsupportedProtocol,
+            "unsupported",
+            Some("1".to_string()),
+            PROTOCOL_BINDINGS.join(", "),
+        );
+
+        let status = error.to_status_message_11("2");
+        assert_eq!(status.status_type, StatusType::UnsupportedProtocol.as_str());
+        assert_eq!(status.in_response_to.as_deref(), Some("1"));
+        let details: Vec<_> = status
+            .status_details
+            .iter()
+            .map(|d| (d.name.as_str(), d.value.as_deref()))
+            .collect();
+        assert_eq!(
+            details,
+            [
+                ("SUPPORTED_PROTOCOL", Some(VID_TAXII_HTTP_10)),
+                ("SUPPORTED_PROTOCOL", Some(VID_TAXII_HTTPS_10))
+            ]
+        );
+
+        let status = error.to_status_message_10("2");
+        assert_eq!(
+            status.status_detail.as_deref(),
+            Some(format!("{VID_TAXII_HTTP_10}, {VID_TAXII_HTTPS_10}").as_str())
+        );
+    }
+}
@@ -2,12 +2,13 @@
 
 use crate::constants::{
     CT_DATA_FEED, SVC_COLLECTION_MANAGEMENT, SVC_FEED_MANAGEMENT, SVC_INBOX, SVC_POLL,
-    VID_TAXII_HTTP_10, VID_TAXII_XML_10, VID_TAXII_XML_11,
+    VID_TAXII_XML_10, VID_TAXII_XML_11,
 };
 use crate::error::{Taxii1xError, Taxii1xResult};
 use crate::messages::{tm10, tm11};
 use taxii_db::Taxii1Repository;
 
+use super::advertised::AdvertisedService;
 use super::base::{HandlerContext, TaxiiHeaders, generate_id};
 
 /// Helper to extract string array from JSON value.
@@ -27,9 +28,9 @@ pub struct CollectionInformationRequest11Handler;
 
 impl CollectionInformationRequest11Handler {
     /// Handle a TAXII 1.1 Collection Information Request.
-    pub async fn handle_11(
+    pub async fn handle_11<R: Taxii1Repository>(
         &self,
-        ctx: &HandlerContext,
+        ctx: &HandlerContext<R>,
         _headers: &TaxiiHeaders,
         message: &tm11::Taxii11Message,
     ) -> Taxii1xResult<tm11::Taxii11Message> {
@@ -66,28 +67,11 @@ impl CollectionInformationRequest11Handler {
                 let polling_service_instances: Vec<tm11::PollingServiceInstance> = polling_services
                     .iter()
                     .map(|svc| {
-                        let service_id = svc.id.as_deref().unwrap_or_default();
-                        let address = svc
-                            .properties
-                            .get("address")
-                            .and_then(|v| v.as_str())
-                            .map(String::from)
-                            .unwrap_or_else(|| format!("/services/{service_id}/"));
-                        let protocol = svc
-                            .properties
-                            .get("protocol_binding")
-                            .and_then(|v| v.as_str())
-                            .map(String::from)
-                            .unwrap_or_else(|| VID_TAXII_HTTP_10.to_string());
-                        let message_bindings = extract_string_array(
-                            svc.properties.get("message_bindings"),
-                            vec![VID_TAXII_XML_11.to_string()],
-                        );
-
+                        let svc = AdvertisedService::new(ctx, svc, VID_TAXII_XML_11);
                         tm11::PollingServiceInstance {
-                            poll_protocol: protocol,
-                            poll_address: address,
-                            poll_message_bindings: message_bindings,
+                            poll_protocol: svc.protocol_binding,
+                            poll_address: svc.address,
+                            poll_message_bindings: svc.message_bindings,
                         }
                     })
                     .collect();
@@ -102,28 +86,11 @@ impl CollectionInformationRequest11Handler {
                 let subscription_methods: Vec<tm11::SubscriptionMethod> = subscription_services
                     .iter()
                     .map(|svc| {
-                        let service_id = svc.id.as_deref().unwrap_or_default();
-                        let address = svc
-                            .properties
-                            .get("address")
-                            .and_then(|v| v.as_str())
-                            .map(String::from)
-                            .unwrap_or_else(|| format!("/services/{service_id}/"));
-                        let protocol = svc
-                            .properties
-                            .get("protocol_binding")
-                            .and_then(|v| v.as_str())
-                            .map(String::from)
-                            .unwrap_or_else(|| VID_TAXII_HTTP_10.to_string());
-                        let message_bindings = extract_string_array(
-                            svc.properties.get("message_bindings"),
-                            vec![VID_TAXII_XML_11.to_string()],
-                        );
-
+                        let svc = AdvertisedService::new(ctx, svc, VID_TAXII_XML_11);
                         tm11::SubscriptionMethod {
-                            subscription_protocol: protocol,
-                            subscription_address: address,
-                            subscription_message_bindings: message_bindings,
+                            subscription_protocol: svc.protocol_binding,
+                            subscription_address: svc.address,
+                            subscription_message_bindings: svc.message_bindings,
                         }
                     })
                     .collect();
@@ -138,33 +105,17 @@ impl CollectionInformationRequest11Handler {
                 let receiving_inbox_services: Vec<tm11::ReceivingInboxService> = inbox_services
                     .iter()
                     .map(|svc| {
-                        let service_id = svc.id.as_deref().unwrap_or_default();
-                        let address = svc
-                            .properties
-                            .get("address")
-                            .and_then(|v| v.as_str())
-                            .map(String::from)
-                            .unwrap_or_else(|| format!("/services/{service_id}/"));
-                        let protocol = svc
-                            .properties
-                            .get("protocol_binding")
-                            .and_then(|v| v.as_str())
-                            .map(String::from)
-                            .unwrap_or_else(|| VID_TAXII_HTTP_10.to_string());
-                        let message_bindings = extract_string_array(
-                            svc.properties.get("message_bindings"),
-                            vec![VID_TAXII_XML_11.to_string()],
-                        );
                         let content_bindings: Vec<tm11::ContentBinding> =
                             extract_string_array(svc.properties.get("content_bindings"), vec![])
                                 .into_iter()
                                 .map(|s| tm11::ContentBinding::new(&s))
                                 .collect();
+                        let svc = AdvertisedService::new(ctx, svc, VID_TAXII_XML_11);
 
                         tm11::ReceivingInboxService {
-                            inbox_protocol: protocol,
-                            inbox_address: address,
-                            inbox_message_bindings: message_bindings,
+                            inbox_protocol: svc.protocol_binding,
+                            inbox_address: svc.address,
+                            inbox_message_bindings: svc.message_bindings,
                             supported_contents: content_bindings,
                         }
                     })
@@ -200,9 +151,9 @@ pub struct FeedInformationRequest10Handler;
 
 impl FeedInformationRequest10Handler {
     /// Handle a TAXII 1.0 Feed Information Request.
-    pub async fn handle_10(
+    pub async fn handle_10<R: Taxii1Repository>(
         &self,
-        ctx: &HandlerContext,
+        ctx: &HandlerContext<R>,
         _headers: &TaxiiHeaders,
         message: &tm10::Taxii10Message,
     ) -> Taxii1xResult<tm10::Taxii10Message> {
@@ -244,28 +195,11 @@ impl FeedInformationRequest10Handler {
                 let polling_service_instances: Vec<tm10::PollingServiceInstance> = polling_services
                     .iter()
                     .map(|svc| {
-                        let service_id = svc.id.as_deref().unwrap_or_default();
-                        let address = svc
-                            .properties
-                            .get("address")
-                            .and_then(|v| v.as_str())
-                            .map(String::from)
-                            .unwrap_or_else(|| format!("/services/{service_id}/"));
-                        let protocol = svc
-                            .properties
-                            .get("protocol_binding")
-                            .and_then(|v| v.as_str())
-                            .map(String::from)
-                            .unwrap_or_else(|| VID_TAXII_HTTP_10.to_string());
-                        let message_bindings = extract_string_array(
-                            svc.properties.get("message_bindings"),
-                            vec![VID_TAXII_XML_10.to_string()],
-                        );
-
+                        let svc = AdvertisedService::new(ctx, svc, VID_TAXII_XML_10);
                         tm10::PollingServiceInstance {
-                            poll_protocol: protocol,
-                            poll_address: address,
-                            poll_message_bindings: message_bindings,
+                            poll_protocol: svc.protocol_binding,
+                            poll_address: svc.address,
+                            poll_message_bindings: svc.message_bindings,
                         }
                     })
                     .collect();
@@ -280,28 +214,11 @@ impl FeedInformationRequest10Handler {
                 let subscription_methods: Vec<tm10::SubscriptionMethod> = subscription_services
                     .iter()
                     .map(|svc| {
-                        let service_id = svc.id.as_deref().unwrap_or_default();
-                        let address = svc
-                            .properties
-                            .get("address")
-                            .and_then(|v| v.as_str())
-                            .map(String::from)
-                            .unwrap_or_else(|| format!("/services/{service_id}/"));
-                        let protocol = svc
-                            .properties
-                            .get("protocol_binding")
-                            .and_then(|v| v.as_str())
-                            .map(String::from)
-                            .unwrap_or_else(|| VID_TAXII_HTTP_10.to_string());
-                        let message_bindings = extract_string_array(
-                            svc.properties.get("message_bindings"),
-                            vec![VID_TAXII_XML_10.to_string()],
-                        );
-
+                        let svc = AdvertisedService::new(ctx, svc, VID_TAXII_XML_10);
                         tm10::SubscriptionMethod {
-                            subscription_protocol: protocol,
-                            subscription_address: address,
-                            subscription_message_bindings: message_bindings,
+                            subscription_protocol: svc.protocol_binding,
+                            subscription_address: svc.address,
+                            subscription_message_bindings: svc.message_bindings,
                         }
                     })
                     .collect();
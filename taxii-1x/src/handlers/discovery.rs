@@ -1,13 +1,13 @@
 //! Discovery request handlers.
 
 use crate::constants::{
-    VID_TAXII_HTTP_10, VID_TAXII_SERVICES_10, VID_TAXII_SERVICES_11, VID_TAXII_XML_10,
-    VID_TAXII_XML_11,
+    VID_TAXII_SERVICES_10, VID_TAXII_SERVICES_11, VID_TAXII_XML_10, VID_TAXII_XML_11,
 };
 use crate::error::{Taxii1xError, Taxii1xResult};
 use crate::messages::{tm10, tm11};
 use taxii_db::Taxii1Repository;
 
+use super::advertised::AdvertisedService;
 use super::base::{HandlerContext, TaxiiHeaders, generate_id};
 
 /// TAXII 1.1 Discovery Request Handler.
@@ -15,9 +15,9 @@ pub struct DiscoveryRequest11Handler;
 
 impl DiscoveryRequest11Handler {
     /// Handle a TAXII 1.1 Discovery Request.
-    pub async fn handle_11(
+    pub async fn handle_11<R: Taxii1Repository>(
         &self,
-        ctx: &HandlerContext,
+        ctx: &HandlerContext<R>,
         _headers: &TaxiiHeaders,
         message: &tm11::Taxii11Message,
     ) -> Taxii1xResult<tm11::Taxii11Message> {
@@ -40,41 +40,19 @@ impl DiscoveryRequest11Handler {
             .await?;
 
         response.service_instances = services
-            .into_iter()
+            .iter()
             .map(|service| {
-                let service_id = service.id.as_deref().unwrap_or_default();
-                let address = service
-                    .properties
-                    .get("address")
-                    .and_then(|v| v.as_str())
-                    .map(String::from)
-                    .unwrap_or_else(|| format!("/services/{service_id}/"));
-
-                let protocol_binding = service
-                    .properties
-                    .get("protocol_binding")
-                    .and_then(|v| v.as_str())
-                    .map(String::from)
-                    .unwrap_or_else(|| VID_TAXII_HTTP_10.to_string());
-
-                let message_bindings: Vec<String> = service
-                    .properties
-                    .get("message_bindings")
-                    .and_then(|v| v.as_array())
-                    .map(|arr| {
-                        arr.iter()
-                            .filter_map(|v| v.as_str().map(String::from))
-                            .collect()
-                    })
-                    .unwrap_or_else(|| vec![VID_TAXII_XML_11.to_string()]);
-
-                tm11::ServiceInstance::new(
-                    service.service_type,
+                let advertised = AdvertisedService::new(ctx, service, VID_TAXII_XML_11);
+                let mut instance = tm11::ServiceInstance::new(
+                    advertised.service_type,
                     VID_TAXII_SERVICES_11,
-                    protocol_binding,
-                    address,
-                    message_bindings,
-                )
+                    advertised.protocol_binding,
+                    advertised.address,
+                    advertised.message_bindings,
+                );
+                // Unavailable services are still listed
+                instance.available = Some(advertised.available);
+                instance
             })
             .collect();
 
@@ -87,9 +65,9 @@ pub struct DiscoveryRequest10Handler;
 
 impl DiscoveryRequest10Handler {
     /// Handle a TAXII 1.0 Discovery Request.
-    pub async fn handle_10(
+    pub async fn handle_10<R: Taxii1Repository>(
         &self,
-        ctx: &HandlerContext,
+        ctx: &HandlerContext<R>,
         _headers: &TaxiiHeaders,
         message: &tm10::Taxii10Message,
     ) -> Taxii1xResult<tm10::Taxii10Message> {
@@ -112,44 +90,151 @@ impl DiscoveryRequest10Handler {
             .await?;
 
         response.service_instances = services
-            .into_iter()
+            .iter()
             .map(|service| {
-                let service_id = service.id.as_deref().unwrap_or_default();
-                let address = service
-                    .properties
-                    .get("address")
-                    .and_then(|v| v.as_str())
-                    .map(String::from)
-                    .unwrap_or_else(|| format!("/services/{service_id}/"));
-
-                let protocol_binding = service
-                    .properties
-                    .get("protocol_binding")
-                    .and_then(|v| v.as_str())
-                    .map(String::from)
-                    .unwrap_or_else(|| VID_TAXII_HTTP_10.to_string());
-
-                let message_bindings: Vec<String> = service
-                    .properties
-                    .get("message_bindings")
-                    .and_then(|v| v.as_array())
-                    .map(|arr| {
-                        arr.iter()
-                            .filter_map(|v| v.as_str().map(String::from))
-                            .collect()
-                    })
-                    .unwrap_or_else(|| vec![VID_TAXII_XML_10.to_string()]);
-
-                tm10::ServiceInstance::new(
-                    service.service_type,
+                let advertised = AdvertisedService::new(ctx, service, VID_TAXII_XML_10);
+                let mut instance = tm10::ServiceInstance::new(
+                    advertised.service_type,
                     VID_TAXII_SERVICES_10,
-                    protocol_binding,
-                    address,
-                    message_bindings,
-                )
+                    advertised.protocol_binding,
+                    advertised.address,
+                    advertised.message_bindings,
+                );
+                // Unavailable services are still listed
+                instance.available = Some(advertised.available);
+                instance
             })
             .collect();
 
         Ok(tm10::Taxii10Message::DiscoveryResponse(response))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::VID_TAXII_HTTPS_10;
+    use crate::handlers::ServiceInfo;
+    use crate::messages::{NS_TAXII_10, NS_TAXII_11};
+    use std::sync::Arc;
+    use taxii_core::ServiceEntity;
+    use taxii_db::InMemoryTaxii1Repository;
+
+    type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+    const DISCOVERY_11: &str = include_str!("../../tests/fixtures/discovery_response_11.xml");
+    const DISCOVERY_10: &str = include_str!("../../tests/fixtures/discovery_response_10.xml");
+
+    /// A discovery service, requested over HTTPS, advertising an inbox at
+    /// the server domain and an unavailable poll service with an absolute
+    /// address, in an in-memory repository.
+    async fn setup() -> Result<HandlerContext<InMemoryTaxii1Repository>, Box<dyn std::error::Error>>
+    {
+        let persistence = Arc::new(InMemoryTaxii1Repository::new());
+        for (id, service_type, properties) in [
+            (
+                "discovery",
+                "DISCOVERY",
+                serde_json::json!({"advertised_services": ["inbox", "poll"]}),
+            ),
+            ("inbox", "INBOX", serde_json::json!({})),
+            (
+                "poll",
+                "POLL",
+                serde_json::json!({
+                    "address": "https://poll.example.com/taxii/poll/",
+                    "protocol_binding": VID_TAXII_HTTPS_10,
+                    "message_bindings": [VID_TAXII_XML_10, VID_TAXII_XML_11],
+                    "available": false
+                }),
+            ),
+        ] {
+            persistence
+                .update_service(&ServiceEntity {
+                    id: Some(id.to_string()),
+                    service_type: service_type.to_string(),
+                    properties,
+                })
+                .await?;
+        }
+
+        Ok(HandlerContext {
+            account: None,
+            persistence,
+            service: ServiceInfo {
+                id: "discovery".to_string(),
+                service_type: "DISCOVERY".to_string(),
+                address: "/services/discovery/".to_string(),
+                description: None,
+                protocol_bindings: vec![VID_TAXII_HTTPS_10.to_string()],
+                message_bindings: vec![VID_TAXII_XML_11.to_string()],
+                available: true,
+                authentication_required: false,
+                properties: serde_json::json!({}),
+            },
+            domain: Some("taxii.example.com".to_string()),
+            limits: Default::default(),
+            push: Default::default(),
+            hooks: None,
+        })
+    }
+
+    fn headers(content_type: &str, services: &str) -> TaxiiHeaders {
+        TaxiiHeaders {
+            content_type: content_type.to_string(),
+            services: services.to_string(),
+            accept: None,
+            protocol: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_discovery_response_11() -> TestResult {
+        let ctx = setup().await?;
+        let request = tm11::Taxii11Message::DiscoveryRequest(tm11::DiscoveryRequest {
+            xmlns: NS_TAXII_11.to_string(),
+            message_id: "1".to_string(),
+            extended_headers: None,
+        });
+        let response = DiscoveryRequest11Handler
+            .handle_11(
+                &ctx,
+                &headers(VID_TAXII_XML_11, VID_TAXII_SERVICES_11),
+                &request,
+            )
+            .await?;
+        let tm11::Taxii11Message::DiscoveryResponse(mut response) = response else {
+            return Err("expected discovery response".into());
+        };
+        response.message_id = "2".to_string();
+
+        let xml = tm11::Taxii11Message::DiscoveryResponse(response).to_xml()?;
+        assert_eq!(xml, DISCOVERY_11.trim_end());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_discovery_response_10() -> TestResult {
+        let ctx = setup().await?;
+        let request = tm10::Taxii10Message::DiscoveryRequest(tm10::DiscoveryRequest {
+            xmlns: NS_TAXII_10.to_string(),
+            message_id: "1".to_string(),
+            extended_headers: None,
+        });
+        let response = DiscoveryRequest10Handler
+            .handle_10(
+                &ctx,
+                &headers(VID_TAXII_XML_10, VID_TAXII_SERVICES_10),
+                &request,
+            )
+            .await?;
+        let tm10::Taxii10Message::DiscoveryResponse(mut response) = response else {
+            return Err("expected discovery response".into());
+        };
+        response.message_id = "2".to_string();
+
+        let xml = tm10::Taxii10Message::DiscoveryResponse(response).to_xml()?;
+        assert_eq!(xml, DISCOVERY_10.trim_end());
+        Ok(())
+    }
+}
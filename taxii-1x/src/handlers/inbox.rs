@@ -2,16 +2,18 @@
 
 use chrono::Utc;
 
-use crate::constants::{SD_ACCEPTABLE_DESTINATION, SD_ITEM, StatusType};
+use crate::constants::{SD_ACCEPTABLE_DESTINATION, SD_DESTINATION_COLLECTION_NAME, StatusType};
+use crate::content::{binding_encoding, decode_content};
 use crate::error::{Taxii1xError, Taxii1xResult};
 use crate::messages::{tm10, tm11};
+use crate::validation::{ContentValidationError, is_stix_xml_binding, validate_stix_content};
 use taxii_db::Taxii1Repository;
 
 use super::base::{HandlerContext, TaxiiHeaders, generate_id};
 
 use taxii_core::{
     CollectionEntity, ContentBindingEntity, ContentBlockCreatedEvent, ContentBlockEntity,
-    InboxMessageCreatedEvent, InboxMessageEntity,
+    InboxMessageCreatedEvent, InboxMessageEntity, signature_policy, validation_mode,
 };
 
 /// Result of validating destination collections.
@@ -26,12 +28,15 @@ struct ValidatedDestinations {
 ///
 /// Validates that:
 /// - Destinations are provided if required (or not provided if prohibited)
-/// - All specified destinations exist and are available
+/// - All specified destinations exist and are available; unknown names are
+///   reported together in the `DESTINATION_COLLECTION_NAME` status detail
 /// - User has permission to modify the collections
-async fn validate_destinations_11(
-    ctx: &HandlerContext,
+///
+/// A collection named more than once is a single destination.
+async fn validate_destinations_11<R: Taxii1Repository>(
+    ctx: &HandlerContext<R>,
     message_id: &str,
-    destination_names: Vec<String>,
+    mut destination_names: Vec<String>,
 ) -> Taxii1xResult<ValidatedDestinations> {
     // Get service configuration
     let destination_collection_required = ctx
@@ -70,31 +75,39 @@ async fn validate_destinations_11(
         });
     }
 
-    // If no destination specified and not required, use all service collections
-    let user_specified = !destination_names.is_empty();
-    let names_to_use = if destination_names.is_empty() {
-        available_names.clone()
-    } else {
-        destination_names.clone()
-    };
+    let mut seen = std::collections::HashSet::new();
+    destination_names.retain(|name| seen.insert(name.clone()));
 
     // Build map of available collections for validation
     let available_set: std::collections::HashSet<&str> =
         available_names.iter().map(String::as_str).collect();
 
+    // Every named collection must be an available collection of the service
+    let unknown_names: Vec<&str> = destination_names
+        .iter()
+        .map(String::as_str)
+        .filter(|name| !available_set.contains(name))
+        .collect();
+    if !unknown_names.is_empty() {
+        let unknown_names = unknown_names.join(", ");
+        return Err(Taxii1xError::StatusMessage {
+            message: format!("Destination collections were not found: {unknown_names}"),
+            in_response_to: Some(message_id.to_string()),
+            status_type: StatusType::DestinationCollectionError,
+            status_detail: Some(format!("{SD_DESTINATION_COLLECTION_NAME}: {unknown_names}")),
+        });
+    }
+
+    // If no destination specified and not required, use all service collections
+    let names_to_use = if destination_names.is_empty() {
+        &available_names
+    } else {
+        &destination_names
+    };
+
     // Get valid collections with permission check
     let mut valid_collections = Vec::new();
-    for name in &names_to_use {
-        // Only check against available set if user explicitly specified collections
-        if user_specified && !available_set.contains(name.as_str()) {
-            return Err(Taxii1xError::StatusMessage {
-                message: format!("Collection {name} was not found"),
-                in_response_to: Some(message_id.to_string()),
-                status_type: StatusType::NotFound,
-                status_detail: Some(format!("{SD_ITEM}: {name}")),
-            });
-        }
-
+    for name in names_to_use {
         let Some(c) = ctx
             .persistence
             .get_collection(name, Some(&ctx.service.id))
@@ -136,58 +149,194 @@ async fn validate_destinations_11(
     })
 }
 
-/// Store an inbox message and emit the creation hook if configured.
-async fn store_inbox_message(
-    ctx: &HandlerContext,
-    message: InboxMessageEntity,
-    save_raw: bool,
-) -> Taxii1xResult<InboxMessageEntity> {
-    if save_raw {
-        let saved = ctx.persistence.create_inbox_message(&message).await?;
-
-        // Emit inbox message created hook
-        if let Some(ref hooks) = ctx.hooks {
-            let event = InboxMessageCreatedEvent {
-                inbox_message: saved.clone(),
-                service_id: Some(ctx.service.id.clone()),
-            };
+/// Hook events of an inbox message's writes, emitted once the writes are
+/// committed.
+struct PendingEvents {
+    inbox_message: Option<InboxMessageCreatedEvent>,
+    content_blocks: Vec<ContentBlockCreatedEvent>,
+}
+
+impl PendingEvents {
+    /// Emit the creation hooks if configured.
+    fn emit<R: Taxii1Repository>(self, ctx: &HandlerContext<R>) {
+        let Some(ref hooks) = ctx.hooks else {
+            return;
+        };
+        if let Some(event) = self.inbox_message {
             hooks.emit_inbox_message_created(event);
         }
-
-        Ok(saved)
-    } else {
-        Ok(message)
+        for event in self.content_blocks {
+            hooks.emit_content_block_created(event);
+        }
     }
 }
 
-/// Store a content block and emit the creation hook if configured.
-async fn store_content_block(
-    ctx: &HandlerContext,
-    block: &ContentBlockEntity,
-    collection_ids: Option<&[i32]>,
-) -> Taxii1xResult<(taxii_core::ContentBlockEntity, Vec<i32>)> {
-    let created = ctx
+/// Store an inbox message and its content blocks together, so a failure
+/// leaves none of them behind, and queue their creation hooks.
+///
+/// Each block is stored once; a hook is queued for each collection it is
+/// added to, or a single one if it is added to none.
+async fn store_inbox_message<R: Taxii1Repository>(
+    ctx: &HandlerContext<R>,
+    inbox_message: Option<&InboxMessageEntity>,
+    blocks: &[(ContentBlockEntity, Option<Vec<i32>>)],
+) -> Taxii1xResult<PendingEvents> {
+    let (saved, created) = ctx
         .persistence
-        .create_content_block(block, collection_ids, Some(&ctx.service.id))
+        .create_inbox_message_with_blocks(inbox_message, blocks, Some(&ctx.service.id))
         .await?;
 
-    let ids = collection_ids.map(|s| s.to_vec()).unwrap_or_default();
-
-    // Emit content block created hook
-    if let Some(ref hooks) = ctx.hooks {
-        let event = ContentBlockCreatedEvent {
-            content_block: created.clone(),
-            collection_ids: ids.clone(),
+    let mut events = PendingEvents {
+        inbox_message: saved.map(|inbox_message| InboxMessageCreatedEvent {
+            inbox_message,
             service_id: Some(ctx.service.id.clone()),
-        };
-        hooks.emit_content_block_created(event);
+        }),
+        content_blocks: Vec::new(),
+    };
+    for ((_, collection_ids), created) in blocks.iter().zip(created) {
+        match collection_ids.as_deref() {
+            Some(ids) if !ids.is_empty() => {
+                events
+                    .content_blocks
+                    .extend(ids.iter().map(|id| ContentBlockCreatedEvent {
+                        content_block: created.clone(),
+                        collection_ids: vec![*id],
+                        service_id: Some(ctx.service.id.clone()),
+                    }));
+            }
+            _ => events.content_blocks.push(ContentBlockCreatedEvent {
+                content_block: created,
+                collection_ids: Vec::new(),
+                service_id: Some(ctx.service.id.clone()),
+            }),
+        }
+    }
+
+    Ok(events)
+}
+
+/// Validate a content block for the collections it is stored in.
+///
+/// Content claiming a STIX XML binding is validated if one of the
+/// collections validates content. Invalid content fails the inbox message
+/// if a collection rejects it; otherwise the validation error is returned
+/// to be stored with the block.
+fn validate_content_block(
+    collections: &[&CollectionEntity],
+    binding: &str,
+    content: &[u8],
+    message_id: &str,
+) -> Taxii1xResult<Option<String>> {
+    let validates = |mode: &str| collections.iter().any(|c| c.validation_mode == mode);
+    if !is_stix_xml_binding(binding)
+        || !(validates(validation_mode::REJECT) || validates(validation_mode::FLAG))
+    {
+        return Ok(None);
+    }
+
+    let Err(error) = validate_stix_content(content) else {
+        return Ok(None);
+    };
+    if !validates(validation_mode::REJECT) {
+        tracing::warn!(binding = %binding, error = %error, "Storing invalid content block");
+        return Ok(Some(error.to_string()));
+    }
+
+    let status_type = match error {
+        ContentValidationError::NotStix(_) => StatusType::UnsupportedContentBinding,
+        ContentValidationError::Malformed(_) => StatusType::Failure,
+        ContentValidationError::Invalid(_) => StatusType::BadMessage,
+    };
+    Err(Taxii1xError::StatusMessage {
+        message: format!("Content block is not valid for binding {binding}: {error}"),
+        in_response_to: Some(message_id.to_string()),
+        status_type,
+        status_detail: None,
+    })
+}
+
+/// Verify the signature of a content block for the collections it is
+/// stored in.
+///
+/// Signatures are verified if one of the collections has a signature
+/// policy. Content whose signature does not verify for every such
+/// collection, and unsigned content for a collection requiring signatures,
+/// fails the inbox message. Otherwise the verification outcome and the
+/// signer's fingerprint are returned to be stored with the block.
+fn verify_content_signature(
+    collections: &[&CollectionEntity],
+    content: &[u8],
+    message_id: &str,
+) -> Taxii1xResult<(Option<String>, Option<String>)> {
+    let verifying: Vec<&CollectionEntity> = collections
+        .iter()
+        .copied()
+        .filter(|c| c.signature_policy != signature_policy::OFF)
+        .collect();
+    if verifying.is_empty() {
+        return Ok((None, None));
+    }
+
+    let failure = |message: String| Taxii1xError::StatusMessage {
+        message,
+        in_response_to: Some(message_id.to_string()),
+        status_type: StatusType::Failure,
+        status_detail: None,
+    };
+
+    #[cfg(feature = "signatures")]
+    {
+        use crate::signature::{TrustedCertificates, is_signed, verify_signature};
+        use taxii_core::signature_status;
+
+        if !is_signed(content) {
+            if verifying
+                .iter()
+                .any(|c| c.signature_policy == signature_policy::REQUIRE)
+            {
+                return Err(failure("Content block is not signed".to_string()));
+            }
+            return Ok((Some(signature_status::UNSIGNED.to_string()), None));
+        }
+
+        let mut signer_fingerprint = None;
+        for collection in verifying {
+            let trusted =
+                TrustedCertificates::from_pem(collection.trusted_certificates.as_deref().unwrap_or_default())
+                    .map_err(|e| {
+                        tracing::error!(collection = %collection.name, error = %e, "Invalid trusted certificates");
+                        failure(format!(
+                            "Collection {} cannot verify signatures",
+                            collection.name
+                        ))
+                    })?;
+            let signer = verify_signature(content, &trusted).map_err(|e| {
+                tracing::warn!(collection = %collection.name, error = %e, "Rejecting content block");
+                failure(format!("Content block signature verification failed: {e}"))
+            })?;
+            signer_fingerprint = Some(signer.fingerprint);
+        }
+        Ok((
+            Some(signature_status::VALID.to_string()),
+            signer_fingerprint,
+        ))
     }
 
-    Ok((created, ids))
+    #[cfg(not(feature = "signatures"))]
+    {
+        let _ = content;
+        tracing::error!(
+            collections = ?verifying.iter().map(|c| &c.name).collect::<Vec<_>>(),
+            "Collection verifies signatures, but this build does not support signature verification"
+        );
+        Err(failure(
+            "Signature verification is not supported by this server".to_string(),
+        ))
+    }
 }
 
 /// Get accepted content bindings from service configuration.
-fn get_service_accepted_content(ctx: &HandlerContext) -> Vec<String> {
+fn get_service_accepted_content<R: Taxii1Repository>(ctx: &HandlerContext<R>) -> Vec<String> {
     ctx.service
         .get_property("accepted_content")
         .and_then(|v| v.as_array())
@@ -204,9 +353,9 @@ pub struct InboxMessage11Handler;
 
 impl InboxMessage11Handler {
     /// Handle a TAXII 1.1 Inbox Message.
-    pub async fn handle_11(
+    pub async fn handle_11<R: Taxii1Repository>(
         &self,
-        ctx: &HandlerContext,
+        ctx: &HandlerContext<R>,
         _headers: &TaxiiHeaders,
         message: &tm11::Taxii11Message,
     ) -> Taxii1xResult<tm11::Taxii11Message> {
@@ -266,9 +415,8 @@ impl InboxMessage11Handler {
             .and_then(|v| v.as_bool())
             .unwrap_or(true);
 
-        let inbox_message = store_inbox_message(ctx, inbox_message, save_raw).await?;
-
-        // Store content blocks
+        // Collect the content blocks to store
+        let mut blocks = Vec::new();
         let service_accepted = get_service_accepted_content(ctx);
         for content_block in &request.content_blocks {
             let content_binding = ContentBindingEntity {
@@ -295,12 +443,12 @@ impl InboxMessage11Handler {
             }
 
             // Filter collections that support this content binding
-            let matching_ids: Vec<i32> = validated
+            let matching: Vec<&CollectionEntity> = validated
                 .valid_collections
                 .iter()
                 .filter(|c| c.is_content_supported(&content_binding))
-                .filter_map(|c| c.id)
                 .collect();
+            let matching_ids: Vec<i32> = matching.iter().filter_map(|c| c.id).collect();
 
             // Skip if no collections support this binding (when collections are configured)
             if matching_ids.is_empty() && !validated.valid_collections.is_empty() {
@@ -318,13 +466,35 @@ impl InboxMessage11Handler {
                 .map(|dt| dt.with_timezone(&Utc))
                 .unwrap_or_else(Utc::now);
 
+            let content = decode_content(&content_binding.binding, &content_block.content)
+                .map_err(|e| Taxii1xError::StatusMessage {
+                    message: e.to_string(),
+                    in_response_to: Some(request.message_id.clone()),
+                    status_type: StatusType::BadMessage,
+                    status_detail: None,
+                })?;
+            let content_encoding = binding_encoding(&content_binding.binding).to_string();
+            let validation_error = validate_content_block(
+                &matching,
+                &content_binding.binding,
+                &content,
+                &request.message_id,
+            )?;
+            let (signature_status, signer_fingerprint) =
+                verify_content_signature(&matching, &content, &request.message_id)?;
+
             let block_entity = ContentBlockEntity {
                 id: None,
-                content: content_block.content.as_bytes().to_vec(),
+                content,
                 timestamp_label,
                 content_binding: Some(content_binding),
                 message: content_block.message.clone(),
-                inbox_message_id: inbox_message.id,
+                // Set once the inbox message is stored
+                inbox_message_id: None,
+                validation_error,
+                signature_status,
+                signer_fingerprint,
+                content_encoding,
             };
 
             let collection_ids = if validated.valid_collections.is_empty() {
@@ -333,9 +503,13 @@ impl InboxMessage11Handler {
                 Some(matching_ids.as_slice())
             };
 
-            store_content_block(ctx, &block_entity, collection_ids).await?;
+            blocks.push((block_entity, collection_ids.map(<[i32]>::to_vec)));
         }
 
+        store_inbox_message(ctx, save_raw.then_some(&inbox_message), &blocks)
+            .await?
+            .emit(ctx);
+
         Ok(tm11::Taxii11Message::StatusMessage(
             tm11::StatusMessage::success(generate_id(), &request.message_id),
         ))
@@ -347,8 +521,8 @@ pub struct InboxMessage10Handler;
 
 impl InboxMessage10Handler {
     /// Get destination collections from service configuration (TAXII 1.0).
-    async fn get_destination_collections(
-        ctx: &HandlerContext,
+    async fn get_destination_collections<R: Taxii1Repository>(
+        ctx: &HandlerContext<R>,
         message_id: &str,
     ) -> Taxii1xResult<Vec<CollectionEntity>> {
         // In TAXII 1.0, destination collections come from service config
@@ -395,9 +569,9 @@ impl InboxMessage10Handler {
     }
 
     /// Handle a TAXII 1.0 Inbox Message.
-    pub async fn handle_10(
+    pub async fn handle_10<R: Taxii1Repository>(
         &self,
-        ctx: &HandlerContext,
+        ctx: &HandlerContext<R>,
         _headers: &TaxiiHeaders,
         message: &tm10::Taxii10Message,
     ) -> Taxii1xResult<tm10::Taxii10Message> {
@@ -447,9 +621,8 @@ impl InboxMessage10Handler {
             .and_then(|v| v.as_bool())
             .unwrap_or(true);
 
-        let inbox_message = store_inbox_message(ctx, inbox_message, save_raw).await?;
-
-        // Store content blocks
+        // Collect the content blocks to store
+        let mut blocks = Vec::new();
         let service_accepted = get_service_accepted_content(ctx);
         for content_block in &request.content_blocks {
             let content_binding = ContentBindingEntity {
@@ -477,33 +650,709 @@ impl InboxMessage10Handler {
                 .map(|dt| dt.with_timezone(&Utc))
                 .unwrap_or_else(Utc::now);
 
+            // Filter collections that support this content binding
+            let matching: Vec<&CollectionEntity> = destination_collections
+                .iter()
+                .filter(|c| c.is_content_supported(&content_binding))
+                .collect();
+            let matching_ids: Vec<i32> = matching.iter().filter_map(|c| c.id).collect();
+
+            let content = decode_content(&content_binding.binding, &content_block.content)
+                .map_err(|e| Taxii1xError::StatusMessage {
+                    message: e.to_string(),
+                    in_response_to: Some(request.message_id.clone()),
+                    status_type: StatusType::BadMessage,
+                    status_detail: None,
+                })?;
+            let content_encoding = binding_encoding(&content_binding.binding).to_string();
+            let validation_error = validate_content_block(
+                &matching,
+                &content_binding.binding,
+                &content,
+                &request.message_id,
+            )?;
+            let (signature_status, signer_fingerprint) =
+                verify_content_signature(&matching, &content, &request.message_id)?;
+
             let block_entity = ContentBlockEntity {
                 id: None,
-                content: content_block.content.as_bytes().to_vec(),
+                content,
                 timestamp_label,
-                content_binding: Some(content_binding.clone()),
+                content_binding: Some(content_binding),
                 message: None,
-                inbox_message_id: inbox_message.id,
+                // Set once the inbox message is stored
+                inbox_message_id: None,
+                validation_error,
+                signature_status,
+                signer_fingerprint,
+                content_encoding,
             };
 
-            // Filter collections that support this content binding
-            let matching_ids: Vec<i32> = destination_collections
-                .iter()
-                .filter(|c| c.is_content_supported(&content_binding))
-                .filter_map(|c| c.id)
-                .collect();
-
             let collection_ids = if matching_ids.is_empty() {
                 None
             } else {
                 Some(matching_ids.as_slice())
             };
 
-            store_content_block(ctx, &block_entity, collection_ids).await?;
+            blocks.push((block_entity, collection_ids.map(<[i32]>::to_vec)));
         }
 
+        store_inbox_message(ctx, save_raw.then_some(&inbox_message), &blocks)
+            .await?
+            .emit(ctx);
+
         Ok(tm10::Taxii10Message::StatusMessage(
             tm10::StatusMessage::success(generate_id(), &request.message_id),
         ))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{
+        CB_SMIME, CB_STIX_XML_111, ST_SUCCESS, VID_TAXII_XML_10, VID_TAXII_XML_11,
+    };
+    use crate::handlers::ServiceInfo;
+    use crate::messages::{NS_TAXII_10, NS_TAXII_11};
+    use crate::{VID_TAXII_SERVICES_10, VID_TAXII_SERVICES_11};
+    use sqlx::PgPool;
+    use std::sync::Arc;
+    use taxii_core::{ServiceEntity, content_encoding};
+    use taxii_db::{DbTaxii1Repository, InMemoryTaxii1Repository, TaxiiPool};
+
+    type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+    const SERVICE_ID: &str = "inbox-a";
+    const COLLECTION_NAME: &str = "collection-a";
+
+    const PACKAGE: &str = r#"<stix:STIX_Package xmlns:stix="http://stix.mitre.org/stix-1" xmlns:example="http://example.com" id="example:Package-1"/>"#;
+    const MALFORMED: &str = r#"<stix:STIX_Package xmlns:stix="http://stix.mitre.org/stix-1">"#;
+    const NOT_STIX: &str = r#"<Indicator xmlns="http://example.com/other"/>"#;
+    /// A STIX package with its sections out of order.
+    #[cfg(feature = "xsd")]
+    const SCHEMA_INVALID: &str = r#"<stix:STIX_Package xmlns:stix="http://stix.mitre.org/stix-1"><stix:Indicators/><stix:STIX_Header/></stix:STIX_Package>"#;
+
+    /// Create the inbox service and a collection with the given validation
+    /// mode, returning the handler context and the collection ID.
+    async fn setup(
+        pool: PgPool,
+        mode: &str,
+        message_binding: &str,
+    ) -> Result<(HandlerContext, i32), Box<dyn std::error::Error>> {
+        setup_with(
+            DbTaxii1Repository::new(TaxiiPool::new(pool)),
+            mode,
+            message_binding,
+        )
+        .await
+    }
+
+    /// Like [`setup`], in the given repository.
+    async fn setup_with<R: Taxii1Repository>(
+        persistence: R,
+        mode: &str,
+        message_binding: &str,
+    ) -> Result<(HandlerContext<R>, i32), Box<dyn std::error::Error>> {
+        let persistence = Arc::new(persistence);
+        let properties = serde_json::json!({
+            "destination_collection_names": [COLLECTION_NAME],
+        });
+        persistence
+            .update_service(&ServiceEntity {
+                id: Some(SERVICE_ID.to_string()),
+                service_type: "INBOX".to_string(),
+                properties: properties.clone(),
+            })
+            .await?;
+        let collection = persistence
+            .create_collection(&CollectionEntity {
+                validation_mode: mode.to_string(),
+                ..CollectionEntity::new(COLLECTION_NAME)
+            })
+            .await?;
+        let collection_id = collection.id.ok_or("collection has no id")?;
+        persistence
+            .set_collection_services(collection_id, &[SERVICE_ID.to_string()])
+            .await?;
+
+        let ctx = HandlerContext {
+            account: None,
+            persistence,
+            service: ServiceInfo {
+                id: SERVICE_ID.to_string(),
+                service_type: "INBOX".to_string(),
+                address: "/services/inbox-a/".to_string(),
+                description: None,
+                protocol_bindings: Vec::new(),
+                message_bindings: vec![message_binding.to_string()],
+                available: true,
+                authentication_required: false,
+                properties,
+            },
+            domain: None,
+            limits: Default::default(),
+            push: Default::default(),
+            hooks: None,
+        };
+        Ok((ctx, collection_id))
+    }
+
+    async fn inbox_11<R: Taxii1Repository>(
+        ctx: &HandlerContext<R>,
+        content: &str,
+    ) -> Taxii1xResult<tm11::Taxii11Message> {
+        inbox_11_binding(ctx, CB_STIX_XML_111, content).await
+    }
+
+    async fn inbox_11_binding<R: Taxii1Repository>(
+        ctx: &HandlerContext<R>,
+        binding: &str,
+        content: &str,
+    ) -> Taxii1xResult<tm11::Taxii11Message> {
+        let headers = TaxiiHeaders {
+            content_type: VID_TAXII_XML_11.to_string(),
+            services: VID_TAXII_SERVICES_11.to_string(),
+            accept: None,
+            protocol: None,
+        };
+        let message = tm11::Taxii11Message::InboxMessage(tm11::InboxMessage {
+            xmlns: NS_TAXII_11.to_string(),
+            message_id: "inbox-1".to_string(),
+            result_id: None,
+            extended_headers: None,
+            destination_collection_names: Vec::new(),
+            message: None,
+            subscription_information: None,
+            record_count: None,
+            content_blocks: vec![tm11::ContentBlock {
+                content_binding: tm11::ContentBinding {
+                    binding_id: binding.to_string(),
+                    subtype_ids: Vec::new(),
+                },
+                content: content.to_string(),
+                timestamp_label: None,
+                message: None,
+                padding: None,
+            }],
+        });
+        InboxMessage11Handler
+            .handle_11(ctx, &headers, &message)
+            .await
+    }
+
+    /// Send an inbox message with one STIX package to the named
+    /// collections.
+    async fn inbox_11_to<R: Taxii1Repository>(
+        ctx: &HandlerContext<R>,
+        destination_collection_names: &[&str],
+    ) -> Taxii1xResult<tm11::Taxii11Message> {
+        let message = tm11::Taxii11Message::InboxMessage(tm11::InboxMessage {
+            xmlns: NS_TAXII_11.to_string(),
+            message_id: "inbox-1".to_string(),
+            result_id: None,
+            extended_headers: None,
+            destination_collection_names: destination_collection_names
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            message: None,
+            subscription_information: None,
+            record_count: None,
+            content_blocks: vec![tm11::ContentBlock {
+                content_binding: tm11::ContentBinding {
+                    binding_id: CB_STIX_XML_111.to_string(),
+                    subtype_ids: Vec::new(),
+                },
+                content: PACKAGE.to_string(),
+                timestamp_label: None,
+                message: None,
+                padding: None,
+            }],
+        });
+        let headers = TaxiiHeaders {
+            content_type: VID_TAXII_XML_11.to_string(),
+            services: VID_TAXII_SERVICES_11.to_string(),
+            accept: None,
+            protocol: None,
+        };
+        InboxMessage11Handler
+            .handle_11(ctx, &headers, &message)
+            .await
+    }
+
+    async fn inbox_10<R: Taxii1Repository>(
+        ctx: &HandlerContext<R>,
+        content: &str,
+    ) -> Taxii1xResult<tm10::Taxii10Message> {
+        let headers = TaxiiHeaders {
+            content_type: VID_TAXII_XML_10.to_string(),
+            services: VID_TAXII_SERVICES_10.to_string(),
+            accept: None,
+            protocol: None,
+        };
+        let message = tm10::Taxii10Message::InboxMessage(tm10::InboxMessage {
+            xmlns: NS_TAXII_10.to_string(),
+            message_id: "inbox-1".to_string(),
+            extended_headers: None,
+            message: None,
+            subscription_information: None,
+            content_blocks: vec![tm10::ContentBlock {
+                content_binding: CB_STIX_XML_111.to_string(),
+                content: content.to_string(),
+                timestamp_label: None,
+                padding: None,
+            }],
+        });
+        InboxMessage10Handler
+            .handle_10(ctx, &headers, &message)
+            .await
+    }
+
+    fn rejected_status(result: Taxii1xResult<impl std::fmt::Debug>) -> Option<StatusType> {
+        match result {
+            Err(Taxii1xError::StatusMessage { status_type, .. }) => Some(status_type),
+            _ => None,
+        }
+    }
+
+    async fn stored_blocks<R: Taxii1Repository>(
+        ctx: &HandlerContext<R>,
+        collection_id: i32,
+    ) -> Result<Vec<ContentBlockEntity>, Box<dyn std::error::Error>> {
+        Ok(ctx
+            .persistence
+            .get_content_blocks(Some(collection_id), None, None, None, 0, None)
+            .await?)
+    }
+
+    #[test]
+    fn test_validation_skipped_when_off_or_not_stix_binding() -> TestResult {
+        let collection = |mode: &str| CollectionEntity {
+            id: Some(1),
+            validation_mode: mode.to_string(),
+            ..CollectionEntity::new(COLLECTION_NAME)
+        };
+        let off = collection(validation_mode::OFF);
+        let reject = collection(validation_mode::REJECT);
+        let flag = collection(validation_mode::FLAG);
+
+        let invalid = MALFORMED.as_bytes();
+        assert_eq!(
+            validate_content_block(&[&off], CB_STIX_XML_111, invalid, "1")?,
+            None
+        );
+        assert_eq!(
+            validate_content_block(&[&reject], "urn:example.com:custom", invalid, "1")?,
+            None
+        );
+        assert!(validate_content_block(&[&off, &flag], CB_STIX_XML_111, invalid, "1")?.is_some());
+        assert!(validate_content_block(&[&flag, &reject], CB_STIX_XML_111, invalid, "1").is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reject_mode_11() -> TestResult {
+        let (ctx, collection_id) = setup_with(
+            InMemoryTaxii1Repository::new(),
+            validation_mode::REJECT,
+            VID_TAXII_XML_11,
+        )
+        .await?;
+
+        assert_eq!(
+            rejected_status(inbox_11(&ctx, MALFORMED).await),
+            Some(StatusType::Failure)
+        );
+        assert_eq!(
+            rejected_status(inbox_11(&ctx, NOT_STIX).await),
+            Some(StatusType::UnsupportedContentBinding)
+        );
+        assert!(stored_blocks(&ctx, collection_id).await?.is_empty());
+
+        match inbox_11(&ctx, PACKAGE).await? {
+            tm11::Taxii11Message::StatusMessage(status) => {
+                assert_eq!(status.status_type, ST_SUCCESS);
+            }
+            other => return Err(format!("expected status message, got {other:?}").into()),
+        }
+        let blocks = stored_blocks(&ctx, collection_id).await?;
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].validation_error, None);
+        assert!(blocks[0].inbox_message_id.is_some());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_flag_mode_11() -> TestResult {
+        let (ctx, collection_id) = setup_with(
+            InMemoryTaxii1Repository::new(),
+            validation_mode::FLAG,
+            VID_TAXII_XML_11,
+        )
+        .await?;
+
+        inbox_11(&ctx, PACKAGE).await?;
+        inbox_11(&ctx, NOT_STIX).await?;
+
+        let blocks = stored_blocks(&ctx, collection_id).await?;
+        let errors: Vec<Option<&str>> = blocks
+            .iter()
+            .map(|b| b.validation_error.as_deref())
+            .collect();
+        assert_eq!(errors.len(), 2);
+        assert!(errors.contains(&None));
+        assert!(
+            errors
+                .iter()
+                .flatten()
+                .any(|e| e.starts_with("Content is not a STIX package"))
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_validation_10() -> TestResult {
+        let (ctx, collection_id) = setup_with(
+            InMemoryTaxii1Repository::new(),
+            validation_mode::REJECT,
+            VID_TAXII_XML_10,
+        )
+        .await?;
+
+        assert_eq!(
+            rejected_status(inbox_10(&ctx, MALFORMED).await),
+            Some(StatusType::Failure)
+        );
+        assert_eq!(
+            rejected_status(inbox_10(&ctx, NOT_STIX).await),
+            Some(StatusType::UnsupportedContentBinding)
+        );
+        inbox_10(&ctx, PACKAGE).await?;
+        assert_eq!(stored_blocks(&ctx, collection_id).await?.len(), 1);
+        Ok(())
+    }
+
+    #[cfg(feature = "xsd")]
+    #[tokio::test]
+    async fn test_schema_invalid_content() -> TestResult {
+        let (ctx, collection_id) = setup_with(
+            InMemoryTaxii1Repository::new(),
+            validation_mode::REJECT,
+            VID_TAXII_XML_11,
+        )
+        .await?;
+        assert_eq!(
+            rejected_status(inbox_11(&ctx, SCHEMA_INVALID).await),
+            Some(StatusType::BadMessage)
+        );
+        assert!(stored_blocks(&ctx, collection_id).await?.is_empty());
+
+        let (ctx, _) = setup_with(
+            InMemoryTaxii1Repository::new(),
+            validation_mode::REJECT,
+            VID_TAXII_XML_10,
+        )
+        .await?;
+        assert_eq!(
+            rejected_status(inbox_10(&ctx, SCHEMA_INVALID).await),
+            Some(StatusType::BadMessage)
+        );
+
+        let (ctx, collection_id) = setup_with(
+            InMemoryTaxii1Repository::new(),
+            validation_mode::FLAG,
+            VID_TAXII_XML_11,
+        )
+        .await?;
+        inbox_11(&ctx, SCHEMA_INVALID).await?;
+        let blocks = stored_blocks(&ctx, collection_id).await?;
+        assert_eq!(blocks.len(), 1);
+        assert!(
+            blocks[0]
+                .validation_error
+                .as_deref()
+                .is_some_and(|e| e.ends_with("at /STIX_Package/STIX_Header")),
+            "{:?}",
+            blocks[0].validation_error
+        );
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_multiple_destinations_11(pool: PgPool) -> TestResult {
+        let (mut ctx, collection_a) = setup(pool, validation_mode::OFF, VID_TAXII_XML_11).await?;
+        ctx.service.properties = serde_json::json!({"destination_collection_required": true});
+        let collection_b = ctx
+            .persistence
+            .create_collection(&CollectionEntity {
+                id: None,
+                name: "collection-b".to_string(),
+                ..ctx
+                    .persistence
+                    .get_collection(COLLECTION_NAME, None)
+                    .await?
+                    .ok_or("collection not found")?
+            })
+            .await?
+            .id
+            .ok_or("collection has no id")?;
+        ctx.persistence
+            .set_collection_services(collection_b, &[SERVICE_ID.to_string()])
+            .await?;
+        let hooks = Arc::new(taxii_core::HookRegistry::new());
+        let mut events = hooks.subscribe();
+        ctx.hooks = Some(hooks);
+
+        // Unknown names are all reported and nothing is stored
+        let result = inbox_11_to(&ctx, &[COLLECTION_NAME, "missing", "collection-b", "gone"]).await;
+        match result {
+            Err(Taxii1xError::StatusMessage {
+                status_type: StatusType::DestinationCollectionError,
+                status_detail: Some(detail),
+                ..
+            }) => assert_eq!(
+                detail,
+                format!("{SD_DESTINATION_COLLECTION_NAME}: missing, gone")
+            ),
+            other => return Err(format!("expected a destination error, got {other:?}").into()),
+        }
+        assert!(stored_blocks(&ctx, collection_a).await?.is_empty());
+        assert!(stored_blocks(&ctx, collection_b).await?.is_empty());
+        assert!(events.try_recv().is_err());
+
+        // The block is stored once in every named collection
+        inbox_11_to(&ctx, &[COLLECTION_NAME, "collection-b", COLLECTION_NAME]).await?;
+        let block_a = stored_blocks(&ctx, collection_a).await?;
+        let block_b = stored_blocks(&ctx, collection_b).await?;
+        assert_eq!(block_a.len(), 1);
+        assert_eq!(block_b.len(), 1);
+        assert_eq!(block_a[0].id, block_b[0].id);
+
+        // One hook per collection the block was added to
+        let mut collection_ids = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            if let taxii_core::SignalEvent::ContentBlockCreated(event) = event {
+                assert_eq!(event.content_block.id, block_a[0].id);
+                collection_ids.extend(event.collection_ids);
+            }
+        }
+        collection_ids.sort_unstable();
+        let mut expected = vec![collection_a, collection_b];
+        expected.sort_unstable();
+        assert_eq!(collection_ids, expected);
+        Ok(())
+    }
+
+    /// Binary content is received base64 encoded and stored decoded.
+    #[tokio::test]
+    async fn test_binary_content_11() -> TestResult {
+        let (ctx, collection_id) = setup_with(
+            InMemoryTaxii1Repository::new(),
+            validation_mode::OFF,
+            VID_TAXII_XML_11,
+        )
+        .await?;
+
+        assert_eq!(
+            rejected_status(inbox_11_binding(&ctx, CB_SMIME, "not base64!").await),
+            Some(StatusType::BadMessage)
+        );
+        inbox_11_binding(&ctx, CB_SMIME, "MIL/\n/gCA").await?;
+
+        let blocks = stored_blocks(&ctx, collection_id).await?;
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].content, [0x30, 0x82, 0xff, 0xfe, 0x00, 0x80]);
+        assert_eq!(blocks[0].content_encoding, content_encoding::BASE64);
+        Ok(())
+    }
+
+    /// STIX content for a collection linked to a TAXII 2.x collection is
+    /// converted into it by the bridge worker, with the collection's
+    /// default marking.
+    #[cfg(feature = "bridge")]
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_bridge_11(pool: PgPool) -> TestResult {
+        use crate::bridge::{BridgeWorker, PROVENANCE_PROPERTY};
+        use taxii_db::{DbTaxii2Repository, Taxii2Repository};
+
+        const TLP_GREEN: &str = "marking-definition--34098fce-860f-48ae-8e50-ebd3cc5e41da";
+
+        let taxii2 = DbTaxii2Repository::new(TaxiiPool::new(pool.clone()));
+        let worker = BridgeWorker::new(
+            DbTaxii1Repository::new(TaxiiPool::new(pool.clone())),
+            DbTaxii2Repository::new(TaxiiPool::new(pool.clone())),
+        );
+        let (mut ctx, collection_id) = setup(pool, validation_mode::OFF, VID_TAXII_XML_11).await?;
+        let hooks = Arc::new(taxii_core::HookRegistry::new());
+        let mut events = hooks.subscribe();
+        ctx.hooks = Some(hooks);
+        let mut bridge_events = async || {
+            let mut created = Vec::new();
+            while let Ok(event) = events.try_recv() {
+                if let taxii_core::SignalEvent::ContentBlockCreated(event) = event {
+                    created.push(event);
+                }
+            }
+            worker.bridge(&created).await;
+        };
+        let api_root = taxii2.add_api_root("Root", None, true, false, None).await?;
+        let collection = taxii2
+            .add_collection(
+                &api_root.id,
+                "Bridged",
+                None,
+                Some("bridged"),
+                false,
+                false,
+                None,
+            )
+            .await?;
+        taxii2
+            .set_collection_default_marking(&collection.id, Some(TLP_GREEN))
+            .await?;
+        ctx.persistence
+            .set_collection_bridge(&taxii_core::CollectionBridgeEntity {
+                data_collection_id: collection_id,
+                api_root_id: api_root.id.clone(),
+                collection_id: collection.id.clone(),
+            })
+            .await?;
+
+        inbox_11(
+            &ctx,
+            include_str!("../../tests/fixtures/stix_package_111.xml"),
+        )
+        .await?;
+
+        // Nothing is converted within the inbox request
+        assert!(
+            taxii2
+                .get_objects(&collection.id, &Default::default())
+                .await?
+                .items
+                .is_empty()
+        );
+        bridge_events().await;
+
+        let block_id = stored_blocks(&ctx, collection_id).await?[0].id;
+        let objects = taxii2
+            .get_objects(&collection.id, &Default::default())
+            .await?
+            .items;
+        let mut types: Vec<&str> = objects.iter().map(|o| o.stix_type.as_str()).collect();
+        types.sort_unstable();
+        assert_eq!(
+            types,
+            [
+                "file",
+                "indicator",
+                "indicator",
+                "indicator",
+                "ipv4-addr",
+                "ipv4-addr",
+                "ipv6-addr"
+            ]
+        );
+        for object in &objects {
+            assert_eq!(
+                object.serialized_data[PROVENANCE_PROPERTY]["collection_id"],
+                collection_id
+            );
+            assert_eq!(
+                object.serialized_data[PROVENANCE_PROPERTY]["content_block_id"],
+                serde_json::json!(block_id)
+            );
+            assert_eq!(
+                object.serialized_data["object_marking_refs"],
+                serde_json::json!([TLP_GREEN])
+            );
+            object.to_typed()?;
+        }
+
+        // Unlinked collections are not converted
+        assert!(
+            ctx.persistence
+                .remove_collection_bridge(collection_id)
+                .await?
+        );
+        inbox_11(&ctx, PACKAGE).await?;
+        bridge_events().await;
+        assert_eq!(
+            taxii2
+                .get_objects(&collection.id, &Default::default())
+                .await?
+                .items
+                .len(),
+            objects.len()
+        );
+        Ok(())
+    }
+
+    /// Signed content is verified against the collection's trusted
+    /// certificates before it is stored.
+    #[cfg(feature = "signatures")]
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_signature_policy_11(pool: PgPool) -> TestResult {
+        use taxii_core::signature_status;
+
+        let (ctx, collection_id) = setup(pool, validation_mode::OFF, VID_TAXII_XML_11).await?;
+        let collection = ctx
+            .persistence
+            .get_collection(COLLECTION_NAME, None)
+            .await?
+            .ok_or("collection not found")?;
+        let set_policy = |policy: &str| CollectionEntity {
+            signature_policy: policy.to_string(),
+            trusted_certificates: Some(
+                include_str!("../../tests/fixtures/signer_rsa.pem").to_string(),
+            ),
+            ..collection.clone()
+        };
+        ctx.persistence
+            .update_collection(&set_policy(signature_policy::REQUIRE))
+            .await?;
+
+        inbox_11(
+            &ctx,
+            include_str!("../../tests/fixtures/stix_package_signed.xml"),
+        )
+        .await?;
+        for content in [
+            include_str!("../../tests/fixtures/stix_package_signed_tampered.xml"),
+            include_str!("../../tests/fixtures/stix_package_signed_untrusted.xml"),
+            PACKAGE,
+        ] {
+            assert_eq!(
+                rejected_status(inbox_11(&ctx, content).await),
+                Some(StatusType::Failure)
+            );
+        }
+        let blocks = stored_blocks(&ctx, collection_id).await?;
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(
+            blocks[0].signature_status.as_deref(),
+            Some(signature_status::VALID)
+        );
+        assert_eq!(
+            blocks[0].signer_fingerprint.as_deref(),
+            Some("4b33995696771f0d751249cc8a96169bf92e05ebb01119d0fb3d74b616ec87a7")
+        );
+
+        // Unsigned content is stored and marked when signatures are optional
+        ctx.persistence
+            .update_collection(&set_policy(signature_policy::VERIFY))
+            .await?;
+        inbox_11(&ctx, PACKAGE).await?;
+        let blocks = stored_blocks(&ctx, collection_id).await?;
+        assert_eq!(blocks.len(), 2);
+        assert!(blocks.iter().any(|b| {
+            b.signature_status.as_deref() == Some(signature_status::UNSIGNED)
+                && b.signer_fingerprint.is_none()
+        }));
+        Ok(())
+    }
+}
@@ -1,5 +1,6 @@
 //! TAXII 1.x message handlers.
 
+pub mod advertised;
 pub mod base;
 pub mod collection_info;
 pub mod discovery;
@@ -8,10 +9,13 @@ pub mod poll;
 pub mod poll_fulfillment;
 pub mod subscription;
 
+pub use advertised::AdvertisedService;
 pub use base::{HandlerContext, ServiceInfo, TaxiiHeaders, generate_id};
 
 use std::collections::HashMap;
 
+use taxii_db::Taxii1Repository;
+
 use crate::constants::{
     MSG_COLLECTION_INFORMATION_REQUEST, MSG_DISCOVERY_REQUEST, MSG_FEED_INFORMATION_REQUEST,
     MSG_INBOX_MESSAGE, MSG_MANAGE_COLLECTION_SUBSCRIPTION_REQUEST,
@@ -51,9 +55,9 @@ pub enum Handler {
 
 impl Handler {
     /// Handle a TAXII 1.0 message.
-    pub async fn handle_10(
+    pub async fn handle_10<R: Taxii1Repository>(
         &self,
-        ctx: &HandlerContext,
+        ctx: &HandlerContext<R>,
         headers: &TaxiiHeaders,
         message: &tm10::Taxii10Message,
     ) -> Taxii1xResult<tm10::Taxii10Message> {
@@ -83,9 +87,9 @@ impl Handler {
     }
 
     /// Handle a TAXII 1.1 message.
-    pub async fn handle_11(
+    pub async fn handle_11<R: Taxii1Repository>(
         &self,
-        ctx: &HandlerContext,
+        ctx: &HandlerContext<R>,
         headers: &TaxiiHeaders,
         message: &tm11::Taxii11Message,
     ) -> Taxii1xResult<tm11::Taxii11Message> {
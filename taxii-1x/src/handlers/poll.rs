@@ -6,20 +6,84 @@ use chrono::{DateTime, Utc};
 
 use crate::constants::{
     CT_DATA_FEED, RT_COUNT_ONLY, RT_FULL, SD_ESTIMATED_WAIT, SD_RESULT_ID, SD_SUPPORTED_CONTENT,
-    SD_WILL_PUSH, ST_PENDING, StatusType,
+    SD_WILL_PUSH, ST_PENDING, StatusType, VID_TAXII_XML_11,
 };
+use crate::content::encode_content;
 use crate::error::{Taxii1xError, Taxii1xResult};
+use crate::limits::blocks_within;
 use crate::messages::{tm10, tm11};
-use taxii_core::{CollectionEntity, ContentBindingEntity};
+use crate::worker::DEFAULT_BATCH_SIZE;
+use taxii_core::{
+    CollectionEntity, ContentBindingEntity, PushParametersEntity, SubscriptionEntity,
+    delivery_status, format_timestamp_label, result_set_status, subscription_status,
+    truncate_timestamp_label,
+};
 use taxii_db::{DatabaseError, Taxii1Repository};
 
 use super::base::{HandlerContext, TaxiiHeaders, generate_id};
+use super::subscription::validate_push_parameters;
+
+/// Default time (seconds) a result set can be fulfilled after it is created
+const DEFAULT_RESULT_SET_TTL: i64 = 86_400;
+
+/// Expiry of a result set created now, from the service's `result_set_ttl`.
+fn result_set_expiry<R: Taxii1Repository>(ctx: &HandlerContext<R>) -> DateTime<Utc> {
+    let ttl = ctx
+        .service
+        .get_property("result_set_ttl")
+        .and_then(|v| v.as_i64())
+        .unwrap_or(DEFAULT_RESULT_SET_TTL);
+
+    Utc::now() + chrono::Duration::seconds(ttl.max(0))
+}
+
+/// Build a PENDING status for a result set.
+///
+/// The estimated wait does not exceed the time left until the result set
+/// expires, and the status message tells when that is.
+pub(super) fn pending_status(
+    in_response_to: &str,
+    result_id: &str,
+    wait_time: i64,
+    expires_at: Option<DateTime<Utc>>,
+    will_push: Option<bool>,
+) -> tm11::StatusMessage {
+    let wait_time = match expires_at {
+        Some(expires_at) => wait_time.min((expires_at - Utc::now()).num_seconds().max(0)),
+        None => wait_time,
+    };
+
+    let mut status_detail = HashMap::from([
+        (SD_ESTIMATED_WAIT.to_owned(), wait_time.to_string()),
+        (SD_RESULT_ID.to_owned(), result_id.to_owned()),
+    ]);
+    if let Some(will_push) = will_push {
+        status_detail.insert(SD_WILL_PUSH.to_owned(), will_push.to_string());
+    }
+
+    let mut status =
+        tm11::StatusMessage::new(generate_id(), in_response_to, ST_PENDING.to_string())
+            .with_status_detail(status_detail);
+    status.message = expires_at.map(|t| format!("Result set expires at {}", t.to_rfc3339()));
+    status
+}
 
-/// Parse an RFC3339 timestamp string into a DateTime<Utc>.
+/// Parse an RFC3339 timestamp string into a DateTime<Utc>, truncated to
+/// the microsecond precision labels are stored at.
 fn parse_timestamp(s: &str) -> Option<DateTime<Utc>> {
     DateTime::parse_from_rfc3339(s)
         .ok()
-        .map(|dt| dt.with_timezone(&Utc))
+        .map(|dt| truncate_timestamp_label(dt.with_timezone(&Utc)))
+}
+
+/// The inclusive end of a poll: the requested end, or else now (but never
+/// before the begin), so the response can echo the exact end that was
+/// queried.
+fn effective_end(start: Option<DateTime<Utc>>, end: Option<DateTime<Utc>>) -> DateTime<Utc> {
+    end.unwrap_or_else(|| {
+        let now = truncate_timestamp_label(Utc::now());
+        start.map_or(now, |start| start.max(now))
+    })
 }
 
 /// Resolved poll parameters including bindings and response type.
@@ -27,21 +91,40 @@ struct ResolvedPollParams {
     content_bindings: Vec<ContentBindingEntity>,
     response_type: String,
     allow_async: bool,
+    delivery: Option<PushParametersEntity>,
+}
+
+/// Reject polling by a paused or unsubscribed subscription.
+fn ensure_active(sub: &SubscriptionEntity, message_id: &str) -> Taxii1xResult<()> {
+    if sub.status == subscription_status::ACTIVE {
+        return Ok(());
+    }
+    Err(Taxii1xError::StatusMessage {
+        message: format!("Requested subscription is {}", sub.status),
+        in_response_to: Some(message_id.to_string()),
+        status_type: StatusType::Failure,
+        status_detail: sub.subscription_id.clone(),
+    })
 }
 
 /// Resolve content bindings from subscription or poll parameters.
 ///
-/// Returns the content bindings, response type, and allow_async flag.
-async fn resolve_poll_bindings_11(
-    ctx: &HandlerContext,
+/// Returns the content bindings, response type, allow_async flag and the
+/// inbox to push asynchronous results to.
+async fn resolve_poll_bindings_11<R: Taxii1Repository>(
+    ctx: &HandlerContext<R>,
     request: &tm11::PollRequest,
     collection: &CollectionEntity,
 ) -> Taxii1xResult<ResolvedPollParams> {
     let collection_id = collection.id.unwrap_or(0);
 
     if let Some(ref subscription_id) = request.subscription_id {
-        // Get bindings from subscription
-        let subscription = ctx.persistence.get_subscription(subscription_id).await?;
+        // Get bindings from the client's subscription
+        let subscription = ctx
+            .persistence
+            .get_subscription(subscription_id)
+            .await?
+            .filter(|sub| ctx.owns(sub));
 
         let sub = subscription.ok_or_else(|| Taxii1xError::StatusMessage {
             message: "Requested subscription was not found".to_string(),
@@ -59,6 +142,7 @@ async fn resolve_poll_bindings_11(
                 status_detail: Some(collection.name.clone()),
             });
         }
+        ensure_active(&sub, &request.message_id)?;
 
         let bindings = sub
             .params
@@ -75,6 +159,7 @@ async fn resolve_poll_bindings_11(
             content_bindings: bindings,
             response_type: resp_type,
             allow_async: false,
+            delivery: None,
         })
     } else if let Some(ref params) = request.poll_parameters {
         // Parse content bindings from poll_parameters
@@ -122,32 +207,118 @@ async fn resolve_poll_bindings_11(
             .clone()
             .unwrap_or_else(|| RT_FULL.to_string());
         let allow_async = params.allow_asynch.unwrap_or(false);
+        // Without push, delivery parameters are ignored (WILL_PUSH false)
+        let delivery = match params.delivery_parameters.as_ref() {
+            Some(p) if ctx.push.enabled => Some(
+                validate_push_parameters(
+                    &ctx.push,
+                    &p.protocol_binding,
+                    &p.address,
+                    &p.message_binding,
+                    VID_TAXII_XML_11,
+                    &request.message_id,
+                )
+                .await?,
+            ),
+            _ => None,
+        };
 
         Ok(ResolvedPollParams {
             content_bindings,
             response_type: resp_type,
             allow_async,
+            delivery,
         })
     } else {
         Ok(ResolvedPollParams {
             content_bindings: Vec::new(),
             response_type: RT_FULL.to_string(),
             allow_async: false,
+            delivery: None,
         })
     }
 }
 
+/// Estimated seconds until a new pending result set is prepared.
+///
+/// The service's `wait_time` is the time to prepare one batch of result
+/// sets; every batch of result sets waiting ahead adds another `wait_time`.
+async fn estimated_wait<R: Taxii1Repository>(ctx: &HandlerContext<R>) -> Taxii1xResult<i64> {
+    let wait_time = ctx
+        .service
+        .get_property("wait_time")
+        .and_then(|v| v.as_i64())
+        .unwrap_or(300); // Default 5 minutes
+    let queued = ctx.persistence.count_pending_result_sets().await?;
+
+    Ok(wait_time.saturating_mul(1 + queued / DEFAULT_BATCH_SIZE))
+}
+
+/// Create a pending result set for content that is not ready yet, and
+/// answer with a PENDING status.
+///
+/// The result set worker prepares its content for poll fulfillment. When
+/// the service can push and the request has delivery parameters, the
+/// prepared results are also pushed to the client's inbox (`WILL_PUSH`).
+async fn pending_poll_result<R: Taxii1Repository>(
+    ctx: &HandlerContext<R>,
+    in_response_to: &str,
+    collection_id: i32,
+    content_bindings: Vec<ContentBindingEntity>,
+    timeframe: (Option<DateTime<Utc>>, Option<DateTime<Utc>>),
+    delivery: Option<PushParametersEntity>,
+) -> Taxii1xResult<PollResult> {
+    let can_push = ctx
+        .service
+        .get_property("can_push")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    let delivery = delivery.filter(|_| can_push);
+    let wait_time = estimated_wait(ctx).await?;
+
+    let result_set_entity = taxii_core::ResultSetEntity {
+        id: generate_id(),
+        collection_id,
+        content_bindings,
+        timeframe,
+        status: result_set_status::PENDING.to_string(),
+        content_block_count: None,
+        expires_at: Some(result_set_expiry(ctx)),
+        delivery_status: delivery
+            .as_ref()
+            .map(|_| delivery_status::PENDING.to_string()),
+        delivery,
+    };
+
+    let result_set = ctx
+        .persistence
+        .create_result_set(&result_set_entity)
+        .await?;
+
+    Ok(PollResult::Status(pending_status(
+        in_response_to,
+        &result_set.id,
+        wait_time,
+        result_set.expires_at,
+        Some(result_set.delivery.is_some()),
+    )))
+}
+
 /// Resolve content bindings for TAXII 1.0 poll requests.
-async fn resolve_poll_bindings_10(
-    ctx: &HandlerContext,
+async fn resolve_poll_bindings_10<R: Taxii1Repository>(
+    ctx: &HandlerContext<R>,
     request: &tm10::PollRequest,
     collection: &CollectionEntity,
 ) -> Taxii1xResult<Vec<ContentBindingEntity>> {
     let collection_id = collection.id.unwrap_or(0);
 
     if let Some(ref subscription_id) = request.subscription_id {
-        // Get bindings from subscription
-        let subscription = ctx.persistence.get_subscription(subscription_id).await?;
+        // Get bindings from the client's subscription
+        let subscription = ctx
+            .persistence
+            .get_subscription(subscription_id)
+            .await?
+            .filter(|sub| ctx.owns(sub));
 
         let sub = subscription.ok_or_else(|| Taxii1xError::StatusMessage {
             message: "Requested subscription was not found".to_string(),
@@ -165,12 +336,23 @@ async fn resolve_poll_bindings_10(
                 status_detail: Some(collection.name.clone()),
             });
         }
+        ensure_active(&sub, &request.message_id)?;
 
-        Ok(sub
-            .params
-            .as_ref()
-            .map(|p| p.content_bindings.clone())
-            .unwrap_or_default())
+        let Some(params) = sub.params else {
+            return Ok(Vec::new());
+        };
+
+        // TAXII 1.0 poll responses have no record count
+        if params.response_type == RT_COUNT_ONLY {
+            return Err(Taxii1xError::StatusMessage {
+                message: "COUNT_ONLY responses are not supported in TAXII 1.0".to_string(),
+                in_response_to: Some(request.message_id.clone()),
+                status_type: StatusType::Failure,
+                status_detail: Some(subscription_id.clone()),
+            });
+        }
+
+        Ok(params.content_bindings)
     } else {
         // Parse bindings from request
         let requested_bindings: Vec<ContentBindingEntity> = request
@@ -283,8 +465,9 @@ struct PollParams<'a> {
 
     /// Content type filter (e.g., STIX, OpenIOC bindings).
     ///
-    /// When `Some`, only matching content blocks are returned.
-    content_bindings: Option<Vec<String>>,
+    /// When `Some`, only matching content blocks are returned. A binding
+    /// with subtypes only matches blocks of those subtypes.
+    content_bindings: Option<Vec<ContentBindingEntity>>,
 
     /// Whether to return full content (`true`) or just count (`false`).
     ///
@@ -299,6 +482,9 @@ struct PollParams<'a> {
     /// If `false` and results aren't ready, returns an error.
     allow_async: bool,
 
+    /// Inbox to push the results to when they are not ready yet.
+    delivery: Option<PushParametersEntity>,
+
     /// Which part of paginated results to return (1-based).
     result_part: i32,
 
@@ -312,8 +498,8 @@ impl PollRequest11Handler {
     /// This method handles both synchronous and asynchronous polling:
     /// - For synchronous polling, returns content blocks directly
     /// - For asynchronous polling (when ResultsNotReady is raised), returns ST_PENDING status
-    async fn prepare_poll_response(
-        ctx: &HandlerContext,
+    async fn prepare_poll_response<R: Taxii1Repository>(
+        ctx: &HandlerContext<R>,
         params: PollParams<'_>,
     ) -> Taxii1xResult<PollResult> {
         let PollParams {
@@ -324,6 +510,7 @@ impl PollRequest11Handler {
             return_content,
             subscription_id,
             allow_async,
+            delivery,
             result_part,
             result_id,
         } = params;
@@ -369,17 +556,7 @@ impl PollRequest11Handler {
             .and_then(|v| v.as_i64())
             .unwrap_or(10_000_000);
 
-        // Convert content bindings to entities
-        let binding_entities: Option<Vec<taxii_core::ContentBindingEntity>> =
-            content_bindings.clone().map(|bindings| {
-                bindings
-                    .iter()
-                    .map(|b| taxii_core::ContentBindingEntity {
-                        binding: b.clone(),
-                        subtypes: Vec::new(),
-                    })
-                    .collect()
-            });
+        let binding_entities = content_bindings.as_deref();
 
         if return_content {
             // Calculate offset based on result_part
@@ -392,14 +569,23 @@ impl PollRequest11Handler {
                     collection.id,
                     timeframe.0,
                     timeframe.1,
-                    binding_entities.as_deref(),
+                    binding_entities,
                     offset,
                     Some(max_result_size as i64),
                 )
                 .await;
 
             match blocks_result {
-                Ok(blocks) => {
+                Ok(mut blocks) => {
+                    // The part ends early once its content reaches the byte
+                    // limit; result parts are split the same way on fulfillment
+                    let fetched = blocks.len();
+                    blocks.truncate(blocks_within(
+                        blocks.iter().map(|block| block.content.len()),
+                        ctx.limits.max_result_bytes,
+                    ));
+                    let truncated = blocks.len() < fetched;
+
                     // Calculate has_more and record_count
                     let (has_more, capped_count, is_partial) = if count_blocks_in_poll_responses {
                         // Count total and calculate
@@ -409,39 +595,36 @@ impl PollRequest11Handler {
                                 collection.id,
                                 timeframe.0,
                                 timeframe.1,
-                                binding_entities.as_deref(),
+                                binding_entities,
                             )
                             .await?;
 
-                        let has_more =
-                            (total_count as f64 / max_result_size as f64) > result_part as f64;
+                        let has_more = truncated
+                            || (total_count as f64 / max_result_size as f64) > result_part as f64;
                         let capped_count = std::cmp::min(max_result_count, total_count);
                         let is_partial = capped_count < total_count;
 
                         (has_more, Some(capped_count), is_partial)
                     } else {
                         // Simple check without counting
-                        let has_more = blocks.len() == max_result_size;
+                        let has_more = truncated || fetched == max_result_size;
                         (has_more, None, false)
                     };
 
                     // Create result set if has_more and no result_id
                     let result_id_to_use = if has_more && result_id.is_none() {
-                        let result_bindings: Vec<taxii_core::ContentBindingEntity> =
-                            content_bindings
-                                .unwrap_or_default()
-                                .into_iter()
-                                .map(|b| taxii_core::ContentBindingEntity {
-                                    binding: b,
-                                    subtypes: Vec::new(),
-                                })
-                                .collect();
+                        let result_bindings = content_bindings.clone().unwrap_or_default();
 
                         let result_set_entity = taxii_core::ResultSetEntity {
                             id: generate_id(),
                             collection_id: collection.id.unwrap_or(0),
                             content_bindings: result_bindings,
                             timeframe,
+                            status: result_set_status::READY.to_string(),
+                            content_block_count: None,
+                            expires_at: Some(result_set_expiry(ctx)),
+                            delivery: None,
+                            delivery_status: None,
                         };
 
                         let result_set = ctx
@@ -472,10 +655,11 @@ impl PollRequest11Handler {
 
                     // Set timeframe in response
                     if let Some(start) = timeframe.0 {
-                        response.exclusive_begin_timestamp_label = Some(start.to_rfc3339());
+                        response.exclusive_begin_timestamp_label =
+                            Some(format_timestamp_label(&start));
                     }
                     if let Some(end) = timeframe.1 {
-                        response.inclusive_end_timestamp_label = Some(end.to_rfc3339());
+                        response.inclusive_end_timestamp_label = Some(format_timestamp_label(&end));
                     }
 
                     // Set record count
@@ -497,8 +681,8 @@ impl PollRequest11Handler {
                                     .map(|cb| cb.binding.as_str())
                                     .unwrap_or(""),
                             ),
-                            content: String::from_utf8_lossy(&block.content).into_owned(),
-                            timestamp_label: Some(block.timestamp_label.to_rfc3339()),
+                            content: encode_content(&block.content_encoding, &block.content),
+                            timestamp_label: Some(format_timestamp_label(&block.timestamp_label)),
                             message: block.message,
                             padding: None,
                         })
@@ -515,70 +699,24 @@ impl PollRequest11Handler {
                         ));
                     }
 
-                    // Build content binding entities for result set
-                    let result_bindings: Vec<taxii_core::ContentBindingEntity> = content_bindings
-                        .unwrap_or_default()
-                        .into_iter()
-                        .map(|b| taxii_core::ContentBindingEntity {
-                            binding: b,
-                            subtypes: Vec::new(),
-                        })
-                        .collect();
-
-                    // Create a result set for poll fulfillment
-                    let result_set_entity = taxii_core::ResultSetEntity {
-                        id: generate_id(),
-                        collection_id: collection.id.unwrap_or(0),
-                        content_bindings: result_bindings,
+                    pending_poll_result(
+                        ctx,
+                        in_response_to,
+                        collection.id.unwrap_or(0),
+                        content_bindings.unwrap_or_default(),
                         timeframe,
-                    };
-
-                    let result_set = ctx
-                        .persistence
-                        .create_result_set(&result_set_entity)
-                        .await?;
-
-                    // Get service configuration for wait_time and can_push
-                    let wait_time = ctx
-                        .service
-                        .get_property("wait_time")
-                        .and_then(|v| v.as_i64())
-                        .unwrap_or(300); // Default 5 minutes
-
-                    let can_push = ctx
-                        .service
-                        .get_property("can_push")
-                        .and_then(|v| v.as_bool())
-                        .unwrap_or(false);
-
-                    // Build status details
-                    let status_detail = HashMap::from([
-                        (SD_ESTIMATED_WAIT.to_owned(), wait_time.to_string()),
-                        (SD_RESULT_ID.to_owned(), result_set.id),
-                        (SD_WILL_PUSH.to_owned(), can_push.to_string()),
-                    ]);
-
-                    let status = tm11::StatusMessage::new(
-                        generate_id(),
-                        in_response_to.to_string(),
-                        ST_PENDING.to_string(),
+                        delivery,
                     )
-                    .with_status_detail(status_detail);
-
-                    Ok(PollResult::Status(status))
+                    .await
                 }
                 Err(e) => Err(e.into()),
             }
         } else {
-            // COUNT_ONLY response - just count the blocks
+            // COUNT_ONLY response - just count the blocks, content blocks
+            // are never fetched
             let count = ctx
                 .persistence
-                .get_content_blocks_count(
-                    collection.id,
-                    timeframe.0,
-                    timeframe.1,
-                    binding_entities.as_deref(),
-                )
+                .get_content_blocks_count(collection.id, timeframe.0, timeframe.1, binding_entities)
                 .await?;
 
             let mut response = tm11::PollResponse::new(
@@ -594,15 +732,17 @@ impl PollRequest11Handler {
 
             // Set timeframe in response
             if let Some(start) = timeframe.0 {
-                response.exclusive_begin_timestamp_label = Some(start.to_rfc3339());
+                response.exclusive_begin_timestamp_label = Some(format_timestamp_label(&start));
             }
             if let Some(end) = timeframe.1 {
-                response.inclusive_end_timestamp_label = Some(end.to_rfc3339());
+                response.inclusive_end_timestamp_label = Some(format_timestamp_label(&end));
             }
 
+            // The count follows the same cap as a full response
+            let capped_count = std::cmp::min(max_result_count, count);
             response.record_count = Some(tm11::RecordCount {
-                partial_count: false,
-                record_count: count,
+                partial_count: capped_count < count,
+                record_count: capped_count,
             });
 
             Ok(PollResult::Response(response))
@@ -610,9 +750,9 @@ impl PollRequest11Handler {
     }
 
     /// Handle a TAXII 1.1 Poll Request.
-    pub async fn handle_11(
+    pub async fn handle_11<R: Taxii1Repository>(
         &self,
-        ctx: &HandlerContext,
+        ctx: &HandlerContext<R>,
         _headers: &TaxiiHeaders,
         message: &tm11::Taxii11Message,
     ) -> Taxii1xResult<tm11::Taxii11Message> {
@@ -689,22 +829,16 @@ impl PollRequest11Handler {
             }
         }
 
+        let end = Some(effective_end(start, end));
+
         // Resolve content bindings and response parameters
         let resolved = resolve_poll_bindings_11(ctx, request, &collection).await?;
         let return_content = resolved.response_type != RT_COUNT_ONLY;
 
-        // Convert bindings to string format for prepare_poll_response
-        let binding_strings: Option<Vec<String>> = if resolved.content_bindings.is_empty() {
-            None
-        } else {
-            Some(
-                resolved
-                    .content_bindings
-                    .iter()
-                    .map(|cb| cb.binding.clone())
-                    .collect(),
-            )
-        };
+        // Bindings keep their subtypes, so polls for a subtype only get
+        // content of that subtype
+        let content_bindings =
+            Some(resolved.content_bindings).filter(|bindings| !bindings.is_empty());
 
         let result = Self::prepare_poll_response(
             ctx,
@@ -712,10 +846,11 @@ impl PollRequest11Handler {
                 collection_name,
                 in_response_to: &request.message_id,
                 timeframe: (start, end),
-                content_bindings: binding_strings,
+                content_bindings,
                 return_content,
                 subscription_id: request.subscription_id.as_deref(),
                 allow_async: resolved.allow_async,
+                delivery: resolved.delivery,
                 result_part: 1,
                 result_id: None,
             },
@@ -734,9 +869,9 @@ pub struct PollRequest10Handler;
 
 impl PollRequest10Handler {
     /// Handle a TAXII 1.0 Poll Request.
-    pub async fn handle_10(
+    pub async fn handle_10<R: Taxii1Repository>(
         &self,
-        ctx: &HandlerContext,
+        ctx: &HandlerContext<R>,
         _headers: &TaxiiHeaders,
         message: &tm10::Taxii10Message,
     ) -> Taxii1xResult<tm10::Taxii10Message> {
@@ -790,15 +925,15 @@ impl PollRequest10Handler {
             .inclusive_end_timestamp_label
             .as_deref()
             .and_then(parse_timestamp);
-        let end_response = end.unwrap_or_else(Utc::now);
+        let end = effective_end(start, end);
 
         // Build response
         let mut response = tm10::PollResponse::new(generate_id(), &request.message_id, feed_name);
         response.subscription_id = request.subscription_id.clone();
         if let Some(s) = start {
-            response.inclusive_begin_timestamp_label = Some(s.to_rfc3339());
+            response.inclusive_begin_timestamp_label = Some(format_timestamp_label(&s));
         }
-        response.inclusive_end_timestamp_label = Some(end_response.to_rfc3339());
+        response.inclusive_end_timestamp_label = Some(format_timestamp_label(&end));
 
         // Convert bindings for query
         let binding_entities: Option<&[ContentBindingEntity]> = if content_bindings.is_empty() {
@@ -810,7 +945,7 @@ impl PollRequest10Handler {
         // Get content blocks
         let blocks = ctx
             .persistence
-            .get_content_blocks(collection.id, start, end, binding_entities, 0, None)
+            .get_content_blocks(collection.id, start, Some(end), binding_entities, 0, None)
             .await?;
 
         response.content_blocks = blocks
@@ -818,10 +953,11 @@ impl PollRequest10Handler {
             .map(|block| tm10::ContentBlock {
                 content_binding: block
                     .content_binding
-                    .map(|cb| cb.binding)
+                    .as_ref()
+                    .map(|cb| cb.binding.clone())
                     .unwrap_or_default(),
-                content: String::from_utf8_lossy(&block.content).into_owned(),
-                timestamp_label: Some(block.timestamp_label.to_rfc3339()),
+                content: encode_content(&block.content_encoding, &block.content),
+                timestamp_label: Some(format_timestamp_label(&block.timestamp_label)),
                 padding: None,
             })
             .collect();
@@ -829,3 +965,621 @@ impl PollRequest10Handler {
         Ok(tm10::Taxii10Message::PollResponse(response))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{ACT_SUBSCRIBE, CB_SMIME, VID_TAXII_HTTP_10};
+    use crate::content::decode_content;
+    use crate::handlers::ServiceInfo;
+    use crate::handlers::subscription::SubscriptionRequest11Handler;
+    use crate::messages::{NS_TAXII_10, NS_TAXII_11};
+    use crate::{VID_TAXII_SERVICES_10, VID_TAXII_SERVICES_11, VID_TAXII_XML_10, VID_TAXII_XML_11};
+    use base64::Engine;
+    use base64::engine::general_purpose::STANDARD as BASE64;
+    use sqlx::PgPool;
+    use std::sync::Arc;
+    use taxii_core::{ContentBlockEntity, ServiceEntity, collection_type, content_encoding};
+    use taxii_db::{DbTaxii1Repository, InMemoryTaxii1Repository, TaxiiPool};
+
+    type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+    const SERVICE_ID: &str = "poll-a";
+    const COLLECTION_NAME: &str = "collection-a";
+    const STIX_XML: &str = "urn:stix.mitre.org:xml:1.1.1";
+
+    fn binding(subtypes: &[&str]) -> ContentBindingEntity {
+        ContentBindingEntity::with_subtypes(
+            STIX_XML,
+            subtypes.iter().map(|s| s.to_string()).collect(),
+        )
+    }
+
+    /// Create a collection supporting the given bindings, with one block of
+    /// each of the subtypes `capec`, `cybox` and `ttp`.
+    async fn setup(
+        pool: PgPool,
+        supported_content: Vec<ContentBindingEntity>,
+    ) -> Result<HandlerContext, Box<dyn std::error::Error>> {
+        setup_with(
+            DbTaxii1Repository::new(TaxiiPool::new(pool)),
+            supported_content,
+        )
+        .await
+    }
+
+    /// Like [`setup`], in the given repository.
+    async fn setup_with<R: Taxii1Repository>(
+        persistence: R,
+        supported_content: Vec<ContentBindingEntity>,
+    ) -> Result<HandlerContext<R>, Box<dyn std::error::Error>> {
+        let persistence = Arc::new(persistence);
+        persistence
+            .update_service(&ServiceEntity {
+                id: Some(SERVICE_ID.to_string()),
+                service_type: "POLL".to_string(),
+                properties: serde_json::json!({}),
+            })
+            .await?;
+        let collection = persistence
+            .create_collection(&CollectionEntity {
+                accept_all_content: supported_content.is_empty(),
+                collection_type: collection_type::DATA_SET.to_string(),
+                supported_content,
+                ..CollectionEntity::new(COLLECTION_NAME)
+            })
+            .await?;
+        let collection_id = collection.id.ok_or("collection has no id")?;
+        persistence
+            .set_collection_services(collection_id, &[SERVICE_ID.to_string()])
+            .await?;
+
+        for subtype in ["capec", "cybox", "ttp"] {
+            let block = ContentBlockEntity::new(format!("<{subtype}/>"), Some(binding(&[subtype])));
+            persistence
+                .create_content_block(&block, Some(&[collection_id]), None)
+                .await?;
+        }
+
+        Ok(HandlerContext {
+            account: None,
+            persistence,
+            service: ServiceInfo {
+                id: SERVICE_ID.to_string(),
+                service_type: "POLL".to_string(),
+                address: "/services/poll-a/".to_string(),
+                description: None,
+                protocol_bindings: Vec::new(),
+                message_bindings: vec![VID_TAXII_XML_11.to_string()],
+                available: true,
+                authentication_required: false,
+                properties: serde_json::json!({}),
+            },
+            domain: None,
+            limits: Default::default(),
+            push: Default::default(),
+            hooks: None,
+        })
+    }
+
+    /// Poll for the given bindings, returning the polled contents sorted.
+    async fn poll(
+        ctx: &HandlerContext,
+        bindings: &[ContentBindingEntity],
+    ) -> Taxii1xResult<Vec<String>> {
+        let headers = TaxiiHeaders {
+            content_type: VID_TAXII_XML_11.to_string(),
+            services: VID_TAXII_SERVICES_11.to_string(),
+            accept: None,
+            protocol: None,
+        };
+        let request = tm11::Taxii11Message::PollRequest(tm11::PollRequest {
+            xmlns: NS_TAXII_11.to_string(),
+            message_id: "poll-1".to_string(),
+            collection_name: COLLECTION_NAME.to_string(),
+            extended_headers: None,
+            exclusive_begin_timestamp_label: None,
+            inclusive_end_timestamp_label: None,
+            subscription_id: None,
+            poll_parameters: Some(tm11::PollParameters {
+                allow_asynch: None,
+                response_type: None,
+                delivery_parameters: None,
+                content_bindings: bindings
+                    .iter()
+                    .map(|b| tm11::ContentBinding::with_subtypes(&b.binding, b.subtypes.clone()))
+                    .collect(),
+            }),
+        });
+        match PollRequest11Handler
+            .handle_11(ctx, &headers, &request)
+            .await?
+        {
+            tm11::Taxii11Message::PollResponse(response) => {
+                let mut contents: Vec<String> = response
+                    .content_blocks
+                    .into_iter()
+                    .map(|b| b.content)
+                    .collect();
+                contents.sort();
+                Ok(contents)
+            }
+            other => Err(Taxii1xError::failure(
+                format!("expected poll response, got {other:?}"),
+                None,
+            )),
+        }
+    }
+
+    fn headers_11() -> TaxiiHeaders {
+        TaxiiHeaders {
+            content_type: VID_TAXII_XML_11.to_string(),
+            services: VID_TAXII_SERVICES_11.to_string(),
+            accept: None,
+            protocol: None,
+        }
+    }
+
+    /// Poll with the given response type, by poll parameters or by
+    /// subscription.
+    async fn poll_response<R: Taxii1Repository>(
+        ctx: &HandlerContext<R>,
+        response_type: &str,
+        subscription_id: Option<&str>,
+    ) -> Taxii1xResult<tm11::PollResponse> {
+        let request = tm11::Taxii11Message::PollRequest(tm11::PollRequest {
+            xmlns: NS_TAXII_11.to_string(),
+            message_id: "poll-1".to_string(),
+            collection_name: COLLECTION_NAME.to_string(),
+            extended_headers: None,
+            exclusive_begin_timestamp_label: None,
+            inclusive_end_timestamp_label: None,
+            subscription_id: subscription_id.map(String::from),
+            poll_parameters: subscription_id.is_none().then(|| tm11::PollParameters {
+                allow_asynch: None,
+                response_type: Some(response_type.to_string()),
+                content_bindings: Vec::new(),
+                delivery_parameters: None,
+            }),
+        });
+        match PollRequest11Handler
+            .handle_11(ctx, &headers_11(), &request)
+            .await?
+        {
+            tm11::Taxii11Message::PollResponse(response) => Ok(response),
+            other => Err(Taxii1xError::failure(
+                format!("expected poll response, got {other:?}"),
+                None,
+            )),
+        }
+    }
+
+    /// Subscribe to the collection with the given response type.
+    async fn subscribe<R: Taxii1Repository>(
+        ctx: &HandlerContext<R>,
+        response_type: &str,
+    ) -> Result<tm11::SubscriptionInstance, Box<dyn std::error::Error>> {
+        let request = tm11::Taxii11Message::ManageCollectionSubscriptionRequest(
+            tm11::ManageCollectionSubscriptionRequest {
+                xmlns: NS_TAXII_11.to_string(),
+                message_id: "subscribe-1".to_string(),
+                action: ACT_SUBSCRIBE.to_string(),
+                collection_name: COLLECTION_NAME.to_string(),
+                extended_headers: None,
+                subscription_id: None,
+                subscription_parameters: Some(tm11::SubscriptionParameters {
+                    response_type: Some(response_type.to_string()),
+                    content_bindings: Vec::new(),
+                }),
+                push_parameters: None,
+            },
+        );
+        match SubscriptionRequest11Handler
+            .handle_11(ctx, &headers_11(), &request)
+            .await?
+        {
+            tm11::Taxii11Message::ManageCollectionSubscriptionResponse(response) => response
+                .subscription_instances
+                .into_iter()
+                .next()
+                .ok_or_else(|| "no subscription instance".into()),
+            other => Err(format!("expected subscription response, got {other:?}").into()),
+        }
+    }
+
+    fn is_unsupported(result: Taxii1xResult<Vec<String>>) -> bool {
+        matches!(
+            result,
+            Err(Taxii1xError::StatusMessage {
+                status_type: StatusType::UnsupportedContentBinding,
+                ..
+            })
+        )
+    }
+
+    /// A collection supporting the parent binding serves any subtype.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_poll_subtypes_of_supported_parent(pool: PgPool) -> TestResult {
+        let ctx = setup(pool, vec![binding(&[])]).await?;
+
+        assert_eq!(
+            poll(&ctx, &[binding(&[])]).await?,
+            vec!["<capec/>", "<cybox/>", "<ttp/>"]
+        );
+        assert_eq!(poll(&ctx, &[binding(&["capec"])]).await?, vec!["<capec/>"]);
+        assert_eq!(
+            poll(&ctx, &[binding(&["capec", "ttp"])]).await?,
+            vec!["<capec/>", "<ttp/>"]
+        );
+        assert!(is_unsupported(
+            poll(&ctx, &[ContentBindingEntity::new("urn:other:binding")]).await
+        ));
+
+        Ok(())
+    }
+
+    /// A collection restricted to subtypes only serves those subtypes.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_poll_subtypes_of_restricted_collection(pool: PgPool) -> TestResult {
+        let ctx = setup(pool, vec![binding(&["capec", "cybox"])]).await?;
+
+        // Parent request: the supported subtypes
+        assert_eq!(
+            poll(&ctx, &[binding(&[])]).await?,
+            vec!["<capec/>", "<cybox/>"]
+        );
+        // Subtype request: the common subtypes
+        assert_eq!(poll(&ctx, &[binding(&["cybox"])]).await?, vec!["<cybox/>"]);
+        assert_eq!(
+            poll(&ctx, &[binding(&["cybox", "ttp"])]).await?,
+            vec!["<cybox/>"]
+        );
+        // No common subtype
+        assert!(is_unsupported(poll(&ctx, &[binding(&["ttp"])]).await));
+
+        Ok(())
+    }
+
+    /// A COUNT_ONLY poll only counts the content blocks.
+    #[tokio::test]
+    async fn test_count_only_poll() -> TestResult {
+        let ctx = setup_with(InMemoryTaxii1Repository::new(), Vec::new()).await?;
+        // Content blocks can be counted but not fetched
+        ctx.persistence.set_results_ready(false);
+
+        // Fetching blocks would fail the poll
+        assert!(poll_response(&ctx, RT_FULL, None).await.is_err());
+
+        let response = poll_response(&ctx, RT_COUNT_ONLY, None).await?;
+        assert!(response.content_blocks.is_empty());
+        assert_eq!(
+            response.record_count,
+            Some(tm11::RecordCount {
+                partial_count: false,
+                record_count: 3,
+            })
+        );
+
+        Ok(())
+    }
+
+    /// A COUNT_ONLY poll caps the count by `max_result_count`.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_count_only_poll_capped(pool: PgPool) -> TestResult {
+        let mut ctx = setup(pool, Vec::new()).await?;
+        ctx.service.properties = serde_json::json!({"max_result_count": 2});
+
+        let response = poll_response(&ctx, RT_COUNT_ONLY, None).await?;
+        assert!(response.content_blocks.is_empty());
+        assert_eq!(
+            response.record_count,
+            Some(tm11::RecordCount {
+                partial_count: true,
+                record_count: 2,
+            })
+        );
+
+        Ok(())
+    }
+
+    /// A COUNT_ONLY subscription keeps its response type.
+    #[tokio::test]
+    async fn test_count_only_subscription() -> TestResult {
+        let ctx = setup_with(InMemoryTaxii1Repository::new(), Vec::new()).await?;
+        let instance = subscribe(&ctx, RT_COUNT_ONLY).await?;
+        assert_eq!(
+            instance
+                .subscription_parameters
+                .and_then(|p| p.response_type)
+                .as_deref(),
+            Some(RT_COUNT_ONLY)
+        );
+
+        let stored = ctx
+            .persistence
+            .get_subscription(&instance.subscription_id)
+            .await?
+            .and_then(|sub| sub.params)
+            .ok_or("subscription has no parameters")?;
+        assert_eq!(stored.response_type, RT_COUNT_ONLY);
+
+        // Content blocks can be counted but not fetched
+        ctx.persistence.set_results_ready(false);
+        let response = poll_response(&ctx, RT_FULL, Some(&instance.subscription_id)).await?;
+        assert!(response.content_blocks.is_empty());
+        assert_eq!(
+            response.record_count.map(|count| count.record_count),
+            Some(3)
+        );
+
+        Ok(())
+    }
+
+    /// TAXII 1.0 polls have no record count, so COUNT_ONLY subscriptions
+    /// are rejected.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_count_only_subscription_10(pool: PgPool) -> TestResult {
+        let ctx = setup(pool, Vec::new()).await?;
+        let mut collection = ctx
+            .persistence
+            .get_collection(COLLECTION_NAME, None)
+            .await?
+            .ok_or("collection not found")?;
+        collection.collection_type = CT_DATA_FEED.to_string();
+        ctx.persistence.update_collection(&collection).await?;
+        let instance = subscribe(&ctx, RT_COUNT_ONLY).await?;
+
+        let headers = TaxiiHeaders {
+            content_type: VID_TAXII_XML_10.to_string(),
+            services: VID_TAXII_SERVICES_10.to_string(),
+            accept: None,
+            protocol: None,
+        };
+        let request = tm10::Taxii10Message::PollRequest(tm10::PollRequest {
+            xmlns: NS_TAXII_10.to_string(),
+            message_id: "poll-1".to_string(),
+            feed_name: COLLECTION_NAME.to_string(),
+            extended_headers: None,
+            subscription_id: Some(instance.subscription_id),
+            exclusive_begin_timestamp_label: None,
+            inclusive_end_timestamp_label: None,
+            content_bindings: Vec::new(),
+        });
+        let result = PollRequest10Handler
+            .handle_10(&ctx, &headers, &request)
+            .await;
+        assert!(matches!(
+            result,
+            Err(Taxii1xError::StatusMessage {
+                status_type: StatusType::Failure,
+                ..
+            })
+        ));
+
+        Ok(())
+    }
+
+    /// Content is polled in the encoding it was stored with: binary
+    /// content base64 encoded once, text content as is, including S/MIME
+    /// content stored as base64 text before encodings were recorded.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_poll_content_encodings(pool: PgPool) -> TestResult {
+        const NOT_UTF8: &[u8] = &[0x30, 0x82, 0xff, 0xfe, 0x00, 0x80];
+        const LEGACY_SMIME: &str = "MIL//gCA";
+
+        let ctx = setup(pool, Vec::new()).await?;
+        let collection_id = ctx
+            .persistence
+            .get_collection(COLLECTION_NAME, None)
+            .await?
+            .and_then(|c| c.id)
+            .ok_or("collection not found")?;
+        for (binding, content, encoding) in [
+            (CB_SMIME, NOT_UTF8, content_encoding::BASE64),
+            ("urn:example.com:binary", NOT_UTF8, content_encoding::BASE64),
+            (CB_SMIME, LEGACY_SMIME.as_bytes(), content_encoding::TEXT),
+        ] {
+            let block = ContentBlockEntity {
+                content_encoding: encoding.to_string(),
+                ..ContentBlockEntity::new(content, Some(ContentBindingEntity::new(binding)))
+            };
+            ctx.persistence
+                .create_content_block(&block, Some(&[collection_id]), None)
+                .await?;
+        }
+
+        let response = poll_response(&ctx, RT_FULL, None).await?;
+        let smime: Vec<&str> = response
+            .content_blocks
+            .iter()
+            .filter(|b| b.content_binding.binding_id == CB_SMIME)
+            .map(|b| b.content.as_str())
+            .collect();
+        assert_eq!(smime.len(), 2);
+        assert_eq!(decode_content(CB_SMIME, smime[0])?, NOT_UTF8);
+        assert_eq!(smime[1], LEGACY_SMIME);
+        let other = response
+            .content_blocks
+            .iter()
+            .find(|b| b.content_binding.binding_id == "urn:example.com:binary")
+            .ok_or("binary block not polled")?;
+        assert_eq!(BASE64.decode(&other.content)?, NOT_UTF8);
+
+        Ok(())
+    }
+
+    /// Poll the timeframe between the given labels.
+    async fn poll_timeframe(
+        ctx: &HandlerContext,
+        begin: Option<&str>,
+        end: Option<&str>,
+    ) -> Taxii1xResult<tm11::PollResponse> {
+        let request = tm11::Taxii11Message::PollRequest(tm11::PollRequest {
+            xmlns: NS_TAXII_11.to_string(),
+            message_id: "poll-1".to_string(),
+            collection_name: COLLECTION_NAME.to_string(),
+            extended_headers: None,
+            exclusive_begin_timestamp_label: begin.map(String::from),
+            inclusive_end_timestamp_label: end.map(String::from),
+            subscription_id: None,
+            poll_parameters: Some(tm11::PollParameters {
+                allow_asynch: None,
+                response_type: None,
+                content_bindings: Vec::new(),
+                delivery_parameters: None,
+            }),
+        });
+        match PollRequest11Handler
+            .handle_11(ctx, &headers_11(), &request)
+            .await?
+        {
+            tm11::Taxii11Message::PollResponse(response) => Ok(response),
+            other => Err(Taxii1xError::failure(
+                format!("expected poll response, got {other:?}"),
+                None,
+            )),
+        }
+    }
+
+    #[test]
+    fn test_parse_timestamp_truncates_to_micros() {
+        let parsed = parse_timestamp("2020-01-01T00:00:00.0000019+00:00");
+        assert_eq!(
+            parsed.map(|t| format_timestamp_label(&t)).as_deref(),
+            Some("2020-01-01T00:00:00.000001Z")
+        );
+    }
+
+    /// A cursor that sends back the end label of each response as the next
+    /// exclusive begin sees blocks a microsecond apart exactly once.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_poll_cursor_microsecond_labels(pool: PgPool) -> TestResult {
+        const FIRST: &str = "2020-01-01T00:00:00.000001Z";
+        const SECOND: &str = "2020-01-01T00:00:00.000002Z";
+
+        let ctx = setup(pool, Vec::new()).await?;
+        let collection_id = ctx
+            .persistence
+            .get_collection(COLLECTION_NAME, None)
+            .await?
+            .and_then(|c| c.id)
+            .ok_or("collection not found")?;
+        for (label, content) in [(FIRST, "<first/>"), (SECOND, "<second/>")] {
+            let block = ContentBlockEntity {
+                timestamp_label: parse_timestamp(label).ok_or("bad label")?,
+                ..ContentBlockEntity::new(content, Some(binding(&["ttp"])))
+            };
+            ctx.persistence
+                .create_content_block(&block, Some(&[collection_id]), None)
+                .await?;
+        }
+        let contents = |response: &tm11::PollResponse| -> Vec<String> {
+            response
+                .content_blocks
+                .iter()
+                .map(|b| b.content.clone())
+                .collect()
+        };
+
+        let first = poll_timeframe(&ctx, None, Some(FIRST)).await?;
+        assert_eq!(contents(&first), ["<first/>"]);
+        assert_eq!(
+            first.content_blocks[0].timestamp_label.as_deref(),
+            Some(FIRST)
+        );
+        let cursor = first.inclusive_end_timestamp_label.ok_or("no end label")?;
+        assert_eq!(cursor, FIRST);
+
+        let second = poll_timeframe(&ctx, Some(&cursor), Some(SECOND)).await?;
+        assert_eq!(contents(&second), ["<second/>"]);
+        assert_eq!(
+            second.exclusive_begin_timestamp_label.as_deref(),
+            Some(FIRST)
+        );
+        let cursor = second.inclusive_end_timestamp_label.ok_or("no end label")?;
+        assert_eq!(cursor, SECOND);
+
+        let third = poll_timeframe(&ctx, Some(&cursor), Some(SECOND)).await?;
+        assert!(contents(&third).is_empty());
+
+        // Without a requested end the end queried is echoed, so the next
+        // poll starts exactly where this one stopped
+        let rest = poll_timeframe(&ctx, Some(&cursor), None).await?;
+        assert_eq!(rest.content_blocks.len(), 3);
+        let cursor = rest.inclusive_end_timestamp_label.ok_or("no end label")?;
+        let empty = poll_timeframe(&ctx, Some(&cursor), Some(&cursor)).await?;
+        assert!(contents(&empty).is_empty());
+
+        Ok(())
+    }
+
+    /// Results that are not ready are pushed when the service can push and
+    /// the request has delivery parameters.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_pending_result_will_push(pool: PgPool) -> TestResult {
+        let mut ctx = setup(pool, Vec::new()).await?;
+        let collection_id = ctx
+            .persistence
+            .get_collection(COLLECTION_NAME, None)
+            .await?
+            .and_then(|c| c.id)
+            .ok_or("collection not found")?;
+        let delivery = PushParametersEntity {
+            protocol_binding: VID_TAXII_HTTP_10.to_string(),
+            address: "http://client.example/inbox/".to_string(),
+            message_binding: VID_TAXII_XML_11.to_string(),
+        };
+        let detail = |status: &tm11::StatusMessage, name: &str| {
+            status
+                .status_details
+                .iter()
+                .find(|d| d.name == name)
+                .and_then(|d| d.value.clone())
+        };
+
+        for (can_push, will_push) in [(false, "false"), (true, "true")] {
+            ctx.service.properties = serde_json::json!({"can_push": can_push, "wait_time": 10});
+            let status = match pending_poll_result(
+                &ctx,
+                "poll-1",
+                collection_id,
+                Vec::new(),
+                (None, None),
+                Some(delivery.clone()),
+            )
+            .await?
+            {
+                PollResult::Status(status) => status,
+                PollResult::Response(response) => {
+                    return Err(format!("expected status, got {response:?}").into());
+                }
+            };
+            assert_eq!(status.status_type, ST_PENDING);
+            assert_eq!(detail(&status, SD_WILL_PUSH).as_deref(), Some(will_push));
+
+            let result_id = detail(&status, SD_RESULT_ID).ok_or("no result id")?;
+            let result_set = ctx
+                .persistence
+                .get_result_set(&result_id)
+                .await?
+                .ok_or("result set not found")?;
+            assert_eq!(result_set.delivery.is_some(), can_push);
+        }
+
+        // Fewer result sets than a batch are queued, one batch is waited for
+        let wait = detail(
+            &pending_status("poll-1", "r", estimated_wait(&ctx).await?, None, None),
+            SD_ESTIMATED_WAIT,
+        );
+        assert_eq!(wait.as_deref(), Some("10"));
+
+        Ok(())
+    }
+}
@@ -3,23 +3,30 @@
 //! Note: Poll fulfillment is only available in TAXII 1.1.
 
 use crate::constants::StatusType;
+use crate::content::encode_content;
 use crate::error::{Taxii1xError, Taxii1xResult};
+use crate::limits::blocks_within;
 use crate::messages::tm11;
+use taxii_core::format_timestamp_label;
 use taxii_db::Taxii1Repository;
 
 use super::base::{HandlerContext, TaxiiHeaders, generate_id};
+use super::poll::pending_status;
 
 /// Default max result size
-const DEFAULT_MAX_RESULT_SIZE: i64 = 1_000_000;
+pub(crate) const DEFAULT_MAX_RESULT_SIZE: i64 = 1_000_000;
+
+/// Default estimated wait (seconds) reported for pending result sets
+const DEFAULT_WAIT_TIME: i64 = 300;
 
 /// TAXII 1.1 Poll Fulfillment Request Handler.
 pub struct PollFulfillmentRequest11Handler;
 
 impl PollFulfillmentRequest11Handler {
     /// Handle a TAXII 1.1 Poll Fulfillment Request.
-    pub async fn handle_11(
+    pub async fn handle_11<R: Taxii1Repository>(
         &self,
-        ctx: &HandlerContext,
+        ctx: &HandlerContext<R>,
         _headers: &TaxiiHeaders,
         message: &tm11::Taxii11Message,
     ) -> Taxii1xResult<tm11::Taxii11Message> {
@@ -37,7 +44,7 @@ impl PollFulfillmentRequest11Handler {
         let result_id = &request.result_id;
         let result_part = request.result_part_number.unwrap_or(1);
 
-        // Get result set
+        // Get result set; expired result sets are not found
         let result_set = ctx.persistence.get_result_set(result_id).await?;
 
         let result_set = result_set.ok_or_else(|| {
@@ -76,6 +83,23 @@ impl PollFulfillmentRequest11Handler {
             }
         }
 
+        // Result set is still being prepared by the result set worker
+        if result_set.is_pending() {
+            let wait_time = ctx
+                .service
+                .get_property("wait_time")
+                .and_then(|v| v.as_i64())
+                .unwrap_or(DEFAULT_WAIT_TIME);
+
+            return Ok(tm11::Taxii11Message::StatusMessage(pending_status(
+                &request.message_id,
+                result_id,
+                wait_time,
+                result_set.expires_at,
+                None,
+            )));
+        }
+
         let mut response =
             tm11::PollResponse::new(generate_id(), &request.message_id, collection_name);
 
@@ -83,14 +107,18 @@ impl PollFulfillmentRequest11Handler {
         response.result_part_number = Some(result_part);
 
         // Extract fields from result set (consuming it)
-        let (timeframe, content_bindings) = (result_set.timeframe, result_set.content_bindings);
+        let (timeframe, content_bindings, prepared_count) = (
+            result_set.timeframe,
+            result_set.content_bindings,
+            result_set.content_block_count,
+        );
         let (start, end) = timeframe;
 
         if let Some(s) = start {
-            response.exclusive_begin_timestamp_label = Some(s.to_rfc3339());
+            response.exclusive_begin_timestamp_label = Some(format_timestamp_label(&s));
         }
         if let Some(e) = end {
-            response.inclusive_end_timestamp_label = Some(e.to_rfc3339());
+            response.inclusive_end_timestamp_label = Some(format_timestamp_label(&e));
         }
 
         // Get content bindings from result set as entities
@@ -106,30 +134,87 @@ impl PollFulfillmentRequest11Handler {
             .service
             .get_property("max_result_size")
             .and_then(|v| v.as_i64())
-            .unwrap_or(DEFAULT_MAX_RESULT_SIZE);
+            .unwrap_or(DEFAULT_MAX_RESULT_SIZE)
+            .max(1);
 
-        // Calculate offset based on result_part
-        // offset = (part_number - 1) * max_result_size
-        let offset = ((result_part - 1) as i64) * max_result_size;
+        // The parts are split by content size on the first fulfillment and
+        // recorded, so later parts are served without sizing every block
+        let counts = match ctx.persistence.get_result_set_parts(result_id).await? {
+            Some(counts) => counts,
+            None => {
+                // Prepared result sets serve the content captured by the
+                // worker; others are resolved from their filters
+                let sizes = match prepared_count {
+                    Some(_) => {
+                        ctx.persistence
+                            .get_result_set_content_block_sizes(result_id)
+                            .await?
+                    }
+                    None => {
+                        ctx.persistence
+                            .get_content_block_sizes(
+                                collection.id,
+                                start,
+                                end,
+                                binding_entities.as_deref(),
+                            )
+                            .await?
+                    }
+                };
+                let sizes: Vec<usize> = sizes
+                    .into_iter()
+                    .map(|size| usize::try_from(size).unwrap_or(0))
+                    .collect();
+                let counts: Vec<i64> = result_parts(
+                    &sizes,
+                    usize::try_from(max_result_size).unwrap_or(usize::MAX),
+                    ctx.limits.max_result_bytes,
+                )
+                .into_iter()
+                .map(|(_, count)| count as i64)
+                .collect();
+                ctx.persistence
+                    .record_result_set_parts(result_id, &counts)
+                    .await?
+                    .unwrap_or(counts)
+            }
+        };
+        let total_count: i64 = counts.iter().sum();
 
-        // Get total count for pagination
-        let total_count = ctx
-            .persistence
-            .get_content_blocks_count(collection.id, start, end, binding_entities.as_deref())
-            .await?;
+        let part_count = counts.len() as i64;
+        let Some((index, &limit)) = usize::try_from(result_part)
+            .ok()
+            .and_then(|part| part.checked_sub(1))
+            .and_then(|index| Some((index, counts.get(index)?)))
+        else {
+            return Err(Taxii1xError::status_with_detail(
+                StatusType::NotFound,
+                format!(
+                    "Requested result part {result_part} is out of range; \
+                     the result set has {part_count} part(s)"
+                ),
+                Some(request.message_id.clone()),
+                result_id,
+            ));
+        };
+        let offset: i64 = counts.iter().take(index).sum();
 
-        // Get content blocks with proper pagination
-        let blocks = ctx
-            .persistence
-            .get_content_blocks(
-                collection.id,
-                start,
-                end,
-                binding_entities.as_deref(),
-                offset,
-                Some(max_result_size),
-            )
-            .await?;
+        let blocks = if prepared_count.is_some() {
+            ctx.persistence
+                .get_result_set_content_blocks(result_id, offset, Some(limit))
+                .await?
+        } else {
+            ctx.persistence
+                .get_content_blocks(
+                    collection.id,
+                    start,
+                    end,
+                    binding_entities.as_deref(),
+                    offset,
+                    Some(limit),
+                )
+                .await?
+        };
 
         response.content_blocks = blocks
             .into_iter()
@@ -141,15 +226,15 @@ impl PollFulfillmentRequest11Handler {
                         .map(|cb| cb.binding.as_str())
                         .unwrap_or(""),
                 ),
-                content: String::from_utf8_lossy(&block.content).into_owned(),
-                timestamp_label: Some(block.timestamp_label.to_rfc3339()),
+                content: encode_content(&block.content_encoding, &block.content),
+                timestamp_label: Some(format_timestamp_label(&block.timestamp_label)),
                 message: block.message,
                 padding: None,
             })
             .collect();
 
-        // Check if more parts available
-        let has_more = (total_count as f64 / max_result_size as f64) > result_part as f64;
+        // Every part but the last has more parts after it
+        let has_more = i64::from(result_part) < part_count;
         response.more = Some(has_more);
 
         response.record_count = Some(tm11::RecordCount {
@@ -160,3 +245,246 @@ impl PollFulfillmentRequest11Handler {
         Ok(tm11::Taxii11Message::PollResponse(response))
     }
 }
+
+/// Offset and number of blocks of each part of a result set whose blocks
+/// have the given content sizes.
+///
+/// Parts hold at most `max_result_size` blocks and, unless a single block is
+/// larger, `max_result_bytes` bytes of content. An empty result set still
+/// has one (empty) part.
+pub(crate) fn result_parts(
+    sizes: &[usize],
+    max_result_size: usize,
+    max_result_bytes: usize,
+) -> Vec<(usize, usize)> {
+    let mut parts = Vec::new();
+    let mut offset = 0;
+    while offset < sizes.len() {
+        let count = blocks_within(
+            sizes[offset..].iter().copied().take(max_result_size),
+            max_result_bytes,
+        );
+        parts.push((offset, count));
+        offset += count;
+    }
+    if parts.is_empty() {
+        parts.push((0, 0));
+    }
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::{ServiceInfo, TaxiiHeaders};
+    use crate::messages::NS_TAXII_11;
+    use crate::{VID_TAXII_SERVICES_11, VID_TAXII_XML_11};
+    use chrono::{Duration, Utc};
+    use sqlx::PgPool;
+    use std::sync::Arc;
+    use taxii_core::{
+        CollectionEntity, ContentBindingEntity, ContentBlockEntity, ResultSetEntity, ServiceEntity,
+        collection_type, result_set_status,
+    };
+    use taxii_db::{DbTaxii1Repository, TaxiiPool};
+
+    type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+    const SERVICE_ID: &str = "poll-a";
+    const COLLECTION_NAME: &str = "collection-a";
+    const RESULT_ID: &str = "result-set-a";
+
+    /// Create the poll service, its collection with five blocks and a result
+    /// set over them, returning a context paging by two blocks.
+    async fn setup(pool: PgPool) -> Result<HandlerContext, Box<dyn std::error::Error>> {
+        let persistence = Arc::new(DbTaxii1Repository::new(TaxiiPool::new(pool)));
+        persistence
+            .update_service(&ServiceEntity {
+                id: Some(SERVICE_ID.to_string()),
+                service_type: "POLL".to_string(),
+                properties: serde_json::json!({}),
+            })
+            .await?;
+        let collection = persistence
+            .create_collection(&CollectionEntity {
+                collection_type: collection_type::DATA_SET.to_string(),
+                ..CollectionEntity::new(COLLECTION_NAME)
+            })
+            .await?;
+        let collection_id = collection.id.ok_or("collection has no id")?;
+        persistence
+            .set_collection_services(collection_id, &[SERVICE_ID.to_string()])
+            .await?;
+
+        let start = Utc::now() - Duration::hours(1);
+        for n in 1..=5 {
+            let block = ContentBlockEntity {
+                timestamp_label: start + Duration::minutes(n),
+                ..ContentBlockEntity::new(
+                    format!("<block n=\"{n}\"/>"),
+                    Some(ContentBindingEntity::new("urn:stix.mitre.org:xml:1.1.1")),
+                )
+            };
+            persistence
+                .create_content_block(&block, Some(&[collection_id]), None)
+                .await?;
+        }
+        persistence
+            .create_result_set(&ResultSetEntity {
+                id: RESULT_ID.to_string(),
+                collection_id,
+                content_bindings: Vec::new(),
+                timeframe: (Some(start), None),
+                status: result_set_status::READY.to_string(),
+                content_block_count: None,
+                expires_at: Some(Utc::now() + Duration::hours(1)),
+                delivery: None,
+                delivery_status: None,
+            })
+            .await?;
+
+        Ok(HandlerContext {
+            account: None,
+            persistence,
+            service: ServiceInfo {
+                id: SERVICE_ID.to_string(),
+                service_type: "POLL".to_string(),
+                address: "/services/poll-a/".to_string(),
+                description: None,
+                protocol_bindings: Vec::new(),
+                message_bindings: vec![VID_TAXII_XML_11.to_string()],
+                available: true,
+                authentication_required: false,
+                properties: serde_json::json!({"max_result_size": 2}),
+            },
+            domain: None,
+            limits: Default::default(),
+            push: Default::default(),
+            hooks: None,
+        })
+    }
+
+    async fn fulfill(ctx: &HandlerContext, part: i32) -> Taxii1xResult<tm11::Taxii11Message> {
+        let headers = TaxiiHeaders {
+            content_type: VID_TAXII_XML_11.to_string(),
+            services: VID_TAXII_SERVICES_11.to_string(),
+            accept: None,
+            protocol: None,
+        };
+        let request = tm11::Taxii11Message::PollFulfillmentRequest(tm11::PollFulfillmentRequest {
+            xmlns: NS_TAXII_11.to_string(),
+            message_id: "fulfillment-1".to_string(),
+            collection_name: COLLECTION_NAME.to_string(),
+            result_id: RESULT_ID.to_string(),
+            result_part_number: Some(part),
+            extended_headers: None,
+        });
+        PollFulfillmentRequest11Handler
+            .handle_11(ctx, &headers, &request)
+            .await
+    }
+
+    #[test]
+    fn test_part_count() {
+        let part_count = |total_count: usize, max_result_size: usize| {
+            result_parts(&vec![1; total_count], max_result_size, usize::MAX).len()
+        };
+        assert_eq!(part_count(0, 2), 1);
+        assert_eq!(part_count(1, 2), 1);
+        assert_eq!(part_count(4, 2), 2);
+        assert_eq!(part_count(5, 2), 3);
+    }
+
+    #[test]
+    fn test_result_parts_by_bytes() {
+        assert_eq!(result_parts(&[], 2, 10), [(0, 0)]);
+        assert_eq!(
+            result_parts(&[4, 4, 4, 4, 4], 2, 10),
+            [(0, 2), (2, 2), (4, 1)]
+        );
+        assert_eq!(
+            result_parts(&[6, 6, 20, 1, 1], 3, 10),
+            [(0, 1), (1, 1), (2, 1), (3, 2)]
+        );
+    }
+
+    /// Walking a three-part result set: every part reports its number and
+    /// whether more follow, and the part past the end is not found.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_fulfillment_walks_parts(pool: PgPool) -> TestResult {
+        let ctx = setup(pool).await?;
+
+        let expected = [
+            (1, vec!["<block n=\"1\"/>", "<block n=\"2\"/>"], true),
+            (2, vec!["<block n=\"3\"/>", "<block n=\"4\"/>"], true),
+            (3, vec!["<block n=\"5\"/>"], false),
+        ];
+        for (part, contents, more) in expected {
+            let tm11::Taxii11Message::PollResponse(response) = fulfill(&ctx, part).await? else {
+                return Err(format!("expected poll response for part {part}").into());
+            };
+            let blocks: Vec<&str> = response
+                .content_blocks
+                .iter()
+                .map(|b| b.content.as_str())
+                .collect();
+            assert_eq!(blocks, contents);
+            assert_eq!(response.result_part_number, Some(part));
+            assert_eq!(response.result_id.as_deref(), Some(RESULT_ID));
+            assert_eq!(response.more, Some(more));
+            let count = response.record_count.ok_or("missing record count")?;
+            assert_eq!(count.record_count, 5);
+        }
+
+        for part in [4, 0] {
+            match fulfill(&ctx, part).await {
+                Err(Taxii1xError::StatusMessage {
+                    status_type,
+                    status_detail,
+                    ..
+                }) => {
+                    assert!(matches!(status_type, StatusType::NotFound));
+                    assert_eq!(status_detail.as_deref(), Some(RESULT_ID));
+                }
+                other => {
+                    return Err(format!("expected not found for part {part}, got {other:?}").into());
+                }
+            }
+        }
+
+        // The parts were recorded on the first fulfillment and are kept
+        assert_eq!(
+            ctx.persistence.get_result_set_parts(RESULT_ID).await?,
+            Some(vec![2, 2, 1])
+        );
+        let tm11::Taxii11Message::PollResponse(response) = fulfill(&ctx, 3).await? else {
+            return Err("expected poll response for part 3".into());
+        };
+        assert_eq!(response.more, Some(false));
+
+        Ok(())
+    }
+
+    /// Parts are split by the size of their content when it fills the
+    /// service's byte limit before its block limit.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_fulfillment_splits_parts_by_bytes(pool: PgPool) -> TestResult {
+        let mut ctx = setup(pool).await?;
+        ctx.service.properties = serde_json::json!({"max_result_size": 5});
+        // Blocks are 14 bytes each, so two fit in a part
+        ctx.limits.max_result_bytes = 30;
+
+        let expected = [(1, 2, true), (2, 2, true), (3, 1, false)];
+        for (part, count, more) in expected {
+            let tm11::Taxii11Message::PollResponse(response) = fulfill(&ctx, part).await? else {
+                return Err(format!("expected poll response for part {part}").into());
+            };
+            assert_eq!(response.content_blocks.len(), count, "part {part}");
+            assert_eq!(response.more, Some(more), "part {part}");
+        }
+        assert!(fulfill(&ctx, 4).await.is_err());
+        Ok(())
+    }
+}
@@ -4,35 +4,261 @@ use std::collections::HashMap;
 
 use crate::constants::{
     ACT_PAUSE, ACT_RESUME, ACT_STATUS, ACT_SUBSCRIBE, ACT_TYPES_10, ACT_TYPES_11, ACT_UNSUBSCRIBE,
-    RT_FULL, SD_SUPPORTED_CONTENT, SVC_POLL, StatusType, VID_TAXII_HTTP_10, VID_TAXII_XML_10,
-    VID_TAXII_XML_11,
+    RT_FULL, ResponseType, SD_SUPPORTED_CONTENT, SVC_POLL, StatusType, VID_TAXII_HTTP_10,
+    VID_TAXII_HTTPS_10, VID_TAXII_XML_10, VID_TAXII_XML_11,
 };
 use crate::error::{Taxii1xError, Taxii1xResult};
-use crate::messages::{tm10, tm11};
+use crate::messages::{PushParameters, tm10, tm11};
+use crate::push::PushPolicy;
 use taxii_db::Taxii1Repository;
 
+use super::advertised::AdvertisedService;
 use super::base::{HandlerContext, TaxiiHeaders, generate_id};
 
 use taxii_core::{
-    ContentBindingEntity, SubscriptionCreatedEvent, SubscriptionEntity, SubscriptionParameters,
-    subscription_status,
+    ContentBindingEntity, DeliveryStatus, PushParametersEntity, SubscriptionCreatedEvent,
+    SubscriptionEntity, SubscriptionParameters, subscription_status,
 };
 
-/// Helper to extract string array from JSON value.
-fn extract_string_array(value: Option<&serde_json::Value>, default: Vec<String>) -> Vec<String> {
-    value
-        .and_then(|v| v.as_array())
-        .map(|arr| {
-            arr.iter()
-                .filter_map(|v| v.as_str().map(String::from))
+/// Convert stored subscription parameters to their TAXII 1.1 message form.
+fn subscription_parameters_11(params: &SubscriptionParameters) -> tm11::SubscriptionParameters {
+    tm11::SubscriptionParameters {
+        response_type: Some(params.response_type.clone()),
+        content_bindings: params
+            .content_bindings
+            .iter()
+            .map(|cb| tm11::ContentBinding::with_subtypes(&cb.binding, cb.subtypes.clone()))
+            .collect(),
+    }
+}
+
+/// Validate the push parameters of a subscription request.
+///
+/// New content is pushed over HTTP or HTTPS, to an absolute address with
+/// the scheme of the protocol binding, in the message binding of the
+/// request's TAXII version. Push must be enabled, and the address allowed
+/// by the push policy; see [`PushPolicy::check_address`].
+pub(super) async fn validate_push_parameters(
+    policy: &PushPolicy,
+    protocol_binding: &str,
+    address: &str,
+    message_binding: &str,
+    supported_message_binding: &str,
+    message_id: &str,
+) -> Taxii1xResult<PushParametersEntity> {
+    if !policy.enabled {
+        return Err(Taxii1xError::StatusMessage {
+            message: "Push delivery is not enabled".to_string(),
+            in_response_to: Some(message_id.to_string()),
+            status_type: StatusType::UnsupportedProtocol,
+            status_detail: None,
+        });
+    }
+    let scheme = match protocol_binding {
+        VID_TAXII_HTTP_10 => "http://",
+        VID_TAXII_HTTPS_10 => "https://",
+        _ => {
+            return Err(Taxii1xError::StatusMessage {
+                message: format!("Push protocol binding {protocol_binding} is not supported"),
+                in_response_to: Some(message_id.to_string()),
+                status_type: StatusType::UnsupportedProtocol,
+                status_detail: None,
+            });
+        }
+    };
+    if message_binding != supported_message_binding {
+        return Err(Taxii1xError::StatusMessage {
+            message: format!("Push message binding {message_binding} is not supported"),
+            in_response_to: Some(message_id.to_string()),
+            status_type: StatusType::UnsupportedMessageBinding,
+            status_detail: None,
+        });
+    }
+    if !address.starts_with(scheme) {
+        return Err(Taxii1xError::StatusMessage {
+            message: format!("Push address must be an absolute {scheme} URL"),
+            in_response_to: Some(message_id.to_string()),
+            status_type: StatusType::BadMessage,
+            status_detail: None,
+        });
+    }
+    if let Err(reason) = policy.check_address(address).await {
+        return Err(Taxii1xError::StatusMessage {
+            message: format!("Push address is not allowed: {reason}"),
+            in_response_to: Some(message_id.to_string()),
+            status_type: StatusType::BadMessage,
+            status_detail: None,
+        });
+    }
+
+    Ok(PushParametersEntity {
+        protocol_binding: protocol_binding.to_string(),
+        address: address.to_string(),
+        message_binding: message_binding.to_string(),
+    })
+}
+
+/// Convert stored push parameters to their message form.
+fn push_parameters_message(push: &PushParametersEntity) -> PushParameters {
+    PushParameters {
+        protocol_binding: push.protocol_binding.clone(),
+        address: push.address.clone(),
+        message_binding: push.message_binding.clone(),
+    }
+}
+
+/// Build the TAXII 1.1 subscription instance of a stored subscription.
+fn subscription_instance_11(
+    sub: SubscriptionEntity,
+    poll_instances: Vec<tm11::PollInstance>,
+) -> tm11::SubscriptionInstance {
+    tm11::SubscriptionInstance {
+        subscription_parameters: sub.params.as_ref().map(subscription_parameters_11),
+        push_parameters: sub.push.as_ref().map(push_parameters_message),
+        subscription_id: sub.subscription_id.unwrap_or_default(),
+        status: Some(sub.status),
+        poll_instances,
+    }
+}
+
+/// Build the TAXII 1.0 subscription instance of a stored subscription.
+///
+/// TAXII 1.0 instances carry no status, and the content bindings of a
+/// subscription are part of its delivery parameters.
+fn subscription_instance_10(
+    sub: SubscriptionEntity,
+    poll_instances: Vec<tm10::PollInstance>,
+) -> tm10::SubscriptionInstance {
+    let content_bindings: Vec<String> = sub
+        .params
+        .map(|p| {
+            p.content_bindings
+                .into_iter()
+                .map(|cb| cb.binding)
                 .collect()
         })
-        .unwrap_or(default)
+        .unwrap_or_default();
+    tm10::SubscriptionInstance {
+        subscription_id: sub.subscription_id.unwrap_or_default(),
+        delivery_parameters: sub.push.map(|push| tm10::DeliveryParameters {
+            inbox_protocol: push.protocol_binding,
+            inbox_address: push.address,
+            delivery_message_binding: push.message_binding,
+            content_bindings,
+        }),
+        poll_instances,
+    }
+}
+
+/// Status a subscription moves to on a PAUSE, RESUME or UNSUBSCRIBE action.
+///
+/// Returns `None` when the action is not allowed from the current status:
+/// an unsubscribed subscription cannot be paused or resumed.
+fn next_status(current: &str, action: &str) -> Option<&'static str> {
+    match (action, current) {
+        (ACT_UNSUBSCRIBE, _) => Some(subscription_status::UNSUBSCRIBED),
+        (_, subscription_status::UNSUBSCRIBED) => None,
+        (ACT_PAUSE, _) => Some(subscription_status::PAUSED),
+        (ACT_RESUME, _) => Some(subscription_status::ACTIVE),
+        _ => None,
+    }
+}
+
+/// Get the subscription id an action requires.
+fn require_subscription_id<'a>(
+    subscription_id: Option<&'a String>,
+    action: &str,
+    message_id: &str,
+) -> Taxii1xResult<&'a String> {
+    subscription_id.ok_or_else(|| Taxii1xError::StatusMessage {
+        message: format!("Action \"{action}\" requires a subscription id"),
+        in_response_to: Some(message_id.to_string()),
+        status_type: StatusType::BadMessage,
+        status_detail: None,
+    })
+}
+
+fn subscription_not_found(subscription_id: &str, message_id: &str) -> Taxii1xError {
+    Taxii1xError::StatusMessage {
+        message: "Subscription not found".to_string(),
+        in_response_to: Some(message_id.to_string()),
+        status_type: StatusType::NotFound,
+        status_detail: Some(subscription_id.to_string()),
+    }
+}
+
+/// Find a subscription of the requesting client to the requested
+/// collection.
+///
+/// A subscription of another collection is reported as not found, and one
+/// of another client is not found.
+async fn find_subscription<R: Taxii1Repository>(
+    ctx: &HandlerContext<R>,
+    subscription_id: &str,
+    collection_id: i32,
+    message_id: &str,
+) -> Taxii1xResult<Option<SubscriptionEntity>> {
+    let Some(sub) = ctx
+        .persistence
+        .get_subscription(subscription_id)
+        .await?
+        .filter(|sub| ctx.owns(sub))
+    else {
+        return Ok(None);
+    };
+
+    if sub.collection_id != collection_id {
+        return Err(Taxii1xError::StatusMessage {
+            message: "Subscription does not belong to requested collection".to_string(),
+            in_response_to: Some(message_id.to_string()),
+            status_type: StatusType::NotFound,
+            status_detail: Some(subscription_id.to_string()),
+        });
+    }
+    Ok(Some(sub))
+}
+
+/// Get the subscriptions of the requesting client to a collection through
+/// the service.
+async fn collection_subscriptions<R: Taxii1Repository>(
+    ctx: &HandlerContext<R>,
+    collection_id: i32,
+) -> Taxii1xResult<Vec<SubscriptionEntity>> {
+    let subscriptions = ctx.persistence.get_subscriptions(&ctx.service.id).await?;
+    Ok(subscriptions
+        .into_iter()
+        .filter(|sub| sub.collection_id == collection_id && ctx.owns(sub))
+        .collect())
+}
+
+/// Apply a PAUSE, RESUME or UNSUBSCRIBE action to a subscription, storing
+/// its new status.
+async fn change_status<R: Taxii1Repository>(
+    ctx: &HandlerContext<R>,
+    mut sub: SubscriptionEntity,
+    action: &str,
+    message_id: &str,
+) -> Taxii1xResult<SubscriptionEntity> {
+    let status = next_status(&sub.status, action).ok_or_else(|| Taxii1xError::StatusMessage {
+        message: format!(
+            "Action \"{action}\" is not allowed on a subscription that is {}",
+            sub.status
+        ),
+        in_response_to: Some(message_id.to_string()),
+        status_type: StatusType::Failure,
+        status_detail: sub.subscription_id.clone(),
+    })?;
+
+    if sub.status == status {
+        return Ok(sub);
+    }
+    sub.status = status.to_string();
+    Ok(ctx.persistence.update_subscription(&sub).await?)
 }
 
 /// Get poll instances for a collection (TAXII 1.1).
-async fn get_poll_instances_11(
-    ctx: &HandlerContext,
+async fn get_poll_instances_11<R: Taxii1Repository>(
+    ctx: &HandlerContext<R>,
     collection_id: i32,
 ) -> Vec<tm11::PollInstance> {
     let polling_services = ctx
@@ -44,36 +270,19 @@ async fn get_poll_instances_11(
     polling_services
         .iter()
         .map(|svc| {
-            let service_id = svc.id.as_deref().unwrap_or_default();
-            let address = svc
-                .properties
-                .get("address")
-                .and_then(|v| v.as_str())
-                .map(String::from)
-                .unwrap_or_else(|| format!("/services/{service_id}/"));
-            let protocol = svc
-                .properties
-                .get("protocol_binding")
-                .and_then(|v| v.as_str())
-                .map(String::from)
-                .unwrap_or_else(|| VID_TAXII_HTTP_10.to_string());
-            let message_bindings = extract_string_array(
-                svc.properties.get("message_bindings"),
-                vec![VID_TAXII_XML_11.to_string()],
-            );
-
+            let svc = AdvertisedService::new(ctx, svc, VID_TAXII_XML_11);
             tm11::PollInstance {
-                poll_protocol: protocol,
-                poll_address: address,
-                poll_message_bindings: message_bindings,
+                poll_protocol: svc.protocol_binding,
+                poll_address: svc.address,
+                poll_message_bindings: svc.message_bindings,
             }
         })
         .collect()
 }
 
 /// Get poll instances for a collection (TAXII 1.0).
-async fn get_poll_instances_10(
-    ctx: &HandlerContext,
+async fn get_poll_instances_10<R: Taxii1Repository>(
+    ctx: &HandlerContext<R>,
     collection_id: i32,
 ) -> Vec<tm10::PollInstance> {
     let polling_services = ctx
@@ -85,28 +294,11 @@ async fn get_poll_instances_10(
     polling_services
         .iter()
         .map(|svc| {
-            let service_id = svc.id.as_deref().unwrap_or_default();
-            let address = svc
-                .properties
-                .get("address")
-                .and_then(|v| v.as_str())
-                .map(String::from)
-                .unwrap_or_else(|| format!("/services/{service_id}/"));
-            let protocol = svc
-                .properties
-                .get("protocol_binding")
-                .and_then(|v| v.as_str())
-                .map(String::from)
-                .unwrap_or_else(|| VID_TAXII_HTTP_10.to_string());
-            let message_bindings = extract_string_array(
-                svc.properties.get("message_bindings"),
-                vec![VID_TAXII_XML_10.to_string()],
-            );
-
+            let svc = AdvertisedService::new(ctx, svc, VID_TAXII_XML_10);
             tm10::PollInstance {
-                poll_protocol: protocol,
-                poll_address: address,
-                poll_message_bindings: message_bindings,
+                poll_protocol: svc.protocol_binding,
+                poll_address: svc.address,
+                poll_message_bindings: svc.message_bindings,
             }
         })
         .collect()
@@ -123,9 +315,9 @@ impl SubscriptionRequest11Handler {
         clippy::excessive_nesting,
         reason = "TAXII protocol requires nested action handling"
     )]
-    pub async fn handle_11(
+    pub async fn handle_11<R: Taxii1Repository>(
         &self,
-        ctx: &HandlerContext,
+        ctx: &HandlerContext<R>,
         _headers: &TaxiiHeaders,
         message: &tm11::Taxii11Message,
     ) -> Taxii1xResult<tm11::Taxii11Message> {
@@ -196,6 +388,14 @@ impl SubscriptionRequest11Handler {
                         .response_type
                         .clone()
                         .unwrap_or_else(|| RT_FULL.to_string());
+                    if response_type.parse::<ResponseType>().is_err() {
+                        return Err(Taxii1xError::StatusMessage {
+                            message: format!("Invalid response type: {response_type}"),
+                            in_response_to: Some(request.message_id.clone()),
+                            status_type: StatusType::BadMessage,
+                            status_detail: Some(response_type),
+                        });
+                    }
 
                     let supported_contents = if p.content_bindings.is_empty() {
                         Vec::new()
@@ -249,12 +449,30 @@ impl SubscriptionRequest11Handler {
                     None
                 };
 
+                let push = match request.push_parameters.as_ref() {
+                    Some(p) => Some(
+                        validate_push_parameters(
+                            &ctx.push,
+                            &p.protocol_binding,
+                            &p.address,
+                            &p.message_binding,
+                            VID_TAXII_XML_11,
+                            &request.message_id,
+                        )
+                        .await?,
+                    ),
+                    None => None,
+                };
+
                 let subscription = SubscriptionEntity {
                     service_id: ctx.service.id.clone(),
                     collection_id,
                     subscription_id: Some(generate_id()),
                     params,
                     status: subscription_status::ACTIVE.to_string(),
+                    delivery: DeliveryStatus::default(),
+                    push,
+                    account_id: ctx.account_id(),
                 };
 
                 let subscription = ctx.persistence.create_subscription(&subscription).await?;
@@ -268,153 +486,42 @@ impl SubscriptionRequest11Handler {
                     hooks.emit_subscription_created(event);
                 }
 
-                let instance = tm11::SubscriptionInstance {
-                    subscription_id: subscription.subscription_id.unwrap_or_default(),
-                    status: Some(subscription.status),
-                    subscription_parameters: request.subscription_parameters.clone(),
-                    push_parameters: request.push_parameters.clone(),
-                    poll_instances,
-                };
-                response.subscription_instances.push(instance);
+                response
+                    .subscription_instances
+                    .push(subscription_instance_11(subscription, poll_instances));
             }
 
-            ACT_UNSUBSCRIBE => {
-                let subscription_id = request.subscription_id.as_ref().ok_or_else(|| {
-                    Taxii1xError::StatusMessage {
-                        message: format!("Action \"{action}\" requires a subscription id"),
-                        in_response_to: Some(request.message_id.clone()),
-                        status_type: StatusType::BadMessage,
-                        status_detail: None,
-                    }
-                })?;
-
-                let subscription = ctx.persistence.get_subscription(subscription_id).await?;
+            ACT_UNSUBSCRIBE | ACT_PAUSE | ACT_RESUME => {
+                let subscription_id = require_subscription_id(
+                    request.subscription_id.as_ref(),
+                    action,
+                    &request.message_id,
+                )?;
 
-                if let Some(mut sub) = subscription {
-                    // Validate subscription belongs to requested collection
-                    if sub.collection_id != collection_id {
-                        return Err(Taxii1xError::StatusMessage {
-                            message: "Subscription does not belong to requested collection"
-                                .to_string(),
-                            in_response_to: Some(request.message_id.clone()),
-                            status_type: StatusType::NotFound,
-                            status_detail: Some(collection_name.clone()),
-                        });
+                let instance = match find_subscription(
+                    ctx,
+                    subscription_id,
+                    collection_id,
+                    &request.message_id,
+                )
+                .await?
+                {
+                    Some(sub) => {
+                        let sub = change_status(ctx, sub, action, &request.message_id).await?;
+                        subscription_instance_11(sub, poll_instances)
                     }
-
-                    sub.status = subscription_status::UNSUBSCRIBED.to_string();
-                    ctx.persistence.update_subscription(&sub).await?;
-
-                    let instance = tm11::SubscriptionInstance {
-                        subscription_id: sub.subscription_id.unwrap_or_default(),
-                        status: Some(sub.status),
-                        subscription_parameters: None,
-                        push_parameters: None,
-                        poll_instances,
-                    };
-                    response.subscription_instances.push(instance);
-                } else {
-                    // Spec says unsubscribe should be successful even if subscription doesn't exist
-                    // Return a synthetic unsubscribed instance
-                    let instance = tm11::SubscriptionInstance {
+                    // Spec says unsubscribe should be successful even if
+                    // subscription doesn't exist
+                    None if action == ACT_UNSUBSCRIBE => tm11::SubscriptionInstance {
                         subscription_id: subscription_id.clone(),
                         status: Some(subscription_status::UNSUBSCRIBED.to_string()),
                         subscription_parameters: None,
                         push_parameters: None,
                         poll_instances,
-                    };
-                    response.subscription_instances.push(instance);
-                }
-            }
-
-            ACT_PAUSE => {
-                let subscription_id = request.subscription_id.as_ref().ok_or_else(|| {
-                    Taxii1xError::StatusMessage {
-                        message: format!("Action \"{action}\" requires a subscription id"),
-                        in_response_to: Some(request.message_id.clone()),
-                        status_type: StatusType::BadMessage,
-                        status_detail: None,
-                    }
-                })?;
-
-                let subscription = ctx.persistence.get_subscription(subscription_id).await?;
-
-                // Raise ST_NOT_FOUND if subscription not found for PAUSE
-                let mut sub = subscription.ok_or_else(|| Taxii1xError::StatusMessage {
-                    message: "Subscription not found".to_string(),
-                    in_response_to: Some(request.message_id.clone()),
-                    status_type: StatusType::NotFound,
-                    status_detail: Some(subscription_id.clone()),
-                })?;
-
-                // Validate subscription belongs to requested collection
-                if sub.collection_id != collection_id {
-                    return Err(Taxii1xError::StatusMessage {
-                        message: "Subscription does not belong to requested collection".to_string(),
-                        in_response_to: Some(request.message_id.clone()),
-                        status_type: StatusType::NotFound,
-                        status_detail: Some(collection_name.clone()),
-                    });
-                }
-
-                // If already paused, just return it without update
-                if sub.status != subscription_status::PAUSED {
-                    sub.status = subscription_status::PAUSED.to_string();
-                    ctx.persistence.update_subscription(&sub).await?;
-                }
-
-                let instance = tm11::SubscriptionInstance {
-                    subscription_id: sub.subscription_id.unwrap_or_default(),
-                    status: Some(sub.status),
-                    subscription_parameters: None,
-                    push_parameters: None,
-                    poll_instances,
-                };
-                response.subscription_instances.push(instance);
-            }
-
-            ACT_RESUME => {
-                let subscription_id = request.subscription_id.as_ref().ok_or_else(|| {
-                    Taxii1xError::StatusMessage {
-                        message: format!("Action \"{action}\" requires a subscription id"),
-                        in_response_to: Some(request.message_id.clone()),
-                        status_type: StatusType::BadMessage,
-                        status_detail: None,
+                    },
+                    None => {
+                        return Err(subscription_not_found(subscription_id, &request.message_id));
                     }
-                })?;
-
-                let subscription = ctx.persistence.get_subscription(subscription_id).await?;
-
-                // Raise ST_NOT_FOUND if subscription not found for RESUME
-                let mut sub = subscription.ok_or_else(|| Taxii1xError::StatusMessage {
-                    message: "Subscription not found".to_string(),
-                    in_response_to: Some(request.message_id.clone()),
-                    status_type: StatusType::NotFound,
-                    status_detail: Some(subscription_id.clone()),
-                })?;
-
-                // Validate subscription belongs to requested collection
-                if sub.collection_id != collection_id {
-                    return Err(Taxii1xError::StatusMessage {
-                        message: "Subscription does not belong to requested collection".to_string(),
-                        in_response_to: Some(request.message_id.clone()),
-                        status_type: StatusType::NotFound,
-                        status_detail: Some(collection_name.clone()),
-                    });
-                }
-
-                // Only resume if currently in PAUSED state
-                if sub.status == subscription_status::PAUSED {
-                    sub.status = subscription_status::ACTIVE.to_string();
-                    ctx.persistence.update_subscription(&sub).await?;
-                }
-
-                let instance = tm11::SubscriptionInstance {
-                    subscription_id: sub.subscription_id.unwrap_or_default(),
-                    status: Some(sub.status),
-                    subscription_parameters: None,
-                    push_parameters: None,
-                    poll_instances,
                 };
                 response.subscription_instances.push(instance);
             }
@@ -422,54 +529,20 @@ impl SubscriptionRequest11Handler {
             ACT_STATUS => {
                 // Return status for all subscriptions or specific one
                 if let Some(subscription_id) = &request.subscription_id {
-                    let subscription = ctx.persistence.get_subscription(subscription_id).await?;
-
-                    match subscription {
-                        Some(sub) => {
-                            // Validate subscription belongs to requested collection
-                            if sub.collection_id != collection_id {
-                                return Err(Taxii1xError::StatusMessage {
-                                    message: "Subscription does not belong to requested collection"
-                                        .to_string(),
-                                    in_response_to: Some(request.message_id.clone()),
-                                    status_type: StatusType::NotFound,
-                                    status_detail: Some(subscription_id.clone()),
-                                });
-                            }
-
-                            let instance = tm11::SubscriptionInstance {
-                                subscription_id: sub.subscription_id.unwrap_or_default(),
-                                status: Some(sub.status),
-                                subscription_parameters: None,
-                                push_parameters: None,
-                                poll_instances,
-                            };
-                            response.subscription_instances.push(instance);
-                        }
-                        None => {
-                            // Raise ST_NOT_FOUND when subscription_id provided but not found
-                            return Err(Taxii1xError::StatusMessage {
-                                message: "Subscription not found".to_string(),
-                                in_response_to: Some(request.message_id.clone()),
-                                status_type: StatusType::NotFound,
-                                status_detail: Some(subscription_id.clone()),
-                            });
-                        }
-                    }
+                    let sub =
+                        find_subscription(ctx, subscription_id, collection_id, &request.message_id)
+                            .await?
+                            .ok_or_else(|| {
+                                subscription_not_found(subscription_id, &request.message_id)
+                            })?;
+                    response
+                        .subscription_instances
+                        .push(subscription_instance_11(sub, poll_instances));
                 } else {
-                    // Return all subscriptions for this service
-                    let subscriptions = ctx.persistence.get_subscriptions(&ctx.service.id).await?;
-
-                    response.subscription_instances = subscriptions
+                    response.subscription_instances = collection_subscriptions(ctx, collection_id)
+                        .await?
                         .into_iter()
-                        .filter(|sub| sub.collection_id == collection_id)
-                        .map(|sub| tm11::SubscriptionInstance {
-                            subscription_id: sub.subscription_id.unwrap_or_default(),
-                            status: Some(sub.status),
-                            subscription_parameters: None,
-                            push_parameters: None,
-                            poll_instances: poll_instances.clone(),
-                        })
+                        .map(|sub| subscription_instance_11(sub, poll_instances.clone()))
                         .collect();
                 }
             }
@@ -493,9 +566,9 @@ pub struct SubscriptionRequest10Handler;
 
 impl SubscriptionRequest10Handler {
     /// Handle a TAXII 1.0 Subscription Management Request.
-    pub async fn handle_10(
+    pub async fn handle_10<R: Taxii1Repository>(
         &self,
-        ctx: &HandlerContext,
+        ctx: &HandlerContext<R>,
         _headers: &TaxiiHeaders,
         message: &tm10::Taxii10Message,
     ) -> Taxii1xResult<tm10::Taxii10Message> {
@@ -560,12 +633,43 @@ impl SubscriptionRequest10Handler {
 
         match action {
             ACT_SUBSCRIBE => {
+                // Content bindings of TAXII 1.0 subscriptions are part of
+                // the delivery parameters
+                let delivery_parameters = request.delivery_parameters.as_ref();
+                let push = match delivery_parameters {
+                    Some(p) => Some(
+                        validate_push_parameters(
+                            &ctx.push,
+                            &p.inbox_protocol,
+                            &p.inbox_address,
+                            &p.delivery_message_binding,
+                            VID_TAXII_XML_10,
+                            &request.message_id,
+                        )
+                        .await?,
+                    ),
+                    None => None,
+                };
+                let params = delivery_parameters
+                    .filter(|p| !p.content_bindings.is_empty())
+                    .map(|p| SubscriptionParameters {
+                        response_type: RT_FULL.to_string(),
+                        content_bindings: p
+                            .content_bindings
+                            .iter()
+                            .map(ContentBindingEntity::new)
+                            .collect(),
+                    });
+
                 let subscription = SubscriptionEntity {
                     service_id: ctx.service.id.clone(),
                     collection_id,
                     subscription_id: Some(generate_id()),
-                    params: None,
+                    params,
                     status: subscription_status::ACTIVE.to_string(),
+                    delivery: DeliveryStatus::default(),
+                    push,
+                    account_id: ctx.account_id(),
                 };
 
                 let subscription = ctx.persistence.create_subscription(&subscription).await?;
@@ -579,53 +683,55 @@ impl SubscriptionRequest10Handler {
                     hooks.emit_subscription_created(event);
                 }
 
-                let instance = tm10::SubscriptionInstance {
-                    subscription_id: subscription.subscription_id.unwrap_or_default(),
-                    delivery_parameters: request.delivery_parameters.clone(),
-                    poll_instances,
-                };
-                response.subscription_instances.push(instance);
+                response
+                    .subscription_instances
+                    .push(subscription_instance_10(subscription, poll_instances));
             }
 
             ACT_UNSUBSCRIBE => {
-                let subscription_id = request.subscription_id.as_ref().ok_or_else(|| {
-                    Taxii1xError::failure(
-                        "Subscription ID required for unsubscribe",
-                        Some(request.message_id.clone()),
-                    )
-                })?;
-
-                let subscription = ctx.persistence.get_subscription(subscription_id).await?;
-
-                if let Some(mut sub) = subscription {
-                    sub.status = subscription_status::UNSUBSCRIBED.to_string();
-                    ctx.persistence.update_subscription(&sub).await?;
-                }
+                let subscription_id = require_subscription_id(
+                    request.subscription_id.as_ref(),
+                    action,
+                    &request.message_id,
+                )?;
+
+                let instance = match find_subscription(
+                    ctx,
+                    subscription_id,
+                    collection_id,
+                    &request.message_id,
+                )
+                .await?
+                {
+                    Some(sub) => {
+                        let sub = change_status(ctx, sub, action, &request.message_id).await?;
+                        subscription_instance_10(sub, poll_instances)
+                    }
+                    None => tm10::SubscriptionInstance {
+                        subscription_id: subscription_id.clone(),
+                        delivery_parameters: None,
+                        poll_instances,
+                    },
+                };
+                response.subscription_instances.push(instance);
             }
 
             ACT_STATUS => {
                 if let Some(subscription_id) = &request.subscription_id {
-                    let subscription = ctx.persistence.get_subscription(subscription_id).await?;
-
-                    if let Some(sub) = subscription {
-                        let instance = tm10::SubscriptionInstance {
-                            subscription_id: sub.subscription_id.unwrap_or_default(),
-                            delivery_parameters: None,
-                            poll_instances,
-                        };
-                        response.subscription_instances.push(instance);
-                    }
+                    let sub =
+                        find_subscription(ctx, subscription_id, collection_id, &request.message_id)
+                            .await?
+                            .ok_or_else(|| {
+                                subscription_not_found(subscription_id, &request.message_id)
+                            })?;
+                    response
+                        .subscription_instances
+                        .push(subscription_instance_10(sub, poll_instances));
                 } else {
-                    let subscriptions = ctx.persistence.get_subscriptions(&ctx.service.id).await?;
-
-                    response.subscription_instances = subscriptions
+                    response.subscription_instances = collection_subscriptions(ctx, collection_id)
+                        .await?
                         .into_iter()
-                        .filter(|sub| sub.collection_id == collection_id)
-                        .map(|sub| tm10::SubscriptionInstance {
-                            subscription_id: sub.subscription_id.unwrap_or_default(),
-                            delivery_parameters: None,
-                            poll_instances: poll_instances.clone(),
-                        })
+                        .map(|sub| subscription_instance_10(sub, poll_instances.clone()))
                         .collect();
                 }
             }
@@ -643,3 +749,485 @@ impl SubscriptionRequest10Handler {
         ))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VID_TAXII_SERVICES_11;
+    use crate::handlers::ServiceInfo;
+    use crate::handlers::poll::PollRequest11Handler;
+    use crate::messages::{NS_TAXII_10, NS_TAXII_11};
+    use sqlx::PgPool;
+    use std::sync::Arc;
+    use taxii_core::{CollectionEntity, ServiceEntity};
+    use taxii_db::{DbTaxii1Repository, TaxiiPool};
+
+    type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+    const SERVICE_ID: &str = "collection-management-a";
+    const COLLECTION_NAME: &str = "collection-a";
+    const INBOX_ADDRESS: &str = "https://client.example.com/inbox/";
+
+    async fn setup(pool: PgPool) -> Result<HandlerContext, Box<dyn std::error::Error>> {
+        let persistence = Arc::new(DbTaxii1Repository::new(TaxiiPool::new(pool)));
+        persistence
+            .update_service(&ServiceEntity {
+                id: Some(SERVICE_ID.to_string()),
+                service_type: "COLLECTION_MANAGEMENT".to_string(),
+                properties: serde_json::json!({}),
+            })
+            .await?;
+        let collection = persistence
+            .create_collection(&CollectionEntity::new(COLLECTION_NAME))
+            .await?;
+        let collection_id = collection.id.ok_or("collection has no id")?;
+        persistence
+            .set_collection_services(collection_id, &[SERVICE_ID.to_string()])
+            .await?;
+
+        Ok(HandlerContext {
+            account: None,
+            persistence,
+            service: ServiceInfo {
+                id: SERVICE_ID.to_string(),
+                service_type: "COLLECTION_MANAGEMENT".to_string(),
+                address: "/services/collection-management-a/".to_string(),
+                description: None,
+                protocol_bindings: Vec::new(),
+                message_bindings: vec![VID_TAXII_XML_11.to_string()],
+                available: true,
+                authentication_required: false,
+                properties: serde_json::json!({}),
+            },
+            domain: None,
+            limits: Default::default(),
+            // Push addresses are not resolved when private ones are allowed
+            push: PushPolicy {
+                enabled: true,
+                allow_private_addresses: true,
+            },
+            hooks: None,
+        })
+    }
+
+    fn headers() -> TaxiiHeaders {
+        TaxiiHeaders {
+            content_type: VID_TAXII_XML_11.to_string(),
+            services: VID_TAXII_SERVICES_11.to_string(),
+            accept: None,
+            protocol: None,
+        }
+    }
+
+    /// Send a TAXII 1.1 subscription management request.
+    async fn manage_11(
+        ctx: &HandlerContext,
+        action: &str,
+        subscription_id: Option<&str>,
+    ) -> Taxii1xResult<Vec<tm11::SubscriptionInstance>> {
+        let subscribe = action == ACT_SUBSCRIBE;
+        let request = tm11::Taxii11Message::ManageCollectionSubscriptionRequest(
+            tm11::ManageCollectionSubscriptionRequest {
+                xmlns: NS_TAXII_11.to_string(),
+                message_id: format!("{action}-1"),
+                action: action.to_string(),
+                collection_name: COLLECTION_NAME.to_string(),
+                extended_headers: None,
+                subscription_id: subscription_id.map(String::from),
+                subscription_parameters: subscribe.then(|| tm11::SubscriptionParameters {
+                    response_type: Some(RT_FULL.to_string()),
+                    content_bindings: Vec::new(),
+                }),
+                push_parameters: subscribe.then(|| PushParameters {
+                    protocol_binding: VID_TAXII_HTTPS_10.to_string(),
+                    address: INBOX_ADDRESS.to_string(),
+                    message_binding: VID_TAXII_XML_11.to_string(),
+                }),
+            },
+        );
+        match SubscriptionRequest11Handler
+            .handle_11(ctx, &headers(), &request)
+            .await?
+        {
+            tm11::Taxii11Message::ManageCollectionSubscriptionResponse(response) => {
+                Ok(response.subscription_instances)
+            }
+            other => Err(Taxii1xError::failure(
+                format!("expected subscription response, got {other:?}"),
+                None,
+            )),
+        }
+    }
+
+    /// Send a TAXII 1.0 subscription management request.
+    async fn manage_10(
+        ctx: &HandlerContext,
+        action: &str,
+        subscription_id: Option<&str>,
+    ) -> Taxii1xResult<Vec<tm10::SubscriptionInstance>> {
+        let request = tm10::Taxii10Message::ManageFeedSubscriptionRequest(
+            tm10::ManageFeedSubscriptionRequest {
+                xmlns: NS_TAXII_10.to_string(),
+                message_id: format!("{action}-1"),
+                action: action.to_string(),
+                feed_name: COLLECTION_NAME.to_string(),
+                extended_headers: None,
+                subscription_id: subscription_id.map(String::from),
+                delivery_parameters: (action == ACT_SUBSCRIBE).then(|| tm10::DeliveryParameters {
+                    inbox_protocol: VID_TAXII_HTTPS_10.to_string(),
+                    inbox_address: INBOX_ADDRESS.to_string(),
+                    delivery_message_binding: VID_TAXII_XML_10.to_string(),
+                    content_bindings: vec!["urn:stix.mitre.org:xml:1.1.1".to_string()],
+                }),
+            },
+        );
+        match SubscriptionRequest10Handler
+            .handle_10(ctx, &headers(), &request)
+            .await?
+        {
+            tm10::Taxii10Message::ManageFeedSubscriptionResponse(response) => {
+                Ok(response.subscription_instances)
+            }
+            other => Err(Taxii1xError::failure(
+                format!("expected subscription response, got {other:?}"),
+                None,
+            )),
+        }
+    }
+
+    /// Poll the collection by subscription.
+    async fn poll(ctx: &HandlerContext, subscription_id: &str) -> Taxii1xResult<()> {
+        let request = tm11::Taxii11Message::PollRequest(tm11::PollRequest {
+            xmlns: NS_TAXII_11.to_string(),
+            message_id: "poll-1".to_string(),
+            collection_name: COLLECTION_NAME.to_string(),
+            extended_headers: None,
+            exclusive_begin_timestamp_label: None,
+            inclusive_end_timestamp_label: None,
+            subscription_id: Some(subscription_id.to_string()),
+            poll_parameters: None,
+        });
+        PollRequest11Handler
+            .handle_11(ctx, &headers(), &request)
+            .await
+            .map(|_| ())
+    }
+
+    /// Apply an action and check the one returned instance and the stored
+    /// subscription.
+    async fn assert_action_11(
+        ctx: &HandlerContext,
+        action: &str,
+        subscription_id: &str,
+        status: &str,
+    ) -> TestResult {
+        let instances = manage_11(ctx, action, Some(subscription_id)).await?;
+        assert_eq!(instances.len(), 1);
+        let instance = &instances[0];
+        assert_eq!(instance.subscription_id, subscription_id);
+        assert_eq!(instance.status.as_deref(), Some(status));
+        let push = instance
+            .push_parameters
+            .as_ref()
+            .ok_or("no push parameters")?;
+        assert_eq!(push.address, INBOX_ADDRESS);
+        let params = instance
+            .subscription_parameters
+            .as_ref()
+            .ok_or("no subscription parameters")?;
+        assert_eq!(params.response_type.as_deref(), Some(RT_FULL));
+
+        let stored = ctx
+            .persistence
+            .get_subscription(subscription_id)
+            .await?
+            .ok_or("subscription not stored")?;
+        assert_eq!(stored.status, status);
+        Ok(())
+    }
+
+    /// Context of a client authenticated as a new account.
+    async fn as_client(
+        ctx: &HandlerContext,
+        pool: &TaxiiPool,
+        username: &str,
+    ) -> Result<HandlerContext, Box<dyn std::error::Error>> {
+        let account = taxii_db::Account::create(pool, username, "hash", false).await?;
+        Ok(HandlerContext {
+            account: Some(taxii_core::Account {
+                id: account.id,
+                username: account.username,
+                is_admin: false,
+                permissions: Default::default(),
+                details: Default::default(),
+                scope: None,
+                identity_id: None,
+            }),
+            ..ctx.clone()
+        })
+    }
+
+    fn status_type<T>(result: Taxii1xResult<T>) -> Option<StatusType> {
+        match result {
+            Err(Taxii1xError::StatusMessage { status_type, .. }) => Some(status_type),
+            _ => None,
+        }
+    }
+
+    /// Push must be enabled, and push addresses must be public unless
+    /// private ones are allowed.
+    #[tokio::test]
+    async fn test_push_addresses_refused() -> TestResult {
+        let validate = |policy: PushPolicy, address: &'static str| async move {
+            status_type(
+                validate_push_parameters(
+                    &policy,
+                    VID_TAXII_HTTP_10,
+                    address,
+                    VID_TAXII_XML_11,
+                    VID_TAXII_XML_11,
+                    "1",
+                )
+                .await,
+            )
+        };
+        let public_only = PushPolicy {
+            enabled: true,
+            allow_private_addresses: false,
+        };
+
+        assert_eq!(
+            validate(PushPolicy::default(), "http://192.0.2.1/inbox/").await,
+            Some(StatusType::UnsupportedProtocol)
+        );
+        for address in [
+            "http://127.0.0.1/inbox/",
+            "http://10.0.0.1/inbox/",
+            "http://192.168.1.10:8080/inbox/",
+            "http://169.254.169.254/latest/meta-data/",
+            "http://[::1]/inbox/",
+            "http://[::ffff:127.0.0.1]/inbox/",
+            "http://[fd00:ec2::254]/inbox/",
+            "http://0.0.0.0/inbox/",
+            "http://localhost/inbox/",
+        ] {
+            assert_eq!(
+                validate(public_only, address).await,
+                Some(StatusType::BadMessage),
+                "{address}"
+            );
+        }
+        assert_eq!(validate(public_only, "http://8.8.8.8/inbox/").await, None);
+
+        let private = PushPolicy {
+            enabled: true,
+            allow_private_addresses: true,
+        };
+        assert_eq!(validate(private, "http://127.0.0.1/inbox/").await, None);
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_subscription_lifecycle(pool: PgPool) -> TestResult {
+        let ctx = setup(pool).await?;
+
+        let instances = manage_11(&ctx, ACT_SUBSCRIBE, None).await?;
+        let subscription_id = instances
+            .first()
+            .map(|i| i.subscription_id.clone())
+            .ok_or("no subscription instance")?;
+        assert_action_11(
+            &ctx,
+            ACT_STATUS,
+            &subscription_id,
+            subscription_status::ACTIVE,
+        )
+        .await?;
+        poll(&ctx, &subscription_id).await?;
+
+        assert_action_11(
+            &ctx,
+            ACT_PAUSE,
+            &subscription_id,
+            subscription_status::PAUSED,
+        )
+        .await?;
+        assert_eq!(
+            status_type(poll(&ctx, &subscription_id).await),
+            Some(StatusType::Failure)
+        );
+        // Pausing again leaves the subscription paused
+        assert_action_11(
+            &ctx,
+            ACT_PAUSE,
+            &subscription_id,
+            subscription_status::PAUSED,
+        )
+        .await?;
+
+        assert_action_11(
+            &ctx,
+            ACT_RESUME,
+            &subscription_id,
+            subscription_status::ACTIVE,
+        )
+        .await?;
+        poll(&ctx, &subscription_id).await?;
+
+        assert_action_11(
+            &ctx,
+            ACT_UNSUBSCRIBE,
+            &subscription_id,
+            subscription_status::UNSUBSCRIBED,
+        )
+        .await?;
+        assert_eq!(
+            status_type(manage_11(&ctx, ACT_RESUME, Some(&subscription_id)).await),
+            Some(StatusType::Failure)
+        );
+        assert_eq!(
+            status_type(manage_11(&ctx, ACT_PAUSE, Some(&subscription_id)).await),
+            Some(StatusType::Failure)
+        );
+        assert_eq!(
+            status_type(poll(&ctx, &subscription_id).await),
+            Some(StatusType::Failure)
+        );
+        assert_action_11(
+            &ctx,
+            ACT_STATUS,
+            &subscription_id,
+            subscription_status::UNSUBSCRIBED,
+        )
+        .await?;
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_subscription_status_lists_collection(pool: PgPool) -> TestResult {
+        let ctx = setup(pool).await?;
+
+        manage_11(&ctx, ACT_SUBSCRIBE, None).await?;
+        let paused = manage_11(&ctx, ACT_SUBSCRIBE, None)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or("no subscription instance")?
+            .subscription_id;
+        manage_11(&ctx, ACT_PAUSE, Some(&paused)).await?;
+
+        let mut statuses: Vec<String> = manage_11(&ctx, ACT_STATUS, None)
+            .await?
+            .into_iter()
+            .map(|i| {
+                assert!(i.push_parameters.is_some());
+                i.status.unwrap_or_default()
+            })
+            .collect();
+        statuses.sort();
+        assert_eq!(
+            statuses,
+            [subscription_status::ACTIVE, subscription_status::PAUSED]
+        );
+
+        assert_eq!(
+            status_type(manage_11(&ctx, ACT_PAUSE, Some("unknown")).await),
+            Some(StatusType::NotFound)
+        );
+        assert_eq!(
+            status_type(manage_11(&ctx, ACT_PAUSE, None).await),
+            Some(StatusType::BadMessage)
+        );
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_subscription_lifecycle_10(pool: PgPool) -> TestResult {
+        let ctx = setup(pool).await?;
+
+        let instance = manage_10(&ctx, ACT_SUBSCRIBE, None)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or("no subscription instance")?;
+        let subscription_id = instance.subscription_id;
+        let delivery = instance
+            .delivery_parameters
+            .ok_or("no delivery parameters")?;
+        assert_eq!(delivery.inbox_address, INBOX_ADDRESS);
+        assert_eq!(delivery.content_bindings.len(), 1);
+
+        let instances = manage_10(&ctx, ACT_UNSUBSCRIBE, Some(&subscription_id)).await?;
+        assert_eq!(instances.len(), 1);
+        assert!(instances[0].delivery_parameters.is_some());
+        let stored = ctx
+            .persistence
+            .get_subscription(&subscription_id)
+            .await?
+            .ok_or("subscription not stored")?;
+        assert_eq!(stored.status, subscription_status::UNSUBSCRIBED);
+
+        let instances = manage_10(&ctx, ACT_STATUS, None).await?;
+        assert_eq!(instances.len(), 1);
+        assert_eq!(instances[0].subscription_id, subscription_id);
+        assert_eq!(
+            status_type(manage_10(&ctx, ACT_STATUS, Some("unknown")).await),
+            Some(StatusType::NotFound)
+        );
+        Ok(())
+    }
+
+    /// Clients only see, poll and manage their own subscriptions.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_subscriptions_scoped_to_owner(pool: PgPool) -> TestResult {
+        let taxii_pool = TaxiiPool::new(pool.clone());
+        let ctx = setup(pool).await?;
+        let client_a = as_client(&ctx, &taxii_pool, "client-a").await?;
+        let client_b = as_client(&ctx, &taxii_pool, "client-b").await?;
+
+        let subscription_id = manage_11(&client_a, ACT_SUBSCRIBE, None)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or("no subscription instance")?
+            .subscription_id;
+        let stored = ctx
+            .persistence
+            .get_subscription(&subscription_id)
+            .await?
+            .ok_or("subscription not stored")?;
+        assert_eq!(stored.account_id, client_a.account_id());
+
+        for other in [&client_b, &ctx] {
+            assert!(manage_11(other, ACT_STATUS, None).await?.is_empty());
+            for action in [ACT_STATUS, ACT_PAUSE, ACT_RESUME] {
+                assert_eq!(
+                    status_type(manage_11(other, action, Some(&subscription_id)).await),
+                    Some(StatusType::NotFound),
+                    "{action}"
+                );
+            }
+            assert_eq!(
+                status_type(poll(other, &subscription_id).await),
+                Some(StatusType::NotFound)
+            );
+            // Unsubscribing from an unknown subscription succeeds, but
+            // leaves the subscription of the other client alone
+            manage_11(other, ACT_UNSUBSCRIBE, Some(&subscription_id)).await?;
+        }
+
+        assert_action_11(
+            &client_a,
+            ACT_STATUS,
+            &subscription_id,
+            subscription_status::ACTIVE,
+        )
+        .await?;
+        assert_eq!(manage_11(&client_a, ACT_STATUS, None).await?.len(), 1);
+        Ok(())
+    }
+}
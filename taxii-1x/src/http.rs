@@ -259,6 +259,8 @@ pub fn validate_response_headers(headers: &HashMap<String, String>) -> Taxii1xRe
 }
 
 /// Generate a unique message ID.
+///
+/// See [`crate::messages::common::generate_message_id`].
 pub fn generate_message_id() -> String {
-    uuid::Uuid::new_v4().to_string()
+    crate::messages::common::generate_message_id()
 }
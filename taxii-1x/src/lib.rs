@@ -1,15 +1,43 @@
 //! TAXII 1.x protocol implementation.
 
+#[cfg(feature = "bridge")]
+pub mod bridge;
+pub mod client;
 pub mod constants;
+pub mod content;
 pub mod error;
 pub mod handlers;
 pub mod http;
+pub mod limits;
 pub mod messages;
+pub mod push;
+#[cfg(feature = "signatures")]
+pub mod signature;
+pub mod validation;
+pub mod worker;
+#[cfg(feature = "xsd")]
+pub mod xsd;
 
+#[cfg(feature = "bridge")]
+pub use bridge::BridgeWorker;
+pub use client::{
+    DiscoveredService, InboxOptions, InboxSubscription, PollOptions, PollResult, Taxii1Client,
+    Taxii1ClientBuilder, is_public_address,
+};
 pub use constants::*;
 pub use error::{Taxii1xError, Taxii1xResult};
 pub use handlers::{
     Handler, HandlerContext, HandlerRegistry, ServiceInfo, TaxiiHeaders, generate_id,
 };
 pub use http::*;
-pub use messages::{TaxiiMessage, get_message_from_xml};
+pub use limits::{MessageBody, MessageLimits};
+#[cfg(feature = "xsd")]
+pub use messages::validate_message_schema;
+pub use messages::{
+    MAX_MESSAGE_SIZE, SchemaViolation, TaxiiMessage, check_message_document, get_message_from_xml,
+};
+pub use push::{
+    DEFAULT_DELIVERY_BATCH_SIZE, DELIVERY_LEASE, DeliveryOutcome, MAX_CONCURRENT_DELIVERIES,
+    PushDeliveryWorker, PushPolicy, RetryPolicy,
+};
+pub use worker::ResultSetWorker;
@@ -0,0 +1,472 @@
+//! Size limits of TAXII 1.x messages.
+//!
+//! Request bodies are limited in size while they are read, before anything
+//! is parsed, and the content blocks of inbox messages are limited once the
+//! message is parsed. Bodies larger than the spool threshold are written to
+//! a temporary file as they arrive and parsed from it, so a message document
+//! is never held in memory whole. Poll responses are split into result parts
+//! by the size of their content as well as by their number of blocks.
+//!
+//! Services override the server-wide limits in their properties:
+//!
+//! | Property | Limit |
+//! |----------|-------|
+//! | `max_message_size` | Request body, in bytes |
+//! | `max_content_block_size` | Content of an inbox content block, in bytes |
+//! | `max_result_bytes` | Content of a poll response part, in bytes |
+
+use std::fs::File;
+use std::io::{self, BufReader, Cursor, Seek};
+
+use axum::body::Body;
+use futures::StreamExt;
+use tokio::io::AsyncWriteExt;
+
+use crate::constants::StatusType;
+use crate::error::{Taxii1xError, Taxii1xResult};
+use crate::messages::{
+    MAX_MESSAGE_SIZE, TaxiiMessage, check_document_type, check_message_size,
+    get_message_from_reader, tm10, tm11,
+};
+#[cfg(feature = "xsd")]
+use crate::messages::{SchemaViolation, validate_message_schema};
+
+/// Default maximum size of the content of an inbox content block, in bytes.
+pub const DEFAULT_MAX_CONTENT_BLOCK_SIZE: usize = 5 * 1024 * 1024;
+
+/// Default maximum size of the content of a poll response part, in bytes.
+pub const DEFAULT_MAX_RESULT_BYTES: usize = 5 * 1024 * 1024;
+
+/// Default size above which request bodies are spooled to a temporary file.
+pub const DEFAULT_SPOOL_THRESHOLD: usize = 1024 * 1024;
+
+/// Size limits applied to the messages of a service.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MessageLimits {
+    /// Maximum size of a request body, in bytes.
+    pub max_message_size: usize,
+
+    /// Maximum size of the content of an inbox content block, in bytes, as
+    /// received (binary content is base64 encoded).
+    pub max_content_block_size: usize,
+
+    /// Maximum size of the content blocks of a poll response part, in bytes,
+    /// as stored. A part always holds at least one block, however large.
+    pub max_result_bytes: usize,
+
+    /// Size above which request bodies are spooled to a temporary file
+    /// instead of being buffered in memory.
+    pub spool_threshold: usize,
+}
+
+impl Default for MessageLimits {
+    fn default() -> Self {
+        Self {
+            max_message_size: MAX_MESSAGE_SIZE,
+            max_content_block_size: DEFAULT_MAX_CONTENT_BLOCK_SIZE,
+            max_result_bytes: DEFAULT_MAX_RESULT_BYTES,
+            spool_threshold: DEFAULT_SPOOL_THRESHOLD,
+        }
+    }
+}
+
+impl MessageLimits {
+    /// Limits of a service, overriding these with the service properties.
+    ///
+    /// Properties that are not positive integers are ignored.
+    #[must_use]
+    pub fn for_service(&self, properties: &serde_json::Value) -> Self {
+        let property = |key: &str, default: usize| {
+            properties
+                .get(key)
+                .and_then(serde_json::Value::as_u64)
+                .filter(|&value| value > 0)
+                .map_or(default, |value| {
+                    usize::try_from(value).unwrap_or(usize::MAX)
+                })
+        };
+        Self {
+            max_message_size: property("max_message_size", self.max_message_size),
+            max_content_block_size: property("max_content_block_size", self.max_content_block_size),
+            max_result_bytes: property("max_result_bytes", self.max_result_bytes),
+            spool_threshold: self.spool_threshold,
+        }
+    }
+}
+
+/// A request body that has been read within the message size limit.
+///
+/// Small bodies are held in memory; larger ones are spooled to an anonymous
+/// temporary file, which is removed when the body is dropped. The document
+/// is read in chunks from either when it is checked and parsed; a spooled
+/// one on a blocking thread.
+#[derive(Debug)]
+pub struct MessageBody {
+    len: u64,
+    data: Spool,
+}
+
+#[derive(Debug)]
+enum Spool {
+    Memory(Vec<u8>),
+    File(File),
+}
+
+impl From<Vec<u8>> for MessageBody {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self {
+            len: bytes.len() as u64,
+            data: Spool::Memory(bytes),
+        }
+    }
+}
+
+impl MessageBody {
+    /// Read a request body.
+    ///
+    /// A body whose `Content-Length` exceeds the maximum message size is
+    /// rejected before it is read, and any other body as soon as it does.
+    /// Both get a `BAD_MESSAGE` status error. A body that cannot be read is
+    /// an invalid request.
+    pub async fn read(
+        body: Body,
+        content_length: Option<u64>,
+        limits: &MessageLimits,
+    ) -> Taxii1xResult<Self> {
+        if let Some(length) = content_length {
+            check_message_size(length, limits.max_message_size)?;
+        }
+
+        let mut stream = body.into_data_stream();
+        let mut memory = Vec::new();
+        let mut spool: Option<tokio::fs::File> = None;
+        let mut len = 0u64;
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.map_err(|e| {
+                Taxii1xError::InvalidRequest(format!("Failed to read message body: {e}"))
+            })?;
+            len += chunk.len() as u64;
+            if len > limits.max_message_size as u64 {
+                return Err(Taxii1xError::status(
+                    StatusType::BadMessage,
+                    format!(
+                        "Message exceeds the maximum size of {} bytes",
+                        limits.max_message_size
+                    ),
+                    None,
+                ));
+            }
+
+            match &mut spool {
+                Some(file) => file.write_all(&chunk).await?,
+                None if memory.len() + chunk.len() > limits.spool_threshold => {
+                    let mut file = tokio::fs::File::from_std(tempfile::tempfile()?);
+                    file.write_all(&memory).await?;
+                    file.write_all(&chunk).await?;
+                    memory = Vec::new();
+                    spool = Some(file);
+                }
+                None => memory.extend_from_slice(&chunk),
+            }
+        }
+
+        let data = match spool {
+            Some(mut file) => {
+                file.flush().await?;
+                Spool::File(file.into_std().await)
+            }
+            None => Spool::Memory(memory),
+        };
+        Ok(Self { len, data })
+    }
+
+    /// Size of the body, in bytes.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Whether the body is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Whether the body was spooled to a temporary file.
+    pub fn is_spooled(&self) -> bool {
+        matches!(self.data, Spool::File(_))
+    }
+
+    /// Check that the document has no document type declaration.
+    ///
+    /// See [`check_document_type`].
+    pub async fn check_document(&self) -> Taxii1xResult<()> {
+        match &self.data {
+            Spool::Memory(bytes) => check_document_type(bytes.as_slice()),
+            Spool::File(file) => read_spooled(file, check_document_type).await?,
+        }
+    }
+
+    /// Validate the document against its message binding schema.
+    ///
+    /// The document is validated as a whole, so a spooled body is read back
+    /// into memory first. See [`validate_message_schema`].
+    #[cfg(feature = "xsd")]
+    pub async fn validate_schema(&self) -> Result<(), SchemaViolation> {
+        match &self.data {
+            Spool::Memory(bytes) => validate_message_schema(bytes),
+            Spool::File(file) => read_spooled(file, |mut reader| {
+                let mut bytes = Vec::new();
+                io::Read::read_to_end(&mut reader, &mut bytes)?;
+                Ok(validate_message_schema(&bytes))
+            })
+            .await
+            .and_then(|result: io::Result<_>| result)
+            .map_err(|e| SchemaViolation {
+                path: "/".to_string(),
+                message: format!("unreadable message: {e}"),
+            })?,
+        }
+    }
+
+    /// Parse the message.
+    ///
+    /// See [`get_message_from_reader`].
+    pub async fn parse(&self) -> Taxii1xResult<TaxiiMessage> {
+        match &self.data {
+            Spool::Memory(bytes) => get_message_from_reader(Cursor::new(bytes.as_slice())),
+            Spool::File(file) => read_spooled(file, get_message_from_reader).await?,
+        }
+    }
+}
+
+/// Run `f` over a spooled body, from its start, on a blocking thread: the
+/// body is read back from disk.
+async fn read_spooled<T, F>(file: &File, f: F) -> io::Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce(BufReader<File>) -> T + Send + 'static,
+{
+    let mut file = file.try_clone()?;
+    tokio::task::spawn_blocking(move || {
+        file.rewind()?;
+        Ok(f(BufReader::new(file)))
+    })
+    .await
+    .map_err(io::Error::other)?
+}
+
+/// Check the size of the content blocks of an inbox message.
+///
+/// Returns a `FAILURE` status error naming the first content block (by its
+/// position, from 1) whose content is larger than `max_size` bytes. Other
+/// messages always pass.
+pub fn check_content_blocks(message: &TaxiiMessage, max_size: usize) -> Taxii1xResult<()> {
+    let (message_id, oversized) = match message {
+        TaxiiMessage::V10(tm10::Taxii10Message::InboxMessage(inbox)) => (
+            &inbox.message_id,
+            oversized_block(
+                inbox.content_blocks.iter().map(|b| b.content.len()),
+                max_size,
+            ),
+        ),
+        TaxiiMessage::V11(tm11::Taxii11Message::InboxMessage(inbox)) => (
+            &inbox.message_id,
+            oversized_block(
+                inbox.content_blocks.iter().map(|b| b.content.len()),
+                max_size,
+            ),
+        ),
+        _ => return Ok(()),
+    };
+
+    match oversized {
+        Some((position, size)) => Err(Taxii1xError::failure(
+            format!(
+                "Content block {position} of {size} bytes exceeds the maximum size of \
+                 {max_size} bytes"
+            ),
+            Some(message_id.clone()),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Position (from 1) and size of the first size larger than `max_size`.
+fn oversized_block(sizes: impl Iterator<Item = usize>, max_size: usize) -> Option<(usize, usize)> {
+    sizes
+        .enumerate()
+        .find(|&(_, size)| size > max_size)
+        .map(|(index, size)| (index + 1, size))
+}
+
+/// Number of leading blocks of the given content sizes that fit in
+/// `max_bytes`.
+///
+/// The first block always fits, however large, so a result part is never
+/// empty while blocks remain.
+pub fn blocks_within(sizes: impl IntoIterator<Item = usize>, max_bytes: usize) -> usize {
+    let mut total = 0usize;
+    let mut count = 0;
+    for size in sizes {
+        total = total.saturating_add(size);
+        if count > 0 && total > max_bytes {
+            break;
+        }
+        count += 1;
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+    const DISCOVERY: &str = r#"<taxii_11:Discovery_Request xmlns:taxii_11="http://taxii.mitre.org/messages/taxii_xml_binding-1.1" message_id="1"/>"#;
+
+    fn limits(max_message_size: usize, spool_threshold: usize) -> MessageLimits {
+        MessageLimits {
+            max_message_size,
+            spool_threshold,
+            ..MessageLimits::default()
+        }
+    }
+
+    fn is_bad_message<T>(result: &Taxii1xResult<T>) -> bool {
+        matches!(
+            result,
+            Err(Taxii1xError::StatusMessage {
+                status_type: StatusType::BadMessage,
+                ..
+            })
+        )
+    }
+
+    /// Chunked body without a length, as sent without `Content-Length`.
+    fn chunked(document: &str, chunk_size: usize) -> Body {
+        let chunks: Vec<Result<Vec<u8>, io::Error>> = document
+            .as_bytes()
+            .chunks(chunk_size)
+            .map(|chunk| Ok(chunk.to_vec()))
+            .collect();
+        Body::from_stream(futures::stream::iter(chunks))
+    }
+
+    #[tokio::test]
+    async fn test_spooled_body_parses_like_memory() -> TestResult {
+        let padded = format!("{DISCOVERY}{}", "\n".repeat(4096));
+
+        let memory = MessageBody::read(chunked(&padded, 100), None, &limits(8192, 8192)).await?;
+        let spooled = MessageBody::read(chunked(&padded, 100), None, &limits(8192, 1024)).await?;
+        assert!(!memory.is_spooled());
+        assert!(spooled.is_spooled());
+        assert_eq!(spooled.len(), padded.len() as u64);
+
+        for body in [&memory, &spooled] {
+            body.check_document().await?;
+            #[cfg(feature = "xsd")]
+            body.validate_schema().await?;
+            // Parsing twice reads the spool from the start each time
+            for _ in 0..2 {
+                let message = body.parse().await?;
+                assert_eq!(message.message_id(), "1");
+                assert_eq!(message.message_type(), "Discovery_Request");
+            }
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_oversized_body_rejected() -> TestResult {
+        let padded = format!("{DISCOVERY}{}", " ".repeat(2048));
+        let limits = limits(1024, 256);
+
+        // Declared length is checked before reading
+        let declared = MessageBody::read(Body::empty(), Some(2048), &limits).await;
+        assert!(is_bad_message(&declared));
+
+        // Undeclared length is checked while reading
+        let streamed = MessageBody::read(chunked(&padded, 100), None, &limits).await;
+        assert!(is_bad_message(&streamed));
+
+        let within = MessageBody::read(chunked(DISCOVERY, 100), Some(1024), &limits).await?;
+        assert_eq!(within.len(), DISCOVERY.len() as u64);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_spooled_document_type_rejected() -> TestResult {
+        let document = format!(
+            "<?xml version=\"1.0\"?>\n<!DOCTYPE foo [<!ENTITY x \"x\">]>\n{DISCOVERY}{}",
+            " ".repeat(1024)
+        );
+        let body = MessageBody::read(chunked(&document, 64), None, &limits(4096, 256)).await?;
+        assert!(body.is_spooled());
+        assert!(is_bad_message(&body.check_document().await));
+        Ok(())
+    }
+
+    #[test]
+    fn test_content_block_limit_names_block() -> TestResult {
+        let block = |content: &str| tm11::ContentBlock {
+            content_binding: tm11::ContentBinding::new("urn:stix.mitre.org:xml:1.1.1"),
+            content: content.to_string(),
+            timestamp_label: None,
+            message: None,
+            padding: None,
+        };
+        let message = TaxiiMessage::V11(tm11::Taxii11Message::InboxMessage(tm11::InboxMessage {
+            xmlns: crate::messages::NS_TAXII_11.to_string(),
+            message_id: "inbox-1".to_string(),
+            extended_headers: None,
+            message: None,
+            result_id: None,
+            destination_collection_names: Vec::new(),
+            subscription_information: None,
+            record_count: None,
+            content_blocks: vec![block("<a/>"), block(&"x".repeat(20)), block("<b/>")],
+        }));
+
+        check_content_blocks(&message, 20)?;
+        match check_content_blocks(&message, 19) {
+            Err(Taxii1xError::StatusMessage {
+                status_type: StatusType::Failure,
+                message,
+                in_response_to,
+                ..
+            }) => {
+                assert_eq!(
+                    message,
+                    "Content block 2 of 20 bytes exceeds the maximum size of 19 bytes"
+                );
+                assert_eq!(in_response_to.as_deref(), Some("inbox-1"));
+            }
+            other => return Err(format!("expected a failure, got {other:?}").into()),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_blocks_within() {
+        assert_eq!(blocks_within([], 10), 0);
+        assert_eq!(blocks_within([4, 4, 4], 10), 2);
+        assert_eq!(blocks_within([4, 6, 4], 10), 2);
+        assert_eq!(blocks_within([20, 1], 10), 1);
+    }
+
+    #[test]
+    fn test_service_overrides() {
+        let defaults = MessageLimits::default();
+        let limits = defaults.for_service(&serde_json::json!({
+            "max_message_size": 2048,
+            "max_result_bytes": 0,
+            "max_content_block_size": "large",
+        }));
+        assert_eq!(limits.max_message_size, 2048);
+        assert_eq!(limits.max_result_bytes, defaults.max_result_bytes);
+        assert_eq!(
+            limits.max_content_block_size,
+            defaults.max_content_block_size
+        );
+    }
+}
@@ -10,6 +10,11 @@ pub const NS_TAXII_10: &str = "http://taxii.mitre.org/messages/taxii_xml_binding
 pub const NS_TAXII_11: &str = "http://taxii.mitre.org/messages/taxii_xml_binding-1.1";
 
 /// Generate a message ID.
+///
+/// IDs are random (version 4) UUIDs, so IDs generated concurrently, by
+/// other threads or other server instances, do not collide without any
+/// coordination. Message, result set and subscription IDs all come from
+/// here.
 pub fn generate_message_id() -> String {
     Uuid::new_v4().to_string()
 }
@@ -140,3 +145,43 @@ pub struct StatusDetail {
     #[serde(rename = "$text")]
     pub value: Option<String>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+    #[test]
+    fn test_generate_message_id_unique_under_concurrency() -> TestResult {
+        const THREADS: usize = 16;
+        const PER_THREAD: usize = 1000;
+
+        let batches = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..THREADS)
+                .map(|_| {
+                    scope.spawn(|| {
+                        (0..PER_THREAD)
+                            .map(|_| generate_message_id())
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().map_err(|_| "id generation panicked"))
+                .collect::<Result<Vec<_>, _>>()
+        })?;
+        let ids: Vec<String> = batches.into_iter().flatten().collect();
+
+        let unique: HashSet<&String> = ids.iter().collect();
+        assert_eq!(unique.len(), THREADS * PER_THREAD);
+        assert!(
+            ids.iter()
+                .all(|id| Uuid::parse_str(id).is_ok_and(|u| u.get_version_num() == 4))
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,143 @@
+//! Guard against hostile TAXII 1.x message documents.
+//!
+//! TAXII messages never need a document type declaration, so any DTD is
+//! rejected before a message is parsed. This rules out entity expansion
+//! attacks such as "billion laughs" and external entity references,
+//! whatever the parser would do with them. Documents larger than
+//! [`MAX_MESSAGE_SIZE`] are rejected without being read.
+
+use std::io::BufRead;
+
+use quick_xml::Reader;
+use quick_xml::events::Event;
+
+use crate::constants::StatusType;
+use crate::error::{Taxii1xError, Taxii1xResult};
+
+/// Maximum size of a TAXII 1.x message document, in bytes.
+pub const MAX_MESSAGE_SIZE: usize = 10 * 1024 * 1024;
+
+/// Check that a message document is safe to parse.
+///
+/// Returns a `BAD_MESSAGE` status error for a document that is larger than
+/// [`MAX_MESSAGE_SIZE`] or that contains a document type declaration.
+/// Malformed XML is left for the parser to report.
+pub fn check_message_document(xml: &str) -> Taxii1xResult<()> {
+    check_message_size(xml.len() as u64, MAX_MESSAGE_SIZE)?;
+    check_document_type(xml.as_bytes())
+}
+
+/// Check that a message of `size` bytes is no larger than `max_size`.
+///
+/// Returns a `BAD_MESSAGE` status error if it is.
+pub fn check_message_size(size: u64, max_size: usize) -> Taxii1xResult<()> {
+    if size > max_size as u64 {
+        return Err(Taxii1xError::status(
+            StatusType::BadMessage,
+            format!("Message of {size} bytes exceeds the maximum size of {max_size} bytes"),
+            None,
+        ));
+    }
+    Ok(())
+}
+
+/// Check that a message document read from `reader` has no document type
+/// declaration.
+///
+/// Returns a `BAD_MESSAGE` status error if it does. The document is read in
+/// chunks, so it never has to be held in memory as a whole. Malformed XML
+/// and read errors are left for the parser to report.
+pub fn check_document_type<R: BufRead>(reader: R) -> Taxii1xResult<()> {
+    let mut reader = Reader::from_reader(reader);
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::DocType(_)) => {
+                return Err(Taxii1xError::status(
+                    StatusType::BadMessage,
+                    "Document type declarations are not allowed in TAXII messages",
+                    None,
+                ));
+            }
+            Ok(Event::Eof) | Err(_) => return Ok(()),
+            Ok(_) => {}
+        }
+        buf.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::get_message_from_xml;
+
+    type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+    const BILLION_LAUGHS: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE lolz [
+  <!ENTITY lol "lol">
+  <!ENTITY lol1 "&lol;&lol;&lol;&lol;&lol;&lol;&lol;&lol;&lol;&lol;">
+  <!ENTITY lol2 "&lol1;&lol1;&lol1;&lol1;&lol1;&lol1;&lol1;&lol1;&lol1;&lol1;">
+  <!ENTITY lol3 "&lol2;&lol2;&lol2;&lol2;&lol2;&lol2;&lol2;&lol2;&lol2;&lol2;">
+  <!ENTITY lol4 "&lol3;&lol3;&lol3;&lol3;&lol3;&lol3;&lol3;&lol3;&lol3;&lol3;">
+  <!ENTITY lol5 "&lol4;&lol4;&lol4;&lol4;&lol4;&lol4;&lol4;&lol4;&lol4;&lol4;">
+  <!ENTITY lol6 "&lol5;&lol5;&lol5;&lol5;&lol5;&lol5;&lol5;&lol5;&lol5;&lol5;">
+  <!ENTITY lol7 "&lol6;&lol6;&lol6;&lol6;&lol6;&lol6;&lol6;&lol6;&lol6;&lol6;">
+  <!ENTITY lol8 "&lol7;&lol7;&lol7;&lol7;&lol7;&lol7;&lol7;&lol7;&lol7;&lol7;">
+  <!ENTITY lol9 "&lol8;&lol8;&lol8;&lol8;&lol8;&lol8;&lol8;&lol8;&lol8;&lol8;">
+]>
+<taxii_11:Inbox_Message xmlns:taxii_11="http://taxii.mitre.org/messages/taxii_xml_binding-1.1" message_id="1">
+  <taxii_11:Content_Block>
+    <taxii_11:Content_Binding binding_id="urn:stix.mitre.org:xml:1.1.1"/>
+    <taxii_11:Content>&lol9;</taxii_11:Content>
+  </taxii_11:Content_Block>
+</taxii_11:Inbox_Message>"#;
+
+    fn assert_bad_message(result: Taxii1xResult<()>) {
+        assert!(matches!(
+            result,
+            Err(Taxii1xError::StatusMessage {
+                status_type: StatusType::BadMessage,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_entity_expansion_rejected() -> TestResult {
+        assert_bad_message(check_message_document(BILLION_LAUGHS));
+        assert!(matches!(
+            get_message_from_xml(BILLION_LAUGHS),
+            Err(Taxii1xError::StatusMessage {
+                status_type: StatusType::BadMessage,
+                ..
+            })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_external_entity_rejected() {
+        let xxe = r#"<?xml version="1.0"?>
+<!DOCTYPE foo [<!ENTITY xxe SYSTEM "file:///etc/passwd">]>
+<taxii_11:Discovery_Request xmlns:taxii_11="http://taxii.mitre.org/messages/taxii_xml_binding-1.1" message_id="&xxe;"/>"#;
+        assert_bad_message(check_message_document(xxe));
+    }
+
+    #[test]
+    fn test_oversized_message_rejected() {
+        let padding = " ".repeat(MAX_MESSAGE_SIZE);
+        let xml = format!(
+            r#"<taxii_11:Discovery_Request xmlns:taxii_11="http://taxii.mitre.org/messages/taxii_xml_binding-1.1" message_id="1"/>{padding}"#
+        );
+        assert_bad_message(check_message_document(&xml));
+    }
+
+    #[test]
+    fn test_plain_message_accepted() -> TestResult {
+        let xml = r#"<taxii_11:Discovery_Request xmlns:taxii_11="http://taxii.mitre.org/messages/taxii_xml_binding-1.1" message_id="1"/>"#;
+        check_message_document(xml)?;
+        get_message_from_xml(xml)?;
+        Ok(())
+    }
+}
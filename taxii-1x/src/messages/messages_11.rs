@@ -271,6 +271,12 @@ pub struct PollParameters {
     #[serde(rename = "Content_Binding", default)]
     pub content_bindings: Vec<ContentBinding>,
     // Query not implemented yet
+    /// Inbox to push the results of an asynchronous poll to.
+    #[serde(
+        rename = "Delivery_Parameters",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub delivery_parameters: Option<PushParameters>,
 }
 
 /// TAXII 1.1 Poll Response.
@@ -1,20 +1,37 @@
 //! TAXII 1.x XML message types.
 
 pub mod common;
+pub mod guard;
 pub mod messages_10;
 pub mod messages_11;
+pub mod schema;
 
 pub use common::*;
+pub use guard::{
+    MAX_MESSAGE_SIZE, check_document_type, check_message_document, check_message_size,
+};
 pub use messages_10 as tm10;
 pub use messages_11 as tm11;
+pub use schema::SchemaViolation;
+#[cfg(feature = "xsd")]
+pub use schema::validate_message_schema;
 
-use quick_xml::de::from_str;
+use std::io::{BufRead, Seek};
+
+use quick_xml::NsReader;
+use quick_xml::de::{from_reader, from_str};
+use quick_xml::events::Event;
+use quick_xml::name::{Namespace, ResolveResult};
 
 use crate::constants::{VID_TAXII_XML_10, VID_TAXII_XML_11};
 use crate::error::{Taxii1xError, Taxii1xResult};
 
 /// Parse a TAXII message from XML, detecting the version automatically.
+///
+/// The document is checked with [`check_message_document`] first.
 pub fn get_message_from_xml(xml_string: &str) -> Taxii1xResult<TaxiiMessage> {
+    check_message_document(xml_string)?;
+
     // Detect version by namespace
     if xml_string.contains(common::NS_TAXII_11) {
         let msg: messages_11::Taxii11Message =
@@ -31,6 +48,57 @@ pub fn get_message_from_xml(xml_string: &str) -> Taxii1xResult<TaxiiMessage> {
     }
 }
 
+/// Parse a TAXII message read from `reader`, detecting the version from the
+/// namespace of the root element.
+///
+/// The document is checked with [`check_document_type`] first. It is read
+/// in chunks, so only the parsed message is held in memory; its size is not
+/// checked. See [`crate::limits::MessageBody`].
+pub fn get_message_from_reader<R: BufRead + Seek>(mut reader: R) -> Taxii1xResult<TaxiiMessage> {
+    check_document_type(&mut reader)?;
+    reader.rewind()?;
+    let namespace = root_namespace(&mut reader)?;
+    reader.rewind()?;
+
+    match namespace.as_deref() {
+        Some(common::NS_TAXII_11) => {
+            let msg: messages_11::Taxii11Message =
+                from_reader(reader).map_err(Taxii1xError::xml_parse)?;
+            Ok(TaxiiMessage::V11(msg))
+        }
+        Some(common::NS_TAXII_10) => {
+            let msg: messages_10::Taxii10Message =
+                from_reader(reader).map_err(Taxii1xError::xml_parse)?;
+            Ok(TaxiiMessage::V10(msg))
+        }
+        _ => Err(Taxii1xError::UnsupportedVersion(
+            "Unknown TAXII namespace".to_string(),
+        )),
+    }
+}
+
+/// Namespace of the root element of a document, if it has one.
+fn root_namespace<R: BufRead>(reader: R) -> Taxii1xResult<Option<String>> {
+    let mut reader = NsReader::from_reader(reader);
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_resolved_event_into(&mut buf) {
+            Ok((ns, Event::Start(_) | Event::Empty(_))) => {
+                return Ok(match ns {
+                    ResolveResult::Bound(Namespace(ns)) => {
+                        Some(String::from_utf8_lossy(ns).into_owned())
+                    }
+                    _ => None,
+                });
+            }
+            Ok((_, Event::Eof)) => return Ok(None),
+            Ok(_) => {}
+            Err(e) => return Err(Taxii1xError::xml_parse_msg(e.to_string())),
+        }
+        buf.clear();
+    }
+}
+
 /// Enum wrapper for TAXII messages of different versions.
 #[derive(Debug, Clone)]
 pub enum TaxiiMessage {
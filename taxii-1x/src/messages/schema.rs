@@ -0,0 +1,245 @@
+//! Schema validation of TAXII 1.x XML messages.
+//!
+//! The message deserializers accept messages missing required attributes
+//! or with elements out of order, which then fail deep inside the handlers.
+//! With the `xsd` feature, a raw document can be validated first against
+//! the bundled TAXII 1.0 or 1.1 XML Message Binding schema (see
+//! [`crate::xsd`]), selected by the namespace of its root element.
+//!
+//! Namespace prefixes are resolved, so prefixed and default-namespace
+//! documents validate alike.
+
+use thiserror::Error;
+
+/// First schema violation found in a document.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("{message} at {path}")]
+pub struct SchemaViolation {
+    /// XPath of the offending element or attribute, e.g.
+    /// `/Inbox_Message/Content_Block[2]/Content`.
+    pub path: String,
+    /// What is wrong.
+    pub message: String,
+}
+
+/// Validate a TAXII 1.0 or 1.1 message against its message binding schema.
+///
+/// Returns the first violation in document order.
+#[cfg(feature = "xsd")]
+pub fn validate_message_schema(xml: &[u8]) -> Result<(), SchemaViolation> {
+    use quick_xml::NsReader;
+    use quick_xml::events::Event;
+    use quick_xml::name::{Namespace, ResolveResult};
+
+    use super::common::{NS_TAXII_10, NS_TAXII_11};
+    use crate::xsd::{TAXII_10_SCHEMA, TAXII_11_SCHEMA};
+
+    let mut reader = NsReader::from_reader(xml);
+    let mut buf = Vec::new();
+    let (namespace, name) = loop {
+        buf.clear();
+        match reader.read_resolved_event_into(&mut buf) {
+            Ok((ns, Event::Start(e) | Event::Empty(e))) => {
+                let namespace = match ns {
+                    ResolveResult::Bound(Namespace(ns)) => String::from_utf8_lossy(ns).into_owned(),
+                    _ => String::new(),
+                };
+                break (
+                    namespace,
+                    String::from_utf8_lossy(e.local_name().as_ref()).into_owned(),
+                );
+            }
+            Ok((_, Event::Eof)) => {
+                return Err(SchemaViolation {
+                    path: "/".to_string(),
+                    message: "no root element".to_string(),
+                });
+            }
+            Ok(_) => {}
+            Err(_) => {
+                return Err(SchemaViolation {
+                    path: "/".to_string(),
+                    message: "document is not well-formed XML".to_string(),
+                });
+            }
+        }
+    };
+
+    match namespace.as_str() {
+        NS_TAXII_11 => TAXII_11_SCHEMA.validate(xml),
+        NS_TAXII_10 => TAXII_10_SCHEMA.validate(xml),
+        _ => Err(SchemaViolation {
+            path: format!("/{name}"),
+            message: format!("{{{namespace}}}{name} is not a TAXII message"),
+        }),
+    }
+}
+
+#[cfg(all(test, feature = "xsd"))]
+mod tests {
+    use super::*;
+    use crate::messages::{NS_TAXII_10, NS_TAXII_11};
+
+    const VALID: &str = include_str!("../../tests/fixtures/inbox_message_valid.xml");
+    const MISSING_MESSAGE_ID: &str =
+        include_str!("../../tests/fixtures/poll_request_missing_message_id.xml");
+    const WRONG_ORDER: &str = include_str!("../../tests/fixtures/inbox_message_wrong_order.xml");
+    const DISCOVERY_10: &str = include_str!("../../tests/fixtures/discovery_response_10.xml");
+    const DISCOVERY_11: &str = include_str!("../../tests/fixtures/discovery_response_11.xml");
+
+    fn violation(xml: &str) -> Result<SchemaViolation, Box<dyn std::error::Error>> {
+        match validate_message_schema(xml.as_bytes()) {
+            Ok(()) => Err("message passed validation".into()),
+            Err(violation) => Ok(violation),
+        }
+    }
+
+    #[test]
+    fn test_valid_message() -> Result<(), Box<dyn std::error::Error>> {
+        validate_message_schema(VALID.as_bytes())?;
+        validate_message_schema(DISCOVERY_10.as_bytes())?;
+        validate_message_schema(DISCOVERY_11.as_bytes())?;
+        // The fixtures are messages the server parses without validation
+        crate::messages::get_message_from_xml(VALID)?;
+        crate::messages::get_message_from_xml(WRONG_ORDER)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_message_id() -> Result<(), Box<dyn std::error::Error>> {
+        let violation = violation(MISSING_MESSAGE_ID)?;
+        assert_eq!(violation.path, "/Poll_Request/@message_id");
+        assert_eq!(
+            violation.message,
+            "The attribute 'message_id' is required but missing."
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_wrong_order() -> Result<(), Box<dyn std::error::Error>> {
+        let violation = violation(WRONG_ORDER)?;
+        assert_eq!(violation.path, "/Inbox_Message/Message");
+        assert!(
+            violation
+                .message
+                .starts_with("This element is not expected."),
+            "{}",
+            violation.message
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_prefixed_and_default_namespace() -> Result<(), Box<dyn std::error::Error>> {
+        validate_message_schema(
+            format!(r#"<Discovery_Request xmlns="{NS_TAXII_11}" message_id="1"/>"#).as_bytes(),
+        )?;
+        validate_message_schema(
+            format!(
+                r#"<t:Poll_Request xmlns:t="{NS_TAXII_11}" message_id="1" collection_name="c">
+                <t:Poll_Parameters allow_asynch="false"><t:Response_Type>FULL</t:Response_Type></t:Poll_Parameters>
+            </t:Poll_Request>"#
+            )
+            .as_bytes(),
+        )?;
+        validate_message_schema(
+            format!(
+                r#"<taxii:Status_Message xmlns:taxii="{NS_TAXII_10}" message_id="2" in_response_to="1" status_type="SUCCESS">
+                <taxii:Message>ok</taxii:Message>
+                <ds:Signature xmlns:ds="http://www.w3.org/2000/09/xmldsig#"/>
+            </taxii:Status_Message>"#
+            )
+            .as_bytes(),
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_violations() -> Result<(), Box<dyn std::error::Error>> {
+        // Subscription_ID or Poll_Parameters is required
+        let v = violation(&format!(
+            r#"<Poll_Request xmlns="{NS_TAXII_11}" message_id="1" collection_name="c"/>"#
+        ))?;
+        assert_eq!(v.path, "/Poll_Request");
+
+        // Both are not allowed either
+        let v = violation(&format!(
+            r#"<Poll_Request xmlns="{NS_TAXII_11}" message_id="1" collection_name="c">
+                <Subscription_ID>s</Subscription_ID><Poll_Parameters/>
+            </Poll_Request>"#
+        ))?;
+        assert_eq!(v.path, "/Poll_Request/Poll_Parameters");
+
+        // Content block children are checked, with positions in the path
+        let v = violation(&format!(
+            r#"<Inbox_Message xmlns="{NS_TAXII_11}" message_id="1">
+                <Content_Block><Content_Binding binding_id="b"/><Content>x</Content></Content_Block>
+                <Content_Block><Content_Binding/><Content>y</Content></Content_Block>
+            </Inbox_Message>"#
+        ))?;
+        assert_eq!(
+            v.path,
+            "/Inbox_Message/Content_Block[2]/Content_Binding/@binding_id"
+        );
+
+        let v = violation(&format!(
+            r#"<Inbox_Message xmlns="{NS_TAXII_11}" message_id="1">
+                <Content_Block><Content>x</Content></Content_Block>
+            </Inbox_Message>"#
+        ))?;
+        assert_eq!(v.path, "/Inbox_Message/Content_Block/Content");
+
+        // Values are checked too
+        let v = violation(&format!(
+            r#"<Inbox_Message xmlns="{NS_TAXII_11}" message_id="1">
+                <Content_Block>
+                    <Content_Binding binding_id="b"/><Content>x</Content>
+                    <Timestamp_Label>yesterday</Timestamp_Label>
+                </Content_Block>
+            </Inbox_Message>"#
+        ))?;
+        assert_eq!(v.path, "/Inbox_Message/Content_Block/Timestamp_Label");
+        let v = violation(&format!(
+            r#"<Subscription_Management_Request xmlns="{NS_TAXII_10}" message_id="1" action="PAUSE">
+                <Feed_Name>f</Feed_Name>
+            </Subscription_Management_Request>"#
+        ))?;
+        assert_eq!(v.path, "/Subscription_Management_Request/@action");
+
+        // Children must be in the message namespace
+        let v = violation(&format!(
+            r#"<Inbox_Message xmlns="{NS_TAXII_11}" xmlns:x="urn:example" message_id="1"><x:Message/></Inbox_Message>"#
+        ))?;
+        assert_eq!(v.path, "/Inbox_Message/Message");
+
+        // Unknown messages and other documents
+        let v = violation(&format!(
+            r#"<Feed_Information_Request xmlns="{NS_TAXII_11}" message_id="1"/>"#
+        ))?;
+        assert_eq!(v.path, "/Feed_Information_Request");
+        let v = violation("<html/>")?;
+        assert_eq!(v.path, "/html");
+        assert_eq!(v.message, "{}html is not a TAXII message");
+        let v = violation("")?;
+        assert_eq!(v.message, "no root element");
+        Ok(())
+    }
+
+    #[test]
+    fn test_open_content_is_not_checked() -> Result<(), Box<dyn std::error::Error>> {
+        validate_message_schema(
+            format!(
+                r#"<Inbox_Message xmlns="{NS_TAXII_11}" message_id="1">
+                <Extended_Headers><Extended_Header name="x"><anything><nested/></anything></Extended_Header></Extended_Headers>
+                <Content_Block>
+                    <Content_Binding binding_id="urn:stix.mitre.org:xml:1.1.1"><Subtype subtype_id="s"/></Content_Binding>
+                    <Content><stix:STIX_Package xmlns:stix="http://stix.mitre.org/stix-1"><Message/></stix:STIX_Package></Content>
+                </Content_Block>
+            </Inbox_Message>"#
+            )
+            .as_bytes(),
+        )?;
+        Ok(())
+    }
+}
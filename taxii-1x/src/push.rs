@@ -0,0 +1,1334 @@
+//! Push delivery of TAXII 1.x messages to subscriber inboxes.
+//!
+//! Subscriptions created with push parameters receive new content of their
+//! collection in Inbox Messages sent to the subscriber's inbox. When a
+//! content block is created, [`PushDeliveryWorker::listen`] queues a
+//! delivery for every active push subscription of its collections that
+//! accepts the block's content binding. [`PushDeliveryWorker::run`] pushes
+//! the queued deliveries in the background, one Inbox Message per
+//! subscription and run, and records their outcome in the delivery log.
+//! Deliveries to paused subscriptions stay queued until they are resumed.
+//!
+//! A subscriber's inbox may be temporarily unreachable. [`PushDeliveryWorker`]
+//! retries each delivery with exponential backoff, up to
+//! [`RetryPolicy::max_attempts`] times, and records the outcome on the
+//! subscription: the time of the last successful delivery, and the number of
+//! failed deliveries since then along with the last error. Once
+//! [`RetryPolicy::pause_after`] deliveries in a row failed, the subscription
+//! is paused and no longer delivered to until it is resumed. Failed
+//! deliveries stay in the log until they are queued again.
+//!
+//! An asynchronous poll with delivery parameters has its results pushed too:
+//! once the result set is prepared, [`PushDeliveryWorker::run`] sends them
+//! to the client's inbox in Inbox Messages with the result ID, with the
+//! same retries. The results are split into the parts Poll Fulfillment
+//! would return, by the `max_result_size` and `max_result_bytes` of the
+//! poll services of the collection, and each part is sent in its own
+//! message. The result set can be fulfilled by Poll Fulfillment either way.
+//!
+//! Push is off unless enabled by the [`PushPolicy`]. Push addresses are
+//! chosen by clients, so unless the policy allows private addresses they
+//! must resolve to public addresses only, both when a subscription or poll
+//! names them and before every delivery attempt.
+//!
+//! Queued deliveries and result sets are claimed by the worker pushing
+//! them for [`DELIVERY_LEASE`], so that the workers of several server
+//! instances do not push them twice. Subscriptions are pushed to
+//! concurrently, up to [`MAX_CONCURRENT_DELIVERIES`] at a time, so a slow
+//! or failing inbox only delays its own deliveries.
+
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::StreamExt;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{debug, error, warn};
+
+use taxii_core::{
+    ContentBlockCreatedEvent, ContentBlockEntity, ResultSetEntity, SignalEvent,
+    SubscriptionDeliveryEntity, SubscriptionEntity, delivery_status, subscription_status,
+};
+use taxii_db::{DatabaseResult, Taxii1Repository};
+
+use crate::client::{InboxOptions, InboxSubscription, Taxii1Client, is_public_address};
+use crate::constants::SVC_POLL;
+use crate::error::Taxii1xError;
+use crate::handlers::poll_fulfillment::{DEFAULT_MAX_RESULT_SIZE, result_parts};
+use crate::limits::MessageLimits;
+use crate::messages::TaxiiMessage;
+
+/// Default number of queued deliveries pushed per run.
+pub const DEFAULT_DELIVERY_BATCH_SIZE: i64 = 100;
+
+/// Time a worker has to push the deliveries it claimed before other
+/// workers may claim them again.
+pub const DELIVERY_LEASE: Duration = Duration::from_secs(30 * 60);
+
+/// Inboxes pushed to at the same time by a worker.
+pub const MAX_CONCURRENT_DELIVERIES: usize = 16;
+
+/// Whether, and to which addresses, content may be pushed.
+///
+/// The default policy disables push.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PushPolicy {
+    /// Whether subscriptions and asynchronous polls may have content
+    /// pushed to them.
+    pub enabled: bool,
+
+    /// Whether push addresses may resolve to loopback, private, link-local
+    /// and other non-public addresses.
+    pub allow_private_addresses: bool,
+}
+
+impl PushPolicy {
+    /// Check that content may be pushed to `address`.
+    ///
+    /// Unless private addresses are allowed, the host of the address is
+    /// resolved and every address it resolves to must be public (see
+    /// [`is_public_address`]). Returns the reason the address is refused.
+    pub async fn check_address(&self, address: &str) -> Result<(), String> {
+        if self.allow_private_addresses {
+            return Ok(());
+        }
+
+        let url = reqwest::Url::parse(address).map_err(|e| format!("Invalid address: {e}"))?;
+        let host = url
+            .host_str()
+            .ok_or_else(|| "Address has no host".to_string())?;
+        let port = url.port_or_known_default().unwrap_or(80);
+        let literal = host.trim_start_matches('[').trim_end_matches(']');
+        let ips: Vec<IpAddr> = match literal.parse::<IpAddr>() {
+            Ok(ip) => vec![ip],
+            Err(_) => tokio::net::lookup_host((host, port))
+                .await
+                .map_err(|e| format!("Cannot resolve {host}: {e}"))?
+                .map(|a| a.ip())
+                .collect(),
+        };
+
+        if ips.is_empty() {
+            return Err(format!("{host} does not resolve to an address"));
+        }
+        match ips.into_iter().find(|ip| !is_public_address(*ip)) {
+            Some(ip) => Err(format!("{host} resolves to the non-public address {ip}")),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Retry behavior of push deliveries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Delay before the first retry; doubled for every further retry.
+    pub initial_delay: Duration,
+
+    /// Upper bound of the delay between attempts.
+    pub max_delay: Duration,
+
+    /// Attempts per delivery, including the first one.
+    pub max_attempts: u32,
+
+    /// Failed deliveries in a row after which the subscription is paused.
+    pub pause_after: i32,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(300),
+            max_attempts: 5,
+            pause_after: 10,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before retry number `retry` (starting at 0).
+    pub fn delay(&self, retry: u32) -> Duration {
+        self.initial_delay
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_delay)
+    }
+}
+
+/// Outcome of a push delivery.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeliveryOutcome {
+    /// The inbox accepted the message.
+    Delivered {
+        /// Attempts it took.
+        attempts: u32,
+    },
+    /// Every attempt failed.
+    Failed {
+        /// Attempts made.
+        attempts: u32,
+        /// Error of the last attempt.
+        error: String,
+    },
+    /// The subscription does not exist or is not active.
+    Skipped,
+}
+
+/// Worker that pushes messages to subscriber inboxes.
+///
+/// # Example
+///
+/// ```ignore
+/// let worker = PushDeliveryWorker::new(Arc::new(DbTaxii1Repository::new(pool)));
+/// worker.deliver(&subscription_id, "https://partner.example/inbox/", &message).await?;
+/// ```
+pub struct PushDeliveryWorker<R> {
+    persistence: Arc<R>,
+    client: Taxii1Client,
+    policy: RetryPolicy,
+    push: PushPolicy,
+    limits: MessageLimits,
+    batch_size: i64,
+}
+
+impl<R: Taxii1Repository> PushDeliveryWorker<R> {
+    /// Create a worker with the default retry policy and batch size,
+    /// pushing to public addresses only.
+    pub fn new(persistence: Arc<R>) -> Self {
+        Self {
+            persistence,
+            client: Taxii1Client::new(),
+            policy: RetryPolicy::default(),
+            push: PushPolicy::default(),
+            limits: MessageLimits::default(),
+            batch_size: DEFAULT_DELIVERY_BATCH_SIZE,
+        }
+    }
+
+    /// Set the retry policy.
+    #[must_use]
+    pub fn with_policy(mut self, policy: RetryPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Set the push policy, e.g. to allow private push addresses.
+    #[must_use]
+    pub fn with_push_policy(mut self, push: PushPolicy) -> Self {
+        self.push = push;
+        self
+    }
+
+    /// Set the server-wide message limits, which poll service properties
+    /// override, splitting pushed results into parts.
+    #[must_use]
+    pub fn with_limits(mut self, limits: MessageLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Set the client pushing the deliveries, e.g. to authenticate to
+    /// subscriber inboxes with a client certificate.
+    #[must_use]
+    pub fn with_client(mut self, client: Taxii1Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Set how many queued deliveries are pushed per run.
+    #[must_use]
+    pub fn with_batch_size(mut self, batch_size: i64) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// Queue the delivery of a new content block to the push subscriptions
+    /// of its collections that accept its content binding.
+    ///
+    /// Returns the number of deliveries queued.
+    pub async fn enqueue(&self, event: &ContentBlockCreatedEvent) -> DatabaseResult<u64> {
+        let Some(block_id) = event.content_block.id else {
+            return Ok(0);
+        };
+
+        let mut queued = 0;
+        for collection_id in &event.collection_ids {
+            let subscription_ids: Vec<String> = self
+                .persistence
+                .get_push_subscriptions(*collection_id)
+                .await?
+                .into_iter()
+                .filter(|s| accepts(s, &event.content_block))
+                .filter_map(|s| s.subscription_id)
+                .collect();
+            if subscription_ids.is_empty() {
+                continue;
+            }
+            queued += self
+                .persistence
+                .create_subscription_deliveries(&subscription_ids, block_id)
+                .await?;
+        }
+
+        Ok(queued)
+    }
+
+    /// Queue deliveries for content blocks created until the hook registry
+    /// is dropped.
+    ///
+    /// Blocks whose events are missed because the receiver lagged behind
+    /// are not pushed.
+    pub async fn listen(self, mut events: broadcast::Receiver<SignalEvent>) {
+        loop {
+            let event = match events.recv().await {
+                Ok(SignalEvent::ContentBlockCreated(event)) => event,
+                Ok(_) => continue,
+                Err(RecvError::Lagged(missed)) => {
+                    warn!(missed, "Push delivery missed content block events");
+                    continue;
+                }
+                Err(RecvError::Closed) => return,
+            };
+
+            match self.enqueue(&event).await {
+                Ok(0) => {}
+                Ok(count) => debug!(count, "Queued push deliveries"),
+                Err(e) => error!(error = %e, "Failed to queue push deliveries"),
+            }
+        }
+    }
+
+    /// Push one batch of queued deliveries, and one batch of prepared
+    /// result sets.
+    ///
+    /// The deliveries of a subscription are pushed in one Inbox Message and
+    /// recorded as delivered or failed; deliveries to subscriptions paused
+    /// meanwhile stay queued. Subscriptions are pushed to concurrently.
+    /// Returns the number of deliveries and result sets delivered.
+    pub async fn run_once(&self) -> DatabaseResult<usize> {
+        let pending = self
+            .persistence
+            .claim_pending_deliveries(self.batch_size, delivery_lease())
+            .await?;
+
+        let mut by_subscription: BTreeMap<String, Vec<SubscriptionDeliveryEntity>> =
+            BTreeMap::new();
+        for delivery in pending {
+            by_subscription
+                .entry(delivery.subscription_id.clone())
+                .or_default()
+                .push(delivery);
+        }
+
+        let pushes: Vec<_> = by_subscription
+            .iter()
+            .map(|(subscription_id, deliveries)| self.push_deliveries(subscription_id, deliveries))
+            .collect();
+        let outcomes: Vec<DatabaseResult<usize>> = futures::stream::iter(pushes)
+            .buffer_unordered(MAX_CONCURRENT_DELIVERIES)
+            .collect()
+            .await;
+
+        let mut delivered = 0;
+        for outcome in outcomes {
+            delivered += outcome?;
+        }
+
+        Ok(delivered + self.push_result_sets().await?)
+    }
+
+    /// Push the results of one batch of prepared result sets to the inboxes
+    /// of the asynchronous polls that asked for them.
+    ///
+    /// Returns the number of result sets delivered.
+    pub async fn push_result_sets(&self) -> DatabaseResult<usize> {
+        let result_sets = self
+            .persistence
+            .claim_result_sets_to_deliver(self.batch_size, delivery_lease())
+            .await?;
+
+        let pushes: Vec<_> = result_sets
+            .iter()
+            .map(|result_set| self.deliver_result_set(result_set))
+            .collect();
+        let outcomes: Vec<DatabaseResult<DeliveryOutcome>> = futures::stream::iter(pushes)
+            .buffer_unordered(MAX_CONCURRENT_DELIVERIES)
+            .collect()
+            .await;
+
+        let mut delivered = 0;
+        for outcome in outcomes {
+            if let DeliveryOutcome::Delivered { .. } = outcome? {
+                delivered += 1;
+            }
+        }
+
+        Ok(delivered)
+    }
+
+    /// Push the results of a prepared result set, one Inbox Message per
+    /// result part, and record the outcome.
+    ///
+    /// The delivery fails at the first part that cannot be sent.
+    async fn deliver_result_set(
+        &self,
+        result_set: &ResultSetEntity,
+    ) -> DatabaseResult<DeliveryOutcome> {
+        let Some(delivery) = result_set.delivery.as_ref() else {
+            return Ok(DeliveryOutcome::Skipped);
+        };
+
+        let (max_result_size, max_result_bytes) =
+            self.result_limits(result_set.collection_id).await?;
+        let sizes: Vec<usize> = self
+            .persistence
+            .get_result_set_content_block_sizes(&result_set.id)
+            .await?
+            .into_iter()
+            .map(|size| usize::try_from(size).unwrap_or(0))
+            .collect();
+        let record_count = sizes.len() as i64;
+
+        let mut attempts = 0;
+        let mut result = Ok(());
+        for (offset, limit) in result_parts(&sizes, max_result_size, max_result_bytes) {
+            let blocks = self
+                .persistence
+                .get_result_set_content_blocks(&result_set.id, offset as i64, Some(limit as i64))
+                .await?;
+            let message = self.client.inbox_message(
+                &blocks,
+                &InboxOptions {
+                    result_id: Some(result_set.id.clone()),
+                    record_count: Some(record_count),
+                    ..Default::default()
+                },
+            );
+
+            let (part_attempts, part_result) = self.send(&delivery.address, &message).await;
+            attempts += part_attempts;
+            result = part_result;
+            if result.is_err() {
+                break;
+            }
+        }
+
+        match result {
+            Ok(()) => {
+                self.persistence
+                    .record_result_set_delivery(
+                        &result_set.id,
+                        delivery_status::DELIVERED,
+                        attempts as i32,
+                        None,
+                    )
+                    .await?;
+                Ok(DeliveryOutcome::Delivered { attempts })
+            }
+            Err(error) => {
+                warn!(result_set = %result_set.id, attempts, error = %error, "Result set delivery failed");
+                self.persistence
+                    .record_result_set_delivery(
+                        &result_set.id,
+                        delivery_status::FAILED,
+                        attempts as i32,
+                        Some(&error),
+                    )
+                    .await?;
+                Ok(DeliveryOutcome::Failed { attempts, error })
+            }
+        }
+    }
+
+    /// Maximum number of blocks and bytes of content of the result parts of
+    /// a collection: the smallest limits of the poll services offering it.
+    async fn result_limits(&self, collection_id: i32) -> DatabaseResult<(usize, usize)> {
+        let services = self
+            .persistence
+            .get_services_for_collection(collection_id, Some(SVC_POLL))
+            .await?;
+        let default_size = usize::try_from(DEFAULT_MAX_RESULT_SIZE).unwrap_or(usize::MAX);
+        if services.is_empty() {
+            return Ok((default_size, self.limits.max_result_bytes));
+        }
+
+        Ok(services
+            .iter()
+            .fold((usize::MAX, usize::MAX), |(size, bytes), service| {
+                let service_size = service
+                    .properties
+                    .get("max_result_size")
+                    .and_then(serde_json::Value::as_u64)
+                    .filter(|&value| value > 0)
+                    .map_or(default_size, |value| {
+                        usize::try_from(value).unwrap_or(usize::MAX)
+                    });
+                let service_bytes = self
+                    .limits
+                    .for_service(&service.properties)
+                    .max_result_bytes;
+                (size.min(service_size), bytes.min(service_bytes))
+            }))
+    }
+
+    /// Run the worker until the task is dropped, pushing a batch every `interval`.
+    pub async fn run(self, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            ticker.tick().await;
+            match self.run_once().await {
+                Ok(0) => {}
+                Ok(count) => debug!(count, "Pushed content to subscribers"),
+                Err(e) => error!(error = %e, "Push delivery worker failed"),
+            }
+        }
+    }
+
+    /// Push the queued deliveries of a subscription, returning how many
+    /// were delivered.
+    async fn push_deliveries(
+        &self,
+        subscription_id: &str,
+        deliveries: &[SubscriptionDeliveryEntity],
+    ) -> DatabaseResult<usize> {
+        let ids: Vec<i32> = deliveries.iter().map(|d| d.id).collect();
+        let Some(subscription) = self.persistence.get_subscription(subscription_id).await? else {
+            return Ok(0);
+        };
+        let Some(push) = subscription.push.as_ref() else {
+            self.persistence
+                .record_subscription_deliveries(
+                    &ids,
+                    delivery_status::FAILED,
+                    0,
+                    Some("Subscription has no push parameters"),
+                )
+                .await?;
+            return Ok(0);
+        };
+
+        let collection_name = self
+            .persistence
+            .get_collections(None)
+            .await?
+            .into_iter()
+            .find(|c| c.id == Some(subscription.collection_id))
+            .map(|c| c.name)
+            .unwrap_or_default();
+        let block_ids: Vec<i32> = deliveries.iter().map(|d| d.content_block_id).collect();
+        let blocks = self
+            .persistence
+            .get_content_blocks_by_ids(&block_ids)
+            .await?;
+        let client = match self.client.with_message_binding(&push.message_binding) {
+            Ok(client) => client,
+            Err(e) => {
+                self.persistence
+                    .record_subscription_deliveries(
+                        &ids,
+                        delivery_status::FAILED,
+                        0,
+                        Some(&e.to_string()),
+                    )
+                    .await?;
+                return Ok(0);
+            }
+        };
+        let message = client.inbox_message(
+            &blocks,
+            &InboxOptions {
+                subscription: Some(InboxSubscription {
+                    collection_name,
+                    subscription_id: subscription.subscription_id.clone().unwrap_or_default(),
+                }),
+                ..Default::default()
+            },
+        );
+
+        match self
+            .deliver(subscription_id, &push.address, &message)
+            .await?
+        {
+            DeliveryOutcome::Delivered { attempts } => {
+                self.persistence
+                    .record_subscription_deliveries(
+                        &ids,
+                        delivery_status::DELIVERED,
+                        attempts as i32,
+                        None,
+                    )
+                    .await?;
+                Ok(ids.len())
+            }
+            DeliveryOutcome::Failed { attempts, error } => {
+                self.persistence
+                    .record_subscription_deliveries(
+                        &ids,
+                        delivery_status::FAILED,
+                        attempts as i32,
+                        Some(&error),
+                    )
+                    .await?;
+                Ok(0)
+            }
+            DeliveryOutcome::Skipped => Ok(0),
+        }
+    }
+
+    /// Deliver a message to the inbox at `address` for a subscription.
+    ///
+    /// Retries failed attempts with exponential backoff and records the
+    /// outcome on the subscription. Only fails if the outcome cannot be
+    /// recorded.
+    pub async fn deliver(
+        &self,
+        subscription_id: &str,
+        address: &str,
+        message: &TaxiiMessage,
+    ) -> DatabaseResult<DeliveryOutcome> {
+        let active = self
+            .persistence
+            .get_subscription(subscription_id)
+            .await?
+            .is_some_and(|s| s.status == subscription_status::ACTIVE);
+        if !active {
+            debug!(subscription = %subscription_id, "Skipping delivery to inactive subscription");
+            return Ok(DeliveryOutcome::Skipped);
+        }
+
+        let (attempts, result) = self.send(address, message).await;
+        let error = match result {
+            Ok(()) => {
+                self.persistence
+                    .record_delivery_success(subscription_id)
+                    .await?;
+                return Ok(DeliveryOutcome::Delivered { attempts });
+            }
+            Err(error) => error,
+        };
+
+        warn!(subscription = %subscription_id, attempts, error = %error, "Delivery failed");
+        let subscription = self
+            .persistence
+            .record_delivery_failure(subscription_id, &error, self.policy.pause_after)
+            .await?;
+        if subscription.is_some_and(|s| s.status == subscription_status::PAUSED) {
+            warn!(subscription = %subscription_id, "Subscription paused after repeated delivery failures");
+        }
+
+        Ok(DeliveryOutcome::Failed { attempts, error })
+    }
+
+    /// Send a message to the inbox at `address`, retrying failed attempts
+    /// with exponential backoff.
+    ///
+    /// Returns the attempts made, and the error of the last attempt if all
+    /// of them failed. Addresses refused by the push policy fail at once.
+    async fn send(&self, address: &str, message: &TaxiiMessage) -> (u32, Result<(), String>) {
+        if let Err(reason) = self.push.check_address(address).await {
+            return (1, Err(format!("Push address refused: {reason}")));
+        }
+
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let error = match self.attempt(address, message).await {
+                Ok(()) => return (attempts, Ok(())),
+                Err(error) => error,
+            };
+
+            if attempts >= self.policy.max_attempts {
+                return (attempts, Err(error));
+            }
+            let delay = self.policy.delay(attempts - 1);
+            debug!(
+                address,
+                attempts,
+                error = %error,
+                "Delivery failed, retrying in {delay:?}"
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// Make one delivery attempt, returning the error if it failed.
+    async fn attempt(&self, address: &str, message: &TaxiiMessage) -> Result<(), String> {
+        self.client
+            .push(address, message)
+            .await
+            .map_err(|e| match e {
+                Taxii1xError::HttpStatus(status) => format!("Inbox responded with HTTP {status}"),
+                Taxii1xError::StatusMessage { status_type, .. } => {
+                    format!("Inbox responded with status {status_type}")
+                }
+                Taxii1xError::UnexpectedResponse(_)
+                | Taxii1xError::XmlParse { .. }
+                | Taxii1xError::UnsupportedVersion(_) => {
+                    "Inbox did not respond with a status message".to_string()
+                }
+                other => other.to_string(),
+            })
+    }
+}
+
+/// Check if a subscription accepts a content block.
+///
+/// Blocks without a content binding are only pushed to subscriptions
+/// without content bindings.
+fn accepts(subscription: &SubscriptionEntity, block: &ContentBlockEntity) -> bool {
+    match block.content_binding.as_ref() {
+        Some(binding) => subscription.accepts_content(binding),
+        None => subscription
+            .params
+            .as_ref()
+            .is_none_or(|p| p.content_bindings.is_empty()),
+    }
+}
+
+/// Lease of the deliveries and result sets claimed by a worker.
+fn delivery_lease() -> chrono::Duration {
+    chrono::Duration::from_std(DELIVERY_LEASE).unwrap_or(chrono::Duration::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{ST_SUCCESS, VID_TAXII_HTTP_10, VID_TAXII_XML_11};
+    use crate::messages::{NS_TAXII_11, get_message_from_xml, tm11};
+    use crate::worker::ResultSetWorker;
+    use axum::Router;
+    use axum::extract::State;
+    use axum::http::StatusCode;
+    use axum::routing::post;
+    use sqlx::PgPool;
+    use std::sync::Mutex;
+    use std::time::Instant;
+    use taxii_core::{
+        CollectionEntity, ContentBindingEntity, PushParametersEntity, ServiceEntity,
+        SubscriptionParameters, collection_type, response_type,
+    };
+    use taxii_db::{DbTaxii1Repository, InMemoryTaxii1Repository, TaxiiPool};
+
+    type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+    const SERVICE_ID: &str = "subscription-a";
+
+    /// Push to the test inboxes on the loopback interface.
+    const LOCAL: PushPolicy = PushPolicy {
+        enabled: true,
+        allow_private_addresses: true,
+    };
+
+    /// Inbox that is down for its first `failures` requests.
+    #[derive(Default)]
+    struct FlappingInbox {
+        failures: usize,
+        requests: Mutex<Vec<Instant>>,
+        bodies: Mutex<Vec<String>>,
+    }
+
+    async fn flapping_inbox(
+        State(inbox): State<Arc<FlappingInbox>>,
+        body: String,
+    ) -> (StatusCode, String) {
+        let seen = {
+            let (Ok(mut requests), Ok(mut bodies)) = (inbox.requests.lock(), inbox.bodies.lock())
+            else {
+                return (StatusCode::INTERNAL_SERVER_ERROR, String::new());
+            };
+            requests.push(Instant::now());
+            bodies.push(body);
+            requests.len()
+        };
+        if seen <= inbox.failures {
+            return (StatusCode::SERVICE_UNAVAILABLE, String::new());
+        }
+
+        let status = tm11::Taxii11Message::StatusMessage(tm11::StatusMessage::new(
+            "status-1", "push-1", ST_SUCCESS,
+        ));
+        (StatusCode::OK, status.to_xml().unwrap_or_default())
+    }
+
+    /// Serve a flapping inbox, returning its address.
+    async fn serve(inbox: Arc<FlappingInbox>) -> Result<String, Box<dyn std::error::Error>> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let address = format!("http://{}/inbox/", listener.local_addr()?);
+        let app = Router::new()
+            .route("/inbox/", post(flapping_inbox))
+            .with_state(inbox);
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        Ok(address)
+    }
+
+    fn message() -> TaxiiMessage {
+        TaxiiMessage::V11(tm11::Taxii11Message::InboxMessage(tm11::InboxMessage {
+            xmlns: NS_TAXII_11.to_string(),
+            message_id: "push-1".to_string(),
+            result_id: None,
+            extended_headers: None,
+            destination_collection_names: Vec::new(),
+            message: None,
+            subscription_information: None,
+            record_count: None,
+            content_blocks: Vec::new(),
+        }))
+    }
+
+    fn policy(max_attempts: u32, pause_after: i32) -> RetryPolicy {
+        RetryPolicy {
+            initial_delay: Duration::from_millis(20),
+            max_delay: Duration::from_millis(50),
+            max_attempts,
+            pause_after,
+        }
+    }
+
+    /// Create a subscription, returning its ID.
+    async fn subscribe<R: Taxii1Repository>(
+        persistence: &R,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let (subscription_id, _) = subscribe_with(persistence, None, None).await?;
+        Ok(subscription_id)
+    }
+
+    /// Create a subscription with parameters and push parameters, returning
+    /// its ID and the ID of its collection.
+    async fn subscribe_with<R: Taxii1Repository>(
+        persistence: &R,
+        params: Option<SubscriptionParameters>,
+        push: Option<PushParametersEntity>,
+    ) -> Result<(String, i32), Box<dyn std::error::Error>> {
+        persistence
+            .update_service(&ServiceEntity {
+                id: Some(SERVICE_ID.to_string()),
+                service_type: "COLLECTION_MANAGEMENT".to_string(),
+                properties: serde_json::json!({}),
+            })
+            .await?;
+        let collection = persistence
+            .create_collection(&CollectionEntity::new("collection-a"))
+            .await?;
+        let collection_id = collection.id.ok_or("collection has no id")?;
+        let subscription = persistence
+            .create_subscription(&SubscriptionEntity {
+                service_id: SERVICE_ID.to_string(),
+                collection_id,
+                subscription_id: None,
+                params,
+                status: subscription_status::ACTIVE.to_string(),
+                delivery: Default::default(),
+                push,
+                account_id: None,
+            })
+            .await?;
+        let subscription_id = subscription
+            .subscription_id
+            .ok_or("subscription has no id")?;
+        Ok((subscription_id, collection_id))
+    }
+
+    fn push_to(address: &str) -> Option<PushParametersEntity> {
+        Some(PushParametersEntity {
+            protocol_binding: VID_TAXII_HTTP_10.to_string(),
+            address: address.to_string(),
+            message_binding: VID_TAXII_XML_11.to_string(),
+        })
+    }
+
+    /// Store a content block in a collection, returning its creation event.
+    async fn create_block<R: Taxii1Repository>(
+        persistence: &R,
+        collection_id: i32,
+        binding: &str,
+        content: &str,
+    ) -> Result<ContentBlockCreatedEvent, Box<dyn std::error::Error>> {
+        let block = persistence
+            .create_content_block(
+                &ContentBlockEntity::new(content, Some(ContentBindingEntity::new(binding))),
+                Some(&[collection_id]),
+                None,
+            )
+            .await?;
+        Ok(ContentBlockCreatedEvent {
+            content_block: block,
+            collection_ids: vec![collection_id],
+            service_id: None,
+        })
+    }
+
+    #[test]
+    fn test_backoff_delays() {
+        let policy = policy(5, 3);
+        let delays: Vec<u128> = (0..4).map(|r| policy.delay(r).as_millis()).collect();
+        assert_eq!(delays, vec![20, 40, 50, 50]);
+        assert_eq!(policy.delay(u32::MAX), policy.max_delay);
+    }
+
+    /// A delivery to a flapping inbox backs off and eventually succeeds.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_flapping_inbox_delivered_after_backoff(pool: PgPool) -> TestResult {
+        let persistence = Arc::new(DbTaxii1Repository::new(TaxiiPool::new(pool)));
+        let subscription_id = subscribe(persistence.as_ref()).await?;
+        let inbox = Arc::new(FlappingInbox {
+            failures: 2,
+            ..Default::default()
+        });
+        let address = serve(inbox.clone()).await?;
+
+        let worker = PushDeliveryWorker::new(persistence.clone())
+            .with_push_policy(LOCAL)
+            .with_policy(policy(5, 3));
+        let outcome = worker
+            .deliver(&subscription_id, &address, &message())
+            .await?;
+        assert_eq!(outcome, DeliveryOutcome::Delivered { attempts: 3 });
+
+        // Retries waited 20ms, then 40ms
+        let requests = inbox.requests.lock().map_err(|e| e.to_string())?.clone();
+        assert_eq!(requests.len(), 3);
+        assert!(requests[1] - requests[0] >= Duration::from_millis(20));
+        assert!(requests[2] - requests[1] >= Duration::from_millis(40));
+
+        let subscription = persistence
+            .get_subscription(&subscription_id)
+            .await?
+            .ok_or("subscription not found")?;
+        assert!(subscription.delivery.last_success.is_some());
+        assert_eq!(subscription.delivery.consecutive_failures, 0);
+        assert_eq!(subscription.status, subscription_status::ACTIVE);
+
+        Ok(())
+    }
+
+    /// Repeatedly failed deliveries pause the subscription.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_repeated_failures_pause_subscription(pool: PgPool) -> TestResult {
+        let persistence = Arc::new(DbTaxii1Repository::new(TaxiiPool::new(pool)));
+        let subscription_id = subscribe(persistence.as_ref()).await?;
+        let inbox = Arc::new(FlappingInbox {
+            failures: 4,
+            ..Default::default()
+        });
+        let address = serve(inbox.clone()).await?;
+
+        let worker = PushDeliveryWorker::new(persistence.clone())
+            .with_push_policy(LOCAL)
+            .with_policy(policy(2, 2));
+        let outcome = worker
+            .deliver(&subscription_id, &address, &message())
+            .await?;
+        assert!(matches!(
+            outcome,
+            DeliveryOutcome::Failed { attempts: 2, .. }
+        ));
+
+        let subscription = persistence
+            .get_subscription(&subscription_id)
+            .await?
+            .ok_or("subscription not found")?;
+        assert_eq!(subscription.delivery.consecutive_failures, 1);
+        assert_eq!(
+            subscription.delivery.last_error.as_deref(),
+            Some("Inbox responded with HTTP 503 Service Unavailable")
+        );
+        assert_eq!(subscription.status, subscription_status::ACTIVE);
+
+        worker
+            .deliver(&subscription_id, &address, &message())
+            .await?;
+        let subscription = persistence
+            .get_subscription(&subscription_id)
+            .await?
+            .ok_or("subscription not found")?;
+        assert_eq!(subscription.delivery.consecutive_failures, 2);
+        assert_eq!(subscription.status, subscription_status::PAUSED);
+
+        // Paused subscriptions are not delivered to, even though the inbox
+        // is back up
+        let outcome = worker
+            .deliver(&subscription_id, &address, &message())
+            .await?;
+        assert_eq!(outcome, DeliveryOutcome::Skipped);
+        assert_eq!(inbox.requests.lock().map_err(|e| e.to_string())?.len(), 4);
+
+        // Resuming resets the failure count
+        let resumed = persistence
+            .update_subscription(&SubscriptionEntity {
+                status: subscription_status::ACTIVE.to_string(),
+                ..subscription
+            })
+            .await?;
+        assert_eq!(resumed.delivery.consecutive_failures, 0);
+        let outcome = worker
+            .deliver(&subscription_id, &address, &message())
+            .await?;
+        assert_eq!(outcome, DeliveryOutcome::Delivered { attempts: 1 });
+
+        Ok(())
+    }
+
+    /// New content is pushed to the subscriber's inbox in an Inbox Message.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_new_content_pushed_to_subscriber(pool: PgPool) -> TestResult {
+        let persistence = Arc::new(DbTaxii1Repository::new(TaxiiPool::new(pool)));
+        let inbox = Arc::new(FlappingInbox::default());
+        let address = serve(inbox.clone()).await?;
+        let (subscription_id, collection_id) =
+            subscribe_with(persistence.as_ref(), None, push_to(&address)).await?;
+
+        let worker = PushDeliveryWorker::new(persistence.clone())
+            .with_push_policy(LOCAL)
+            .with_policy(policy(2, 3));
+        let event = create_block(
+            persistence.as_ref(),
+            collection_id,
+            "urn:stix.mitre.org:xml:1.1.1",
+            "<stix:STIX_Package/>",
+        )
+        .await?;
+        assert_eq!(worker.enqueue(&event).await?, 1);
+        // Queuing the same block again is a no-op
+        assert_eq!(worker.enqueue(&event).await?, 0);
+
+        assert_eq!(worker.run_once().await?, 1);
+        assert_eq!(worker.run_once().await?, 0);
+
+        let bodies = inbox.bodies.lock().map_err(|e| e.to_string())?.clone();
+        assert_eq!(bodies.len(), 1);
+        let TaxiiMessage::V11(tm11::Taxii11Message::InboxMessage(message)) =
+            get_message_from_xml(&bodies[0])?
+        else {
+            return Err("expected a TAXII 1.1 Inbox Message".into());
+        };
+        assert_eq!(message.content_blocks.len(), 1);
+        assert_eq!(message.content_blocks[0].content, "<stix:STIX_Package/>");
+        let information = message
+            .subscription_information
+            .ok_or("missing subscription information")?;
+        assert_eq!(information.subscription_id, subscription_id);
+        assert_eq!(information.collection_name, "collection-a");
+
+        let deliveries = persistence
+            .get_subscription_deliveries(None, Some(&subscription_id), 10)
+            .await?;
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].status, delivery_status::DELIVERED);
+        assert_eq!(deliveries[0].attempts, 1);
+
+        Ok(())
+    }
+
+    /// Only content matching the subscription's content bindings is queued,
+    /// and paused subscriptions keep their deliveries queued.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_bindings_and_pause_respected(pool: PgPool) -> TestResult {
+        let persistence = Arc::new(DbTaxii1Repository::new(TaxiiPool::new(pool)));
+        let inbox = Arc::new(FlappingInbox::default());
+        let address = serve(inbox.clone()).await?;
+        let params = SubscriptionParameters {
+            response_type: response_type::FULL.to_string(),
+            content_bindings: vec![ContentBindingEntity::new("urn:stix.mitre.org:xml:1.2")],
+        };
+        let (subscription_id, collection_id) =
+            subscribe_with(persistence.as_ref(), Some(params), push_to(&address)).await?;
+        let worker = PushDeliveryWorker::new(persistence.clone())
+            .with_push_policy(LOCAL)
+            .with_policy(policy(2, 3));
+
+        let other =
+            create_block(persistence.as_ref(), collection_id, "urn:custom:csv", "a,b").await?;
+        assert_eq!(worker.enqueue(&other).await?, 0);
+
+        let matching = create_block(
+            persistence.as_ref(),
+            collection_id,
+            "urn:stix.mitre.org:xml:1.2",
+            "<stix:STIX_Package/>",
+        )
+        .await?;
+        assert_eq!(worker.enqueue(&matching).await?, 1);
+
+        // Paused subscriptions get no new content, and their queued
+        // deliveries wait until they are resumed
+        let subscription = persistence
+            .get_subscription(&subscription_id)
+            .await?
+            .ok_or("subscription not found")?;
+        let subscription = persistence
+            .update_subscription(&SubscriptionEntity {
+                status: subscription_status::PAUSED.to_string(),
+                ..subscription
+            })
+            .await?;
+        let paused = create_block(
+            persistence.as_ref(),
+            collection_id,
+            "urn:stix.mitre.org:xml:1.2",
+            "<stix:STIX_Package/>",
+        )
+        .await?;
+        assert_eq!(worker.enqueue(&paused).await?, 0);
+        assert_eq!(worker.run_once().await?, 0);
+        assert!(inbox.bodies.lock().map_err(|e| e.to_string())?.is_empty());
+
+        persistence
+            .update_subscription(&SubscriptionEntity {
+                status: subscription_status::ACTIVE.to_string(),
+                ..subscription
+            })
+            .await?;
+        assert_eq!(worker.run_once().await?, 1);
+        assert_eq!(inbox.bodies.lock().map_err(|e| e.to_string())?.len(), 1);
+
+        Ok(())
+    }
+
+    /// Failed deliveries are logged and delivered once queued again.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_failed_delivery_requeued(pool: PgPool) -> TestResult {
+        let persistence = Arc::new(DbTaxii1Repository::new(TaxiiPool::new(pool)));
+        let inbox = Arc::new(FlappingInbox {
+            failures: 2,
+            ..Default::default()
+        });
+        let address = serve(inbox.clone()).await?;
+        let (subscription_id, collection_id) =
+            subscribe_with(persistence.as_ref(), None, push_to(&address)).await?;
+        let worker = PushDeliveryWorker::new(persistence.clone())
+            .with_push_policy(LOCAL)
+            .with_policy(policy(2, 3));
+
+        let event = create_block(
+            persistence.as_ref(),
+            collection_id,
+            "urn:stix.mitre.org:xml:1.1.1",
+            "<stix:STIX_Package/>",
+        )
+        .await?;
+        worker.enqueue(&event).await?;
+        assert_eq!(worker.run_once().await?, 0);
+
+        let failed = persistence
+            .get_subscription_deliveries(Some(delivery_status::FAILED), None, 10)
+            .await?;
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].attempts, 2);
+        assert_eq!(
+            failed[0].last_error.as_deref(),
+            Some("Inbox responded with HTTP 503 Service Unavailable")
+        );
+        // Failed deliveries are not retried on their own
+        assert_eq!(worker.run_once().await?, 0);
+
+        let requeued = persistence
+            .requeue_failed_deliveries(&[], Some(&subscription_id))
+            .await?;
+        assert_eq!(requeued, 1);
+        assert_eq!(worker.run_once().await?, 1);
+
+        let deliveries = persistence
+            .get_subscription_deliveries(None, Some(&subscription_id), 10)
+            .await?;
+        assert_eq!(deliveries[0].status, delivery_status::DELIVERED);
+        assert_eq!(deliveries[0].attempts, 3);
+
+        Ok(())
+    }
+
+    /// Addresses refused by the push policy are not pushed to, nor retried.
+    #[tokio::test]
+    async fn test_private_address_refused() -> TestResult {
+        let persistence = Arc::new(InMemoryTaxii1Repository::new());
+        let subscription_id = subscribe(persistence.as_ref()).await?;
+        let inbox = Arc::new(FlappingInbox::default());
+        let address = serve(inbox.clone()).await?;
+
+        let worker = PushDeliveryWorker::new(persistence.clone()).with_policy(policy(3, 3));
+        let outcome = worker
+            .deliver(&subscription_id, &address, &message())
+            .await?;
+        let DeliveryOutcome::Failed { attempts, error } = outcome else {
+            return Err(format!("expected a failed delivery, got {outcome:?}").into());
+        };
+        assert_eq!(attempts, 1);
+        assert!(
+            error.starts_with("Push address refused: 127.0.0.1 resolves to the non-public"),
+            "{error}"
+        );
+        assert!(inbox.requests.lock().map_err(|_| "poisoned")?.is_empty());
+        Ok(())
+    }
+
+    /// Workers running at the same time push each queued delivery once.
+    #[tokio::test]
+    async fn test_concurrent_workers_push_once() -> TestResult {
+        let persistence = Arc::new(InMemoryTaxii1Repository::new());
+        let inbox = Arc::new(FlappingInbox::default());
+        let address = serve(inbox.clone()).await?;
+        let (_, collection_id) =
+            subscribe_with(persistence.as_ref(), None, push_to(&address)).await?;
+
+        let worker = |persistence: Arc<InMemoryTaxii1Repository>| {
+            PushDeliveryWorker::new(persistence)
+                .with_push_policy(LOCAL)
+                .with_policy(policy(2, 3))
+        };
+        let (first, second) = (worker(persistence.clone()), worker(persistence.clone()));
+        for content in ["<a/>", "<b/>", "<c/>"] {
+            let event = create_block(
+                persistence.as_ref(),
+                collection_id,
+                "urn:stix.mitre.org:xml:1.1.1",
+                content,
+            )
+            .await?;
+            first.enqueue(&event).await?;
+        }
+
+        let (pushed, other) = tokio::join!(first.run_once(), second.run_once());
+        assert_eq!(pushed? + other?, 3);
+        assert_eq!(inbox.bodies.lock().map_err(|_| "poisoned")?.len(), 1);
+        Ok(())
+    }
+
+    /// Repository with a collection offered by a poll service with the
+    /// given properties, holding blocks with the given contents, and a
+    /// pending result set of all of them pushed to `address`.
+    async fn result_set_to_push(
+        contents: &[&str],
+        poll_properties: serde_json::Value,
+        address: &str,
+    ) -> Result<InMemoryTaxii1Repository, Box<dyn std::error::Error>> {
+        let mut builder = InMemoryTaxii1Repository::builder()
+            .service(ServiceEntity {
+                id: Some("poll-a".to_string()),
+                service_type: SVC_POLL.to_string(),
+                properties: poll_properties,
+            })
+            .collection(
+                CollectionEntity {
+                    collection_type: collection_type::DATA_SET.to_string(),
+                    ..CollectionEntity::new("collection-a")
+                },
+                &["poll-a"],
+            );
+        for content in contents {
+            builder = builder.content_block(
+                ContentBlockEntity::new(
+                    *content,
+                    Some(ContentBindingEntity::new("urn:stix.mitre.org:xml:1.1.1")),
+                ),
+                &["collection-a"],
+            );
+        }
+        let persistence = builder.build()?;
+        persistence
+            .create_result_set(&ResultSetEntity {
+                id: "result-set-a".to_string(),
+                collection_id: 1,
+                content_bindings: Vec::new(),
+                timeframe: (None, None),
+                status: taxii_core::result_set_status::PENDING.to_string(),
+                content_block_count: None,
+                expires_at: None,
+                delivery: push_to(address),
+                delivery_status: Some(delivery_status::PENDING.to_string()),
+            })
+            .await?;
+        Ok(persistence)
+    }
+
+    /// The results of an asynchronous poll are pushed once its content is
+    /// ready, after retrying a flapping inbox.
+    #[tokio::test]
+    async fn test_result_set_pushed_when_ready() -> TestResult {
+        let inbox = Arc::new(FlappingInbox {
+            failures: 1,
+            ..Default::default()
+        });
+        let address = serve(inbox.clone()).await?;
+        let persistence =
+            Arc::new(result_set_to_push(&["<block/>"], serde_json::json!({}), &address).await?);
+
+        let preparer = ResultSetWorker::new(persistence.clone());
+        let pusher = PushDeliveryWorker::new(persistence.clone())
+            .with_push_policy(LOCAL)
+            .with_policy(policy(3, 3));
+
+        // Nothing is pushed while the content is not ready
+        persistence.set_results_ready(false);
+        assert_eq!(preparer.run_once().await?, 0);
+        assert_eq!(pusher.run_once().await?, 0);
+        assert!(inbox.bodies.lock().map_err(|_| "poisoned")?.is_empty());
+
+        persistence.set_results_ready(true);
+        assert_eq!(preparer.run_once().await?, 1);
+        assert_eq!(pusher.run_once().await?, 1);
+
+        let bodies = inbox.bodies.lock().map_err(|_| "poisoned")?.clone();
+        assert_eq!(bodies.len(), 2);
+        match get_message_from_xml(&bodies[1])? {
+            TaxiiMessage::V11(tm11::Taxii11Message::InboxMessage(message)) => {
+                assert_eq!(message.result_id.as_deref(), Some("result-set-a"));
+                assert_eq!(message.content_blocks.len(), 1);
+                assert_eq!(message.content_blocks[0].content, "<block/>");
+            }
+            other => return Err(format!("expected inbox message, got {other:?}").into()),
+        }
+
+        // Delivered results are not pushed again
+        assert_eq!(pusher.run_once().await?, 0);
+        assert_eq!(inbox.bodies.lock().map_err(|_| "poisoned")?.len(), 2);
+        Ok(())
+    }
+
+    /// Pushed results are split into the parts of the poll service of the
+    /// collection, by number of blocks and by bytes of content.
+    #[tokio::test]
+    async fn test_result_set_pushed_in_parts() -> TestResult {
+        let inbox = Arc::new(FlappingInbox::default());
+        let address = serve(inbox.clone()).await?;
+        let persistence = Arc::new(
+            result_set_to_push(
+                &["<a/>", "<b/>", "<c/>", "<dddddddd/>"],
+                serde_json::json!({"max_result_size": 2, "max_result_bytes": 10}),
+                &address,
+            )
+            .await?,
+        );
+        ResultSetWorker::new(persistence.clone()).run_once().await?;
+        let pusher = PushDeliveryWorker::new(persistence.clone()).with_push_policy(LOCAL);
+        assert_eq!(pusher.run_once().await?, 1);
+
+        let mut parts = Vec::new();
+        for body in inbox.bodies.lock().map_err(|_| "poisoned")?.iter() {
+            match get_message_from_xml(body)? {
+                TaxiiMessage::V11(tm11::Taxii11Message::InboxMessage(message)) => {
+                    assert_eq!(message.result_id.as_deref(), Some("result-set-a"));
+                    assert_eq!(message.record_count.map(|c| c.record_count), Some(4));
+                    parts.push(
+                        message
+                            .content_blocks
+                            .into_iter()
+                            .map(|block| block.content)
+                            .collect::<Vec<_>>(),
+                    );
+                }
+                other => return Err(format!("expected inbox message, got {other:?}").into()),
+            }
+        }
+        assert_eq!(
+            parts,
+            vec![vec!["<a/>", "<b/>"], vec!["<c/>"], vec!["<dddddddd/>"]]
+        );
+        Ok(())
+    }
+}
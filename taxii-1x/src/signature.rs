@@ -0,0 +1,1259 @@
+//! XML signature verification of inbound content.
+//!
+//! Content blocks may carry an enveloped XML signature (XML-DSig): a
+//! `ds:Signature` child of the document element whose references cover the
+//! whole document. [`verify_signature`] checks such a signature against a
+//! collection's trusted certificates.
+//!
+//! Supported are:
+//!
+//! - Inclusive and exclusive C14N 1.0, with or without comments, as
+//!   canonicalization method and reference transform.
+//! - References to the whole document, as `URI=""` or the ID of the
+//!   document element, with the enveloped-signature transform.
+//! - SHA-256, SHA-384 and SHA-512 digests.
+//! - RSA (PKCS #1 v1.5, keys of 2048 bits or more) signatures with SHA-256,
+//!   SHA-384 and SHA-512, and ECDSA signatures on P-256 and P-384.
+//!
+//! SHA-1, XPath and XSLT transforms, and documents with a DTD are rejected.
+//!
+//! Trusted certificates are pinned: the signer must be one of them, matched
+//! by SHA-256 fingerprint. Certificate chains, validity periods and
+//! revocation are not checked.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use quick_xml::events::attributes::Attribute;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::name::{Namespace, ResolveResult};
+use quick_xml::{NsReader, Reader};
+use ring::digest;
+use rustls_pki_types::CertificateDer;
+use rustls_pki_types::pem::PemObject;
+use thiserror::Error;
+use webpki::{EndEntityCert, ring as algorithms};
+
+/// XML-DSig namespace.
+pub const DSIG_NAMESPACE: &str = "http://www.w3.org/2000/09/xmldsig#";
+
+/// Exclusive canonicalization namespace (of `InclusiveNamespaces`).
+const EXC_C14N_NAMESPACE: &str = "http://www.w3.org/2001/10/xml-exc-c14n#";
+
+/// Namespace bound to the `xml` prefix.
+const XML_NAMESPACE: &str = "http://www.w3.org/XML/1998/namespace";
+
+const C14N: &str = "http://www.w3.org/TR/2001/REC-xml-c14n-20010315";
+const C14N_WITH_COMMENTS: &str = "http://www.w3.org/TR/2001/REC-xml-c14n-20010315#WithComments";
+const EXC_C14N: &str = "http://www.w3.org/2001/10/xml-exc-c14n#";
+const EXC_C14N_WITH_COMMENTS: &str = "http://www.w3.org/2001/10/xml-exc-c14n#WithComments";
+const ENVELOPED_SIGNATURE: &str = "http://www.w3.org/2000/09/xmldsig#enveloped-signature";
+
+/// Why a signature does not verify.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum SignatureError {
+    /// The content has no enveloped signature.
+    #[error("content is not signed")]
+    Unsigned,
+
+    /// The content or its signature is not well-formed.
+    #[error("malformed signature: {0}")]
+    Malformed(String),
+
+    /// The signature uses an algorithm or construct that is not supported.
+    #[error("unsupported {0}")]
+    Unsupported(String),
+
+    /// A reference digest does not match the content.
+    #[error("digest of reference '{0}' does not match, the content was modified")]
+    DigestMismatch(String),
+
+    /// The signature value does not verify.
+    #[error("signature value does not verify with a trusted certificate")]
+    InvalidSignature,
+
+    /// The signing certificate is not trusted.
+    #[error("signer certificate {0} is not trusted")]
+    UntrustedSigner(String),
+
+    /// A trusted certificate cannot be used.
+    #[error("invalid trusted certificate: {0}")]
+    InvalidCertificate(String),
+}
+
+/// Certificates trusted to sign content.
+#[derive(Debug, Clone, Default)]
+pub struct TrustedCertificates {
+    certificates: Vec<(String, CertificateDer<'static>)>,
+}
+
+impl TrustedCertificates {
+    /// Parse a bundle of PEM certificates.
+    pub fn from_pem(pem: &str) -> Result<Self, SignatureError> {
+        let mut certificates = Vec::new();
+        for certificate in CertificateDer::pem_slice_iter(pem.as_bytes()) {
+            let certificate =
+                certificate.map_err(|e| SignatureError::InvalidCertificate(e.to_string()))?;
+            EndEntityCert::try_from(&certificate)
+                .map_err(|e| SignatureError::InvalidCertificate(e.to_string()))?;
+            certificates.push((fingerprint(&certificate), certificate));
+        }
+        Ok(Self { certificates })
+    }
+
+    /// Whether no certificate is trusted.
+    pub fn is_empty(&self) -> bool {
+        self.certificates.is_empty()
+    }
+
+    /// Whether a certificate is trusted.
+    pub fn contains(&self, fingerprint: &str) -> bool {
+        self.certificates.iter().any(|(f, _)| f == fingerprint)
+    }
+}
+
+/// Signer of verified content.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signer {
+    /// SHA-256 fingerprint (hex) of the signing certificate.
+    pub fingerprint: String,
+}
+
+/// SHA-256 fingerprint (hex) of a DER certificate.
+pub fn fingerprint(certificate: &[u8]) -> String {
+    hex::encode(digest::digest(&digest::SHA256, certificate))
+}
+
+/// Whether content is an XML document with an enveloped signature.
+///
+/// Content that is not XML is not signed.
+pub fn is_signed(content: &[u8]) -> bool {
+    let Ok(xml) = std::str::from_utf8(content) else {
+        return false;
+    };
+    let is_signature = |ns: &ResolveResult<'_>, start: &BytesStart<'_>| {
+        start.local_name().as_ref() == b"Signature"
+            && matches!(ns, ResolveResult::Bound(Namespace(ns)) if *ns == DSIG_NAMESPACE.as_bytes())
+    };
+
+    // Only the children of the document element are looked at
+    let mut reader = NsReader::from_str(xml);
+    let mut depth = 0usize;
+    loop {
+        match reader.read_resolved_event() {
+            Ok((ns, Event::Start(start))) => {
+                if depth == 1 && is_signature(&ns, &start) {
+                    return true;
+                }
+                depth += 1;
+            }
+            Ok((ns, Event::Empty(start))) => {
+                if depth == 1 && is_signature(&ns, &start) {
+                    return true;
+                }
+            }
+            Ok((_, Event::End(_))) => {
+                depth = depth.saturating_sub(1);
+                if depth == 0 {
+                    return false;
+                }
+            }
+            Ok((_, Event::Eof)) | Err(_) => return false,
+            Ok(_) => {}
+        }
+    }
+}
+
+/// Verify the enveloped signature of content.
+///
+/// Returns the signer if the signature verifies and the signer is one of
+/// the trusted certificates.
+pub fn verify_signature(
+    content: &[u8],
+    trusted: &TrustedCertificates,
+) -> Result<Signer, SignatureError> {
+    let document = Document::parse(content)?;
+    let mut signatures = document
+        .root
+        .child_elements()
+        .filter(|e| e.is(DSIG_NAMESPACE, "Signature"));
+    let signature = signatures.next().ok_or(SignatureError::Unsigned)?;
+    if signatures.next().is_some() {
+        return Err(malformed("more than one signature"));
+    }
+
+    let signed_info = signature.required_child(DSIG_NAMESPACE, "SignedInfo")?;
+    let canonicalization = Canonicalization::from_element(
+        signed_info.required_child(DSIG_NAMESPACE, "CanonicalizationMethod")?,
+    )?;
+    let method = signed_info
+        .required_child(DSIG_NAMESPACE, "SignatureMethod")?
+        .required_attribute("Algorithm")?;
+    let signature_value = decode_base64(
+        &signature
+            .required_child(DSIG_NAMESPACE, "SignatureValue")?
+            .text(),
+        "SignatureValue",
+    )?;
+
+    // The signer is the trusted certificate included in the signature or,
+    // without one, any trusted certificate
+    let included: Vec<CertificateDer<'static>> = signature
+        .child(DSIG_NAMESPACE, "KeyInfo")
+        .into_iter()
+        .flat_map(|key_info| {
+            key_info
+                .child_elements()
+                .filter(|e| e.is(DSIG_NAMESPACE, "X509Data"))
+        })
+        .flat_map(|data| {
+            data.child_elements()
+                .filter(|e| e.is(DSIG_NAMESPACE, "X509Certificate"))
+        })
+        .map(|certificate| {
+            decode_base64(&certificate.text(), "X509Certificate").map(CertificateDer::from)
+        })
+        .collect::<Result<_, _>>()?;
+    let candidates: Vec<&CertificateDer<'static>> = if included.is_empty() {
+        trusted.certificates.iter().map(|(_, c)| c).collect()
+    } else {
+        let candidates: Vec<_> = included
+            .iter()
+            .filter(|c| trusted.contains(&fingerprint(c)))
+            .collect();
+        if candidates.is_empty() {
+            return Err(SignatureError::UntrustedSigner(fingerprint(&included[0])));
+        }
+        candidates
+    };
+
+    // Reference validation precedes signature validation, as in XML-DSig
+    // core validation
+    let mut references = signed_info
+        .child_elements()
+        .filter(|e| e.is(DSIG_NAMESPACE, "Reference"))
+        .peekable();
+    if references.peek().is_none() {
+        return Err(malformed("SignedInfo has no Reference"));
+    }
+    for reference in references {
+        verify_reference(&document, signature, reference)?;
+    }
+
+    let signed = signed_info_octets(&document, signature, signed_info, &canonicalization);
+    let mut signer = None;
+    for certificate in candidates {
+        if verify_signature_value(certificate, method, &signed, &signature_value)? {
+            signer = Some(certificate);
+            break;
+        }
+    }
+    let signer = signer.ok_or(SignatureError::InvalidSignature)?;
+
+    Ok(Signer {
+        fingerprint: fingerprint(signer),
+    })
+}
+
+fn malformed(message: impl Into<String>) -> SignatureError {
+    SignatureError::Malformed(message.into())
+}
+
+fn decode_base64(text: &str, element: &str) -> Result<Vec<u8>, SignatureError> {
+    let compact: String = text.chars().filter(|c| !c.is_ascii_whitespace()).collect();
+    BASE64
+        .decode(compact)
+        .map_err(|e| malformed(format!("{element} is not base64: {e}")))
+}
+
+/// Canonical SignedInfo, the octets the signature value signs.
+fn signed_info_octets(
+    document: &Document,
+    signature: &Element,
+    signed_info: &Element,
+    canonicalization: &Canonicalization,
+) -> Vec<u8> {
+    let ancestors = [&document.root, signature];
+    let scope = ancestors
+        .iter()
+        .fold(Scope::new(), |scope, element| element.scope(&scope));
+    let inherited = if canonicalization.exclusive {
+        Vec::new()
+    } else {
+        inherited_xml_attributes(&ancestors)
+    };
+
+    let mut renderer = Renderer::new(canonicalization, None);
+    renderer.element(signed_info, &scope, &Scope::new(), &inherited);
+    renderer.out.into_bytes()
+}
+
+/// Attributes in the `xml` namespace of ancestors, nearest first.
+fn inherited_xml_attributes(ancestors: &[&Element]) -> Vec<(String, String)> {
+    let mut inherited: Vec<(String, String)> = Vec::new();
+    for element in ancestors.iter().rev() {
+        for (name, value) in &element.attributes {
+            if name.starts_with("xml:") && !inherited.iter().any(|(n, _)| n == name) {
+                inherited.push((name.clone(), value.clone()));
+            }
+        }
+    }
+    inherited
+}
+
+/// Check the signature value with a certificate.
+///
+/// Returns whether the signature verifies with the certificate's key.
+fn verify_signature_value(
+    certificate: &CertificateDer<'_>,
+    method: &str,
+    signed: &[u8],
+    signature_value: &[u8],
+) -> Result<bool, SignatureError> {
+    let (ecdsa, candidates) = match method {
+        "http://www.w3.org/2001/04/xmldsig-more#rsa-sha256" => {
+            (false, vec![algorithms::RSA_PKCS1_2048_8192_SHA256])
+        }
+        "http://www.w3.org/2001/04/xmldsig-more#rsa-sha384" => {
+            (false, vec![algorithms::RSA_PKCS1_2048_8192_SHA384])
+        }
+        "http://www.w3.org/2001/04/xmldsig-more#rsa-sha512" => {
+            (false, vec![algorithms::RSA_PKCS1_2048_8192_SHA512])
+        }
+        "http://www.w3.org/2001/04/xmldsig-more#ecdsa-sha256" => (
+            true,
+            vec![algorithms::ECDSA_P256_SHA256, algorithms::ECDSA_P384_SHA256],
+        ),
+        "http://www.w3.org/2001/04/xmldsig-more#ecdsa-sha384" => (
+            true,
+            vec![algorithms::ECDSA_P384_SHA384, algorithms::ECDSA_P256_SHA384],
+        ),
+        _ => {
+            return Err(SignatureError::Unsupported(format!(
+                "signature method '{method}'"
+            )));
+        }
+    };
+
+    // XML-DSig ECDSA signature values are r and s concatenated, webpki
+    // verifies the DER encoding
+    let signature_value = if ecdsa {
+        ecdsa_signature_der(signature_value)
+            .ok_or_else(|| malformed("ECDSA SignatureValue has an odd length"))?
+    } else {
+        signature_value.to_vec()
+    };
+
+    let certificate = EndEntityCert::try_from(certificate)
+        .map_err(|e| SignatureError::InvalidCertificate(e.to_string()))?;
+    for algorithm in candidates {
+        match certificate.verify_signature(algorithm, signed, &signature_value) {
+            Ok(()) => return Ok(true),
+            Err(webpki::Error::InvalidSignatureForPublicKey) => return Ok(false),
+            // The algorithm does not match the key, try the next one
+            Err(_) => {}
+        }
+    }
+    Ok(false)
+}
+
+/// DER encoding of an ECDSA signature given as r and s concatenated.
+fn ecdsa_signature_der(raw: &[u8]) -> Option<Vec<u8>> {
+    if raw.is_empty() || raw.len() % 2 != 0 {
+        return None;
+    }
+    let (r, s) = raw.split_at(raw.len() / 2);
+
+    let mut body = Vec::new();
+    for integer in [r, s] {
+        let start = integer
+            .iter()
+            .position(|b| *b != 0)
+            .unwrap_or(integer.len() - 1);
+        let integer = &integer[start..];
+        let pad = integer[0] & 0x80 != 0;
+        body.push(0x02);
+        push_der_length(&mut body, integer.len() + usize::from(pad));
+        if pad {
+            body.push(0);
+        }
+        body.extend_from_slice(integer);
+    }
+
+    let mut der = vec![0x30];
+    push_der_length(&mut der, body.len());
+    der.extend(body);
+    Some(der)
+}
+
+fn push_der_length(out: &mut Vec<u8>, length: usize) {
+    if length < 0x80 {
+        out.push(length as u8);
+    } else {
+        let bytes: Vec<u8> = length
+            .to_be_bytes()
+            .into_iter()
+            .skip_while(|b| *b == 0)
+            .collect();
+        out.push(0x80 | bytes.len() as u8);
+        out.extend(bytes);
+    }
+}
+
+/// Check that the digest of a reference matches.
+fn verify_reference(
+    document: &Document,
+    signature: &Element,
+    reference: &Element,
+) -> Result<(), SignatureError> {
+    let octets = reference_octets(document, signature, reference)?;
+
+    let digest_method = reference
+        .required_child(DSIG_NAMESPACE, "DigestMethod")?
+        .required_attribute("Algorithm")?;
+    let algorithm = match digest_method {
+        "http://www.w3.org/2001/04/xmlenc#sha256" => &digest::SHA256,
+        "http://www.w3.org/2001/04/xmldsig-more#sha384" => &digest::SHA384,
+        "http://www.w3.org/2001/04/xmlenc#sha512" => &digest::SHA512,
+        _ => {
+            return Err(SignatureError::Unsupported(format!(
+                "digest method '{digest_method}'"
+            )));
+        }
+    };
+    let expected = decode_base64(
+        &reference
+            .required_child(DSIG_NAMESPACE, "DigestValue")?
+            .text(),
+        "DigestValue",
+    )?;
+
+    if digest::digest(algorithm, &octets).as_ref() != expected.as_slice() {
+        let uri = reference.attribute("URI").unwrap_or_default();
+        return Err(SignatureError::DigestMismatch(uri.to_string()));
+    }
+    Ok(())
+}
+
+/// Canonical content of a reference, the octets its digest is taken of.
+///
+/// References must cover the whole document.
+fn reference_octets(
+    document: &Document,
+    signature: &Element,
+    reference: &Element,
+) -> Result<Vec<u8>, SignatureError> {
+    let uri = reference.attribute("URI").unwrap_or_default();
+    let whole_document = match uri.strip_prefix('#') {
+        None if uri.is_empty() => true,
+        Some(id) if document.root.id() == Some(id) => false,
+        _ => {
+            return Err(SignatureError::Unsupported(format!(
+                "reference '{uri}', references must cover the whole document"
+            )));
+        }
+    };
+
+    let mut enveloped = false;
+    let mut canonicalization = None;
+    if let Some(transforms) = reference.child(DSIG_NAMESPACE, "Transforms") {
+        for transform in transforms
+            .child_elements()
+            .filter(|e| e.is(DSIG_NAMESPACE, "Transform"))
+        {
+            if canonicalization.is_some() {
+                return Err(SignatureError::Unsupported(
+                    "transform after canonicalization".to_string(),
+                ));
+            }
+            if transform.required_attribute("Algorithm")? == ENVELOPED_SIGNATURE {
+                enveloped = true;
+            } else {
+                canonicalization = Some(Canonicalization::from_element(transform)?);
+            }
+        }
+    }
+
+    // Same-document references never include comments; without a
+    // canonicalization transform the node set is canonicalized with
+    // inclusive C14N
+    let mut canonicalization = canonicalization.unwrap_or_default();
+    canonicalization.with_comments = false;
+
+    let mut renderer = Renderer::new(&canonicalization, enveloped.then_some(signature));
+    if whole_document {
+        renderer.document(document);
+    } else {
+        renderer.element(&document.root, &Scope::new(), &Scope::new(), &[]);
+    }
+    Ok(renderer.out.into_bytes())
+}
+
+// ============================================================================
+// Canonicalization
+// ============================================================================
+
+/// In-scope namespaces by prefix (`""` for the default namespace).
+type Scope = BTreeMap<String, String>;
+
+/// Canonical XML (C14N 1.0) variant.
+#[derive(Debug, Clone, Default)]
+struct Canonicalization {
+    exclusive: bool,
+    with_comments: bool,
+    /// Prefixes treated as with inclusive C14N (`""` for the default
+    /// namespace), for exclusive C14N.
+    inclusive_prefixes: Vec<String>,
+}
+
+impl Canonicalization {
+    /// Canonicalization of a `CanonicalizationMethod` or `Transform` element.
+    fn from_element(element: &Element) -> Result<Self, SignatureError> {
+        let algorithm = element.required_attribute("Algorithm")?;
+        let (exclusive, with_comments) = match algorithm {
+            C14N => (false, false),
+            C14N_WITH_COMMENTS => (false, true),
+            EXC_C14N => (true, false),
+            EXC_C14N_WITH_COMMENTS => (true, true),
+            _ => {
+                return Err(SignatureError::Unsupported(format!(
+                    "canonicalization or transform '{algorithm}'"
+                )));
+            }
+        };
+
+        let inclusive_prefixes = match element.child(EXC_C14N_NAMESPACE, "InclusiveNamespaces") {
+            Some(inclusive) if exclusive => inclusive
+                .attribute("PrefixList")
+                .unwrap_or_default()
+                .split_ascii_whitespace()
+                .map(|prefix| match prefix {
+                    "#default" => String::new(),
+                    prefix => prefix.to_string(),
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        Ok(Self {
+            exclusive,
+            with_comments,
+            inclusive_prefixes,
+        })
+    }
+}
+
+/// Renders the canonical form of a document or element.
+struct Renderer<'a> {
+    canonicalization: &'a Canonicalization,
+    /// Element left out with its descendants (the enveloped signature).
+    excluded: Option<&'a Element>,
+    out: String,
+}
+
+impl<'a> Renderer<'a> {
+    fn new(canonicalization: &'a Canonicalization, excluded: Option<&'a Element>) -> Self {
+        Self {
+            canonicalization,
+            excluded,
+            out: String::new(),
+        }
+    }
+
+    fn document(&mut self, document: &Document) {
+        for node in &document.prolog {
+            if self.misc(node) {
+                self.out.push('\n');
+            }
+        }
+        self.element(&document.root, &Scope::new(), &Scope::new(), &[]);
+        for node in &document.epilog {
+            let mark = self.out.len();
+            self.out.push('\n');
+            if !self.misc(node) {
+                self.out.truncate(mark);
+            }
+        }
+    }
+
+    /// Render a comment or processing instruction, returning whether
+    /// anything was rendered.
+    fn misc(&mut self, node: &Node) -> bool {
+        match node {
+            Node::Comment(comment) if self.canonicalization.with_comments => {
+                self.out.push_str("<!--");
+                self.out.push_str(comment);
+                self.out.push_str("-->");
+                true
+            }
+            Node::Instruction(target, data) => {
+                self.out.push_str("<?");
+                self.out.push_str(target);
+                if !data.is_empty() {
+                    self.out.push(' ');
+                    self.out.push_str(data);
+                }
+                self.out.push_str("?>");
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Render an element.
+    ///
+    /// `parent_scope` holds the namespaces in scope of the parent,
+    /// `rendered` those declared by rendered ancestors, and `inherited`
+    /// attributes of omitted ancestors to render on the element.
+    fn element(
+        &mut self,
+        element: &Element,
+        parent_scope: &Scope,
+        rendered: &Scope,
+        inherited: &[(String, String)],
+    ) {
+        if self
+            .excluded
+            .is_some_and(|excluded| std::ptr::eq(excluded, element))
+        {
+            return;
+        }
+
+        let scope = element.scope(parent_scope);
+        let mut declarations: Vec<(&str, &str)> = Vec::new();
+        let mut now_rendered = rendered.clone();
+        let default_rendered = rendered.get("").is_some_and(|ns| !ns.is_empty());
+
+        if self.canonicalization.exclusive {
+            let mut utilized: BTreeSet<&str> = self
+                .canonicalization
+                .inclusive_prefixes
+                .iter()
+                .map(String::as_str)
+                .collect();
+            utilized.insert(prefix_of(&element.name));
+            for (name, _) in &element.attributes {
+                let prefix = prefix_of(name);
+                if !prefix.is_empty() {
+                    utilized.insert(prefix);
+                }
+            }
+            for prefix in utilized {
+                match scope.get(prefix) {
+                    Some(ns) if prefix != "xml" && rendered.get(prefix) != Some(ns) => {
+                        declarations.push((prefix, ns));
+                        now_rendered.insert(prefix.to_string(), ns.clone());
+                    }
+                    None if prefix.is_empty() && default_rendered => {
+                        declarations.push(("", ""));
+                        now_rendered.remove("");
+                    }
+                    _ => {}
+                }
+            }
+        } else {
+            for (prefix, ns) in &scope {
+                if prefix != "xml" && rendered.get(prefix) != Some(ns) {
+                    declarations.push((prefix, ns));
+                }
+            }
+            if !scope.contains_key("") && default_rendered {
+                declarations.push(("", ""));
+            }
+            now_rendered = scope.clone();
+        }
+        declarations.sort_unstable();
+
+        let mut attributes: Vec<(&str, &str, &str, &str)> = element
+            .attributes
+            .iter()
+            .chain(
+                inherited
+                    .iter()
+                    .filter(|(name, _)| element.attribute(name).is_none()),
+            )
+            .map(|(name, value)| {
+                let (ns, local) = match name.split_once(':') {
+                    Some(("xml", local)) => (XML_NAMESPACE, local),
+                    Some((prefix, local)) => (
+                        scope.get(prefix).map(String::as_str).unwrap_or_default(),
+                        local,
+                    ),
+                    None => ("", name.as_str()),
+                };
+                (ns, local, name.as_str(), value.as_str())
+            })
+            .collect();
+        attributes.sort_unstable();
+
+        self.out.push('<');
+        self.out.push_str(&element.name);
+        for (prefix, ns) in declarations {
+            self.out.push_str(" xmlns");
+            if !prefix.is_empty() {
+                self.out.push(':');
+                self.out.push_str(prefix);
+            }
+            self.out.push_str("=\"");
+            escape_attribute(&mut self.out, ns);
+            self.out.push('"');
+        }
+        for (_, _, name, value) in attributes {
+            self.out.push(' ');
+            self.out.push_str(name);
+            self.out.push_str("=\"");
+            escape_attribute(&mut self.out, value);
+            self.out.push('"');
+        }
+        self.out.push('>');
+
+        for child in &element.children {
+            match child {
+                Node::Element(child) => self.element(child, &scope, &now_rendered, &[]),
+                Node::Text(text) => escape_text(&mut self.out, text),
+                node => {
+                    self.misc(node);
+                }
+            }
+        }
+
+        self.out.push_str("</");
+        self.out.push_str(&element.name);
+        self.out.push('>');
+    }
+}
+
+fn prefix_of(name: &str) -> &str {
+    name.split_once(':').map_or("", |(prefix, _)| prefix)
+}
+
+fn escape_text(out: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '\r' => out.push_str("&#xD;"),
+            c => out.push(c),
+        }
+    }
+}
+
+fn escape_attribute(out: &mut String, value: &str) {
+    for c in value.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '"' => out.push_str("&quot;"),
+            '\t' => out.push_str("&#x9;"),
+            '\n' => out.push_str("&#xA;"),
+            '\r' => out.push_str("&#xD;"),
+            c => out.push(c),
+        }
+    }
+}
+
+// ============================================================================
+// XML
+// ============================================================================
+
+/// Parsed document.
+#[derive(Debug)]
+struct Document {
+    /// Comments and processing instructions before the document element.
+    prolog: Vec<Node>,
+    root: Element,
+    /// Comments and processing instructions after the document element.
+    epilog: Vec<Node>,
+}
+
+#[derive(Debug)]
+enum Node {
+    Element(Element),
+    Text(String),
+    Comment(String),
+    /// Processing instruction target and data.
+    Instruction(String, String),
+}
+
+/// Element of a parsed document.
+///
+/// Names are qualified names as written; namespace declarations are kept
+/// apart from attributes.
+#[derive(Debug, Default)]
+struct Element {
+    name: String,
+    /// Namespace URI of the element.
+    namespace: String,
+    /// Declared namespaces by prefix (`""` for the default namespace).
+    declarations: Vec<(String, String)>,
+    attributes: Vec<(String, String)>,
+    children: Vec<Node>,
+}
+
+impl Element {
+    fn is(&self, namespace: &str, local_name: &str) -> bool {
+        self.namespace == namespace && self.local_name() == local_name
+    }
+
+    fn local_name(&self) -> &str {
+        self.name
+            .split_once(':')
+            .map_or(self.name.as_str(), |(_, local)| local)
+    }
+
+    /// Namespaces in scope of the element, given those of its parent.
+    fn scope(&self, parent: &Scope) -> Scope {
+        let mut scope = parent.clone();
+        for (prefix, ns) in &self.declarations {
+            if ns.is_empty() {
+                scope.remove(prefix);
+            } else {
+                scope.insert(prefix.clone(), ns.clone());
+            }
+        }
+        scope
+    }
+
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn required_attribute(&self, name: &str) -> Result<&str, SignatureError> {
+        self.attribute(name)
+            .ok_or_else(|| malformed(format!("{} has no {name} attribute", self.local_name())))
+    }
+
+    /// ID of the element (its `Id`, `ID` or `id` attribute).
+    fn id(&self) -> Option<&str> {
+        ["Id", "ID", "id"]
+            .into_iter()
+            .find_map(|name| self.attribute(name))
+    }
+
+    fn child_elements(&self) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter_map(|node| match node {
+            Node::Element(element) => Some(element),
+            _ => None,
+        })
+    }
+
+    fn child(&self, namespace: &str, local_name: &str) -> Option<&Element> {
+        self.child_elements().find(|e| e.is(namespace, local_name))
+    }
+
+    fn required_child(
+        &self,
+        namespace: &str,
+        local_name: &str,
+    ) -> Result<&Element, SignatureError> {
+        self.child(namespace, local_name)
+            .ok_or_else(|| malformed(format!("{} has no {local_name} element", self.local_name())))
+    }
+
+    fn text(&self) -> String {
+        self.children
+            .iter()
+            .filter_map(|node| match node {
+                Node::Text(text) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn from_start(start: &BytesStart<'_>, parent: &Scope) -> Result<Self, SignatureError> {
+        let mut element = Self {
+            name: utf8(start.name().as_ref())?.to_string(),
+            ..Self::default()
+        };
+        for attribute in start.attributes() {
+            let attribute = attribute.map_err(|e| malformed(e.to_string()))?;
+            let name = utf8(attribute.key.as_ref())?;
+            let value = attribute_value(&attribute)?;
+            match name.strip_prefix("xmlns") {
+                Some("") => element.declarations.push((String::new(), value)),
+                Some(prefix) if prefix.starts_with(':') => {
+                    element.declarations.push((prefix[1..].to_string(), value));
+                }
+                _ => element.attributes.push((name.to_string(), value)),
+            }
+        }
+
+        let scope = element.scope(parent);
+        let resolve = |prefix: &str| match prefix {
+            "xml" => Ok(XML_NAMESPACE.to_string()),
+            prefix => scope
+                .get(prefix)
+                .cloned()
+                .ok_or_else(|| malformed(format!("undeclared namespace prefix '{prefix}'"))),
+        };
+        element.namespace = match prefix_of(&element.name) {
+            "" => scope.get("").cloned().unwrap_or_default(),
+            prefix => resolve(prefix)?,
+        };
+        for (name, _) in &element.attributes {
+            if let Some((prefix, _)) = name.split_once(':') {
+                resolve(prefix)?;
+            }
+        }
+        Ok(element)
+    }
+
+    fn push_text(&mut self, text: &str) {
+        match self.children.last_mut() {
+            Some(Node::Text(last)) => last.push_str(text),
+            _ => self.children.push(Node::Text(text.to_string())),
+        }
+    }
+}
+
+impl Document {
+    fn parse(content: &[u8]) -> Result<Self, SignatureError> {
+        let xml =
+            std::str::from_utf8(content).map_err(|e| malformed(format!("invalid UTF-8: {e}")))?;
+
+        let mut reader = Reader::from_str(xml);
+        let mut prolog = Vec::new();
+        let mut root: Option<Element> = None;
+        let mut epilog = Vec::new();
+        let mut stack: Vec<(Element, Scope)> = Vec::new();
+
+        loop {
+            let event = reader.read_event().map_err(|e| malformed(e.to_string()))?;
+            let misc = match event {
+                Event::Start(start) => {
+                    let parent = stack
+                        .last()
+                        .map(|(_, scope)| scope.clone())
+                        .unwrap_or_default();
+                    let element = Element::from_start(&start, &parent)?;
+                    let scope = element.scope(&parent);
+                    stack.push((element, scope));
+                    continue;
+                }
+                Event::Empty(start) => {
+                    let parent = stack
+                        .last()
+                        .map(|(_, scope)| scope.clone())
+                        .unwrap_or_default();
+                    Node::Element(Element::from_start(&start, &parent)?)
+                }
+                Event::End(_) => {
+                    let (element, _) =
+                        stack.pop().ok_or_else(|| malformed("unbalanced end tag"))?;
+                    Node::Element(element)
+                }
+                Event::Text(text) => {
+                    let text = text.xml10_content().map_err(|e| malformed(e.to_string()))?;
+                    match stack.last_mut() {
+                        Some((element, _)) => element.push_text(&text),
+                        None if text.chars().all(|c| c.is_ascii_whitespace()) => {}
+                        None => return Err(malformed("text outside the document element")),
+                    }
+                    continue;
+                }
+                Event::CData(data) => {
+                    let text = data.xml10_content().map_err(|e| malformed(e.to_string()))?;
+                    let (element, _) = stack
+                        .last_mut()
+                        .ok_or_else(|| malformed("CDATA outside the document element"))?;
+                    element.push_text(&text);
+                    continue;
+                }
+                Event::GeneralRef(reference) => {
+                    let (element, _) = stack
+                        .last_mut()
+                        .ok_or_else(|| malformed("reference outside the document element"))?;
+                    match reference
+                        .resolve_char_ref()
+                        .map_err(|e| malformed(e.to_string()))?
+                    {
+                        Some(c) => element.push_text(c.encode_utf8(&mut [0; 4])),
+                        None => {
+                            let name = reference.decode().map_err(|e| malformed(e.to_string()))?;
+                            let resolved = quick_xml::escape::resolve_predefined_entity(&name)
+                                .ok_or_else(|| malformed(format!("unknown entity &{name};")))?;
+                            element.push_text(resolved);
+                        }
+                    }
+                    continue;
+                }
+                Event::Comment(comment) => Node::Comment(
+                    comment
+                        .xml10_content()
+                        .map_err(|e| malformed(e.to_string()))?
+                        .into_owned(),
+                ),
+                Event::PI(instruction) => Node::Instruction(
+                    utf8(instruction.target())?.to_string(),
+                    utf8(instruction.content())?.trim_start().to_string(),
+                ),
+                Event::DocType(_) => {
+                    return Err(SignatureError::Unsupported(
+                        "document type declaration".to_string(),
+                    ));
+                }
+                Event::Decl(_) => continue,
+                Event::Eof => break,
+            };
+
+            match (stack.last_mut(), misc) {
+                (Some((parent, _)), node) => parent.children.push(node),
+                (None, Node::Element(element)) => {
+                    if root.is_some() {
+                        return Err(malformed("more than one document element"));
+                    }
+                    root = Some(element);
+                }
+                (None, node) if root.is_some() => epilog.push(node),
+                (None, node) => prolog.push(node),
+            }
+        }
+
+        if !stack.is_empty() {
+            return Err(malformed("unexpected end of document"));
+        }
+        let root = root.ok_or_else(|| malformed("no document element"))?;
+        Ok(Self {
+            prolog,
+            root,
+            epilog,
+        })
+    }
+}
+
+fn utf8(bytes: &[u8]) -> Result<&str, SignatureError> {
+    std::str::from_utf8(bytes).map_err(|e| malformed(format!("invalid UTF-8: {e}")))
+}
+
+/// Normalized value of an attribute.
+///
+/// Line ends and literal whitespace become spaces; character and
+/// predefined entity references are resolved.
+fn attribute_value(attribute: &Attribute<'_>) -> Result<String, SignatureError> {
+    let raw = utf8(&attribute.value)?;
+    let mut value = String::with_capacity(raw.len());
+    let mut rest = raw;
+    while let Some(c) = rest.chars().next() {
+        match c {
+            '&' => {
+                let end = rest
+                    .find(';')
+                    .ok_or_else(|| malformed("unterminated reference in attribute value"))?;
+                let name = &rest[1..end];
+                let resolved = match name.strip_prefix('#') {
+                    Some(number) => {
+                        let code = match number.strip_prefix('x') {
+                            Some(hex) => u32::from_str_radix(hex, 16),
+                            None => number.parse(),
+                        };
+                        code.ok().and_then(char::from_u32).ok_or_else(|| {
+                            malformed(format!("invalid character reference &{name};"))
+                        })?
+                    }
+                    None => quick_xml::escape::resolve_predefined_entity(name)
+                        .and_then(|s| s.chars().next())
+                        .ok_or_else(|| malformed(format!("unknown entity &{name};")))?,
+                };
+                value.push(resolved);
+                rest = &rest[end + 1..];
+                continue;
+            }
+            '\r' => {
+                value.push(' ');
+                if rest[1..].starts_with('\n') {
+                    rest = &rest[1..];
+                }
+            }
+            '\n' | '\t' => value.push(' '),
+            c => value.push(c),
+        }
+        rest = &rest[c.len_utf8()..];
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIGNED: &str = include_str!("../tests/fixtures/stix_package_signed.xml");
+    const TAMPERED: &str = include_str!("../tests/fixtures/stix_package_signed_tampered.xml");
+    const UNTRUSTED: &str = include_str!("../tests/fixtures/stix_package_signed_untrusted.xml");
+    const SIGNED_ECDSA: &str = include_str!("../tests/fixtures/stix_package_signed_ecdsa.xml");
+    const UNSIGNED: &str = include_str!("../tests/fixtures/stix_package_111.xml");
+    const SIGNER_RSA: &str = include_str!("../tests/fixtures/signer_rsa.pem");
+    const SIGNER_EC: &str = include_str!("../tests/fixtures/signer_ec.pem");
+
+    const SIGNER_RSA_FINGERPRINT: &str =
+        "4b33995696771f0d751249cc8a96169bf92e05ebb01119d0fb3d74b616ec87a7";
+    const SIGNER_EC_FINGERPRINT: &str =
+        "cd19d203b5bf025bcae89b8ed52f81a9ec9815a52abafdaa524d3bd06a246ab9";
+    const UNTRUSTED_FINGERPRINT: &str =
+        "7a373ab192e35f6abdbbcd5cb2b6b627fe7f048622ee65f9b93948c3d6cf65cd";
+
+    type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+    fn canonicalize(
+        xml: &str,
+        exclusive: bool,
+        with_comments: bool,
+    ) -> Result<String, SignatureError> {
+        let document = Document::parse(xml.as_bytes())?;
+        let canonicalization = Canonicalization {
+            exclusive,
+            with_comments,
+            inclusive_prefixes: Vec::new(),
+        };
+        let mut renderer = Renderer::new(&canonicalization, None);
+        renderer.document(&document);
+        Ok(renderer.out)
+    }
+
+    #[test]
+    fn test_is_signed() {
+        assert!(is_signed(SIGNED.as_bytes()));
+        assert!(is_signed(SIGNED_ECDSA.as_bytes()));
+        assert!(!is_signed(UNSIGNED.as_bytes()));
+        assert!(!is_signed(b"not xml at all"));
+        // Signatures of nested elements do not sign the document
+        assert!(!is_signed(
+            br#"<a><b><ds:Signature xmlns:ds="http://www.w3.org/2000/09/xmldsig#"/></b></a>"#
+        ));
+    }
+
+    #[test]
+    fn test_valid_signatures() -> TestResult {
+        let trusted = TrustedCertificates::from_pem(SIGNER_RSA)?;
+        assert_eq!(
+            verify_signature(SIGNED.as_bytes(), &trusted)?.fingerprint,
+            SIGNER_RSA_FINGERPRINT
+        );
+
+        // Without a certificate in the signature, the trusted certificates
+        // are tried in turn
+        let trusted = TrustedCertificates::from_pem(&format!("{SIGNER_RSA}{SIGNER_EC}"))?;
+        assert_eq!(
+            verify_signature(SIGNED_ECDSA.as_bytes(), &trusted)?.fingerprint,
+            SIGNER_EC_FINGERPRINT
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_tampered_content() -> TestResult {
+        let trusted = TrustedCertificates::from_pem(SIGNER_RSA)?;
+        assert_eq!(
+            verify_signature(TAMPERED.as_bytes(), &trusted),
+            Err(SignatureError::DigestMismatch(String::new()))
+        );
+
+        let forged = SIGNED.replacen("<ds:SignatureValue>", "<ds:SignatureValue>AAAA", 1);
+        assert_eq!(
+            verify_signature(forged.as_bytes(), &trusted),
+            Err(SignatureError::InvalidSignature)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_untrusted_signer() -> TestResult {
+        let trusted = TrustedCertificates::from_pem(SIGNER_RSA)?;
+        assert_eq!(
+            verify_signature(UNTRUSTED.as_bytes(), &trusted),
+            Err(SignatureError::UntrustedSigner(
+                UNTRUSTED_FINGERPRINT.to_string()
+            ))
+        );
+        assert_eq!(
+            verify_signature(SIGNED_ECDSA.as_bytes(), &trusted),
+            Err(SignatureError::InvalidSignature)
+        );
+        assert_eq!(
+            verify_signature(UNSIGNED.as_bytes(), &trusted),
+            Err(SignatureError::Unsigned)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_unsupported_signatures() -> TestResult {
+        let trusted = TrustedCertificates::from_pem(SIGNER_RSA)?;
+        for (from, to) in [
+            ("xmldsig-more#rsa-sha256", "xmldsig#rsa-sha1"),
+            (
+                "<ds:Reference URI=\"\">",
+                "<ds:Reference URI=\"#example:indicator-1\">",
+            ),
+            (
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>",
+                "<!DOCTYPE stix:STIX_Package>",
+            ),
+        ] {
+            let content = SIGNED.replacen(from, to, 1);
+            assert!(
+                matches!(
+                    verify_signature(content.as_bytes(), &trusted),
+                    Err(SignatureError::Unsupported(_))
+                ),
+                "{to}"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_trusted_certificates() -> TestResult {
+        let trusted = TrustedCertificates::from_pem(&format!("{SIGNER_RSA}\n{SIGNER_EC}"))?;
+        assert!(trusted.contains(SIGNER_RSA_FINGERPRINT));
+        assert!(trusted.contains(SIGNER_EC_FINGERPRINT));
+        assert!(!trusted.contains(UNTRUSTED_FINGERPRINT));
+        assert!(TrustedCertificates::from_pem("")?.is_empty());
+        assert!(matches!(
+            TrustedCertificates::from_pem(
+                "-----BEGIN CERTIFICATE-----\nbm90IGEgY2VydGlmaWNhdGU=\n-----END CERTIFICATE-----\n"
+            ),
+            Err(SignatureError::InvalidCertificate(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_canonicalization() -> TestResult {
+        let xml = "<?xml version=\"1.0\"?>\n<?pi  data ?>\n<!-- before -->\n\
+            <a xmlns=\"urn:a\" xmlns:b=\"urn:b\" xmlns:u=\"urn:u\" z=\"1\" b:y=\"&quot;2&#9;\" a=\"x\r\ny\">\
+            <b:c xmlns:b=\"urn:b\"/><d xmlns=\"\">t &amp; &lt; &gt; &#13;</d><![CDATA[<x>]]><!-- inside --></a>\n\
+            <!-- after -->";
+
+        assert_eq!(
+            canonicalize(xml, false, false)?,
+            "<?pi data ?>\n\
+             <a xmlns=\"urn:a\" xmlns:b=\"urn:b\" xmlns:u=\"urn:u\" a=\"x y\" z=\"1\" b:y=\"&quot;2&#x9;\">\
+             <b:c></b:c><d xmlns=\"\">t &amp; &lt; &gt; &#xD;</d>&lt;x&gt;</a>"
+        );
+        assert_eq!(
+            canonicalize(xml, true, true)?,
+            "<?pi data ?>\n<!-- before -->\n\
+             <a xmlns=\"urn:a\" xmlns:b=\"urn:b\" a=\"x y\" z=\"1\" b:y=\"&quot;2&#x9;\">\
+             <b:c></b:c><d xmlns=\"\">t &amp; &lt; &gt; &#xD;</d>&lt;x&gt;<!-- inside --></a>\n\
+             <!-- after -->"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_ecdsa_signature_der() {
+        let mut raw = vec![0u8; 64];
+        raw[1] = 0x01;
+        raw[32] = 0x80;
+        let der = ecdsa_signature_der(&raw).unwrap_or_default();
+        assert_eq!(&der[..2], &[0x30, 0x44]);
+        assert_eq!(&der[2..7], &[0x02, 0x1f, 0x01, 0x00, 0x00]);
+        assert_eq!(&der[35..39], &[0x02, 0x21, 0x00, 0x80]);
+        assert_eq!(ecdsa_signature_der(&[1, 2, 3]), None);
+    }
+}
@@ -0,0 +1,216 @@
+//! Validation of inbound STIX 1.x content.
+//!
+//! Content blocks claiming a STIX XML binding are checked to be well-formed
+//! XML whose root element is a `STIX_Package` in the STIX 1.x namespace.
+//! With the `xsd` feature, packages are also validated against the bundled
+//! STIX package schema (see [`crate::xsd`]).
+
+use quick_xml::NsReader;
+use quick_xml::events::Event;
+use quick_xml::name::{Namespace, ResolveResult};
+use thiserror::Error;
+
+use crate::constants::{
+    CB_STIX_XML_10, CB_STIX_XML_11, CB_STIX_XML_12, CB_STIX_XML_101, CB_STIX_XML_111,
+};
+use crate::messages::SchemaViolation;
+#[cfg(feature = "xsd")]
+use crate::xsd::STIX_PACKAGE_SCHEMA;
+
+/// Namespace of STIX 1.x packages (all STIX 1.x versions).
+pub const STIX_1_NAMESPACE: &str = "http://stix.mitre.org/stix-1";
+
+/// Root element of STIX 1.x documents.
+pub const STIX_PACKAGE: &str = "STIX_Package";
+
+/// STIX XML content bindings.
+const STIX_XML_BINDINGS: &[&str] = &[
+    CB_STIX_XML_10,
+    CB_STIX_XML_101,
+    CB_STIX_XML_11,
+    CB_STIX_XML_111,
+    CB_STIX_XML_12,
+];
+
+/// Why content is not valid STIX 1.x.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ContentValidationError {
+    /// Content is not well-formed XML.
+    #[error("Content is not well-formed XML: {0}")]
+    Malformed(String),
+
+    /// Content is XML, but not a STIX package.
+    #[error("Content is not a STIX package: {0}")]
+    NotStix(String),
+
+    /// Content is a STIX package that does not conform to the STIX schema
+    /// (`xsd` feature).
+    #[error("Content does not conform to the STIX schema: {0}")]
+    Invalid(SchemaViolation),
+}
+
+/// Whether a content binding claims STIX XML content.
+pub fn is_stix_xml_binding(binding: &str) -> bool {
+    STIX_XML_BINDINGS.contains(&binding)
+}
+
+/// Validate STIX 1.x XML content.
+pub fn validate_stix_content(content: &[u8]) -> Result<(), ContentValidationError> {
+    let xml = std::str::from_utf8(content)
+        .map_err(|e| ContentValidationError::Malformed(format!("invalid UTF-8: {e}")))?;
+
+    let mut reader = NsReader::from_str(xml);
+    let mut depth = 0usize;
+    let mut root_seen = false;
+
+    loop {
+        let (ns, event) = reader
+            .read_resolved_event()
+            .map_err(|e| ContentValidationError::Malformed(e.to_string()))?;
+
+        match event {
+            Event::Start(_) | Event::Empty(_) if depth == 0 => {
+                if root_seen {
+                    return Err(ContentValidationError::Malformed(
+                        "multiple root elements".to_string(),
+                    ));
+                }
+                root_seen = true;
+
+                let name = match &event {
+                    Event::Start(e) | Event::Empty(e) => e.local_name(),
+                    _ => continue,
+                };
+                let local_name = String::from_utf8_lossy(name.as_ref()).into_owned();
+                let namespace = match ns {
+                    ResolveResult::Bound(Namespace(ns)) => String::from_utf8_lossy(ns).into_owned(),
+                    ResolveResult::Unbound => String::new(),
+                    ResolveResult::Unknown(prefix) => {
+                        return Err(ContentValidationError::Malformed(format!(
+                            "undeclared namespace prefix '{}'",
+                            String::from_utf8_lossy(&prefix)
+                        )));
+                    }
+                };
+
+                if local_name != STIX_PACKAGE || namespace != STIX_1_NAMESPACE {
+                    return Err(ContentValidationError::NotStix(format!(
+                        "root element is {{{namespace}}}{local_name}, expected {{{STIX_1_NAMESPACE}}}{STIX_PACKAGE}"
+                    )));
+                }
+                if matches!(event, Event::Start(_)) {
+                    depth += 1;
+                }
+            }
+            Event::Start(_) => depth += 1,
+            Event::End(_) => depth = depth.saturating_sub(1),
+            Event::Text(text) if depth == 0 && !text.iter().all(u8::is_ascii_whitespace) => {
+                return Err(ContentValidationError::Malformed(
+                    "text outside the root element".to_string(),
+                ));
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    if !root_seen {
+        return Err(ContentValidationError::Malformed(
+            "no root element".to_string(),
+        ));
+    }
+    if depth > 0 {
+        return Err(ContentValidationError::Malformed(
+            "unexpected end of document".to_string(),
+        ));
+    }
+
+    #[cfg(feature = "xsd")]
+    STIX_PACKAGE_SCHEMA
+        .validate(content)
+        .map_err(ContentValidationError::Invalid)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PACKAGE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<stix:STIX_Package xmlns:stix="http://stix.mitre.org/stix-1" xmlns:example="http://example.com" id="example:Package-1" version="1.1.1">
+    <stix:STIX_Header><stix:Title>Example</stix:Title></stix:STIX_Header>
+</stix:STIX_Package>"#;
+
+    #[test]
+    fn test_stix_bindings() {
+        assert!(is_stix_xml_binding(CB_STIX_XML_111));
+        assert!(is_stix_xml_binding(CB_STIX_XML_12));
+        assert!(!is_stix_xml_binding("urn:example.com:custom"));
+    }
+
+    #[test]
+    fn test_well_formed_package() {
+        assert_eq!(validate_stix_content(PACKAGE.as_bytes()), Ok(()));
+        assert_eq!(
+            validate_stix_content(
+                br#"<STIX_Package xmlns="http://stix.mitre.org/stix-1" xmlns:example="http://example.com" id="example:Package-2"/>"#
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_malformed_content() {
+        for content in [
+            &b"<stix:STIX_Package xmlns:stix=\"http://stix.mitre.org/stix-1\">"[..],
+            b"<stix:STIX_Package xmlns:stix=\"http://stix.mitre.org/stix-1\"></stix:Other>",
+            b"<stix:STIX_Package/>",
+            b"not xml at all",
+            b"\xff\xfe\x00binary",
+            b"",
+        ] {
+            assert!(
+                matches!(
+                    validate_stix_content(content),
+                    Err(ContentValidationError::Malformed(_))
+                ),
+                "{}",
+                String::from_utf8_lossy(content)
+            );
+        }
+    }
+
+    #[cfg(feature = "xsd")]
+    #[test]
+    fn test_schema_invalid_package() -> Result<(), Box<dyn std::error::Error>> {
+        let content = br#"<stix:STIX_Package xmlns:stix="http://stix.mitre.org/stix-1">
+    <stix:Indicators/>
+    <stix:STIX_Header/>
+</stix:STIX_Package>"#;
+        match validate_stix_content(content) {
+            Err(ContentValidationError::Invalid(violation)) => {
+                assert_eq!(violation.path, "/STIX_Package/STIX_Header");
+                Ok(())
+            }
+            other => Err(format!("expected a schema violation, got {other:?}").into()),
+        }
+    }
+
+    #[test]
+    fn test_wrong_namespace_or_root() {
+        for content in [
+            &br#"<stix:STIX_Package xmlns:stix="http://example.com/stix"/>"#[..],
+            br#"<STIX_Package/>"#,
+            br#"<stix:Indicator xmlns:stix="http://stix.mitre.org/stix-1"/>"#,
+        ] {
+            assert!(
+                matches!(
+                    validate_stix_content(content),
+                    Err(ContentValidationError::NotStix(_))
+                ),
+                "{}",
+                String::from_utf8_lossy(content)
+            );
+        }
+    }
+}
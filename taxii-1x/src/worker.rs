@@ -0,0 +1,316 @@
+//! Background preparation of asynchronous poll result sets.
+//!
+//! When a TAXII 1.1 poll allows asynchronous delivery and content is not
+//! immediately available, the poll handler creates a `PENDING` result set and
+//! answers with `ST_PENDING`. [`ResultSetWorker`] picks up those result sets,
+//! materializes the matching content blocks and marks them `READY`, after
+//! which Poll Fulfillment serves the prepared content. Result sets whose
+//! content is still not ready stay `PENDING` until a later run.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use tracing::{debug, error, warn};
+
+use taxii_db::{DatabaseError, DatabaseResult, Taxii1Repository};
+
+/// Default number of pending result sets prepared per run.
+pub const DEFAULT_BATCH_SIZE: i64 = 100;
+
+/// Worker that prepares pending TAXII 1.x result sets.
+///
+/// # Example
+///
+/// ```ignore
+/// let worker = ResultSetWorker::new(Arc::new(DbTaxii1Repository::new(pool)));
+/// tokio::spawn(worker.run(Duration::from_secs(10)));
+/// ```
+pub struct ResultSetWorker<R> {
+    persistence: Arc<R>,
+    batch_size: i64,
+}
+
+impl<R: Taxii1Repository> ResultSetWorker<R> {
+    /// Create a worker with the default batch size.
+    pub fn new(persistence: Arc<R>) -> Self {
+        Self {
+            persistence,
+            batch_size: DEFAULT_BATCH_SIZE,
+        }
+    }
+
+    /// Set how many pending result sets are prepared per run.
+    #[must_use]
+    pub fn with_batch_size(mut self, batch_size: i64) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// Prepare one batch of pending result sets.
+    ///
+    /// Failures for individual result sets are logged and left `PENDING`
+    /// so they are retried on the next run. Returns the number of result
+    /// sets that were prepared.
+    pub async fn run_once(&self) -> DatabaseResult<usize> {
+        let pending = self
+            .persistence
+            .get_pending_result_sets(self.batch_size)
+            .await?;
+
+        let mut prepared = 0;
+        for result_set in pending {
+            match self.persistence.prepare_result_set(&result_set.id).await {
+                Ok(Some(_)) => prepared += 1,
+                Ok(None) => {
+                    debug!(result_set = %result_set.id, "Result set removed before preparation");
+                }
+                Err(DatabaseError::ResultsNotReady) => {
+                    debug!(result_set = %result_set.id, "Result set content not ready yet");
+                }
+                Err(e) => {
+                    warn!(result_set = %result_set.id, error = %e, "Failed to prepare result set");
+                }
+            }
+        }
+
+        Ok(prepared)
+    }
+
+    /// Run the worker until the task is dropped, preparing a batch every `interval`.
+    pub async fn run(self, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            ticker.tick().await;
+            match self.run_once().await {
+                Ok(0) => {}
+                Ok(count) => debug!(count, "Prepared pending result sets"),
+                Err(e) => error!(error = %e, "Result set worker failed"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::poll_fulfillment::PollFulfillmentRequest11Handler;
+    use crate::handlers::{HandlerContext, ServiceInfo, TaxiiHeaders};
+    use crate::messages::{NS_TAXII_11, tm11};
+    use crate::{ST_PENDING, VID_TAXII_SERVICES_11, VID_TAXII_XML_11};
+    use chrono::{Duration as ChronoDuration, Utc};
+    use sqlx::PgPool;
+    use taxii_core::{
+        CollectionEntity, ContentBindingEntity, ContentBlockEntity, ResultSetEntity, ServiceEntity,
+        collection_type, result_set_status,
+    };
+    use taxii_db::{DbTaxii1Repository, TaxiiPool};
+
+    type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+    const SERVICE_ID: &str = "poll-a";
+    const COLLECTION_NAME: &str = "collection-a";
+    const RESULT_ID: &str = "result-set-a";
+
+    fn poll_context(persistence: Arc<DbTaxii1Repository>) -> HandlerContext {
+        HandlerContext {
+            account: None,
+            persistence,
+            service: ServiceInfo {
+                id: SERVICE_ID.to_string(),
+                service_type: "POLL".to_string(),
+                address: "/services/poll-a/".to_string(),
+                description: None,
+                protocol_bindings: Vec::new(),
+                message_bindings: vec![VID_TAXII_XML_11.to_string()],
+                available: true,
+                authentication_required: false,
+                properties: serde_json::json!({}),
+            },
+            domain: None,
+            limits: Default::default(),
+            push: Default::default(),
+            hooks: None,
+        }
+    }
+
+    async fn fulfill(ctx: &HandlerContext) -> crate::Taxii1xResult<tm11::Taxii11Message> {
+        let headers = TaxiiHeaders {
+            content_type: VID_TAXII_XML_11.to_string(),
+            services: VID_TAXII_SERVICES_11.to_string(),
+            accept: None,
+            protocol: None,
+        };
+        let request = tm11::Taxii11Message::PollFulfillmentRequest(tm11::PollFulfillmentRequest {
+            xmlns: NS_TAXII_11.to_string(),
+            message_id: "fulfillment-1".to_string(),
+            collection_name: COLLECTION_NAME.to_string(),
+            result_id: RESULT_ID.to_string(),
+            result_part_number: Some(1),
+            extended_headers: None,
+        });
+
+        PollFulfillmentRequest11Handler
+            .handle_11(ctx, &headers, &request)
+            .await
+    }
+
+    async fn add_block(
+        persistence: &DbTaxii1Repository,
+        collection_id: i32,
+        content: &str,
+    ) -> TestResult {
+        let block = ContentBlockEntity::new(
+            content,
+            Some(ContentBindingEntity::new("urn:stix.mitre.org:xml:1.1.1")),
+        );
+        persistence
+            .create_content_block(&block, Some(&[collection_id]), None)
+            .await?;
+        Ok(())
+    }
+
+    /// Create the poll service and its collection, returning the collection ID.
+    async fn create_collection(
+        persistence: &DbTaxii1Repository,
+    ) -> Result<i32, Box<dyn std::error::Error>> {
+        persistence
+            .update_service(&ServiceEntity {
+                id: Some(SERVICE_ID.to_string()),
+                service_type: "POLL".to_string(),
+                properties: serde_json::json!({}),
+            })
+            .await?;
+        let collection = persistence
+            .create_collection(&CollectionEntity {
+                collection_type: collection_type::DATA_SET.to_string(),
+                ..CollectionEntity::new(COLLECTION_NAME)
+            })
+            .await?;
+        let collection_id = collection.id.ok_or("collection has no id")?;
+        persistence
+            .set_collection_services(collection_id, &[SERVICE_ID.to_string()])
+            .await?;
+        Ok(collection_id)
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_pending_result_set_prepared_and_fulfilled(pool: PgPool) -> TestResult {
+        let persistence = Arc::new(DbTaxii1Repository::new(TaxiiPool::new(pool)));
+        let collection_id = create_collection(&persistence).await?;
+
+        add_block(&persistence, collection_id, "<first/>").await?;
+        add_block(&persistence, collection_id, "<second/>").await?;
+
+        persistence
+            .create_result_set(&ResultSetEntity {
+                id: RESULT_ID.to_string(),
+                collection_id,
+                content_bindings: Vec::new(),
+                timeframe: (Some(Utc::now() - ChronoDuration::hours(1)), None),
+                status: result_set_status::PENDING.to_string(),
+                content_block_count: None,
+                expires_at: Some(Utc::now() + ChronoDuration::hours(1)),
+                delivery: None,
+                delivery_status: None,
+            })
+            .await?;
+
+        let ctx = poll_context(persistence.clone());
+
+        // Not prepared yet: fulfillment reports the result set as pending,
+        // and when it expires
+        match fulfill(&ctx).await? {
+            tm11::Taxii11Message::StatusMessage(status) => {
+                assert_eq!(status.status_type, ST_PENDING);
+                assert!(
+                    status
+                        .message
+                        .is_some_and(|m| m.starts_with("Result set expires at"))
+                );
+            }
+            other => return Err(format!("expected pending status, got {other:?}").into()),
+        }
+
+        let worker = ResultSetWorker::new(persistence.clone());
+        assert_eq!(worker.run_once().await?, 1);
+        assert_eq!(worker.run_once().await?, 0);
+
+        // Content added after preparation is not part of the result set
+        add_block(&persistence, collection_id, "<third/>").await?;
+
+        match fulfill(&ctx).await? {
+            tm11::Taxii11Message::PollResponse(response) => {
+                let contents: Vec<&str> = response
+                    .content_blocks
+                    .iter()
+                    .map(|b| b.content.as_str())
+                    .collect();
+                assert_eq!(contents, vec!["<first/>", "<second/>"]);
+                assert_eq!(response.more, Some(false));
+            }
+            other => return Err(format!("expected poll response, got {other:?}").into()),
+        }
+
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_expired_result_set_not_fulfilled_and_purged(pool: PgPool) -> TestResult {
+        let persistence = Arc::new(DbTaxii1Repository::new(TaxiiPool::new(pool)));
+        let collection_id = create_collection(&persistence).await?;
+        add_block(&persistence, collection_id, "<first/>").await?;
+
+        let now = Utc::now();
+        persistence
+            .create_result_set(&ResultSetEntity {
+                id: RESULT_ID.to_string(),
+                collection_id,
+                content_bindings: Vec::new(),
+                timeframe: (None, None),
+                status: result_set_status::READY.to_string(),
+                content_block_count: None,
+                expires_at: Some(now - ChronoDuration::minutes(1)),
+                delivery: None,
+                delivery_status: None,
+            })
+            .await?;
+        persistence
+            .create_result_set(&ResultSetEntity {
+                id: "result-set-b".to_string(),
+                collection_id,
+                content_bindings: Vec::new(),
+                timeframe: (None, None),
+                status: result_set_status::PENDING.to_string(),
+                content_block_count: None,
+                expires_at: Some(now + ChronoDuration::hours(1)),
+                delivery: None,
+                delivery_status: None,
+            })
+            .await?;
+
+        let ctx = poll_context(persistence.clone());
+        match fulfill(&ctx).await {
+            Err(crate::Taxii1xError::StatusMessage { status_type, .. }) => {
+                assert!(matches!(status_type, crate::StatusType::NotFound));
+            }
+            other => return Err(format!("expected not found status, got {other:?}").into()),
+        }
+
+        // Expired result sets are not prepared either
+        let pending = persistence.get_pending_result_sets(10).await?;
+        assert_eq!(
+            pending.iter().map(|r| r.id.as_str()).collect::<Vec<_>>(),
+            vec!["result-set-b"]
+        );
+
+        assert_eq!(persistence.purge_expired_result_sets(now).await?, 1);
+        assert_eq!(persistence.purge_expired_result_sets(now).await?, 0);
+        assert!(persistence.get_result_set("result-set-b").await?.is_some());
+
+        Ok(())
+    }
+}
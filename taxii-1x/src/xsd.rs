@@ -0,0 +1,267 @@
+//! XML Schema validation with libxml2.
+//!
+//! The schemas are bundled with the crate. Each is compiled once per thread
+//! on first use and kept for the lifetime of the thread.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+
+use libxml::error::StructuredError;
+use libxml::parser::{Parser, ParserOptions};
+use libxml::schemas::{SchemaParserContext, SchemaValidationContext};
+use quick_xml::Reader;
+use quick_xml::events::Event;
+
+use crate::messages::SchemaViolation;
+
+/// A bundled XML schema.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct XmlSchema {
+    name: &'static str,
+    source: &'static str,
+}
+
+/// TAXII 1.0 XML Message Binding; see `schemas/taxii_xml_binding_10.xsd`.
+pub const TAXII_10_SCHEMA: XmlSchema = XmlSchema {
+    name: "taxii_xml_binding_10",
+    source: include_str!("../schemas/taxii_xml_binding_10.xsd"),
+};
+
+/// TAXII 1.1 XML Message Binding; see `schemas/taxii_xml_binding_11.xsd`.
+pub const TAXII_11_SCHEMA: XmlSchema = XmlSchema {
+    name: "taxii_xml_binding_11",
+    source: include_str!("../schemas/taxii_xml_binding_11.xsd"),
+};
+
+/// Structure of STIX 1.x packages; see `schemas/stix_package.xsd`.
+pub const STIX_PACKAGE_SCHEMA: XmlSchema = XmlSchema {
+    name: "stix_package",
+    source: include_str!("../schemas/stix_package.xsd"),
+};
+
+thread_local! {
+    static CONTEXTS: RefCell<HashMap<&'static str, SchemaValidationContext>> =
+        RefCell::new(HashMap::new());
+}
+
+impl XmlSchema {
+    /// Validate a document against the schema.
+    ///
+    /// Returns the first violation in document order, with the XPath of the
+    /// element or attribute it concerns.
+    pub fn validate(&self, xml: &[u8]) -> Result<(), SchemaViolation> {
+        let options = ParserOptions {
+            recover: false,
+            no_net: true,
+            ..ParserOptions::default()
+        };
+        let document = Parser::default()
+            .parse_string_with_options(xml, options)
+            .map_err(|_| SchemaViolation {
+                path: "/".to_string(),
+                message: "document is not well-formed XML".to_string(),
+            })?;
+
+        CONTEXTS.with(|contexts| {
+            let mut contexts = contexts.borrow_mut();
+            let context = match contexts.entry(self.name) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => entry.insert(self.compile()?),
+            };
+            context.drain_errors();
+            context
+                .validate_document(&document)
+                .map_err(|errors| first_violation(xml, &errors))
+        })
+    }
+
+    fn compile(&self) -> Result<SchemaValidationContext, SchemaViolation> {
+        let mut parser = SchemaParserContext::from_buffer(self.source);
+        SchemaValidationContext::from_parser(&mut parser).map_err(|errors| SchemaViolation {
+            path: "/".to_string(),
+            message: format!(
+                "schema {} does not compile: {}",
+                self.name,
+                errors
+                    .first()
+                    .and_then(|e| e.message.as_deref())
+                    .unwrap_or("unknown error")
+                    .trim()
+            ),
+        })
+    }
+}
+
+/// Translate the first libxml2 validation error into a violation.
+///
+/// Errors name the element as `Element '{namespace}name'` or
+/// `Element '{namespace}name', attribute 'attr'`, followed by the
+/// description; the element is located by its name and line.
+fn first_violation(xml: &[u8], errors: &[StructuredError]) -> SchemaViolation {
+    let text = errors
+        .first()
+        .and_then(|e| e.message.as_deref())
+        .unwrap_or("document does not conform to the schema")
+        .trim();
+    let line = errors.first().and_then(|e| e.line);
+
+    let Some(subject) = text.strip_prefix("Element '") else {
+        return SchemaViolation {
+            path: "/".to_string(),
+            message: text.to_string(),
+        };
+    };
+    let Some((element, rest)) = subject.split_once('\'') else {
+        return SchemaViolation {
+            path: "/".to_string(),
+            message: text.to_string(),
+        };
+    };
+    let local_name = element.rsplit_once('}').map_or(element, |(_, name)| name);
+
+    let (attribute, description) = match rest.strip_prefix(", attribute '") {
+        Some(rest) => match rest.split_once('\'') {
+            Some((attribute, description)) => (Some(attribute), description),
+            None => (None, rest),
+        },
+        None => (None, rest),
+    };
+    // Required attributes are reported on their element
+    let attribute = attribute.or_else(|| {
+        description
+            .split_once("The attribute '")
+            .and_then(|(_, rest)| rest.split_once('\''))
+            .filter(|(_, rest)| rest.starts_with(" is required"))
+            .map(|(attribute, _)| attribute)
+    });
+
+    let mut path = element_path(xml, local_name, line).unwrap_or_else(|| "/".to_string());
+    if let Some(attribute) = attribute {
+        let local_attribute = attribute
+            .rsplit_once('}')
+            .map_or(attribute, |(_, name)| name);
+        path = format!("{}/@{local_attribute}", path.trim_end_matches('/'));
+    }
+    SchemaViolation {
+        path,
+        message: description.trim_start_matches(':').trim().to_string(),
+    }
+}
+
+/// XPath of the first element named `local_name` whose start tag spans
+/// `line`, or of the first element so named if the line is unknown.
+fn element_path(xml: &[u8], local_name: &str, line: Option<i32>) -> Option<String> {
+    let mut reader = Reader::from_reader(xml);
+    let mut buf = Vec::new();
+    // Name of each open element and the positions of its children by name
+    let mut stack: Vec<(String, HashMap<String, usize>)> = Vec::new();
+    let mut root_children: HashMap<String, usize> = HashMap::new();
+    let mut position = 0usize;
+    let mut current_line = 1i32;
+
+    loop {
+        buf.clear();
+        let event = reader.read_event_into(&mut buf).ok()?;
+        let start_line = current_line;
+        let end = usize::try_from(reader.buffer_position()).ok()?;
+        let consumed = xml.get(position..end).unwrap_or_default();
+        let newlines = consumed.iter().filter(|b| **b == b'\n').count();
+        current_line = current_line.saturating_add(i32::try_from(newlines).unwrap_or(i32::MAX));
+        position = end;
+
+        let (element, is_empty) = match &event {
+            Event::Start(e) => (e, false),
+            Event::Empty(e) => (e, true),
+            Event::End(_) => {
+                stack.pop();
+                continue;
+            }
+            Event::Eof => return None,
+            _ => continue,
+        };
+
+        let name = String::from_utf8_lossy(element.local_name().as_ref()).into_owned();
+        let siblings = match stack.last_mut() {
+            Some((_, children)) => children,
+            None => &mut root_children,
+        };
+        let index = siblings.entry(name.clone()).or_insert(0);
+        *index += 1;
+        let step = if *index == 1 {
+            name.clone()
+        } else {
+            format!("{name}[{index}]")
+        };
+
+        let matches = name == local_name
+            && line.is_none_or(|line| (start_line..=current_line).contains(&line));
+        if matches || !is_empty {
+            stack.push((step, HashMap::new()));
+        }
+        if matches {
+            let steps: Vec<&str> = stack.iter().map(|(step, _)| step.as_str()).collect();
+            return Some(format!("/{}", steps.join("/")));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PACKAGE: &str = include_str!("../tests/fixtures/stix_package_111.xml");
+    const SIGNED: &str = include_str!("../tests/fixtures/stix_package_signed.xml");
+
+    fn violation(xml: &str) -> Result<SchemaViolation, Box<dyn std::error::Error>> {
+        match STIX_PACKAGE_SCHEMA.validate(xml.as_bytes()) {
+            Ok(()) => Err("document passed validation".into()),
+            Err(violation) => Ok(violation),
+        }
+    }
+
+    #[test]
+    fn test_schemas_compile() -> Result<(), Box<dyn std::error::Error>> {
+        for schema in [TAXII_10_SCHEMA, TAXII_11_SCHEMA, STIX_PACKAGE_SCHEMA] {
+            schema.compile()?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_valid_packages() -> Result<(), Box<dyn std::error::Error>> {
+        STIX_PACKAGE_SCHEMA.validate(PACKAGE.as_bytes())?;
+        STIX_PACKAGE_SCHEMA.validate(SIGNED.as_bytes())?;
+        STIX_PACKAGE_SCHEMA.validate(
+            br#"<STIX_Package xmlns="http://stix.mitre.org/stix-1" xmlns:example="http://example.com" id="example:Package-2" version="1.2"/>"#,
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_violation_paths() -> Result<(), Box<dyn std::error::Error>> {
+        let v = violation(
+            r#"<stix:STIX_Package xmlns:stix="http://stix.mitre.org/stix-1" version="1.1.1">
+    <stix:Indicators/>
+    <stix:STIX_Header/>
+</stix:STIX_Package>"#,
+        )?;
+        assert_eq!(v.path, "/STIX_Package/STIX_Header");
+        assert!(v.message.contains("not expected"), "{}", v.message);
+
+        let v = violation(
+            r#"<stix:STIX_Package xmlns:stix="http://stix.mitre.org/stix-1" version="9"/>"#,
+        )?;
+        assert_eq!(v.path, "/STIX_Package/@version");
+
+        let v = violation(
+            r#"<stix:STIX_Package xmlns:stix="http://stix.mitre.org/stix-1" colour="red"/>"#,
+        )?;
+        assert_eq!(v.path, "/STIX_Package/@colour");
+
+        let v = violation("<stix:STIX_Package")?;
+        assert_eq!(v.path, "/");
+        assert_eq!(v.message, "document is not well-formed XML");
+        Ok(())
+    }
+}
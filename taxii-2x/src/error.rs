@@ -45,6 +45,10 @@ pub enum Taxii2Error {
     #[error("Forbidden")]
     Forbidden,
 
+    /// Forbidden by the scope of the token (names the missing scope).
+    #[error("Insufficient token scope: {0}")]
+    InsufficientScope(String),
+
     /// Bad request.
     #[error("Bad request: {0}")]
     BadRequest(String),
@@ -65,6 +69,14 @@ pub enum Taxii2Error {
     #[error("Request entity too large")]
     RequestEntityTooLarge,
 
+    /// Request did not complete within its deadline.
+    #[error("Request timed out")]
+    Timeout,
+
+    /// Client exceeded its request rate limit.
+    #[error("Too many requests")]
+    TooManyRequests,
+
     /// Internal server error.
     #[error("Internal server error: {0}")]
     Internal(String),
@@ -82,6 +94,24 @@ pub enum Taxii2Error {
     Stix2(#[from] stix2::Error),
 }
 
+/// HTTP status of a database error.
+///
+/// A missing entity is `404`, an exhausted pool or results still being
+/// prepared `503`, and anything else `500`. The server maps database
+/// errors from every layer through this.
+pub fn database_status(error: &taxii_db::DatabaseError) -> StatusCode {
+    use taxii_db::DatabaseError;
+    match error {
+        DatabaseError::NotFound(_) => StatusCode::NOT_FOUND,
+        DatabaseError::PoolTimeout | DatabaseError::ResultsNotReady => {
+            StatusCode::SERVICE_UNAVAILABLE
+        }
+        DatabaseError::Sqlx(_) | DatabaseError::InvalidData(_) | DatabaseError::Json(_) => {
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
 impl Taxii2Error {
     /// Get HTTP status code.
     pub fn status_code(&self) -> StatusCode {
@@ -90,13 +120,16 @@ impl Taxii2Error {
             Self::NotFound(_) => StatusCode::NOT_FOUND,
             Self::Unauthorized => StatusCode::UNAUTHORIZED,
             Self::Forbidden => StatusCode::FORBIDDEN,
+            Self::InsufficientScope(_) => StatusCode::FORBIDDEN,
             Self::BadRequest(_) => StatusCode::BAD_REQUEST,
             Self::MethodNotAllowed => StatusCode::METHOD_NOT_ALLOWED,
             Self::NotAcceptable => StatusCode::NOT_ACCEPTABLE,
             Self::UnsupportedMediaType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
             Self::RequestEntityTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::Timeout => StatusCode::SERVICE_UNAVAILABLE,
+            Self::TooManyRequests => StatusCode::TOO_MANY_REQUESTS,
             Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
-            Self::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::Database(e) => database_status(e),
             Self::Json(_) => StatusCode::BAD_REQUEST,
             Self::Stix2(_) => StatusCode::BAD_REQUEST,
         }
@@ -122,10 +155,17 @@ impl Taxii2Error {
             Self::Validation(msg) => Some(msg.clone()),
             Self::NotFound(msg) => Some(msg.clone()),
             Self::BadRequest(msg) => Some(msg.clone()),
+            Self::InsufficientScope(scope) => {
+                Some(format!("Token scope does not include '{scope}'"))
+            }
+
+            Self::Database(taxii_db::DatabaseError::NotFound(msg)) => Some(msg.clone()),
 
             // Generic messages for internal/sensitive errors
             Self::Database(_) => Some("A database error occurred".to_string()),
             Self::Internal(_) => Some("An internal error occurred".to_string()),
+            Self::Timeout => Some("The request did not complete in time".to_string()),
+            Self::TooManyRequests => Some("Request rate limit exceeded".to_string()),
             Self::Json(_) => Some("Invalid JSON format".to_string()),
             Self::Stix2(_) => Some("Invalid STIX object format".to_string()),
 
@@ -145,13 +185,18 @@ impl Taxii2Error {
             Self::NotFound(_) => "Not Found",
             Self::Unauthorized => "Unauthorized",
             Self::Forbidden => "Forbidden",
+            Self::InsufficientScope(_) => "Forbidden",
             Self::BadRequest(_) => "Bad Request",
             Self::MethodNotAllowed => "Method Not Allowed",
             Self::NotAcceptable => "Not Acceptable",
             Self::UnsupportedMediaType => "Unsupported Media Type",
             Self::RequestEntityTooLarge => "Payload Too Large",
+            Self::Timeout => "Service Unavailable",
+            Self::TooManyRequests => "Too Many Requests",
             Self::Internal(_) => "Internal Server Error",
-            Self::Database(_) => "Internal Server Error",
+            Self::Database(e) => database_status(e)
+                .canonical_reason()
+                .unwrap_or("Internal Server Error"),
             Self::Json(_) => "Bad Request",
             Self::Stix2(_) => "STIX Validation Error",
         }
@@ -165,6 +210,7 @@ impl IntoResponse for Taxii2Error {
         match &self {
             Self::Database(e) => error!("Database error: {:?}", e),
             Self::Internal(msg) => error!("Internal error: {}", msg),
+            Self::Timeout => warn!("Request timed out"),
             Self::Json(e) => warn!("JSON parsing error: {}", e),
             Self::Stix2(e) => warn!("STIX2 validation error: {}", e),
             _ => debug!("Client error: {:?}", self),
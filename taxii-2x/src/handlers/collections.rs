@@ -22,8 +22,8 @@ use taxii_db::{PaginatedResult, Taxii2QueryParams, Taxii2Repository};
 /// Collections handler.
 ///
 /// GET /taxii2/{api_root_id}/collections/
-pub async fn collections_handler(
-    State(state): State<Arc<Taxii2State>>,
+pub async fn collections_handler<R: Taxii2Repository>(
+    State(state): State<Arc<Taxii2State<R>>>,
     Path(api_root_id): Path<String>,
     headers: HeaderMap,
     account: Option<Extension<Account>>,
@@ -59,6 +59,7 @@ pub async fn collections_handler(
             can_read: c.can_read(account.as_ref()),
             can_write: c.can_write(account.as_ref()),
             media_types: vec!["application/stix+json;version=2.1".to_string()],
+            stats: None,
         })
         .collect();
 
@@ -76,8 +77,8 @@ pub async fn collections_handler(
 /// Single collection handler.
 ///
 /// GET /taxii2/{api_root_id}/collections/{collection_id}/
-pub async fn collection_handler(
-    State(state): State<Arc<Taxii2State>>,
+pub async fn collection_handler<R: Taxii2Repository>(
+    State(state): State<Arc<Taxii2State<R>>>,
     Path((api_root_id, collection_id_or_alias)): Path<(String, String)>,
     headers: HeaderMap,
     account: Option<Extension<Account>>,
@@ -104,14 +105,22 @@ pub async fn collection_handler(
         return Err(Taxii2Error::Unauthorized);
     }
 
+    let can_read = collection.can_read(account.as_ref());
+    let stats = if state.config.collection_stats && can_read {
+        Some(state.persistence.collection_stats(&collection.id).await?)
+    } else {
+        None
+    };
+
     let response = CollectionInfo {
         id: collection.id.clone(),
         title: collection.title.clone(),
         description: collection.description.clone(),
         alias: collection.alias.clone(),
-        can_read: collection.can_read(account.as_ref()),
+        can_read,
         can_write: collection.can_write(account.as_ref()),
         media_types: vec!["application/stix+json;version=2.1".to_string()],
+        stats,
     };
 
     Ok(Taxii2Response::new(response))
@@ -120,8 +129,8 @@ pub async fn collection_handler(
 /// Manifest handler.
 ///
 /// GET /taxii2/{api_root_id}/collections/{collection_id}/manifest/
-pub async fn manifest_handler(
-    State(state): State<Arc<Taxii2State>>,
+pub async fn manifest_handler<R: Taxii2Repository>(
+    State(state): State<Arc<Taxii2State<R>>>,
     Path((api_root_id, collection_id_or_alias)): Path<(String, String)>,
     headers: HeaderMap,
     Query(params): Query<ListQueryParams>,
@@ -131,6 +140,11 @@ pub async fn manifest_handler(
 
     let account = account.map(|e| e.0);
     let filter = validate_list_params(&params)?;
+    if filter.match_contains.is_some() {
+        return Err(Taxii2Error::Validation(
+            "match[contains] is only supported by the objects endpoint".to_string(),
+        ));
+    }
 
     // Get collection first to check access
     let collection = state
@@ -168,6 +182,8 @@ pub async fn manifest_handler(
         match_type: filter.match_type.as_deref(),
         match_version: filter.match_version.as_deref(),
         match_spec_version: filter.match_spec_version.as_deref(),
+        match_properties: filter.match_properties.as_deref(),
+        include_deleted: false,
     };
     let PaginatedResult {
         items: manifest,
@@ -200,7 +216,8 @@ pub async fn manifest_handler(
 
     let response = ManifestResponse {
         more: Some(more),
-        next: next_param,
+        // The final page has no next cursor
+        next: next_param.filter(|_| more),
         objects: Some(entries),
     };
 
@@ -232,8 +249,8 @@ where
 /// Versions handler.
 ///
 /// GET /taxii2/{api_root_id}/collections/{collection_id}/objects/{object_id}/versions/
-pub async fn versions_handler(
-    State(state): State<Arc<Taxii2State>>,
+pub async fn versions_handler<R: Taxii2Repository>(
+    State(state): State<Arc<Taxii2State<R>>>,
     Path((api_root_id, collection_id_or_alias, object_id)): Path<(String, String, String)>,
     headers: HeaderMap,
     Query(params): Query<VersionsQueryParams>,
@@ -304,7 +321,8 @@ pub async fn versions_handler(
 
     let response = VersionsResponse {
         more: Some(more),
-        next: next_param,
+        // The final page has no next cursor
+        next: next_param.filter(|_| more),
         versions: Some(version_strings),
     };
 
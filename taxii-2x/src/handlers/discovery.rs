@@ -7,9 +7,12 @@ use axum::http::HeaderMap;
 use axum::response::IntoResponse;
 
 use crate::error::{Taxii2Error, Taxii2Result};
-use crate::http::Taxii2Response;
-use crate::responses::{ApiRootResponse, DiscoveryResponse};
-use crate::state::Taxii2State;
+use crate::http::{STIX21_CONTENT_TYPE, TAXII2_CONTENT_TYPE, Taxii2Response};
+use crate::responses::{
+    ApiRootResponse, CapabilitiesResponse, DeleteCapabilities, DiscoveryResponse,
+    PaginationCapabilities,
+};
+use crate::state::{Taxii2Config, Taxii2State};
 use crate::validation::validate_accept_header;
 use taxii_core::Account;
 use taxii_db::Taxii2Repository;
@@ -17,8 +20,8 @@ use taxii_db::Taxii2Repository;
 /// Discovery handler.
 ///
 /// GET /taxii2/
-pub async fn discovery_handler(
-    State(state): State<Arc<Taxii2State>>,
+pub async fn discovery_handler<R: Taxii2Repository>(
+    State(state): State<Arc<Taxii2State<R>>>,
     headers: HeaderMap,
     account: Option<Extension<Account>>,
 ) -> Taxii2Result<impl IntoResponse> {
@@ -53,11 +56,59 @@ pub async fn discovery_handler(
     Ok(Taxii2Response::new(response))
 }
 
+/// Capabilities handler.
+///
+/// GET /taxii2/capabilities/
+///
+/// Only routed when [`Taxii2Config::capabilities_endpoint`] is set. Access
+/// follows the discovery endpoint.
+pub async fn capabilities_handler<R: Taxii2Repository>(
+    State(state): State<Arc<Taxii2State<R>>>,
+    headers: HeaderMap,
+    account: Option<Extension<Account>>,
+) -> Taxii2Result<impl IntoResponse> {
+    validate_accept_header(&headers)?;
+
+    if account.is_none() && !state.config.public_discovery {
+        return Err(Taxii2Error::Unauthorized);
+    }
+
+    Ok(Taxii2Response::new(capabilities(
+        &state.config,
+        state.persistence.hard_delete(),
+    )))
+}
+
+/// Describe the features enabled by a server configuration.
+///
+/// `hard_delete` is whether the repository removes deleted objects instead
+/// of keeping tombstones.
+pub fn capabilities(config: &Taxii2Config, hard_delete: bool) -> CapabilitiesResponse {
+    CapabilitiesResponse {
+        versions: vec![TAXII2_CONTENT_TYPE.to_string()],
+        media_types: vec![STIX21_CONTENT_TYPE.to_string()],
+        max_content_length: config.max_content_length,
+        pagination: PaginationCapabilities {
+            default_limit: config
+                .default_pagination_limit
+                .min(config.max_pagination_limit),
+            max_limit: config.max_pagination_limit,
+        },
+        delete: DeleteCapabilities {
+            supported: true,
+            tombstones: !hard_delete,
+        },
+        versioning: true,
+        auth_methods: config.auth_methods.clone(),
+        public_discovery: config.public_discovery,
+    }
+}
+
 /// API Root handler.
 ///
 /// GET /taxii2/{api_root_id}/
-pub async fn api_root_handler(
-    State(state): State<Arc<Taxii2State>>,
+pub async fn api_root_handler<R: Taxii2Repository>(
+    State(state): State<Arc<Taxii2State<R>>>,
     Path(api_root_id): Path<String>,
     headers: HeaderMap,
     account: Option<Extension<Account>>,
@@ -82,10 +133,10 @@ pub async fn api_root_handler(
     }
 
     let response = ApiRootResponse {
+        max_content_length: api_root.content_length_limit(state.config.max_content_length),
         title: api_root.title,
         description: api_root.description,
         versions: vec!["application/taxii+json;version=2.1".to_string()],
-        max_content_length: state.config.max_content_length,
     };
 
     Ok(Taxii2Response::new(response))
@@ -94,8 +145,8 @@ pub async fn api_root_handler(
 /// Job status handler.
 ///
 /// GET /taxii2/{api_root_id}/status/{job_id}/
-pub async fn job_handler(
-    State(state): State<Arc<Taxii2State>>,
+pub async fn job_handler<R: Taxii2Repository>(
+    State(state): State<Arc<Taxii2State<R>>>,
     Path((api_root_id, job_id)): Path<(String, String)>,
     headers: HeaderMap,
     account: Option<Extension<Account>>,
@@ -127,3 +178,43 @@ pub async fn job_handler(
 
     Ok(Taxii2Response::new(job.as_taxii2_dict()))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capabilities_reflect_config() -> Result<(), serde_json::Error> {
+        let config = Taxii2Config {
+            max_content_length: 2048,
+            public_discovery: true,
+            default_pagination_limit: 5000,
+            max_pagination_limit: 500,
+            capabilities_endpoint: true,
+            auth_methods: vec!["bearer".to_string(), "basic".to_string()],
+            ..Taxii2Config::default()
+        };
+
+        let document = serde_json::to_value(capabilities(&config, true))?;
+        assert_eq!(
+            document,
+            serde_json::json!({
+                "versions": ["application/taxii+json;version=2.1"],
+                "media_types": ["application/stix+json;version=2.1"],
+                "max_content_length": 2048,
+                "pagination": {"default_limit": 500, "max_limit": 500},
+                "delete": {"supported": true, "tombstones": false},
+                "versioning": true,
+                "auth_methods": ["bearer", "basic"],
+                "public_discovery": true
+            })
+        );
+
+        let document = serde_json::to_value(capabilities(&Taxii2Config::default(), false))?;
+        assert_eq!(document["max_content_length"], 10 * 1024 * 1024);
+        assert_eq!(document["delete"]["tombstones"], true);
+        assert_eq!(document["auth_methods"], serde_json::json!(["bearer"]));
+        assert_eq!(document["public_discovery"], false);
+        Ok(())
+    }
+}
@@ -17,6 +17,7 @@
 //! | Endpoint | Description |
 //! |----------|-------------|
 //! | `GET /taxii2/` | Server discovery - lists available API roots |
+//! | `GET /taxii2/capabilities/` | Enabled features (when configured) |
 //! | `GET /taxii2/{api_root}/` | API root information |
 //! | `GET /taxii2/{api_root}/collections/` | List collections in an API root |
 //! | `GET /taxii2/{api_root}/collections/{id}/` | Get collection details |
@@ -37,7 +38,9 @@ mod objects;
 pub use collections::{
     collection_handler, collections_handler, manifest_handler, versions_handler,
 };
-pub use discovery::{api_root_handler, discovery_handler, job_handler};
+pub use discovery::{
+    api_root_handler, capabilities, capabilities_handler, discovery_handler, job_handler,
+};
 pub use objects::{
     object_delete_handler, object_get_handler, objects_get_handler, objects_post_handler,
 };
@@ -1,5 +1,6 @@
 //! STIX object handlers.
 
+use std::collections::BTreeMap;
 use std::sync::Arc;
 
 use axum::extract::{Extension, Path, Query, State};
@@ -9,21 +10,22 @@ use serde_json::{Value, json};
 
 use crate::error::{Taxii2Error, Taxii2Result};
 use crate::http::{EmptyTaxii2Response, Taxii2Response};
+use crate::provenance::apply_provenance_policy;
 use crate::responses::ObjectsResponse;
 use crate::state::{Taxii2State, enforce_pagination_limit};
 use crate::validation::{
-    DeleteQueryParams, ListQueryParams, ObjectQueryParams, validate_accept_header,
-    validate_content_length, validate_content_type, validate_delete_params, validate_envelope,
-    validate_list_params, validate_object_params,
+    DeleteQueryParams, ListQueryParams, ObjectQueryParams, check_indicator_patterns,
+    validate_accept_header, validate_content_length, validate_content_type, validate_delete_params,
+    validate_envelope, validate_list_params, validate_object_params,
 };
-use taxii_core::{Account, taxii2_datetimeformat};
+use taxii_core::{Account, Collection, ScopeAccess, taxii2_datetimeformat};
 use taxii_db::{PaginatedResult, Taxii2QueryParams, Taxii2Repository};
 
 /// Objects GET handler.
 ///
 /// GET /taxii2/{api_root_id}/collections/{collection_id}/objects/
-pub async fn objects_get_handler(
-    State(state): State<Arc<Taxii2State>>,
+pub async fn objects_get_handler<R: Taxii2Repository>(
+    State(state): State<Arc<Taxii2State<R>>>,
     Path((api_root_id, collection_id_or_alias)): Path<(String, String)>,
     headers: HeaderMap,
     Query(params): Query<ListQueryParams>,
@@ -69,15 +71,28 @@ pub async fn objects_get_handler(
         match_type: filter.match_type.as_deref(),
         match_version: filter.match_version.as_deref(),
         match_spec_version: filter.match_spec_version.as_deref(),
+        match_properties: filter.match_properties.as_deref(),
+        include_deleted: false,
     };
+    // Searches are ordered by relevance rather than date added
     let PaginatedResult {
         items: objects,
         more,
         next: next_param,
-    } = state
-        .persistence
-        .get_objects(&collection.id, &params)
-        .await?;
+    } = match filter.match_contains.as_deref() {
+        Some(query) => {
+            state
+                .persistence
+                .search_objects(&collection.id, query, &params)
+                .await?
+        }
+        None => {
+            state
+                .persistence
+                .get_objects(&collection.id, &params)
+                .await?
+        }
+    };
 
     if objects.is_empty() {
         return Ok(Taxii2Response::new(ObjectsResponse {
@@ -104,7 +119,8 @@ pub async fn objects_get_handler(
 
     let response = ObjectsResponse {
         more: Some(more),
-        next: next_param,
+        // The final page has no next cursor
+        next: next_param.filter(|_| more),
         objects: Some(obj_values),
     };
 
@@ -136,21 +152,33 @@ where
 /// Objects POST handler.
 ///
 /// POST /taxii2/{api_root_id}/collections/{collection_id}/objects/
-pub async fn objects_post_handler(
-    State(state): State<Arc<Taxii2State>>,
+pub async fn objects_post_handler<R: Taxii2Repository>(
+    State(state): State<Arc<Taxii2State<R>>>,
     Path((api_root_id, collection_id_or_alias)): Path<(String, String)>,
     headers: HeaderMap,
     account: Option<Extension<Account>>,
     body: axum::body::Bytes,
 ) -> Taxii2Result<impl IntoResponse> {
     validate_accept_header(&headers)?;
-    validate_content_type(&headers)?;
+    validate_content_type(&headers, state.config.strict_content_type)?;
     validate_content_length(&headers, body.len(), state.config.max_content_length)?;
 
+    // The API root may accept less than the server; checked before the body
+    // is parsed
+    if let Some(api_root) = state.persistence.get_api_root(&api_root_id).await? {
+        let limit = api_root.content_length_limit(state.config.max_content_length);
+        validate_content_length(&headers, body.len(), limit)?;
+    }
+
     let account = account.map(|e| e.0);
 
     // Validate STIX bundle with stix2-rust
-    let validated = validate_envelope(&body, state.config.allow_custom_properties)?;
+    let mut validated = validate_envelope(
+        &body,
+        state.config.allow_custom_properties,
+        state.config.max_bundle_objects,
+    )?;
+    check_indicator_patterns(&validated.objects, &state.config.pattern_limits)?;
 
     let collection = state
         .persistence
@@ -167,6 +195,8 @@ pub async fn objects_post_handler(
     if !collection.can_write(account.as_ref()) {
         return Err(if account.is_none() {
             Taxii2Error::Unauthorized
+        } else if let Some(err) = insufficient_write_scope(&collection, account.as_ref()) {
+            err
         } else {
             Taxii2Error::NotFound("Collection not found".to_string())
         });
@@ -174,16 +204,39 @@ pub async fn objects_post_handler(
 
     // Extract objects from validated bundle
     let objects = validated.json_data["objects"]
-        .as_array()
+        .as_array_mut()
         .ok_or_else(|| Taxii2Error::Validation("Objects must be an array".to_string()))?;
 
-    let job = state
+    apply_provenance_policy(objects, state.config.provenance_policy, account.as_ref())?;
+
+    // Unmarked objects get the collection's default marking
+    for object in objects.iter_mut() {
+        collection.apply_default_marking(object)?;
+    }
+
+    // Objects failing the collection's rules are reported in the job status
+    let rejected: BTreeMap<usize, String> = objects
+        .iter()
+        .enumerate()
+        .filter_map(|(index, obj)| collection.check_object(obj).err().map(|msg| (index, msg)))
+        .collect();
+
+    // Objects colliding with stored versions are handled by the collision
+    // policy, and the decision is reported in the job status as well
+    let report = state
         .persistence
-        .add_objects(&api_root_id, &collection.id, objects)
+        .add_objects_bulk_with_rejections(
+            &api_root_id,
+            &collection.id,
+            objects,
+            &rejected,
+            state.config.collision_policy,
+            account.as_ref().map(|a| a.id),
+        )
         .await?;
 
     Ok(Taxii2Response::with_status(
-        job.as_taxii2_dict(),
+        report.job.as_taxii2_dict(),
         StatusCode::ACCEPTED,
     ))
 }
@@ -191,8 +244,8 @@ pub async fn objects_post_handler(
 /// Single object GET handler.
 ///
 /// GET /taxii2/{api_root_id}/collections/{collection_id}/objects/{object_id}/
-pub async fn object_get_handler(
-    State(state): State<Arc<Taxii2State>>,
+pub async fn object_get_handler<R: Taxii2Repository>(
+    State(state): State<Arc<Taxii2State<R>>>,
     Path((api_root_id, collection_id_or_alias, object_id)): Path<(String, String, String)>,
     headers: HeaderMap,
     Query(params): Query<ObjectQueryParams>,
@@ -240,6 +293,8 @@ pub async fn object_get_handler(
         match_type: None,
         match_version: filter.match_version.as_deref(),
         match_spec_version: filter.match_spec_version.as_deref(),
+        match_properties: None,
+        include_deleted: false,
     };
     let PaginatedResult {
         items: objects,
@@ -275,7 +330,8 @@ pub async fn object_get_handler(
 
     let response = ObjectsResponse {
         more: Some(more),
-        next: next_param,
+        // The final page has no next cursor
+        next: next_param.filter(|_| more),
         objects: Some(obj_values),
     };
 
@@ -285,8 +341,8 @@ pub async fn object_get_handler(
 /// Single object DELETE handler.
 ///
 /// DELETE /taxii2/{api_root_id}/collections/{collection_id}/objects/{object_id}/
-pub async fn object_delete_handler(
-    State(state): State<Arc<Taxii2State>>,
+pub async fn object_delete_handler<R: Taxii2Repository>(
+    State(state): State<Arc<Taxii2State<R>>>,
     Path((api_root_id, collection_id_or_alias, object_id)): Path<(String, String, String)>,
     headers: HeaderMap,
     Query(params): Query<DeleteQueryParams>,
@@ -316,6 +372,8 @@ pub async fn object_delete_handler(
         } else if !collection.can_read(account.as_ref()) && !collection.can_write(account.as_ref())
         {
             Taxii2Error::NotFound("Collection not found".to_string())
+        } else if let Some(err) = insufficient_write_scope(&collection, account.as_ref()) {
+            err
         } else {
             Taxii2Error::Forbidden
         });
@@ -328,8 +386,246 @@ pub async fn object_delete_handler(
             &object_id,
             filter.match_version.as_deref(),
             filter.match_spec_version.as_deref(),
+            account.as_ref().map(|a| a.id),
         )
         .await?;
 
     Ok(EmptyTaxii2Response::new())
 }
+
+/// Error for a write denied only by the scope of the token.
+///
+/// Returns `None` if the account's own permissions deny the write as well,
+/// or if the collection is not readable with the token.
+fn insufficient_write_scope(
+    collection: &Collection,
+    account: Option<&Account>,
+) -> Option<Taxii2Error> {
+    let account = account?;
+    let missing = account.missing_scope(&collection.id, ScopeAccess::Write)?;
+
+    let unscoped = Account {
+        scope: None,
+        ..account.clone()
+    };
+    (collection.can_read(Some(account)) && collection.can_write(Some(&unscoped)))
+        .then(|| Taxii2Error::InsufficientScope(missing))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::to_bytes;
+    use axum::http::header::{ACCEPT, CONTENT_TYPE};
+    use axum::response::Response;
+    use taxii_core::ValidationRule;
+    use taxii_db::InMemoryTaxii2Repository;
+
+    use crate::http::TAXII2_CONTENT_TYPE;
+    use crate::state::Taxii2Config;
+
+    type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+    const TLP_GREEN: &str = "marking-definition--34098fce-860f-48ae-8e50-ebd3cc5e41da";
+
+    fn indicator(n: u32, confidence: Option<u32>) -> Value {
+        let mut indicator = json!({
+            "type": "indicator",
+            "spec_version": "2.1",
+            "id": format!("indicator--8e2e2d2b-17d4-4cbf-938f-98ee46b3c{n:03}"),
+            "created": "2024-01-01T00:00:00.000Z",
+            "modified": "2024-01-01T00:00:00.000Z",
+            "pattern": "[ipv4-addr:value = '10.0.0.1']",
+            "pattern_type": "stix",
+            "valid_from": "2024-01-01T00:00:00Z"
+        });
+        if let Some(confidence) = confidence {
+            indicator["confidence"] = json!(confidence);
+        }
+        indicator
+    }
+
+    fn headers() -> Result<HeaderMap, Box<dyn std::error::Error>> {
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT, TAXII2_CONTENT_TYPE.parse()?);
+        headers.insert(CONTENT_TYPE, TAXII2_CONTENT_TYPE.parse()?);
+        Ok(headers)
+    }
+
+    async fn body(response: Response) -> Result<Value, Box<dyn std::error::Error>> {
+        Ok(serde_json::from_slice(
+            &to_bytes(response.into_body(), usize::MAX).await?,
+        )?)
+    }
+
+    /// A public collection requiring confidence, marked TLP:GREEN by
+    /// default, in an in-memory repository.
+    async fn setup() -> Result<
+        (Arc<Taxii2State<InMemoryTaxii2Repository>>, String, String),
+        Box<dyn std::error::Error>,
+    > {
+        let persistence = InMemoryTaxii2Repository::new();
+        let api_root = persistence
+            .add_api_root("Root", None, true, true, None)
+            .await?;
+        let collection = persistence
+            .add_collection(&api_root.id, "Feed", None, None, true, true, None)
+            .await?;
+        persistence
+            .set_collection_validation_rules(&collection.id, &[ValidationRule::RequireConfidence])
+            .await?;
+        persistence
+            .set_collection_default_marking(&collection.id, Some(TLP_GREEN))
+            .await?;
+        let state = Arc::new(Taxii2State {
+            persistence,
+            config: Taxii2Config::default(),
+        });
+        Ok((state, api_root.id, collection.id))
+    }
+
+    async fn post(
+        state: &Arc<Taxii2State<InMemoryTaxii2Repository>>,
+        api_root_id: &str,
+        collection_id: &str,
+        objects: Vec<Value>,
+    ) -> Result<Response, Box<dyn std::error::Error>> {
+        let bundle = json!({
+            "type": "bundle",
+            "id": "bundle--5d0092c5-5f74-4287-9642-33f4c354e56d",
+            "objects": objects
+        });
+        Ok(objects_post_handler(
+            State(state.clone()),
+            Path((api_root_id.to_string(), collection_id.to_string())),
+            headers()?,
+            None,
+            axum::body::Bytes::from(bundle.to_string()),
+        )
+        .await
+        .into_response())
+    }
+
+    /// Posted objects get the default marking, objects failing the rules
+    /// are rejected, and colliding versions follow the collision policy.
+    #[tokio::test]
+    async fn test_post_applies_collection_policies() -> TestResult {
+        let (state, api_root_id, collection_id) = setup().await?;
+
+        let response = post(
+            &state,
+            &api_root_id,
+            &collection_id,
+            vec![indicator(1, Some(80)), indicator(2, None)],
+        )
+        .await?;
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        let status = body(response).await?;
+        assert_eq!(status["success_count"], 1);
+        assert_eq!(status["failure_count"], 1);
+        assert_eq!(
+            status["failures"][0]["id"],
+            indicator(2, None)["id"].clone()
+        );
+
+        let response = post(
+            &state,
+            &api_root_id,
+            &collection_id,
+            vec![indicator(1, Some(20))],
+        )
+        .await?;
+        let status = body(response).await?;
+        assert_eq!(status["failure_count"], 1);
+        assert!(
+            status["failures"][0]["message"]
+                .as_str()
+                .unwrap_or_default()
+                .contains("already exists with different content")
+        );
+
+        let response = objects_get_handler(
+            State(state.clone()),
+            Path((api_root_id, collection_id)),
+            headers()?,
+            Query(ListQueryParams::default()),
+            None,
+        )
+        .await
+        .into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        let envelope = body(response).await?;
+        let objects = envelope["objects"].as_array().ok_or("no objects")?;
+        assert_eq!(objects.len(), 1);
+        assert_eq!(objects[0]["confidence"], 80);
+        assert_eq!(objects[0]["object_marking_refs"], json!([TLP_GREEN]));
+        Ok(())
+    }
+
+    /// The API root's content length limit applies before the body is
+    /// parsed.
+    #[tokio::test]
+    async fn test_api_root_content_length_limit() -> TestResult {
+        let (state, api_root_id, collection_id) = setup().await?;
+        state
+            .persistence
+            .update_api_root(&api_root_id, "Root", None, true, true, Some(16))
+            .await?;
+
+        let response = objects_post_handler(
+            State(state.clone()),
+            Path((api_root_id, collection_id)),
+            headers()?,
+            None,
+            axum::body::Bytes::from_static(b"{ not a bundle, but too large }"),
+        )
+        .await
+        .into_response();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        Ok(())
+    }
+
+    /// Deleting an object hides it from reads.
+    #[tokio::test]
+    async fn test_delete_hides_object() -> TestResult {
+        let (state, api_root_id, collection_id) = setup().await?;
+        post(
+            &state,
+            &api_root_id,
+            &collection_id,
+            vec![indicator(1, Some(80))],
+        )
+        .await?;
+        let object_id = indicator(1, None)["id"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+
+        let response = object_delete_handler(
+            State(state.clone()),
+            Path((
+                api_root_id.clone(),
+                collection_id.clone(),
+                object_id.clone(),
+            )),
+            headers()?,
+            Query(DeleteQueryParams::default()),
+            None,
+        )
+        .await
+        .into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = object_get_handler(
+            State(state),
+            Path((api_root_id, collection_id, object_id)),
+            headers()?,
+            Query(ObjectQueryParams::default()),
+            None,
+        )
+        .await
+        .into_response();
+        assert_eq!(body(response).await?, json!({}));
+        Ok(())
+    }
+}
@@ -18,8 +18,11 @@ pub const VALID_ACCEPT_MIMETYPES: &[&str] = &[
     "*/*",
 ];
 
-/// Valid content types for POST requests.
-pub const VALID_CONTENT_TYPES: &[&str] = &["application/taxii+json;version=2.1"];
+/// Media types (without parameters) of valid POST request content.
+pub const VALID_CONTENT_TYPES: &[&str] = &["application/taxii+json", "application/stix+json"];
+
+/// Version parameter valid POST request content types must carry.
+pub const CONTENT_TYPE_VERSION: &str = "2.1";
 
 /// TAXII 2.x JSON response.
 pub struct Taxii2Response<T: Serialize> {
@@ -3,16 +3,18 @@
 pub mod error;
 pub mod handlers;
 pub mod http;
+pub mod provenance;
 pub mod responses;
 pub mod state;
 pub mod validation;
 
-pub use error::{Taxii2Error, Taxii2Result};
+pub use error::{Taxii2Error, Taxii2Result, database_status};
 pub use handlers::*;
 pub use http::*;
+pub use provenance::ProvenancePolicy;
 pub use responses::*;
 pub use state::{Taxii2Config, Taxii2State, enforce_pagination_limit};
 pub use validation::ValidatedBundle;
 
 // Re-export stix2 types for consumers
-pub use stix2::{Bundle, Identifier, StixObject};
+pub use stix2::{Bundle, Identifier, PatternLimits, StixObject};
@@ -0,0 +1,210 @@
+//! Provenance policy for ingested objects.
+//!
+//! Objects that can carry a creator (SDOs, SROs, marking definitions and
+//! language content, i.e. those with a `created` property) may be required
+//! to name it in `created_by_ref`, or have it filled in with the STIX
+//! identity associated with the posting account. Cyber-observables have no
+//! creator and are never affected.
+
+use std::str::FromStr;
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::error::{Taxii2Error, Taxii2Result};
+use taxii_core::Account;
+
+/// Handling of posted objects without `created_by_ref`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProvenancePolicy {
+    /// Accept objects as posted.
+    #[default]
+    Off,
+    /// Reject envelopes containing objects without `created_by_ref`.
+    Reject,
+    /// Set `created_by_ref` to the identity of the posting account.
+    Stamp,
+}
+
+impl FromStr for ProvenancePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" => Ok(Self::Off),
+            "reject" => Ok(Self::Reject),
+            "stamp" => Ok(Self::Stamp),
+            _ => Err(format!(
+                "unknown provenance policy '{s}' (expected off, reject or stamp)"
+            )),
+        }
+    }
+}
+
+/// Whether an object lacks a creator it could carry.
+fn missing_creator(object: &Value) -> bool {
+    object.get("created").is_some() && object.get("created_by_ref").is_none()
+}
+
+/// Apply a provenance policy to posted objects.
+///
+/// Fails without modifying any object if an object is rejected, or needs
+/// stamping while the account has no associated identity.
+pub fn apply_provenance_policy(
+    objects: &mut [Value],
+    policy: ProvenancePolicy,
+    account: Option<&Account>,
+) -> Taxii2Result<()> {
+    if policy == ProvenancePolicy::Off {
+        return Ok(());
+    }
+
+    let Some(unattributed) = objects.iter().find(|o| missing_creator(o)) else {
+        return Ok(());
+    };
+    let object_id = unattributed["id"].as_str().unwrap_or("<unknown>");
+
+    let identity_id = match policy {
+        ProvenancePolicy::Stamp => account.and_then(|a| a.identity_id.as_deref()),
+        _ => None,
+    };
+    let Some(identity_id) = identity_id else {
+        return Err(Taxii2Error::Validation(match policy {
+            ProvenancePolicy::Stamp => format!(
+                "Object {object_id} has no created_by_ref and the account has no associated identity"
+            ),
+            _ => format!("Object {object_id} has no created_by_ref"),
+        }));
+    };
+
+    for object in objects.iter_mut().filter(|o| missing_creator(o)) {
+        if let Some(map) = object.as_object_mut() {
+            map.insert("created_by_ref".to_string(), Value::from(identity_id));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    const IDENTITY: &str = "identity--f431f809-377b-45e0-aa1c-6a4751cae5ff";
+    const OTHER_IDENTITY: &str = "identity--8e2e2d2b-17d4-4cbf-938f-98ee46b3cd3f";
+
+    fn account(identity_id: Option<&str>) -> Account {
+        Account {
+            id: 1,
+            username: "alice".to_string(),
+            is_admin: false,
+            permissions: HashMap::new(),
+            details: HashMap::new(),
+            scope: None,
+            identity_id: identity_id.map(str::to_string),
+        }
+    }
+
+    fn objects() -> Vec<Value> {
+        vec![
+            json!({
+                "type": "indicator",
+                "id": "indicator--00000000-0000-4000-8000-000000000001",
+                "created": "2024-01-01T00:00:00.000Z",
+                "modified": "2024-01-01T00:00:00.000Z",
+            }),
+            json!({
+                "type": "malware",
+                "id": "malware--00000000-0000-4000-8000-000000000002",
+                "created": "2024-01-01T00:00:00.000Z",
+                "modified": "2024-01-01T00:00:00.000Z",
+                "created_by_ref": OTHER_IDENTITY,
+            }),
+            json!({
+                "type": "ipv4-addr",
+                "id": "ipv4-addr--00000000-0000-4000-8000-000000000003",
+                "value": "10.0.0.1",
+            }),
+        ]
+    }
+
+    fn creators(objects: &[Value]) -> Vec<Option<&str>> {
+        objects
+            .iter()
+            .map(|o| o.get("created_by_ref").and_then(Value::as_str))
+            .collect()
+    }
+
+    #[test]
+    fn test_reject_mode() -> Taxii2Result<()> {
+        let mut posted = objects();
+        let err = apply_provenance_policy(
+            &mut posted,
+            ProvenancePolicy::Reject,
+            Some(&account(Some(IDENTITY))),
+        );
+        assert!(matches!(
+            err,
+            Err(Taxii2Error::Validation(msg)) if msg.contains("indicator--00000000-0000-4000-8000-000000000001")
+        ));
+        assert_eq!(posted, objects());
+
+        // Attributed objects and observables are accepted
+        let mut attributed = objects()[1..].to_vec();
+        apply_provenance_policy(&mut attributed, ProvenancePolicy::Reject, None)?;
+        assert_eq!(attributed, objects()[1..].to_vec());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_stamp_mode() -> Taxii2Result<()> {
+        let mut posted = objects();
+        apply_provenance_policy(
+            &mut posted,
+            ProvenancePolicy::Stamp,
+            Some(&account(Some(IDENTITY))),
+        )?;
+        assert_eq!(
+            creators(&posted),
+            vec![Some(IDENTITY), Some(OTHER_IDENTITY), None]
+        );
+
+        // Without an identity to stamp with, nothing is stamped
+        for account in [None, Some(account(None))] {
+            let mut posted = objects();
+            let result =
+                apply_provenance_policy(&mut posted, ProvenancePolicy::Stamp, account.as_ref());
+            assert!(matches!(result, Err(Taxii2Error::Validation(_))));
+            assert_eq!(posted, objects());
+        }
+
+        // Unless nothing needs stamping
+        let mut attributed = objects()[1..].to_vec();
+        apply_provenance_policy(&mut attributed, ProvenancePolicy::Stamp, None)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_off_mode_and_parsing() -> Result<(), String> {
+        let mut posted = objects();
+        assert!(apply_provenance_policy(&mut posted, ProvenancePolicy::Off, None).is_ok());
+        assert_eq!(posted, objects());
+
+        assert_eq!(
+            "Reject".parse::<ProvenancePolicy>()?,
+            ProvenancePolicy::Reject
+        );
+        assert_eq!(
+            "stamp".parse::<ProvenancePolicy>()?,
+            ProvenancePolicy::Stamp
+        );
+        assert!("strict".parse::<ProvenancePolicy>().is_err());
+
+        Ok(())
+    }
+}
@@ -5,6 +5,7 @@
 
 use serde::Serialize;
 use serde_json::Value;
+use taxii_core::CollectionStats;
 
 /// Discovery response.
 ///
@@ -56,6 +57,10 @@ pub struct CollectionInfo {
     pub can_read: bool,
     pub can_write: bool,
     pub media_types: Vec<String>,
+    /// Collection statistics (custom property, single collection responses
+    /// only when enabled).
+    #[serde(rename = "x_darwis_stats", skip_serializing_if = "Option::is_none")]
+    pub stats: Option<CollectionStats>,
 }
 
 /// Manifest response.
@@ -107,3 +112,40 @@ pub struct VersionsResponse {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub versions: Option<Vec<String>>,
 }
+
+/// Capabilities response.
+///
+/// Returned by `GET /taxii2/capabilities/` when enabled. Lets clients
+/// configure themselves from the server's enabled features instead of by
+/// trial and error.
+#[derive(Debug, Serialize)]
+pub struct CapabilitiesResponse {
+    /// Supported TAXII versions, as media types.
+    pub versions: Vec<String>,
+    /// Media types accepted and returned for STIX content.
+    pub media_types: Vec<String>,
+    pub max_content_length: usize,
+    pub pagination: PaginationCapabilities,
+    pub delete: DeleteCapabilities,
+    /// Whether object versions are kept and listed.
+    pub versioning: bool,
+    /// Accepted authorization schemes.
+    pub auth_methods: Vec<String>,
+    /// Whether discovery is available without authentication.
+    pub public_discovery: bool,
+}
+
+/// Pagination limits of a capabilities response.
+#[derive(Debug, Serialize)]
+pub struct PaginationCapabilities {
+    pub default_limit: i64,
+    pub max_limit: i64,
+}
+
+/// Object deletion support of a capabilities response.
+#[derive(Debug, Serialize)]
+pub struct DeleteCapabilities {
+    pub supported: bool,
+    /// Whether deleted objects are kept as tombstones.
+    pub tombstones: bool,
+}
@@ -1,7 +1,10 @@
 //! TAXII 2.x server state and configuration.
 
+use taxii_core::CollisionPolicy;
 use taxii_db::DbTaxii2Repository;
 
+use crate::provenance::ProvenancePolicy;
+
 /// Configuration for a TAXII 2.1 server instance.
 ///
 /// Controls server-wide behavior including discovery information,
@@ -22,8 +25,17 @@ use taxii_db::DbTaxii2Repository;
 ///
 /// - `max_content_length`: Maximum size of POST bodies (STIX bundles).
 ///   Protects against resource exhaustion attacks.
+/// - `max_bundle_objects`: Maximum number of objects in a posted envelope,
+///   counted before the objects are parsed.
 /// - `allow_custom_properties`: Whether to accept STIX objects with
 ///   custom properties beyond the specification.
+/// - `strict_content_type`: Whether POST bodies must be declared as TAXII
+///   or STIX 2.1 content.
+/// - `provenance_policy`: Whether posted objects must name their creator
+///   in `created_by_ref`, or are attributed to the posting account.
+/// - `collision_policy`: Whether a posted object repeating an existing
+///   version with different content is rejected, replaces it, or is
+///   ignored.
 ///
 /// # Pagination
 ///
@@ -31,6 +43,13 @@ use taxii_db::DbTaxii2Repository;
 /// enforces limits to prevent excessive memory usage:
 /// - `default_pagination_limit`: Used when client doesn't specify `limit`
 /// - `max_pagination_limit`: Hard cap regardless of client request
+///
+/// # Capabilities
+///
+/// - `capabilities_endpoint`: Whether `GET /taxii2/capabilities/` describes
+///   the enabled features to clients.
+/// - `auth_methods`: Authentication schemes accepted by the server, as
+///   advertised in the capabilities document.
 #[derive(Debug, Clone)]
 pub struct Taxii2Config {
     /// Server title shown in discovery response.
@@ -47,6 +66,13 @@ pub struct Taxii2Config {
     /// Requests exceeding this limit receive HTTP 413 (Request Entity Too Large).
     pub max_content_length: usize,
 
+    /// Maximum number of objects in a posted envelope or bundle.
+    ///
+    /// Objects are counted before they are deserialized, so a small body
+    /// claiming a huge number of objects is refused with HTTP 400 without
+    /// holding them in memory.
+    pub max_bundle_objects: usize,
+
     /// Whether to allow unauthenticated access to the discovery endpoint.
     ///
     /// When `false`, clients must authenticate to discover API roots.
@@ -58,6 +84,25 @@ pub struct Taxii2Config {
     /// Set to `false` for strict validation.
     pub allow_custom_properties: bool,
 
+    /// Whether POST requests must declare TAXII or STIX 2.1 content.
+    ///
+    /// When `true`, requests without `Content-Type:
+    /// application/taxii+json;version=2.1` (or `application/stix+json`)
+    /// receive HTTP 415. When `false`, any JSON media type is accepted and
+    /// the header may be omitted.
+    pub strict_content_type: bool,
+
+    /// Handling of posted objects without `created_by_ref`.
+    ///
+    /// See [`ProvenancePolicy`].
+    pub provenance_policy: ProvenancePolicy,
+
+    /// Handling of posted objects whose version (`id` and `modified`)
+    /// exists with different content.
+    ///
+    /// See [`CollisionPolicy`].
+    pub collision_policy: CollisionPolicy,
+
     /// Default pagination limit when client omits the `limit` parameter.
     ///
     /// Applied to objects, manifest, and versions endpoints.
@@ -67,6 +112,24 @@ pub struct Taxii2Config {
     ///
     /// Client-requested limits exceeding this value are reduced.
     pub max_pagination_limit: i64,
+
+    /// Whether single collection responses include collection statistics
+    /// (`x_darwis_stats`) for clients that can read the collection.
+    pub collection_stats: bool,
+
+    /// Limits on the patterns of posted Indicators.
+    ///
+    /// An Indicator whose pattern is too long, or a STIX pattern with too
+    /// many clauses, is rejected with HTTP 400.
+    pub pattern_limits: stix2::PatternLimits,
+
+    /// Whether to serve the capabilities document at
+    /// `GET /taxii2/capabilities/`.
+    pub capabilities_endpoint: bool,
+
+    /// Authorization schemes accepted by the server (e.g. `bearer`,
+    /// `basic`), advertised in the capabilities document.
+    pub auth_methods: Vec<String>,
 }
 
 impl Default for Taxii2Config {
@@ -76,10 +139,18 @@ impl Default for Taxii2Config {
             description: None,
             contact: None,
             max_content_length: 10 * 1024 * 1024, // 10MB
+            max_bundle_objects: 10_000,
             public_discovery: false,
             allow_custom_properties: true,
+            strict_content_type: true,
+            provenance_policy: ProvenancePolicy::Off,
+            collision_policy: CollisionPolicy::Reject,
             default_pagination_limit: 1000,
             max_pagination_limit: 1000,
+            collection_stats: false,
+            pattern_limits: stix2::PatternLimits::default(),
+            capabilities_endpoint: false,
+            auth_methods: vec!["bearer".to_string()],
         }
     }
 }
@@ -104,12 +175,13 @@ impl Default for Taxii2Config {
 ///     .route("/taxii2/", get(discovery_handler))
 ///     .with_state(state);
 /// ```
-pub struct Taxii2State {
+pub struct Taxii2State<R = DbTaxii2Repository> {
     /// Database access layer for TAXII 2.1 entities.
     ///
     /// Provides methods for querying API roots, collections, objects,
-    /// manifests, and versions.
-    pub persistence: DbTaxii2Repository,
+    /// manifests, and versions. Any [`taxii_db::Taxii2Repository`] works,
+    /// e.g. an in-memory one in tests.
+    pub persistence: R,
 
     /// Server configuration controlling limits and behavior.
     pub config: Taxii2Config,
@@ -120,8 +192,42 @@ pub struct Taxii2State {
 /// Returns the effective limit to use:
 /// - If no limit requested, use default_limit
 /// - If limit requested, cap at max_limit
+///
+/// The requested value is expected to have been validated as positive by
+/// the query parameter parsing; the result is never below 1 so a
+/// misconfigured default cannot produce an empty page.
 #[inline]
 pub fn enforce_pagination_limit(requested: Option<i64>, default_limit: i64, max_limit: i64) -> i64 {
     let limit = requested.unwrap_or(default_limit);
-    limit.min(max_limit)
+    limit.min(max_limit).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limit_within_max() {
+        assert_eq!(enforce_pagination_limit(Some(10), 100, 1000), 10);
+    }
+
+    #[test]
+    fn test_limit_above_max_is_clamped() {
+        assert_eq!(enforce_pagination_limit(Some(5000), 100, 1000), 1000);
+    }
+
+    #[test]
+    fn test_limit_equal_to_max() {
+        assert_eq!(enforce_pagination_limit(Some(1000), 100, 1000), 1000);
+    }
+
+    #[test]
+    fn test_missing_limit_uses_default() {
+        assert_eq!(enforce_pagination_limit(None, 100, 1000), 100);
+    }
+
+    #[test]
+    fn test_default_above_max_is_clamped() {
+        assert_eq!(enforce_pagination_limit(None, 2000, 1000), 1000);
+    }
 }
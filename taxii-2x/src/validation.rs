@@ -8,8 +8,8 @@ use serde::Deserialize;
 use std::collections::HashMap;
 
 use crate::error::{Taxii2Error, Taxii2Result};
-use crate::http::{VALID_ACCEPT_MIMETYPES, VALID_CONTENT_TYPES};
-use taxii_db::{PaginationCursor, parse_next_param as db_parse_next_param};
+use crate::http::{CONTENT_TYPE_VERSION, VALID_ACCEPT_MIMETYPES, VALID_CONTENT_TYPES};
+use taxii_db::{PaginationCursor, PropertyFilter, parse_next_param as db_parse_next_param};
 
 /// TAXII 2.x datetime format.
 pub const DATETIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.6fZ";
@@ -21,6 +21,24 @@ pub const VERSION_LAST: &str = "last";
 /// Version filter value: return all versions.
 pub const VERSION_ALL: &str = "all";
 
+/// Maximum length of a `match[contains]` search query, in characters.
+pub const MAX_SEARCH_QUERY_LENGTH: usize = 256;
+
+/// Object properties that can be filtered with `match[<property>]`
+/// (extension).
+pub const FILTERABLE_PROPERTIES: &[&str] = &[
+    "created_by_ref",
+    "labels",
+    "object_marking_refs",
+    "pattern_type",
+    "relationship_type",
+    "source_ref",
+    "target_ref",
+];
+
+/// Property filtered by `match[tlp]`.
+const TLP_PROPERTY: &str = "object_marking_refs";
+
 /// Result of STIX bundle validation.
 ///
 /// Contains the parsed type-safe bundle and raw JSON data for storage.
@@ -56,6 +74,13 @@ pub struct ListQueryParams {
     pub match_version: Option<String>,
     #[serde(rename = "match[spec_version]")]
     pub match_spec_version: Option<String>,
+    /// Extension: full-text search over object content (objects endpoint).
+    #[serde(rename = "match[contains]")]
+    pub match_contains: Option<String>,
+    /// Other parameters, including the `match[<property>]` and `match[tlp]`
+    /// extension filters.
+    #[serde(flatten)]
+    pub other: HashMap<String, String>,
 }
 
 /// Raw query parameters for single object endpoints.
@@ -103,6 +128,8 @@ pub struct ListFilterParams {
     pub match_type: Option<Vec<String>>,
     pub match_version: Option<Vec<String>>,
     pub match_spec_version: Option<Vec<String>>,
+    pub match_contains: Option<String>,
+    pub match_properties: Option<Vec<PropertyFilter>>,
 }
 
 /// Validated filter parameters for object endpoints.
@@ -155,16 +182,81 @@ fn parse_next_param(value: &str) -> Option<PaginationCursor> {
 }
 
 /// Parse limit parameter.
+///
+/// The limit must be a positive integer; `0`, negative values and
+/// non-numeric input are rejected. Clamping to the server maximum
+/// happens later in [`crate::state::enforce_pagination_limit`].
 #[inline]
 fn parse_limit(value: Option<&str>) -> Taxii2Result<Option<i64>> {
     value
         .map(|s| {
-            s.parse()
-                .map_err(|_| Taxii2Error::Validation("Invalid limit".to_string()))
+            let limit: i64 = s
+                .parse()
+                .map_err(|_| Taxii2Error::Validation("Invalid limit".to_string()))?;
+            if limit < 1 {
+                return Err(Taxii2Error::Validation(
+                    "Limit must be a positive integer".to_string(),
+                ));
+            }
+            Ok(limit)
         })
         .transpose()
 }
 
+/// Parse `match[contains]` search query parameter.
+#[inline]
+fn parse_contains(value: Option<&str>) -> Taxii2Result<Option<String>> {
+    value
+        .map(|s| {
+            let query = s.trim();
+            if query.is_empty() {
+                return Err(Taxii2Error::Validation(
+                    "match[contains] must not be empty".to_string(),
+                ));
+            }
+            if query.chars().count() > MAX_SEARCH_QUERY_LENGTH {
+                return Err(Taxii2Error::Validation(format!(
+                    "match[contains] must not exceed {MAX_SEARCH_QUERY_LENGTH} characters"
+                )));
+            }
+            Ok(query.to_string())
+        })
+        .transpose()
+}
+
+/// Parse `match[<property>]` and `match[tlp]` filter parameters.
+///
+/// `match[tlp]` takes TLP levels (`clear`, `white`, `green`, `amber`,
+/// `amber+strict`, `red`) and matches objects marked with the standard
+/// marking definition of any of them. Parameters for properties that are not
+/// in [`FILTERABLE_PROPERTIES`] are ignored like other unknown parameters.
+fn parse_property_filters<'a>(
+    params: impl Iterator<Item = (&'a String, &'a String)>,
+) -> Taxii2Result<Option<Vec<PropertyFilter>>> {
+    let mut filters = Vec::new();
+    for (key, value) in params {
+        let Some(property) = key.strip_prefix("match[").and_then(|k| k.strip_suffix(']')) else {
+            continue;
+        };
+        if property == "tlp" {
+            let marking_ids = value
+                .split(',')
+                .map(|level| {
+                    serde_json::from_value::<stix2::TlpLevel>(serde_json::Value::from(level))
+                        .map(|tlp| tlp.marking_definition_id().to_string())
+                        .map_err(|_| Taxii2Error::Validation(format!("Unknown TLP level: {level}")))
+                })
+                .collect::<Taxii2Result<Vec<_>>>()?;
+            filters.push(PropertyFilter::new(TLP_PROPERTY, marking_ids));
+        } else if FILTERABLE_PROPERTIES.contains(&property) {
+            filters.push(PropertyFilter::new(property, parse_filter(value)));
+        }
+    }
+    // Query parameter order is not significant
+    filters.sort_by(|a, b| a.property.cmp(&b.property).then(a.values.cmp(&b.values)));
+    Ok((!filters.is_empty()).then_some(filters))
+}
+
 /// Parse added_after datetime parameter.
 #[inline]
 fn parse_added_after(value: Option<&str>) -> Taxii2Result<Option<DateTime<Utc>>> {
@@ -177,6 +269,20 @@ fn parse_added_after(value: Option<&str>) -> Taxii2Result<Option<DateTime<Utc>>>
         .transpose()
 }
 
+/// Objects of an envelope, counted without being parsed.
+///
+/// `IgnoredAny` is zero-sized, so the vector never allocates.
+#[derive(Deserialize)]
+struct ObjectCount {
+    #[serde(default)]
+    objects: Vec<serde::de::IgnoredAny>,
+}
+
+/// Error for an envelope with more than `max` objects.
+fn too_many_objects(max: usize) -> Taxii2Error {
+    Taxii2Error::Validation(format!("Envelope contains more than {max} objects"))
+}
+
 /// Validate envelope (STIX bundle) using stix2-rust.
 ///
 /// Handles both:
@@ -187,14 +293,27 @@ fn parse_added_after(value: Option<&str>) -> Taxii2Result<Option<DateTime<Utc>>>
 ///
 /// * `json_data` - Raw JSON bytes of the STIX bundle
 /// * `_allow_custom` - Whether to allow custom STIX types and properties (reserved for future use)
+/// * `max_objects` - Maximum number of objects in the envelope
 ///
 /// # Returns
 ///
 /// A `ValidatedBundle` containing the parsed objects and raw JSON data.
-pub fn validate_envelope(json_data: &[u8], _allow_custom: bool) -> Taxii2Result<ValidatedBundle> {
+pub fn validate_envelope(
+    json_data: &[u8],
+    _allow_custom: bool,
+    max_objects: usize,
+) -> Taxii2Result<ValidatedBundle> {
     let json_str = std::str::from_utf8(json_data)
         .map_err(|e| Taxii2Error::Validation(format!("Invalid UTF-8: {e}")))?;
 
+    // Count objects before building any value, so an envelope claiming
+    // more objects than allowed is refused without holding them in memory
+    if let Ok(envelope) = serde_json::from_str::<ObjectCount>(json_str) {
+        if envelope.objects.len() > max_objects {
+            return Err(too_many_objects(max_objects));
+        }
+    }
+
     // Parse JSON first to check structure
     let json_value: serde_json::Value = serde_json::from_str(json_str)?;
 
@@ -204,7 +323,11 @@ pub fn validate_envelope(json_data: &[u8], _allow_custom: bool) -> Taxii2Result<
 
     if is_full_bundle {
         // Parse as full Bundle using stix2-rust
-        let bundle: stix2::Bundle = stix2::parse_bundle(json_str)?;
+        let bundle =
+            stix2::parse_bundle_with_limit(json_str, max_objects).map_err(|e| match e {
+                stix2::Error::TooManyObjects(max) => too_many_objects(max),
+                e => e.into(),
+            })?;
         let objects: Vec<stix2::StixObject> = bundle.objects.clone();
         let json_data = serde_json::to_value(&bundle)?;
 
@@ -247,6 +370,24 @@ pub fn validate_envelope(json_data: &[u8], _allow_custom: bool) -> Taxii2Result<
     }
 }
 
+/// Check the patterns of the Indicators of a bundle against size limits.
+pub fn check_indicator_patterns(
+    objects: &[stix2::StixObject],
+    limits: &stix2::PatternLimits,
+) -> Taxii2Result<()> {
+    for (idx, obj) in objects.iter().enumerate() {
+        if let stix2::StixObject::Indicator(indicator) = obj {
+            indicator.check_pattern_limits(limits).map_err(|e| {
+                Taxii2Error::Validation(format!(
+                    "Invalid STIX object at index {idx}: {e}; id: {}",
+                    indicator.id
+                ))
+            })?;
+        }
+    }
+    Ok(())
+}
+
 /// Validate and parse list filter parameters from typed query params.
 pub fn validate_list_params(params: &ListQueryParams) -> Taxii2Result<ListFilterParams> {
     Ok(ListFilterParams {
@@ -257,6 +398,8 @@ pub fn validate_list_params(params: &ListQueryParams) -> Taxii2Result<ListFilter
         match_type: params.match_type.as_deref().map(parse_filter),
         match_version: params.match_version.as_deref().map(parse_version_filter),
         match_spec_version: params.match_spec_version.as_deref().map(parse_filter),
+        match_contains: parse_contains(params.match_contains.as_deref())?,
+        match_properties: parse_property_filters(params.other.iter())?,
     })
 }
 
@@ -275,6 +418,8 @@ pub fn validate_list_filter_params(
             .get("match[version]")
             .map(|s| parse_version_filter(s)),
         match_spec_version: params.get("match[spec_version]").map(|s| parse_filter(s)),
+        match_contains: parse_contains(params.get("match[contains]").map(String::as_str))?,
+        match_properties: parse_property_filters(params.iter())?,
     })
 }
 
@@ -375,14 +520,38 @@ pub fn validate_accept_header(headers: &HeaderMap) -> Taxii2Result<()> {
 
 /// Validate Content-Type header for POST requests.
 ///
-/// Ensures the Content-Type matches one of the valid TAXII 2.x content types.
-pub fn validate_content_type(headers: &HeaderMap) -> Taxii2Result<()> {
-    let content_type = headers
-        .get(header::CONTENT_TYPE)
-        .and_then(|v| v.to_str().ok())
-        .unwrap_or("");
-
-    if !VALID_CONTENT_TYPES.contains(&content_type) {
+/// In strict mode the media type must be one of [`VALID_CONTENT_TYPES`]
+/// with a `version` parameter of [`CONTENT_TYPE_VERSION`], and the header is
+/// required. Otherwise any JSON media type is accepted, as is a request
+/// without the header. Media types and parameter names are compared
+/// case-insensitively, ignoring whitespace around parameters.
+pub fn validate_content_type(headers: &HeaderMap, strict: bool) -> Taxii2Result<()> {
+    let Some(content_type) = headers.get(header::CONTENT_TYPE) else {
+        return if strict {
+            Err(Taxii2Error::UnsupportedMediaType)
+        } else {
+            Ok(())
+        };
+    };
+    let content_type = content_type
+        .to_str()
+        .map_err(|_| Taxii2Error::UnsupportedMediaType)?;
+
+    let mut parts = content_type.split(';');
+    let media_type = parts.next().unwrap_or_default().trim().to_ascii_lowercase();
+    let version = parts.find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        name.trim()
+            .eq_ignore_ascii_case("version")
+            .then(|| value.trim().trim_matches('"'))
+    });
+
+    let valid = if strict {
+        VALID_CONTENT_TYPES.contains(&media_type.as_str()) && version == Some(CONTENT_TYPE_VERSION)
+    } else {
+        media_type == "application/json" || media_type.ends_with("+json")
+    };
+    if !valid {
         return Err(Taxii2Error::UnsupportedMediaType);
     }
     Ok(())
@@ -414,3 +583,272 @@ pub fn validate_content_length(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::StatusCode;
+
+    fn list_params_with_limit(limit: &str) -> ListQueryParams {
+        ListQueryParams {
+            limit: Some(limit.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn limit_error_status(limit: &str) -> Option<StatusCode> {
+        validate_list_params(&list_params_with_limit(limit))
+            .err()
+            .map(|e| e.status_code())
+    }
+
+    #[test]
+    fn test_limit_absent() {
+        let filter = validate_list_params(&ListQueryParams::default());
+        assert!(matches!(filter, Ok(ListFilterParams { limit: None, .. })));
+    }
+
+    #[test]
+    fn test_limit_valid() {
+        let filter = validate_list_params(&list_params_with_limit("50"));
+        assert!(matches!(
+            filter,
+            Ok(ListFilterParams {
+                limit: Some(50),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_limit_zero_rejected() {
+        assert_eq!(limit_error_status("0"), Some(StatusCode::BAD_REQUEST));
+    }
+
+    #[test]
+    fn test_limit_negative_rejected() {
+        assert_eq!(limit_error_status("-10"), Some(StatusCode::BAD_REQUEST));
+    }
+
+    #[test]
+    fn test_limit_non_numeric_rejected() {
+        assert_eq!(limit_error_status("ten"), Some(StatusCode::BAD_REQUEST));
+    }
+
+    #[test]
+    fn test_match_contains() {
+        let params = |query: &str| ListQueryParams {
+            match_contains: Some(query.to_string()),
+            ..Default::default()
+        };
+
+        let filter = validate_list_params(&params("  ghost ship "));
+        assert!(matches!(
+            filter,
+            Ok(ListFilterParams { match_contains: Some(q), .. }) if q == "ghost ship"
+        ));
+        assert!(validate_list_params(&params(" ")).is_err());
+        assert!(validate_list_params(&params(&"a".repeat(MAX_SEARCH_QUERY_LENGTH + 1))).is_err());
+    }
+
+    #[test]
+    fn test_match_properties() -> Result<(), Box<dyn std::error::Error>> {
+        let parse = |query: &str| -> Result<_, Box<dyn std::error::Error>> {
+            let uri: axum::http::Uri = format!("/objects/?{query}").parse()?;
+            let axum::extract::Query(params) = axum::extract::Query::try_from_uri(&uri)?;
+            Ok(validate_list_params(&params))
+        };
+
+        let filter = parse(
+            "match%5Btlp%5D=green,red&match%5Blabels%5D=caf%C3%A9,c2&match%5Bname%5D=x&limit=5",
+        )??;
+        assert_eq!(filter.limit, Some(5));
+        assert_eq!(
+            filter.match_properties,
+            Some(vec![
+                PropertyFilter::new("labels", vec!["café".to_string(), "c2".to_string()]),
+                PropertyFilter::new(
+                    "object_marking_refs",
+                    vec![
+                        "marking-definition--34098fce-860f-48ae-8e50-ebd3cc5e41da".to_string(),
+                        "marking-definition--5e57c739-391a-4eb3-b6be-7d15ca92d5ed".to_string(),
+                    ]
+                ),
+            ])
+        );
+
+        assert_eq!(parse("limit=5")??.match_properties, None);
+        assert!(parse("match%5Btlp%5D=purple")?.is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_limit_rejected_on_object_and_versions_endpoints() {
+        let object_params = ObjectQueryParams {
+            limit: Some("0".to_string()),
+            ..Default::default()
+        };
+        assert!(validate_object_params(&object_params).is_err());
+
+        let versions_params = VersionsQueryParams {
+            limit: Some("-1".to_string()),
+            ..Default::default()
+        };
+        assert!(validate_versions_params(&versions_params).is_err());
+    }
+
+    fn content_type(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        if let Ok(value) = value.parse() {
+            headers.insert(header::CONTENT_TYPE, value);
+        }
+        headers
+    }
+
+    #[test]
+    fn test_content_type_accepted() {
+        for value in [
+            "application/taxii+json;version=2.1",
+            "application/stix+json;version=2.1",
+            "Application/TAXII+json; Version=\"2.1\"",
+        ] {
+            assert!(
+                validate_content_type(&content_type(value), true).is_ok(),
+                "{value}"
+            );
+            assert!(
+                validate_content_type(&content_type(value), false).is_ok(),
+                "{value}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_content_type_wrong_type_rejected() {
+        for value in [
+            "application/json",
+            "application/taxii+json",
+            "application/taxii+json;version=2.0",
+            "application/xml",
+            "text/plain",
+        ] {
+            let err = validate_content_type(&content_type(value), true).err();
+            assert_eq!(
+                err.map(|e| e.status_code()),
+                Some(StatusCode::UNSUPPORTED_MEDIA_TYPE),
+                "{value}"
+            );
+        }
+
+        // Lenient mode still refuses content that is not JSON
+        assert!(validate_content_type(&content_type("application/json"), false).is_ok());
+        assert!(validate_content_type(&content_type("text/plain"), false).is_err());
+    }
+
+    #[test]
+    fn test_content_type_missing() {
+        let err = validate_content_type(&HeaderMap::new(), true).err();
+        assert_eq!(
+            err.map(|e| e.status_code()),
+            Some(StatusCode::UNSUPPORTED_MEDIA_TYPE)
+        );
+        assert!(validate_content_type(&HeaderMap::new(), false).is_ok());
+    }
+
+    fn indicator_envelope(pattern: &str) -> Vec<u8> {
+        serde_json::json!({
+            "objects": [{
+                "type": "indicator",
+                "spec_version": "2.1",
+                "id": "indicator--8e2e2d2b-17d4-4cbf-938f-98ee46b3cd3f",
+                "created": "2024-01-01T00:00:00.000Z",
+                "modified": "2024-01-01T00:00:00.000Z",
+                "pattern": pattern,
+                "pattern_type": "stix",
+                "valid_from": "2024-01-01T00:00:00Z"
+            }]
+        })
+        .to_string()
+        .into_bytes()
+    }
+
+    #[test]
+    fn test_envelope_object_count() -> Result<(), Box<dyn std::error::Error>> {
+        let address = |i: usize| {
+            serde_json::json!({
+                "type": "ipv4-addr",
+                "spec_version": "2.1",
+                "id": format!("ipv4-addr--ff26c055-6336-5bc5-b98d-13d6226742{i:02x}"),
+                "value": format!("198.51.100.{i}")
+            })
+        };
+        let envelope = |objects: Vec<serde_json::Value>| {
+            serde_json::json!({"objects": objects})
+                .to_string()
+                .into_bytes()
+        };
+        let bundle = |objects: Vec<serde_json::Value>| {
+            serde_json::json!({
+                "type": "bundle",
+                "id": "bundle--5d0092c5-5f74-4287-9642-33f4c354e56d",
+                "objects": objects
+            })
+            .to_string()
+            .into_bytes()
+        };
+
+        let within = validate_envelope(&envelope(vec![address(1), address(2)]), true, 2)?;
+        assert_eq!(within.objects.len(), 2);
+        let within = validate_envelope(&bundle(vec![address(1), address(2)]), true, 2)?;
+        assert_eq!(within.objects.len(), 2);
+
+        // The object past the limit is not valid STIX: the count is
+        // checked before any object is parsed
+        let over = vec![
+            address(1),
+            address(2),
+            serde_json::json!({"type": "indicator"}),
+        ];
+        for body in [envelope(over.clone()), bundle(over)] {
+            let err = validate_envelope(&body, true, 2).err();
+            assert!(
+                err.is_some_and(|e| e.status_code() == StatusCode::BAD_REQUEST
+                    && e.to_string().contains("more than 2 objects"))
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_indicator_pattern_limits() -> Result<(), Box<dyn std::error::Error>> {
+        let limits = stix2::PatternLimits {
+            max_length: 256,
+            max_clauses: 4,
+        };
+        let clauses = |n: usize| {
+            (0..n)
+                .map(|i| format!("[ipv4-addr:value = '10.0.0.{i}']"))
+                .collect::<Vec<_>>()
+                .join(" OR ")
+        };
+
+        let within = validate_envelope(&indicator_envelope(&clauses(4)), true, 1)?;
+        assert!(check_indicator_patterns(&within.objects, &limits).is_ok());
+
+        let over_complex = validate_envelope(&indicator_envelope(&clauses(5)), true, 1)?;
+        let err = check_indicator_patterns(&over_complex.objects, &limits).err();
+        assert!(err.is_some_and(
+            |e| e.status_code() == StatusCode::BAD_REQUEST && e.to_string().contains("clauses")
+        ));
+
+        let long = format!("[url:value = '{}']", "a".repeat(256));
+        let over_length = validate_envelope(&indicator_envelope(&long), true, 1)?;
+        let err = check_indicator_patterns(&over_length.objects, &limits).err();
+        assert!(
+            err.is_some_and(|e| e.status_code() == StatusCode::BAD_REQUEST
+                && e.to_string().contains("maximum length"))
+        );
+        Ok(())
+    }
+}
@@ -31,4 +31,23 @@ pub enum AuthError {
     /// Invalid permission error.
     #[error("Invalid permission: {0}")]
     InvalidPermission(String),
+
+    /// TOTP error.
+    #[error("TOTP error: {0}")]
+    Totp(String),
+
+    /// Password reset token is unknown, expired or already used.
+    ///
+    /// Deliberately does not say which, to avoid disclosing token state.
+    #[error("Invalid or expired password reset token")]
+    InvalidResetToken,
+
+    /// Password was valid but the account requires a TOTP code.
+    #[error("TOTP code required")]
+    TotpRequired,
+
+    /// Too many failed TOTP or recovery codes; the second factor is locked
+    /// until the given time.
+    #[error("Too many failed two-factor attempts, try again later")]
+    SecondFactorLocked(chrono::DateTime<chrono::Utc>),
 }
@@ -1,23 +1,27 @@
 //! Authentication for DARWIS TAXII.
 //!
-//! This crate handles JWT token generation/validation and password hashing.
-//! Database operations are delegated to taxii-db.
+//! This crate handles JWT token generation/validation, password hashing and
+//! TOTP two-factor authentication. Database operations are delegated to taxii-db.
 
 pub mod error;
 pub mod password;
+pub mod totp;
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::net::IpAddr;
 
-use chrono::{Duration, Utc};
+use chrono::{DateTime, Duration, Utc};
 use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation, decode, encode};
 use serde::{Deserialize, Serialize};
-use tracing::warn;
+use tracing::{info, warn};
 
 pub use error::{AuthError, AuthResult};
 
-use taxii_core::Account as AccountEntity;
-use taxii_db::{Account, AccountActivity, EventType, TaxiiPool, validate_permissions};
+use taxii_core::{Account as AccountEntity, TokenScope};
+use taxii_db::{
+    Account, AccountActivity, AccountTotp, ActivityFilter, ActivityRecord, DatabaseError,
+    EventType, PaginatedResult, PasswordReset, TaxiiPool, validate_permissions,
+};
 
 /// Client information for activity logging.
 #[derive(Debug, Clone, Default)]
@@ -47,14 +51,35 @@ fn account_to_entity(account: &Account) -> AccountEntity {
         is_admin: account.is_admin,
         permissions: account.permissions(),
         details: HashMap::new(),
+        scope: None,
+        identity_id: account.identity_id.clone(),
     }
 }
 
+/// Result of enrolling an account in TOTP two-factor authentication.
+///
+/// The secret and recovery codes are only available at enrollment time.
+#[derive(Debug, Clone)]
+pub struct TotpEnrollment {
+    /// Base32-encoded secret, for manual entry.
+    pub secret: String,
+    /// otpauth:// URI, for QR code rendering.
+    pub otpauth_uri: String,
+    /// Single-use recovery codes.
+    pub recovery_codes: Vec<String>,
+}
+
 /// JWT token claims.
 #[derive(Debug, Serialize, Deserialize)]
 struct Claims {
     account_id: i32,
     exp: i64,
+    /// Token generation of the account at issue time.
+    #[serde(default)]
+    generation: i32,
+    /// Restriction of a down-scoped token.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    scope: Option<TokenScope>,
 }
 
 /// SQL Database Auth API.
@@ -71,6 +96,9 @@ pub struct AuthAPI {
 /// Default token TTL: 1 hour in seconds.
 pub const DEFAULT_TOKEN_TTL_SECS: i64 = 60 * 60;
 
+/// Password reset token TTL: 1 hour in seconds.
+pub const PASSWORD_RESET_TTL_SECS: i64 = 60 * 60;
+
 impl AuthAPI {
     /// Create a new auth API.
     ///
@@ -83,6 +111,10 @@ impl AuthAPI {
             return Err(AuthError::Config("Secret is not defined".to_string()));
         }
 
+        // Compute the dummy hash up front rather than on the first login
+        // with an unknown username
+        password::dummy_password_hash();
+
         Ok(Self {
             pool,
             secret,
@@ -101,7 +133,8 @@ impl AuthAPI {
     /// This is a simple version without activity logging.
     /// Use `authenticate_with_logging` when client info is available.
     pub async fn authenticate(&self, username: &str, password: &str) -> AuthResult<Option<String>> {
-        self.authenticate_internal(username, password, None).await
+        self.authenticate_internal(username, password, None, None, None)
+            .await
     }
 
     /// Authenticate user with activity logging.
@@ -114,79 +147,261 @@ impl AuthAPI {
         password: &str,
         client_info: ClientInfo,
     ) -> AuthResult<Option<String>> {
-        self.authenticate_internal(username, password, Some(client_info))
+        self.authenticate_internal(username, password, None, None, Some(client_info))
+            .await
+    }
+
+    /// Authenticate user with a TOTP code and activity logging.
+    ///
+    /// `totp_code` may be a current TOTP code or an unused recovery code. It is
+    /// required for accounts with two-factor authentication enabled, and
+    /// `AuthError::TotpRequired` is returned when it is missing.
+    pub async fn authenticate_with_totp(
+        &self,
+        username: &str,
+        password: &str,
+        totp_code: Option<&str>,
+        client_info: ClientInfo,
+    ) -> AuthResult<Option<String>> {
+        self.authenticate_internal(username, password, totp_code, None, Some(client_info))
             .await
     }
 
+    /// Authenticate user and return a down-scoped JWT token.
+    ///
+    /// Like `authenticate_with_totp`, but the issued token is restricted to
+    /// `scope`. See `generate_scoped_token`.
+    pub async fn authenticate_with_scope(
+        &self,
+        username: &str,
+        password: &str,
+        totp_code: Option<&str>,
+        scope: TokenScope,
+        client_info: ClientInfo,
+    ) -> AuthResult<Option<String>> {
+        self.authenticate_internal(
+            username,
+            password,
+            totp_code,
+            Some(scope),
+            Some(client_info),
+        )
+        .await
+    }
+
     /// Internal authentication logic.
     async fn authenticate_internal(
         &self,
         username: &str,
         password: &str,
+        totp_code: Option<&str>,
+        scope: Option<TokenScope>,
         client_info: Option<ClientInfo>,
     ) -> AuthResult<Option<String>> {
         let account = Account::find_by_username(&self.pool, username).await?;
 
-        let account = match account {
-            Some(a) => a,
-            None => {
-                // Log failed attempt for unknown username (fire-and-forget)
-                if let Some(info) = client_info {
-                    let pool = self.pool.clone();
-                    let username = username.to_string();
-                    tokio::spawn(async move {
-                        let _ = AccountActivity::log_failed_by_username(
-                            &pool,
-                            &username,
-                            info.ip_address,
-                            info.user_agent.as_deref(),
-                        )
-                        .await;
-                    });
-                }
-                return Ok(None);
-            }
+        // Unknown usernames go through the same password check and failure
+        // logging as wrong passwords, so neither response time nor logging
+        // reveals whether a username exists
+        let Some(account) = account else {
+            password::check_dummy_password(password);
+            self.log_failed_login(username, client_info);
+            return Ok(None);
         };
 
         if !password::check_password_hash(&account.password_hash, password) {
-            // Log failed login attempt (fire-and-forget)
-            if let Some(info) = client_info {
-                let pool = self.pool.clone();
-                let account_id = account.id;
-                tokio::spawn(async move {
-                    let _ = AccountActivity::log(
-                        &pool,
-                        account_id,
-                        EventType::LoginFailed,
-                        info.ip_address,
-                        info.user_agent.as_deref(),
-                    )
-                    .await;
-                });
-            }
+            self.log_failed_login(username, client_info);
             return Ok(None);
         }
 
-        // Log successful login (fire-and-forget)
-        if let Some(info) = client_info {
-            let pool = self.pool.clone();
-            let account_id = account.id;
-            tokio::spawn(async move {
-                let _ = AccountActivity::log(
-                    &pool,
-                    account_id,
-                    EventType::LoginSuccess,
-                    info.ip_address,
-                    info.user_agent.as_deref(),
-                )
-                .await;
-            });
+        // Accounts with two-factor authentication need a valid second factor
+        if let Some(totp) = AccountTotp::find(&self.pool, account.id).await? {
+            let Some(code) = totp_code else {
+                return Err(AuthError::TotpRequired);
+            };
+            match self.verify_second_factor(&totp, code).await {
+                Ok(true) => {}
+                Ok(false) => {
+                    self.log_activity(account.id, EventType::LoginFailed, client_info);
+                    return Ok(None);
+                }
+                Err(e) => {
+                    self.log_activity(account.id, EventType::LoginFailed, client_info);
+                    return Err(e);
+                }
+            }
         }
 
-        let token = self.generate_token(account.id, Some(self.token_ttl_secs))?;
+        self.log_activity(account.id, EventType::LoginSuccess, client_info);
+
+        let token = match scope {
+            Some(scope) => self.generate_scoped_token(&account, scope)?,
+            None => self.generate_token(&account, Some(self.token_ttl_secs), None)?,
+        };
         Ok(Some(token))
     }
 
+    /// Generate a token restricted to a scope.
+    ///
+    /// The scope is encoded in the token claims. It can only narrow access:
+    /// the effective permissions are the intersection of the scope and the
+    /// account's stored permissions at the time the token is used.
+    pub fn generate_scoped_token(
+        &self,
+        account: &Account,
+        scope: TokenScope,
+    ) -> AuthResult<String> {
+        info!(account_id = account.id, scope = %scope, "Issuing scoped token");
+        self.generate_token(account, Some(self.token_ttl_secs), Some(scope))
+    }
+
+    /// Log a failed login by username in the background (fire-and-forget).
+    ///
+    /// Nothing is recorded for unknown usernames, but the work done is the
+    /// same as for existing accounts.
+    fn log_failed_login(&self, username: &str, client_info: Option<ClientInfo>) {
+        let Some(info) = client_info else {
+            return;
+        };
+
+        let pool = self.pool.clone();
+        let username = username.to_string();
+        tokio::spawn(async move {
+            let _ = AccountActivity::log_failed_by_username(
+                &pool,
+                &username,
+                info.ip_address,
+                info.user_agent.as_deref(),
+            )
+            .await;
+        });
+    }
+
+    /// Log account activity in the background (fire-and-forget).
+    fn log_activity(
+        &self,
+        account_id: i32,
+        event_type: EventType,
+        client_info: Option<ClientInfo>,
+    ) {
+        let Some(info) = client_info else {
+            return;
+        };
+
+        let pool = self.pool.clone();
+        tokio::spawn(async move {
+            let _ = AccountActivity::log(
+                &pool,
+                account_id,
+                event_type,
+                info.ip_address,
+                info.user_agent.as_deref(),
+            )
+            .await;
+        });
+    }
+
+    /// Enroll an account in TOTP two-factor authentication.
+    ///
+    /// Generates a new secret and recovery codes, replacing any previous
+    /// enrollment. The secret is stored encrypted with a key derived from
+    /// the auth secret, so changing the auth secret requires re-enrollment.
+    pub async fn enroll_totp(&self, account_id: i32) -> AuthResult<TotpEnrollment> {
+        let account = Account::find(&self.pool, account_id)
+            .await?
+            .ok_or_else(|| DatabaseError::not_found("Account not found"))?;
+
+        let secret = totp::generate_secret();
+        let secret_encrypted = totp::encrypt_secret(&self.secret, &secret)?;
+        let recovery_codes = totp::generate_recovery_codes();
+        let recovery_code_hashes: Vec<String> = recovery_codes
+            .iter()
+            .map(|code| totp::hash_recovery_code(code))
+            .collect();
+
+        AccountTotp::enroll(
+            &self.pool,
+            account.id,
+            &secret_encrypted,
+            &recovery_code_hashes,
+        )
+        .await?;
+
+        Ok(TotpEnrollment {
+            secret: totp::encode_secret(&secret),
+            otpauth_uri: totp::provisioning_uri(&secret, &account.username),
+            recovery_codes,
+        })
+    }
+
+    /// Verify a TOTP or recovery code for an account.
+    ///
+    /// Accepted codes are consumed and cannot be used again. Returns `false`
+    /// if the account is not enrolled. Failures count towards the lockout
+    /// of `verify_second_factor`.
+    pub async fn verify_totp(&self, account_id: i32, code: &str) -> AuthResult<bool> {
+        match AccountTotp::find(&self.pool, account_id).await? {
+            Some(totp) => self.verify_second_factor(&totp, code).await,
+            None => Ok(false),
+        }
+    }
+
+    /// Check whether an account has TOTP two-factor authentication enabled.
+    pub async fn is_totp_enabled(&self, account_id: i32) -> AuthResult<bool> {
+        Ok(AccountTotp::find(&self.pool, account_id).await?.is_some())
+    }
+
+    /// Disable TOTP two-factor authentication for an account.
+    ///
+    /// Returns `false` if the account was not enrolled.
+    pub async fn disable_totp(&self, account_id: i32) -> AuthResult<bool> {
+        Ok(AccountTotp::delete(&self.pool, account_id).await?)
+    }
+
+    /// Verify and consume a TOTP or recovery code.
+    ///
+    /// `totp::MAX_FAILED_ATTEMPTS` consecutive wrong codes lock the second
+    /// factor for `totp::LOCKOUT_SECS`; until then every code fails with
+    /// `AuthError::SecondFactorLocked` without being checked.
+    async fn verify_second_factor(&self, totp: &AccountTotp, code: &str) -> AuthResult<bool> {
+        if let Some(until) = totp.locked_until.filter(|until| *until > Utc::now()) {
+            return Err(AuthError::SecondFactorLocked(until));
+        }
+
+        let verified = self.check_second_factor(totp, code.trim()).await?;
+        if verified {
+            AccountTotp::reset_failures(&self.pool, totp.account_id).await?;
+        } else if let Some(until) = AccountTotp::record_failure(
+            &self.pool,
+            totp.account_id,
+            totp::MAX_FAILED_ATTEMPTS,
+            totp::LOCKOUT_SECS as f64,
+        )
+        .await?
+        .filter(|until| *until > Utc::now())
+        {
+            warn!(account_id = totp.account_id, %until, "Second factor locked after failed attempts");
+        }
+        Ok(verified)
+    }
+
+    /// Check and consume a TOTP or recovery code.
+    async fn check_second_factor(&self, totp: &AccountTotp, code: &str) -> AuthResult<bool> {
+        if totp::is_totp_code(code) {
+            let secret = totp::decrypt_secret(&self.secret, &totp.secret_encrypted)?;
+            let Some(step) =
+                totp::verify_code(&secret, code, Utc::now().timestamp(), totp.last_used_step)
+            else {
+                return Ok(false);
+            };
+            // Recorded atomically so concurrent logins cannot reuse the code
+            return Ok(AccountTotp::mark_step_used(&self.pool, totp.account_id, step).await?);
+        }
+
+        let code_hash = totp::hash_recovery_code(code);
+        Ok(AccountTotp::use_recovery_code(&self.pool, totp.account_id, &code_hash).await?)
+    }
+
     /// Create a new account.
     pub async fn create_account(
         &self,
@@ -199,15 +414,78 @@ impl AuthAPI {
         Ok(account_to_entity(&account))
     }
 
+    /// Query recorded account activity, newest first.
+    ///
+    /// Pass the `next` value of a page as `filter.after` (see
+    /// `taxii_db::ActivityCursor::parse`) to get the following page.
+    pub async fn get_activity(
+        &self,
+        filter: &ActivityFilter,
+    ) -> AuthResult<PaginatedResult<Vec<ActivityRecord>>> {
+        Ok(AccountActivity::find_filtered(&self.pool, filter).await?)
+    }
+
+    /// Count recorded account activity since `since`, by event type.
+    pub async fn activity_counts(&self, since: DateTime<Utc>) -> AuthResult<BTreeMap<String, i64>> {
+        Ok(AccountActivity::count_by_event_type(&self.pool, since).await?)
+    }
+
+    /// Get the most recent successful login of an account.
+    pub async fn last_successful_login(
+        &self,
+        account_id: i32,
+    ) -> AuthResult<Option<ActivityRecord>> {
+        Ok(AccountActivity::last_successful_login(&self.pool, account_id).await?)
+    }
+
+    /// Create the first admin account on a fresh deployment.
+    ///
+    /// Does nothing and returns `None` once any account exists, so it is safe
+    /// to call on every startup. The password must satisfy the password policy.
+    pub async fn bootstrap_admin(
+        &self,
+        username: &str,
+        password: &str,
+    ) -> AuthResult<Option<AccountEntity>> {
+        if Account::exists_any(&self.pool).await? {
+            return Ok(None);
+        }
+
+        password::validate_password(password).map_err(AuthError::Password)?;
+
+        match self.create_account(username, password, true).await {
+            Ok(account) => Ok(Some(account)),
+            // Another instance bootstrapped the same account concurrently
+            Err(AuthError::Database(e)) if e.is_unique_violation() => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
     /// Get account from token.
+    ///
+    /// For scoped tokens, the returned account carries the token scope.
+    /// Tokens issued before the account's password was reset are refused.
     pub async fn get_account(&self, token: &str) -> AuthResult<Option<AccountEntity>> {
-        let account_id = match self.get_account_id(token) {
-            Some(id) => id,
+        let claims = match self.get_claims(token) {
+            Some(claims) => claims,
             None => return Ok(None),
         };
 
-        let account = Account::find(&self.pool, account_id).await?;
-        Ok(account.as_ref().map(account_to_entity))
+        let account = Account::find(&self.pool, claims.account_id).await?;
+        let account = account.filter(|account| {
+            let current = account.token_generation == claims.generation;
+            if !current {
+                warn!(
+                    account_id = account.id,
+                    "Token of an earlier generation used"
+                );
+            }
+            current
+        });
+        Ok(account.as_ref().map(|account| AccountEntity {
+            scope: claims.scope,
+            ..account_to_entity(account)
+        }))
     }
 
     /// Delete an account.
@@ -288,14 +566,87 @@ impl AuthAPI {
         Ok(account_to_entity(&updated))
     }
 
+    /// Associate an account with a STIX identity, or clear it with `None`.
+    ///
+    /// Objects the account posts are attributed to this identity by the
+    /// TAXII 2.x provenance policy.
+    pub async fn set_account_identity(
+        &self,
+        username: &str,
+        identity_id: Option<&str>,
+    ) -> AuthResult<AccountEntity> {
+        let account = Account::find_by_username(&self.pool, username)
+            .await?
+            .ok_or_else(|| DatabaseError::not_found("Account not found"))?;
+        let updated = Account::set_identity(&self.pool, account.id, identity_id).await?;
+        Ok(account_to_entity(&updated))
+    }
+
+    /// Create a password reset token for an account.
+    ///
+    /// The token is single-use, expires after `PASSWORD_RESET_TTL_SECS` and is
+    /// stored hashed. It is returned only once and must be handed to the
+    /// user out of band; the server never sends it anywhere.
+    pub async fn create_password_reset(&self, username: &str) -> AuthResult<String> {
+        let account = Account::find_by_username(&self.pool, username)
+            .await?
+            .ok_or_else(|| DatabaseError::not_found("Account not found"))?;
+
+        let token = password::generate_reset_token();
+        let expires_at = Utc::now() + Duration::seconds(PASSWORD_RESET_TTL_SECS);
+        PasswordReset::create(
+            &self.pool,
+            account.id,
+            &password::hash_reset_token(&token),
+            expires_at,
+        )
+        .await?;
+
+        Ok(token)
+    }
+
+    /// Redeem a password reset token and set a new password.
+    ///
+    /// The new password must satisfy the password policy; the token is not
+    /// consumed if it does not. Unknown, expired and already used tokens all
+    /// fail with `AuthError::InvalidResetToken`. Tokens issued for the
+    /// account before the reset are no longer accepted afterwards.
+    pub async fn redeem_password_reset(&self, token: &str, new_password: &str) -> AuthResult<()> {
+        password::validate_password(new_password).map_err(AuthError::Password)?;
+
+        let password_hash = password::generate_password_hash(new_password);
+        let account_id = PasswordReset::redeem(
+            &self.pool,
+            &password::hash_reset_token(token),
+            &password_hash,
+        )
+        .await?
+        .ok_or(AuthError::InvalidResetToken)?;
+
+        if let Err(e) =
+            AccountActivity::log(&self.pool, account_id, EventType::PasswordReset, None, None).await
+        {
+            warn!(account_id, error = %e, "Failed to log password reset");
+        }
+
+        Ok(())
+    }
+
     /// Generate JWT token.
-    fn generate_token(&self, account_id: i32, ttl_secs: Option<i64>) -> AuthResult<String> {
+    fn generate_token(
+        &self,
+        account: &Account,
+        ttl_secs: Option<i64>,
+        scope: Option<TokenScope>,
+    ) -> AuthResult<String> {
         let ttl_secs = ttl_secs.unwrap_or(self.token_ttl_secs);
         let exp = Utc::now() + Duration::seconds(ttl_secs);
 
         let claims = Claims {
-            account_id,
+            account_id: account.id,
             exp: exp.timestamp(),
+            generation: account.token_generation,
+            scope,
         };
 
         let token = encode(
@@ -307,8 +658,8 @@ impl AuthAPI {
         Ok(token)
     }
 
-    /// Get account ID from token.
-    fn get_account_id(&self, token: &str) -> Option<i32> {
+    /// Get claims from token.
+    fn get_claims(&self, token: &str) -> Option<Claims> {
         let validation = Validation::default();
 
         match decode::<Claims>(
@@ -316,7 +667,7 @@ impl AuthAPI {
             &DecodingKey::from_secret(self.secret.as_bytes()),
             &validation,
         ) {
-            Ok(data) => Some(data.claims.account_id),
+            Ok(data) => Some(data.claims),
             Err(e) => {
                 match e.kind() {
                     jsonwebtoken::errors::ErrorKind::ExpiredSignature => {
@@ -331,3 +682,492 @@ impl AuthAPI {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::PgPool;
+
+    type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+    const OLD_PASSWORD: &str = "old-password";
+    const NEW_PASSWORD: &str = "new-password";
+
+    async fn auth_with_account(pool: PgPool) -> AuthResult<AuthAPI> {
+        let auth = AuthAPI::new(TaxiiPool::new(pool), "test-secret".to_string(), None)?;
+        auth.create_account("alice", OLD_PASSWORD, false).await?;
+        Ok(auth)
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_password_reset_redeem(pool: PgPool) -> TestResult {
+        let auth = auth_with_account(pool).await?;
+
+        let token = auth.create_password_reset("alice").await?;
+        auth.redeem_password_reset(&token, NEW_PASSWORD).await?;
+
+        // Second redeem of the same token fails
+        let second = auth.redeem_password_reset(&token, "another-password").await;
+        assert!(matches!(second, Err(AuthError::InvalidResetToken)));
+
+        // Old password no longer works, new one does
+        assert!(auth.authenticate("alice", OLD_PASSWORD).await?.is_none());
+        assert!(auth.authenticate("alice", NEW_PASSWORD).await?.is_some());
+
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_password_reset_revokes_tokens(pool: PgPool) -> TestResult {
+        let auth = auth_with_account(pool).await?;
+        let before = auth
+            .authenticate("alice", OLD_PASSWORD)
+            .await?
+            .ok_or("authentication failed")?;
+        assert!(auth.get_account(&before).await?.is_some());
+
+        let token = auth.create_password_reset("alice").await?;
+        auth.redeem_password_reset(&token, NEW_PASSWORD).await?;
+
+        // The token issued before the reset is refused, new ones work
+        assert!(auth.get_account(&before).await?.is_none());
+        let after = auth
+            .authenticate("alice", NEW_PASSWORD)
+            .await?
+            .ok_or("authentication failed")?;
+        assert!(auth.get_account(&after).await?.is_some());
+
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_password_reset_expired_token(pool: PgPool) -> TestResult {
+        let auth = auth_with_account(pool).await?;
+        let account = Account::find_by_username(auth.pool(), "alice")
+            .await?
+            .ok_or("account not created")?;
+
+        let token = password::generate_reset_token();
+        PasswordReset::create(
+            auth.pool(),
+            account.id,
+            &password::hash_reset_token(&token),
+            Utc::now() - Duration::minutes(1),
+        )
+        .await?;
+
+        // Same error as for an unknown token
+        let expired = auth.redeem_password_reset(&token, NEW_PASSWORD).await;
+        assert!(matches!(expired, Err(AuthError::InvalidResetToken)));
+        let unknown = auth.redeem_password_reset("unknown", NEW_PASSWORD).await;
+        assert!(matches!(unknown, Err(AuthError::InvalidResetToken)));
+
+        assert!(auth.authenticate("alice", OLD_PASSWORD).await?.is_some());
+
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_password_reset_policy_keeps_token(pool: PgPool) -> TestResult {
+        let auth = auth_with_account(pool).await?;
+
+        let token = auth.create_password_reset("alice").await?;
+        let weak = auth.redeem_password_reset(&token, "short").await;
+        assert!(matches!(weak, Err(AuthError::Password(_))));
+
+        // The token was not consumed by the rejected attempt
+        auth.redeem_password_reset(&token, NEW_PASSWORD).await?;
+        assert!(auth.authenticate("alice", NEW_PASSWORD).await?.is_some());
+
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_scoped_token_carries_scope(pool: PgPool) -> TestResult {
+        let auth = auth_with_account(pool).await?;
+        let scope = TokenScope::read_only();
+
+        let token = auth
+            .authenticate_with_scope(
+                "alice",
+                OLD_PASSWORD,
+                None,
+                scope.clone(),
+                ClientInfo::default(),
+            )
+            .await?
+            .ok_or("authentication failed")?;
+        let account = auth.get_account(&token).await?.ok_or("account not found")?;
+        assert_eq!(account.scope, Some(scope));
+
+        let token = auth
+            .authenticate("alice", OLD_PASSWORD)
+            .await?
+            .ok_or("authentication failed")?;
+        let account = auth.get_account(&token).await?.ok_or("account not found")?;
+        assert_eq!(account.scope, None);
+
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_second_factor_lockout(pool: PgPool) -> TestResult {
+        let auth = auth_with_account(pool.clone()).await?;
+        let account = Account::find_by_username(auth.pool(), "alice")
+            .await?
+            .ok_or("account not created")?;
+        let enrollment = auth.enroll_totp(account.id).await?;
+        let login = |password: &'static str, code: String| {
+            let auth = &auth;
+            async move {
+                auth.authenticate_with_totp("alice", password, Some(&code), ClientInfo::default())
+                    .await
+            }
+        };
+
+        for attempt in 0..totp::MAX_FAILED_ATTEMPTS {
+            let code = format!("wrong-{attempt}");
+            assert!(login(OLD_PASSWORD, code).await?.is_none());
+        }
+
+        // Even a valid code is refused while locked
+        let valid = enrollment.recovery_codes[0].clone();
+        let locked = login(OLD_PASSWORD, valid.clone()).await;
+        assert!(matches!(locked, Err(AuthError::SecondFactorLocked(_))));
+        // Without the password the lockout is not disclosed
+        assert!(login("wrong-password", valid.clone()).await?.is_none());
+
+        sqlx::query("UPDATE account_totp SET locked_until = NOW() - INTERVAL '1 second'")
+            .execute(&pool)
+            .await?;
+        assert!(login(OLD_PASSWORD, valid).await?.is_some());
+
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_account_identity(pool: PgPool) -> TestResult {
+        let auth = auth_with_account(pool).await?;
+        let identity = "identity--f431f809-377b-45e0-aa1c-6a4751cae5ff";
+
+        let account = auth.set_account_identity("alice", Some(identity)).await?;
+        assert_eq!(account.identity_id.as_deref(), Some(identity));
+
+        // Authenticated requests carry the identity
+        let token = auth
+            .authenticate("alice", OLD_PASSWORD)
+            .await?
+            .ok_or("authentication failed")?;
+        let account = auth.get_account(&token).await?.ok_or("account not found")?;
+        assert_eq!(account.identity_id.as_deref(), Some(identity));
+
+        let account = auth.set_account_identity("alice", None).await?;
+        assert_eq!(account.identity_id, None);
+        assert!(
+            auth.set_account_identity("bob", Some(identity))
+                .await
+                .is_err()
+        );
+
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_unknown_username_timing(pool: PgPool) -> TestResult {
+        use std::time::{Duration as StdDuration, Instant};
+
+        const ITERATIONS: usize = 5;
+
+        let auth = auth_with_account(pool).await?;
+
+        let mut unknown = StdDuration::MAX;
+        let mut wrong_password = StdDuration::MAX;
+        for _ in 0..ITERATIONS {
+            let start = Instant::now();
+            assert!(auth.authenticate("mallory", OLD_PASSWORD).await?.is_none());
+            unknown = unknown.min(start.elapsed());
+
+            let start = Instant::now();
+            assert!(
+                auth.authenticate("alice", "wrong-password")
+                    .await?
+                    .is_none()
+            );
+            wrong_password = wrong_password.min(start.elapsed());
+        }
+
+        // Both paths hash the password; generous bounds tolerate noisy CI machines
+        let ratio = unknown.as_secs_f64() / wrong_password.as_secs_f64();
+        assert!(
+            (0.5..2.0).contains(&ratio),
+            "unknown {unknown:?} vs wrong password {wrong_password:?}"
+        );
+
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_bootstrap_admin_on_empty_database(pool: PgPool) -> TestResult {
+        let auth = AuthAPI::new(TaxiiPool::new(pool), "test-secret".to_string(), None)?;
+
+        let account = auth
+            .bootstrap_admin("admin", NEW_PASSWORD)
+            .await?
+            .ok_or("admin not created")?;
+        assert!(account.is_admin);
+        assert!(auth.authenticate("admin", NEW_PASSWORD).await?.is_some());
+
+        // Runs only once
+        assert!(
+            auth.bootstrap_admin("admin2", NEW_PASSWORD)
+                .await?
+                .is_none()
+        );
+        assert_eq!(auth.get_accounts().await?.len(), 1);
+
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_bootstrap_admin_noop_when_accounts_exist(pool: PgPool) -> TestResult {
+        let auth = auth_with_account(pool).await?;
+
+        // Even a password violating the policy is ignored once accounts exist
+        assert!(auth.bootstrap_admin("admin", NEW_PASSWORD).await?.is_none());
+        assert!(auth.bootstrap_admin("admin", "short").await?.is_none());
+
+        let accounts = auth.get_accounts().await?;
+        assert_eq!(accounts.len(), 1);
+        assert!(accounts.iter().all(|a| a.username == "alice"));
+
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_bootstrap_admin_password_policy(pool: PgPool) -> TestResult {
+        let auth = AuthAPI::new(TaxiiPool::new(pool), "test-secret".to_string(), None)?;
+
+        let result = auth.bootstrap_admin("admin", "short").await;
+        assert!(matches!(result, Err(AuthError::Password(_))));
+        assert!(auth.get_accounts().await?.is_empty());
+
+        Ok(())
+    }
+
+    /// Insert an activity row with an explicit timestamp.
+    async fn insert_activity(
+        auth: &AuthAPI,
+        account_id: i32,
+        event_type: EventType,
+        ip_address: &str,
+        minutes_ago: i64,
+    ) -> TestResult {
+        sqlx::query(
+            r#"INSERT INTO account_activity (account_id, event_type, ip_address, created_at)
+               VALUES ($1, $2, $3::inet, $4)"#,
+        )
+        .bind(account_id)
+        .bind(event_type.as_str())
+        .bind(ip_address)
+        .bind(Utc::now() - Duration::minutes(minutes_ago))
+        .execute(auth.pool().inner())
+        .await?;
+        Ok(())
+    }
+
+    /// Fetch all pages of a filter, returning event IDs in order.
+    async fn fetch_all_pages(auth: &AuthAPI, mut filter: ActivityFilter) -> AuthResult<Vec<i64>> {
+        let mut ids = Vec::new();
+        loop {
+            let page = auth.get_activity(&filter).await?;
+            ids.extend(page.items.iter().map(|r| r.id));
+            match page
+                .next
+                .as_deref()
+                .and_then(taxii_db::ActivityCursor::parse)
+            {
+                Some(cursor) if page.more => filter.after = Some(cursor),
+                _ => return Ok(ids),
+            }
+        }
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_activity_filters_and_pages(pool: PgPool) -> TestResult {
+        let auth = auth_with_account(pool).await?;
+        let alice = Account::find_by_username(auth.pool(), "alice")
+            .await?
+            .ok_or("account not created")?
+            .id;
+        let bob = auth.create_account("bob", OLD_PASSWORD, false).await?.id;
+
+        insert_activity(&auth, alice, EventType::LoginFailed, "10.0.0.1", 50).await?;
+        insert_activity(&auth, alice, EventType::LoginFailed, "10.0.0.1", 40).await?;
+        insert_activity(&auth, alice, EventType::LoginSuccess, "10.0.0.2", 30).await?;
+        insert_activity(&auth, bob, EventType::LoginFailed, "10.0.0.1", 20).await?;
+        insert_activity(&auth, alice, EventType::LoginFailed, "10.0.0.3", 10).await?;
+        insert_activity(&auth, bob, EventType::LoginSuccess, "10.0.0.1", 5).await?;
+
+        let count = |filter: ActivityFilter| {
+            let auth = &auth;
+            async move { Ok::<_, AuthError>(fetch_all_pages(auth, filter).await?.len()) }
+        };
+
+        assert_eq!(count(ActivityFilter::default()).await?, 6);
+        assert_eq!(
+            count(ActivityFilter {
+                username: Some("alice".to_string()),
+                ..Default::default()
+            })
+            .await?,
+            4
+        );
+        assert_eq!(
+            count(ActivityFilter {
+                account_id: Some(alice),
+                event_types: vec![EventType::LoginFailed],
+                ..Default::default()
+            })
+            .await?,
+            3
+        );
+        assert_eq!(
+            count(ActivityFilter {
+                event_types: vec![EventType::LoginFailed],
+                ip_address: Some("10.0.0.1".parse()?),
+                ..Default::default()
+            })
+            .await?,
+            3
+        );
+        assert_eq!(
+            count(ActivityFilter {
+                ip_prefix: taxii_db::IpPrefix::parse("10.0.0.0/31"),
+                ..Default::default()
+            })
+            .await?,
+            4
+        );
+        assert_eq!(
+            count(ActivityFilter {
+                ip_prefix: taxii_db::IpPrefix::parse("10.0.1.0/24"),
+                ..Default::default()
+            })
+            .await?,
+            0
+        );
+        assert_eq!(
+            count(ActivityFilter {
+                username: Some("alice".to_string()),
+                since: Some(Utc::now() - Duration::minutes(45)),
+                until: Some(Utc::now() - Duration::minutes(15)),
+                ..Default::default()
+            })
+            .await?,
+            2
+        );
+
+        // Page boundaries: every record exactly once, newest first
+        let all = fetch_all_pages(&auth, ActivityFilter::default()).await?;
+        for limit in 1..=7 {
+            let paged = fetch_all_pages(
+                &auth,
+                ActivityFilter {
+                    limit: Some(limit),
+                    ..Default::default()
+                },
+            )
+            .await?;
+            assert_eq!(paged, all, "limit {limit}");
+        }
+
+        let page = auth
+            .get_activity(&ActivityFilter {
+                limit: Some(6),
+                ..Default::default()
+            })
+            .await?;
+        assert!(!page.more);
+        assert!(page.next.is_none());
+
+        let last = auth
+            .last_successful_login(alice)
+            .await?
+            .ok_or("no successful login")?;
+        assert_eq!(last.event_type, "login_success");
+        assert_eq!(last.ip_address.as_deref(), Some("10.0.0.2"));
+        assert!(auth.last_successful_login(0).await?.is_none());
+
+        let counts = auth
+            .activity_counts(Utc::now() - Duration::minutes(25))
+            .await?;
+        assert_eq!(
+            counts.into_iter().collect::<Vec<_>>(),
+            vec![
+                ("login_failed".to_string(), 2),
+                ("login_success".to_string(), 1)
+            ]
+        );
+
+        Ok(())
+    }
+
+    /// Rows inserted while paging do not shift pages: they are newer than
+    /// the cursor, so every older record is returned exactly once.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_activity_pages_stable_during_inserts(pool: PgPool) -> TestResult {
+        let auth = auth_with_account(pool).await?;
+        let alice = Account::find_by_username(auth.pool(), "alice")
+            .await?
+            .ok_or("account not created")?
+            .id;
+
+        // Several events share a timestamp, so pages split on the ID
+        for minutes_ago in [30, 30, 30, 20, 20, 10] {
+            insert_activity(
+                &auth,
+                alice,
+                EventType::LoginFailed,
+                "10.0.0.1",
+                minutes_ago,
+            )
+            .await?;
+        }
+        let before = fetch_all_pages(&auth, ActivityFilter::default()).await?;
+
+        let mut filter = ActivityFilter {
+            limit: Some(2),
+            ..Default::default()
+        };
+        let mut ids = Vec::new();
+        loop {
+            let page = auth.get_activity(&filter).await?;
+            ids.extend(page.items.iter().map(|r| r.id));
+            insert_activity(&auth, alice, EventType::LoginSuccess, "10.0.0.2", 0).await?;
+            match page
+                .next
+                .as_deref()
+                .and_then(taxii_db::ActivityCursor::parse)
+            {
+                Some(cursor) if page.more => filter.after = Some(cursor),
+                _ => break,
+            }
+        }
+        assert_eq!(ids, before);
+
+        Ok(())
+    }
+}
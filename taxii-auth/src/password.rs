@@ -3,6 +3,8 @@
 //! Format: scrypt:n:r:p$salt$hash
 //! Example: scrypt:32768:8:1$K2a4Wu51MKGutWTo$hash
 
+use std::sync::LazyLock;
+
 use scrypt::{Params, scrypt};
 use subtle::ConstantTimeEq;
 
@@ -21,6 +23,19 @@ const OUTPUT_LEN: usize = 64;
 /// Salt length in bytes.
 const SALT_LENGTH: usize = 16;
 
+/// Minimum password length in characters.
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+
+/// Password reset token length in bytes.
+const RESET_TOKEN_LENGTH: usize = 32;
+
+/// Password of the dummy hash.
+const DUMMY_PASSWORD: &str = "darwis-taxii-dummy-password";
+
+/// Dummy hash with the default parameters, verified for unknown usernames.
+static DUMMY_PASSWORD_HASH: LazyLock<String> =
+    LazyLock::new(|| generate_password_hash(DUMMY_PASSWORD));
+
 /// Generate a password hash.
 ///
 /// Format: scrypt:n:r:p$salt$hash
@@ -106,6 +121,53 @@ pub fn check_password_hash(hash: &str, password: &str) -> bool {
         .into()
 }
 
+/// Get the dummy password hash, computing it on first use.
+pub fn dummy_password_hash() -> &'static str {
+    &DUMMY_PASSWORD_HASH
+}
+
+/// Check a password against the dummy hash. Always returns `false`.
+///
+/// Used when no account matches a username, so that a login for an unknown
+/// username costs the same as one with a wrong password and response times
+/// do not reveal which usernames exist.
+pub fn check_dummy_password(password: &str) -> bool {
+    std::hint::black_box(check_password_hash(dummy_password_hash(), password));
+    false
+}
+
+/// Check a new password against the password policy.
+pub fn validate_password(password: &str) -> Result<(), String> {
+    if password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(format!(
+            "Password must be at least {MIN_PASSWORD_LENGTH} characters long"
+        ));
+    }
+    if password.trim().is_empty() {
+        return Err("Password must not be blank".to_string());
+    }
+    Ok(())
+}
+
+/// Generate a random password reset token (URL-safe base64).
+pub fn generate_reset_token() -> String {
+    use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+    use rand::Rng;
+
+    let mut token = [0u8; RESET_TOKEN_LENGTH];
+    rand::rng().fill(&mut token);
+    URL_SAFE_NO_PAD.encode(token)
+}
+
+/// Hash a password reset token for storage.
+///
+/// Tokens are high-entropy random values, so a plain SHA-256 is sufficient.
+pub fn hash_reset_token(token: &str) -> String {
+    use sha2::{Digest, Sha256};
+
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -153,4 +215,65 @@ mod tests {
             "password"
         ));
     }
+
+    #[test]
+    fn test_dummy_hash_uses_default_params() {
+        assert!(dummy_password_hash().starts_with("scrypt:32768:8:1$"));
+        assert!(check_password_hash(dummy_password_hash(), DUMMY_PASSWORD));
+
+        // Never authenticates, even with the dummy password itself
+        assert!(!check_dummy_password(DUMMY_PASSWORD));
+        assert!(!check_dummy_password("wrong_password"));
+    }
+
+    #[test]
+    fn test_dummy_check_costs_as_much_as_real_check() {
+        use std::time::{Duration, Instant};
+
+        const ITERATIONS: usize = 5;
+
+        fn fastest(check: impl Fn() -> bool) -> Duration {
+            (0..ITERATIONS)
+                .map(|_| {
+                    let start = Instant::now();
+                    std::hint::black_box(check());
+                    start.elapsed()
+                })
+                .min()
+                .unwrap_or_default()
+        }
+
+        let hash = generate_password_hash("test_password");
+        dummy_password_hash();
+
+        let real = fastest(|| check_password_hash(&hash, "wrong_password"));
+        let dummy = fastest(|| check_dummy_password("wrong_password"));
+
+        // Same work on both paths; generous bounds tolerate noisy CI machines
+        let ratio = dummy.as_secs_f64() / real.as_secs_f64();
+        assert!(
+            (0.5..2.0).contains(&ratio),
+            "dummy {dummy:?} vs real {real:?}"
+        );
+    }
+
+    #[test]
+    fn test_validate_password() {
+        assert!(validate_password("long enough").is_ok());
+        assert!(validate_password("short").is_err());
+        assert!(validate_password("        ").is_err());
+        assert!(validate_password("").is_err());
+    }
+
+    #[test]
+    fn test_reset_token() {
+        let token = generate_reset_token();
+        assert_eq!(token.len(), 43);
+        assert_ne!(generate_reset_token(), token);
+
+        let hash = hash_reset_token(&token);
+        assert_eq!(hash.len(), 64);
+        assert_eq!(hash_reset_token(&token), hash);
+        assert_ne!(hash_reset_token("other"), hash);
+    }
 }
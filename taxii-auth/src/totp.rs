@@ -0,0 +1,374 @@
+//! TOTP two-factor authentication (RFC 6238).
+//!
+//! Codes are 6-digit HMAC-SHA1 codes over 30-second time steps, accepted
+//! with a drift of one step in either direction.
+//!
+//! Secrets are stored encrypted with ChaCha20-Poly1305 using a key derived
+//! from the auth secret. Format: nonce$ciphertext (URL-safe base64).
+
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use hmac::{Hmac, Mac};
+use percent_encoding::{NON_ALPHANUMERIC, utf8_percent_encode};
+use rand::Rng;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+
+use crate::error::{AuthError, AuthResult};
+
+/// Length of a time step in seconds.
+pub const STEP_SECS: i64 = 30;
+
+/// Number of digits in a code.
+pub const DIGITS: usize = 6;
+
+/// Number of time steps accepted before and after the current one.
+pub const DRIFT_STEPS: i64 = 1;
+
+/// Secret length in bytes (160 bits, as recommended for HMAC-SHA1).
+pub const SECRET_LENGTH: usize = 20;
+
+/// Number of recovery codes generated at enrollment.
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+/// Recovery code length in bytes (80 bits).
+pub const RECOVERY_CODE_LENGTH: usize = 10;
+
+/// Consecutive failed codes that lock the second factor of an account.
+pub const MAX_FAILED_ATTEMPTS: i32 = 5;
+
+/// Duration of a second factor lockout in seconds.
+pub const LOCKOUT_SECS: i64 = 15 * 60;
+
+/// Issuer shown by authenticator apps.
+pub const ISSUER: &str = "DARWIS TAXII";
+
+/// Nonce length for ChaCha20-Poly1305.
+const NONCE_LENGTH: usize = 12;
+
+/// Domain separator for deriving the encryption key from the auth secret.
+const KEY_CONTEXT: &[u8] = b"darwis-taxii-totp:";
+
+/// RFC 4648 base32 alphabet.
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// Generate a random secret.
+pub fn generate_secret() -> Vec<u8> {
+    let mut secret = vec![0u8; SECRET_LENGTH];
+    rand::rng().fill(&mut secret[..]);
+    secret
+}
+
+/// Encode a secret as unpadded base32, the format used by authenticator apps.
+pub fn encode_secret(secret: &[u8]) -> String {
+    let mut encoded = String::with_capacity(secret.len().div_ceil(5) * 8);
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+
+    for &byte in secret {
+        buffer = (buffer << 8) | u32::from(byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            encoded.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        encoded.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+
+    encoded
+}
+
+/// Build an otpauth:// URI for QR code rendering.
+pub fn provisioning_uri(secret: &[u8], account_name: &str) -> String {
+    let issuer = utf8_percent_encode(ISSUER, NON_ALPHANUMERIC);
+    let account_name = utf8_percent_encode(account_name, NON_ALPHANUMERIC);
+
+    format!(
+        "otpauth://totp/{issuer}:{account_name}?secret={}&issuer={issuer}&algorithm=SHA1&digits={DIGITS}&period={STEP_SECS}",
+        encode_secret(secret)
+    )
+}
+
+/// Get the time step for a Unix timestamp.
+pub fn time_step(timestamp: i64) -> i64 {
+    timestamp.div_euclid(STEP_SECS)
+}
+
+/// Generate the code for a time step (RFC 4226 HOTP with the step as counter).
+#[expect(
+    clippy::expect_used,
+    reason = "infallible: HMAC accepts keys of any length"
+)]
+pub fn generate_code(secret: &[u8], step: i64) -> String {
+    let mut mac = <Hmac<Sha1> as Mac>::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(&step.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    // Dynamic truncation
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+
+    format!(
+        "{:0width$}",
+        binary % 10u32.pow(DIGITS as u32),
+        width = DIGITS
+    )
+}
+
+/// Check whether a string has the shape of a TOTP code.
+pub fn is_totp_code(code: &str) -> bool {
+    code.len() == DIGITS && code.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Verify a code at a Unix timestamp.
+///
+/// Steps at or before `last_used_step` are rejected so a code cannot be
+/// replayed within its window. Returns the matching time step, which the
+/// caller must record as used.
+pub fn verify_code(
+    secret: &[u8],
+    code: &str,
+    timestamp: i64,
+    last_used_step: Option<i64>,
+) -> Option<i64> {
+    if !is_totp_code(code) {
+        return None;
+    }
+
+    let current = time_step(timestamp);
+    (current - DRIFT_STEPS..=current + DRIFT_STEPS)
+        .filter(|step| last_used_step.is_none_or(|last| *step > last))
+        .find(|step| {
+            bool::from(
+                generate_code(secret, *step)
+                    .as_bytes()
+                    .ct_eq(code.as_bytes()),
+            )
+        })
+}
+
+/// Generate single-use recovery codes.
+///
+/// Format: xxxxx-xxxxx-xxxxx-xxxxx (lowercase hex).
+pub fn generate_recovery_codes() -> Vec<String> {
+    let mut rng = rand::rng();
+
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let mut bytes = [0u8; RECOVERY_CODE_LENGTH];
+            rng.fill(&mut bytes);
+            let code = hex::encode(bytes);
+            let groups: Vec<&str> = (0..code.len())
+                .step_by(5)
+                .map(|start| &code[start..start + 5])
+                .collect();
+            groups.join("-")
+        })
+        .collect()
+}
+
+/// Hash a recovery code for storage.
+///
+/// Dashes, whitespace and case are ignored so codes can be typed loosely.
+pub fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| *c != '-' && !c.is_whitespace())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+
+    hex::encode(Sha256::digest(normalized.as_bytes()))
+}
+
+/// Derive the secret encryption key from the auth secret.
+fn encryption_key(auth_secret: &str) -> Key {
+    let mut hasher = Sha256::new();
+    hasher.update(KEY_CONTEXT);
+    hasher.update(auth_secret.as_bytes());
+    hasher.finalize()
+}
+
+/// Encrypt a secret for storage.
+pub fn encrypt_secret(auth_secret: &str, secret: &[u8]) -> AuthResult<String> {
+    let cipher = ChaCha20Poly1305::new(&encryption_key(auth_secret));
+
+    let mut nonce = [0u8; NONCE_LENGTH];
+    rand::rng().fill(&mut nonce);
+
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), secret)
+        .map_err(|_| AuthError::Totp("Failed to encrypt TOTP secret".to_string()))?;
+
+    Ok(format!(
+        "{}${}",
+        URL_SAFE_NO_PAD.encode(nonce),
+        URL_SAFE_NO_PAD.encode(ciphertext)
+    ))
+}
+
+/// Decrypt a stored secret.
+///
+/// Fails if the auth secret changed since the secret was encrypted.
+pub fn decrypt_secret(auth_secret: &str, encrypted: &str) -> AuthResult<Vec<u8>> {
+    let invalid = || AuthError::Totp("Invalid encrypted TOTP secret".to_string());
+
+    let (nonce, ciphertext) = encrypted.split_once('$').ok_or_else(invalid)?;
+    let nonce = URL_SAFE_NO_PAD.decode(nonce).map_err(|_| invalid())?;
+    let ciphertext = URL_SAFE_NO_PAD.decode(ciphertext).map_err(|_| invalid())?;
+    if nonce.len() != NONCE_LENGTH {
+        return Err(invalid());
+    }
+
+    let cipher = ChaCha20Poly1305::new(&encryption_key(auth_secret));
+    cipher
+        .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+        .map_err(|_| AuthError::Totp("Failed to decrypt TOTP secret".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// RFC 6238 Appendix B SHA1 secret.
+    const SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn test_rfc6238_vectors() {
+        // RFC 6238 8-digit values truncated to 6 digits
+        assert_eq!(generate_code(SECRET, time_step(59)), "287082");
+        assert_eq!(generate_code(SECRET, time_step(1111111109)), "081804");
+        assert_eq!(generate_code(SECRET, time_step(1111111111)), "050471");
+        assert_eq!(generate_code(SECRET, time_step(1234567890)), "005924");
+        assert_eq!(generate_code(SECRET, time_step(2000000000)), "279037");
+    }
+
+    #[test]
+    fn test_verify_current_step() {
+        let timestamp = 1234567890;
+        let code = generate_code(SECRET, time_step(timestamp));
+
+        assert_eq!(
+            verify_code(SECRET, &code, timestamp, None),
+            Some(time_step(timestamp))
+        );
+        assert_eq!(verify_code(SECRET, "000000", timestamp, None), None);
+    }
+
+    #[test]
+    fn test_verify_drift_tolerance() {
+        let timestamp = 1234567890;
+        let step = time_step(timestamp);
+
+        let previous = generate_code(SECRET, step - 1);
+        let next = generate_code(SECRET, step + 1);
+        assert_eq!(
+            verify_code(SECRET, &previous, timestamp, None),
+            Some(step - 1)
+        );
+        assert_eq!(verify_code(SECRET, &next, timestamp, None), Some(step + 1));
+
+        let too_old = generate_code(SECRET, step - 2);
+        let too_new = generate_code(SECRET, step + 2);
+        assert_eq!(verify_code(SECRET, &too_old, timestamp, None), None);
+        assert_eq!(verify_code(SECRET, &too_new, timestamp, None), None);
+    }
+
+    #[test]
+    fn test_verify_rejects_replay() {
+        let timestamp = 1234567890;
+        let step = time_step(timestamp);
+        let code = generate_code(SECRET, step);
+
+        // Same code again within its window
+        assert_eq!(verify_code(SECRET, &code, timestamp, Some(step)), None);
+        assert_eq!(
+            verify_code(SECRET, &code, timestamp + STEP_SECS, Some(step)),
+            None
+        );
+
+        // An older code after a newer one was used
+        let previous = generate_code(SECRET, step - 1);
+        assert_eq!(verify_code(SECRET, &previous, timestamp, Some(step)), None);
+
+        // The next code is still accepted
+        let next = generate_code(SECRET, step + 1);
+        assert_eq!(
+            verify_code(SECRET, &next, timestamp + STEP_SECS, Some(step)),
+            Some(step + 1)
+        );
+    }
+
+    #[test]
+    fn test_verify_rejects_malformed() {
+        assert_eq!(verify_code(SECRET, "", 59, None), None);
+        assert_eq!(verify_code(SECRET, "28708", 59, None), None);
+        assert_eq!(verify_code(SECRET, "2870820", 59, None), None);
+        assert_eq!(verify_code(SECRET, "28708a", 59, None), None);
+    }
+
+    #[test]
+    fn test_encode_secret() {
+        assert_eq!(encode_secret(SECRET), "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        assert_eq!(encode_secret(b"f"), "MY");
+        assert_eq!(encode_secret(b"foobar"), "MZXW6YTBOI");
+        assert_eq!(encode_secret(b""), "");
+    }
+
+    #[test]
+    fn test_provisioning_uri() {
+        assert_eq!(
+            provisioning_uri(SECRET, "admin@example.com"),
+            "otpauth://totp/DARWIS%20TAXII:admin%40example%2Ecom?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&issuer=DARWIS%20TAXII&algorithm=SHA1&digits=6&period=30"
+        );
+    }
+
+    #[test]
+    fn test_encrypt_decrypt_roundtrip() {
+        let encrypted = encrypt_secret("auth-secret", SECRET);
+        let decrypted = encrypted
+            .as_deref()
+            .map_err(|e| e.to_string())
+            .and_then(|e| decrypt_secret("auth-secret", e).map_err(|e| e.to_string()));
+        assert_eq!(decrypted.as_deref(), Ok(SECRET));
+
+        // A different auth secret cannot decrypt
+        let wrong_key = encrypted
+            .as_deref()
+            .map_err(|e| e.to_string())
+            .and_then(|e| decrypt_secret("other-secret", e).map_err(|e| e.to_string()));
+        assert!(wrong_key.is_err());
+    }
+
+    #[test]
+    fn test_decrypt_invalid_format() {
+        assert!(decrypt_secret("auth-secret", "").is_err());
+        assert!(decrypt_secret("auth-secret", "not-base64!$x").is_err());
+        assert!(decrypt_secret("auth-secret", "AAAA$AAAA").is_err());
+    }
+
+    #[test]
+    fn test_recovery_codes() {
+        let codes = generate_recovery_codes();
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+        for code in &codes {
+            assert_eq!(code.len(), 23);
+            assert_eq!(code.matches('-').count(), 3);
+            assert_eq!(code.replace('-', "").len(), RECOVERY_CODE_LENGTH * 2);
+        }
+
+        let hash = hash_recovery_code("abcde-01234");
+        assert_eq!(hash.len(), 64);
+        assert_eq!(hash_recovery_code("ABCDE 01234"), hash);
+        assert_eq!(hash_recovery_code("abcde01234"), hash);
+        assert_ne!(hash_recovery_code("abcde-01235"), hash);
+    }
+}
@@ -1,8 +1,18 @@
 //! Account management commands.
 
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
 use clap::Subcommand;
+use serde::Serialize;
 use taxii_auth::AuthAPI;
-use taxii_db::TaxiiPool;
+use taxii_core::PermissionValue;
+use taxii_db::{Account, ActivityCursor, ActivityFilter, EventType, IpPrefix, TaxiiPool};
+use uuid::Uuid;
+
+use super::output::{Output, Record};
+use super::permissions::{self, Access, Collections};
 
 /// Account management actions.
 #[derive(Subcommand)]
@@ -16,6 +26,236 @@ pub enum AccountAction {
         #[arg(short, long)]
         username: String,
     },
+
+    /// Issue a single-use password reset token for an account.
+    ///
+    /// The token is printed and must be handed to the user out of band.
+    #[command(name = "reset-link")]
+    ResetLink {
+        /// Username of the account.
+        username: String,
+    },
+
+    /// Show recorded activity of an account, newest first.
+    Activity {
+        /// Username of the account.
+        username: String,
+
+        /// Only events since this time (RFC 3339, YYYY-MM-DD, or relative like 24h or 7d).
+        #[arg(long, value_parser = parse_time)]
+        since: Option<DateTime<Utc>>,
+
+        /// Only events before this time (same formats as --since).
+        #[arg(long, value_parser = parse_time)]
+        until: Option<DateTime<Utc>>,
+
+        /// Only events of this type (login-success, login-failed, password-reset).
+        #[arg(long = "event", value_parser = parse_event_type)]
+        events: Vec<EventType>,
+
+        /// Only events from this client IP address.
+        #[arg(long)]
+        ip: Option<IpAddr>,
+
+        /// Only events from client IP addresses in this prefix (CIDR, e.g. 192.0.2.0/24).
+        #[arg(long, value_parser = parse_ip_prefix)]
+        ip_prefix: Option<IpPrefix>,
+
+        /// Maximum number of events to show.
+        #[arg(long, default_value = "50")]
+        limit: i64,
+
+        /// Continue from a previous page.
+        #[arg(long, value_parser = parse_cursor)]
+        next: Option<ActivityCursor>,
+    },
+
+    /// Associate an account with the STIX identity its objects are
+    /// attributed to.
+    #[command(name = "set-identity")]
+    SetIdentity {
+        /// Username of the account.
+        #[arg(short, long)]
+        username: String,
+
+        /// STIX identity ID (identity--<uuid>). Omit to clear.
+        #[arg(long, value_parser = parse_identity_id)]
+        identity: Option<String>,
+    },
+
+    /// Grant access on a collection to an account.
+    Grant {
+        /// Username of the account.
+        username: String,
+
+        /// Collection: TAXII 2.x UUID, title or alias, or TAXII 1.x name with --taxii1.
+        #[arg(long)]
+        collection: String,
+
+        /// Access to grant. Existing access on the collection is kept.
+        #[arg(long, value_enum)]
+        access: Access,
+
+        /// The collection is a TAXII 1.x collection.
+        #[arg(long, default_value = "false")]
+        taxii1: bool,
+    },
+
+    /// Revoke access on a collection from an account.
+    Revoke {
+        /// Username of the account.
+        username: String,
+
+        /// Collection: TAXII 2.x UUID, title or alias, or TAXII 1.x name with --taxii1.
+        #[arg(long)]
+        collection: String,
+
+        /// Access to revoke. Omit to revoke all access on the collection.
+        #[arg(long, value_enum)]
+        access: Option<Access>,
+
+        /// The collection is a TAXII 1.x collection.
+        #[arg(long, default_value = "false")]
+        taxii1: bool,
+    },
+
+    /// Show the collection permissions of an account.
+    Permissions {
+        /// Username of the account.
+        username: String,
+    },
+
+    /// Manage TOTP two-factor authentication.
+    Totp {
+        #[command(subcommand)]
+        action: TotpAction,
+    },
+}
+
+/// TOTP two-factor authentication actions.
+#[derive(Subcommand)]
+pub enum TotpAction {
+    /// Enroll an account, replacing any existing secret and recovery codes.
+    Enroll {
+        /// Username of the account to enroll.
+        #[arg(short, long)]
+        username: String,
+    },
+
+    /// Disable two-factor authentication for an account.
+    Disable {
+        /// Username of the account.
+        #[arg(short, long)]
+        username: String,
+    },
+}
+
+/// An account in `account list`.
+#[derive(Debug, Serialize)]
+struct AccountRecord {
+    id: i32,
+    username: String,
+    is_admin: bool,
+    permissions: BTreeMap<String, PermissionValue>,
+}
+
+impl Record for AccountRecord {
+    const COLUMNS: &'static [(&'static str, &'static str)] = &[
+        ("id", "ID"),
+        ("username", "Username"),
+        ("is_admin", "Admin"),
+        ("permissions", "Permissions"),
+    ];
+}
+
+/// An event in `account activity`.
+#[derive(Debug, Serialize)]
+struct ActivityRecord {
+    id: i64,
+    username: String,
+    event_type: String,
+    ip_address: Option<String>,
+    user_agent: Option<String>,
+    created_at: DateTime<Utc>,
+}
+
+impl Record for ActivityRecord {
+    const COLUMNS: &'static [(&'static str, &'static str)] = &[
+        ("created_at", "Time"),
+        ("event_type", "Event"),
+        ("ip_address", "IP Address"),
+        ("user_agent", "User Agent"),
+        ("id", "ID"),
+        ("username", "Username"),
+    ];
+}
+
+/// An account in `account delete`.
+#[derive(Debug, Serialize)]
+struct DeletedAccountRecord {
+    username: String,
+}
+
+impl Record for DeletedAccountRecord {
+    const COLUMNS: &'static [(&'static str, &'static str)] = &[("username", "Deleted")];
+}
+
+/// A password reset token in `account reset-link`.
+#[derive(Debug, Serialize)]
+struct PasswordResetRecord {
+    username: String,
+    token: String,
+    valid_minutes: i64,
+}
+
+impl Record for PasswordResetRecord {
+    const COLUMNS: &'static [(&'static str, &'static str)] = &[
+        ("username", "Username"),
+        ("token", "Token"),
+        ("valid_minutes", "Valid (minutes)"),
+    ];
+}
+
+/// The identity of an account in `account set-identity`.
+#[derive(Debug, Serialize)]
+struct IdentityRecord {
+    username: String,
+    identity_id: Option<String>,
+}
+
+impl Record for IdentityRecord {
+    const COLUMNS: &'static [(&'static str, &'static str)] =
+        &[("username", "Username"), ("identity_id", "Identity")];
+}
+
+/// A TOTP enrollment in `account totp enroll`.
+#[derive(Debug, Serialize)]
+struct TotpEnrollmentRecord {
+    username: String,
+    secret: String,
+    otpauth_uri: String,
+    recovery_codes: Vec<String>,
+}
+
+impl Record for TotpEnrollmentRecord {
+    const COLUMNS: &'static [(&'static str, &'static str)] = &[
+        ("username", "Username"),
+        ("secret", "Secret"),
+        ("otpauth_uri", "otpauth URI"),
+        ("recovery_codes", "Recovery codes"),
+    ];
+}
+
+/// Outcome of `account totp disable`.
+#[derive(Debug, Serialize)]
+struct TotpDisableRecord {
+    username: String,
+    was_enabled: bool,
+}
+
+impl Record for TotpDisableRecord {
+    const COLUMNS: &'static [(&'static str, &'static str)] =
+        &[("username", "Username"), ("was_enabled", "Was enabled")];
 }
 
 /// Handle account commands.
@@ -23,66 +263,290 @@ pub async fn handle(
     pool: TaxiiPool,
     auth_secret: &str,
     action: AccountAction,
+    output: &Output,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let auth = AuthAPI::new(pool, auth_secret.to_string(), None)?;
 
     match action {
         AccountAction::List => {
-            list_accounts(&auth).await?;
+            list_accounts(&auth, output).await?;
         }
         AccountAction::Delete { username } => {
-            delete_account(&auth, &username).await?;
+            delete_account(&auth, &username, output).await?;
+        }
+        AccountAction::ResetLink { username } => {
+            create_password_reset(&auth, &username, output).await?;
+        }
+        AccountAction::Activity {
+            username,
+            since,
+            until,
+            events,
+            ip,
+            ip_prefix,
+            limit,
+            next,
+        } => {
+            let filter = ActivityFilter {
+                username: Some(username),
+                event_types: events,
+                since,
+                until,
+                ip_address: ip,
+                ip_prefix,
+                limit: Some(limit),
+                after: next,
+                ..Default::default()
+            };
+            show_activity(&auth, &filter, output).await?;
+        }
+        AccountAction::SetIdentity { username, identity } => {
+            let account = auth
+                .set_account_identity(&username, identity.as_deref())
+                .await?;
+            output.record(&IdentityRecord {
+                username,
+                identity_id: account.identity_id,
+            })?;
         }
+        AccountAction::Grant {
+            username,
+            collection,
+            access,
+            taxii1,
+        } => {
+            let collections = Collections::load(auth.pool()).await?;
+            let account =
+                permissions::grant(&auth, &collections, &username, &collection, access, taxii1)
+                    .await?;
+            permissions::print_permissions(&account, &collections, output)?;
+        }
+        AccountAction::Revoke {
+            username,
+            collection,
+            access,
+            taxii1,
+        } => {
+            let collections = Collections::load(auth.pool()).await?;
+            let account =
+                permissions::revoke(&auth, &collections, &username, &collection, access, taxii1)
+                    .await?;
+            permissions::print_permissions(&account, &collections, output)?;
+        }
+        AccountAction::Permissions { username } => {
+            permissions::show(&auth, &username, output).await?;
+        }
+        AccountAction::Totp { action } => match action {
+            TotpAction::Enroll { username } => {
+                enroll_totp(&auth, &username, output).await?;
+            }
+            TotpAction::Disable { username } => {
+                disable_totp(&auth, &username, output).await?;
+            }
+        },
     }
 
     Ok(())
 }
 
 /// Delete an account.
-async fn delete_account(auth: &AuthAPI, username: &str) -> Result<(), Box<dyn std::error::Error>> {
+async fn delete_account(
+    auth: &AuthAPI,
+    username: &str,
+    output: &Output,
+) -> Result<(), Box<dyn std::error::Error>> {
     auth.delete_account(username).await?;
-    println!("Account '{username}' deleted successfully");
-    Ok(())
+    output.record(&DeletedAccountRecord {
+        username: username.to_string(),
+    })
 }
 
-/// List all accounts with permissions.
-async fn list_accounts(auth: &AuthAPI) -> Result<(), Box<dyn std::error::Error>> {
-    let accounts = auth.get_accounts().await?;
+/// Issue a password reset token.
+async fn create_password_reset(
+    auth: &AuthAPI,
+    username: &str,
+    output: &Output,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let token = auth.create_password_reset(username).await?;
 
-    if accounts.is_empty() {
-        println!("No accounts found.");
-        return Ok(());
+    tracing::info!(
+        "Redeem the token once with POST /management/password-reset/redeem \
+         {{\"token\": ..., \"new_password\": ...}}"
+    );
+    output.record(&PasswordResetRecord {
+        username: username.to_string(),
+        token,
+        valid_minutes: taxii_auth::PASSWORD_RESET_TTL_SECS / 60,
+    })
+}
+
+/// Parse a point in time: RFC 3339, a date, or a duration ago (`30m`, `24h`, `7d`).
+pub(crate) fn parse_time(value: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Ok(dt.with_timezone(&Utc));
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(date.and_time(chrono::NaiveTime::MIN).and_utc());
     }
 
-    println!("{:<5} {:<20} {:<8} Permissions", "ID", "Username", "Admin");
-    println!("{}", "-".repeat(70));
-
-    for account in accounts {
-        let permissions_str = if account.permissions.is_empty() {
-            "-".to_string()
-        } else {
-            account
-                .permissions
-                .iter()
-                .map(|(col, perm)| {
-                    let perm_str = match perm {
-                        taxii_core::PermissionValue::Taxii1(s) => s.clone(),
-                        taxii_core::PermissionValue::Taxii2(list) => list.join("+"),
-                    };
-                    format!("{col}:{perm_str}")
-                })
-                .collect::<Vec<_>>()
-                .join(", ")
-        };
-
-        println!(
-            "{:<5} {:<20} {:<8} {}",
-            account.id,
-            account.username,
-            if account.is_admin { "Yes" } else { "No" },
-            permissions_str
+    let invalid = || format!("invalid time '{value}'");
+    let (split, unit) = value.char_indices().last().ok_or_else(invalid)?;
+    let amount: i64 = value[..split].parse().map_err(|_| invalid())?;
+    let ago = match unit {
+        'm' => Duration::minutes(amount),
+        'h' => Duration::hours(amount),
+        'd' => Duration::days(amount),
+        _ => return Err(invalid()),
+    };
+    Ok(Utc::now() - ago)
+}
+
+/// Parse an activity event type.
+fn parse_event_type(value: &str) -> Result<EventType, String> {
+    EventType::parse(value).ok_or_else(|| {
+        format!("unknown event '{value}' (expected login-success, login-failed or password-reset)")
+    })
+}
+
+/// Parse an IP address prefix in CIDR notation.
+fn parse_ip_prefix(value: &str) -> Result<IpPrefix, String> {
+    IpPrefix::parse(value).ok_or_else(|| format!("invalid IP prefix '{value}'"))
+}
+
+/// Parse a STIX identity ID.
+fn parse_identity_id(value: &str) -> Result<String, String> {
+    value
+        .strip_prefix("identity--")
+        .and_then(|uuid| Uuid::parse_str(uuid).ok())
+        .map(|_| value.to_string())
+        .ok_or_else(|| format!("invalid identity '{value}' (expected identity--<uuid>)"))
+}
+
+/// Parse a pagination cursor printed by a previous page.
+pub(crate) fn parse_cursor(value: &str) -> Result<ActivityCursor, String> {
+    ActivityCursor::parse(value).ok_or_else(|| "invalid --next value".to_string())
+}
+
+/// Show recorded activity of an account.
+async fn show_activity(
+    auth: &AuthAPI,
+    filter: &ActivityFilter,
+    output: &Output,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let page = auth.get_activity(filter).await?;
+
+    let records: Vec<_> = page
+        .items
+        .into_iter()
+        .map(|record| ActivityRecord {
+            id: record.id,
+            username: record.username,
+            event_type: record.event_type,
+            ip_address: record.ip_address,
+            user_agent: record.user_agent,
+            created_at: record.created_at,
+        })
+        .collect();
+    output.page(&records, page.next.as_deref(), "No activity found.")
+}
+
+/// Find an account ID by username.
+async fn find_account_id(
+    auth: &AuthAPI,
+    username: &str,
+) -> Result<i32, Box<dyn std::error::Error>> {
+    Account::find_by_username(auth.pool(), username)
+        .await?
+        .map(|account| account.id)
+        .ok_or_else(|| format!("Account '{username}' not found").into())
+}
+
+/// Enroll an account in TOTP two-factor authentication.
+async fn enroll_totp(
+    auth: &AuthAPI,
+    username: &str,
+    output: &Output,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let account_id = find_account_id(auth, username).await?;
+    let enrollment = auth.enroll_totp(account_id).await?;
+
+    tracing::info!("Each recovery code can be used once; store them safely");
+    output.record(&TotpEnrollmentRecord {
+        username: username.to_string(),
+        secret: enrollment.secret,
+        otpauth_uri: enrollment.otpauth_uri,
+        recovery_codes: enrollment.recovery_codes,
+    })
+}
+
+/// Disable TOTP two-factor authentication for an account.
+async fn disable_totp(
+    auth: &AuthAPI,
+    username: &str,
+    output: &Output,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let account_id = find_account_id(auth, username).await?;
+    let was_enabled = auth.disable_totp(account_id).await?;
+    output.record(&TotpDisableRecord {
+        username: username.to_string(),
+        was_enabled,
+    })
+}
+
+/// List all accounts with permissions.
+async fn list_accounts(auth: &AuthAPI, output: &Output) -> Result<(), Box<dyn std::error::Error>> {
+    let records: Vec<_> = auth
+        .get_accounts()
+        .await?
+        .into_iter()
+        .map(|account| AccountRecord {
+            id: account.id,
+            username: account.username,
+            is_admin: account.is_admin,
+            permissions: account.permissions.into_iter().collect(),
+        })
+        .collect();
+    output.list(&records, "No accounts found.")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_time() -> Result<(), String> {
+        assert_eq!(
+            parse_time("2025-01-02T03:04:05Z")?.to_rfc3339(),
+            "2025-01-02T03:04:05+00:00"
+        );
+        assert_eq!(
+            parse_time("2025-01-02")?.to_rfc3339(),
+            "2025-01-02T00:00:00+00:00"
         );
+
+        let since = parse_time("7d")?;
+        let ago = Utc::now() - since;
+        assert!(ago >= Duration::days(7) && ago < Duration::days(7) + Duration::minutes(1));
+
+        assert!(parse_time("7w").is_err());
+        assert!(parse_time("d").is_err());
+        assert!(parse_time("").is_err());
+        assert!(parse_time("7é").is_err());
+
+        Ok(())
     }
 
-    Ok(())
+    #[test]
+    fn test_parse_identity_id() {
+        let id = "identity--f431f809-377b-45e0-aa1c-6a4751cae5ff";
+        assert_eq!(parse_identity_id(id), Ok(id.to_string()));
+        assert!(parse_identity_id("indicator--f431f809-377b-45e0-aa1c-6a4751cae5ff").is_err());
+        assert!(parse_identity_id("identity--acme").is_err());
+    }
+
+    #[test]
+    fn test_parse_event_type() {
+        assert_eq!(parse_event_type("login-failed"), Ok(EventType::LoginFailed));
+        assert!(parse_event_type("logout").is_err());
+    }
 }
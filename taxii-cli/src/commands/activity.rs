@@ -1,8 +1,11 @@
 //! Account activity commands for credential usage tracking.
 
-use chrono::{DateTime, Local, Utc};
+use chrono::{DateTime, Utc};
 use clap::Subcommand;
-use taxii_db::{AccountActivity, TaxiiPool};
+use serde::Serialize;
+use taxii_db::{AccountActivity, AccountUsageSummary, TaxiiPool};
+
+use super::output::{Output, Record};
 
 /// Activity management actions.
 #[derive(Subcommand)]
@@ -30,10 +33,77 @@ pub enum ActivityAction {
     },
 }
 
+/// Credential usage of an account in `activity usage`.
+#[derive(Debug, Serialize)]
+struct UsageRecord {
+    account_id: i32,
+    username: String,
+    is_admin: bool,
+    last_login: Option<DateTime<Utc>>,
+    last_ip: Option<String>,
+    login_count: i64,
+    failed_count: i64,
+}
+
+impl Record for UsageRecord {
+    const COLUMNS: &'static [(&'static str, &'static str)] = &[
+        ("account_id", "ID"),
+        ("username", "Username"),
+        ("is_admin", "Admin"),
+        ("last_login", "Last Login"),
+        ("last_ip", "Last IP"),
+        ("login_count", "Logins"),
+        ("failed_count", "Failed"),
+    ];
+}
+
+impl From<AccountUsageSummary> for UsageRecord {
+    fn from(summary: AccountUsageSummary) -> Self {
+        Self {
+            account_id: summary.account_id,
+            username: summary.username,
+            is_admin: summary.is_admin,
+            last_login: summary.last_login,
+            last_ip: summary.last_ip,
+            login_count: summary.login_count,
+            failed_count: summary.failed_count,
+        }
+    }
+}
+
+/// An account that never logged in, in `activity usage --unused`.
+#[derive(Debug, Serialize)]
+struct UnusedRecord {
+    account_id: i32,
+    username: String,
+}
+
+impl Record for UnusedRecord {
+    const COLUMNS: &'static [(&'static str, &'static str)] =
+        &[("account_id", "ID"), ("username", "Username")];
+}
+
+/// Outcome of `activity cleanup`; nothing is deleted in a dry run.
+#[derive(Debug, Serialize)]
+struct CleanupRecord {
+    retention_days: i32,
+    dry_run: bool,
+    deleted: Option<u64>,
+}
+
+impl Record for CleanupRecord {
+    const COLUMNS: &'static [(&'static str, &'static str)] = &[
+        ("retention_days", "Retention (days)"),
+        ("dry_run", "Dry run"),
+        ("deleted", "Deleted"),
+    ];
+}
+
 /// Handle activity commands.
 pub async fn handle(
     pool: TaxiiPool,
     action: ActivityAction,
+    output: &Output,
 ) -> Result<(), Box<dyn std::error::Error>> {
     match action {
         ActivityAction::Usage {
@@ -41,128 +111,68 @@ pub async fn handle(
             unused,
         } => {
             if unused {
-                show_unused_accounts(&pool).await?;
+                show_unused_accounts(&pool, output).await?;
             } else if let Some(days) = inactive_days {
-                show_inactive_accounts(&pool, days).await?;
+                show_inactive_accounts(&pool, days, output).await?;
             } else {
-                show_usage_summary(&pool).await?;
+                show_usage_summary(&pool, output).await?;
             }
         }
         ActivityAction::Cleanup {
             retention_days,
             confirm,
         } => {
-            cleanup_old_records(&pool, retention_days, confirm).await?;
+            cleanup_old_records(&pool, retention_days, confirm, output).await?;
         }
     }
 
     Ok(())
 }
 
-/// Format datetime for display in local timezone.
-fn format_datetime(dt: Option<DateTime<Utc>>) -> String {
-    match dt {
-        Some(dt) => {
-            let local: DateTime<Local> = dt.into();
-            local.format("%Y-%m-%d %H:%M").to_string()
-        }
-        None => "Never".to_string(),
-    }
-}
-
 /// Show credential usage summary for all accounts.
-async fn show_usage_summary(pool: &TaxiiPool) -> Result<(), Box<dyn std::error::Error>> {
-    let summaries = AccountActivity::get_usage_summary(pool).await?;
-
-    if summaries.is_empty() {
-        println!("No accounts found.");
-        return Ok(());
-    }
-
-    println!(
-        "{:<5} {:<20} {:<8} {:<18} {:<16} {:<8} {:<8}",
-        "ID", "Username", "Admin", "Last Login", "Last IP", "Logins", "Failed"
-    );
-    println!("{}", "-".repeat(95));
-
-    for summary in summaries {
-        let last_login = format_datetime(summary.last_login);
-        let last_ip = summary.last_ip.unwrap_or_else(|| "-".to_string());
-
-        println!(
-            "{:<5} {:<20} {:<8} {:<18} {:<16} {:<8} {:<8}",
-            summary.account_id,
-            truncate(&summary.username, 20),
-            if summary.is_admin { "Yes" } else { "No" },
-            last_login,
-            truncate(&last_ip, 16),
-            summary.login_count,
-            summary.failed_count
-        );
-    }
-
-    Ok(())
+async fn show_usage_summary(
+    pool: &TaxiiPool,
+    output: &Output,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let records: Vec<UsageRecord> = AccountActivity::get_usage_summary(pool)
+        .await?
+        .into_iter()
+        .map(Into::into)
+        .collect();
+    output.list(&records, "No accounts found.")
 }
 
 /// Show accounts that have never logged in.
-async fn show_unused_accounts(pool: &TaxiiPool) -> Result<(), Box<dyn std::error::Error>> {
-    let unused = AccountActivity::get_unused_accounts(pool).await?;
-
-    if unused.is_empty() {
-        println!("All accounts have been used at least once.");
-        return Ok(());
-    }
-
-    println!("Accounts that have never logged in:");
-    println!("{:<5} {:<30}", "ID", "Username");
-    println!("{}", "-".repeat(40));
-
-    let count = unused.len();
-    for (id, username) in unused {
-        println!("{id:<5} {username:<30}");
-    }
-
-    println!("\nTotal: {count} unused account(s)");
-
-    Ok(())
+async fn show_unused_accounts(
+    pool: &TaxiiPool,
+    output: &Output,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let records: Vec<_> = AccountActivity::get_unused_accounts(pool)
+        .await?
+        .into_iter()
+        .map(|(account_id, username)| UnusedRecord {
+            account_id,
+            username,
+        })
+        .collect();
+    output.list(&records, "All accounts have been used at least once.")
 }
 
 /// Show accounts inactive for specified number of days.
 async fn show_inactive_accounts(
     pool: &TaxiiPool,
     days: i32,
+    output: &Output,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let summaries = AccountActivity::get_inactive_accounts(pool, days).await?;
-
-    if summaries.is_empty() {
-        println!("All accounts have been active in the last {days} days.");
-        return Ok(());
-    }
-
-    println!("Accounts inactive for {days}+ days:");
-    println!(
-        "{:<5} {:<20} {:<8} {:<18} {:<16}",
-        "ID", "Username", "Admin", "Last Login", "Last IP"
-    );
-    println!("{}", "-".repeat(75));
-
-    for summary in &summaries {
-        let last_login = format_datetime(summary.last_login);
-        let last_ip = summary.last_ip.clone().unwrap_or_else(|| "-".to_string());
-
-        println!(
-            "{:<5} {:<20} {:<8} {:<18} {:<16}",
-            summary.account_id,
-            truncate(&summary.username, 20),
-            if summary.is_admin { "Yes" } else { "No" },
-            last_login,
-            truncate(&last_ip, 16)
-        );
-    }
-
-    println!("\nTotal: {} inactive account(s)", summaries.len());
-
-    Ok(())
+    let records: Vec<UsageRecord> = AccountActivity::get_inactive_accounts(pool, days)
+        .await?
+        .into_iter()
+        .map(Into::into)
+        .collect();
+    output.list(
+        &records,
+        &format!("All accounts have been active in the last {days} days."),
+    )
 }
 
 /// Clean up old activity records.
@@ -170,30 +180,17 @@ async fn cleanup_old_records(
     pool: &TaxiiPool,
     retention_days: i32,
     confirm: bool,
+    output: &Output,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    if !confirm {
-        // Dry run - just show what would be deleted
-        println!("Would delete activity records older than {retention_days} days.");
-        println!("Run with --confirm to actually delete records.");
-        return Ok(());
-    }
-
-    let deleted = AccountActivity::cleanup_old_records(pool, retention_days).await?;
-
-    if deleted > 0 {
-        println!("Deleted {deleted} activity record(s) older than {retention_days} days.");
+    let deleted = if confirm {
+        Some(AccountActivity::cleanup_old_records(pool, retention_days).await?)
     } else {
-        println!("No records older than {retention_days} days found.");
-    }
-
-    Ok(())
-}
-
-/// Truncate string to specified length.
-fn truncate(s: &str, max_len: usize) -> String {
-    if s.len() <= max_len {
-        s.to_string()
-    } else {
-        format!("{}...", &s[..max_len - 3])
-    }
+        tracing::info!("Dry run; pass --confirm to delete the records");
+        None
+    };
+    output.record(&CleanupRecord {
+        retention_days,
+        dry_run: !confirm,
+        deleted,
+    })
 }
@@ -0,0 +1,138 @@
+//! Object audit log commands.
+
+use chrono::{DateTime, Utc};
+use clap::Subcommand;
+use serde::Serialize;
+use taxii_db::{ActivityCursor, AuditOperation, ObjectAudit, ObjectAuditFilter, TaxiiPool};
+use uuid::Uuid;
+
+use super::account::{parse_cursor, parse_time};
+use super::output::{Output, Record};
+
+/// Object audit actions.
+#[derive(Subcommand)]
+pub enum AuditAction {
+    /// Show TAXII 2.x object changes, newest first.
+    List {
+        /// Only changes in this collection (ID).
+        #[arg(long)]
+        collection: Option<Uuid>,
+
+        /// Only changes of this STIX object.
+        #[arg(long)]
+        object: Option<String>,
+
+        /// Only changes by this account.
+        #[arg(long)]
+        username: Option<String>,
+
+        /// Only changes of this kind (create, update, delete).
+        #[arg(long = "operation", value_parser = parse_operation)]
+        operations: Vec<AuditOperation>,
+
+        /// Only changes since this time (RFC 3339, YYYY-MM-DD, or relative like 24h or 7d).
+        #[arg(long, value_parser = parse_time)]
+        since: Option<DateTime<Utc>>,
+
+        /// Only changes before this time (same formats as --since).
+        #[arg(long, value_parser = parse_time)]
+        until: Option<DateTime<Utc>>,
+
+        /// Maximum number of changes to show.
+        #[arg(long, default_value = "50")]
+        limit: i64,
+
+        /// Continue from a previous page.
+        #[arg(long, value_parser = parse_cursor)]
+        next: Option<ActivityCursor>,
+    },
+}
+
+/// An object change in `audit list`.
+#[derive(Debug, Serialize)]
+struct AuditRecord {
+    created_at: DateTime<Utc>,
+    operation: String,
+    username: Option<String>,
+    account_id: Option<i32>,
+    collection_id: Uuid,
+    object_id: String,
+    object_version: String,
+}
+
+impl Record for AuditRecord {
+    const COLUMNS: &'static [(&'static str, &'static str)] = &[
+        ("created_at", "Time"),
+        ("operation", "Change"),
+        ("username", "User"),
+        ("account_id", "Account ID"),
+        ("collection_id", "Collection"),
+        ("object_version", "Version"),
+        ("object_id", "Object"),
+    ];
+}
+
+/// Handle audit commands.
+pub async fn handle(
+    pool: TaxiiPool,
+    action: AuditAction,
+    output: &Output,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match action {
+        AuditAction::List {
+            collection,
+            object,
+            username,
+            operations,
+            since,
+            until,
+            limit,
+            next,
+        } => {
+            let filter = ObjectAuditFilter {
+                collection_id: collection,
+                object_id: object,
+                username,
+                operations,
+                since,
+                until,
+                limit: Some(limit),
+                after: next,
+                ..Default::default()
+            };
+            show_audit(&pool, &filter, output).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Parse an audit operation.
+fn parse_operation(value: &str) -> Result<AuditOperation, String> {
+    AuditOperation::parse(value)
+        .ok_or_else(|| format!("unknown operation '{value}' (expected create, update or delete)"))
+}
+
+/// Show recorded object changes.
+async fn show_audit(
+    pool: &TaxiiPool,
+    filter: &ObjectAuditFilter,
+    output: &Output,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let page = ObjectAudit::find_filtered(pool, filter).await?;
+
+    let records: Vec<_> = page
+        .items
+        .into_iter()
+        .map(|record| AuditRecord {
+            created_at: record.created_at,
+            operation: record.operation.to_string(),
+            username: record.username,
+            account_id: record.account_id,
+            collection_id: record.collection_id,
+            object_id: record.object_id,
+            object_version: taxii_core::taxii2_datetimeformat(&record.object_version),
+        })
+        .collect();
+    output.page(&records, page.next.as_deref(), "No object changes found.")
+}
@@ -0,0 +1,432 @@
+//! Export TAXII 2.x collections to files.
+
+use chrono::{DateTime, Utc};
+use clap::{Args, ValueEnum};
+use futures::StreamExt;
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use stix2::BundleWriter;
+use taxii_core::STIXObject;
+use taxii_db::{DbTaxii2Repository, Taxii2QueryParams, Taxii2Repository, TaxiiPool};
+
+use super::account::parse_time;
+use super::output::{Output, Record};
+use super::push::find_collection;
+
+/// Arguments of `collection export`.
+#[derive(Args)]
+pub struct ExportArgs {
+    /// API root ID.
+    #[arg(long)]
+    pub api_root: String,
+
+    /// Collection ID.
+    #[arg(long)]
+    pub collection: String,
+
+    /// Output file. With --split-size, files are numbered
+    /// (`bundle-1.json`, `bundle-2.json`, ...).
+    #[arg(long)]
+    pub output: PathBuf,
+
+    /// Output format.
+    #[arg(long, value_enum, default_value = "bundle")]
+    pub format: ExportFormat,
+
+    /// Only objects of this type (repeatable).
+    #[arg(long = "type")]
+    pub types: Vec<String>,
+
+    /// Only objects added after this time (RFC 3339, YYYY-MM-DD, or relative like 24h or 7d).
+    #[arg(long, value_parser = parse_time)]
+    pub added_after: Option<DateTime<Utc>>,
+
+    /// Versions of each object to export.
+    #[arg(long, value_enum, default_value = "last")]
+    pub match_version: ExportVersions,
+
+    /// Start a new file after this many objects.
+    #[arg(long)]
+    pub split_size: Option<NonZeroUsize>,
+
+    /// Also export deleted versions.
+    #[arg(long, default_value = "false")]
+    pub include_deleted: bool,
+}
+
+/// Format of exported files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// A STIX bundle per file.
+    Bundle,
+    /// One object per line.
+    Ndjson,
+}
+
+/// Versions of each object to export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportVersions {
+    /// Every version.
+    All,
+    /// The latest version.
+    Last,
+}
+
+impl ExportVersions {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::All => "all",
+            Self::Last => "last",
+        }
+    }
+}
+
+/// Outcome of an export.
+#[derive(Debug, Default)]
+struct ExportSummary {
+    /// Objects written.
+    objects: usize,
+    /// Files written.
+    files: Vec<PathBuf>,
+    /// Total size of the files in bytes.
+    bytes: u64,
+    /// Earliest and latest date added of the objects written.
+    added: Option<(DateTime<Utc>, DateTime<Utc>)>,
+}
+
+impl ExportSummary {
+    fn record(self) -> ExportRecord {
+        ExportRecord {
+            objects: self.objects,
+            files: self
+                .files
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect(),
+            bytes: self.bytes,
+            first_added: self.added.map(|(first, _)| first),
+            last_added: self.added.map(|(_, last)| last),
+        }
+    }
+}
+
+/// Outcome of `collection export`.
+#[derive(Debug, Serialize)]
+struct ExportRecord {
+    objects: usize,
+    files: Vec<String>,
+    bytes: u64,
+    first_added: Option<DateTime<Utc>>,
+    last_added: Option<DateTime<Utc>>,
+}
+
+impl Record for ExportRecord {
+    const COLUMNS: &'static [(&'static str, &'static str)] = &[
+        ("objects", "Objects"),
+        ("files", "Files"),
+        ("bytes", "Bytes"),
+        ("first_added", "First added"),
+        ("last_added", "Last added"),
+    ];
+}
+
+/// An open output file.
+enum Sink {
+    Bundle(BundleWriter<BufWriter<File>>),
+    Ndjson(BufWriter<File>),
+}
+
+impl Sink {
+    fn create(path: &Path, format: ExportFormat) -> Result<Self, Box<dyn std::error::Error>> {
+        let file = BufWriter::new(
+            File::create(path).map_err(|e| format!("Failed to create {}: {e}", path.display()))?,
+        );
+        Ok(match format {
+            ExportFormat::Bundle => Self::Bundle(BundleWriter::new(file)?),
+            ExportFormat::Ndjson => Self::Ndjson(file),
+        })
+    }
+
+    fn write(&mut self, object: &Value) -> Result<(), Box<dyn std::error::Error>> {
+        match self {
+            Self::Bundle(writer) => writer.write_object(object)?,
+            Self::Ndjson(writer) => {
+                serde_json::to_writer(&mut *writer, object)?;
+                writer.write_all(b"\n")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<(), Box<dyn std::error::Error>> {
+        match self {
+            Self::Bundle(writer) => writer.finish()?.flush()?,
+            Self::Ndjson(mut writer) => writer.flush()?,
+        }
+        Ok(())
+    }
+}
+
+/// Writes objects to one file, or to numbered files of `split_size`
+/// objects each.
+struct ExportWriter<'a> {
+    args: &'a ExportArgs,
+    current: Option<Sink>,
+    in_current: usize,
+    summary: ExportSummary,
+}
+
+impl<'a> ExportWriter<'a> {
+    fn new(args: &'a ExportArgs) -> Self {
+        Self {
+            args,
+            current: None,
+            in_current: 0,
+            summary: ExportSummary::default(),
+        }
+    }
+
+    /// Path of the `number`th file (1-based).
+    fn path(&self, number: usize) -> PathBuf {
+        let output = &self.args.output;
+        if self.args.split_size.is_none() {
+            return output.clone();
+        }
+        let stem = output
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let name = match output.extension() {
+            Some(ext) => format!("{stem}-{number}.{}", ext.to_string_lossy()),
+            None => format!("{stem}-{number}"),
+        };
+        output.with_file_name(name)
+    }
+
+    fn write(&mut self, object: &STIXObject) -> Result<(), Box<dyn std::error::Error>> {
+        let full = self
+            .args
+            .split_size
+            .is_some_and(|size| self.in_current == size.get());
+        if full {
+            self.close()?;
+        }
+        let sink = match &mut self.current {
+            Some(sink) => sink,
+            None => {
+                let path = self.path(self.summary.files.len() + 1);
+                let sink = Sink::create(&path, self.args.format)?;
+                self.summary.files.push(path);
+                self.current.insert(sink)
+            }
+        };
+
+        sink.write(&full_object(object))?;
+        self.in_current += 1;
+        self.summary.objects += 1;
+        self.summary.added = Some(match self.summary.added {
+            Some((first, last)) => (first.min(object.date_added), last.max(object.date_added)),
+            None => (object.date_added, object.date_added),
+        });
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(sink) = self.current.take() {
+            sink.finish()?;
+        }
+        self.in_current = 0;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<ExportSummary, Box<dyn std::error::Error>> {
+        // An empty export still writes an (empty) file
+        if self.summary.files.is_empty() {
+            let path = self.path(1);
+            self.current = Some(Sink::create(&path, self.args.format)?);
+            self.summary.files.push(path);
+        }
+        self.close()?;
+        for path in &self.summary.files {
+            self.summary.bytes += fs::metadata(path)?.len();
+        }
+        Ok(self.summary)
+    }
+}
+
+/// Reconstruct the STIX object of a stored row.
+fn full_object(object: &STIXObject) -> Value {
+    let mut value = object.serialized_data.clone();
+    if let Some(map) = value.as_object_mut() {
+        map.insert("id".to_string(), json!(object.id));
+        map.insert("type".to_string(), json!(object.stix_type));
+        map.insert("spec_version".to_string(), json!(object.spec_version));
+    }
+    value
+}
+
+/// Export a collection to files.
+pub async fn export_collection(
+    pool: &TaxiiPool,
+    persistence: &DbTaxii2Repository,
+    args: &ExportArgs,
+    output: &Output,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let summary = export_objects(pool, persistence, args).await?;
+    output.record(&summary.record())
+}
+
+/// Stream the objects of a collection into the output files.
+async fn export_objects(
+    pool: &TaxiiPool,
+    persistence: &DbTaxii2Repository,
+    args: &ExportArgs,
+) -> Result<ExportSummary, Box<dyn std::error::Error>> {
+    find_collection(pool, &args.api_root, &args.collection).await?;
+
+    let match_version = [args.match_version.as_str().to_string()];
+    let params = Taxii2QueryParams {
+        added_after: args.added_after,
+        match_type: (!args.types.is_empty()).then_some(args.types.as_slice()),
+        match_version: Some(&match_version),
+        include_deleted: args.include_deleted,
+        ..Default::default()
+    };
+
+    // Rows are written as they arrive, so memory use does not grow with
+    // the size of the collection
+    let mut writer = ExportWriter::new(args);
+    let mut rows = persistence.stream_objects(&args.collection, &params);
+    while let Some(object) = rows.next().await {
+        writer.write(&object?)?;
+    }
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::PgPool;
+    use stix2::Bundle;
+
+    type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+    const BUNDLE: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/bundle_with_invalid_object.json"
+    );
+    const INVALID_ID: &str = "indicator--c1f5a8b2-3d4e-4f60-8a7b-9c0d1e2f3a4b";
+    const MALWARE_ID: &str = "malware--31b940d4-6f7f-459a-80ea-9c1f17b5891b";
+
+    fn export_args(output: PathBuf) -> ExportArgs {
+        ExportArgs {
+            api_root: String::new(),
+            collection: String::new(),
+            output,
+            format: ExportFormat::Bundle,
+            types: Vec::new(),
+            added_after: None,
+            match_version: ExportVersions::Last,
+            split_size: None,
+            include_deleted: false,
+        }
+    }
+
+    /// A seeded collection exports to a bundle and to split NDJSON files
+    /// that parse back to the stored objects; deleted objects are only
+    /// exported on request.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_export_collection(pool: PgPool) -> TestResult {
+        let pool = TaxiiPool::new(pool);
+        let persistence = DbTaxii2Repository::new(pool.clone());
+        let api_root = persistence
+            .add_api_root("Root", None, true, true, None)
+            .await?;
+        let collection = persistence
+            .add_collection(&api_root.id, "Feed", None, None, true, true, None)
+            .await?;
+        let bundle: Value = serde_json::from_str(&fs::read_to_string(BUNDLE)?)?;
+        let objects: Vec<Value> = bundle["objects"]
+            .as_array()
+            .ok_or("fixture has no objects")?
+            .iter()
+            .filter(|o| o["id"] != INVALID_ID)
+            .cloned()
+            .collect();
+        persistence
+            .add_objects_bulk(&api_root.id, &collection.id, &objects)
+            .await?;
+        persistence
+            .delete_object(&collection.id, MALWARE_ID, None, None, None)
+            .await?;
+
+        let dir = tempfile::tempdir()?;
+        let mut args = export_args(dir.path().join("feed.json"));
+        args.api_root = api_root.id.clone();
+        args.collection = collection.id.clone();
+
+        // One bundle without the deleted malware
+        let summary = export_objects(&pool, &persistence, &args).await?;
+        assert_eq!(summary.objects, 2);
+        assert_eq!(summary.files, [args.output.clone()]);
+        assert_eq!(summary.bytes, fs::metadata(&args.output)?.len());
+        let bundle = Bundle::from_json(&fs::read_to_string(&args.output)?)?;
+        assert_eq!(bundle.len(), 2);
+        assert!(bundle.iter().all(|o| o.id().to_string() != MALWARE_ID));
+
+        // Split NDJSON with the deleted malware
+        args.format = ExportFormat::Ndjson;
+        args.split_size = NonZeroUsize::new(2);
+        args.include_deleted = true;
+        let summary = export_objects(&pool, &persistence, &args).await?;
+        assert_eq!(summary.objects, 3);
+        assert_eq!(
+            summary.files,
+            [
+                dir.path().join("feed-1.json"),
+                dir.path().join("feed-2.json")
+            ]
+        );
+        let mut ids = Vec::new();
+        for path in &summary.files {
+            for line in fs::read_to_string(path)?.lines() {
+                ids.push(stix2::parse(line)?.id().to_string());
+            }
+        }
+        assert_eq!(ids.len(), 3);
+        assert!(ids.iter().any(|id| id == MALWARE_ID));
+
+        // Filters apply; an empty export writes an empty bundle
+        args.format = ExportFormat::Bundle;
+        args.split_size = None;
+        args.types = vec!["indicator".to_string()];
+        assert_eq!(export_objects(&pool, &persistence, &args).await?.objects, 1);
+        args.added_after = Some(Utc::now());
+        let summary = export_objects(&pool, &persistence, &args).await?;
+        assert_eq!(summary.objects, 0);
+        assert!(Bundle::from_json(&fs::read_to_string(&args.output)?)?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_paths() {
+        let mut args = export_args(PathBuf::from("/tmp/out/bundle.json"));
+        assert_eq!(
+            ExportWriter::new(&args).path(3),
+            Path::new("/tmp/out/bundle.json")
+        );
+        args.split_size = NonZeroUsize::new(10);
+        assert_eq!(
+            ExportWriter::new(&args).path(3),
+            Path::new("/tmp/out/bundle-3.json")
+        );
+        args.output = PathBuf::from("objects");
+        assert_eq!(ExportWriter::new(&args).path(1), Path::new("objects-1"));
+    }
+}
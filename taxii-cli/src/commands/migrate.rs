@@ -1,8 +1,13 @@
 //! Database migration commands.
 
+use chrono::{DateTime, Utc};
 use clap::Subcommand;
-use std::collections::HashSet;
-use taxii_db::TaxiiPool;
+use serde::Serialize;
+use taxii_db::migrations::{MigrationInfo, MigrationState};
+use taxii_db::{TaxiiPool, partitioning};
+use tracing::{info, warn};
+
+use super::output::{Output, Record};
 
 #[derive(Subcommand)]
 pub enum MigrateAction {
@@ -12,77 +17,205 @@ pub enum MigrateAction {
     Status,
     /// List all available migrations.
     Info,
+    /// Revert the last applied migration using its down-migration.
+    Revert {
+        /// Confirm the revert; down-migrations can drop data.
+        #[arg(long = "yes-i-know")]
+        yes_i_know: bool,
+    },
+    /// Partition the STIX object table by collection (PostgreSQL 11+).
+    ///
+    /// Copies every object into a new table while holding an exclusive lock
+    /// on it; stop all servers first. Enable database.partition_objects
+    /// afterwards.
+    Partition,
+    /// Convert a partitioned STIX object table back to a plain table.
+    ///
+    /// Copies every object like `partition`; disable
+    /// database.partition_objects and stop all servers first.
+    Unpartition,
+}
+
+/// A migration in `migrate info` and `migrate revert`.
+#[derive(Debug, Serialize)]
+struct MigrationRecord {
+    version: i64,
+    description: String,
+    reversible: bool,
+}
+
+impl Record for MigrationRecord {
+    const COLUMNS: &'static [(&'static str, &'static str)] = &[
+        ("version", "Version"),
+        ("description", "Description"),
+        ("reversible", "Reversible"),
+    ];
+}
+
+impl From<MigrationInfo> for MigrationRecord {
+    fn from(migration: MigrationInfo) -> Self {
+        Self {
+            version: migration.version,
+            description: migration.description,
+            reversible: migration.reversible,
+        }
+    }
+}
+
+/// A migration in `migrate status`.
+#[derive(Debug, Serialize)]
+struct MigrationStatusRecord {
+    version: i64,
+    description: String,
+    state: &'static str,
+    reversible: bool,
+    installed_on: Option<DateTime<Utc>>,
+}
+
+impl Record for MigrationStatusRecord {
+    const COLUMNS: &'static [(&'static str, &'static str)] = &[
+        ("version", "Version"),
+        ("description", "Description"),
+        ("state", "State"),
+        ("reversible", "Reversible"),
+        ("installed_on", "Installed"),
+    ];
+}
+
+/// Outcome of `migrate run`.
+#[derive(Debug, Serialize)]
+struct RunRecord {
+    applied: Vec<i64>,
+}
+
+impl Record for RunRecord {
+    const COLUMNS: &'static [(&'static str, &'static str)] = &[("applied", "Applied")];
+}
+
+/// Outcome of `migrate partition` and `migrate unpartition`.
+#[derive(Debug, Serialize)]
+struct ConversionRecord {
+    rows: u64,
+    partitions: u64,
+}
+
+impl Record for ConversionRecord {
+    const COLUMNS: &'static [(&'static str, &'static str)] =
+        &[("rows", "Objects copied"), ("partitions", "Partitions")];
 }
 
 pub async fn handle(
     pool: TaxiiPool,
     action: MigrateAction,
+    output: &Output,
 ) -> Result<(), Box<dyn std::error::Error>> {
     match action {
-        MigrateAction::Run => run_migrations(pool).await,
-        MigrateAction::Status => show_status(pool).await,
-        MigrateAction::Info => show_info(),
+        MigrateAction::Run => run_migrations(pool, output).await,
+        MigrateAction::Status => show_status(pool, output).await,
+        MigrateAction::Info => show_info(output),
+        MigrateAction::Revert { yes_i_know } => revert_last(pool, yes_i_know, output).await,
+        MigrateAction::Partition => convert_object_table(pool, true, output).await,
+        MigrateAction::Unpartition => convert_object_table(pool, false, output).await,
     }
 }
 
-async fn run_migrations(pool: TaxiiPool) -> Result<(), Box<dyn std::error::Error>> {
-    println!("Running migrations...");
+async fn convert_object_table(
+    pool: TaxiiPool,
+    partition: bool,
+    output: &Output,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let report = if partition {
+        info!("Partitioning the STIX object table by collection...");
+        let report = partitioning::partition_table(&pool).await?;
+        info!("Set database.partition_objects = true before starting the server.");
+        report
+    } else {
+        info!("Converting the STIX object table to a plain table...");
+        partitioning::unpartition_table(&pool).await?
+    };
 
-    match taxii_db::migrations::run(pool.inner()).await {
-        Ok(()) => {
-            println!("Migrations completed successfully.");
-            Ok(())
-        }
-        Err(e) => {
-            eprintln!("Migration failed: {e}");
-            Err(e.into())
-        }
-    }
+    output.record(&ConversionRecord {
+        rows: report.rows,
+        partitions: report.partitions,
+    })
 }
 
-async fn show_status(pool: TaxiiPool) -> Result<(), Box<dyn std::error::Error>> {
-    let all_migrations = taxii_db::migrations::list();
+async fn run_migrations(
+    pool: TaxiiPool,
+    output: &Output,
+) -> Result<(), Box<dyn std::error::Error>> {
+    info!("Running migrations...");
+
+    let before = taxii_db::migrations::applied(pool.inner()).await?;
+    taxii_db::migrations::run(pool.inner()).await?;
     let applied = taxii_db::migrations::applied(pool.inner())
         .await?
         .into_iter()
-        .collect::<HashSet<_>>();
-
-    println!("Migration Status:");
-    println!("{:-<60}", "");
-
-    let mut pending_count = 0;
-    for migration in &all_migrations {
-        let status = if applied.contains(&migration.version) {
-            "applied"
-        } else {
-            pending_count += 1;
-            "pending"
-        };
-        println!(
-            "  {} {} [{}]",
-            migration.version, migration.description, status
+        .filter(|version| !before.contains(version))
+        .collect();
+
+    output.record(&RunRecord { applied })
+}
+
+async fn show_status(pool: TaxiiPool, output: &Output) -> Result<(), Box<dyn std::error::Error>> {
+    let statuses = taxii_db::migrations::status(pool.inner()).await?;
+
+    let count = |state| statuses.iter().filter(|s| s.state == state).count();
+    let modified = count(MigrationState::Modified);
+    let failed = count(MigrationState::Failed);
+    let pending = count(MigrationState::Pending);
+    if modified > 0 {
+        warn!(
+            "{modified} applied migration(s) were modified. Restore the original files before running migrations."
         );
     }
-
-    println!("{:-<60}", "");
-    if pending_count == 0 {
-        println!("All migrations are up to date.");
-    } else {
-        println!("{pending_count} pending migration(s). Run 'taxii-cli migrate run' to apply.");
+    if failed > 0 {
+        warn!("{failed} migration(s) failed and must be repaired manually.");
+    }
+    if pending > 0 {
+        info!("{pending} pending migration(s). Run 'taxii-cli migrate run' to apply.");
     }
 
-    Ok(())
+    let records: Vec<_> = statuses
+        .into_iter()
+        .map(|migration| MigrationStatusRecord {
+            version: migration.version,
+            description: migration.description,
+            state: migration.state.as_str(),
+            reversible: migration.reversible,
+            installed_on: migration.installed_on,
+        })
+        .collect();
+    output.list(&records, "No migrations found.")
 }
 
-fn show_info() -> Result<(), Box<dyn std::error::Error>> {
-    let migrations = taxii_db::migrations::list();
-
-    println!("Available Migrations ({} total):", migrations.len());
-    println!("{:-<60}", "");
-
-    for migration in migrations {
-        println!("  {} {}", migration.version, migration.description);
+async fn revert_last(
+    pool: TaxiiPool,
+    yes_i_know: bool,
+    output: &Output,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !yes_i_know {
+        if let Some(version) = taxii_db::migrations::applied(pool.inner()).await?.pop() {
+            info!("Would revert migration {version}.");
+        }
+        return Err(
+            "Down-migrations can drop data. Back up the database and pass --yes-i-know to revert."
+                .into(),
+        );
     }
 
-    Ok(())
+    let records: Vec<MigrationRecord> = taxii_db::migrations::revert_last(pool.inner())
+        .await?
+        .into_iter()
+        .map(Into::into)
+        .collect();
+    output.list(&records, "No migrations are applied.")
+}
+
+fn show_info(output: &Output) -> Result<(), Box<dyn std::error::Error>> {
+    let records: Vec<MigrationRecord> = taxii_db::migrations::list()
+        .into_iter()
+        .map(Into::into)
+        .collect();
+    output.list(&records, "No migrations found.")
 }
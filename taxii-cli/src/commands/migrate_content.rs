@@ -0,0 +1,484 @@
+//! Migrate TAXII 1.x content blocks into TAXII 2.x collections.
+
+use std::collections::BTreeMap;
+use std::num::NonZeroUsize;
+
+use clap::Args;
+use futures::StreamExt;
+use serde::Serialize;
+use serde_json::Value;
+use taxii_1x::validation::is_stix_xml_binding;
+use taxii_core::ContentBlockEntity;
+use taxii_db::{Collection, DbTaxii1Repository, DbTaxii2Repository, Taxii1Repository, TaxiiPool};
+use uuid::Uuid;
+
+use super::output::{Output, Record};
+use super::push::{PushRecord, PushSummary, push_objects};
+
+/// Arguments of `content migrate`.
+#[derive(Args)]
+pub struct MigrateContentArgs {
+    /// TAXII 1.x collection name.
+    #[arg(long)]
+    pub from_collection: String,
+
+    /// TAXII 2.x collection ID.
+    #[arg(long)]
+    pub to_collection: Uuid,
+
+    /// Skip STIX 1.x XML content instead of converting it to STIX 2.1.
+    #[arg(long, default_value = "false")]
+    pub skip_xml: bool,
+
+    /// Validate the objects against the STIX 2.1 specification and skip
+    /// invalid ones.
+    #[arg(long, default_value = "false")]
+    pub validate: bool,
+
+    /// Objects written per batch.
+    #[arg(long, default_value = "500")]
+    pub batch_size: NonZeroUsize,
+}
+
+/// STIX objects read from a content block.
+#[derive(Debug)]
+enum BlockContent {
+    /// STIX 2.x objects, passed through as they are.
+    Stix2(Vec<Value>),
+    /// Objects converted from a STIX 1.x package, and the number of
+    /// constructs that could not be converted by kind.
+    #[cfg_attr(
+        not(feature = "bridge"),
+        expect(dead_code, reason = "only converted with the bridge feature")
+    )]
+    Stix1 {
+        objects: Vec<Value>,
+        unconverted: BTreeMap<String, usize>,
+    },
+    /// STIX 1.x content left out, with `--skip-xml` or without the bridge
+    /// feature.
+    Skipped,
+}
+
+/// Outcome of a migration.
+#[derive(Debug, Default)]
+struct MigrateSummary {
+    /// Content blocks read.
+    blocks: usize,
+    /// Blocks of STIX 2.x content.
+    passed_through: usize,
+    /// Blocks of STIX 1.x content converted.
+    converted: usize,
+    /// Blocks of STIX 1.x content skipped.
+    skipped: usize,
+    /// STIX 1.x constructs that could not be converted, by kind.
+    unconverted: BTreeMap<String, usize>,
+    /// Blocks that could not be read, with the reason.
+    failed_blocks: Vec<(String, String)>,
+    /// Outcome of writing the objects.
+    objects: PushSummary,
+}
+
+impl MigrateSummary {
+    /// Print the summary, failing when any block or object failed.
+    fn report(
+        &self,
+        source: &str,
+        target: &str,
+        output: &Output,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if self.skipped > 0 && !cfg!(feature = "bridge") {
+            tracing::warn!("STIX 1.x content is only converted with the bridge feature");
+        }
+        output.record(&MigrateRecord {
+            source: source.to_string(),
+            blocks: self.blocks,
+            passed_through: self.passed_through,
+            converted: self.converted,
+            skipped: self.skipped,
+            failed: self.failed_blocks.len(),
+            failures: self.failed_blocks.iter().cloned().collect(),
+            unconverted: self.unconverted.clone(),
+            objects: self.objects.record(target),
+        })?;
+
+        match self.failed_blocks.len() {
+            0 => self.objects.result(),
+            failed => Err(format!(
+                "{failed} of {} content blocks could not be migrated",
+                self.blocks
+            )
+            .into()),
+        }
+    }
+}
+
+/// Summary of `content migrate`.
+#[derive(Debug, Serialize)]
+struct MigrateRecord {
+    /// TAXII 1.x collection name.
+    source: String,
+    blocks: usize,
+    passed_through: usize,
+    converted: usize,
+    skipped: usize,
+    failed: usize,
+    /// Reason each failed block was not migrated, by block.
+    failures: BTreeMap<String, String>,
+    /// STIX 1.x constructs that could not be converted, by kind.
+    unconverted: BTreeMap<String, usize>,
+    /// Outcome of writing the objects.
+    objects: PushRecord,
+}
+
+impl Record for MigrateRecord {
+    const COLUMNS: &'static [(&'static str, &'static str)] = &[
+        ("source", "Source"),
+        ("blocks", "Blocks"),
+        ("passed_through", "STIX 2.x"),
+        ("converted", "Converted"),
+        ("skipped", "Skipped"),
+        ("failed", "Failed"),
+        ("failures", "Failures"),
+        ("unconverted", "Not Converted"),
+        ("objects", "Objects"),
+    ];
+}
+
+/// Migrate the content blocks of a TAXII 1.x collection into a TAXII 2.x
+/// collection.
+pub async fn migrate_content(
+    pool: TaxiiPool,
+    args: &MigrateContentArgs,
+    output: &Output,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let target = Collection::find(&pool, args.to_collection)
+        .await?
+        .ok_or_else(|| format!("Collection '{}' not found", args.to_collection))?;
+    let summary = migrate_blocks(&pool, args, &target.api_root_id.to_string()).await?;
+    summary.report(&args.from_collection, &target.title, output)
+}
+
+/// Read the blocks of the source collection and write their objects in
+/// batches.
+async fn migrate_blocks(
+    pool: &TaxiiPool,
+    args: &MigrateContentArgs,
+    api_root_id: &str,
+) -> Result<MigrateSummary, Box<dyn std::error::Error>> {
+    let taxii1 = DbTaxii1Repository::new(pool.clone());
+    let taxii2 = DbTaxii2Repository::new(pool.clone());
+    let collection_id = args.to_collection.to_string();
+    let source_id = taxii1
+        .get_collection(&args.from_collection, None)
+        .await?
+        .and_then(|collection| collection.id)
+        .ok_or_else(|| format!("TAXII 1.x collection '{}' not found", args.from_collection))?;
+
+    let mut summary = MigrateSummary::default();
+    let mut pending: Vec<Value> = Vec::new();
+    let mut blocks = taxii1.stream_content_blocks(Some(source_id), None, None, None);
+    while let Some(block) = blocks.next().await {
+        let block = block?;
+        summary.blocks += 1;
+
+        match read_block(&block, source_id, args.skip_xml) {
+            Ok(BlockContent::Stix2(objects)) => {
+                summary.passed_through += 1;
+                pending.extend(objects);
+            }
+            Ok(BlockContent::Stix1 {
+                objects,
+                unconverted,
+            }) => {
+                summary.converted += 1;
+                for (construct, count) in unconverted {
+                    *summary.unconverted.entry(construct).or_default() += count;
+                }
+                pending.extend(objects);
+            }
+            Ok(BlockContent::Skipped) => summary.skipped += 1,
+            Err(reason) => summary.failed_blocks.push((block_label(&block), reason)),
+        }
+
+        if pending.len() >= args.batch_size.get() {
+            let batch = std::mem::take(&mut pending);
+            let written = push_objects(
+                &taxii2,
+                api_root_id,
+                &collection_id,
+                batch,
+                args.validate,
+                args.batch_size.get(),
+            )
+            .await?;
+            summary.objects.merge(written);
+        }
+    }
+
+    if !pending.is_empty() {
+        let written = push_objects(
+            &taxii2,
+            api_root_id,
+            &collection_id,
+            pending,
+            args.validate,
+            args.batch_size.get(),
+        )
+        .await?;
+        summary.objects.merge(written);
+    }
+
+    Ok(summary)
+}
+
+/// Read the STIX objects of a content block.
+///
+/// Blocks with a STIX XML binding are STIX 1.x packages. Any other block
+/// must hold STIX 2.x JSON: a bundle, an envelope or a single object.
+fn read_block(
+    block: &ContentBlockEntity,
+    collection_id: i32,
+    skip_xml: bool,
+) -> Result<BlockContent, String> {
+    let binding = block
+        .content_binding
+        .as_ref()
+        .map(|binding| binding.binding.as_str());
+
+    if binding.is_some_and(is_stix_xml_binding) {
+        if skip_xml {
+            return Ok(BlockContent::Skipped);
+        }
+        return convert_block(block, collection_id);
+    }
+
+    let not_stix = || match binding {
+        Some(binding) => format!("not STIX content (content binding {binding})"),
+        None => "not STIX content (no content binding)".to_string(),
+    };
+    let mut document: Value = serde_json::from_slice(&block.content).map_err(|_| not_stix())?;
+    match document.get_mut("objects").map(Value::take) {
+        Some(Value::Array(objects)) => Ok(BlockContent::Stix2(objects)),
+        Some(_) => Err("objects is not an array".to_string()),
+        None if document["type"].is_string() && document["id"].is_string() => {
+            Ok(BlockContent::Stix2(vec![document]))
+        }
+        None => Err(not_stix()),
+    }
+}
+
+/// Convert the STIX 1.x package of a content block.
+#[cfg(feature = "bridge")]
+fn convert_block(block: &ContentBlockEntity, collection_id: i32) -> Result<BlockContent, String> {
+    use taxii_1x::bridge::{Provenance, convert_package};
+
+    let provenance = Provenance {
+        collection_id,
+        content_block_id: block.id,
+    };
+    convert_package(&block.content, &provenance)
+        .map(|conversion| BlockContent::Stix1 {
+            objects: conversion.objects,
+            unconverted: conversion.unconverted,
+        })
+        .map_err(|e| e.to_string())
+}
+
+/// STIX 1.x content is skipped without the bridge feature.
+#[cfg(not(feature = "bridge"))]
+fn convert_block(_block: &ContentBlockEntity, _collection_id: i32) -> Result<BlockContent, String> {
+    Ok(BlockContent::Skipped)
+}
+
+/// Label of a content block in the report.
+fn block_label(block: &ContentBlockEntity) -> String {
+    block.id.map_or_else(
+        || "content block".to_string(),
+        |id| format!("content block {id}"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::PgPool;
+    use taxii_core::{CollectionEntity, ContentBindingEntity};
+    use taxii_db::Taxii2Repository;
+
+    type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+    const BUNDLE: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/bundle_with_invalid_object.json"
+    );
+    const INVALID_ID: &str = "indicator--c1f5a8b2-3d4e-4f60-8a7b-9c0d1e2f3a4b";
+
+    const PACKAGE: &str = r#"<stix:STIX_Package xmlns:stix="http://stix.mitre.org/stix-1"
+        xmlns:cybox="http://cybox.mitre.org/cybox-2"
+        xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" id="example:Package-1">
+      <stix:Indicators>
+        <stix:Indicator id="example:indicator-1" timestamp="2015-05-15T09:00:00Z">
+          <indicator:Observable xmlns:indicator="http://stix.mitre.org/Indicator-2">
+            <cybox:Object>
+              <cybox:Properties xsi:type="AddressObj:AddressObjectType">
+                <AddressObj:Address_Value>10.0.0.1</AddressObj:Address_Value>
+              </cybox:Properties>
+            </cybox:Object>
+          </indicator:Observable>
+        </stix:Indicator>
+      </stix:Indicators>
+      <stix:TTPs><stix:TTP id="example:ttp-1"/></stix:TTPs>
+    </stix:STIX_Package>"#;
+
+    fn args(to_collection: Uuid, skip_xml: bool) -> MigrateContentArgs {
+        MigrateContentArgs {
+            from_collection: "legacy".to_string(),
+            to_collection,
+            skip_xml,
+            validate: true,
+            batch_size: NonZeroUsize::MIN.saturating_add(1),
+        }
+    }
+
+    /// A block carrying a STIX 2.1 bundle is passed through, a STIX 1.x
+    /// package converted and other content reported; migrating again finds
+    /// duplicates.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_migrate_content(pool: PgPool) -> TestResult {
+        let pool = TaxiiPool::new(pool);
+        let taxii1 = DbTaxii1Repository::new(pool.clone());
+        let taxii2 = DbTaxii2Repository::new(pool.clone());
+
+        let source = taxii1
+            .create_collection(&CollectionEntity::new("legacy"))
+            .await?;
+        let source_ids = source.id.map(|id| vec![id]);
+        let bundle = std::fs::read(BUNDLE)?;
+        for content in [
+            ContentBlockEntity::new(
+                bundle,
+                Some(ContentBindingEntity::new("urn:stix.mitre.org:json:2.1")),
+            ),
+            ContentBlockEntity::new(
+                PACKAGE.as_bytes(),
+                Some(ContentBindingEntity::new("urn:stix.mitre.org:xml:1.1.1")),
+            ),
+            ContentBlockEntity::new(
+                b"plain text",
+                Some(ContentBindingEntity::new("urn:example:text")),
+            ),
+        ] {
+            taxii1
+                .create_content_block(&content, source_ids.as_deref(), None)
+                .await?;
+        }
+
+        let api_root = taxii2.add_api_root("Root", None, true, true, None).await?;
+        let target = taxii2
+            .add_collection(&api_root.id, "Feed", None, None, true, true, None)
+            .await?;
+        let args = args(Uuid::parse_str(&target.id)?, false);
+
+        // Three valid bundle objects, plus the indicator converted with the
+        // bridge feature
+        let (converted, stored) = if cfg!(feature = "bridge") {
+            (1, 4)
+        } else {
+            (0, 3)
+        };
+
+        let summary = migrate_blocks(&pool, &args, &api_root.id).await?;
+        assert_eq!(summary.blocks, 3);
+        assert_eq!(summary.passed_through, 1);
+        assert_eq!(summary.converted, converted);
+        assert_eq!(summary.skipped, 1 - converted);
+        assert_eq!(
+            summary.unconverted.get("TTP").copied().unwrap_or(0),
+            converted
+        );
+        assert_eq!(summary.failed_blocks.len(), 1);
+        assert!(summary.failed_blocks[0].1.contains("urn:example:text"));
+        assert_eq!(summary.objects.inserted, stored);
+        assert_eq!(summary.objects.failed.len(), 1);
+        assert_eq!(summary.objects.failed[0].0, INVALID_ID);
+        assert!(
+            summary
+                .report("legacy", "Feed", &Output::default())
+                .is_err()
+        );
+
+        let manifest = taxii2.get_manifest(&target.id, &Default::default()).await?;
+        assert_eq!(manifest.items.len(), stored);
+
+        let summary = migrate_blocks(&pool, &args, &api_root.id).await?;
+        assert_eq!(summary.objects.inserted, 0);
+        assert_eq!(summary.objects.duplicate, stored);
+
+        // Skipping STIX 1.x content
+        let args = MigrateContentArgs {
+            skip_xml: true,
+            ..args
+        };
+        let summary = migrate_blocks(&pool, &args, &api_root.id).await?;
+        assert_eq!(summary.converted, 0);
+        assert_eq!(summary.skipped, 1);
+        assert_eq!(summary.objects.duplicate, 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_block() -> TestResult {
+        let object =
+            br#"{"type": "identity", "id": "identity--f431f809-377b-45e0-aa1c-6a4751cae5ff"}"#;
+        let content = read_block(&ContentBlockEntity::new(object, None), 1, false)?;
+        assert!(matches!(content, BlockContent::Stix2(objects) if objects.len() == 1));
+
+        let envelope = br#"{"more": false, "objects": []}"#;
+        let content = read_block(
+            &ContentBlockEntity::new(
+                envelope,
+                Some(ContentBindingEntity::new("urn:example:json")),
+            ),
+            1,
+            false,
+        )?;
+        assert!(matches!(content, BlockContent::Stix2(objects) if objects.is_empty()));
+
+        assert!(
+            read_block(
+                &ContentBlockEntity::new(b"{\"objects\": 1}", None),
+                1,
+                false
+            )
+            .is_err()
+        );
+        assert!(read_block(&ContentBlockEntity::new(b"{}", None), 1, false).is_err());
+        #[cfg(feature = "bridge")]
+        assert!(
+            read_block(
+                &ContentBlockEntity::new(
+                    b"<Other/>",
+                    Some(ContentBindingEntity::new("urn:stix.mitre.org:xml:1.2"))
+                ),
+                1,
+                false
+            )
+            .is_err()
+        );
+        assert!(matches!(
+            read_block(
+                &ContentBlockEntity::new(
+                    b"<Other/>",
+                    Some(ContentBindingEntity::new("urn:stix.mitre.org:xml:1.2"))
+                ),
+                1,
+                true
+            )?,
+            BlockContent::Skipped
+        ));
+        Ok(())
+    }
+}
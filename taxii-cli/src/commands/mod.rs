@@ -2,6 +2,14 @@
 
 pub mod account;
 pub mod activity;
+pub mod audit;
+pub mod export;
 pub mod migrate;
+pub mod migrate_content;
+pub mod output;
+pub mod permissions;
 pub mod persistence;
+pub mod push;
 pub mod taxii2;
+pub mod taxii2_config;
+pub mod validate;
@@ -0,0 +1,488 @@
+//! Output of command results as tables, JSON or CSV.
+//!
+//! Commands collect their results in typed records and hand them to
+//! [`Output`], which renders them in the format chosen with the global
+//! `--output` flag. JSON field names are part of the CLI interface and are
+//! documented in `docs/src/cli.md`: change them only together with it.
+
+use std::io::{self, Write};
+
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// Maximum width of a table cell; longer values are truncated.
+const MAX_CELL_WIDTH: usize = 60;
+
+/// Format of command results.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Aligned columns for reading.
+    #[default]
+    Table,
+    /// JSON on stdout; errors as JSON objects on stderr.
+    Json,
+    /// Comma-separated values with a header line.
+    Csv,
+}
+
+/// A record of command results.
+pub trait Record: Serialize {
+    /// Columns of the record, in order: JSON field name and table header.
+    const COLUMNS: &'static [(&'static str, &'static str)];
+}
+
+/// Format and column selection of command results.
+#[derive(Debug, Clone, Default)]
+pub struct Output {
+    format: OutputFormat,
+    columns: Option<Vec<String>>,
+}
+
+type OutputResult = Result<(), Box<dyn std::error::Error>>;
+
+/// Selected columns of a record type.
+type Columns = Vec<(&'static str, &'static str)>;
+
+impl Output {
+    /// Output in `format`, limited to `columns` (JSON field names) in tables
+    /// and CSV.
+    pub fn new(format: OutputFormat, columns: Option<Vec<String>>) -> Self {
+        Self { format, columns }
+    }
+
+    /// Format of the results.
+    pub fn format(&self) -> OutputFormat {
+        self.format
+    }
+
+    /// Whether results are printed as JSON.
+    pub fn is_json(&self) -> bool {
+        self.format == OutputFormat::Json
+    }
+
+    /// Print a list of records; a table shows `empty` when there are none.
+    pub fn list<T: Record>(&self, records: &[T], empty: &str) -> OutputResult {
+        self.write_list(&mut io::stdout().lock(), records, empty)
+    }
+
+    /// Print a page of records and the cursor of the next page.
+    pub fn page<T: Record>(&self, records: &[T], next: Option<&str>, empty: &str) -> OutputResult {
+        self.write_page(&mut io::stdout().lock(), records, next, empty)?;
+        if self.format == OutputFormat::Csv
+            && let Some(next) = next
+        {
+            eprintln!("More results available, continue with --next {next}");
+        }
+        Ok(())
+    }
+
+    /// Print a single record.
+    pub fn record<T: Record>(&self, record: &T) -> OutputResult {
+        self.write_record(&mut io::stdout().lock(), record)
+    }
+
+    /// Print a document that is not a record, such as a configuration file:
+    /// as JSON, or as `text` in the other formats.
+    pub fn document<T: Serialize>(&self, document: &T, text: &str) -> OutputResult {
+        self.write_document(&mut io::stdout().lock(), document, text)
+    }
+
+    /// Print an error; as a JSON object on stderr in JSON mode.
+    pub fn error(&self, error: &dyn std::error::Error) {
+        if self.is_json() {
+            eprintln!("{}", serde_json::json!({ "error": error.to_string() }));
+        } else {
+            tracing::error!("Error: {}", error);
+        }
+    }
+
+    /// Write a list of records: a JSON array, a table or CSV.
+    pub(crate) fn write_list<T: Record>(
+        &self,
+        out: &mut dyn Write,
+        records: &[T],
+        empty: &str,
+    ) -> OutputResult {
+        match self.format {
+            OutputFormat::Json => {
+                serde_json::to_writer_pretty(&mut *out, records)?;
+                writeln!(out)?;
+            }
+            OutputFormat::Table if records.is_empty() => writeln!(out, "{empty}")?,
+            OutputFormat::Table => write_table(out, &self.columns::<T>()?, &rows(records)?)?,
+            OutputFormat::Csv => write_csv(out, &self.columns::<T>()?, &rows(records)?)?,
+        }
+        Ok(())
+    }
+
+    /// Write a page of records: a JSON object with `items` and `next`, or
+    /// the list followed by the cursor in a table.
+    fn write_page<T: Record>(
+        &self,
+        out: &mut dyn Write,
+        records: &[T],
+        next: Option<&str>,
+        empty: &str,
+    ) -> OutputResult {
+        if self.is_json() {
+            let page = serde_json::json!({ "items": records, "next": next });
+            serde_json::to_writer_pretty(&mut *out, &page)?;
+            writeln!(out)?;
+            return Ok(());
+        }
+
+        self.write_list(out, records, empty)?;
+        if self.format == OutputFormat::Table
+            && let Some(next) = next
+        {
+            writeln!(out)?;
+            writeln!(out, "More results available, continue with --next {next}")?;
+        }
+        Ok(())
+    }
+
+    /// Write a single record: a JSON object, `Header: value` lines or CSV.
+    fn write_record<T: Record>(&self, out: &mut dyn Write, record: &T) -> OutputResult {
+        match self.format {
+            OutputFormat::Json => {
+                serde_json::to_writer_pretty(&mut *out, record)?;
+                writeln!(out)?;
+            }
+            OutputFormat::Table => {
+                let columns = self.columns::<T>()?;
+                let fields = fields(record)?;
+                let width = columns.iter().map(|(_, h)| h.chars().count()).max();
+                let width = width.unwrap_or(0) + 1;
+                for (key, header) in &columns {
+                    match fields.get(*key) {
+                        Some(Value::Object(map)) => {
+                            writeln!(out, "{header}:")?;
+                            write_nested(out, map)?;
+                        }
+                        value => {
+                            let label = format!("{header}:");
+                            writeln!(out, "{label:<width$} {}", table_cell(value))?;
+                        }
+                    }
+                }
+            }
+            OutputFormat::Csv => {
+                write_csv(out, &self.columns::<T>()?, &[fields(record)?])?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write a document: JSON, or its text as it is.
+    fn write_document<T: Serialize>(
+        &self,
+        out: &mut dyn Write,
+        document: &T,
+        text: &str,
+    ) -> OutputResult {
+        if self.is_json() {
+            serde_json::to_writer_pretty(&mut *out, document)?;
+            writeln!(out)?;
+        } else {
+            write!(out, "{text}")?;
+        }
+        Ok(())
+    }
+
+    /// Columns to show for records of type `T`.
+    fn columns<T: Record>(&self) -> Result<Columns, String> {
+        let Some(selected) = &self.columns else {
+            return Ok(T::COLUMNS.to_vec());
+        };
+
+        selected
+            .iter()
+            .map(|name| {
+                T::COLUMNS
+                    .iter()
+                    .find(|(key, _)| key == name)
+                    .copied()
+                    .ok_or_else(|| {
+                        let available: Vec<_> = T::COLUMNS.iter().map(|(key, _)| *key).collect();
+                        format!(
+                            "unknown column '{name}' (available: {})",
+                            available.join(", ")
+                        )
+                    })
+            })
+            .collect()
+    }
+}
+
+/// Fields of a serialized record.
+fn fields<T: Serialize>(record: &T) -> Result<Map<String, Value>, String> {
+    match serde_json::to_value(record) {
+        Ok(Value::Object(map)) => Ok(map),
+        Ok(_) => Err("record is not a JSON object".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Fields of each serialized record.
+fn rows<T: Serialize>(records: &[T]) -> Result<Vec<Map<String, Value>>, String> {
+    records.iter().map(fields).collect()
+}
+
+/// Write rows as a table with a header and aligned columns.
+fn write_table(
+    out: &mut dyn Write,
+    columns: &Columns,
+    rows: &[Map<String, Value>],
+) -> io::Result<()> {
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| {
+            columns
+                .iter()
+                .map(|(key, _)| truncate(&table_cell(row.get(*key)), MAX_CELL_WIDTH))
+                .collect()
+        })
+        .collect();
+
+    let widths: Vec<usize> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, (_, header))| {
+            cells
+                .iter()
+                .map(|row| row[i].chars().count())
+                .chain(std::iter::once(header.chars().count()))
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let headers: Vec<String> = columns.iter().map(|(_, h)| h.to_string()).collect();
+    write_table_line(out, &headers, &widths)?;
+    writeln!(
+        out,
+        "{}",
+        "-".repeat(widths.iter().sum::<usize>() + 2 * widths.len().saturating_sub(1))
+    )?;
+    for row in &cells {
+        write_table_line(out, row, &widths)?;
+    }
+    Ok(())
+}
+
+/// Write one line of a table, without trailing spaces.
+fn write_table_line(out: &mut dyn Write, cells: &[String], widths: &[usize]) -> io::Result<()> {
+    let padded: Vec<String> = cells
+        .iter()
+        .zip(widths)
+        .map(|(cell, width)| format!("{cell:<width$}"))
+        .collect();
+    writeln!(out, "{}", padded.join("  ").trim_end())
+}
+
+/// Write the entries of a nested object as indented lines.
+fn write_nested(out: &mut dyn Write, map: &Map<String, Value>) -> io::Result<()> {
+    let width = map.keys().map(|k| k.chars().count()).max().unwrap_or(0);
+    for (key, value) in map {
+        writeln!(out, "  {key:<width$}  {}", table_cell(Some(value)))?;
+    }
+    Ok(())
+}
+
+/// Text of a value in a table.
+fn table_cell(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => "-".to_string(),
+        Some(Value::Bool(true)) => "Yes".to_string(),
+        Some(Value::Bool(false)) => "No".to_string(),
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Number(n)) => n.to_string(),
+        Some(Value::Array(items)) => {
+            let items: Vec<String> = items.iter().map(|v| table_cell(Some(v))).collect();
+            items.join(",")
+        }
+        Some(Value::Object(map)) => {
+            let entries: Vec<String> = map
+                .iter()
+                .map(|(k, v)| format!("{k}={}", table_cell(Some(v))))
+                .collect();
+            entries.join("; ")
+        }
+    }
+}
+
+/// Write rows as CSV with a header line of JSON field names.
+fn write_csv(
+    out: &mut dyn Write,
+    columns: &Columns,
+    rows: &[Map<String, Value>],
+) -> io::Result<()> {
+    let header: Vec<String> = columns.iter().map(|(key, _)| csv_field(key)).collect();
+    writeln!(out, "{}", header.join(","))?;
+    for row in rows {
+        let fields: Vec<String> = columns
+            .iter()
+            .map(|(key, _)| csv_field(&csv_cell(row.get(*key))))
+            .collect();
+        writeln!(out, "{}", fields.join(","))?;
+    }
+    Ok(())
+}
+
+/// Text of a value in CSV: nested values are compact JSON.
+fn csv_cell(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(s)) => s.clone(),
+        Some(value) => value.to_string(),
+    }
+}
+
+/// Quote a CSV field if needed (RFC 4180).
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Truncate a string to a maximum length.
+fn truncate(s: &str, max_len: usize) -> String {
+    if s.chars().count() <= max_len {
+        s.to_string()
+    } else {
+        let kept: String = s.chars().take(max_len.saturating_sub(3)).collect();
+        format!("{kept}...")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+    #[derive(Serialize)]
+    struct Sample {
+        id: u32,
+        title: String,
+        public: bool,
+        alias: Option<String>,
+        counts: BTreeMap<String, u64>,
+    }
+
+    impl Record for Sample {
+        const COLUMNS: &'static [(&'static str, &'static str)] = &[
+            ("id", "ID"),
+            ("title", "Title"),
+            ("public", "Public"),
+            ("alias", "Alias"),
+            ("counts", "Counts"),
+        ];
+    }
+
+    fn samples() -> Vec<Sample> {
+        vec![
+            Sample {
+                id: 1,
+                title: "Feed, \"daily\"".to_string(),
+                public: true,
+                alias: Some("feed".to_string()),
+                counts: BTreeMap::from([("indicator".to_string(), 3)]),
+            },
+            Sample {
+                id: 22,
+                title: "Intel".to_string(),
+                public: false,
+                alias: None,
+                counts: BTreeMap::new(),
+            },
+        ]
+    }
+
+    fn render(
+        output: &Output,
+        f: impl FnOnce(&Output, &mut Vec<u8>) -> OutputResult,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let mut buf = Vec::new();
+        f(output, &mut buf)?;
+        Ok(String::from_utf8(buf)?)
+    }
+
+    #[test]
+    fn test_csv_field() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn test_columns() -> TestResult {
+        let output = Output::new(
+            OutputFormat::Table,
+            Some(vec!["title".to_string(), "id".to_string()]),
+        );
+        assert_eq!(
+            output.columns::<Sample>()?,
+            vec![("title", "Title"), ("id", "ID")]
+        );
+
+        let output = Output::new(OutputFormat::Csv, Some(vec!["name".to_string()]));
+        assert_eq!(
+            output.columns::<Sample>().err().as_deref(),
+            Some("unknown column 'name' (available: id, title, public, alias, counts)")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_table_empty() -> TestResult {
+        let output = Output::new(OutputFormat::Table, None);
+        let text = render(&output, |o, buf| {
+            o.write_list::<Sample>(buf, &[], "No samples found.")
+        })?;
+        assert_eq!(text, "No samples found.\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_table() -> TestResult {
+        let output = Output::new(OutputFormat::Table, None);
+        let sample = &samples()[0];
+        let text = render(&output, |o, buf| o.write_record(buf, sample))?;
+        assert_eq!(
+            text,
+            "ID:     1\n\
+             Title:  Feed, \"daily\"\n\
+             Public: Yes\n\
+             Alias:  feed\n\
+             Counts:\n\
+             \x20 indicator  3\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_document() -> TestResult {
+        let sample = &samples()[1];
+
+        let output = Output::new(OutputFormat::Json, None);
+        let text = render(&output, |o, buf| o.write_document(buf, sample, "ignored"))?;
+        assert_eq!(
+            serde_json::from_str::<Value>(&text)?,
+            serde_json::json!({
+                "id": 22, "title": "Intel", "public": false, "alias": null, "counts": {}
+            })
+        );
+
+        for format in [OutputFormat::Table, OutputFormat::Csv] {
+            let output = Output::new(format, None);
+            let text = render(&output, |o, buf| o.write_document(buf, sample, "id: 22\n"))?;
+            assert_eq!(text, "id: 22\n");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,560 @@
+//! Collection permissions of accounts.
+//!
+//! Permissions are keyed by collection name for TAXII 1.x (`"read"` or
+//! `"modify"`) and by collection UUID for TAXII 2.x (a list of `"read"` and
+//! `"write"`). These commands resolve the collection, write the entry in the
+//! right format and save the account, so the map is never edited by hand.
+
+use std::collections::HashMap;
+
+use clap::ValueEnum;
+use serde::Serialize;
+use taxii_auth::AuthAPI;
+use taxii_core::{Account as AccountEntity, PermissionValue};
+use taxii_db::{ApiRoot, Collection, DataCollection, DatabaseResult, TaxiiPool};
+use uuid::Uuid;
+
+use super::output::{Output, Record};
+
+/// Maximum number of close matches suggested for an unknown collection.
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Access level granted or revoked on a collection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Access {
+    /// Read objects or content.
+    Read,
+    /// Add objects or content. For TAXII 1.x this is `modify`, which
+    /// includes read.
+    Write,
+    /// Both read and write.
+    ReadWrite,
+}
+
+impl Access {
+    /// Whether this level includes read access.
+    fn read(self) -> bool {
+        matches!(self, Access::Read | Access::ReadWrite)
+    }
+
+    /// Whether this level includes write access.
+    fn write(self) -> bool {
+        matches!(self, Access::Write | Access::ReadWrite)
+    }
+}
+
+/// Collections that permissions can refer to.
+pub(crate) struct Collections {
+    taxii1: Vec<DataCollection>,
+    taxii2: Vec<Collection>,
+}
+
+impl Collections {
+    /// Load all TAXII 1.x and 2.x collections.
+    pub(crate) async fn load(pool: &TaxiiPool) -> DatabaseResult<Self> {
+        let taxii1 = DataCollection::find_all(pool).await?;
+        let mut taxii2 = Vec::new();
+        for api_root in ApiRoot::find_all(pool).await? {
+            taxii2.extend(Collection::find_by_api_root(pool, api_root.id).await?);
+        }
+        Ok(Self { taxii1, taxii2 })
+    }
+
+    /// Resolve a collection to its permission key.
+    ///
+    /// TAXII 1.x collections are given by name. TAXII 2.x collections are
+    /// given by UUID, or by a title or alias matching exactly one
+    /// collection. Unknown collections are rejected with close matches.
+    fn resolve(&self, value: &str, taxii1: bool) -> Result<String, String> {
+        if taxii1 {
+            if self.taxii1.iter().any(|c| c.name == value) {
+                return Ok(value.to_string());
+            }
+            let names = self.taxii1.iter().map(|c| c.name.as_str());
+            return Err(unknown_collection("TAXII 1.x", value, names));
+        }
+
+        if let Ok(id) = Uuid::parse_str(value)
+            && self.taxii2.iter().any(|c| c.id == id)
+        {
+            return Ok(id.to_string());
+        }
+
+        let matches: Vec<_> = self
+            .taxii2
+            .iter()
+            .filter(|c| c.title == value || c.alias.as_deref() == Some(value))
+            .collect();
+        match matches.as_slice() {
+            [collection] => Ok(collection.id.to_string()),
+            [] => {
+                let names = self
+                    .taxii2
+                    .iter()
+                    .flat_map(|c| std::iter::once(c.title.as_str()).chain(c.alias.as_deref()));
+                Err(unknown_collection("TAXII 2.x", value, names))
+            }
+            _ => {
+                let ids: Vec<_> = matches.iter().map(|c| c.id.to_string()).collect();
+                Err(format!(
+                    "TAXII 2.x collection '{value}' is ambiguous, use one of: {}",
+                    ids.join(", ")
+                ))
+            }
+        }
+    }
+
+    /// Title of the collection a permission entry refers to.
+    fn title(&self, key: &str, permission: &PermissionValue) -> Option<&str> {
+        match permission {
+            PermissionValue::Taxii1(_) => self
+                .taxii1
+                .iter()
+                .find(|c| c.name == key)
+                .map(|c| c.name.as_str()),
+            PermissionValue::Taxii2(_) => {
+                let id = Uuid::parse_str(key).ok()?;
+                self.taxii2
+                    .iter()
+                    .find(|c| c.id == id)
+                    .map(|c| c.title.as_str())
+            }
+        }
+    }
+}
+
+/// Error for an unknown collection, suggesting close matches among `names`.
+fn unknown_collection<'a>(kind: &str, value: &str, names: impl Iterator<Item = &'a str>) -> String {
+    let suggestions = suggestions(value, names);
+    if suggestions.is_empty() {
+        format!("Unknown {kind} collection '{value}'")
+    } else {
+        let quoted: Vec<_> = suggestions.iter().map(|s| format!("'{s}'")).collect();
+        format!(
+            "Unknown {kind} collection '{value}' (did you mean {}?)",
+            quoted.join(", ")
+        )
+    }
+}
+
+/// Names close to `value`, closest first.
+///
+/// A name is close when it is within a few edits of `value` or contains it,
+/// ignoring case.
+fn suggestions<'a>(value: &str, names: impl Iterator<Item = &'a str>) -> Vec<&'a str> {
+    let value = value.to_lowercase();
+    let max_distance = (value.chars().count() / 3).max(1);
+
+    let mut close: Vec<_> = names
+        .filter_map(|name| {
+            let lower = name.to_lowercase();
+            let distance = edit_distance(&value, &lower);
+            let contains = value.chars().count() >= 3 && lower.contains(&value);
+            (distance <= max_distance || contains).then_some((distance, name))
+        })
+        .collect();
+    close.sort();
+    close.dedup();
+    close
+        .into_iter()
+        .map(|(_, name)| name)
+        .take(MAX_SUGGESTIONS)
+        .collect()
+}
+
+/// Levenshtein distance between two strings, in characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, ca) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
+
+/// Permission entry for the given rights, in TAXII 1.x or 2.x format.
+///
+/// Returns `None` when no rights remain. TAXII 1.x has no write-only level:
+/// write needs read, and is expressed as `modify`.
+pub(crate) fn permission_value(read: bool, write: bool, taxii1: bool) -> Option<PermissionValue> {
+    if taxii1 {
+        return match (read, write) {
+            (true, true) => Some(PermissionValue::Taxii1("modify".to_string())),
+            (true, false) => Some(PermissionValue::Taxii1("read".to_string())),
+            (false, _) => None,
+        };
+    }
+
+    let list = taxii2_rights(read, write);
+    (!list.is_empty()).then_some(PermissionValue::Taxii2(list))
+}
+
+/// TAXII 2.x permission list for the given rights.
+fn taxii2_rights(read: bool, write: bool) -> Vec<String> {
+    [(read, "read"), (write, "write")]
+        .into_iter()
+        .filter(|(granted, _)| *granted)
+        .map(|(_, name)| name.to_string())
+        .collect()
+}
+
+/// Add `access` to an existing permission entry.
+///
+/// Granting never removes rights the entry already has.
+fn granted(existing: Option<&PermissionValue>, access: Access, taxii1: bool) -> PermissionValue {
+    let read = existing.is_some_and(PermissionValue::can_read) || access.read();
+    let write = existing.is_some_and(PermissionValue::can_write) || access.write();
+
+    if taxii1 {
+        // TAXII 1.x write access is modify, which includes read
+        let level = if write { "modify" } else { "read" };
+        PermissionValue::Taxii1(level.to_string())
+    } else {
+        PermissionValue::Taxii2(taxii2_rights(read, write))
+    }
+}
+
+/// Remove `access` from a permission entry, or all of it with `None`.
+///
+/// Returns `None` when the entry has no rights left.
+fn revoked(existing: &PermissionValue, access: Option<Access>) -> Option<PermissionValue> {
+    let access = access?;
+    let read = existing.can_read() && !access.read();
+    let write = existing.can_write() && !access.write();
+    let taxii1 = matches!(existing, PermissionValue::Taxii1(_));
+
+    permission_value(read, write, taxii1)
+}
+
+/// Label of the access a permission entry grants.
+fn access_label(permission: &PermissionValue) -> String {
+    match permission {
+        PermissionValue::Taxii1(s) => s.clone(),
+        PermissionValue::Taxii2(list) => list.join("-"),
+    }
+}
+
+/// Load an account by username.
+async fn load_account(
+    auth: &AuthAPI,
+    username: &str,
+) -> Result<AccountEntity, Box<dyn std::error::Error>> {
+    auth.get_accounts()
+        .await?
+        .into_iter()
+        .find(|account| account.username == username)
+        .ok_or_else(|| format!("Account '{username}' not found").into())
+}
+
+/// Save the permissions of an account after validating them.
+async fn save_permissions(
+    auth: &AuthAPI,
+    account: &AccountEntity,
+) -> Result<AccountEntity, Box<dyn std::error::Error>> {
+    taxii_db::validate_permissions(&account.permissions)?;
+    Ok(auth.update_account(account, None).await?)
+}
+
+/// Grant `access` on a collection to an account.
+pub(crate) async fn grant(
+    auth: &AuthAPI,
+    collections: &Collections,
+    username: &str,
+    collection: &str,
+    access: Access,
+    taxii1: bool,
+) -> Result<AccountEntity, Box<dyn std::error::Error>> {
+    let mut account = load_account(auth, username).await?;
+    let key = collections.resolve(collection, taxii1)?;
+
+    let permission = granted(account.permissions.get(&key), access, taxii1);
+    account.permissions.insert(key, permission);
+
+    save_permissions(auth, &account).await
+}
+
+/// Revoke `access` on a collection from an account, or all access with
+/// `None`.
+///
+/// An existing entry is matched by its key first, so permissions on
+/// collections that no longer exist can still be revoked.
+pub(crate) async fn revoke(
+    auth: &AuthAPI,
+    collections: &Collections,
+    username: &str,
+    collection: &str,
+    access: Option<Access>,
+    taxii1: bool,
+) -> Result<AccountEntity, Box<dyn std::error::Error>> {
+    let mut account = load_account(auth, username).await?;
+
+    let matches_version =
+        |permission: &PermissionValue| matches!(permission, PermissionValue::Taxii1(_)) == taxii1;
+    let key = match account.permissions.get(collection) {
+        Some(permission) if matches_version(permission) => collection.to_string(),
+        _ => collections.resolve(collection, taxii1)?,
+    };
+
+    let Some(existing) = account.permissions.remove(&key).filter(matches_version) else {
+        return Err(
+            format!("Account '{username}' has no permission on collection '{collection}'").into(),
+        );
+    };
+    if let Some(remaining) = revoked(&existing, access) {
+        account.permissions.insert(key, remaining);
+    }
+
+    save_permissions(auth, &account).await
+}
+
+/// A permission entry in `account grant`, `revoke` and `permissions`.
+#[derive(Debug, Serialize)]
+struct PermissionRecord {
+    /// `taxii1` or `taxii2`.
+    version: &'static str,
+    collection: String,
+    /// `None` for collections that no longer exist.
+    title: Option<String>,
+    access: String,
+}
+
+impl Record for PermissionRecord {
+    const COLUMNS: &'static [(&'static str, &'static str)] = &[
+        ("version", "Version"),
+        ("collection", "Collection"),
+        ("title", "Title"),
+        ("access", "Access"),
+    ];
+}
+
+/// Print the permissions of an account with collection titles.
+pub(crate) fn print_permissions(
+    account: &AccountEntity,
+    collections: &Collections,
+    output: &Output,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if account.is_admin {
+        tracing::info!(
+            "Account '{}' is an admin and can read and write all collections.",
+            account.username
+        );
+    }
+
+    let records: Vec<_> = sorted(&account.permissions)
+        .into_iter()
+        .map(|(key, permission)| PermissionRecord {
+            version: match permission {
+                PermissionValue::Taxii1(_) => "taxii1",
+                PermissionValue::Taxii2(_) => "taxii2",
+            },
+            collection: key.clone(),
+            title: collections.title(key, permission).map(String::from),
+            access: access_label(permission),
+        })
+        .collect();
+    output.list(
+        &records,
+        &format!(
+            "Account '{}' has no collection permissions.",
+            account.username
+        ),
+    )
+}
+
+/// Permission entries ordered by version, then key.
+fn sorted(permissions: &HashMap<String, PermissionValue>) -> Vec<(&String, &PermissionValue)> {
+    let mut entries: Vec<_> = permissions.iter().collect();
+    entries
+        .sort_by_key(|(key, permission)| (matches!(permission, PermissionValue::Taxii2(_)), *key));
+    entries
+}
+
+/// Show the permissions of an account.
+pub(crate) async fn show(
+    auth: &AuthAPI,
+    username: &str,
+    output: &Output,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let account = load_account(auth, username).await?;
+    let collections = Collections::load(auth.pool()).await?;
+    print_permissions(&account, &collections, output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::PgPool;
+    use taxii_core::CollectionEntity;
+    use taxii_db::{DbTaxii1Repository, DbTaxii2Repository, Taxii1Repository, Taxii2Repository};
+
+    type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+    fn taxii2(list: &[&str]) -> PermissionValue {
+        PermissionValue::Taxii2(list.iter().map(|p| p.to_string()).collect())
+    }
+
+    fn taxii1(s: &str) -> PermissionValue {
+        PermissionValue::Taxii1(s.to_string())
+    }
+
+    #[test]
+    fn test_granted() {
+        assert_eq!(granted(None, Access::Read, false), taxii2(&["read"]));
+        assert_eq!(granted(None, Access::Write, false), taxii2(&["write"]));
+        assert_eq!(
+            granted(Some(&taxii2(&["read"])), Access::ReadWrite, false),
+            taxii2(&["read", "write"])
+        );
+        assert_eq!(
+            granted(Some(&taxii2(&["write"])), Access::Read, false),
+            taxii2(&["read", "write"])
+        );
+
+        assert_eq!(granted(None, Access::Read, true), taxii1("read"));
+        assert_eq!(granted(None, Access::Write, true), taxii1("modify"));
+        assert_eq!(
+            granted(Some(&taxii1("modify")), Access::Read, true),
+            taxii1("modify")
+        );
+    }
+
+    #[test]
+    fn test_revoked() {
+        let read_write = taxii2(&["read", "write"]);
+        assert_eq!(revoked(&read_write, None), None);
+        assert_eq!(
+            revoked(&read_write, Some(Access::Write)),
+            Some(taxii2(&["read"]))
+        );
+        assert_eq!(revoked(&read_write, Some(Access::ReadWrite)), None);
+
+        assert_eq!(
+            revoked(&taxii1("modify"), Some(Access::Write)),
+            Some(taxii1("read"))
+        );
+        // Modify without read cannot be expressed
+        assert_eq!(revoked(&taxii1("modify"), Some(Access::Read)), None);
+    }
+
+    #[test]
+    fn test_suggestions() {
+        let names = ["Feed", "Feeds", "Malware Intel", "feed-archive", "Other"];
+        assert_eq!(
+            suggestions("feed", names.into_iter()),
+            vec!["Feed", "Feeds", "feed-archive"]
+        );
+        assert_eq!(suggestions("fed", names.into_iter()), vec!["Feed"]);
+        assert_eq!(
+            suggestions("malware", names.into_iter()),
+            vec!["Malware Intel"]
+        );
+        assert!(suggestions("zzz", names.into_iter()).is_empty());
+
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("même", "meme"), 1);
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_grant_and_revoke(pool: PgPool) -> TestResult {
+        let pool = TaxiiPool::new(pool);
+        let auth = AuthAPI::new(pool.clone(), "secret".to_string(), None)?;
+        let taxii1_repo = DbTaxii1Repository::new(pool.clone());
+        let taxii2_repo = DbTaxii2Repository::new(pool.clone());
+
+        taxii1_repo
+            .create_collection(&CollectionEntity::new("legacy"))
+            .await?;
+        let api_root = taxii2_repo
+            .add_api_root("Root", None, true, true, None)
+            .await?;
+        let feed = taxii2_repo
+            .add_collection(&api_root.id, "Feed", None, None, false, false, None)
+            .await?;
+        auth.create_account("alice", "correct horse battery", false)
+            .await?;
+        let collections = Collections::load(&pool).await?;
+
+        // TAXII 2.x collection by UUID
+        let account = grant(&auth, &collections, "alice", &feed.id, Access::Read, false).await?;
+        assert_eq!(account.permissions.get(&feed.id), Some(&taxii2(&["read"])));
+
+        // TAXII 1.x collection by name
+        let account = grant(&auth, &collections, "alice", "legacy", Access::Write, true).await?;
+        assert_eq!(account.permissions.get("legacy"), Some(&taxii1("modify")));
+
+        // Upgrading read to read-write, addressing the collection by title
+        let account = grant(
+            &auth,
+            &collections,
+            "alice",
+            "Feed",
+            Access::ReadWrite,
+            false,
+        )
+        .await?;
+        assert_eq!(
+            account.permissions.get(&feed.id),
+            Some(&taxii2(&["read", "write"]))
+        );
+        assert!(
+            account
+                .permissions
+                .get(&feed.id)
+                .is_some_and(PermissionValue::can_write)
+        );
+
+        // Unknown collections are rejected with close matches
+        let err = grant(&auth, &collections, "alice", "Fed", Access::Read, false)
+            .await
+            .err()
+            .ok_or("unknown collection accepted")?;
+        assert_eq!(
+            err.to_string(),
+            "Unknown TAXII 2.x collection 'Fed' (did you mean 'Feed'?)"
+        );
+        let err = grant(&auth, &collections, "alice", "legacyy", Access::Read, true)
+            .await
+            .err()
+            .ok_or("unknown collection accepted")?;
+        assert!(err.to_string().contains("did you mean 'legacy'?"));
+
+        // Revoking write keeps read, revoking the rest removes the entry
+        let account = revoke(
+            &auth,
+            &collections,
+            "alice",
+            &feed.id,
+            Some(Access::Write),
+            false,
+        )
+        .await?;
+        assert_eq!(account.permissions.get(&feed.id), Some(&taxii2(&["read"])));
+        revoke(&auth, &collections, "alice", &feed.id, None, false).await?;
+
+        // Revoking the last permission leaves an empty map
+        let account = revoke(&auth, &collections, "alice", "legacy", None, true).await?;
+        assert!(account.permissions.is_empty());
+        assert!(load_account(&auth, "alice").await?.permissions.is_empty());
+
+        let err = revoke(&auth, &collections, "alice", "legacy", None, true)
+            .await
+            .err()
+            .ok_or("revoked a missing permission")?;
+        assert_eq!(
+            err.to_string(),
+            "Account 'alice' has no permission on collection 'legacy'"
+        );
+
+        Ok(())
+    }
+}
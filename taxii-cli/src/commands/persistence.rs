@@ -2,16 +2,24 @@
 
 use chrono::{DateTime, Utc};
 use clap::Subcommand;
-use serde::Deserialize;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use taxii_auth::AuthAPI;
-use taxii_core::{CollectionEntity, ContentBindingEntity, PermissionValue, ServiceEntity};
+use taxii_core::{
+    CollectionBridgeEntity, CollectionEntity, ContentBindingEntity, PermissionValue, ServiceEntity,
+    delivery_status, signature_policy, validation_mode,
+};
 use taxii_db::{
-    DbTaxii1Repository, TAXII1_PERMISSIONS, TAXII2_PERMISSIONS, Taxii1Repository, TaxiiPool,
+    Collection, DbTaxii1Repository, DbTaxii2Repository, PaginationCursor, TAXII1_PERMISSIONS,
+    TAXII2_PERMISSIONS, Taxii1Repository, Taxii2QueryParams, Taxii2Repository, TaxiiPool,
     validate_collection_references, validate_permissions,
 };
 use tracing::{debug, info};
+use uuid::Uuid;
+
+use super::output::{Output, Record};
+use super::taxii2_config::{self, ApiRootConfig};
 
 /// Content block management actions.
 #[derive(Subcommand)]
@@ -34,6 +42,81 @@ pub enum ContentAction {
         #[arg(short = 'm', long, default_value = "false")]
         with_messages: bool,
     },
+
+    /// Delete expired poll result sets.
+    CleanupResultSets,
+
+    /// Revoke the objects added to a TAXII 2.x collection since a time.
+    ///
+    /// A revoked version of the latest version of each object is added,
+    /// keeping its markings and other properties. Objects that cannot be
+    /// revoked (observables, already revoked objects) are reported and left
+    /// untouched.
+    Revoke {
+        /// Collection ID.
+        #[arg(short, long)]
+        collection: String,
+
+        /// Revoke objects added after this time (ISO8601).
+        #[arg(long)]
+        since: String,
+    },
+
+    /// Push a STIX bundle into a TAXII 2.x collection.
+    ///
+    /// Objects are written to the database in batches, or posted to a
+    /// remote server with `--remote`. Exits with an error when any object
+    /// could not be pushed.
+    Push(super::push::PushArgs),
+
+    /// Migrate the content blocks of a TAXII 1.x collection into a TAXII
+    /// 2.x collection.
+    ///
+    /// STIX 2.x JSON content is passed through and STIX 1.x XML content
+    /// converted to STIX 2.1. Exits with an error when any content block or
+    /// object could not be migrated.
+    Migrate(super::migrate_content::MigrateContentArgs),
+}
+
+/// Objects read per page when revoking objects.
+const REVOKE_PAGE_SIZE: i64 = 500;
+
+/// Subscription management actions (TAXII 1.x).
+#[derive(Subcommand)]
+pub enum SubscriptionAction {
+    /// List the subscriptions of a collection management service with their
+    /// push delivery status.
+    List {
+        /// Collection management service ID.
+        #[arg(long)]
+        service: String,
+    },
+
+    /// List pushes of content blocks to subscriber inboxes.
+    Deliveries {
+        /// Delivery status: pending, delivered, failed or all.
+        #[arg(long, default_value = "failed")]
+        status: String,
+
+        /// Only list deliveries of this subscription.
+        #[arg(long)]
+        subscription: Option<String>,
+
+        /// Maximum number of deliveries to list, most recent first.
+        #[arg(long, default_value = "50")]
+        limit: i64,
+    },
+
+    /// Queue failed deliveries to be pushed again by the server.
+    Retry {
+        /// Delivery ID(s) to retry (can be specified multiple times).
+        #[arg(long)]
+        id: Vec<i32>,
+
+        /// Only retry deliveries of this subscription.
+        #[arg(long)]
+        subscription: Option<String>,
+    },
 }
 
 /// Action for collections not in config.
@@ -67,6 +150,9 @@ struct YamlConfig {
     collections: Vec<CollectionConfig>,
     #[serde(default)]
     accounts: Vec<AccountConfig>,
+    /// TAXII 2.x API roots with their collections.
+    #[serde(default)]
+    api_roots: Vec<ApiRootConfig>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -98,6 +184,31 @@ struct CollectionConfig {
     accept_all_content: bool,
     #[serde(rename = "type", default = "default_collection_type")]
     collection_type: String,
+    /// Days content blocks are kept (unset keeps them).
+    #[serde(default)]
+    retention_days: Option<i32>,
+    /// Store identical content blocks once.
+    #[serde(default)]
+    deduplicate_content: bool,
+    /// Validation of inbound STIX XML content: off, reject or flag.
+    #[serde(default = "validation_mode::default_mode")]
+    validation_mode: String,
+    /// Verification of content signatures: off, verify or require.
+    #[serde(default = "signature_policy::default_policy")]
+    signature_policy: String,
+    /// PEM files of the certificates trusted to sign content.
+    #[serde(default)]
+    trusted_certificates: Vec<String>,
+    /// TAXII 2.x collection receiving converted STIX content (unset unlinks).
+    #[serde(default)]
+    bridge: Option<BridgeConfig>,
+}
+
+/// TAXII 2.x collection a collection is linked to.
+#[derive(Debug, Deserialize)]
+struct BridgeConfig {
+    api_root_id: String,
+    collection_id: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -137,15 +248,31 @@ fn default_collection_type() -> String {
 }
 
 /// Handle sync command.
+///
+/// With `force_delete`, TAXII 2.x collections of the configured API roots
+/// that are not in the file are deleted with their objects.
 pub async fn handle_sync(
     pool: TaxiiPool,
     auth_secret: &str,
     config_path: &str,
+    force_delete: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
     // Load YAML configuration
     let yaml_content = fs::read_to_string(config_path)?;
     let config: YamlConfig = serde_yaml::from_str(&yaml_content)?;
 
+    // Collection grants may name accounts created by this sync
+    let mut usernames: std::collections::HashSet<&str> = config
+        .accounts
+        .iter()
+        .map(|a| a.username.as_str())
+        .collect();
+    let existing_accounts = taxii_db::Account::find_all(&pool).await?;
+    if !config.prune_accounts {
+        usernames.extend(existing_accounts.iter().map(|a| a.username.as_str()));
+    }
+    taxii2_config::validate_api_roots(&config.api_roots, &usernames)?;
+
     let persistence = DbTaxii1Repository::new(pool.clone());
 
     // Sync services
@@ -159,10 +286,18 @@ pub async fn handle_sync(
     )
     .await?;
 
+    // Sync TAXII 2.x API roots and collections, before the accounts that
+    // may refer to them
+    let taxii2 = DbTaxii2Repository::new(pool.clone());
+    let grants = taxii2_config::sync_api_roots(&taxii2, &config.api_roots, force_delete).await?;
+
     // Sync accounts
     sync_accounts(&pool, auth_secret, &config.accounts, config.prune_accounts).await?;
 
-    println!("Configuration synchronized successfully");
+    // Collection grants take precedence over account permissions
+    taxii2_config::apply_grants(&pool, auth_secret, &grants).await?;
+
+    info!("Configuration synchronized successfully");
     Ok(())
 }
 
@@ -215,6 +350,42 @@ async fn sync_services(
     Ok(())
 }
 
+/// Read the trusted certificate files of a collection into one PEM bundle.
+fn read_trusted_certificates(
+    coll_config: &CollectionConfig,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    if coll_config.trusted_certificates.is_empty() {
+        if coll_config.signature_policy != signature_policy::OFF {
+            return Err(format!(
+                "Collection '{}' has signature_policy '{}' but no trusted_certificates",
+                coll_config.name, coll_config.signature_policy
+            )
+            .into());
+        }
+        return Ok(None);
+    }
+
+    let mut bundle = String::new();
+    for path in &coll_config.trusted_certificates {
+        let pem = fs::read_to_string(path).map_err(|e| {
+            format!(
+                "Collection '{}': cannot read trusted certificate '{path}': {e}",
+                coll_config.name
+            )
+        })?;
+        if !pem.contains("-----BEGIN CERTIFICATE-----") {
+            return Err(format!(
+                "Collection '{}': '{path}' does not contain a PEM certificate",
+                coll_config.name
+            )
+            .into());
+        }
+        bundle.push_str(pem.trim_end());
+        bundle.push('\n');
+    }
+    Ok(Some(bundle))
+}
+
 /// Sync collections from configuration.
 async fn sync_collections(
     persistence: &DbTaxii1Repository,
@@ -234,6 +405,41 @@ async fn sync_collections(
     let mut updated = 0;
 
     for coll_config in collections {
+        if coll_config.retention_days.is_some_and(|days| days <= 0) {
+            return Err(format!(
+                "Collection '{}' has invalid retention_days, expected a positive number",
+                coll_config.name
+            )
+            .into());
+        }
+        if ![
+            validation_mode::OFF,
+            validation_mode::REJECT,
+            validation_mode::FLAG,
+        ]
+        .contains(&coll_config.validation_mode.as_str())
+        {
+            return Err(format!(
+                "Collection '{}' has invalid validation_mode '{}', expected off, reject or flag",
+                coll_config.name, coll_config.validation_mode
+            )
+            .into());
+        }
+        if ![
+            signature_policy::OFF,
+            signature_policy::VERIFY,
+            signature_policy::REQUIRE,
+        ]
+        .contains(&coll_config.signature_policy.as_str())
+        {
+            return Err(format!(
+                "Collection '{}' has invalid signature_policy '{}', expected off, verify or require",
+                coll_config.name, coll_config.signature_policy
+            )
+            .into());
+        }
+        let trusted_certificates = read_trusted_certificates(coll_config)?;
+
         let supported_content: Vec<ContentBindingEntity> = coll_config
             .supported_content
             .iter()
@@ -251,6 +457,11 @@ async fn sync_collections(
                 accept_all_content: coll_config.accept_all_content,
                 collection_type: coll_config.collection_type.clone(),
                 supported_content,
+                retention_days: coll_config.retention_days,
+                deduplicate_content: coll_config.deduplicate_content,
+                validation_mode: coll_config.validation_mode.clone(),
+                signature_policy: coll_config.signature_policy.clone(),
+                trusted_certificates,
             };
 
             persistence.update_collection(&entity).await?;
@@ -260,6 +471,7 @@ async fn sync_collections(
                 persistence
                     .set_collection_services(coll_id, &coll_config.service_ids)
                     .await?;
+                sync_collection_bridge(persistence, coll_id, coll_config.bridge.as_ref()).await?;
             }
 
             updated += 1;
@@ -275,6 +487,11 @@ async fn sync_collections(
                 accept_all_content: coll_config.accept_all_content,
                 collection_type: coll_config.collection_type.clone(),
                 supported_content,
+                retention_days: coll_config.retention_days,
+                deduplicate_content: coll_config.deduplicate_content,
+                validation_mode: coll_config.validation_mode.clone(),
+                signature_policy: coll_config.signature_policy.clone(),
+                trusted_certificates,
             };
 
             let created_coll = persistence.create_collection(&entity).await?;
@@ -284,6 +501,7 @@ async fn sync_collections(
                 persistence
                     .set_collection_services(coll_id, &coll_config.service_ids)
                     .await?;
+                sync_collection_bridge(persistence, coll_id, coll_config.bridge.as_ref()).await?;
             }
 
             created += 1;
@@ -310,6 +528,11 @@ async fn sync_collections(
                             accept_all_content: existing_coll.accept_all_content,
                             collection_type: existing_coll.collection_type.clone(),
                             supported_content: existing_coll.supported_content.clone(),
+                            retention_days: existing_coll.retention_days,
+                            deduplicate_content: existing_coll.deduplicate_content,
+                            validation_mode: existing_coll.validation_mode.clone(),
+                            signature_policy: existing_coll.signature_policy.clone(),
+                            trusted_certificates: existing_coll.trusted_certificates.clone(),
                         };
                         persistence.update_collection(&entity).await?;
                         disabled += 1;
@@ -332,6 +555,29 @@ async fn sync_collections(
     Ok(())
 }
 
+/// Link a collection to its configured TAXII 2.x collection, or unlink it.
+async fn sync_collection_bridge(
+    persistence: &DbTaxii1Repository,
+    coll_id: i32,
+    bridge: Option<&BridgeConfig>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match bridge {
+        Some(bridge) => {
+            persistence
+                .set_collection_bridge(&CollectionBridgeEntity {
+                    data_collection_id: coll_id,
+                    api_root_id: bridge.api_root_id.clone(),
+                    collection_id: bridge.collection_id.clone(),
+                })
+                .await?;
+        }
+        None => {
+            persistence.remove_collection_bridge(coll_id).await?;
+        }
+    }
+    Ok(())
+}
+
 /// Sync accounts from configuration.
 async fn sync_accounts(
     pool: &TaxiiPool,
@@ -393,6 +639,8 @@ async fn sync_accounts(
                 is_admin: account_config.is_admin,
                 permissions: permissions.clone(),
                 details: existing_account.details.clone(),
+                scope: None,
+                identity_id: existing_account.identity_id.clone(),
             };
 
             auth.update_account(&updated_account, Some(&account_config.password))
@@ -417,6 +665,8 @@ async fn sync_accounts(
                     is_admin: new_account.is_admin,
                     permissions,
                     details: new_account.details,
+                    scope: None,
+                    identity_id: new_account.identity_id,
                 };
                 auth.update_account(&account_with_perms, None).await?;
             }
@@ -477,12 +727,109 @@ fn convert_permissions(
     Ok(result)
 }
 
+/// Outcome of `content delete`.
+#[derive(Debug, Default, Serialize)]
+struct ContentDeleteRecord {
+    /// Content blocks deleted by collection name.
+    deleted: BTreeMap<String, i64>,
+    total: i64,
+}
+
+impl Record for ContentDeleteRecord {
+    const COLUMNS: &'static [(&'static str, &'static str)] =
+        &[("deleted", "Deleted"), ("total", "Total")];
+}
+
+/// Outcome of `content cleanup-result-sets`.
+#[derive(Debug, Serialize)]
+struct ResultSetCleanupRecord {
+    deleted: u64,
+}
+
+impl Record for ResultSetCleanupRecord {
+    const COLUMNS: &'static [(&'static str, &'static str)] = &[("deleted", "Deleted")];
+}
+
+/// Outcome of `content revoke`.
+#[derive(Debug, Serialize)]
+struct RevokeRecord {
+    collection: String,
+    revoked: usize,
+    /// Why objects were not revoked, by object ID.
+    skipped: BTreeMap<String, String>,
+}
+
+impl Record for RevokeRecord {
+    const COLUMNS: &'static [(&'static str, &'static str)] = &[
+        ("collection", "Collection"),
+        ("revoked", "Revoked"),
+        ("skipped", "Skipped"),
+    ];
+}
+
+/// A subscription in `subscription list`.
+#[derive(Debug, Serialize)]
+struct SubscriptionRecord {
+    id: Option<String>,
+    collection_id: i32,
+    status: String,
+    last_delivery: Option<DateTime<Utc>>,
+    consecutive_failures: i32,
+    last_error: Option<String>,
+}
+
+impl Record for SubscriptionRecord {
+    const COLUMNS: &'static [(&'static str, &'static str)] = &[
+        ("id", "ID"),
+        ("collection_id", "Collection"),
+        ("status", "Status"),
+        ("last_delivery", "Last Delivery"),
+        ("consecutive_failures", "Failures"),
+        ("last_error", "Last Error"),
+    ];
+}
+
+/// A push delivery in `subscription deliveries`.
+#[derive(Debug, Serialize)]
+struct DeliveryRecord {
+    id: i32,
+    subscription_id: String,
+    content_block_id: i32,
+    status: String,
+    attempts: i32,
+    last_attempt_at: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+}
+
+impl Record for DeliveryRecord {
+    const COLUMNS: &'static [(&'static str, &'static str)] = &[
+        ("id", "ID"),
+        ("subscription_id", "Subscription"),
+        ("content_block_id", "Block"),
+        ("status", "Status"),
+        ("attempts", "Attempts"),
+        ("last_attempt_at", "Last Attempt"),
+        ("last_error", "Last Error"),
+    ];
+}
+
+/// Outcome of `subscription retry`.
+#[derive(Debug, Serialize)]
+struct RetryRecord {
+    queued: u64,
+}
+
+impl Record for RetryRecord {
+    const COLUMNS: &'static [(&'static str, &'static str)] = &[("queued", "Queued")];
+}
+
 /// Handle content block commands.
 pub async fn handle_content(
     pool: TaxiiPool,
     action: ContentAction,
+    output: &Output,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let persistence = DbTaxii1Repository::new(pool);
+    let persistence = DbTaxii1Repository::new(pool.clone());
 
     match action {
         ContentAction::Delete {
@@ -503,18 +850,172 @@ pub async fn handle_content(
                 })
                 .transpose()?;
 
-            let mut total_deleted = 0;
-
-            for coll_name in &collection {
+            let mut record = ContentDeleteRecord::default();
+            for coll_name in collection {
                 let deleted = persistence
-                    .delete_content_blocks(coll_name, start_time, end_time, with_messages)
+                    .delete_content_blocks(&coll_name, start_time, end_time, with_messages)
                     .await?;
+                record.total += deleted;
+                record.deleted.insert(coll_name, deleted);
+            }
+            output.record(&record)?;
+        }
+        ContentAction::CleanupResultSets => {
+            let deleted = persistence.purge_expired_result_sets(Utc::now()).await?;
+            output.record(&ResultSetCleanupRecord { deleted })?;
+        }
+        ContentAction::Revoke { collection, since } => {
+            let since = DateTime::parse_from_rfc3339(&since)
+                .map_err(|e| format!("Invalid since timestamp: {e}"))?
+                .with_timezone(&Utc);
+            let record = revoke_objects(pool, &collection, since).await?;
+            output.record(&record)?;
+        }
+        ContentAction::Push(args) => {
+            super::push::push_to_database(pool, &args, output).await?;
+        }
+        ContentAction::Migrate(args) => {
+            super::migrate_content::migrate_content(pool, &args, output).await?;
+        }
+    }
+
+    Ok(())
+}
 
-                println!("Deleted {deleted} content blocks from '{coll_name}'");
-                total_deleted += deleted;
+/// Revoke the latest versions of the objects added to a collection after
+/// `since`.
+async fn revoke_objects(
+    pool: TaxiiPool,
+    collection_id: &str,
+    since: DateTime<Utc>,
+) -> Result<RevokeRecord, Box<dyn std::error::Error>> {
+    let collection_uuid = Uuid::parse_str(collection_id)
+        .map_err(|_| format!("Invalid collection ID: {collection_id}"))?;
+    let collection = Collection::find(&pool, collection_uuid)
+        .await?
+        .ok_or_else(|| format!("Collection '{collection_id}' not found"))?;
+    let persistence = DbTaxii2Repository::new(pool);
+
+    // Read everything first so the revoked versions are not paged through
+    let mut objects = Vec::new();
+    let mut unparsable = Vec::new();
+    let mut cursor: Option<PaginationCursor> = None;
+    loop {
+        let params = Taxii2QueryParams {
+            limit: Some(REVOKE_PAGE_SIZE),
+            added_after: Some(since),
+            next: cursor.as_ref(),
+            ..Default::default()
+        };
+        let page = persistence.get_objects(collection_id, &params).await?;
+        for object in page.items {
+            match object.to_typed() {
+                Ok(parsed) => objects.push(parsed),
+                Err(e) => unparsable.push((object.id, e.to_string())),
+            }
+        }
+        match page.next.filter(|_| page.more) {
+            Some(next) => {
+                cursor =
+                    Some(taxii_db::parse_next_param(&next).ok_or("Invalid pagination cursor")?);
             }
+            None => break,
+        }
+    }
+
+    let report = stix2::revoke_all_with_report(objects);
+    if !report.revoked.is_empty() {
+        let revoked = report
+            .revoked
+            .iter()
+            .map(serde_json::to_value)
+            .collect::<Result<Vec<_>, _>>()?;
+        let job = persistence
+            .add_objects(&collection.api_root_id.to_string(), collection_id, &revoked)
+            .await?;
+        if job.failure_count > 0 {
+            return Err(format!(
+                "{} of {} revoked versions could not be stored (job {})",
+                job.failure_count, job.total_count, job.id
+            )
+            .into());
+        }
+    }
 
-            println!("Total deleted: {total_deleted} content blocks");
+    let skipped = report
+        .skipped
+        .into_iter()
+        .map(|skipped| (skipped.id, skipped.reason))
+        .chain(unparsable)
+        .collect();
+    Ok(RevokeRecord {
+        collection: collection.title,
+        revoked: report.revoked.len(),
+        skipped,
+    })
+}
+
+/// Handle subscription commands.
+pub async fn handle_subscription(
+    pool: TaxiiPool,
+    action: SubscriptionAction,
+    output: &Output,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let persistence = DbTaxii1Repository::new(pool);
+
+    match action {
+        SubscriptionAction::List { service } => {
+            let records: Vec<_> = persistence
+                .get_subscriptions(&service)
+                .await?
+                .into_iter()
+                .map(|subscription| SubscriptionRecord {
+                    id: subscription.subscription_id,
+                    collection_id: subscription.collection_id,
+                    status: subscription.status,
+                    last_delivery: subscription.delivery.last_success,
+                    consecutive_failures: subscription.delivery.consecutive_failures,
+                    last_error: subscription.delivery.last_error,
+                })
+                .collect();
+            output.list(
+                &records,
+                &format!("No subscriptions found for service '{service}'."),
+            )?;
+        }
+        SubscriptionAction::Deliveries {
+            status,
+            subscription,
+            limit,
+        } => {
+            let status = match status.to_ascii_lowercase().as_str() {
+                "all" => None,
+                "pending" => Some(delivery_status::PENDING),
+                "delivered" => Some(delivery_status::DELIVERED),
+                "failed" => Some(delivery_status::FAILED),
+                other => return Err(format!("Unknown delivery status '{other}'").into()),
+            };
+            let records: Vec<_> = persistence
+                .get_subscription_deliveries(status, subscription.as_deref(), limit)
+                .await?
+                .into_iter()
+                .map(|delivery| DeliveryRecord {
+                    id: delivery.id,
+                    subscription_id: delivery.subscription_id,
+                    content_block_id: delivery.content_block_id,
+                    status: delivery.status,
+                    attempts: delivery.attempts,
+                    last_attempt_at: delivery.last_attempt_at,
+                    last_error: delivery.last_error,
+                })
+                .collect();
+            output.list(&records, "No deliveries found.")?;
+        }
+        SubscriptionAction::Retry { id, subscription } => {
+            let queued = persistence
+                .requeue_failed_deliveries(&id, subscription.as_deref())
+                .await?;
+            output.record(&RetryRecord { queued })?;
         }
     }
 
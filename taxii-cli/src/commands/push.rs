@@ -0,0 +1,382 @@
+//! Push STIX bundles into TAXII 2.x collections.
+
+use clap::Args;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use stix2::StixObject;
+use stix2::datastore::TaxiiClient;
+use stix2::validation::validate_object;
+use taxii_core::BulkInsertOutcome;
+use taxii_db::{Collection, DbTaxii2Repository, Taxii2Repository, TaxiiPool};
+use uuid::Uuid;
+
+use super::output::{Output, Record};
+
+/// Arguments of `content push`.
+#[derive(Args)]
+pub struct PushArgs {
+    /// API root ID.
+    #[arg(long)]
+    pub api_root: String,
+
+    /// Collection ID.
+    #[arg(long)]
+    pub collection: String,
+
+    /// STIX bundle or TAXII envelope (JSON file).
+    pub file: PathBuf,
+
+    /// Validate the objects against the STIX 2.1 specification and skip
+    /// invalid ones.
+    #[arg(long, default_value = "false")]
+    pub validate: bool,
+
+    /// Objects written per batch.
+    #[arg(long, default_value = "500")]
+    pub batch_size: NonZeroUsize,
+
+    /// Push to a TAXII 2.1 server instead of the database, e.g.
+    /// `https://taxii.example.com/taxii2`. Objects are always validated.
+    #[arg(long)]
+    pub remote: Option<String>,
+
+    /// Username for the remote server.
+    #[arg(long, requires_all = ["remote", "password"])]
+    pub username: Option<String>,
+
+    /// Password for the remote server.
+    #[arg(long, env = "DARWIS_TAXII_REMOTE_PASSWORD", requires = "username")]
+    pub password: Option<String>,
+}
+
+/// Outcome of a push.
+#[derive(Debug, Default)]
+pub(crate) struct PushSummary {
+    /// Objects stored.
+    pub(crate) inserted: usize,
+    /// Objects whose version was already stored.
+    pub(crate) duplicate: usize,
+    /// Objects accepted by a remote server.
+    pub(crate) accepted: usize,
+    /// Objects that could not be pushed, with the reason.
+    pub(crate) failed: Vec<(String, String)>,
+}
+
+impl PushSummary {
+    fn total(&self) -> usize {
+        self.inserted + self.duplicate + self.accepted + self.failed.len()
+    }
+
+    /// Add the outcome of another push.
+    pub(crate) fn merge(&mut self, other: PushSummary) {
+        self.inserted += other.inserted;
+        self.duplicate += other.duplicate;
+        self.accepted += other.accepted;
+        self.failed.extend(other.failed);
+    }
+
+    /// The summary as a record.
+    pub(crate) fn record(&self, target: &str) -> PushRecord {
+        PushRecord {
+            target: target.to_string(),
+            total: self.total(),
+            inserted: self.inserted,
+            duplicate: self.duplicate,
+            accepted: self.accepted,
+            failed: self.failed.len(),
+            failures: self.failed.iter().cloned().collect(),
+        }
+    }
+
+    /// Print the summary, failing when any object failed.
+    pub(crate) fn report(
+        &self,
+        target: &str,
+        output: &Output,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        output.record(&self.record(target))?;
+        self.result()
+    }
+
+    /// Fail when any object failed.
+    pub(crate) fn result(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.failed.is_empty() {
+            Ok(())
+        } else {
+            Err(format!(
+                "{} of {} objects could not be pushed",
+                self.failed.len(),
+                self.total()
+            )
+            .into())
+        }
+    }
+}
+
+/// Summary of `content push`.
+#[derive(Debug, Serialize)]
+pub(crate) struct PushRecord {
+    /// Collection title, or the URL of a remote server.
+    target: String,
+    total: usize,
+    inserted: usize,
+    duplicate: usize,
+    accepted: usize,
+    failed: usize,
+    /// Reason each failed object was not pushed, by object ID.
+    failures: BTreeMap<String, String>,
+}
+
+impl Record for PushRecord {
+    const COLUMNS: &'static [(&'static str, &'static str)] = &[
+        ("target", "Target"),
+        ("total", "Total"),
+        ("inserted", "Inserted"),
+        ("duplicate", "Duplicate"),
+        ("accepted", "Accepted"),
+        ("failed", "Failed"),
+        ("failures", "Failures"),
+    ];
+}
+
+/// Push a bundle into a collection of the database.
+pub async fn push_to_database(
+    pool: TaxiiPool,
+    args: &PushArgs,
+    output: &Output,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let collection = find_collection(&pool, &args.api_root, &args.collection).await?;
+    let objects = read_objects(&args.file)?;
+    let persistence = DbTaxii2Repository::new(pool);
+    let summary = push_objects(
+        &persistence,
+        &args.api_root,
+        &args.collection,
+        objects,
+        args.validate,
+        args.batch_size.get(),
+    )
+    .await?;
+    summary.report(&collection.title, output)
+}
+
+/// Push a bundle into a collection of a remote TAXII 2.1 server.
+pub async fn push_to_remote(
+    url: &str,
+    args: &PushArgs,
+    output: &Output,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let client = match (&args.username, &args.password) {
+        (Some(username), Some(password)) => {
+            TaxiiClient::with_auth(url, username.as_str(), password.as_str())?
+        }
+        _ => TaxiiClient::new(url)?,
+    };
+
+    // The client sends typed objects, so objects that do not parse fail
+    let mut summary = PushSummary::default();
+    let objects: Vec<StixObject> = read_objects(&args.file)?
+        .iter()
+        .enumerate()
+        .filter_map(|(index, object)| match validate_object(object, None) {
+            Ok(parsed) => Some(parsed),
+            Err(e) => {
+                summary
+                    .failed
+                    .push((object_label(object, index), e.to_string()));
+                None
+            }
+        })
+        .collect();
+
+    for batch in objects.chunks(args.batch_size.get()) {
+        let status = client
+            .add_objects(&args.api_root, &args.collection, batch.to_vec())
+            .await?;
+        summary.accepted += status.successes.len();
+        summary.failed.extend(
+            status
+                .failures
+                .into_iter()
+                .map(|failure| (failure.id, failure.message.unwrap_or_default())),
+        );
+    }
+
+    summary.report(url, output)
+}
+
+/// Find a collection of an API root.
+pub(crate) async fn find_collection(
+    pool: &TaxiiPool,
+    api_root_id: &str,
+    collection_id: &str,
+) -> Result<Collection, Box<dyn std::error::Error>> {
+    let collection_uuid = Uuid::parse_str(collection_id)
+        .map_err(|_| format!("Invalid collection ID: {collection_id}"))?;
+    Collection::find(pool, collection_uuid)
+        .await?
+        .filter(|collection| collection.api_root_id.to_string() == api_root_id)
+        .ok_or_else(|| {
+            format!("Collection '{collection_id}' not found in API root '{api_root_id}'").into()
+        })
+}
+
+/// Read the objects of a bundle or envelope.
+fn read_objects(path: &Path) -> Result<Vec<Value>, Box<dyn std::error::Error>> {
+    let data =
+        fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+    let mut document: Value = serde_json::from_str(&data)
+        .map_err(|e| format!("Invalid JSON in {}: {e}", path.display()))?;
+    match document.get_mut("objects").map(Value::take) {
+        Some(Value::Array(objects)) => Ok(objects),
+        _ => Err(format!("{} has no objects array", path.display()).into()),
+    }
+}
+
+/// ID of an object, or its position when it has none.
+fn object_label(object: &Value, index: usize) -> String {
+    object["id"]
+        .as_str()
+        .map_or_else(|| format!("object {}", index + 1), String::from)
+}
+
+/// Write objects to a collection in batches.
+pub(crate) async fn push_objects(
+    persistence: &DbTaxii2Repository,
+    api_root_id: &str,
+    collection_id: &str,
+    objects: Vec<Value>,
+    validate: bool,
+    batch_size: usize,
+) -> Result<PushSummary, Box<dyn std::error::Error>> {
+    let mut summary = PushSummary::default();
+    let objects: Vec<Value> = if validate {
+        objects
+            .into_iter()
+            .enumerate()
+            .filter_map(|(index, object)| match validate_object(&object, None) {
+                Ok(_) => Some(object),
+                Err(e) => {
+                    summary
+                        .failed
+                        .push((object_label(&object, index), e.to_string()));
+                    None
+                }
+            })
+            .collect()
+    } else {
+        objects
+    };
+
+    for batch in objects.chunks(batch_size) {
+        let report = persistence
+            .add_objects_bulk(api_root_id, collection_id, batch)
+            .await?;
+        for outcome in &report.outcomes {
+            match outcome {
+                BulkInsertOutcome::Inserted | BulkInsertOutcome::Overwritten => {
+                    summary.inserted += 1;
+                }
+                BulkInsertOutcome::Duplicate | BulkInsertOutcome::Kept => {
+                    summary.duplicate += 1;
+                }
+                BulkInsertOutcome::Failed => {}
+            }
+        }
+        summary
+            .failed
+            .extend(report.job.details.failure.into_iter().map(|detail| {
+                match detail.stix_id.as_str() {
+                    "" => ("object without ID".to_string(), detail.message),
+                    _ => (detail.stix_id, detail.message),
+                }
+            }));
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::PgPool;
+
+    type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+    const BUNDLE: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/bundle_with_invalid_object.json"
+    );
+    const INVALID_ID: &str = "indicator--c1f5a8b2-3d4e-4f60-8a7b-9c0d1e2f3a4b";
+
+    /// Pushing validates the objects, stores the valid ones in batches and
+    /// reports the invalid one; pushing again finds duplicates.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_push_bundle(pool: PgPool) -> TestResult {
+        let pool = TaxiiPool::new(pool);
+        let persistence = DbTaxii2Repository::new(pool.clone());
+        let api_root = persistence
+            .add_api_root("Root", None, true, true, None)
+            .await?;
+        let collection = persistence
+            .add_collection(&api_root.id, "Feed", None, None, true, true, None)
+            .await?;
+        find_collection(&pool, &api_root.id, &collection.id).await?;
+        assert!(
+            find_collection(&pool, &Uuid::new_v4().to_string(), &collection.id)
+                .await
+                .is_err()
+        );
+
+        let objects = read_objects(Path::new(BUNDLE))?;
+        let summary = push_objects(
+            &persistence,
+            &api_root.id,
+            &collection.id,
+            objects.clone(),
+            true,
+            2,
+        )
+        .await?;
+        assert_eq!(summary.inserted, 3);
+        assert_eq!(summary.duplicate, 0);
+        assert_eq!(summary.failed.len(), 1);
+        assert_eq!(summary.failed[0].0, INVALID_ID);
+        assert!(summary.report("collection", &Output::default()).is_err());
+        let record = serde_json::to_value(summary.record("collection"))?;
+        assert_eq!(record["total"], 4);
+        assert!(record["failures"][INVALID_ID].is_string());
+
+        let summary =
+            push_objects(&persistence, &api_root.id, &collection.id, objects, true, 2).await?;
+        assert_eq!(summary.inserted, 0);
+        assert_eq!(summary.duplicate, 3);
+        assert_eq!(summary.failed.len(), 1);
+
+        // Only the valid objects were stored
+        let manifest = persistence
+            .get_manifest(&collection.id, &Default::default())
+            .await?;
+        assert_eq!(manifest.items.len(), 3);
+        assert!(manifest.items.iter().all(|m| m.id != INVALID_ID));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_objects_requires_objects_array() {
+        assert!(read_objects(Path::new(BUNDLE)).is_ok());
+        assert!(
+            read_objects(Path::new(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/Cargo.toml"
+            )))
+            .is_err()
+        );
+    }
+}
@@ -1,7 +1,18 @@
 //! TAXII 2.x management commands.
 
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
 use clap::Subcommand;
-use taxii_db::{DbTaxii2Repository, Taxii2Repository, TaxiiPool};
+use serde::Serialize;
+use taxii_core::{Collection, CollectionStats, ValidationRule};
+use taxii_db::{
+    DbTaxii1Repository, DbTaxii2Repository, Job, PaginationCursor, Taxii1Repository,
+    Taxii2QueryParams, Taxii2Repository, TaxiiPool, parse_next_param, partitioning,
+};
+use uuid::Uuid;
+
+use super::output::{Output, Record};
 
 /// API Root management actions.
 #[derive(Subcommand)]
@@ -63,25 +74,386 @@ pub enum CollectionAction {
         public_write: bool,
     },
 
+    /// Delete a collection with all its objects.
+    ///
+    /// When the STIX object table is partitioned, the collection's partition
+    /// is dropped.
+    Delete {
+        /// Collection ID.
+        #[arg(long)]
+        id: String,
+
+        /// Confirm the deletion; it cannot be undone.
+        #[arg(long = "yes-i-know")]
+        yes_i_know: bool,
+    },
+
     /// List collections for an API root.
     List {
         /// API root ID.
         #[arg(long)]
         api_root_id: String,
     },
+
+    /// Set how long objects are kept in a collection.
+    ///
+    /// Objects added longer ago than the retention period are purged by the
+    /// server. Omit --days to keep objects forever.
+    SetRetention {
+        /// Collection ID.
+        #[arg(long)]
+        id: String,
+
+        /// Days to keep objects after they are added.
+        #[arg(long, value_parser = clap::value_parser!(i32).range(1..))]
+        days: Option<i32>,
+    },
+
+    /// Set the validation rules objects posted to a collection must pass.
+    ///
+    /// Objects failing a rule are not stored and are reported as failures
+    /// in the job status. Omit --rule to remove all rules.
+    SetRules {
+        /// Collection ID.
+        #[arg(long)]
+        id: String,
+
+        /// Rule to apply (repeatable): require-confidence, require-tlp or
+        /// require-description.
+        #[arg(long = "rule")]
+        rules: Vec<ValidationRule>,
+    },
+
+    /// Set the marking applied to objects posted without markings.
+    ///
+    /// Objects posted with object_marking_refs keep their markings. Omit
+    /// --marking to stop marking objects.
+    SetDefaultMarking {
+        /// Collection ID.
+        #[arg(long)]
+        id: String,
+
+        /// TLP level (clear, white, green, amber, amber+strict, red) or
+        /// marking definition ID.
+        #[arg(long, value_parser = parse_marking)]
+        marking: Option<String>,
+    },
+
+    /// Search the latest versions of objects in a collection by content.
+    ///
+    /// Matches names, values, labels, descriptions and patterns. The query
+    /// accepts "quoted phrases", `or` and `-excluded` words. Name matches
+    /// are listed first.
+    Search {
+        /// Collection ID.
+        #[arg(long)]
+        id: String,
+
+        /// Search query.
+        query: String,
+
+        /// Only objects of this type (repeatable).
+        #[arg(long = "type")]
+        types: Vec<String>,
+
+        /// Maximum number of objects to show.
+        #[arg(long, default_value = "20", value_parser = clap::value_parser!(i64).range(1..))]
+        limit: i64,
+
+        /// Continue from a previous page.
+        #[arg(long, value_parser = parse_cursor)]
+        next: Option<PaginationCursor>,
+    },
+
+    /// Export the objects of a collection to a bundle or NDJSON file.
+    ///
+    /// Objects are streamed from the database, so collections of any size
+    /// can be exported.
+    Export(super::export::ExportArgs),
+
+    /// Print the API roots, collections and account grants as a sync
+    /// configuration file.
+    ///
+    /// Applying the output with `sync` on another database recreates the
+    /// same API roots and collections, with the same IDs.
+    ExportConfig,
+
+    /// Show object counts of a collection by type.
+    ///
+    /// Statistics of very large collections are cached by the server and
+    /// may be up to an hour old.
+    Stats {
+        /// Collection ID.
+        #[arg(long, required_unless_present = "taxii1")]
+        id: Option<String>,
+
+        /// Name of a TAXII 1.x collection instead (counts content blocks by
+        /// content binding).
+        #[arg(long, conflicts_with = "id")]
+        taxii1: Option<String>,
+    },
 }
 
 /// Job management actions.
 #[derive(Subcommand)]
 pub enum JobAction {
-    /// Clean up old job logs (>24h).
-    Cleanup,
+    /// List the most recently requested jobs.
+    List {
+        /// Only jobs of this API root.
+        #[arg(long)]
+        api_root_id: Option<Uuid>,
+
+        /// Only jobs with this status.
+        #[arg(long, value_parser = ["pending", "complete"])]
+        status: Option<String>,
+
+        /// Maximum number of jobs to show.
+        #[arg(long, default_value = "50")]
+        limit: i64,
+    },
+
+    /// Clean up old job logs.
+    Cleanup {
+        /// Remove jobs completed more than this many days ago.
+        #[arg(long, default_value_t = 30, value_parser = clap::value_parser!(i64).range(0..=36_500))]
+        older_than_days: i64,
+
+        /// Also remove jobs that never completed, instead of marking them
+        /// failed once stale.
+        #[arg(long)]
+        delete_incomplete: bool,
+    },
+}
+
+/// An API root in `api-root list`.
+#[derive(Debug, Serialize)]
+struct ApiRootRecord {
+    id: String,
+    title: String,
+    description: Option<String>,
+    default: bool,
+    is_public: bool,
+}
+
+impl Record for ApiRootRecord {
+    const COLUMNS: &'static [(&'static str, &'static str)] = &[
+        ("id", "ID"),
+        ("title", "Title"),
+        ("description", "Description"),
+        ("default", "Default"),
+        ("is_public", "Public"),
+    ];
+}
+
+/// A collection in `collection list`.
+#[derive(Debug, Serialize)]
+struct CollectionRecord {
+    id: String,
+    title: String,
+    alias: Option<String>,
+    is_public: bool,
+    is_public_write: bool,
+    retention_days: Option<i32>,
+}
+
+impl Record for CollectionRecord {
+    const COLUMNS: &'static [(&'static str, &'static str)] = &[
+        ("id", "ID"),
+        ("title", "Title"),
+        ("alias", "Alias"),
+        ("is_public", "Public"),
+        ("is_public_write", "Writable"),
+        ("retention_days", "Retention (days)"),
+    ];
+}
+
+/// A collection in `collection add`.
+#[derive(Debug, Serialize)]
+struct AddedCollectionRecord {
+    id: String,
+    api_root_id: String,
+    title: String,
+    description: Option<String>,
+    alias: Option<String>,
+    is_public: bool,
+    is_public_write: bool,
+}
+
+impl Record for AddedCollectionRecord {
+    const COLUMNS: &'static [(&'static str, &'static str)] = &[
+        ("id", "ID"),
+        ("api_root_id", "API Root"),
+        ("title", "Title"),
+        ("description", "Description"),
+        ("alias", "Alias"),
+        ("is_public", "Public Read"),
+        ("is_public_write", "Public Write"),
+    ];
+}
+
+/// A collection in `collection delete`.
+#[derive(Debug, Serialize)]
+struct DeletedCollectionRecord {
+    id: String,
+}
+
+impl Record for DeletedCollectionRecord {
+    const COLUMNS: &'static [(&'static str, &'static str)] = &[("id", "Deleted")];
+}
+
+/// Settings of a collection after `collection set-retention`,
+/// `set-rules` or `set-default-marking`.
+#[derive(Debug, Serialize)]
+struct CollectionSettingsRecord {
+    id: String,
+    title: String,
+    retention_days: Option<i32>,
+    validation_rules: Vec<&'static str>,
+    default_marking: Option<String>,
+}
+
+impl Record for CollectionSettingsRecord {
+    const COLUMNS: &'static [(&'static str, &'static str)] = &[
+        ("id", "ID"),
+        ("title", "Title"),
+        ("retention_days", "Retention (days)"),
+        ("validation_rules", "Rules"),
+        ("default_marking", "Default Marking"),
+    ];
+}
+
+impl From<Collection> for CollectionSettingsRecord {
+    fn from(collection: Collection) -> Self {
+        Self {
+            id: collection.id,
+            title: collection.title,
+            retention_days: collection.retention_days,
+            validation_rules: collection
+                .validation_rules
+                .iter()
+                .map(|r| r.name())
+                .collect(),
+            default_marking: collection.default_marking,
+        }
+    }
+}
+
+/// An object in `collection search`.
+#[derive(Debug, Serialize)]
+struct SearchRecord {
+    id: String,
+    #[serde(rename = "type")]
+    object_type: String,
+    name: Option<String>,
+    date_added: DateTime<Utc>,
+}
+
+impl Record for SearchRecord {
+    const COLUMNS: &'static [(&'static str, &'static str)] = &[
+        ("id", "ID"),
+        ("type", "Type"),
+        ("name", "Name"),
+        ("date_added", "Added"),
+    ];
+}
+
+/// Statistics in `collection stats`.
+#[derive(Debug, Serialize)]
+struct StatsRecord {
+    object_count: u64,
+    distinct_ids: u64,
+    latest_added: Option<DateTime<Utc>>,
+    computed_at: DateTime<Utc>,
+    by_type: BTreeMap<String, u64>,
+}
+
+impl Record for StatsRecord {
+    const COLUMNS: &'static [(&'static str, &'static str)] = &[
+        ("object_count", "Objects"),
+        ("distinct_ids", "Distinct IDs"),
+        ("latest_added", "Last added"),
+        ("computed_at", "Computed"),
+        ("by_type", "By type"),
+    ];
+}
+
+impl From<CollectionStats> for StatsRecord {
+    fn from(stats: CollectionStats) -> Self {
+        Self {
+            object_count: stats.object_count,
+            distinct_ids: stats.distinct_ids,
+            latest_added: stats.latest_added,
+            computed_at: stats.computed_at,
+            by_type: stats.by_type,
+        }
+    }
+}
+
+/// A job in `job list`.
+#[derive(Debug, Serialize)]
+struct JobRecord {
+    id: Uuid,
+    api_root_id: Uuid,
+    status: String,
+    requested: Option<DateTime<Utc>>,
+    completed: Option<DateTime<Utc>>,
+    total_count: Option<i32>,
+    success_count: Option<i32>,
+    failure_count: Option<i32>,
+    pending_count: Option<i32>,
+}
+
+impl Record for JobRecord {
+    const COLUMNS: &'static [(&'static str, &'static str)] = &[
+        ("id", "ID"),
+        ("api_root_id", "API Root"),
+        ("status", "Status"),
+        ("requested", "Requested"),
+        ("completed", "Completed"),
+        ("total_count", "Total"),
+        ("success_count", "Succeeded"),
+        ("failure_count", "Failed"),
+        ("pending_count", "Pending"),
+    ];
+}
+
+impl From<Job> for JobRecord {
+    fn from(job: Job) -> Self {
+        Self {
+            id: job.id,
+            api_root_id: job.api_root_id,
+            status: job.status,
+            requested: job.request_timestamp.map(|t| t.and_utc()),
+            completed: job.completed_timestamp.map(|t| t.and_utc()),
+            total_count: job.total_count,
+            success_count: job.success_count,
+            failure_count: job.failure_count,
+            pending_count: job.pending_count,
+        }
+    }
+}
+
+/// Outcome of `job cleanup`.
+#[derive(Debug, Serialize)]
+struct JobCleanupRecord {
+    jobs_removed: u64,
+    details_removed: u64,
+    jobs_failed: u64,
+}
+
+impl Record for JobCleanupRecord {
+    const COLUMNS: &'static [(&'static str, &'static str)] = &[
+        ("jobs_removed", "Jobs removed"),
+        ("details_removed", "Details removed"),
+        ("jobs_failed", "Stale jobs failed"),
+    ];
 }
 
 /// Handle API root commands.
 pub async fn handle_api_root(
     pool: TaxiiPool,
     action: ApiRootAction,
+    output: &Output,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let persistence = DbTaxii2Repository::new(pool);
 
@@ -104,38 +476,28 @@ pub async fn handle_api_root(
                 )
                 .await?;
 
-            println!("API root created successfully:");
-            println!("  ID: {}", api_root.id);
-            println!("  Title: {}", api_root.title);
-            if let Some(desc) = &api_root.description {
-                println!("  Description: {desc}");
-            }
-            println!("  Default: {}", api_root.default);
-            println!("  Public: {}", api_root.is_public);
+            output.record(&ApiRootRecord {
+                id: api_root.id,
+                title: api_root.title,
+                description: api_root.description,
+                default: api_root.default,
+                is_public: api_root.is_public,
+            })?;
         }
         ApiRootAction::List => {
-            let api_roots = persistence.get_api_roots().await?;
-
-            if api_roots.is_empty() {
-                println!("No API roots found.");
-                return Ok(());
-            }
-
-            println!(
-                "{:<40} {:<30} {:<10} {:<10}",
-                "ID", "Title", "Default", "Public"
-            );
-            println!("{}", "-".repeat(95));
-
-            for root in api_roots {
-                println!(
-                    "{:<40} {:<30} {:<10} {:<10}",
-                    root.id,
-                    truncate(&root.title, 28),
-                    if root.default { "Yes" } else { "No" },
-                    if root.is_public { "Yes" } else { "No" }
-                );
-            }
+            let records: Vec<_> = persistence
+                .get_api_roots()
+                .await?
+                .into_iter()
+                .map(|root| ApiRootRecord {
+                    id: root.id,
+                    title: root.title,
+                    description: root.description,
+                    default: root.default,
+                    is_public: root.is_public,
+                })
+                .collect();
+            output.list(&records, "No API roots found.")?;
         }
     }
 
@@ -146,8 +508,10 @@ pub async fn handle_api_root(
 pub async fn handle_collection(
     pool: TaxiiPool,
     action: CollectionAction,
+    output: &Output,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let persistence = DbTaxii2Repository::new(pool);
+    let persistence = DbTaxii2Repository::new(pool.clone())
+        .with_partitioning(partitioning::is_partitioned(&pool).await?);
 
     match action {
         CollectionAction::Add {
@@ -172,74 +536,303 @@ pub async fn handle_collection(
                     alias.as_deref(),
                     public,
                     public_write,
+                    None,
                 )
                 .await?;
 
-            println!("Collection created successfully:");
-            println!("  ID: {}", collection.id);
-            println!("  API Root: {}", collection.api_root_id);
-            println!("  Title: {}", collection.title);
-            if let Some(desc) = &collection.description {
-                println!("  Description: {desc}");
-            }
-            if let Some(a) = &collection.alias {
-                println!("  Alias: {a}");
-            }
-            println!("  Public Read: {}", collection.is_public);
-            println!("  Public Write: {}", collection.is_public_write);
+            output.record(&AddedCollectionRecord {
+                id: collection.id,
+                api_root_id: collection.api_root_id,
+                title: collection.title,
+                description: collection.description,
+                alias: collection.alias,
+                is_public: collection.is_public,
+                is_public_write: collection.is_public_write,
+            })?;
         }
-        CollectionAction::List { api_root_id } => {
-            let collections = persistence.get_collections(&api_root_id).await?;
-
-            if collections.is_empty() {
-                println!("No collections found for API root '{api_root_id}'.");
-                return Ok(());
+        CollectionAction::Delete { id, yes_i_know } => {
+            if !yes_i_know {
+                return Err(format!(
+                    "Deleting collection '{id}' removes all its objects. \
+                     Pass --yes-i-know to delete it."
+                )
+                .into());
             }
 
-            println!(
-                "{:<40} {:<30} {:<15} {:<10} {:<10}",
-                "ID", "Title", "Alias", "Public", "Writable"
-            );
-            println!("{}", "-".repeat(110));
-
-            for coll in collections {
-                println!(
-                    "{:<40} {:<30} {:<15} {:<10} {:<10}",
-                    coll.id,
-                    truncate(&coll.title, 28),
-                    coll.alias.as_deref().unwrap_or("-"),
-                    if coll.is_public { "Yes" } else { "No" },
-                    if coll.is_public_write { "Yes" } else { "No" }
-                );
-            }
+            persistence.delete_collection(&id).await?;
+            output.record(&DeletedCollectionRecord { id })?;
+        }
+        CollectionAction::List { api_root_id } => {
+            let records: Vec<_> = persistence
+                .get_collections(&api_root_id)
+                .await?
+                .into_iter()
+                .map(|coll| CollectionRecord {
+                    id: coll.id,
+                    title: coll.title,
+                    alias: coll.alias,
+                    is_public: coll.is_public,
+                    is_public_write: coll.is_public_write,
+                    retention_days: coll.retention_days,
+                })
+                .collect();
+            output.list(
+                &records,
+                &format!("No collections found for API root '{api_root_id}'."),
+            )?;
+        }
+        CollectionAction::SetRetention { id, days } => {
+            let collection = persistence.set_collection_retention(&id, days).await?;
+            output.record(&CollectionSettingsRecord::from(collection))?;
+        }
+        CollectionAction::SetRules { id, rules } => {
+            let collection = persistence
+                .set_collection_validation_rules(&id, &rules)
+                .await?;
+            output.record(&CollectionSettingsRecord::from(collection))?;
+        }
+        CollectionAction::SetDefaultMarking { id, marking } => {
+            let collection = persistence
+                .set_collection_default_marking(&id, marking.as_deref())
+                .await?;
+            output.record(&CollectionSettingsRecord::from(collection))?;
+        }
+        CollectionAction::Search {
+            id,
+            query,
+            types,
+            limit,
+            next,
+        } => {
+            let params = Taxii2QueryParams {
+                limit: Some(limit),
+                next: next.as_ref(),
+                match_type: (!types.is_empty()).then_some(types.as_slice()),
+                ..Default::default()
+            };
+            let page = persistence.search_objects(&id, &query, &params).await?;
+
+            let records: Vec<_> = page
+                .items
+                .into_iter()
+                .map(|object| SearchRecord {
+                    name: ["name", "value"]
+                        .iter()
+                        .find_map(|key| object.serialized_data[key].as_str())
+                        .map(String::from),
+                    id: object.id,
+                    object_type: object.stix_type,
+                    date_added: object.date_added,
+                })
+                .collect();
+            let next = page.next.filter(|_| page.more);
+            output.page(&records, next.as_deref(), "No objects found.")?;
+        }
+        CollectionAction::Export(args) => {
+            super::export::export_collection(&pool, &persistence, &args, output).await?;
+        }
+        CollectionAction::ExportConfig => {
+            let config = super::taxii2_config::export_config(&pool).await?;
+            output.document(&config, &serde_yaml::to_string(&config)?)?;
+        }
+        CollectionAction::Stats { id, taxii1 } => {
+            let stats = match (id, taxii1) {
+                (_, Some(name)) => {
+                    let repo = DbTaxii1Repository::new(pool);
+                    let collection_id = repo
+                        .get_collection(&name, None)
+                        .await?
+                        .and_then(|c| c.id)
+                        .ok_or_else(|| format!("TAXII 1.x collection '{name}' not found"))?;
+                    repo.collection_stats(collection_id).await?
+                }
+                (Some(id), None) => persistence.collection_stats(&id).await?,
+                (None, None) => return Err("--id or --taxii1 is required".into()),
+            };
+            output.record(&StatsRecord::from(stats))?;
         }
     }
 
     Ok(())
 }
 
+/// Parse a pagination cursor printed by a previous page.
+fn parse_cursor(value: &str) -> Result<PaginationCursor, String> {
+    parse_next_param(value).ok_or_else(|| "invalid --next value".to_string())
+}
+
+/// Parse a TLP level or marking definition ID into a marking definition ID.
+fn parse_marking(value: &str) -> Result<String, String> {
+    if let Ok(level) = serde_json::from_value::<stix2::TlpLevel>(serde_json::Value::from(
+        value.to_ascii_lowercase(),
+    )) {
+        return Ok(level.marking_definition_id().to_string());
+    }
+    match value.parse::<stix2::Identifier>() {
+        Ok(id) if id.is_type("marking-definition") => Ok(id.to_string()),
+        _ => Err(format!(
+            "'{value}' is neither a TLP level nor a marking definition ID"
+        )),
+    }
+}
+
 /// Handle job commands.
 pub async fn handle_job(
     pool: TaxiiPool,
     action: JobAction,
+    output: &Output,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let persistence = DbTaxii2Repository::new(pool);
+    let persistence = DbTaxii2Repository::new(pool.clone());
 
     match action {
-        JobAction::Cleanup => {
-            let removed = persistence.job_cleanup().await?;
-            println!("{removed} job(s) removed");
+        JobAction::List {
+            api_root_id,
+            status,
+            limit,
+        } => {
+            let records: Vec<JobRecord> =
+                Job::find_recent(&pool, api_root_id, status.as_deref(), limit)
+                    .await?
+                    .into_iter()
+                    .map(Into::into)
+                    .collect();
+            output.list(&records, "No jobs found.")?;
+        }
+        JobAction::Cleanup {
+            older_than_days,
+            delete_incomplete,
+        } => {
+            let older_than = Utc::now() - chrono::Duration::days(older_than_days);
+            let stats = persistence
+                .cleanup_jobs(older_than, !delete_incomplete)
+                .await?;
+            output.record(&JobCleanupRecord {
+                jobs_removed: stats.jobs_removed,
+                details_removed: stats.details_removed,
+                jobs_failed: stats.jobs_failed,
+            })?;
         }
     }
 
     Ok(())
 }
 
-/// Truncate a string to a maximum length.
-fn truncate(s: &str, max_len: usize) -> String {
-    if s.len() <= max_len {
-        s.to_string()
-    } else {
-        format!("{}...", &s[..max_len - 3])
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::output::OutputFormat;
+
+    type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+    fn api_roots() -> Vec<ApiRootRecord> {
+        vec![
+            ApiRootRecord {
+                id: "9f1a7c3e-2b4d-4e6f-8a0b-1c2d3e4f5a6b".to_string(),
+                title: "Intel".to_string(),
+                description: Some("Shared feeds, partners".to_string()),
+                default: true,
+                is_public: false,
+            },
+            ApiRootRecord {
+                id: "0b8e5d2c-7a1f-4c3e-9d6b-5a4f3e2d1c0b".to_string(),
+                title: "Public".to_string(),
+                description: None,
+                default: false,
+                is_public: true,
+            },
+        ]
+    }
+
+    fn render(
+        format: OutputFormat,
+        columns: Option<&str>,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let columns = columns.map(|c| c.split(',').map(str::to_string).collect());
+        let mut out = Vec::new();
+        Output::new(format, columns).write_list(&mut out, &api_roots(), "No API roots found.")?;
+        Ok(String::from_utf8(out)?)
+    }
+
+    #[test]
+    fn test_api_root_list_table() -> TestResult {
+        assert_eq!(
+            render(OutputFormat::Table, None)?,
+            "\
+ID                                    Title   Description             Default  Public
+-------------------------------------------------------------------------------------
+9f1a7c3e-2b4d-4e6f-8a0b-1c2d3e4f5a6b  Intel   Shared feeds, partners  Yes      No
+0b8e5d2c-7a1f-4c3e-9d6b-5a4f3e2d1c0b  Public  -                       No       Yes
+"
+        );
+        assert_eq!(
+            render(OutputFormat::Table, Some("title,is_public"))?,
+            "\
+Title   Public
+--------------
+Intel   No
+Public  Yes
+"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_api_root_list_json() -> TestResult {
+        // Field names are part of the documented JSON output
+        assert_eq!(
+            render(OutputFormat::Json, Some("title"))?,
+            r#"[
+  {
+    "id": "9f1a7c3e-2b4d-4e6f-8a0b-1c2d3e4f5a6b",
+    "title": "Intel",
+    "description": "Shared feeds, partners",
+    "default": true,
+    "is_public": false
+  },
+  {
+    "id": "0b8e5d2c-7a1f-4c3e-9d6b-5a4f3e2d1c0b",
+    "title": "Public",
+    "description": null,
+    "default": false,
+    "is_public": true
+  }
+]
+"#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_api_root_list_csv() -> TestResult {
+        assert_eq!(
+            render(OutputFormat::Csv, None)?,
+            "\
+id,title,description,default,is_public
+9f1a7c3e-2b4d-4e6f-8a0b-1c2d3e4f5a6b,Intel,\"Shared feeds, partners\",true,false
+0b8e5d2c-7a1f-4c3e-9d6b-5a4f3e2d1c0b,Public,,false,true
+"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_csv() -> TestResult {
+        let records = [SearchRecord {
+            id: "indicator--8e2e2d2b-17d4-4cbf-938f-98ee46b3cd3f".to_string(),
+            object_type: "indicator".to_string(),
+            name: None,
+            date_added: DateTime::parse_from_rfc3339("2024-01-02T03:04:05Z")?.with_timezone(&Utc),
+        }];
+        let mut out = Vec::new();
+        Output::new(
+            OutputFormat::Csv,
+            Some(vec!["type".to_string(), "id".to_string()]),
+        )
+        .write_list(&mut out, &records, "No objects found.")?;
+        assert_eq!(
+            String::from_utf8(out)?,
+            "type,id\nindicator,indicator--8e2e2d2b-17d4-4cbf-938f-98ee46b3cd3f\n"
+        );
+        Ok(())
     }
 }
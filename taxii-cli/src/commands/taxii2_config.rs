@@ -0,0 +1,690 @@
+//! TAXII 2.x API roots and collections in sync configuration files.
+//!
+//! The `api_roots` section of a sync file declares API roots with their
+//! collections. API roots are matched by ID, or by title when the ID is
+//! left out; collections are matched by ID or alias within their API root.
+//! Unmatched entries are created, matched ones updated to the file. Account
+//! grants listed on a collection replace the grants of every account on it.
+
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+use taxii_auth::AuthAPI;
+use taxii_core::ApiRoot;
+use taxii_db::{Account, DbTaxii2Repository, Taxii2Repository, TaxiiPool};
+use tracing::{debug, info};
+use uuid::Uuid;
+
+use super::permissions::permission_value;
+
+/// TAXII 2.x API root from YAML.
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct ApiRootConfig {
+    /// API root ID (matched by title when unset).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Whether this is the default API root.
+    #[serde(default)]
+    pub is_default: bool,
+    #[serde(default)]
+    pub is_public: bool,
+    /// Largest POST body in bytes (unset uses the server limit).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_content_length: Option<u64>,
+    #[serde(default)]
+    pub collections: Vec<Taxii2CollectionConfig>,
+}
+
+/// TAXII 2.x collection from YAML.
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct Taxii2CollectionConfig {
+    /// Collection ID; a collection needs an ID, an alias or both.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub is_public: bool,
+    #[serde(default)]
+    pub is_public_write: bool,
+    /// Days objects are kept (unset keeps them).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retention_days: Option<i32>,
+    /// Accounts allowed to read (unset leaves grants untouched).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub can_read: Option<Vec<String>>,
+    /// Accounts allowed to write (unset leaves grants untouched).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub can_write: Option<Vec<String>>,
+}
+
+impl Taxii2CollectionConfig {
+    /// ID or alias, for messages.
+    fn key(&self) -> &str {
+        self.id
+            .as_deref()
+            .or(self.alias.as_deref())
+            .unwrap_or(&self.title)
+    }
+}
+
+/// Account grants of a synchronized collection.
+#[derive(Debug)]
+pub(crate) struct CollectionGrants {
+    collection_id: String,
+    can_read: HashSet<String>,
+    can_write: HashSet<String>,
+}
+
+/// Check API roots before any database change.
+///
+/// `usernames` are the accounts that exist once accounts are synchronized.
+pub(crate) fn validate_api_roots(
+    api_roots: &[ApiRootConfig],
+    usernames: &HashSet<&str>,
+) -> Result<(), String> {
+    let defaults = api_roots.iter().filter(|r| r.is_default).count();
+    if defaults > 1 {
+        return Err(format!(
+            "{defaults} API roots have is_default set, expected at most one"
+        ));
+    }
+
+    let mut root_keys = HashSet::new();
+    for api_root in api_roots {
+        if let Some(id) = &api_root.id {
+            Uuid::parse_str(id)
+                .map_err(|_| format!("API root '{}' has invalid id '{id}'", api_root.title))?;
+        }
+        let key = api_root.id.as_deref().unwrap_or(&api_root.title);
+        if !root_keys.insert(key) {
+            return Err(format!("API root '{key}' is listed more than once"));
+        }
+        if api_root.max_content_length == Some(0) {
+            return Err(format!(
+                "API root '{}' has invalid max_content_length, expected a positive number",
+                api_root.title
+            ));
+        }
+
+        let mut collection_keys = HashSet::new();
+        for collection in &api_root.collections {
+            if collection.id.is_none() && collection.alias.is_none() {
+                return Err(format!(
+                    "Collection '{}' in API root '{}' needs an id or an alias",
+                    collection.title, api_root.title
+                ));
+            }
+            if let Some(id) = &collection.id {
+                Uuid::parse_str(id).map_err(|_| {
+                    format!("Collection '{}' has invalid id '{id}'", collection.title)
+                })?;
+            }
+            let duplicate = [&collection.id, &collection.alias]
+                .into_iter()
+                .flatten()
+                .any(|key| !collection_keys.insert(key.as_str()));
+            if duplicate {
+                return Err(format!(
+                    "Collection '{}' is listed more than once in API root '{}'",
+                    collection.key(),
+                    api_root.title
+                ));
+            }
+            if collection.retention_days.is_some_and(|days| days <= 0) {
+                return Err(format!(
+                    "Collection '{}' has invalid retention_days, expected a positive number",
+                    collection.key()
+                ));
+            }
+            for username in collection
+                .can_read
+                .iter()
+                .chain(&collection.can_write)
+                .flatten()
+            {
+                if !usernames.contains(username.as_str()) {
+                    return Err(format!(
+                        "Collection '{}' grants access to unknown account '{username}'",
+                        collection.key()
+                    ));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Find the existing API root a configured one refers to.
+fn match_api_root<'a>(
+    existing: &'a [ApiRoot],
+    config: &ApiRootConfig,
+) -> Result<Option<&'a ApiRoot>, String> {
+    if let Some(id) = &config.id {
+        return Ok(existing.iter().find(|r| &r.id == id));
+    }
+
+    let mut matches = existing.iter().filter(|r| r.title == config.title);
+    match (matches.next(), matches.next()) {
+        (Some(_), Some(_)) => Err(format!(
+            "Several API roots are titled '{}', set the id of the one to sync",
+            config.title
+        )),
+        (found, _) => Ok(found),
+    }
+}
+
+/// Sync TAXII 2.x API roots and their collections.
+///
+/// Collections of a listed API root that are not in the file are kept,
+/// unless `force_delete` is set: then they are deleted with all their
+/// objects. API roots that are not listed are left untouched. Returns the
+/// grants to apply once accounts are synchronized.
+pub(crate) async fn sync_api_roots(
+    repo: &DbTaxii2Repository,
+    api_roots: &[ApiRootConfig],
+    force_delete: bool,
+) -> Result<Vec<CollectionGrants>, Box<dyn std::error::Error>> {
+    let existing = repo.get_api_roots().await?;
+
+    let mut created = 0;
+    let mut updated = 0;
+    let mut grants = Vec::new();
+
+    for root_config in api_roots {
+        let api_root = match match_api_root(&existing, root_config)? {
+            Some(api_root) => {
+                if api_root.title != root_config.title
+                    || api_root.description != root_config.description
+                    || api_root.default != root_config.is_default
+                    || api_root.is_public != root_config.is_public
+                    || api_root.max_content_length != root_config.max_content_length
+                {
+                    updated += 1;
+                    debug!(id = %api_root.id, "API root updated");
+                    update_api_root(repo, &api_root.id, root_config).await?
+                } else {
+                    api_root.clone()
+                }
+            }
+            None => {
+                let api_root = repo
+                    .add_api_root(
+                        &root_config.title,
+                        root_config.description.as_deref(),
+                        root_config.is_default,
+                        root_config.is_public,
+                        root_config.id.as_deref(),
+                    )
+                    .await?;
+                created += 1;
+                debug!(id = %api_root.id, "API root created");
+                if root_config.max_content_length.is_some() {
+                    update_api_root(repo, &api_root.id, root_config).await?
+                } else {
+                    api_root
+                }
+            }
+        };
+
+        grants.extend(
+            sync_collections(repo, &api_root, &root_config.collections, force_delete).await?,
+        );
+    }
+
+    info!(created, updated, "API roots synchronized");
+    Ok(grants)
+}
+
+/// Update an API root to its configuration.
+async fn update_api_root(
+    repo: &DbTaxii2Repository,
+    api_root_id: &str,
+    config: &ApiRootConfig,
+) -> Result<ApiRoot, Box<dyn std::error::Error>> {
+    Ok(repo
+        .update_api_root(
+            api_root_id,
+            &config.title,
+            config.description.as_deref(),
+            config.is_default,
+            config.is_public,
+            config.max_content_length,
+        )
+        .await?)
+}
+
+/// Sync the collections of an API root.
+async fn sync_collections(
+    repo: &DbTaxii2Repository,
+    api_root: &ApiRoot,
+    collections: &[Taxii2CollectionConfig],
+    force_delete: bool,
+) -> Result<Vec<CollectionGrants>, Box<dyn std::error::Error>> {
+    let existing = repo.get_collections(&api_root.id).await?;
+
+    let mut created = 0;
+    let mut updated = 0;
+    let mut matched = HashSet::new();
+    let mut grants = Vec::new();
+
+    for config in collections {
+        let found = existing.iter().find(|c| match &config.id {
+            Some(id) => &c.id == id,
+            None => c.alias == config.alias,
+        });
+
+        let collection = match found {
+            Some(collection) => {
+                let mut collection = collection.clone();
+                if collection.title != config.title
+                    || collection.description != config.description
+                    || collection.alias != config.alias
+                    || collection.is_public != config.is_public
+                    || collection.is_public_write != config.is_public_write
+                {
+                    collection = repo
+                        .update_collection(
+                            &collection.id,
+                            &config.title,
+                            config.description.as_deref(),
+                            config.alias.as_deref(),
+                            config.is_public,
+                            config.is_public_write,
+                        )
+                        .await?;
+                    updated += 1;
+                    debug!(id = %collection.id, "Collection updated");
+                }
+                collection
+            }
+            None => {
+                let collection = repo
+                    .add_collection(
+                        &api_root.id,
+                        &config.title,
+                        config.description.as_deref(),
+                        config.alias.as_deref(),
+                        config.is_public,
+                        config.is_public_write,
+                        config.id.as_deref(),
+                    )
+                    .await?;
+                created += 1;
+                debug!(id = %collection.id, "Collection created");
+                collection
+            }
+        };
+
+        if collection.retention_days != config.retention_days {
+            repo.set_collection_retention(&collection.id, config.retention_days)
+                .await?;
+        }
+
+        if config.can_read.is_some() || config.can_write.is_some() {
+            grants.push(CollectionGrants {
+                collection_id: collection.id.clone(),
+                can_read: config.can_read.iter().flatten().cloned().collect(),
+                can_write: config.can_write.iter().flatten().cloned().collect(),
+            });
+        }
+        matched.insert(collection.id);
+    }
+
+    let mut deleted = 0;
+    for collection in existing.iter().filter(|c| !matched.contains(&c.id)) {
+        if force_delete {
+            eprintln!(
+                "WARNING: deleting TAXII 2.x collection '{}' ({}) of API root '{}' \
+                 with all its objects. This cannot be undone.",
+                collection.title, collection.id, api_root.title
+            );
+            repo.delete_collection(&collection.id).await?;
+            deleted += 1;
+        } else {
+            eprintln!(
+                "Collection '{}' ({}) of API root '{}' is not in the configuration; \
+                 kept. Pass --force-delete to delete it with all its objects.",
+                collection.title, collection.id, api_root.title
+            );
+        }
+    }
+
+    info!(
+        api_root = %api_root.id,
+        created, updated, deleted, "TAXII 2.x collections synchronized"
+    );
+    Ok(grants)
+}
+
+/// Apply the account grants of synchronized collections.
+///
+/// Accounts listed get exactly the configured access; every other account
+/// loses its access to the collection.
+pub(crate) async fn apply_grants(
+    pool: &TaxiiPool,
+    auth_secret: &str,
+    grants: &[CollectionGrants],
+) -> Result<(), Box<dyn std::error::Error>> {
+    if grants.is_empty() {
+        return Ok(());
+    }
+
+    let auth = AuthAPI::new(pool.clone(), auth_secret.to_string(), None)?;
+    let mut updated = 0;
+
+    for mut account in auth.get_accounts().await? {
+        let mut changed = false;
+        for grant in grants {
+            let desired = permission_value(
+                grant.can_read.contains(&account.username),
+                grant.can_write.contains(&account.username),
+                false,
+            );
+            if account.permissions.get(&grant.collection_id) == desired.as_ref() {
+                continue;
+            }
+            match desired {
+                Some(value) => account
+                    .permissions
+                    .insert(grant.collection_id.clone(), value),
+                None => account.permissions.remove(&grant.collection_id),
+            };
+            changed = true;
+        }
+
+        if changed {
+            auth.update_account(&account, None).await?;
+            updated += 1;
+            debug!(username = %account.username, "Collection grants updated");
+        }
+    }
+
+    info!(updated, "Collection grants applied");
+    Ok(())
+}
+
+/// Document printed by `collection export-config`.
+#[derive(Debug, Serialize)]
+pub(crate) struct ExportedConfig {
+    api_roots: Vec<ApiRootConfig>,
+}
+
+/// Read the TAXII 2.x API roots, collections and account grants in the
+/// database as a sync configuration file.
+pub(crate) async fn export_config(
+    pool: &TaxiiPool,
+) -> Result<ExportedConfig, Box<dyn std::error::Error>> {
+    let repo = DbTaxii2Repository::new(pool.clone());
+    let accounts = Account::find_all(pool).await?;
+    let permissions: Vec<_> = accounts
+        .iter()
+        .map(|account| (account.username.as_str(), account.permissions()))
+        .collect();
+    let accounts_with = |collection_id: &str, allowed: fn(&taxii_core::PermissionValue) -> bool| {
+        let mut usernames: Vec<String> = permissions
+            .iter()
+            .filter(|(_, permissions)| permissions.get(collection_id).is_some_and(allowed))
+            .map(|(username, _)| username.to_string())
+            .collect();
+        usernames.sort();
+        usernames
+    };
+
+    let mut api_roots = Vec::new();
+    for api_root in repo.get_api_roots().await? {
+        let collections = repo
+            .get_collections(&api_root.id)
+            .await?
+            .into_iter()
+            .map(|collection| Taxii2CollectionConfig {
+                can_read: Some(accounts_with(&collection.id, |p| p.can_read())),
+                can_write: Some(accounts_with(&collection.id, |p| p.can_write())),
+                id: Some(collection.id),
+                alias: collection.alias,
+                title: collection.title,
+                description: collection.description,
+                is_public: collection.is_public,
+                is_public_write: collection.is_public_write,
+                retention_days: collection.retention_days,
+            })
+            .collect();
+
+        api_roots.push(ApiRootConfig {
+            id: Some(api_root.id),
+            title: api_root.title,
+            description: api_root.description,
+            is_default: api_root.default,
+            is_public: api_root.is_public,
+            max_content_length: api_root.max_content_length,
+            collections,
+        });
+    }
+
+    Ok(ExportedConfig { api_roots })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::persistence::handle_sync;
+    use sqlx::PgPool;
+    use taxii_core::PermissionValue;
+
+    type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+    const FEED_ID: &str = "6f1d2a3b-4c5d-4e6f-8a9b-0c1d2e3f4a5b";
+
+    fn parse(yaml: &str) -> Result<Vec<ApiRootConfig>, serde_yaml::Error> {
+        #[derive(Deserialize)]
+        struct File {
+            api_roots: Vec<ApiRootConfig>,
+        }
+        Ok(serde_yaml::from_str::<File>(yaml)?.api_roots)
+    }
+
+    #[test]
+    fn test_validate_api_roots() -> TestResult {
+        let usernames = HashSet::from(["alice"]);
+        let check = |yaml: &str| -> Result<Result<(), String>, serde_yaml::Error> {
+            Ok(validate_api_roots(&parse(yaml)?, &usernames))
+        };
+
+        check(
+            "api_roots:
+  - title: Intel
+    collections:
+      - alias: feed
+        title: Feed
+        can_read: [alice]",
+        )??;
+
+        assert_eq!(
+            check("api_roots: [{title: A, is_default: true}, {title: B, is_default: true}]")?,
+            Err("2 API roots have is_default set, expected at most one".to_string())
+        );
+        assert_eq!(
+            check("api_roots: [{title: Intel, collections: [{title: Feed}]}]")?,
+            Err("Collection 'Feed' in API root 'Intel' needs an id or an alias".to_string())
+        );
+        assert_eq!(
+            check(
+                "api_roots: [{title: Intel, collections: [{alias: feed, title: Feed}, {alias: feed, title: Other}]}]"
+            )?,
+            Err("Collection 'feed' is listed more than once in API root 'Intel'".to_string())
+        );
+        assert_eq!(
+            check(
+                "api_roots: [{title: Intel, collections: [{alias: feed, title: Feed, can_write: [bob]}]}]"
+            )?,
+            Err("Collection 'feed' grants access to unknown account 'bob'".to_string())
+        );
+        Ok(())
+    }
+
+    async fn sync(pool: &TaxiiPool, yaml: &str, force_delete: bool) -> TestResult {
+        let file = tempfile::NamedTempFile::new()?;
+        std::fs::write(file.path(), yaml)?;
+        let path = file.path().to_str().ok_or("temporary path is not UTF-8")?;
+        handle_sync(pool.clone(), "secret", path, force_delete).await
+    }
+
+    async fn rights(
+        auth: &AuthAPI,
+        username: &str,
+        collection_id: &str,
+    ) -> Result<Option<PermissionValue>, Box<dyn std::error::Error>> {
+        let accounts = auth.get_accounts().await?;
+        let account = accounts
+            .iter()
+            .find(|a| a.username == username)
+            .ok_or("account missing")?;
+        Ok(account.permissions.get(collection_id).cloned())
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_sync_api_roots(pool: PgPool) -> TestResult {
+        let pool = TaxiiPool::new(pool);
+        let repo = DbTaxii2Repository::new(pool.clone());
+        let auth = AuthAPI::new(pool.clone(), "secret".to_string(), None)?;
+        auth.create_account("alice", "correct horse battery", false)
+            .await?;
+        auth.create_account("bob", "correct horse battery", false)
+            .await?;
+
+        let config = format!(
+            "api_roots:
+  - title: Intel
+    description: Shared intelligence
+    is_default: true
+    max_content_length: 1048576
+    collections:
+      - id: {FEED_ID}
+        alias: feed
+        title: Feed
+        retention_days: 30
+        can_read: [alice, bob]
+        can_write: [alice]
+      - alias: archive
+        title: Archive
+        is_public: true
+"
+        );
+
+        // Applying the same file twice changes nothing the second time
+        sync(&pool, &config, false).await?;
+        let api_roots = repo.get_api_roots().await?;
+        let collections = repo.get_collections(&api_roots[0].id).await?;
+        sync(&pool, &config, false).await?;
+        assert_eq!(repo.get_api_roots().await?.len(), 1);
+        let again = repo.get_collections(&api_roots[0].id).await?;
+        assert_eq!(
+            again.iter().map(|c| &c.id).collect::<Vec<_>>(),
+            collections.iter().map(|c| &c.id).collect::<Vec<_>>()
+        );
+
+        let api_root = &api_roots[0];
+        assert_eq!(api_root.title, "Intel");
+        assert!(api_root.default);
+        assert_eq!(api_root.max_content_length, Some(1_048_576));
+        let feed = repo
+            .get_collection(&api_root.id, FEED_ID)
+            .await?
+            .ok_or("feed not created")?;
+        assert_eq!(feed.retention_days, Some(30));
+        let archive = repo
+            .get_collection(&api_root.id, "archive")
+            .await?
+            .ok_or("archive not created")?;
+        assert!(archive.is_public);
+
+        assert_eq!(
+            rights(&auth, "alice", FEED_ID).await?,
+            Some(PermissionValue::Taxii2(vec![
+                "read".to_string(),
+                "write".to_string()
+            ]))
+        );
+        assert_eq!(
+            rights(&auth, "bob", FEED_ID).await?,
+            Some(PermissionValue::Taxii2(vec!["read".to_string()]))
+        );
+
+        // The exported configuration describes the same state
+        let exported = parse(&serde_yaml::to_string(&export_config(&pool).await?)?)?;
+        assert_eq!(exported.len(), 1);
+        assert_eq!(exported[0].id.as_deref(), Some(api_root.id.as_str()));
+        let exported_feed = exported[0]
+            .collections
+            .iter()
+            .find(|c| c.alias.as_deref() == Some("feed"))
+            .ok_or("feed not exported")?;
+        assert_eq!(exported_feed.id.as_deref(), Some(FEED_ID));
+        assert_eq!(
+            exported_feed.can_read,
+            Some(vec!["alice".to_string(), "bob".to_string()])
+        );
+        assert_eq!(exported_feed.can_write, Some(vec!["alice".to_string()]));
+
+        // A modified file: renamed feed, bob promoted, alice removed,
+        // archive left out and no size limit
+        let modified = format!(
+            "api_roots:
+  - title: Intel
+    is_default: true
+    collections:
+      - id: {FEED_ID}
+        alias: feed
+        title: Indicators
+        can_read: [bob]
+        can_write: [bob]
+"
+        );
+        sync(&pool, &modified, false).await?;
+
+        let api_root = repo
+            .get_api_root(&api_root.id)
+            .await?
+            .ok_or("API root missing")?;
+        assert_eq!(api_root.description, None);
+        assert_eq!(api_root.max_content_length, None);
+        let feed = repo
+            .get_collection(&api_root.id, FEED_ID)
+            .await?
+            .ok_or("feed missing")?;
+        assert_eq!(feed.title, "Indicators");
+        assert_eq!(feed.retention_days, None);
+        assert_eq!(rights(&auth, "alice", FEED_ID).await?, None);
+        assert_eq!(
+            rights(&auth, "bob", FEED_ID).await?,
+            Some(PermissionValue::Taxii2(vec![
+                "read".to_string(),
+                "write".to_string()
+            ]))
+        );
+
+        // Collections missing from the file are only deleted when forced
+        assert!(
+            repo.get_collection(&api_root.id, "archive")
+                .await?
+                .is_some()
+        );
+        sync(&pool, &modified, true).await?;
+        assert!(
+            repo.get_collection(&api_root.id, "archive")
+                .await?
+                .is_none()
+        );
+        assert_eq!(repo.get_collections(&api_root.id).await?.len(), 1);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,979 @@
+//! Offline validation of STIX bundles.
+//!
+//! Files are read object by object, so large bundles are never held in
+//! memory. Every object is parsed as a typed STIX object and checked for
+//! identifier, spec version, custom content, pattern, vocabulary and
+//! timestamp issues; references are resolved against the objects of the
+//! same file once it has been read.
+
+use clap::Args;
+use serde::Serialize;
+use serde::de::{DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use stix2::v20::{Stix20Object, upgrade_to_v21};
+use stix2::validation::{ValidationContext, validate_id, validate_object, with_context};
+use stix2::vocab::{
+    AttackMotivation, AttackResourceLevel, GroupingContext, IdentityClass, IndicatorType,
+    IndustrySector, InfrastructureType, MalwareAnalysisResult, MalwareCapability, MalwareType,
+    ReportType, ThreatActorRole, ThreatActorSophistication, ThreatActorType, ToolType,
+};
+use stix2::{
+    Bundle, FindingSeverity, Identifier, NormalizeOptions, Pattern, SpecVersion, StixObject,
+    StixVersion, TlpLevel, detect_version, is_registered_type,
+};
+
+use super::output::{Output, OutputFormat, Record};
+
+/// Exit code when only warnings were found.
+pub const EXIT_WARNINGS: i32 = 1;
+
+/// Exit code when errors were found.
+pub const EXIT_ERRORS: i32 = 2;
+
+/// Standard values of an open vocabulary.
+type VocabularyValues = fn() -> &'static [&'static str];
+
+/// Open vocabulary properties checked for non-standard values.
+const VOCABULARIES: &[(&str, &str, VocabularyValues)] = &[
+    ("grouping", "context", GroupingContext::values),
+    ("identity", "identity_class", IdentityClass::values),
+    ("identity", "sectors", IndustrySector::values),
+    ("indicator", "indicator_types", IndicatorType::values),
+    (
+        "infrastructure",
+        "infrastructure_types",
+        InfrastructureType::values,
+    ),
+    (
+        "intrusion-set",
+        "resource_level",
+        AttackResourceLevel::values,
+    ),
+    (
+        "intrusion-set",
+        "primary_motivation",
+        AttackMotivation::values,
+    ),
+    (
+        "intrusion-set",
+        "secondary_motivations",
+        AttackMotivation::values,
+    ),
+    ("malware", "malware_types", MalwareType::values),
+    ("malware", "capabilities", MalwareCapability::values),
+    ("malware-analysis", "result", MalwareAnalysisResult::values),
+    ("report", "report_types", ReportType::values),
+    (
+        "threat-actor",
+        "threat_actor_types",
+        ThreatActorType::values,
+    ),
+    ("threat-actor", "roles", ThreatActorRole::values),
+    (
+        "threat-actor",
+        "sophistication",
+        ThreatActorSophistication::values,
+    ),
+    (
+        "threat-actor",
+        "resource_level",
+        AttackResourceLevel::values,
+    ),
+    (
+        "threat-actor",
+        "primary_motivation",
+        AttackMotivation::values,
+    ),
+    (
+        "threat-actor",
+        "secondary_motivations",
+        AttackMotivation::values,
+    ),
+    (
+        "threat-actor",
+        "personal_motivations",
+        AttackMotivation::values,
+    ),
+    ("tool", "tool_types", ToolType::values),
+];
+
+/// Arguments of `validate`.
+#[derive(Args)]
+pub struct ValidateArgs {
+    /// Bundle or envelope files, or directories of `.json` files.
+    #[arg(required = true)]
+    pub paths: Vec<PathBuf>,
+
+    /// STIX version the objects must conform to (2.0 or 2.1).
+    #[arg(long, default_value = "2.1", value_parser = parse_spec_version)]
+    pub spec_version: SpecVersion,
+
+    /// Accept custom object types and custom (`x_`) properties.
+    #[arg(long, default_value = "false")]
+    pub allow_custom: bool,
+
+    /// Only check the format of identifier UUIDs, not their version and
+    /// variant.
+    #[arg(long, default_value = "false")]
+    pub interoperability: bool,
+}
+
+/// What a finding is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Category {
+    /// The file could not be read as a bundle.
+    File,
+    /// The object is not a valid STIX object.
+    Parse,
+    /// The identifier is malformed.
+    Id,
+    /// The object is of another STIX version.
+    SpecVersion,
+    /// Custom object type or property.
+    Custom,
+    /// The indicator pattern is not valid STIX pattern syntax.
+    Pattern,
+    /// A value outside an open vocabulary.
+    Vocabulary,
+    /// A reference to an object not in the file.
+    Reference,
+    /// The same object version occurs more than once.
+    Duplicate,
+    /// A correction normalization would make.
+    Normalization,
+}
+
+impl Category {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::File => "file",
+            Self::Parse => "parse",
+            Self::Id => "id",
+            Self::SpecVersion => "spec_version",
+            Self::Custom => "custom",
+            Self::Pattern => "pattern",
+            Self::Vocabulary => "vocabulary",
+            Self::Reference => "reference",
+            Self::Duplicate => "duplicate",
+            Self::Normalization => "normalization",
+        }
+    }
+}
+
+/// A problem found in a file.
+#[derive(Debug, Clone, Serialize)]
+struct Finding {
+    /// Object concerned, `None` for problems with the file itself.
+    object: Option<String>,
+    #[serde(serialize_with = "serialize_severity")]
+    severity: FindingSeverity,
+    category: Category,
+    message: String,
+}
+
+fn serialize_severity<S: serde::Serializer>(
+    severity: &FindingSeverity,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(severity_name(*severity))
+}
+
+fn severity_name(severity: FindingSeverity) -> &'static str {
+    match severity {
+        FindingSeverity::Warning => "warning",
+        FindingSeverity::Error => "error",
+    }
+}
+
+/// A finding with its file, as a row of `--output csv`.
+#[derive(Debug, Serialize)]
+struct FindingRecord<'a> {
+    file: &'a Path,
+    #[serde(flatten)]
+    finding: &'a Finding,
+}
+
+impl Record for FindingRecord<'_> {
+    const COLUMNS: &'static [(&'static str, &'static str)] = &[
+        ("file", "File"),
+        ("object", "Object"),
+        ("severity", "Severity"),
+        ("category", "Category"),
+        ("message", "Message"),
+    ];
+}
+
+/// Findings of one file.
+#[derive(Debug, Serialize)]
+struct FileReport {
+    path: PathBuf,
+    /// Objects read.
+    objects: usize,
+    findings: Vec<Finding>,
+}
+
+impl FileReport {
+    fn count(&self, severity: FindingSeverity) -> usize {
+        self.findings
+            .iter()
+            .filter(|finding| finding.severity == severity)
+            .count()
+    }
+}
+
+/// Report of a run, as printed by `--output json`.
+#[derive(Debug, Serialize)]
+struct Report {
+    files: Vec<FileReport>,
+    errors: usize,
+    warnings: usize,
+}
+
+impl Report {
+    fn new(files: Vec<FileReport>) -> Self {
+        let errors = files
+            .iter()
+            .map(|file| file.count(FindingSeverity::Error))
+            .sum();
+        let warnings = files
+            .iter()
+            .map(|file| file.count(FindingSeverity::Warning))
+            .sum();
+        Self {
+            files,
+            errors,
+            warnings,
+        }
+    }
+
+    /// Exit code reflecting the most serious finding.
+    fn exit_code(&self) -> i32 {
+        if self.errors > 0 {
+            EXIT_ERRORS
+        } else if self.warnings > 0 {
+            EXIT_WARNINGS
+        } else {
+            0
+        }
+    }
+
+    /// Every finding with its file.
+    fn records(&self) -> Vec<FindingRecord<'_>> {
+        self.files
+            .iter()
+            .flat_map(|file| {
+                file.findings.iter().map(|finding| FindingRecord {
+                    file: &file.path,
+                    finding,
+                })
+            })
+            .collect()
+    }
+
+    fn write_text(&self, out: &mut impl Write) -> io::Result<()> {
+        for file in &self.files {
+            writeln!(out, "{} ({} objects)", file.path.display(), file.objects)?;
+            if file.findings.is_empty() {
+                writeln!(out, "  No findings")?;
+            }
+            for findings in file.findings.chunk_by(|a, b| a.object == b.object) {
+                let indent = match &findings[0].object {
+                    Some(object) => {
+                        writeln!(out, "  {object}")?;
+                        "    "
+                    }
+                    None => "  ",
+                };
+                for finding in findings {
+                    writeln!(
+                        out,
+                        "{indent}{:<8} {:<13} {}",
+                        severity_name(finding.severity),
+                        finding.category.as_str(),
+                        finding.message
+                    )?;
+                }
+            }
+        }
+
+        let objects: usize = self.files.iter().map(|file| file.objects).sum();
+        writeln!(out)?;
+        writeln!(
+            out,
+            "Checked {} files, {objects} objects: {} errors, {} warnings",
+            self.files.len(),
+            self.errors,
+            self.warnings
+        )
+    }
+}
+
+/// Validate the files and print the findings: grouped by file and object
+/// in a table, one line per finding in CSV, or the whole report as JSON.
+///
+/// Returns the exit code: 0 without findings, [`EXIT_WARNINGS`] when the
+/// most serious finding is a warning and [`EXIT_ERRORS`] otherwise.
+pub fn handle(args: &ValidateArgs, output: &Output) -> Result<i32, Box<dyn std::error::Error>> {
+    let context = ValidationContext::strict()
+        .spec_version(args.spec_version)
+        .allow_custom(args.allow_custom)
+        .interoperability(args.interoperability);
+
+    let mut files = Vec::new();
+    for path in &args.paths {
+        collect_files(path, &mut files)?;
+    }
+    let report = Report::new(
+        files
+            .into_iter()
+            .map(|path| validate_file(path, &context))
+            .collect(),
+    );
+
+    if output.format() == OutputFormat::Csv {
+        output.list(&report.records(), "")?;
+    } else {
+        let mut text = Vec::new();
+        report.write_text(&mut text)?;
+        output.document(&report, &String::from_utf8(text)?)?;
+    }
+    Ok(report.exit_code())
+}
+
+/// Parse a STIX version argument.
+fn parse_spec_version(value: &str) -> Result<SpecVersion, String> {
+    value
+        .parse()
+        .map_err(|_| format!("unknown STIX version '{value}' (expected 2.0 or 2.1)"))
+}
+
+/// Add a file, or the `.json` files below a directory, in name order.
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    if !path.is_dir() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+
+    let mut entries = fs::read_dir(path)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() {
+            collect_files(&entry, files)?;
+        } else if entry.extension().is_some_and(|ext| ext == "json") {
+            files.push(entry);
+        }
+    }
+    Ok(())
+}
+
+/// Validate the objects of a bundle or envelope file.
+fn validate_file(path: PathBuf, context: &ValidationContext) -> FileReport {
+    let mut checker = Checker::new(context);
+    let read = File::open(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|file| {
+            with_context(context.clone(), || {
+                for_each_object(BufReader::new(file), &mut |object| checker.check(object))
+            })
+            .map_err(|e| format!("invalid JSON: {e}"))
+        });
+
+    let file_error = match read {
+        Ok(true) => None,
+        Ok(false) => Some("no objects array".to_string()),
+        Err(e) => Some(e),
+    };
+    let mut report = checker.finish(path);
+    if let Some(message) = file_error {
+        report.findings.insert(
+            0,
+            Finding {
+                object: None,
+                severity: FindingSeverity::Error,
+                category: Category::File,
+                message,
+            },
+        );
+    }
+    report
+}
+
+/// Call `visit` with every object of a bundle or envelope as it is read.
+///
+/// Returns whether the document has an `objects` array.
+fn for_each_object<R: Read>(
+    reader: R,
+    visit: &mut dyn FnMut(Value),
+) -> Result<bool, serde_json::Error> {
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    let found = Document(visit).deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    Ok(found)
+}
+
+/// Top-level document, handing the elements of `objects` to a callback.
+struct Document<'a>(&'a mut dyn FnMut(Value));
+
+impl<'de> DeserializeSeed<'de> for Document<'_> {
+    type Value = bool;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<bool, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for Document<'_> {
+    type Value = bool;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a STIX bundle or TAXII envelope")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<bool, A::Error> {
+        let mut found = false;
+        while let Some(key) = map.next_key::<String>()? {
+            if key == "objects" {
+                map.next_value_seed(Objects(&mut *self.0))?;
+                found = true;
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        Ok(found)
+    }
+}
+
+/// The `objects` array of a document.
+struct Objects<'a>(&'a mut dyn FnMut(Value));
+
+impl<'de> DeserializeSeed<'de> for Objects<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for Objects<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("an array of STIX objects")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while let Some(object) = seq.next_element::<Value>()? {
+            (self.0)(object);
+        }
+        Ok(())
+    }
+}
+
+/// Checks the objects of one file.
+struct Checker<'a> {
+    context: &'a ValidationContext,
+    objects: usize,
+    findings: Vec<Finding>,
+    /// IDs of the objects read.
+    ids: HashSet<String>,
+    /// Object versions read, to find duplicates.
+    versions: HashSet<(String, String)>,
+    /// References made: object, property and referenced ID.
+    references: Vec<(String, String, String)>,
+}
+
+impl<'a> Checker<'a> {
+    fn new(context: &'a ValidationContext) -> Self {
+        Self {
+            context,
+            objects: 0,
+            findings: Vec::new(),
+            ids: HashSet::new(),
+            versions: HashSet::new(),
+            references: Vec::new(),
+        }
+    }
+
+    fn push(
+        &mut self,
+        object: &str,
+        severity: FindingSeverity,
+        category: Category,
+        message: String,
+    ) {
+        self.findings.push(Finding {
+            object: Some(object.to_string()),
+            severity,
+            category,
+            message,
+        });
+    }
+
+    /// Check an object, remembering its ID and references.
+    fn check(&mut self, object: Value) {
+        self.objects += 1;
+        let label = object["id"]
+            .as_str()
+            .map_or_else(|| format!("object {}", self.objects), String::from);
+        let object_type = object["type"].as_str().unwrap_or_default();
+
+        if let Some(id) = object["id"].as_str()
+            && let Err(e) = validate_id(
+                id,
+                self.context.spec_version,
+                Some(&format!("{object_type}--")),
+                self.context.interoperability,
+            )
+        {
+            self.push(&label, FindingSeverity::Error, Category::Id, e.to_string());
+        }
+
+        self.check_spec_version(&label, &object);
+        let pattern_error = self.check_pattern(&label, &object);
+
+        match self.parse(&object) {
+            Ok(parsed) => self.check_normalization(&label, parsed),
+            // An invalid pattern also fails parsing, with the same message
+            Err(e)
+                if pattern_error
+                    .as_ref()
+                    .is_some_and(|p| e.contains(p.as_str())) => {}
+            Err(e) => self.push(&label, FindingSeverity::Error, Category::Parse, e),
+        }
+
+        self.check_custom(&label, object_type, &object);
+        self.check_vocabularies(&label, object_type, &object);
+
+        if let Some(id) = object["id"].as_str() {
+            let version = object["modified"]
+                .as_str()
+                .or(object["created"].as_str())
+                .unwrap_or_default();
+            if !self.versions.insert((id.to_string(), version.to_string())) {
+                self.push(
+                    &label,
+                    FindingSeverity::Warning,
+                    Category::Duplicate,
+                    "same version occurs earlier in the file".to_string(),
+                );
+            }
+            self.ids.insert(id.to_string());
+        }
+
+        let mut references = Vec::new();
+        collect_references(&object, "", &mut references);
+        self.references.extend(
+            references
+                .into_iter()
+                .map(|(property, target)| (label.clone(), property, target)),
+        );
+    }
+
+    /// Parse the object as a typed object of the requested version.
+    ///
+    /// Deserialization stops at the first invalid property, so this returns
+    /// a single error per object.
+    fn parse(&self, object: &Value) -> Result<StixObject, String> {
+        let parsed = match self.context.spec_version {
+            SpecVersion::V20 => upgrade_to_v21(&Stix20Object {
+                value: object.clone(),
+            }),
+            SpecVersion::V21 => validate_object(object, None),
+        };
+        parsed.map_err(|e| e.to_string())
+    }
+
+    fn check_spec_version(&mut self, label: &str, object: &Value) {
+        let (expected, found) = match (self.context.spec_version, detect_version(object)) {
+            (SpecVersion::V20, StixVersion::V21) => ("2.0", "2.1"),
+            (SpecVersion::V21, StixVersion::V20) => ("2.1", "2.0"),
+            _ => return,
+        };
+        self.push(
+            label,
+            FindingSeverity::Error,
+            Category::SpecVersion,
+            format!("object is STIX {found}, expected {expected}"),
+        );
+    }
+
+    /// Check the syntax of a STIX indicator pattern, returning the error.
+    fn check_pattern(&mut self, label: &str, object: &Value) -> Option<String> {
+        if object["type"] != "indicator" || object["pattern_type"] != "stix" {
+            return None;
+        }
+        let pattern = object["pattern"].as_str()?;
+        let error = Pattern::new(pattern).validate().err()?.to_string();
+        self.push(
+            label,
+            FindingSeverity::Error,
+            Category::Pattern,
+            error.clone(),
+        );
+        Some(error)
+    }
+
+    /// Report the corrections normalizing the object would make.
+    fn check_normalization(&mut self, label: &str, object: StixObject) {
+        let (_, findings) =
+            Bundle::from_objects(vec![object]).normalize(&NormalizeOptions::default());
+        for finding in findings {
+            self.push(
+                label,
+                finding.severity,
+                Category::Normalization,
+                finding.message,
+            );
+        }
+    }
+
+    fn check_custom(&mut self, label: &str, object_type: &str, object: &Value) {
+        if self.context.allow_custom {
+            return;
+        }
+        if !object_type.is_empty() && !is_registered_type(object_type, self.context.spec_version) {
+            self.push(
+                label,
+                FindingSeverity::Error,
+                Category::Custom,
+                format!("custom object type '{object_type}'"),
+            );
+        }
+        let custom_properties: Vec<String> = object
+            .as_object()
+            .into_iter()
+            .flat_map(|properties| properties.keys())
+            .filter(|property| property.starts_with("x_"))
+            .cloned()
+            .collect();
+        for property in custom_properties {
+            self.push(
+                label,
+                FindingSeverity::Error,
+                Category::Custom,
+                format!("custom property '{property}'"),
+            );
+        }
+    }
+
+    fn check_vocabularies(&mut self, label: &str, object_type: &str, object: &Value) {
+        for (vocab_type, property, values) in VOCABULARIES {
+            if *vocab_type != object_type {
+                continue;
+            }
+            let found: Vec<&str> = match &object[*property] {
+                Value::String(value) => vec![value.as_str()],
+                Value::Array(items) => items.iter().filter_map(Value::as_str).collect(),
+                _ => continue,
+            };
+            for value in found {
+                if !values().contains(&value) {
+                    self.push(
+                        label,
+                        FindingSeverity::Warning,
+                        Category::Vocabulary,
+                        format!("{property} value '{value}' is not in the standard vocabulary"),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Report unresolved references and return the findings of the file,
+    /// grouped by object in the order the objects were read.
+    fn finish(mut self, path: PathBuf) -> FileReport {
+        for (label, property, target) in std::mem::take(&mut self.references) {
+            if self.ids.contains(&target) || is_tlp_marking(&target) {
+                continue;
+            }
+            self.push(
+                &label,
+                FindingSeverity::Warning,
+                Category::Reference,
+                format!("{property} references {target}, which is not in the file"),
+            );
+        }
+
+        let mut order: HashMap<Option<String>, usize> = HashMap::new();
+        for finding in &self.findings {
+            let next = order.len();
+            order.entry(finding.object.clone()).or_insert(next);
+        }
+        self.findings
+            .sort_by_key(|finding| order.get(&finding.object).copied());
+
+        FileReport {
+            path,
+            objects: self.objects,
+            findings: self.findings,
+        }
+    }
+}
+
+/// Collect the identifiers in `*_ref` and `*_refs` properties at any depth,
+/// with the path of the property.
+fn collect_references(value: &Value, path: &str, references: &mut Vec<(String, String)>) {
+    match value {
+        Value::Object(properties) => {
+            for (key, item) in properties {
+                let property = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{path}.{key}")
+                };
+                match item {
+                    Value::String(target) if key.ends_with("_ref") => {
+                        references.push((property, target.clone()));
+                    }
+                    Value::Array(targets) if key.ends_with("_refs") => references.extend(
+                        targets
+                            .iter()
+                            .filter_map(Value::as_str)
+                            .map(|target| (property.clone(), target.to_string())),
+                    ),
+                    _ => collect_references(item, &property, references),
+                }
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                collect_references(item, path, references);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Whether an ID is one of the predefined TLP marking definitions.
+fn is_tlp_marking(id: &str) -> bool {
+    id.parse::<Identifier>()
+        .is_ok_and(|id| TlpLevel::from_marking_definition_id(&id).is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+    const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/validate");
+
+    fn args(spec_version: SpecVersion, allow_custom: bool, interoperability: bool) -> ValidateArgs {
+        ValidateArgs {
+            paths: vec![PathBuf::from(FIXTURES)],
+            spec_version,
+            allow_custom,
+            interoperability,
+        }
+    }
+
+    fn context(args: &ValidateArgs) -> ValidationContext {
+        ValidationContext::strict()
+            .spec_version(args.spec_version)
+            .allow_custom(args.allow_custom)
+            .interoperability(args.interoperability)
+    }
+
+    fn validate(name: &str, args: &ValidateArgs) -> FileReport {
+        validate_file(Path::new(FIXTURES).join(name), &context(args))
+    }
+
+    /// Categories found for an object.
+    fn categories(report: &FileReport, object: &str) -> Vec<(Category, FindingSeverity)> {
+        report
+            .findings
+            .iter()
+            .filter(|finding| finding.object.as_deref() == Some(object))
+            .map(|finding| (finding.category, finding.severity))
+            .collect()
+    }
+
+    #[test]
+    fn test_valid_bundle() {
+        let report = validate("valid.json", &args(SpecVersion::V21, false, false));
+        assert_eq!(report.objects, 3);
+        assert!(report.findings.is_empty(), "{:?}", report.findings);
+    }
+
+    #[test]
+    fn test_finding_categories() -> TestResult {
+        use Category::{
+            Custom, Duplicate, Id, Normalization, Parse, Pattern, Reference, Vocabulary,
+        };
+        use FindingSeverity::{Error, Warning};
+
+        let report = validate("findings.json", &args(SpecVersion::V21, false, false));
+        assert_eq!(report.objects, 11);
+        let expected: &[(&str, &[(Category, FindingSeverity)])] = &[
+            (
+                "malware--0c7b5b88-8ff7-4a4d-aa9d-feb398cd0061",
+                &[(Parse, Error)],
+            ),
+            (
+                "identity--00000000-0000-0000-0000-000000000000",
+                &[(Id, Error)],
+            ),
+            (
+                "indicator--a932fcc6-e032-476c-826f-cb970a5a1ade",
+                &[(Category::SpecVersion, Error), (Parse, Error)],
+            ),
+            (
+                "x-acme-sensor--2b3f4a5c-6d7e-4f80-9a1b-2c3d4e5f6a7b",
+                &[(Custom, Error)],
+            ),
+            (
+                "tool--5a6b7c8d-9e0f-4a1b-8c2d-3e4f5a6b7c8d",
+                &[(Custom, Error)],
+            ),
+            (
+                "indicator--1d2e3f4a-5b6c-4d7e-8f9a-0b1c2d3e4f5a",
+                &[(Pattern, Error)],
+            ),
+            (
+                "malware--31b940d4-6f7f-459a-80ea-9c1f17b5891b",
+                &[(Vocabulary, Warning), (Reference, Warning)],
+            ),
+            (
+                "relationship--7e8f9a0b-1c2d-4e3f-8a4b-5c6d7e8f9a0b",
+                &[(Reference, Warning)],
+            ),
+            (
+                "campaign--8f9a0b1c-2d3e-4f4a-9b5c-6d7e8f9a0b1c",
+                &[(Duplicate, Warning)],
+            ),
+            (
+                "threat-actor--9a0b1c2d-3e4f-4a5b-8c6d-7e8f9a0b1c2d",
+                &[(Normalization, Warning), (Normalization, Warning)],
+            ),
+        ];
+        for (object, found) in expected {
+            assert_eq!(&categories(&report, object), found, "{object}");
+        }
+        assert_eq!(report.count(FindingSeverity::Error), 7);
+        assert_eq!(report.count(FindingSeverity::Warning), 6);
+
+        // Findings are grouped by object, references included
+        let groups = report
+            .findings
+            .chunk_by(|a, b| a.object == b.object)
+            .count();
+        assert_eq!(groups, expected.len());
+
+        let report = Report::new(vec![report]);
+        assert_eq!(report.exit_code(), EXIT_ERRORS);
+        let json = serde_json::to_value(&report)?;
+        assert_eq!(json["errors"], 7);
+        assert_eq!(json["files"][0]["findings"][0]["severity"], "error");
+        assert_eq!(json["files"][0]["findings"][0]["category"], "parse");
+        Ok(())
+    }
+
+    #[test]
+    fn test_flags() {
+        let report = validate("findings.json", &args(SpecVersion::V21, true, true));
+        assert!(
+            report
+                .findings
+                .iter()
+                .all(|finding| !matches!(finding.category, Category::Custom | Category::Id))
+        );
+
+        // Against 2.0, every 2.1 object is of the wrong version
+        let report = validate("valid.json", &args(SpecVersion::V20, false, false));
+        assert_eq!(
+            report
+                .findings
+                .iter()
+                .filter(|finding| finding.category == Category::SpecVersion)
+                .count(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_file_findings() -> TestResult {
+        let args = args(SpecVersion::V21, false, false);
+        let report = validate("truncated.json", &args);
+        assert_eq!(report.objects, 1);
+        assert_eq!(report.findings[0].category, Category::File);
+        assert!(report.findings[0].object.is_none());
+
+        let report = validate_file(Path::new(FIXTURES).join("missing.json"), &context(&args));
+        assert_eq!(report.findings[0].category, Category::File);
+
+        let report = Report::new(vec![validate("warnings.json", &args)]);
+        assert_eq!(report.errors, 0);
+        assert_eq!(report.exit_code(), EXIT_WARNINGS);
+
+        let mut text = Vec::new();
+        report.write_text(&mut text)?;
+        let text = String::from_utf8(text)?;
+        assert!(text.contains("  indicator--"));
+        assert!(text.ends_with("1 files, 1 objects: 0 errors, 1 warnings\n"));
+
+        // CSV rows carry the file of each finding
+        let records = serde_json::to_value(report.records())?;
+        assert_eq!(records[0]["file"], format!("{FIXTURES}/warnings.json"));
+        assert_eq!(records[0]["severity"], "warning");
+        assert!(records[0]["object"].as_str().is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_collect_files() -> TestResult {
+        let mut files = Vec::new();
+        collect_files(Path::new(FIXTURES), &mut files)?;
+        let names: Vec<_> = files
+            .iter()
+            .filter_map(|path| path.file_name()?.to_str())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "findings.json",
+                "truncated.json",
+                "valid.json",
+                "warnings.json"
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_collect_references() {
+        let object = serde_json::json!({
+            "created_by_ref": "identity--1",
+            "object_marking_refs": ["marking-definition--2"],
+            "extensions": {"ext": {"body_ref": "artifact--3"}},
+            "id": "indicator--4"
+        });
+        let mut references = Vec::new();
+        collect_references(&object, "", &mut references);
+        references.sort();
+        assert_eq!(
+            references,
+            [
+                ("created_by_ref".to_string(), "identity--1".to_string()),
+                (
+                    "extensions.ext.body_ref".to_string(),
+                    "artifact--3".to_string()
+                ),
+                (
+                    "object_marking_refs".to_string(),
+                    "marking-definition--2".to_string()
+                ),
+            ]
+        );
+        assert!(is_tlp_marking(
+            "marking-definition--613f2e26-407d-48c7-9eca-b8e91df99dc9"
+        ));
+    }
+}
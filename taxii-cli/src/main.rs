@@ -7,15 +7,16 @@
 
 mod commands;
 
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 use serde::Deserialize;
 use std::env;
 use std::path::Path;
-use tracing::error;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use tracing_subscriber::{Layer, layer::SubscriberExt, util::SubscriberInitExt};
 
 use taxii_db::TaxiiPool;
 
+use commands::output::{Output, OutputFormat};
+
 /// Environment variable prefix for overrides.
 const ENV_PREFIX: &str = "DARWIS_TAXII_";
 
@@ -70,10 +71,36 @@ struct Cli {
     #[arg(short, long, default_value = "false")]
     verbose: bool,
 
+    /// Format of log output.
+    #[arg(
+        long,
+        value_enum,
+        env = "DARWIS_TAXII_LOG_FORMAT",
+        default_value = "text"
+    )]
+    log_format: LogFormat,
+
+    /// Format of command results.
+    #[arg(long, value_enum, default_value = "table")]
+    output: OutputFormat,
+
+    /// Columns to show in table and CSV output, comma-separated.
+    #[arg(long, value_delimiter = ',')]
+    columns: Option<Vec<String>>,
+
     #[command(subcommand)]
     command: Commands,
 }
 
+/// Format of log output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum LogFormat {
+    /// Human-readable lines.
+    Text,
+    /// One JSON object per line.
+    Json,
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// Manage user accounts.
@@ -88,6 +115,12 @@ enum Commands {
         action: commands::activity::ActivityAction,
     },
 
+    /// View the audit log of TAXII 2.x object changes.
+    Audit {
+        #[command(subcommand)]
+        action: commands::audit::AuditAction,
+    },
+
     /// Run database migrations.
     Migrate {
         #[command(subcommand)]
@@ -98,15 +131,26 @@ enum Commands {
     Sync {
         /// Path to YAML configuration file.
         config: String,
+
+        /// Delete TAXII 2.x collections of the configured API roots that are
+        /// not in the file, with all their objects.
+        #[arg(long, default_value = "false")]
+        force_delete: bool,
     },
 
-    /// Delete content blocks from collections.
+    /// Delete content blocks and revoke objects in collections.
     #[command(name = "content")]
     Content {
         #[command(subcommand)]
         action: commands::persistence::ContentAction,
     },
 
+    /// Inspect TAXII 1.x subscriptions.
+    Subscription {
+        #[command(subcommand)]
+        action: commands::persistence::SubscriptionAction,
+    },
+
     /// Manage TAXII 2.x API roots.
     #[command(name = "api-root")]
     ApiRoot {
@@ -126,6 +170,9 @@ enum Commands {
         #[command(subcommand)]
         action: commands::taxii2::JobAction,
     },
+
+    /// Check STIX bundles offline, without a database.
+    Validate(commands::validate::ValidateArgs),
 }
 
 /// Resolved configuration after merging TOML, env, and CLI args.
@@ -199,21 +246,49 @@ impl ResolvedConfig {
 
 #[tokio::main]
 async fn main() {
-    // Initialize tracing
+    let cli = Cli::parse();
+
+    // Initialize tracing; logs go to stderr, keeping stdout for results
+    let fmt_layer = match cli.log_format {
+        LogFormat::Text => tracing_subscriber::fmt::layer()
+            .with_writer(std::io::stderr)
+            .boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer()
+            .with_writer(std::io::stderr)
+            .json()
+            .flatten_event(true)
+            .boxed(),
+    };
+    // Without RUST_LOG, notices of the CLI itself are shown
+    let filter = tracing_subscriber::EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("warn,taxii_cli=info"));
     tracing_subscriber::registry()
-        .with(tracing_subscriber::fmt::layer())
-        .with(tracing_subscriber::EnvFilter::from_default_env())
+        .with(fmt_layer)
+        .with(filter)
         .init();
 
-    let cli = Cli::parse();
-
-    if let Err(e) = run(cli).await {
-        error!("Error: {}", e);
+    let output = Output::new(cli.output, cli.columns.clone());
+    if let Err(e) = run(cli, &output).await {
+        output.error(&*e);
         std::process::exit(1);
     }
 }
 
-async fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
+async fn run(cli: Cli, output: &Output) -> Result<(), Box<dyn std::error::Error>> {
+    // Validation is offline, and its exit code reflects the findings
+    if let Commands::Validate(args) = &cli.command {
+        std::process::exit(commands::validate::handle(args, output)?);
+    }
+
+    // Pushing to another server needs no database
+    if let Commands::Content {
+        action: commands::persistence::ContentAction::Push(args),
+    } = &cli.command
+        && let Some(url) = &args.remote
+    {
+        return commands::push::push_to_remote(url, args, output).await;
+    }
+
     // Load and resolve configuration
     let config = ResolvedConfig::load(&cli)?;
 
@@ -222,31 +297,45 @@ async fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
 
     match cli.command {
         Commands::Account { action } => {
-            commands::account::handle(pool, &config.auth_secret, action).await?;
+            commands::account::handle(pool, &config.auth_secret, action, output).await?;
         }
         Commands::Activity { action } => {
-            commands::activity::handle(pool, action).await?;
+            commands::activity::handle(pool, action, output).await?;
+        }
+        Commands::Audit { action } => {
+            commands::audit::handle(pool, action, output).await?;
         }
         Commands::Migrate { action } => {
-            commands::migrate::handle(pool, action).await?;
+            commands::migrate::handle(pool, action, output).await?;
         }
         Commands::Sync {
             config: yaml_config,
+            force_delete,
         } => {
-            commands::persistence::handle_sync(pool, &config.auth_secret, &yaml_config).await?;
+            commands::persistence::handle_sync(
+                pool,
+                &config.auth_secret,
+                &yaml_config,
+                force_delete,
+            )
+            .await?;
         }
         Commands::Content { action } => {
-            commands::persistence::handle_content(pool, action).await?;
+            commands::persistence::handle_content(pool, action, output).await?;
+        }
+        Commands::Subscription { action } => {
+            commands::persistence::handle_subscription(pool, action, output).await?;
         }
         Commands::ApiRoot { action } => {
-            commands::taxii2::handle_api_root(pool, action).await?;
+            commands::taxii2::handle_api_root(pool, action, output).await?;
         }
         Commands::Collection { action } => {
-            commands::taxii2::handle_collection(pool, action).await?;
+            commands::taxii2::handle_collection(pool, action, output).await?;
         }
         Commands::Job { action } => {
-            commands::taxii2::handle_job(pool, action).await?;
+            commands::taxii2::handle_job(pool, action, output).await?;
         }
+        Commands::Validate(_) => {}
     }
 
     Ok(())
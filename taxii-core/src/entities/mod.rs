@@ -3,8 +3,11 @@
 pub mod taxii1;
 pub mod taxii2;
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use stix2::Identity;
 
 /// Permission value that supports both TAXII 1.x and 2.x formats.
 ///
@@ -47,6 +50,91 @@ impl PermissionValue {
     }
 }
 
+/// Access level granted by a token scope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScopeAccess {
+    /// Read-only access.
+    Read,
+    /// Read and write access.
+    Write,
+}
+
+impl ScopeAccess {
+    /// Get the access level as a string.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScopeAccess::Read => "read",
+            ScopeAccess::Write => "write",
+        }
+    }
+}
+
+/// Restriction carried by a down-scoped token.
+///
+/// A scope can only narrow access: the effective permission is the
+/// intersection of the scope and the account's own permissions (or admin
+/// rights), never more than either.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenScope {
+    /// Collections the token may access, by permission key (TAXII 2.x
+    /// collection UUID or TAXII 1.x collection name). `None` means all
+    /// collections the account can access.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collections: Option<Vec<String>>,
+
+    /// Access level.
+    pub access: ScopeAccess,
+}
+
+impl TokenScope {
+    /// Scope granting read-only access to all collections.
+    pub fn read_only() -> Self {
+        Self {
+            collections: None,
+            access: ScopeAccess::Read,
+        }
+    }
+
+    /// Scope limited to the given collections.
+    pub fn for_collections(collections: Vec<String>, access: ScopeAccess) -> Self {
+        Self {
+            collections: Some(collections),
+            access,
+        }
+    }
+
+    /// Check whether a collection is within the scope.
+    pub fn includes_collection(&self, collection_key: &str) -> bool {
+        self.collections
+            .as_ref()
+            .is_none_or(|collections| collections.iter().any(|c| c == collection_key))
+    }
+
+    /// Describe the scope missing for an access to a collection.
+    ///
+    /// Returns `None` if the scope permits the access.
+    pub fn missing_for(&self, collection_key: &str, access: ScopeAccess) -> Option<String> {
+        if !self.includes_collection(collection_key) {
+            return Some(format!("collection:{collection_key}"));
+        }
+        if access == ScopeAccess::Write && self.access == ScopeAccess::Read {
+            return Some(ScopeAccess::Write.as_str().to_string());
+        }
+        None
+    }
+}
+
+impl fmt::Display for TokenScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.access.as_str())?;
+        if let Some(collections) = &self.collections {
+            write!(f, " collections={}", collections.join(","))?;
+        }
+        Ok(())
+    }
+}
+
 /// Account entity.
 ///
 /// Permission checks are handled by Collection entities (TAXII 1.x CollectionEntity
@@ -71,4 +159,191 @@ pub struct Account {
     /// Additional details.
     #[serde(default)]
     pub details: HashMap<String, serde_json::Value>,
+
+    /// Scope of the token used to authenticate, if it was down-scoped.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<TokenScope>,
+
+    /// STIX identity (`identity--<uuid>`) objects posted by the account
+    /// are attributed to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identity_id: Option<String>,
+}
+
+impl Account {
+    /// Describe the token scope missing for an access to a collection.
+    ///
+    /// Returns `None` for unscoped tokens and scopes permitting the access.
+    pub fn missing_scope(&self, collection_key: &str, access: ScopeAccess) -> Option<String> {
+        self.scope
+            .as_ref()
+            .and_then(|scope| scope.missing_for(collection_key, access))
+    }
+
+    /// Check whether the token scope permits an access to a collection.
+    pub fn scope_allows(&self, collection_key: &str, access: ScopeAccess) -> bool {
+        self.missing_scope(collection_key, access).is_none()
+    }
+
+    /// Attribute the objects the account posts to a STIX identity.
+    ///
+    /// Only the identity id is kept; persist it with the auth API's
+    /// `set_account_identity`.
+    pub fn link_identity(&mut self, identity: &Identity) {
+        self.identity_id = Some(identity.id.to_string());
+    }
+}
+
+/// Conversion of an account into a STIX object describing it.
+pub trait FromAccount: Sized {
+    /// Build the object describing `account`.
+    fn from_account(account: &Account) -> stix2::Result<Self>;
+}
+
+impl FromAccount for Identity {
+    /// Individual identity named after the username.
+    ///
+    /// Keeps the identity id the account is linked to, if any, so the
+    /// result can be posted as the object `created_by_ref` points at.
+    fn from_account(account: &Account) -> stix2::Result<Self> {
+        let mut identity = Identity::individual(&account.username)?;
+        if let Some(ref identity_id) = account.identity_id {
+            let id: stix2::Identifier = identity_id.parse()?;
+            if id.object_type() != Identity::TYPE {
+                return Err(stix2::Error::InvalidId(format!(
+                    "'{identity_id}' is not an identity id"
+                )));
+            }
+            identity.id = id;
+        }
+        Ok(identity)
+    }
+}
+
+/// Outcome of purging expired content from a collection.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PurgeStats {
+    /// Objects or content blocks removed from the collection.
+    pub purged: u64,
+
+    /// Expired content blocks kept because a result set holds them.
+    pub retained: u64,
+}
+
+impl PurgeStats {
+    /// Whether the purge found anything expired.
+    pub fn is_empty(&self) -> bool {
+        self.purged == 0 && self.retained == 0
+    }
+}
+
+/// Outcome of cleaning up TAXII 2.x job logs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CleanupStats {
+    /// Jobs removed.
+    pub jobs_removed: u64,
+
+    /// Job details removed with their jobs.
+    pub details_removed: u64,
+
+    /// Jobs that never completed and were marked failed.
+    pub jobs_failed: u64,
+}
+
+/// Statistics of the content of a collection.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CollectionStats {
+    /// Stored objects (every version counts) or content blocks.
+    pub object_count: u64,
+
+    /// Distinct object IDs. Equals `object_count` for content blocks.
+    pub distinct_ids: u64,
+
+    /// When the latest object or content block was added.
+    pub latest_added: Option<DateTime<Utc>>,
+
+    /// Object count by STIX type (TAXII 2.x) or content binding (TAXII 1.x).
+    pub by_type: BTreeMap<String, u64>,
+
+    /// When the statistics were computed.
+    pub computed_at: DateTime<Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scope_missing_for() {
+        let scope = TokenScope::for_collections(vec!["a".to_string()], ScopeAccess::Read);
+
+        assert_eq!(scope.missing_for("a", ScopeAccess::Read), None);
+        assert_eq!(
+            scope.missing_for("a", ScopeAccess::Write),
+            Some("write".to_string())
+        );
+        assert_eq!(
+            scope.missing_for("b", ScopeAccess::Read),
+            Some("collection:b".to_string())
+        );
+
+        let scope = TokenScope::for_collections(vec!["a".to_string()], ScopeAccess::Write);
+        assert_eq!(scope.missing_for("a", ScopeAccess::Write), None);
+    }
+
+    #[test]
+    fn test_identity_from_account() -> stix2::Result<()> {
+        let mut account = Account {
+            id: 1,
+            username: "alice".to_string(),
+            is_admin: false,
+            permissions: HashMap::new(),
+            details: HashMap::new(),
+            scope: None,
+            identity_id: None,
+        };
+
+        let identity = Identity::from_account(&account)?;
+        assert_eq!(identity.name, "alice");
+        assert_eq!(
+            identity.identity_class,
+            Some(stix2::vocab::IdentityClass::Individual)
+        );
+
+        account.link_identity(&identity);
+        assert_eq!(account.identity_id, Some(identity.id.to_string()));
+
+        // A linked account keeps its identity id
+        assert_eq!(Identity::from_account(&account)?.id, identity.id);
+
+        account.identity_id = Some("indicator--f431f809-377b-45e0-aa1c-6a4751cae5ff".to_string());
+        assert!(Identity::from_account(&account).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_scope_display() {
+        assert_eq!(TokenScope::read_only().to_string(), "read");
+        assert_eq!(
+            TokenScope::for_collections(vec!["a".to_string(), "b".to_string()], ScopeAccess::Write)
+                .to_string(),
+            "write collections=a,b"
+        );
+    }
+
+    #[test]
+    fn test_scope_serialization() {
+        let scope: Result<TokenScope, _> =
+            serde_json::from_str(r#"{"collections": ["a"], "access": "read"}"#);
+        assert_eq!(
+            scope.ok(),
+            Some(TokenScope::for_collections(
+                vec!["a".to_string()],
+                ScopeAccess::Read
+            ))
+        );
+
+        let scope: Result<TokenScope, _> = serde_json::from_str(r#"{"access": "write"}"#);
+        assert_eq!(scope.ok().and_then(|s| s.collections), None);
+    }
 }
@@ -1,10 +1,23 @@
 //! TAXII 1.x entities.
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, SecondsFormat, SubsecRound, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 
-use super::Account;
+use super::{Account, ScopeAccess};
+
+/// Truncate a timestamp label to microseconds, the precision labels are
+/// stored and compared at.
+pub fn truncate_timestamp_label(dt: DateTime<Utc>) -> DateTime<Utc> {
+    dt.trunc_subsecs(6)
+}
+
+/// Format a timestamp label with its full microsecond precision, so a label
+/// from a response can be sent back as the begin of the next poll.
+pub fn format_timestamp_label(dt: &DateTime<Utc>) -> String {
+    dt.trunc_subsecs(6)
+        .to_rfc3339_opts(SecondsFormat::Micros, true)
+}
 
 /// TAXII 1.x collection types.
 ///
@@ -31,6 +44,83 @@ pub mod subscription_status {
     pub const UNSUBSCRIBED: &str = "UNSUBSCRIBED";
 }
 
+/// Push delivery status of a content block to a subscriber.
+///
+/// Deliveries are queued `PENDING` when a content block is added to a
+/// collection with push subscriptions, and become `DELIVERED` or `FAILED`
+/// once they were pushed to the subscriber's inbox.
+pub mod delivery_status {
+    pub const PENDING: &str = "PENDING";
+    pub const DELIVERED: &str = "DELIVERED";
+    pub const FAILED: &str = "FAILED";
+}
+
+/// TAXII 1.x result set preparation status.
+///
+/// Result sets for asynchronous polls start out `PENDING` and become
+/// `READY` once their content has been prepared for Poll Fulfillment.
+pub mod result_set_status {
+    pub const PENDING: &str = "PENDING";
+    pub const READY: &str = "READY";
+}
+
+/// TAXII 1.x collection validation modes.
+///
+/// How the Inbox service treats content claiming a STIX XML binding that
+/// is not valid STIX.
+pub mod validation_mode {
+    /// Content is not validated.
+    pub const OFF: &str = "off";
+    /// Inbox messages with invalid content are refused.
+    pub const REJECT: &str = "reject";
+    /// Invalid content is stored with its validation error.
+    pub const FLAG: &str = "flag";
+
+    /// Default mode of collections.
+    pub fn default_mode() -> String {
+        OFF.to_string()
+    }
+}
+
+/// TAXII 1.x collection signature policies.
+///
+/// How the Inbox service treats XML signatures of content.
+pub mod signature_policy {
+    /// Signatures are not verified.
+    pub const OFF: &str = "off";
+    /// Signed content must verify; unsigned content is accepted.
+    pub const VERIFY: &str = "verify";
+    /// Content must be signed and verify.
+    pub const REQUIRE: &str = "require";
+
+    /// Default policy of collections.
+    pub fn default_policy() -> String {
+        OFF.to_string()
+    }
+}
+
+/// How the bytes of TAXII 1.x content blocks are carried in messages.
+pub mod content_encoding {
+    /// The content is UTF-8 text, sent as is.
+    pub const TEXT: &str = "text";
+    /// The content is binary, base64 encoded in messages.
+    pub const BASE64: &str = "base64";
+
+    /// Encoding of blocks stored before encodings were recorded, which
+    /// hold their content as it was received.
+    pub fn default_encoding() -> String {
+        TEXT.to_string()
+    }
+}
+
+/// Outcome of content block signature verification.
+pub mod signature_status {
+    /// The content is signed by a trusted certificate.
+    pub const VALID: &str = "valid";
+    /// The content is not signed.
+    pub const UNSIGNED: &str = "unsigned";
+}
+
 /// TAXII Service entity.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServiceEntity {
@@ -95,6 +185,46 @@ impl ContentBindingEntity {
     }
 }
 
+/// Match a requested binding against a supported one.
+///
+/// See [`CollectionEntity::get_matching_bindings`] for the subtype semantics.
+fn match_binding(
+    requested: &ContentBindingEntity,
+    supported: &ContentBindingEntity,
+) -> Option<ContentBindingEntity> {
+    if requested.binding != supported.binding {
+        return None;
+    }
+
+    // Parent supported: covers whatever was requested
+    if supported.subtypes.is_empty() {
+        return Some(requested.clone());
+    }
+
+    // Parent requested: limited to the supported subtypes
+    if requested.subtypes.is_empty() {
+        return Some(supported.clone());
+    }
+
+    let supported_set: HashSet<_> = supported.subtypes.iter().collect();
+    let subtypes_overlap: Vec<String> = requested
+        .subtypes
+        .iter()
+        .filter(|s| supported_set.contains(s))
+        .cloned()
+        .collect();
+
+    // No common subtype; an empty list would mean "all subtypes"
+    if subtypes_overlap.is_empty() {
+        return None;
+    }
+
+    Some(ContentBindingEntity {
+        binding: requested.binding.clone(),
+        subtypes: subtypes_overlap,
+    })
+}
+
 /// Collection entity.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CollectionEntity {
@@ -122,9 +252,49 @@ pub struct CollectionEntity {
 
     /// List of supported content bindings.
     pub supported_content: Vec<ContentBindingEntity>,
+
+    /// Days content blocks are kept (`None` keeps them).
+    #[serde(default)]
+    pub retention_days: Option<i32>,
+
+    /// Whether identical content blocks are stored once.
+    #[serde(default)]
+    pub deduplicate_content: bool,
+
+    /// Validation of inbound STIX XML content (see [`validation_mode`]).
+    #[serde(default = "validation_mode::default_mode")]
+    pub validation_mode: String,
+
+    /// Verification of content signatures (see [`signature_policy`]).
+    #[serde(default = "signature_policy::default_policy")]
+    pub signature_policy: String,
+
+    /// PEM bundle of the certificates trusted to sign content.
+    #[serde(default)]
+    pub trusted_certificates: Option<String>,
 }
 
 impl CollectionEntity {
+    /// Create an available data feed that accepts all content, with the
+    /// default retention, deduplication, validation and signature settings.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            id: None,
+            name: name.into(),
+            available: true,
+            volume: None,
+            description: None,
+            accept_all_content: true,
+            collection_type: collection_type::DATA_FEED.to_string(),
+            supported_content: Vec::new(),
+            retention_days: None,
+            deduplicate_content: false,
+            validation_mode: validation_mode::default_mode(),
+            signature_policy: signature_policy::default_policy(),
+            trusted_certificates: None,
+        }
+    }
+
     /// Check if content binding is supported.
     pub fn is_content_supported(&self, content_binding: &ContentBindingEntity) -> bool {
         if self.accept_all_content {
@@ -149,6 +319,19 @@ impl CollectionEntity {
     }
 
     /// Get matching bindings between requested and supported.
+    ///
+    /// Subtype semantics:
+    /// - A binding without subtypes stands for the parent binding and covers
+    ///   all of its subtypes.
+    /// - A requested parent binding matches everything the collection supports
+    ///   for that binding: all subtypes if the parent is supported, otherwise
+    ///   the supported subtypes.
+    /// - A requested subtype matches only that subtype. It is served if the
+    ///   collection supports the parent binding (a superset) or lists the
+    ///   subtype itself; otherwise the binding does not match.
+    ///
+    /// An empty result for a non-empty request means none of the requested
+    /// content is supported.
     pub fn get_matching_bindings(
         &self,
         requested_bindings: &[ContentBindingEntity],
@@ -169,32 +352,12 @@ impl CollectionEntity {
 
         for requested in requested_bindings {
             for supported in &self.supported_content {
-                if requested.binding != supported.binding {
-                    continue;
-                }
-
-                if supported.subtypes.is_empty() {
-                    overlap.push(requested.clone());
-                    continue;
-                }
-
-                if requested.subtypes.is_empty() {
-                    overlap.push(supported.clone());
+                let Some(matching) = match_binding(requested, supported) else {
                     continue;
+                };
+                if !overlap.contains(&matching) {
+                    overlap.push(matching);
                 }
-
-                let supported_set: HashSet<_> = supported.subtypes.iter().collect();
-                let subtypes_overlap: Vec<String> = requested
-                    .subtypes
-                    .iter()
-                    .filter(|s| supported_set.contains(s))
-                    .cloned()
-                    .collect();
-
-                overlap.push(ContentBindingEntity {
-                    binding: requested.binding.clone(),
-                    subtypes: subtypes_overlap,
-                });
             }
         }
 
@@ -205,6 +368,10 @@ impl CollectionEntity {
     ///
     /// Uses collection NAME as the permission key (TAXII 1.x style).
     pub fn can_read(&self, account: &Account) -> bool {
+        if !account.scope_allows(&self.name, ScopeAccess::Read) {
+            return false;
+        }
+
         account.is_admin
             || account
                 .permissions
@@ -217,6 +384,10 @@ impl CollectionEntity {
     ///
     /// Uses collection NAME as the permission key (TAXII 1.x style).
     pub fn can_modify(&self, account: &Account) -> bool {
+        if !account.scope_allows(&self.name, ScopeAccess::Write) {
+            return false;
+        }
+
         account.is_admin
             || account
                 .permissions
@@ -246,6 +417,42 @@ pub struct ContentBlockEntity {
 
     /// Internal ID of the inbox message.
     pub inbox_message_id: Option<i32>,
+
+    /// Why the content failed validation, if it was stored anyway.
+    #[serde(default)]
+    pub validation_error: Option<String>,
+
+    /// Outcome of signature verification (see [`signature_status`]), if
+    /// the content was verified.
+    #[serde(default)]
+    pub signature_status: Option<String>,
+
+    /// SHA-256 fingerprint (hex) of the certificate that signed the content.
+    #[serde(default)]
+    pub signer_fingerprint: Option<String>,
+
+    /// How the content is carried in messages (see [`content_encoding`]).
+    #[serde(default = "content_encoding::default_encoding")]
+    pub content_encoding: String,
+}
+
+impl ContentBlockEntity {
+    /// Create a text content block labelled with the current time, not
+    /// attached to an inbox message.
+    pub fn new(content: impl Into<Vec<u8>>, content_binding: Option<ContentBindingEntity>) -> Self {
+        Self {
+            id: None,
+            content: content.into(),
+            timestamp_label: Utc::now(),
+            content_binding,
+            message: None,
+            inbox_message_id: None,
+            validation_error: None,
+            signature_status: None,
+            signer_fingerprint: None,
+            content_encoding: content_encoding::default_encoding(),
+        }
+    }
 }
 
 /// Inbox Message entity.
@@ -305,6 +512,39 @@ pub struct ResultSetEntity {
 
     /// Timeframe as (begin, end).
     pub timeframe: (Option<DateTime<Utc>>, Option<DateTime<Utc>>),
+
+    /// Preparation status (PENDING or READY).
+    pub status: String,
+
+    /// Number of prepared content blocks.
+    ///
+    /// `None` if the result set content is resolved from its filters at
+    /// fulfillment time rather than prepared in advance.
+    pub content_block_count: Option<i32>,
+
+    /// Time after which the result set can no longer be fulfilled.
+    ///
+    /// `None` never expires.
+    pub expires_at: Option<DateTime<Utc>>,
+
+    /// Inbox the results are pushed to once the result set is prepared.
+    ///
+    /// `None` leaves the results to Poll Fulfillment.
+    #[serde(default)]
+    pub delivery: Option<PushParametersEntity>,
+
+    /// Push status of the results (see [`delivery_status`]).
+    ///
+    /// `None` without a delivery target.
+    #[serde(default)]
+    pub delivery_status: Option<String>,
+}
+
+impl ResultSetEntity {
+    /// Check if the result set is still waiting to be prepared.
+    pub fn is_pending(&self) -> bool {
+        self.status == result_set_status::PENDING
+    }
 }
 
 /// Subscription Parameters entity.
@@ -347,4 +587,267 @@ pub struct SubscriptionEntity {
 
     /// Subscription status (ACTIVE, PAUSED, UNSUBSCRIBED).
     pub status: String,
+
+    /// Push delivery status.
+    #[serde(default)]
+    pub delivery: DeliveryStatus,
+
+    /// Where new content is pushed to; `None` for poll-only subscriptions.
+    #[serde(default)]
+    pub push: Option<PushParametersEntity>,
+
+    /// Account that created the subscription and alone may manage it;
+    /// `None` if it was created without authentication.
+    #[serde(default)]
+    pub account_id: Option<i32>,
+}
+
+impl SubscriptionEntity {
+    /// Check if content with this binding is delivered to the subscriber.
+    ///
+    /// Subscriptions without content bindings receive all content. A
+    /// subscribed binding without subtypes matches content of any subtype;
+    /// one with subtypes only matches content of those subtypes, as when
+    /// polling through the subscription.
+    pub fn accepts_content(&self, content_binding: &ContentBindingEntity) -> bool {
+        let Some(params) = self.params.as_ref() else {
+            return true;
+        };
+        if params.content_bindings.is_empty() {
+            return true;
+        }
+
+        params.content_bindings.iter().any(|requested| {
+            requested.binding == content_binding.binding
+                && (requested.subtypes.is_empty()
+                    || content_binding
+                        .subtypes
+                        .iter()
+                        .any(|subtype| requested.subtypes.contains(subtype)))
+        })
+    }
+}
+
+/// Push parameters of a subscription.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PushParametersEntity {
+    /// Protocol binding of the subscriber's inbox (HTTP or HTTPS).
+    pub protocol_binding: String,
+
+    /// Address of the subscriber's inbox.
+    pub address: String,
+
+    /// Message binding of the pushed Inbox Messages.
+    pub message_binding: String,
+}
+
+/// Push delivery of a content block to a subscriber.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubscriptionDeliveryEntity {
+    /// Delivery ID.
+    pub id: i32,
+
+    /// Subscription the content block is delivered to.
+    pub subscription_id: String,
+
+    /// Content block delivered.
+    pub content_block_id: i32,
+
+    /// Delivery status (see [`delivery_status`]).
+    pub status: String,
+
+    /// Delivery attempts made.
+    pub attempts: i32,
+
+    /// Error of the last failed attempt.
+    pub last_error: Option<String>,
+
+    /// Time the delivery was queued.
+    pub date_created: DateTime<Utc>,
+
+    /// Time of the last delivery attempt.
+    pub last_attempt_at: Option<DateTime<Utc>>,
+}
+
+/// Link of a TAXII 1.x collection to the TAXII 2.x collection that STIX
+/// packages received into it are converted into.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CollectionBridgeEntity {
+    /// TAXII 1.x collection ID.
+    pub data_collection_id: i32,
+
+    /// API root of the TAXII 2.x collection.
+    pub api_root_id: String,
+
+    /// TAXII 2.x collection ID.
+    pub collection_id: String,
+}
+
+/// Push delivery status of a subscription.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeliveryStatus {
+    /// Time of the last successful delivery.
+    pub last_success: Option<DateTime<Utc>>,
+
+    /// Failed deliveries since the last successful one.
+    pub consecutive_failures: i32,
+
+    /// Error of the last failed delivery.
+    pub last_error: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STIX_XML: &str = "urn:stix.mitre.org:xml:1.1.1";
+    const CAPEC: &str = "capec";
+    const CYBOX: &str = "cybox";
+
+    fn collection(supported_content: Vec<ContentBindingEntity>) -> CollectionEntity {
+        CollectionEntity {
+            id: Some(1),
+            accept_all_content: false,
+            supported_content,
+            ..CollectionEntity::new("collection")
+        }
+    }
+
+    fn subtypes(binding: &str, subtypes: &[&str]) -> ContentBindingEntity {
+        ContentBindingEntity::with_subtypes(
+            binding,
+            subtypes.iter().map(|s| s.to_string()).collect(),
+        )
+    }
+
+    #[test]
+    fn test_parent_request_matches_supported_parent() {
+        let collection = collection(vec![ContentBindingEntity::new(STIX_XML)]);
+
+        let matching = collection.get_matching_bindings(&[ContentBindingEntity::new(STIX_XML)]);
+
+        assert_eq!(matching, vec![ContentBindingEntity::new(STIX_XML)]);
+    }
+
+    #[test]
+    fn test_parent_request_covers_supported_subtypes() {
+        let collection = collection(vec![subtypes(STIX_XML, &[CAPEC, CYBOX])]);
+
+        let matching = collection.get_matching_bindings(&[ContentBindingEntity::new(STIX_XML)]);
+
+        assert_eq!(matching, vec![subtypes(STIX_XML, &[CAPEC, CYBOX])]);
+    }
+
+    #[test]
+    fn test_subtype_request_matches_supported_parent() {
+        let collection = collection(vec![ContentBindingEntity::new(STIX_XML)]);
+
+        let matching = collection.get_matching_bindings(&[subtypes(STIX_XML, &[CAPEC])]);
+
+        assert_eq!(matching, vec![subtypes(STIX_XML, &[CAPEC])]);
+    }
+
+    #[test]
+    fn test_subtype_request_matches_only_that_subtype() {
+        let collection = collection(vec![subtypes(STIX_XML, &[CAPEC, CYBOX])]);
+
+        let matching = collection.get_matching_bindings(&[subtypes(STIX_XML, &[CAPEC])]);
+
+        assert_eq!(matching, vec![subtypes(STIX_XML, &[CAPEC])]);
+    }
+
+    #[test]
+    fn test_subtype_request_without_common_subtype_does_not_match() {
+        let collection = collection(vec![subtypes(STIX_XML, &[CYBOX])]);
+
+        let matching = collection.get_matching_bindings(&[subtypes(STIX_XML, &[CAPEC])]);
+
+        assert!(matching.is_empty());
+    }
+
+    #[test]
+    fn test_different_binding_does_not_match() {
+        let collection = collection(vec![ContentBindingEntity::new(STIX_XML)]);
+
+        let matching =
+            collection.get_matching_bindings(&[ContentBindingEntity::new("urn:other:binding")]);
+
+        assert!(matching.is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_matches_are_collapsed() {
+        let collection = collection(vec![
+            ContentBindingEntity::new(STIX_XML),
+            subtypes(STIX_XML, &[CAPEC]),
+        ]);
+
+        let matching = collection.get_matching_bindings(&[subtypes(STIX_XML, &[CAPEC])]);
+
+        assert_eq!(matching, vec![subtypes(STIX_XML, &[CAPEC])]);
+    }
+
+    #[test]
+    fn test_accept_all_content_returns_request() {
+        let mut collection = collection(vec![subtypes(STIX_XML, &[CYBOX])]);
+        collection.accept_all_content = true;
+
+        let requested = vec![subtypes(STIX_XML, &[CAPEC])];
+
+        assert_eq!(collection.get_matching_bindings(&requested), requested);
+    }
+
+    #[test]
+    fn test_subtype_request_limited_to_common_subtypes() {
+        let collection = collection(vec![subtypes(STIX_XML, &[CAPEC, CYBOX])]);
+
+        let matching = collection.get_matching_bindings(&[subtypes(STIX_XML, &[CAPEC, "ttp"])]);
+
+        assert_eq!(matching, vec![subtypes(STIX_XML, &[CAPEC])]);
+    }
+
+    #[test]
+    fn test_empty_request_matches_supported_content() {
+        let collection = collection(vec![subtypes(STIX_XML, &[CAPEC])]);
+
+        assert_eq!(
+            collection.get_matching_bindings(&[]),
+            vec![subtypes(STIX_XML, &[CAPEC])]
+        );
+    }
+
+    #[test]
+    fn test_subscription_accepts_content() {
+        let subscription = |bindings: Vec<ContentBindingEntity>| SubscriptionEntity {
+            service_id: "collection-management".to_string(),
+            collection_id: 1,
+            subscription_id: None,
+            params: Some(SubscriptionParameters {
+                response_type: response_type::FULL.to_string(),
+                content_bindings: bindings,
+            }),
+            status: subscription_status::ACTIVE.to_string(),
+            delivery: Default::default(),
+            push: None,
+            account_id: None,
+        };
+        let capec = subtypes(STIX_XML, &[CAPEC]);
+        let cybox = subtypes(STIX_XML, &[CYBOX]);
+        let parent = ContentBindingEntity::new(STIX_XML);
+
+        // Parent binding: any subtype
+        let all = subscription(vec![parent.clone()]);
+        assert!(all.accepts_content(&capec));
+        assert!(all.accepts_content(&parent));
+
+        // Subtype: only content of that subtype
+        let only_capec = subscription(vec![capec.clone()]);
+        assert!(only_capec.accepts_content(&capec));
+        assert!(!only_capec.accepts_content(&cybox));
+        assert!(!only_capec.accepts_content(&parent));
+        assert!(!only_capec.accepts_content(&ContentBindingEntity::new("urn:other:binding")));
+
+        // No bindings: all content
+        assert!(subscription(Vec::new()).accepts_content(&cybox));
+    }
 }
@@ -1,9 +1,13 @@
 //! TAXII 2.x entities.
 
+use std::fmt;
+use std::str::FromStr;
+
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
-use super::Account;
+use super::{Account, ScopeAccess};
 
 /// TAXII 2.x datetime format with 6-digit microsecond precision.
 pub const DATETIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.6fZ";
@@ -30,6 +34,10 @@ pub struct ApiRoot {
 
     /// Whether this is publicly readable.
     pub is_public: bool,
+
+    /// Largest POST body in bytes (`None` uses the server limit).
+    #[serde(default)]
+    pub max_content_length: Option<u64>,
 }
 
 /// TAXII 2.x Collection entity.
@@ -55,18 +63,45 @@ pub struct Collection {
 
     /// Whether this is publicly writable.
     pub is_public_write: bool,
+
+    /// Days objects are kept after being added (`None` keeps them).
+    #[serde(default)]
+    pub retention_days: Option<i32>,
+
+    /// Rules objects posted to the collection must pass.
+    #[serde(default)]
+    pub validation_rules: Vec<ValidationRule>,
+
+    /// Marking definition ID applied to posted objects without markings.
+    #[serde(default)]
+    pub default_marking: Option<String>,
+}
+
+impl ApiRoot {
+    /// Largest POST body accepted by this API root; its own limit can only
+    /// lower the server's.
+    pub fn content_length_limit(&self, server_limit: usize) -> usize {
+        self.max_content_length
+            .and_then(|limit| usize::try_from(limit).ok())
+            .map_or(server_limit, |limit| limit.min(server_limit))
+    }
 }
 
 impl Collection {
     /// Determine if account is allowed to read from this collection.
     ///
     /// Permissions are keyed by collection UUID (normalized at CLI sync time).
+    /// For down-scoped tokens, access is further limited to the token scope.
     pub fn can_read(&self, account: Option<&Account>) -> bool {
         if self.is_public {
             return true;
         }
 
         if let Some(acct) = account {
+            if !acct.scope_allows(&self.id, ScopeAccess::Read) {
+                return false;
+            }
+
             if acct.is_admin {
                 return true;
             }
@@ -82,12 +117,17 @@ impl Collection {
     /// Determine if account is allowed to write to this collection.
     ///
     /// Permissions are keyed by collection UUID (normalized at CLI sync time).
+    /// For down-scoped tokens, access is further limited to the token scope.
     pub fn can_write(&self, account: Option<&Account>) -> bool {
         if self.is_public_write {
             return true;
         }
 
         if let Some(acct) = account {
+            if !acct.scope_allows(&self.id, ScopeAccess::Write) {
+                return false;
+            }
+
             if acct.is_admin {
                 return true;
             }
@@ -99,6 +139,135 @@ impl Collection {
 
         false
     }
+
+    /// Check a posted object against the validation rules of the collection.
+    ///
+    /// Returns the message of the first failing rule.
+    pub fn check_object(&self, object: &Value) -> Result<(), String> {
+        self.validation_rules
+            .iter()
+            .try_for_each(|rule| rule.check(object))
+    }
+
+    /// Apply the default marking of the collection to a posted object
+    /// without `object_marking_refs`.
+    ///
+    /// Objects with markings and marking definitions are left alone, as are
+    /// objects that cannot carry markings. Only `object_marking_refs` is
+    /// changed, so properties the typed object does not model are kept.
+    pub fn apply_default_marking(&self, object: &mut Value) -> stix2::Result<()> {
+        let Some(marking) = &self.default_marking else {
+            return Ok(());
+        };
+        let marked = object["object_marking_refs"]
+            .as_array()
+            .is_some_and(|refs| !refs.is_empty());
+        if marked || object["type"] == "marking-definition" {
+            return Ok(());
+        }
+
+        let mut typed: stix2::StixObject = serde_json::from_value(object.clone())?;
+        typed.add_marking(marking.parse()?)?;
+        if let Some(refs) = serde_json::to_value(&typed)?.get("object_marking_refs") {
+            object["object_marking_refs"] = refs.clone();
+        }
+        Ok(())
+    }
+}
+
+/// Built-in write validation rule of a collection, selected by name.
+///
+/// Rules apply to SDOs and SROs (objects with a `created` property other
+/// than marking definitions and language content); other objects always
+/// pass.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ValidationRule {
+    /// Objects must have a `confidence`.
+    RequireConfidence,
+    /// Objects must reference a TLP marking definition in
+    /// `object_marking_refs`.
+    RequireTlp,
+    /// Objects must have a non-empty `description`.
+    RequireDescription,
+}
+
+impl ValidationRule {
+    /// All built-in rules.
+    pub const ALL: [Self; 3] = [
+        Self::RequireConfidence,
+        Self::RequireTlp,
+        Self::RequireDescription,
+    ];
+
+    /// Get the name of the rule.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::RequireConfidence => "require-confidence",
+            Self::RequireTlp => "require-tlp",
+            Self::RequireDescription => "require-description",
+        }
+    }
+
+    /// Check an object against the rule.
+    ///
+    /// Returns the message to reject the object with if it fails.
+    pub fn check(&self, object: &Value) -> Result<(), String> {
+        let applies = object.get("created").is_some()
+            && !matches!(
+                object["type"].as_str(),
+                Some("marking-definition" | "language-content")
+            );
+        if !applies {
+            return Ok(());
+        }
+
+        let passes = match self {
+            Self::RequireConfidence => object.get("confidence").is_some_and(Value::is_i64),
+            Self::RequireTlp => object["object_marking_refs"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|r| r.as_str()?.parse().ok())
+                .any(|id| stix2::TlpLevel::from_marking_definition_id(&id).is_some()),
+            Self::RequireDescription => object["description"]
+                .as_str()
+                .is_some_and(|d| !d.trim().is_empty()),
+        };
+        if passes {
+            return Ok(());
+        }
+
+        let object_id = object["id"].as_str().unwrap_or("<unknown>");
+        Err(match self {
+            Self::RequireConfidence => format!("Object {object_id} has no confidence"),
+            Self::RequireTlp => format!("Object {object_id} has no TLP marking"),
+            Self::RequireDescription => format!("Object {object_id} has no description"),
+        })
+    }
+}
+
+impl fmt::Display for ValidationRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for ValidationRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|rule| rule.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                let names: Vec<&str> = Self::ALL.iter().map(|r| r.name()).collect();
+                format!(
+                    "unknown validation rule '{s}' (expected one of {})",
+                    names.join(", ")
+                )
+            })
+    }
 }
 
 /// TAXII 2.x STIX Object entity.
@@ -228,6 +397,24 @@ pub struct VersionRecord {
     pub version: DateTime<Utc>,
 }
 
+/// A deleted object version (tombstone).
+///
+/// Lets a replication peer learn about removals since its last sync.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeletionRecord {
+    /// STIX object ID.
+    pub id: String,
+
+    /// Deleted object version.
+    pub version: DateTime<Utc>,
+
+    /// Time of deletion.
+    pub deleted_at: DateTime<Utc>,
+
+    /// Account that deleted the version.
+    pub deleted_by: Option<i32>,
+}
+
 /// TAXII 2.x Job Detail entity (part of status resource).
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JobDetail {
@@ -354,3 +541,342 @@ impl Job {
         response
     }
 }
+
+/// Outcome of one submitted object in a bulk insert.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BulkInsertOutcome {
+    /// The object was stored.
+    Inserted,
+    /// The same object version already existed (or was repeated in the batch).
+    Duplicate,
+    /// The object version existed with different content, which was replaced.
+    Overwritten,
+    /// The object version existed with different content, which was kept.
+    Kept,
+    /// The object could not be stored.
+    Failed,
+}
+
+/// Handling of a submitted object whose version (`id` and `modified`)
+/// already exists with different content.
+///
+/// Objects repeating an existing version with the same content are always
+/// accepted as duplicates.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CollisionPolicy {
+    /// Fail the object, keeping the existing content.
+    #[default]
+    Reject,
+    /// Replace the existing content with the submitted one.
+    Overwrite,
+    /// Accept the object without storing it, keeping the existing content.
+    KeepExisting,
+}
+
+impl FromStr for CollisionPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().replace('-', "_").as_str() {
+            "reject" => Ok(Self::Reject),
+            "overwrite" => Ok(Self::Overwrite),
+            "keep_existing" => Ok(Self::KeepExisting),
+            _ => Err(format!(
+                "unknown collision policy '{s}' (expected reject, overwrite or keep_existing)"
+            )),
+        }
+    }
+}
+
+/// Result of adding a batch of STIX objects.
+#[derive(Debug, Clone)]
+pub struct BulkInsertReport {
+    /// Status resource of the ingest job.
+    pub job: Job,
+
+    /// Outcome per submitted object, in submission order.
+    pub outcomes: Vec<BulkInsertOutcome>,
+}
+
+impl BulkInsertReport {
+    /// Number of objects with the given outcome.
+    pub fn count(&self, outcome: BulkInsertOutcome) -> usize {
+        self.outcomes.iter().filter(|o| **o == outcome).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entities::{PermissionValue, TokenScope};
+    use std::collections::HashMap;
+
+    const COLLECTION_A: &str = "91a7b528-80eb-42ed-a74d-c6fbd5a26116";
+    const COLLECTION_B: &str = "52892447-4d7e-4f70-b94d-d7f22742ff63";
+
+    fn collection(id: &str) -> Collection {
+        Collection {
+            id: id.to_string(),
+            api_root_id: "api-root".to_string(),
+            title: "Collection".to_string(),
+            description: None,
+            alias: None,
+            is_public: false,
+            is_public_write: false,
+            retention_days: None,
+            validation_rules: Vec::new(),
+            default_marking: None,
+        }
+    }
+
+    fn account(is_admin: bool, permissions: &[(&str, &[&str])]) -> Account {
+        Account {
+            id: 1,
+            username: "partner".to_string(),
+            is_admin,
+            permissions: permissions
+                .iter()
+                .map(|(id, perms)| {
+                    (
+                        id.to_string(),
+                        PermissionValue::Taxii2(perms.iter().map(|p| p.to_string()).collect()),
+                    )
+                })
+                .collect(),
+            details: HashMap::new(),
+            scope: None,
+            identity_id: None,
+        }
+    }
+
+    fn scoped(mut account: Account, collections: &[&str], access: ScopeAccess) -> Account {
+        account.scope = Some(TokenScope::for_collections(
+            collections.iter().map(|c| c.to_string()).collect(),
+            access,
+        ));
+        account
+    }
+
+    #[test]
+    fn test_api_root_content_length_limit() {
+        let mut api_root = ApiRoot {
+            id: "api-root".to_string(),
+            default: false,
+            title: "API root".to_string(),
+            description: None,
+            is_public: false,
+            max_content_length: None,
+        };
+        assert_eq!(api_root.content_length_limit(4096), 4096);
+
+        api_root.max_content_length = Some(1024);
+        assert_eq!(api_root.content_length_limit(4096), 1024);
+
+        // The server limit still applies
+        api_root.max_content_length = Some(8192);
+        assert_eq!(api_root.content_length_limit(4096), 4096);
+    }
+
+    #[test]
+    fn test_unscoped_account_uses_stored_permissions() {
+        let account = account(false, &[(COLLECTION_A, &["read", "write"])]);
+
+        assert!(collection(COLLECTION_A).can_read(Some(&account)));
+        assert!(collection(COLLECTION_A).can_write(Some(&account)));
+        assert!(!collection(COLLECTION_B).can_read(Some(&account)));
+    }
+
+    #[test]
+    fn test_read_scope_narrows_non_admin() {
+        let account = scoped(
+            account(
+                false,
+                &[
+                    (COLLECTION_A, &["read", "write"]),
+                    (COLLECTION_B, &["read", "write"]),
+                ],
+            ),
+            &[COLLECTION_A],
+            ScopeAccess::Read,
+        );
+
+        assert!(collection(COLLECTION_A).can_read(Some(&account)));
+        assert!(!collection(COLLECTION_A).can_write(Some(&account)));
+        assert!(!collection(COLLECTION_B).can_read(Some(&account)));
+        assert!(!collection(COLLECTION_B).can_write(Some(&account)));
+    }
+
+    #[test]
+    fn test_scope_does_not_widen_non_admin() {
+        // Write scope on a read-only permission stays read-only
+        let account = scoped(
+            account(false, &[(COLLECTION_A, &["read"])]),
+            &[COLLECTION_A, COLLECTION_B],
+            ScopeAccess::Write,
+        );
+
+        assert!(collection(COLLECTION_A).can_read(Some(&account)));
+        assert!(!collection(COLLECTION_A).can_write(Some(&account)));
+        assert!(!collection(COLLECTION_B).can_read(Some(&account)));
+    }
+
+    #[test]
+    fn test_read_scope_narrows_admin() {
+        let account = scoped(account(true, &[]), &[COLLECTION_A], ScopeAccess::Read);
+
+        assert!(collection(COLLECTION_A).can_read(Some(&account)));
+        assert!(!collection(COLLECTION_A).can_write(Some(&account)));
+        assert!(!collection(COLLECTION_B).can_read(Some(&account)));
+    }
+
+    #[test]
+    fn test_write_scope_for_admin() {
+        let account = scoped(account(true, &[]), &[COLLECTION_A], ScopeAccess::Write);
+
+        assert!(collection(COLLECTION_A).can_read(Some(&account)));
+        assert!(collection(COLLECTION_A).can_write(Some(&account)));
+        assert!(!collection(COLLECTION_B).can_write(Some(&account)));
+    }
+
+    #[test]
+    fn test_read_only_scope_for_all_collections() {
+        let mut account = account(true, &[]);
+        account.scope = Some(TokenScope::read_only());
+
+        assert!(collection(COLLECTION_A).can_read(Some(&account)));
+        assert!(collection(COLLECTION_B).can_read(Some(&account)));
+        assert!(!collection(COLLECTION_A).can_write(Some(&account)));
+    }
+
+    #[test]
+    fn test_public_collection_not_restricted_by_scope() {
+        let account = scoped(account(false, &[]), &[COLLECTION_A], ScopeAccess::Read);
+        let mut public = collection(COLLECTION_B);
+        public.is_public = true;
+
+        // Anonymous clients can read it, so a scoped token can too
+        assert!(public.can_read(Some(&account)));
+        assert!(!public.can_write(Some(&account)));
+    }
+
+    #[test]
+    fn test_validation_rules() {
+        let indicator = serde_json::json!({
+            "type": "indicator",
+            "id": "indicator--8e2e2d2b-17d4-4cbf-938f-98ee46b3cd3f",
+            "created": "2024-01-01T00:00:00.000Z",
+            "description": " ",
+            "object_marking_refs": [
+                "marking-definition--f88d31f6-486f-44da-b317-01333bde0b82"
+            ],
+        });
+        assert_eq!(
+            ValidationRule::RequireConfidence.check(&indicator),
+            Err(
+                "Object indicator--8e2e2d2b-17d4-4cbf-938f-98ee46b3cd3f has no confidence"
+                    .to_string()
+            )
+        );
+        assert!(ValidationRule::RequireTlp.check(&indicator).is_ok());
+        assert!(
+            ValidationRule::RequireDescription
+                .check(&indicator)
+                .is_err()
+        );
+
+        // Observables and marking definitions are not subject to rules
+        let observable = serde_json::json!({"type": "ipv4-addr", "value": "10.0.0.1"});
+        let marking = serde_json::json!({
+            "type": "marking-definition",
+            "created": "2024-01-01T00:00:00.000Z",
+        });
+        for rule in ValidationRule::ALL {
+            assert!(rule.check(&observable).is_ok());
+            assert!(rule.check(&marking).is_ok());
+            assert_eq!(rule.name().parse::<ValidationRule>(), Ok(rule));
+        }
+        assert!("require-everything".parse::<ValidationRule>().is_err());
+    }
+
+    #[test]
+    fn test_collision_policy_from_str() {
+        assert_eq!("reject".parse(), Ok(CollisionPolicy::Reject));
+        assert_eq!("Overwrite".parse(), Ok(CollisionPolicy::Overwrite));
+        assert_eq!("keep_existing".parse(), Ok(CollisionPolicy::KeepExisting));
+        assert_eq!("keep-existing".parse(), Ok(CollisionPolicy::KeepExisting));
+        assert!("merge".parse::<CollisionPolicy>().is_err());
+        assert_eq!(CollisionPolicy::default(), CollisionPolicy::Reject);
+    }
+
+    #[test]
+    fn test_default_marking() -> Result<(), Box<dyn std::error::Error>> {
+        const TLP_GREEN: &str = "marking-definition--34098fce-860f-48ae-8e50-ebd3cc5e41da";
+        const TLP_RED: &str = "marking-definition--5e57c739-391a-4eb3-b6be-7d15ca92d5ed";
+
+        let mut collection = collection(COLLECTION_A);
+        collection.default_marking = Some(TLP_GREEN.to_string());
+        let indicator = serde_json::json!({
+            "type": "indicator",
+            "spec_version": "2.1",
+            "id": "indicator--8e2e2d2b-17d4-4cbf-938f-98ee46b3cd3f",
+            "created": "2024-01-01T00:00:00.000Z",
+            "modified": "2024-01-01T00:00:00.000Z",
+            "pattern": "[ipv4-addr:value = '10.0.0.1']",
+            "pattern_type": "stix",
+            "valid_from": "2024-01-01T00:00:00Z",
+        });
+
+        // An unmarked object gets the default
+        let mut unmarked = indicator.clone();
+        collection.apply_default_marking(&mut unmarked)?;
+        assert_eq!(
+            unmarked["object_marking_refs"],
+            serde_json::json!([TLP_GREEN])
+        );
+        assert_eq!(unmarked["pattern"], indicator["pattern"]);
+
+        // Custom properties of observables are kept
+        let mut observable = serde_json::json!({
+            "type": "ipv4-addr",
+            "spec_version": "2.1",
+            "id": "ipv4-addr--ff26c055-6336-5bc5-b98d-13d6226742dd",
+            "value": "10.0.0.1",
+            "x_origin": {"feed": "partner"},
+        });
+        collection.apply_default_marking(&mut observable)?;
+        assert_eq!(
+            observable["object_marking_refs"],
+            serde_json::json!([TLP_GREEN])
+        );
+        assert_eq!(observable["x_origin"]["feed"], "partner");
+
+        // A marked object is left alone
+        let mut marked = indicator.clone();
+        marked["object_marking_refs"] = serde_json::json!([TLP_RED]);
+        let before = marked.clone();
+        collection.apply_default_marking(&mut marked)?;
+        assert_eq!(marked, before);
+
+        // So are marking definitions and objects of collections without one
+        let mut definition = serde_json::json!({
+            "type": "marking-definition",
+            "spec_version": "2.1",
+            "id": TLP_RED,
+            "created": "2017-01-20T00:00:00.000Z",
+            "definition_type": "tlp",
+            "name": "TLP:RED",
+            "definition": {"tlp": "red"},
+        });
+        let before = definition.clone();
+        collection.apply_default_marking(&mut definition)?;
+        assert_eq!(definition, before);
+
+        collection.default_marking = None;
+        let mut unmarked = indicator.clone();
+        collection.apply_default_marking(&mut unmarked)?;
+        assert_eq!(unmarked, indicator);
+        Ok(())
+    }
+}
@@ -6,7 +6,10 @@ pub mod error;
 pub mod signals;
 
 pub use config::ServerConfig;
-pub use entities::{Account, PermissionValue};
+pub use entities::{
+    Account, CleanupStats, CollectionStats, FromAccount, PermissionValue, PurgeStats, ScopeAccess,
+    TokenScope,
+};
 pub use error::TaxiiError;
 pub use signals::{
     ContentBlockCreatedEvent, HookRegistry, InboxMessageCreatedEvent, SharedHookRegistry,
@@ -15,13 +18,17 @@ pub use signals::{
 
 // Re-export TAXII 1.x entities
 pub use entities::taxii1::{
-    CollectionEntity, ContentBindingEntity, ContentBlockEntity, InboxMessageEntity,
-    PollRequestParametersEntity, ResultSetEntity, ServiceEntity, SubscriptionEntity,
-    SubscriptionParameters, collection_type, response_type, subscription_status,
+    CollectionBridgeEntity, CollectionEntity, ContentBindingEntity, ContentBlockEntity,
+    DeliveryStatus, InboxMessageEntity, PollRequestParametersEntity, PushParametersEntity,
+    ResultSetEntity, ServiceEntity, SubscriptionDeliveryEntity, SubscriptionEntity,
+    SubscriptionParameters, collection_type, content_encoding, delivery_status,
+    format_timestamp_label, response_type, result_set_status, signature_policy, signature_status,
+    subscription_status, truncate_timestamp_label, validation_mode,
 };
 
 // Re-export TAXII 2.x entities
 pub use entities::taxii2::{
-    ApiRoot, Collection, DATETIME_FORMAT, Job, JobDetail, JobDetails, ManifestRecord, STIXObject,
+    ApiRoot, BulkInsertOutcome, BulkInsertReport, Collection, CollisionPolicy, DATETIME_FORMAT,
+    DeletionRecord, Job, JobDetail, JobDetails, ManifestRecord, STIXObject, ValidationRule,
     VersionRecord, taxii2_datetimeformat,
 };
@@ -48,6 +48,9 @@ pub struct ContentBlockCreatedEvent {
     pub content_block: ContentBlockEntity,
 
     /// Collection IDs the block was added to.
+    ///
+    /// The inbox emits an event for each collection a block is added to,
+    /// so it sets at most one.
     pub collection_ids: Vec<i32>,
 
     /// Service ID that received the block.
@@ -146,7 +149,6 @@ pub fn create_hook_registry() -> SharedHookRegistry {
 )]
 mod tests {
     use super::*;
-    use chrono::Utc;
 
     #[tokio::test]
     async fn test_content_block_created_signal() {
@@ -156,11 +158,7 @@ mod tests {
         let event = ContentBlockCreatedEvent {
             content_block: ContentBlockEntity {
                 id: Some(1),
-                content: b"test content".to_vec(),
-                timestamp_label: Utc::now(),
-                content_binding: None,
-                message: None,
-                inbox_message_id: None,
+                ..ContentBlockEntity::new(b"test content", None)
             },
             collection_ids: vec![1, 2],
             service_id: Some("inbox-1".to_string()),
@@ -190,11 +188,7 @@ mod tests {
         let event = ContentBlockCreatedEvent {
             content_block: ContentBlockEntity {
                 id: Some(1),
-                content: b"test".to_vec(),
-                timestamp_label: Utc::now(),
-                content_binding: None,
-                message: None,
-                inbox_message_id: None,
+                ..ContentBlockEntity::new(b"test", None)
             },
             collection_ids: vec![1],
             service_id: None,
@@ -7,7 +7,11 @@ use thiserror::Error;
 pub enum DatabaseError {
     /// SQLx error.
     #[error("Database error: {0}")]
-    Sqlx(#[from] sqlx::Error),
+    Sqlx(sqlx::Error),
+
+    /// No pooled connection became available within the acquire timeout.
+    #[error("Timed out waiting for a database connection")]
+    PoolTimeout,
 
     /// Entity not found.
     #[error("Not found: {0}")]
@@ -29,6 +33,15 @@ pub enum DatabaseError {
     ResultsNotReady,
 }
 
+impl From<sqlx::Error> for DatabaseError {
+    fn from(e: sqlx::Error) -> Self {
+        match e {
+            sqlx::Error::PoolTimedOut => Self::PoolTimeout,
+            e => Self::Sqlx(e),
+        }
+    }
+}
+
 impl DatabaseError {
     /// Create a not found error with context.
     pub fn not_found<S: Into<String>>(msg: S) -> Self {
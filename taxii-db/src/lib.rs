@@ -8,40 +8,60 @@
 //! - **Repository**: Trait-based database access layer for mockability
 //! - **DatabaseManager**: Connection pool lifecycle management
 //! - **TaxiiPool**: Type-safe pool wrapper for database operations
+//! - **TaxiiTransaction**: Unit of work spanning several repository calls
 
 pub mod error;
 pub mod manager;
 pub mod migrations;
 pub mod models;
+pub mod partitioning;
 pub mod pool;
 pub mod repository;
+pub mod retention;
+pub mod stats;
+pub mod stream;
+pub mod transaction;
 
 // Core types
 pub use error::{DatabaseError, DatabaseResult};
 pub use manager::DatabaseManager;
-pub use pool::{PoolOptions, TaxiiPool};
+pub use pool::{PoolOptions, PoolStats, TaxiiPool};
+pub use stream::{RowStream, STREAM_BUFFER_SIZE};
+pub use transaction::TaxiiTransaction;
 
 // Auth models
 pub use models::account::{
     Account, InvalidCollectionRef, TAXII1_PERMISSIONS, TAXII2_PERMISSIONS,
     validate_collection_references, validate_permissions,
 };
-pub use models::account_activity::{AccountActivity, AccountUsageSummary, EventType};
+pub use models::account_activity::{
+    AccountActivity, AccountUsageSummary, ActivityCursor, ActivityFilter, ActivityRecord,
+    EventType, IpPrefix,
+};
+pub use models::account_totp::AccountTotp;
+pub use models::collection_stats::{CachedCollectionStats, TypeStats, aggregate_stats};
+pub use models::password_reset::PasswordReset;
+pub use models::retention_purge::RetentionPurge;
 
 // TAXII 1.x models
 pub use models::taxii1::{
     ContentBindingFilter, ContentBlock, ContentBlockFilter, DataCollection, InboxMessage,
-    NewInboxMessage, ResultSet, Service, Subscription, UpdateDataCollection,
+    NewContentBlock, NewDataCollection, NewInboxMessage, ResultSet, Service, Subscription,
+    UpdateDataCollection,
 };
 
 // TAXII 2.x models
 pub use models::taxii2::{
-    ApiRoot, Collection, FilteredResult, Job, JobDetail, NewJob, NewSTIXObject, PaginatedResult,
-    PaginationCursor, STIXObject, Taxii2QueryParams, VersionInfo, VersionsResult, get_next_param,
-    parse_next_param,
+    ApiRoot, AuditOperation, Collection, DeleteMode, DeletedVersion, FilteredResult, Job,
+    JobDetail, NewJob, NewJobDetail, NewSTIXObject, ObjectAudit, ObjectAuditFilter,
+    ObjectAuditRecord, PaginatedResult, PaginationCursor, PropertyFilter, STIXObject,
+    Taxii2QueryParams, VersionInfo, VersionSelector, VersionsResult, get_next_param,
+    get_search_next_param, get_versions_next_param, parse_next_param,
 };
 
 // Repository traits and implementations
 pub use repository::{
     DbTaxii1Repository, DbTaxii2Repository, Taxii1Repository, Taxii2Repository, get_object_version,
 };
+#[cfg(any(test, feature = "test-util"))]
+pub use repository::{InMemoryTaxii1Repository, InMemoryTaxii2Repository};
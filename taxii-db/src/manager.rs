@@ -13,6 +13,7 @@ use crate::pool::TaxiiPool;
 #[derive(Debug, Clone)]
 pub struct DatabaseManager {
     pool: PgPool,
+    read_pool: Option<PgPool>,
 }
 
 impl DatabaseManager {
@@ -27,7 +28,10 @@ impl DatabaseManager {
             .connect(db_connection)
             .await?;
 
-        Ok(Self { pool })
+        Ok(Self {
+            pool,
+            read_pool: None,
+        })
     }
 
     /// Create a new database manager with custom pool options.
@@ -45,12 +49,40 @@ impl DatabaseManager {
             .connect(db_connection)
             .await?;
 
-        Ok(Self { pool })
+        Ok(Self {
+            pool,
+            read_pool: None,
+        })
+    }
+
+    /// Add a read pool (e.g. on a read replica) for reads.
+    ///
+    /// See [`TaxiiPool::reader`].
+    pub async fn with_read_url(
+        mut self,
+        read_url: &str,
+        max_connections: u32,
+    ) -> DatabaseResult<Self> {
+        let read_pool = PgPoolOptions::new()
+            .max_connections(max_connections)
+            .min_connections(1)
+            .acquire_timeout(std::time::Duration::from_secs(30))
+            .idle_timeout(std::time::Duration::from_secs(600))
+            .max_lifetime(std::time::Duration::from_secs(1800))
+            .connect(read_url)
+            .await?;
+
+        self.read_pool = Some(read_pool);
+        Ok(self)
     }
 
     /// Get a TaxiiPool for database operations.
     pub fn pool(&self) -> TaxiiPool {
-        TaxiiPool::new(self.pool.clone())
+        let pool = TaxiiPool::new(self.pool.clone());
+        match &self.read_pool {
+            Some(read_pool) => pool.with_read_pool(read_pool.clone()),
+            None => pool,
+        }
     }
 
     /// Get a reference to the inner PgPool.
@@ -74,6 +106,9 @@ impl DatabaseManager {
     /// Close all database connections gracefully.
     pub async fn close(&self) {
         self.pool.close().await;
+        if let Some(read_pool) = &self.read_pool {
+            read_pool.close().await;
+        }
     }
 
     /// Check if the pool is closed.
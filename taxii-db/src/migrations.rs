@@ -1,21 +1,107 @@
 //! Database migrations for TAXII server.
 //!
 //! Migrations are managed via SQLx and stored in the `migrations/` directory
-//! at the project root.
+//! at the project root. SQLx records the checksum of every applied
+//! migration in `_sqlx_migrations`; a migration file that changed after it
+//! was applied is reported by [`status`] and stops [`run`].
+//!
+//! A migration can have a paired down-migration
+//! (`<version>_<description>.down.sql` next to the migration file), which
+//! [`revert_last`] executes to roll back a bad deploy.
+
+use std::collections::HashMap;
 
+use chrono::{DateTime, Utc};
 use sqlx::PgPool;
-use sqlx::migrate::{MigrateError, Migrator};
+use sqlx::migrate::{MigrateError, Migration, Migrator};
+use thiserror::Error;
 
 /// Static migrator loaded from `migrations/` directory at compile time.
 static MIGRATOR: Migrator = sqlx::migrate!("../migrations");
 
+/// Migration errors.
+#[derive(Debug, Error)]
+pub enum MigrationError {
+    /// SQLx migration error.
+    #[error(transparent)]
+    Migrate(#[from] MigrateError),
+
+    /// SQLx error.
+    #[error("Database error: {0}")]
+    Sqlx(#[from] sqlx::Error),
+
+    /// Applied migrations whose files changed since they were applied.
+    #[error(
+        "migration(s) {} were modified after they were applied; restore the original files",
+        join_versions(.0)
+    )]
+    Modified(Vec<i64>),
+
+    /// The last applied migration has no down-migration.
+    #[error("migration {0} has no down-migration and cannot be reverted")]
+    Irreversible(i64),
+}
+
+fn join_versions(versions: &[i64]) -> String {
+    versions
+        .iter()
+        .map(i64::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
 /// Run all pending migrations.
 ///
 /// This is idempotent - migrations that have already been applied will be skipped.
 /// For existing OpenTAXII databases, the initial migration uses `IF NOT EXISTS`
 /// to avoid conflicts with existing tables.
-pub async fn run(pool: &PgPool) -> Result<(), MigrateError> {
-    MIGRATOR.run(pool).await
+///
+/// Fails without applying anything if an applied migration was modified.
+pub async fn run(pool: &PgPool) -> Result<(), MigrationError> {
+    verify(pool).await?;
+    MIGRATOR.run(pool).await?;
+    Ok(())
+}
+
+/// Check that no applied migration was modified since it was applied.
+pub async fn verify(pool: &PgPool) -> Result<(), MigrationError> {
+    let modified: Vec<i64> = status(pool)
+        .await?
+        .into_iter()
+        .filter(|m| m.state == MigrationState::Modified)
+        .map(|m| m.version)
+        .collect();
+
+    if modified.is_empty() {
+        Ok(())
+    } else {
+        Err(MigrationError::Modified(modified))
+    }
+}
+
+/// Revert the last applied migration using its down-migration.
+///
+/// Down-migrations can drop data, so callers should require explicit
+/// confirmation. Returns the reverted migration, or `None` if no migration
+/// is applied.
+pub async fn revert_last(pool: &PgPool) -> Result<Option<MigrationInfo>, MigrationError> {
+    verify(pool).await?;
+
+    let Some(last) = applied(pool).await?.into_iter().max() else {
+        return Ok(None);
+    };
+
+    let Some(info) = list().into_iter().find(|m| m.version == last) else {
+        return Err(MigrateError::VersionMissing(last).into());
+    };
+    if !info.reversible {
+        return Err(MigrationError::Irreversible(last));
+    }
+
+    // Reverts every applied migration above the target, which is only `last`
+    MIGRATOR.undo(pool, last - 1).await?;
+
+    Ok(Some(info))
 }
 
 /// Information about a migration.
@@ -25,33 +111,271 @@ pub struct MigrationInfo {
     pub version: i64,
     /// Migration description.
     pub description: String,
+    /// Whether the migration has a down-migration.
+    pub reversible: bool,
 }
 
-/// Get list of all migrations defined in the migrations directory.
-pub fn list() -> Vec<MigrationInfo> {
+/// Up-migrations of the migrator.
+fn up_migrations() -> impl Iterator<Item = &'static Migration> {
     MIGRATOR
         .iter()
+        .filter(|m| m.migration_type.is_up_migration())
+}
+
+/// Get list of all migrations defined in the migrations directory.
+pub fn list() -> Vec<MigrationInfo> {
+    up_migrations()
         .map(|m| MigrationInfo {
             version: m.version,
             description: m.description.to_string(),
+            reversible: MIGRATOR
+                .iter()
+                .any(|d| d.version == m.version && d.migration_type.is_down_migration()),
         })
         .collect()
 }
 
 /// Get the number of migrations.
 pub fn count() -> usize {
-    MIGRATOR.iter().count()
+    up_migrations().count()
 }
 
 /// Get information about applied migrations by querying the database.
 ///
 /// Returns version numbers of all applied migrations.
 pub async fn applied(pool: &PgPool) -> Result<Vec<i64>, sqlx::Error> {
-    // Query the SQLx migrations table directly
-    let rows: Vec<(i64,)> = sqlx::query_as("SELECT version FROM _sqlx_migrations ORDER BY version")
-        .fetch_all(pool)
-        .await
-        .unwrap_or_default();
+    Ok(applied_rows(pool)
+        .await?
+        .into_iter()
+        .map(|row| row.version)
+        .collect())
+}
+
+/// State of a migration in the database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MigrationState {
+    /// Applied, and the file is unchanged.
+    Applied,
+    /// Not applied yet.
+    Pending,
+    /// Applied, but the file changed since.
+    Modified,
+    /// Started but did not complete.
+    Failed,
+    /// Applied, but unknown to this build (e.g. after a downgrade).
+    Missing,
+}
+
+impl MigrationState {
+    /// Get the state as a string.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Applied => "applied",
+            Self::Pending => "pending",
+            Self::Modified => "modified",
+            Self::Failed => "failed",
+            Self::Missing => "missing",
+        }
+    }
+}
+
+/// Status of a migration.
+#[derive(Debug, Clone)]
+pub struct MigrationStatus {
+    /// Migration version (timestamp).
+    pub version: i64,
+    /// Migration description.
+    pub description: String,
+    /// State in the database.
+    pub state: MigrationState,
+    /// Whether the migration has a down-migration.
+    pub reversible: bool,
+    /// Time the migration was applied.
+    pub installed_on: Option<DateTime<Utc>>,
+}
+
+/// Row of the SQLx migrations table.
+#[derive(sqlx::FromRow)]
+struct AppliedRow {
+    version: i64,
+    description: String,
+    installed_on: DateTime<Utc>,
+    success: bool,
+    checksum: Vec<u8>,
+}
+
+/// Get the rows of the SQLx migrations table, which does not exist before
+/// the first run.
+async fn applied_rows(pool: &PgPool) -> Result<Vec<AppliedRow>, sqlx::Error> {
+    let exists: bool = sqlx::query_scalar("SELECT to_regclass('_sqlx_migrations') IS NOT NULL")
+        .fetch_one(pool)
+        .await?;
+    if !exists {
+        return Ok(Vec::new());
+    }
+
+    sqlx::query_as(
+        "SELECT version, description, installed_on, success, checksum
+         FROM _sqlx_migrations ORDER BY version",
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// List all known and applied migrations with their state, by version.
+pub async fn status(pool: &PgPool) -> Result<Vec<MigrationStatus>, sqlx::Error> {
+    let mut rows: HashMap<i64, AppliedRow> = applied_rows(pool)
+        .await?
+        .into_iter()
+        .map(|row| (row.version, row))
+        .collect();
+
+    let mut statuses: Vec<MigrationStatus> = list()
+        .into_iter()
+        .zip(up_migrations())
+        .map(|(info, migration)| {
+            let row = rows.remove(&info.version);
+            let state = match &row {
+                None => MigrationState::Pending,
+                Some(row) if !row.success => MigrationState::Failed,
+                Some(row) if row.checksum != *migration.checksum => MigrationState::Modified,
+                Some(_) => MigrationState::Applied,
+            };
+            MigrationStatus {
+                version: info.version,
+                description: info.description,
+                state,
+                reversible: info.reversible,
+                installed_on: row.map(|row| row.installed_on),
+            }
+        })
+        .collect();
+
+    statuses.extend(rows.into_values().map(|row| MigrationStatus {
+        version: row.version,
+        description: row.description,
+        state: MigrationState::Missing,
+        reversible: false,
+        installed_on: Some(row.installed_on),
+    }));
+    statuses.sort_by_key(|s| s.version);
+
+    Ok(statuses)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+    fn states(statuses: &[MigrationStatus]) -> Vec<MigrationState> {
+        statuses.iter().map(|s| s.state).collect()
+    }
+
+    #[test]
+    fn test_list_excludes_down_migrations() {
+        let migrations = list();
+        assert_eq!(migrations.len(), count());
+
+        let mut versions: Vec<i64> = migrations.iter().map(|m| m.version).collect();
+        versions.dedup();
+        assert_eq!(versions.len(), migrations.len());
+
+        // The latest migration can always be reverted
+        assert!(migrations.last().is_some_and(|m| m.reversible));
+    }
+
+    /// A migration file changed after it was applied stops further runs.
+    #[sqlx::test(migrations = false)]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_checksum_mismatch_is_detected(pool: PgPool) -> TestResult {
+        let pending = status(&pool).await?;
+        assert_eq!(pending.len(), count());
+        assert!(
+            states(&pending)
+                .iter()
+                .all(|s| *s == MigrationState::Pending)
+        );
+
+        run(&pool).await?;
+        let applied = status(&pool).await?;
+        assert!(
+            states(&applied)
+                .iter()
+                .all(|s| *s == MigrationState::Applied)
+        );
+        assert!(applied.iter().all(|s| s.installed_on.is_some()));
+
+        let first = list().first().map(|m| m.version).ok_or("no migrations")?;
+        sqlx::query("UPDATE _sqlx_migrations SET checksum = '\\x00' WHERE version = $1")
+            .bind(first)
+            .execute(&pool)
+            .await?;
+
+        let modified = status(&pool).await?;
+        assert_eq!(modified[0].state, MigrationState::Modified);
+        assert!(
+            states(&modified[1..])
+                .iter()
+                .all(|s| *s == MigrationState::Applied)
+        );
+
+        match run(&pool).await {
+            Err(MigrationError::Modified(versions)) => assert_eq!(versions, vec![first]),
+            other => return Err(format!("expected a modified migration, got {other:?}").into()),
+        }
+        assert!(matches!(
+            revert_last(&pool).await,
+            Err(MigrationError::Modified(_))
+        ));
+
+        // A migration applied by a newer build is reported, not hidden
+        sqlx::query(
+            "INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time)
+             VALUES (99990101000000, 'from the future', TRUE, '\\x00', 0)",
+        )
+        .execute(&pool)
+        .await?;
+        let future = status(&pool).await?;
+        assert_eq!(
+            future.last().map(|s| (s.version, s.state)),
+            Some((99990101000000, MigrationState::Missing))
+        );
+
+        Ok(())
+    }
+
+    /// Reverting the last migration and applying it again.
+    #[sqlx::test(migrations = false)]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_apply_revert_reapply(pool: PgPool) -> TestResult {
+        assert!(revert_last(&pool).await?.is_none());
+
+        run(&pool).await?;
+        let last = list().pop().ok_or("no migrations")?;
+
+        let reverted = revert_last(&pool).await?.ok_or("nothing reverted")?;
+        assert_eq!(reverted.version, last.version);
+        let reverted_status = status(&pool).await?;
+        assert_eq!(
+            reverted_status.last().map(|s| (s.version, s.state)),
+            Some((last.version, MigrationState::Pending))
+        );
+        assert!(
+            states(&reverted_status[..reverted_status.len() - 1])
+                .iter()
+                .all(|s| *s == MigrationState::Applied)
+        );
+
+        run(&pool).await?;
+        let reapplied = status(&pool).await?;
+        assert!(
+            states(&reapplied)
+                .iter()
+                .all(|s| *s == MigrationState::Applied)
+        );
 
-    Ok(rows.into_iter().map(|(v,)| v).collect())
+        Ok(())
+    }
 }
@@ -37,6 +37,14 @@ pub struct Account {
     /// Permissions as JSON text.
     #[sqlx(rename = "_permissions")]
     pub permissions_json: String,
+
+    /// STIX identity (`identity--<uuid>`) the account's objects are
+    /// attributed to.
+    pub identity_id: Option<String>,
+
+    /// Generation of the account's tokens; tokens of an older generation
+    /// are no longer accepted.
+    pub token_generation: i32,
 }
 
 impl Account {
@@ -52,7 +60,8 @@ impl Account {
         let account = sqlx::query_as!(
             Self,
             r#"SELECT id, username as "username!", password_hash as "password_hash!",
-                      is_admin as "is_admin!", _permissions as "permissions_json!"
+                      is_admin as "is_admin!", _permissions as "permissions_json!", identity_id,
+                      token_generation
                FROM accounts WHERE id = $1"#,
             id
         )
@@ -70,7 +79,8 @@ impl Account {
         let account = sqlx::query_as!(
             Self,
             r#"SELECT id, username as "username!", password_hash as "password_hash!",
-                      is_admin as "is_admin!", _permissions as "permissions_json!"
+                      is_admin as "is_admin!", _permissions as "permissions_json!", identity_id,
+                      token_generation
                FROM accounts WHERE username = $1"#,
             username
         )
@@ -85,7 +95,8 @@ impl Account {
         let accounts = sqlx::query_as!(
             Self,
             r#"SELECT id, username as "username!", password_hash as "password_hash!",
-                      is_admin as "is_admin!", _permissions as "permissions_json!"
+                      is_admin as "is_admin!", _permissions as "permissions_json!", identity_id,
+                      token_generation
                FROM accounts"#
         )
         .fetch_all(pool.inner())
@@ -94,6 +105,15 @@ impl Account {
         Ok(accounts)
     }
 
+    /// Check whether any account exists.
+    pub async fn exists_any(pool: &TaxiiPool) -> DatabaseResult<bool> {
+        let exists = sqlx::query_scalar!(r#"SELECT EXISTS(SELECT 1 FROM accounts) as "exists!""#)
+            .fetch_one(pool.inner())
+            .await?;
+
+        Ok(exists)
+    }
+
     /// Create a new account.
     pub async fn create(
         pool: &TaxiiPool,
@@ -164,6 +184,25 @@ impl Account {
             .ok_or_else(|| DatabaseError::not_found("Account not found"))
     }
 
+    /// Associate an account with a STIX identity, or clear it with `None`.
+    pub async fn set_identity(
+        pool: &TaxiiPool,
+        id: i32,
+        identity_id: Option<&str>,
+    ) -> DatabaseResult<Self> {
+        sqlx::query!(
+            "UPDATE accounts SET identity_id = $2 WHERE id = $1",
+            id,
+            identity_id
+        )
+        .execute(pool.inner())
+        .await?;
+
+        Self::find(pool, id)
+            .await?
+            .ok_or_else(|| DatabaseError::not_found("Account not found"))
+    }
+
     /// Delete an account by username.
     pub async fn delete_by_username(pool: &TaxiiPool, username: &str) -> DatabaseResult<bool> {
         let result = sqlx::query!("DELETE FROM accounts WHERE username = $1", username)
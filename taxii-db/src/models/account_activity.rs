@@ -1,11 +1,15 @@
 //! Account activity model for tracking credential usage.
 
+use std::collections::BTreeMap;
+use std::fmt;
 use std::net::IpAddr;
 
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
 use chrono::{DateTime, Utc};
 use sqlx::FromRow;
 
 use crate::error::DatabaseResult;
+use crate::models::taxii2::PaginatedResult;
 use crate::pool::TaxiiPool;
 
 /// Event types for account activity.
@@ -15,6 +19,8 @@ pub enum EventType {
     LoginSuccess,
     /// Failed login attempt.
     LoginFailed,
+    /// Password reset with a reset token.
+    PasswordReset,
 }
 
 impl EventType {
@@ -24,10 +30,138 @@ impl EventType {
         match self {
             Self::LoginSuccess => "login_success",
             Self::LoginFailed => "login_failed",
+            Self::PasswordReset => "password_reset",
+        }
+    }
+
+    /// Parse from the database representation.
+    ///
+    /// Dashes are accepted in place of underscores (`login-failed`).
+    #[must_use]
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.replace('-', "_").as_str() {
+            "login_success" => Some(Self::LoginSuccess),
+            "login_failed" => Some(Self::LoginFailed),
+            "password_reset" => Some(Self::PasswordReset),
+            _ => None,
         }
     }
 }
 
+/// Activity record with the account's username.
+#[derive(Debug, Clone, FromRow)]
+pub struct ActivityRecord {
+    /// Primary key.
+    pub id: i64,
+
+    /// Account ID.
+    pub account_id: i32,
+
+    /// Username of the account.
+    pub username: String,
+
+    /// Event type (login_success, login_failed, password_reset).
+    pub event_type: String,
+
+    /// Client IP address (optional).
+    pub ip_address: Option<String>,
+
+    /// Client user agent (optional).
+    pub user_agent: Option<String>,
+
+    /// Timestamp of the event.
+    pub created_at: DateTime<Utc>,
+}
+
+/// Keyset position in activity results, which are ordered newest first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActivityCursor {
+    /// Timestamp of the last record returned.
+    pub created_at: DateTime<Utc>,
+    /// ID of the last record returned, for tie-breaking.
+    pub id: i64,
+}
+
+impl ActivityCursor {
+    /// Encode as an opaque string for clients.
+    #[must_use]
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(format!("{}|{}", self.created_at.to_rfc3339(), self.id))
+    }
+
+    /// Parse a string produced by [`Self::encode`].
+    #[must_use]
+    pub fn parse(value: &str) -> Option<Self> {
+        let decoded = String::from_utf8(URL_SAFE_NO_PAD.decode(value).ok()?).ok()?;
+        let (created_at, id) = decoded.split_once('|')?;
+
+        Some(Self {
+            created_at: DateTime::parse_from_rfc3339(created_at)
+                .ok()?
+                .with_timezone(&Utc),
+            id: id.parse().ok()?,
+        })
+    }
+}
+
+/// Client IP address prefix in CIDR notation (`192.0.2.0/24`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpPrefix {
+    /// Network address (host bits are ignored).
+    pub address: IpAddr,
+    /// Prefix length in bits.
+    pub length: u8,
+}
+
+impl IpPrefix {
+    /// Parse CIDR notation; a bare address is a prefix of its full length.
+    #[must_use]
+    pub fn parse(value: &str) -> Option<Self> {
+        let (address, length) = match value.split_once('/') {
+            Some((address, length)) => (address.parse().ok()?, Some(length.parse().ok()?)),
+            None => (value.parse().ok()?, None),
+        };
+        let max = match address {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        let length = length.unwrap_or(max);
+
+        (length <= max).then_some(Self { address, length })
+    }
+}
+
+impl fmt::Display for IpPrefix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.address, self.length)
+    }
+}
+
+/// Filter for querying account activity.
+///
+/// All set criteria must match. Results are ordered newest first.
+#[derive(Debug, Clone, Default)]
+pub struct ActivityFilter {
+    /// Only events of this account.
+    pub account_id: Option<i32>,
+    /// Only events of the account with this username.
+    pub username: Option<String>,
+    /// Only events of these types (any type if empty).
+    pub event_types: Vec<EventType>,
+    /// Only events at or after this time.
+    pub since: Option<DateTime<Utc>>,
+    /// Only events before this time.
+    pub until: Option<DateTime<Utc>>,
+    /// Only events from this client IP address.
+    pub ip_address: Option<IpAddr>,
+    /// Only events from client IP addresses within this prefix.
+    pub ip_prefix: Option<IpPrefix>,
+    /// Maximum number of records to return.
+    pub limit: Option<i64>,
+    /// Continue after this position (from a previous page).
+    pub after: Option<ActivityCursor>,
+}
+
 /// Account activity database row.
 ///
 /// Table: account_activity
@@ -39,7 +173,7 @@ pub struct AccountActivity {
     /// Account ID (foreign key to accounts).
     pub account_id: i32,
 
-    /// Event type (login_success, login_failed).
+    /// Event type (login_success, login_failed, password_reset).
     pub event_type: String,
 
     /// Client IP address (optional).
@@ -127,6 +261,170 @@ impl AccountActivity {
         Ok(result.rows_affected() > 0)
     }
 
+    /// Query activity records, newest first, with keyset pagination.
+    pub async fn find_filtered(
+        pool: &TaxiiPool,
+        filter: &ActivityFilter,
+    ) -> DatabaseResult<PaginatedResult<Vec<ActivityRecord>>> {
+        let mut query = String::from(
+            r#"SELECT aa.id, aa.account_id, a.username, aa.event_type,
+                      host(aa.ip_address) AS ip_address, aa.user_agent, aa.created_at
+               FROM account_activity aa
+               JOIN accounts a ON a.id = aa.account_id
+               WHERE TRUE"#,
+        );
+
+        let mut param_idx = 1;
+
+        if filter.account_id.is_some() {
+            query.push_str(&format!(" AND aa.account_id = ${param_idx}"));
+            param_idx += 1;
+        }
+
+        if filter.username.is_some() {
+            query.push_str(&format!(" AND a.username = ${param_idx}"));
+            param_idx += 1;
+        }
+
+        if !filter.event_types.is_empty() {
+            query.push_str(&format!(" AND aa.event_type = ANY(${param_idx})"));
+            param_idx += 1;
+        }
+
+        if filter.since.is_some() {
+            query.push_str(&format!(" AND aa.created_at >= ${param_idx}"));
+            param_idx += 1;
+        }
+
+        if filter.until.is_some() {
+            query.push_str(&format!(" AND aa.created_at < ${param_idx}"));
+            param_idx += 1;
+        }
+
+        if filter.ip_address.is_some() {
+            query.push_str(&format!(" AND aa.ip_address = ${param_idx}::text::inet"));
+            param_idx += 1;
+        }
+
+        if filter.ip_prefix.is_some() {
+            query.push_str(&format!(" AND aa.ip_address <<= ${param_idx}::text::inet"));
+            param_idx += 1;
+        }
+
+        if filter.after.is_some() {
+            query.push_str(&format!(
+                " AND (aa.created_at, aa.id) < (${}, ${})",
+                param_idx,
+                param_idx + 1
+            ));
+        }
+
+        query.push_str(" ORDER BY aa.created_at DESC, aa.id DESC");
+
+        // Apply limit + 1 for efficient "more" detection
+        if let Some(limit) = filter.limit {
+            query.push_str(&format!(" LIMIT {}", limit + 1));
+        }
+
+        let mut q = sqlx::query_as::<_, ActivityRecord>(&query);
+
+        if let Some(account_id) = filter.account_id {
+            q = q.bind(account_id);
+        }
+
+        if let Some(username) = &filter.username {
+            q = q.bind(username);
+        }
+
+        if !filter.event_types.is_empty() {
+            let event_types: Vec<&str> = filter.event_types.iter().map(|e| e.as_str()).collect();
+            q = q.bind(event_types);
+        }
+
+        if let Some(since) = filter.since {
+            q = q.bind(since);
+        }
+
+        if let Some(until) = filter.until {
+            q = q.bind(until);
+        }
+
+        if let Some(ip_address) = filter.ip_address {
+            q = q.bind(ip_address.to_string());
+        }
+
+        if let Some(ip_prefix) = filter.ip_prefix {
+            q = q.bind(ip_prefix.to_string());
+        }
+
+        if let Some(after) = &filter.after {
+            q = q.bind(after.created_at).bind(after.id);
+        }
+
+        let mut records = q.fetch_all(pool.inner()).await?;
+
+        let more = filter
+            .limit
+            .is_some_and(|limit| records.len() as i64 > limit);
+        if let Some(limit) = filter.limit {
+            records.truncate(limit as usize);
+        }
+
+        let next = if more {
+            records.last().map(|last| {
+                ActivityCursor {
+                    created_at: last.created_at,
+                    id: last.id,
+                }
+                .encode()
+            })
+        } else {
+            None
+        };
+
+        Ok(PaginatedResult::new(records, more, next))
+    }
+
+    /// Get the most recent successful login of an account.
+    pub async fn last_successful_login(
+        pool: &TaxiiPool,
+        account_id: i32,
+    ) -> DatabaseResult<Option<ActivityRecord>> {
+        let filter = ActivityFilter {
+            account_id: Some(account_id),
+            event_types: vec![EventType::LoginSuccess],
+            limit: Some(1),
+            ..Default::default()
+        };
+
+        Ok(Self::find_filtered(pool, &filter)
+            .await?
+            .items
+            .into_iter()
+            .next())
+    }
+
+    /// Count events since `since` by event type, for dashboards.
+    pub async fn count_by_event_type(
+        pool: &TaxiiPool,
+        since: DateTime<Utc>,
+    ) -> DatabaseResult<BTreeMap<String, i64>> {
+        let rows = sqlx::query!(
+            r#"SELECT event_type, COUNT(*) as "count!"
+               FROM account_activity
+               WHERE created_at >= $1
+               GROUP BY event_type"#,
+            since
+        )
+        .fetch_all(pool.inner())
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.event_type, row.count))
+            .collect())
+    }
+
     /// Get account usage summary for all accounts.
     ///
     /// Returns usage statistics including last login and counts.
@@ -284,3 +582,50 @@ impl AccountActivity {
         Ok(result.rows_affected())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_type_parse() {
+        assert_eq!(
+            EventType::parse("login_failed"),
+            Some(EventType::LoginFailed)
+        );
+        assert_eq!(
+            EventType::parse("login-success"),
+            Some(EventType::LoginSuccess)
+        );
+        assert_eq!(
+            EventType::parse("password-reset"),
+            Some(EventType::PasswordReset)
+        );
+        assert_eq!(EventType::parse("logout"), None);
+    }
+
+    #[test]
+    fn test_cursor_roundtrip() {
+        let cursor = ActivityCursor {
+            created_at: Utc::now(),
+            id: 42,
+        };
+
+        assert_eq!(ActivityCursor::parse(&cursor.encode()), Some(cursor));
+        assert_eq!(ActivityCursor::parse("not a cursor"), None);
+    }
+
+    #[test]
+    fn test_ip_prefix_parse() {
+        let prefix = IpPrefix::parse("10.0.0.0/24");
+        assert_eq!(
+            prefix.map(|p| p.to_string()).as_deref(),
+            Some("10.0.0.0/24")
+        );
+        assert_eq!(IpPrefix::parse("2001:db8::1").map(|p| p.length), Some(128));
+        assert_eq!(IpPrefix::parse("10.0.0.1").map(|p| p.length), Some(32));
+        assert_eq!(IpPrefix::parse("10.0.0.0/33"), None);
+        assert_eq!(IpPrefix::parse("10.0.0.0/x"), None);
+        assert_eq!(IpPrefix::parse("example.com"), None);
+    }
+}
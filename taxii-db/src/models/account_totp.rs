@@ -0,0 +1,198 @@
+//! Account TOTP model for two-factor authentication.
+
+use chrono::{DateTime, Utc};
+use sqlx::FromRow;
+
+use crate::error::DatabaseResult;
+use crate::pool::TaxiiPool;
+
+/// Account TOTP database row.
+///
+/// Table: account_totp
+///
+/// Recovery codes are stored in the `account_recovery_codes` table and
+/// are removed together with the TOTP row.
+#[derive(Debug, Clone, FromRow)]
+pub struct AccountTotp {
+    /// Account ID (primary key, foreign key to accounts).
+    pub account_id: i32,
+
+    /// Encrypted TOTP secret.
+    pub secret_encrypted: String,
+
+    /// Last accepted time step (for replay prevention).
+    pub last_used_step: Option<i64>,
+
+    /// Failed second factor attempts since the last success or lockout.
+    pub failed_attempts: i32,
+
+    /// Time until which second factor attempts are refused.
+    pub locked_until: Option<DateTime<Utc>>,
+
+    /// Enrollment timestamp.
+    pub created_at: DateTime<Utc>,
+}
+
+impl AccountTotp {
+    /// Find the TOTP enrollment of an account.
+    pub async fn find(pool: &TaxiiPool, account_id: i32) -> DatabaseResult<Option<Self>> {
+        let totp = sqlx::query_as!(
+            Self,
+            r#"SELECT account_id, secret_encrypted, last_used_step, failed_attempts,
+                      locked_until, created_at
+               FROM account_totp WHERE account_id = $1"#,
+            account_id
+        )
+        .fetch_optional(pool.inner())
+        .await?;
+
+        Ok(totp)
+    }
+
+    /// Enroll an account, replacing any previous secret and recovery codes.
+    pub async fn enroll(
+        pool: &TaxiiPool,
+        account_id: i32,
+        secret_encrypted: &str,
+        recovery_code_hashes: &[String],
+    ) -> DatabaseResult<Self> {
+        let mut tx = pool.inner().begin().await?;
+
+        sqlx::query!("DELETE FROM account_totp WHERE account_id = $1", account_id)
+            .execute(&mut *tx)
+            .await?;
+
+        let totp = sqlx::query_as!(
+            Self,
+            r#"INSERT INTO account_totp (account_id, secret_encrypted)
+               VALUES ($1, $2)
+               RETURNING account_id, secret_encrypted, last_used_step, failed_attempts,
+                         locked_until, created_at"#,
+            account_id,
+            secret_encrypted
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            r#"INSERT INTO account_recovery_codes (account_id, code_hash)
+               SELECT $1, code_hash FROM UNNEST($2::VARCHAR[]) AS codes(code_hash)"#,
+            account_id,
+            recovery_code_hashes
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(totp)
+    }
+
+    /// Record a time step as used.
+    ///
+    /// Returns `false` if the step (or a later one) was already used, so
+    /// concurrent logins cannot accept the same code twice.
+    pub async fn mark_step_used(
+        pool: &TaxiiPool,
+        account_id: i32,
+        step: i64,
+    ) -> DatabaseResult<bool> {
+        let result = sqlx::query!(
+            r#"UPDATE account_totp SET last_used_step = $2
+               WHERE account_id = $1 AND (last_used_step IS NULL OR last_used_step < $2)"#,
+            account_id,
+            step
+        )
+        .execute(pool.inner())
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Consume an unused recovery code.
+    ///
+    /// Returns `false` if no unused code with this hash exists.
+    pub async fn use_recovery_code(
+        pool: &TaxiiPool,
+        account_id: i32,
+        code_hash: &str,
+    ) -> DatabaseResult<bool> {
+        let result = sqlx::query!(
+            r#"UPDATE account_recovery_codes SET used_at = NOW()
+               WHERE account_id = $1 AND code_hash = $2 AND used_at IS NULL"#,
+            account_id,
+            code_hash
+        )
+        .execute(pool.inner())
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Record a failed second factor attempt.
+    ///
+    /// The `max_failures`th consecutive failure locks the second factor for
+    /// `lockout_secs` and starts counting anew. Returns the end of the
+    /// lockout, if any.
+    pub async fn record_failure(
+        pool: &TaxiiPool,
+        account_id: i32,
+        max_failures: i32,
+        lockout_secs: f64,
+    ) -> DatabaseResult<Option<DateTime<Utc>>> {
+        let locked_until = sqlx::query_scalar!(
+            r#"UPDATE account_totp SET
+                   failed_attempts = CASE WHEN failed_attempts + 1 >= $2
+                                          THEN 0 ELSE failed_attempts + 1 END,
+                   locked_until = CASE WHEN failed_attempts + 1 >= $2
+                                       THEN NOW() + make_interval(secs => $3)
+                                       ELSE locked_until END
+               WHERE account_id = $1
+               RETURNING locked_until"#,
+            account_id,
+            max_failures,
+            lockout_secs
+        )
+        .fetch_optional(pool.inner())
+        .await?;
+
+        Ok(locked_until.flatten())
+    }
+
+    /// Forget failed second factor attempts after a successful one.
+    pub async fn reset_failures(pool: &TaxiiPool, account_id: i32) -> DatabaseResult<()> {
+        sqlx::query!(
+            "UPDATE account_totp SET failed_attempts = 0 WHERE account_id = $1 AND failed_attempts > 0",
+            account_id
+        )
+        .execute(pool.inner())
+        .await?;
+
+        Ok(())
+    }
+
+    /// Count unused recovery codes of an account.
+    pub async fn count_unused_recovery_codes(
+        pool: &TaxiiPool,
+        account_id: i32,
+    ) -> DatabaseResult<i64> {
+        let count = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) as "count!" FROM account_recovery_codes
+               WHERE account_id = $1 AND used_at IS NULL"#,
+            account_id
+        )
+        .fetch_one(pool.inner())
+        .await?;
+
+        Ok(count)
+    }
+
+    /// Remove the TOTP enrollment (and recovery codes) of an account.
+    pub async fn delete(pool: &TaxiiPool, account_id: i32) -> DatabaseResult<bool> {
+        let result = sqlx::query!("DELETE FROM account_totp WHERE account_id = $1", account_id)
+            .execute(pool.inner())
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
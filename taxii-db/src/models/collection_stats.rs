@@ -0,0 +1,174 @@
+//! Collection statistics models.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use sqlx::types::Json;
+use sqlx::{FromRow, PgConnection};
+use taxii_core::CollectionStats;
+
+use crate::error::DatabaseResult;
+use crate::pool::TaxiiPool;
+
+/// Statistics of one object type (or content binding) of a collection.
+#[derive(Debug, Clone, FromRow)]
+pub struct TypeStats {
+    /// STIX type or content binding.
+    pub object_type: String,
+
+    /// Stored objects (every version counts) or content blocks.
+    pub object_count: i64,
+
+    /// Distinct object IDs.
+    pub distinct_ids: i64,
+
+    /// When the latest object or content block was added.
+    pub latest_added: Option<DateTime<Utc>>,
+}
+
+/// Combine per-type statistics into collection statistics.
+pub fn aggregate_stats(types: Vec<TypeStats>, computed_at: DateTime<Utc>) -> CollectionStats {
+    let mut stats = CollectionStats {
+        object_count: 0,
+        distinct_ids: 0,
+        latest_added: None,
+        by_type: BTreeMap::new(),
+        computed_at,
+    };
+
+    for t in types {
+        let count = t.object_count.max(0) as u64;
+        stats.object_count += count;
+        // An ID always has the same type, so distinct IDs add up
+        stats.distinct_ids += t.distinct_ids.max(0) as u64;
+        stats.latest_added = stats.latest_added.max(t.latest_added);
+        *stats.by_type.entry(t.object_type).or_default() += count;
+    }
+
+    stats
+}
+
+/// Cached collection statistics database row.
+///
+/// Table: collection_stats
+///
+/// Only very large collections are cached; the server refreshes the rows
+/// periodically (see [`crate::stats`]).
+#[derive(Debug, Clone, FromRow)]
+pub struct CachedCollectionStats {
+    /// Protocol of the collection (`taxii1` or `taxii2`).
+    pub protocol: String,
+
+    /// Collection ID (integer for TAXII 1.x, UUID for TAXII 2.x).
+    pub collection_id: String,
+
+    /// Stored objects or content blocks.
+    pub object_count: i64,
+
+    /// Distinct object IDs.
+    pub distinct_ids: i64,
+
+    /// When the latest object or content block was added.
+    pub latest_added: Option<DateTime<Utc>>,
+
+    /// Object count by type.
+    pub by_type: Json<BTreeMap<String, u64>>,
+
+    /// When the statistics were computed.
+    pub computed_at: DateTime<Utc>,
+}
+
+impl From<CachedCollectionStats> for CollectionStats {
+    fn from(row: CachedCollectionStats) -> Self {
+        Self {
+            object_count: row.object_count.max(0) as u64,
+            distinct_ids: row.distinct_ids.max(0) as u64,
+            latest_added: row.latest_added,
+            by_type: row.by_type.0,
+            computed_at: row.computed_at,
+        }
+    }
+}
+
+impl CachedCollectionStats {
+    /// Find the cached statistics of a collection.
+    pub async fn find(
+        pool: &TaxiiPool,
+        protocol: &str,
+        collection_id: &str,
+    ) -> DatabaseResult<Option<Self>> {
+        let row = sqlx::query_as!(
+            Self,
+            r#"SELECT protocol, collection_id, object_count, distinct_ids, latest_added,
+                      by_type as "by_type: Json<BTreeMap<String, u64>>", computed_at
+               FROM collection_stats
+               WHERE protocol = $1 AND collection_id = $2"#,
+            protocol,
+            collection_id
+        )
+        .fetch_optional(pool.inner())
+        .await?;
+
+        Ok(row)
+    }
+
+    /// Store the statistics of a collection, replacing cached ones.
+    pub async fn store(
+        pool: &TaxiiPool,
+        protocol: &str,
+        collection_id: &str,
+        stats: &CollectionStats,
+    ) -> DatabaseResult<()> {
+        sqlx::query!(
+            r#"INSERT INTO collection_stats
+                   (protocol, collection_id, object_count, distinct_ids, latest_added, by_type, computed_at)
+               VALUES ($1, $2, $3, $4, $5, $6, $7)
+               ON CONFLICT (protocol, collection_id) DO UPDATE SET
+                   object_count = EXCLUDED.object_count,
+                   distinct_ids = EXCLUDED.distinct_ids,
+                   latest_added = EXCLUDED.latest_added,
+                   by_type = EXCLUDED.by_type,
+                   computed_at = EXCLUDED.computed_at"#,
+            protocol,
+            collection_id,
+            stats.object_count as i64,
+            stats.distinct_ids as i64,
+            stats.latest_added,
+            Json(&stats.by_type) as _,
+            stats.computed_at
+        )
+        .execute(pool.inner())
+        .await?;
+
+        Ok(())
+    }
+
+    /// Remove the cached statistics of a collection.
+    ///
+    /// Returns `false` if none were cached.
+    pub async fn delete(
+        pool: &TaxiiPool,
+        protocol: &str,
+        collection_id: &str,
+    ) -> DatabaseResult<bool> {
+        let mut conn = pool.acquire().await?;
+        Self::delete_in(&mut conn, protocol, collection_id).await
+    }
+
+    /// Remove the cached statistics of a collection on a connection.
+    pub async fn delete_in(
+        conn: &mut PgConnection,
+        protocol: &str,
+        collection_id: &str,
+    ) -> DatabaseResult<bool> {
+        let result = sqlx::query!(
+            "DELETE FROM collection_stats WHERE protocol = $1 AND collection_id = $2",
+            protocol,
+            collection_id
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
@@ -3,6 +3,10 @@
 // Shared models
 pub mod account;
 pub mod account_activity;
+pub mod account_totp;
+pub mod collection_stats;
+pub mod password_reset;
+pub mod retention_purge;
 
 // Protocol-specific models
 pub mod taxii1;
@@ -0,0 +1,115 @@
+//! Password reset token model.
+
+use chrono::{DateTime, Utc};
+use sqlx::FromRow;
+
+use crate::error::DatabaseResult;
+use crate::pool::TaxiiPool;
+
+/// Password reset database row.
+///
+/// Table: password_resets
+///
+/// Only a hash of the token is stored.
+#[derive(Debug, Clone, FromRow)]
+pub struct PasswordReset {
+    /// Primary key.
+    pub id: i32,
+
+    /// Account ID (foreign key to accounts).
+    pub account_id: i32,
+
+    /// Token hash (hex-encoded SHA-256).
+    pub token_hash: String,
+
+    /// Time after which the token can no longer be redeemed.
+    pub expires_at: DateTime<Utc>,
+
+    /// Time the token was redeemed.
+    pub used_at: Option<DateTime<Utc>>,
+
+    /// Row creation timestamp.
+    pub created_at: DateTime<Utc>,
+}
+
+impl PasswordReset {
+    /// Create a new password reset token.
+    pub async fn create(
+        pool: &TaxiiPool,
+        account_id: i32,
+        token_hash: &str,
+        expires_at: DateTime<Utc>,
+    ) -> DatabaseResult<Self> {
+        let reset = sqlx::query_as!(
+            Self,
+            r#"INSERT INTO password_resets (account_id, token_hash, expires_at)
+               VALUES ($1, $2, $3)
+               RETURNING id, account_id, token_hash, expires_at, used_at, created_at"#,
+            account_id,
+            token_hash,
+            expires_at
+        )
+        .fetch_one(pool.inner())
+        .await?;
+
+        Ok(reset)
+    }
+
+    /// Redeem a token and set the account's new password hash.
+    ///
+    /// Both happen in one transaction, which also increments the account's
+    /// token generation so that tokens issued before the reset are refused. Returns the account ID, or `None` if
+    /// the token is unknown, expired or already used.
+    pub async fn redeem(
+        pool: &TaxiiPool,
+        token_hash: &str,
+        password_hash: &str,
+    ) -> DatabaseResult<Option<i32>> {
+        let mut tx = pool.inner().begin().await?;
+
+        let account_id = sqlx::query_scalar!(
+            r#"UPDATE password_resets SET used_at = NOW()
+               WHERE token_hash = $1 AND used_at IS NULL AND expires_at > NOW()
+               RETURNING account_id"#,
+            token_hash
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some(account_id) = account_id else {
+            return Ok(None);
+        };
+
+        sqlx::query!(
+            "UPDATE accounts SET password_hash = $2, token_generation = token_generation + 1
+             WHERE id = $1",
+            account_id,
+            password_hash
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        // Any other outstanding tokens for the account are no longer needed
+        sqlx::query!(
+            "DELETE FROM password_resets WHERE account_id = $1 AND used_at IS NULL",
+            account_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(Some(account_id))
+    }
+
+    /// Delete tokens that expired before the given time.
+    ///
+    /// Returns the number of deleted rows.
+    pub async fn delete_expired(pool: &TaxiiPool, before: DateTime<Utc>) -> DatabaseResult<u64> {
+        let result = sqlx::query!("DELETE FROM password_resets WHERE expires_at < $1", before)
+            .execute(pool.inner())
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+}
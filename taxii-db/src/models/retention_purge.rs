@@ -0,0 +1,92 @@
+//! Retention purge log model.
+
+use chrono::{DateTime, Utc};
+use sqlx::FromRow;
+use taxii_core::PurgeStats;
+
+use crate::error::DatabaseResult;
+use crate::pool::TaxiiPool;
+
+/// Protocol of the collection a purge ran on.
+pub mod protocol {
+    pub const TAXII1: &str = "taxii1";
+    pub const TAXII2: &str = "taxii2";
+}
+
+/// Retention purge database row.
+///
+/// Table: retention_purges
+#[derive(Debug, Clone, FromRow)]
+pub struct RetentionPurge {
+    /// Primary key.
+    pub id: i64,
+
+    /// Protocol of the collection (`taxii1` or `taxii2`).
+    pub protocol: String,
+
+    /// Collection ID (integer for TAXII 1.x, UUID for TAXII 2.x).
+    pub collection_id: String,
+
+    /// Content older than this was purged.
+    pub cutoff: DateTime<Utc>,
+
+    /// Number of purged objects or content blocks.
+    pub purged: i64,
+
+    /// Number of expired content blocks retained for result sets.
+    pub retained: i64,
+
+    /// Time of the purge.
+    pub purged_at: DateTime<Utc>,
+}
+
+impl RetentionPurge {
+    /// Record a purge.
+    pub async fn record(
+        pool: &TaxiiPool,
+        protocol: &str,
+        collection_id: &str,
+        cutoff: DateTime<Utc>,
+        stats: PurgeStats,
+    ) -> DatabaseResult<Self> {
+        let purge = sqlx::query_as!(
+            Self,
+            r#"INSERT INTO retention_purges (protocol, collection_id, cutoff, purged, retained)
+               VALUES ($1, $2, $3, $4, $5)
+               RETURNING id, protocol, collection_id, cutoff, purged, retained, purged_at"#,
+            protocol,
+            collection_id,
+            cutoff,
+            stats.purged as i64,
+            stats.retained as i64
+        )
+        .fetch_one(pool.inner())
+        .await?;
+
+        Ok(purge)
+    }
+
+    /// Find the most recent purges of a collection, newest first.
+    pub async fn find_by_collection(
+        pool: &TaxiiPool,
+        protocol: &str,
+        collection_id: &str,
+        limit: i64,
+    ) -> DatabaseResult<Vec<Self>> {
+        let purges = sqlx::query_as!(
+            Self,
+            r#"SELECT id, protocol, collection_id, cutoff, purged, retained, purged_at
+               FROM retention_purges
+               WHERE protocol = $1 AND collection_id = $2
+               ORDER BY purged_at DESC, id DESC
+               LIMIT $3"#,
+            protocol,
+            collection_id,
+            limit
+        )
+        .fetch_all(pool.inner())
+        .await?;
+
+        Ok(purges)
+    }
+}
@@ -1,7 +1,7 @@
 //! DataCollection model (TAXII 1.x collections).
 
 use chrono::{DateTime, Utc};
-use sqlx::FromRow;
+use sqlx::{FromRow, PgConnection};
 
 use crate::error::DatabaseResult;
 use crate::pool::TaxiiPool;
@@ -38,6 +38,37 @@ pub struct DataCollection {
 
     /// Row creation timestamp.
     pub date_created: DateTime<Utc>,
+
+    /// Days content blocks are kept (`None` keeps them).
+    pub retention_days: Option<i32>,
+
+    /// Whether identical content blocks are stored once.
+    pub deduplicate_content: bool,
+
+    /// Validation of inbound STIX XML content (`off`, `reject` or `flag`).
+    pub validation_mode: String,
+
+    /// Verification of content signatures (`off`, `verify` or `require`).
+    pub signature_policy: String,
+
+    /// PEM bundle of the certificates trusted to sign content.
+    pub trusted_certificates: Option<String>,
+}
+
+/// Parameters for creating a data collection.
+#[derive(Debug, Clone)]
+pub struct NewDataCollection<'a> {
+    pub name: &'a str,
+    pub collection_type: &'a str,
+    pub description: Option<&'a str>,
+    pub available: bool,
+    pub accept_all_content: bool,
+    pub bindings: Option<&'a str>,
+    pub retention_days: Option<i32>,
+    pub deduplicate_content: bool,
+    pub validation_mode: &'a str,
+    pub signature_policy: &'a str,
+    pub trusted_certificates: Option<&'a str>,
 }
 
 /// Parameters for updating a data collection.
@@ -50,6 +81,11 @@ pub struct UpdateDataCollection<'a> {
     pub available: bool,
     pub accept_all_content: bool,
     pub bindings: Option<&'a str>,
+    pub retention_days: Option<i32>,
+    pub deduplicate_content: bool,
+    pub validation_mode: &'a str,
+    pub signature_policy: &'a str,
+    pub trusted_certificates: Option<&'a str>,
 }
 
 impl DataCollection {
@@ -65,7 +101,8 @@ impl DataCollection {
             Self,
             r#"SELECT id, name as "name!", type as "collection_type!", description,
                       accept_all_content as "accept_all_content!", bindings,
-                      available as "available!", volume as "volume!", date_created as "date_created!"
+                      available as "available!", volume as "volume!", date_created as "date_created!", retention_days,
+                      deduplicate_content, validation_mode, signature_policy, trusted_certificates
                FROM data_collections WHERE id = $1"#,
             id
         )
@@ -81,7 +118,8 @@ impl DataCollection {
             Self,
             r#"SELECT id, name as "name!", type as "collection_type!", description,
                       accept_all_content as "accept_all_content!", bindings,
-                      available as "available!", volume as "volume!", date_created as "date_created!"
+                      available as "available!", volume as "volume!", date_created as "date_created!", retention_days,
+                      deduplicate_content, validation_mode, signature_policy, trusted_certificates
                FROM data_collections WHERE name = $1"#,
             name
         )
@@ -97,7 +135,8 @@ impl DataCollection {
             Self,
             r#"SELECT id, name as "name!", type as "collection_type!", description,
                       accept_all_content as "accept_all_content!", bindings,
-                      available as "available!", volume as "volume!", date_created as "date_created!"
+                      available as "available!", volume as "volume!", date_created as "date_created!", retention_days,
+                      deduplicate_content, validation_mode, signature_policy, trusted_certificates
                FROM data_collections"#
         )
         .fetch_all(pool.inner())
@@ -112,7 +151,8 @@ impl DataCollection {
             Self,
             r#"SELECT dc.id, dc.name as "name!", dc.type as "collection_type!", dc.description,
                       dc.accept_all_content as "accept_all_content!", dc.bindings,
-                      dc.available as "available!", dc.volume as "volume!", dc.date_created as "date_created!"
+                      dc.available as "available!", dc.volume as "volume!", dc.date_created as "date_created!", retention_days,
+                      deduplicate_content, validation_mode, signature_policy, trusted_certificates
                FROM data_collections dc
                JOIN service_to_collection stc ON dc.id = stc.collection_id
                WHERE stc.service_id = $1"#,
@@ -134,7 +174,8 @@ impl DataCollection {
             Self,
             r#"SELECT dc.id, dc.name as "name!", dc.type as "collection_type!", dc.description,
                       dc.accept_all_content as "accept_all_content!", dc.bindings,
-                      dc.available as "available!", dc.volume as "volume!", dc.date_created as "date_created!"
+                      dc.available as "available!", dc.volume as "volume!", dc.date_created as "date_created!", retention_days,
+                      deduplicate_content, validation_mode, signature_policy, trusted_certificates
                FROM data_collections dc
                JOIN service_to_collection stc ON dc.id = stc.collection_id
                WHERE stc.service_id = $1 AND dc.name = $2"#,
@@ -148,28 +189,26 @@ impl DataCollection {
     }
 
     /// Create a new collection.
-    pub async fn create(
-        pool: &TaxiiPool,
-        name: &str,
-        collection_type: &str,
-        description: Option<&str>,
-        available: bool,
-        accept_all_content: bool,
-        bindings: Option<&str>,
-    ) -> DatabaseResult<Self> {
+    pub async fn create(pool: &TaxiiPool, params: &NewDataCollection<'_>) -> DatabaseResult<Self> {
         let collection = sqlx::query_as!(
             Self,
-            r#"INSERT INTO data_collections (name, type, description, available, accept_all_content, bindings)
-               VALUES ($1, $2, $3, $4, $5, $6)
+            r#"INSERT INTO data_collections (name, type, description, available, accept_all_content, bindings, retention_days, deduplicate_content, validation_mode, signature_policy, trusted_certificates)
+               VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
                RETURNING id, name as "name!", type as "collection_type!", description,
                          accept_all_content as "accept_all_content!", bindings,
-                         available as "available!", volume as "volume!", date_created as "date_created!""#,
-            name,
-            collection_type,
-            description,
-            available,
-            accept_all_content,
-            bindings
+                         available as "available!", volume as "volume!", date_created as "date_created!", retention_days,
+                      deduplicate_content, validation_mode, signature_policy, trusted_certificates"#,
+            params.name,
+            params.collection_type,
+            params.description,
+            params.available,
+            params.accept_all_content,
+            params.bindings,
+            params.retention_days,
+            params.deduplicate_content,
+            params.validation_mode,
+            params.signature_policy,
+            params.trusted_certificates
         )
         .fetch_one(pool.inner())
         .await?;
@@ -186,18 +225,26 @@ impl DataCollection {
             Self,
             r#"UPDATE data_collections
                SET name = $2, type = $3, description = $4, available = $5,
-                   accept_all_content = $6, bindings = $7
+                   accept_all_content = $6, bindings = $7, retention_days = $8,
+                   deduplicate_content = $9, validation_mode = $10,
+                   signature_policy = $11, trusted_certificates = $12
                WHERE id = $1
                RETURNING id, name as "name!", type as "collection_type!", description,
                          accept_all_content as "accept_all_content!", bindings,
-                         available as "available!", volume as "volume!", date_created as "date_created!""#,
+                         available as "available!", volume as "volume!", date_created as "date_created!", retention_days,
+                      deduplicate_content, validation_mode, signature_policy, trusted_certificates"#,
             params.id,
             params.name,
             params.collection_type,
             params.description,
             params.available,
             params.accept_all_content,
-            params.bindings
+            params.bindings,
+            params.retention_days,
+            params.deduplicate_content,
+            params.validation_mode,
+            params.signature_policy,
+            params.trusted_certificates
         )
         .fetch_one(pool.inner())
         .await?;
@@ -287,14 +334,42 @@ impl DataCollection {
 
     /// Increment volume count for a collection by 1.
     pub async fn increment_volume(pool: &TaxiiPool, id: i32) -> DatabaseResult<()> {
+        let mut conn = pool.acquire().await?;
+        Self::increment_volume_in(&mut conn, id).await
+    }
+
+    /// Increment volume counter on a connection (e.g. inside a transaction).
+    pub async fn increment_volume_in(conn: &mut PgConnection, id: i32) -> DatabaseResult<()> {
         sqlx::query("UPDATE data_collections SET volume = COALESCE(volume, 0) + 1 WHERE id = $1")
             .bind(id)
-            .execute(pool.inner())
+            .execute(conn)
             .await?;
 
         Ok(())
     }
 
+    /// Get the IDs of the given collections that deduplicate content.
+    pub async fn find_deduplicating(pool: &TaxiiPool, ids: &[i32]) -> DatabaseResult<Vec<i32>> {
+        let mut conn = pool.acquire().await?;
+        Self::find_deduplicating_in(&mut conn, ids).await
+    }
+
+    /// Get the IDs of the given collections that deduplicate content, on a
+    /// connection (e.g. inside a transaction).
+    pub async fn find_deduplicating_in(
+        conn: &mut PgConnection,
+        ids: &[i32],
+    ) -> DatabaseResult<Vec<i32>> {
+        let ids = sqlx::query_scalar!(
+            "SELECT id FROM data_collections WHERE id = ANY($1) AND deduplicate_content ORDER BY id",
+            ids
+        )
+        .fetch_all(conn)
+        .await?;
+
+        Ok(ids)
+    }
+
     /// Check if a collection with the given name exists.
     pub async fn exists_by_name(pool: &TaxiiPool, name: &str) -> DatabaseResult<bool> {
         let result = sqlx::query_scalar!(
@@ -0,0 +1,83 @@
+//! Collection bridge model.
+
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use crate::error::DatabaseResult;
+use crate::pool::TaxiiPool;
+
+/// Collection bridge database row.
+///
+/// Table: collection_bridges
+#[derive(Debug, Clone, FromRow)]
+pub struct CollectionBridge {
+    /// Foreign key to data_collections.id.
+    pub data_collection_id: i32,
+
+    /// Foreign key to opentaxii_api_root.id.
+    pub api_root_id: Uuid,
+
+    /// Foreign key to opentaxii_collection.id.
+    pub collection_id: Uuid,
+}
+
+impl CollectionBridge {
+    /// Link a TAXII 1.x collection to a TAXII 2.x collection, replacing its
+    /// previous link.
+    pub async fn upsert(
+        pool: &TaxiiPool,
+        data_collection_id: i32,
+        api_root_id: Uuid,
+        collection_id: Uuid,
+    ) -> DatabaseResult<Self> {
+        let bridge = sqlx::query_as!(
+            Self,
+            r#"INSERT INTO collection_bridges (data_collection_id, api_root_id, collection_id)
+               VALUES ($1, $2, $3)
+               ON CONFLICT (data_collection_id)
+               DO UPDATE SET api_root_id = EXCLUDED.api_root_id,
+                             collection_id = EXCLUDED.collection_id
+               RETURNING data_collection_id, api_root_id, collection_id"#,
+            data_collection_id,
+            api_root_id,
+            collection_id
+        )
+        .fetch_one(pool.inner())
+        .await?;
+
+        Ok(bridge)
+    }
+
+    /// Remove the link of a TAXII 1.x collection.
+    ///
+    /// Returns whether the collection was linked.
+    pub async fn delete(pool: &TaxiiPool, data_collection_id: i32) -> DatabaseResult<bool> {
+        let result = sqlx::query!(
+            "DELETE FROM collection_bridges WHERE data_collection_id = $1",
+            data_collection_id
+        )
+        .execute(pool.inner())
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Find the links of TAXII 1.x collections.
+    pub async fn find_by_data_collections(
+        pool: &TaxiiPool,
+        data_collection_ids: &[i32],
+    ) -> DatabaseResult<Vec<Self>> {
+        let bridges = sqlx::query_as!(
+            Self,
+            r#"SELECT data_collection_id, api_root_id, collection_id
+               FROM collection_bridges
+               WHERE data_collection_id = ANY($1)
+               ORDER BY data_collection_id"#,
+            data_collection_ids
+        )
+        .fetch_all(pool.inner())
+        .await?;
+
+        Ok(bridges)
+    }
+}
@@ -1,10 +1,14 @@
 //! ContentBlock model.
 
 use chrono::{DateTime, Utc};
-use sqlx::FromRow;
+use sha2::{Digest, Sha256};
+use sqlx::{FromRow, PgConnection};
+use taxii_core::truncate_timestamp_label;
 
 use crate::error::DatabaseResult;
+use crate::models::collection_stats::TypeStats;
 use crate::pool::TaxiiPool;
+use crate::stream::{RowStream, forward_rows, spawn_stream};
 
 /// Content binding filter for queries.
 #[derive(Debug, Clone)]
@@ -60,6 +64,32 @@ pub struct ContentBlock {
 
     /// Row creation timestamp.
     pub date_created: DateTime<Utc>,
+
+    /// Why the content failed validation, if it was stored anyway.
+    pub validation_error: Option<String>,
+
+    /// Outcome of signature verification, if the content was verified.
+    pub signature_status: Option<String>,
+
+    /// SHA-256 fingerprint (hex) of the certificate that signed the content.
+    pub signer_fingerprint: Option<String>,
+
+    /// How the content is carried in messages ('text' or 'base64').
+    pub content_encoding: String,
+}
+
+/// Parameters for creating a content block.
+#[derive(Debug)]
+pub struct NewContentBlock<'a> {
+    pub timestamp_label: DateTime<Utc>,
+    pub inbox_message_id: Option<i32>,
+    pub content: &'a [u8],
+    pub binding_id: Option<&'a str>,
+    pub binding_subtype: Option<&'a str>,
+    pub validation_error: Option<&'a str>,
+    pub signature_status: Option<&'a str>,
+    pub signer_fingerprint: Option<&'a str>,
+    pub content_encoding: &'a str,
 }
 
 impl ContentBlock {
@@ -68,7 +98,8 @@ impl ContentBlock {
         let block = sqlx::query_as!(
             Self,
             r#"SELECT id, message, timestamp_label as "timestamp_label!", inbox_message_id, content,
-                      binding_id, binding_subtype, date_created as "date_created!"
+                      binding_id, binding_subtype, date_created as "date_created!", validation_error,
+                      signature_status, signer_fingerprint, content_encoding
                FROM content_blocks WHERE id = $1"#,
             id
         )
@@ -78,50 +109,283 @@ impl ContentBlock {
         Ok(block)
     }
 
+    /// Find content blocks by ID, ordered by timestamp label.
+    pub async fn find_many(pool: &TaxiiPool, ids: &[i32]) -> DatabaseResult<Vec<Self>> {
+        let blocks = sqlx::query_as!(
+            Self,
+            r#"SELECT id, message, timestamp_label as "timestamp_label!", inbox_message_id, content,
+                      binding_id, binding_subtype, date_created as "date_created!", validation_error,
+                      signature_status, signer_fingerprint, content_encoding
+               FROM content_blocks WHERE id = ANY($1)
+               ORDER BY timestamp_label, id"#,
+            ids
+        )
+        .fetch_all(pool.inner())
+        .await?;
+
+        Ok(blocks)
+    }
+
     /// Create a new content block.
-    pub async fn create(
+    pub async fn create(pool: &TaxiiPool, params: &NewContentBlock<'_>) -> DatabaseResult<Self> {
+        let mut conn = pool.acquire().await?;
+        Self::create_in(&mut conn, params).await
+    }
+
+    /// Create a new content block on a connection (e.g. inside a transaction).
+    pub async fn create_in(
+        conn: &mut PgConnection,
+        params: &NewContentBlock<'_>,
+    ) -> DatabaseResult<Self> {
+        let block = sqlx::query_as!(
+            Self,
+            r#"INSERT INTO content_blocks (timestamp_label, inbox_message_id, content, binding_id, binding_subtype, content_sha256,
+                                           validation_error, signature_status, signer_fingerprint, content_encoding)
+               VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+               RETURNING id, message, timestamp_label as "timestamp_label!", inbox_message_id, content,
+                         binding_id, binding_subtype, date_created as "date_created!", validation_error,
+                         signature_status, signer_fingerprint, content_encoding"#,
+            truncate_timestamp_label(params.timestamp_label),
+            params.inbox_message_id,
+            params.content,
+            params.binding_id,
+            params.binding_subtype,
+            content_digest(params.content),
+            params.validation_error,
+            params.signature_status,
+            params.signer_fingerprint,
+            params.content_encoding
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+
+        if let Some(inbox_message_id) = params.inbox_message_id {
+            Self::add_inbox_message_in(conn, block.id, inbox_message_id).await?;
+        }
+
+        Ok(block)
+    }
+
+    /// Find a block of one of the collections added since `since` with the
+    /// same content digest and content binding.
+    pub async fn find_duplicate(
         pool: &TaxiiPool,
-        timestamp_label: DateTime<Utc>,
-        inbox_message_id: Option<i32>,
-        content: &[u8],
+        collection_ids: &[i32],
+        content_sha256: &str,
         binding_id: Option<&str>,
         binding_subtype: Option<&str>,
-    ) -> DatabaseResult<Self> {
+        since: DateTime<Utc>,
+    ) -> DatabaseResult<Option<Self>> {
+        let mut conn = pool.acquire().await?;
+        Self::find_duplicate_in(
+            &mut conn,
+            collection_ids,
+            content_sha256,
+            binding_id,
+            binding_subtype,
+            since,
+        )
+        .await
+    }
+
+    /// Find a duplicate block on a connection (e.g. inside a transaction).
+    pub async fn find_duplicate_in(
+        conn: &mut PgConnection,
+        collection_ids: &[i32],
+        content_sha256: &str,
+        binding_id: Option<&str>,
+        binding_subtype: Option<&str>,
+        since: DateTime<Utc>,
+    ) -> DatabaseResult<Option<Self>> {
         let block = sqlx::query_as!(
             Self,
-            r#"INSERT INTO content_blocks (timestamp_label, inbox_message_id, content, binding_id, binding_subtype)
-               VALUES ($1, $2, $3, $4, $5)
-               RETURNING id, message, timestamp_label as "timestamp_label!", inbox_message_id, content,
-                         binding_id, binding_subtype, date_created as "date_created!""#,
-            timestamp_label,
-            inbox_message_id,
-            content,
+            r#"SELECT cb.id, cb.message, cb.timestamp_label as "timestamp_label!", cb.inbox_message_id,
+                      cb.content, cb.binding_id, cb.binding_subtype, cb.date_created as "date_created!",
+                      cb.validation_error, cb.signature_status, cb.signer_fingerprint, cb.content_encoding
+               FROM content_blocks cb
+               JOIN collection_to_content_block ctcb ON ctcb.content_block_id = cb.id
+               WHERE ctcb.collection_id = ANY($1)
+                 AND cb.content_sha256 = $2
+                 AND cb.binding_id IS NOT DISTINCT FROM $3
+                 AND cb.binding_subtype IS NOT DISTINCT FROM $4
+                 AND cb.date_created >= $5
+               ORDER BY cb.date_created DESC, cb.id DESC
+               LIMIT 1"#,
+            collection_ids,
+            content_sha256,
             binding_id,
-            binding_subtype
+            binding_subtype,
+            since
         )
-        .fetch_one(pool.inner())
+        .fetch_optional(&mut *conn)
         .await?;
 
         Ok(block)
     }
 
+    /// Set the content digest of blocks of the collections added since
+    /// `since` that have none (added before digests were stored).
+    ///
+    /// Returns the number of updated blocks.
+    pub async fn backfill_digests_in(
+        conn: &mut PgConnection,
+        collection_ids: &[i32],
+        since: DateTime<Utc>,
+    ) -> DatabaseResult<u64> {
+        let rows = sqlx::query!(
+            r#"SELECT cb.id, cb.content
+               FROM content_blocks cb
+               WHERE cb.content_sha256 IS NULL
+                 AND cb.date_created >= $2
+                 AND EXISTS (SELECT 1 FROM collection_to_content_block ctcb
+                             WHERE ctcb.content_block_id = cb.id AND ctcb.collection_id = ANY($1))"#,
+            collection_ids,
+            since
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+
+        for row in &rows {
+            sqlx::query!(
+                "UPDATE content_blocks SET content_sha256 = $2 WHERE id = $1",
+                row.id,
+                content_digest(&row.content)
+            )
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        Ok(rows.len() as u64)
+    }
+
+    /// Serialize deduplication of a content digest until the end of the
+    /// transaction.
+    ///
+    /// Concurrent transactions storing the same content wait for each other,
+    /// so the later one finds the block the earlier one stored instead of
+    /// storing it again.
+    pub async fn lock_digest_in(
+        conn: &mut PgConnection,
+        content_sha256: &str,
+    ) -> DatabaseResult<()> {
+        sqlx::query(
+            "SELECT pg_advisory_xact_lock(hashtext('opentaxii_content_block:' || $1::text))",
+        )
+        .bind(content_sha256)
+        .execute(&mut *conn)
+        .await?;
+        Ok(())
+    }
+
+    /// Record an inbox message that delivered a content block.
+    pub async fn add_inbox_message(
+        pool: &TaxiiPool,
+        content_block_id: i32,
+        inbox_message_id: i32,
+    ) -> DatabaseResult<()> {
+        let mut conn = pool.acquire().await?;
+        Self::add_inbox_message_in(&mut conn, content_block_id, inbox_message_id).await
+    }
+
+    /// Record an inbox message that delivered a content block on a
+    /// connection (e.g. inside a transaction).
+    pub async fn add_inbox_message_in(
+        conn: &mut PgConnection,
+        content_block_id: i32,
+        inbox_message_id: i32,
+    ) -> DatabaseResult<()> {
+        sqlx::query!(
+            r#"INSERT INTO content_block_inbox_messages (content_block_id, inbox_message_id)
+               VALUES ($1, $2)
+               ON CONFLICT DO NOTHING"#,
+            content_block_id,
+            inbox_message_id
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Get the IDs of the inbox messages that delivered a content block,
+    /// in delivery order.
+    pub async fn find_inbox_message_ids(
+        pool: &TaxiiPool,
+        content_block_id: i32,
+    ) -> DatabaseResult<Vec<i32>> {
+        let ids = sqlx::query_scalar!(
+            r#"SELECT inbox_message_id as "inbox_message_id!"
+               FROM content_block_inbox_messages
+               WHERE content_block_id = $1
+               ORDER BY inbox_message_id"#,
+            content_block_id
+        )
+        .fetch_all(pool.inner())
+        .await?;
+
+        Ok(ids)
+    }
+
     /// Attach content block to a single collection.
     pub async fn attach_to_collection(
         pool: &TaxiiPool,
         content_block_id: i32,
         collection_id: i32,
+    ) -> DatabaseResult<()> {
+        let mut conn = pool.acquire().await?;
+        Self::attach_to_collection_in(&mut conn, content_block_id, collection_id).await
+    }
+
+    /// Attach content block to a single collection on a connection (e.g.
+    /// inside a transaction).
+    pub async fn attach_to_collection_in(
+        conn: &mut PgConnection,
+        content_block_id: i32,
+        collection_id: i32,
     ) -> DatabaseResult<()> {
         sqlx::query!(
             "INSERT INTO collection_to_content_block (collection_id, content_block_id) VALUES ($1, $2)",
             collection_id,
             content_block_id
         )
-        .execute(pool.inner())
+        .execute(&mut *conn)
         .await?;
 
         Ok(())
     }
 
+    /// Attach content block to a collection unless it is already attached.
+    ///
+    /// Returns `false` if it was already attached.
+    pub async fn attach_to_collection_if_absent(
+        pool: &TaxiiPool,
+        content_block_id: i32,
+        collection_id: i32,
+    ) -> DatabaseResult<bool> {
+        let mut conn = pool.acquire().await?;
+        Self::attach_to_collection_if_absent_in(&mut conn, content_block_id, collection_id).await
+    }
+
+    /// Attach content block to a collection unless it is already attached,
+    /// on a connection (e.g. inside a transaction).
+    pub async fn attach_to_collection_if_absent_in(
+        conn: &mut PgConnection,
+        content_block_id: i32,
+        collection_id: i32,
+    ) -> DatabaseResult<bool> {
+        let result = sqlx::query!(
+            r#"INSERT INTO collection_to_content_block (collection_id, content_block_id)
+               VALUES ($1, $2)
+               ON CONFLICT DO NOTHING"#,
+            collection_id,
+            content_block_id
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
     /// Attach content block to multiple collections.
     pub async fn attach_to_collections(
         pool: &TaxiiPool,
@@ -144,6 +408,85 @@ impl ContentBlock {
         Ok(result.rows_affected())
     }
 
+    /// Remove one batch of content blocks labelled before `cutoff` from a
+    /// collection.
+    ///
+    /// Blocks are detached from the collection and deleted once no other
+    /// collection holds them. Blocks materialized in a result set are left
+    /// alone so prepared polls stay complete.
+    ///
+    /// Returns the number of blocks removed from the collection; fewer than
+    /// `batch_size` means nothing is left to purge.
+    pub async fn purge_before(
+        pool: &TaxiiPool,
+        collection_id: i32,
+        cutoff: DateTime<Utc>,
+        batch_size: i64,
+    ) -> DatabaseResult<u64> {
+        let mut tx = pool.inner().begin().await?;
+
+        let detached = sqlx::query_scalar!(
+            r#"DELETE FROM collection_to_content_block
+               WHERE collection_id = $1 AND content_block_id IN (
+                   SELECT ctcb.content_block_id
+                   FROM collection_to_content_block ctcb
+                   JOIN content_blocks cb ON cb.id = ctcb.content_block_id
+                   WHERE ctcb.collection_id = $1 AND cb.timestamp_label < $2
+                     AND NOT EXISTS (
+                         SELECT 1 FROM result_set_content_blocks rscb
+                         WHERE rscb.content_block_id = cb.id
+                     )
+                   LIMIT $3
+               )
+               RETURNING content_block_id as "content_block_id!""#,
+            collection_id,
+            cutoff,
+            batch_size
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            r#"DELETE FROM content_blocks cb
+               WHERE cb.id = ANY($1)
+                 AND NOT EXISTS (
+                     SELECT 1 FROM collection_to_content_block ctcb
+                     WHERE ctcb.content_block_id = cb.id
+                 )
+                 AND NOT EXISTS (
+                     SELECT 1 FROM result_set_content_blocks rscb
+                     WHERE rscb.content_block_id = cb.id
+                 )"#,
+            &detached
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(detached.len() as u64)
+    }
+
+    /// Count content blocks labelled before `cutoff` in a collection.
+    pub async fn count_before(
+        pool: &TaxiiPool,
+        collection_id: i32,
+        cutoff: DateTime<Utc>,
+    ) -> DatabaseResult<i64> {
+        let count = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) as "count!"
+               FROM collection_to_content_block ctcb
+               JOIN content_blocks cb ON cb.id = ctcb.content_block_id
+               WHERE ctcb.collection_id = $1 AND cb.timestamp_label < $2"#,
+            collection_id,
+            cutoff
+        )
+        .fetch_one(pool.inner())
+        .await?;
+
+        Ok(count)
+    }
+
     /// Get content block IDs for a collection within a time range.
     pub async fn find_ids_by_collection_and_time(
         pool: &TaxiiPool,
@@ -194,15 +537,45 @@ impl ContentBlock {
         Ok(count)
     }
 
+    /// Get statistics of the content blocks of a collection by content
+    /// binding.
+    ///
+    /// Blocks without a binding are counted as `unspecified`.
+    pub async fn binding_stats(
+        pool: &TaxiiPool,
+        collection_id: i32,
+    ) -> DatabaseResult<Vec<TypeStats>> {
+        let stats = sqlx::query_as!(
+            TypeStats,
+            r#"SELECT COALESCE(cb.binding_id, 'unspecified') as "object_type!",
+                      COUNT(*) as "object_count!", COUNT(*) as "distinct_ids!",
+                      MAX(cb.timestamp_label) as latest_added
+               FROM content_blocks cb
+               JOIN collection_to_content_block ctcb ON cb.id = ctcb.content_block_id
+               WHERE ctcb.collection_id = $1
+               GROUP BY 1
+               ORDER BY 1"#,
+            collection_id
+        )
+        .fetch_all(pool.inner())
+        .await?;
+
+        Ok(stats)
+    }
+
     /// Get inbox message IDs for content blocks.
     pub async fn get_inbox_message_ids(
         pool: &TaxiiPool,
         content_block_ids: &[i32],
     ) -> DatabaseResult<Vec<i32>> {
         let ids = sqlx::query_scalar!(
-            r#"SELECT DISTINCT inbox_message_id as "inbox_message_id!"
+            r#"SELECT inbox_message_id as "inbox_message_id!"
                FROM content_blocks
-               WHERE id = ANY($1) AND inbox_message_id IS NOT NULL"#,
+               WHERE id = ANY($1) AND inbox_message_id IS NOT NULL
+               UNION
+               SELECT inbox_message_id
+               FROM content_block_inbox_messages
+               WHERE content_block_id = ANY($1)"#,
             content_block_ids
         )
         .fetch_all(pool.inner())
@@ -218,67 +591,111 @@ impl ContentBlock {
         pool: &TaxiiPool,
         filter: &ContentBlockFilter<'_>,
     ) -> DatabaseResult<Vec<Self>> {
-        // Build dynamic query
         let mut query = String::from(
             r#"SELECT cb.id, cb.message, cb.timestamp_label, cb.inbox_message_id,
-                      cb.content, cb.binding_id, cb.binding_subtype, cb.date_created
+                      cb.content, cb.binding_id, cb.binding_subtype, cb.date_created,
+                      cb.validation_error, cb.signature_status, cb.signer_fingerprint, cb.content_encoding
                FROM content_blocks cb"#,
         );
+        query.push_str(&filter_clause(filter));
+        query.push_str(" ORDER BY cb.timestamp_label ASC, cb.id ASC");
+        push_pagination(&mut query, filter);
 
-        let mut conditions = Vec::new();
-        let mut param_idx = 1;
+        // Execute query with dynamic bindings
+        let mut q = sqlx::query_as::<_, Self>(&query);
 
-        if filter.collection_id.is_some() {
-            query.push_str(
-                " JOIN collection_to_content_block ctcb ON cb.id = ctcb.content_block_id",
-            );
-            conditions.push(format!("ctcb.collection_id = ${param_idx}"));
-            param_idx += 1;
+        if let Some(coll_id) = filter.collection_id {
+            q = q.bind(coll_id);
         }
-
-        if filter.start_time.is_some() {
-            conditions.push(format!("cb.timestamp_label > ${param_idx}"));
-            param_idx += 1;
+        if let Some(st) = filter.start_time {
+            q = q.bind(st);
         }
-
-        if filter.end_time.is_some() {
-            conditions.push(format!("cb.timestamp_label <= ${param_idx}"));
-            param_idx += 1;
+        if let Some(et) = filter.end_time {
+            q = q.bind(et);
         }
-
-        // Handle bindings filter
-        if let Some(binds) = filter.bindings.filter(|b| !b.is_empty()) {
-            let mut binding_conditions = Vec::new();
-            for binding in binds {
-                if binding.subtypes.is_empty() {
-                    binding_conditions.push(format!("cb.binding_id = ${param_idx}"));
-                    param_idx += 1;
-                } else {
-                    binding_conditions.push(format!(
-                        "(cb.binding_id = ${} AND cb.binding_subtype = ANY(${}::text[]))",
-                        param_idx,
-                        param_idx + 1
-                    ));
-                    param_idx += 2;
-                }
+        for binding in filter.bindings.unwrap_or_default() {
+            q = q.bind(&binding.binding);
+            if !binding.subtypes.is_empty() {
+                q = q.bind(&binding.subtypes);
             }
-            conditions.push(format!("({})", binding_conditions.join(" OR ")));
         }
 
-        if !conditions.is_empty() {
-            query.push_str(" WHERE ");
-            query.push_str(&conditions.join(" AND "));
-        }
+        let blocks = q.fetch_all(pool.inner()).await?;
+        Ok(blocks)
+    }
 
-        query.push_str(" ORDER BY cb.timestamp_label ASC");
+    /// Stream content blocks with filtering.
+    ///
+    /// Uses the same filters and ordering as [`ContentBlock::find_filtered`].
+    /// Rows are yielded as the database returns them.
+    pub fn stream_filtered(pool: &TaxiiPool, filter: &ContentBlockFilter<'_>) -> RowStream<Self> {
+        let pool = pool.clone();
+        let ContentBlockFilter {
+            collection_id,
+            start_time,
+            end_time,
+            bindings,
+            offset,
+            limit,
+        } = *filter;
+        let bindings = bindings.map(<[ContentBindingFilter]>::to_vec);
+
+        spawn_stream(move |tx| async move {
+            let filter = ContentBlockFilter {
+                collection_id,
+                start_time,
+                end_time,
+                bindings: bindings.as_deref(),
+                offset,
+                limit,
+            };
+
+            let mut query = String::from(
+                r#"SELECT cb.id, cb.message, cb.timestamp_label, cb.inbox_message_id,
+                          cb.content, cb.binding_id, cb.binding_subtype, cb.date_created,
+                      cb.validation_error, cb.signature_status, cb.signer_fingerprint, cb.content_encoding
+                   FROM content_blocks cb"#,
+            );
+            query.push_str(&filter_clause(&filter));
+            query.push_str(" ORDER BY cb.timestamp_label ASC, cb.id ASC");
+            push_pagination(&mut query, &filter);
 
-        if let Some(lim) = filter.limit {
-            query.push_str(&format!(" LIMIT {lim}"));
-        }
-        query.push_str(&format!(" OFFSET {}", filter.offset));
+            let mut q = sqlx::query_as::<_, Self>(&query);
 
-        // Execute query with dynamic bindings
-        let mut q = sqlx::query_as::<_, Self>(&query);
+            if let Some(coll_id) = filter.collection_id {
+                q = q.bind(coll_id);
+            }
+            if let Some(st) = filter.start_time {
+                q = q.bind(st);
+            }
+            if let Some(et) = filter.end_time {
+                q = q.bind(et);
+            }
+            for binding in filter.bindings.unwrap_or_default() {
+                q = q.bind(&binding.binding);
+                if !binding.subtypes.is_empty() {
+                    q = q.bind(&binding.subtypes);
+                }
+            }
+
+            forward_rows(q.fetch(pool.inner()), tx).await;
+        })
+    }
+
+    /// Find content block IDs with filtering.
+    ///
+    /// Uses the same filters and ordering as [`ContentBlock::find_filtered`]
+    /// without loading block content.
+    pub async fn find_ids_filtered(
+        pool: &TaxiiPool,
+        filter: &ContentBlockFilter<'_>,
+    ) -> DatabaseResult<Vec<i32>> {
+        let mut query = String::from("SELECT cb.id FROM content_blocks cb");
+        query.push_str(&filter_clause(filter));
+        query.push_str(" ORDER BY cb.timestamp_label ASC, cb.id ASC");
+        push_pagination(&mut query, filter);
+
+        let mut q = sqlx::query_scalar::<_, i32>(&query);
 
         if let Some(coll_id) = filter.collection_id {
             q = q.bind(coll_id);
@@ -289,71 +706,62 @@ impl ContentBlock {
         if let Some(et) = filter.end_time {
             q = q.bind(et);
         }
-        if let Some(binds) = filter.bindings {
-            for binding in binds {
-                q = q.bind(&binding.binding);
-                if !binding.subtypes.is_empty() {
-                    q = q.bind(&binding.subtypes);
-                }
+        for binding in filter.bindings.unwrap_or_default() {
+            q = q.bind(&binding.binding);
+            if !binding.subtypes.is_empty() {
+                q = q.bind(&binding.subtypes);
             }
         }
 
-        let blocks = q.fetch_all(pool.inner()).await?;
-        Ok(blocks)
+        let ids = q.fetch_all(pool.inner()).await?;
+        Ok(ids)
     }
 
-    /// Count content blocks with filtering.
+    /// Find the content sizes, in bytes, of content blocks with filtering.
     ///
-    /// Supports filtering by collection, time range, and content bindings.
-    pub async fn count_filtered(
+    /// Uses the same filters and ordering as [`ContentBlock::find_filtered`]
+    /// without loading block content.
+    pub async fn sizes_filtered(
         pool: &TaxiiPool,
         filter: &ContentBlockFilter<'_>,
-    ) -> DatabaseResult<i64> {
-        let mut query = String::from("SELECT COUNT(cb.id) FROM content_blocks cb");
+    ) -> DatabaseResult<Vec<i64>> {
+        let mut query =
+            String::from("SELECT octet_length(cb.content)::BIGINT FROM content_blocks cb");
+        query.push_str(&filter_clause(filter));
+        query.push_str(" ORDER BY cb.timestamp_label ASC, cb.id ASC");
+        push_pagination(&mut query, filter);
 
-        let mut conditions = Vec::new();
-        let mut param_idx = 1;
+        let mut q = sqlx::query_scalar::<_, i64>(&query);
 
-        if filter.collection_id.is_some() {
-            query.push_str(
-                " JOIN collection_to_content_block ctcb ON cb.id = ctcb.content_block_id",
-            );
-            conditions.push(format!("ctcb.collection_id = ${param_idx}"));
-            param_idx += 1;
+        if let Some(coll_id) = filter.collection_id {
+            q = q.bind(coll_id);
         }
-
-        if filter.start_time.is_some() {
-            conditions.push(format!("cb.timestamp_label > ${param_idx}"));
-            param_idx += 1;
+        if let Some(st) = filter.start_time {
+            q = q.bind(st);
         }
-
-        if filter.end_time.is_some() {
-            conditions.push(format!("cb.timestamp_label <= ${param_idx}"));
-            param_idx += 1;
+        if let Some(et) = filter.end_time {
+            q = q.bind(et);
         }
-
-        if let Some(binds) = filter.bindings.filter(|b| !b.is_empty()) {
-            let mut binding_conditions = Vec::new();
-            for binding in binds {
-                if binding.subtypes.is_empty() {
-                    binding_conditions.push(format!("cb.binding_id = ${param_idx}"));
-                    param_idx += 1;
-                } else {
-                    binding_conditions.push(format!(
-                        "(cb.binding_id = ${} AND cb.binding_subtype = ANY(${}::text[]))",
-                        param_idx,
-                        param_idx + 1
-                    ));
-                    param_idx += 2;
-                }
+        for binding in filter.bindings.unwrap_or_default() {
+            q = q.bind(&binding.binding);
+            if !binding.subtypes.is_empty() {
+                q = q.bind(&binding.subtypes);
             }
-            conditions.push(format!("({})", binding_conditions.join(" OR ")));
         }
 
-        if !conditions.is_empty() {
-            query.push_str(" WHERE ");
-            query.push_str(&conditions.join(" AND "));
-        }
+        let sizes = q.fetch_all(pool.inner()).await?;
+        Ok(sizes)
+    }
+
+    /// Count content blocks with filtering.
+    ///
+    /// Supports filtering by collection, time range, and content bindings.
+    pub async fn count_filtered(
+        pool: &TaxiiPool,
+        filter: &ContentBlockFilter<'_>,
+    ) -> DatabaseResult<i64> {
+        let mut query = String::from("SELECT COUNT(cb.id) FROM content_blocks cb");
+        query.push_str(&filter_clause(filter));
 
         let mut q = sqlx::query_scalar::<_, i64>(&query);
 
@@ -366,12 +774,10 @@ impl ContentBlock {
         if let Some(et) = filter.end_time {
             q = q.bind(et);
         }
-        if let Some(binds) = filter.bindings {
-            for binding in binds {
-                q = q.bind(&binding.binding);
-                if !binding.subtypes.is_empty() {
-                    q = q.bind(&binding.subtypes);
-                }
+        for binding in filter.bindings.unwrap_or_default() {
+            q = q.bind(&binding.binding);
+            if !binding.subtypes.is_empty() {
+                q = q.bind(&binding.subtypes);
             }
         }
 
@@ -379,3 +785,68 @@ impl ContentBlock {
         Ok(count)
     }
 }
+
+/// Build the JOIN and WHERE clauses for a content block filter.
+///
+/// Placeholders are numbered in bind order: collection ID, start time,
+/// end time, then each binding ID followed by its subtypes (if any).
+fn filter_clause(filter: &ContentBlockFilter<'_>) -> String {
+    let mut clause = String::new();
+    let mut conditions = Vec::new();
+    let mut param_idx = 1;
+
+    if filter.collection_id.is_some() {
+        clause.push_str(" JOIN collection_to_content_block ctcb ON cb.id = ctcb.content_block_id");
+        conditions.push(format!("ctcb.collection_id = ${param_idx}"));
+        param_idx += 1;
+    }
+
+    if filter.start_time.is_some() {
+        conditions.push(format!("cb.timestamp_label > ${param_idx}"));
+        param_idx += 1;
+    }
+
+    if filter.end_time.is_some() {
+        conditions.push(format!("cb.timestamp_label <= ${param_idx}"));
+        param_idx += 1;
+    }
+
+    // Handle bindings filter
+    if let Some(binds) = filter.bindings.filter(|b| !b.is_empty()) {
+        let mut binding_conditions = Vec::new();
+        for binding in binds {
+            if binding.subtypes.is_empty() {
+                binding_conditions.push(format!("cb.binding_id = ${param_idx}"));
+                param_idx += 1;
+            } else {
+                binding_conditions.push(format!(
+                    "(cb.binding_id = ${} AND cb.binding_subtype = ANY(${}::text[]))",
+                    param_idx,
+                    param_idx + 1
+                ));
+                param_idx += 2;
+            }
+        }
+        conditions.push(format!("({})", binding_conditions.join(" OR ")));
+    }
+
+    if !conditions.is_empty() {
+        clause.push_str(" WHERE ");
+        clause.push_str(&conditions.join(" AND "));
+    }
+
+    clause
+}
+
+/// Append LIMIT and OFFSET for a content block filter.
+fn push_pagination(query: &mut String, filter: &ContentBlockFilter<'_>) {
+    if let Some(lim) = filter.limit {
+        query.push_str(&format!(" LIMIT {lim}"));
+    }
+    query.push_str(&format!(" OFFSET {}", filter.offset));
+}
+
+/// Hex-encoded SHA-256 digest of content block content.
+pub fn content_digest(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
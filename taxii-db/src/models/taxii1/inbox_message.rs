@@ -1,7 +1,7 @@
 //! InboxMessage model.
 
 use chrono::{DateTime, Utc};
-use sqlx::FromRow;
+use sqlx::{FromRow, PgConnection};
 
 use crate::error::DatabaseResult;
 use crate::pool::TaxiiPool;
@@ -92,6 +92,16 @@ impl InboxMessage {
 
     /// Create a new inbox message.
     pub async fn create(pool: &TaxiiPool, params: &NewInboxMessage<'_>) -> DatabaseResult<Self> {
+        let mut conn = pool.acquire().await?;
+        Self::create_in(&mut conn, params).await
+    }
+
+    /// Create a new inbox message on a connection (e.g. inside a
+    /// transaction).
+    pub async fn create_in(
+        conn: &mut PgConnection,
+        params: &NewInboxMessage<'_>,
+    ) -> DatabaseResult<Self> {
         let message = sqlx::query_as!(
             Self,
             r#"INSERT INTO inbox_messages (
@@ -119,7 +129,7 @@ impl InboxMessage {
             params.exclusive_begin_timestamp_label,
             params.inclusive_end_timestamp_label
         )
-        .fetch_one(pool.inner())
+        .fetch_one(conn)
         .await?;
 
         Ok(message)
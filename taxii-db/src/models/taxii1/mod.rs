@@ -7,21 +7,30 @@
 //! - inbox_messages
 //! - result_sets
 //! - subscriptions
+//! - subscription_deliveries
+//! - collection_bridges
 //!
 //! Junction tables:
 //! - collection_to_content_block
 //! - service_to_collection
+//! - result_set_content_blocks
 
 pub mod collection;
+pub mod collection_bridge;
 pub mod content_block;
 pub mod inbox_message;
 pub mod result_set;
 pub mod service;
 pub mod subscription;
+pub mod subscription_delivery;
 
-pub use collection::{DataCollection, UpdateDataCollection};
-pub use content_block::{ContentBindingFilter, ContentBlock, ContentBlockFilter};
+pub use collection::{DataCollection, NewDataCollection, UpdateDataCollection};
+pub use collection_bridge::CollectionBridge;
+pub use content_block::{
+    ContentBindingFilter, ContentBlock, ContentBlockFilter, NewContentBlock, content_digest,
+};
 pub use inbox_message::{InboxMessage, NewInboxMessage};
-pub use result_set::ResultSet;
+pub use result_set::{NewResultSet, ResultSet, status as result_set_status};
 pub use service::Service;
-pub use subscription::{Subscription, status as subscription_status};
+pub use subscription::{NewSubscription, Subscription, status as subscription_status};
+pub use subscription_delivery::{SubscriptionDelivery, status as delivery_status};
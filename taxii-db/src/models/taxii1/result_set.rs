@@ -1,9 +1,10 @@
 //! ResultSet model.
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 use sqlx::FromRow;
 
 use crate::error::DatabaseResult;
+use crate::models::taxii1::ContentBlock;
 use crate::pool::TaxiiPool;
 
 /// ResultSet database row.
@@ -30,16 +31,85 @@ pub struct ResultSet {
 
     /// Row creation timestamp.
     pub date_created: DateTime<Utc>,
+
+    /// Preparation status (PENDING or READY).
+    pub status: String,
+
+    /// Number of materialized content blocks.
+    ///
+    /// `None` when the result set is not materialized and is resolved
+    /// by querying content blocks with its filters instead.
+    pub content_block_count: Option<i32>,
+
+    /// Time after which the result set can no longer be fulfilled.
+    ///
+    /// `None` never expires.
+    pub expires_at: Option<DateTime<Utc>>,
+
+    /// Protocol binding of the inbox the results are pushed to.
+    pub delivery_protocol_binding: Option<String>,
+
+    /// Address of the inbox the results are pushed to.
+    pub delivery_address: Option<String>,
+
+    /// Message binding of the pushed results.
+    pub delivery_message_binding: Option<String>,
+
+    /// Push status of the results (PENDING, DELIVERED or FAILED).
+    ///
+    /// `None` without a delivery target.
+    pub delivery_status: Option<String>,
+
+    /// Attempts of the last push of the results.
+    pub delivery_attempts: i32,
+
+    /// Error of the last failed push of the results.
+    pub delivery_error: Option<String>,
+}
+
+/// Parameters for creating a new result set.
+#[derive(Debug, Clone, Default)]
+pub struct NewResultSet<'a> {
+    pub id: &'a str,
+    pub collection_id: i32,
+    pub bindings: Option<&'a str>,
+    pub begin_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+    pub status: &'a str,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub delivery_protocol_binding: Option<&'a str>,
+    pub delivery_address: Option<&'a str>,
+    pub delivery_message_binding: Option<&'a str>,
+    pub delivery_status: Option<&'a str>,
+}
+
+/// Result set status constants.
+pub mod status {
+    pub const PENDING: &str = "PENDING";
+    pub const READY: &str = "READY";
+}
+
+/// Result set delivery status constants.
+pub mod delivery_status {
+    pub const PENDING: &str = "PENDING";
+    pub const DELIVERED: &str = "DELIVERED";
+    pub const FAILED: &str = "FAILED";
 }
 
 impl ResultSet {
     /// Find a result set by ID.
+    ///
+    /// Expired result sets are not found.
     pub async fn find(pool: &TaxiiPool, id: &str) -> DatabaseResult<Option<Self>> {
         let result_set = sqlx::query_as!(
             Self,
             r#"SELECT id, collection_id as "collection_id!", bindings, begin_time, end_time,
-                      date_created as "date_created!"
-               FROM result_sets WHERE id = $1"#,
+                      date_created as "date_created!", status, content_block_count,
+                      expires_at, delivery_protocol_binding, delivery_address,
+                      delivery_message_binding, delivery_status, delivery_attempts,
+                      delivery_error
+               FROM result_sets
+               WHERE id = $1 AND (expires_at IS NULL OR expires_at > NOW())"#,
             id
         )
         .fetch_optional(pool.inner())
@@ -48,33 +118,292 @@ impl ResultSet {
         Ok(result_set)
     }
 
+    /// Find unexpired result sets waiting to be prepared, oldest first.
+    pub async fn find_pending(pool: &TaxiiPool, limit: i64) -> DatabaseResult<Vec<Self>> {
+        let result_sets = sqlx::query_as!(
+            Self,
+            r#"SELECT id, collection_id as "collection_id!", bindings, begin_time, end_time,
+                      date_created as "date_created!", status, content_block_count,
+                      expires_at, delivery_protocol_binding, delivery_address,
+                      delivery_message_binding, delivery_status, delivery_attempts,
+                      delivery_error
+               FROM result_sets
+               WHERE status = $1 AND (expires_at IS NULL OR expires_at > NOW())
+               ORDER BY date_created ASC
+               LIMIT $2"#,
+            status::PENDING,
+            limit
+        )
+        .fetch_all(pool.inner())
+        .await?;
+
+        Ok(result_sets)
+    }
+
+    /// Claim prepared, unexpired result sets whose results are waiting to be
+    /// pushed, oldest first.
+    ///
+    /// The result sets are claimed for `lease`: until then, or until their
+    /// delivery is recorded, they are not claimed again, also not by other
+    /// server instances.
+    pub async fn claim_to_deliver(
+        pool: &TaxiiPool,
+        limit: i64,
+        lease: Duration,
+    ) -> DatabaseResult<Vec<Self>> {
+        let lease_secs = lease.num_milliseconds() as f64 / 1000.0;
+        let mut result_sets = sqlx::query_as!(
+            Self,
+            r#"UPDATE result_sets
+               SET claimed_until = NOW() + make_interval(secs => $4)
+               WHERE id IN (
+                   SELECT id
+                   FROM result_sets
+                   WHERE status = $1 AND delivery_status = $2
+                     AND (expires_at IS NULL OR expires_at > NOW())
+                     AND (claimed_until IS NULL OR claimed_until < NOW())
+                   ORDER BY date_created ASC
+                   LIMIT $3
+                   FOR UPDATE SKIP LOCKED
+               )
+               RETURNING id, collection_id as "collection_id!", bindings, begin_time, end_time,
+                         date_created as "date_created!", status, content_block_count,
+                         expires_at, delivery_protocol_binding, delivery_address,
+                         delivery_message_binding, delivery_status, delivery_attempts,
+                         delivery_error"#,
+            status::READY,
+            delivery_status::PENDING,
+            limit,
+            lease_secs
+        )
+        .fetch_all(pool.inner())
+        .await?;
+        result_sets.sort_by_key(|rs| rs.date_created);
+
+        Ok(result_sets)
+    }
+
+    /// Count unexpired result sets waiting to be prepared.
+    pub async fn count_pending(pool: &TaxiiPool) -> DatabaseResult<i64> {
+        let count = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) as "count!" FROM result_sets
+               WHERE status = $1 AND (expires_at IS NULL OR expires_at > NOW())"#,
+            status::PENDING
+        )
+        .fetch_one(pool.inner())
+        .await?;
+
+        Ok(count)
+    }
+
+    /// Record the outcome of pushing the results of a result set, releasing
+    /// its claim.
+    pub async fn record_delivery(
+        pool: &TaxiiPool,
+        id: &str,
+        delivery_status: &str,
+        attempts: i32,
+        error: Option<&str>,
+    ) -> DatabaseResult<()> {
+        sqlx::query!(
+            r#"UPDATE result_sets
+               SET delivery_status = $2, delivery_attempts = $3, delivery_error = $4,
+                   claimed_until = NULL
+               WHERE id = $1"#,
+            id,
+            delivery_status,
+            attempts,
+            error
+        )
+        .execute(pool.inner())
+        .await?;
+
+        Ok(())
+    }
+
     /// Create a new result set.
-    pub async fn create(
+    pub async fn create(pool: &TaxiiPool, params: &NewResultSet<'_>) -> DatabaseResult<Self> {
+        let result_set = sqlx::query_as!(
+            Self,
+            r#"INSERT INTO result_sets
+                   (id, collection_id, bindings, begin_time, end_time, status, expires_at,
+                delivery_protocol_binding, delivery_address, delivery_message_binding,
+                delivery_status)
+               VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+               RETURNING id, collection_id as "collection_id!", bindings, begin_time, end_time,
+                         date_created as "date_created!", status, content_block_count,
+                         expires_at, delivery_protocol_binding, delivery_address,
+                         delivery_message_binding, delivery_status, delivery_attempts,
+                         delivery_error"#,
+            params.id,
+            params.collection_id,
+            params.bindings,
+            params.begin_time,
+            params.end_time,
+            params.status,
+            params.expires_at,
+            params.delivery_protocol_binding,
+            params.delivery_address,
+            params.delivery_message_binding,
+            params.delivery_status
+        )
+        .fetch_one(pool.inner())
+        .await?;
+
+        Ok(result_set)
+    }
+
+    /// Materialize content blocks into a result set and mark it ready.
+    ///
+    /// The position of each block is taken from its index in `content_block_ids`.
+    /// Any previously materialized content is replaced, so the operation can
+    /// be safely retried.
+    pub async fn materialize(
         pool: &TaxiiPool,
         id: &str,
-        collection_id: i32,
-        bindings: Option<&str>,
-        begin_time: Option<DateTime<Utc>>,
-        end_time: Option<DateTime<Utc>>,
-    ) -> DatabaseResult<Self> {
+        content_block_ids: &[i32],
+    ) -> DatabaseResult<Option<Self>> {
+        let mut tx = pool.inner().begin().await?;
+
+        sqlx::query!(
+            "DELETE FROM result_set_content_blocks WHERE result_set_id = $1",
+            id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            r#"INSERT INTO result_set_content_blocks (result_set_id, content_block_id, position)
+               SELECT $1, ids.content_block_id, ids.position::INTEGER
+               FROM UNNEST($2::INTEGER[]) WITH ORDINALITY AS ids(content_block_id, position)"#,
+            id,
+            content_block_ids
+        )
+        .execute(&mut *tx)
+        .await?;
+
         let result_set = sqlx::query_as!(
             Self,
-            r#"INSERT INTO result_sets (id, collection_id, bindings, begin_time, end_time)
-               VALUES ($1, $2, $3, $4, $5)
+            r#"UPDATE result_sets SET status = $2, content_block_count = $3
+               WHERE id = $1
                RETURNING id, collection_id as "collection_id!", bindings, begin_time, end_time,
-                         date_created as "date_created!""#,
+                         date_created as "date_created!", status, content_block_count,
+                         expires_at, delivery_protocol_binding, delivery_address,
+                         delivery_message_binding, delivery_status, delivery_attempts,
+                         delivery_error"#,
             id,
-            collection_id,
-            bindings,
-            begin_time,
-            end_time
+            status::READY,
+            content_block_ids.len() as i32
         )
-        .fetch_one(pool.inner())
+        .fetch_optional(&mut *tx)
         .await?;
 
+        tx.commit().await?;
+
         Ok(result_set)
     }
 
+    /// Get a page of materialized content blocks, in result set order.
+    pub async fn find_content_blocks(
+        pool: &TaxiiPool,
+        id: &str,
+        offset: i64,
+        limit: Option<i64>,
+    ) -> DatabaseResult<Vec<ContentBlock>> {
+        let blocks = sqlx::query_as!(
+            ContentBlock,
+            r#"SELECT cb.id, cb.message, cb.timestamp_label as "timestamp_label!",
+                      cb.inbox_message_id, cb.content, cb.binding_id, cb.binding_subtype,
+                      cb.date_created as "date_created!", cb.validation_error,
+                      cb.signature_status, cb.signer_fingerprint, cb.content_encoding
+               FROM content_blocks cb
+               JOIN result_set_content_blocks rscb ON cb.id = rscb.content_block_id
+               WHERE rscb.result_set_id = $1
+               ORDER BY rscb.position ASC
+               LIMIT $2 OFFSET $3"#,
+            id,
+            limit,
+            offset
+        )
+        .fetch_all(pool.inner())
+        .await?;
+
+        Ok(blocks)
+    }
+
+    /// Find the content sizes, in bytes, of the prepared content blocks of a
+    /// result set, in position order.
+    pub async fn find_content_block_sizes(pool: &TaxiiPool, id: &str) -> DatabaseResult<Vec<i64>> {
+        let sizes = sqlx::query_scalar!(
+            r#"SELECT octet_length(cb.content)::BIGINT as "size!"
+               FROM content_blocks cb
+               JOIN result_set_content_blocks rscb ON cb.id = rscb.content_block_id
+               WHERE rscb.result_set_id = $1
+               ORDER BY rscb.position ASC"#,
+            id
+        )
+        .fetch_all(pool.inner())
+        .await?;
+
+        Ok(sizes)
+    }
+
+    /// Find the number of content blocks in each part of a result set.
+    ///
+    /// `None` until the parts are recorded.
+    pub async fn find_part_sizes(pool: &TaxiiPool, id: &str) -> DatabaseResult<Option<Vec<i64>>> {
+        let sizes = sqlx::query_scalar!("SELECT part_sizes FROM result_sets WHERE id = $1", id)
+            .fetch_optional(pool.inner())
+            .await?;
+
+        Ok(sizes.flatten())
+    }
+
+    /// Record the number of content blocks in each part of a result set,
+    /// unless parts are already recorded.
+    ///
+    /// Returns the recorded parts, or `None` if the result set does not
+    /// exist.
+    pub async fn record_part_sizes(
+        pool: &TaxiiPool,
+        id: &str,
+        sizes: &[i64],
+    ) -> DatabaseResult<Option<Vec<i64>>> {
+        let sizes = sqlx::query_scalar!(
+            r#"UPDATE result_sets SET part_sizes = COALESCE(part_sizes, $2)
+               WHERE id = $1
+               RETURNING part_sizes as "part_sizes!""#,
+            id,
+            sizes
+        )
+        .fetch_optional(pool.inner())
+        .await?;
+
+        Ok(sizes)
+    }
+
+    /// Delete up to `batch_size` result sets that expired before `now`,
+    /// together with their materialized content.
+    ///
+    /// Returns the number of deleted result sets.
+    pub async fn delete_expired(
+        pool: &TaxiiPool,
+        now: DateTime<Utc>,
+        batch_size: i64,
+    ) -> DatabaseResult<u64> {
+        let result = sqlx::query!(
+            r#"DELETE FROM result_sets WHERE id IN (
+                   SELECT id FROM result_sets WHERE expires_at <= $1 LIMIT $2
+               )"#,
+            now,
+            batch_size
+        )
+        .execute(pool.inner())
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
     /// Delete a result set by ID.
     pub async fn delete(pool: &TaxiiPool, id: &str) -> DatabaseResult<bool> {
         let result = sqlx::query!("DELETE FROM result_sets WHERE id = $1", id)
@@ -29,6 +29,42 @@ pub struct Subscription {
 
     /// Row creation timestamp.
     pub date_created: DateTime<Utc>,
+
+    /// Time of the last successful push delivery.
+    pub last_delivery_at: Option<DateTime<Utc>>,
+
+    /// Failed push deliveries since the last successful one.
+    pub delivery_failures: i32,
+
+    /// Error of the last failed push delivery.
+    pub last_delivery_error: Option<String>,
+
+    /// Protocol binding of the subscriber's inbox.
+    pub push_protocol_binding: Option<String>,
+
+    /// Address of the subscriber's inbox; `NULL` if content is not pushed.
+    pub push_address: Option<String>,
+
+    /// Message binding of pushed messages.
+    pub push_message_binding: Option<String>,
+
+    /// Foreign key to accounts.id of the account that created the
+    /// subscription; `NULL` if created without authentication.
+    pub account_id: Option<i32>,
+}
+
+/// Parameters for creating or updating a subscription.
+#[derive(Debug, Clone)]
+pub struct NewSubscription<'a> {
+    pub id: &'a str,
+    pub collection_id: i32,
+    pub params: Option<&'a str>,
+    pub status: &'a str,
+    pub service_id: &'a str,
+    pub push_protocol_binding: Option<&'a str>,
+    pub push_address: Option<&'a str>,
+    pub push_message_binding: Option<&'a str>,
+    pub account_id: Option<i32>,
 }
 
 /// Subscription status constants.
@@ -44,7 +80,9 @@ impl Subscription {
         let subscription = sqlx::query_as!(
             Self,
             r#"SELECT id, collection_id as "collection_id!", params, status as "status!",
-                      service_id as "service_id!", date_created as "date_created!"
+                      service_id as "service_id!", date_created as "date_created!",
+                      last_delivery_at, delivery_failures, last_delivery_error,
+                      push_protocol_binding, push_address, push_message_binding, account_id
                FROM subscriptions WHERE id = $1"#,
             id
         )
@@ -59,7 +97,9 @@ impl Subscription {
         let subscriptions = sqlx::query_as!(
             Self,
             r#"SELECT id, collection_id as "collection_id!", params, status as "status!",
-                      service_id as "service_id!", date_created as "date_created!"
+                      service_id as "service_id!", date_created as "date_created!",
+                      last_delivery_at, delivery_failures, last_delivery_error,
+                      push_protocol_binding, push_address, push_message_binding, account_id
                FROM subscriptions WHERE service_id = $1"#,
             service_id
         )
@@ -69,23 +109,40 @@ impl Subscription {
         Ok(subscriptions)
     }
 
-    /// Upsert a subscription (insert or update).
-    /// Uses transaction with SELECT FOR UPDATE for atomicity.
-    pub async fn upsert(
+    /// Find active subscriptions of a collection that content is pushed to.
+    pub async fn find_push_by_collection(
         pool: &TaxiiPool,
-        id: &str,
         collection_id: i32,
-        params: Option<&str>,
-        status: &str,
-        service_id: &str,
-    ) -> DatabaseResult<Self> {
+    ) -> DatabaseResult<Vec<Self>> {
+        let subscriptions = sqlx::query_as!(
+            Self,
+            r#"SELECT id, collection_id as "collection_id!", params, status as "status!",
+                      service_id as "service_id!", date_created as "date_created!",
+                      last_delivery_at, delivery_failures, last_delivery_error,
+                      push_protocol_binding, push_address, push_message_binding, account_id
+               FROM subscriptions
+               WHERE collection_id = $1 AND status = $2 AND push_address IS NOT NULL
+               ORDER BY id"#,
+            collection_id,
+            status::ACTIVE
+        )
+        .fetch_all(pool.inner())
+        .await?;
+
+        Ok(subscriptions)
+    }
+
+    /// Upsert a subscription (insert or update).
+    /// The owner of an existing subscription is kept.
+    /// Uses transaction with SELECT FOR UPDATE for atomicity.
+    pub async fn upsert(pool: &TaxiiPool, new: &NewSubscription<'_>) -> DatabaseResult<Self> {
         // Use a transaction for atomicity
         let mut tx = pool.inner().begin().await?;
 
         // Check if exists with row lock
         let existing = sqlx::query_scalar!(
             r#"SELECT id FROM subscriptions WHERE id = $1 FOR UPDATE"#,
-            id
+            new.id
         )
         .fetch_optional(&mut *tx)
         .await?;
@@ -93,24 +150,37 @@ impl Subscription {
         if existing.is_some() {
             // Update existing
             sqlx::query!(
-                r#"UPDATE subscriptions SET collection_id = $2, params = $3, status = $4, service_id = $5 WHERE id = $1"#,
-                id,
-                collection_id,
-                params,
-                status,
-                service_id
+                r#"UPDATE subscriptions SET collection_id = $2, params = $3, status = $4, service_id = $5,
+                       delivery_failures = CASE WHEN status <> $4::VARCHAR THEN 0 ELSE delivery_failures END,
+                       push_protocol_binding = $6, push_address = $7, push_message_binding = $8
+                   WHERE id = $1"#,
+                new.id,
+                new.collection_id,
+                new.params,
+                new.status,
+                new.service_id,
+                new.push_protocol_binding,
+                new.push_address,
+                new.push_message_binding
             )
             .execute(&mut *tx)
             .await?;
         } else {
             // Insert new
             sqlx::query!(
-                r#"INSERT INTO subscriptions (id, collection_id, params, status, service_id) VALUES ($1, $2, $3, $4, $5)"#,
-                id,
-                collection_id,
-                params,
-                status,
-                service_id
+                r#"INSERT INTO subscriptions (id, collection_id, params, status, service_id,
+                                              push_protocol_binding, push_address, push_message_binding,
+                                              account_id)
+                   VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"#,
+                new.id,
+                new.collection_id,
+                new.params,
+                new.status,
+                new.service_id,
+                new.push_protocol_binding,
+                new.push_address,
+                new.push_message_binding,
+                new.account_id
             )
             .execute(&mut *tx)
             .await?;
@@ -118,11 +188,71 @@ impl Subscription {
 
         tx.commit().await?;
 
-        Self::find(pool, id)
+        Self::find(pool, new.id)
             .await?
             .ok_or_else(|| crate::error::DatabaseError::not_found("Failed to upsert subscription"))
     }
 
+    /// Record a successful push delivery, resetting the failure count.
+    ///
+    /// Returns `None` if the subscription does not exist.
+    pub async fn record_delivery_success(
+        pool: &TaxiiPool,
+        id: &str,
+    ) -> DatabaseResult<Option<Self>> {
+        let subscription = sqlx::query_as!(
+            Self,
+            r#"UPDATE subscriptions
+               SET last_delivery_at = NOW(), delivery_failures = 0, last_delivery_error = NULL
+               WHERE id = $1
+               RETURNING id, collection_id as "collection_id!", params, status as "status!",
+                         service_id as "service_id!", date_created as "date_created!",
+                         last_delivery_at, delivery_failures, last_delivery_error,
+                         push_protocol_binding, push_address, push_message_binding,
+                         account_id"#,
+            id
+        )
+        .fetch_optional(pool.inner())
+        .await?;
+
+        Ok(subscription)
+    }
+
+    /// Record a failed push delivery.
+    ///
+    /// An active subscription is paused once `pause_after` deliveries in a
+    /// row failed. Returns `None` if the subscription does not exist.
+    pub async fn record_delivery_failure(
+        pool: &TaxiiPool,
+        id: &str,
+        error: &str,
+        pause_after: i32,
+    ) -> DatabaseResult<Option<Self>> {
+        let subscription = sqlx::query_as!(
+            Self,
+            r#"UPDATE subscriptions
+               SET delivery_failures = delivery_failures + 1,
+                   last_delivery_error = $2,
+                   status = CASE WHEN status = $4 AND delivery_failures + 1 >= $3 THEN $5
+                                 ELSE status END
+               WHERE id = $1
+               RETURNING id, collection_id as "collection_id!", params, status as "status!",
+                         service_id as "service_id!", date_created as "date_created!",
+                         last_delivery_at, delivery_failures, last_delivery_error,
+                         push_protocol_binding, push_address, push_message_binding,
+                         account_id"#,
+            id,
+            error,
+            pause_after,
+            status::ACTIVE,
+            status::PAUSED
+        )
+        .fetch_optional(pool.inner())
+        .await?;
+
+        Ok(subscription)
+    }
+
     /// Delete a subscription by ID.
     pub async fn delete(pool: &TaxiiPool, id: &str) -> DatabaseResult<bool> {
         let result = sqlx::query!("DELETE FROM subscriptions WHERE id = $1", id)
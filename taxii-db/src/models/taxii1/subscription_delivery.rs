@@ -0,0 +1,188 @@
+//! Subscription delivery model.
+
+use chrono::{DateTime, Duration, Utc};
+use sqlx::FromRow;
+
+use crate::error::DatabaseResult;
+use crate::models::taxii1::subscription_status;
+use crate::pool::TaxiiPool;
+
+/// Subscription delivery database row.
+///
+/// Table: subscription_deliveries
+#[derive(Debug, Clone, FromRow)]
+pub struct SubscriptionDelivery {
+    /// Primary key.
+    pub id: i32,
+
+    /// Foreign key to subscriptions.id.
+    pub subscription_id: String,
+
+    /// Foreign key to content_blocks.id.
+    pub content_block_id: i32,
+
+    /// Delivery status (PENDING, DELIVERED or FAILED).
+    pub status: String,
+
+    /// Delivery attempts made.
+    pub attempts: i32,
+
+    /// Error of the last failed attempt.
+    pub last_error: Option<String>,
+
+    /// Row creation timestamp.
+    pub date_created: DateTime<Utc>,
+
+    /// Time of the last delivery attempt.
+    pub last_attempt_at: Option<DateTime<Utc>>,
+}
+
+/// Subscription delivery status constants.
+pub mod status {
+    pub const PENDING: &str = "PENDING";
+    pub const DELIVERED: &str = "DELIVERED";
+    pub const FAILED: &str = "FAILED";
+}
+
+impl SubscriptionDelivery {
+    /// Queue the delivery of a content block to subscriptions.
+    ///
+    /// Deliveries that are already queued are left as they are. Returns the
+    /// number of deliveries queued.
+    pub async fn enqueue(
+        pool: &TaxiiPool,
+        subscription_ids: &[String],
+        content_block_id: i32,
+    ) -> DatabaseResult<u64> {
+        let result = sqlx::query!(
+            r#"INSERT INTO subscription_deliveries (subscription_id, content_block_id, status)
+               SELECT UNNEST($1::VARCHAR[]), $2, $3
+               ON CONFLICT (subscription_id, content_block_id) DO NOTHING"#,
+            subscription_ids,
+            content_block_id,
+            status::PENDING
+        )
+        .execute(pool.inner())
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Claim pending deliveries to active subscriptions, oldest first.
+    ///
+    /// The deliveries are claimed for `lease`: until then, or until their
+    /// outcome is recorded, they are not claimed again, also not by other
+    /// server instances. Deliveries to paused subscriptions stay pending
+    /// until the subscription is resumed.
+    pub async fn claim_pending(
+        pool: &TaxiiPool,
+        limit: i64,
+        lease: Duration,
+    ) -> DatabaseResult<Vec<Self>> {
+        let lease_secs = lease.num_milliseconds() as f64 / 1000.0;
+        let mut deliveries = sqlx::query_as!(
+            Self,
+            r#"UPDATE subscription_deliveries
+               SET claimed_until = NOW() + make_interval(secs => $4)
+               WHERE id IN (
+                   SELECT d.id
+                   FROM subscription_deliveries d
+                   JOIN subscriptions s ON s.id = d.subscription_id
+                   WHERE d.status = $1 AND s.status = $2
+                     AND (d.claimed_until IS NULL OR d.claimed_until < NOW())
+                   ORDER BY d.id
+                   LIMIT $3
+                   FOR UPDATE OF d SKIP LOCKED
+               )
+               RETURNING id, subscription_id, content_block_id, status, attempts,
+                         last_error, date_created, last_attempt_at"#,
+            status::PENDING,
+            subscription_status::ACTIVE,
+            limit,
+            lease_secs
+        )
+        .fetch_all(pool.inner())
+        .await?;
+        deliveries.sort_by_key(|d| d.id);
+
+        Ok(deliveries)
+    }
+
+    /// Find deliveries, newest first, optionally by status and subscription.
+    pub async fn find_filtered(
+        pool: &TaxiiPool,
+        delivery_status: Option<&str>,
+        subscription_id: Option<&str>,
+        limit: i64,
+    ) -> DatabaseResult<Vec<Self>> {
+        let deliveries = sqlx::query_as!(
+            Self,
+            r#"SELECT id, subscription_id, content_block_id, status, attempts,
+                      last_error, date_created, last_attempt_at
+               FROM subscription_deliveries
+               WHERE ($1::VARCHAR IS NULL OR status = $1)
+                 AND ($2::VARCHAR IS NULL OR subscription_id = $2)
+               ORDER BY id DESC
+               LIMIT $3"#,
+            delivery_status,
+            subscription_id,
+            limit
+        )
+        .fetch_all(pool.inner())
+        .await?;
+
+        Ok(deliveries)
+    }
+
+    /// Record the outcome of delivery attempts, releasing their claim.
+    ///
+    /// `attempts` is added to the attempts of each delivery.
+    pub async fn record(
+        pool: &TaxiiPool,
+        ids: &[i32],
+        delivery_status: &str,
+        attempts: i32,
+        error: Option<&str>,
+    ) -> DatabaseResult<u64> {
+        let result = sqlx::query!(
+            r#"UPDATE subscription_deliveries
+               SET status = $2, attempts = attempts + $3, last_error = $4,
+                   last_attempt_at = NOW(), claimed_until = NULL
+               WHERE id = ANY($1)"#,
+            ids,
+            delivery_status,
+            attempts,
+            error
+        )
+        .execute(pool.inner())
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Queue failed deliveries again.
+    ///
+    /// Requeues the failed deliveries with the given IDs, or all failed
+    /// deliveries if `ids` is empty, optionally only of one subscription.
+    pub async fn requeue_failed(
+        pool: &TaxiiPool,
+        ids: &[i32],
+        subscription_id: Option<&str>,
+    ) -> DatabaseResult<u64> {
+        let result = sqlx::query!(
+            r#"UPDATE subscription_deliveries
+               SET status = $3, claimed_until = NULL
+               WHERE status = $4
+                 AND (cardinality($1::INTEGER[]) = 0 OR id = ANY($1))
+                 AND ($2::VARCHAR IS NULL OR subscription_id = $2)"#,
+            ids,
+            subscription_id,
+            status::PENDING,
+            status::FAILED
+        )
+        .execute(pool.inner())
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
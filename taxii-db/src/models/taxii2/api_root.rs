@@ -25,6 +25,9 @@ pub struct ApiRoot {
 
     /// Whether this API root is publicly accessible.
     pub is_public: bool,
+
+    /// Largest POST body in bytes (`None` uses the server limit).
+    pub max_content_length: Option<i64>,
 }
 
 impl ApiRoot {
@@ -32,7 +35,7 @@ impl ApiRoot {
     pub async fn find(pool: &TaxiiPool, id: Uuid) -> DatabaseResult<Option<Self>> {
         let api_root = sqlx::query_as!(
             Self,
-            r#"SELECT id, "default", title, description, is_public
+            r#"SELECT id, "default", title, description, is_public, max_content_length
                FROM opentaxii_api_root WHERE id = $1"#,
             id
         )
@@ -46,7 +49,7 @@ impl ApiRoot {
     pub async fn find_all(pool: &TaxiiPool) -> DatabaseResult<Vec<Self>> {
         let api_roots = sqlx::query_as!(
             Self,
-            r#"SELECT id, "default", title, description, is_public
+            r#"SELECT id, "default", title, description, is_public, max_content_length
                FROM opentaxii_api_root ORDER BY title"#
         )
         .fetch_all(pool.inner())
@@ -68,7 +71,7 @@ impl ApiRoot {
             Self,
             r#"INSERT INTO opentaxii_api_root (id, title, description, "default", is_public)
                VALUES ($1, $2, $3, $4, $5)
-               RETURNING id, "default", title, description, is_public"#,
+               RETURNING id, "default", title, description, is_public, max_content_length"#,
             id,
             title,
             description,
@@ -91,6 +94,47 @@ impl ApiRoot {
         Ok(api_root)
     }
 
+    /// Update the title, description, default flag, public access and
+    /// content length limit of an API root.
+    pub async fn update(
+        pool: &TaxiiPool,
+        id: Uuid,
+        title: &str,
+        description: Option<&str>,
+        default: bool,
+        is_public: bool,
+        max_content_length: Option<i64>,
+    ) -> DatabaseResult<Option<Self>> {
+        let api_root = sqlx::query_as!(
+            Self,
+            r#"UPDATE opentaxii_api_root
+               SET title = $2, description = $3, "default" = $4, is_public = $5,
+                   max_content_length = $6
+               WHERE id = $1
+               RETURNING id, "default", title, description, is_public, max_content_length"#,
+            id,
+            title,
+            description,
+            default,
+            is_public,
+            max_content_length
+        )
+        .fetch_optional(pool.inner())
+        .await?;
+
+        // If this is default, unset other defaults
+        if default && api_root.is_some() {
+            sqlx::query!(
+                r#"UPDATE opentaxii_api_root SET "default" = false WHERE id != $1"#,
+                id
+            )
+            .execute(pool.inner())
+            .await?;
+        }
+
+        Ok(api_root)
+    }
+
     /// Delete an API root by ID.
     pub async fn delete(pool: &TaxiiPool, id: Uuid) -> DatabaseResult<bool> {
         let result = sqlx::query!("DELETE FROM opentaxii_api_root WHERE id = $1", id)
@@ -1,6 +1,6 @@
 //! Collection model (TAXII 2.x collections).
 
-use sqlx::FromRow;
+use sqlx::{FromRow, PgConnection};
 use uuid::Uuid;
 
 use crate::error::DatabaseResult;
@@ -31,6 +31,15 @@ pub struct Collection {
 
     /// Whether collection is publicly writable.
     pub is_public_write: bool,
+
+    /// Days objects are kept after being added (`None` keeps them).
+    pub retention_days: Option<i32>,
+
+    /// Names of validation rules posted objects must pass.
+    pub validation_rules: Vec<String>,
+
+    /// Marking definition applied to posted objects without markings.
+    pub default_marking: Option<String>,
 }
 
 impl Collection {
@@ -39,7 +48,8 @@ impl Collection {
         let collection = sqlx::query_as!(
             Self,
             r#"SELECT id, api_root_id as "api_root_id!", title as "title!", description, alias,
-                      is_public as "is_public!", is_public_write as "is_public_write!"
+                      is_public as "is_public!", is_public_write as "is_public_write!", retention_days,
+                      validation_rules, default_marking
                FROM opentaxii_collection WHERE id = $1"#,
             id
         )
@@ -57,7 +67,8 @@ impl Collection {
         let collections = sqlx::query_as!(
             Self,
             r#"SELECT id, api_root_id as "api_root_id!", title as "title!", description, alias,
-                      is_public as "is_public!", is_public_write as "is_public_write!"
+                      is_public as "is_public!", is_public_write as "is_public_write!", retention_days,
+                      validation_rules, default_marking
                FROM opentaxii_collection WHERE api_root_id = $1 ORDER BY title"#,
             api_root_id
         )
@@ -68,6 +79,9 @@ impl Collection {
     }
 
     /// Find a collection by ID or alias within an API root.
+    ///
+    /// A UUID resolves to the collection with that ID before a collection
+    /// whose alias happens to be the same UUID.
     pub async fn find_by_id_or_alias(
         pool: &TaxiiPool,
         api_root_id: Uuid,
@@ -80,9 +94,12 @@ impl Collection {
             sqlx::query_as!(
                 Self,
                 r#"SELECT id, api_root_id as "api_root_id!", title as "title!", description, alias,
-                          is_public as "is_public!", is_public_write as "is_public_write!"
+                          is_public as "is_public!", is_public_write as "is_public_write!", retention_days,
+                          validation_rules, default_marking
                    FROM opentaxii_collection
-                   WHERE api_root_id = $1 AND (id = $2 OR alias = $3)"#,
+                   WHERE api_root_id = $1 AND (id = $2 OR alias = $3)
+                   ORDER BY id = $2 DESC
+                   LIMIT 1"#,
                 api_root_id,
                 coll_uuid,
                 id_or_alias
@@ -93,7 +110,8 @@ impl Collection {
             sqlx::query_as!(
                 Self,
                 r#"SELECT id, api_root_id as "api_root_id!", title as "title!", description, alias,
-                          is_public as "is_public!", is_public_write as "is_public_write!"
+                          is_public as "is_public!", is_public_write as "is_public_write!", retention_days,
+                          validation_rules, default_marking
                    FROM opentaxii_collection
                    WHERE api_root_id = $1 AND alias = $2"#,
                 api_root_id,
@@ -107,8 +125,10 @@ impl Collection {
     }
 
     /// Create a new collection.
+    #[expect(clippy::too_many_arguments, reason = "mirrors the collection columns")]
     pub async fn create(
         pool: &TaxiiPool,
+        id: Uuid,
         api_root_id: Uuid,
         title: &str,
         description: Option<&str>,
@@ -116,14 +136,39 @@ impl Collection {
         is_public: bool,
         is_public_write: bool,
     ) -> DatabaseResult<Self> {
-        let id = Uuid::new_v4();
+        let mut conn = pool.acquire().await?;
+        Self::create_in(
+            &mut conn,
+            id,
+            api_root_id,
+            title,
+            description,
+            alias,
+            is_public,
+            is_public_write,
+        )
+        .await
+    }
 
+    /// Create a new collection on a connection.
+    #[expect(clippy::too_many_arguments, reason = "mirrors the collection columns")]
+    pub async fn create_in(
+        conn: &mut PgConnection,
+        id: Uuid,
+        api_root_id: Uuid,
+        title: &str,
+        description: Option<&str>,
+        alias: Option<&str>,
+        is_public: bool,
+        is_public_write: bool,
+    ) -> DatabaseResult<Self> {
         let collection = sqlx::query_as!(
             Self,
             r#"INSERT INTO opentaxii_collection (id, api_root_id, title, description, alias, is_public, is_public_write)
                VALUES ($1, $2, $3, $4, $5, $6, $7)
                RETURNING id, api_root_id as "api_root_id!", title as "title!", description, alias,
-                         is_public as "is_public!", is_public_write as "is_public_write!""#,
+                         is_public as "is_public!", is_public_write as "is_public_write!", retention_days,
+                         validation_rules, default_marking"#,
             id,
             api_root_id,
             title,
@@ -132,7 +177,100 @@ impl Collection {
             is_public,
             is_public_write
         )
-        .fetch_one(pool.inner())
+        .fetch_one(conn)
+        .await?;
+
+        Ok(collection)
+    }
+
+    /// Update the title, description, alias and public access of a
+    /// collection.
+    pub async fn update(
+        pool: &TaxiiPool,
+        id: Uuid,
+        title: &str,
+        description: Option<&str>,
+        alias: Option<&str>,
+        is_public: bool,
+        is_public_write: bool,
+    ) -> DatabaseResult<Option<Self>> {
+        let collection = sqlx::query_as!(
+            Self,
+            r#"UPDATE opentaxii_collection
+               SET title = $2, description = $3, alias = $4, is_public = $5, is_public_write = $6
+               WHERE id = $1
+               RETURNING id, api_root_id as "api_root_id!", title as "title!", description, alias,
+                         is_public as "is_public!", is_public_write as "is_public_write!", retention_days,
+                         validation_rules, default_marking"#,
+            id,
+            title,
+            description,
+            alias,
+            is_public,
+            is_public_write
+        )
+        .fetch_optional(pool.inner())
+        .await?;
+
+        Ok(collection)
+    }
+
+    /// Find collections with a retention policy.
+    pub async fn find_with_retention(pool: &TaxiiPool) -> DatabaseResult<Vec<Self>> {
+        let collections = sqlx::query_as!(
+            Self,
+            r#"SELECT id, api_root_id as "api_root_id!", title as "title!", description, alias,
+                      is_public as "is_public!", is_public_write as "is_public_write!", retention_days,
+                      validation_rules, default_marking
+               FROM opentaxii_collection WHERE retention_days IS NOT NULL ORDER BY id"#
+        )
+        .fetch_all(pool.inner())
+        .await?;
+
+        Ok(collections)
+    }
+
+    /// Set the retention policy of a collection (`None` removes it).
+    ///
+    /// Returns `None` if the collection does not exist.
+    pub async fn set_retention_days(
+        pool: &TaxiiPool,
+        id: Uuid,
+        retention_days: Option<i32>,
+    ) -> DatabaseResult<Option<Self>> {
+        let collection = sqlx::query_as!(
+            Self,
+            r#"UPDATE opentaxii_collection SET retention_days = $2 WHERE id = $1
+               RETURNING id, api_root_id as "api_root_id!", title as "title!", description, alias,
+                         is_public as "is_public!", is_public_write as "is_public_write!", retention_days,
+                         validation_rules, default_marking"#,
+            id,
+            retention_days
+        )
+        .fetch_optional(pool.inner())
+        .await?;
+
+        Ok(collection)
+    }
+
+    /// Set the validation rules of a collection.
+    ///
+    /// Returns `None` if the collection does not exist.
+    pub async fn set_validation_rules(
+        pool: &TaxiiPool,
+        id: Uuid,
+        validation_rules: &[String],
+    ) -> DatabaseResult<Option<Self>> {
+        let collection = sqlx::query_as!(
+            Self,
+            r#"UPDATE opentaxii_collection SET validation_rules = $2 WHERE id = $1
+               RETURNING id, api_root_id as "api_root_id!", title as "title!", description, alias,
+                         is_public as "is_public!", is_public_write as "is_public_write!", retention_days,
+                         validation_rules, default_marking"#,
+            id,
+            validation_rules
+        )
+        .fetch_optional(pool.inner())
         .await?;
 
         Ok(collection)
@@ -140,8 +278,16 @@ impl Collection {
 
     /// Delete a collection by ID.
     pub async fn delete(pool: &TaxiiPool, id: Uuid) -> DatabaseResult<bool> {
+        let mut conn = pool.acquire().await?;
+        Self::delete_in(&mut conn, id).await
+    }
+
+    /// Delete a collection by ID on a connection.
+    ///
+    /// Objects of the collection are deleted with it.
+    pub async fn delete_in(conn: &mut PgConnection, id: Uuid) -> DatabaseResult<bool> {
         let result = sqlx::query!("DELETE FROM opentaxii_collection WHERE id = $1", id)
-            .execute(pool.inner())
+            .execute(conn)
             .await?;
 
         Ok(result.rows_affected() > 0)
@@ -158,4 +304,27 @@ impl Collection {
 
         Ok(result)
     }
+
+    /// Set the default marking of a collection (`None` removes it).
+    ///
+    /// Returns `None` if the collection does not exist.
+    pub async fn set_default_marking(
+        pool: &TaxiiPool,
+        id: Uuid,
+        default_marking: Option<&str>,
+    ) -> DatabaseResult<Option<Self>> {
+        let collection = sqlx::query_as!(
+            Self,
+            r#"UPDATE opentaxii_collection SET default_marking = $2 WHERE id = $1
+               RETURNING id, api_root_id as "api_root_id!", title as "title!", description, alias,
+                         is_public as "is_public!", is_public_write as "is_public_write!", retention_days,
+                         validation_rules, default_marking"#,
+            id,
+            default_marking
+        )
+        .fetch_optional(pool.inner())
+        .await?;
+
+        Ok(collection)
+    }
 }
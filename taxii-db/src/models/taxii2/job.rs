@@ -1,7 +1,7 @@
 //! Job and JobDetail models (TAXII 2.x async jobs).
 
 use chrono::{NaiveDateTime, Utc};
-use sqlx::FromRow;
+use sqlx::{FromRow, PgConnection};
 use uuid::Uuid;
 
 use crate::error::DatabaseResult;
@@ -86,8 +86,45 @@ impl Job {
         Ok(job)
     }
 
+    /// Find the most recently requested jobs, optionally of one API root
+    /// and with one status ("pending" or "complete").
+    pub async fn find_recent(
+        pool: &TaxiiPool,
+        api_root_id: Option<Uuid>,
+        status: Option<&str>,
+        limit: i64,
+    ) -> DatabaseResult<Vec<Self>> {
+        let jobs = sqlx::query_as!(
+            Self,
+            r#"SELECT id, api_root_id as "api_root_id!", status::text as "status!",
+                      request_timestamp, completed_timestamp,
+                      total_count, success_count, failure_count, pending_count
+               FROM opentaxii_job
+               WHERE ($1::uuid IS NULL OR api_root_id = $1)
+                 AND ($2::text IS NULL OR status::text = $2)
+               ORDER BY request_timestamp DESC NULLS LAST, id
+               LIMIT $3"#,
+            api_root_id,
+            status,
+            limit
+        )
+        .fetch_all(pool.inner())
+        .await?;
+
+        Ok(jobs)
+    }
+
     /// Create a new pending job.
+    ///
+    /// Job (status) IDs are random UUIDs, so concurrent requests never get
+    /// the same ID.
     pub async fn create(pool: &TaxiiPool, params: &NewJob) -> DatabaseResult<Self> {
+        let mut conn = pool.acquire().await?;
+        Self::create_in(&mut conn, params).await
+    }
+
+    /// Create a new pending job on a connection (e.g. inside a transaction).
+    pub async fn create_in(conn: &mut PgConnection, params: &NewJob) -> DatabaseResult<Self> {
         let id = Uuid::new_v4();
         let now = Utc::now().naive_utc();
 
@@ -101,7 +138,7 @@ impl Job {
             params.api_root_id,
             now
         )
-        .fetch_one(pool.inner())
+        .fetch_one(conn)
         .await?;
 
         Ok(job)
@@ -114,6 +151,18 @@ impl Job {
         total_count: i32,
         success_count: i32,
         failure_count: i32,
+    ) -> DatabaseResult<()> {
+        let mut conn = pool.acquire().await?;
+        Self::complete_in(&mut conn, id, total_count, success_count, failure_count).await
+    }
+
+    /// Complete a job with counts on a connection (e.g. inside a transaction).
+    pub async fn complete_in(
+        conn: &mut PgConnection,
+        id: Uuid,
+        total_count: i32,
+        success_count: i32,
+        failure_count: i32,
     ) -> DatabaseResult<()> {
         sqlx::query!(
             r#"UPDATE opentaxii_job
@@ -126,24 +175,94 @@ impl Job {
             success_count,
             failure_count
         )
-        .execute(pool.inner())
+        .execute(conn)
         .await?;
 
         Ok(())
     }
 
-    /// Cleanup old completed jobs (older than 24 hours).
-    pub async fn cleanup_old(pool: &TaxiiPool) -> DatabaseResult<i64> {
-        let cutoff = (Utc::now() - chrono::Duration::hours(24)).naive_utc();
+    /// Delete up to `batch_size` jobs completed before `cutoff` with their
+    /// details, and with `include_incomplete` also jobs still pending that
+    /// were requested before `cutoff`.
+    ///
+    /// Returns the numbers of deleted jobs and details.
+    pub async fn delete_before(
+        pool: &TaxiiPool,
+        cutoff: NaiveDateTime,
+        include_incomplete: bool,
+        batch_size: i64,
+    ) -> DatabaseResult<(u64, u64)> {
+        let mut tx = pool.inner().begin().await?;
+
+        let ids = sqlx::query_scalar!(
+            r#"SELECT id FROM opentaxii_job
+               WHERE (status = 'complete' AND completed_timestamp < $1)
+                  OR ($2 AND status = 'pending' AND request_timestamp < $1)
+               ORDER BY id
+               LIMIT $3
+               FOR UPDATE SKIP LOCKED"#,
+            cutoff,
+            include_incomplete,
+            batch_size
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        if ids.is_empty() {
+            return Ok((0, 0));
+        }
 
-        let result = sqlx::query!(
-            "DELETE FROM opentaxii_job WHERE completed_timestamp < $1",
-            cutoff
+        let details = sqlx::query!(
+            "DELETE FROM opentaxii_job_detail WHERE job_id = ANY($1)",
+            &ids
         )
-        .execute(pool.inner())
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        let jobs = sqlx::query!("DELETE FROM opentaxii_job WHERE id = ANY($1)", &ids)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+        tx.commit().await?;
+
+        Ok((jobs, details))
+    }
+
+    /// Mark jobs still pending that were requested before `cutoff` as
+    /// failed: the job completes and its pending details become failures.
+    ///
+    /// Returns the number of failed jobs.
+    pub async fn fail_stale(pool: &TaxiiPool, cutoff: NaiveDateTime) -> DatabaseResult<u64> {
+        let mut tx = pool.inner().begin().await?;
+
+        let ids = sqlx::query_scalar!(
+            r#"UPDATE opentaxii_job
+               SET status = 'complete', completed_timestamp = $2,
+                   failure_count = COALESCE(failure_count, 0) + COALESCE(pending_count, 0),
+                   pending_count = 0
+               WHERE status = 'pending' AND request_timestamp < $1
+               RETURNING id"#,
+            cutoff,
+            Utc::now().naive_utc()
+        )
+        .fetch_all(&mut *tx)
         .await?;
 
-        Ok(result.rows_affected() as i64)
+        if !ids.is_empty() {
+            sqlx::query!(
+                r#"UPDATE opentaxii_job_detail
+                   SET status = 'failure', message = 'Job did not complete'
+                   WHERE job_id = ANY($1) AND status = 'pending'"#,
+                &ids
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(ids.len() as u64)
     }
 }
 
@@ -172,6 +291,18 @@ pub struct JobDetail {
     pub status: String,
 }
 
+/// Parameters for creating a job detail.
+#[derive(Debug, Clone)]
+pub struct NewJobDetail {
+    pub id: Uuid,
+    pub stix_id: String,
+    /// Object version. Stored as timestamp without timezone in PostgreSQL.
+    pub version: NaiveDateTime,
+    /// Detail status (see [`job_detail_status`]).
+    pub status: &'static str,
+    pub message: Option<String>,
+}
+
 impl JobDetail {
     /// Find all details for a job.
     pub async fn find_by_job(pool: &TaxiiPool, job_id: Uuid) -> DatabaseResult<Vec<Self>> {
@@ -226,6 +357,40 @@ impl JobDetail {
 
         Ok(detail)
     }
+
+    /// Create many job details of a job with one statement.
+    pub async fn create_many(
+        conn: &mut PgConnection,
+        job_id: Uuid,
+        details: &[NewJobDetail],
+    ) -> DatabaseResult<()> {
+        if details.is_empty() {
+            return Ok(());
+        }
+
+        let ids: Vec<Uuid> = details.iter().map(|d| d.id).collect();
+        let stix_ids: Vec<String> = details.iter().map(|d| d.stix_id.clone()).collect();
+        let versions: Vec<NaiveDateTime> = details.iter().map(|d| d.version).collect();
+        let statuses: Vec<String> = details.iter().map(|d| d.status.to_string()).collect();
+        let messages: Vec<Option<String>> = details.iter().map(|d| d.message.clone()).collect();
+
+        sqlx::query!(
+            r#"INSERT INTO opentaxii_job_detail (id, job_id, stix_id, version, status, message)
+               SELECT t.id, $1, t.stix_id, t.version, t.status::job_detail_status_enum, t.message
+               FROM UNNEST($2::uuid[], $3::varchar[], $4::timestamp[], $5::text[], $6::text[])
+                        AS t(id, stix_id, version, status, message)"#,
+            job_id,
+            &ids,
+            &stix_ids,
+            &versions,
+            &statuses,
+            &messages as &[Option<String>]
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
 }
 
 /// Job status constants.
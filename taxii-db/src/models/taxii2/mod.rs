@@ -6,17 +6,24 @@
 //! - opentaxii_stixobject
 //! - opentaxii_job
 //! - opentaxii_job_detail
+//! - object_audit
 
 pub mod api_root;
 pub mod collection;
 pub mod job;
+pub mod object_audit;
 pub mod query;
 pub mod stix_object;
 
 pub use api_root::ApiRoot;
 pub use collection::Collection;
-pub use job::{Job, JobDetail, NewJob, job_detail_status, job_status};
+pub use job::{Job, JobDetail, NewJob, NewJobDetail, job_detail_status, job_status};
+pub use object_audit::{AuditOperation, ObjectAudit, ObjectAuditFilter, ObjectAuditRecord};
 pub use query::{
-    PaginatedResult, PaginationCursor, Taxii2QueryParams, get_next_param, parse_next_param,
+    PaginatedResult, PaginationCursor, PropertyFilter, Taxii2QueryParams, VersionSelector,
+    get_next_param, get_search_next_param, get_versions_next_param, parse_next_param,
+};
+pub use stix_object::{
+    DeleteMode, DeletedVersion, FilteredResult, NewSTIXObject, STIXObject, VersionInfo,
+    VersionsResult,
 };
-pub use stix_object::{FilteredResult, NewSTIXObject, STIXObject, VersionInfo, VersionsResult};
@@ -0,0 +1,282 @@
+//! Audit log of TAXII 2.x object mutations.
+//!
+//! Every object version created, overwritten or deleted is recorded in the
+//! transaction of the change, with the account that made it.
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use sqlx::{FromRow, PgConnection};
+use uuid::Uuid;
+
+use super::query::PaginatedResult;
+use crate::error::DatabaseResult;
+use crate::models::account_activity::ActivityCursor;
+use crate::pool::TaxiiPool;
+
+/// Kind of object mutation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditOperation {
+    /// A new object version was stored.
+    Create,
+    /// The content of a stored version was replaced.
+    Update,
+    /// A version was deleted.
+    Delete,
+}
+
+impl AuditOperation {
+    /// Convert to database string representation.
+    #[must_use]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Create => "create",
+            Self::Update => "update",
+            Self::Delete => "delete",
+        }
+    }
+
+    /// Parse from the database representation.
+    #[must_use]
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "create" => Some(Self::Create),
+            "update" => Some(Self::Update),
+            "delete" => Some(Self::Delete),
+            _ => None,
+        }
+    }
+}
+
+/// Audit record with the account's username.
+#[derive(Debug, Clone, FromRow)]
+pub struct ObjectAuditRecord {
+    /// Primary key.
+    pub id: i64,
+
+    /// Account that made the change, if authenticated.
+    pub account_id: Option<i32>,
+
+    /// Username of the account, unless it was deleted since.
+    pub username: Option<String>,
+
+    /// Operation (create, update, delete).
+    pub operation: String,
+
+    /// Collection of the object.
+    pub collection_id: Uuid,
+
+    /// STIX object ID.
+    pub object_id: String,
+
+    /// Object version (modified timestamp).
+    pub object_version: DateTime<Utc>,
+
+    /// Time of the change.
+    pub created_at: DateTime<Utc>,
+}
+
+/// Filter for querying the object audit log.
+///
+/// All set criteria must match. Results are ordered newest first.
+#[derive(Debug, Clone, Default)]
+pub struct ObjectAuditFilter {
+    /// Only changes in this collection.
+    pub collection_id: Option<Uuid>,
+    /// Only changes of this STIX object.
+    pub object_id: Option<String>,
+    /// Only changes by this account.
+    pub account_id: Option<i32>,
+    /// Only changes by the account with this username.
+    pub username: Option<String>,
+    /// Only these operations (any operation if empty).
+    pub operations: Vec<AuditOperation>,
+    /// Only changes at or after this time.
+    pub since: Option<DateTime<Utc>>,
+    /// Only changes before this time.
+    pub until: Option<DateTime<Utc>>,
+    /// Maximum number of records to return.
+    pub limit: Option<i64>,
+    /// Continue after this position (from a previous page).
+    pub after: Option<ActivityCursor>,
+}
+
+/// Object audit operations.
+///
+/// Table: object_audit
+pub struct ObjectAudit;
+
+impl ObjectAudit {
+    /// Record an operation on object versions of a collection.
+    pub async fn record_many(
+        conn: &mut PgConnection,
+        account_id: Option<i32>,
+        operation: AuditOperation,
+        collection_id: Uuid,
+        versions: &[(String, NaiveDateTime)],
+    ) -> DatabaseResult<u64> {
+        if versions.is_empty() {
+            return Ok(0);
+        }
+
+        let (ids, object_versions): (Vec<String>, Vec<DateTime<Utc>>) = versions
+            .iter()
+            .map(|(id, version)| (id.clone(), version.and_utc()))
+            .unzip();
+
+        let result = sqlx::query!(
+            r#"INSERT INTO object_audit
+                   (account_id, operation, collection_id, object_id, object_version)
+               SELECT $1, $2, $3, object_id, object_version
+               FROM UNNEST($4::varchar[], $5::timestamptz[]) AS t(object_id, object_version)"#,
+            account_id,
+            operation.as_str(),
+            collection_id,
+            &ids,
+            &object_versions,
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Query audit records, newest first, with keyset pagination.
+    pub async fn find_filtered(
+        pool: &TaxiiPool,
+        filter: &ObjectAuditFilter,
+    ) -> DatabaseResult<PaginatedResult<Vec<ObjectAuditRecord>>> {
+        let mut query = String::from(
+            r#"SELECT oa.id, oa.account_id, a.username, oa.operation, oa.collection_id,
+                      oa.object_id, oa.object_version, oa.created_at
+               FROM object_audit oa
+               LEFT JOIN accounts a ON a.id = oa.account_id
+               WHERE TRUE"#,
+        );
+
+        let mut param_idx = 1;
+
+        if filter.collection_id.is_some() {
+            query.push_str(&format!(" AND oa.collection_id = ${param_idx}"));
+            param_idx += 1;
+        }
+
+        if filter.object_id.is_some() {
+            query.push_str(&format!(" AND oa.object_id = ${param_idx}"));
+            param_idx += 1;
+        }
+
+        if filter.account_id.is_some() {
+            query.push_str(&format!(" AND oa.account_id = ${param_idx}"));
+            param_idx += 1;
+        }
+
+        if filter.username.is_some() {
+            query.push_str(&format!(" AND a.username = ${param_idx}"));
+            param_idx += 1;
+        }
+
+        if !filter.operations.is_empty() {
+            query.push_str(&format!(" AND oa.operation = ANY(${param_idx})"));
+            param_idx += 1;
+        }
+
+        if filter.since.is_some() {
+            query.push_str(&format!(" AND oa.created_at >= ${param_idx}"));
+            param_idx += 1;
+        }
+
+        if filter.until.is_some() {
+            query.push_str(&format!(" AND oa.created_at < ${param_idx}"));
+            param_idx += 1;
+        }
+
+        if filter.after.is_some() {
+            query.push_str(&format!(
+                " AND (oa.created_at, oa.id) < (${}, ${})",
+                param_idx,
+                param_idx + 1
+            ));
+        }
+
+        query.push_str(" ORDER BY oa.created_at DESC, oa.id DESC");
+
+        // Apply limit + 1 for efficient "more" detection
+        if let Some(limit) = filter.limit {
+            query.push_str(&format!(" LIMIT {}", limit + 1));
+        }
+
+        let mut q = sqlx::query_as::<_, ObjectAuditRecord>(&query);
+
+        if let Some(collection_id) = filter.collection_id {
+            q = q.bind(collection_id);
+        }
+
+        if let Some(object_id) = &filter.object_id {
+            q = q.bind(object_id);
+        }
+
+        if let Some(account_id) = filter.account_id {
+            q = q.bind(account_id);
+        }
+
+        if let Some(username) = &filter.username {
+            q = q.bind(username);
+        }
+
+        if !filter.operations.is_empty() {
+            let operations: Vec<&str> = filter.operations.iter().map(|o| o.as_str()).collect();
+            q = q.bind(operations);
+        }
+
+        if let Some(since) = filter.since {
+            q = q.bind(since);
+        }
+
+        if let Some(until) = filter.until {
+            q = q.bind(until);
+        }
+
+        if let Some(after) = &filter.after {
+            q = q.bind(after.created_at).bind(after.id);
+        }
+
+        let mut records = q.fetch_all(pool.inner()).await?;
+
+        let more = filter
+            .limit
+            .is_some_and(|limit| records.len() as i64 > limit);
+        if let Some(limit) = filter.limit {
+            records.truncate(limit as usize);
+        }
+
+        let next = if more {
+            records.last().map(|last| {
+                ActivityCursor {
+                    created_at: last.created_at,
+                    id: last.id,
+                }
+                .encode()
+            })
+        } else {
+            None
+        };
+
+        Ok(PaginatedResult::new(records, more, next))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audit_operation_parse() {
+        for operation in [
+            AuditOperation::Create,
+            AuditOperation::Update,
+            AuditOperation::Delete,
+        ] {
+            assert_eq!(AuditOperation::parse(operation.as_str()), Some(operation));
+        }
+        assert_eq!(AuditOperation::parse("purge"), None);
+    }
+}
@@ -2,6 +2,8 @@
 
 use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
 use chrono::{DateTime, NaiveDateTime, Utc};
+use serde_json::Value;
+use uuid::Uuid;
 
 /// Paginated result for TAXII 2.x queries.
 ///
@@ -41,12 +43,27 @@ impl<T> PaginatedResult<T> {
 ///
 /// Replaces tuple `(DateTime<Utc>, String)` with named fields for clarity.
 /// Used to track position in paginated result sets.
+///
+/// Objects are paged in "recently added" order, `(date_added, id)`. Inserts
+/// are serialized per collection and get strictly increasing `date_added`
+/// values, so objects added while a client is paging always sort after the
+/// cursor: they are returned by a later page, never skipped or repeated.
+///
+/// Search results are paged by relevance first, `(rank, date_added, id)`.
+///
+/// Versions of an object are paged in version order, `(version, date_added,
+/// pk)`: cursors into a versions listing carry the version, and the primary
+/// key of the row in `object_id`.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct PaginationCursor {
     /// The date when the object was added to the collection.
     pub date_added: DateTime<Utc>,
     /// The object ID for tie-breaking when dates are equal.
     pub object_id: String,
+    /// Search rank of the object, for cursors into search results.
+    pub rank: Option<i32>,
+    /// Version of the object, for cursors into a versions listing.
+    pub version: Option<DateTime<Utc>>,
 }
 
 impl PaginationCursor {
@@ -56,9 +73,109 @@ impl PaginationCursor {
         Self {
             date_added,
             object_id: object_id.into(),
+            rank: None,
+            version: None,
         }
     }
 
+    /// Set the search rank of the cursor.
+    #[inline]
+    #[must_use]
+    pub fn with_rank(mut self, rank: i32) -> Self {
+        self.rank = Some(rank);
+        self
+    }
+
+    /// Set the version of the cursor.
+    #[inline]
+    #[must_use]
+    pub fn with_version(mut self, version: DateTime<Utc>) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    /// SQL ordering that cursors page through.
+    pub const ORDER_BY: &'static str = "date_added, id";
+
+    /// SQL ordering of the versions of an object, from first to last.
+    ///
+    /// Rows sharing a version (possible in data loaded before the unique
+    /// constraint existed) are ordered by date added, then primary key.
+    pub const VERSION_ORDER_BY: &'static str = "version, date_added, pk";
+
+    /// SQL condition selecting versions after a versions cursor.
+    ///
+    /// The cursor's version, date added and primary key are bound as
+    /// parameters `param_idx` to `param_idx + 2` (see
+    /// [`Self::bind_version`]).
+    #[must_use]
+    pub fn versions_sql_condition(param_idx: usize) -> String {
+        format!(
+            " AND (version, date_added, pk) > (${}, ${}, ${}::uuid)",
+            param_idx,
+            param_idx + 1,
+            param_idx + 2
+        )
+    }
+
+    /// SQL ordering that search cursors page through, given the SQL
+    /// expression of the search rank.
+    #[must_use]
+    pub fn search_order_by(rank_expr: &str) -> String {
+        format!("{rank_expr}, {}", Self::ORDER_BY)
+    }
+
+    /// SQL condition selecting search results after a cursor.
+    ///
+    /// The cursor's rank, date added and object ID are bound as parameters
+    /// `param_idx` to `param_idx + 2` (see [`Self::bind_rank`]).
+    #[must_use]
+    pub fn search_sql_condition(rank_expr: &str, param_idx: usize) -> String {
+        format!(
+            " AND ({rank_expr}, date_added, id) > (${}, ${}, ${})",
+            param_idx,
+            param_idx + 1,
+            param_idx + 2
+        )
+    }
+
+    /// Search rank to bind; cursors into unranked results start at the
+    /// most relevant rank.
+    #[inline]
+    pub fn bind_rank(&self) -> i32 {
+        self.rank.unwrap_or(0)
+    }
+
+    /// SQL condition selecting rows after a cursor.
+    ///
+    /// The cursor's date added and object ID are bound as parameters
+    /// `param_idx` and `param_idx + 1` (see [`Self::bind_date_added`]).
+    #[must_use]
+    pub fn sql_condition(param_idx: usize) -> String {
+        format!(
+            " AND (date_added, id) > (${}, ${})",
+            param_idx,
+            param_idx + 1
+        )
+    }
+
+    /// Date added as stored in the database (timestamp without timezone).
+    ///
+    /// Binding this instead of the `DateTime<Utc>` avoids a conversion through
+    /// the session time zone when comparing with `date_added`.
+    #[inline]
+    pub fn bind_date_added(&self) -> NaiveDateTime {
+        self.date_added.naive_utc()
+    }
+
+    /// Version as stored in the database; cursors without a version start
+    /// before the first version.
+    #[inline]
+    pub fn bind_version(&self) -> NaiveDateTime {
+        self.version
+            .map_or(NaiveDateTime::MIN, |version| version.naive_utc())
+    }
+
     /// Convert to tuple for backward compatibility with existing code.
     #[inline]
     pub fn as_tuple(&self) -> (DateTime<Utc>, &str) {
@@ -66,6 +183,96 @@ impl PaginationCursor {
     }
 }
 
+/// Selects a single version of an object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VersionSelector {
+    /// The earliest version (lowest `modified`).
+    First,
+    /// The latest version (highest `modified`).
+    Last,
+    /// The version with exactly this `modified` timestamp.
+    Exact(DateTime<Utc>),
+}
+
+impl VersionSelector {
+    /// Parse a `match[version]` style value: `first`, `last` or a timestamp.
+    #[must_use]
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "first" => Some(Self::First),
+            "last" => Some(Self::Last),
+            _ => DateTime::parse_from_rfc3339(value)
+                .ok()
+                .map(|dt| Self::Exact(dt.with_timezone(&Utc))),
+        }
+    }
+}
+
+/// Filter on a property of stored objects (`match[<property>]`).
+///
+/// An object matches if the property is one of the values, or is an array
+/// containing one of them. Objects without the property, or with a
+/// non-string value, never match. Values are compared exactly (no case or
+/// unicode normalization).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PropertyFilter {
+    /// Top-level property name.
+    pub property: String,
+    /// Accepted values.
+    pub values: Vec<String>,
+}
+
+impl PropertyFilter {
+    /// Create a property filter.
+    pub fn new(property: impl Into<String>, values: Vec<String>) -> Self {
+        Self {
+            property: property.into(),
+            values,
+        }
+    }
+
+    /// Whether an object's serialized data matches the filter.
+    ///
+    /// This is the reference for the SQL condition of the filter (see
+    /// [`Self::sql_condition`]), which must select exactly the same objects.
+    #[must_use]
+    pub fn matches(&self, data: &Value) -> bool {
+        let accepts = |value: &Value| {
+            value
+                .as_str()
+                .is_some_and(|s| self.values.iter().any(|v| v == s))
+        };
+        match data.get(&self.property) {
+            Some(Value::Array(items)) => items.iter().any(accepts),
+            Some(value) => accepts(value),
+            None => false,
+        }
+    }
+
+    /// SQL condition of the filter, with the values bound as parameter
+    /// `param_idx` (see [`Self::bind_values`]).
+    ///
+    /// JSONB containment of a string matches both an equal string and an
+    /// array containing it. The property name is part of the SQL so the
+    /// expression indexes on filtered properties can be used.
+    #[must_use]
+    pub fn sql_condition(&self, param_idx: usize) -> String {
+        format!(
+            " AND serialized_data -> '{}' @> ANY(${param_idx}::jsonb[])",
+            self.property.replace('\'', "''")
+        )
+    }
+
+    /// Values to bind for [`Self::sql_condition`].
+    #[must_use]
+    pub fn bind_values(&self) -> Vec<Value> {
+        self.values
+            .iter()
+            .map(|v| Value::from(v.as_str()))
+            .collect()
+    }
+}
+
 /// Query parameters for TAXII 2.x object retrieval.
 ///
 /// Groups common filtering parameters to reduce function argument count.
@@ -86,6 +293,10 @@ pub struct Taxii2QueryParams<'a> {
     pub match_version: Option<&'a [String]>,
     /// Filter by STIX spec versions
     pub match_spec_version: Option<&'a [String]>,
+    /// Filter by object properties (all must match)
+    pub match_properties: Option<&'a [PropertyFilter]>,
+    /// Also return deleted (tombstoned) versions, for exports
+    pub include_deleted: bool,
 }
 
 /// Get value for `next` based on dict instance.
@@ -102,27 +313,184 @@ pub fn get_next_param(date_added: &NaiveDateTime, id: &str) -> String {
     BASE64.encode(data.as_bytes())
 }
 
+/// Get value for `next` of a page of search results.
+#[must_use]
+pub fn get_search_next_param(rank: i32, date_added: &NaiveDateTime, id: &str) -> String {
+    let data = format!(
+        "{rank}|{}+00:00|{id}",
+        date_added.format("%Y-%m-%dT%H:%M:%S%.6f")
+    );
+    BASE64.encode(data.as_bytes())
+}
+
+/// Get value for `next` of a page of versions of an object.
+#[must_use]
+pub fn get_versions_next_param(
+    version: &NaiveDateTime,
+    date_added: &NaiveDateTime,
+    pk: &Uuid,
+) -> String {
+    let data = format!(
+        "{}+00:00|{}+00:00|{pk}",
+        version.format("%Y-%m-%dT%H:%M:%S%.6f"),
+        date_added.format("%Y-%m-%dT%H:%M:%S%.6f")
+    );
+    BASE64.encode(data.as_bytes())
+}
+
 /// Parse provided `next_param` into a pagination cursor.
 ///
-/// Handles timestamps with timezone offsets (e.g., +00:00, -05:00) and without.
+/// Handles timestamps with timezone offsets (e.g., +00:00, -05:00) and without,
+/// cursors into search results, which carry a rank, and cursors into a
+/// versions listing, which carry a version and a primary key.
 #[must_use]
 pub fn parse_next_param(next_param: &str) -> Option<PaginationCursor> {
     let decoded = BASE64.decode(next_param).ok()?;
     let data = String::from_utf8(decoded).ok()?;
-    let parts: Vec<&str> = data.split('|').collect();
-    if parts.len() != 2 {
-        return None;
+    let mut parts: Vec<&str> = data.split('|').collect();
+    let (rank, version) = match parts.len() {
+        2 => (None, None),
+        3 => {
+            let first = parts.remove(0);
+            match first.parse::<i32>() {
+                Ok(rank) => (Some(rank), None),
+                Err(_) => {
+                    Uuid::parse_str(parts[1]).ok()?;
+                    (None, Some(parse_cursor_timestamp(first)?))
+                }
+            }
+        }
+        _ => return None,
+    };
+
+    let date_added = parse_cursor_timestamp(parts[0])?;
+
+    let mut cursor = PaginationCursor::new(date_added, parts[1]);
+    if let Some(rank) = rank {
+        cursor = cursor.with_rank(rank);
     }
+    if let Some(version) = version {
+        cursor = cursor.with_version(version);
+    }
+    Some(cursor)
+}
 
+/// Parse a timestamp of a `next` value.
+fn parse_cursor_timestamp(value: &str) -> Option<DateTime<Utc>> {
     // Try RFC3339 parsing first (handles +00:00, -05:00, Z, etc.)
-    let date_added = if let Ok(dt) = DateTime::parse_from_rfc3339(parts[0]) {
-        dt.with_timezone(&Utc)
-    } else {
-        // Fallback for timestamps without timezone (legacy cursors)
-        chrono::NaiveDateTime::parse_from_str(parts[0], "%Y-%m-%dT%H:%M:%S%.6f")
-            .ok()?
-            .and_utc()
-    };
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Some(dt.with_timezone(&Utc));
+    }
+    // Fallback for timestamps without timezone (legacy cursors)
+    chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%.6f")
+        .ok()
+        .map(|dt| dt.and_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    Some(PaginationCursor::new(date_added, parts[1]))
+    #[test]
+    fn test_next_param_roundtrip() -> Result<(), chrono::ParseError> {
+        let date_added = DateTime::parse_from_rfc3339("2025-01-02T03:04:05.123456Z")?.naive_utc();
+
+        let next = get_next_param(&date_added, "indicator--a");
+        let cursor = parse_next_param(&next);
+
+        assert_eq!(
+            cursor.map(|c| (c.bind_date_added(), c.object_id)),
+            Some((date_added, "indicator--a".to_string()))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_next_param_roundtrip() -> Result<(), chrono::ParseError> {
+        let date_added = DateTime::parse_from_rfc3339("2025-01-02T03:04:05.123456Z")?.naive_utc();
+
+        let cursor = parse_next_param(&get_search_next_param(2, &date_added, "indicator--a"));
+        assert_eq!(
+            cursor,
+            Some(PaginationCursor::new(date_added.and_utc(), "indicator--a").with_rank(2))
+        );
+
+        let invalid = BASE64.encode(b"x|2025-01-02T03:04:05.123456+00:00|indicator--a");
+        assert_eq!(parse_next_param(&invalid), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_versions_next_param_roundtrip() -> Result<(), chrono::ParseError> {
+        let version = DateTime::parse_from_rfc3339("2025-01-01T00:00:00.000001Z")?.naive_utc();
+        let date_added = DateTime::parse_from_rfc3339("2025-01-02T03:04:05.123456Z")?.naive_utc();
+        let pk = Uuid::nil();
+
+        let cursor = parse_next_param(&get_versions_next_param(&version, &date_added, &pk));
+        assert_eq!(
+            cursor,
+            Some(
+                PaginationCursor::new(date_added.and_utc(), pk.to_string())
+                    .with_version(version.and_utc())
+            )
+        );
+        assert_eq!(cursor.map(|c| c.bind_version()), Some(version));
+
+        let invalid = BASE64
+            .encode(b"2025-01-01T00:00:00+00:00|2025-01-02T03:04:05.123456+00:00|indicator--a");
+        assert_eq!(parse_next_param(&invalid), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_version_selector_parse() -> Result<(), chrono::ParseError> {
+        assert_eq!(
+            VersionSelector::parse("first"),
+            Some(VersionSelector::First)
+        );
+        assert_eq!(VersionSelector::parse("last"), Some(VersionSelector::Last));
+        assert_eq!(
+            VersionSelector::parse("2025-01-02T03:04:05.123Z"),
+            Some(VersionSelector::Exact(
+                DateTime::parse_from_rfc3339("2025-01-02T03:04:05.123Z")?.with_timezone(&Utc)
+            ))
+        );
+        assert_eq!(VersionSelector::parse("all"), None);
+        assert_eq!(VersionSelector::parse("yesterday"), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_property_filter_matches() {
+        let filter = PropertyFilter::new("labels", vec!["café".to_string(), "c2".to_string()]);
+
+        assert!(filter.matches(&serde_json::json!({"labels": ["benign", "café"]})));
+        assert!(filter.matches(&serde_json::json!({"labels": "c2"})));
+        // No case or unicode normalization
+        assert!(!filter.matches(&serde_json::json!({"labels": ["CAFÉ", "cafe\u{301}"]})));
+        assert!(!filter.matches(&serde_json::json!({"labels": [["c2"]]})));
+        assert!(!filter.matches(&serde_json::json!({"name": "c2"})));
+    }
+
+    #[test]
+    fn test_property_filter_sql_condition() {
+        let filter = PropertyFilter::new("o'clock", vec!["x".to_string()]);
+        assert_eq!(
+            filter.sql_condition(4),
+            " AND serialized_data -> 'o''clock' @> ANY($4::jsonb[])"
+        );
+        assert_eq!(filter.bind_values(), vec![serde_json::json!("x")]);
+    }
+
+    #[test]
+    fn test_sql_condition() {
+        assert_eq!(
+            PaginationCursor::sql_condition(3),
+            " AND (date_added, id) > ($3, $4)"
+        );
+    }
 }
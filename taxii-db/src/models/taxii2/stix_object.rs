@@ -1,13 +1,20 @@
 //! STIXObject model (TAXII 2.x STIX objects).
 
-use chrono::{DateTime, NaiveDateTime, Utc};
+use chrono::{DateTime, NaiveDateTime, SubsecRound, Utc};
 use serde_json::Value;
-use sqlx::FromRow;
+use sqlx::postgres::PgArguments;
+use sqlx::query::QueryAs;
+use sqlx::{FromRow, PgConnection, Postgres};
 use uuid::Uuid;
 
-use super::query::{PaginationCursor, Taxii2QueryParams, get_next_param};
+use super::query::{
+    PaginationCursor, PropertyFilter, Taxii2QueryParams, VersionSelector, get_next_param,
+    get_search_next_param, get_versions_next_param,
+};
 use crate::error::DatabaseResult;
+use crate::models::collection_stats::TypeStats;
 use crate::pool::TaxiiPool;
+use crate::stream::{RowStream, forward_rows, spawn_stream};
 
 /// Result of a filtered STIX object query.
 #[derive(Debug)]
@@ -20,6 +27,21 @@ pub struct FilteredResult {
     pub next: Option<String>,
 }
 
+/// SQL condition matching objects against the search query bound as `$2`.
+const SEARCH_CONDITION: &str =
+    " AND stix_search_vector(serialized_data) @@ websearch_to_tsquery('english', $2)";
+
+/// SQL expression of the search rank of an object (0 is most relevant).
+const SEARCH_RANK: &str = "stix_search_rank(serialized_data, websearch_to_tsquery('english', $2))";
+
+/// A STIX object matching a search, with its rank.
+#[derive(Debug, FromRow)]
+struct SearchHit {
+    #[sqlx(flatten)]
+    object: STIXObject,
+    search_rank: i32,
+}
+
 /// Version record from version query (model layer, uses NaiveDateTime).
 #[derive(Debug, Clone)]
 pub struct VersionInfo {
@@ -40,6 +62,31 @@ pub struct VersionsResult {
     pub next: Option<String>,
 }
 
+/// How objects are deleted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeleteMode {
+    /// Keep the rows as tombstones, recording who deleted them.
+    Soft {
+        /// Account that deleted the objects.
+        deleted_by: Option<i32>,
+    },
+    /// Remove the rows.
+    Hard,
+}
+
+/// A deleted (tombstoned) object version.
+#[derive(Debug, Clone, FromRow)]
+pub struct DeletedVersion {
+    /// STIX object ID.
+    pub id: String,
+    /// Object version (modified timestamp).
+    pub version: NaiveDateTime,
+    /// Time of deletion.
+    pub deleted_at: DateTime<Utc>,
+    /// Account that deleted the version.
+    pub deleted_by: Option<i32>,
+}
+
 /// STIXObject database row.
 ///
 /// Table: opentaxii_stixobject
@@ -69,7 +116,7 @@ pub struct STIXObject {
     /// Stored as timestamp without timezone in PostgreSQL.
     pub version: NaiveDateTime,
 
-    /// Serialized STIX data as JSON.
+    /// Serialized STIX data as JSONB (without id, type and spec_version).
     pub serialized_data: Value,
 }
 
@@ -97,6 +144,7 @@ impl STIXObject {
             r#"SELECT EXISTS(
                    SELECT 1 FROM opentaxii_stixobject
                    WHERE id = $1 AND collection_id = $2 AND version = $3
+                     AND deleted_at IS NULL
                ) as "exists!""#,
             stix_id,
             collection_id,
@@ -117,7 +165,7 @@ impl STIXObject {
         let exists = sqlx::query_scalar!(
             r#"SELECT EXISTS(
                    SELECT 1 FROM opentaxii_stixobject
-                   WHERE id = $1 AND collection_id = $2
+                   WHERE id = $1 AND collection_id = $2 AND deleted_at IS NULL
                ) as "exists!""#,
             stix_id,
             collection_id
@@ -129,14 +177,33 @@ impl STIXObject {
     }
 
     /// Create a new STIX object.
+    ///
+    /// Inserts into a collection are serialized with a transaction-level
+    /// advisory lock, and `date_added` is taken from the database clock while
+    /// holding it, strictly after the latest `date_added` of the collection.
+    /// An object therefore never becomes visible with a `date_added` earlier
+    /// than already visible objects, which keeps "recently added" pagination
+    /// (see [`PaginationCursor`]) free of skips under concurrent writes.
     pub async fn create(pool: &TaxiiPool, params: &NewSTIXObject<'_>) -> DatabaseResult<Self> {
         let pk = Uuid::new_v4();
-        let date_added = Utc::now().naive_utc();
+
+        let mut tx = pool.inner().begin().await?;
+
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtext('opentaxii_stixobject:' || $1::text))")
+            .bind(params.collection_id)
+            .execute(&mut *tx)
+            .await?;
 
         let obj = sqlx::query_as!(
             Self,
             r#"INSERT INTO opentaxii_stixobject (pk, id, collection_id, type, spec_version, date_added, version, serialized_data)
-               VALUES ($1, $2, $3, $4, $5, $6, $7, $8::json)
+               VALUES ($1, $2, $3, $4, $5,
+                       GREATEST(
+                           clock_timestamp() AT TIME ZONE 'UTC',
+                           (SELECT MAX(date_added) + INTERVAL '1 microsecond'
+                            FROM opentaxii_stixobject WHERE collection_id = $3)
+                       ),
+                       $6, $7::jsonb)
                RETURNING pk, id as "id!", collection_id as "collection_id!", type as "stix_type!",
                          spec_version as "spec_version!", date_added as "date_added!", version as "version!",
                          serialized_data as "serialized_data!""#,
@@ -145,79 +212,258 @@ impl STIXObject {
             params.collection_id,
             params.stix_type,
             params.spec_version,
-            date_added,
             params.version,
             params.serialized_data
         )
-        .fetch_one(pool.inner())
+        .fetch_one(&mut *tx)
         .await?;
 
+        tx.commit().await?;
+
         Ok(obj)
     }
 
-    /// Delete all versions of an object.
-    pub async fn delete_all_versions(
-        pool: &TaxiiPool,
+    /// Insert many STIX objects into a collection with one statement.
+    ///
+    /// All objects must belong to `collection_id`. Objects whose
+    /// (id, version) already exists in the collection, or repeat an earlier
+    /// object of the batch, are skipped. Uses the same advisory lock and
+    /// `date_added` ordering as [`create`](Self::create), with consecutive
+    /// `date_added` values in batch order, so it can be called repeatedly
+    /// inside one transaction for chunks of a larger batch.
+    ///
+    /// Re-adding a deleted (tombstoned) version restores it with a new
+    /// `date_added`, so clients paging by date added see it again.
+    ///
+    /// Returns the (id, version) pairs that were inserted or restored.
+    pub async fn create_many(
+        conn: &mut PgConnection,
         collection_id: Uuid,
-        stix_id: &str,
-    ) -> DatabaseResult<u64> {
-        let result = sqlx::query!(
-            "DELETE FROM opentaxii_stixobject WHERE collection_id = $1 AND id = $2",
+        objects: &[NewSTIXObject<'_>],
+    ) -> DatabaseResult<Vec<(String, NaiveDateTime)>> {
+        if objects.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let pks: Vec<Uuid> = objects.iter().map(|_| Uuid::new_v4()).collect();
+        let ids: Vec<String> = objects.iter().map(|o| o.id.to_string()).collect();
+        let types: Vec<String> = objects.iter().map(|o| o.stix_type.to_string()).collect();
+        let spec_versions: Vec<String> =
+            objects.iter().map(|o| o.spec_version.to_string()).collect();
+        let versions: Vec<NaiveDateTime> = objects.iter().map(|o| o.version).collect();
+        let data: Vec<String> = objects
+            .iter()
+            .map(|o| o.serialized_data.to_string())
+            .collect();
+
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtext('opentaxii_stixobject:' || $1::text))")
+            .bind(collection_id)
+            .execute(&mut *conn)
+            .await?;
+
+        let rows = sqlx::query!(
+            r#"INSERT INTO opentaxii_stixobject (pk, id, collection_id, type, spec_version, date_added, version, serialized_data)
+               SELECT t.pk, t.id, $1, t.type, t.spec_version,
+                      base.date_added + (t.ord - 1) * INTERVAL '1 microsecond',
+                      t.version, t.data::jsonb
+               FROM (SELECT DISTINCT ON (u.id, u.version) u.*
+                     FROM UNNEST($2::uuid[], $3::varchar[], $4::varchar[], $5::varchar[],
+                                 $6::timestamp[], $7::text[])
+                              WITH ORDINALITY AS u(pk, id, type, spec_version, version, data, ord)
+                     ORDER BY u.id, u.version, u.ord) AS t,
+                    (SELECT GREATEST(
+                                clock_timestamp() AT TIME ZONE 'UTC',
+                                (SELECT MAX(date_added) + INTERVAL '1 microsecond'
+                                 FROM opentaxii_stixobject WHERE collection_id = $1)
+                            ) AS date_added) AS base
+               ORDER BY t.ord
+               ON CONFLICT (collection_id, id, version) DO UPDATE
+                   SET type = EXCLUDED.type, spec_version = EXCLUDED.spec_version,
+                       date_added = EXCLUDED.date_added,
+                       serialized_data = EXCLUDED.serialized_data,
+                       deleted_at = NULL, deleted_by = NULL
+                   WHERE opentaxii_stixobject.deleted_at IS NOT NULL
+               RETURNING id as "id!", version as "version!""#,
             collection_id,
-            stix_id
+            &pks,
+            &ids,
+            &types,
+            &spec_versions,
+            &versions,
+            &data
         )
-        .execute(pool.inner())
+        .fetch_all(&mut *conn)
         .await?;
 
-        Ok(result.rows_affected())
+        Ok(rows.into_iter().map(|r| (r.id, r.version)).collect())
     }
 
-    /// Delete the first (oldest) version of an object.
-    pub async fn delete_first_version(
-        pool: &TaxiiPool,
+    /// Find the content of the current (not deleted) versions of objects in
+    /// a collection.
+    ///
+    /// Takes the collection's insert lock first, so the versions cannot
+    /// change before the end of the transaction's writes.
+    ///
+    /// Returns (id, version, serialized data) of every version of the given
+    /// object IDs.
+    pub async fn find_current_data(
+        conn: &mut PgConnection,
         collection_id: Uuid,
-        stix_id: &str,
-    ) -> DatabaseResult<u64> {
-        let result = sqlx::query!(
-            r#"DELETE FROM opentaxii_stixobject
-               WHERE pk IN (
-                   SELECT pk FROM opentaxii_stixobject
-                   WHERE collection_id = $1 AND id = $2
-                   ORDER BY version ASC
-                   LIMIT 1
-               )"#,
+        ids: &[String],
+    ) -> DatabaseResult<Vec<(String, NaiveDateTime, Value)>> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtext('opentaxii_stixobject:' || $1::text))")
+            .bind(collection_id)
+            .execute(&mut *conn)
+            .await?;
+
+        let rows = sqlx::query!(
+            r#"SELECT id as "id!", version as "version!", serialized_data as "serialized_data!"
+               FROM opentaxii_stixobject
+               WHERE collection_id = $1 AND id = ANY($2) AND deleted_at IS NULL"#,
             collection_id,
-            stix_id
+            ids
         )
-        .execute(pool.inner())
+        .fetch_all(&mut *conn)
         .await?;
 
-        Ok(result.rows_affected())
+        Ok(rows
+            .into_iter()
+            .map(|r| (r.id, r.version, r.serialized_data))
+            .collect())
     }
 
-    /// Delete the last (newest) version of an object.
-    pub async fn delete_last_version(
-        pool: &TaxiiPool,
+    /// Replace the content of existing object versions of a collection.
+    ///
+    /// Each object replaces the current (not deleted) row with its
+    /// (id, version) and gets a new `date_added`, after every object added
+    /// so far, so clients paging by date added see the new content. Objects
+    /// must not repeat an (id, version).
+    ///
+    /// Returns the number of replaced rows.
+    pub async fn overwrite_many(
+        conn: &mut PgConnection,
         collection_id: Uuid,
-        stix_id: &str,
+        objects: &[NewSTIXObject<'_>],
     ) -> DatabaseResult<u64> {
+        if objects.is_empty() {
+            return Ok(0);
+        }
+
+        let ids: Vec<String> = objects.iter().map(|o| o.id.to_string()).collect();
+        let spec_versions: Vec<String> =
+            objects.iter().map(|o| o.spec_version.to_string()).collect();
+        let versions: Vec<NaiveDateTime> = objects.iter().map(|o| o.version).collect();
+        let data: Vec<String> = objects
+            .iter()
+            .map(|o| o.serialized_data.to_string())
+            .collect();
+
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtext('opentaxii_stixobject:' || $1::text))")
+            .bind(collection_id)
+            .execute(&mut *conn)
+            .await?;
+
         let result = sqlx::query!(
-            r#"DELETE FROM opentaxii_stixobject
-               WHERE pk IN (
-                   SELECT pk FROM opentaxii_stixobject
-                   WHERE collection_id = $1 AND id = $2
-                   ORDER BY version DESC
-                   LIMIT 1
-               )"#,
+            r#"UPDATE opentaxii_stixobject o
+               SET spec_version = t.spec_version,
+                   serialized_data = t.data::jsonb,
+                   date_added = base.date_added + (t.ord - 1) * INTERVAL '1 microsecond'
+               FROM UNNEST($2::varchar[], $3::varchar[], $4::timestamp[], $5::text[])
+                        WITH ORDINALITY AS t(id, spec_version, version, data, ord),
+                    (SELECT GREATEST(
+                                clock_timestamp() AT TIME ZONE 'UTC',
+                                (SELECT MAX(date_added) + INTERVAL '1 microsecond'
+                                 FROM opentaxii_stixobject WHERE collection_id = $1)
+                            ) AS date_added) AS base
+               WHERE o.collection_id = $1 AND o.id = t.id AND o.version = t.version
+                 AND o.deleted_at IS NULL"#,
             collection_id,
-            stix_id
+            &ids,
+            &spec_versions,
+            &versions,
+            &data
         )
-        .execute(pool.inner())
+        .execute(&mut *conn)
         .await?;
 
         Ok(result.rows_affected())
     }
 
+    /// Find one version of an object.
+    ///
+    /// Rows sharing a version (possible in data loaded before the unique
+    /// constraint existed) are ordered by date added, then primary key, so
+    /// the same row is always chosen: the earliest added for `First`, the
+    /// latest added otherwise.
+    pub async fn find_version(
+        pool: &TaxiiPool,
+        collection_id: Uuid,
+        stix_id: &str,
+        selector: VersionSelector,
+    ) -> DatabaseResult<Option<Self>> {
+        let obj = match selector {
+            VersionSelector::First => {
+                sqlx::query_as!(
+                    Self,
+                    r#"SELECT pk as "pk!", id as "id!", collection_id as "collection_id!",
+                              type as "stix_type!", spec_version as "spec_version!",
+                              date_added as "date_added!", version as "version!",
+                              serialized_data as "serialized_data!"
+                       FROM opentaxii_stixobject
+                       WHERE collection_id = $1 AND id = $2 AND deleted_at IS NULL
+                       ORDER BY version ASC, date_added ASC, pk ASC
+                       LIMIT 1"#,
+                    collection_id,
+                    stix_id
+                )
+                .fetch_optional(pool.inner())
+                .await?
+            }
+            VersionSelector::Last => {
+                sqlx::query_as!(
+                    Self,
+                    r#"SELECT pk as "pk!", id as "id!", collection_id as "collection_id!",
+                              type as "stix_type!", spec_version as "spec_version!",
+                              date_added as "date_added!", version as "version!",
+                              serialized_data as "serialized_data!"
+                       FROM opentaxii_stixobject
+                       WHERE collection_id = $1 AND id = $2 AND deleted_at IS NULL
+                       ORDER BY version DESC, date_added DESC, pk DESC
+                       LIMIT 1"#,
+                    collection_id,
+                    stix_id
+                )
+                .fetch_optional(pool.inner())
+                .await?
+            }
+            VersionSelector::Exact(version) => {
+                sqlx::query_as!(
+                    Self,
+                    r#"SELECT pk as "pk!", id as "id!", collection_id as "collection_id!",
+                              type as "stix_type!", spec_version as "spec_version!",
+                              date_added as "date_added!", version as "version!",
+                              serialized_data as "serialized_data!"
+                       FROM opentaxii_stixobject
+                       WHERE collection_id = $1 AND id = $2 AND version = $3
+                         AND deleted_at IS NULL
+                       ORDER BY date_added DESC, pk DESC
+                       LIMIT 1"#,
+                    collection_id,
+                    stix_id,
+                    version.round_subsecs(6).naive_utc()
+                )
+                .fetch_optional(pool.inner())
+                .await?
+            }
+        };
+
+        Ok(obj)
+    }
+
     /// Delete objects with flexible filtering.
     ///
     /// Supports:
@@ -225,17 +471,19 @@ impl STIXObject {
     /// - Delete first version (match_version: "first")
     /// - Delete last version (match_version: "last")
     /// - Delete specific versions by timestamp
-    /// - Filter by spec_version
+    /// - Filter by spec_version (all and specific versions only)
     ///
-    /// Note: For simple cases without spec_version filtering, this delegates to
-    /// the cached sqlx::query! macro methods. For complex filtering, dynamic queries are used.
+    /// Already deleted versions are ignored. With [`DeleteMode::Soft`] the
+    /// rows are kept as tombstones, otherwise they are removed. Returns the
+    /// ID and version of each deleted row.
     pub async fn delete_filtered(
-        pool: &TaxiiPool,
+        conn: &mut PgConnection,
         collection_id: Uuid,
         stix_id: &str,
         match_version: Option<&[String]>,
         match_spec_version: Option<&[String]>,
-    ) -> DatabaseResult<u64> {
+        mode: DeleteMode,
+    ) -> DatabaseResult<Vec<(String, NaiveDateTime)>> {
         let default_version = vec!["all".to_string()];
         let effective_version = match_version.unwrap_or(&default_version);
 
@@ -244,267 +492,216 @@ impl STIXObject {
         let has_last = effective_version.iter().any(|v| v == "last");
 
         // Collect specific datetime versions
-        let specific_versions: Vec<&str> = effective_version
+        let specific_versions: Vec<String> = effective_version
             .iter()
             .filter(|v| *v != "all" && *v != "first" && *v != "last")
-            .map(|s| s.as_str())
+            .cloned()
             .collect();
 
-        if has_all {
-            if match_spec_version.is_none() {
-                // Simple case: use cached sqlx::query! macro
-                return Self::delete_all_versions(pool, collection_id, stix_id).await;
+        let live = "collection_id = $1 AND id = $2 AND deleted_at IS NULL";
+        let mut param_idx = 3;
+        let mut bind_versions = false;
+        let mut bind_spec_versions = false;
+
+        let condition = if has_all {
+            let mut condition = live.to_string();
+            if match_spec_version.is_some() {
+                condition.push_str(&format!(" AND spec_version = ANY(${param_idx})"));
+                param_idx += 1;
+                bind_spec_versions = true;
             }
-            // Complex case: delete all with spec_version filter (dynamic query)
-            let result = sqlx::query(
-                "DELETE FROM opentaxii_stixobject WHERE collection_id = $1 AND id = $2 AND spec_version = ANY($3)",
-            )
-            .bind(collection_id)
-            .bind(stix_id)
-            .bind(match_spec_version)
-            .execute(pool.inner())
-            .await?;
-            Ok(result.rows_affected())
-        } else if has_first {
-            // Use cached sqlx::query! macro
+            condition
+        } else if has_first || has_last {
             // Note: spec_version filter not supported for first/last - matches TAXII spec
-            Self::delete_first_version(pool, collection_id, stix_id).await
-        } else if has_last {
-            // Use cached sqlx::query! macro
-            Self::delete_last_version(pool, collection_id, stix_id).await
+            let direction = if has_first { "ASC" } else { "DESC" };
+            format!(
+                "pk IN (SELECT pk FROM opentaxii_stixobject WHERE {live} \
+                 ORDER BY version {direction}, date_added {direction}, pk {direction} LIMIT 1)"
+            )
         } else if !specific_versions.is_empty() {
-            // Delete specific versions by timestamp (dynamic query)
-            let version_strings: Vec<String> =
-                specific_versions.iter().map(|s| s.to_string()).collect();
-
-            if let Some(spec_versions) = match_spec_version {
-                let result = sqlx::query(
-                    "DELETE FROM opentaxii_stixobject WHERE collection_id = $1 AND id = $2 AND version = ANY($3::timestamptz[]) AND spec_version = ANY($4)",
-                )
-                .bind(collection_id)
-                .bind(stix_id)
-                .bind(&version_strings)
-                .bind(spec_versions)
-                .execute(pool.inner())
-                .await?;
-                Ok(result.rows_affected())
-            } else {
-                let result = sqlx::query(
-                    "DELETE FROM opentaxii_stixobject WHERE collection_id = $1 AND id = $2 AND version = ANY($3::timestamptz[])",
-                )
-                .bind(collection_id)
-                .bind(stix_id)
-                .bind(&version_strings)
-                .execute(pool.inner())
-                .await?;
-                Ok(result.rows_affected())
+            let mut condition = format!("{live} AND version = ANY(${param_idx}::timestamptz[])");
+            param_idx += 1;
+            bind_versions = true;
+            if match_spec_version.is_some() {
+                condition.push_str(&format!(" AND spec_version = ANY(${param_idx})"));
+                param_idx += 1;
+                bind_spec_versions = true;
             }
+            condition
         } else {
             // No matching criteria - nothing to delete
-            Ok(0)
-        }
-    }
-
-    /// Find STIX objects with filtering and pagination.
-    ///
-    /// Supports filtering by ID, type, version, spec_version, and pagination
-    /// with cursor-based next parameter.
-    pub async fn find_filtered(
-        pool: &TaxiiPool,
-        collection_id: Uuid,
-        params: &Taxii2QueryParams<'_>,
-    ) -> DatabaseResult<FilteredResult> {
-        let Taxii2QueryParams {
-            limit,
-            added_after,
-            next: next_kwargs,
-            match_id,
-            match_type,
-            match_version,
-            match_spec_version,
-        } = params;
-
-        // Build base query
-        let mut query = String::from(
-            r#"SELECT pk, id, collection_id, type, spec_version, date_added, version, serialized_data
-               FROM opentaxii_stixobject
-               WHERE collection_id = $1"#,
-        );
+            return Ok(Vec::new());
+        };
 
-        let mut param_idx = 2;
+        let query = match mode {
+            DeleteMode::Soft { .. } => format!(
+                "UPDATE opentaxii_stixobject SET deleted_at = NOW(), deleted_by = ${param_idx} \
+                 WHERE {condition} RETURNING id, version"
+            ),
+            DeleteMode::Hard => {
+                format!("DELETE FROM opentaxii_stixobject WHERE {condition} RETURNING id, version")
+            }
+        };
 
-        if added_after.is_some() {
-            query.push_str(&format!(" AND date_added > ${param_idx}"));
-            param_idx += 1;
+        let mut q = sqlx::query_as::<_, (String, NaiveDateTime)>(&query)
+            .bind(collection_id)
+            .bind(stix_id);
+        if bind_versions {
+            q = q.bind(&specific_versions);
         }
-
-        if next_kwargs.is_some() {
-            query.push_str(&format!(
-                " AND (date_added > ${} OR (date_added = ${} AND id > ${}))",
-                param_idx,
-                param_idx,
-                param_idx + 1
-            ));
-            param_idx += 2;
+        if bind_spec_versions {
+            q = q.bind(match_spec_version);
         }
-
-        if match_id.is_some() {
-            query.push_str(&format!(" AND id = ANY(${param_idx})"));
-            param_idx += 1;
+        if let DeleteMode::Soft { deleted_by } = mode {
+            q = q.bind(deleted_by);
         }
 
-        if match_type.is_some() {
-            query.push_str(&format!(" AND type = ANY(${param_idx})"));
-            param_idx += 1;
-        }
+        Ok(q.fetch_all(conn).await?)
+    }
 
-        if match_spec_version.is_some() {
-            query.push_str(&format!(" AND spec_version = ANY(${param_idx})"));
-            param_idx += 1;
-        }
+    /// Find versions deleted from a collection after `since`.
+    ///
+    /// Returns tombstones ordered by deletion time.
+    pub async fn find_deletions(
+        pool: &TaxiiPool,
+        collection_id: Uuid,
+        since: DateTime<Utc>,
+    ) -> DatabaseResult<Vec<DeletedVersion>> {
+        let deletions = sqlx::query_as!(
+            DeletedVersion,
+            r#"SELECT id as "id!", version as "version!", deleted_at as "deleted_at!", deleted_by
+               FROM opentaxii_stixobject
+               WHERE collection_id = $1 AND deleted_at IS NOT NULL AND deleted_at > $2
+               ORDER BY deleted_at, id, version"#,
+            collection_id,
+            since
+        )
+        .fetch_all(pool.inner())
+        .await?;
 
-        // Handle match_version - default to "last"
-        let default_version = vec!["last".to_string()];
-        let effective_version = match_version.unwrap_or(&default_version);
+        Ok(deletions)
+    }
 
-        let has_all = effective_version.iter().any(|v| v == "all");
-        let has_first = effective_version.iter().any(|v| v == "first");
-        let has_last = effective_version.iter().any(|v| v == "last");
+    /// Remove tombstones of versions deleted before `older_than`.
+    ///
+    /// Returns the number of removed rows.
+    pub async fn purge_deleted(pool: &TaxiiPool, older_than: DateTime<Utc>) -> DatabaseResult<u64> {
+        let result = sqlx::query!(
+            "DELETE FROM opentaxii_stixobject WHERE deleted_at IS NOT NULL AND deleted_at < $1",
+            older_than
+        )
+        .execute(pool.inner())
+        .await?;
 
-        // Collect specific datetime versions
-        let specific_versions: Vec<&str> = effective_version
-            .iter()
-            .filter(|v| *v != "all" && *v != "first" && *v != "last")
-            .map(|s| s.as_str())
-            .collect();
+        Ok(result.rows_affected())
+    }
 
-        if !has_all {
-            if has_first {
-                // Get first version using DISTINCT ON with ASC ordering
-                query = r#"SELECT DISTINCT ON (id) pk, id, collection_id, type, spec_version, date_added, version, serialized_data
-                       FROM opentaxii_stixobject
-                       WHERE collection_id = $1"#
-                    .to_string();
-                param_idx = 2;
-                if added_after.is_some() {
-                    query.push_str(&format!(" AND date_added > ${param_idx}"));
-                    param_idx += 1;
-                }
-                if next_kwargs.is_some() {
-                    query.push_str(&format!(
-                        " AND (date_added > ${} OR (date_added = ${} AND id > ${}))",
-                        param_idx,
-                        param_idx,
-                        param_idx + 1
-                    ));
-                    param_idx += 2;
-                }
-                if match_id.is_some() {
-                    query.push_str(&format!(" AND id = ANY(${param_idx})"));
-                    param_idx += 1;
-                }
-                if match_type.is_some() {
-                    query.push_str(&format!(" AND type = ANY(${param_idx})"));
-                    param_idx += 1;
-                }
-                if match_spec_version.is_some() {
-                    query.push_str(&format!(" AND spec_version = ANY(${param_idx})"));
-                }
-                query.push_str(" ORDER BY id, version ASC");
-            } else if has_last {
-                // Get last version using DISTINCT ON with DESC ordering
-                query = r#"SELECT DISTINCT ON (id) pk, id, collection_id, type, spec_version, date_added, version, serialized_data
-                       FROM opentaxii_stixobject
-                       WHERE collection_id = $1"#
-                    .to_string();
-                param_idx = 2;
-                if added_after.is_some() {
-                    query.push_str(&format!(" AND date_added > ${param_idx}"));
-                    param_idx += 1;
-                }
-                if next_kwargs.is_some() {
-                    query.push_str(&format!(
-                        " AND (date_added > ${} OR (date_added = ${} AND id > ${}))",
-                        param_idx,
-                        param_idx,
-                        param_idx + 1
-                    ));
-                    param_idx += 2;
-                }
-                if match_id.is_some() {
-                    query.push_str(&format!(" AND id = ANY(${param_idx})"));
-                    param_idx += 1;
-                }
-                if match_type.is_some() {
-                    query.push_str(&format!(" AND type = ANY(${param_idx})"));
-                    param_idx += 1;
-                }
-                if match_spec_version.is_some() {
-                    query.push_str(&format!(" AND spec_version = ANY(${param_idx})"));
-                }
-                query.push_str(" ORDER BY id, version DESC");
-            } else if !specific_versions.is_empty() {
-                // Filter by specific version timestamps
-                query.push_str(&format!(" AND version = ANY(${param_idx}::timestamptz[])"));
+    /// Delete one batch of versions added to a collection before `cutoff`.
+    ///
+    /// Returns the number of deleted rows; fewer than `batch_size` means
+    /// nothing is left to purge. With [`DeleteMode::Hard`] tombstones of
+    /// expired versions are removed as well.
+    pub async fn purge_added_before(
+        pool: &TaxiiPool,
+        collection_id: Uuid,
+        cutoff: DateTime<Utc>,
+        mode: DeleteMode,
+        batch_size: i64,
+    ) -> DatabaseResult<u64> {
+        let result = match mode {
+            DeleteMode::Soft { deleted_by } => {
+                sqlx::query!(
+                    r#"UPDATE opentaxii_stixobject SET deleted_at = NOW(), deleted_by = $4
+                       WHERE pk IN (
+                           SELECT pk FROM opentaxii_stixobject
+                           WHERE collection_id = $1 AND date_added < $2 AND deleted_at IS NULL
+                           LIMIT $3
+                       )"#,
+                    collection_id,
+                    cutoff.naive_utc(),
+                    batch_size,
+                    deleted_by
+                )
+                .execute(pool.inner())
+                .await?
             }
-        }
-
-        // Wrap DISTINCT ON query for final ordering
-        if has_first || has_last {
-            query = format!("SELECT * FROM ({query}) AS subq ORDER BY date_added, id");
-        } else {
-            query.push_str(" ORDER BY date_added, id");
-        }
-
-        // Apply limit + 1 for efficient "more" detection
-        let fetch_limit = limit.map(|lim| lim + 1);
-        if let Some(lim) = fetch_limit {
-            query.push_str(&format!(" LIMIT {lim}"));
-        }
-
-        // Bind parameters
-        let mut q = sqlx::query_as::<_, Self>(&query);
-        q = q.bind(collection_id);
-
-        if let Some(aa) = added_after {
-            q = q.bind(aa);
-        }
+            DeleteMode::Hard => {
+                sqlx::query!(
+                    r#"DELETE FROM opentaxii_stixobject
+                       WHERE pk IN (
+                           SELECT pk FROM opentaxii_stixobject
+                           WHERE collection_id = $1 AND date_added < $2
+                           LIMIT $3
+                       )"#,
+                    collection_id,
+                    cutoff.naive_utc(),
+                    batch_size
+                )
+                .execute(pool.inner())
+                .await?
+            }
+        };
 
-        if let Some(cursor) = next_kwargs {
-            q = q.bind(cursor.date_added);
-            q = q.bind(&cursor.object_id);
-        }
+        Ok(result.rows_affected())
+    }
 
-        if let Some(ids) = match_id {
-            q = q.bind(ids);
-        }
+    /// Get statistics of the objects of a collection by type.
+    ///
+    /// Deleted versions are not counted. Served by an index-only scan of
+    /// `ix_opentaxii_stixobject_collection_stats`.
+    pub async fn type_stats(
+        pool: &TaxiiPool,
+        collection_id: Uuid,
+    ) -> DatabaseResult<Vec<TypeStats>> {
+        let stats = sqlx::query_as!(
+            TypeStats,
+            r#"SELECT type as "object_type!", COUNT(*) as "object_count!",
+                      COUNT(DISTINCT id) as "distinct_ids!",
+                      MAX(date_added) AT TIME ZONE 'UTC' as latest_added
+               FROM opentaxii_stixobject
+               WHERE collection_id = $1 AND deleted_at IS NULL
+               GROUP BY type
+               ORDER BY type"#,
+            collection_id
+        )
+        .fetch_all(pool.inner())
+        .await?;
 
-        if let Some(types) = match_type {
-            q = q.bind(types);
-        }
+        Ok(stats)
+    }
 
-        if let Some(versions) = match_spec_version {
-            q = q.bind(versions);
-        }
+    /// Find STIX objects with filtering and pagination.
+    ///
+    /// Supports filtering by ID, type, version, spec_version and object
+    /// properties, and pagination with cursor-based next parameter.
+    pub async fn find_filtered(
+        pool: &TaxiiPool,
+        collection_id: Uuid,
+        params: &Taxii2QueryParams<'_>,
+    ) -> DatabaseResult<FilteredResult> {
+        let limit = params.limit;
 
-        // Bind specific version timestamps if provided
-        if !specific_versions.is_empty() {
-            let version_strings: Vec<String> =
-                specific_versions.iter().map(|s| s.to_string()).collect();
-            q = q.bind(version_strings);
-        }
+        // Apply limit + 1 for efficient "more" detection
+        let (query, specific_versions) = filtered_query(params, limit.map(|lim| lim + 1), false);
+        let q = bind_filtered(
+            sqlx::query_as::<_, Self>(&query),
+            collection_id,
+            None,
+            params,
+            specific_versions,
+        );
 
         let mut items: Vec<Self> = q.fetch_all(pool.inner()).await?;
 
         // Determine if more results
-        let more = if let Some(lim) = *limit {
+        let more = if let Some(lim) = limit {
             items.len() as i64 > lim
         } else {
             false
         };
 
         // Truncate to actual limit
-        if let Some(lim) = *limit {
+        if let Some(lim) = limit {
             items.truncate(lim as usize);
         }
 
@@ -524,8 +721,112 @@ impl STIXObject {
         })
     }
 
+    /// Find STIX objects matching a full-text search, with filtering and
+    /// pagination.
+    ///
+    /// The query uses web search syntax (`"quoted phrases"`, `or`, `-word`)
+    /// over the name, value, labels, description and pattern of objects.
+    /// Results are ordered by relevance (name and value matches first, then
+    /// label matches, then the rest) and within that by date added.
+    pub async fn search(
+        pool: &TaxiiPool,
+        collection_id: Uuid,
+        search: &str,
+        params: &Taxii2QueryParams<'_>,
+    ) -> DatabaseResult<FilteredResult> {
+        let limit = params.limit;
+
+        let (query, specific_versions) = filtered_query(params, limit.map(|lim| lim + 1), true);
+        let q = bind_filtered(
+            sqlx::query_as::<_, SearchHit>(&query),
+            collection_id,
+            Some(search),
+            params,
+            specific_versions,
+        );
+
+        let mut hits: Vec<SearchHit> = q.fetch_all(pool.inner()).await?;
+
+        let more = limit.is_some_and(|lim| hits.len() as i64 > lim);
+        if let Some(lim) = limit {
+            hits.truncate(lim as usize);
+        }
+
+        let next = if more {
+            hits.last().map(|last| {
+                get_search_next_param(last.search_rank, &last.object.date_added, &last.object.id)
+            })
+        } else {
+            None
+        };
+
+        Ok(FilteredResult {
+            objects: hits.into_iter().map(|hit| hit.object).collect(),
+            more,
+            next,
+        })
+    }
+
+    /// Stream STIX objects with filtering.
+    ///
+    /// Uses the same filters and ordering as [`STIXObject::find_filtered`],
+    /// without a "more" lookahead row. Rows are yielded as the database
+    /// returns them.
+    pub fn stream_filtered(
+        pool: &TaxiiPool,
+        collection_id: Uuid,
+        params: &Taxii2QueryParams<'_>,
+    ) -> RowStream<Self> {
+        let pool = pool.clone();
+        let Taxii2QueryParams {
+            limit,
+            added_after,
+            next,
+            match_id,
+            match_type,
+            match_version,
+            match_spec_version,
+            match_properties,
+            include_deleted,
+        } = params;
+        let (limit, added_after, include_deleted) = (*limit, *added_after, *include_deleted);
+        let next = next.cloned();
+        let match_id = match_id.map(<[String]>::to_vec);
+        let match_type = match_type.map(<[String]>::to_vec);
+        let match_version = match_version.map(<[String]>::to_vec);
+        let match_spec_version = match_spec_version.map(<[String]>::to_vec);
+        let match_properties = match_properties.map(<[PropertyFilter]>::to_vec);
+
+        spawn_stream(move |tx| async move {
+            let params = Taxii2QueryParams {
+                limit,
+                added_after,
+                next: next.as_ref(),
+                match_id: match_id.as_deref(),
+                match_type: match_type.as_deref(),
+                match_version: match_version.as_deref(),
+                match_spec_version: match_spec_version.as_deref(),
+                match_properties: match_properties.as_deref(),
+                include_deleted,
+            };
+            let (query, specific_versions) = filtered_query(&params, limit, false);
+            let q = bind_filtered(
+                sqlx::query_as::<_, Self>(&query),
+                collection_id,
+                None,
+                &params,
+                specific_versions,
+            );
+            forward_rows(q.fetch(pool.inner()), tx).await;
+        })
+    }
+
     /// Get versions of a specific object.
     ///
+    /// Versions are listed from first to last, in the order of
+    /// [`PaginationCursor::VERSION_ORDER_BY`], and paged by versions
+    /// cursors (see [`get_versions_next_param`]).
+    ///
     /// Returns None for versions if the object doesn't exist in the collection.
     pub async fn find_versions(
         pool: &TaxiiPool,
@@ -548,9 +849,9 @@ impl STIXObject {
         }
 
         let mut query = String::from(
-            r#"SELECT date_added, version, id
+            r#"SELECT pk, date_added, version
                FROM opentaxii_stixobject
-               WHERE collection_id = $1 AND id = $2"#,
+               WHERE collection_id = $1 AND id = $2 AND deleted_at IS NULL"#,
         );
 
         let mut param_idx = 3;
@@ -561,20 +862,15 @@ impl STIXObject {
         }
 
         if next_kwargs.is_some() {
-            query.push_str(&format!(
-                " AND (date_added > ${} OR (date_added = ${} AND id > ${}))",
-                param_idx,
-                param_idx,
-                param_idx + 1
-            ));
-            param_idx += 2;
+            query.push_str(&PaginationCursor::versions_sql_condition(param_idx));
+            param_idx += 3;
         }
 
         if match_spec_version.is_some() {
             query.push_str(&format!(" AND spec_version = ANY(${param_idx})"));
         }
 
-        query.push_str(" ORDER BY date_added, id");
+        query.push_str(&format!(" ORDER BY {}", PaginationCursor::VERSION_ORDER_BY));
 
         // Apply limit + 1 for efficient "more" detection
         let fetch_limit = limit.map(|lim| lim + 1);
@@ -591,7 +887,8 @@ impl STIXObject {
         }
 
         if let Some(cursor) = next_kwargs {
-            q = q.bind(cursor.date_added);
+            q = q.bind(cursor.bind_version());
+            q = q.bind(cursor.bind_date_added());
             q = q.bind(&cursor.object_id);
         }
 
@@ -619,9 +916,10 @@ impl STIXObject {
         let next = if more {
             rows.last().map(|row| {
                 use sqlx::Row;
+                let version: NaiveDateTime = row.get("version");
                 let date_added: NaiveDateTime = row.get("date_added");
-                let id: String = row.get("id");
-                get_next_param(&date_added, &id)
+                let pk: Uuid = row.get("pk");
+                get_versions_next_param(&version, &date_added, &pk)
             })
         } else {
             None
@@ -645,3 +943,215 @@ impl STIXObject {
         })
     }
 }
+
+/// Build the SQL of a filtered STIX object query.
+///
+/// With `search`, only objects matching the search query are selected,
+/// with their `search_rank`, in order of relevance.
+///
+/// Returns the query and the specific version timestamps it filters on,
+/// which [`bind_filtered`] binds last.
+fn filtered_query(
+    params: &Taxii2QueryParams<'_>,
+    limit: Option<i64>,
+    search: bool,
+) -> (String, Vec<String>) {
+    let Taxii2QueryParams {
+        limit: _,
+        added_after,
+        next: next_kwargs,
+        match_id,
+        match_type,
+        match_version,
+        match_spec_version,
+        match_properties,
+        include_deleted,
+    } = params;
+
+    // Build base query
+    let mut query = String::from(
+        "SELECT pk, id, collection_id, type, spec_version, date_added, version, serialized_data",
+    );
+    if search {
+        query.push_str(&format!(", {SEARCH_RANK} AS search_rank"));
+    }
+    query.push_str(
+        r#"
+           FROM opentaxii_stixobject
+           WHERE collection_id = $1"#,
+    );
+
+    // Filters deciding which versions of an object are considered when
+    // picking its first or last version (see `other_version_condition`)
+    let mut version_filters = String::new();
+    if !include_deleted {
+        query.push_str(" AND deleted_at IS NULL");
+        version_filters.push_str(" AND other.deleted_at IS NULL");
+    }
+
+    let mut param_idx = 2;
+
+    if search {
+        query.push_str(SEARCH_CONDITION);
+        param_idx += 1;
+    }
+
+    if added_after.is_some() {
+        query.push_str(&format!(" AND date_added > ${param_idx}"));
+        version_filters.push_str(&format!(" AND other.date_added > ${param_idx}"));
+        param_idx += 1;
+    }
+
+    if next_kwargs.is_some() {
+        if search {
+            query.push_str(&PaginationCursor::search_sql_condition(
+                SEARCH_RANK,
+                param_idx,
+            ));
+            param_idx += 3;
+        } else {
+            query.push_str(&PaginationCursor::sql_condition(param_idx));
+            param_idx += 2;
+        }
+    }
+
+    if match_id.is_some() {
+        query.push_str(&format!(" AND id = ANY(${param_idx})"));
+        param_idx += 1;
+    }
+
+    if match_type.is_some() {
+        query.push_str(&format!(" AND type = ANY(${param_idx})"));
+        param_idx += 1;
+    }
+
+    if match_spec_version.is_some() {
+        query.push_str(&format!(" AND spec_version = ANY(${param_idx})"));
+        version_filters.push_str(&format!(" AND other.spec_version = ANY(${param_idx})"));
+        param_idx += 1;
+    }
+
+    // Property filters apply to the selected versions only: by default an
+    // object matches if its last version does
+    for filter in match_properties.unwrap_or_default() {
+        query.push_str(&filter.sql_condition(param_idx));
+        param_idx += 1;
+    }
+
+    // Handle match_version - default to "last"
+    let default_version = vec!["last".to_string()];
+    let effective_version = match_version.unwrap_or(&default_version);
+
+    let has_all = effective_version.iter().any(|v| v == "all");
+    let has_first = effective_version.iter().any(|v| v == "first");
+    let has_last = effective_version.iter().any(|v| v == "last");
+
+    // Collect specific datetime versions
+    let specific_versions: Vec<&str> = effective_version
+        .iter()
+        .filter(|v| *v != "all" && *v != "first" && *v != "last")
+        .map(|s| s.as_str())
+        .collect();
+
+    let mut bound_versions = Vec::new();
+    if !has_all {
+        if has_first {
+            query.push_str(&other_version_condition("<", &version_filters));
+        } else if has_last {
+            query.push_str(&other_version_condition(">", &version_filters));
+        } else if !specific_versions.is_empty() {
+            // Filter by specific version timestamps
+            query.push_str(&format!(" AND version = ANY(${param_idx}::timestamptz[])"));
+            bound_versions = specific_versions.iter().map(|s| s.to_string()).collect();
+        }
+    }
+
+    if search {
+        query.push_str(&format!(
+            " ORDER BY {}",
+            PaginationCursor::search_order_by(SEARCH_RANK)
+        ));
+    } else {
+        query.push_str(&format!(" ORDER BY {}", PaginationCursor::ORDER_BY));
+    }
+
+    if let Some(lim) = limit {
+        query.push_str(&format!(" LIMIT {lim}"));
+    }
+
+    (query, bound_versions)
+}
+
+/// Bind the parameters of a query built by [`filtered_query`].
+///
+/// `search` must be given exactly if the query was built for a search.
+fn bind_filtered<'q, O>(
+    mut q: QueryAs<'q, Postgres, O, PgArguments>,
+    collection_id: Uuid,
+    search: Option<&'q str>,
+    params: &Taxii2QueryParams<'q>,
+    specific_versions: Vec<String>,
+) -> QueryAs<'q, Postgres, O, PgArguments> {
+    q = q.bind(collection_id);
+
+    if let Some(search) = search {
+        q = q.bind(search);
+    }
+
+    if let Some(aa) = params.added_after {
+        q = q.bind(aa);
+    }
+
+    if let Some(cursor) = params.next {
+        if search.is_some() {
+            q = q.bind(cursor.bind_rank());
+        }
+        q = q.bind(cursor.bind_date_added());
+        q = q.bind(&cursor.object_id);
+    }
+
+    if let Some(ids) = params.match_id {
+        q = q.bind(ids);
+    }
+
+    if let Some(types) = params.match_type {
+        q = q.bind(types);
+    }
+
+    if let Some(versions) = params.match_spec_version {
+        q = q.bind(versions);
+    }
+
+    for filter in params.match_properties.unwrap_or_default() {
+        q = q.bind(filter.bind_values());
+    }
+
+    // Bind specific version timestamps if provided
+    if !specific_versions.is_empty() {
+        q = q.bind(specific_versions);
+    }
+
+    q
+}
+
+/// SQL condition keeping a row only if no other matching version of the
+/// same object compares `cmp` to it.
+///
+/// With `>` this keeps the last version of each object, with `<` the first.
+/// Versions are compared in [`PaginationCursor::VERSION_ORDER_BY`] order, so
+/// exactly one row is kept even if several share a version. The anti-join
+/// is checked per row against the `(collection_id, id, version, date_added,
+/// pk)` index, so pages are read in `(date_added, id)` order
+/// straight from the collection index: a page costs the same at any depth,
+/// and the cursor only limits which rows are returned, never which version
+/// counts as first or last.
+fn other_version_condition(cmp: &str, version_filters: &str) -> String {
+    format!(
+        " AND NOT EXISTS (SELECT 1 FROM opentaxii_stixobject other \
+         WHERE other.collection_id = opentaxii_stixobject.collection_id \
+         AND other.id = opentaxii_stixobject.id \
+         AND (other.version, other.date_added, other.pk) {cmp} \
+         (opentaxii_stixobject.version, opentaxii_stixobject.date_added, \
+         opentaxii_stixobject.pk){version_filters})"
+    )
+}
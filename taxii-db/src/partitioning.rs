@@ -0,0 +1,417 @@
+//! Optional partitioning of the STIX object table by collection.
+//!
+//! With partitioning, `opentaxii_stixobject` is a PostgreSQL (11+) table
+//! partitioned by `LIST (collection_id)`, with one partition per collection
+//! and a default partition for objects of collections created without one.
+//! Queries stay unchanged: they all filter by collection, so the planner
+//! only scans the collection's partition. Deleting a collection drops its
+//! partition instead of deleting its objects row by row.
+//!
+//! Converting the table is an offline operation: [`partition_table`] and
+//! [`unpartition_table`] copy every object into a new table in one
+//! transaction that holds an exclusive lock on the table, so servers should
+//! be stopped while they run.
+
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::error::{DatabaseError, DatabaseResult};
+use crate::pool::TaxiiPool;
+
+/// The STIX object table.
+const TABLE: &str = "opentaxii_stixobject";
+
+/// Name the table is moved to while it is converted.
+const OLD_TABLE: &str = "opentaxii_stixobject_unconverted";
+
+/// Partition for objects of collections without a partition of their own.
+pub const DEFAULT_PARTITION: &str = "opentaxii_stixobject_default";
+
+/// First PostgreSQL version (`server_version_num`) supporting partitioned
+/// tables with primary keys, foreign keys and `ON CONFLICT`.
+const MIN_SERVER_VERSION: i32 = 110000;
+
+/// Result of converting the STIX object table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConversionReport {
+    /// Objects copied into the converted table.
+    pub rows: u64,
+    /// Collection partitions created (besides the default partition).
+    pub partitions: u64,
+}
+
+/// Name of the partition of a collection.
+pub fn partition_name(collection_id: Uuid) -> String {
+    format!("{TABLE}_{}", collection_id.simple())
+}
+
+/// Check whether the STIX object table is partitioned.
+pub async fn is_partitioned(pool: &TaxiiPool) -> DatabaseResult<bool> {
+    let mut conn = pool.acquire().await?;
+    is_partitioned_in(&mut conn).await
+}
+
+/// Check whether the STIX object table is partitioned, on a connection.
+pub async fn is_partitioned_in(conn: &mut PgConnection) -> DatabaseResult<bool> {
+    // relkind 'p' does not exist before PostgreSQL 10, which simply never
+    // reports a partitioned table
+    let partitioned: bool = sqlx::query_scalar(
+        "SELECT COALESCE(
+             (SELECT relkind = 'p' FROM pg_class WHERE oid = to_regclass($1)),
+             FALSE)",
+    )
+    .bind(TABLE)
+    .fetch_one(conn)
+    .await?;
+
+    Ok(partitioned)
+}
+
+/// Create the partition of a collection.
+///
+/// Does nothing if the partition already exists.
+pub async fn create_partition(conn: &mut PgConnection, collection_id: Uuid) -> DatabaseResult<()> {
+    // Both the name and the value derive from a UUID, so they need no quoting
+    sqlx::query(&format!(
+        "CREATE TABLE IF NOT EXISTS {} PARTITION OF {TABLE} FOR VALUES IN ('{collection_id}')",
+        partition_name(collection_id)
+    ))
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Drop the partition of a collection, with all objects in it.
+///
+/// Returns `false` if the collection has no partition.
+pub async fn drop_partition(conn: &mut PgConnection, collection_id: Uuid) -> DatabaseResult<bool> {
+    let name = partition_name(collection_id);
+
+    let exists: bool = sqlx::query_scalar("SELECT to_regclass($1) IS NOT NULL")
+        .bind(&name)
+        .fetch_one(&mut *conn)
+        .await?;
+    if !exists {
+        return Ok(false);
+    }
+
+    sqlx::query(&format!("DROP TABLE {name}"))
+        .execute(conn)
+        .await?;
+
+    Ok(true)
+}
+
+/// Convert the STIX object table to a table partitioned by collection.
+///
+/// Creates a partition for every existing collection and the default
+/// partition. Fails if the table is already partitioned, the server is older
+/// than PostgreSQL 11 or objects without a collection exist.
+pub async fn partition_table(pool: &TaxiiPool) -> DatabaseResult<ConversionReport> {
+    rebuild(pool, true).await
+}
+
+/// Convert a partitioned STIX object table back to a plain table.
+///
+/// Fails if the table is not partitioned.
+pub async fn unpartition_table(pool: &TaxiiPool) -> DatabaseResult<ConversionReport> {
+    rebuild(pool, false).await
+}
+
+/// Copy the STIX object table into a new (partitioned or plain) table with
+/// the same columns, constraints and indexes.
+async fn rebuild(pool: &TaxiiPool, partition: bool) -> DatabaseResult<ConversionReport> {
+    let mut tx = pool.begin().await?;
+    let conn = tx.conn();
+
+    let server_version: String = sqlx::query_scalar("SELECT current_setting('server_version_num')")
+        .fetch_one(&mut *conn)
+        .await?;
+    if server_version.parse::<i32>().unwrap_or(0) < MIN_SERVER_VERSION {
+        return Err(DatabaseError::invalid_data(
+            "partitioning the STIX object table requires PostgreSQL 11 or later",
+        ));
+    }
+
+    sqlx::query(&format!("LOCK TABLE {TABLE} IN ACCESS EXCLUSIVE MODE"))
+        .execute(&mut *conn)
+        .await?;
+
+    if is_partitioned_in(conn).await? == partition {
+        return Err(DatabaseError::invalid_data(if partition {
+            "the STIX object table is already partitioned"
+        } else {
+            "the STIX object table is not partitioned"
+        }));
+    }
+
+    if partition {
+        let orphaned: i64 = sqlx::query_scalar(&format!(
+            "SELECT COUNT(*) FROM {TABLE} WHERE collection_id IS NULL"
+        ))
+        .fetch_one(&mut *conn)
+        .await?;
+        if orphaned > 0 {
+            return Err(DatabaseError::invalid_data(format!(
+                "{orphaned} STIX objects have no collection; delete them before partitioning"
+            )));
+        }
+    }
+
+    // The primary key is recreated separately: a partitioned table's primary
+    // key must include the partition key
+    let constraints: Vec<(String, String)> = sqlx::query_as(
+        "SELECT conname::text, pg_get_constraintdef(oid)
+         FROM pg_constraint
+         WHERE conrelid = to_regclass($1) AND contype <> 'p'
+         ORDER BY conname",
+    )
+    .bind(TABLE)
+    .fetch_all(&mut *conn)
+    .await?;
+    let indexes: Vec<String> = sqlx::query_scalar(
+        "SELECT pg_get_indexdef(i.indexrelid)
+         FROM pg_index i
+         WHERE i.indrelid = to_regclass($1)
+           AND NOT EXISTS (
+               SELECT 1 FROM pg_constraint c
+               WHERE c.conindid = i.indexrelid AND c.conrelid = i.indrelid)
+         ORDER BY i.indexrelid",
+    )
+    .bind(TABLE)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    sqlx::query(&format!("ALTER TABLE {TABLE} RENAME TO {OLD_TABLE}"))
+        .execute(&mut *conn)
+        .await?;
+
+    let mut partitions = 0;
+    if partition {
+        sqlx::query(&format!(
+            "CREATE TABLE {TABLE} (LIKE {OLD_TABLE} INCLUDING DEFAULTS)
+             PARTITION BY LIST (collection_id)"
+        ))
+        .execute(&mut *conn)
+        .await?;
+        sqlx::query(&format!(
+            "ALTER TABLE {TABLE} ALTER COLUMN collection_id SET NOT NULL"
+        ))
+        .execute(&mut *conn)
+        .await?;
+        sqlx::query(&format!(
+            "CREATE TABLE {DEFAULT_PARTITION} PARTITION OF {TABLE} DEFAULT"
+        ))
+        .execute(&mut *conn)
+        .await?;
+
+        let collection_ids: Vec<Uuid> =
+            sqlx::query_scalar("SELECT id FROM opentaxii_collection ORDER BY id")
+                .fetch_all(&mut *conn)
+                .await?;
+        for collection_id in collection_ids {
+            create_partition(conn, collection_id).await?;
+            partitions += 1;
+        }
+    } else {
+        sqlx::query(&format!(
+            "CREATE TABLE {TABLE} (LIKE {OLD_TABLE} INCLUDING DEFAULTS)"
+        ))
+        .execute(&mut *conn)
+        .await?;
+        sqlx::query(&format!(
+            "ALTER TABLE {TABLE} ALTER COLUMN collection_id DROP NOT NULL"
+        ))
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    let rows = sqlx::query(&format!("INSERT INTO {TABLE} SELECT * FROM {OLD_TABLE}"))
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+
+    // Dropping the old table frees the constraint and index names
+    sqlx::query(&format!("DROP TABLE {OLD_TABLE}"))
+        .execute(&mut *conn)
+        .await?;
+
+    let primary_key = if partition {
+        "(collection_id, pk)"
+    } else {
+        "(pk)"
+    };
+    sqlx::query(&format!(
+        "ALTER TABLE {TABLE} ADD CONSTRAINT {TABLE}_pkey PRIMARY KEY {primary_key}"
+    ))
+    .execute(&mut *conn)
+    .await?;
+    for (name, definition) in constraints {
+        sqlx::query(&format!(
+            "ALTER TABLE {TABLE} ADD CONSTRAINT \"{name}\" {definition}"
+        ))
+        .execute(&mut *conn)
+        .await?;
+    }
+    for definition in indexes {
+        // Indexes of a partitioned table are defined `ON ONLY` the parent
+        sqlx::query(&definition.replacen(" ON ONLY ", " ON ", 1))
+            .execute(&mut *conn)
+            .await?;
+    }
+    if partition {
+        // Lookups by primary key alone can no longer use the primary key
+        sqlx::query(&format!(
+            "CREATE INDEX IF NOT EXISTS ix_{TABLE}_pk ON {TABLE} (pk)"
+        ))
+        .execute(&mut *conn)
+        .await?;
+    } else {
+        sqlx::query(&format!("DROP INDEX IF EXISTS ix_{TABLE}_pk"))
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    sqlx::query(&format!("ANALYZE {TABLE}"))
+        .execute(&mut *conn)
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(ConversionReport { rows, partitions })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::taxii2::Taxii2QueryParams;
+    use crate::repository::{DbTaxii2Repository, Taxii2Repository};
+    use serde_json::json;
+    use sqlx::PgPool;
+
+    type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+    fn indicator(n: usize) -> serde_json::Value {
+        json!({
+            "id": format!("indicator--00000000-0000-4000-8000-{n:012}"),
+            "type": "indicator",
+            "spec_version": "2.1",
+            "created": "2024-01-01T00:00:00.000Z",
+            "modified": "2024-01-01T00:00:00.000Z",
+            "pattern": "[ipv4-addr:value = '10.0.0.1']",
+            "pattern_type": "stix",
+            "valid_from": "2024-01-01T00:00:00Z"
+        })
+    }
+
+    async fn index_names(pool: &PgPool) -> Result<Vec<String>, sqlx::Error> {
+        sqlx::query_scalar(
+            "SELECT indexname::text FROM pg_indexes WHERE tablename = $1 ORDER BY indexname",
+        )
+        .bind(TABLE)
+        .fetch_all(pool)
+        .await
+    }
+
+    async fn table_exists(pool: &PgPool, name: &str) -> Result<bool, sqlx::Error> {
+        sqlx::query_scalar("SELECT to_regclass($1) IS NOT NULL")
+            .bind(name)
+            .fetch_one(pool)
+            .await
+    }
+
+    /// Converting the table, adding to and deleting a collection created
+    /// afterwards, and converting it back.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_partition_lifecycle(pool: PgPool) -> TestResult {
+        let taxii_pool = TaxiiPool::new(pool.clone());
+        let repo = DbTaxii2Repository::new(taxii_pool.clone());
+        let api_root = repo.add_api_root("Root", None, true, false, None).await?;
+        let existing = repo
+            .add_collection(&api_root.id, "Existing", None, None, false, false, None)
+            .await?;
+        repo.add_objects(&api_root.id, &existing.id, &[indicator(0), indicator(1)])
+            .await?;
+        let indexes = index_names(&pool).await?;
+
+        assert!(!is_partitioned(&taxii_pool).await?);
+        assert!(unpartition_table(&taxii_pool).await.is_err());
+
+        let report = partition_table(&taxii_pool).await?;
+        assert_eq!(
+            report,
+            ConversionReport {
+                rows: 2,
+                partitions: 1
+            }
+        );
+        assert!(is_partitioned(&taxii_pool).await?);
+        assert!(partition_table(&taxii_pool).await.is_err());
+        let existing_uuid = Uuid::parse_str(&existing.id)?;
+        assert!(table_exists(&pool, &partition_name(existing_uuid)).await?);
+        assert!(table_exists(&pool, DEFAULT_PARTITION).await?);
+
+        // New collections get a partition of their own
+        let repo = repo.with_partitioning(true);
+        let created = repo
+            .add_collection(&api_root.id, "Created", None, None, false, false, None)
+            .await?;
+        let created_partition = partition_name(Uuid::parse_str(&created.id)?);
+        assert!(table_exists(&pool, &created_partition).await?);
+
+        // Upserts still resolve conflicts on the partitioned table
+        repo.add_objects(&api_root.id, &created.id, &[indicator(2)])
+            .await?;
+        repo.add_objects(&api_root.id, &created.id, &[indicator(2)])
+            .await?;
+        let params = Taxii2QueryParams::default();
+        let created_objects = repo.get_objects(&created.id, &params).await?;
+        assert_eq!(created_objects.items.len(), 1);
+        let in_partition: i64 =
+            sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {created_partition}"))
+                .fetch_one(&pool)
+                .await?;
+        assert_eq!(in_partition, 1);
+        assert_eq!(
+            repo.get_objects(&existing.id, &params).await?.items.len(),
+            2
+        );
+
+        repo.delete_collection(&created.id).await?;
+        assert!(!table_exists(&pool, &created_partition).await?);
+        assert!(
+            repo.get_collection(&api_root.id, &created.id)
+                .await?
+                .is_none()
+        );
+        assert!(
+            repo.delete_collection(&created.id)
+                .await
+                .is_err_and(|e| e.is_not_found())
+        );
+
+        let report = unpartition_table(&taxii_pool).await?;
+        assert_eq!(
+            report,
+            ConversionReport {
+                rows: 2,
+                partitions: 0
+            }
+        );
+        assert!(!is_partitioned(&taxii_pool).await?);
+        assert_eq!(index_names(&pool).await?, indexes);
+        assert!(!table_exists(&pool, DEFAULT_PARTITION).await?);
+
+        // Without partitioning, deleting a collection deletes its objects
+        let repo = DbTaxii2Repository::new(taxii_pool);
+        repo.delete_collection(&existing.id).await?;
+        let remaining: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {TABLE}"))
+            .fetch_one(&pool)
+            .await?;
+        assert_eq!(remaining, 0);
+
+        Ok(())
+    }
+}
@@ -1,11 +1,23 @@
 //! Database connection pool.
+//!
+//! A [`TaxiiPool`] can hold a second pool for reads, e.g. on a read replica,
+//! so heavy read traffic does not starve writes of connections. Writes and
+//! transactions always use the primary pool; repositories send plain reads
+//! to [`TaxiiPool::reader`].
+//!
+//! [`TaxiiPool::stats`] reports the size of the primary pool and how long
+//! acquiring a connection took recently, for the readiness check.
 
-use std::time::Duration;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use sqlx::PgPool;
-use sqlx::postgres::PgPoolOptions;
+use sqlx::pool::PoolConnection;
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use sqlx::{Executor, PgPool, Postgres};
 
-use crate::error::DatabaseResult;
+use crate::error::{DatabaseError, DatabaseResult};
+use crate::transaction::TaxiiTransaction;
 
 /// Default maximum number of connections in the pool.
 pub const DEFAULT_MAX_CONNECTIONS: u32 = 10;
@@ -16,6 +28,16 @@ pub const DEFAULT_MIN_CONNECTIONS: u32 = 1;
 /// Default connection acquire timeout in seconds.
 pub const DEFAULT_ACQUIRE_TIMEOUT_SECS: u64 = 30;
 
+/// Default idle timeout in seconds, after which idle connections above
+/// `min_connections` are closed.
+pub const DEFAULT_IDLE_TIMEOUT_SECS: u64 = 600;
+
+/// Default `application_name` of the server's database sessions.
+pub const DEFAULT_APPLICATION_NAME: &str = "darwis-taxii";
+
+/// Number of recent acquire waits kept for [`PoolStats::acquire_wait_p99`].
+const ACQUIRE_WAIT_SAMPLES: usize = 1024;
+
 /// Configuration options for the database connection pool.
 #[derive(Debug, Clone)]
 pub struct PoolOptions {
@@ -25,6 +47,21 @@ pub struct PoolOptions {
     pub min_connections: u32,
     /// Timeout for acquiring a connection from the pool.
     pub acquire_timeout: Duration,
+    /// Time after which idle connections above `min_connections` are
+    /// closed (`None` keeps them).
+    pub idle_timeout: Option<Duration>,
+    /// Maximum run time of a statement, set as the session's
+    /// `statement_timeout` (`None` uses the server's setting).
+    pub statement_timeout: Option<Duration>,
+    /// `application_name` of the sessions, to identify them in
+    /// `pg_stat_activity`.
+    pub application_name: String,
+    /// Connection string for a separate read pool (e.g. a read replica).
+    /// Without it, reads share the primary pool.
+    pub read_url: Option<String>,
+    /// Maximum number of connections in the read pool (defaults to
+    /// `max_connections`).
+    pub read_max_connections: Option<u32>,
 }
 
 impl Default for PoolOptions {
@@ -33,6 +70,11 @@ impl Default for PoolOptions {
             max_connections: DEFAULT_MAX_CONNECTIONS,
             min_connections: DEFAULT_MIN_CONNECTIONS,
             acquire_timeout: Duration::from_secs(DEFAULT_ACQUIRE_TIMEOUT_SECS),
+            idle_timeout: Some(Duration::from_secs(DEFAULT_IDLE_TIMEOUT_SECS)),
+            statement_timeout: None,
+            application_name: DEFAULT_APPLICATION_NAME.to_string(),
+            read_url: None,
+            read_max_connections: None,
         }
     }
 }
@@ -46,19 +88,150 @@ impl PoolOptions {
             ..Default::default()
         }
     }
+
+    /// Check that the options are usable.
+    pub fn validate(&self) -> DatabaseResult<()> {
+        if self.max_connections == 0 {
+            return Err(DatabaseError::invalid_data(
+                "max_connections must be at least 1",
+            ));
+        }
+        if self.min_connections > self.max_connections {
+            return Err(DatabaseError::invalid_data(format!(
+                "min_connections ({}) must not exceed max_connections ({})",
+                self.min_connections, self.max_connections
+            )));
+        }
+        if self.acquire_timeout.is_zero() {
+            return Err(DatabaseError::invalid_data(
+                "acquire_timeout must be greater than zero",
+            ));
+        }
+        if self.idle_timeout.is_some_and(|t| t.is_zero()) {
+            return Err(DatabaseError::invalid_data(
+                "idle_timeout must be greater than zero",
+            ));
+        }
+        // PostgreSQL takes milliseconds; 0 would disable the timeout
+        if self
+            .statement_timeout
+            .is_some_and(|t| t.as_millis() == 0 || t.as_millis() > i32::MAX as u128)
+        {
+            return Err(DatabaseError::invalid_data(
+                "statement_timeout must be between 1 millisecond and about 24 days",
+            ));
+        }
+        if self.read_max_connections == Some(0) {
+            return Err(DatabaseError::invalid_data(
+                "read_max_connections must be at least 1",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Build sqlx pool options with the given size.
+    fn pg_pool_options(&self, max_connections: u32) -> PgPoolOptions {
+        let statement_timeout = self.statement_timeout.map(|t| t.as_millis());
+        PgPoolOptions::new()
+            .max_connections(max_connections)
+            .min_connections(self.min_connections.min(max_connections))
+            .acquire_timeout(self.acquire_timeout)
+            .idle_timeout(self.idle_timeout)
+            .after_connect(move |conn, _meta| {
+                Box::pin(async move {
+                    if let Some(ms) = statement_timeout {
+                        conn.execute(format!("SET statement_timeout = {ms}").as_str())
+                            .await?;
+                    }
+                    Ok(())
+                })
+            })
+    }
+
+    /// Parse a connection string, applying the session settings.
+    fn connect_options(&self, db_connection: &str) -> DatabaseResult<PgConnectOptions> {
+        Ok(self.session_options(db_connection.parse()?))
+    }
+
+    /// Apply the session settings to connect options.
+    fn session_options(&self, options: PgConnectOptions) -> PgConnectOptions {
+        options.application_name(&self.application_name)
+    }
+}
+
+/// Connection statistics of a pool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolStats {
+    /// Open connections, in use or idle.
+    pub size: u32,
+    /// Idle connections.
+    pub idle: usize,
+    /// Maximum number of connections.
+    pub max_connections: u32,
+    /// 99th percentile of recent waits for a connection, or `None` if no
+    /// connection was acquired through [`TaxiiPool::acquire`] or
+    /// [`TaxiiPool::begin`] yet.
+    pub acquire_wait_p99: Option<Duration>,
+}
+
+impl PoolStats {
+    /// Whether every connection is in use and no more can be opened.
+    pub fn is_saturated(&self) -> bool {
+        self.idle == 0 && self.size >= self.max_connections
+    }
+}
+
+/// Recent connection acquire waits.
+#[derive(Debug, Default)]
+struct AcquireWaits {
+    samples: Mutex<VecDeque<Duration>>,
+}
+
+impl AcquireWaits {
+    fn record(&self, wait: Duration) {
+        if let Ok(mut samples) = self.samples.lock() {
+            if samples.len() == ACQUIRE_WAIT_SAMPLES {
+                samples.pop_front();
+            }
+            samples.push_back(wait);
+        }
+    }
+
+    fn p99(&self) -> Option<Duration> {
+        let mut samples: Vec<Duration> = self.samples.lock().ok()?.iter().copied().collect();
+        if samples.is_empty() {
+            return None;
+        }
+        samples.sort_unstable();
+        let rank = (samples.len() * 99).div_ceil(100);
+        samples.get(rank.saturating_sub(1)).copied()
+    }
 }
 
 /// Database connection pool wrapper.
 #[derive(Debug, Clone)]
 pub struct TaxiiPool {
     pool: PgPool,
+    read_pool: Option<PgPool>,
+    acquire_waits: Arc<AcquireWaits>,
 }
 
 impl TaxiiPool {
     /// Create a new pool from an existing PgPool.
     #[must_use]
     pub fn new(pool: PgPool) -> Self {
-        Self { pool }
+        Self {
+            pool,
+            read_pool: None,
+            acquire_waits: Arc::default(),
+        }
+    }
+
+    /// Send reads to a separate pool.
+    #[must_use]
+    pub fn with_read_pool(mut self, read_pool: PgPool) -> Self {
+        self.read_pool = Some(read_pool);
+        self
     }
 
     /// Connect to database with connection string using default options.
@@ -71,19 +244,186 @@ impl TaxiiPool {
         db_connection: &str,
         options: PoolOptions,
     ) -> DatabaseResult<Self> {
-        let pool = PgPoolOptions::new()
-            .max_connections(options.max_connections)
-            .min_connections(options.min_connections)
-            .acquire_timeout(options.acquire_timeout)
-            .connect(db_connection)
+        options.validate()?;
+
+        let pool = options
+            .pg_pool_options(options.max_connections)
+            .connect_with(options.connect_options(db_connection)?)
             .await?;
 
-        Ok(Self { pool })
+        let read_pool = match &options.read_url {
+            Some(read_url) => {
+                let read_pool = options
+                    .pg_pool_options(
+                        options
+                            .read_max_connections
+                            .unwrap_or(options.max_connections),
+                    )
+                    .connect_with(options.connect_options(read_url)?)
+                    .await?;
+                Some(read_pool)
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            pool,
+            read_pool,
+            acquire_waits: Arc::default(),
+        })
     }
 
     /// Get reference to inner pool.
+    ///
+    /// This is the primary pool, used for writes and transactions.
     #[must_use]
     pub fn inner(&self) -> &PgPool {
         &self.pool
     }
+
+    /// Acquire a connection from the primary pool.
+    ///
+    /// Fails with [`DatabaseError::PoolTimeout`] if no connection became
+    /// available within the acquire timeout.
+    pub async fn acquire(&self) -> DatabaseResult<PoolConnection<Postgres>> {
+        let started = Instant::now();
+        let conn = self.pool.acquire().await;
+        self.acquire_waits.record(started.elapsed());
+        Ok(conn?)
+    }
+
+    /// Begin a transaction on the primary pool.
+    pub async fn begin(&self) -> DatabaseResult<TaxiiTransaction> {
+        let started = Instant::now();
+        let tx = self.pool.begin().await;
+        self.acquire_waits.record(started.elapsed());
+        Ok(TaxiiTransaction::new(tx?))
+    }
+
+    /// Get connection statistics of the primary pool.
+    #[must_use]
+    pub fn stats(&self) -> PoolStats {
+        PoolStats {
+            size: self.pool.size(),
+            idle: self.pool.num_idle(),
+            max_connections: self.pool.options().get_max_connections(),
+            acquire_wait_p99: self.acquire_waits.p99(),
+        }
+    }
+
+    /// Whether reads use a separate pool.
+    #[must_use]
+    pub fn has_read_pool(&self) -> bool {
+        self.read_pool.is_some()
+    }
+
+    /// Get a pool for reads: the read pool, or the primary pool if none is
+    /// configured.
+    ///
+    /// Reads through it may lag behind writes on a replica, so reads that
+    /// must see a preceding write use [`Self::inner`].
+    #[must_use]
+    pub fn reader(&self) -> TaxiiPool {
+        Self::new(self.read_pool.as_ref().unwrap_or(&self.pool).clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+    #[test]
+    fn test_validate_options() {
+        assert!(PoolOptions::default().validate().is_ok());
+
+        let invalid = [
+            PoolOptions::with_max_connections(0),
+            PoolOptions {
+                min_connections: 11,
+                ..Default::default()
+            },
+            PoolOptions {
+                acquire_timeout: Duration::ZERO,
+                ..Default::default()
+            },
+            PoolOptions {
+                statement_timeout: Some(Duration::from_micros(10)),
+                ..Default::default()
+            },
+            PoolOptions {
+                read_max_connections: Some(0),
+                ..Default::default()
+            },
+        ];
+        for options in invalid {
+            assert!(options.validate().is_err(), "{options:?}");
+        }
+    }
+
+    #[test]
+    fn test_acquire_wait_p99() {
+        let waits = AcquireWaits::default();
+        assert_eq!(waits.p99(), None);
+
+        for ms in 1..=200 {
+            waits.record(Duration::from_millis(ms));
+        }
+        assert_eq!(waits.p99(), Some(Duration::from_millis(198)));
+
+        // Only recent waits count
+        for _ in 0..ACQUIRE_WAIT_SAMPLES {
+            waits.record(Duration::from_millis(1));
+        }
+        assert_eq!(waits.p99(), Some(Duration::from_millis(1)));
+    }
+
+    /// Acquiring beyond `max_connections` times out with a typed error.
+    #[sqlx::test(migrations = false)]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_acquire_beyond_max_connections_times_out(pool: PgPool) -> TestResult {
+        let options = PoolOptions {
+            max_connections: 1,
+            min_connections: 0,
+            acquire_timeout: Duration::from_millis(200),
+            statement_timeout: Some(Duration::from_secs(5)),
+            ..Default::default()
+        };
+        let connect_options = options.session_options((*pool.connect_options()).clone());
+        let pool = TaxiiPool::new(
+            options
+                .pg_pool_options(1)
+                .connect_with(connect_options)
+                .await?,
+        );
+
+        let mut held = pool.acquire().await?;
+        let (statement_timeout, application_name): (String, String) = sqlx::query_as(
+            "SELECT current_setting('statement_timeout'), current_setting('application_name')",
+        )
+        .fetch_one(&mut *held)
+        .await?;
+        assert_eq!(statement_timeout, "5s");
+        assert_eq!(application_name, DEFAULT_APPLICATION_NAME);
+
+        let started = Instant::now();
+        let result = pool.acquire().await;
+        assert!(matches!(result, Err(DatabaseError::PoolTimeout)));
+        assert!(started.elapsed() >= options.acquire_timeout);
+
+        let stats = pool.stats();
+        assert_eq!((stats.size, stats.idle), (1, 0));
+        assert!(stats.is_saturated());
+        assert!(
+            stats
+                .acquire_wait_p99
+                .is_some_and(|p99| p99 >= options.acquire_timeout)
+        );
+
+        drop(held);
+        assert!(pool.acquire().await.is_ok());
+
+        Ok(())
+    }
 }
@@ -0,0 +1,1076 @@
+//! Conformance suite shared by the repository implementations.
+//!
+//! Each check is written against the repository traits and runs on the
+//! in-memory repositories and, when a database is available, on the
+//! PostgreSQL ones, so tests relying on the in-memory repositories exercise
+//! the same behavior as production.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use futures::TryStreamExt;
+use serde_json::{Value, json};
+use sqlx::PgPool;
+
+use super::memory::{InMemoryTaxii1Repository, InMemoryTaxii2Repository};
+use super::{DbTaxii1Repository, DbTaxii2Repository, Taxii1Repository, Taxii2Repository};
+use crate::models::taxii2::{Taxii2QueryParams, parse_next_param};
+use crate::pool::TaxiiPool;
+use taxii_core::{
+    BulkInsertOutcome, CollectionBridgeEntity, CollectionEntity, CollisionPolicy,
+    ContentBindingEntity, ContentBlockEntity, InboxMessageEntity, PushParametersEntity,
+    ResultSetEntity, ServiceEntity, SubscriptionEntity, delivery_status, result_set_status,
+    subscription_status,
+};
+
+type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+const STIX_XML: &str = "urn:stix.mitre.org:xml:1.1.1";
+const CUSTOM: &str = "urn:example.com:custom";
+
+// ============================================================================
+// TAXII 1.x
+// ============================================================================
+
+fn label(minutes: i64) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0)
+        .single()
+        .unwrap_or_default()
+        + Duration::minutes(minutes)
+}
+
+/// Block `n`, labelled `n` minutes after the start of 2024. Odd blocks are
+/// STIX XML, even blocks custom content of subtype `even`.
+fn block(n: i64) -> ContentBlockEntity {
+    let content_binding = if n % 2 == 1 {
+        ContentBindingEntity::new(STIX_XML)
+    } else {
+        ContentBindingEntity::with_subtypes(CUSTOM, vec!["even".to_string()])
+    };
+    ContentBlockEntity {
+        timestamp_label: label(n),
+        ..ContentBlockEntity::new(format!("<block n=\"{n}\"/>"), Some(content_binding))
+    }
+}
+
+/// Create a collection holding blocks 1 to `count`.
+async fn seed_blocks<R: Taxii1Repository>(
+    repo: &R,
+    name: &str,
+    count: i64,
+) -> Result<i32, Box<dyn std::error::Error>> {
+    let collection_id = repo
+        .create_collection(&CollectionEntity::new(name))
+        .await?
+        .id
+        .ok_or("collection without id")?;
+    for n in 1..=count {
+        repo.create_content_block(&block(n), Some(&[collection_id]), None)
+            .await?;
+    }
+    Ok(collection_id)
+}
+
+fn contents(blocks: &[ContentBlockEntity]) -> Vec<String> {
+    blocks
+        .iter()
+        .map(|b| String::from_utf8_lossy(&b.content).into_owned())
+        .collect()
+}
+
+/// Timeframes exclude their start and include their end; bindings match
+/// any subtype unless subtypes are given.
+async fn check_content_block_filters<R: Taxii1Repository>(repo: &R) -> TestResult {
+    let feed = seed_blocks(repo, "feed", 6).await?;
+
+    let window = repo
+        .get_content_blocks(Some(feed), Some(label(2)), Some(label(4)), None, 0, None)
+        .await?;
+    assert_eq!(contents(&window), contents(&[block(3), block(4)]));
+
+    let stix = [ContentBindingEntity::new(STIX_XML)];
+    assert_eq!(
+        repo.get_content_blocks_count(Some(feed), None, None, Some(&stix))
+            .await?,
+        3
+    );
+    let any_custom = [ContentBindingEntity::new(CUSTOM)];
+    assert_eq!(
+        repo.get_content_blocks_count(Some(feed), None, None, Some(&any_custom))
+            .await?,
+        3
+    );
+    let other_subtype = [ContentBindingEntity::with_subtypes(
+        CUSTOM,
+        vec!["odd".to_string()],
+    )];
+    assert_eq!(
+        repo.get_content_blocks_count(Some(feed), None, None, Some(&other_subtype))
+            .await?,
+        0
+    );
+
+    let page = repo
+        .get_content_blocks(Some(feed), None, None, None, 2, Some(3))
+        .await?;
+    assert_eq!(contents(&page), contents(&[block(3), block(4), block(5)]));
+
+    let all = repo
+        .get_content_blocks(Some(feed), None, None, None, 0, None)
+        .await?;
+    let streamed: Vec<ContentBlockEntity> = repo
+        .stream_content_blocks(Some(feed), None, None, None)
+        .try_collect()
+        .await?;
+    assert_eq!(contents(&streamed), contents(&all));
+    assert_eq!(all.len(), 6);
+
+    Ok(())
+}
+
+/// Identical blocks are stored once in deduplicating collections only.
+async fn check_deduplication<R: Taxii1Repository>(repo: &R) -> TestResult {
+    let dedup = repo
+        .create_collection(&CollectionEntity {
+            deduplicate_content: true,
+            ..CollectionEntity::new("dedup")
+        })
+        .await?
+        .id
+        .ok_or("collection without id")?;
+    let plain = repo
+        .create_collection(&CollectionEntity::new("plain"))
+        .await?
+        .id
+        .ok_or("collection without id")?;
+
+    let first = repo
+        .create_content_block(&block(1), Some(&[dedup]), None)
+        .await?;
+    let second = repo
+        .create_content_block(&block(1), Some(&[dedup]), None)
+        .await?;
+    assert_eq!(second.id, first.id);
+
+    let third = repo
+        .create_content_block(&block(1), Some(&[dedup, plain]), None)
+        .await?;
+    assert_ne!(third.id, first.id);
+
+    let volume = |name: &'static str| async move {
+        repo.get_collection(name, None)
+            .await
+            .map(|c| c.and_then(|c| c.volume))
+    };
+    assert_eq!(volume("dedup").await?, Some(1));
+    assert_eq!(volume("plain").await?, Some(1));
+
+    Ok(())
+}
+
+/// Blocks stored with an inbox message refer to it and are added to their
+/// collections.
+async fn check_inbox_message_with_blocks<R: Taxii1Repository>(repo: &R) -> TestResult {
+    repo.update_service(&ServiceEntity {
+        id: Some("inbox".to_string()),
+        service_type: "INBOX".to_string(),
+        properties: json!({}),
+    })
+    .await?;
+    let feed = repo
+        .create_collection(&CollectionEntity::new("feed"))
+        .await?
+        .id
+        .ok_or("collection without id")?;
+    let message = InboxMessageEntity {
+        id: None,
+        message_id: "inbox-1".to_string(),
+        original_message: b"<Inbox_Message/>".to_vec(),
+        content_block_count: 2,
+        service_id: "inbox".to_string(),
+        destination_collections: Vec::new(),
+        result_id: None,
+        record_count: None,
+        partial_count: false,
+        subscription_collection_name: None,
+        subscription_id: None,
+        exclusive_begin_timestamp_label: None,
+        inclusive_end_timestamp_label: None,
+    };
+
+    let (saved, blocks) = repo
+        .create_inbox_message_with_blocks(
+            Some(&message),
+            &[(block(1), Some(vec![feed])), (block(2), None)],
+            Some("inbox"),
+        )
+        .await?;
+    let saved = saved.ok_or("inbox message not stored")?;
+    assert!(saved.id.is_some());
+    assert_eq!(blocks.len(), 2);
+    assert!(blocks.iter().all(|b| b.inbox_message_id == saved.id));
+    let stored = repo
+        .get_content_blocks(Some(feed), None, None, None, 0, None)
+        .await?;
+    assert_eq!(contents(&stored), ["<block n=\"1\"/>"]);
+
+    // Without an inbox message, the blocks refer to none
+    let (saved, blocks) = repo
+        .create_inbox_message_with_blocks(None, &[(block(3), Some(vec![feed]))], Some("inbox"))
+        .await?;
+    assert!(saved.is_none());
+    assert_eq!(blocks[0].inbox_message_id, None);
+    Ok(())
+}
+
+/// Prepared result sets hold the matching blocks in label order.
+async fn check_result_sets<R: Taxii1Repository>(repo: &R) -> TestResult {
+    let feed = seed_blocks(repo, "feed", 6).await?;
+    let result_set = ResultSetEntity {
+        id: "rs-1".to_string(),
+        collection_id: feed,
+        content_bindings: vec![ContentBindingEntity::new(STIX_XML)],
+        timeframe: (Some(label(1)), None),
+        status: result_set_status::PENDING.to_string(),
+        content_block_count: None,
+        expires_at: Some(Utc::now() + Duration::hours(1)),
+        delivery: Some(PushParametersEntity {
+            protocol_binding: "urn:taxii.mitre.org:protocol:http:1.0".to_string(),
+            address: "http://client.example/inbox/".to_string(),
+            message_binding: "urn:taxii.mitre.org:message:xml:1.1".to_string(),
+        }),
+        delivery_status: Some(delivery_status::PENDING.to_string()),
+    };
+    repo.create_result_set(&result_set).await?;
+
+    let pending = repo.get_pending_result_sets(10).await?;
+    assert_eq!(pending.len(), 1);
+    assert_eq!(repo.count_pending_result_sets().await?, 1);
+    assert!(
+        repo.claim_result_sets_to_deliver(10, Duration::minutes(5))
+            .await?
+            .is_empty()
+    );
+
+    let prepared = repo
+        .prepare_result_set("rs-1")
+        .await?
+        .ok_or("result set not found")?;
+    assert_eq!(prepared.status, result_set_status::READY);
+    assert_eq!(prepared.content_block_count, Some(2));
+    assert!(repo.get_pending_result_sets(10).await?.is_empty());
+    assert_eq!(repo.count_pending_result_sets().await?, 0);
+
+    // Prepared results wait to be pushed; a claim hides them from other
+    // workers until it expires or their delivery is recorded
+    let to_deliver = repo
+        .claim_result_sets_to_deliver(10, Duration::minutes(5))
+        .await?;
+    assert_eq!(to_deliver.len(), 1);
+    assert_eq!(to_deliver[0].delivery, result_set.delivery);
+    assert!(
+        repo.claim_result_sets_to_deliver(10, Duration::minutes(5))
+            .await?
+            .is_empty()
+    );
+    repo.record_result_set_delivery("rs-1", delivery_status::PENDING, 1, None)
+        .await?;
+    let expired = repo
+        .claim_result_sets_to_deliver(10, Duration::milliseconds(-1))
+        .await?;
+    assert_eq!(expired.len(), 1);
+    let reclaimed = repo
+        .claim_result_sets_to_deliver(10, Duration::minutes(5))
+        .await?;
+    assert_eq!(reclaimed.len(), 1);
+    repo.record_result_set_delivery("rs-1", delivery_status::DELIVERED, 2, None)
+        .await?;
+    assert!(
+        repo.claim_result_sets_to_deliver(10, Duration::minutes(5))
+            .await?
+            .is_empty()
+    );
+
+    let part = repo
+        .get_result_set_content_blocks("rs-1", 1, Some(1))
+        .await?;
+    assert_eq!(contents(&part), contents(&[block(5)]));
+
+    // Parts are recorded once; later records keep the first
+    assert_eq!(repo.get_result_set_parts("rs-1").await?, None);
+    assert_eq!(
+        repo.record_result_set_parts("rs-1", &[1, 1]).await?,
+        Some(vec![1, 1])
+    );
+    assert_eq!(
+        repo.record_result_set_parts("rs-1", &[2]).await?,
+        Some(vec![1, 1])
+    );
+    assert_eq!(repo.get_result_set_parts("rs-1").await?, Some(vec![1, 1]));
+    assert_eq!(repo.record_result_set_parts("rs-x", &[2]).await?, None);
+
+    assert_eq!(
+        repo.purge_expired_result_sets(Utc::now() + Duration::hours(2))
+            .await?,
+        1
+    );
+    assert!(repo.get_result_set("rs-1").await?.is_none());
+
+    Ok(())
+}
+
+/// Expired blocks held by a result set are retained.
+async fn check_block_retention<R: Taxii1Repository>(repo: &R) -> TestResult {
+    let feed = seed_blocks(repo, "feed", 4).await?;
+    repo.create_result_set(&ResultSetEntity {
+        id: "rs-1".to_string(),
+        collection_id: feed,
+        content_bindings: vec![ContentBindingEntity::new(CUSTOM)],
+        timeframe: (None, Some(label(2))),
+        status: result_set_status::PENDING.to_string(),
+        content_block_count: None,
+        expires_at: None,
+        delivery: None,
+        delivery_status: None,
+    })
+    .await?;
+    repo.prepare_result_set("rs-1").await?;
+
+    let stats = repo.purge_expired(feed, label(3)).await?;
+    assert_eq!((stats.purged, stats.retained), (1, 1));
+
+    let left = repo
+        .get_content_blocks(Some(feed), None, None, None, 0, None)
+        .await?;
+    assert_eq!(contents(&left), contents(&[block(2), block(3), block(4)]));
+    let volume = repo
+        .get_collection("feed", None)
+        .await?
+        .and_then(|c| c.volume);
+    assert_eq!(volume, Some(3));
+
+    Ok(())
+}
+
+/// Subscriptions pause after repeated delivery failures.
+async fn check_delivery_status<R: Taxii1Repository>(repo: &R) -> TestResult {
+    let feed = seed_blocks(repo, "feed", 0).await?;
+    repo.update_service(&ServiceEntity {
+        id: Some("collection-management".to_string()),
+        service_type: "COLLECTION_MANAGEMENT".to_string(),
+        properties: json!({}),
+    })
+    .await?;
+    let subscription = repo
+        .create_subscription(&SubscriptionEntity {
+            service_id: "collection-management".to_string(),
+            collection_id: feed,
+            subscription_id: None,
+            params: None,
+            status: subscription_status::ACTIVE.to_string(),
+            delivery: Default::default(),
+            push: None,
+            account_id: None,
+        })
+        .await?;
+    let id = subscription
+        .subscription_id
+        .ok_or("subscription without id")?;
+
+    let failed = repo
+        .record_delivery_failure(&id, "connection refused", 2)
+        .await?
+        .ok_or("subscription not found")?;
+    assert_eq!(failed.status, subscription_status::ACTIVE);
+    let paused = repo
+        .record_delivery_failure(&id, "connection refused", 2)
+        .await?
+        .ok_or("subscription not found")?;
+    assert_eq!(paused.status, subscription_status::PAUSED);
+    assert_eq!(paused.delivery.consecutive_failures, 2);
+    assert_eq!(
+        paused.delivery.last_error.as_deref(),
+        Some("connection refused")
+    );
+
+    let delivered = repo
+        .record_delivery_success(&id)
+        .await?
+        .ok_or("subscription not found")?;
+    assert_eq!(delivered.delivery.consecutive_failures, 0);
+    assert!(delivered.delivery.last_success.is_some());
+    assert!(repo.record_delivery_success("unknown").await?.is_none());
+
+    assert_eq!(
+        repo.get_subscriptions("collection-management").await?.len(),
+        1
+    );
+
+    Ok(())
+}
+
+/// Queued deliveries are pushed once, only while their subscription is
+/// active, and failed ones are queued again on request.
+async fn check_delivery_queue<R: Taxii1Repository>(repo: &R) -> TestResult {
+    let feed = seed_blocks(repo, "feed", 3).await?;
+    repo.update_service(&ServiceEntity {
+        id: Some("collection-management".to_string()),
+        service_type: "COLLECTION_MANAGEMENT".to_string(),
+        properties: json!({}),
+    })
+    .await?;
+    let subscription = repo
+        .create_subscription(&SubscriptionEntity {
+            service_id: "collection-management".to_string(),
+            collection_id: feed,
+            subscription_id: None,
+            params: None,
+            status: subscription_status::ACTIVE.to_string(),
+            delivery: Default::default(),
+            push: Some(PushParametersEntity {
+                protocol_binding: "urn:taxii.mitre.org:protocol:http:1.0".to_string(),
+                address: "http://subscriber.example/inbox/".to_string(),
+                message_binding: "urn:taxii.mitre.org:message:xml:1.1".to_string(),
+            }),
+            account_id: None,
+        })
+        .await?;
+    let id = subscription
+        .subscription_id
+        .clone()
+        .ok_or("subscription without id")?;
+    assert_eq!(repo.get_push_subscriptions(feed).await?.len(), 1);
+
+    let blocks = repo
+        .get_content_blocks(Some(feed), None, None, None, 0, None)
+        .await?;
+    let block_ids: Vec<i32> = blocks.iter().filter_map(|b| b.id).collect();
+    for block_id in &block_ids {
+        assert_eq!(
+            repo.create_subscription_deliveries(std::slice::from_ref(&id), *block_id)
+                .await?,
+            1
+        );
+    }
+    assert_eq!(
+        repo.create_subscription_deliveries(std::slice::from_ref(&id), block_ids[0])
+            .await?,
+        0
+    );
+    let fetched = repo.get_content_blocks_by_ids(&block_ids).await?;
+    assert_eq!(contents(&fetched), contents(&blocks));
+
+    // Deliveries to paused subscriptions wait
+    let lease = chrono::Duration::minutes(10);
+    let paused = repo
+        .update_subscription(&SubscriptionEntity {
+            status: subscription_status::PAUSED.to_string(),
+            ..subscription.clone()
+        })
+        .await?;
+    assert!(repo.claim_pending_deliveries(10, lease).await?.is_empty());
+    assert!(repo.get_push_subscriptions(feed).await?.is_empty());
+    repo.update_subscription(&SubscriptionEntity {
+        status: subscription_status::ACTIVE.to_string(),
+        ..paused
+    })
+    .await?;
+
+    let pending = repo.claim_pending_deliveries(2, lease).await?;
+    assert_eq!(pending.len(), 2);
+    assert_eq!(pending[0].content_block_id, block_ids[0]);
+
+    // Claimed deliveries are not claimed again until their lease ends
+    let expired = chrono::Duration::milliseconds(-1);
+    let rest = repo.claim_pending_deliveries(10, expired).await?;
+    assert_eq!(rest.len(), 1);
+    assert_eq!(rest[0].content_block_id, block_ids[2]);
+    assert_eq!(repo.claim_pending_deliveries(10, lease).await?.len(), 1);
+    assert!(repo.claim_pending_deliveries(10, lease).await?.is_empty());
+
+    // Recording the outcome releases the claim
+    let mut ids: Vec<i32> = pending.iter().map(|d| d.id).collect();
+    ids.push(rest[0].id);
+    repo.record_subscription_deliveries(&ids[..1], delivery_status::DELIVERED, 1, None)
+        .await?;
+    repo.record_subscription_deliveries(&ids[1..], delivery_status::FAILED, 3, Some("timeout"))
+        .await?;
+    let failed = repo
+        .get_subscription_deliveries(Some(delivery_status::FAILED), Some(&id), 10)
+        .await?;
+    assert_eq!(failed.len(), 2);
+    assert_eq!(failed[0].attempts, 3);
+    assert_eq!(failed[0].last_error.as_deref(), Some("timeout"));
+    assert!(failed[0].last_attempt_at.is_some());
+    assert!(repo.claim_pending_deliveries(10, lease).await?.is_empty());
+
+    assert_eq!(
+        repo.requeue_failed_deliveries(&[], Some("unknown")).await?,
+        0
+    );
+    assert_eq!(repo.requeue_failed_deliveries(&ids[1..2], None).await?, 1);
+    assert_eq!(repo.claim_pending_deliveries(10, lease).await?.len(), 1);
+    assert_eq!(
+        repo.get_subscription_deliveries(None, None, 10)
+            .await?
+            .len(),
+        3
+    );
+
+    Ok(())
+}
+
+/// Collections link to at most one TAXII 2.x collection, and deleting a
+/// collection removes its link.
+async fn check_collection_bridges<R: Taxii1Repository, T: Taxii2Repository>(
+    repo: &R,
+    taxii2: &T,
+) -> TestResult {
+    let (api_root_id, collection_id) = seed_collection(taxii2).await?;
+    let linked = repo
+        .create_collection(&CollectionEntity::new("linked"))
+        .await?
+        .id
+        .ok_or("collection without id")?;
+    let other = repo
+        .create_collection(&CollectionEntity::new("other"))
+        .await?
+        .id
+        .ok_or("collection without id")?;
+
+    let bridge = CollectionBridgeEntity {
+        data_collection_id: linked,
+        api_root_id,
+        collection_id,
+    };
+    assert_eq!(repo.set_collection_bridge(&bridge).await?, bridge);
+    assert_eq!(repo.set_collection_bridge(&bridge).await?, bridge);
+    assert_eq!(
+        repo.get_collection_bridges(&[linked, other]).await?,
+        vec![bridge.clone()]
+    );
+
+    assert!(!repo.remove_collection_bridge(other).await?);
+    assert!(repo.remove_collection_bridge(linked).await?);
+    assert!(repo.get_collection_bridges(&[linked]).await?.is_empty());
+
+    repo.set_collection_bridge(&bridge).await?;
+    repo.delete_collection("linked").await?;
+    assert!(repo.get_collection_bridges(&[linked]).await?.is_empty());
+
+    Ok(())
+}
+
+// ============================================================================
+// TAXII 2.x
+// ============================================================================
+
+fn indicator(n: usize, modified: &str) -> Value {
+    json!({
+        "id": format!("indicator--00000000-0000-4000-8000-{n:012}"),
+        "type": "indicator",
+        "spec_version": "2.1",
+        "created": "2024-01-01T00:00:00.000Z",
+        "modified": modified,
+        "pattern": "[ipv4-addr:value = '10.0.0.1']",
+        "pattern_type": "stix",
+        "valid_from": "2024-01-01T00:00:00Z"
+    })
+}
+
+const V1: &str = "2024-01-01T00:00:00.000Z";
+const V2: &str = "2024-02-01T00:00:00.000Z";
+const V3: &str = "2024-03-01T00:00:00.000Z";
+
+/// Create a collection and return its API root and collection IDs.
+async fn seed_collection<R: Taxii2Repository>(
+    repo: &R,
+) -> Result<(String, String), Box<dyn std::error::Error>> {
+    let api_root = repo.add_api_root("Root", None, true, false, None).await?;
+    let collection = repo
+        .add_collection(&api_root.id, "Feed", None, Some("feed"), false, false, None)
+        .await?;
+    Ok((api_root.id, collection.id))
+}
+
+/// Paging through objects returns what one unpaged read returns.
+async fn check_object_paging<R: Taxii2Repository>(repo: &R) -> TestResult {
+    let (api_root, collection) = seed_collection(repo).await?;
+    for n in 0..5 {
+        repo.add_objects(&api_root, &collection, &[indicator(n, V1)])
+            .await?;
+    }
+    repo.add_objects(&api_root, &collection, &[indicator(2, V2)])
+        .await?;
+
+    let all_versions = ["all".to_string()];
+    let unpaged = repo
+        .get_objects(
+            &collection,
+            &Taxii2QueryParams {
+                match_version: Some(&all_versions),
+                ..Default::default()
+            },
+        )
+        .await?;
+    assert_eq!(unpaged.items.len(), 6);
+    assert!(!unpaged.more);
+
+    let mut paged = Vec::new();
+    let mut cursor = None;
+    loop {
+        let page = repo
+            .get_objects(
+                &collection,
+                &Taxii2QueryParams {
+                    limit: Some(2),
+                    next: cursor.as_ref(),
+                    match_version: Some(&all_versions),
+                    ..Default::default()
+                },
+            )
+            .await?;
+        paged.extend(page.items);
+        match page.next {
+            Some(next) if page.more => {
+                cursor = Some(parse_next_param(&next).ok_or("invalid next param")?);
+            }
+            _ => break,
+        }
+    }
+
+    let key = |o: &taxii_core::STIXObject| (o.id.clone(), o.version);
+    assert_eq!(
+        paged.iter().map(key).collect::<Vec<_>>(),
+        unpaged.items.iter().map(key).collect::<Vec<_>>()
+    );
+
+    let streamed: Vec<taxii_core::STIXObject> = repo
+        .stream_objects(
+            &collection,
+            &Taxii2QueryParams {
+                match_version: Some(&all_versions),
+                ..Default::default()
+            },
+        )
+        .try_collect()
+        .await?;
+    assert_eq!(streamed.len(), 6);
+
+    Ok(())
+}
+
+/// Versions are selected by `match[version]`, the last one by default.
+async fn check_version_selection<R: Taxii2Repository>(repo: &R) -> TestResult {
+    let (api_root, collection) = seed_collection(repo).await?;
+    for modified in [V2, V1, V3] {
+        repo.add_objects(&api_root, &collection, &[indicator(1, modified)])
+            .await?;
+    }
+    let id = indicator(1, V1)["id"]
+        .as_str()
+        .unwrap_or_default()
+        .to_string();
+
+    let versions = |match_version: &'static [&'static str]| {
+        let (collection, id) = (collection.clone(), id.clone());
+        async move {
+            let match_version: Vec<String> = match_version.iter().map(|v| v.to_string()).collect();
+            let params = Taxii2QueryParams {
+                match_version: (!match_version.is_empty()).then_some(match_version.as_slice()),
+                ..Default::default()
+            };
+            repo.get_object(&collection, &id, &params).await.map(|r| {
+                r.items
+                    .iter()
+                    .map(|o| o.version.format("%Y-%m").to_string())
+                    .collect::<Vec<_>>()
+            })
+        }
+    };
+
+    assert_eq!(versions(&[]).await?, ["2024-03"]);
+    assert_eq!(versions(&["first"]).await?, ["2024-01"]);
+    assert_eq!(versions(&["all"]).await?.len(), 3);
+    assert_eq!(versions(&[V2]).await?, ["2024-02"]);
+
+    let page = repo
+        .get_versions(&collection, &id, Some(2), None, None, None)
+        .await?;
+    assert!(page.more);
+    let cursor = parse_next_param(page.next.as_deref().ok_or("no next param")?)
+        .ok_or("invalid next param")?;
+    let rest = repo
+        .get_versions(&collection, &id, Some(2), None, Some(cursor), None)
+        .await?;
+    let listed: Vec<String> = page
+        .items
+        .iter()
+        .chain(&rest.items)
+        .map(|v| v.version.format("%Y-%m").to_string())
+        .collect();
+    assert_eq!(listed, ["2024-01", "2024-02", "2024-03"]);
+    assert!(!rest.more);
+
+    Ok(())
+}
+
+/// Bulk inserts report duplicates and invalid objects; deleted versions
+/// leave tombstones and can be added again.
+async fn check_bulk_insert_and_deletion<R: Taxii2Repository>(repo: &R) -> TestResult {
+    let (api_root, collection) = seed_collection(repo).await?;
+    let report = repo
+        .add_objects_bulk(
+            &api_root,
+            &collection,
+            &[
+                indicator(1, V1),
+                indicator(1, V1),
+                json!({"type": "indicator"}),
+            ],
+        )
+        .await?;
+    assert_eq!(
+        report.outcomes,
+        [
+            BulkInsertOutcome::Inserted,
+            BulkInsertOutcome::Duplicate,
+            BulkInsertOutcome::Failed
+        ]
+    );
+    let job = repo
+        .get_job_and_details(&api_root, &report.job.id)
+        .await?
+        .ok_or("job not found")?;
+    assert_eq!(job.status, "complete");
+
+    let id = indicator(1, V1)["id"]
+        .as_str()
+        .unwrap_or_default()
+        .to_string();
+    let before = Utc::now() - Duration::seconds(1);
+    repo.delete_object(&collection, &id, None, None, None)
+        .await?;
+    let read = repo
+        .get_object(&collection, &id, &Taxii2QueryParams::default())
+        .await?;
+    assert!(read.items.is_empty());
+
+    let deletions = repo.list_deletions(&collection, before).await?;
+    assert_eq!(deletions.len(), 1);
+    assert_eq!(deletions[0].id, id);
+
+    let report = repo
+        .add_objects_bulk(&api_root, &collection, &[indicator(1, V1)])
+        .await?;
+    assert_eq!(report.outcomes, [BulkInsertOutcome::Inserted]);
+    let read = repo
+        .get_object(&collection, &id, &Taxii2QueryParams::default())
+        .await?;
+    assert_eq!(read.items.len(), 1);
+
+    let stats = repo.collection_stats(&collection).await?;
+    assert_eq!(stats.object_count, 1);
+
+    Ok(())
+}
+
+/// Rejected objects fail with their message, and colliding versions follow
+/// the collision policy.
+async fn check_rejections_and_collisions<R: Taxii2Repository>(repo: &R) -> TestResult {
+    let (api_root, collection) = seed_collection(repo).await?;
+    repo.add_objects_bulk(&api_root, &collection, &[indicator(1, V1)])
+        .await?;
+
+    let mut changed = indicator(1, V1);
+    changed["name"] = json!("changed");
+    let rejected = [(1, "Rule failed".to_string())].into_iter().collect();
+    let report = repo
+        .add_objects_bulk_with_rejections(
+            &api_root,
+            &collection,
+            &[changed.clone(), indicator(2, V1)],
+            &rejected,
+            CollisionPolicy::Reject,
+            None,
+        )
+        .await?;
+    assert_eq!(
+        report.outcomes,
+        [BulkInsertOutcome::Failed, BulkInsertOutcome::Failed]
+    );
+    let messages: Vec<&str> = report
+        .job
+        .details
+        .failure
+        .iter()
+        .map(|d| d.message.as_str())
+        .collect();
+    assert!(messages[0].contains("already exists with different content"));
+    assert_eq!(messages[1], "Rule failed");
+
+    for (policy, outcome, name) in [
+        (CollisionPolicy::KeepExisting, BulkInsertOutcome::Kept, None),
+        (
+            CollisionPolicy::Overwrite,
+            BulkInsertOutcome::Overwritten,
+            Some("changed"),
+        ),
+    ] {
+        let report = repo
+            .add_objects_bulk_with_rejections(
+                &api_root,
+                &collection,
+                std::slice::from_ref(&changed),
+                &BTreeMap::new(),
+                policy,
+                None,
+            )
+            .await?;
+        assert_eq!(report.outcomes, [outcome]);
+        let stored = repo
+            .get_object(
+                &collection,
+                changed["id"].as_str().unwrap_or_default(),
+                &Taxii2QueryParams::default(),
+            )
+            .await?;
+        assert_eq!(stored.items.len(), 1);
+        assert_eq!(
+            stored.items[0].serialized_data["name"].as_str(),
+            name,
+            "{policy:?}"
+        );
+    }
+
+    Ok(())
+}
+
+/// Collections resolve by ID or alias within their API root.
+async fn check_collection_lookup<R: Taxii2Repository>(repo: &R) -> TestResult {
+    let (api_root, collection) = seed_collection(repo).await?;
+    let other = repo.add_api_root("Other", None, false, false, None).await?;
+
+    let by_alias = repo.get_collection(&api_root, "feed").await?;
+    assert_eq!(by_alias.map(|c| c.id), Some(collection.clone()));
+    let by_id = repo.get_collection(&api_root, &collection).await?;
+    assert_eq!(by_id.map(|c| c.id), Some(collection.clone()));
+    assert!(repo.get_collection(&other.id, "feed").await?.is_none());
+
+    let roots = repo.get_api_roots().await?;
+    assert_eq!(roots.iter().filter(|r| r.default).count(), 1);
+
+    Ok(())
+}
+
+/// API roots and collections update in place, keeping their IDs.
+async fn check_api_root_and_collection_updates<R: Taxii2Repository>(repo: &R) -> TestResult {
+    let (api_root, collection) = seed_collection(repo).await?;
+    let other = repo.add_api_root("Other", None, false, false, None).await?;
+
+    let updated = repo
+        .update_api_root(&other.id, "Renamed", Some("Moved"), true, true, Some(4096))
+        .await?;
+    assert_eq!(updated.title, "Renamed");
+    assert_eq!(updated.max_content_length, Some(4096));
+    let roots = repo.get_api_roots().await?;
+    let defaults: Vec<_> = roots.iter().filter(|r| r.default).map(|r| &r.id).collect();
+    assert_eq!(defaults, vec![&other.id]);
+
+    let updated = repo
+        .update_collection(&collection, "Indicators", None, Some("iocs"), true, false)
+        .await?;
+    assert_eq!(updated.id, collection);
+    assert!(repo.get_collection(&api_root, "feed").await?.is_none());
+    let by_alias = repo.get_collection(&api_root, "iocs").await?;
+    assert_eq!(by_alias.map(|c| c.title), Some("Indicators".to_string()));
+
+    let id = "0b8e5d2c-7a1f-4c3e-9d6b-5a4f3e2d1c0b";
+    let created = repo
+        .add_collection(&api_root, "Fixed", None, None, false, false, Some(id))
+        .await?;
+    assert_eq!(created.id, id);
+
+    let missing = "4f3e2d1c-0b8e-4c3e-9d6b-5a7a1f5d2c0b";
+    assert!(
+        repo.update_collection(missing, "Gone", None, None, false, false)
+            .await
+            .is_err()
+    );
+
+    Ok(())
+}
+
+// ============================================================================
+// Runners
+// ============================================================================
+
+fn memory1() -> InMemoryTaxii1Repository {
+    InMemoryTaxii1Repository::new()
+}
+
+fn db1(pool: PgPool) -> DbTaxii1Repository {
+    DbTaxii1Repository::new(TaxiiPool::new(pool))
+}
+
+fn memory2() -> InMemoryTaxii2Repository {
+    InMemoryTaxii2Repository::new()
+}
+
+fn db2(pool: PgPool) -> DbTaxii2Repository {
+    DbTaxii2Repository::new(TaxiiPool::new(pool))
+}
+
+#[tokio::test]
+async fn test_memory_content_block_filters() -> TestResult {
+    check_content_block_filters(&memory1()).await
+}
+
+#[sqlx::test(migrations = "../migrations")]
+#[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+async fn test_db_content_block_filters(pool: PgPool) -> TestResult {
+    check_content_block_filters(&db1(pool)).await
+}
+
+#[tokio::test]
+async fn test_memory_deduplication() -> TestResult {
+    check_deduplication(&memory1()).await
+}
+
+#[sqlx::test(migrations = "../migrations")]
+#[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+async fn test_db_deduplication(pool: PgPool) -> TestResult {
+    check_deduplication(&db1(pool)).await
+}
+
+#[tokio::test]
+async fn test_memory_inbox_message_with_blocks() -> TestResult {
+    check_inbox_message_with_blocks(&memory1()).await
+}
+
+#[sqlx::test(migrations = "../migrations")]
+#[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+async fn test_db_inbox_message_with_blocks(pool: PgPool) -> TestResult {
+    check_inbox_message_with_blocks(&db1(pool)).await
+}
+
+#[tokio::test]
+async fn test_memory_result_sets() -> TestResult {
+    check_result_sets(&memory1()).await
+}
+
+#[sqlx::test(migrations = "../migrations")]
+#[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+async fn test_db_result_sets(pool: PgPool) -> TestResult {
+    check_result_sets(&db1(pool)).await
+}
+
+#[tokio::test]
+async fn test_memory_block_retention() -> TestResult {
+    check_block_retention(&memory1()).await
+}
+
+#[sqlx::test(migrations = "../migrations")]
+#[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+async fn test_db_block_retention(pool: PgPool) -> TestResult {
+    check_block_retention(&db1(pool)).await
+}
+
+#[tokio::test]
+async fn test_memory_delivery_status() -> TestResult {
+    check_delivery_status(&memory1()).await
+}
+
+#[sqlx::test(migrations = "../migrations")]
+#[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+async fn test_db_delivery_status(pool: PgPool) -> TestResult {
+    check_delivery_status(&db1(pool)).await
+}
+
+#[tokio::test]
+async fn test_memory_delivery_queue() -> TestResult {
+    check_delivery_queue(&memory1()).await
+}
+
+#[sqlx::test(migrations = "../migrations")]
+#[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+async fn test_db_delivery_queue(pool: PgPool) -> TestResult {
+    check_delivery_queue(&db1(pool)).await
+}
+
+#[tokio::test]
+async fn test_memory_collection_bridges() -> TestResult {
+    check_collection_bridges(&memory1(), &memory2()).await
+}
+
+#[sqlx::test(migrations = "../migrations")]
+#[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+async fn test_db_collection_bridges(pool: PgPool) -> TestResult {
+    check_collection_bridges(&db1(pool.clone()), &db2(pool)).await
+}
+
+#[tokio::test]
+async fn test_memory_object_paging() -> TestResult {
+    check_object_paging(&memory2()).await
+}
+
+#[sqlx::test(migrations = "../migrations")]
+#[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+async fn test_db_object_paging(pool: PgPool) -> TestResult {
+    check_object_paging(&db2(pool)).await
+}
+
+#[tokio::test]
+async fn test_memory_version_selection() -> TestResult {
+    check_version_selection(&memory2()).await
+}
+
+#[sqlx::test(migrations = "../migrations")]
+#[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+async fn test_db_version_selection(pool: PgPool) -> TestResult {
+    check_version_selection(&db2(pool)).await
+}
+
+#[tokio::test]
+async fn test_memory_bulk_insert_and_deletion() -> TestResult {
+    check_bulk_insert_and_deletion(&memory2()).await
+}
+
+#[sqlx::test(migrations = "../migrations")]
+#[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+async fn test_db_bulk_insert_and_deletion(pool: PgPool) -> TestResult {
+    check_bulk_insert_and_deletion(&db2(pool)).await
+}
+
+#[tokio::test]
+async fn test_memory_rejections_and_collisions() -> TestResult {
+    check_rejections_and_collisions(&memory2()).await
+}
+
+#[sqlx::test(migrations = "../migrations")]
+#[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+async fn test_db_rejections_and_collisions(pool: PgPool) -> TestResult {
+    check_rejections_and_collisions(&db2(pool)).await
+}
+
+#[tokio::test]
+async fn test_memory_collection_lookup() -> TestResult {
+    check_collection_lookup(&memory2()).await
+}
+
+#[sqlx::test(migrations = "../migrations")]
+#[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+async fn test_db_collection_lookup(pool: PgPool) -> TestResult {
+    check_collection_lookup(&db2(pool)).await
+}
+
+#[tokio::test]
+async fn test_memory_api_root_and_collection_updates() -> TestResult {
+    check_api_root_and_collection_updates(&memory2()).await
+}
+
+#[sqlx::test(migrations = "../migrations")]
+#[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+async fn test_db_api_root_and_collection_updates(pool: PgPool) -> TestResult {
+    check_api_root_and_collection_updates(&db2(pool)).await
+}
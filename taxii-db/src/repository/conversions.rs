@@ -3,14 +3,18 @@
 //! This module provides `From` implementations to convert database models
 //! to domain entities, ensuring consistent and type-safe transformations.
 
+use tracing::warn;
+
 use taxii_core::{
-    ApiRoot, Collection, CollectionEntity, ContentBindingEntity, ContentBlockEntity,
-    InboxMessageEntity, ManifestRecord, ResultSetEntity, STIXObject, ServiceEntity,
+    ApiRoot, Collection, CollectionBridgeEntity, CollectionEntity, ContentBindingEntity,
+    ContentBlockEntity, DeletionRecord, DeliveryStatus, InboxMessageEntity, ManifestRecord,
+    PushParametersEntity, ResultSetEntity, STIXObject, ServiceEntity, SubscriptionDeliveryEntity,
     SubscriptionEntity, SubscriptionParameters, VersionRecord,
 };
 
 use crate::models::taxii1::{
-    ContentBlock, DataCollection, InboxMessage, ResultSet, Service, Subscription,
+    CollectionBridge, ContentBlock, DataCollection, InboxMessage, ResultSet, Service, Subscription,
+    SubscriptionDelivery,
 };
 use crate::models::taxii2;
 
@@ -42,6 +46,21 @@ impl From<DataCollection> for CollectionEntity {
             accept_all_content: model.accept_all_content,
             collection_type: model.collection_type,
             supported_content,
+            retention_days: model.retention_days,
+            deduplicate_content: model.deduplicate_content,
+            validation_mode: model.validation_mode,
+            signature_policy: model.signature_policy,
+            trusted_certificates: model.trusted_certificates,
+        }
+    }
+}
+
+impl From<CollectionBridge> for CollectionBridgeEntity {
+    fn from(model: CollectionBridge) -> Self {
+        Self {
+            data_collection_id: model.data_collection_id,
+            api_root_id: model.api_root_id.to_string(),
+            collection_id: model.collection_id.to_string(),
         }
     }
 }
@@ -61,6 +80,10 @@ impl From<ContentBlock> for ContentBlockEntity {
             content_binding,
             message: model.message,
             inbox_message_id: model.inbox_message_id,
+            validation_error: model.validation_error,
+            signature_status: model.signature_status,
+            signer_fingerprint: model.signer_fingerprint,
+            content_encoding: model.content_encoding,
         }
     }
 }
@@ -95,11 +118,30 @@ impl From<ResultSet> for ResultSetEntity {
     fn from(model: ResultSet) -> Self {
         let content_bindings =
             ContentBindingEntity::deserialize_many(model.bindings.as_deref().unwrap_or("[]"));
+        let delivery = match (
+            model.delivery_protocol_binding,
+            model.delivery_address,
+            model.delivery_message_binding,
+        ) {
+            (Some(protocol_binding), Some(address), Some(message_binding)) => {
+                Some(PushParametersEntity {
+                    protocol_binding,
+                    address,
+                    message_binding,
+                })
+            }
+            _ => None,
+        };
         Self {
             id: model.id,
             collection_id: model.collection_id,
             content_bindings,
             timeframe: (model.begin_time, model.end_time),
+            status: model.status,
+            content_block_count: model.content_block_count,
+            expires_at: model.expires_at,
+            delivery,
+            delivery_status: model.delivery_status,
         }
     }
 }
@@ -125,12 +167,49 @@ impl From<Subscription> for SubscriptionEntity {
             })
         });
 
+        let push = match (
+            model.push_protocol_binding,
+            model.push_address,
+            model.push_message_binding,
+        ) {
+            (Some(protocol_binding), Some(address), Some(message_binding)) => {
+                Some(PushParametersEntity {
+                    protocol_binding,
+                    address,
+                    message_binding,
+                })
+            }
+            _ => None,
+        };
+
         Self {
             service_id: model.service_id,
             collection_id: model.collection_id,
             subscription_id: Some(model.id),
             params,
             status: model.status,
+            delivery: DeliveryStatus {
+                last_success: model.last_delivery_at,
+                consecutive_failures: model.delivery_failures,
+                last_error: model.last_delivery_error,
+            },
+            push,
+            account_id: model.account_id,
+        }
+    }
+}
+
+impl From<SubscriptionDelivery> for SubscriptionDeliveryEntity {
+    fn from(model: SubscriptionDelivery) -> Self {
+        Self {
+            id: model.id,
+            subscription_id: model.subscription_id,
+            content_block_id: model.content_block_id,
+            status: model.status,
+            attempts: model.attempts,
+            last_error: model.last_error,
+            date_created: model.date_created,
+            last_attempt_at: model.last_attempt_at,
         }
     }
 }
@@ -147,12 +226,26 @@ impl From<taxii2::ApiRoot> for ApiRoot {
             title: model.title,
             description: model.description,
             is_public: model.is_public,
+            max_content_length: model
+                .max_content_length
+                .and_then(|length| u64::try_from(length).ok()),
         }
     }
 }
 
 impl From<taxii2::Collection> for Collection {
     fn from(model: taxii2::Collection) -> Self {
+        // Rule names are checked when set; skip any this build does not know
+        let validation_rules = model
+            .validation_rules
+            .iter()
+            .filter_map(|name| {
+                name.parse()
+                    .inspect_err(|e| warn!(collection = %model.id, "{e}"))
+                    .ok()
+            })
+            .collect();
+
         Self {
             id: model.id.to_string(),
             api_root_id: model.api_root_id.to_string(),
@@ -161,6 +254,9 @@ impl From<taxii2::Collection> for Collection {
             alias: model.alias,
             is_public: model.is_public,
             is_public_write: model.is_public_write,
+            retention_days: model.retention_days,
+            validation_rules,
+            default_marking: model.default_marking,
         }
     }
 }
@@ -190,6 +286,17 @@ impl From<taxii2::STIXObject> for ManifestRecord {
     }
 }
 
+impl From<taxii2::DeletedVersion> for DeletionRecord {
+    fn from(model: taxii2::DeletedVersion) -> Self {
+        Self {
+            id: model.id,
+            version: model.version.and_utc(),
+            deleted_at: model.deleted_at,
+            deleted_by: model.deleted_by,
+        }
+    }
+}
+
 impl From<taxii2::VersionInfo> for VersionRecord {
     fn from(model: taxii2::VersionInfo) -> Self {
         Self {
@@ -0,0 +1,53 @@
+//! In-memory repository implementations for tests.
+//!
+//! [`InMemoryTaxii1Repository`] and [`InMemoryTaxii2Repository`] implement
+//! the repository traits over plain collections guarded by a mutex, with
+//! the semantics of the PostgreSQL implementations: the same filters,
+//! orderings, pagination cursors, version selection and deduplication.
+//! Permissions are not stored by repositories, so none are checked here
+//! either.
+//!
+//! Both are seeded with fixtures through their builders and are only
+//! compiled for tests and with the `test-util` feature. The shared
+//! conformance suite (`repository::conformance`) runs against them and the
+//! database implementations so the two cannot drift.
+//!
+//! Full-text search is approximated: words are compared case-insensitively
+//! without stemming or stop words, so only queries whose words appear
+//! unchanged in the objects give the same results as PostgreSQL.
+
+mod taxii1;
+mod taxii2;
+
+use std::sync::{Mutex, MutexGuard};
+
+use chrono::{DateTime, Duration, SubsecRound, Utc};
+
+pub use taxii1::{InMemoryTaxii1Builder, InMemoryTaxii1Repository};
+pub use taxii2::{InMemoryTaxii2Builder, InMemoryTaxii2Repository};
+
+/// Lock repository state.
+///
+/// A panic while holding the lock (a failed assertion in a test) leaves
+/// the state consistent, as every operation completes its changes before
+/// returning, so a poisoned lock is recovered.
+fn lock<T>(state: &Mutex<T>) -> MutexGuard<'_, T> {
+    state.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Current time at the precision PostgreSQL stores timestamps with.
+fn now() -> DateTime<Utc> {
+    Utc::now().round_subsecs(6)
+}
+
+/// Next timestamp of a strictly increasing sequence, at database precision.
+///
+/// Like inserts in the database, the value is the current time unless
+/// that is not after `last`.
+fn next_after(last: Option<DateTime<Utc>>) -> DateTime<Utc> {
+    let now = now();
+    match last {
+        Some(last) if last >= now => last + Duration::microseconds(1),
+        _ => now,
+    }
+}
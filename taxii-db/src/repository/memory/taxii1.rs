@@ -0,0 +1,1462 @@
+//! In-memory TAXII 1.x repository.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Mutex;
+
+use chrono::{DateTime, Duration, Utc};
+use futures::StreamExt;
+use uuid::Uuid;
+
+use super::{lock, now};
+use crate::error::{DatabaseError, DatabaseResult};
+use crate::models::collection_stats::{TypeStats, aggregate_stats};
+use crate::models::taxii1::content_digest;
+use crate::repository::taxii1::DEFAULT_DEDUP_WINDOW;
+use crate::repository::traits::Taxii1Repository;
+use crate::stream::RowStream;
+
+use taxii_core::{
+    CollectionBridgeEntity, CollectionEntity, CollectionStats, ContentBindingEntity,
+    ContentBlockEntity, InboxMessageEntity, PurgeStats, ResultSetEntity, ServiceEntity,
+    SubscriptionDeliveryEntity, SubscriptionEntity, delivery_status, result_set_status,
+    subscription_status, truncate_timestamp_label,
+};
+
+/// A stored content block.
+#[derive(Debug, Clone)]
+struct StoredBlock {
+    block: ContentBlockEntity,
+    date_created: DateTime<Utc>,
+    digest: String,
+}
+
+impl StoredBlock {
+    fn binding(&self) -> Option<&str> {
+        self.block
+            .content_binding
+            .as_ref()
+            .map(|b| b.binding.as_str())
+    }
+
+    fn subtype(&self) -> Option<&str> {
+        self.block
+            .content_binding
+            .as_ref()
+            .and_then(|b| b.subtypes.first())
+            .map(String::as_str)
+    }
+}
+
+/// A stored result set with its materialized content blocks.
+#[derive(Debug, Clone)]
+struct StoredResultSet {
+    result_set: ResultSetEntity,
+    date_created: DateTime<Utc>,
+    block_ids: Vec<i32>,
+    part_sizes: Option<Vec<i64>>,
+}
+
+impl StoredResultSet {
+    fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.result_set.expires_at.is_some_and(|e| e <= now)
+    }
+}
+
+/// Content block filters shared by reads, counts and result sets.
+struct BlockFilter<'a> {
+    collection_id: Option<i32>,
+    start_time: Option<DateTime<Utc>>,
+    end_time: Option<DateTime<Utc>>,
+    bindings: Option<&'a [ContentBindingEntity]>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    services: BTreeMap<String, ServiceEntity>,
+    collections: BTreeMap<i32, CollectionEntity>,
+    next_collection_id: i32,
+    /// (service ID, collection ID) links.
+    service_collections: BTreeSet<(String, i32)>,
+    /// TAXII 2.x links, by collection ID.
+    bridges: BTreeMap<i32, CollectionBridgeEntity>,
+    blocks: BTreeMap<i32, StoredBlock>,
+    next_block_id: i32,
+    /// (collection ID, content block ID) links.
+    block_collections: BTreeSet<(i32, i32)>,
+    /// (content block ID, inbox message ID) deliveries.
+    block_messages: BTreeSet<(i32, i32)>,
+    inbox_messages: BTreeMap<i32, InboxMessageEntity>,
+    next_inbox_message_id: i32,
+    result_sets: BTreeMap<String, StoredResultSet>,
+    /// Claimed result set IDs, with the end of their claim.
+    result_set_claims: BTreeMap<String, DateTime<Utc>>,
+    subscriptions: BTreeMap<String, SubscriptionEntity>,
+    deliveries: BTreeMap<i32, SubscriptionDeliveryEntity>,
+    /// Claimed delivery IDs, with the end of their claim.
+    delivery_claims: BTreeMap<i32, DateTime<Utc>>,
+    next_delivery_id: i32,
+    /// Whether content reads fail with [`DatabaseError::ResultsNotReady`].
+    results_not_ready: bool,
+}
+
+impl State {
+    /// IDs of the content blocks matching `filter`, by timestamp label.
+    ///
+    /// Timeframes exclude their start and include their end. A binding
+    /// without subtypes matches any subtype.
+    fn filter_blocks(&self, filter: &BlockFilter<'_>) -> Vec<i32> {
+        let bindings = filter.bindings.filter(|b| !b.is_empty());
+        let mut blocks: Vec<&StoredBlock> = self
+            .blocks
+            .values()
+            .filter(|b| {
+                let id = b.block.id.unwrap_or_default();
+                filter
+                    .collection_id
+                    .is_none_or(|c| self.block_collections.contains(&(c, id)))
+            })
+            .filter(|b| {
+                filter
+                    .start_time
+                    .is_none_or(|st| b.block.timestamp_label > st)
+            })
+            .filter(|b| {
+                filter
+                    .end_time
+                    .is_none_or(|et| b.block.timestamp_label <= et)
+            })
+            .filter(|b| {
+                bindings.is_none_or(|bindings| {
+                    bindings.iter().any(|binding| {
+                        b.binding() == Some(binding.binding.as_str())
+                            && (binding.subtypes.is_empty()
+                                || b.subtype()
+                                    .is_some_and(|s| binding.subtypes.iter().any(|t| t == s)))
+                    })
+                })
+            })
+            .collect();
+        blocks.sort_by_key(|b| (b.block.timestamp_label, b.block.id));
+        blocks.iter().filter_map(|b| b.block.id).collect()
+    }
+
+    fn blocks_of(&self, ids: &[i32]) -> Vec<ContentBlockEntity> {
+        ids.iter()
+            .filter_map(|id| self.blocks.get(id))
+            .map(|b| b.block.clone())
+            .collect()
+    }
+
+    fn volume(&self, collection_id: i32) -> i32 {
+        self.block_collections
+            .iter()
+            .filter(|(c, _)| *c == collection_id)
+            .count() as i32
+    }
+
+    fn update_volume(&mut self, collection_id: i32) {
+        let volume = self.volume(collection_id);
+        if let Some(collection) = self.collections.get_mut(&collection_id) {
+            collection.volume = Some(volume);
+        }
+    }
+
+    fn is_in_result_set(&self, block_id: i32) -> bool {
+        self.result_sets
+            .values()
+            .any(|rs| rs.block_ids.contains(&block_id))
+    }
+
+    /// Delete content blocks with their collection links and deliveries.
+    fn delete_blocks(&mut self, ids: &[i32]) {
+        for id in ids {
+            self.blocks.remove(id);
+        }
+        self.block_collections.retain(|(_, b)| !ids.contains(b));
+        self.block_messages.retain(|(b, _)| !ids.contains(b));
+        for result_set in self.result_sets.values_mut() {
+            result_set.block_ids.retain(|b| !ids.contains(b));
+        }
+        self.deliveries
+            .retain(|_, d| !ids.contains(&d.content_block_id));
+    }
+
+    fn create_collection(&mut self, entity: &CollectionEntity) -> DatabaseResult<CollectionEntity> {
+        if self.collections.values().any(|c| c.name == entity.name) {
+            return Err(DatabaseError::InvalidData(format!(
+                "Collection with name '{}' already exists",
+                entity.name
+            )));
+        }
+
+        self.next_collection_id += 1;
+        let collection = CollectionEntity {
+            id: Some(self.next_collection_id),
+            volume: Some(0),
+            ..entity.clone()
+        };
+        self.collections
+            .insert(self.next_collection_id, collection.clone());
+        Ok(collection)
+    }
+
+    fn create_inbox_message(&mut self, entity: &InboxMessageEntity) -> InboxMessageEntity {
+        self.next_inbox_message_id += 1;
+        let id = self.next_inbox_message_id;
+        let message = InboxMessageEntity {
+            id: Some(id),
+            ..entity.clone()
+        };
+        self.inbox_messages.insert(id, message.clone());
+        message
+    }
+
+    fn create_content_block(
+        &mut self,
+        entity: &ContentBlockEntity,
+        collection_ids: &[i32],
+        dedup_window: Duration,
+    ) -> ContentBlockEntity {
+        let digest = content_digest(&entity.content);
+        let binding = entity.content_binding.as_ref().map(|b| b.binding.as_str());
+        let subtype = entity
+            .content_binding
+            .as_ref()
+            .and_then(|b| b.subtypes.first())
+            .map(String::as_str);
+
+        let mut coll_ids = collection_ids.to_vec();
+        coll_ids.sort_unstable();
+        coll_ids.dedup();
+
+        // Collections that deduplicate content link an identical block added
+        // within the window instead of storing the content again
+        let dedup_ids: Vec<i32> = coll_ids
+            .iter()
+            .copied()
+            .filter(|id| {
+                self.collections
+                    .get(id)
+                    .is_some_and(|c| c.deduplicate_content)
+            })
+            .collect();
+        let since = Utc::now() - dedup_window;
+        let duplicate = self
+            .blocks
+            .values()
+            .filter(|b| {
+                let id = b.block.id.unwrap_or_default();
+                dedup_ids
+                    .iter()
+                    .any(|c| self.block_collections.contains(&(*c, id)))
+                    && b.digest == digest
+                    && b.binding() == binding
+                    && b.subtype() == subtype
+                    && b.date_created >= since
+            })
+            .max_by_key(|b| (b.date_created, b.block.id))
+            .and_then(|b| b.block.id);
+
+        let mut remaining = coll_ids;
+        if let Some(existing) = duplicate {
+            for coll_id in &dedup_ids {
+                self.block_collections.insert((*coll_id, existing));
+            }
+            if let Some(inbox_message_id) = entity.inbox_message_id {
+                self.block_messages.insert((existing, inbox_message_id));
+            }
+            for coll_id in &dedup_ids {
+                self.update_volume(*coll_id);
+            }
+
+            remaining.retain(|id| !dedup_ids.contains(id));
+            if remaining.is_empty()
+                && let Some(block) = self.blocks.get(&existing)
+            {
+                return block.block.clone();
+            }
+        }
+
+        self.next_block_id += 1;
+        let id = self.next_block_id;
+        let block = ContentBlockEntity {
+            id: Some(id),
+            timestamp_label: truncate_timestamp_label(entity.timestamp_label),
+            // Only the first subtype of a binding is stored
+            content_binding: entity.content_binding.as_ref().map(|b| {
+                ContentBindingEntity::with_subtypes(
+                    b.binding.clone(),
+                    b.subtypes.first().cloned().into_iter().collect(),
+                )
+            }),
+            ..entity.clone()
+        };
+        self.blocks.insert(
+            id,
+            StoredBlock {
+                block: block.clone(),
+                date_created: now(),
+                digest,
+            },
+        );
+        if let Some(inbox_message_id) = entity.inbox_message_id {
+            self.block_messages.insert((id, inbox_message_id));
+        }
+        for coll_id in &remaining {
+            self.block_collections.insert((*coll_id, id));
+            self.update_volume(*coll_id);
+        }
+
+        block
+    }
+
+    fn update_subscription(&mut self, entity: &SubscriptionEntity) -> SubscriptionEntity {
+        let subscription_id = entity
+            .subscription_id
+            .clone()
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+        let existing = self.subscriptions.get(&subscription_id);
+        let account_id = existing.map_or(entity.account_id, |e| e.account_id);
+        let delivery = match existing {
+            // Changing the status starts a new run of failures
+            Some(existing) if existing.status != entity.status => taxii_core::DeliveryStatus {
+                consecutive_failures: 0,
+                ..existing.delivery.clone()
+            },
+            Some(existing) => existing.delivery.clone(),
+            None => taxii_core::DeliveryStatus::default(),
+        };
+
+        let subscription = SubscriptionEntity {
+            subscription_id: Some(subscription_id.clone()),
+            delivery,
+            account_id,
+            ..entity.clone()
+        };
+        self.subscriptions
+            .insert(subscription_id, subscription.clone());
+        subscription
+    }
+}
+
+/// In-memory implementation of [`Taxii1Repository`].
+///
+/// Behaves like [`crate::DbTaxii1Repository`] (see the [module
+/// docs](super)); seed it with [`Self::builder`]. Collection statistics are
+/// always computed, never cached.
+#[derive(Debug)]
+pub struct InMemoryTaxii1Repository {
+    state: Mutex<State>,
+    dedup_window: Duration,
+}
+
+impl Default for InMemoryTaxii1Repository {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InMemoryTaxii1Repository {
+    /// Create an empty repository.
+    ///
+    /// Deduplicating collections use [`DEFAULT_DEDUP_WINDOW`].
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(State::default()),
+            dedup_window: DEFAULT_DEDUP_WINDOW,
+        }
+    }
+
+    /// Create a builder seeding a repository with fixtures.
+    pub fn builder() -> InMemoryTaxii1Builder {
+        InMemoryTaxii1Builder::default()
+    }
+
+    /// Set how far back collections that deduplicate content look for an
+    /// identical content block.
+    #[must_use]
+    pub fn with_dedup_window(mut self, dedup_window: Duration) -> Self {
+        self.dedup_window = dedup_window;
+        self
+    }
+}
+
+impl InMemoryTaxii1Repository {
+    /// Simulate content that is not available yet.
+    ///
+    /// While results are not ready, reading content blocks and preparing
+    /// result sets fail with [`DatabaseError::ResultsNotReady`], as a
+    /// backend that prepares content asynchronously would.
+    pub fn set_results_ready(&self, ready: bool) {
+        lock(&self.state).results_not_ready = !ready;
+    }
+}
+
+/// Builder seeding an [`InMemoryTaxii1Repository`] with fixtures.
+///
+/// Collections get IDs in the order they are added, starting at 1, and are
+/// referenced by name when linking services and content blocks.
+#[derive(Debug, Default)]
+pub struct InMemoryTaxii1Builder {
+    services: Vec<ServiceEntity>,
+    collections: Vec<(CollectionEntity, Vec<String>)>,
+    content_blocks: Vec<(ContentBlockEntity, Vec<String>)>,
+    subscriptions: Vec<SubscriptionEntity>,
+    dedup_window: Option<Duration>,
+}
+
+impl InMemoryTaxii1Builder {
+    /// Add a service.
+    #[must_use]
+    pub fn service(mut self, service: ServiceEntity) -> Self {
+        self.services.push(service);
+        self
+    }
+
+    /// Add a collection, advertised by the given services.
+    #[must_use]
+    pub fn collection(mut self, collection: CollectionEntity, service_ids: &[&str]) -> Self {
+        let service_ids = service_ids.iter().map(|s| s.to_string()).collect();
+        self.collections.push((collection, service_ids));
+        self
+    }
+
+    /// Add a content block to the named collections.
+    #[must_use]
+    pub fn content_block(mut self, block: ContentBlockEntity, collections: &[&str]) -> Self {
+        let collections = collections.iter().map(|s| s.to_string()).collect();
+        self.content_blocks.push((block, collections));
+        self
+    }
+
+    /// Add a subscription.
+    #[must_use]
+    pub fn subscription(mut self, subscription: SubscriptionEntity) -> Self {
+        self.subscriptions.push(subscription);
+        self
+    }
+
+    /// Set how far back collections that deduplicate content look for an
+    /// identical content block.
+    #[must_use]
+    pub fn dedup_window(mut self, dedup_window: Duration) -> Self {
+        self.dedup_window = Some(dedup_window);
+        self
+    }
+
+    /// Build the repository.
+    ///
+    /// Fails if two collections have the same name.
+    pub fn build(self) -> DatabaseResult<InMemoryTaxii1Repository> {
+        let dedup_window = self.dedup_window.unwrap_or(DEFAULT_DEDUP_WINDOW);
+        let mut state = State::default();
+
+        for service in self.services {
+            if let Some(id) = service.id.clone() {
+                state.services.insert(id, service);
+            }
+        }
+        for (collection, service_ids) in self.collections {
+            let id = state.create_collection(&collection)?.id.unwrap_or_default();
+            for service_id in service_ids {
+                state.service_collections.insert((service_id, id));
+            }
+        }
+        for (block, names) in self.content_blocks {
+            let ids: Vec<i32> = state
+                .collections
+                .values()
+                .filter(|c| names.contains(&c.name))
+                .filter_map(|c| c.id)
+                .collect();
+            state.create_content_block(&block, &ids, dedup_window);
+        }
+        for subscription in &self.subscriptions {
+            state.update_subscription(subscription);
+        }
+
+        Ok(InMemoryTaxii1Repository {
+            state: Mutex::new(state),
+            dedup_window,
+        })
+    }
+}
+
+impl Taxii1Repository for InMemoryTaxii1Repository {
+    // ========================================================================
+    // Service Operations
+    // ========================================================================
+
+    async fn get_services(&self, collection_id: Option<i32>) -> DatabaseResult<Vec<ServiceEntity>> {
+        let state = lock(&self.state);
+        Ok(state
+            .services
+            .values()
+            .filter(|s| {
+                collection_id.is_none_or(|c| {
+                    s.id.as_ref()
+                        .is_some_and(|id| state.service_collections.contains(&(id.clone(), c)))
+                })
+            })
+            .cloned()
+            .collect())
+    }
+
+    async fn get_service(&self, service_id: &str) -> DatabaseResult<Option<ServiceEntity>> {
+        Ok(lock(&self.state).services.get(service_id).cloned())
+    }
+
+    async fn update_service(&self, entity: &ServiceEntity) -> DatabaseResult<ServiceEntity> {
+        let service_id = entity
+            .id
+            .as_ref()
+            .ok_or_else(|| DatabaseError::NotFound("Service ID required".to_string()))?;
+
+        lock(&self.state)
+            .services
+            .insert(service_id.clone(), entity.clone());
+        Ok(entity.clone())
+    }
+
+    async fn create_service(&self, entity: &ServiceEntity) -> DatabaseResult<ServiceEntity> {
+        self.update_service(entity).await
+    }
+
+    async fn delete_service(&self, service_id: &str) -> DatabaseResult<()> {
+        let mut state = lock(&self.state);
+        state.services.remove(service_id);
+        state.service_collections.retain(|(s, _)| s != service_id);
+        state
+            .subscriptions
+            .retain(|_, s| s.service_id != service_id);
+        Ok(())
+    }
+
+    async fn get_domain(&self, service_id: &str) -> DatabaseResult<Option<String>> {
+        let service = self.get_service(service_id).await?;
+
+        let domain = service
+            .and_then(|svc| svc.properties.get("domain").cloned())
+            .and_then(|v| v.as_str().map(String::from))
+            .filter(|s| !s.is_empty());
+
+        Ok(domain)
+    }
+
+    async fn get_advertised_services(
+        &self,
+        discovery_service_id: &str,
+    ) -> DatabaseResult<Vec<ServiceEntity>> {
+        let Some(service) = self.get_service(discovery_service_id).await? else {
+            return Ok(Vec::new());
+        };
+
+        let advertised_ids: Vec<String> = service
+            .properties
+            .get("advertised_services")
+            .and_then(|v| v.as_array())
+            .map(|arr| {
+                arr.iter()
+                    .filter_map(|v| v.as_str().map(String::from))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        // If no advertised services are configured, all services are
+        if advertised_ids.is_empty() {
+            return self.get_services(None).await;
+        }
+
+        let state = lock(&self.state);
+        Ok(advertised_ids
+            .iter()
+            .filter_map(|id| state.services.get(id).cloned())
+            .collect())
+    }
+
+    async fn get_services_for_collection(
+        &self,
+        collection_id: i32,
+        service_type: Option<&str>,
+    ) -> DatabaseResult<Vec<ServiceEntity>> {
+        let services = self.get_services(Some(collection_id)).await?;
+        Ok(services
+            .into_iter()
+            .filter(|s| service_type.is_none_or(|t| s.service_type == t))
+            .collect())
+    }
+
+    // ========================================================================
+    // Collection Operations
+    // ========================================================================
+
+    async fn get_collections(
+        &self,
+        service_id: Option<&str>,
+    ) -> DatabaseResult<Vec<CollectionEntity>> {
+        let state = lock(&self.state);
+        Ok(state
+            .collections
+            .values()
+            .filter(|c| {
+                service_id.is_none_or(|s| {
+                    c.id.is_some_and(|id| state.service_collections.contains(&(s.to_string(), id)))
+                })
+            })
+            .cloned()
+            .collect())
+    }
+
+    async fn get_collection(
+        &self,
+        name: &str,
+        service_id: Option<&str>,
+    ) -> DatabaseResult<Option<CollectionEntity>> {
+        let collections = self.get_collections(service_id).await?;
+        Ok(collections.into_iter().find(|c| c.name == name))
+    }
+
+    async fn create_collection(
+        &self,
+        entity: &CollectionEntity,
+    ) -> DatabaseResult<CollectionEntity> {
+        lock(&self.state).create_collection(entity)
+    }
+
+    async fn update_collection(
+        &self,
+        entity: &CollectionEntity,
+    ) -> DatabaseResult<CollectionEntity> {
+        let id = entity
+            .id
+            .ok_or_else(|| DatabaseError::NotFound("Collection ID required".to_string()))?;
+
+        let mut state = lock(&self.state);
+        let volume = state.volume(id);
+        let collection = state.collections.get_mut(&id).ok_or_else(|| {
+            DatabaseError::NotFound(format!("Collection with id {id} does not exist"))
+        })?;
+        *collection = CollectionEntity {
+            volume: Some(volume),
+            ..entity.clone()
+        };
+        Ok(collection.clone())
+    }
+
+    async fn delete_collection(&self, collection_name: &str) -> DatabaseResult<()> {
+        let mut state = lock(&self.state);
+        let Some(id) = state
+            .collections
+            .values()
+            .find(|c| c.name == collection_name)
+            .and_then(|c| c.id)
+        else {
+            return Ok(());
+        };
+
+        state.collections.remove(&id);
+        state.service_collections.retain(|(_, c)| *c != id);
+        state.bridges.remove(&id);
+        state.block_collections.retain(|(c, _)| *c != id);
+        state
+            .result_sets
+            .retain(|_, rs| rs.result_set.collection_id != id);
+        state.subscriptions.retain(|_, s| s.collection_id != id);
+        let State {
+            subscriptions,
+            deliveries,
+            ..
+        } = &mut *state;
+        deliveries.retain(|_, d| subscriptions.contains_key(&d.subscription_id));
+        Ok(())
+    }
+
+    async fn set_collection_services(
+        &self,
+        collection_id: i32,
+        service_ids: &[String],
+    ) -> DatabaseResult<()> {
+        let mut state = lock(&self.state);
+        if !state.collections.contains_key(&collection_id) {
+            return Err(DatabaseError::NotFound(format!(
+                "Collection with id {collection_id} does not exist"
+            )));
+        }
+        if !service_ids.iter().all(|s| state.services.contains_key(s)) {
+            return Err(DatabaseError::NotFound(
+                "Some services do not exist".to_string(),
+            ));
+        }
+
+        state
+            .service_collections
+            .retain(|(_, c)| *c != collection_id);
+        for service_id in service_ids {
+            state
+                .service_collections
+                .insert((service_id.clone(), collection_id));
+        }
+        Ok(())
+    }
+
+    async fn set_collection_bridge(
+        &self,
+        bridge: &CollectionBridgeEntity,
+    ) -> DatabaseResult<CollectionBridgeEntity> {
+        let mut state = lock(&self.state);
+        if !state.collections.contains_key(&bridge.data_collection_id) {
+            return Err(DatabaseError::NotFound(format!(
+                "Collection with id {} does not exist",
+                bridge.data_collection_id
+            )));
+        }
+        state
+            .bridges
+            .insert(bridge.data_collection_id, bridge.clone());
+        Ok(bridge.clone())
+    }
+
+    async fn remove_collection_bridge(&self, data_collection_id: i32) -> DatabaseResult<bool> {
+        Ok(lock(&self.state)
+            .bridges
+            .remove(&data_collection_id)
+            .is_some())
+    }
+
+    async fn get_collection_bridges(
+        &self,
+        data_collection_ids: &[i32],
+    ) -> DatabaseResult<Vec<CollectionBridgeEntity>> {
+        let state = lock(&self.state);
+        Ok(state
+            .bridges
+            .values()
+            .filter(|b| data_collection_ids.contains(&b.data_collection_id))
+            .cloned()
+            .collect())
+    }
+
+    // ========================================================================
+    // Content Block Operations
+    // ========================================================================
+
+    async fn get_content_blocks(
+        &self,
+        collection_id: Option<i32>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        bindings: Option<&[ContentBindingEntity]>,
+        offset: i64,
+        limit: Option<i64>,
+    ) -> DatabaseResult<Vec<ContentBlockEntity>> {
+        let state = lock(&self.state);
+        if state.results_not_ready {
+            return Err(DatabaseError::ResultsNotReady);
+        }
+        let ids = state.filter_blocks(&BlockFilter {
+            collection_id,
+            start_time,
+            end_time,
+            bindings,
+        });
+        let page: Vec<i32> = ids
+            .into_iter()
+            .skip(offset.max(0) as usize)
+            .take(limit.map_or(usize::MAX, |lim| lim.max(0) as usize))
+            .collect();
+        Ok(state.blocks_of(&page))
+    }
+
+    fn stream_content_blocks(
+        &self,
+        collection_id: Option<i32>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        bindings: Option<&[ContentBindingEntity]>,
+    ) -> RowStream<ContentBlockEntity> {
+        let state = lock(&self.state);
+        let ids = state.filter_blocks(&BlockFilter {
+            collection_id,
+            start_time,
+            end_time,
+            bindings,
+        });
+        let blocks = state.blocks_of(&ids);
+        futures::stream::iter(blocks.into_iter().map(Ok)).boxed()
+    }
+
+    async fn get_content_blocks_count(
+        &self,
+        collection_id: Option<i32>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        bindings: Option<&[ContentBindingEntity]>,
+    ) -> DatabaseResult<i64> {
+        let state = lock(&self.state);
+        let ids = state.filter_blocks(&BlockFilter {
+            collection_id,
+            start_time,
+            end_time,
+            bindings,
+        });
+        Ok(ids.len() as i64)
+    }
+
+    async fn get_content_block_sizes(
+        &self,
+        collection_id: Option<i32>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        bindings: Option<&[ContentBindingEntity]>,
+    ) -> DatabaseResult<Vec<i64>> {
+        let state = lock(&self.state);
+        let ids = state.filter_blocks(&BlockFilter {
+            collection_id,
+            start_time,
+            end_time,
+            bindings,
+        });
+        Ok(state
+            .blocks_of(&ids)
+            .iter()
+            .map(|block| block.content.len() as i64)
+            .collect())
+    }
+
+    async fn get_result_set_parts(&self, result_set_id: &str) -> DatabaseResult<Option<Vec<i64>>> {
+        Ok(lock(&self.state)
+            .result_sets
+            .get(result_set_id)
+            .and_then(|rs| rs.part_sizes.clone()))
+    }
+
+    async fn record_result_set_parts(
+        &self,
+        result_set_id: &str,
+        parts: &[i64],
+    ) -> DatabaseResult<Option<Vec<i64>>> {
+        let mut state = lock(&self.state);
+        Ok(state
+            .result_sets
+            .get_mut(result_set_id)
+            .map(|rs| rs.part_sizes.get_or_insert_with(|| parts.to_vec()).clone()))
+    }
+
+    async fn create_content_block(
+        &self,
+        entity: &ContentBlockEntity,
+        collection_ids: Option<&[i32]>,
+        _service_id: Option<&str>,
+    ) -> DatabaseResult<ContentBlockEntity> {
+        Ok(lock(&self.state).create_content_block(
+            entity,
+            collection_ids.unwrap_or_default(),
+            self.dedup_window,
+        ))
+    }
+
+    async fn delete_content_blocks(
+        &self,
+        collection_name: &str,
+        start_time: DateTime<Utc>,
+        end_time: Option<DateTime<Utc>>,
+        with_messages: bool,
+    ) -> DatabaseResult<i64> {
+        let mut state = lock(&self.state);
+        let collection_id = state
+            .collections
+            .values()
+            .find(|c| c.name == collection_name)
+            .and_then(|c| c.id)
+            .ok_or_else(|| {
+                DatabaseError::NotFound(format!(
+                    "Collection with name '{collection_name}' does not exist"
+                ))
+            })?;
+
+        let mut ids = state.filter_blocks(&BlockFilter {
+            collection_id: Some(collection_id),
+            start_time: Some(start_time),
+            end_time,
+            bindings: None,
+        });
+        if ids.is_empty() {
+            return Ok(0);
+        }
+        let deleted = ids.len() as i64;
+
+        // Deleting inbox messages deletes the blocks they carried as well
+        if with_messages {
+            let message_ids: BTreeSet<i32> = ids
+                .iter()
+                .filter_map(|id| state.blocks.get(id))
+                .filter_map(|b| b.block.inbox_message_id)
+                .chain(
+                    state
+                        .block_messages
+                        .iter()
+                        .filter(|(b, _)| ids.contains(b))
+                        .map(|(_, m)| *m),
+                )
+                .collect();
+            for message_id in &message_ids {
+                state.inbox_messages.remove(message_id);
+            }
+            ids.extend(
+                state
+                    .blocks
+                    .values()
+                    .filter(|b| {
+                        b.block
+                            .inbox_message_id
+                            .is_some_and(|m| message_ids.contains(&m))
+                    })
+                    .filter_map(|b| b.block.id),
+            );
+            state
+                .block_messages
+                .retain(|(_, m)| !message_ids.contains(m));
+        }
+
+        state.delete_blocks(&ids);
+        let affected: Vec<i32> = state.collections.keys().copied().collect();
+        for collection_id in affected {
+            state.update_volume(collection_id);
+        }
+
+        Ok(deleted)
+    }
+
+    async fn purge_expired(
+        &self,
+        collection_id: i32,
+        cutoff: DateTime<Utc>,
+    ) -> DatabaseResult<PurgeStats> {
+        let mut state = lock(&self.state);
+        let expired: Vec<i32> = state
+            .block_collections
+            .iter()
+            .filter(|(c, _)| *c == collection_id)
+            .map(|(_, b)| *b)
+            .filter(|b| {
+                state
+                    .blocks
+                    .get(b)
+                    .is_some_and(|block| block.block.timestamp_label < cutoff)
+            })
+            .collect();
+
+        // Blocks materialized in a result set are left alone
+        let (retained, purged): (Vec<i32>, Vec<i32>) = expired
+            .into_iter()
+            .partition(|b| state.is_in_result_set(*b));
+        state
+            .block_collections
+            .retain(|(c, b)| *c != collection_id || !purged.contains(b));
+        let orphans: Vec<i32> = purged
+            .iter()
+            .copied()
+            .filter(|b| !state.block_collections.iter().any(|(_, other)| other == b))
+            .collect();
+        state.delete_blocks(&orphans);
+        if !purged.is_empty() {
+            state.update_volume(collection_id);
+        }
+
+        Ok(PurgeStats {
+            purged: purged.len() as u64,
+            retained: retained.len() as u64,
+        })
+    }
+
+    async fn collection_stats(&self, collection_id: i32) -> DatabaseResult<CollectionStats> {
+        let state = lock(&self.state);
+        let mut by_binding: BTreeMap<String, (i64, DateTime<Utc>)> = BTreeMap::new();
+        for (_, block_id) in state
+            .block_collections
+            .iter()
+            .filter(|(c, _)| *c == collection_id)
+        {
+            let Some(block) = state.blocks.get(block_id) else {
+                continue;
+            };
+            let label = block.block.timestamp_label;
+            let entry = by_binding
+                .entry(block.binding().unwrap_or("unspecified").to_string())
+                .or_insert((0, label));
+            entry.0 += 1;
+            entry.1 = entry.1.max(label);
+        }
+
+        let types = by_binding
+            .into_iter()
+            .map(|(object_type, (count, latest_added))| TypeStats {
+                object_type,
+                object_count: count,
+                distinct_ids: count,
+                latest_added: Some(latest_added),
+            })
+            .collect();
+
+        Ok(aggregate_stats(types, Utc::now()))
+    }
+
+    // ========================================================================
+    // Inbox Message Operations
+    // ========================================================================
+
+    async fn create_inbox_message(
+        &self,
+        entity: &InboxMessageEntity,
+    ) -> DatabaseResult<InboxMessageEntity> {
+        Ok(lock(&self.state).create_inbox_message(entity))
+    }
+
+    async fn create_inbox_message_with_blocks(
+        &self,
+        inbox_message: Option<&InboxMessageEntity>,
+        blocks: &[(ContentBlockEntity, Option<Vec<i32>>)],
+        _service_id: Option<&str>,
+    ) -> DatabaseResult<(Option<InboxMessageEntity>, Vec<ContentBlockEntity>)> {
+        let mut state = lock(&self.state);
+        let message = inbox_message.map(|entity| state.create_inbox_message(entity));
+
+        let created = blocks
+            .iter()
+            .map(|(block, collection_ids)| {
+                let block = ContentBlockEntity {
+                    inbox_message_id: message.as_ref().and_then(|m| m.id),
+                    ..block.clone()
+                };
+                state.create_content_block(
+                    &block,
+                    collection_ids.as_deref().unwrap_or_default(),
+                    self.dedup_window,
+                )
+            })
+            .collect();
+        Ok((message, created))
+    }
+
+    // ========================================================================
+    // Result Set Operations
+    // ========================================================================
+
+    async fn create_result_set(&self, entity: &ResultSetEntity) -> DatabaseResult<ResultSetEntity> {
+        let mut state = lock(&self.state);
+        if state.result_sets.contains_key(&entity.id) {
+            return Err(DatabaseError::InvalidData(format!(
+                "Result set {} already exists",
+                entity.id
+            )));
+        }
+
+        state.result_sets.insert(
+            entity.id.clone(),
+            StoredResultSet {
+                result_set: entity.clone(),
+                date_created: now(),
+                block_ids: Vec::new(),
+                part_sizes: None,
+            },
+        );
+        Ok(entity.clone())
+    }
+
+    async fn get_result_set(&self, result_set_id: &str) -> DatabaseResult<Option<ResultSetEntity>> {
+        let state = lock(&self.state);
+        Ok(state
+            .result_sets
+            .get(result_set_id)
+            .filter(|rs| !rs.is_expired(Utc::now()))
+            .map(|rs| rs.result_set.clone()))
+    }
+
+    async fn get_pending_result_sets(&self, limit: i64) -> DatabaseResult<Vec<ResultSetEntity>> {
+        let state = lock(&self.state);
+        let now = Utc::now();
+        let mut pending: Vec<&StoredResultSet> = state
+            .result_sets
+            .values()
+            .filter(|rs| rs.result_set.status == result_set_status::PENDING && !rs.is_expired(now))
+            .collect();
+        pending.sort_by_key(|rs| rs.date_created);
+
+        Ok(pending
+            .into_iter()
+            .take(limit.max(0) as usize)
+            .map(|rs| rs.result_set.clone())
+            .collect())
+    }
+
+    async fn count_pending_result_sets(&self) -> DatabaseResult<i64> {
+        let state = lock(&self.state);
+        let now = Utc::now();
+        Ok(state
+            .result_sets
+            .values()
+            .filter(|rs| rs.result_set.status == result_set_status::PENDING && !rs.is_expired(now))
+            .count() as i64)
+    }
+
+    async fn claim_result_sets_to_deliver(
+        &self,
+        limit: i64,
+        lease: Duration,
+    ) -> DatabaseResult<Vec<ResultSetEntity>> {
+        let mut state = lock(&self.state);
+        let state = &mut *state;
+        let now = now();
+        let mut to_deliver: Vec<&StoredResultSet> = state
+            .result_sets
+            .values()
+            .filter(|rs| {
+                rs.result_set.status == result_set_status::READY
+                    && rs.result_set.delivery_status.as_deref() == Some(delivery_status::PENDING)
+                    && !rs.is_expired(now)
+                    && state
+                        .result_set_claims
+                        .get(&rs.result_set.id)
+                        .is_none_or(|until| *until < now)
+            })
+            .collect();
+        to_deliver.sort_by_key(|rs| rs.date_created);
+
+        let claimed: Vec<ResultSetEntity> = to_deliver
+            .into_iter()
+            .take(usize::try_from(limit).unwrap_or(0))
+            .map(|rs| rs.result_set.clone())
+            .collect();
+        for result_set in &claimed {
+            state
+                .result_set_claims
+                .insert(result_set.id.clone(), now + lease);
+        }
+        Ok(claimed)
+    }
+
+    async fn record_result_set_delivery(
+        &self,
+        result_set_id: &str,
+        status: &str,
+        _attempts: i32,
+        _error: Option<&str>,
+    ) -> DatabaseResult<()> {
+        let mut state = lock(&self.state);
+        state.result_set_claims.remove(result_set_id);
+        if let Some(stored) = state.result_sets.get_mut(result_set_id) {
+            stored.result_set.delivery_status = Some(status.to_string());
+        }
+        Ok(())
+    }
+
+    async fn prepare_result_set(
+        &self,
+        result_set_id: &str,
+    ) -> DatabaseResult<Option<ResultSetEntity>> {
+        let mut state = lock(&self.state);
+        if state.results_not_ready {
+            return Err(DatabaseError::ResultsNotReady);
+        }
+        let Some(result_set) = state
+            .result_sets
+            .get(result_set_id)
+            .filter(|rs| !rs.is_expired(Utc::now()))
+            .map(|rs| rs.result_set.clone())
+        else {
+            return Ok(None);
+        };
+
+        let block_ids = state.filter_blocks(&BlockFilter {
+            collection_id: Some(result_set.collection_id),
+            start_time: result_set.timeframe.0,
+            end_time: result_set.timeframe.1,
+            bindings: Some(&result_set.content_bindings),
+        });
+
+        let Some(stored) = state.result_sets.get_mut(result_set_id) else {
+            return Ok(None);
+        };
+        stored.result_set.status = result_set_status::READY.to_string();
+        stored.result_set.content_block_count = Some(block_ids.len() as i32);
+        stored.block_ids = block_ids;
+        Ok(Some(stored.result_set.clone()))
+    }
+
+    async fn purge_expired_result_sets(&self, now: DateTime<Utc>) -> DatabaseResult<u64> {
+        let mut state = lock(&self.state);
+        let count = state.result_sets.len();
+        state.result_sets.retain(|_, rs| !rs.is_expired(now));
+        Ok((count - state.result_sets.len()) as u64)
+    }
+
+    async fn get_result_set_content_blocks(
+        &self,
+        result_set_id: &str,
+        offset: i64,
+        limit: Option<i64>,
+    ) -> DatabaseResult<Vec<ContentBlockEntity>> {
+        let state = lock(&self.state);
+        let Some(result_set) = state.result_sets.get(result_set_id) else {
+            return Ok(Vec::new());
+        };
+
+        let page: Vec<i32> = result_set
+            .block_ids
+            .iter()
+            .copied()
+            .skip(offset.max(0) as usize)
+            .take(limit.map_or(usize::MAX, |lim| lim.max(0) as usize))
+            .collect();
+        Ok(state.blocks_of(&page))
+    }
+
+    async fn get_result_set_content_block_sizes(
+        &self,
+        result_set_id: &str,
+    ) -> DatabaseResult<Vec<i64>> {
+        let state = lock(&self.state);
+        let Some(result_set) = state.result_sets.get(result_set_id) else {
+            return Ok(Vec::new());
+        };
+        Ok(state
+            .blocks_of(&result_set.block_ids)
+            .iter()
+            .map(|block| block.content.len() as i64)
+            .collect())
+    }
+
+    // ========================================================================
+    // Subscription Operations
+    // ========================================================================
+
+    async fn get_subscription(
+        &self,
+        subscription_id: &str,
+    ) -> DatabaseResult<Option<SubscriptionEntity>> {
+        Ok(lock(&self.state)
+            .subscriptions
+            .get(subscription_id)
+            .cloned())
+    }
+
+    async fn get_subscriptions(&self, service_id: &str) -> DatabaseResult<Vec<SubscriptionEntity>> {
+        let state = lock(&self.state);
+        Ok(state
+            .subscriptions
+            .values()
+            .filter(|s| s.service_id == service_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn update_subscription(
+        &self,
+        entity: &SubscriptionEntity,
+    ) -> DatabaseResult<SubscriptionEntity> {
+        Ok(lock(&self.state).update_subscription(entity))
+    }
+
+    async fn create_subscription(
+        &self,
+        entity: &SubscriptionEntity,
+    ) -> DatabaseResult<SubscriptionEntity> {
+        self.update_subscription(entity).await
+    }
+
+    async fn record_delivery_success(
+        &self,
+        subscription_id: &str,
+    ) -> DatabaseResult<Option<SubscriptionEntity>> {
+        let mut state = lock(&self.state);
+        let Some(subscription) = state.subscriptions.get_mut(subscription_id) else {
+            return Ok(None);
+        };
+
+        subscription.delivery.last_success = Some(now());
+        subscription.delivery.consecutive_failures = 0;
+        subscription.delivery.last_error = None;
+        Ok(Some(subscription.clone()))
+    }
+
+    async fn record_delivery_failure(
+        &self,
+        subscription_id: &str,
+        error: &str,
+        pause_after: i32,
+    ) -> DatabaseResult<Option<SubscriptionEntity>> {
+        let mut state = lock(&self.state);
+        let Some(subscription) = state.subscriptions.get_mut(subscription_id) else {
+            return Ok(None);
+        };
+
+        subscription.delivery.consecutive_failures += 1;
+        subscription.delivery.last_error = Some(error.to_string());
+        if subscription.status == subscription_status::ACTIVE
+            && subscription.delivery.consecutive_failures >= pause_after
+        {
+            subscription.status = subscription_status::PAUSED.to_string();
+        }
+        Ok(Some(subscription.clone()))
+    }
+
+    async fn get_push_subscriptions(
+        &self,
+        collection_id: i32,
+    ) -> DatabaseResult<Vec<SubscriptionEntity>> {
+        let state = lock(&self.state);
+        Ok(state
+            .subscriptions
+            .values()
+            .filter(|s| {
+                s.collection_id == collection_id
+                    && s.status == subscription_status::ACTIVE
+                    && s.push.is_some()
+            })
+            .cloned()
+            .collect())
+    }
+
+    async fn create_subscription_deliveries(
+        &self,
+        subscription_ids: &[String],
+        content_block_id: i32,
+    ) -> DatabaseResult<u64> {
+        let mut state = lock(&self.state);
+        let mut queued = 0;
+        for subscription_id in subscription_ids {
+            let exists = state.deliveries.values().any(|d| {
+                d.subscription_id == *subscription_id && d.content_block_id == content_block_id
+            });
+            if exists
+                || !state.subscriptions.contains_key(subscription_id)
+                || !state.blocks.contains_key(&content_block_id)
+            {
+                continue;
+            }
+
+            state.next_delivery_id += 1;
+            let id = state.next_delivery_id;
+            state.deliveries.insert(
+                id,
+                SubscriptionDeliveryEntity {
+                    id,
+                    subscription_id: subscription_id.clone(),
+                    content_block_id,
+                    status: delivery_status::PENDING.to_string(),
+                    attempts: 0,
+                    last_error: None,
+                    date_created: now(),
+                    last_attempt_at: None,
+                },
+            );
+            queued += 1;
+        }
+        Ok(queued)
+    }
+
+    async fn claim_pending_deliveries(
+        &self,
+        limit: i64,
+        lease: Duration,
+    ) -> DatabaseResult<Vec<SubscriptionDeliveryEntity>> {
+        let mut state = lock(&self.state);
+        let now = now();
+        let claimed: Vec<SubscriptionDeliveryEntity> = state
+            .deliveries
+            .values()
+            .filter(|d| {
+                d.status == delivery_status::PENDING
+                    && state
+                        .delivery_claims
+                        .get(&d.id)
+                        .is_none_or(|until| *until < now)
+                    && state
+                        .subscriptions
+                        .get(&d.subscription_id)
+                        .is_some_and(|s| s.status == subscription_status::ACTIVE)
+            })
+            .take(usize::try_from(limit).unwrap_or(0))
+            .cloned()
+            .collect();
+        for delivery in &claimed {
+            state.delivery_claims.insert(delivery.id, now + lease);
+        }
+        Ok(claimed)
+    }
+
+    async fn get_subscription_deliveries(
+        &self,
+        status: Option<&str>,
+        subscription_id: Option<&str>,
+        limit: i64,
+    ) -> DatabaseResult<Vec<SubscriptionDeliveryEntity>> {
+        let state = lock(&self.state);
+        Ok(state
+            .deliveries
+            .values()
+            .rev()
+            .filter(|d| status.is_none_or(|s| d.status == s))
+            .filter(|d| subscription_id.is_none_or(|s| d.subscription_id == s))
+            .take(usize::try_from(limit).unwrap_or(0))
+            .cloned()
+            .collect())
+    }
+
+    async fn record_subscription_deliveries(
+        &self,
+        ids: &[i32],
+        status: &str,
+        attempts: i32,
+        error: Option<&str>,
+    ) -> DatabaseResult<u64> {
+        let mut state = lock(&self.state);
+        let state = &mut *state;
+        let mut recorded = 0;
+        for id in ids {
+            let Some(delivery) = state.deliveries.get_mut(id) else {
+                continue;
+            };
+            delivery.status = status.to_string();
+            delivery.attempts += attempts;
+            delivery.last_error = error.map(str::to_string);
+            delivery.last_attempt_at = Some(now());
+            state.delivery_claims.remove(id);
+            recorded += 1;
+        }
+        Ok(recorded)
+    }
+
+    async fn requeue_failed_deliveries(
+        &self,
+        ids: &[i32],
+        subscription_id: Option<&str>,
+    ) -> DatabaseResult<u64> {
+        let mut state = lock(&self.state);
+        let state = &mut *state;
+        let mut requeued = 0;
+        for delivery in state.deliveries.values_mut() {
+            if delivery.status == delivery_status::FAILED
+                && (ids.is_empty() || ids.contains(&delivery.id))
+                && subscription_id.is_none_or(|s| delivery.subscription_id == s)
+            {
+                delivery.status = delivery_status::PENDING.to_string();
+                state.delivery_claims.remove(&delivery.id);
+                requeued += 1;
+            }
+        }
+        Ok(requeued)
+    }
+
+    async fn get_content_blocks_by_ids(
+        &self,
+        ids: &[i32],
+    ) -> DatabaseResult<Vec<ContentBlockEntity>> {
+        let state = lock(&self.state);
+        let mut blocks = state.blocks_of(ids);
+        blocks.sort_by_key(|b| (b.timestamp_label, b.id));
+        blocks.dedup_by_key(|b| b.id);
+        Ok(blocks)
+    }
+}
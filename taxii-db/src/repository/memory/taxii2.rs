@@ -0,0 +1,1435 @@
+//! In-memory TAXII 2.x repository.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Mutex;
+
+use chrono::{DateTime, Duration, SubsecRound, Utc};
+use futures::StreamExt;
+use serde_json::Value;
+use uuid::Uuid;
+
+use super::{lock, next_after, now};
+use crate::error::{DatabaseError, DatabaseResult};
+use crate::models::collection_stats::{TypeStats, aggregate_stats};
+use crate::models::taxii2::job_detail_status;
+use crate::models::taxii2::{
+    PaginatedResult, PaginationCursor, Taxii2QueryParams, VersionSelector, get_next_param,
+    get_search_next_param, get_versions_next_param,
+};
+use crate::repository::taxii2::{DEFAULT_STALE_JOB_AGE, get_object_version};
+use crate::repository::traits::Taxii2Repository;
+use crate::stream::{RowStream, error_stream};
+
+use taxii_core::{
+    ApiRoot, BulkInsertOutcome, BulkInsertReport, CleanupStats, Collection, CollectionStats,
+    CollisionPolicy, DeletionRecord, Job, JobDetail, JobDetails, ManifestRecord, PurgeStats,
+    STIXObject, ValidationRule, VersionRecord, taxii2_datetimeformat,
+};
+
+/// A stored version of an object.
+#[derive(Debug, Clone)]
+struct StoredObject {
+    pk: Uuid,
+    object: STIXObject,
+    deleted_at: Option<DateTime<Utc>>,
+    deleted_by: Option<i32>,
+}
+
+impl StoredObject {
+    fn is_live(&self) -> bool {
+        self.deleted_at.is_none()
+    }
+
+    /// Position among the versions of the object (see
+    /// [`PaginationCursor::VERSION_ORDER_BY`]).
+    fn version_key(&self) -> (DateTime<Utc>, DateTime<Utc>, Uuid) {
+        (self.object.version, self.object.date_added, self.pk)
+    }
+}
+
+/// How `match[version]` selects versions.
+enum VersionMatch {
+    All,
+    First,
+    Last,
+    Exact(Vec<DateTime<Utc>>),
+    None,
+}
+
+impl VersionMatch {
+    fn parse(match_version: Option<&[String]>, default: &str) -> Self {
+        let values = match match_version {
+            Some(values) => values.to_vec(),
+            None => vec![default.to_string()],
+        };
+        if values.iter().any(|v| v == "all") {
+            return Self::All;
+        }
+        if values.iter().any(|v| v == "first") {
+            return Self::First;
+        }
+        if values.iter().any(|v| v == "last") {
+            return Self::Last;
+        }
+        let versions: Vec<_> = values
+            .iter()
+            .filter_map(|v| DateTime::parse_from_rfc3339(v).ok())
+            .map(|v| v.with_timezone(&Utc).round_subsecs(6))
+            .collect();
+        if versions.is_empty() && values.is_empty() {
+            Self::None
+        } else {
+            Self::Exact(versions)
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    api_roots: Vec<ApiRoot>,
+    collections: Vec<Collection>,
+    objects: Vec<StoredObject>,
+    jobs: Vec<Job>,
+    /// Latest date added of each collection.
+    last_added: HashMap<String, DateTime<Utc>>,
+}
+
+impl State {
+    /// Versions in a collection, deleted ones included.
+    fn objects_in<'a>(
+        &'a self,
+        collection_id: &str,
+    ) -> impl Iterator<Item = &'a StoredObject> + use<'a> {
+        let collection_id = collection_id.to_string();
+        self.objects
+            .iter()
+            .filter(move |o| o.object.collection_id == collection_id)
+    }
+
+    fn live_in<'a>(
+        &'a self,
+        collection_id: &str,
+    ) -> impl Iterator<Item = &'a StoredObject> + use<'a> {
+        let collection_id = collection_id.to_string();
+        self.objects
+            .iter()
+            .filter(move |o| o.is_live() && o.object.collection_id == collection_id)
+    }
+
+    /// Versions matching `params` with their search rank, in result order.
+    ///
+    /// Mirrors the filtered query of the database implementation.
+    fn select<'s>(
+        &'s self,
+        collection_id: &str,
+        params: &Taxii2QueryParams<'_>,
+        search: Option<&SearchQuery>,
+    ) -> Vec<(i32, &'s StoredObject)> {
+        // Versions considered when picking the first or last version
+        let counts = |o: &StoredObject| {
+            (params.include_deleted || o.is_live())
+                && params.added_after.is_none_or(|aa| o.object.date_added > aa)
+                && params
+                    .match_spec_version
+                    .is_none_or(|s| s.contains(&o.object.spec_version))
+        };
+
+        let version_match = VersionMatch::parse(params.match_version, "last");
+        let mut extremes: HashMap<&str, (DateTime<Utc>, DateTime<Utc>, Uuid)> = HashMap::new();
+        if matches!(version_match, VersionMatch::First | VersionMatch::Last) {
+            let first = matches!(version_match, VersionMatch::First);
+            for o in self.objects_in(collection_id).filter(|o| counts(o)) {
+                let key = o.version_key();
+                let extreme = extremes.entry(o.object.id.as_str()).or_insert(key);
+                if (first && key < *extreme) || (!first && key > *extreme) {
+                    *extreme = key;
+                }
+            }
+        }
+
+        let mut selected: Vec<(i32, &StoredObject)> = self
+            .objects_in(collection_id)
+            .filter(|o| counts(o))
+            .filter(|o| params.match_id.is_none_or(|ids| ids.contains(&o.object.id)))
+            .filter(|o| {
+                params
+                    .match_type
+                    .is_none_or(|types| types.contains(&o.object.stix_type))
+            })
+            .filter(|o| {
+                params
+                    .match_properties
+                    .unwrap_or_default()
+                    .iter()
+                    .all(|f| f.matches(&o.object.serialized_data))
+            })
+            .filter(|o| match &version_match {
+                VersionMatch::All | VersionMatch::None => true,
+                VersionMatch::First | VersionMatch::Last => {
+                    extremes.get(o.object.id.as_str()) == Some(&o.version_key())
+                }
+                VersionMatch::Exact(versions) => versions.contains(&o.object.version),
+            })
+            .filter_map(|o| match search {
+                Some(query) => query.rank(&o.object.serialized_data).map(|rank| (rank, o)),
+                None => Some((0, o)),
+            })
+            .filter(|(rank, o)| {
+                params.next.is_none_or(|cursor| {
+                    let key = (o.object.date_added, o.object.id.as_str());
+                    let after = (cursor.date_added, cursor.object_id.as_str());
+                    if search.is_some() {
+                        (*rank, key) > (cursor.bind_rank(), after)
+                    } else {
+                        key > after
+                    }
+                })
+            })
+            .collect();
+
+        selected.sort_by(|(ra, a), (rb, b)| {
+            (ra, a.object.date_added, &a.object.id).cmp(&(rb, b.object.date_added, &b.object.id))
+        });
+        selected
+    }
+
+    /// One page of [`Self::select`], with its `next` cursor.
+    fn page(
+        &self,
+        collection_id: &str,
+        params: &Taxii2QueryParams<'_>,
+        search: Option<&SearchQuery>,
+    ) -> PaginatedResult<Vec<STIXObject>> {
+        let mut selected = self.select(collection_id, params, search);
+
+        let more = params.limit.is_some_and(|lim| selected.len() as i64 > lim);
+        if let Some(lim) = params.limit {
+            selected.truncate(lim as usize);
+        }
+
+        let next = if more {
+            selected.last().map(|(rank, last)| {
+                let date_added = last.object.date_added.naive_utc();
+                if search.is_some() {
+                    get_search_next_param(*rank, &date_added, &last.object.id)
+                } else {
+                    get_next_param(&date_added, &last.object.id)
+                }
+            })
+        } else {
+            None
+        };
+
+        let objects = selected
+            .into_iter()
+            .map(|(_, o)| o.object.clone())
+            .collect();
+        PaginatedResult::new(objects, more, next)
+    }
+
+    /// Add objects to a collection like the bulk insert of the database
+    /// implementation.
+    ///
+    /// Objects whose index is in `rejected` fail with its message, and
+    /// versions existing with other content are handled by `policy`.
+    fn add_objects(
+        &mut self,
+        api_root_id: &str,
+        collection_id: &str,
+        objects: &[Value],
+        rejected: &BTreeMap<usize, String>,
+        policy: CollisionPolicy,
+    ) -> BulkInsertReport {
+        let job_id = Uuid::new_v4().to_string();
+        let request_timestamp = now();
+
+        let mut outcomes = Vec::with_capacity(objects.len());
+        let mut collisions = BTreeMap::new();
+        for (index, obj) in objects.iter().enumerate() {
+            if rejected.contains_key(&index) {
+                outcomes.push(BulkInsertOutcome::Failed);
+                continue;
+            }
+            let Some((stix_id, stix_type)) = obj["id"]
+                .as_str()
+                .and_then(|id| id.split_once("--").map(|(stix_type, _)| (id, stix_type)))
+            else {
+                outcomes.push(BulkInsertOutcome::Failed);
+                continue;
+            };
+            let version = get_object_version(obj).round_subsecs(6);
+            let serialized_data: Value = obj
+                .as_object()
+                .map(|o| {
+                    let filtered: serde_json::Map<String, Value> = o
+                        .iter()
+                        .filter(|(k, _)| !["id", "type", "spec_version"].contains(&k.as_str()))
+                        .map(|(k, v)| (k.clone(), v.clone()))
+                        .collect();
+                    Value::Object(filtered)
+                })
+                .unwrap_or_default();
+            let date_added = next_after(self.last_added.get(collection_id).copied());
+            let spec_version = obj["spec_version"].as_str().unwrap_or("2.1").to_string();
+
+            let existing = self.objects.iter_mut().find(|o| {
+                o.object.collection_id == collection_id
+                    && o.object.id == stix_id
+                    && o.object.version == version
+            });
+            let outcome = match existing {
+                Some(existing)
+                    if existing.is_live() && existing.object.serialized_data == serialized_data =>
+                {
+                    BulkInsertOutcome::Duplicate
+                }
+                Some(existing) if existing.is_live() => match policy {
+                    CollisionPolicy::Reject => {
+                        collisions.insert(
+                            index,
+                            format!(
+                                "Object {stix_id} version {} already exists with different content",
+                                taxii2_datetimeformat(&version)
+                            ),
+                        );
+                        BulkInsertOutcome::Failed
+                    }
+                    CollisionPolicy::KeepExisting => BulkInsertOutcome::Kept,
+                    // Replaced content counts as newly added
+                    CollisionPolicy::Overwrite => {
+                        existing.object.spec_version = spec_version;
+                        existing.object.serialized_data = serialized_data;
+                        existing.object.date_added = date_added;
+                        BulkInsertOutcome::Overwritten
+                    }
+                },
+                // Re-adding a deleted version restores it as newly added
+                Some(existing) => {
+                    existing.object.spec_version = spec_version;
+                    existing.object.serialized_data = serialized_data;
+                    existing.object.date_added = date_added;
+                    existing.deleted_at = None;
+                    existing.deleted_by = None;
+                    BulkInsertOutcome::Inserted
+                }
+                None => {
+                    self.objects.push(StoredObject {
+                        pk: Uuid::new_v4(),
+                        object: STIXObject {
+                            id: stix_id.to_string(),
+                            collection_id: collection_id.to_string(),
+                            stix_type: stix_type.to_string(),
+                            spec_version,
+                            date_added,
+                            version,
+                            serialized_data,
+                        },
+                        deleted_at: None,
+                        deleted_by: None,
+                    });
+                    BulkInsertOutcome::Inserted
+                }
+            };
+            if matches!(
+                outcome,
+                BulkInsertOutcome::Inserted | BulkInsertOutcome::Overwritten
+            ) {
+                self.last_added
+                    .insert(collection_id.to_string(), date_added);
+            }
+            outcomes.push(outcome);
+        }
+
+        let mut details = JobDetails::default();
+        for (index, (obj, outcome)) in objects.iter().zip(&outcomes).enumerate() {
+            let failed = *outcome == BulkInsertOutcome::Failed;
+            let detail = JobDetail {
+                id: Uuid::new_v4().to_string(),
+                job_id: job_id.clone(),
+                stix_id: obj["id"].as_str().unwrap_or_default().to_string(),
+                version: get_object_version(obj).round_subsecs(6),
+                message: match outcome {
+                    BulkInsertOutcome::Failed => rejected
+                        .get(&index)
+                        .or_else(|| collisions.get(&index))
+                        .cloned()
+                        .unwrap_or_else(|| "Object has no valid STIX identifier".to_string()),
+                    BulkInsertOutcome::Overwritten => {
+                        "Replaced the existing content of this version".to_string()
+                    }
+                    BulkInsertOutcome::Kept => {
+                        "Kept the existing content of this version".to_string()
+                    }
+                    _ => String::new(),
+                },
+                status: if failed {
+                    job_detail_status::FAILURE
+                } else {
+                    job_detail_status::SUCCESS
+                }
+                .to_string(),
+            };
+            if failed {
+                details.failure.push(detail);
+            } else {
+                details.success.push(detail);
+            }
+        }
+
+        let total_count = i32::try_from(objects.len()).unwrap_or(i32::MAX);
+        let failure_count = i32::try_from(details.failure.len()).unwrap_or(i32::MAX);
+        let job = Job {
+            id: job_id,
+            api_root_id: api_root_id.to_string(),
+            status: "complete".to_string(),
+            request_timestamp,
+            completed_timestamp: Some(now()),
+            total_count,
+            success_count: total_count - failure_count,
+            failure_count,
+            pending_count: 0,
+            details,
+        };
+        self.jobs.push(job.clone());
+
+        BulkInsertReport { job, outcomes }
+    }
+}
+
+/// In-memory implementation of [`Taxii2Repository`].
+///
+/// Behaves like [`crate::DbTaxii2Repository`] (see the [module
+/// docs](super)); seed it with [`Self::builder`].
+#[derive(Debug)]
+pub struct InMemoryTaxii2Repository {
+    state: Mutex<State>,
+    hard_delete: bool,
+    stale_job_age: Duration,
+}
+
+impl Default for InMemoryTaxii2Repository {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InMemoryTaxii2Repository {
+    /// Create an empty repository.
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(State::default()),
+            hard_delete: false,
+            stale_job_age: DEFAULT_STALE_JOB_AGE,
+        }
+    }
+
+    /// Create a builder seeding a repository with fixtures.
+    pub fn builder() -> InMemoryTaxii2Builder {
+        InMemoryTaxii2Builder::default()
+    }
+
+    /// Remove deleted objects immediately instead of keeping tombstones.
+    #[must_use]
+    pub fn with_hard_delete(mut self, hard_delete: bool) -> Self {
+        self.hard_delete = hard_delete;
+        self
+    }
+
+    /// Set the age after which a job that never completed is marked failed
+    /// by [`Taxii2Repository::cleanup_jobs`].
+    #[must_use]
+    pub fn with_stale_job_age(mut self, stale_job_age: Duration) -> Self {
+        self.stale_job_age = stale_job_age;
+        self
+    }
+}
+
+/// Builder seeding an [`InMemoryTaxii2Repository`] with fixtures.
+///
+/// Objects are added in order like [`Taxii2Repository::add_objects`], so
+/// they get increasing `date_added` values and the same deduplication.
+#[derive(Debug, Default)]
+pub struct InMemoryTaxii2Builder {
+    api_roots: Vec<ApiRoot>,
+    collections: Vec<Collection>,
+    objects: Vec<(String, Value)>,
+    jobs: Vec<Job>,
+    hard_delete: bool,
+    stale_job_age: Option<Duration>,
+}
+
+impl InMemoryTaxii2Builder {
+    /// Add an API root.
+    #[must_use]
+    pub fn api_root(mut self, api_root: ApiRoot) -> Self {
+        self.api_roots.push(api_root);
+        self
+    }
+
+    /// Add a collection.
+    #[must_use]
+    pub fn collection(mut self, collection: Collection) -> Self {
+        self.collections.push(collection);
+        self
+    }
+
+    /// Add a STIX object to a collection.
+    #[must_use]
+    pub fn object(mut self, collection_id: &str, object: Value) -> Self {
+        self.objects.push((collection_id.to_string(), object));
+        self
+    }
+
+    /// Add STIX objects to a collection.
+    #[must_use]
+    pub fn objects(
+        mut self,
+        collection_id: &str,
+        objects: impl IntoIterator<Item = Value>,
+    ) -> Self {
+        self.objects.extend(
+            objects
+                .into_iter()
+                .map(|object| (collection_id.to_string(), object)),
+        );
+        self
+    }
+
+    /// Add a job, e.g. one still pending.
+    #[must_use]
+    pub fn job(mut self, job: Job) -> Self {
+        self.jobs.push(job);
+        self
+    }
+
+    /// Remove deleted objects immediately instead of keeping tombstones.
+    #[must_use]
+    pub fn hard_delete(mut self, hard_delete: bool) -> Self {
+        self.hard_delete = hard_delete;
+        self
+    }
+
+    /// Set the age after which a job that never completed is marked failed.
+    #[must_use]
+    pub fn stale_job_age(mut self, stale_job_age: Duration) -> Self {
+        self.stale_job_age = Some(stale_job_age);
+        self
+    }
+
+    /// Build the repository.
+    pub fn build(self) -> InMemoryTaxii2Repository {
+        let mut state = State {
+            api_roots: self.api_roots,
+            collections: self.collections,
+            jobs: self.jobs,
+            ..State::default()
+        };
+        for (collection_id, object) in self.objects {
+            let api_root_id = state
+                .collections
+                .iter()
+                .find(|c| c.id == collection_id)
+                .map(|c| c.api_root_id.clone())
+                .unwrap_or_default();
+            state.add_objects(
+                &api_root_id,
+                &collection_id,
+                std::slice::from_ref(&object),
+                &BTreeMap::new(),
+                CollisionPolicy::KeepExisting,
+            );
+        }
+
+        InMemoryTaxii2Repository {
+            state: Mutex::new(state),
+            hard_delete: self.hard_delete,
+            stale_job_age: self.stale_job_age.unwrap_or(DEFAULT_STALE_JOB_AGE),
+        }
+    }
+}
+
+fn api_root_uuid(api_root_id: &str) -> DatabaseResult<Uuid> {
+    Uuid::parse_str(api_root_id)
+        .map_err(|_| DatabaseError::NotFound(format!("Invalid API root UUID: {api_root_id}")))
+}
+
+fn collection_uuid(collection_id: &str) -> DatabaseResult<Uuid> {
+    Uuid::parse_str(collection_id)
+        .map_err(|_| DatabaseError::NotFound(format!("Invalid collection UUID: {collection_id}")))
+}
+
+fn collection_not_found(collection_id: &str) -> DatabaseError {
+    DatabaseError::NotFound(format!("Collection with id {collection_id} does not exist"))
+}
+
+impl Taxii2Repository for InMemoryTaxii2Repository {
+    fn hard_delete(&self) -> bool {
+        self.hard_delete
+    }
+
+    // ========================================================================
+    // API Root Operations
+    // ========================================================================
+
+    async fn get_api_roots(&self) -> DatabaseResult<Vec<ApiRoot>> {
+        let mut api_roots = lock(&self.state).api_roots.clone();
+        api_roots.sort_by(|a, b| a.title.cmp(&b.title));
+        Ok(api_roots)
+    }
+
+    async fn get_api_root(&self, api_root_id: &str) -> DatabaseResult<Option<ApiRoot>> {
+        Uuid::parse_str(api_root_id)
+            .map_err(|_| DatabaseError::NotFound(format!("Invalid UUID: {api_root_id}")))?;
+
+        let state = lock(&self.state);
+        Ok(state
+            .api_roots
+            .iter()
+            .find(|r| r.id == api_root_id)
+            .cloned())
+    }
+
+    async fn add_api_root(
+        &self,
+        title: &str,
+        description: Option<&str>,
+        default: bool,
+        is_public: bool,
+        api_root_id: Option<&str>,
+    ) -> DatabaseResult<ApiRoot> {
+        let id = match api_root_id {
+            Some(id_str) => Uuid::parse_str(id_str)
+                .map_err(|e| DatabaseError::InvalidData(format!("Invalid UUID '{id_str}': {e}")))?,
+            None => Uuid::new_v4(),
+        };
+
+        let mut state = lock(&self.state);
+        if state.api_roots.iter().any(|r| r.id == id.to_string()) {
+            return Err(DatabaseError::InvalidData(format!(
+                "API root with id {id} already exists"
+            )));
+        }
+        if default {
+            for api_root in &mut state.api_roots {
+                api_root.default = false;
+            }
+        }
+
+        let api_root = ApiRoot {
+            id: id.to_string(),
+            default,
+            title: title.to_string(),
+            description: description.map(String::from),
+            is_public,
+            max_content_length: None,
+        };
+        state.api_roots.push(api_root.clone());
+        Ok(api_root)
+    }
+
+    async fn update_api_root(
+        &self,
+        api_root_id: &str,
+        title: &str,
+        description: Option<&str>,
+        default: bool,
+        is_public: bool,
+        max_content_length: Option<u64>,
+    ) -> DatabaseResult<ApiRoot> {
+        api_root_uuid(api_root_id)?;
+
+        let mut state = lock(&self.state);
+        if !state.api_roots.iter().any(|r| r.id == api_root_id) {
+            return Err(DatabaseError::NotFound(format!(
+                "API root with id {api_root_id} does not exist"
+            )));
+        }
+        for api_root in &mut state.api_roots {
+            if api_root.id == api_root_id {
+                api_root.title = title.to_string();
+                api_root.description = description.map(String::from);
+                api_root.default = default;
+                api_root.is_public = is_public;
+                api_root.max_content_length = max_content_length;
+            } else if default {
+                api_root.default = false;
+            }
+        }
+        state
+            .api_roots
+            .iter()
+            .find(|r| r.id == api_root_id)
+            .cloned()
+            .ok_or_else(|| {
+                DatabaseError::NotFound(format!("API root with id {api_root_id} does not exist"))
+            })
+    }
+
+    // ========================================================================
+    // Collection Operations (TAXII 2.x)
+    // ========================================================================
+
+    async fn get_collections(&self, api_root_id: &str) -> DatabaseResult<Vec<Collection>> {
+        Uuid::parse_str(api_root_id)
+            .map_err(|_| DatabaseError::NotFound(format!("Invalid UUID: {api_root_id}")))?;
+
+        let state = lock(&self.state);
+        let mut collections: Vec<Collection> = state
+            .collections
+            .iter()
+            .filter(|c| c.api_root_id == api_root_id)
+            .cloned()
+            .collect();
+        collections.sort_by(|a, b| a.title.cmp(&b.title));
+        Ok(collections)
+    }
+
+    async fn get_collection(
+        &self,
+        api_root_id: &str,
+        collection_id_or_alias: &str,
+    ) -> DatabaseResult<Option<Collection>> {
+        api_root_uuid(api_root_id)?;
+
+        // A UUID resolves to the collection with that ID before a collection
+        // whose alias happens to be the same UUID
+        let state = lock(&self.state);
+        let in_root = || {
+            state
+                .collections
+                .iter()
+                .filter(|c| c.api_root_id == api_root_id)
+        };
+        let by_id = Uuid::parse_str(collection_id_or_alias)
+            .ok()
+            .and_then(|_| in_root().find(|c| c.id == collection_id_or_alias));
+        let collection = by_id
+            .or_else(|| in_root().find(|c| c.alias.as_deref() == Some(collection_id_or_alias)));
+
+        Ok(collection.cloned())
+    }
+
+    async fn add_collection(
+        &self,
+        api_root_id: &str,
+        title: &str,
+        description: Option<&str>,
+        alias: Option<&str>,
+        is_public: bool,
+        is_public_write: bool,
+        collection_id: Option<&str>,
+    ) -> DatabaseResult<Collection> {
+        api_root_uuid(api_root_id)?;
+        let id = match collection_id {
+            Some(id_str) => collection_uuid(id_str)?,
+            None => Uuid::new_v4(),
+        };
+
+        let mut state = lock(&self.state);
+        if !state.api_roots.iter().any(|r| r.id == api_root_id) {
+            return Err(DatabaseError::NotFound(format!(
+                "API root with id {api_root_id} does not exist"
+            )));
+        }
+        if let Some(alias) = alias
+            && state
+                .collections
+                .iter()
+                .any(|c| c.api_root_id == api_root_id && c.alias.as_deref() == Some(alias))
+        {
+            return Err(DatabaseError::InvalidData(format!(
+                "Collection with alias {alias} already exists"
+            )));
+        }
+
+        if state.collections.iter().any(|c| c.id == id.to_string()) {
+            return Err(DatabaseError::InvalidData(format!(
+                "Collection with id {id} already exists"
+            )));
+        }
+
+        let collection = Collection {
+            id: id.to_string(),
+            api_root_id: api_root_id.to_string(),
+            title: title.to_string(),
+            description: description.map(String::from),
+            alias: alias.map(String::from),
+            is_public,
+            is_public_write,
+            retention_days: None,
+            validation_rules: Vec::new(),
+            default_marking: None,
+        };
+        state.collections.push(collection.clone());
+        Ok(collection)
+    }
+
+    async fn update_collection(
+        &self,
+        collection_id: &str,
+        title: &str,
+        description: Option<&str>,
+        alias: Option<&str>,
+        is_public: bool,
+        is_public_write: bool,
+    ) -> DatabaseResult<Collection> {
+        collection_uuid(collection_id)?;
+
+        let mut state = lock(&self.state);
+        let collection = state
+            .collections
+            .iter_mut()
+            .find(|c| c.id == collection_id)
+            .ok_or_else(|| collection_not_found(collection_id))?;
+        collection.title = title.to_string();
+        collection.description = description.map(String::from);
+        collection.alias = alias.map(String::from);
+        collection.is_public = is_public;
+        collection.is_public_write = is_public_write;
+        Ok(collection.clone())
+    }
+
+    async fn delete_collection(&self, collection_id: &str) -> DatabaseResult<()> {
+        collection_uuid(collection_id)?;
+
+        let mut state = lock(&self.state);
+        let count = state.collections.len();
+        state.collections.retain(|c| c.id != collection_id);
+        if state.collections.len() == count {
+            return Err(collection_not_found(collection_id));
+        }
+        state
+            .objects
+            .retain(|o| o.object.collection_id != collection_id);
+        state.last_added.remove(collection_id);
+
+        Ok(())
+    }
+
+    async fn set_collection_retention(
+        &self,
+        collection_id: &str,
+        retention_days: Option<i32>,
+    ) -> DatabaseResult<Collection> {
+        collection_uuid(collection_id)?;
+
+        let mut state = lock(&self.state);
+        let collection = state
+            .collections
+            .iter_mut()
+            .find(|c| c.id == collection_id)
+            .ok_or_else(|| collection_not_found(collection_id))?;
+        collection.retention_days = retention_days;
+        Ok(collection.clone())
+    }
+
+    async fn set_collection_validation_rules(
+        &self,
+        collection_id: &str,
+        rules: &[ValidationRule],
+    ) -> DatabaseResult<Collection> {
+        collection_uuid(collection_id)?;
+
+        let mut state = lock(&self.state);
+        let collection = state
+            .collections
+            .iter_mut()
+            .find(|c| c.id == collection_id)
+            .ok_or_else(|| collection_not_found(collection_id))?;
+        collection.validation_rules = rules.to_vec();
+        Ok(collection.clone())
+    }
+
+    async fn set_collection_default_marking(
+        &self,
+        collection_id: &str,
+        marking: Option<&str>,
+    ) -> DatabaseResult<Collection> {
+        collection_uuid(collection_id)?;
+
+        let mut state = lock(&self.state);
+        let collection = state
+            .collections
+            .iter_mut()
+            .find(|c| c.id == collection_id)
+            .ok_or_else(|| collection_not_found(collection_id))?;
+        collection.default_marking = marking.map(String::from);
+        Ok(collection.clone())
+    }
+
+    async fn get_collections_with_retention(&self) -> DatabaseResult<Vec<Collection>> {
+        let state = lock(&self.state);
+        let mut collections: Vec<Collection> = state
+            .collections
+            .iter()
+            .filter(|c| c.retention_days.is_some())
+            .cloned()
+            .collect();
+        collections.sort_by_key(|c| Uuid::parse_str(&c.id).unwrap_or_default());
+        Ok(collections)
+    }
+
+    // ========================================================================
+    // STIX Object Operations
+    // ========================================================================
+
+    async fn get_manifest(
+        &self,
+        collection_id: &str,
+        params: &Taxii2QueryParams<'_>,
+    ) -> DatabaseResult<PaginatedResult<Vec<ManifestRecord>>> {
+        collection_uuid(collection_id)?;
+
+        let page = lock(&self.state).page(collection_id, params, None);
+        let records = page
+            .items
+            .into_iter()
+            .map(|o| ManifestRecord {
+                id: o.id,
+                date_added: o.date_added,
+                version: o.version,
+                spec_version: o.spec_version,
+            })
+            .collect();
+
+        Ok(PaginatedResult::new(records, page.more, page.next))
+    }
+
+    async fn get_objects(
+        &self,
+        collection_id: &str,
+        params: &Taxii2QueryParams<'_>,
+    ) -> DatabaseResult<PaginatedResult<Vec<STIXObject>>> {
+        collection_uuid(collection_id)?;
+
+        Ok(lock(&self.state).page(collection_id, params, None))
+    }
+
+    async fn search_objects(
+        &self,
+        collection_id: &str,
+        query: &str,
+        params: &Taxii2QueryParams<'_>,
+    ) -> DatabaseResult<PaginatedResult<Vec<STIXObject>>> {
+        collection_uuid(collection_id)?;
+
+        let query = SearchQuery::parse(query);
+        Ok(lock(&self.state).page(collection_id, params, Some(&query)))
+    }
+
+    fn stream_objects(
+        &self,
+        collection_id: &str,
+        params: &Taxii2QueryParams<'_>,
+    ) -> RowStream<STIXObject> {
+        if let Err(e) = collection_uuid(collection_id) {
+            return error_stream(e);
+        }
+
+        let state = lock(&self.state);
+        let mut objects: Vec<STIXObject> = state
+            .select(collection_id, params, None)
+            .into_iter()
+            .map(|(_, o)| o.object.clone())
+            .collect();
+        if let Some(lim) = params.limit {
+            objects.truncate(lim as usize);
+        }
+
+        futures::stream::iter(objects.into_iter().map(Ok)).boxed()
+    }
+
+    async fn add_objects(
+        &self,
+        api_root_id: &str,
+        collection_id: &str,
+        objects: &[Value],
+    ) -> DatabaseResult<Job> {
+        let report = self
+            .add_objects_bulk(api_root_id, collection_id, objects)
+            .await?;
+        Ok(report.job)
+    }
+
+    async fn add_objects_bulk(
+        &self,
+        api_root_id: &str,
+        collection_id: &str,
+        objects: &[Value],
+    ) -> DatabaseResult<BulkInsertReport> {
+        self.add_objects_bulk_with_rejections(
+            api_root_id,
+            collection_id,
+            objects,
+            &BTreeMap::new(),
+            CollisionPolicy::KeepExisting,
+            None,
+        )
+        .await
+    }
+
+    async fn add_objects_bulk_with_rejections(
+        &self,
+        api_root_id: &str,
+        collection_id: &str,
+        objects: &[Value],
+        rejected: &BTreeMap<usize, String>,
+        policy: CollisionPolicy,
+        _account_id: Option<i32>,
+    ) -> DatabaseResult<BulkInsertReport> {
+        api_root_uuid(api_root_id)?;
+        collection_uuid(collection_id)?;
+
+        let mut state = lock(&self.state);
+        if !state.collections.iter().any(|c| c.id == collection_id) {
+            return Err(collection_not_found(collection_id));
+        }
+        Ok(state.add_objects(api_root_id, collection_id, objects, rejected, policy))
+    }
+
+    async fn get_object(
+        &self,
+        collection_id: &str,
+        object_id: &str,
+        params: &Taxii2QueryParams<'_>,
+    ) -> DatabaseResult<PaginatedResult<Vec<STIXObject>>> {
+        collection_uuid(collection_id)?;
+
+        let state = lock(&self.state);
+        if !state
+            .live_in(collection_id)
+            .any(|o| o.object.id == object_id)
+        {
+            return Ok(PaginatedResult::empty());
+        }
+
+        let match_id = vec![object_id.to_string()];
+        let params_with_id = Taxii2QueryParams {
+            match_id: Some(&match_id),
+            ..*params
+        };
+        Ok(state.page(collection_id, &params_with_id, None))
+    }
+
+    async fn resolve_object_version(
+        &self,
+        collection_id: &str,
+        object_id: &str,
+        selector: VersionSelector,
+    ) -> DatabaseResult<Option<STIXObject>> {
+        collection_uuid(collection_id)?;
+
+        let state = lock(&self.state);
+        let versions = state
+            .live_in(collection_id)
+            .filter(|o| o.object.id == object_id);
+        let found = match selector {
+            VersionSelector::First => versions.min_by_key(|o| o.version_key()),
+            VersionSelector::Last => versions.max_by_key(|o| o.version_key()),
+            VersionSelector::Exact(version) => {
+                let version = version.round_subsecs(6);
+                versions
+                    .filter(|o| o.object.version == version)
+                    .max_by_key(|o| o.version_key())
+            }
+        };
+
+        Ok(found.map(|o| o.object.clone()))
+    }
+
+    async fn delete_object(
+        &self,
+        collection_id: &str,
+        object_id: &str,
+        match_version: Option<&[String]>,
+        match_spec_version: Option<&[String]>,
+        deleted_by: Option<i32>,
+    ) -> DatabaseResult<()> {
+        collection_uuid(collection_id)?;
+
+        let mut state = lock(&self.state);
+        let versions = || {
+            state
+                .live_in(collection_id)
+                .filter(|o| o.object.id == object_id)
+        };
+        let spec_matches = |o: &StoredObject| {
+            match_spec_version.is_none_or(|s| s.contains(&o.object.spec_version))
+        };
+
+        // Spec versions are not filtered for first/last, like the database
+        let pks: Vec<Uuid> = match VersionMatch::parse(match_version, "all") {
+            VersionMatch::All => versions()
+                .filter(|o| spec_matches(o))
+                .map(|o| o.pk)
+                .collect(),
+            VersionMatch::First => versions()
+                .min_by_key(|o| o.version_key())
+                .map(|o| o.pk)
+                .into_iter()
+                .collect(),
+            VersionMatch::Last => versions()
+                .max_by_key(|o| o.version_key())
+                .map(|o| o.pk)
+                .into_iter()
+                .collect(),
+            VersionMatch::Exact(exact) => versions()
+                .filter(|o| exact.contains(&o.object.version) && spec_matches(o))
+                .map(|o| o.pk)
+                .collect(),
+            VersionMatch::None => Vec::new(),
+        };
+
+        if self.hard_delete {
+            state.objects.retain(|o| !pks.contains(&o.pk));
+        } else {
+            let deleted_at = now();
+            for o in state.objects.iter_mut().filter(|o| pks.contains(&o.pk)) {
+                o.deleted_at = Some(deleted_at);
+                o.deleted_by = deleted_by;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn list_deletions(
+        &self,
+        collection_id: &str,
+        since: DateTime<Utc>,
+    ) -> DatabaseResult<Vec<DeletionRecord>> {
+        collection_uuid(collection_id)?;
+
+        let state = lock(&self.state);
+        let mut deletions: Vec<DeletionRecord> = state
+            .objects
+            .iter()
+            .filter(|o| o.object.collection_id == collection_id)
+            .filter_map(|o| {
+                let deleted_at = o.deleted_at.filter(|d| *d > since)?;
+                Some(DeletionRecord {
+                    id: o.object.id.clone(),
+                    version: o.object.version,
+                    deleted_at,
+                    deleted_by: o.deleted_by,
+                })
+            })
+            .collect();
+        deletions.sort_by(|a, b| {
+            (a.deleted_at, &a.id, a.version).cmp(&(b.deleted_at, &b.id, b.version))
+        });
+
+        Ok(deletions)
+    }
+
+    async fn purge_deleted(&self, older_than: DateTime<Utc>) -> DatabaseResult<u64> {
+        let mut state = lock(&self.state);
+        let count = state.objects.len();
+        state
+            .objects
+            .retain(|o| o.deleted_at.is_none_or(|d| d >= older_than));
+        Ok((count - state.objects.len()) as u64)
+    }
+
+    async fn purge_expired(
+        &self,
+        collection_id: &str,
+        cutoff: DateTime<Utc>,
+    ) -> DatabaseResult<PurgeStats> {
+        collection_uuid(collection_id)?;
+
+        let mut state = lock(&self.state);
+        let expired = |o: &StoredObject| {
+            o.object.collection_id == collection_id && o.object.date_added < cutoff
+        };
+
+        let mut stats = PurgeStats::default();
+        if self.hard_delete {
+            let count = state.objects.len();
+            state.objects.retain(|o| !expired(o));
+            stats.purged = (count - state.objects.len()) as u64;
+        } else {
+            let deleted_at = now();
+            for o in state
+                .objects
+                .iter_mut()
+                .filter(|o| o.is_live() && expired(o))
+            {
+                o.deleted_at = Some(deleted_at);
+                o.deleted_by = None;
+                stats.purged += 1;
+            }
+        }
+
+        Ok(stats)
+    }
+
+    async fn collection_stats(&self, collection_id: &str) -> DatabaseResult<CollectionStats> {
+        collection_uuid(collection_id)?;
+
+        let state = lock(&self.state);
+        let mut by_type: BTreeMap<&str, (i64, HashSet<&str>, DateTime<Utc>)> = BTreeMap::new();
+        for o in state.live_in(collection_id) {
+            let entry = by_type.entry(o.object.stix_type.as_str()).or_insert((
+                0,
+                HashSet::new(),
+                o.object.date_added,
+            ));
+            entry.0 += 1;
+            entry.1.insert(o.object.id.as_str());
+            entry.2 = entry.2.max(o.object.date_added);
+        }
+
+        let types = by_type
+            .into_iter()
+            .map(
+                |(object_type, (object_count, ids, latest_added))| TypeStats {
+                    object_type: object_type.to_string(),
+                    object_count,
+                    distinct_ids: ids.len() as i64,
+                    latest_added: Some(latest_added),
+                },
+            )
+            .collect();
+
+        Ok(aggregate_stats(types, Utc::now()))
+    }
+
+    async fn get_versions(
+        &self,
+        collection_id: &str,
+        object_id: &str,
+        limit: Option<i64>,
+        added_after: Option<DateTime<Utc>>,
+        next_kwargs: Option<PaginationCursor>,
+        match_spec_version: Option<&[String]>,
+    ) -> DatabaseResult<PaginatedResult<Vec<VersionRecord>>> {
+        collection_uuid(collection_id)?;
+
+        let after = match &next_kwargs {
+            Some(cursor) => {
+                let pk = Uuid::parse_str(&cursor.object_id).map_err(|_| {
+                    DatabaseError::InvalidData(format!(
+                        "Invalid versions cursor key: {}",
+                        cursor.object_id
+                    ))
+                })?;
+                Some((cursor.bind_version().and_utc(), cursor.date_added, pk))
+            }
+            None => None,
+        };
+
+        let state = lock(&self.state);
+        let mut versions: Vec<&StoredObject> = state
+            .live_in(collection_id)
+            .filter(|o| o.object.id == object_id)
+            .collect();
+        if versions.is_empty() {
+            return Ok(PaginatedResult::empty());
+        }
+
+        versions.retain(|o| {
+            added_after.is_none_or(|aa| o.object.date_added > aa)
+                && after.is_none_or(|after| o.version_key() > after)
+                && match_spec_version.is_none_or(|s| s.contains(&o.object.spec_version))
+        });
+        versions.sort_by_key(|o| o.version_key());
+
+        let more = limit.is_some_and(|lim| versions.len() as i64 > lim);
+        if let Some(lim) = limit {
+            versions.truncate(lim as usize);
+        }
+
+        let next = if more {
+            versions.last().map(|o| {
+                get_versions_next_param(
+                    &o.object.version.naive_utc(),
+                    &o.object.date_added.naive_utc(),
+                    &o.pk,
+                )
+            })
+        } else {
+            None
+        };
+
+        let records = versions
+            .into_iter()
+            .map(|o| VersionRecord {
+                date_added: o.object.date_added,
+                version: o.object.version,
+            })
+            .collect();
+
+        Ok(PaginatedResult::new(records, more, next))
+    }
+
+    // ========================================================================
+    // Job Operations
+    // ========================================================================
+
+    async fn get_job_and_details(
+        &self,
+        api_root_id: &str,
+        job_id: &str,
+    ) -> DatabaseResult<Option<Job>> {
+        api_root_uuid(api_root_id)?;
+        Uuid::parse_str(job_id)
+            .map_err(|_| DatabaseError::NotFound(format!("Invalid job UUID: {job_id}")))?;
+
+        let state = lock(&self.state);
+        Ok(state
+            .jobs
+            .iter()
+            .find(|j| j.id == job_id && j.api_root_id == api_root_id)
+            .cloned())
+    }
+
+    async fn cleanup_jobs(
+        &self,
+        older_than: DateTime<Utc>,
+        keep_incomplete: bool,
+    ) -> DatabaseResult<CleanupStats> {
+        let mut stats = CleanupStats::default();
+        let mut state = lock(&self.state);
+
+        if keep_incomplete {
+            let stale_cutoff = Utc::now() - self.stale_job_age;
+            for job in state
+                .jobs
+                .iter_mut()
+                .filter(|j| j.status == "pending" && j.request_timestamp < stale_cutoff)
+            {
+                job.status = "complete".to_string();
+                job.completed_timestamp = Some(now());
+                job.failure_count += job.pending_count;
+                job.pending_count = 0;
+                for mut detail in std::mem::take(&mut job.details.pending) {
+                    detail.status = job_detail_status::FAILURE.to_string();
+                    detail.message = "Job did not complete".to_string();
+                    job.details.failure.push(detail);
+                }
+                stats.jobs_failed += 1;
+            }
+        }
+
+        let removed = |job: &Job| {
+            (job.status == "complete" && job.completed_timestamp.is_some_and(|t| t < older_than))
+                || (!keep_incomplete
+                    && job.status == "pending"
+                    && job.request_timestamp < older_than)
+        };
+        for job in state.jobs.iter().filter(|j| removed(j)) {
+            stats.jobs_removed += 1;
+            stats.details_removed += (job.details.success.len()
+                + job.details.failure.len()
+                + job.details.pending.len()) as u64;
+        }
+        state.jobs.retain(|j| !removed(j));
+
+        Ok(stats)
+    }
+}
+
+// ============================================================================
+// Search
+// ============================================================================
+
+/// A term of a search query: a word or quoted phrase, possibly negated.
+#[derive(Debug)]
+struct SearchTerm {
+    words: Vec<String>,
+    negated: bool,
+}
+
+impl SearchTerm {
+    fn found_in(&self, words: &[String]) -> bool {
+        words
+            .windows(self.words.len())
+            .any(|window| window == self.words.as_slice())
+    }
+}
+
+/// A search query in web search syntax: terms are all required, `or`
+/// separates alternatives and `-` negates a term.
+#[derive(Debug)]
+struct SearchQuery {
+    alternatives: Vec<Vec<SearchTerm>>,
+}
+
+impl SearchQuery {
+    fn parse(query: &str) -> Self {
+        let mut alternatives = vec![Vec::new()];
+        let mut rest = query.trim_start();
+        while !rest.is_empty() {
+            let negated = rest.starts_with('-');
+            if negated {
+                rest = &rest[1..];
+            }
+            let (token, quoted) = if let Some(quoted) = rest.strip_prefix('"') {
+                let end = quoted.find('"').unwrap_or(quoted.len());
+                rest = quoted.get(end + 1..).unwrap_or_default();
+                (&quoted[..end], true)
+            } else {
+                let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+                let token = &rest[..end];
+                rest = &rest[end..];
+                (token, false)
+            };
+            rest = rest.trim_start();
+
+            if !quoted && !negated && token.eq_ignore_ascii_case("or") {
+                alternatives.push(Vec::new());
+                continue;
+            }
+            let words = search_words(token);
+            if !words.is_empty()
+                && let Some(terms) = alternatives.last_mut()
+            {
+                terms.push(SearchTerm { words, negated });
+            }
+        }
+        alternatives.retain(|terms| !terms.is_empty());
+
+        Self { alternatives }
+    }
+
+    fn matches(&self, words: &[String]) -> bool {
+        self.alternatives.iter().any(|terms| {
+            terms
+                .iter()
+                .all(|term| term.found_in(words) != term.negated)
+        })
+    }
+
+    /// Search rank of an object (0 is most relevant), or `None` if it does
+    /// not match.
+    fn rank(&self, data: &Value) -> Option<i32> {
+        let field = |name: &str| match data.get(name) {
+            Some(Value::String(s)) => s.clone(),
+            Some(value) => value.to_string(),
+            None => String::new(),
+        };
+        let mut words = search_words(&format!("{} {}", field("name"), field("value")));
+        if self.matches(&words) {
+            return Some(0);
+        }
+        words.extend(search_words(&field("labels")));
+        if self.matches(&words) {
+            return Some(1);
+        }
+        words.extend(search_words(&format!(
+            "{} {}",
+            field("description"),
+            field("pattern")
+        )));
+        self.matches(&words).then_some(2)
+    }
+}
+
+/// Lowercased words of a text.
+fn search_words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
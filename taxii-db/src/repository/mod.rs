@@ -7,14 +7,30 @@
 //!
 //! - **Traits**: [`Taxii1Repository`] and [`Taxii2Repository`] define the interface
 //! - **Implementations**: [`DbTaxii1Repository`] and [`DbTaxii2Repository`] provide PostgreSQL implementations
+//! - **In-memory**: [`InMemoryTaxii1Repository`] and [`InMemoryTaxii2Repository`] implement the
+//!   same semantics without a database, for tests (`test-util` feature)
 //! - **Conversions**: `From` implementations for model-to-entity transformations
 
+#[cfg(test)]
+mod conformance;
 pub mod conversions;
+#[cfg(any(test, feature = "test-util"))]
+pub mod memory;
 pub mod taxii1;
 pub mod taxii2;
 pub mod traits;
 
 // Conversions are used via From trait, no need to re-export
-pub use taxii1::DbTaxii1Repository;
-pub use taxii2::{DbTaxii2Repository, get_object_version};
+pub use taxii1::{DEFAULT_DEDUP_WINDOW, DbTaxii1Repository};
+pub use taxii2::{DEFAULT_STALE_JOB_AGE, DbTaxii2Repository, get_object_version};
 pub use traits::{Taxii1Repository, Taxii2Repository};
+
+#[cfg(any(test, feature = "test-util"))]
+pub use memory::{
+    InMemoryTaxii1Builder, InMemoryTaxii1Repository, InMemoryTaxii2Builder,
+    InMemoryTaxii2Repository,
+};
+
+/// Maximum number of rows removed by one statement when purging expired
+/// content, so purges never hold locks on a large part of a table.
+pub(crate) const PURGE_BATCH_SIZE: i64 = 1000;
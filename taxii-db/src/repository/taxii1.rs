@@ -3,21 +3,29 @@
 //! Provides database operations for TAXII 1.x entities including services,
 //! collections, content blocks, inbox messages, result sets, and subscriptions.
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
+use futures::StreamExt;
 use serde_json::json;
 use tracing::debug;
 use uuid::Uuid;
 
 use crate::error::{DatabaseError, DatabaseResult};
+use crate::models::collection_stats::CachedCollectionStats;
+use crate::models::retention_purge::protocol;
 use crate::models::taxii1::{
-    ContentBlock, DataCollection, InboxMessage, ResultSet, Service, Subscription,
+    CollectionBridge, ContentBlock, DataCollection, InboxMessage, NewSubscription, ResultSet,
+    Service, Subscription, SubscriptionDelivery, content_digest,
 };
 use crate::pool::TaxiiPool;
+use crate::repository::PURGE_BATCH_SIZE;
 use crate::repository::traits::Taxii1Repository;
+use crate::stream::RowStream;
+use crate::transaction::TaxiiTransaction;
 
 use taxii_core::{
-    CollectionEntity, ContentBindingEntity, ContentBlockEntity, InboxMessageEntity,
-    ResultSetEntity, ServiceEntity, SubscriptionEntity,
+    CollectionBridgeEntity, CollectionEntity, CollectionStats, ContentBindingEntity,
+    ContentBlockEntity, InboxMessageEntity, PurgeStats, ResultSetEntity, ServiceEntity,
+    SubscriptionDeliveryEntity, SubscriptionEntity,
 };
 
 /// PostgreSQL implementation of [`Taxii1Repository`].
@@ -26,18 +34,186 @@ use taxii_core::{
 /// database operations.
 pub struct DbTaxii1Repository {
     pool: TaxiiPool,
+    reads: TaxiiPool,
+    dedup_window: Duration,
 }
 
+/// How far back collections that deduplicate content look for an identical
+/// content block.
+pub const DEFAULT_DEDUP_WINDOW: Duration = Duration::hours(24);
+
 impl DbTaxii1Repository {
     /// Create a new repository instance.
+    ///
+    /// Deduplicating collections use [`DEFAULT_DEDUP_WINDOW`] (see
+    /// [`Self::with_dedup_window`]).
+    ///
+    /// Reads that do not need to see a preceding write use the pool's read
+    /// pool (see [`TaxiiPool::reader`]); everything else uses the primary.
     pub fn new(pool: TaxiiPool) -> Self {
-        Self { pool }
+        Self {
+            reads: pool.reader(),
+            pool,
+            dedup_window: DEFAULT_DEDUP_WINDOW,
+        }
+    }
+
+    /// Set how far back collections that deduplicate content look for an
+    /// identical content block.
+    #[must_use]
+    pub fn with_dedup_window(mut self, dedup_window: Duration) -> Self {
+        self.dedup_window = dedup_window;
+        self
     }
 
     /// Get pool reference.
     pub fn pool(&self) -> &TaxiiPool {
         &self.pool
     }
+
+    /// Get the pool reads are sent to.
+    pub fn read_pool(&self) -> &TaxiiPool {
+        &self.reads
+    }
+
+    /// Create an inbox message as part of a unit of work.
+    ///
+    /// Like [`Taxii1Repository::create_inbox_message`], but written in `tx`.
+    pub async fn create_inbox_message_in(
+        &self,
+        tx: &mut TaxiiTransaction,
+        entity: &InboxMessageEntity,
+    ) -> DatabaseResult<InboxMessageEntity> {
+        let destination_collections = if entity.destination_collections.is_empty() {
+            None
+        } else {
+            Some(serde_json::to_string(&entity.destination_collections).unwrap_or_default())
+        };
+
+        let params = crate::models::taxii1::NewInboxMessage {
+            message_id: &entity.message_id,
+            original_message: &entity.original_message,
+            content_block_count: entity.content_block_count,
+            destination_collections: destination_collections.as_deref(),
+            service_id: &entity.service_id,
+            result_id: entity.result_id.as_deref(),
+            record_count: entity.record_count,
+            partial_count: entity.partial_count,
+            subscription_collection_name: entity.subscription_collection_name.as_deref(),
+            subscription_id: entity.subscription_id.as_deref(),
+            exclusive_begin_timestamp_label: entity.exclusive_begin_timestamp_label,
+            inclusive_end_timestamp_label: entity.inclusive_end_timestamp_label,
+        };
+
+        let message = InboxMessage::create_in(tx.conn(), &params).await?;
+        Ok(message.into())
+    }
+
+    /// Create a content block as part of a unit of work.
+    ///
+    /// Like [`Taxii1Repository::create_content_block`], but the block, its
+    /// collection links and volume updates are written in `tx`.
+    pub async fn create_content_block_in(
+        &self,
+        tx: &mut TaxiiTransaction,
+        entity: &ContentBlockEntity,
+        collection_ids: Option<&[i32]>,
+        _service_id: Option<&str>,
+    ) -> DatabaseResult<ContentBlockEntity> {
+        let (binding, subtype) = entity
+            .content_binding
+            .as_ref()
+            .map(|cb| {
+                let subtype = cb.subtypes.first().map(|s| s.as_str());
+                (Some(cb.binding.as_str()), subtype)
+            })
+            .unwrap_or((None, None));
+
+        // Collection rows are locked in ascending ID order (see
+        // `crate::transaction`)
+        let mut coll_ids = collection_ids.unwrap_or_default().to_vec();
+        coll_ids.sort_unstable();
+        coll_ids.dedup();
+
+        // Collections that deduplicate content link an identical block added
+        // within the window instead of storing the content again
+        let dedup_ids = if coll_ids.is_empty() {
+            Vec::new()
+        } else {
+            DataCollection::find_deduplicating_in(tx.conn(), &coll_ids).await?
+        };
+        let mut duplicate = None;
+        if !dedup_ids.is_empty() {
+            let since = Utc::now() - self.dedup_window;
+            let digest = content_digest(&entity.content);
+            ContentBlock::lock_digest_in(tx.conn(), &digest).await?;
+            ContentBlock::backfill_digests_in(tx.conn(), &dedup_ids, since).await?;
+            duplicate = ContentBlock::find_duplicate_in(
+                tx.conn(),
+                &dedup_ids,
+                &digest,
+                binding,
+                subtype,
+                since,
+            )
+            .await?;
+        }
+
+        let mut remaining = coll_ids.clone();
+        if let Some(existing) = duplicate {
+            for coll_id in &dedup_ids {
+                if ContentBlock::attach_to_collection_if_absent_in(tx.conn(), existing.id, *coll_id)
+                    .await?
+                {
+                    DataCollection::increment_volume_in(tx.conn(), *coll_id).await?;
+                }
+            }
+            if let Some(inbox_message_id) = entity.inbox_message_id {
+                ContentBlock::add_inbox_message_in(tx.conn(), existing.id, inbox_message_id)
+                    .await?;
+            }
+
+            debug!(
+                content_block = existing.id,
+                collections = dedup_ids.len(),
+                "Duplicate content block linked to collections"
+            );
+
+            remaining.retain(|id| !dedup_ids.contains(id));
+            if remaining.is_empty() {
+                return Ok(existing.into());
+            }
+        }
+
+        let params = crate::models::taxii1::NewContentBlock {
+            timestamp_label: entity.timestamp_label,
+            inbox_message_id: entity.inbox_message_id,
+            content: &entity.content,
+            binding_id: binding,
+            binding_subtype: subtype,
+            validation_error: entity.validation_error.as_deref(),
+            signature_status: entity.signature_status.as_deref(),
+            signer_fingerprint: entity.signer_fingerprint.as_deref(),
+            content_encoding: &entity.content_encoding,
+        };
+        let block = ContentBlock::create_in(tx.conn(), &params).await?;
+
+        // Attach to collections and update volume (interleaved to match original semantics)
+        if !remaining.is_empty() {
+            for coll_id in &remaining {
+                ContentBlock::attach_to_collection_in(tx.conn(), block.id, *coll_id).await?;
+                DataCollection::increment_volume_in(tx.conn(), *coll_id).await?;
+            }
+
+            debug!(
+                content_block = block.id,
+                collections = remaining.len(),
+                "Content block added to collections"
+            );
+        }
+
+        Ok(block.into())
+    }
 }
 
 impl Taxii1Repository for DbTaxii1Repository {
@@ -47,16 +223,16 @@ impl Taxii1Repository for DbTaxii1Repository {
 
     async fn get_services(&self, collection_id: Option<i32>) -> DatabaseResult<Vec<ServiceEntity>> {
         let services = if let Some(coll_id) = collection_id {
-            Service::find_by_collection(&self.pool, coll_id).await?
+            Service::find_by_collection(&self.reads, coll_id).await?
         } else {
-            Service::find_all(&self.pool).await?
+            Service::find_all(&self.reads).await?
         };
 
         Ok(services.into_iter().map(Into::into).collect())
     }
 
     async fn get_service(&self, service_id: &str) -> DatabaseResult<Option<ServiceEntity>> {
-        let service = Service::find(&self.pool, service_id).await?;
+        let service = Service::find(&self.reads, service_id).await?;
         Ok(service.map(Into::into))
     }
 
@@ -146,9 +322,9 @@ impl Taxii1Repository for DbTaxii1Repository {
         service_type: Option<&str>,
     ) -> DatabaseResult<Vec<ServiceEntity>> {
         let services = if let Some(svc_type) = service_type {
-            Service::find_by_collection_and_type(&self.pool, collection_id, svc_type).await?
+            Service::find_by_collection_and_type(&self.reads, collection_id, svc_type).await?
         } else {
-            Service::find_by_collection(&self.pool, collection_id).await?
+            Service::find_by_collection(&self.reads, collection_id).await?
         };
 
         Ok(services.into_iter().map(Into::into).collect())
@@ -163,9 +339,9 @@ impl Taxii1Repository for DbTaxii1Repository {
         service_id: Option<&str>,
     ) -> DatabaseResult<Vec<CollectionEntity>> {
         let collections = if let Some(svc_id) = service_id {
-            DataCollection::find_by_service(&self.pool, svc_id).await?
+            DataCollection::find_by_service(&self.reads, svc_id).await?
         } else {
-            DataCollection::find_all(&self.pool).await?
+            DataCollection::find_all(&self.reads).await?
         };
 
         Ok(collections.into_iter().map(Into::into).collect())
@@ -177,9 +353,9 @@ impl Taxii1Repository for DbTaxii1Repository {
         service_id: Option<&str>,
     ) -> DatabaseResult<Option<CollectionEntity>> {
         let collection = if let Some(svc_id) = service_id {
-            DataCollection::find_by_name_and_service(&self.pool, name, svc_id).await?
+            DataCollection::find_by_name_and_service(&self.reads, name, svc_id).await?
         } else {
-            DataCollection::find_by_name(&self.pool, name).await?
+            DataCollection::find_by_name(&self.reads, name).await?
         };
 
         Ok(collection.map(Into::into))
@@ -191,16 +367,21 @@ impl Taxii1Repository for DbTaxii1Repository {
     ) -> DatabaseResult<CollectionEntity> {
         let bindings = ContentBindingEntity::serialize_many(&entity.supported_content);
 
-        let collection = DataCollection::create(
-            &self.pool,
-            &entity.name,
-            &entity.collection_type,
-            entity.description.as_deref(),
-            entity.available,
-            entity.accept_all_content,
-            Some(&bindings),
-        )
-        .await?;
+        let params = crate::models::taxii1::NewDataCollection {
+            name: &entity.name,
+            collection_type: &entity.collection_type,
+            description: entity.description.as_deref(),
+            available: entity.available,
+            accept_all_content: entity.accept_all_content,
+            bindings: Some(&bindings),
+            retention_days: entity.retention_days,
+            deduplicate_content: entity.deduplicate_content,
+            validation_mode: &entity.validation_mode,
+            signature_policy: &entity.signature_policy,
+            trusted_certificates: entity.trusted_certificates.as_deref(),
+        };
+
+        let collection = DataCollection::create(&self.pool, &params).await?;
 
         Ok(collection.into())
     }
@@ -223,6 +404,11 @@ impl Taxii1Repository for DbTaxii1Repository {
             available: entity.available,
             accept_all_content: entity.accept_all_content,
             bindings: Some(&bindings),
+            retention_days: entity.retention_days,
+            deduplicate_content: entity.deduplicate_content,
+            validation_mode: &entity.validation_mode,
+            signature_policy: &entity.signature_policy,
+            trusted_certificates: entity.trusted_certificates.as_deref(),
         };
 
         let collection = DataCollection::update(&self.pool, &params).await?;
@@ -250,6 +436,52 @@ impl Taxii1Repository for DbTaxii1Repository {
         Ok(())
     }
 
+    async fn set_collection_bridge(
+        &self,
+        bridge: &CollectionBridgeEntity,
+    ) -> DatabaseResult<CollectionBridgeEntity> {
+        let api_root_id = Uuid::parse_str(&bridge.api_root_id).map_err(|_| {
+            DatabaseError::NotFound(format!("Invalid API root UUID: {}", bridge.api_root_id))
+        })?;
+        let collection_id = Uuid::parse_str(&bridge.collection_id).map_err(|_| {
+            DatabaseError::NotFound(format!("Invalid collection UUID: {}", bridge.collection_id))
+        })?;
+
+        let model = CollectionBridge::upsert(
+            &self.pool,
+            bridge.data_collection_id,
+            api_root_id,
+            collection_id,
+        )
+        .await?;
+
+        debug!(
+            id = bridge.data_collection_id,
+            api_root_id = %api_root_id,
+            collection_id = %collection_id,
+            "collection.bridge_set"
+        );
+
+        Ok(model.into())
+    }
+
+    async fn remove_collection_bridge(&self, data_collection_id: i32) -> DatabaseResult<bool> {
+        let removed = CollectionBridge::delete(&self.pool, data_collection_id).await?;
+        if removed {
+            debug!(id = data_collection_id, "collection.bridge_removed");
+        }
+        Ok(removed)
+    }
+
+    async fn get_collection_bridges(
+        &self,
+        data_collection_ids: &[i32],
+    ) -> DatabaseResult<Vec<CollectionBridgeEntity>> {
+        let bridges =
+            CollectionBridge::find_by_data_collections(&self.pool, data_collection_ids).await?;
+        Ok(bridges.into_iter().map(Into::into).collect())
+    }
+
     // ========================================================================
     // Content Block Operations
     // ========================================================================
@@ -284,10 +516,42 @@ impl Taxii1Repository for DbTaxii1Repository {
             limit,
         };
 
-        let blocks = ContentBlock::find_filtered(&self.pool, &filter).await?;
+        let blocks = ContentBlock::find_filtered(&self.reads, &filter).await?;
         Ok(blocks.into_iter().map(Into::into).collect())
     }
 
+    fn stream_content_blocks(
+        &self,
+        collection_id: Option<i32>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        bindings: Option<&[ContentBindingEntity]>,
+    ) -> RowStream<ContentBlockEntity> {
+        let model_bindings: Option<Vec<crate::models::taxii1::ContentBindingFilter>> = bindings
+            .map(|binds| {
+                binds
+                    .iter()
+                    .map(|b| crate::models::taxii1::ContentBindingFilter {
+                        binding: b.binding.clone(),
+                        subtypes: b.subtypes.clone(),
+                    })
+                    .collect()
+            });
+
+        let filter = crate::models::taxii1::ContentBlockFilter {
+            collection_id,
+            start_time,
+            end_time,
+            bindings: model_bindings.as_deref(),
+            offset: 0,
+            limit: None,
+        };
+
+        ContentBlock::stream_filtered(&self.reads, &filter)
+            .map(|row| row.map(Into::into))
+            .boxed()
+    }
+
     async fn get_content_blocks_count(
         &self,
         collection_id: Option<i32>,
@@ -316,49 +580,51 @@ impl Taxii1Repository for DbTaxii1Repository {
             limit: None,
         };
 
-        ContentBlock::count_filtered(&self.pool, &filter).await
+        ContentBlock::count_filtered(&self.reads, &filter).await
+    }
+
+    async fn get_content_block_sizes(
+        &self,
+        collection_id: Option<i32>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        bindings: Option<&[ContentBindingEntity]>,
+    ) -> DatabaseResult<Vec<i64>> {
+        let model_bindings: Option<Vec<crate::models::taxii1::ContentBindingFilter>> = bindings
+            .map(|binds| {
+                binds
+                    .iter()
+                    .map(|b| crate::models::taxii1::ContentBindingFilter {
+                        binding: b.binding.clone(),
+                        subtypes: b.subtypes.clone(),
+                    })
+                    .collect()
+            });
+
+        let filter = crate::models::taxii1::ContentBlockFilter {
+            collection_id,
+            start_time,
+            end_time,
+            bindings: model_bindings.as_deref(),
+            offset: 0,
+            limit: None,
+        };
+
+        ContentBlock::sizes_filtered(&self.reads, &filter).await
     }
 
     async fn create_content_block(
         &self,
         entity: &ContentBlockEntity,
         collection_ids: Option<&[i32]>,
-        _service_id: Option<&str>,
+        service_id: Option<&str>,
     ) -> DatabaseResult<ContentBlockEntity> {
-        let (binding, subtype) = entity
-            .content_binding
-            .as_ref()
-            .map(|cb| {
-                let subtype = cb.subtypes.first().map(|s| s.as_str());
-                (Some(cb.binding.as_str()), subtype)
-            })
-            .unwrap_or((None, None));
-
-        let block = ContentBlock::create(
-            &self.pool,
-            entity.timestamp_label,
-            entity.inbox_message_id,
-            &entity.content,
-            binding,
-            subtype,
-        )
-        .await?;
-
-        // Attach to collections and update volume (interleaved to match original semantics)
-        if let Some(coll_ids) = collection_ids {
-            for coll_id in coll_ids {
-                ContentBlock::attach_to_collection(&self.pool, block.id, *coll_id).await?;
-                DataCollection::increment_volume(&self.pool, *coll_id).await?;
-            }
-
-            debug!(
-                content_block = block.id,
-                collections = coll_ids.len(),
-                "Content block added to collections"
-            );
-        }
-
-        Ok(block.into())
+        let mut tx = self.pool.begin().await?;
+        let block = self
+            .create_content_block_in(&mut tx, entity, collection_ids, service_id)
+            .await?;
+        tx.commit().await?;
+        Ok(block)
     }
 
     async fn delete_content_blocks(
@@ -409,6 +675,52 @@ impl Taxii1Repository for DbTaxii1Repository {
         Ok(counter)
     }
 
+    async fn purge_expired(
+        &self,
+        collection_id: i32,
+        cutoff: DateTime<Utc>,
+    ) -> DatabaseResult<PurgeStats> {
+        let mut stats = PurgeStats::default();
+        loop {
+            let purged =
+                ContentBlock::purge_before(&self.pool, collection_id, cutoff, PURGE_BATCH_SIZE)
+                    .await?;
+            stats.purged += purged;
+            if purged < PURGE_BATCH_SIZE as u64 {
+                break;
+            }
+        }
+
+        // Whatever expired content is left is held by result sets
+        stats.retained =
+            ContentBlock::count_before(&self.pool, collection_id, cutoff).await? as u64;
+
+        if stats.purged > 0 {
+            let volume = ContentBlock::count_by_collection(&self.pool, collection_id).await?;
+            DataCollection::update_volume(&self.pool, collection_id, volume as i32).await?;
+        }
+
+        debug!(
+            id = collection_id,
+            purged = stats.purged,
+            retained = stats.retained,
+            "collection.purged"
+        );
+
+        Ok(stats)
+    }
+
+    async fn collection_stats(&self, collection_id: i32) -> DatabaseResult<CollectionStats> {
+        if let Some(cached) =
+            CachedCollectionStats::find(&self.reads, protocol::TAXII1, &collection_id.to_string())
+                .await?
+        {
+            return Ok(cached.into());
+        }
+
+        crate::stats::compute_taxii1(&self.reads, collection_id).await
+    }
+
     // ========================================================================
     // Inbox Message Operations
     // ========================================================================
@@ -417,29 +729,43 @@ impl Taxii1Repository for DbTaxii1Repository {
         &self,
         entity: &InboxMessageEntity,
     ) -> DatabaseResult<InboxMessageEntity> {
-        let destination_collections = if entity.destination_collections.is_empty() {
-            None
-        } else {
-            Some(serde_json::to_string(&entity.destination_collections).unwrap_or_default())
-        };
+        let mut tx = self.pool.begin().await?;
+        let message = self.create_inbox_message_in(&mut tx, entity).await?;
+        tx.commit().await?;
+        Ok(message)
+    }
 
-        let params = crate::models::taxii1::NewInboxMessage {
-            message_id: &entity.message_id,
-            original_message: &entity.original_message,
-            content_block_count: entity.content_block_count,
-            destination_collections: destination_collections.as_deref(),
-            service_id: &entity.service_id,
-            result_id: entity.result_id.as_deref(),
-            record_count: entity.record_count,
-            partial_count: entity.partial_count,
-            subscription_collection_name: entity.subscription_collection_name.as_deref(),
-            subscription_id: entity.subscription_id.as_deref(),
-            exclusive_begin_timestamp_label: entity.exclusive_begin_timestamp_label,
-            inclusive_end_timestamp_label: entity.inclusive_end_timestamp_label,
+    async fn create_inbox_message_with_blocks(
+        &self,
+        inbox_message: Option<&InboxMessageEntity>,
+        blocks: &[(ContentBlockEntity, Option<Vec<i32>>)],
+        service_id: Option<&str>,
+    ) -> DatabaseResult<(Option<InboxMessageEntity>, Vec<ContentBlockEntity>)> {
+        let mut tx = self.pool.begin().await?;
+        let message = match inbox_message {
+            Some(entity) => Some(self.create_inbox_message_in(&mut tx, entity).await?),
+            None => None,
         };
 
-        let message = InboxMessage::create(&self.pool, &params).await?;
-        Ok(message.into())
+        let mut created = Vec::with_capacity(blocks.len());
+        for (block, collection_ids) in blocks {
+            let block = ContentBlockEntity {
+                inbox_message_id: message.as_ref().and_then(|m| m.id),
+                ..block.clone()
+            };
+            created.push(
+                self.create_content_block_in(
+                    &mut tx,
+                    &block,
+                    collection_ids.as_deref(),
+                    service_id,
+                )
+                .await?,
+            );
+        }
+
+        tx.commit().await?;
+        Ok((message, created))
     }
 
     // ========================================================================
@@ -448,17 +774,23 @@ impl Taxii1Repository for DbTaxii1Repository {
 
     async fn create_result_set(&self, entity: &ResultSetEntity) -> DatabaseResult<ResultSetEntity> {
         let bindings = ContentBindingEntity::serialize_many(&entity.content_bindings);
+        let delivery = entity.delivery.as_ref();
 
-        let result_set = ResultSet::create(
-            &self.pool,
-            &entity.id,
-            entity.collection_id,
-            Some(&bindings),
-            entity.timeframe.0,
-            entity.timeframe.1,
-        )
-        .await?;
+        let params = crate::models::taxii1::NewResultSet {
+            id: &entity.id,
+            collection_id: entity.collection_id,
+            bindings: Some(&bindings),
+            begin_time: entity.timeframe.0,
+            end_time: entity.timeframe.1,
+            status: &entity.status,
+            expires_at: entity.expires_at,
+            delivery_protocol_binding: delivery.map(|d| d.protocol_binding.as_str()),
+            delivery_address: delivery.map(|d| d.address.as_str()),
+            delivery_message_binding: delivery.map(|d| d.message_binding.as_str()),
+            delivery_status: entity.delivery_status.as_deref(),
+        };
 
+        let result_set = ResultSet::create(&self.pool, &params).await?;
         Ok(result_set.into())
     }
 
@@ -467,6 +799,117 @@ impl Taxii1Repository for DbTaxii1Repository {
         Ok(result_set.map(Into::into))
     }
 
+    async fn get_pending_result_sets(&self, limit: i64) -> DatabaseResult<Vec<ResultSetEntity>> {
+        let result_sets = ResultSet::find_pending(&self.pool, limit).await?;
+        Ok(result_sets.into_iter().map(Into::into).collect())
+    }
+
+    async fn count_pending_result_sets(&self) -> DatabaseResult<i64> {
+        ResultSet::count_pending(&self.pool).await
+    }
+
+    async fn claim_result_sets_to_deliver(
+        &self,
+        limit: i64,
+        lease: chrono::Duration,
+    ) -> DatabaseResult<Vec<ResultSetEntity>> {
+        let result_sets = ResultSet::claim_to_deliver(&self.pool, limit, lease).await?;
+        Ok(result_sets.into_iter().map(Into::into).collect())
+    }
+
+    async fn record_result_set_delivery(
+        &self,
+        result_set_id: &str,
+        status: &str,
+        attempts: i32,
+        error: Option<&str>,
+    ) -> DatabaseResult<()> {
+        ResultSet::record_delivery(&self.pool, result_set_id, status, attempts, error).await
+    }
+
+    async fn prepare_result_set(
+        &self,
+        result_set_id: &str,
+    ) -> DatabaseResult<Option<ResultSetEntity>> {
+        let Some(result_set) = self.get_result_set(result_set_id).await? else {
+            return Ok(None);
+        };
+
+        let model_bindings: Vec<crate::models::taxii1::ContentBindingFilter> = result_set
+            .content_bindings
+            .iter()
+            .map(|b| crate::models::taxii1::ContentBindingFilter {
+                binding: b.binding.clone(),
+                subtypes: b.subtypes.clone(),
+            })
+            .collect();
+
+        let filter = crate::models::taxii1::ContentBlockFilter {
+            collection_id: Some(result_set.collection_id),
+            start_time: result_set.timeframe.0,
+            end_time: result_set.timeframe.1,
+            bindings: Some(&model_bindings),
+            offset: 0,
+            limit: None,
+        };
+
+        let content_block_ids = ContentBlock::find_ids_filtered(&self.pool, &filter).await?;
+        let prepared =
+            ResultSet::materialize(&self.pool, result_set_id, &content_block_ids).await?;
+
+        debug!(
+            result_set = result_set_id,
+            content_blocks = content_block_ids.len(),
+            "Result set prepared"
+        );
+
+        Ok(prepared.map(Into::into))
+    }
+
+    async fn purge_expired_result_sets(&self, now: DateTime<Utc>) -> DatabaseResult<u64> {
+        let mut purged = 0;
+        loop {
+            let deleted = ResultSet::delete_expired(&self.pool, now, PURGE_BATCH_SIZE).await?;
+            purged += deleted;
+            if deleted < PURGE_BATCH_SIZE as u64 {
+                break;
+            }
+        }
+
+        debug!(purged, "result_sets.purged");
+        Ok(purged)
+    }
+
+    async fn get_result_set_content_blocks(
+        &self,
+        result_set_id: &str,
+        offset: i64,
+        limit: Option<i64>,
+    ) -> DatabaseResult<Vec<ContentBlockEntity>> {
+        let blocks =
+            ResultSet::find_content_blocks(&self.pool, result_set_id, offset, limit).await?;
+        Ok(blocks.into_iter().map(Into::into).collect())
+    }
+
+    async fn get_result_set_content_block_sizes(
+        &self,
+        result_set_id: &str,
+    ) -> DatabaseResult<Vec<i64>> {
+        ResultSet::find_content_block_sizes(&self.pool, result_set_id).await
+    }
+
+    async fn get_result_set_parts(&self, result_set_id: &str) -> DatabaseResult<Option<Vec<i64>>> {
+        ResultSet::find_part_sizes(&self.pool, result_set_id).await
+    }
+
+    async fn record_result_set_parts(
+        &self,
+        result_set_id: &str,
+        parts: &[i64],
+    ) -> DatabaseResult<Option<Vec<i64>>> {
+        ResultSet::record_part_sizes(&self.pool, result_set_id, parts).await
+    }
+
     // ========================================================================
     // Subscription Operations
     // ========================================================================
@@ -475,12 +918,12 @@ impl Taxii1Repository for DbTaxii1Repository {
         &self,
         subscription_id: &str,
     ) -> DatabaseResult<Option<SubscriptionEntity>> {
-        let subscription = Subscription::find(&self.pool, subscription_id).await?;
+        let subscription = Subscription::find(&self.reads, subscription_id).await?;
         Ok(subscription.map(Into::into))
     }
 
     async fn get_subscriptions(&self, service_id: &str) -> DatabaseResult<Vec<SubscriptionEntity>> {
-        let subscriptions = Subscription::find_by_service(&self.pool, service_id).await?;
+        let subscriptions = Subscription::find_by_service(&self.reads, service_id).await?;
         Ok(subscriptions.into_iter().map(Into::into).collect())
     }
 
@@ -501,13 +944,20 @@ impl Taxii1Repository for DbTaxii1Repository {
             .clone()
             .unwrap_or_else(|| Uuid::new_v4().to_string());
 
+        let push = entity.push.as_ref();
         let subscription = Subscription::upsert(
             &self.pool,
-            &subscription_id,
-            entity.collection_id,
-            params.as_deref(),
-            &entity.status,
-            &entity.service_id,
+            &NewSubscription {
+                id: &subscription_id,
+                collection_id: entity.collection_id,
+                params: params.as_deref(),
+                status: &entity.status,
+                service_id: &entity.service_id,
+                push_protocol_binding: push.map(|p| p.protocol_binding.as_str()),
+                push_address: push.map(|p| p.address.as_str()),
+                push_message_binding: push.map(|p| p.message_binding.as_str()),
+                account_id: entity.account_id,
+            },
         )
         .await?;
 
@@ -527,4 +977,347 @@ impl Taxii1Repository for DbTaxii1Repository {
     ) -> DatabaseResult<SubscriptionEntity> {
         self.update_subscription(entity).await
     }
+
+    async fn record_delivery_success(
+        &self,
+        subscription_id: &str,
+    ) -> DatabaseResult<Option<SubscriptionEntity>> {
+        let subscription =
+            Subscription::record_delivery_success(&self.pool, subscription_id).await?;
+        Ok(subscription.map(Into::into))
+    }
+
+    async fn record_delivery_failure(
+        &self,
+        subscription_id: &str,
+        error: &str,
+        pause_after: i32,
+    ) -> DatabaseResult<Option<SubscriptionEntity>> {
+        let subscription =
+            Subscription::record_delivery_failure(&self.pool, subscription_id, error, pause_after)
+                .await?;
+        Ok(subscription.map(Into::into))
+    }
+
+    // ========================================================================
+    // Push Delivery Operations
+    // ========================================================================
+
+    async fn get_push_subscriptions(
+        &self,
+        collection_id: i32,
+    ) -> DatabaseResult<Vec<SubscriptionEntity>> {
+        let subscriptions =
+            Subscription::find_push_by_collection(&self.pool, collection_id).await?;
+        Ok(subscriptions.into_iter().map(Into::into).collect())
+    }
+
+    async fn create_subscription_deliveries(
+        &self,
+        subscription_ids: &[String],
+        content_block_id: i32,
+    ) -> DatabaseResult<u64> {
+        SubscriptionDelivery::enqueue(&self.pool, subscription_ids, content_block_id).await
+    }
+
+    async fn claim_pending_deliveries(
+        &self,
+        limit: i64,
+        lease: chrono::Duration,
+    ) -> DatabaseResult<Vec<SubscriptionDeliveryEntity>> {
+        let deliveries = SubscriptionDelivery::claim_pending(&self.pool, limit, lease).await?;
+        Ok(deliveries.into_iter().map(Into::into).collect())
+    }
+
+    async fn get_subscription_deliveries(
+        &self,
+        status: Option<&str>,
+        subscription_id: Option<&str>,
+        limit: i64,
+    ) -> DatabaseResult<Vec<SubscriptionDeliveryEntity>> {
+        let deliveries =
+            SubscriptionDelivery::find_filtered(&self.reads, status, subscription_id, limit)
+                .await?;
+        Ok(deliveries.into_iter().map(Into::into).collect())
+    }
+
+    async fn record_subscription_deliveries(
+        &self,
+        ids: &[i32],
+        status: &str,
+        attempts: i32,
+        error: Option<&str>,
+    ) -> DatabaseResult<u64> {
+        SubscriptionDelivery::record(&self.pool, ids, status, attempts, error).await
+    }
+
+    async fn requeue_failed_deliveries(
+        &self,
+        ids: &[i32],
+        subscription_id: Option<&str>,
+    ) -> DatabaseResult<u64> {
+        SubscriptionDelivery::requeue_failed(&self.pool, ids, subscription_id).await
+    }
+
+    async fn get_content_blocks_by_ids(
+        &self,
+        ids: &[i32],
+    ) -> DatabaseResult<Vec<ContentBlockEntity>> {
+        let blocks = ContentBlock::find_many(&self.pool, ids).await?;
+        Ok(blocks.into_iter().map(Into::into).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::PgPool;
+
+    type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+    fn inbox_message(message_id: &str) -> InboxMessageEntity {
+        InboxMessageEntity {
+            id: None,
+            message_id: message_id.to_string(),
+            original_message: Vec::new(),
+            content_block_count: 1,
+            service_id: "inbox".to_string(),
+            destination_collections: Vec::new(),
+            result_id: None,
+            record_count: None,
+            partial_count: false,
+            subscription_collection_name: None,
+            subscription_id: None,
+            exclusive_begin_timestamp_label: None,
+            inclusive_end_timestamp_label: None,
+        }
+    }
+
+    fn block(inbox_message_id: Option<i32>) -> ContentBlockEntity {
+        ContentBlockEntity {
+            inbox_message_id,
+            ..ContentBlockEntity::new(
+                b"<stix:STIX_Package id=\"example:Package-1\"/>",
+                Some(ContentBindingEntity::new("urn:stix.mitre.org:xml:1.1.1")),
+            )
+        }
+    }
+
+    /// Pushing the same block twice stores it once with both inbox messages.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_duplicate_content_block_is_linked(pool: PgPool) -> TestResult {
+        let repo = DbTaxii1Repository::new(TaxiiPool::new(pool.clone()));
+        let dedup = repo
+            .create_collection(&CollectionEntity {
+                deduplicate_content: true,
+                ..CollectionEntity::new("dedup")
+            })
+            .await?
+            .id
+            .ok_or("collection without id")?;
+        let plain = repo
+            .create_collection(&CollectionEntity::new("plain"))
+            .await?
+            .id
+            .ok_or("collection without id")?;
+
+        repo.update_service(&ServiceEntity {
+            id: Some("inbox".to_string()),
+            service_type: "INBOX".to_string(),
+            properties: json!({}),
+        })
+        .await?;
+        let mut inbox_ids = Vec::new();
+        for message_id in ["1", "2"] {
+            let message = repo
+                .create_inbox_message(&inbox_message(message_id))
+                .await?;
+            inbox_ids.push(message.id.ok_or("inbox message without id")?);
+        }
+
+        let first = repo
+            .create_content_block(&block(Some(inbox_ids[0])), Some(&[dedup]), None)
+            .await?;
+        let second = repo
+            .create_content_block(&block(Some(inbox_ids[1])), Some(&[dedup]), None)
+            .await?;
+        assert_eq!(second.id, first.id);
+
+        let rows: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM content_blocks")
+            .fetch_one(&pool)
+            .await?;
+        assert_eq!(rows, 1);
+        let first_id = first.id.ok_or("content block without id")?;
+        assert_eq!(
+            ContentBlock::find_inbox_message_ids(repo.pool(), first_id).await?,
+            inbox_ids
+        );
+        let volume = repo
+            .get_collection("dedup", None)
+            .await?
+            .and_then(|c| c.volume);
+        assert_eq!(volume, Some(1));
+
+        // Polls return the block as stored
+        let polled = repo
+            .get_content_blocks(Some(dedup), None, None, None, 0, None)
+            .await?;
+        assert_eq!(polled.len(), 1);
+        assert_eq!(polled[0].content, block(None).content);
+        assert_eq!(polled[0].timestamp_label, first.timestamp_label);
+
+        // Collections without deduplication still store the content again
+        let third = repo
+            .create_content_block(&block(None), Some(&[dedup, plain]), None)
+            .await?;
+        assert_ne!(third.id, first.id);
+        assert_eq!(
+            repo.get_content_blocks_count(Some(dedup), None, None, None)
+                .await?,
+            1
+        );
+        assert_eq!(
+            repo.get_content_blocks_count(Some(plain), None, None, None)
+                .await?,
+            1
+        );
+
+        Ok(())
+    }
+
+    /// Blocks outside the window, or without a digest, are not reused blindly.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_deduplication_window(pool: PgPool) -> TestResult {
+        let repo = DbTaxii1Repository::new(TaxiiPool::new(pool.clone()))
+            .with_dedup_window(Duration::hours(1));
+        let dedup = repo
+            .create_collection(&CollectionEntity {
+                deduplicate_content: true,
+                ..CollectionEntity::new("dedup")
+            })
+            .await?
+            .id
+            .ok_or("collection without id")?;
+
+        let old = repo
+            .create_content_block(&block(None), Some(&[dedup]), None)
+            .await?;
+        sqlx::query("UPDATE content_blocks SET date_created = NOW() - INTERVAL '2 hours'")
+            .execute(&pool)
+            .await?;
+        let new = repo
+            .create_content_block(&block(None), Some(&[dedup]), None)
+            .await?;
+        assert_ne!(new.id, old.id);
+
+        // Blocks stored before digests existed get one when matched
+        sqlx::query("UPDATE content_blocks SET content_sha256 = NULL")
+            .execute(&pool)
+            .await?;
+        let again = repo
+            .create_content_block(&block(None), Some(&[dedup]), None)
+            .await?;
+        assert_eq!(again.id, new.id);
+
+        Ok(())
+    }
+
+    /// Concurrent messages with the same content store it once.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_concurrent_duplicates_are_stored_once(pool: PgPool) -> TestResult {
+        let repo = DbTaxii1Repository::new(TaxiiPool::new(pool));
+        let dedup = repo
+            .create_collection(&CollectionEntity {
+                deduplicate_content: true,
+                ..CollectionEntity::new("dedup")
+            })
+            .await?
+            .id
+            .ok_or("collection without id")?;
+
+        let content = block(None);
+        let collections = [dedup];
+        let blocks = futures::future::try_join_all(
+            (0..32).map(|_| repo.create_content_block(&content, Some(&collections), None)),
+        )
+        .await?;
+        assert!(blocks.iter().all(|b| b.id == blocks[0].id));
+        assert_eq!(
+            repo.get_content_blocks_count(Some(dedup), None, None, None)
+                .await?,
+            1
+        );
+
+        Ok(())
+    }
+
+    /// A failed write rolls back the whole unit of work.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_unit_of_work_rolls_back(pool: PgPool) -> TestResult {
+        let repo = DbTaxii1Repository::new(TaxiiPool::new(pool.clone()));
+        let collection_id = repo
+            .create_collection(&CollectionEntity::new("inbox"))
+            .await?
+            .id
+            .ok_or("collection without id")?;
+        repo.update_service(&ServiceEntity {
+            id: Some("inbox".to_string()),
+            service_type: "INBOX".to_string(),
+            properties: json!({}),
+        })
+        .await?;
+
+        let counts = || async {
+            let messages: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM inbox_messages")
+                .fetch_one(&pool)
+                .await?;
+            let blocks: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM content_blocks")
+                .fetch_one(&pool)
+                .await?;
+            Ok::<_, sqlx::Error>((messages, blocks))
+        };
+
+        // The second block fails on an unknown collection after the inbox
+        // message and the first block were written
+        let mut tx = repo.pool().begin().await?;
+        let message = repo
+            .create_inbox_message_in(&mut tx, &inbox_message("1"))
+            .await?;
+        repo.create_content_block_in(&mut tx, &block(message.id), Some(&[collection_id]), None)
+            .await?;
+        let failed = repo
+            .create_content_block_in(&mut tx, &block(message.id), Some(&[i32::MAX]), None)
+            .await;
+        assert!(failed.is_err());
+        drop(tx);
+
+        assert_eq!(counts().await?, (0, 0));
+        let volume = repo
+            .get_collection("inbox", None)
+            .await?
+            .and_then(|c| c.volume);
+        assert_eq!(volume, Some(0));
+
+        // Explicit rollback
+        let mut tx = repo.pool().begin().await?;
+        repo.create_inbox_message_in(&mut tx, &inbox_message("2"))
+            .await?;
+        tx.rollback().await?;
+        assert_eq!(counts().await?, (0, 0));
+
+        let mut tx = repo.pool().begin().await?;
+        let message = repo
+            .create_inbox_message_in(&mut tx, &inbox_message("3"))
+            .await?;
+        repo.create_content_block_in(&mut tx, &block(message.id), Some(&[collection_id]), None)
+            .await?;
+        tx.commit().await?;
+        assert_eq!(counts().await?, (1, 1));
+
+        Ok(())
+    }
 }
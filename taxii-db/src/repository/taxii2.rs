@@ -3,22 +3,53 @@
 //! Provides database operations for TAXII 2.x entities including API roots,
 //! collections, STIX objects, and jobs.
 
-use chrono::{DateTime, Utc};
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, Duration, NaiveDateTime, SubsecRound, Utc};
+use futures::StreamExt;
+use tracing::info;
 use uuid::Uuid;
 
 use crate::error::{DatabaseError, DatabaseResult};
-use crate::models::taxii2::{PaginatedResult, PaginationCursor, Taxii2QueryParams};
+use crate::models::collection_stats::CachedCollectionStats;
+use crate::models::retention_purge::protocol;
+use crate::models::taxii2::{
+    DeleteMode, PaginatedResult, PaginationCursor, Taxii2QueryParams, VersionSelector,
+};
+use crate::partitioning;
 use crate::pool::TaxiiPool;
+use crate::repository::PURGE_BATCH_SIZE;
 use crate::repository::traits::Taxii2Repository;
+use crate::stream::{RowStream, error_stream};
+use crate::transaction::TaxiiTransaction;
 
 use taxii_core::{
-    ApiRoot, Collection, Job, JobDetail, JobDetails, ManifestRecord, STIXObject, VersionRecord,
+    ApiRoot, BulkInsertOutcome, BulkInsertReport, CleanupStats, Collection, CollectionStats,
+    CollisionPolicy, DeletionRecord, Job, JobDetail, JobDetails, ManifestRecord, PurgeStats,
+    STIXObject, ValidationRule, VersionRecord,
 };
 
 // ============================================================================
 // Utilities
 // ============================================================================
 
+/// Maximum number of rows written by one multi-row statement in bulk inserts.
+///
+/// Rows are passed as arrays, so the number of bind parameters does not grow
+/// with the batch; the chunk bounds statement size and memory per round trip.
+const BULK_INSERT_CHUNK_SIZE: usize = 1000;
+
+/// An object prepared for bulk insertion.
+struct BulkRow<'a> {
+    /// Position in the submitted batch.
+    index: usize,
+    stix_id: &'a str,
+    stix_type: &'a str,
+    spec_version: &'a str,
+    version: DateTime<Utc>,
+    serialized_data: serde_json::Value,
+}
+
 /// TAXII 2.1 datetime format for parsing timestamps.
 /// Uses %.6f for 6-digit microsecond precision.
 const TAXII2_DATETIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.6fZ";
@@ -36,6 +67,12 @@ const TAXII2_DATETIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.6fZ";
 /// > an object does not have a created or modified timestamp or any other
 /// > version information that can be used, then the server should use a value for
 /// > the version that is consistent to the server.
+///
+/// Versions of an object are totally ordered by `(version, date_added, pk)`:
+/// rows sharing a version, such as two copies with the same `modified`, are
+/// ordered by date added, then by primary key. First and last version
+/// selection, deletes and the versions listing all use this order, so the
+/// same row is chosen on every query.
 pub fn get_object_version(obj: &serde_json::Value) -> DateTime<Utc> {
     // Try modified first
     if let Some(modified) = obj.get("modified").and_then(|v| v.as_str()) {
@@ -73,27 +110,353 @@ pub fn get_object_version(obj: &serde_json::Value) -> DateTime<Utc> {
 /// database operations.
 pub struct DbTaxii2Repository {
     pool: TaxiiPool,
+    reads: TaxiiPool,
+    hard_delete: bool,
+    stale_job_age: Duration,
+    partitioned: bool,
 }
 
+/// Age after which a job that never completed is marked failed.
+pub const DEFAULT_STALE_JOB_AGE: Duration = Duration::days(90);
+
 impl DbTaxii2Repository {
     /// Create a new repository instance.
+    ///
+    /// Deleted objects are kept as tombstones (see [`Self::with_hard_delete`]).
+    ///
+    /// Reads that do not need to see a preceding write use the pool's read
+    /// pool (see [`TaxiiPool::reader`]); everything else uses the primary.
     pub fn new(pool: TaxiiPool) -> Self {
-        Self { pool }
+        Self {
+            reads: pool.reader(),
+            pool,
+            hard_delete: false,
+            stale_job_age: DEFAULT_STALE_JOB_AGE,
+            partitioned: false,
+        }
+    }
+
+    /// Remove deleted objects immediately instead of keeping tombstones.
+    #[must_use]
+    pub fn with_hard_delete(mut self, hard_delete: bool) -> Self {
+        self.hard_delete = hard_delete;
+        self
+    }
+
+    /// Set the age after which a job that never completed is marked failed
+    /// by [`Taxii2Repository::cleanup_jobs`].
+    #[must_use]
+    pub fn with_stale_job_age(mut self, stale_job_age: Duration) -> Self {
+        self.stale_job_age = stale_job_age;
+        self
+    }
+
+    /// Create a partition of the STIX object table for every new collection
+    /// and drop it when the collection is deleted.
+    ///
+    /// Requires the table to be partitioned (see [`crate::partitioning`]).
+    #[must_use]
+    pub fn with_partitioning(mut self, partitioned: bool) -> Self {
+        self.partitioned = partitioned;
+        self
     }
 
     /// Get pool reference.
     pub fn pool(&self) -> &TaxiiPool {
         &self.pool
     }
+
+    /// Get the pool reads are sent to.
+    pub fn read_pool(&self) -> &TaxiiPool {
+        &self.reads
+    }
+
+    /// Add STIX objects as part of a unit of work.
+    ///
+    /// Like [`Taxii2Repository::add_objects_bulk_with_rejections`], but the job, objects,
+    /// job details and audit records are written in `tx`.
+    #[expect(
+        clippy::too_many_arguments,
+        reason = "the unit of work and the bulk insert parameters"
+    )]
+    pub async fn add_objects_bulk_in(
+        &self,
+        tx: &mut TaxiiTransaction,
+        api_root_id: &str,
+        collection_id: &str,
+        objects: &[serde_json::Value],
+        rejected: &BTreeMap<usize, String>,
+        policy: CollisionPolicy,
+        account_id: Option<i32>,
+    ) -> DatabaseResult<BulkInsertReport> {
+        use crate::models::taxii2::{AuditOperation, NewJobDetail, ObjectAudit, job_detail_status};
+
+        let api_root_uuid = Uuid::parse_str(api_root_id).map_err(|_| {
+            DatabaseError::NotFound(format!("Invalid API root UUID: {api_root_id}"))
+        })?;
+        let collection_uuid = Uuid::parse_str(collection_id).map_err(|_| {
+            DatabaseError::NotFound(format!("Invalid collection UUID: {collection_id}"))
+        })?;
+
+        // Prepare rows; rejected objects and objects without a usable ID are
+        // reported as failures
+        let mut outcomes = vec![BulkInsertOutcome::Failed; objects.len()];
+        let mut rows = Vec::with_capacity(objects.len());
+        for (index, obj) in objects.iter().enumerate() {
+            if rejected.contains_key(&index) {
+                continue;
+            }
+            let Some((stix_id, stix_type)) = obj["id"]
+                .as_str()
+                .and_then(|id| id.split_once("--").map(|(stix_type, _)| (id, stix_type)))
+            else {
+                continue;
+            };
+            let serialized_data: serde_json::Value = obj
+                .as_object()
+                .map(|o| {
+                    let filtered: serde_json::Map<String, serde_json::Value> = o
+                        .iter()
+                        .filter(|(k, _)| !["id", "type", "spec_version"].contains(&k.as_str()))
+                        .map(|(k, v)| (k.clone(), v.clone()))
+                        .collect();
+                    serde_json::Value::Object(filtered)
+                })
+                .unwrap_or_default();
+
+            rows.push(BulkRow {
+                index,
+                stix_id,
+                stix_type,
+                spec_version: obj["spec_version"].as_str().unwrap_or("2.1"),
+                // Rounded like PostgreSQL timestamps, to match returned rows
+                version: get_object_version(obj).round_subsecs(6),
+                serialized_data,
+            });
+        }
+
+        let job = crate::models::taxii2::Job::create_in(
+            tx.conn(),
+            &crate::models::taxii2::NewJob {
+                api_root_id: api_root_uuid,
+            },
+        )
+        .await?;
+        let job_id = job.id;
+        let now = job
+            .request_timestamp
+            .unwrap_or_else(|| Utc::now().naive_utc());
+
+        // An object whose version exists with other content, or repeats an
+        // earlier object of the batch with other content, is a collision
+        let ids: Vec<String> = rows.iter().map(|row| row.stix_id.to_string()).collect();
+        let mut current: HashMap<(String, NaiveDateTime), serde_json::Value> =
+            crate::models::taxii2::STIXObject::find_current_data(tx.conn(), collection_uuid, &ids)
+                .await?
+                .into_iter()
+                .map(|(id, version, data)| ((id, version), data))
+                .collect();
+        let mut new_rows = Vec::with_capacity(rows.len());
+        let mut overwrites: BTreeMap<(String, NaiveDateTime), &BulkRow<'_>> = BTreeMap::new();
+        let mut collisions = BTreeMap::new();
+        for row in &rows {
+            let key = (row.stix_id.to_string(), row.version.naive_utc());
+            match current.get(&key) {
+                None => {
+                    current.insert(key, row.serialized_data.clone());
+                    new_rows.push(row);
+                }
+                Some(data) if *data == row.serialized_data => {
+                    outcomes[row.index] = BulkInsertOutcome::Duplicate;
+                }
+                Some(_) => match policy {
+                    CollisionPolicy::Reject => {
+                        collisions.insert(
+                            row.index,
+                            format!(
+                                "Object {} version {} already exists with different content",
+                                row.stix_id,
+                                taxii_core::taxii2_datetimeformat(&row.version)
+                            ),
+                        );
+                    }
+                    CollisionPolicy::KeepExisting => {
+                        outcomes[row.index] = BulkInsertOutcome::Kept;
+                    }
+                    CollisionPolicy::Overwrite => {
+                        outcomes[row.index] = BulkInsertOutcome::Overwritten;
+                        current.insert(key.clone(), row.serialized_data.clone());
+                        overwrites.insert(key, row);
+                    }
+                },
+            }
+        }
+
+        let mut inserted = std::collections::HashSet::new();
+        for chunk in new_rows.chunks(BULK_INSERT_CHUNK_SIZE) {
+            let new_objects: Vec<_> = chunk
+                .iter()
+                .map(|row| crate::models::taxii2::NewSTIXObject {
+                    id: row.stix_id,
+                    collection_id: collection_uuid,
+                    stix_type: row.stix_type,
+                    spec_version: row.spec_version,
+                    version: row.version.naive_utc(),
+                    serialized_data: &row.serialized_data,
+                })
+                .collect();
+            inserted.extend(
+                crate::models::taxii2::STIXObject::create_many(
+                    tx.conn(),
+                    collection_uuid,
+                    &new_objects,
+                )
+                .await?,
+            );
+        }
+
+        // A new (id, version) counts as inserted, unless it was added
+        // concurrently
+        let mut created = Vec::with_capacity(inserted.len());
+        for row in &new_rows {
+            let key = (row.stix_id.to_string(), row.version.naive_utc());
+            outcomes[row.index] = if inserted.remove(&key) {
+                created.push(key);
+                BulkInsertOutcome::Inserted
+            } else {
+                BulkInsertOutcome::Duplicate
+            };
+        }
+        ObjectAudit::record_many(
+            tx.conn(),
+            account_id,
+            AuditOperation::Create,
+            collection_uuid,
+            &created,
+        )
+        .await?;
+
+        // Overwrites apply after the inserts, in batch order, so the last
+        // content submitted for a version wins
+        let mut overwrites: Vec<&BulkRow<'_>> = overwrites.into_values().collect();
+        overwrites.sort_by_key(|row| row.index);
+        for chunk in overwrites.chunks(BULK_INSERT_CHUNK_SIZE) {
+            let objects: Vec<_> = chunk
+                .iter()
+                .map(|row| crate::models::taxii2::NewSTIXObject {
+                    id: row.stix_id,
+                    collection_id: collection_uuid,
+                    stix_type: row.stix_type,
+                    spec_version: row.spec_version,
+                    version: row.version.naive_utc(),
+                    serialized_data: &row.serialized_data,
+                })
+                .collect();
+            crate::models::taxii2::STIXObject::overwrite_many(tx.conn(), collection_uuid, &objects)
+                .await?;
+        }
+        let updated: Vec<(String, NaiveDateTime)> = overwrites
+            .iter()
+            .map(|row| (row.stix_id.to_string(), row.version.naive_utc()))
+            .collect();
+        ObjectAudit::record_many(
+            tx.conn(),
+            account_id,
+            AuditOperation::Update,
+            collection_uuid,
+            &updated,
+        )
+        .await?;
+
+        let mut details = JobDetails::default();
+        let mut new_details = Vec::with_capacity(objects.len());
+        for (index, (obj, outcome)) in objects.iter().zip(&outcomes).enumerate() {
+            let (status, message) = match outcome {
+                BulkInsertOutcome::Failed => (
+                    job_detail_status::FAILURE,
+                    rejected
+                        .get(&index)
+                        .or_else(|| collisions.get(&index))
+                        .cloned()
+                        .unwrap_or_else(|| "Object has no valid STIX identifier".to_string()),
+                ),
+                BulkInsertOutcome::Overwritten => (
+                    job_detail_status::SUCCESS,
+                    "Replaced the existing content of this version".to_string(),
+                ),
+                BulkInsertOutcome::Kept => (
+                    job_detail_status::SUCCESS,
+                    "Kept the existing content of this version".to_string(),
+                ),
+                _ => (job_detail_status::SUCCESS, String::new()),
+            };
+            let detail_id = Uuid::new_v4();
+            let detail = JobDetail {
+                id: detail_id.to_string(),
+                job_id: job_id.to_string(),
+                stix_id: obj["id"].as_str().unwrap_or_default().to_string(),
+                version: get_object_version(obj).round_subsecs(6),
+                message,
+                status: status.to_string(),
+            };
+            new_details.push(NewJobDetail {
+                id: detail_id,
+                stix_id: detail.stix_id.clone(),
+                version: detail.version.naive_utc(),
+                status,
+                message: (!detail.message.is_empty()).then(|| detail.message.clone()),
+            });
+            match outcome {
+                BulkInsertOutcome::Failed => details.failure.push(detail),
+                _ => details.success.push(detail),
+            }
+        }
+
+        for chunk in new_details.chunks(BULK_INSERT_CHUNK_SIZE) {
+            crate::models::taxii2::JobDetail::create_many(tx.conn(), job_id, chunk).await?;
+        }
+
+        let total_count = i32::try_from(objects.len()).unwrap_or(i32::MAX);
+        let failure_count = i32::try_from(details.failure.len()).unwrap_or(i32::MAX);
+        let success_count = total_count - failure_count;
+        crate::models::taxii2::Job::complete_in(
+            tx.conn(),
+            job_id,
+            total_count,
+            success_count,
+            failure_count,
+        )
+        .await?;
+
+        Ok(BulkInsertReport {
+            job: Job {
+                id: job_id.to_string(),
+                api_root_id: api_root_id.to_string(),
+                status: "complete".to_string(),
+                request_timestamp: now.and_utc(),
+                completed_timestamp: Some(Utc::now()),
+                total_count,
+                success_count,
+                failure_count,
+                pending_count: 0,
+                details,
+            },
+            outcomes,
+        })
+    }
 }
 
 impl Taxii2Repository for DbTaxii2Repository {
+    fn hard_delete(&self) -> bool {
+        self.hard_delete
+    }
+
     // ========================================================================
     // API Root Operations
     // ========================================================================
 
     async fn get_api_roots(&self) -> DatabaseResult<Vec<ApiRoot>> {
-        let api_roots = crate::models::taxii2::ApiRoot::find_all(&self.pool).await?;
+        let api_roots = crate::models::taxii2::ApiRoot::find_all(&self.reads).await?;
         Ok(api_roots.into_iter().map(Into::into).collect())
     }
 
@@ -101,7 +464,7 @@ impl Taxii2Repository for DbTaxii2Repository {
         let uuid = Uuid::parse_str(api_root_id)
             .map_err(|_| DatabaseError::NotFound(format!("Invalid UUID: {api_root_id}")))?;
 
-        let api_root = crate::models::taxii2::ApiRoot::find(&self.pool, uuid).await?;
+        let api_root = crate::models::taxii2::ApiRoot::find(&self.reads, uuid).await?;
         Ok(api_root.map(Into::into))
     }
 
@@ -132,6 +495,39 @@ impl Taxii2Repository for DbTaxii2Repository {
         Ok(r.into())
     }
 
+    async fn update_api_root(
+        &self,
+        api_root_id: &str,
+        title: &str,
+        description: Option<&str>,
+        default: bool,
+        is_public: bool,
+        max_content_length: Option<u64>,
+    ) -> DatabaseResult<ApiRoot> {
+        let uuid = Uuid::parse_str(api_root_id)
+            .map_err(|_| DatabaseError::NotFound(format!("Invalid UUID: {api_root_id}")))?;
+        let max_content_length = max_content_length
+            .map(i64::try_from)
+            .transpose()
+            .map_err(|_| DatabaseError::InvalidData("max_content_length is too large".into()))?;
+
+        let api_root = crate::models::taxii2::ApiRoot::update(
+            &self.pool,
+            uuid,
+            title,
+            description,
+            default,
+            is_public,
+            max_content_length,
+        )
+        .await?
+        .ok_or_else(|| {
+            DatabaseError::NotFound(format!("API root with id {api_root_id} does not exist"))
+        })?;
+
+        Ok(api_root.into())
+    }
+
     // ========================================================================
     // Collection Operations (TAXII 2.x)
     // ========================================================================
@@ -141,7 +537,7 @@ impl Taxii2Repository for DbTaxii2Repository {
             .map_err(|_| DatabaseError::NotFound(format!("Invalid UUID: {api_root_id}")))?;
 
         let collections =
-            crate::models::taxii2::Collection::find_by_api_root(&self.pool, uuid).await?;
+            crate::models::taxii2::Collection::find_by_api_root(&self.reads, uuid).await?;
 
         Ok(collections.into_iter().map(Into::into).collect())
     }
@@ -156,7 +552,7 @@ impl Taxii2Repository for DbTaxii2Repository {
         })?;
 
         let collection = crate::models::taxii2::Collection::find_by_id_or_alias(
-            &self.pool,
+            &self.reads,
             api_root_uuid,
             collection_id_or_alias,
         )
@@ -173,13 +569,36 @@ impl Taxii2Repository for DbTaxii2Repository {
         alias: Option<&str>,
         is_public: bool,
         is_public_write: bool,
+        collection_id: Option<&str>,
     ) -> DatabaseResult<Collection> {
         let api_root_uuid = Uuid::parse_str(api_root_id).map_err(|_| {
             DatabaseError::NotFound(format!("Invalid API root UUID: {api_root_id}"))
         })?;
+        let id = match collection_id {
+            Some(id_str) => Uuid::parse_str(id_str)
+                .map_err(|e| DatabaseError::InvalidData(format!("Invalid UUID '{id_str}': {e}")))?,
+            None => Uuid::new_v4(),
+        };
 
-        let c = crate::models::taxii2::Collection::create(
-            &self.pool,
+        if !self.partitioned {
+            let c = crate::models::taxii2::Collection::create(
+                &self.pool,
+                id,
+                api_root_uuid,
+                title,
+                description,
+                alias,
+                is_public,
+                is_public_write,
+            )
+            .await?;
+            return Ok(c.into());
+        }
+
+        let mut tx = self.pool.begin().await?;
+        let c = crate::models::taxii2::Collection::create_in(
+            tx.conn(),
+            id,
             api_root_uuid,
             title,
             description,
@@ -188,10 +607,136 @@ impl Taxii2Repository for DbTaxii2Repository {
             is_public_write,
         )
         .await?;
+        partitioning::create_partition(tx.conn(), c.id).await?;
+        tx.commit().await?;
 
         Ok(c.into())
     }
 
+    async fn update_collection(
+        &self,
+        collection_id: &str,
+        title: &str,
+        description: Option<&str>,
+        alias: Option<&str>,
+        is_public: bool,
+        is_public_write: bool,
+    ) -> DatabaseResult<Collection> {
+        let collection_uuid = Uuid::parse_str(collection_id).map_err(|_| {
+            DatabaseError::NotFound(format!("Invalid collection UUID: {collection_id}"))
+        })?;
+
+        let collection = crate::models::taxii2::Collection::update(
+            &self.pool,
+            collection_uuid,
+            title,
+            description,
+            alias,
+            is_public,
+            is_public_write,
+        )
+        .await?
+        .ok_or_else(|| {
+            DatabaseError::NotFound(format!("Collection with id {collection_id} does not exist"))
+        })?;
+
+        Ok(collection.into())
+    }
+
+    async fn delete_collection(&self, collection_id: &str) -> DatabaseResult<()> {
+        let collection_uuid = Uuid::parse_str(collection_id).map_err(|_| {
+            DatabaseError::NotFound(format!("Invalid collection UUID: {collection_id}"))
+        })?;
+
+        // Dropping the partition is much faster than deleting its objects
+        let mut tx = self.pool.begin().await?;
+        if self.partitioned {
+            partitioning::drop_partition(tx.conn(), collection_uuid).await?;
+        }
+        if !crate::models::taxii2::Collection::delete_in(tx.conn(), collection_uuid).await? {
+            return Err(DatabaseError::NotFound(format!(
+                "Collection with id {collection_id} does not exist"
+            )));
+        }
+        CachedCollectionStats::delete_in(tx.conn(), protocol::TAXII2, collection_id).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn set_collection_retention(
+        &self,
+        collection_id: &str,
+        retention_days: Option<i32>,
+    ) -> DatabaseResult<Collection> {
+        let collection_uuid = Uuid::parse_str(collection_id).map_err(|_| {
+            DatabaseError::NotFound(format!("Invalid collection UUID: {collection_id}"))
+        })?;
+
+        let collection = crate::models::taxii2::Collection::set_retention_days(
+            &self.pool,
+            collection_uuid,
+            retention_days,
+        )
+        .await?
+        .ok_or_else(|| {
+            DatabaseError::NotFound(format!("Collection with id {collection_id} does not exist"))
+        })?;
+
+        Ok(collection.into())
+    }
+
+    async fn set_collection_validation_rules(
+        &self,
+        collection_id: &str,
+        rules: &[ValidationRule],
+    ) -> DatabaseResult<Collection> {
+        let collection_uuid = Uuid::parse_str(collection_id).map_err(|_| {
+            DatabaseError::NotFound(format!("Invalid collection UUID: {collection_id}"))
+        })?;
+
+        let names: Vec<String> = rules.iter().map(|r| r.name().to_string()).collect();
+        let collection = crate::models::taxii2::Collection::set_validation_rules(
+            &self.pool,
+            collection_uuid,
+            &names,
+        )
+        .await?
+        .ok_or_else(|| {
+            DatabaseError::NotFound(format!("Collection with id {collection_id} does not exist"))
+        })?;
+
+        Ok(collection.into())
+    }
+
+    async fn set_collection_default_marking(
+        &self,
+        collection_id: &str,
+        marking: Option<&str>,
+    ) -> DatabaseResult<Collection> {
+        let collection_uuid = Uuid::parse_str(collection_id).map_err(|_| {
+            DatabaseError::NotFound(format!("Invalid collection UUID: {collection_id}"))
+        })?;
+
+        let collection = crate::models::taxii2::Collection::set_default_marking(
+            &self.pool,
+            collection_uuid,
+            marking,
+        )
+        .await?
+        .ok_or_else(|| {
+            DatabaseError::NotFound(format!("Collection with id {collection_id} does not exist"))
+        })?;
+
+        Ok(collection.into())
+    }
+
+    async fn get_collections_with_retention(&self) -> DatabaseResult<Vec<Collection>> {
+        let collections =
+            crate::models::taxii2::Collection::find_with_retention(&self.pool).await?;
+        Ok(collections.into_iter().map(Into::into).collect())
+    }
+
     // ========================================================================
     // STIX Object Operations
     // ========================================================================
@@ -206,7 +751,7 @@ impl Taxii2Repository for DbTaxii2Repository {
         })?;
 
         let result =
-            crate::models::taxii2::STIXObject::find_filtered(&self.pool, collection_uuid, params)
+            crate::models::taxii2::STIXObject::find_filtered(&self.reads, collection_uuid, params)
                 .await?;
 
         let records = result.objects.into_iter().map(Into::into).collect();
@@ -224,7 +769,7 @@ impl Taxii2Repository for DbTaxii2Repository {
         })?;
 
         let result =
-            crate::models::taxii2::STIXObject::find_filtered(&self.pool, collection_uuid, params)
+            crate::models::taxii2::STIXObject::find_filtered(&self.reads, collection_uuid, params)
                 .await?;
 
         let objects = result.objects.into_iter().map(Into::into).collect();
@@ -232,124 +777,95 @@ impl Taxii2Repository for DbTaxii2Repository {
         Ok(PaginatedResult::new(objects, result.more, result.next))
     }
 
-    async fn add_objects(
+    async fn search_objects(
         &self,
-        api_root_id: &str,
         collection_id: &str,
-        objects: &[serde_json::Value],
-    ) -> DatabaseResult<Job> {
-        let api_root_uuid = Uuid::parse_str(api_root_id).map_err(|_| {
-            DatabaseError::NotFound(format!("Invalid API root UUID: {api_root_id}"))
-        })?;
+        query: &str,
+        params: &Taxii2QueryParams<'_>,
+    ) -> DatabaseResult<PaginatedResult<Vec<STIXObject>>> {
         let collection_uuid = Uuid::parse_str(collection_id).map_err(|_| {
             DatabaseError::NotFound(format!("Invalid collection UUID: {collection_id}"))
         })?;
 
-        // Create job using model
-        let job = crate::models::taxii2::Job::create(
-            &self.pool,
-            &crate::models::taxii2::NewJob {
-                api_root_id: api_root_uuid,
-            },
-        )
-        .await?;
-        let job_id = job.id;
-        let now = job
-            .request_timestamp
-            .unwrap_or_else(|| Utc::now().naive_utc());
+        let result =
+            crate::models::taxii2::STIXObject::search(&self.reads, collection_uuid, query, params)
+                .await?;
 
-        let mut job_details = Vec::new();
-        let mut total_count = 0;
-        let mut success_count = 0;
+        let objects = result.objects.into_iter().map(Into::into).collect();
 
-        for obj in objects {
-            let stix_id = obj["id"].as_str().unwrap_or_default();
-            let spec_version = obj["spec_version"].as_str().unwrap_or("2.1");
+        Ok(PaginatedResult::new(objects, result.more, result.next))
+    }
 
-            // Parse version using TAXII 2.1 fallback logic (modified -> created -> epoch)
-            let version = get_object_version(obj);
-            let version_naive = version.naive_utc();
+    fn stream_objects(
+        &self,
+        collection_id: &str,
+        params: &Taxii2QueryParams<'_>,
+    ) -> RowStream<STIXObject> {
+        let Ok(collection_uuid) = Uuid::parse_str(collection_id) else {
+            return error_stream(DatabaseError::NotFound(format!(
+                "Invalid collection UUID: {collection_id}"
+            )));
+        };
 
-            // Check if object already exists using model
-            let exists = crate::models::taxii2::STIXObject::exists(
-                &self.pool,
-                stix_id,
-                collection_uuid,
-                version_naive,
-            )
+        crate::models::taxii2::STIXObject::stream_filtered(&self.reads, collection_uuid, params)
+            .map(|row| row.map(Into::into))
+            .boxed()
+    }
+
+    async fn add_objects(
+        &self,
+        api_root_id: &str,
+        collection_id: &str,
+        objects: &[serde_json::Value],
+    ) -> DatabaseResult<Job> {
+        let report = self
+            .add_objects_bulk(api_root_id, collection_id, objects)
             .await?;
+        Ok(report.job)
+    }
 
-            if !exists {
-                let stix_type = stix_id.split("--").next().unwrap_or_default();
-                let serialized_data: serde_json::Value = obj
-                    .as_object()
-                    .map(|o| {
-                        let filtered: serde_json::Map<String, serde_json::Value> = o
-                            .iter()
-                            .filter(|(k, _)| !["id", "type", "spec_version"].contains(&k.as_str()))
-                            .map(|(k, v)| (k.clone(), v.clone()))
-                            .collect();
-                        serde_json::Value::Object(filtered)
-                    })
-                    .unwrap_or_default();
-
-                // Create STIX object using model
-                let new_obj = crate::models::taxii2::NewSTIXObject {
-                    id: stix_id,
-                    collection_id: collection_uuid,
-                    stix_type,
-                    spec_version,
-                    version: version_naive,
-                    serialized_data: &serialized_data,
-                };
-                crate::models::taxii2::STIXObject::create(&self.pool, &new_obj).await?;
-            }
+    async fn add_objects_bulk(
+        &self,
+        api_root_id: &str,
+        collection_id: &str,
+        objects: &[serde_json::Value],
+    ) -> DatabaseResult<BulkInsertReport> {
+        // Tools and bridges re-adding objects keep the stored content
+        self.add_objects_bulk_with_rejections(
+            api_root_id,
+            collection_id,
+            objects,
+            &BTreeMap::new(),
+            CollisionPolicy::KeepExisting,
+            None,
+        )
+        .await
+    }
 
-            // Create job detail using model
-            let detail = crate::models::taxii2::JobDetail::create(
-                &self.pool,
-                job_id,
-                stix_id,
-                version_naive,
-                crate::models::taxii2::job_detail_status::SUCCESS,
-                None,
+    async fn add_objects_bulk_with_rejections(
+        &self,
+        api_root_id: &str,
+        collection_id: &str,
+        objects: &[serde_json::Value],
+        rejected: &BTreeMap<usize, String>,
+        policy: CollisionPolicy,
+        account_id: Option<i32>,
+    ) -> DatabaseResult<BulkInsertReport> {
+        let mut tx = self.pool.begin().await?;
+        let report = self
+            .add_objects_bulk_in(
+                &mut tx,
+                api_root_id,
+                collection_id,
+                objects,
+                rejected,
+                policy,
+                account_id,
             )
             .await?;
-
-            job_details.push(JobDetail {
-                id: detail.id.to_string(),
-                job_id: detail.job_id.to_string(),
-                stix_id: detail.stix_id,
-                version,
-                message: String::new(),
-                status: "success".to_string(),
-            });
-
-            total_count += 1;
-            success_count += 1;
-        }
-
-        // Complete job using model
-        crate::models::taxii2::Job::complete(&self.pool, job_id, total_count, success_count, 0)
-            .await?;
-
-        // Build job entity
-        let mut details = JobDetails::default();
-        details.success.extend(job_details);
-
-        Ok(Job {
-            id: job_id.to_string(),
-            api_root_id: api_root_id.to_string(),
-            status: "complete".to_string(),
-            request_timestamp: now.and_utc(),
-            completed_timestamp: Some(Utc::now()),
-            total_count,
-            success_count,
-            failure_count: 0,
-            pending_count: 0,
-            details,
-        })
-    }
+        tx.commit().await?;
+        Ok(report)
+    }
 
     async fn get_job_and_details(
         &self,
@@ -362,6 +878,8 @@ impl Taxii2Repository for DbTaxii2Repository {
         let job_uuid = Uuid::parse_str(job_id)
             .map_err(|_| DatabaseError::NotFound(format!("Invalid job UUID: {job_id}")))?;
 
+        // Clients poll the status right after the POST, so this reads the
+        // primary rather than a possibly lagging read pool
         let job = crate::models::taxii2::Job::find_by_api_root(&self.pool, api_root_uuid, job_uuid)
             .await?;
 
@@ -420,7 +938,7 @@ impl Taxii2Repository for DbTaxii2Repository {
 
         // Check if object exists in collection
         let exists = crate::models::taxii2::STIXObject::exists_any_version(
-            &self.pool,
+            &self.reads,
             object_id,
             collection_uuid,
         )
@@ -438,7 +956,7 @@ impl Taxii2Repository for DbTaxii2Repository {
         };
 
         let result = crate::models::taxii2::STIXObject::find_filtered(
-            &self.pool,
+            &self.reads,
             collection_uuid,
             &params_with_id,
         )
@@ -449,29 +967,137 @@ impl Taxii2Repository for DbTaxii2Repository {
         Ok(PaginatedResult::new(objects, result.more, result.next))
     }
 
+    async fn resolve_object_version(
+        &self,
+        collection_id: &str,
+        object_id: &str,
+        selector: VersionSelector,
+    ) -> DatabaseResult<Option<STIXObject>> {
+        let collection_uuid = Uuid::parse_str(collection_id).map_err(|_| {
+            DatabaseError::NotFound(format!("Invalid collection UUID: {collection_id}"))
+        })?;
+
+        let obj = crate::models::taxii2::STIXObject::find_version(
+            &self.pool,
+            collection_uuid,
+            object_id,
+            selector,
+        )
+        .await?;
+
+        Ok(obj.map(Into::into))
+    }
+
     async fn delete_object(
         &self,
         collection_id: &str,
         object_id: &str,
         match_version: Option<&[String]>,
         match_spec_version: Option<&[String]>,
+        deleted_by: Option<i32>,
     ) -> DatabaseResult<()> {
         let collection_uuid = Uuid::parse_str(collection_id).map_err(|_| {
             DatabaseError::NotFound(format!("Invalid collection UUID: {collection_id}"))
         })?;
 
-        crate::models::taxii2::STIXObject::delete_filtered(
-            &self.pool,
+        let mode = if self.hard_delete {
+            DeleteMode::Hard
+        } else {
+            DeleteMode::Soft { deleted_by }
+        };
+
+        // The deleted versions are audited in the transaction of the delete
+        let mut tx = self.pool.begin().await?;
+        let deleted = crate::models::taxii2::STIXObject::delete_filtered(
+            tx.conn(),
             collection_uuid,
             object_id,
             match_version,
             match_spec_version,
+            mode,
         )
         .await?;
+        crate::models::taxii2::ObjectAudit::record_many(
+            tx.conn(),
+            deleted_by,
+            crate::models::taxii2::AuditOperation::Delete,
+            collection_uuid,
+            &deleted,
+        )
+        .await?;
+        tx.commit().await?;
 
         Ok(())
     }
 
+    async fn list_deletions(
+        &self,
+        collection_id: &str,
+        since: DateTime<Utc>,
+    ) -> DatabaseResult<Vec<DeletionRecord>> {
+        let collection_uuid = Uuid::parse_str(collection_id).map_err(|_| {
+            DatabaseError::NotFound(format!("Invalid collection UUID: {collection_id}"))
+        })?;
+
+        let deletions =
+            crate::models::taxii2::STIXObject::find_deletions(&self.reads, collection_uuid, since)
+                .await?;
+
+        Ok(deletions.into_iter().map(Into::into).collect())
+    }
+
+    async fn purge_deleted(&self, older_than: DateTime<Utc>) -> DatabaseResult<u64> {
+        crate::models::taxii2::STIXObject::purge_deleted(&self.pool, older_than).await
+    }
+
+    async fn purge_expired(
+        &self,
+        collection_id: &str,
+        cutoff: DateTime<Utc>,
+    ) -> DatabaseResult<PurgeStats> {
+        let collection_uuid = Uuid::parse_str(collection_id).map_err(|_| {
+            DatabaseError::NotFound(format!("Invalid collection UUID: {collection_id}"))
+        })?;
+
+        let mode = if self.hard_delete {
+            DeleteMode::Hard
+        } else {
+            DeleteMode::Soft { deleted_by: None }
+        };
+
+        let mut stats = PurgeStats::default();
+        loop {
+            let purged = crate::models::taxii2::STIXObject::purge_added_before(
+                &self.pool,
+                collection_uuid,
+                cutoff,
+                mode,
+                PURGE_BATCH_SIZE,
+            )
+            .await?;
+            stats.purged += purged;
+            if purged < PURGE_BATCH_SIZE as u64 {
+                break;
+            }
+        }
+
+        Ok(stats)
+    }
+
+    async fn collection_stats(&self, collection_id: &str) -> DatabaseResult<CollectionStats> {
+        let collection_uuid = Uuid::parse_str(collection_id).map_err(|_| {
+            DatabaseError::NotFound(format!("Invalid collection UUID: {collection_id}"))
+        })?;
+
+        if let Some(cached) =
+            CachedCollectionStats::find(&self.reads, protocol::TAXII2, collection_id).await?
+        {
+            return Ok(cached.into());
+        }
+
+        crate::stats::compute_taxii2(&self.reads, collection_uuid).await
+    }
+
     async fn get_versions(
         &self,
         collection_id: &str,
@@ -486,7 +1112,7 @@ impl Taxii2Repository for DbTaxii2Repository {
         })?;
 
         let result = crate::models::taxii2::STIXObject::find_versions(
-            &self.pool,
+            &self.reads,
             collection_uuid,
             object_id,
             limit,
@@ -504,9 +1130,44 @@ impl Taxii2Repository for DbTaxii2Repository {
         Ok(PaginatedResult::new(records, result.more, result.next))
     }
 
-    async fn job_cleanup(&self) -> DatabaseResult<i32> {
-        let count = crate::models::taxii2::Job::cleanup_old(&self.pool).await?;
-        Ok(count as i32)
+    async fn cleanup_jobs(
+        &self,
+        older_than: DateTime<Utc>,
+        keep_incomplete: bool,
+    ) -> DatabaseResult<CleanupStats> {
+        let mut stats = CleanupStats::default();
+
+        if keep_incomplete {
+            let stale_cutoff = (Utc::now() - self.stale_job_age).naive_utc();
+            stats.jobs_failed =
+                crate::models::taxii2::Job::fail_stale(&self.pool, stale_cutoff).await?;
+        }
+
+        loop {
+            let (jobs, details) = crate::models::taxii2::Job::delete_before(
+                &self.pool,
+                older_than.naive_utc(),
+                !keep_incomplete,
+                PURGE_BATCH_SIZE,
+            )
+            .await?;
+            stats.jobs_removed += jobs;
+            stats.details_removed += details;
+            if jobs < PURGE_BATCH_SIZE as u64 {
+                break;
+            }
+        }
+
+        if stats != CleanupStats::default() {
+            info!(
+                jobs_removed = stats.jobs_removed,
+                details_removed = stats.details_removed,
+                jobs_failed = stats.jobs_failed,
+                "Cleaned up job logs"
+            );
+        }
+
+        Ok(stats)
     }
 }
 
@@ -517,8 +1178,14 @@ impl Taxii2Repository for DbTaxii2Repository {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::taxii2::{PropertyFilter, parse_next_param};
+    use crate::stream::STREAM_BUFFER_SIZE;
     use chrono::Datelike;
     use serde_json::json;
+    use sqlx::PgPool;
+    use std::collections::HashSet;
+
+    type TestResult = Result<(), Box<dyn std::error::Error>>;
 
     /// Test get_object_version with modified field present.
     #[test]
@@ -597,4 +1264,1678 @@ mod tests {
         assert_eq!(version.month(), 8);
         assert_eq!(version.day(), 15);
     }
+
+    /// Collections resolve by alias or ID, IDs taking precedence over
+    /// aliases that look like UUIDs.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_get_collection_by_id_or_alias(pool: PgPool) -> TestResult {
+        let repo = DbTaxii2Repository::new(TaxiiPool::new(pool));
+        let api_root = repo.add_api_root("Root", None, true, false, None).await?;
+        let other_root = repo.add_api_root("Other", None, false, false, None).await?;
+        let indicators = repo
+            .add_collection(
+                &api_root.id,
+                "Indicators",
+                None,
+                Some("indicators"),
+                false,
+                false,
+                None,
+            )
+            .await?;
+        let shadow = repo
+            .add_collection(
+                &api_root.id,
+                "Shadow",
+                None,
+                Some(&indicators.id),
+                false,
+                false,
+                None,
+            )
+            .await?;
+        let elsewhere = repo
+            .add_collection(
+                &other_root.id,
+                "Indicators",
+                None,
+                Some("indicators"),
+                false,
+                false,
+                None,
+            )
+            .await?;
+
+        let resolve = |root: &str, id_or_alias: &str| {
+            let (repo, root, id_or_alias) = (&repo, root.to_string(), id_or_alias.to_string());
+            async move {
+                repo.get_collection(&root, &id_or_alias)
+                    .await
+                    .map(|c| c.map(|c| c.id))
+            }
+        };
+
+        assert_eq!(
+            resolve(&api_root.id, "indicators").await?,
+            Some(indicators.id.clone())
+        );
+        assert_eq!(
+            resolve(&api_root.id, &indicators.id).await?,
+            Some(indicators.id.clone())
+        );
+        assert_eq!(
+            resolve(&api_root.id, &shadow.id).await?,
+            Some(shadow.id.clone())
+        );
+        assert_eq!(
+            resolve(&other_root.id, "indicators").await?,
+            Some(elsewhere.id.clone())
+        );
+        assert_eq!(resolve(&api_root.id, "unknown").await?, None);
+        // Collections of other API roots do not resolve by ID either
+        assert_eq!(resolve(&other_root.id, &indicators.id).await?, None);
+
+        Ok(())
+    }
+
+    fn indicator(n: usize) -> serde_json::Value {
+        json!({
+            "id": format!("indicator--00000000-0000-4000-8000-{n:012}"),
+            "type": "indicator",
+            "spec_version": "2.1",
+            "created": "2024-01-01T00:00:00.000Z",
+            "modified": "2024-01-01T00:00:00.000Z",
+            "pattern": "[ipv4-addr:value = '10.0.0.1']",
+            "pattern_type": "stix",
+            "valid_from": "2024-01-01T00:00:00Z"
+        })
+    }
+
+    /// Objects added while paging are returned exactly once, in a later page.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_recently_added_paging_under_writes(pool: PgPool) -> TestResult {
+        let repo = DbTaxii2Repository::new(TaxiiPool::new(pool));
+        let api_root = repo.add_api_root("Root", None, true, false, None).await?;
+        let collection = repo
+            .add_collection(&api_root.id, "Tail", None, None, false, false, None)
+            .await?;
+
+        let initial: Vec<_> = (0..5).map(indicator).collect();
+        repo.add_objects(&api_root.id, &collection.id, &initial)
+            .await?;
+        let mut added = initial.len();
+
+        let mut seen = Vec::new();
+        let mut cursor: Option<PaginationCursor> = None;
+        let match_version = ["all".to_string()];
+        loop {
+            let params = Taxii2QueryParams {
+                limit: Some(2),
+                next: cursor.as_ref(),
+                match_version: Some(&match_version),
+                ..Default::default()
+            };
+            let page = repo.get_objects(&collection.id, &params).await?;
+            seen.extend(page.items.iter().map(|o| o.id.clone()));
+
+            // Concurrent writers add more objects between page fetches
+            if added < 15 {
+                let first = [indicator(added)];
+                let second = [indicator(added + 1)];
+                let (a, b) = tokio::join!(
+                    repo.add_objects(&api_root.id, &collection.id, &first),
+                    repo.add_objects(&api_root.id, &collection.id, &second),
+                );
+                a?;
+                b?;
+                added += 2;
+            }
+
+            match page.next {
+                Some(next) if page.more => {
+                    cursor = Some(parse_next_param(&next).ok_or("invalid next param")?);
+                }
+                _ => break,
+            }
+        }
+
+        let unique: HashSet<&String> = seen.iter().collect();
+        assert_eq!(unique.len(), seen.len(), "objects repeated: {seen:?}");
+        assert_eq!(seen.len(), added, "objects skipped: {seen:?}");
+
+        Ok(())
+    }
+
+    /// Count stored objects of a collection.
+    async fn count_objects(pool: &PgPool, collection_id: &str) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar("SELECT COUNT(*) FROM opentaxii_stixobject WHERE collection_id = $1")
+            .bind(Uuid::parse_str(collection_id).unwrap_or_default())
+            .fetch_one(pool)
+            .await
+    }
+
+    /// A batch mixing new, existing, repeated and invalid objects reports
+    /// each outcome and stores only the new objects.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_bulk_insert_partial_duplicates(pool: PgPool) -> TestResult {
+        let repo = DbTaxii2Repository::new(TaxiiPool::new(pool.clone()));
+        let api_root = repo.add_api_root("Root", None, true, false, None).await?;
+        let collection = repo
+            .add_collection(&api_root.id, "Bulk", None, None, false, false, None)
+            .await?;
+
+        let first = repo
+            .add_objects_bulk(&api_root.id, &collection.id, &[indicator(0), indicator(1)])
+            .await?;
+        assert_eq!(first.count(BulkInsertOutcome::Inserted), 2);
+
+        let batch = [
+            indicator(0),
+            indicator(2),
+            indicator(1),
+            indicator(2),
+            json!({"type": "indicator", "spec_version": "2.1"}),
+            indicator(3),
+        ];
+        let report = repo
+            .add_objects_bulk(&api_root.id, &collection.id, &batch)
+            .await?;
+
+        use BulkInsertOutcome::{Duplicate, Failed, Inserted};
+        assert_eq!(
+            report.outcomes,
+            vec![Duplicate, Inserted, Duplicate, Duplicate, Failed, Inserted]
+        );
+        assert_eq!(report.job.total_count, 6);
+        assert_eq!(report.job.success_count, 5);
+        assert_eq!(report.job.failure_count, 1);
+        assert_eq!(count_objects(&pool, &collection.id).await?, 4);
+
+        // The status resource is stored with the same details
+        let job = repo
+            .get_job_and_details(&api_root.id, &report.job.id)
+            .await?
+            .ok_or("job not found")?;
+        assert_eq!(job.status, "complete");
+        assert_eq!(job.details.success.len(), 5);
+        assert_eq!(job.details.failure.len(), 1);
+
+        // Batch order is kept in date_added
+        let params = Taxii2QueryParams::default();
+        let objects = repo.get_objects(&collection.id, &params).await?.items;
+        let ids: Vec<&str> = objects.iter().map(|o| o.id.as_str()).collect();
+        assert_eq!(
+            ids,
+            vec![
+                "indicator--00000000-0000-4000-8000-000000000000",
+                "indicator--00000000-0000-4000-8000-000000000001",
+                "indicator--00000000-0000-4000-8000-000000000002",
+                "indicator--00000000-0000-4000-8000-000000000003",
+            ]
+        );
+
+        Ok(())
+    }
+
+    /// Batches larger than one chunk are inserted completely.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_bulk_insert_spans_chunks(pool: PgPool) -> TestResult {
+        let repo = DbTaxii2Repository::new(TaxiiPool::new(pool.clone()));
+        let api_root = repo.add_api_root("Root", None, true, false, None).await?;
+        let collection = repo
+            .add_collection(&api_root.id, "Chunks", None, None, false, false, None)
+            .await?;
+
+        let objects: Vec<_> = (0..BULK_INSERT_CHUNK_SIZE * 2 + 7).map(indicator).collect();
+        let report = repo
+            .add_objects_bulk(&api_root.id, &collection.id, &objects)
+            .await?;
+
+        assert_eq!(report.count(BulkInsertOutcome::Inserted), objects.len());
+        assert_eq!(
+            count_objects(&pool, &collection.id).await?,
+            i64::try_from(objects.len())?
+        );
+
+        Ok(())
+    }
+
+    /// Benchmark-style comparison with one insert round trip per object.
+    ///
+    /// The bulk path issues a constant number of statements per chunk, so it
+    /// must be substantially faster than the per-object path it replaced.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_bulk_insert_fewer_round_trips(pool: PgPool) -> TestResult {
+        const OBJECTS: usize = 2000;
+
+        let taxii_pool = TaxiiPool::new(pool.clone());
+        let repo = DbTaxii2Repository::new(taxii_pool.clone());
+        let api_root = repo.add_api_root("Root", None, true, false, None).await?;
+        let per_object = repo
+            .add_collection(&api_root.id, "Per object", None, None, false, false, None)
+            .await?;
+        let bulk = repo
+            .add_collection(&api_root.id, "Bulk", None, None, false, false, None)
+            .await?;
+        let objects: Vec<_> = (0..OBJECTS).map(indicator).collect();
+
+        let started = std::time::Instant::now();
+        let per_object_uuid = Uuid::parse_str(&per_object.id)?;
+        for obj in &objects {
+            let version = get_object_version(obj).naive_utc();
+            crate::models::taxii2::STIXObject::exists(
+                &taxii_pool,
+                obj["id"].as_str().unwrap_or_default(),
+                per_object_uuid,
+                version,
+            )
+            .await?;
+            crate::models::taxii2::STIXObject::create(
+                &taxii_pool,
+                &crate::models::taxii2::NewSTIXObject {
+                    id: obj["id"].as_str().unwrap_or_default(),
+                    collection_id: per_object_uuid,
+                    stix_type: "indicator",
+                    spec_version: "2.1",
+                    version,
+                    serialized_data: obj,
+                },
+            )
+            .await?;
+        }
+        let per_object_elapsed = started.elapsed();
+
+        let started = std::time::Instant::now();
+        repo.add_objects_bulk(&api_root.id, &bulk.id, &objects)
+            .await?;
+        let bulk_elapsed = started.elapsed();
+
+        assert_eq!(
+            count_objects(&pool, &bulk.id).await?,
+            i64::try_from(OBJECTS)?
+        );
+        assert!(
+            bulk_elapsed * 5 < per_object_elapsed,
+            "bulk insert took {bulk_elapsed:?}, per-object inserts took {per_object_elapsed:?}"
+        );
+
+        Ok(())
+    }
+
+    /// Indicator `n` with the given `modified` timestamp.
+    fn indicator_version(n: usize, modified: &str) -> serde_json::Value {
+        let mut obj = indicator(n);
+        obj["modified"] = json!(modified);
+        obj
+    }
+
+    /// Fetch every page of a query, returning `(id, version)` of each item.
+    async fn collect_pages(
+        repo: &DbTaxii2Repository,
+        collection_id: &str,
+        params: &Taxii2QueryParams<'_>,
+        limit: i64,
+    ) -> Result<Vec<(String, DateTime<Utc>)>, Box<dyn std::error::Error>> {
+        let mut seen = Vec::new();
+        let mut cursor: Option<PaginationCursor> = None;
+        loop {
+            let page_params = Taxii2QueryParams {
+                limit: Some(limit),
+                next: cursor.as_ref(),
+                ..params.clone()
+            };
+            let page = repo.get_objects(collection_id, &page_params).await?;
+            seen.extend(page.items.iter().map(|o| (o.id.clone(), o.version)));
+
+            match page.next {
+                Some(next) if page.more => {
+                    cursor = Some(parse_next_param(&next).ok_or("invalid next param")?);
+                }
+                _ => return Ok(seen),
+            }
+        }
+    }
+
+    /// Paging through any filter returns exactly the unpaged result, in the
+    /// same order, without skipping or repeating rows.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_keyset_paging_matches_unpaged(pool: PgPool) -> TestResult {
+        let repo = DbTaxii2Repository::new(TaxiiPool::new(pool));
+        let api_root = repo.add_api_root("Root", None, true, false, None).await?;
+        let collection = repo
+            .add_collection(&api_root.id, "Keyset", None, None, false, false, None)
+            .await?;
+
+        let batches = [
+            (0..6).map(indicator).collect::<Vec<_>>(),
+            // Newer versions of some objects
+            vec![
+                indicator_version(1, "2024-03-01T00:00:00.000Z"),
+                indicator_version(4, "2024-03-01T00:00:00.000Z"),
+            ],
+            // Older versions added after newer ones, and new objects
+            vec![
+                indicator_version(4, "2023-06-01T00:00:00.000Z"),
+                indicator_version(2, "2023-06-01T00:00:00.000Z"),
+                indicator(6),
+                indicator(7),
+            ],
+            vec![json!({
+                "id": "malware--00000000-0000-4000-8000-000000000000",
+                "type": "malware",
+                "spec_version": "2.1",
+                "created": "2024-01-01T00:00:00.000Z",
+                "modified": "2024-01-01T00:00:00.000Z",
+                "name": "Keyset",
+                "is_family": false
+            })],
+        ];
+        let mut batch_added = Vec::new();
+        for batch in &batches {
+            repo.add_objects(&api_root.id, &collection.id, batch)
+                .await?;
+            let all = repo
+                .get_objects(&collection.id, &Taxii2QueryParams::default())
+                .await?;
+            batch_added.push(all.items.iter().map(|o| o.date_added).max());
+        }
+
+        let added_after = batch_added[0];
+        let all = ["all".to_string()];
+        let first = ["first".to_string()];
+        let last = ["last".to_string()];
+        let indicators = ["indicator".to_string()];
+        let filters = [
+            Taxii2QueryParams::default(),
+            Taxii2QueryParams {
+                match_version: Some(&all),
+                ..Default::default()
+            },
+            Taxii2QueryParams {
+                match_version: Some(&first),
+                ..Default::default()
+            },
+            Taxii2QueryParams {
+                match_version: Some(&last),
+                match_type: Some(&indicators),
+                ..Default::default()
+            },
+            Taxii2QueryParams {
+                match_version: Some(&all),
+                added_after,
+                ..Default::default()
+            },
+            Taxii2QueryParams {
+                added_after,
+                ..Default::default()
+            },
+        ];
+
+        for params in &filters {
+            let unpaged: Vec<_> = repo
+                .get_objects(&collection.id, params)
+                .await?
+                .items
+                .into_iter()
+                .map(|o| (o.id, o.version))
+                .collect();
+            assert!(!unpaged.is_empty(), "no objects for {params:?}");
+
+            for limit in [1, 2, 3] {
+                let paged = collect_pages(&repo, &collection.id, params, limit).await?;
+                assert_eq!(paged, unpaged, "limit {limit}, params {params:?}");
+            }
+        }
+
+        // Only the latest version of each object is listed by default, even
+        // when an older version was added later
+        let latest = collect_pages(&repo, &collection.id, &filters[0], 2).await?;
+        let ids: HashSet<&String> = latest.iter().map(|(id, _)| id).collect();
+        assert_eq!(ids.len(), latest.len());
+        assert_eq!(latest.len(), 9);
+        assert!(
+            latest
+                .iter()
+                .all(|(id, version)| { !id.ends_with("000000000004") || version.month() == 3 })
+        );
+
+        Ok(())
+    }
+
+    /// Streaming yields the same objects, in the same order, as a query.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_stream_objects(pool: PgPool) -> TestResult {
+        let repo = DbTaxii2Repository::new(TaxiiPool::new(pool));
+        let api_root = repo.add_api_root("Root", None, true, false, None).await?;
+        let collection = repo
+            .add_collection(&api_root.id, "Stream", None, None, false, false, None)
+            .await?;
+        let objects: Vec<_> = (0..STREAM_BUFFER_SIZE * 4).map(indicator).collect();
+        repo.add_objects(&api_root.id, &collection.id, &objects)
+            .await?;
+
+        let types = ["indicator".to_string()];
+        let params = Taxii2QueryParams {
+            match_type: Some(&types),
+            ..Default::default()
+        };
+        let expected: Vec<String> = repo
+            .get_objects(&collection.id, &params)
+            .await?
+            .items
+            .into_iter()
+            .map(|o| o.id)
+            .collect();
+
+        let mut stream = repo.stream_objects(&collection.id, &params);
+        let mut streamed = Vec::new();
+        while let Some(object) = stream.next().await {
+            streamed.push(object?.id);
+        }
+        assert_eq!(streamed, expected);
+
+        let mut invalid = repo.stream_objects("not-a-uuid", &params);
+        assert!(matches!(
+            invalid.next().await,
+            Some(Err(DatabaseError::NotFound(_)))
+        ));
+        assert!(invalid.next().await.is_none());
+
+        Ok(())
+    }
+
+    /// Dropping a stream part way returns its connection to the pool.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_dropped_stream_releases_connection(pool: PgPool) -> TestResult {
+        let repo = DbTaxii2Repository::new(TaxiiPool::new(pool.clone()));
+        let api_root = repo.add_api_root("Root", None, true, false, None).await?;
+        let collection = repo
+            .add_collection(&api_root.id, "Stream", None, None, false, false, None)
+            .await?;
+        let objects: Vec<_> = (0..STREAM_BUFFER_SIZE * 20).map(indicator).collect();
+        repo.add_objects(&api_root.id, &collection.id, &objects)
+            .await?;
+
+        let mut stream = repo.stream_objects(&collection.id, &Taxii2QueryParams::default());
+        stream.next().await.ok_or("empty stream")??;
+        assert!(pool.num_idle() < pool.size() as usize);
+
+        drop(stream);
+        for _ in 0..100 {
+            if pool.num_idle() == pool.size() as usize {
+                return Ok(());
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        Err("connection not released".into())
+    }
+
+    /// First, last and exact version selectors each resolve one version.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_resolve_object_version(pool: PgPool) -> TestResult {
+        let repo = DbTaxii2Repository::new(TaxiiPool::new(pool));
+        let api_root = repo.add_api_root("Root", None, true, false, None).await?;
+        let collection = repo
+            .add_collection(&api_root.id, "Versions", None, None, false, false, None)
+            .await?;
+        // Versions are added out of order
+        let objects = [
+            indicator_version(0, "2024-02-01T00:00:00.000Z"),
+            indicator_version(0, "2024-03-01T00:00:00.000Z"),
+            indicator_version(0, "2024-01-01T00:00:00.000Z"),
+        ];
+        repo.add_objects(&api_root.id, &collection.id, &objects)
+            .await?;
+        let id = "indicator--00000000-0000-4000-8000-000000000000";
+
+        let resolve = |selector: &str| {
+            let selector = VersionSelector::parse(selector);
+            let repo = &repo;
+            let collection_id = collection.id.clone();
+            async move {
+                let selector = selector.ok_or("invalid selector")?;
+                let obj = repo
+                    .resolve_object_version(&collection_id, id, selector)
+                    .await?;
+                Ok::<_, Box<dyn std::error::Error>>(obj.map(|o| o.version.to_rfc3339()))
+            }
+        };
+
+        assert_eq!(
+            resolve("first").await?.as_deref(),
+            Some("2024-01-01T00:00:00+00:00")
+        );
+        assert_eq!(
+            resolve("last").await?.as_deref(),
+            Some("2024-03-01T00:00:00+00:00")
+        );
+        assert_eq!(
+            resolve("2024-02-01T00:00:00Z").await?.as_deref(),
+            Some("2024-02-01T00:00:00+00:00")
+        );
+        assert_eq!(resolve("2024-02-02T00:00:00Z").await?, None);
+
+        let missing = repo
+            .resolve_object_version(&collection.id, "indicator--missing", VersionSelector::Last)
+            .await?;
+        assert!(missing.is_none());
+
+        Ok(())
+    }
+
+    /// Rows sharing a version (dirty data) resolve to the same row every time.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_resolve_object_version_duplicate_modified(pool: PgPool) -> TestResult {
+        sqlx::query(
+            "ALTER TABLE opentaxii_stixobject
+             DROP CONSTRAINT opentaxii_stixobject_collection_id_id_version_key",
+        )
+        .execute(&pool)
+        .await?;
+
+        let repo = DbTaxii2Repository::new(TaxiiPool::new(pool));
+        let api_root = repo.add_api_root("Root", None, true, false, None).await?;
+        let collection = repo
+            .add_collection(&api_root.id, "Dirty", None, None, false, false, None)
+            .await?;
+        let mut first_copy = indicator(0);
+        first_copy["name"] = json!("first copy");
+        let mut second_copy = indicator(0);
+        second_copy["name"] = json!("second copy");
+        let id = "indicator--00000000-0000-4000-8000-000000000000";
+        let version = get_object_version(&first_copy);
+        for obj in [&first_copy, &second_copy] {
+            crate::models::taxii2::STIXObject::create(
+                repo.pool(),
+                &crate::models::taxii2::NewSTIXObject {
+                    id,
+                    collection_id: Uuid::parse_str(&collection.id)?,
+                    stix_type: "indicator",
+                    spec_version: "2.1",
+                    version: version.naive_utc(),
+                    serialized_data: obj,
+                },
+            )
+            .await?;
+        }
+
+        let expected = [
+            (VersionSelector::First, "first copy"),
+            (VersionSelector::Last, "second copy"),
+            (VersionSelector::Exact(version), "second copy"),
+        ];
+        for (selector, name) in expected {
+            for _ in 0..3 {
+                let obj = repo
+                    .resolve_object_version(&collection.id, id, selector)
+                    .await?
+                    .ok_or("version not found")?;
+                assert_eq!(obj.serialized_data["name"], name, "{selector:?}");
+            }
+        }
+
+        // Listings pick the same copies and list them in the same order
+        for _ in 0..3 {
+            for (selector, name) in [("first", "first copy"), ("last", "second copy")] {
+                let match_version = [selector.to_string()];
+                let params = Taxii2QueryParams {
+                    match_version: Some(&match_version),
+                    ..Default::default()
+                };
+                let objects = repo.get_objects(&collection.id, &params).await?.items;
+                assert_eq!(objects.len(), 1, "{selector}");
+                assert_eq!(objects[0].serialized_data["name"], name, "{selector}");
+            }
+
+            let page = repo
+                .get_versions(&collection.id, id, Some(1), None, None, None)
+                .await?;
+            assert!(page.more);
+            let cursor = page.next.as_deref().and_then(parse_next_param);
+            assert!(cursor.is_some());
+            let rest = repo
+                .get_versions(&collection.id, id, Some(1), None, cursor, None)
+                .await?;
+            assert!(!rest.more);
+            let listed: Vec<_> = page.items.iter().chain(&rest.items).collect();
+            assert_eq!(listed.len(), 2);
+            assert!(listed[0].date_added <= listed[1].date_added);
+            assert!(listed.iter().all(|v| v.version == version));
+        }
+
+        Ok(())
+    }
+
+    /// Every stored, overwritten and deleted version gets one audit record
+    /// with the account, written with the change.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_object_audit(pool: PgPool) -> TestResult {
+        use crate::models::account_activity::ActivityCursor;
+        use crate::models::taxii2::{AuditOperation, ObjectAudit, ObjectAuditFilter};
+
+        let repo = DbTaxii2Repository::new(TaxiiPool::new(pool.clone()));
+        let api_root = repo.add_api_root("Root", None, true, false, None).await?;
+        let collection = repo
+            .add_collection(&api_root.id, "Audited", None, None, false, false, None)
+            .await?;
+        let account_id: i32 = sqlx::query_scalar(
+            "INSERT INTO accounts (username, password_hash) VALUES ('alice', 'x') RETURNING id",
+        )
+        .fetch_one(&pool)
+        .await?;
+        let audit = |operation| ObjectAuditFilter {
+            collection_id: Uuid::parse_str(&collection.id).ok(),
+            operations: vec![operation],
+            ..Default::default()
+        };
+
+        // One create per stored version; duplicates are not audited
+        let objects = [
+            indicator_version(0, "2024-01-01T00:00:00.000Z"),
+            indicator_version(0, "2024-02-01T00:00:00.000Z"),
+            indicator(1),
+        ];
+        for _ in 0..2 {
+            repo.add_objects_bulk_with_rejections(
+                &api_root.id,
+                &collection.id,
+                &objects,
+                &BTreeMap::new(),
+                CollisionPolicy::Overwrite,
+                Some(account_id),
+            )
+            .await?;
+        }
+        let created = ObjectAudit::find_filtered(repo.pool(), &audit(AuditOperation::Create))
+            .await?
+            .items;
+        assert_eq!(created.len(), 3);
+        assert!(created.iter().all(|r| r.account_id == Some(account_id)));
+        assert!(
+            created
+                .iter()
+                .all(|r| r.username.as_deref() == Some("alice"))
+        );
+
+        // Overwriting a version is an update
+        let mut changed = indicator(1);
+        changed["pattern"] = json!("[ipv4-addr:value = '10.0.0.2']");
+        repo.add_objects_bulk_with_rejections(
+            &api_root.id,
+            &collection.id,
+            &[changed],
+            &BTreeMap::new(),
+            CollisionPolicy::Overwrite,
+            Some(account_id),
+        )
+        .await?;
+        let updated = ObjectAudit::find_filtered(repo.pool(), &audit(AuditOperation::Update))
+            .await?
+            .items;
+        assert_eq!(updated.len(), 1);
+        assert_eq!(updated[0].object_id, indicator(1)["id"]);
+
+        // One delete per deleted version; deleting again is not audited
+        let id = "indicator--00000000-0000-4000-8000-000000000000";
+        for _ in 0..2 {
+            repo.delete_object(&collection.id, id, None, None, Some(account_id))
+                .await?;
+        }
+        let deleted = ObjectAudit::find_filtered(repo.pool(), &audit(AuditOperation::Delete))
+            .await?
+            .items;
+        assert_eq!(deleted.len(), 2);
+        assert!(deleted.iter().all(|r| r.object_id == id));
+        let mut versions: Vec<_> = deleted.iter().map(|r| r.object_version.month()).collect();
+        versions.sort_unstable();
+        assert_eq!(versions, [1, 2]);
+
+        // Writes rolled back leave no audit records
+        let mut tx = repo.pool().begin().await?;
+        repo.add_objects_bulk_in(
+            &mut tx,
+            &api_root.id,
+            &collection.id,
+            &[indicator(2)],
+            &BTreeMap::new(),
+            CollisionPolicy::Reject,
+            Some(account_id),
+        )
+        .await?;
+        tx.rollback().await?;
+        let filter = ObjectAuditFilter {
+            object_id: Some(indicator(2)["id"].as_str().unwrap_or_default().to_string()),
+            ..Default::default()
+        };
+        assert!(
+            ObjectAudit::find_filtered(repo.pool(), &filter)
+                .await?
+                .items
+                .is_empty()
+        );
+
+        // Records are paged newest first by account
+        let first = ObjectAudit::find_filtered(
+            repo.pool(),
+            &ObjectAuditFilter {
+                username: Some("alice".to_string()),
+                limit: Some(4),
+                ..Default::default()
+            },
+        )
+        .await?;
+        assert!(first.more);
+        let after = first.next.as_deref().and_then(ActivityCursor::parse);
+        let rest = ObjectAudit::find_filtered(
+            repo.pool(),
+            &ObjectAuditFilter {
+                account_id: Some(account_id),
+                after,
+                ..Default::default()
+            },
+        )
+        .await?;
+        assert_eq!(first.items.len() + rest.items.len(), 6);
+        assert_eq!(first.items[0].operation, "delete");
+
+        // Records cannot be altered
+        assert!(
+            sqlx::query("DELETE FROM object_audit")
+                .execute(&pool)
+                .await
+                .is_err()
+        );
+
+        Ok(())
+    }
+
+    /// Deleted versions are hidden from reads, listed as deletions and
+    /// removed for good by a purge.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_soft_delete_and_purge(pool: PgPool) -> TestResult {
+        let repo = DbTaxii2Repository::new(TaxiiPool::new(pool.clone()));
+        let api_root = repo.add_api_root("Root", None, true, false, None).await?;
+        let collection = repo
+            .add_collection(&api_root.id, "Tombstones", None, None, false, false, None)
+            .await?;
+        let account_id: i32 = sqlx::query_scalar(
+            "INSERT INTO accounts (username, password_hash) VALUES ('alice', 'x') RETURNING id",
+        )
+        .fetch_one(&pool)
+        .await?;
+
+        let objects = [
+            indicator_version(0, "2024-01-01T00:00:00.000Z"),
+            indicator_version(0, "2024-02-01T00:00:00.000Z"),
+            indicator(1),
+        ];
+        repo.add_objects(&api_root.id, &collection.id, &objects)
+            .await?;
+        let id = "indicator--00000000-0000-4000-8000-000000000000";
+        let before_delete = Utc::now();
+
+        // Deleting the last version makes the previous one the latest
+        let last = ["last".to_string()];
+        repo.delete_object(&collection.id, id, Some(&last), None, Some(account_id))
+            .await?;
+        let latest = repo
+            .resolve_object_version(&collection.id, id, VersionSelector::Last)
+            .await?
+            .ok_or("object not found")?;
+        assert_eq!(latest.version.month(), 1);
+
+        // Deleting the remaining version hides the object everywhere
+        repo.delete_object(&collection.id, id, None, None, Some(account_id))
+            .await?;
+        let params = Taxii2QueryParams::default();
+        assert!(
+            repo.get_object(&collection.id, id, &params)
+                .await?
+                .items
+                .is_empty()
+        );
+        let all = ["all".to_string()];
+        let all_versions = Taxii2QueryParams {
+            match_version: Some(&all),
+            ..Default::default()
+        };
+        let remaining = repo.get_objects(&collection.id, &all_versions).await?.items;
+        assert!(remaining.iter().all(|o| o.id != id));
+        assert_eq!(
+            repo.get_manifest(&collection.id, &params)
+                .await?
+                .items
+                .len(),
+            1
+        );
+        let versions = repo
+            .get_versions(&collection.id, id, None, None, None, None)
+            .await?;
+        assert!(versions.items.is_empty());
+
+        // The rows are kept as tombstones
+        assert_eq!(count_objects(&pool, &collection.id).await?, 3);
+        let deletions = repo.list_deletions(&collection.id, before_delete).await?;
+        assert_eq!(deletions.len(), 2);
+        assert!(
+            deletions
+                .iter()
+                .all(|d| d.id == id && d.deleted_by == Some(account_id))
+        );
+        assert!(deletions[0].deleted_at <= deletions[1].deleted_at);
+        assert!(
+            repo.list_deletions(&collection.id, Utc::now())
+                .await?
+                .is_empty()
+        );
+
+        // Purging only removes tombstones older than the horizon
+        assert_eq!(repo.purge_deleted(before_delete).await?, 0);
+        assert_eq!(repo.purge_deleted(Utc::now()).await?, 2);
+        assert_eq!(count_objects(&pool, &collection.id).await?, 1);
+        assert!(
+            repo.list_deletions(&collection.id, before_delete)
+                .await?
+                .is_empty()
+        );
+
+        Ok(())
+    }
+
+    /// Re-adding a deleted version restores it as a newly added object.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_readd_deleted_version(pool: PgPool) -> TestResult {
+        let repo = DbTaxii2Repository::new(TaxiiPool::new(pool));
+        let api_root = repo.add_api_root("Root", None, true, false, None).await?;
+        let collection = repo
+            .add_collection(&api_root.id, "Restore", None, None, false, false, None)
+            .await?;
+        repo.add_objects(&api_root.id, &collection.id, &[indicator(0), indicator(1)])
+            .await?;
+        let id = "indicator--00000000-0000-4000-8000-000000000000";
+        repo.delete_object(&collection.id, id, None, None, None)
+            .await?;
+
+        let report = repo
+            .add_objects_bulk(&api_root.id, &collection.id, &[indicator(0), indicator(0)])
+            .await?;
+        use BulkInsertOutcome::{Duplicate, Inserted};
+        assert_eq!(report.outcomes, vec![Inserted, Duplicate]);
+
+        let objects = repo
+            .get_objects(&collection.id, &Taxii2QueryParams::default())
+            .await?
+            .items;
+        let ids: Vec<&str> = objects.iter().map(|o| o.id.as_str()).collect();
+        assert_eq!(
+            ids,
+            vec!["indicator--00000000-0000-4000-8000-000000000001", id]
+        );
+
+        Ok(())
+    }
+
+    /// Old complete jobs are removed, old incomplete ones kept or marked
+    /// failed once stale, recent ones kept.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_cleanup_jobs(pool: PgPool) -> TestResult {
+        use crate::models::taxii2::{Job, JobDetail, NewJob, job_detail_status};
+
+        let repo = DbTaxii2Repository::new(TaxiiPool::new(pool.clone()))
+            .with_stale_job_age(Duration::days(90));
+        let api_root = repo.add_api_root("Root", None, true, false, None).await?;
+        let api_root_id = Uuid::parse_str(&api_root.id)?;
+
+        // A job requested `age` days ago, completed right after if `complete`
+        let seed = |age: i64, complete: bool| {
+            let pool = repo.pool().clone();
+            async move {
+                let job = Job::create(&pool, &NewJob { api_root_id }).await?;
+                let status = if complete {
+                    job_detail_status::SUCCESS
+                } else {
+                    job_detail_status::PENDING
+                };
+                for n in 0..2 {
+                    JobDetail::create(
+                        &pool,
+                        job.id,
+                        &format!("indicator--00000000-0000-4000-8000-{n:012}"),
+                        Utc::now().naive_utc(),
+                        status,
+                        None,
+                    )
+                    .await?;
+                }
+                if complete {
+                    Job::complete(&pool, job.id, 2, 2, 0).await?;
+                }
+                let at = (Utc::now() - Duration::days(age)).naive_utc();
+                sqlx::query(
+                    "UPDATE opentaxii_job SET request_timestamp = $2,
+                         completed_timestamp = CASE WHEN status = 'complete' THEN $2 END,
+                         pending_count = CASE WHEN status = 'pending' THEN 2 ELSE 0 END
+                     WHERE id = $1",
+                )
+                .bind(job.id)
+                .bind(at)
+                .execute(pool.inner())
+                .await?;
+                Ok::<_, Box<dyn std::error::Error>>(job.id)
+            }
+        };
+        let old_complete = seed(40, true).await?;
+        let old_incomplete = seed(40, false).await?;
+        let stale = seed(100, false).await?;
+        let recent = seed(1, true).await?;
+
+        let older_than = Utc::now() - Duration::days(30);
+        let stats = repo.cleanup_jobs(older_than, true).await?;
+        assert_eq!(
+            stats,
+            CleanupStats {
+                jobs_removed: 1,
+                details_removed: 2,
+                jobs_failed: 1,
+            }
+        );
+        assert!(Job::find(repo.pool(), old_complete).await?.is_none());
+        assert!(
+            JobDetail::find_by_job(repo.pool(), old_complete)
+                .await?
+                .is_empty()
+        );
+        assert_eq!(
+            Job::find(repo.pool(), old_incomplete)
+                .await?
+                .map(|j| j.status),
+            Some("pending".to_string())
+        );
+        assert!(Job::find(repo.pool(), recent).await?.is_some());
+
+        // The stale job failed and is removed once past the retention
+        let failed = Job::find(repo.pool(), stale)
+            .await?
+            .ok_or("stale job removed")?;
+        assert_eq!(failed.status, "complete");
+        assert_eq!(
+            (failed.failure_count, failed.pending_count),
+            (Some(2), Some(0))
+        );
+        let details = JobDetail::find_by_job(repo.pool(), stale).await?;
+        assert!(
+            details
+                .iter()
+                .all(|d| d.status == job_detail_status::FAILURE)
+        );
+
+        // Without keeping incomplete jobs, old pending ones are removed too
+        let stats = repo.cleanup_jobs(older_than, false).await?;
+        assert_eq!((stats.jobs_removed, stats.jobs_failed), (1, 0));
+        assert!(Job::find(repo.pool(), old_incomplete).await?.is_none());
+        assert!(Job::find(repo.pool(), stale).await?.is_some());
+        assert!(Job::find(repo.pool(), recent).await?.is_some());
+
+        Ok(())
+    }
+
+    /// Objects and their job are rolled back with the unit of work.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_add_objects_rolls_back(pool: PgPool) -> TestResult {
+        let repo = DbTaxii2Repository::new(TaxiiPool::new(pool.clone()));
+        let api_root = repo.add_api_root("Root", None, true, false, None).await?;
+        let collection = repo
+            .add_collection(&api_root.id, "Atomic", None, None, false, false, None)
+            .await?;
+
+        // A validation failure after the first write
+        let mut tx = repo.pool().begin().await?;
+        let report = repo
+            .add_objects_bulk_in(
+                &mut tx,
+                &api_root.id,
+                &collection.id,
+                &[indicator(0)],
+                &BTreeMap::new(),
+                CollisionPolicy::Reject,
+                None,
+            )
+            .await?;
+        tx.rollback().await?;
+
+        let objects = repo
+            .get_objects(&collection.id, &Default::default())
+            .await?;
+        assert!(objects.items.is_empty());
+        assert!(
+            repo.get_job_and_details(&api_root.id, &report.job.id)
+                .await?
+                .is_none()
+        );
+        let details: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM opentaxii_job_detail")
+            .fetch_one(&pool)
+            .await?;
+        assert_eq!(details, 0);
+
+        let mut tx = repo.pool().begin().await?;
+        let report = repo
+            .add_objects_bulk_in(
+                &mut tx,
+                &api_root.id,
+                &collection.id,
+                &[indicator(0)],
+                &BTreeMap::new(),
+                CollisionPolicy::Reject,
+                None,
+            )
+            .await?;
+        tx.commit().await?;
+        assert!(
+            repo.get_job_and_details(&api_root.id, &report.job.id)
+                .await?
+                .is_some()
+        );
+        let objects = repo
+            .get_objects(&collection.id, &Default::default())
+            .await?;
+        assert_eq!(objects.items.len(), 1);
+
+        Ok(())
+    }
+
+    /// Reads go to the read pool, writes and transactions to the primary.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_reads_use_read_pool(pool: PgPool) -> TestResult {
+        use crate::repository::{DbTaxii1Repository, Taxii1Repository};
+
+        // Same database, separate pool: it only opens connections on use
+        let read = sqlx::postgres::PgPoolOptions::new()
+            .max_connections(1)
+            .connect_lazy_with((*pool.connect_options()).clone());
+        let taxii_pool = TaxiiPool::new(pool.clone()).with_read_pool(read.clone());
+        assert!(taxii_pool.has_read_pool());
+        let repo = DbTaxii2Repository::new(taxii_pool.clone());
+
+        let api_root = repo.add_api_root("Root", None, true, false, None).await?;
+        let collection = repo
+            .add_collection(&api_root.id, "Reads", None, None, false, false, None)
+            .await?;
+        repo.add_objects(&api_root.id, &collection.id, &[indicator(0)])
+            .await?;
+        assert_eq!(read.size(), 0);
+
+        let objects = repo
+            .get_objects(&collection.id, &Default::default())
+            .await?;
+        assert_eq!(objects.items.len(), 1);
+        assert_eq!(read.size(), 1);
+
+        let taxii1 = DbTaxii1Repository::new(taxii_pool);
+        assert!(taxii1.read_pool().inner().size() > 0);
+        assert!(taxii1.get_collections(None).await?.is_empty());
+
+        // Without a read pool, reads use the primary
+        let primary = TaxiiPool::new(pool);
+        assert!(!primary.has_read_pool());
+        let repo = DbTaxii2Repository::new(primary);
+        assert!(!repo.read_pool().has_read_pool());
+        assert_eq!(repo.get_api_roots().await?.len(), 1);
+
+        Ok(())
+    }
+
+    /// With hard deletes, rows are removed immediately.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_hard_delete(pool: PgPool) -> TestResult {
+        let repo = DbTaxii2Repository::new(TaxiiPool::new(pool.clone())).with_hard_delete(true);
+        let api_root = repo.add_api_root("Root", None, true, false, None).await?;
+        let collection = repo
+            .add_collection(&api_root.id, "Hard", None, None, false, false, None)
+            .await?;
+        repo.add_objects(&api_root.id, &collection.id, &[indicator(0)])
+            .await?;
+        let since = Utc::now() - chrono::Duration::days(1);
+
+        let id = "indicator--00000000-0000-4000-8000-000000000000";
+        repo.delete_object(&collection.id, id, None, None, None)
+            .await?;
+
+        assert_eq!(count_objects(&pool, &collection.id).await?, 0);
+        assert!(repo.list_deletions(&collection.id, since).await?.is_empty());
+
+        Ok(())
+    }
+
+    /// Property filters select in SQL exactly the objects the Rust reference
+    /// implementation selects, including unicode values and objects lacking
+    /// the property.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_property_filters_match_reference(pool: PgPool) -> TestResult {
+        let repo = DbTaxii2Repository::new(TaxiiPool::new(pool));
+        let api_root = repo.add_api_root("Root", None, true, false, None).await?;
+        let collection = repo
+            .add_collection(&api_root.id, "Filters", None, None, false, false, None)
+            .await?;
+
+        let green = "marking-definition--34098fce-860f-48ae-8e50-ebd3cc5e41da";
+        let red = "marking-definition--5e57c739-391a-4eb3-b6be-7d15ca92d5ed";
+        let identity = "identity--00000000-0000-4000-8000-000000000001";
+        let properties = [
+            json!({"labels": ["malicious-activity", "café"]}),
+            json!({"labels": ["cafe\u{301}"], "pattern_type": "sigma"}),
+            json!({"labels": "malicious-activity", "created_by_ref": identity}),
+            json!({"labels": ["日本語", "c2"], "object_marking_refs": [green]}),
+            json!({"labels": [], "object_marking_refs": [green, red]}),
+            json!({"labels": [["c2"]], "created_by_ref": identity}),
+            json!({"labels": 42, "object_marking_refs": [red]}),
+            json!({"labels": null, "pattern_type": "yara"}),
+            json!({"pattern_type": "snort", "created_by_ref": "identity--other"}),
+            json!({"labels": ["MALICIOUS-ACTIVITY"], "relationship_type": "o'clock"}),
+        ];
+        let objects: Vec<_> = properties
+            .iter()
+            .enumerate()
+            .map(|(n, extra)| {
+                let mut obj = indicator(n);
+                for (key, value) in extra.as_object().into_iter().flatten() {
+                    obj[key] = value.clone();
+                }
+                obj
+            })
+            .collect();
+        repo.add_objects(&api_root.id, &collection.id, &objects)
+            .await?;
+
+        let filter = |property: &str, values: &[&str]| {
+            PropertyFilter::new(property, values.iter().map(|v| v.to_string()).collect())
+        };
+        let cases = [
+            vec![filter("labels", &["malicious-activity"])],
+            vec![filter("labels", &["café", "日本語"])],
+            vec![filter("labels", &["cafe\u{301}"])],
+            vec![filter("labels", &["c2"])],
+            vec![filter("labels", &["42"])],
+            vec![filter("pattern_type", &["stix"])],
+            vec![filter("pattern_type", &["sigma", "yara", "snort"])],
+            vec![filter("created_by_ref", &[identity])],
+            vec![filter("object_marking_refs", &[red])],
+            vec![filter("relationship_type", &["o'clock"])],
+            vec![
+                filter("object_marking_refs", &[green]),
+                filter("labels", &["c2"]),
+            ],
+            vec![filter("source_ref", &["anything"])],
+        ];
+
+        let all_versions = ["all".to_string()];
+        for filters in cases {
+            let params = Taxii2QueryParams {
+                match_version: Some(&all_versions),
+                match_properties: Some(&filters),
+                ..Default::default()
+            };
+            let mut sql: Vec<String> = repo
+                .get_objects(&collection.id, &params)
+                .await?
+                .items
+                .into_iter()
+                .map(|o| o.id)
+                .collect();
+            sql.sort_unstable();
+
+            let reference: Vec<String> = objects
+                .iter()
+                .filter(|obj| filters.iter().all(|f| f.matches(obj)))
+                .filter_map(|obj| obj["id"].as_str().map(str::to_string))
+                .collect();
+            assert_eq!(sql, reference, "{filters:?}");
+        }
+
+        Ok(())
+    }
+
+    /// Indicator `n` with the given name and description.
+    fn described_indicator(n: usize, name: &str, description: &str) -> serde_json::Value {
+        let mut obj = indicator(n);
+        obj["name"] = json!(name);
+        obj["description"] = json!(description);
+        obj
+    }
+
+    /// Search a collection, returning the IDs of the matches.
+    async fn search_ids(
+        repo: &DbTaxii2Repository,
+        collection_id: &str,
+        query: &str,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let page = repo
+            .search_objects(collection_id, query, &Taxii2QueryParams::default())
+            .await?;
+        Ok(page.items.into_iter().map(|o| o.id).collect())
+    }
+
+    /// Multi-word queries match across properties, name matches first.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_search_objects(pool: PgPool) -> TestResult {
+        let repo = DbTaxii2Repository::new(TaxiiPool::new(pool));
+        let api_root = repo.add_api_root("Root", None, true, false, None).await?;
+        let collection = repo
+            .add_collection(&api_root.id, "Search", None, None, false, false, None)
+            .await?;
+
+        let mut with_pattern = indicator(4);
+        with_pattern["pattern"] = json!("[domain-name:value = 'evil.example.com']");
+        let objects = [
+            described_indicator(0, "Beacon", "Part of the Ghost Ship campaigns"),
+            described_indicator(1, "Unrelated", "Nothing to see"),
+            described_indicator(2, "Ghost Ship loader", "Talks to evil.example.com"),
+            described_indicator(3, "Ship tracker", "Ghost of a chance"),
+            with_pattern,
+        ];
+        repo.add_objects(&api_root.id, &collection.id, &objects)
+            .await?;
+        let id = |n: usize| format!("indicator--00000000-0000-4000-8000-{n:012}");
+
+        // All words must match; the name match ranks before the earlier
+        // description match
+        assert_eq!(
+            search_ids(&repo, &collection.id, "ghost ship campaign").await?,
+            vec![id(0)]
+        );
+        assert_eq!(
+            search_ids(&repo, &collection.id, "ghost ship").await?,
+            vec![id(2), id(0), id(3)]
+        );
+
+        // Phrases, exclusions and alternatives
+        assert_eq!(
+            search_ids(&repo, &collection.id, "\"ghost ship\" -beacon").await?,
+            vec![id(2)]
+        );
+        assert_eq!(
+            search_ids(&repo, &collection.id, "unrelated or tracker").await?,
+            vec![id(1), id(3)]
+        );
+
+        // Values inside descriptions and patterns
+        assert_eq!(
+            search_ids(&repo, &collection.id, "evil.example.com").await?,
+            vec![id(2), id(4)]
+        );
+        assert!(
+            search_ids(&repo, &collection.id, "phishing")
+                .await?
+                .is_empty()
+        );
+
+        // Deleted objects are not found
+        repo.delete_object(&collection.id, &id(2), None, None, None)
+            .await?;
+        assert_eq!(
+            search_ids(&repo, &collection.id, "ghost ship").await?,
+            vec![id(0), id(3)]
+        );
+
+        Ok(())
+    }
+
+    /// Search results page in relevance order without gaps or repeats.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_search_objects_paging(pool: PgPool) -> TestResult {
+        let repo = DbTaxii2Repository::new(TaxiiPool::new(pool));
+        let api_root = repo.add_api_root("Root", None, true, false, None).await?;
+        let collection = repo
+            .add_collection(&api_root.id, "Search", None, None, false, false, None)
+            .await?;
+
+        // Name and description matches interleaved by date added
+        let objects: Vec<_> = (0..11)
+            .map(|n| {
+                if n % 3 == 0 {
+                    described_indicator(n, "Cobalt Strike beacon", "Seen in the wild")
+                } else {
+                    described_indicator(n, "Other", "Drops a Cobalt Strike beacon")
+                }
+            })
+            .collect();
+        repo.add_objects(&api_root.id, &collection.id, &objects)
+            .await?;
+        let query = "cobalt strike";
+
+        let unpaged = search_ids(&repo, &collection.id, query).await?;
+        let expected: Vec<_> = [0, 3, 6, 9, 1, 2, 4, 5, 7, 8, 10]
+            .iter()
+            .map(|n| format!("indicator--00000000-0000-4000-8000-{n:012}"))
+            .collect();
+        assert_eq!(unpaged, expected);
+
+        let mut seen = Vec::new();
+        let mut cursor: Option<PaginationCursor> = None;
+        loop {
+            let params = Taxii2QueryParams {
+                limit: Some(3),
+                next: cursor.as_ref(),
+                ..Default::default()
+            };
+            let page = repo.search_objects(&collection.id, query, &params).await?;
+            seen.extend(page.items.into_iter().map(|o| o.id));
+
+            match page.next {
+                Some(next) if page.more => {
+                    cursor = Some(parse_next_param(&next).ok_or("invalid next param")?);
+                }
+                _ => break,
+            }
+        }
+        assert_eq!(seen, expected);
+
+        Ok(())
+    }
+
+    /// Page latency does not grow with page depth.
+    ///
+    /// Seeds 100k rows, so it only runs with the `slow-tests` feature.
+    #[cfg(feature = "slow-tests")]
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_keyset_page_latency_independent_of_depth(pool: PgPool) -> TestResult {
+        const OBJECTS: usize = 100_000;
+        const PAGE: i64 = 100;
+
+        let repo = DbTaxii2Repository::new(TaxiiPool::new(pool.clone()));
+        let api_root = repo.add_api_root("Root", None, true, false, None).await?;
+        let collection = repo
+            .add_collection(&api_root.id, "Deep", None, None, false, false, None)
+            .await?;
+        for chunk in (0..OBJECTS).collect::<Vec<_>>().chunks(10_000) {
+            let objects: Vec<_> = chunk.iter().copied().map(indicator).collect();
+            repo.add_objects_bulk(&api_root.id, &collection.id, &objects)
+                .await?;
+        }
+        sqlx::query("ANALYZE opentaxii_stixobject")
+            .execute(&pool)
+            .await?;
+
+        // Cursor positioned before the row at `depth`
+        let cursor_at = |depth: i64| {
+            let pool = pool.clone();
+            let collection_id = Uuid::parse_str(&collection.id).unwrap_or_default();
+            async move {
+                let (date_added, id): (chrono::NaiveDateTime, String) = sqlx::query_as(
+                    "SELECT date_added, id FROM opentaxii_stixobject
+                     WHERE collection_id = $1 ORDER BY date_added, id OFFSET $2 LIMIT 1",
+                )
+                .bind(collection_id)
+                .bind(depth)
+                .fetch_one(&pool)
+                .await?;
+                Ok::<_, sqlx::Error>(PaginationCursor::new(date_added.and_utc(), id))
+            }
+        };
+
+        let mut median_latency = Vec::new();
+        for depth in [0, 50_000, 99_000] {
+            let cursor = cursor_at(depth).await?;
+            let params = Taxii2QueryParams {
+                limit: Some(PAGE),
+                next: Some(&cursor),
+                ..Default::default()
+            };
+            let mut samples = Vec::new();
+            for _ in 0..7 {
+                let started = std::time::Instant::now();
+                let page = repo.get_objects(&collection.id, &params).await?;
+                samples.push(started.elapsed());
+                assert_eq!(page.items.len(), usize::try_from(PAGE)?);
+            }
+            samples.sort();
+            median_latency.push(samples[samples.len() / 2]);
+        }
+
+        let shallow = median_latency[0].max(std::time::Duration::from_millis(5));
+        for deep in &median_latency[1..] {
+            assert!(
+                *deep < shallow * 3,
+                "page latencies by depth: {median_latency:?}"
+            );
+        }
+
+        Ok(())
+    }
+
+    /// A version posted again with different content is rejected, replaces
+    /// the stored content, or is ignored depending on the policy; the same
+    /// content is always a duplicate.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_collision_policies(pool: PgPool) -> TestResult {
+        let repo = DbTaxii2Repository::new(TaxiiPool::new(pool));
+        let api_root = repo.add_api_root("Root", None, true, false, None).await?;
+
+        let original = indicator(0);
+        let mut changed = indicator(0);
+        changed["pattern"] = json!("[ipv4-addr:value = '10.0.0.2']");
+
+        for (policy, outcome, status, pattern) in [
+            (
+                CollisionPolicy::Reject,
+                BulkInsertOutcome::Failed,
+                "failure",
+                "[ipv4-addr:value = '10.0.0.1']",
+            ),
+            (
+                CollisionPolicy::Overwrite,
+                BulkInsertOutcome::Overwritten,
+                "success",
+                "[ipv4-addr:value = '10.0.0.2']",
+            ),
+            (
+                CollisionPolicy::KeepExisting,
+                BulkInsertOutcome::Kept,
+                "success",
+                "[ipv4-addr:value = '10.0.0.1']",
+            ),
+        ] {
+            let collection = repo
+                .add_collection(&api_root.id, "Colliding", None, None, false, false, None)
+                .await?;
+            repo.add_objects_bulk_with_rejections(
+                &api_root.id,
+                &collection.id,
+                &[original.clone()],
+                &BTreeMap::new(),
+                policy,
+                None,
+            )
+            .await?;
+            let before = repo
+                .get_objects(&collection.id, &Default::default())
+                .await?;
+
+            let report = repo
+                .add_objects_bulk_with_rejections(
+                    &api_root.id,
+                    &collection.id,
+                    &[original.clone(), changed.clone()],
+                    &BTreeMap::new(),
+                    policy,
+                    None,
+                )
+                .await?;
+            assert_eq!(
+                report.outcomes,
+                [BulkInsertOutcome::Duplicate, outcome],
+                "{policy:?}"
+            );
+
+            let job = repo
+                .get_job_and_details(&api_root.id, &report.job.id)
+                .await?
+                .ok_or("job not found")?;
+            let details = if status == "failure" {
+                &job.details.failure
+            } else {
+                &job.details.success
+            };
+            let detail = details.last().ok_or("missing job detail")?;
+            assert_eq!(detail.status, status, "{policy:?}");
+            assert!(!detail.message.is_empty(), "{policy:?}");
+
+            let after = repo
+                .get_objects(&collection.id, &Default::default())
+                .await?;
+            assert_eq!(after.items.len(), 1, "{policy:?}");
+            assert_eq!(
+                after.items[0].serialized_data["pattern"], pattern,
+                "{policy:?}"
+            );
+            // Overwritten content is added again for clients paging by date
+            assert_eq!(
+                after.items[0].date_added > before.items[0].date_added,
+                policy == CollisionPolicy::Overwrite,
+                "{policy:?}"
+            );
+        }
+
+        Ok(())
+    }
+
+    /// A collection requiring confidence rejects an indicator without it.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_validation_rules_reject_objects(pool: PgPool) -> TestResult {
+        let repo = DbTaxii2Repository::new(TaxiiPool::new(pool));
+        let api_root = repo.add_api_root("Root", None, true, false, None).await?;
+        let collection = repo
+            .add_collection(
+                &api_root.id,
+                "Vetted",
+                None,
+                Some("vetted"),
+                false,
+                false,
+                None,
+            )
+            .await?;
+        assert!(collection.validation_rules.is_empty());
+
+        repo.set_collection_validation_rules(&collection.id, &[ValidationRule::RequireConfidence])
+            .await?;
+        let collection = repo
+            .get_collection(&api_root.id, "vetted")
+            .await?
+            .ok_or("collection not found")?;
+        assert_eq!(
+            collection.validation_rules,
+            vec![ValidationRule::RequireConfidence]
+        );
+
+        let mut confident = indicator(1);
+        confident["confidence"] = json!(80);
+        let objects = [indicator(0), confident];
+        let rejected: BTreeMap<usize, String> = objects
+            .iter()
+            .enumerate()
+            .filter_map(|(i, obj)| collection.check_object(obj).err().map(|msg| (i, msg)))
+            .collect();
+        assert_eq!(rejected.keys().collect::<Vec<_>>(), vec![&0]);
+
+        let report = repo
+            .add_objects_bulk_with_rejections(
+                &api_root.id,
+                &collection.id,
+                &objects,
+                &rejected,
+                CollisionPolicy::Reject,
+                None,
+            )
+            .await?;
+        assert_eq!(
+            report.outcomes,
+            vec![BulkInsertOutcome::Failed, BulkInsertOutcome::Inserted]
+        );
+        assert_eq!(report.job.failure_count, 1);
+        assert_eq!(
+            report.job.details.failure[0].message,
+            format!(
+                "Object {} has no confidence",
+                objects[0]["id"].as_str().unwrap_or_default()
+            )
+        );
+
+        let stored = repo
+            .get_objects(&collection.id, &Default::default())
+            .await?;
+        assert_eq!(stored.items.len(), 1);
+        assert_eq!(stored.items[0].id, objects[1]["id"]);
+
+        // Removing the rules accepts the indicator again
+        let collection = repo
+            .set_collection_validation_rules(&collection.id, &[])
+            .await?;
+        assert!(collection.check_object(&objects[0]).is_ok());
+
+        Ok(())
+    }
+
+    /// The default marking of a collection is stored and removed.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_default_marking(pool: PgPool) -> TestResult {
+        const TLP_GREEN: &str = "marking-definition--34098fce-860f-48ae-8e50-ebd3cc5e41da";
+
+        let repo = DbTaxii2Repository::new(TaxiiPool::new(pool));
+        let api_root = repo.add_api_root("Root", None, true, false, None).await?;
+        let collection = repo
+            .add_collection(
+                &api_root.id,
+                "Shared",
+                None,
+                Some("shared"),
+                false,
+                false,
+                None,
+            )
+            .await?;
+        assert_eq!(collection.default_marking, None);
+
+        repo.set_collection_default_marking(&collection.id, Some(TLP_GREEN))
+            .await?;
+        let collection = repo
+            .get_collection(&api_root.id, "shared")
+            .await?
+            .ok_or("collection not found")?;
+        assert_eq!(collection.default_marking.as_deref(), Some(TLP_GREEN));
+
+        let mut object = indicator(0);
+        collection.apply_default_marking(&mut object)?;
+        assert_eq!(object["object_marking_refs"], json!([TLP_GREEN]));
+
+        let collection = repo
+            .set_collection_default_marking(&collection.id, None)
+            .await?;
+        assert_eq!(collection.default_marking, None);
+
+        Ok(())
+    }
 }
@@ -5,15 +5,21 @@
 //! - Potential alternative implementations (e.g., in-memory, different databases)
 //! - Clear API contracts
 
+use std::collections::BTreeMap;
+
 use chrono::{DateTime, Utc};
 
 use crate::error::DatabaseResult;
-use crate::models::taxii2::{PaginatedResult, PaginationCursor, Taxii2QueryParams};
+use crate::models::taxii2::{
+    PaginatedResult, PaginationCursor, Taxii2QueryParams, VersionSelector,
+};
+use crate::stream::RowStream;
 
 use taxii_core::{
-    ApiRoot, Collection, CollectionEntity, ContentBindingEntity, ContentBlockEntity,
-    InboxMessageEntity, Job, ManifestRecord, ResultSetEntity, STIXObject, ServiceEntity,
-    SubscriptionEntity, VersionRecord,
+    ApiRoot, BulkInsertReport, CleanupStats, Collection, CollectionBridgeEntity, CollectionEntity,
+    CollectionStats, CollisionPolicy, ContentBindingEntity, ContentBlockEntity, DeletionRecord,
+    InboxMessageEntity, Job, ManifestRecord, PurgeStats, ResultSetEntity, STIXObject,
+    ServiceEntity, SubscriptionDeliveryEntity, SubscriptionEntity, ValidationRule, VersionRecord,
 };
 
 // ============================================================================
@@ -117,6 +123,27 @@ pub trait Taxii1Repository: Send + Sync {
         service_ids: &[String],
     ) -> impl Future<Output = DatabaseResult<()>> + Send;
 
+    /// Link a collection to a TAXII 2.x collection that receives its content,
+    /// replacing any previous link.
+    fn set_collection_bridge(
+        &self,
+        bridge: &CollectionBridgeEntity,
+    ) -> impl Future<Output = DatabaseResult<CollectionBridgeEntity>> + Send;
+
+    /// Remove the TAXII 2.x link of a collection.
+    ///
+    /// Returns whether the collection was linked.
+    fn remove_collection_bridge(
+        &self,
+        data_collection_id: i32,
+    ) -> impl Future<Output = DatabaseResult<bool>> + Send;
+
+    /// Get the TAXII 2.x links of collections.
+    fn get_collection_bridges(
+        &self,
+        data_collection_ids: &[i32],
+    ) -> impl Future<Output = DatabaseResult<Vec<CollectionBridgeEntity>>> + Send;
+
     // ========================================================================
     // Content Block Operations
     // ========================================================================
@@ -132,6 +159,19 @@ pub trait Taxii1Repository: Send + Sync {
         limit: Option<i64>,
     ) -> impl Future<Output = DatabaseResult<Vec<ContentBlockEntity>>> + Send;
 
+    /// Stream content blocks with filtering.
+    ///
+    /// Yields the blocks [`Self::get_content_blocks`] would return without
+    /// an offset or limit, as they are read. Dropping the stream cancels the
+    /// query.
+    fn stream_content_blocks(
+        &self,
+        collection_id: Option<i32>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        bindings: Option<&[ContentBindingEntity]>,
+    ) -> RowStream<ContentBlockEntity>;
+
     /// Get count of content blocks matching criteria.
     fn get_content_blocks_count(
         &self,
@@ -141,7 +181,24 @@ pub trait Taxii1Repository: Send + Sync {
         bindings: Option<&[ContentBindingEntity]>,
     ) -> impl Future<Output = DatabaseResult<i64>> + Send;
 
+    /// Get the content sizes, in bytes, of the content blocks matching
+    /// criteria.
+    ///
+    /// Sizes are in the order [`Self::get_content_blocks`] returns the
+    /// blocks, so they describe its pages without loading any content.
+    fn get_content_block_sizes(
+        &self,
+        collection_id: Option<i32>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        bindings: Option<&[ContentBindingEntity]>,
+    ) -> impl Future<Output = DatabaseResult<Vec<i64>>> + Send;
+
     /// Create a content block.
+    ///
+    /// Collections that deduplicate content get an identical block added
+    /// within the deduplication window linked instead; the existing block
+    /// is returned if no collection needed a new one.
     fn create_content_block(
         &self,
         entity: &ContentBlockEntity,
@@ -158,6 +215,27 @@ pub trait Taxii1Repository: Send + Sync {
         with_messages: bool,
     ) -> impl Future<Output = DatabaseResult<i64>> + Send;
 
+    /// Remove content blocks labelled before `cutoff` from a collection.
+    ///
+    /// Blocks are removed in bounded batches and deleted once no other
+    /// collection holds them. Blocks materialized in a result set are
+    /// retained (and counted) until the result set is removed.
+    fn purge_expired(
+        &self,
+        collection_id: i32,
+        cutoff: DateTime<Utc>,
+    ) -> impl Future<Output = DatabaseResult<PurgeStats>> + Send;
+
+    /// Get statistics of the content blocks of a collection, by content
+    /// binding.
+    ///
+    /// Cached statistics are returned for collections large enough to be
+    /// cached (see [`crate::stats`]).
+    fn collection_stats(
+        &self,
+        collection_id: i32,
+    ) -> impl Future<Output = DatabaseResult<CollectionStats>> + Send;
+
     // ========================================================================
     // Inbox Message Operations
     // ========================================================================
@@ -168,6 +246,18 @@ pub trait Taxii1Repository: Send + Sync {
         entity: &InboxMessageEntity,
     ) -> impl Future<Output = DatabaseResult<InboxMessageEntity>> + Send;
 
+    /// Create an inbox message and its content blocks, all or none of them.
+    ///
+    /// Each block is created as by [`Self::create_content_block`] in the
+    /// collections given with it, and refers to the inbox message if one is
+    /// given. Returns the created inbox message and blocks, in order.
+    fn create_inbox_message_with_blocks(
+        &self,
+        inbox_message: Option<&InboxMessageEntity>,
+        blocks: &[(ContentBlockEntity, Option<Vec<i32>>)],
+        service_id: Option<&str>,
+    ) -> impl Future<Output = DatabaseResult<(Option<InboxMessageEntity>, Vec<ContentBlockEntity>)>> + Send;
+
     // ========================================================================
     // Result Set Operations
     // ========================================================================
@@ -179,11 +269,98 @@ pub trait Taxii1Repository: Send + Sync {
     ) -> impl Future<Output = DatabaseResult<ResultSetEntity>> + Send;
 
     /// Get a result set by ID.
+    ///
+    /// Expired result sets are not found.
     fn get_result_set(
         &self,
         result_set_id: &str,
     ) -> impl Future<Output = DatabaseResult<Option<ResultSetEntity>>> + Send;
 
+    /// Get result sets waiting to be prepared, oldest first.
+    fn get_pending_result_sets(
+        &self,
+        limit: i64,
+    ) -> impl Future<Output = DatabaseResult<Vec<ResultSetEntity>>> + Send;
+
+    /// Count result sets waiting to be prepared.
+    fn count_pending_result_sets(&self) -> impl Future<Output = DatabaseResult<i64>> + Send;
+
+    /// Claim prepared result sets whose results are waiting to be pushed,
+    /// oldest first.
+    ///
+    /// Claimed result sets are not claimed again for `lease`, or until
+    /// their delivery is recorded, so that concurrent workers do not push
+    /// them twice.
+    fn claim_result_sets_to_deliver(
+        &self,
+        limit: i64,
+        lease: chrono::Duration,
+    ) -> impl Future<Output = DatabaseResult<Vec<ResultSetEntity>>> + Send;
+
+    /// Record the outcome of pushing the results of a result set, with the
+    /// attempts it took and the error of the last failed attempt.
+    fn record_result_set_delivery(
+        &self,
+        result_set_id: &str,
+        status: &str,
+        attempts: i32,
+        error: Option<&str>,
+    ) -> impl Future<Output = DatabaseResult<()>> + Send;
+
+    /// Prepare a result set by materializing its matching content blocks.
+    ///
+    /// Returns the updated (READY) result set, or `None` if it does not exist.
+    fn prepare_result_set(
+        &self,
+        result_set_id: &str,
+    ) -> impl Future<Output = DatabaseResult<Option<ResultSetEntity>>> + Send;
+
+    /// Delete result sets that expired before `now`.
+    ///
+    /// Content blocks they retained past their collection's retention are
+    /// purged by the next retention run. Returns the number of deleted
+    /// result sets.
+    fn purge_expired_result_sets(
+        &self,
+        now: DateTime<Utc>,
+    ) -> impl Future<Output = DatabaseResult<u64>> + Send;
+
+    /// Get a page of prepared content blocks for a result set.
+    fn get_result_set_content_blocks(
+        &self,
+        result_set_id: &str,
+        offset: i64,
+        limit: Option<i64>,
+    ) -> impl Future<Output = DatabaseResult<Vec<ContentBlockEntity>>> + Send;
+
+    /// Get the content sizes, in bytes, of the prepared content blocks of a
+    /// result set, in the order [`Self::get_result_set_content_blocks`]
+    /// returns them.
+    fn get_result_set_content_block_sizes(
+        &self,
+        result_set_id: &str,
+    ) -> impl Future<Output = DatabaseResult<Vec<i64>>> + Send;
+
+    /// Get the number of content blocks in each part of a result set, as
+    /// recorded by [`Self::record_result_set_parts`].
+    ///
+    /// `None` until the parts are recorded.
+    fn get_result_set_parts(
+        &self,
+        result_set_id: &str,
+    ) -> impl Future<Output = DatabaseResult<Option<Vec<i64>>>> + Send;
+
+    /// Record the number of content blocks in each part of a result set, so
+    /// that the parts are computed once. Parts already recorded are kept.
+    ///
+    /// Returns the recorded parts, or `None` if the result set does not
+    /// exist.
+    fn record_result_set_parts(
+        &self,
+        result_set_id: &str,
+        parts: &[i64],
+    ) -> impl Future<Output = DatabaseResult<Option<Vec<i64>>>> + Send;
+
     // ========================================================================
     // Subscription Operations
     // ========================================================================
@@ -211,6 +388,88 @@ pub trait Taxii1Repository: Send + Sync {
         &self,
         entity: &SubscriptionEntity,
     ) -> impl Future<Output = DatabaseResult<SubscriptionEntity>> + Send;
+
+    /// Record a successful push delivery to a subscriber.
+    ///
+    /// Returns `None` if the subscription does not exist.
+    fn record_delivery_success(
+        &self,
+        subscription_id: &str,
+    ) -> impl Future<Output = DatabaseResult<Option<SubscriptionEntity>>> + Send;
+
+    /// Record a failed push delivery to a subscriber.
+    ///
+    /// The subscription is paused once `pause_after` deliveries in a row
+    /// failed. Returns `None` if the subscription does not exist.
+    fn record_delivery_failure(
+        &self,
+        subscription_id: &str,
+        error: &str,
+        pause_after: i32,
+    ) -> impl Future<Output = DatabaseResult<Option<SubscriptionEntity>>> + Send;
+
+    // ========================================================================
+    // Push Delivery Operations
+    // ========================================================================
+
+    /// Get the active subscriptions of a collection that content is pushed to.
+    fn get_push_subscriptions(
+        &self,
+        collection_id: i32,
+    ) -> impl Future<Output = DatabaseResult<Vec<SubscriptionEntity>>> + Send;
+
+    /// Queue the push delivery of a content block to subscriptions.
+    ///
+    /// Deliveries already queued are left as they are. Returns the number
+    /// of deliveries queued.
+    fn create_subscription_deliveries(
+        &self,
+        subscription_ids: &[String],
+        content_block_id: i32,
+    ) -> impl Future<Output = DatabaseResult<u64>> + Send;
+
+    /// Claim pending deliveries to active subscriptions, oldest first.
+    ///
+    /// Claimed deliveries are not claimed again for `lease`, or until
+    /// their outcome is recorded, so that concurrent workers do not push
+    /// them twice.
+    fn claim_pending_deliveries(
+        &self,
+        limit: i64,
+        lease: chrono::Duration,
+    ) -> impl Future<Output = DatabaseResult<Vec<SubscriptionDeliveryEntity>>> + Send;
+
+    /// Get deliveries, newest first, optionally by status and subscription.
+    fn get_subscription_deliveries(
+        &self,
+        status: Option<&str>,
+        subscription_id: Option<&str>,
+        limit: i64,
+    ) -> impl Future<Output = DatabaseResult<Vec<SubscriptionDeliveryEntity>>> + Send;
+
+    /// Record the outcome of delivery attempts, adding `attempts` to the
+    /// attempts of each delivery.
+    fn record_subscription_deliveries(
+        &self,
+        ids: &[i32],
+        status: &str,
+        attempts: i32,
+        error: Option<&str>,
+    ) -> impl Future<Output = DatabaseResult<u64>> + Send;
+
+    /// Queue failed deliveries again: those with the given IDs, or all of
+    /// them if `ids` is empty, optionally only of one subscription.
+    fn requeue_failed_deliveries(
+        &self,
+        ids: &[i32],
+        subscription_id: Option<&str>,
+    ) -> impl Future<Output = DatabaseResult<u64>> + Send;
+
+    /// Get content blocks by ID, ordered by timestamp label.
+    fn get_content_blocks_by_ids(
+        &self,
+        ids: &[i32],
+    ) -> impl Future<Output = DatabaseResult<Vec<ContentBlockEntity>>> + Send;
 }
 
 // ============================================================================
@@ -222,6 +481,9 @@ pub trait Taxii1Repository: Send + Sync {
 /// Provides async methods for managing TAXII 2.x entities including API roots,
 /// collections, STIX objects, and jobs.
 pub trait Taxii2Repository: Send + Sync {
+    /// Whether deleted objects are removed instead of kept as tombstones.
+    fn hard_delete(&self) -> bool;
+
     // ========================================================================
     // API Root Operations
     // ========================================================================
@@ -245,6 +507,17 @@ pub trait Taxii2Repository: Send + Sync {
         api_root_id: Option<&str>,
     ) -> impl Future<Output = DatabaseResult<ApiRoot>> + Send;
 
+    /// Update an API root; setting `default` unsets it on the others.
+    fn update_api_root(
+        &self,
+        api_root_id: &str,
+        title: &str,
+        description: Option<&str>,
+        default: bool,
+        is_public: bool,
+        max_content_length: Option<u64>,
+    ) -> impl Future<Output = DatabaseResult<ApiRoot>> + Send;
+
     // ========================================================================
     // Collection Operations (TAXII 2.x)
     // ========================================================================
@@ -263,6 +536,7 @@ pub trait Taxii2Repository: Send + Sync {
     ) -> impl Future<Output = DatabaseResult<Option<Collection>>> + Send;
 
     /// Add a new collection.
+    #[expect(clippy::too_many_arguments, reason = "mirrors the collection columns")]
     fn add_collection(
         &self,
         api_root_id: &str,
@@ -271,8 +545,55 @@ pub trait Taxii2Repository: Send + Sync {
         alias: Option<&str>,
         is_public: bool,
         is_public_write: bool,
+        collection_id: Option<&str>,
+    ) -> impl Future<Output = DatabaseResult<Collection>> + Send;
+
+    /// Update the title, description, alias and public access of a
+    /// collection.
+    fn update_collection(
+        &self,
+        collection_id: &str,
+        title: &str,
+        description: Option<&str>,
+        alias: Option<&str>,
+        is_public: bool,
+        is_public_write: bool,
+    ) -> impl Future<Output = DatabaseResult<Collection>> + Send;
+
+    /// Delete a collection with all its objects.
+    fn delete_collection(
+        &self,
+        collection_id: &str,
+    ) -> impl Future<Output = DatabaseResult<()>> + Send;
+
+    /// Set the retention policy of a collection (`None` removes it).
+    fn set_collection_retention(
+        &self,
+        collection_id: &str,
+        retention_days: Option<i32>,
+    ) -> impl Future<Output = DatabaseResult<Collection>> + Send;
+
+    /// Set the validation rules posted objects must pass (empty removes
+    /// them).
+    fn set_collection_validation_rules(
+        &self,
+        collection_id: &str,
+        rules: &[ValidationRule],
+    ) -> impl Future<Output = DatabaseResult<Collection>> + Send;
+
+    /// Set the marking definition applied to posted objects without
+    /// markings (`None` removes it).
+    fn set_collection_default_marking(
+        &self,
+        collection_id: &str,
+        marking: Option<&str>,
     ) -> impl Future<Output = DatabaseResult<Collection>> + Send;
 
+    /// Get collections of all API roots that have a retention policy.
+    fn get_collections_with_retention(
+        &self,
+    ) -> impl Future<Output = DatabaseResult<Vec<Collection>>> + Send;
+
     // ========================================================================
     // STIX Object Operations
     // ========================================================================
@@ -291,6 +612,29 @@ pub trait Taxii2Repository: Send + Sync {
         params: &Taxii2QueryParams<'_>,
     ) -> impl Future<Output = DatabaseResult<PaginatedResult<Vec<STIXObject>>>> + Send;
 
+    /// Search STIX objects by content.
+    ///
+    /// `query` uses web search syntax over the name, value, labels,
+    /// description and pattern of objects. Matches are filtered like
+    /// [`Self::get_objects`] and ordered by relevance, name matches first.
+    fn search_objects(
+        &self,
+        collection_id: &str,
+        query: &str,
+        params: &Taxii2QueryParams<'_>,
+    ) -> impl Future<Output = DatabaseResult<PaginatedResult<Vec<STIXObject>>>> + Send;
+
+    /// Stream STIX objects.
+    ///
+    /// Yields the objects [`Self::get_objects`] would return as they are
+    /// read, so whole collections can be exported without loading them into
+    /// memory. Dropping the stream cancels the query.
+    fn stream_objects(
+        &self,
+        collection_id: &str,
+        params: &Taxii2QueryParams<'_>,
+    ) -> RowStream<STIXObject>;
+
     /// Add STIX objects.
     fn add_objects(
         &self,
@@ -299,6 +643,36 @@ pub trait Taxii2Repository: Send + Sync {
         objects: &[serde_json::Value],
     ) -> impl Future<Output = DatabaseResult<Job>> + Send;
 
+    /// Add STIX objects in one transaction, with the outcome of each object.
+    ///
+    /// The objects, the job and its details are written together with
+    /// multi-row statements instead of one round trip per object. An object
+    /// whose version exists with different content is accepted without
+    /// replacing it ([`taxii_core::CollisionPolicy::KeepExisting`]).
+    fn add_objects_bulk(
+        &self,
+        api_root_id: &str,
+        collection_id: &str,
+        objects: &[serde_json::Value],
+    ) -> impl Future<Output = DatabaseResult<BulkInsertReport>> + Send;
+
+    /// Add STIX objects, failing the ones rejected by validation.
+    ///
+    /// Like [`Self::add_objects_bulk`], but objects whose index is in
+    /// `rejected` are not stored and are reported as failures with the given
+    /// message, and objects whose version exists with different content are
+    /// handled by `policy`. Stored and overwritten versions are recorded in
+    /// the object audit log for `account_id`.
+    fn add_objects_bulk_with_rejections(
+        &self,
+        api_root_id: &str,
+        collection_id: &str,
+        objects: &[serde_json::Value],
+        rejected: &BTreeMap<usize, String>,
+        policy: CollisionPolicy,
+        account_id: Option<i32>,
+    ) -> impl Future<Output = DatabaseResult<BulkInsertReport>> + Send;
+
     /// Get a single object (returns empty items if object doesn't exist).
     fn get_object(
         &self,
@@ -307,18 +681,67 @@ pub trait Taxii2Repository: Send + Sync {
         params: &Taxii2QueryParams<'_>,
     ) -> impl Future<Output = DatabaseResult<PaginatedResult<Vec<STIXObject>>>> + Send;
 
+    /// Resolve one version of an object (`None` if no version matches).
+    fn resolve_object_version(
+        &self,
+        collection_id: &str,
+        object_id: &str,
+        selector: VersionSelector,
+    ) -> impl Future<Output = DatabaseResult<Option<STIXObject>>> + Send;
+
     /// Delete an object.
+    ///
+    /// Deleted versions are hidden from all reads. Unless the repository
+    /// is configured for hard deletes, they are kept as tombstones recording
+    /// `deleted_by` until purged.
     fn delete_object(
         &self,
         collection_id: &str,
         object_id: &str,
         match_version: Option<&[String]>,
         match_spec_version: Option<&[String]>,
+        deleted_by: Option<i32>,
     ) -> impl Future<Output = DatabaseResult<()>> + Send;
 
-    /// Get versions of an object.
+    /// List versions deleted from a collection after `since`, oldest first.
+    fn list_deletions(
+        &self,
+        collection_id: &str,
+        since: DateTime<Utc>,
+    ) -> impl Future<Output = DatabaseResult<Vec<DeletionRecord>>> + Send;
+
+    /// Remove tombstones of versions deleted before `older_than`.
     ///
-    /// Returns empty items if the object doesn't exist in the collection.
+    /// Returns the number of removed tombstones.
+    fn purge_deleted(
+        &self,
+        older_than: DateTime<Utc>,
+    ) -> impl Future<Output = DatabaseResult<u64>> + Send;
+
+    /// Delete versions added to a collection before `cutoff`.
+    ///
+    /// Versions are deleted in bounded batches, as tombstones unless the
+    /// repository is configured for hard deletes.
+    fn purge_expired(
+        &self,
+        collection_id: &str,
+        cutoff: DateTime<Utc>,
+    ) -> impl Future<Output = DatabaseResult<PurgeStats>> + Send;
+
+    /// Get statistics of the objects of a collection, by type.
+    ///
+    /// Deleted versions are not counted. Cached statistics are returned for
+    /// collections large enough to be cached (see [`crate::stats`]).
+    fn collection_stats(
+        &self,
+        collection_id: &str,
+    ) -> impl Future<Output = DatabaseResult<CollectionStats>> + Send;
+
+    /// Get versions of an object, from first to last.
+    ///
+    /// Versions are ordered by `(version, date_added, pk)` (see
+    /// [`crate::repository::get_object_version`]). Returns empty items if the
+    /// object doesn't exist in the collection.
     fn get_versions(
         &self,
         collection_id: &str,
@@ -340,8 +763,16 @@ pub trait Taxii2Repository: Send + Sync {
         job_id: &str,
     ) -> impl Future<Output = DatabaseResult<Option<Job>>> + Send;
 
-    /// Cleanup old jobs.
-    fn job_cleanup(&self) -> impl Future<Output = DatabaseResult<i32>> + Send;
+    /// Remove job logs completed before `older_than`, with their details.
+    ///
+    /// Jobs still pending that were requested before `older_than` are
+    /// removed too, unless `keep_incomplete` is set; kept pending jobs past
+    /// the repository's stale job age are marked failed instead.
+    fn cleanup_jobs(
+        &self,
+        older_than: DateTime<Utc>,
+        keep_incomplete: bool,
+    ) -> impl Future<Output = DatabaseResult<CleanupStats>> + Send;
 }
 
 use std::future::Future;
@@ -0,0 +1,346 @@
+//! Per-collection retention policies.
+//!
+//! Collections of both protocols can have a `retention_days` policy. A
+//! maintenance run purges content older than the policy from every such
+//! collection and records each purge in the `retention_purges` table:
+//!
+//! - TAXII 2.x objects expire by `date_added`. They are soft-deleted like
+//!   any other deletion (or removed, with hard deletes configured).
+//! - TAXII 1.x content blocks expire by `timestamp_label`. Blocks
+//!   materialized in a result set are retained until the result set is
+//!   removed, so prepared polls are not cut short.
+
+use chrono::{DateTime, Duration, Utc};
+use tracing::info;
+
+use crate::error::DatabaseResult;
+use crate::models::retention_purge::{RetentionPurge, protocol};
+use crate::repository::{
+    DbTaxii1Repository, DbTaxii2Repository, Taxii1Repository, Taxii2Repository,
+};
+
+/// Cutoff of a retention policy at `now`.
+///
+/// Policies reaching before the earliest representable time keep everything.
+pub fn retention_cutoff(now: DateTime<Utc>, retention_days: i32) -> DateTime<Utc> {
+    now.checked_sub_signed(Duration::days(i64::from(retention_days)))
+        .unwrap_or(DateTime::<Utc>::MIN_UTC)
+}
+
+/// Purge expired content from all collections with a retention policy.
+///
+/// Returns the recorded purges; collections without expired content are
+/// not recorded.
+pub async fn purge_expired_collections(
+    taxii1: &DbTaxii1Repository,
+    taxii2: &DbTaxii2Repository,
+    now: DateTime<Utc>,
+) -> DatabaseResult<Vec<RetentionPurge>> {
+    let mut purges = Vec::new();
+
+    for collection in taxii2.get_collections_with_retention().await? {
+        let Some(days) = collection.retention_days else {
+            continue;
+        };
+        let cutoff = retention_cutoff(now, days);
+        let stats = taxii2.purge_expired(&collection.id, cutoff).await?;
+        if !stats.is_empty() {
+            info!(collection = %collection.id, purged = stats.purged, "Purged expired objects");
+            purges.push(
+                RetentionPurge::record(
+                    taxii2.pool(),
+                    protocol::TAXII2,
+                    &collection.id,
+                    cutoff,
+                    stats,
+                )
+                .await?,
+            );
+        }
+    }
+
+    for collection in taxii1.get_collections(None).await? {
+        let (Some(id), Some(days)) = (collection.id, collection.retention_days) else {
+            continue;
+        };
+        let cutoff = retention_cutoff(now, days);
+        let stats = taxii1.purge_expired(id, cutoff).await?;
+        if !stats.is_empty() {
+            info!(
+                collection = %collection.name,
+                purged = stats.purged,
+                retained = stats.retained,
+                "Purged expired content blocks"
+            );
+            purges.push(
+                RetentionPurge::record(
+                    taxii1.pool(),
+                    protocol::TAXII1,
+                    &id.to_string(),
+                    cutoff,
+                    stats,
+                )
+                .await?,
+            );
+        }
+    }
+
+    Ok(purges)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::taxii1::{NewResultSet, ResultSet};
+    use crate::models::taxii2::Taxii2QueryParams;
+    use crate::pool::TaxiiPool;
+    use serde_json::json;
+    use sqlx::PgPool;
+    use taxii_core::{CollectionEntity, ContentBlockEntity, PurgeStats};
+
+    type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+    #[test]
+    fn test_retention_cutoff_saturates() {
+        let now = Utc::now();
+        assert_eq!(retention_cutoff(now, 30), now - Duration::days(30));
+        assert_eq!(retention_cutoff(now, i32::MAX), DateTime::<Utc>::MIN_UTC);
+    }
+
+    fn indicator(n: usize) -> serde_json::Value {
+        json!({
+            "id": format!("indicator--00000000-0000-4000-8000-{n:012}"),
+            "type": "indicator",
+            "spec_version": "2.1",
+            "created": "2024-01-01T00:00:00.000Z",
+            "modified": "2024-01-01T00:00:00.000Z",
+            "pattern": "[ipv4-addr:value = '10.0.0.1']",
+            "pattern_type": "stix",
+            "valid_from": "2024-01-01T00:00:00Z"
+        })
+    }
+
+    fn repositories(pool: &PgPool, hard_delete: bool) -> (DbTaxii1Repository, DbTaxii2Repository) {
+        let pool = TaxiiPool::new(pool.clone());
+        (
+            DbTaxii1Repository::new(pool.clone()),
+            DbTaxii2Repository::new(pool).with_hard_delete(hard_delete),
+        )
+    }
+
+    /// Add objects `0..count` to a new collection kept for 30 days and age
+    /// the first `old` of them past the policy.
+    async fn seed_objects(
+        pool: &PgPool,
+        repo: &DbTaxii2Repository,
+        count: usize,
+        old: usize,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let api_root = repo.add_api_root("Root", None, true, false, None).await?;
+        let collection = repo
+            .add_collection(&api_root.id, "Expiring", None, None, false, false, None)
+            .await?;
+        repo.set_collection_retention(&collection.id, Some(30))
+            .await?;
+        let objects: Vec<_> = (0..count).map(indicator).collect();
+        repo.add_objects(&api_root.id, &collection.id, &objects)
+            .await?;
+
+        let old_ids: Vec<String> = (0..old)
+            .map(|n| format!("indicator--00000000-0000-4000-8000-{n:012}"))
+            .collect();
+        sqlx::query(
+            "UPDATE opentaxii_stixobject SET date_added = date_added - INTERVAL '31 days'
+             WHERE id = ANY($1)",
+        )
+        .bind(&old_ids)
+        .execute(pool)
+        .await?;
+
+        Ok(collection.id)
+    }
+
+    async fn count_rows(pool: &PgPool, collection_id: &str) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            "SELECT COUNT(*) FROM opentaxii_stixobject WHERE collection_id = $1::TEXT::UUID",
+        )
+        .bind(collection_id)
+        .fetch_one(pool)
+        .await
+    }
+
+    /// Expired objects are soft-deleted and the purge is recorded.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_purge_expired_objects(pool: PgPool) -> TestResult {
+        let (taxii1, taxii2) = repositories(&pool, false);
+        let collection_id = seed_objects(&pool, &taxii2, 5, 2).await?;
+
+        let purges = purge_expired_collections(&taxii1, &taxii2, Utc::now()).await?;
+        assert_eq!(purges.len(), 1);
+        assert_eq!(purges[0].protocol, protocol::TAXII2);
+        assert_eq!(purges[0].collection_id, collection_id);
+        assert_eq!((purges[0].purged, purges[0].retained), (2, 0));
+
+        let remaining = taxii2
+            .get_objects(&collection_id, &Taxii2QueryParams::default())
+            .await?
+            .items;
+        let mut ids: Vec<&str> = remaining.iter().map(|o| o.id.as_str()).collect();
+        ids.sort_unstable();
+        assert_eq!(
+            ids,
+            vec![
+                "indicator--00000000-0000-4000-8000-000000000002",
+                "indicator--00000000-0000-4000-8000-000000000003",
+                "indicator--00000000-0000-4000-8000-000000000004",
+            ]
+        );
+
+        // Purged objects are kept as tombstones
+        assert_eq!(count_rows(&pool, &collection_id).await?, 5);
+        let since = Utc::now() - Duration::hours(1);
+        assert_eq!(taxii2.list_deletions(&collection_id, since).await?.len(), 2);
+
+        // Nothing is left to purge, so nothing is recorded
+        assert!(
+            purge_expired_collections(&taxii1, &taxii2, Utc::now())
+                .await?
+                .is_empty()
+        );
+        let log =
+            RetentionPurge::find_by_collection(taxii2.pool(), protocol::TAXII2, &collection_id, 10)
+                .await?;
+        assert_eq!(log.len(), 1);
+
+        Ok(())
+    }
+
+    /// With hard deletes, expired objects are removed across batches.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_purge_expired_objects_hard_delete(pool: PgPool) -> TestResult {
+        let (_, taxii2) = repositories(&pool, true);
+        let old = crate::repository::PURGE_BATCH_SIZE as usize + 5;
+        let collection_id = seed_objects(&pool, &taxii2, old + 3, old).await?;
+
+        let cutoff = retention_cutoff(Utc::now(), 30);
+        let stats = taxii2.purge_expired(&collection_id, cutoff).await?;
+        assert_eq!(
+            stats,
+            PurgeStats {
+                purged: old as u64,
+                retained: 0
+            }
+        );
+        assert_eq!(count_rows(&pool, &collection_id).await?, 3);
+
+        Ok(())
+    }
+
+    /// Add a content block to collections.
+    async fn add_block(
+        repo: &DbTaxii1Repository,
+        timestamp_label: DateTime<Utc>,
+        collection_ids: &[i32],
+    ) -> Result<i32, Box<dyn std::error::Error>> {
+        let entity = ContentBlockEntity {
+            timestamp_label,
+            ..ContentBlockEntity::new(format!("block {timestamp_label}"), None)
+        };
+        let block = repo
+            .create_content_block(&entity, Some(collection_ids), None)
+            .await?;
+        Ok(block.id.ok_or("content block without id")?)
+    }
+
+    /// Expired blocks are removed, except blocks held by a result set or
+    /// another collection.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_purge_expired_content_blocks(pool: PgPool) -> TestResult {
+        let (taxii1, taxii2) = repositories(&pool, false);
+        let collection = |name: &str, retention_days| CollectionEntity {
+            retention_days,
+            ..CollectionEntity::new(name)
+        };
+        let expiring = taxii1
+            .create_collection(&collection("expiring", Some(30)))
+            .await?
+            .id
+            .ok_or("collection without id")?;
+        let kept = taxii1
+            .create_collection(&collection("kept", None))
+            .await?
+            .id
+            .ok_or("collection without id")?;
+
+        let now = Utc::now();
+        let block = |days: i64, collections: Vec<i32>| {
+            let taxii1 = &taxii1;
+            async move { add_block(taxii1, now - Duration::days(days), &collections).await }
+        };
+        let expired = block(40, vec![expiring]).await?;
+        let shared = block(40, vec![expiring, kept]).await?;
+        let pinned = block(35, vec![expiring]).await?;
+        let fresh = block(5, vec![expiring]).await?;
+        let old_elsewhere = block(40, vec![kept]).await?;
+
+        let result_set = NewResultSet {
+            id: "rs-1",
+            collection_id: expiring,
+            status: "PENDING",
+            ..Default::default()
+        };
+        ResultSet::create(taxii1.pool(), &result_set).await?;
+        ResultSet::materialize(taxii1.pool(), "rs-1", &[pinned, fresh]).await?;
+
+        let purges = purge_expired_collections(&taxii1, &taxii2, now).await?;
+        assert_eq!(purges.len(), 1);
+        assert_eq!(purges[0].protocol, protocol::TAXII1);
+        assert_eq!(purges[0].collection_id, expiring.to_string());
+        assert_eq!((purges[0].purged, purges[0].retained), (2, 1));
+
+        let ids = |blocks: Vec<ContentBlockEntity>| -> Vec<Option<i32>> {
+            let mut ids: Vec<_> = blocks.into_iter().map(|b| b.id).collect();
+            ids.sort_unstable();
+            ids
+        };
+        let remaining = taxii1
+            .get_content_blocks(Some(expiring), None, None, None, 0, None)
+            .await?;
+        assert_eq!(ids(remaining), vec![Some(pinned), Some(fresh)]);
+        let elsewhere = taxii1
+            .get_content_blocks(Some(kept), None, None, None, 0, None)
+            .await?;
+        assert_eq!(ids(elsewhere), vec![Some(shared), Some(old_elsewhere)]);
+
+        let deleted: Vec<i32> =
+            sqlx::query_scalar("SELECT id FROM content_blocks WHERE id = ANY($1)")
+                .bind(vec![expired, shared])
+                .fetch_all(&pool)
+                .await?;
+        assert_eq!(deleted, vec![shared]);
+
+        let volume = taxii1
+            .get_collection("expiring", None)
+            .await?
+            .and_then(|c| c.volume);
+        assert_eq!(volume, Some(2));
+
+        // Removing the result set releases the pinned block
+        ResultSet::delete(taxii1.pool(), "rs-1").await?;
+        let stats = taxii1
+            .purge_expired(expiring, retention_cutoff(now, 30))
+            .await?;
+        assert_eq!(
+            stats,
+            PurgeStats {
+                purged: 1,
+                retained: 0
+            }
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,318 @@
+//! Collection statistics.
+//!
+//! Statistics (object count, distinct IDs, latest addition and a per-type
+//! breakdown) are computed with indexed queries on demand. For very large
+//! collections even those take a while, so a maintenance run caches the
+//! statistics of collections above a size threshold in the
+//! `collection_stats` table. Cached statistics are served until the next
+//! run; their `computed_at` tells how current they are.
+
+use chrono::Utc;
+use taxii_core::CollectionStats;
+use tracing::debug;
+use uuid::Uuid;
+
+use crate::error::DatabaseResult;
+use crate::models::collection_stats::{CachedCollectionStats, aggregate_stats};
+use crate::models::retention_purge::protocol;
+use crate::models::taxii1::ContentBlock;
+use crate::models::taxii2::STIXObject;
+use crate::pool::TaxiiPool;
+use crate::repository::{
+    DbTaxii1Repository, DbTaxii2Repository, Taxii1Repository, Taxii2Repository,
+};
+
+/// Object count from which statistics of a collection are cached.
+pub const DEFAULT_CACHE_THRESHOLD: u64 = 100_000;
+
+/// Compute the statistics of a TAXII 2.x collection.
+pub async fn compute_taxii2(
+    pool: &TaxiiPool,
+    collection_id: Uuid,
+) -> DatabaseResult<CollectionStats> {
+    let types = STIXObject::type_stats(pool, collection_id).await?;
+    Ok(aggregate_stats(types, Utc::now()))
+}
+
+/// Compute the statistics of a TAXII 1.x collection.
+pub async fn compute_taxii1(
+    pool: &TaxiiPool,
+    collection_id: i32,
+) -> DatabaseResult<CollectionStats> {
+    let types = ContentBlock::binding_stats(pool, collection_id).await?;
+    Ok(aggregate_stats(types, Utc::now()))
+}
+
+/// Cache the statistics of a collection if it has at least `threshold`
+/// objects, and drop cached statistics of smaller collections.
+///
+/// Returns whether the statistics are cached.
+async fn cache_if_large(
+    pool: &TaxiiPool,
+    protocol: &str,
+    collection_id: &str,
+    stats: &CollectionStats,
+    threshold: u64,
+) -> DatabaseResult<bool> {
+    if stats.object_count >= threshold {
+        CachedCollectionStats::store(pool, protocol, collection_id, stats).await?;
+        Ok(true)
+    } else {
+        CachedCollectionStats::delete(pool, protocol, collection_id).await?;
+        Ok(false)
+    }
+}
+
+/// Refresh cached statistics of the collections of both protocols.
+///
+/// Returns the number of collections whose statistics are cached.
+pub async fn refresh_collection_stats(
+    taxii1: &DbTaxii1Repository,
+    taxii2: &DbTaxii2Repository,
+    threshold: u64,
+) -> DatabaseResult<usize> {
+    let mut cached = 0;
+
+    for api_root in taxii2.get_api_roots().await? {
+        for collection in taxii2.get_collections(&api_root.id).await? {
+            let Ok(id) = Uuid::parse_str(&collection.id) else {
+                continue;
+            };
+            let stats = compute_taxii2(taxii2.pool(), id).await?;
+            if cache_if_large(
+                taxii2.pool(),
+                protocol::TAXII2,
+                &collection.id,
+                &stats,
+                threshold,
+            )
+            .await?
+            {
+                cached += 1;
+            }
+        }
+    }
+
+    for collection in taxii1.get_collections(None).await? {
+        let Some(id) = collection.id else {
+            continue;
+        };
+        let stats = compute_taxii1(taxii1.pool(), id).await?;
+        if cache_if_large(
+            taxii1.pool(),
+            protocol::TAXII1,
+            &id.to_string(),
+            &stats,
+            threshold,
+        )
+        .await?
+        {
+            cached += 1;
+        }
+    }
+
+    debug!(cached, "Refreshed collection statistics");
+    Ok(cached)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, Duration};
+    use serde_json::json;
+    use sqlx::PgPool;
+    use std::collections::BTreeMap;
+    use taxii_core::{CollectionEntity, ContentBindingEntity, ContentBlockEntity};
+
+    type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+    fn object(stix_type: &str, n: usize, modified: &str) -> serde_json::Value {
+        json!({
+            "id": format!("{stix_type}--00000000-0000-4000-8000-{n:012}"),
+            "type": stix_type,
+            "spec_version": "2.1",
+            "created": "2024-01-01T00:00:00.000Z",
+            "modified": modified,
+            "name": format!("{stix_type} {n}")
+        })
+    }
+
+    fn by_type(counts: &[(&str, u64)]) -> BTreeMap<String, u64> {
+        counts
+            .iter()
+            .map(|(name, count)| (name.to_string(), *count))
+            .collect()
+    }
+
+    /// Per-type counts of seeded objects, with versions and deletions.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_taxii2_collection_stats(pool: PgPool) -> TestResult {
+        let repo = DbTaxii2Repository::new(TaxiiPool::new(pool));
+        let api_root = repo.add_api_root("Root", None, true, false, None).await?;
+        let collection = repo
+            .add_collection(&api_root.id, "Stats", None, None, false, false, None)
+            .await?;
+
+        let empty = repo.collection_stats(&collection.id).await?;
+        assert_eq!((empty.object_count, empty.distinct_ids), (0, 0));
+        assert!(empty.by_type.is_empty());
+        assert_eq!(empty.latest_added, None);
+
+        let mut objects = Vec::new();
+        for n in 0..3 {
+            objects.push(object("indicator", n, "2024-01-01T00:00:00.000Z"));
+        }
+        // Second versions of two indicators
+        for n in 0..2 {
+            objects.push(object("indicator", n, "2024-02-01T00:00:00.000Z"));
+        }
+        for n in 0..2 {
+            objects.push(object("malware", n, "2024-01-01T00:00:00.000Z"));
+        }
+        objects.push(object("identity", 0, "2024-01-01T00:00:00.000Z"));
+        repo.add_objects(&api_root.id, &collection.id, &objects)
+            .await?;
+
+        // Deleted objects are not counted
+        repo.delete_object(
+            &collection.id,
+            "malware--00000000-0000-4000-8000-000000000001",
+            None,
+            None,
+            None,
+        )
+        .await?;
+
+        let stats = repo.collection_stats(&collection.id).await?;
+        assert_eq!(
+            stats.by_type,
+            by_type(&[("identity", 1), ("indicator", 5), ("malware", 1)])
+        );
+        assert_eq!(stats.object_count, 7);
+        assert_eq!(stats.distinct_ids, 5);
+
+        let latest = repo
+            .get_objects(&collection.id, &Default::default())
+            .await?
+            .items
+            .iter()
+            .map(|o| o.date_added)
+            .max();
+        assert_eq!(stats.latest_added, latest);
+
+        Ok(())
+    }
+
+    /// Statistics of large collections are cached until the next refresh.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_refresh_caches_large_collections(pool: PgPool) -> TestResult {
+        let pool = TaxiiPool::new(pool);
+        let taxii1 = DbTaxii1Repository::new(pool.clone());
+        let taxii2 = DbTaxii2Repository::new(pool.clone());
+        let api_root = taxii2.add_api_root("Root", None, true, false, None).await?;
+        let large = taxii2
+            .add_collection(&api_root.id, "Large", None, None, false, false, None)
+            .await?;
+        let small = taxii2
+            .add_collection(&api_root.id, "Small", None, None, false, false, None)
+            .await?;
+
+        let objects: Vec<_> = (0..3)
+            .map(|n| object("indicator", n, "2024-01-01T00:00:00.000Z"))
+            .collect();
+        taxii2
+            .add_objects(&api_root.id, &large.id, &objects)
+            .await?;
+        taxii2
+            .add_objects(&api_root.id, &small.id, &objects[..1])
+            .await?;
+
+        assert_eq!(refresh_collection_stats(&taxii1, &taxii2, 2).await?, 1);
+        let cached = taxii2.collection_stats(&large.id).await?;
+        assert_eq!(cached.object_count, 3);
+
+        // Cached statistics are served until the next refresh
+        taxii2
+            .add_objects(
+                &api_root.id,
+                &large.id,
+                &[object("malware", 0, "2024-01-01T00:00:00.000Z")],
+            )
+            .await?;
+        assert_eq!(taxii2.collection_stats(&large.id).await?, cached);
+        assert_eq!(taxii2.collection_stats(&small.id).await?.object_count, 1);
+
+        refresh_collection_stats(&taxii1, &taxii2, 2).await?;
+        let refreshed = taxii2.collection_stats(&large.id).await?;
+        assert_eq!(
+            refreshed.by_type,
+            by_type(&[("indicator", 3), ("malware", 1)])
+        );
+
+        // Collections below the threshold are no longer cached
+        assert_eq!(refresh_collection_stats(&taxii1, &taxii2, 10).await?, 0);
+        assert!(
+            CachedCollectionStats::find(&pool, protocol::TAXII2, &large.id)
+                .await?
+                .is_none()
+        );
+
+        Ok(())
+    }
+
+    /// Content blocks are counted by content binding.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_taxii1_collection_stats(pool: PgPool) -> TestResult {
+        let repo = DbTaxii1Repository::new(TaxiiPool::new(pool));
+        let collection = repo
+            .create_collection(&CollectionEntity::new("stats"))
+            .await?
+            .id
+            .ok_or("collection without id")?;
+
+        let now = Utc::now();
+        let blocks: [(Option<&str>, DateTime<Utc>); 4] = [
+            (
+                Some("urn:stix.mitre.org:xml:1.1.1"),
+                now - Duration::days(2),
+            ),
+            (
+                Some("urn:stix.mitre.org:xml:1.1.1"),
+                now - Duration::days(1),
+            ),
+            (Some("urn:stix.mitre.org:xml:1.2"), now - Duration::days(3)),
+            (None, now - Duration::days(4)),
+        ];
+        for (binding, timestamp_label) in blocks {
+            let entity = ContentBlockEntity {
+                timestamp_label,
+                ..ContentBlockEntity::new(
+                    b"<stix:STIX_Package/>",
+                    binding.map(ContentBindingEntity::new),
+                )
+            };
+            repo.create_content_block(&entity, Some(&[collection]), None)
+                .await?;
+        }
+
+        let stats = repo.collection_stats(collection).await?;
+        assert_eq!(
+            stats.by_type,
+            by_type(&[
+                ("unspecified", 1),
+                ("urn:stix.mitre.org:xml:1.1.1", 2),
+                ("urn:stix.mitre.org:xml:1.2", 1),
+            ])
+        );
+        assert_eq!((stats.object_count, stats.distinct_ids), (4, 4));
+        assert_eq!(
+            stats.latest_added.map(|t| t.timestamp_micros()),
+            Some((now - Duration::days(1)).timestamp_micros())
+        );
+
+        Ok(())
+    }
+}
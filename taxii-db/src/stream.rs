@@ -0,0 +1,121 @@
+//! Streaming query results.
+//!
+//! Large result sets (e.g. exporting a whole collection) are streamed row by
+//! row instead of being collected into a `Vec`. A query runs on a background
+//! task that sends rows through a bounded channel, so at most
+//! [`STREAM_BUFFER_SIZE`] rows are held in memory ahead of the consumer.
+//!
+//! Dropping a stream cancels its query and returns the connection to the
+//! pool, even while the query is waiting for the database.
+
+use futures::stream::{self, BoxStream, Stream, StreamExt};
+use tokio::sync::mpsc;
+
+use crate::error::{DatabaseError, DatabaseResult};
+
+/// Number of rows buffered ahead of a stream's consumer.
+pub const STREAM_BUFFER_SIZE: usize = 64;
+
+/// Stream of rows from a database query.
+pub type RowStream<T> = BoxStream<'static, DatabaseResult<T>>;
+
+/// Sending half of a [`RowStream`].
+pub(crate) type RowSender<T> = mpsc::Sender<DatabaseResult<T>>;
+
+/// Run `produce` on a background task and stream the rows it sends.
+///
+/// `produce` waits whenever the buffer is full. It is dropped (cancelling
+/// the query and releasing its connection) as soon as the stream is.
+pub(crate) fn spawn_stream<T, F, Fut>(produce: F) -> RowStream<T>
+where
+    T: Send + 'static,
+    F: FnOnce(RowSender<T>) -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let (tx, mut rx) = mpsc::channel(STREAM_BUFFER_SIZE);
+    let closed = tx.clone();
+    let producer = produce(tx);
+
+    tokio::spawn(async move {
+        tokio::select! {
+            () = producer => {}
+            () = closed.closed() => {}
+        }
+    });
+
+    stream::poll_fn(move |cx| rx.poll_recv(cx)).boxed()
+}
+
+/// Send every row of a query to a [`RowSender`], converting each row.
+///
+/// Stops at the first error (which is sent) or when the receiver is gone.
+pub(crate) async fn forward_rows<R, T>(
+    rows: impl Stream<Item = Result<R, sqlx::Error>>,
+    tx: RowSender<T>,
+) where
+    T: From<R>,
+{
+    let mut rows = std::pin::pin!(rows);
+    while let Some(row) = rows.next().await {
+        let failed = row.is_err();
+        let row = row.map(T::from).map_err(DatabaseError::from);
+        if tx.send(row).await.is_err() || failed {
+            break;
+        }
+    }
+}
+
+/// A stream yielding a single error.
+pub(crate) fn error_stream<T: Send + 'static>(err: DatabaseError) -> RowStream<T> {
+    stream::once(async move { Err(err) }).boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A producer that could emit far more rows than the buffer holds.
+    fn counting_stream(total: usize, produced: Arc<AtomicUsize>) -> RowStream<usize> {
+        spawn_stream(move |tx| async move {
+            for n in 0..total {
+                if tx.send(Ok(n)).await.is_err() {
+                    break;
+                }
+                produced.fetch_add(1, Ordering::SeqCst);
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn test_stream_buffer_is_bounded() -> Result<(), Box<dyn std::error::Error>> {
+        let produced = Arc::new(AtomicUsize::new(0));
+        let mut rows = counting_stream(10_000, produced.clone());
+
+        // Let the producer run until the buffer is full
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(produced.load(Ordering::SeqCst) <= STREAM_BUFFER_SIZE);
+
+        let mut consumed = 0;
+        while let Some(row) = rows.next().await {
+            assert_eq!(row?, consumed);
+            consumed += 1;
+            assert!(produced.load(Ordering::SeqCst) <= consumed + STREAM_BUFFER_SIZE);
+        }
+        assert_eq!(consumed, 10_000);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_dropping_stream_stops_producer() {
+        let produced = Arc::new(AtomicUsize::new(0));
+        let rows = counting_stream(10_000, produced.clone());
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        drop(rows);
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+
+        assert!(produced.load(Ordering::SeqCst) <= STREAM_BUFFER_SIZE + 1);
+    }
+}
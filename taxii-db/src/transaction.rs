@@ -0,0 +1,57 @@
+//! Unit-of-work transactions.
+//!
+//! A [`TaxiiTransaction`] groups writes of several repository calls so they
+//! are committed together or not at all. Start one with
+//! [`TaxiiPool::begin`](crate::TaxiiPool::begin) (always on the primary
+//! pool), pass it to the `*_in` methods of the repositories and commit it;
+//! dropping it without committing rolls every write back.
+//!
+//! # Lock ordering
+//!
+//! Writes take row locks that are held until the transaction ends, so two
+//! transactions locking the same rows in a different order can deadlock.
+//! Writes of a unit of work should follow this order:
+//!
+//! 1. TAXII 1.x inbox message, then its content blocks.
+//! 2. Collection rows (`data_collections` volume updates and collection
+//!    junction rows) in ascending collection ID; the repositories sort the
+//!    collection IDs they are given.
+//! 3. TAXII 2.x job, then objects, then job details.
+//!
+//! Keep transactions short: do validation and other slow work before
+//! beginning one.
+
+use sqlx::{PgConnection, Postgres, Transaction};
+
+use crate::error::DatabaseResult;
+
+/// A database transaction on the primary pool.
+#[derive(Debug)]
+pub struct TaxiiTransaction {
+    tx: Transaction<'static, Postgres>,
+}
+
+impl TaxiiTransaction {
+    pub(crate) fn new(tx: Transaction<'static, Postgres>) -> Self {
+        Self { tx }
+    }
+
+    /// Get the connection of the transaction, to run queries on.
+    pub fn conn(&mut self) -> &mut PgConnection {
+        &mut self.tx
+    }
+
+    /// Commit all writes of the transaction.
+    pub async fn commit(self) -> DatabaseResult<()> {
+        self.tx.commit().await?;
+        Ok(())
+    }
+
+    /// Roll back all writes of the transaction.
+    ///
+    /// Dropping the transaction has the same effect.
+    pub async fn rollback(self) -> DatabaseResult<()> {
+        self.tx.rollback().await?;
+        Ok(())
+    }
+}
@@ -10,7 +10,7 @@ use axum::response::{IntoResponse, Response};
 use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
 use futures::future::BoxFuture;
 use tower::{Layer, Service};
-use tracing::{error, warn};
+use tracing::{debug, error, warn};
 
 use taxii_auth::{AuthAPI, ClientInfo};
 use taxii_core::Account;
@@ -102,6 +102,7 @@ where
                     // Try to authenticate
                     match authenticate(&auth, auth_type, token_or_creds, client_info).await {
                         AuthResult::Success(account) => {
+                            tracing::Span::current().record("account_id", account.id);
                             req.extensions_mut().insert(account);
                             inner.call(req).await
                         }
@@ -230,6 +231,11 @@ async fn authenticate(
             {
                 Ok(Some(token)) => token,
                 Ok(None) => return AuthResult::Unauthorized("Authentication failed"),
+                Err(taxii_auth::AuthError::TotpRequired) => {
+                    return AuthResult::Unauthorized(
+                        "Two-factor authentication required, use a bearer token",
+                    );
+                }
                 Err(_) => return AuthResult::Unauthorized("Authentication error"),
             }
         }
@@ -237,7 +243,12 @@ async fn authenticate(
     };
 
     match auth.get_account(&token).await {
-        Ok(Some(account)) => AuthResult::Success(account),
+        Ok(Some(account)) => {
+            if let Some(scope) = &account.scope {
+                debug!(account = %account.username, scope = %scope, "Request with scoped token");
+            }
+            AuthResult::Success(account)
+        }
         Ok(None) => AuthResult::Unauthorized("Invalid token"),
         Err(_) => AuthResult::Unauthorized("Token validation error"),
     }
@@ -5,9 +5,15 @@
 
 use serde::Deserialize;
 use std::env;
+use std::net::IpAddr;
 use std::path::Path;
 use std::sync::{Mutex, OnceLock};
-use taxii_db::Taxii1Repository;
+use std::time::Duration;
+use taxii_2x::ProvenancePolicy;
+use taxii_core::CollisionPolicy;
+use taxii_db::{PoolOptions, Taxii1Repository};
+
+use crate::logging::LogFormat;
 
 /// Global server configuration, initialized once.
 /// Uses OnceLock with Result to handle initialization errors.
@@ -19,6 +25,10 @@ static INIT_LOCK: Mutex<()> = Mutex::new(());
 /// Environment variable prefix for overrides.
 const ENV_PREFIX: &str = "DARWIS_TAXII_";
 
+/// Largest accepted number of days in retention and age settings (about a
+/// century). Larger values overflow when subtracted from the current time.
+const MAX_DAYS: i64 = 36_500;
+
 /// Config file paths to search (in order).
 const CONFIG_PATHS: &[&str] = &["taxii.toml", "config/taxii.toml"];
 
@@ -34,10 +44,15 @@ pub struct TomlConfig {
     pub domain: Option<String>,
     pub support_basic_auth: Option<bool>,
     pub return_server_error_details: Option<bool>,
+    /// Log output format (`text` or `json`).
+    pub log_format: Option<LogFormat>,
     pub database: DatabaseConfig,
     pub auth: AuthConfig,
     pub taxii1: Taxii1Config,
     pub taxii2: Taxii2Config,
+    pub timeouts: TimeoutsConfig,
+    pub rate_limits: RateLimitsConfig,
+    pub bootstrap: BootstrapConfig,
 }
 
 /// Database configuration section.
@@ -45,6 +60,24 @@ pub struct TomlConfig {
 #[serde(default)]
 pub struct DatabaseConfig {
     pub url: Option<String>,
+    /// Connection string for reads (e.g. a read replica).
+    pub read_url: Option<String>,
+    /// Maximum connections of the read pool.
+    pub read_max_connections: Option<u32>,
+    /// Maximum connections of the pool.
+    pub max_connections: Option<u32>,
+    /// Connections kept open while idle.
+    pub min_connections: Option<u32>,
+    /// Seconds to wait for a connection before failing a request.
+    pub acquire_timeout_secs: Option<u64>,
+    /// Seconds after which idle connections are closed (0 keeps them).
+    pub idle_timeout_secs: Option<u64>,
+    /// Milliseconds a statement may run (0 uses the database's setting).
+    pub statement_timeout_ms: Option<u64>,
+    /// `application_name` of the server's database sessions.
+    pub application_name: Option<String>,
+    /// Create and drop a partition of the STIX object table per collection.
+    pub partition_objects: Option<bool>,
 }
 
 /// Auth configuration section.
@@ -53,6 +86,8 @@ pub struct DatabaseConfig {
 pub struct AuthConfig {
     pub secret: Option<String>,
     pub token_ttl_secs: Option<i64>,
+    /// Days of account activity to keep (0 keeps everything).
+    pub activity_retention_days: Option<i32>,
 }
 
 /// TAXII 1.x configuration section.
@@ -61,8 +96,35 @@ pub struct AuthConfig {
 pub struct Taxii1Config {
     pub save_raw_inbox_messages: Option<bool>,
     pub xml_parser_supports_huge_tree: Option<bool>,
+    /// Validate messages against the TAXII XML Message Binding schemas.
+    pub validate_xml: Option<bool>,
     pub count_blocks_in_poll_responses: Option<bool>,
     pub unauthorized_status: Option<String>,
+    /// Seconds between result set worker runs (0 disables the worker).
+    pub result_set_worker_interval_secs: Option<u64>,
+    /// Push content to subscriber and poller inboxes (off by default).
+    pub push_enabled: Option<bool>,
+    /// Allow push addresses resolving to loopback, private or link-local
+    /// addresses.
+    pub push_allow_private_addresses: Option<bool>,
+    /// Seconds between push delivery worker runs (0 disables push delivery).
+    pub push_worker_interval_secs: Option<u64>,
+    /// PEM file with the client certificate chain and private key
+    /// authenticating push deliveries to HTTPS inboxes.
+    pub push_client_certificate: Option<String>,
+    /// PEM file with a root certificate trusted for push deliveries, in
+    /// addition to the system roots.
+    pub push_ca_certificate: Option<String>,
+    /// Hours collections that deduplicate content look back for duplicates.
+    pub dedup_window_hours: Option<i64>,
+    /// Maximum size of a request body, in bytes.
+    pub max_message_size: Option<usize>,
+    /// Maximum size of an inbox content block, in bytes.
+    pub max_content_block_size: Option<usize>,
+    /// Maximum size of the content of a poll response part, in bytes.
+    pub max_result_bytes: Option<usize>,
+    /// Size above which request bodies are spooled to a temporary file.
+    pub spool_threshold: Option<usize>,
 }
 
 /// TAXII 2.x configuration section.
@@ -73,12 +135,84 @@ pub struct Taxii2Config {
     pub description: Option<String>,
     pub contact: Option<String>,
     pub max_content_length: Option<usize>,
+    /// Maximum number of objects in a posted envelope.
+    pub max_bundle_objects: Option<usize>,
     pub public_discovery: Option<bool>,
     pub allow_custom_properties: Option<bool>,
+    /// Require TAXII or STIX 2.1 media types on POST requests.
+    pub strict_content_type: Option<bool>,
+    /// Handling of posted objects without `created_by_ref`.
+    pub provenance_policy: Option<ProvenancePolicy>,
+    /// Handling of posted objects whose version exists with other content.
+    pub collision_policy: Option<CollisionPolicy>,
     /// Default pagination limit when client doesn't specify.
     pub default_pagination_limit: Option<i64>,
     /// Maximum pagination limit (hard cap).
     pub max_pagination_limit: Option<i64>,
+    /// Remove deleted objects instead of keeping tombstones.
+    pub hard_delete: Option<bool>,
+    /// Days to keep tombstones of deleted objects (0 keeps them).
+    pub deleted_retention_days: Option<i64>,
+    /// Include collection statistics in single collection responses.
+    pub collection_stats: Option<bool>,
+    /// Maximum length of a posted Indicator pattern, in bytes.
+    pub max_pattern_length: Option<usize>,
+    /// Maximum number of clauses of a posted STIX Indicator pattern.
+    pub max_pattern_clauses: Option<usize>,
+    /// Serve the capabilities document at `/taxii2/capabilities/`.
+    pub capabilities_endpoint: Option<bool>,
+    /// Days to keep completed job logs (0 keeps them).
+    pub job_retention_days: Option<i64>,
+    /// Days after which jobs that never completed are marked failed.
+    pub stale_job_days: Option<i64>,
+}
+
+/// First-run admin bootstrap configuration section.
+#[derive(Debug, Clone, Deserialize, Default)]
+#[serde(default)]
+pub struct BootstrapConfig {
+    pub admin_username: Option<String>,
+    pub admin_password: Option<String>,
+    /// File containing the admin password (e.g. a container secret).
+    pub admin_password_file: Option<String>,
+}
+
+/// Request timeouts configuration section.
+#[derive(Debug, Clone, Deserialize, Default)]
+#[serde(default)]
+pub struct TimeoutsConfig {
+    /// Deadline in seconds for TAXII 2.x discovery, API root, collection
+    /// and status requests (0 disables).
+    pub discovery_secs: Option<u64>,
+    /// Deadline in seconds for TAXII 2.x object, manifest and version
+    /// requests (0 disables).
+    pub objects_secs: Option<u64>,
+    /// Deadline in seconds for TAXII 1.x service requests (0 disables).
+    pub taxii1_secs: Option<u64>,
+}
+
+/// Rate limits configuration section.
+#[derive(Debug, Clone, Deserialize, Default)]
+#[serde(default)]
+pub struct RateLimitsConfig {
+    /// Sustained TAXII 2.x object read requests per second and client
+    /// (0 disables).
+    pub read_per_sec: Option<u32>,
+    /// Object read requests a client may burst (0 uses `read_per_sec`).
+    pub read_burst: Option<u32>,
+    /// Sustained TAXII 2.x object write requests per second and client
+    /// (0 disables).
+    pub write_per_sec: Option<u32>,
+    /// Object write requests a client may burst (0 uses `write_per_sec`).
+    pub write_burst: Option<u32>,
+    /// Sustained `/management/auth` attempts per minute, per client address
+    /// and per username (0 disables).
+    pub login_per_minute: Option<u32>,
+    /// Login attempts that may burst (0 uses `login_per_minute`).
+    pub login_burst: Option<u32>,
+    /// Addresses of reverse proxies whose `X-Forwarded-For` and `X-Real-IP`
+    /// headers identify unauthenticated clients.
+    pub trusted_proxies: Option<Vec<IpAddr>>,
 }
 
 /// Server configuration (flattened runtime config).
@@ -87,12 +221,33 @@ pub struct ServerConfig {
     /// Database connection string.
     pub db_connection: String,
 
+    /// Connection string for reads (e.g. a read replica). Reads use
+    /// `db_connection` if unset.
+    pub db_read_connection: Option<String>,
+
+    /// Maximum connections of the read pool (defaults to the size of the
+    /// primary pool).
+    pub db_read_max_connections: Option<u32>,
+
+    /// Connection pool settings (the read pool settings above are copied
+    /// into it).
+    pub db_pool: PoolOptions,
+
+    /// Create a partition of the STIX object table for every new collection
+    /// and drop it when the collection is deleted (TAXII 2.x). The table
+    /// must have been partitioned with `taxii-cli migrate partition`.
+    pub partition_objects: bool,
+
     /// Auth secret for JWT.
     pub auth_secret: String,
 
     /// Token TTL in seconds.
     pub token_ttl_secs: i64,
 
+    /// Days of account activity to keep; older records are purged
+    /// periodically. 0 keeps everything.
+    pub activity_retention_days: i32,
+
     /// Server bind address.
     pub bind_address: String,
 
@@ -118,15 +273,36 @@ pub struct ServerConfig {
     /// Maximum content length for TAXII 2.x.
     pub max_content_length: usize,
 
+    /// Maximum number of objects in a posted envelope (TAXII 2.x).
+    pub max_bundle_objects: usize,
+
     /// Whether to allow public discovery.
     pub public_discovery: bool,
 
     /// Whether to allow custom STIX properties.
     pub allow_custom_properties: bool,
 
+    /// Reject POST requests not declared as TAXII or STIX 2.1 content
+    /// with 415; otherwise any JSON media type, or none, is accepted
+    /// (TAXII 2.x).
+    pub strict_content_type: bool,
+
+    /// Handling of posted objects without `created_by_ref`: accept them
+    /// (`off`), reject them, or stamp them with the identity of the
+    /// posting account (TAXII 2.x).
+    pub provenance_policy: ProvenancePolicy,
+
+    /// Handling of posted objects whose version (`id` and `modified`)
+    /// exists with different content: reject them, overwrite the stored
+    /// content, or keep it (TAXII 2.x).
+    pub collision_policy: CollisionPolicy,
+
     /// Whether to return server error details.
     pub return_server_error_details: bool,
 
+    /// Format of log output.
+    pub log_format: LogFormat,
+
     /// Unauthorized status type for TAXII 1.x.
     pub unauthorized_status: String,
 
@@ -139,15 +315,131 @@ pub struct ServerConfig {
     /// When enabled, allows parsing of large XML documents.
     pub xml_parser_supports_huge_tree: bool,
 
+    /// Whether to validate messages against the TAXII XML Message Binding
+    /// schemas before parsing them (TAXII 1.x). Invalid messages are
+    /// answered with a `BAD_MESSAGE` status naming the first violation.
+    pub validate_xml: bool,
+
     /// Whether to count blocks in poll responses (TAXII 1.x).
     /// When enabled, includes total count in poll responses (can be expensive).
     pub count_blocks_in_poll_responses: bool,
 
+    /// Seconds between runs of the result set worker (TAXII 1.x).
+    /// The worker prepares result sets for asynchronous polls; 0 disables it.
+    pub result_set_worker_interval_secs: u64,
+
+    /// Whether content is pushed to subscriber and poller inboxes, and to
+    /// which addresses (TAXII 1.x). Push is off by default, and push
+    /// addresses must resolve to public addresses.
+    pub push: taxii_1x::PushPolicy,
+
+    /// Seconds between runs of the push delivery worker (TAXII 1.x).
+    /// The worker pushes new content to subscriber inboxes; 0 disables it.
+    pub push_worker_interval_secs: u64,
+
+    /// PEM file with the client certificate chain and private key the push
+    /// delivery worker authenticates to HTTPS inboxes with (TAXII 1.x).
+    pub push_client_certificate: Option<String>,
+
+    /// PEM file with a root certificate the push delivery worker trusts, in
+    /// addition to the system roots (TAXII 1.x).
+    pub push_ca_certificate: Option<String>,
+
+    /// Hours collections that deduplicate content look back for an
+    /// identical content block (TAXII 1.x).
+    pub dedup_window_hours: i64,
+
+    /// Size limits of TAXII 1.x messages: request bodies, inbox content
+    /// blocks and poll response parts. Services override them in their
+    /// properties.
+    pub taxii1_limits: taxii_1x::MessageLimits,
+
     /// Default pagination limit when client doesn't specify (TAXII 2.x).
     pub default_pagination_limit: i64,
 
     /// Maximum pagination limit, hard cap (TAXII 2.x).
     pub max_pagination_limit: i64,
+
+    /// Remove deleted objects immediately instead of keeping tombstones
+    /// (TAXII 2.x).
+    pub hard_delete: bool,
+
+    /// Days to keep tombstones of deleted objects; older ones are purged
+    /// periodically. 0 keeps them (TAXII 2.x).
+    pub deleted_retention_days: i64,
+
+    /// Days to keep logs of completed jobs; older ones are removed
+    /// periodically. 0 keeps them (TAXII 2.x).
+    pub job_retention_days: i64,
+
+    /// Days after which jobs that never completed are marked failed, so
+    /// they are removed with completed jobs later (TAXII 2.x).
+    pub stale_job_days: i64,
+
+    /// Include collection statistics (`x_darwis_stats`) in single
+    /// collection responses (TAXII 2.x).
+    pub collection_stats: bool,
+
+    /// Maximum length in bytes of the pattern of a posted Indicator
+    /// (TAXII 2.x).
+    pub max_pattern_length: usize,
+
+    /// Maximum number of clauses of the STIX pattern of a posted Indicator
+    /// (TAXII 2.x).
+    pub max_pattern_clauses: usize,
+
+    /// Serve a document describing the enabled features at
+    /// `/taxii2/capabilities/` (TAXII 2.x).
+    pub capabilities_endpoint: bool,
+
+    /// Request deadline in seconds for TAXII 2.x discovery, API root,
+    /// collection and status endpoints; 0 disables it.
+    pub discovery_timeout_secs: u64,
+
+    /// Request deadline in seconds for TAXII 2.x object, manifest and
+    /// version endpoints; 0 disables it.
+    pub objects_timeout_secs: u64,
+
+    /// Request deadline in seconds for TAXII 1.x services; 0 disables it.
+    pub taxii1_timeout_secs: u64,
+
+    /// Sustained TAXII 2.x object reads (GET) per second and client;
+    /// 0 disables the limit.
+    pub read_rate_limit_per_sec: u32,
+
+    /// Object reads a client may burst above the sustained rate; 0 uses
+    /// the rate.
+    pub read_rate_limit_burst: u32,
+
+    /// Sustained TAXII 2.x object writes (POST, DELETE) per second and
+    /// client; 0 disables the limit.
+    pub write_rate_limit_per_sec: u32,
+
+    /// Object writes a client may burst above the sustained rate; 0 uses
+    /// the rate.
+    pub write_rate_limit_burst: u32,
+
+    /// Sustained login attempts per minute, per client address and per
+    /// username; 0 disables the limit.
+    pub login_rate_limit_per_minute: u32,
+
+    /// Login attempts that may burst above the sustained rate; 0 uses the
+    /// rate.
+    pub login_rate_limit_burst: u32,
+
+    /// Reverse proxies trusted to forward client addresses; other clients
+    /// are rate limited by their peer address.
+    pub rate_limit_trusted_proxies: Vec<IpAddr>,
+
+    /// Username of the admin account created on first run.
+    pub bootstrap_admin_username: String,
+
+    /// Password of the admin account created on first run.
+    /// Bootstrap is disabled unless this or the password file is set.
+    pub bootstrap_admin_password: Option<String>,
+
+    /// File containing the password of the admin account created on first run.
+    pub bootstrap_admin_password_file: Option<String>,
 }
 
 /// Configuration loading error.
@@ -161,6 +453,9 @@ pub enum ConfigError {
 
     #[error("Missing required configuration: {0}")]
     MissingRequired(String),
+
+    #[error("Invalid configuration: {0}")]
+    Invalid(String),
 }
 
 impl ServerConfig {
@@ -226,7 +521,7 @@ impl ServerConfig {
     }
 
     /// Build ServerConfig from TOML config with environment variable overrides.
-    fn from_toml_with_env_overrides(toml: TomlConfig) -> Result<Self, ConfigError> {
+    pub(crate) fn from_toml_with_env_overrides(toml: TomlConfig) -> Result<Self, ConfigError> {
         // Database connection: env > toml, required
         let db_connection = env_var("DB_CONNECTION")
             .or(toml.database.url)
@@ -236,6 +531,105 @@ impl ServerConfig {
                 )
             })?;
 
+        // Read pool: optional, but its size alone is a mistake
+        let db_read_connection = env_var("DB_READ_CONNECTION").or(toml.database.read_url);
+        let db_read_max_connections =
+            env_var_parse("DB_READ_MAX_CONNECTIONS").or(toml.database.read_max_connections);
+        if db_read_connection.is_none() && db_read_max_connections.is_some() {
+            return Err(ConfigError::Invalid(
+                "database.read_max_connections requires database.read_url \
+                 (or DARWIS_TAXII_DB_READ_CONNECTION env var)"
+                    .to_string(),
+            ));
+        }
+        if db_read_max_connections == Some(0) {
+            return Err(ConfigError::Invalid(
+                "database.read_max_connections must be at least 1".to_string(),
+            ));
+        }
+
+        // Schema validation needs libxml2, linked by the xsd feature
+        let validate_xml = env_var_parse("VALIDATE_XML")
+            .or(toml.taxii1.validate_xml)
+            .unwrap_or(false);
+        if validate_xml && !cfg!(feature = "xsd") {
+            return Err(ConfigError::Invalid(
+                "taxii1.validate_xml requires a server built with the xsd feature".to_string(),
+            ));
+        }
+
+        let pool_defaults = PoolOptions::default();
+        let db_pool = PoolOptions {
+            max_connections: env_var_parse("DB_MAX_CONNECTIONS")
+                .or(toml.database.max_connections)
+                .unwrap_or(pool_defaults.max_connections),
+            min_connections: env_var_parse("DB_MIN_CONNECTIONS")
+                .or(toml.database.min_connections)
+                .unwrap_or(pool_defaults.min_connections),
+            acquire_timeout: env_var_parse("DB_ACQUIRE_TIMEOUT_SECS")
+                .or(toml.database.acquire_timeout_secs)
+                .map_or(pool_defaults.acquire_timeout, Duration::from_secs),
+            idle_timeout: match env_var_parse("DB_IDLE_TIMEOUT_SECS")
+                .or(toml.database.idle_timeout_secs)
+            {
+                Some(0) => None,
+                Some(secs) => Some(Duration::from_secs(secs)),
+                None => pool_defaults.idle_timeout,
+            },
+            statement_timeout: env_var_parse("DB_STATEMENT_TIMEOUT_MS")
+                .or(toml.database.statement_timeout_ms)
+                .filter(|ms| *ms > 0)
+                .map(Duration::from_millis),
+            application_name: env_var("DB_APPLICATION_NAME")
+                .or(toml.database.application_name)
+                .unwrap_or(pool_defaults.application_name),
+            read_url: db_read_connection.clone(),
+            read_max_connections: db_read_max_connections,
+        };
+        db_pool
+            .validate()
+            .map_err(|e| ConfigError::Invalid(format!("database: {e}")))?;
+
+        let rate_limit_trusted_proxies = match env_var("RATE_LIMIT_TRUSTED_PROXIES") {
+            Some(proxies) => proxies
+                .split(',')
+                .filter(|proxy| !proxy.trim().is_empty())
+                .map(|proxy| {
+                    proxy.trim().parse().map_err(|_| {
+                        ConfigError::Invalid(format!(
+                            "rate_limits.trusted_proxies: invalid IP address '{proxy}'"
+                        ))
+                    })
+                })
+                .collect::<Result<_, _>>()?,
+            None => toml.rate_limits.trusted_proxies.unwrap_or_default(),
+        };
+
+        let dedup_window_hours = env_var_parse("DEDUP_WINDOW_HOURS")
+            .or(toml.taxii1.dedup_window_hours)
+            .unwrap_or(24);
+        check_range(
+            "taxii1.dedup_window_hours",
+            dedup_window_hours,
+            MAX_DAYS * 24,
+        )?;
+        let deleted_retention_days = env_var_parse("DELETED_RETENTION_DAYS")
+            .or(toml.taxii2.deleted_retention_days)
+            .unwrap_or(0);
+        check_range(
+            "taxii2.deleted_retention_days",
+            deleted_retention_days,
+            MAX_DAYS,
+        )?;
+        let job_retention_days = env_var_parse("JOB_RETENTION_DAYS")
+            .or(toml.taxii2.job_retention_days)
+            .unwrap_or(30);
+        check_range("taxii2.job_retention_days", job_retention_days, MAX_DAYS)?;
+        let stale_job_days = env_var_parse("STALE_JOB_DAYS")
+            .or(toml.taxii2.stale_job_days)
+            .unwrap_or(90);
+        check_range("taxii2.stale_job_days", stale_job_days, MAX_DAYS)?;
+
         // Auth secret: env > toml, required
         let auth_secret = env_var("AUTH_SECRET").or(toml.auth.secret).ok_or_else(|| {
             ConfigError::MissingRequired(
@@ -245,10 +639,19 @@ impl ServerConfig {
 
         Ok(Self {
             db_connection,
+            db_read_connection,
+            db_read_max_connections,
+            db_pool,
+            partition_objects: env_var_parse("DB_PARTITION_OBJECTS")
+                .or(toml.database.partition_objects)
+                .unwrap_or(false),
             auth_secret,
             token_ttl_secs: env_var_parse("TOKEN_TTL_SECS")
                 .or(toml.auth.token_ttl_secs)
                 .unwrap_or(3600),
+            activity_retention_days: env_var_parse("ACTIVITY_RETENTION_DAYS")
+                .or(toml.auth.activity_retention_days)
+                .unwrap_or(0),
             bind_address: env_var("BIND_ADDRESS")
                 .or(toml.bind_address)
                 .unwrap_or_else(|| "0.0.0.0".to_string()),
@@ -267,15 +670,30 @@ impl ServerConfig {
             max_content_length: env_var_parse("MAX_CONTENT_LENGTH")
                 .or(toml.taxii2.max_content_length)
                 .unwrap_or(2048),
+            max_bundle_objects: env_var_parse("MAX_BUNDLE_OBJECTS")
+                .or(toml.taxii2.max_bundle_objects)
+                .unwrap_or(10_000),
             public_discovery: env_var_parse("PUBLIC_DISCOVERY")
                 .or(toml.taxii2.public_discovery)
                 .unwrap_or(true),
             allow_custom_properties: env_var_parse("ALLOW_CUSTOM_PROPERTIES")
                 .or(toml.taxii2.allow_custom_properties)
                 .unwrap_or(true),
+            strict_content_type: env_var_parse("STRICT_CONTENT_TYPE")
+                .or(toml.taxii2.strict_content_type)
+                .unwrap_or(true),
+            provenance_policy: env_var_parse("PROVENANCE_POLICY")
+                .or(toml.taxii2.provenance_policy)
+                .unwrap_or_default(),
+            collision_policy: env_var_parse("COLLISION_POLICY")
+                .or(toml.taxii2.collision_policy)
+                .unwrap_or_default(),
             return_server_error_details: env_var_parse("RETURN_SERVER_ERROR_DETAILS")
                 .or(toml.return_server_error_details)
                 .unwrap_or(false),
+            log_format: env_var_parse("LOG_FORMAT")
+                .or(toml.log_format)
+                .unwrap_or_default(),
             unauthorized_status: env_var("UNAUTHORIZED_STATUS")
                 .or(toml.taxii1.unauthorized_status)
                 .unwrap_or_else(|| "UNAUTHORIZED".to_string()),
@@ -285,17 +703,121 @@ impl ServerConfig {
             xml_parser_supports_huge_tree: env_var_parse("XML_PARSER_SUPPORTS_HUGE_TREE")
                 .or(toml.taxii1.xml_parser_supports_huge_tree)
                 .unwrap_or(true),
+            validate_xml,
             count_blocks_in_poll_responses: env_var_parse("COUNT_BLOCKS_IN_POLL_RESPONSES")
                 .or(toml.taxii1.count_blocks_in_poll_responses)
                 .unwrap_or(false),
+            result_set_worker_interval_secs: env_var_parse("RESULT_SET_WORKER_INTERVAL_SECS")
+                .or(toml.taxii1.result_set_worker_interval_secs)
+                .unwrap_or(10),
+            push: taxii_1x::PushPolicy {
+                enabled: env_var_parse("PUSH_ENABLED")
+                    .or(toml.taxii1.push_enabled)
+                    .unwrap_or(false),
+                allow_private_addresses: env_var_parse("PUSH_ALLOW_PRIVATE_ADDRESSES")
+                    .or(toml.taxii1.push_allow_private_addresses)
+                    .unwrap_or(false),
+            },
+            push_worker_interval_secs: env_var_parse("PUSH_WORKER_INTERVAL_SECS")
+                .or(toml.taxii1.push_worker_interval_secs)
+                .unwrap_or(10),
+            push_client_certificate: env_var("PUSH_CLIENT_CERTIFICATE")
+                .or(toml.taxii1.push_client_certificate),
+            push_ca_certificate: env_var("PUSH_CA_CERTIFICATE").or(toml.taxii1.push_ca_certificate),
+            dedup_window_hours,
+            taxii1_limits: taxii_1x::MessageLimits {
+                max_message_size: env_var_parse("TAXII1_MAX_MESSAGE_SIZE")
+                    .or(toml.taxii1.max_message_size)
+                    .unwrap_or(taxii_1x::messages::MAX_MESSAGE_SIZE),
+                max_content_block_size: env_var_parse("TAXII1_MAX_CONTENT_BLOCK_SIZE")
+                    .or(toml.taxii1.max_content_block_size)
+                    .unwrap_or(taxii_1x::limits::DEFAULT_MAX_CONTENT_BLOCK_SIZE),
+                max_result_bytes: env_var_parse("TAXII1_MAX_RESULT_BYTES")
+                    .or(toml.taxii1.max_result_bytes)
+                    .unwrap_or(taxii_1x::limits::DEFAULT_MAX_RESULT_BYTES),
+                spool_threshold: env_var_parse("TAXII1_SPOOL_THRESHOLD")
+                    .or(toml.taxii1.spool_threshold)
+                    .unwrap_or(taxii_1x::limits::DEFAULT_SPOOL_THRESHOLD),
+            },
             default_pagination_limit: env_var_parse("DEFAULT_PAGINATION_LIMIT")
                 .or(toml.taxii2.default_pagination_limit)
                 .unwrap_or(1000),
             max_pagination_limit: env_var_parse("MAX_PAGINATION_LIMIT")
                 .or(toml.taxii2.max_pagination_limit)
                 .unwrap_or(1000),
+            hard_delete: env_var_parse("HARD_DELETE")
+                .or(toml.taxii2.hard_delete)
+                .unwrap_or(false),
+            deleted_retention_days,
+            job_retention_days,
+            stale_job_days,
+            collection_stats: env_var_parse("COLLECTION_STATS")
+                .or(toml.taxii2.collection_stats)
+                .unwrap_or(false),
+            max_pattern_length: env_var_parse("MAX_PATTERN_LENGTH")
+                .or(toml.taxii2.max_pattern_length)
+                .unwrap_or(16384),
+            max_pattern_clauses: env_var_parse("MAX_PATTERN_CLAUSES")
+                .or(toml.taxii2.max_pattern_clauses)
+                .unwrap_or(256),
+            capabilities_endpoint: env_var_parse("CAPABILITIES_ENDPOINT")
+                .or(toml.taxii2.capabilities_endpoint)
+                .unwrap_or(false),
+            discovery_timeout_secs: env_var_parse("DISCOVERY_TIMEOUT_SECS")
+                .or(toml.timeouts.discovery_secs)
+                .unwrap_or(10),
+            objects_timeout_secs: env_var_parse("OBJECTS_TIMEOUT_SECS")
+                .or(toml.timeouts.objects_secs)
+                .unwrap_or(60),
+            taxii1_timeout_secs: env_var_parse("TAXII1_TIMEOUT_SECS")
+                .or(toml.timeouts.taxii1_secs)
+                .unwrap_or(60),
+            read_rate_limit_per_sec: env_var_parse("READ_RATE_LIMIT_PER_SEC")
+                .or(toml.rate_limits.read_per_sec)
+                .unwrap_or(0),
+            read_rate_limit_burst: env_var_parse("READ_RATE_LIMIT_BURST")
+                .or(toml.rate_limits.read_burst)
+                .unwrap_or(0),
+            write_rate_limit_per_sec: env_var_parse("WRITE_RATE_LIMIT_PER_SEC")
+                .or(toml.rate_limits.write_per_sec)
+                .unwrap_or(0),
+            write_rate_limit_burst: env_var_parse("WRITE_RATE_LIMIT_BURST")
+                .or(toml.rate_limits.write_burst)
+                .unwrap_or(0),
+            login_rate_limit_per_minute: env_var_parse("LOGIN_RATE_LIMIT_PER_MINUTE")
+                .or(toml.rate_limits.login_per_minute)
+                .unwrap_or(10),
+            login_rate_limit_burst: env_var_parse("LOGIN_RATE_LIMIT_BURST")
+                .or(toml.rate_limits.login_burst)
+                .unwrap_or(0),
+            rate_limit_trusted_proxies,
+            bootstrap_admin_username: env_var("BOOTSTRAP_ADMIN_USERNAME")
+                .or(toml.bootstrap.admin_username)
+                .unwrap_or_else(|| "admin".to_string()),
+            bootstrap_admin_password: env_var("BOOTSTRAP_ADMIN_PASSWORD")
+                .or(toml.bootstrap.admin_password),
+            bootstrap_admin_password_file: env_var("BOOTSTRAP_ADMIN_PASSWORD_FILE")
+                .or(toml.bootstrap.admin_password_file),
         })
     }
+
+    /// Resolve the first-run admin password.
+    ///
+    /// The password takes precedence over the password file. Returns `None`
+    /// when bootstrap is not configured.
+    pub fn bootstrap_admin_password(&self) -> Result<Option<String>, ConfigError> {
+        if let Some(password) = &self.bootstrap_admin_password {
+            return Ok(Some(password.clone()));
+        }
+
+        match &self.bootstrap_admin_password_file {
+            Some(path) => {
+                let content = std::fs::read_to_string(path)?;
+                Ok(Some(content.trim_end_matches(['\r', '\n']).to_string()))
+            }
+            None => Ok(None),
+        }
+    }
 }
 
 /// Get environment variable with DARWIS_TAXII_ prefix.
@@ -308,6 +830,17 @@ fn env_var_parse<T: std::str::FromStr>(name: &str) -> Option<T> {
     env_var(name).and_then(|s| s.parse().ok())
 }
 
+/// Check that a count of days or hours is within `0..=max`.
+fn check_range(key: &str, value: i64, max: i64) -> Result<(), ConfigError> {
+    if (0..=max).contains(&value) {
+        Ok(())
+    } else {
+        Err(ConfigError::Invalid(format!(
+            "{key} must be between 0 and {max}, got {value}"
+        )))
+    }
+}
+
 /// Get the domain for a service, checking persistence first, then falling back to config.
 ///
 /// The domain resolution order is:
@@ -326,3 +859,158 @@ pub async fn get_domain(
     // Fall back to config domain
     config.domain.clone()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+    fn load(database: &str) -> Result<ServerConfig, ConfigError> {
+        let toml: TomlConfig = toml::from_str(&format!(
+            "[database]\nurl = \"postgresql://primary/taxii\"\n{database}\n[auth]\nsecret = \"secret\"\n"
+        ))?;
+        ServerConfig::from_toml_with_env_overrides(toml)
+    }
+
+    #[test]
+    fn test_read_pool_config() -> TestResult {
+        let config = load("")?;
+        assert_eq!(config.db_read_connection, None);
+        assert_eq!(config.db_read_max_connections, None);
+
+        let config = load("read_url = \"postgresql://replica/taxii\"\nread_max_connections = 20")?;
+        assert_eq!(
+            config.db_read_connection.as_deref(),
+            Some("postgresql://replica/taxii")
+        );
+        assert_eq!(config.db_read_max_connections, Some(20));
+
+        // A read pool size without a read URL is a partial configuration
+        assert!(matches!(
+            load("read_max_connections = 20"),
+            Err(ConfigError::Invalid(_))
+        ));
+        assert!(matches!(
+            load("read_url = \"postgresql://replica/taxii\"\nread_max_connections = 0"),
+            Err(ConfigError::Invalid(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pool_config() -> TestResult {
+        let config = load("")?;
+        assert!(!config.partition_objects);
+        assert_eq!(config.db_pool.max_connections, 10);
+        assert_eq!(config.db_pool.statement_timeout, None);
+        assert_eq!(config.db_pool.application_name, "darwis-taxii");
+
+        let config = load(
+            "max_connections = 40\nmin_connections = 5\nacquire_timeout_secs = 5\n\
+             idle_timeout_secs = 0\nstatement_timeout_ms = 30000\napplication_name = \"taxii-a\"\n\
+             read_url = \"postgresql://replica/taxii\"",
+        )?;
+        assert_eq!(config.db_pool.max_connections, 40);
+        assert_eq!(config.db_pool.min_connections, 5);
+        assert_eq!(config.db_pool.acquire_timeout, Duration::from_secs(5));
+        assert_eq!(config.db_pool.idle_timeout, None);
+        assert_eq!(
+            config.db_pool.statement_timeout,
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(config.db_pool.application_name, "taxii-a");
+        assert!(load("partition_objects = true")?.partition_objects);
+        assert_eq!(
+            config.db_pool.read_url.as_deref(),
+            Some("postgresql://replica/taxii")
+        );
+
+        // Rejected at startup rather than when connecting
+        for invalid in [
+            "max_connections = 0",
+            "max_connections = 2\nmin_connections = 3",
+            "acquire_timeout_secs = 0",
+        ] {
+            assert!(matches!(load(invalid), Err(ConfigError::Invalid(_))));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_xml_requires_xsd_feature() -> TestResult {
+        let toml: TomlConfig = toml::from_str(
+            "[database]\nurl = \"postgresql://primary/taxii\"\n[auth]\nsecret = \"secret\"\n\
+             [taxii1]\nvalidate_xml = true\n",
+        )?;
+        let config = ServerConfig::from_toml_with_env_overrides(toml);
+        if cfg!(feature = "xsd") {
+            assert!(config?.validate_xml);
+        } else {
+            assert!(matches!(config, Err(ConfigError::Invalid(_))));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_out_of_range_windows_are_rejected() -> TestResult {
+        let load_with = |extra: &str| -> Result<ServerConfig, ConfigError> {
+            let toml: TomlConfig = toml::from_str(&format!(
+                "[database]\nurl = \"postgresql://primary/taxii\"\n[auth]\nsecret = \"secret\"\n{extra}\n"
+            ))?;
+            ServerConfig::from_toml_with_env_overrides(toml)
+        };
+
+        assert_eq!(
+            load_with("[taxii1]\ndedup_window_hours = 48")?.dedup_window_hours,
+            48
+        );
+        assert_eq!(
+            load_with("[taxii2]\ndeleted_retention_days = 7")?.deleted_retention_days,
+            7
+        );
+        for invalid in [
+            "[taxii1]\ndedup_window_hours = -1",
+            "[taxii1]\ndedup_window_hours = 9223372036854775807",
+            "[taxii2]\ndeleted_retention_days = -1",
+            "[taxii2]\ndeleted_retention_days = 9223372036854775807",
+            "[taxii2]\njob_retention_days = -1",
+            "[taxii2]\nstale_job_days = 9223372036854775807",
+        ] {
+            assert!(matches!(load_with(invalid), Err(ConfigError::Invalid(_))));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_trusted_proxies() -> TestResult {
+        let load_with = |extra: &str| -> Result<ServerConfig, ConfigError> {
+            let toml: TomlConfig = toml::from_str(&format!(
+                "[database]\nurl = \"postgresql://primary/taxii\"\n[auth]\nsecret = \"secret\"\n{extra}\n"
+            ))?;
+            ServerConfig::from_toml_with_env_overrides(toml)
+        };
+
+        assert!(load_with("")?.rate_limit_trusted_proxies.is_empty());
+        // Logins are limited unless disabled
+        assert_eq!(load_with("")?.login_rate_limit_per_minute, 10);
+        assert_eq!(
+            load_with("[rate_limits]\nlogin_per_minute = 0")?.login_rate_limit_per_minute,
+            0
+        );
+        assert_eq!(
+            load_with("[rate_limits]\ntrusted_proxies = [\"10.0.0.1\", \"::1\"]")?
+                .rate_limit_trusted_proxies,
+            vec![
+                IpAddr::from([10, 0, 0, 1]),
+                IpAddr::from([0, 0, 0, 0, 0, 0, 0, 1u16])
+            ]
+        );
+        assert!(load_with("[rate_limits]\ntrusted_proxies = [\"proxy\"]").is_err());
+
+        Ok(())
+    }
+}
@@ -1,9 +1,20 @@
 //! Server errors.
+//!
+//! Errors from the database, auth, TAXII 1.x and TAXII 2.x layers map to an
+//! HTTP status here, once: a missing entity is `404`, failed authentication
+//! `401`, a refused operation `403`, an invalid request `400`, an exhausted
+//! database pool `503` and anything internal `500`; database errors map
+//! through [`taxii_2x::database_status`] from every layer. Response bodies
+//! never carry internal details.
 
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
+use taxii_1x::{StatusType, Taxii1xError};
+use taxii_2x::database_status;
+use taxii_auth::AuthError;
+use taxii_db::DatabaseError;
 use thiserror::Error;
-use tracing::error;
+use tracing::{debug, error};
 
 /// Server result type.
 pub type ServerResult<T> = Result<T, ServerError>;
@@ -36,29 +47,240 @@ pub enum ServerError {
     Io(#[from] std::io::Error),
 }
 
+impl ServerError {
+    /// Get HTTP status code.
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            Self::Config(_) | Self::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::Database(e) => database_status(e),
+            Self::Auth(e) => auth_status(e),
+            Self::Taxii1x(e) => taxii1x_status(e),
+            Self::Taxii2x(e) => e.status_code(),
+        }
+    }
+
+    /// Get user-safe message (no internal details exposed).
+    fn user_message(&self) -> String {
+        match self {
+            Self::Config(_) => "Server configuration error".to_string(),
+            Self::Io(_) => "Server I/O error".to_string(),
+            Self::Database(e) => database_message(e),
+            Self::Auth(e) => auth_message(e),
+            Self::Taxii1x(e) => taxii1x_message(e),
+            Self::Taxii2x(e) => e.to_error_response().title,
+        }
+    }
+}
+
+/// HTTP status of an auth error.
+pub(crate) fn auth_status(error: &AuthError) -> StatusCode {
+    match error {
+        AuthError::Database(e) => database_status(e),
+        AuthError::TotpRequired => StatusCode::UNAUTHORIZED,
+        AuthError::SecondFactorLocked(_) => StatusCode::TOO_MANY_REQUESTS,
+        AuthError::Password(_) | AuthError::InvalidPermission(_) | AuthError::InvalidResetToken => {
+            StatusCode::BAD_REQUEST
+        }
+        AuthError::Config(_) | AuthError::Json(_) | AuthError::Jwt(_) | AuthError::Totp(_) => {
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+/// HTTP status of a TAXII 1.x error.
+///
+/// Status messages take the status of their status type. A handler's
+/// `FAILURE` refuses the client's request, so it is a client error; only
+/// errors that are not status messages, and the status types reporting a
+/// server-side failure, are server errors.
+pub(crate) fn taxii1x_status(error: &Taxii1xError) -> StatusCode {
+    match error {
+        Taxii1xError::StatusMessage { status_type, .. } => match status_type {
+            StatusType::BadMessage
+            | StatusType::UnsupportedMessageBinding
+            | StatusType::UnsupportedContentBinding
+            | StatusType::UnsupportedProtocol
+            | StatusType::UnsupportedQuery
+            | StatusType::PollingUnsupported
+            | StatusType::InvalidResponsePart
+            | StatusType::DestinationCollectionError
+            | StatusType::Failure => StatusCode::BAD_REQUEST,
+            StatusType::Unauthorized => StatusCode::UNAUTHORIZED,
+            StatusType::Denied => StatusCode::FORBIDDEN,
+            StatusType::NotFound => StatusCode::NOT_FOUND,
+            StatusType::Pending | StatusType::Retry | StatusType::Unavailable => {
+                StatusCode::SERVICE_UNAVAILABLE
+            }
+            StatusType::Success => StatusCode::OK,
+            StatusType::AsynchronousPollError | StatusType::NetworkError => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        },
+        Taxii1xError::InvalidRequest(_)
+        | Taxii1xError::MissingHeader(_)
+        | Taxii1xError::XmlParse { .. }
+        | Taxii1xError::UnsupportedVersion(_) => StatusCode::BAD_REQUEST,
+        Taxii1xError::UnsupportedContentType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        Taxii1xError::Database(e) => database_status(e),
+        Taxii1xError::Http(_)
+        | Taxii1xError::HttpStatus(_)
+        | Taxii1xError::UnexpectedResponse(_) => StatusCode::BAD_GATEWAY,
+        Taxii1xError::XmlSerialize { .. } | Taxii1xError::Io(_) => {
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+/// User-safe message of a database error.
+fn database_message(error: &DatabaseError) -> String {
+    match error {
+        DatabaseError::NotFound(msg) => msg.clone(),
+        DatabaseError::PoolTimeout | DatabaseError::ResultsNotReady => {
+            "Service temporarily unavailable".to_string()
+        }
+        _ => "Database error occurred".to_string(),
+    }
+}
+
+/// User-safe message of an auth error.
+fn auth_message(error: &AuthError) -> String {
+    match error {
+        AuthError::Database(e) => database_message(e),
+        AuthError::Password(msg) | AuthError::InvalidPermission(msg) => msg.clone(),
+        AuthError::InvalidResetToken
+        | AuthError::TotpRequired
+        | AuthError::SecondFactorLocked(_) => error.to_string(),
+        _ => "Authentication error occurred".to_string(),
+    }
+}
+
+/// User-safe message of a TAXII 1.x error.
+fn taxii1x_message(error: &Taxii1xError) -> String {
+    match error {
+        Taxii1xError::StatusMessage { message, .. } => message.clone(),
+        Taxii1xError::InvalidRequest(_)
+        | Taxii1xError::MissingHeader(_)
+        | Taxii1xError::XmlParse { .. }
+        | Taxii1xError::UnsupportedVersion(_)
+        | Taxii1xError::UnsupportedContentType(_) => error.to_string(),
+        Taxii1xError::Database(e) => database_message(e),
+        _ => "TAXII 1.x processing error".to_string(),
+    }
+}
+
 impl IntoResponse for ServerError {
     fn into_response(self) -> Response {
+        let status = self.status_code();
+
         // Log full error details internally for debugging
-        error!("Server error: {:?}", self);
+        if status.is_server_error() {
+            error!("Server error: {:?}", self);
+        } else {
+            debug!("Client error: {:?}", self);
+        }
 
-        // Handle Taxii2x separately - it has its own IntoResponse with proper formatting
+        // TAXII 2.x errors carry their own JSON body
         if let Self::Taxii2x(e) = self {
             return e.into_response();
         }
 
-        // Return user-friendly messages only - no internal details exposed
-        let (status, message) = match &self {
-            Self::Config(_) => (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "Server configuration error",
-            ),
-            Self::Database(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Database error occurred"),
-            Self::Auth(_) => (StatusCode::UNAUTHORIZED, "Authentication failed"),
-            Self::Taxii1x(_) => (StatusCode::BAD_REQUEST, "Invalid TAXII 1.x request"),
-            Self::Taxii2x(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error"),
-            Self::Io(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Server I/O error"),
-        };
-
-        (status, message).into_response()
+        (status, self.user_message()).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use taxii_2x::Taxii2Error;
+
+    type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+    fn not_found() -> DatabaseError {
+        DatabaseError::not_found("Account not found")
+    }
+
+    async fn body(response: Response) -> Result<String, Box<dyn std::error::Error>> {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+        Ok(String::from_utf8(bytes.to_vec())?)
+    }
+
+    #[tokio::test]
+    async fn test_database_not_found() -> TestResult {
+        let response = ServerError::from(not_found()).into_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(body(response).await?, "Account not found");
+
+        // The same error wrapped by any layer maps the same way
+        for error in [
+            ServerError::Auth(AuthError::Database(not_found())),
+            ServerError::Taxii1x(Taxii1xError::Database(not_found())),
+            ServerError::Taxii2x(Taxii2Error::Database(not_found())),
+        ] {
+            assert_eq!(error.status_code(), StatusCode::NOT_FOUND, "{error:?}");
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_auth_failure() -> TestResult {
+        let response = ServerError::from(AuthError::TotpRequired).into_response();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(body(response).await?, "TOTP code required");
+
+        for error in [
+            ServerError::Taxii2x(Taxii2Error::Unauthorized),
+            ServerError::Taxii1x(Taxii1xError::status(
+                StatusType::Unauthorized,
+                "Authentication required",
+                None,
+            )),
+        ] {
+            assert_eq!(error.status_code(), StatusCode::UNAUTHORIZED, "{error:?}");
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_validation_error() -> TestResult {
+        let response =
+            ServerError::from(Taxii2Error::Validation("Missing id".to_string())).into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let json: serde_json::Value = serde_json::from_str(&body(response).await?)?;
+        assert_eq!(json["http_status"], 400);
+        assert_eq!(json["description"], "Missing id");
+
+        for error in [
+            ServerError::Taxii1x(Taxii1xError::InvalidRequest("bad".to_string())),
+            ServerError::Taxii1x(Taxii1xError::status(StatusType::BadMessage, "bad", None)),
+            ServerError::Taxii1x(Taxii1xError::failure("Subscription is paused", None)),
+            ServerError::Auth(AuthError::Password("too short".to_string())),
+        ] {
+            assert_eq!(error.status_code(), StatusCode::BAD_REQUEST, "{error:?}");
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_internal_errors_hide_details() -> TestResult {
+        let pool_timeout = ServerError::from(DatabaseError::PoolTimeout);
+        assert_eq!(pool_timeout.status_code(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(
+            ServerError::Taxii2x(Taxii2Error::Database(DatabaseError::PoolTimeout)).status_code(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+
+        let response =
+            ServerError::from(DatabaseError::invalid_data("column secret_hash")).into_response();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(body(response).await?, "Database error occurred");
+
+        let response = ServerError::from(std::io::Error::other("/var/lib/taxii")).into_response();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(body(response).await?, "Server I/O error");
+
+        Ok(())
     }
 }
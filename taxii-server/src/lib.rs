@@ -3,14 +3,20 @@
 pub mod auth_middleware;
 pub mod config;
 pub mod error;
+pub mod logging;
+pub mod rate_limit;
 pub mod router;
 pub mod taxii1x_routes;
+pub mod timeout;
 
 pub use auth_middleware::AuthLayer;
 pub use config::{ConfigError, ServerConfig};
 pub use error::{ServerError, ServerResult};
+pub use logging::LogFormat;
+pub use rate_limit::{RateLimit, RateLimiter, RateLimits};
 pub use router::{RouterWithHooks, create_router, create_router_with_hooks};
 pub use taxii1x_routes::Taxii1xState;
+pub use timeout::RequestTimeouts;
 
 // Re-export signal types for hook subscribers
 pub use taxii_core::{
@@ -0,0 +1,268 @@
+//! Log output and request logging.
+//!
+//! Logs are written as human-readable text by default, or as one JSON
+//! object per line (`log_format = "json"`) for log pipelines. Every request
+//! runs in a `request` span carrying its request ID, method, path, the
+//! collection it addresses and, once authenticated, the account ID; a
+//! `request completed` event with the status and latency ends it.
+
+use std::str::FromStr;
+use std::time::Instant;
+
+use axum::extract::Request;
+use axum::http::HeaderValue;
+use axum::middleware::Next;
+use axum::response::Response;
+use serde::Deserialize;
+use tracing::field::Empty;
+use tracing::{Instrument, info, info_span};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{EnvFilter, Layer, layer::SubscriberExt, util::SubscriberInitExt};
+use uuid::Uuid;
+
+/// Header carrying the request ID, taken from the client when valid and
+/// echoed in the response.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Maximum length of a request ID accepted from a client.
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Format of log output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    /// Human-readable lines.
+    #[default]
+    Text,
+    /// One JSON object per line.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("unknown log format '{s}' (expected text or json)")),
+        }
+    }
+}
+
+/// Install the global subscriber, writing to stdout in `format`.
+///
+/// The level is taken from `RUST_LOG`, `info` by default.
+pub fn init(format: LogFormat) {
+    tracing_subscriber::registry()
+        .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "info,tower_http=debug".into()))
+        .with(fmt_layer(format, std::io::stdout))
+        .init();
+}
+
+/// Formatting layer writing events to `writer`.
+///
+/// JSON events carry their own fields at the top level and the fields of
+/// the current span under `span`.
+pub fn fmt_layer<S, W>(format: LogFormat, writer: W) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    match format {
+        LogFormat::Text => tracing_subscriber::fmt::layer().with_writer(writer).boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer()
+            .json()
+            .flatten_event(true)
+            .with_current_span(true)
+            .with_span_list(false)
+            .with_writer(writer)
+            .boxed(),
+    }
+}
+
+/// Middleware running each request in a `request` span and logging its
+/// completion.
+pub async fn log_requests(req: Request, next: Next) -> Response {
+    let request_id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| valid_request_id(id))
+        .map_or_else(|| Uuid::new_v4().to_string(), String::from);
+
+    let path = req.uri().path();
+    let span = info_span!(
+        "request",
+        request_id = %request_id,
+        method = %req.method(),
+        path = %path,
+        collection = Empty,
+        account_id = Empty,
+    );
+    if let Some(collection) = collection_from_path(path) {
+        span.record("collection", collection);
+    }
+
+    let start = Instant::now();
+    let mut response = next.run(req).instrument(span.clone()).await;
+    let latency_ms = start.elapsed().as_secs_f64() * 1000.0;
+    span.in_scope(|| {
+        info!(
+            status = response.status().as_u16(),
+            latency_ms, "request completed"
+        );
+    });
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+/// Whether a client-supplied request ID is safe to log and echo.
+fn valid_request_id(id: &str) -> bool {
+    !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN && id.bytes().all(|b| b.is_ascii_graphic())
+}
+
+/// Collection ID or alias of a TAXII 2.x collection path.
+fn collection_from_path(path: &str) -> Option<&str> {
+    let mut segments = path.trim_matches('/').split('/');
+    match (segments.next(), segments.next(), segments.next()) {
+        (Some("taxii2"), Some(_), Some("collections")) => {
+            segments.next().filter(|id| !id.is_empty())
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::Router;
+    use axum::body::Body;
+    use axum::middleware::from_fn;
+    use axum::routing::get;
+    use std::io;
+    use std::sync::{Arc, Mutex};
+    use tower::ServiceExt;
+
+    type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+    /// Log output captured in memory.
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0
+                .lock()
+                .map_err(|_| io::Error::other("poisoned"))?
+                .extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'w> MakeWriter<'w> for Captured {
+        type Writer = Self;
+
+        fn make_writer(&'w self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    #[test]
+    fn test_log_format_parse() {
+        assert_eq!("json".parse(), Ok(LogFormat::Json));
+        assert_eq!("Text".parse(), Ok(LogFormat::Text));
+        assert!("xml".parse::<LogFormat>().is_err());
+    }
+
+    #[test]
+    fn test_collection_from_path() {
+        assert_eq!(
+            collection_from_path("/taxii2/root/collections/feed/objects/"),
+            Some("feed")
+        );
+        assert_eq!(
+            collection_from_path("/taxii2/root/collections/feed/"),
+            Some("feed")
+        );
+        assert_eq!(collection_from_path("/taxii2/root/collections/"), None);
+        assert_eq!(collection_from_path("/taxii2/root/status/1/"), None);
+        assert_eq!(collection_from_path("/services/inbox/"), None);
+    }
+
+    /// In JSON mode every line parses, and the completion event of a
+    /// request carries the request fields.
+    #[tokio::test]
+    async fn test_json_request_log() -> TestResult {
+        let output = Captured::default();
+        let subscriber = tracing_subscriber::registry()
+            .with(fmt_layer(LogFormat::Json, output.clone()))
+            .with(tracing_subscriber::filter::LevelFilter::INFO);
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        // Authentication records the account on the request span
+        let app = Router::new()
+            .route(
+                "/taxii2/root/collections/feed/objects/",
+                get(|| async {
+                    tracing::Span::current().record("account_id", 7);
+                    "ok"
+                }),
+            )
+            .layer(from_fn(log_requests));
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/taxii2/root/collections/feed/objects/")
+                    .header(REQUEST_ID_HEADER, "req-42")
+                    .body(Body::empty())?,
+            )
+            .await?;
+        assert_eq!(response.headers()[REQUEST_ID_HEADER], "req-42");
+
+        // Unusable client IDs are replaced
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/taxii2/root/collections/feed/objects/")
+                    .header(REQUEST_ID_HEADER, "two words")
+                    .body(Body::empty())?,
+            )
+            .await?;
+        let generated = response.headers()[REQUEST_ID_HEADER].to_str()?.to_string();
+        assert!(Uuid::parse_str(&generated).is_ok());
+
+        let logged = String::from_utf8(output.0.lock().map_err(|_| "poisoned")?.clone())?;
+        let lines = logged
+            .lines()
+            .map(serde_json::from_str::<serde_json::Value>)
+            .collect::<Result<Vec<_>, _>>()?;
+        let completed: Vec<_> = lines
+            .iter()
+            .filter(|line| line["message"] == "request completed")
+            .collect();
+        assert_eq!(completed.len(), 2);
+
+        let first = completed[0];
+        assert_eq!(first["level"], "INFO");
+        assert_eq!(first["status"], 200);
+        assert!(first["latency_ms"].is_number());
+        assert_eq!(first["span"]["request_id"], "req-42");
+        assert_eq!(first["span"]["method"], "GET");
+        assert_eq!(first["span"]["collection"], "feed");
+        assert_eq!(first["span"]["account_id"], 7);
+        assert_eq!(completed[1]["span"]["request_id"], generated.as_str());
+
+        Ok(())
+    }
+}
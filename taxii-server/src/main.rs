@@ -1,39 +1,44 @@
 //! DARWIS TAXII server binary.
 
 use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
 
+use chrono::Utc;
 use tokio::net::TcpListener;
-use tracing::{error, info};
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use tracing::{debug, error, info, warn};
 
+use taxii_1x::{PushDeliveryWorker, ResultSetWorker, Taxii1Client};
 use taxii_auth::AuthAPI;
-use taxii_db::{DbTaxii1Repository, DbTaxii2Repository, TaxiiPool, migrations};
-use taxii_server::{ServerConfig, create_router};
+use taxii_db::{
+    AccountActivity, DbTaxii1Repository, DbTaxii2Repository, Taxii1Repository, Taxii2Repository,
+    TaxiiPool, migrations, partitioning, retention, stats,
+};
+use taxii_server::{LogFormat, RouterWithHooks, ServerConfig, create_router_with_hooks, logging};
 
 #[tokio::main]
 async fn main() {
-    // Initialize tracing
-    tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "info,tower_http=debug".into()),
-        )
-        .with(tracing_subscriber::fmt::layer())
-        .init();
+    // Load configuration (TOML primary, env overrides) first, as it picks
+    // the log format. Uses lazy initialization - config is loaded once and
+    // cached
+    let config = ServerConfig::init();
+
+    // Initialize tracing (text logs if the configuration is unusable)
+    logging::init(config.as_ref().map_or(LogFormat::Text, |c| c.log_format));
 
     info!("Starting DARWIS TAXII server...");
 
-    if let Err(e) = run().await {
+    let result = match config {
+        Ok(config) => run(config).await,
+        Err(e) => Err(e.into()),
+    };
+    if let Err(e) = result {
         error!("Server error: {}", e);
         std::process::exit(1);
     }
 }
 
-async fn run() -> Result<(), Box<dyn std::error::Error>> {
-    // Load configuration (TOML primary, env overrides)
-    // Uses lazy initialization - config is loaded once and cached
-    let config = ServerConfig::init()?;
-
+async fn run(config: &'static ServerConfig) -> Result<(), Box<dyn std::error::Error>> {
     info!(
         bind = %config.bind_address,
         port = %config.port,
@@ -41,17 +46,102 @@ async fn run() -> Result<(), Box<dyn std::error::Error>> {
     );
 
     // Create database pool
-    let pool = TaxiiPool::connect(&config.db_connection).await?;
-    info!("Database connection established");
+    let pool =
+        TaxiiPool::connect_with_options(&config.db_connection, config.db_pool.clone()).await?;
+    info!(
+        read_pool = pool.has_read_pool(),
+        max_connections = config.db_pool.max_connections,
+        "Database connection established"
+    );
 
     // Run migrations (idempotent - skips already applied)
     info!("Running database migrations...");
     migrations::run(pool.inner()).await?;
     info!("Database migrations completed");
 
+    if config.partition_objects {
+        if !partitioning::is_partitioned(&pool).await? {
+            return Err(
+                "database.partition_objects is enabled but the STIX object table \
+                        is not partitioned; run `taxii-cli migrate partition` first"
+                    .into(),
+            );
+        }
+        info!("STIX object table is partitioned by collection");
+    }
+
     // Create repository instances
-    let taxii1_persistence = DbTaxii1Repository::new(pool.clone());
-    let taxii2_persistence = DbTaxii2Repository::new(pool.clone());
+    let taxii1_persistence = DbTaxii1Repository::new(pool.clone())
+        .with_dedup_window(chrono::Duration::hours(config.dedup_window_hours));
+    let taxii2_persistence = DbTaxii2Repository::new(pool.clone())
+        .with_hard_delete(config.hard_delete)
+        .with_partitioning(config.partition_objects);
+
+    // Start background preparation of asynchronous poll result sets
+    if config.result_set_worker_interval_secs > 0 {
+        let worker = ResultSetWorker::new(Arc::new(DbTaxii1Repository::new(pool.clone())));
+        tokio::spawn(worker.run(Duration::from_secs(config.result_set_worker_interval_secs)));
+        info!(
+            interval_secs = config.result_set_worker_interval_secs,
+            "Result set worker started"
+        );
+    }
+
+    // Start periodic purge of old account activity
+    if config.activity_retention_days > 0 {
+        tokio::spawn(purge_activity(pool.clone(), config.activity_retention_days));
+        info!(
+            retention_days = config.activity_retention_days,
+            "Account activity retention enabled"
+        );
+    }
+
+    // Start periodic purge of old tombstones of deleted objects
+    if config.deleted_retention_days > 0 {
+        tokio::spawn(purge_deleted_objects(
+            DbTaxii2Repository::new(pool.clone()),
+            config.deleted_retention_days,
+        ));
+        info!(
+            retention_days = config.deleted_retention_days,
+            "Deleted object retention enabled"
+        );
+    }
+
+    // Start periodic cleanup of job logs
+    if config.job_retention_days > 0 {
+        tokio::spawn(cleanup_jobs(
+            DbTaxii2Repository::new(pool.clone())
+                .with_stale_job_age(chrono::Duration::days(config.stale_job_days)),
+            config.job_retention_days,
+        ));
+        info!(
+            retention_days = config.job_retention_days,
+            "Job log retention enabled"
+        );
+    }
+
+    // Start periodic purge of content expired by collection retention policies
+    tokio::spawn(purge_expired_content(
+        DbTaxii1Repository::new(pool.clone()),
+        DbTaxii2Repository::new(pool.clone()).with_hard_delete(config.hard_delete),
+    ));
+
+    // Start periodic refresh of cached statistics of large collections
+    tokio::spawn(refresh_collection_stats(
+        DbTaxii1Repository::new(pool.clone()),
+        DbTaxii2Repository::new(pool.clone()),
+    ));
+
+    let push_persistence = Arc::new(DbTaxii1Repository::new(pool.clone()));
+    #[cfg(feature = "bridge")]
+    let bridge_worker = taxii_1x::BridgeWorker::new(
+        DbTaxii1Repository::new(pool.clone()),
+        DbTaxii2Repository::new(pool.clone())
+            .with_hard_delete(config.hard_delete)
+            .with_partitioning(config.partition_objects),
+    )
+    .with_collision_policy(config.collision_policy);
 
     // Create auth API
     let auth = AuthAPI::new(
@@ -61,19 +151,170 @@ async fn run() -> Result<(), Box<dyn std::error::Error>> {
     )?;
     info!("Auth API initialized");
 
+    bootstrap_admin(&auth, config).await?;
+
     // Create listener address before moving config
     let addr: SocketAddr = format!("{}:{}", config.bind_address, config.port).parse()?;
 
     // Create router
-    let app = create_router(taxii1_persistence, taxii2_persistence, auth, config);
+    let RouterWithHooks { router: app, hooks } =
+        create_router_with_hooks(taxii1_persistence, taxii2_persistence, auth, config);
     info!("Router created");
 
+    // Start push delivery of new content to TAXII 1.x subscribers
+    if config.push.enabled && config.push_worker_interval_secs > 0 {
+        let mut push_client = Taxii1Client::builder();
+        if !config.push.allow_private_addresses {
+            push_client = push_client.public_addresses_only();
+        }
+        if let Some(path) = &config.push_client_certificate {
+            let pem = std::fs::read(path)
+                .map_err(|e| format!("Failed to read push client certificate {path}: {e}"))?;
+            push_client = push_client.client_certificate(pem);
+        }
+        if let Some(path) = &config.push_ca_certificate {
+            let pem = std::fs::read(path)
+                .map_err(|e| format!("Failed to read push CA certificate {path}: {e}"))?;
+            push_client = push_client.root_certificate(pem);
+        }
+        let push_client = push_client.build()?;
+        tokio::spawn(PushDeliveryWorker::new(push_persistence.clone()).listen(hooks.subscribe()));
+        tokio::spawn(
+            PushDeliveryWorker::new(push_persistence)
+                .with_client(push_client)
+                .with_push_policy(config.push)
+                .with_limits(config.taxii1_limits)
+                .run(Duration::from_secs(config.push_worker_interval_secs)),
+        );
+        info!(
+            interval_secs = config.push_worker_interval_secs,
+            "Push delivery worker started"
+        );
+    }
+
+    // Start the bridge of STIX 1.x content into linked TAXII 2.x collections
+    #[cfg(feature = "bridge")]
+    tokio::spawn(bridge_worker.listen(hooks.subscribe()));
+
     // Bind listener
     let listener = TcpListener::bind(addr).await?;
     info!(address = %addr, "Server listening");
 
-    // Run server
-    axum::serve(listener, app).await?;
+    // Run server (with peer addresses for rate limiting unproxied clients)
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Interval between purges of old account activity, tombstones, job logs,
+/// expired result sets and content expired by retention policies, and
+/// refreshes of collection statistics.
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Periodically delete account activity older than the retention horizon.
+async fn purge_activity(pool: TaxiiPool, retention_days: i32) {
+    let mut ticker = tokio::time::interval(PURGE_INTERVAL);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        ticker.tick().await;
+        match AccountActivity::cleanup_old_records(&pool, retention_days).await {
+            Ok(0) => {}
+            Ok(count) => debug!(count, "Purged old account activity"),
+            Err(e) => warn!(error = %e, "Failed to purge old account activity"),
+        }
+    }
+}
+
+/// Periodically remove tombstones of objects deleted before the retention
+/// horizon.
+async fn purge_deleted_objects(persistence: DbTaxii2Repository, retention_days: i64) {
+    let mut ticker = tokio::time::interval(PURGE_INTERVAL);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        ticker.tick().await;
+        let older_than = Utc::now() - chrono::Duration::days(retention_days);
+        match persistence.purge_deleted(older_than).await {
+            Ok(0) => {}
+            Ok(count) => debug!(count, "Purged deleted objects"),
+            Err(e) => warn!(error = %e, "Failed to purge deleted objects"),
+        }
+    }
+}
+
+/// Periodically remove job logs older than the retention horizon.
+///
+/// The repository logs what was removed.
+async fn cleanup_jobs(persistence: DbTaxii2Repository, retention_days: i64) {
+    let mut ticker = tokio::time::interval(PURGE_INTERVAL);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        ticker.tick().await;
+        let older_than = Utc::now() - chrono::Duration::days(retention_days);
+        if let Err(e) = persistence.cleanup_jobs(older_than, true).await {
+            warn!(error = %e, "Failed to clean up job logs");
+        }
+    }
+}
+
+/// Periodically purge expired TAXII 1.x result sets and content expired by
+/// collection retention policies.
+async fn purge_expired_content(taxii1: DbTaxii1Repository, taxii2: DbTaxii2Repository) {
+    let mut ticker = tokio::time::interval(PURGE_INTERVAL);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        ticker.tick().await;
+        // Result sets go first, releasing the content blocks they retained
+        match taxii1.purge_expired_result_sets(Utc::now()).await {
+            Ok(0) => {}
+            Ok(purged) => info!(purged, "Purged expired result sets"),
+            Err(e) => warn!(error = %e, "Failed to purge expired result sets"),
+        }
+        if let Err(e) = retention::purge_expired_collections(&taxii1, &taxii2, Utc::now()).await {
+            warn!(error = %e, "Failed to purge expired content");
+        }
+    }
+}
+
+/// Periodically refresh cached statistics of large collections.
+async fn refresh_collection_stats(taxii1: DbTaxii1Repository, taxii2: DbTaxii2Repository) {
+    let mut ticker = tokio::time::interval(PURGE_INTERVAL);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        ticker.tick().await;
+        if let Err(e) =
+            stats::refresh_collection_stats(&taxii1, &taxii2, stats::DEFAULT_CACHE_THRESHOLD).await
+        {
+            warn!(error = %e, "Failed to refresh collection statistics");
+        }
+    }
+}
+
+/// Create the first admin account if bootstrap is configured and no
+/// accounts exist yet.
+async fn bootstrap_admin(
+    auth: &AuthAPI,
+    config: &ServerConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(password) = config.bootstrap_admin_password()? else {
+        return Ok(());
+    };
+
+    match auth
+        .bootstrap_admin(&config.bootstrap_admin_username, &password)
+        .await?
+    {
+        Some(account) => info!(username = %account.username, "Bootstrap admin account created"),
+        None => debug!("Accounts already exist, skipping admin bootstrap"),
+    }
 
     Ok(())
 }
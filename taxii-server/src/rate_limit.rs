@@ -0,0 +1,680 @@
+//! Per-client rate limiting for TAXII 2.x object endpoints and logins.
+//!
+//! Every client has a token bucket per request class (reads and writes are
+//! limited separately). Clients are identified by their authenticated
+//! account, falling back to their IP address. Login attempts are limited
+//! both per IP address and per username. Requests arriving on an empty
+//! bucket receive `429 Too Many Requests` with a `Retry-After` header.
+//!
+//! The IP address is the peer address of the connection. `X-Forwarded-For`
+//! and `X-Real-IP` are only honored on connections from trusted proxies, as
+//! any other client could set them to dodge its limit.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex, PoisonError, Weak};
+use std::time::{Duration, Instant};
+
+use axum::body::{Body, to_bytes};
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::{HeaderMap, HeaderValue, Method, StatusCode, header::RETRY_AFTER};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use serde::Deserialize;
+use tracing::debug;
+
+use taxii_2x::Taxii2Error;
+use taxii_core::Account;
+
+/// Interval between sweeps dropping idle buckets.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Largest login request body read to find the username.
+const MAX_LOGIN_BODY: usize = 64 * 1024;
+
+/// Token bucket parameters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    /// Sustained requests per second.
+    pub per_sec: f64,
+    /// Requests allowed in a burst.
+    pub burst: u32,
+}
+
+impl RateLimit {
+    /// Create a limit, where a rate of 0 disables limiting and a burst of 0
+    /// allows one second worth of requests.
+    pub fn new(per_sec: u32, burst: u32) -> Option<Self> {
+        let burst = if burst == 0 { per_sec } else { burst };
+        (per_sec > 0).then_some(Self {
+            per_sec: f64::from(per_sec),
+            burst,
+        })
+    }
+
+    /// Create a limit in requests per minute, where a rate of 0 disables
+    /// limiting and a burst of 0 allows one minute worth of requests.
+    pub fn per_minute(per_minute: u32, burst: u32) -> Option<Self> {
+        let burst = if burst == 0 { per_minute } else { burst };
+        (per_minute > 0).then_some(Self {
+            per_sec: f64::from(per_minute) / 60.0,
+            burst,
+        })
+    }
+}
+
+/// Limits per request class. `None` disables limiting of a class.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RateLimits {
+    /// GET, HEAD and OPTIONS requests.
+    pub read: Option<RateLimit>,
+    /// All other requests (POST, DELETE).
+    pub write: Option<RateLimit>,
+    /// Login attempts, limited by [`login_rate_limit`].
+    pub login: Option<RateLimit>,
+}
+
+impl RateLimits {
+    /// Whether any request class is limited.
+    pub fn is_enabled(&self) -> bool {
+        self.read.is_some() || self.write.is_some() || self.login.is_some()
+    }
+
+    fn get(&self, class: RequestClass) -> Option<RateLimit> {
+        match class {
+            RequestClass::Read => self.read,
+            RequestClass::Write => self.write,
+            RequestClass::Login => self.login,
+        }
+    }
+}
+
+/// Class of a request, limited separately.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum RequestClass {
+    Read,
+    Write,
+    Login,
+}
+
+impl RequestClass {
+    fn of(method: &Method) -> Self {
+        if method == Method::GET || method == Method::HEAD || method == Method::OPTIONS {
+            Self::Read
+        } else {
+            Self::Write
+        }
+    }
+}
+
+/// Identity a bucket belongs to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum ClientKey {
+    Account(i32),
+    Ip(IpAddr),
+    /// Username a login attempt is made for.
+    Username(String),
+    /// Unauthenticated client whose address is unknown.
+    Unknown,
+}
+
+/// Token bucket state.
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    /// Tokens available at `now`.
+    fn available(&self, limit: RateLimit, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        (self.tokens + elapsed * limit.per_sec).min(f64::from(limit.burst))
+    }
+}
+
+/// Token buckets of all clients.
+#[derive(Debug)]
+pub struct RateLimiter {
+    limits: RateLimits,
+    trusted_proxies: Vec<IpAddr>,
+    buckets: Mutex<HashMap<(ClientKey, RequestClass), Bucket>>,
+}
+
+impl RateLimiter {
+    /// Create a rate limiter.
+    pub fn new(limits: RateLimits) -> Self {
+        Self {
+            limits,
+            trusted_proxies: Vec::new(),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Set the reverse proxies whose forwarding headers identify clients.
+    #[must_use]
+    pub fn with_trusted_proxies(mut self, trusted_proxies: Vec<IpAddr>) -> Self {
+        self.trusted_proxies = trusted_proxies;
+        self
+    }
+
+    /// Drop idle buckets every [`PRUNE_INTERVAL`] until the limiter is
+    /// dropped.
+    ///
+    /// Does nothing outside a Tokio runtime.
+    pub fn spawn_pruning(self: &Arc<Self>) {
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let limiter: Weak<Self> = Arc::downgrade(self);
+        runtime.spawn(async move {
+            let mut ticker = tokio::time::interval(PRUNE_INTERVAL);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                let Some(limiter) = limiter.upgrade() else {
+                    return;
+                };
+                limiter.prune(Instant::now());
+            }
+        });
+    }
+
+    /// Drop the buckets that are full at `now`.
+    ///
+    /// A full bucket behaves exactly like a new one.
+    fn prune(&self, now: Instant) {
+        let limits = self.limits;
+        let mut buckets = self.buckets.lock().unwrap_or_else(PoisonError::into_inner);
+        buckets.retain(|(_, class), bucket| {
+            limits
+                .get(*class)
+                .is_some_and(|limit| bucket.available(limit, now) < f64::from(limit.burst))
+        });
+    }
+
+    /// Take a token from a client's bucket.
+    ///
+    /// Returns the time until a token is available if the bucket is empty.
+    fn acquire(&self, key: &ClientKey, class: RequestClass, now: Instant) -> Result<(), Duration> {
+        let Some(limit) = self.limits.get(class) else {
+            return Ok(());
+        };
+
+        let mut buckets = self.buckets.lock().unwrap_or_else(PoisonError::into_inner);
+        let bucket = buckets.entry((key.clone(), class)).or_insert(Bucket {
+            tokens: f64::from(limit.burst),
+            updated: now,
+        });
+        bucket.tokens = bucket.available(limit, now);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            let missing = 1.0 - bucket.tokens;
+            Err(Duration::from_secs_f64(missing / limit.per_sec))
+        }
+    }
+
+    /// Identify the client of a request.
+    fn client_key(&self, req: &Request) -> ClientKey {
+        match req.extensions().get::<Account>() {
+            Some(account) => ClientKey::Account(account.id),
+            None => self.address_key(req),
+        }
+    }
+
+    /// Identify the client of a request by its IP address.
+    fn address_key(&self, req: &Request) -> ClientKey {
+        let Some(peer) = req
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip())
+        else {
+            return ClientKey::Unknown;
+        };
+        if !self.trusted_proxies.contains(&peer) {
+            return ClientKey::Ip(peer);
+        }
+        ClientKey::Ip(self.forwarded_client(req.headers()).unwrap_or(peer))
+    }
+
+    /// Client address forwarded by a trusted proxy.
+    ///
+    /// Proxies append the address they received a request from to
+    /// `X-Forwarded-For`, so the client is its last untrusted address;
+    /// the addresses before it may be made up by the client.
+    fn forwarded_client(&self, headers: &HeaderMap) -> Option<IpAddr> {
+        let forwarded: Vec<IpAddr> = headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(|ip| ip.trim().parse().ok())
+            .collect();
+        if let Some(client) = forwarded
+            .iter()
+            .rev()
+            .find(|ip| !self.trusted_proxies.contains(ip))
+        {
+            return Some(*client);
+        }
+
+        headers
+            .get("x-real-ip")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|ip| ip.trim().parse().ok())
+    }
+}
+
+/// Middleware rejecting requests of clients that exceed their rate limit.
+///
+/// Must run inside the auth layer so requests are keyed by account.
+pub async fn rate_limit(
+    State(limiter): State<Arc<RateLimiter>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let key = limiter.client_key(&req);
+    let class = RequestClass::of(req.method());
+
+    match limiter.acquire(&key, class, Instant::now()) {
+        Ok(()) => next.run(req).await,
+        Err(wait) => {
+            debug!(client = ?key, class = ?class, "Rate limit exceeded");
+            with_retry_after(Taxii2Error::TooManyRequests.into_response(), wait)
+        }
+    }
+}
+
+/// Username of a login request.
+#[derive(Deserialize)]
+struct LoginRequest {
+    username: String,
+}
+
+/// Middleware limiting login attempts per IP address and per username.
+///
+/// An attempt takes a token from both buckets, so neither guessing many
+/// passwords from one address nor spreading guesses for one account over
+/// many addresses escapes the limit. The client address is used even when
+/// the request carries a token.
+pub async fn login_rate_limit(
+    State(limiter): State<Arc<RateLimiter>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let address = limiter.address_key(&req);
+    let (parts, body) = req.into_parts();
+    let Ok(body) = to_bytes(body, MAX_LOGIN_BODY).await else {
+        return StatusCode::PAYLOAD_TOO_LARGE.into_response();
+    };
+    let username = serde_json::from_slice::<LoginRequest>(&body)
+        .ok()
+        .map(|login| ClientKey::Username(login.username));
+
+    let now = Instant::now();
+    let allowed = limiter
+        .acquire(&address, RequestClass::Login, now)
+        .and_then(|()| match &username {
+            Some(username) => limiter.acquire(username, RequestClass::Login, now),
+            None => Ok(()),
+        });
+    match allowed {
+        Ok(()) => next.run(Request::from_parts(parts, Body::from(body))).await,
+        Err(wait) => {
+            debug!(client = ?address, "Login rate limit exceeded");
+            with_retry_after(StatusCode::TOO_MANY_REQUESTS.into_response(), wait)
+        }
+    }
+}
+
+/// Tell a rate limited client when to retry.
+fn with_retry_after(mut response: Response, wait: Duration) -> Response {
+    // Retry-After is in whole seconds, rounded up
+    let secs = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+    response
+        .headers_mut()
+        .insert(RETRY_AFTER, HeaderValue::from(secs.max(1)));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::Router;
+    use axum::middleware::from_fn_with_state;
+    use axum::routing::{get, post};
+    use tower::ServiceExt;
+
+    type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+    async fn handler() -> &'static str {
+        "done"
+    }
+
+    fn app(limits: RateLimits) -> Router {
+        app_with(RateLimiter::new(limits))
+    }
+
+    fn app_with(limiter: RateLimiter) -> Router {
+        Router::new()
+            .route("/objects/", get(handler).post(handler))
+            .layer(from_fn_with_state(Arc::new(limiter), rate_limit))
+    }
+
+    /// Build a request from the peer address `ip`.
+    fn request(method: Method, ip: &str) -> Result<Request, Box<dyn std::error::Error>> {
+        let mut req = Request::builder()
+            .method(method)
+            .uri("/objects/")
+            .body(Body::empty())?;
+        req.extensions_mut()
+            .insert(ConnectInfo(SocketAddr::new(ip.parse()?, 443)));
+        Ok(req)
+    }
+
+    fn account(id: i32) -> Account {
+        Account {
+            id,
+            username: format!("user{id}"),
+            is_admin: false,
+            permissions: HashMap::new(),
+            details: HashMap::new(),
+            scope: None,
+            identity_id: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_exceeding_limit_returns_429() -> TestResult {
+        let app = app(RateLimits {
+            read: RateLimit::new(1, 3),
+            write: None,
+            login: None,
+        });
+
+        for _ in 0..3 {
+            let response = app
+                .clone()
+                .oneshot(request(Method::GET, "10.0.0.1")?)
+                .await?;
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        let response = app.oneshot(request(Method::GET, "10.0.0.1")?).await?;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(
+            response.headers().get(RETRY_AFTER),
+            Some(&HeaderValue::from(1u64))
+        );
+        let body: serde_json::Value =
+            serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await?)?;
+        assert_eq!(body["title"], "Too Many Requests");
+        assert_eq!(body["http_status"], 429);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_clients_and_classes_are_limited_separately() -> TestResult {
+        let app = app(RateLimits {
+            read: RateLimit::new(1, 1),
+            write: RateLimit::new(1, 1),
+            login: None,
+        });
+
+        let status = |response: Response| response.status();
+        let send = |req| app.clone().oneshot(req);
+
+        assert_eq!(
+            status(send(request(Method::GET, "10.0.0.1")?).await?),
+            StatusCode::OK
+        );
+        assert_eq!(
+            status(send(request(Method::GET, "10.0.0.1")?).await?),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+        // Writes have their own bucket
+        assert_eq!(
+            status(send(request(Method::POST, "10.0.0.1")?).await?),
+            StatusCode::OK
+        );
+        // Other addresses have their own bucket
+        assert_eq!(
+            status(send(request(Method::GET, "10.0.0.2")?).await?),
+            StatusCode::OK
+        );
+
+        // Authenticated requests are keyed by account, not address
+        let mut req = request(Method::GET, "10.0.0.1")?;
+        req.extensions_mut().insert(account(1));
+        assert_eq!(status(send(req).await?), StatusCode::OK);
+        let mut req = request(Method::GET, "10.0.0.3")?;
+        req.extensions_mut().insert(account(1));
+        assert_eq!(status(send(req).await?), StatusCode::TOO_MANY_REQUESTS);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unlimited_class_is_not_limited() -> TestResult {
+        let app = app(RateLimits {
+            read: None,
+            write: RateLimit::new(1, 1),
+            login: None,
+        });
+
+        for _ in 0..10 {
+            let response = app
+                .clone()
+                .oneshot(request(Method::GET, "10.0.0.1")?)
+                .await?;
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_forwarded_addresses_need_trusted_proxy() -> TestResult {
+        let limits = RateLimits {
+            read: RateLimit::new(1, 1),
+            write: None,
+            login: None,
+        };
+        let forwarded = |peer: &str, xff: &str| {
+            let mut req = request(Method::GET, peer)?;
+            req.headers_mut().insert("x-forwarded-for", xff.parse()?);
+            Ok::<_, Box<dyn std::error::Error>>(req)
+        };
+
+        // Untrusted peers are limited by their own address
+        let app = app(limits);
+        for (xff, expected) in [
+            ("10.0.0.1", StatusCode::OK),
+            ("10.0.0.2", StatusCode::TOO_MANY_REQUESTS),
+        ] {
+            let response = app.clone().oneshot(forwarded("192.0.2.1", xff)?).await?;
+            assert_eq!(response.status(), expected, "{xff}");
+        }
+
+        // Trusted proxies forward the last untrusted address, whatever the
+        // client put before it
+        let app = app_with(
+            RateLimiter::new(limits)
+                .with_trusted_proxies(vec!["192.0.2.1".parse()?, "192.0.2.2".parse()?]),
+        );
+        for (xff, expected) in [
+            ("10.0.0.1", StatusCode::OK),
+            ("10.0.0.2", StatusCode::OK),
+            (
+                "10.0.0.9, 10.0.0.1, 192.0.2.2",
+                StatusCode::TOO_MANY_REQUESTS,
+            ),
+        ] {
+            let response = app.clone().oneshot(forwarded("192.0.2.1", xff)?).await?;
+            assert_eq!(response.status(), expected, "{xff}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_prune_drops_full_buckets() {
+        let limiter = RateLimiter::new(RateLimits {
+            read: RateLimit::new(1, 2),
+            write: None,
+            login: None,
+        });
+        let start = Instant::now();
+        let lookup = |ip: [u8; 4]| ClientKey::Ip(IpAddr::from(ip));
+        assert!(
+            limiter
+                .acquire(&lookup([10, 0, 0, 1]), RequestClass::Read, start)
+                .is_ok()
+        );
+        limiter.prune(start);
+        assert_eq!(limiter.buckets.lock().map(|b| b.len()).unwrap_or(0), 1);
+
+        // One second refills the bucket
+        limiter.prune(start + Duration::from_secs(1));
+        assert_eq!(limiter.buckets.lock().map(|b| b.len()).unwrap_or(1), 0);
+    }
+
+    #[test]
+    fn test_bucket_refills_at_rate() {
+        let limiter = RateLimiter::new(RateLimits {
+            read: RateLimit::new(2, 2),
+            write: None,
+            login: None,
+        });
+        let key = ClientKey::Unknown;
+        let start = Instant::now();
+
+        assert!(limiter.acquire(&key, RequestClass::Read, start).is_ok());
+        assert!(limiter.acquire(&key, RequestClass::Read, start).is_ok());
+        assert_eq!(
+            limiter.acquire(&key, RequestClass::Read, start),
+            Err(Duration::from_millis(500))
+        );
+
+        // Half a second refills one token
+        let later = start + Duration::from_millis(500);
+        assert!(limiter.acquire(&key, RequestClass::Read, later).is_ok());
+        assert!(limiter.acquire(&key, RequestClass::Read, later).is_err());
+
+        // Idle time never fills the bucket past the burst
+        let much_later = start + Duration::from_secs(60);
+        assert!(
+            limiter
+                .acquire(&key, RequestClass::Read, much_later)
+                .is_ok()
+        );
+        assert!(
+            limiter
+                .acquire(&key, RequestClass::Read, much_later)
+                .is_ok()
+        );
+        assert!(
+            limiter
+                .acquire(&key, RequestClass::Read, much_later)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_zero_rate_disables_limit() {
+        assert_eq!(RateLimit::new(0, 10), None);
+        assert_eq!(
+            RateLimit::new(5, 0),
+            Some(RateLimit {
+                per_sec: 5.0,
+                burst: 5
+            })
+        );
+        assert_eq!(
+            RateLimit::per_minute(6, 0),
+            Some(RateLimit {
+                per_sec: 0.1,
+                burst: 6
+            })
+        );
+        assert_eq!(RateLimit::per_minute(0, 6), None);
+        assert!(!RateLimits::default().is_enabled());
+    }
+
+    fn login_app(limit: RateLimit) -> Router {
+        let limiter = RateLimiter::new(RateLimits {
+            login: Some(limit),
+            ..RateLimits::default()
+        });
+        Router::new()
+            .route("/auth", post(|body: String| async move { body }))
+            .layer(from_fn_with_state(Arc::new(limiter), login_rate_limit))
+    }
+
+    /// Build a login request for `username` from the peer address `ip`.
+    fn login(username: &str, ip: &str) -> Result<Request, Box<dyn std::error::Error>> {
+        let body = serde_json::json!({"username": username, "password": "guess"});
+        let mut req = Request::builder()
+            .method(Method::POST)
+            .uri("/auth")
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))?;
+        req.extensions_mut()
+            .insert(ConnectInfo(SocketAddr::new(ip.parse()?, 443)));
+        Ok(req)
+    }
+
+    #[tokio::test]
+    async fn test_login_limited_per_address() -> TestResult {
+        let app = login_app(RateLimit::new(1, 2).ok_or("no limit")?);
+        let send = |username: &str, ip: &str| {
+            let req = login(username, ip);
+            let app = app.clone();
+            async move { Ok::<_, Box<dyn std::error::Error>>(app.oneshot(req?).await?) }
+        };
+
+        // The handler still sees the body
+        let response = send("alice", "10.0.0.1").await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await?;
+        assert!(String::from_utf8(body.to_vec())?.contains("alice"));
+
+        // Other usernames from the same address share its bucket
+        assert_eq!(send("bob", "10.0.0.1").await?.status(), StatusCode::OK);
+        let response = send("carol", "10.0.0.1").await?;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(
+            response.headers().get(RETRY_AFTER),
+            Some(&HeaderValue::from(1u64))
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_login_limited_per_username() -> TestResult {
+        let app = login_app(RateLimit::per_minute(2, 2).ok_or("no limit")?);
+        let send = |username: &str, ip: &str| {
+            let req = login(username, ip);
+            let app = app.clone();
+            async move { Ok::<_, Box<dyn std::error::Error>>(app.oneshot(req?).await?.status()) }
+        };
+
+        // Guesses for one account from many addresses share its bucket
+        assert_eq!(send("alice", "10.0.0.1").await?, StatusCode::OK);
+        assert_eq!(send("alice", "10.0.0.2").await?, StatusCode::OK);
+        assert_eq!(
+            send("alice", "10.0.0.3").await?,
+            StatusCode::TOO_MANY_REQUESTS
+        );
+        // Other accounts are not affected
+        assert_eq!(send("bob", "10.0.0.4").await?, StatusCode::OK);
+
+        Ok(())
+    }
+}
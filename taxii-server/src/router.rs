@@ -1,26 +1,36 @@
 //! Router setup.
 
+use std::collections::BTreeMap;
 use std::net::IpAddr;
 use std::sync::Arc;
+use std::time::Duration;
 
-use axum::extract::State;
+use axum::extract::{Query, Request, State};
 use axum::http::{StatusCode, header::USER_AGENT};
-use axum::response::IntoResponse;
+use axum::middleware::{Next, from_fn, from_fn_with_state};
+use axum::response::{IntoResponse, Response};
 use axum::routing::{get, post};
-use axum::{Json, Router};
+use axum::{Extension, Json, Router};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use tower_http::catch_panic::CatchPanicLayer;
 use tracing::error;
 
 use taxii_1x::HandlerRegistry;
-use taxii_2x::{Taxii2Config, Taxii2State};
+use taxii_2x::{PatternLimits, Taxii2Config, Taxii2State};
 use taxii_auth::{AuthAPI, ClientInfo};
-use taxii_core::{HookRegistry, SharedHookRegistry};
-use taxii_db::{DbTaxii1Repository, DbTaxii2Repository};
+use taxii_core::{Account, HookRegistry, SharedHookRegistry, TokenScope};
+use taxii_db::{
+    ActivityCursor, ActivityFilter, DbTaxii1Repository, DbTaxii2Repository, EventType, IpPrefix,
+    TaxiiPool,
+};
 
 use crate::AuthLayer;
 use crate::config::ServerConfig;
+use crate::error::ServerError;
+use crate::rate_limit::{RateLimit, RateLimiter, RateLimits, login_rate_limit, rate_limit};
 use crate::taxii1x_routes::{Taxii1xState, taxii1x_options_handler, taxii1x_service_handler};
+use crate::timeout::{RequestTimeouts, taxii1x_timeout, taxii2_timeout};
 
 /// Health check response.
 #[derive(Serialize)]
@@ -33,11 +43,67 @@ async fn health_handler() -> Json<HealthResponse> {
     Json(HealthResponse { alive: true })
 }
 
+/// Readiness check response.
+#[derive(Serialize)]
+struct ReadyResponse {
+    ready: bool,
+    pool: PoolStatsResponse,
+}
+
+/// Connection pool statistics of a readiness check.
+#[derive(Serialize)]
+struct PoolStatsResponse {
+    size: u32,
+    idle: usize,
+    max_connections: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    acquire_wait_p99_ms: Option<u128>,
+    saturated: bool,
+}
+
+/// Readiness check handler.
+///
+/// Unlike the health check, the server is only ready if it can get a
+/// database connection within the pool's acquire timeout.
+async fn ready_handler(State(state): State<Arc<ManagementState>>) -> Response {
+    let ready = match state.pool.acquire().await {
+        Ok(_) => true,
+        Err(e) => {
+            error!(error = %e, "Readiness check failed to get a database connection");
+            false
+        }
+    };
+
+    let stats = state.pool.stats();
+    let body = Json(ReadyResponse {
+        ready,
+        pool: PoolStatsResponse {
+            size: stats.size,
+            idle: stats.idle,
+            max_connections: stats.max_connections,
+            acquire_wait_p99_ms: stats.acquire_wait_p99.map(|d| d.as_millis()),
+            saturated: stats.is_saturated(),
+        },
+    });
+
+    if ready {
+        body.into_response()
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, body).into_response()
+    }
+}
+
 /// Auth request body.
 #[derive(Deserialize)]
 struct AuthRequest {
     username: String,
     password: String,
+    /// TOTP or recovery code, required for accounts with two-factor authentication.
+    #[serde(default)]
+    totp_code: Option<String>,
+    /// Restrict the issued token to a set of collections and/or read access.
+    #[serde(default)]
+    scope: Option<TokenScope>,
 }
 
 /// Auth response.
@@ -46,9 +112,79 @@ struct AuthResponse {
     token: String,
 }
 
+/// Password reset request body (admin only).
+#[derive(Deserialize)]
+struct PasswordResetRequest {
+    username: String,
+}
+
+/// Password reset response.
+#[derive(Serialize)]
+struct PasswordResetResponse {
+    token: String,
+}
+
+/// Password reset redemption request body.
+#[derive(Deserialize)]
+struct PasswordResetRedeemRequest {
+    token: String,
+    new_password: String,
+}
+
+/// Activity query parameters (admin only).
+#[derive(Deserialize)]
+struct ActivityQuery {
+    username: Option<String>,
+    /// Comma-separated event types.
+    event: Option<String>,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    ip: Option<IpAddr>,
+    /// Client IP address prefix in CIDR notation.
+    ip_prefix: Option<String>,
+    limit: Option<i64>,
+    next: Option<String>,
+}
+
+/// Activity counts query parameters (admin only).
+#[derive(Deserialize)]
+struct ActivityCountsQuery {
+    since: DateTime<Utc>,
+}
+
+/// Activity counts response.
+#[derive(Serialize)]
+struct ActivityCountsResponse {
+    counts: BTreeMap<String, i64>,
+}
+
+/// Activity record in responses.
+#[derive(Serialize)]
+struct ActivityEvent {
+    id: i64,
+    username: String,
+    event_type: String,
+    ip_address: Option<String>,
+    user_agent: Option<String>,
+    created_at: DateTime<Utc>,
+}
+
+/// Activity response.
+#[derive(Serialize)]
+struct ActivityResponse {
+    events: Vec<ActivityEvent>,
+    more: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next: Option<String>,
+}
+
+/// Default and maximum page size of activity queries.
+const ACTIVITY_PAGE_LIMIT: i64 = 100;
+
 /// State for management routes that need auth.
 struct ManagementState {
     auth: Arc<AuthAPI>,
+    pool: TaxiiPool,
 }
 
 /// Extract client IP from headers or connection.
@@ -101,17 +237,208 @@ async fn auth_handler(
 
     let client_info = ClientInfo::new(extract_client_ip(&headers), extract_user_agent(&headers));
 
+    let result = match req.scope {
+        Some(scope) => {
+            state
+                .auth
+                .authenticate_with_scope(
+                    &req.username,
+                    &req.password,
+                    req.totp_code.as_deref(),
+                    scope,
+                    client_info,
+                )
+                .await
+        }
+        None => {
+            state
+                .auth
+                .authenticate_with_totp(
+                    &req.username,
+                    &req.password,
+                    req.totp_code.as_deref(),
+                    client_info,
+                )
+                .await
+        }
+    };
+
+    match result {
+        Ok(Some(token)) => Json(AuthResponse { token }).into_response(),
+        Ok(None) => StatusCode::UNAUTHORIZED.into_response(),
+        Err(e) => ServerError::from(e).into_response(),
+    }
+}
+
+/// Check that a request is made by an administrator with an unscoped token.
+///
+/// Down-scoped tokens only grant collection access, so they are refused on
+/// management endpoints even when issued to an admin.
+fn require_admin(account: Option<Extension<Account>>) -> Result<(), StatusCode> {
+    let Some(Extension(account)) = account else {
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+    if !account.is_admin || account.scope.is_some() {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    Ok(())
+}
+
+/// Password reset handler - issue a reset token for an account (admin only).
+///
+/// The token is returned to the admin, who hands it to the user out of band.
+async fn password_reset_handler(
+    State(state): State<Arc<ManagementState>>,
+    account: Option<Extension<Account>>,
+    Json(req): Json<PasswordResetRequest>,
+) -> impl IntoResponse {
+    if let Err(status) = require_admin(account) {
+        return status.into_response();
+    }
+
+    match state.auth.create_password_reset(&req.username).await {
+        Ok(token) => Json(PasswordResetResponse { token }).into_response(),
+        Err(e) => ServerError::from(e).into_response(),
+    }
+}
+
+/// Activity handler - query recorded account activity (admin only).
+async fn activity_handler(
+    State(state): State<Arc<ManagementState>>,
+    account: Option<Extension<Account>>,
+    Query(query): Query<ActivityQuery>,
+) -> impl IntoResponse {
+    if let Err(status) = require_admin(account) {
+        return status.into_response();
+    }
+
+    let mut event_types = Vec::new();
+    for event in query.event.iter().flat_map(|e| e.split(',')) {
+        match EventType::parse(event.trim()) {
+            Some(event_type) => event_types.push(event_type),
+            None => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    format!("Unknown event type '{event}'"),
+                )
+                    .into_response();
+            }
+        }
+    }
+
+    let after = match query.next.as_deref().map(ActivityCursor::parse) {
+        Some(None) => return (StatusCode::BAD_REQUEST, "Invalid next value").into_response(),
+        Some(cursor) => cursor,
+        None => None,
+    };
+
+    let ip_prefix = match query.ip_prefix.as_deref().map(IpPrefix::parse) {
+        Some(None) => return (StatusCode::BAD_REQUEST, "Invalid ip_prefix value").into_response(),
+        Some(prefix) => prefix,
+        None => None,
+    };
+
+    let filter = ActivityFilter {
+        username: query.username,
+        event_types,
+        since: query.since,
+        until: query.until,
+        ip_address: query.ip,
+        ip_prefix,
+        limit: Some(
+            query
+                .limit
+                .unwrap_or(ACTIVITY_PAGE_LIMIT)
+                .clamp(1, ACTIVITY_PAGE_LIMIT),
+        ),
+        after,
+        ..Default::default()
+    };
+
+    match state.auth.get_activity(&filter).await {
+        Ok(page) => Json(ActivityResponse {
+            events: page
+                .items
+                .into_iter()
+                .map(|r| ActivityEvent {
+                    id: r.id,
+                    username: r.username,
+                    event_type: r.event_type,
+                    ip_address: r.ip_address,
+                    user_agent: r.user_agent,
+                    created_at: r.created_at,
+                })
+                .collect(),
+            more: page.more,
+            next: page.next,
+        })
+        .into_response(),
+        Err(e) => ServerError::from(e).into_response(),
+    }
+}
+
+/// Activity counts handler - count recorded account activity by event type
+/// (admin only).
+async fn activity_counts_handler(
+    State(state): State<Arc<ManagementState>>,
+    account: Option<Extension<Account>>,
+    Query(query): Query<ActivityCountsQuery>,
+) -> impl IntoResponse {
+    if let Err(status) = require_admin(account) {
+        return status.into_response();
+    }
+
+    match state.auth.activity_counts(query.since).await {
+        Ok(counts) => Json(ActivityCountsResponse { counts }).into_response(),
+        Err(e) => ServerError::from(e).into_response(),
+    }
+}
+
+/// Password reset redemption handler - set a new password with a reset token.
+async fn password_reset_redeem_handler(
+    State(state): State<Arc<ManagementState>>,
+    Json(req): Json<PasswordResetRedeemRequest>,
+) -> impl IntoResponse {
     match state
         .auth
-        .authenticate_with_logging(&req.username, &req.password, client_info)
+        .redeem_password_reset(&req.token, &req.new_password)
         .await
     {
-        Ok(Some(token)) => Json(AuthResponse { token }).into_response(),
-        Ok(None) => StatusCode::UNAUTHORIZED.into_response(),
-        Err(e) => {
-            error!("Authentication error: {:?}", e);
-            StatusCode::INTERNAL_SERVER_ERROR.into_response()
-        }
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => ServerError::from(e).into_response(),
+    }
+}
+
+/// Authorization schemes accepted by the auth middleware.
+fn auth_methods(config: &ServerConfig) -> Vec<String> {
+    let mut methods = vec!["bearer".to_string()];
+    if config.support_basic_auth {
+        methods.push("basic".to_string());
+    }
+    methods
+}
+
+/// Apply a request deadline middleware to routes, unless disabled.
+fn with_deadline<F, T>(routes: Router, limit: Option<Duration>, middleware: F) -> Router
+where
+    F: Fn(State<Duration>, Request, Next) -> T + Clone + Send + Sync + 'static,
+    T: Future<Output = Response> + Send + 'static,
+{
+    match limit {
+        Some(limit) => routes.layer(from_fn_with_state(limit, middleware)),
+        None => routes,
+    }
+}
+
+/// Apply the per-client rate limiter to routes, unless no limit is set.
+fn with_rate_limit(routes: Router, limits: RateLimits, trusted_proxies: &[IpAddr]) -> Router {
+    if limits.is_enabled() {
+        let limiter =
+            Arc::new(RateLimiter::new(limits).with_trusted_proxies(trusted_proxies.to_vec()));
+        limiter.spawn_pruning();
+        routes.layer(from_fn_with_state(limiter, rate_limit))
+    } else {
+        routes
     }
 }
 
@@ -209,20 +536,39 @@ fn create_router_internal(
         description: config.description.clone(),
         contact: config.contact.clone(),
         max_content_length: config.max_content_length,
+        max_bundle_objects: config.max_bundle_objects,
         public_discovery: config.public_discovery,
         allow_custom_properties: config.allow_custom_properties,
+        strict_content_type: config.strict_content_type,
+        provenance_policy: config.provenance_policy,
+        collision_policy: config.collision_policy,
         default_pagination_limit: config.default_pagination_limit,
         max_pagination_limit: config.max_pagination_limit,
+        collection_stats: config.collection_stats,
+        pattern_limits: PatternLimits {
+            max_length: config.max_pattern_length,
+            max_clauses: config.max_pattern_clauses,
+        },
+        capabilities_endpoint: config.capabilities_endpoint,
+        auth_methods: auth_methods(config),
     };
 
+    let pool = taxii2_persistence.pool().clone();
     let taxii2_state = Arc::new(Taxii2State {
         persistence: taxii2_persistence,
         config: taxii2_config,
     });
 
-    // TAXII 2.x routes
-    // Note: Using :param syntax for Axum path parameters
-    let taxii2_routes = Router::new()
+    let timeouts = RequestTimeouts::from_secs(
+        config.discovery_timeout_secs,
+        config.objects_timeout_secs,
+        config.taxii1_timeout_secs,
+    );
+
+    // TAXII 2.x routes, grouped by route class for request deadlines
+    // Note: Using :param syntax for Axum path parameters. GET routes also
+    // answer HEAD with the GET status and headers and no body
+    let mut taxii2_discovery_routes = Router::new()
         // Discovery
         .route("/taxii2/", get(taxii_2x::discovery_handler))
         // API Root
@@ -241,7 +587,15 @@ fn create_router_internal(
         .route(
             "/taxii2/{api_root_id}/collections/{collection_id}/",
             get(taxii_2x::collection_handler),
-        )
+        );
+    // Capabilities (API root ids are UUIDs, so no API root is shadowed)
+    if taxii2_state.config.capabilities_endpoint {
+        taxii2_discovery_routes = taxii2_discovery_routes
+            .route("/taxii2/capabilities/", get(taxii_2x::capabilities_handler));
+    }
+    let taxii2_discovery_routes = taxii2_discovery_routes.with_state(taxii2_state.clone());
+
+    let taxii2_object_routes = Router::new()
         // Manifest
         .route(
             "/taxii2/{api_root_id}/collections/{collection_id}/manifest/",
@@ -264,11 +618,36 @@ fn create_router_internal(
         )
         .with_state(taxii2_state);
 
+    // Object routes are rate limited per client (inside the auth layer, so
+    // authenticated requests are keyed by account)
+    let rate_limits = RateLimits {
+        read: RateLimit::new(config.read_rate_limit_per_sec, config.read_rate_limit_burst),
+        write: RateLimit::new(
+            config.write_rate_limit_per_sec,
+            config.write_rate_limit_burst,
+        ),
+        login: None,
+    };
+    let taxii2_object_routes = with_rate_limit(
+        taxii2_object_routes,
+        rate_limits,
+        &config.rate_limit_trusted_proxies,
+    );
+
+    let taxii2_routes =
+        with_deadline(taxii2_discovery_routes, timeouts.discovery, taxii2_timeout).merge(
+            with_deadline(taxii2_object_routes, timeouts.objects, taxii2_timeout),
+        );
+
     // TAXII 1.x state
     let taxii1x_state = Arc::new(Taxii1xState {
         persistence: Arc::new(taxii1_persistence),
         handler_registry: Arc::new(HandlerRegistry::new()),
         hooks,
+        validate_xml: config.validate_xml,
+        domain: config.domain.clone(),
+        limits: config.taxii1_limits,
+        push: config.push,
     });
 
     // TAXII 1.x routes
@@ -280,20 +659,59 @@ fn create_router_internal(
             post(taxii1x_service_handler).options(taxii1x_options_handler),
         )
         .with_state(taxii1x_state);
+    let taxii1x_routes = with_deadline(taxii1x_routes, timeouts.taxii1, taxii1x_timeout);
 
-    // Management routes (no auth required)
+    // Management routes (no auth required, except issuing password resets)
     // Note: /management/auth needs AuthAPI access but doesn't require authentication itself
-    let management_state = Arc::new(ManagementState { auth: auth.clone() });
+    let management_state = Arc::new(ManagementState {
+        auth: auth.clone(),
+        pool,
+    });
+
+    // Logins are limited per client address and per username, against
+    // password and second factor guessing
+    let mut auth_route = post(auth_handler).with_state(management_state.clone());
+    if let Some(limit) = RateLimit::per_minute(
+        config.login_rate_limit_per_minute,
+        config.login_rate_limit_burst,
+    ) {
+        let limiter = RateLimiter::new(RateLimits {
+            login: Some(limit),
+            ..RateLimits::default()
+        })
+        .with_trusted_proxies(config.rate_limit_trusted_proxies.clone());
+        let limiter = Arc::new(limiter);
+        limiter.spawn_pruning();
+        auth_route = auth_route.layer(from_fn_with_state(limiter, login_rate_limit));
+    }
 
     let management_routes = Router::new()
         .route("/management/health", get(health_handler))
         .route(
-            "/management/auth",
-            post(auth_handler).with_state(management_state),
+            "/management/ready",
+            get(ready_handler).with_state(management_state.clone()),
+        )
+        .route("/management/auth", auth_route)
+        .route(
+            "/management/password-reset",
+            post(password_reset_handler).with_state(management_state.clone()),
+        )
+        .route(
+            "/management/activity",
+            get(activity_handler).with_state(management_state.clone()),
+        )
+        .route(
+            "/management/activity/counts",
+            get(activity_counts_handler).with_state(management_state.clone()),
+        )
+        .route(
+            "/management/password-reset/redeem",
+            post(password_reset_redeem_handler).with_state(management_state),
         );
 
     // Combine routes with auth middleware
-    // CatchPanicLayer is the outermost layer as a safety net for unhandled panics
+    // CatchPanicLayer is a safety net for unhandled panics; request logging
+    // wraps it so the resulting errors are logged like any other response
     Router::new()
         .merge(management_routes) // Health endpoint before auth
         .merge(taxii2_routes)
@@ -305,4 +723,577 @@ fn create_router_internal(
             // Return a generic error response - no internal details exposed
             (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error").into_response()
         }))
+        .layer(from_fn(crate::logging::log_requests))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::{Body, to_bytes};
+    use axum::http::header::{ACCEPT, CONTENT_LENGTH};
+    use axum::http::{HeaderMap, Method};
+    use sqlx::PgPool;
+    use taxii_db::{Taxii1Repository, Taxii2Repository};
+    use tower::ServiceExt;
+
+    use crate::config::TomlConfig;
+
+    type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+    async fn send(
+        app: &Router,
+        method: Method,
+        uri: &str,
+    ) -> Result<(StatusCode, HeaderMap, Vec<u8>), Box<dyn std::error::Error>> {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(ACCEPT, taxii_2x::TAXII2_CONTENT_TYPE)
+            .body(Body::empty())?;
+        let response = app.clone().oneshot(request).await?;
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = to_bytes(response.into_body(), usize::MAX).await?;
+        Ok((status, headers, body.to_vec()))
+    }
+
+    const INDICATOR_ID: &str = "indicator--8e2e2d2b-17d4-4cbf-938f-98ee46b3cd3f";
+
+    /// Create a readable collection holding one indicator, returning the
+    /// router, the API root URI and the collection URI.
+    async fn setup(pool: PgPool) -> Result<(Router, String, String), Box<dyn std::error::Error>> {
+        setup_with(pool, "").await
+    }
+
+    /// Like [`setup`], with extra TOML configuration.
+    async fn setup_with(
+        pool: PgPool,
+        extra: &str,
+    ) -> Result<(Router, String, String), Box<dyn std::error::Error>> {
+        let pool = TaxiiPool::new(pool);
+        let taxii2 = DbTaxii2Repository::new(pool.clone());
+        let api_root = taxii2.add_api_root("Root", None, true, true, None).await?;
+        let collection = taxii2
+            .add_collection(&api_root.id, "Feed", None, None, true, false, None)
+            .await?;
+        taxii2
+            .add_objects(
+                &api_root.id,
+                &collection.id,
+                &[serde_json::json!({
+                    "type": "indicator",
+                    "spec_version": "2.1",
+                    "id": INDICATOR_ID,
+                    "created": "2024-01-01T00:00:00.000Z",
+                    "modified": "2024-01-01T00:00:00.000Z",
+                    "pattern": "[ipv4-addr:value = '10.0.0.1']",
+                    "pattern_type": "stix",
+                    "valid_from": "2024-01-01T00:00:00Z"
+                })],
+            )
+            .await?;
+
+        let toml: TomlConfig = toml::from_str(&format!(
+            "[database]\nurl = \"postgresql://primary/taxii\"\n[auth]\nsecret = \"secret\"\n{extra}"
+        ))?;
+        let config = ServerConfig::from_toml_with_env_overrides(toml)?;
+        let app = create_router(
+            DbTaxii1Repository::new(pool.clone()),
+            taxii2,
+            AuthAPI::new(pool, "secret".to_string(), None)?,
+            &config,
+        );
+
+        let api_root_uri = format!("/taxii2/{}", api_root.id);
+        let collection_uri = format!("{api_root_uri}/collections/{}/", collection.id);
+        Ok((app, api_root_uri, collection_uri))
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_head_matches_get(pool: PgPool) -> TestResult {
+        let (app, api_root_uri, collection_uri) = setup(pool).await?;
+
+        for uri in [
+            collection_uri.clone(),
+            format!("{collection_uri}objects/"),
+            format!("{collection_uri}manifest/"),
+            format!("{api_root_uri}/collections/missing/"),
+        ] {
+            let (get_status, get_headers, get_body) = send(&app, Method::GET, &uri).await?;
+            let (head_status, head_headers, head_body) = send(&app, Method::HEAD, &uri).await?;
+
+            assert_eq!(head_status, get_status, "{uri}");
+            assert!(head_body.is_empty(), "{uri}");
+            assert!(!get_body.is_empty(), "{uri}");
+            assert_eq!(
+                head_headers.get(CONTENT_LENGTH),
+                get_headers.get(CONTENT_LENGTH),
+                "{uri}"
+            );
+            for header in [
+                "content-type",
+                "x-taxii-date-added-first",
+                "x-taxii-date-added-last",
+            ] {
+                assert_eq!(
+                    head_headers.get(header),
+                    get_headers.get(header),
+                    "{uri} {header}"
+                );
+            }
+        }
+
+        let (_, headers, _) =
+            send(&app, Method::HEAD, &format!("{collection_uri}objects/")).await?;
+        assert!(headers.contains_key("x-taxii-date-added-first"));
+        Ok(())
+    }
+
+    /// A single object is returned in an envelope, not bare.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_single_object_get_returns_envelope(pool: PgPool) -> TestResult {
+        let (app, _, collection_uri) = setup(pool).await?;
+
+        let uri = format!("{collection_uri}objects/{INDICATOR_ID}/");
+        let (status, headers, body) = send(&app, Method::GET, &uri).await?;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            headers.get("content-type").map(|v| v.as_bytes()),
+            Some(taxii_2x::TAXII2_CONTENT_TYPE.as_bytes())
+        );
+        assert!(headers.contains_key("x-taxii-date-added-first"));
+        assert!(headers.contains_key("x-taxii-date-added-last"));
+
+        let envelope: serde_json::Value = serde_json::from_slice(&body)?;
+        assert_eq!(envelope["more"], false);
+        assert!(envelope.get("type").is_none());
+        let objects = envelope["objects"]
+            .as_array()
+            .ok_or("envelope without objects")?;
+        assert_eq!(objects.len(), 1);
+        assert_eq!(objects[0]["id"], INDICATOR_ID);
+        assert_eq!(objects[0]["type"], "indicator");
+        Ok(())
+    }
+
+    /// Pages followed by more results carry `next`; the final page has no
+    /// `next`. Every page is 200, and the date headers always cover the page
+    /// returned.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_pages(pool: PgPool) -> TestResult {
+        let taxii2 = DbTaxii2Repository::new(TaxiiPool::new(pool.clone()));
+        let (app, _, collection_uri) = setup_with(pool, "").await?;
+        let (api_root_id, collection_id) = collection_uri
+            .trim_start_matches("/taxii2/")
+            .trim_end_matches('/')
+            .split_once("/collections/")
+            .ok_or("unexpected collection URI")?;
+        for id in [
+            "indicator--0b5a2f5e-2ac8-4b0e-9e4a-1f8c2f1c3a01",
+            "indicator--0b5a2f5e-2ac8-4b0e-9e4a-1f8c2f1c3a02",
+        ] {
+            taxii2
+                .add_objects(
+                    api_root_id,
+                    collection_id,
+                    &[serde_json::json!({
+                        "type": "indicator",
+                        "spec_version": "2.1",
+                        "id": id,
+                        "created": "2024-01-01T00:00:00.000Z",
+                        "modified": "2024-01-01T00:00:00.000Z",
+                        "pattern": "[ipv4-addr:value = '10.0.0.2']",
+                        "pattern_type": "stix",
+                        "valid_from": "2024-01-01T00:00:00Z"
+                    })],
+                )
+                .await?;
+        }
+        let header = |headers: &HeaderMap, name: &str| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(String::from)
+        };
+
+        for endpoint in ["objects", "manifest"] {
+            // A middle page
+            let uri = format!("{collection_uri}{endpoint}/?limit=2");
+            let (status, headers, body) = send(&app, Method::GET, &uri).await?;
+            assert_eq!(status, StatusCode::OK, "{endpoint}");
+            let page: serde_json::Value = serde_json::from_slice(&body)?;
+            assert_eq!(page["more"], true, "{endpoint}");
+            assert_eq!(page["objects"].as_array().map(Vec::len), Some(2));
+            let next = page["next"]
+                .as_str()
+                .ok_or("middle page without next")?
+                .replace('+', "%2B")
+                .replace('/', "%2F")
+                .replace('=', "%3D");
+
+            // The headers match the dates added of the manifest page
+            let (_, _, manifest) = send(
+                &app,
+                Method::GET,
+                &format!("{collection_uri}manifest/?limit=2"),
+            )
+            .await?;
+            let manifest: serde_json::Value = serde_json::from_slice(&manifest)?;
+            let mut dates: Vec<&str> = manifest["objects"]
+                .as_array()
+                .ok_or("manifest without objects")?
+                .iter()
+                .filter_map(|entry| entry["date_added"].as_str())
+                .collect();
+            dates.sort_unstable();
+            assert_eq!(
+                header(&headers, "x-taxii-date-added-first").as_deref(),
+                dates.first().copied()
+            );
+            assert_eq!(
+                header(&headers, "x-taxii-date-added-last").as_deref(),
+                dates.last().copied()
+            );
+
+            // The final page
+            let uri = format!("{collection_uri}{endpoint}/?limit=2&next={next}");
+            let (status, headers, body) = send(&app, Method::GET, &uri).await?;
+            assert_eq!(status, StatusCode::OK, "{endpoint}");
+            let page: serde_json::Value = serde_json::from_slice(&body)?;
+            assert_eq!(page["more"], false, "{endpoint}");
+            assert!(page.get("next").is_none(), "{endpoint}");
+            assert_eq!(page["objects"].as_array().map(Vec::len), Some(1));
+            assert!(
+                header(&headers, "x-taxii-date-added-first") >= dates.last().map(|d| d.to_string())
+            );
+            assert_eq!(
+                header(&headers, "x-taxii-date-added-first"),
+                header(&headers, "x-taxii-date-added-last")
+            );
+        }
+
+        Ok(())
+    }
+
+    /// The capabilities document is only routed when enabled, and reflects
+    /// the server configuration.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_capabilities_endpoint(pool: PgPool) -> TestResult {
+        let pool = TaxiiPool::new(pool);
+        let router = |extra: &str| -> Result<Router, Box<dyn std::error::Error>> {
+            let toml: TomlConfig = toml::from_str(&format!(
+                "[database]\nurl = \"postgresql://primary/taxii\"\n[auth]\nsecret = \"secret\"\n{extra}"
+            ))?;
+            let config = ServerConfig::from_toml_with_env_overrides(toml)?;
+            Ok(create_router(
+                DbTaxii1Repository::new(pool.clone()),
+                DbTaxii2Repository::new(pool.clone()),
+                AuthAPI::new(pool.clone(), "secret".to_string(), None)?,
+                &config,
+            ))
+        };
+
+        let app = router("[taxii2]\npublic_discovery = true\n")?;
+        let (status, _, _) = send(&app, Method::GET, "/taxii2/capabilities/").await?;
+        assert_ne!(status, StatusCode::OK);
+
+        let app = router(
+            "[taxii2]\npublic_discovery = true\n\
+             capabilities_endpoint = true\nmax_content_length = 4096\nmax_pagination_limit = 50\n",
+        )?;
+        let (status, _, body) = send(&app, Method::GET, "/taxii2/capabilities/").await?;
+        assert_eq!(status, StatusCode::OK);
+        let document: serde_json::Value = serde_json::from_slice(&body)?;
+        assert_eq!(document["max_content_length"], 4096);
+        assert_eq!(document["pagination"]["max_limit"], 50);
+        assert_eq!(
+            document["auth_methods"],
+            serde_json::json!(["bearer", "basic"])
+        );
+        assert_eq!(document["delete"]["tombstones"], true);
+        Ok(())
+    }
+
+    /// Post a TAXII 1.1 message to a service, returning the status code and
+    /// the response body.
+    async fn post_taxii1(
+        app: &Router,
+        service_id: &str,
+        xml: String,
+        content_length: bool,
+    ) -> Result<(StatusCode, String), Box<dyn std::error::Error>> {
+        let mut request = Request::builder()
+            .method(Method::POST)
+            .uri(format!("/services/{service_id}/"))
+            .header("content-type", "application/xml")
+            .header(
+                taxii_1x::HTTP_X_TAXII_CONTENT_TYPE,
+                taxii_1x::VID_TAXII_XML_11,
+            )
+            .header(
+                taxii_1x::HTTP_X_TAXII_SERVICES,
+                taxii_1x::VID_TAXII_SERVICES_11,
+            )
+            .header(taxii_1x::HTTP_X_TAXII_PROTOCOL, taxii_1x::VID_TAXII_HTTP_10);
+        if content_length {
+            request = request.header(CONTENT_LENGTH, xml.len());
+        }
+        let response = app.clone().oneshot(request.body(Body::from(xml))?).await?;
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await?;
+        Ok((status, String::from_utf8(body.to_vec())?))
+    }
+
+    /// Oversized TAXII 1.x messages and content blocks are rejected with the
+    /// limits of their service.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_taxii1_message_size_limits(pool: PgPool) -> TestResult {
+        let pool = TaxiiPool::new(pool);
+        let taxii1 = DbTaxii1Repository::new(pool.clone());
+        taxii1
+            .update_service(&taxii_core::ServiceEntity {
+                id: Some("inbox".to_string()),
+                service_type: "INBOX".to_string(),
+                properties: serde_json::json!({
+                    "max_message_size": 1024,
+                    "max_content_block_size": 64
+                }),
+            })
+            .await?;
+
+        let toml: TomlConfig = toml::from_str(
+            "[database]\nurl = \"postgresql://primary/taxii\"\n[auth]\nsecret = \"secret\"\n",
+        )?;
+        let config = ServerConfig::from_toml_with_env_overrides(toml)?;
+        let app = create_router(
+            taxii1,
+            DbTaxii2Repository::new(pool.clone()),
+            AuthAPI::new(pool, "secret".to_string(), None)?,
+            &config,
+        );
+
+        let inbox = |content: &str| {
+            format!(
+                r#"<taxii_11:Inbox_Message xmlns:taxii_11="http://taxii.mitre.org/messages/taxii_xml_binding-1.1" message_id="42"><taxii_11:Content_Block><taxii_11:Content_Binding binding_id="urn:stix.mitre.org:xml:1.1.1"/><taxii_11:Content>{content}</taxii_11:Content></taxii_11:Content_Block></taxii_11:Inbox_Message>"#
+            )
+        };
+
+        // Over the message size, with and without a declared length
+        let oversized = inbox(&"a".repeat(2048));
+        for content_length in [true, false] {
+            let (status, body) =
+                post_taxii1(&app, "inbox", oversized.clone(), content_length).await?;
+            assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE, "{body}");
+            assert!(body.contains("BAD_MESSAGE"), "{body}");
+            assert!(body.contains("1024 bytes"), "{body}");
+        }
+
+        // Within the message size, over the content block size
+        let (status, body) = post_taxii1(&app, "inbox", inbox(&"a".repeat(100)), true).await?;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE, "{body}");
+        assert!(body.contains("FAILURE"), "{body}");
+        assert!(body.contains("Content block 1 of 100 bytes"), "{body}");
+        assert!(body.contains(r#"in_response_to="42""#), "{body}");
+
+        // Unknown services read the body within the server defaults
+        let (status, body) = post_taxii1(&app, "missing", oversized, true).await?;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{body}");
+        Ok(())
+    }
+
+    /// A handler refusing a TAXII 1.x message answers with a client error,
+    /// not a server error.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_taxii1_handler_failure_is_client_error(pool: PgPool) -> TestResult {
+        let pool = TaxiiPool::new(pool);
+        let taxii1 = DbTaxii1Repository::new(pool.clone());
+        taxii1
+            .update_service(&taxii_core::ServiceEntity {
+                id: Some("inbox".to_string()),
+                service_type: "INBOX".to_string(),
+                properties: serde_json::json!({ "destination_collection_names": ["feed"] }),
+            })
+            .await?;
+        let collection = taxii1
+            .create_collection(&taxii_core::CollectionEntity {
+                validation_mode: taxii_core::validation_mode::REJECT.to_string(),
+                ..taxii_core::CollectionEntity::new("feed")
+            })
+            .await?;
+        taxii1
+            .set_collection_services(
+                collection.id.ok_or("collection has no id")?,
+                &["inbox".to_string()],
+            )
+            .await?;
+
+        let toml: TomlConfig = toml::from_str(
+            "[database]\nurl = \"postgresql://primary/taxii\"\n[auth]\nsecret = \"secret\"\n",
+        )?;
+        let config = ServerConfig::from_toml_with_env_overrides(toml)?;
+        let app = create_router(
+            taxii1,
+            DbTaxii2Repository::new(pool.clone()),
+            AuthAPI::new(pool, "secret".to_string(), None)?,
+            &config,
+        );
+
+        // Malformed STIX content for a collection rejecting invalid content
+        let xml = r#"<taxii_11:Inbox_Message xmlns:taxii_11="http://taxii.mitre.org/messages/taxii_xml_binding-1.1" message_id="42"><taxii_11:Content_Block><taxii_11:Content_Binding binding_id="urn:stix.mitre.org:xml:1.1.1"/><taxii_11:Content>&lt;stix:STIX_Package xmlns:stix="http://stix.mitre.org/stix-1"&gt;</taxii_11:Content></taxii_11:Content_Block></taxii_11:Inbox_Message>"#;
+        let (status, body) = post_taxii1(&app, "inbox", xml.to_string(), true).await?;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{body}");
+        assert!(body.contains("FAILURE"), "{body}");
+        assert!(body.contains(r#"in_response_to="42""#), "{body}");
+        Ok(())
+    }
+
+    /// With `validate_xml`, TAXII 1.x messages and the STIX content they
+    /// carry are validated against the bundled schemas before they are
+    /// handled.
+    #[cfg(feature = "xsd")]
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_taxii1_schema_validation(pool: PgPool) -> TestResult {
+        let pool = TaxiiPool::new(pool);
+        let taxii1 = DbTaxii1Repository::new(pool.clone());
+        taxii1
+            .update_service(&taxii_core::ServiceEntity {
+                id: Some("inbox".to_string()),
+                service_type: "INBOX".to_string(),
+                properties: serde_json::json!({ "destination_collection_required": true }),
+            })
+            .await?;
+        let collection = taxii1
+            .create_collection(&taxii_core::CollectionEntity {
+                validation_mode: taxii_core::validation_mode::REJECT.to_string(),
+                ..taxii_core::CollectionEntity::new("default")
+            })
+            .await?;
+        let collection_id = collection.id.ok_or("collection has no id")?;
+        taxii1
+            .set_collection_services(collection_id, &["inbox".to_string()])
+            .await?;
+
+        let toml: TomlConfig = toml::from_str(
+            "[database]\nurl = \"postgresql://primary/taxii\"\n[auth]\nsecret = \"secret\"\n\
+             [taxii1]\nvalidate_xml = true\n",
+        )?;
+        let config = ServerConfig::from_toml_with_env_overrides(toml)?;
+        let app = create_router(
+            taxii1,
+            DbTaxii2Repository::new(pool.clone()),
+            AuthAPI::new(pool.clone(), "secret".to_string(), None)?,
+            &config,
+        );
+
+        let fixture = |name: &str| {
+            std::fs::read_to_string(format!(
+                "{}/../taxii-1x/tests/fixtures/{name}",
+                env!("CARGO_MANIFEST_DIR")
+            ))
+        };
+
+        let (status, body) =
+            post_taxii1(&app, "inbox", fixture("inbox_message_valid.xml")?, true).await?;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        assert!(body.contains("SUCCESS"), "{body}");
+
+        for (name, path) in [
+            ("inbox_message_wrong_order.xml", "/Inbox_Message/Message"),
+            (
+                "poll_request_missing_message_id.xml",
+                "/Poll_Request/@message_id",
+            ),
+        ] {
+            let (status, body) = post_taxii1(&app, "inbox", fixture(name)?, true).await?;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{body}");
+            assert!(body.contains("BAD_MESSAGE"), "{body}");
+            assert!(body.contains(&format!("at {path}")), "{body}");
+        }
+
+        // A schema-valid message carrying content that is not
+        let xml = r#"<taxii_11:Inbox_Message xmlns:taxii_11="http://taxii.mitre.org/messages/taxii_xml_binding-1.1" message_id="42"><taxii_11:Destination_Collection_Name>default</taxii_11:Destination_Collection_Name><taxii_11:Content_Block><taxii_11:Content_Binding binding_id="urn:stix.mitre.org:xml:1.1.1"/><taxii_11:Content>&lt;stix:STIX_Package xmlns:stix="http://stix.mitre.org/stix-1"&gt;&lt;stix:Indicators/&gt;&lt;stix:STIX_Header/&gt;&lt;/stix:STIX_Package&gt;</taxii_11:Content></taxii_11:Content_Block></taxii_11:Inbox_Message>"#;
+        let (status, body) = post_taxii1(&app, "inbox", xml.to_string(), true).await?;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{body}");
+        assert!(body.contains("BAD_MESSAGE"), "{body}");
+        assert!(body.contains("at /STIX_Package/STIX_Header"), "{body}");
+        assert!(body.contains(r#"in_response_to="42""#), "{body}");
+
+        // Only the valid message was stored
+        let blocks = DbTaxii1Repository::new(pool)
+            .get_content_blocks(Some(collection_id), None, None, None, 0, None)
+            .await?;
+        assert_eq!(blocks.len(), 1);
+        Ok(())
+    }
+
+    /// Management endpoints refuse down-scoped tokens, even an admin's.
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn test_management_refuses_scoped_tokens(pool: PgPool) -> TestResult {
+        let auth = AuthAPI::new(TaxiiPool::new(pool.clone()), "secret".to_string(), None)?;
+        auth.create_account("admin", "password", true).await?;
+        let (app, _, _) = setup(pool).await?;
+
+        let token = |scope: Option<serde_json::Value>| {
+            let app = app.clone();
+            async move {
+                let mut body = serde_json::json!({"username": "admin", "password": "password"});
+                if let Some(scope) = scope {
+                    body["scope"] = scope;
+                }
+                let request = Request::builder()
+                    .method(Method::POST)
+                    .uri("/management/auth")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))?;
+                let response = app.oneshot(request).await?;
+                assert_eq!(response.status(), StatusCode::OK);
+                let body = to_bytes(response.into_body(), usize::MAX).await?;
+                let body: serde_json::Value = serde_json::from_slice(&body)?;
+                Ok::<_, Box<dyn std::error::Error>>(
+                    body["token"].as_str().unwrap_or_default().to_string(),
+                )
+            }
+        };
+        let scoped = token(Some(serde_json::json!({"access": "write"}))).await?;
+        let unscoped = token(None).await?;
+
+        let management = |method: Method, uri: &str, token: &str| {
+            let request = Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("authorization", format!("Bearer {token}"))
+                .header("content-type", "application/json")
+                .body(Body::from(r#"{"username": "admin"}"#));
+            let app = app.clone();
+            async move { Ok::<_, Box<dyn std::error::Error>>(app.oneshot(request?).await?.status()) }
+        };
+        for (method, uri) in [
+            (Method::POST, "/management/password-reset"),
+            (Method::GET, "/management/activity"),
+            (
+                Method::GET,
+                "/management/activity/counts?since=2024-01-01T00:00:00Z",
+            ),
+        ] {
+            assert_eq!(
+                management(method.clone(), uri, &scoped).await?,
+                StatusCode::FORBIDDEN,
+                "{uri}"
+            );
+            assert_eq!(
+                management(method, uri, &unscoped).await?,
+                StatusCode::OK,
+                "{uri}"
+            );
+        }
+        Ok(())
+    }
 }
@@ -2,6 +2,7 @@
 
 use std::sync::{Arc, LazyLock};
 
+use axum::body::Body;
 use axum::extract::{Path, State};
 use axum::http::{HeaderMap, HeaderName, StatusCode, Uri, header};
 use axum::response::{IntoResponse, Response};
@@ -9,14 +10,16 @@ use tracing::error;
 
 use taxii_1x::{
     HTTP_X_FORWARDED_PROTO, HTTP_X_FORWARDED_SSL, HTTP_X_TAXII_ACCEPT, HTTP_X_TAXII_CONTENT_TYPE,
-    HTTP_X_TAXII_PROTOCOL, HTTP_X_TAXII_SERVICES, HandlerContext, HandlerRegistry, ServiceInfo,
-    TaxiiHeaders, TaxiiMessage, VID_TAXII_HTTP_10, VID_TAXII_HTTPS_10, VID_TAXII_XML_10,
-    VID_TAXII_XML_11, get_message_from_xml, messages::messages_10 as tm10,
-    messages::messages_11 as tm11,
+    HTTP_X_TAXII_PROTOCOL, HTTP_X_TAXII_SERVICES, HandlerContext, HandlerRegistry, MessageBody,
+    MessageLimits, PushPolicy, ServiceInfo, Taxii1xError, TaxiiHeaders, TaxiiMessage,
+    VID_TAXII_HTTP_10, VID_TAXII_HTTPS_10, VID_TAXII_XML_10, VID_TAXII_XML_11,
+    limits::check_content_blocks, messages::messages_10 as tm10, messages::messages_11 as tm11,
 };
 use taxii_core::Account;
 use taxii_db::{DbTaxii1Repository, Taxii1Repository};
 
+use crate::error::taxii1x_status;
+
 /// Pre-parsed TAXII header names for response building.
 #[expect(clippy::expect_used, reason = "infallible: valid header literal")]
 static HEADER_X_TAXII_CONTENT_TYPE: LazyLock<HeaderName> = LazyLock::new(|| {
@@ -39,12 +42,21 @@ pub struct Taxii1xState {
     pub persistence: Arc<DbTaxii1Repository>,
     pub handler_registry: Arc<HandlerRegistry>,
     pub hooks: Option<taxii_core::SharedHookRegistry>,
+    /// Validate messages against the message binding schemas (`xsd`
+    /// feature).
+    pub validate_xml: bool,
+    /// External domain used in advertised service addresses.
+    pub domain: Option<String>,
+    /// Server-wide message size limits, overridden by service properties.
+    pub limits: MessageLimits,
+    /// Whether, and to which addresses, content may be pushed.
+    pub push: PushPolicy,
 }
 
 /// Detect if the request is secure (HTTPS).
 ///
 /// Checks X-Forwarded-Proto header (for reverse proxies) or URI scheme.
-fn is_request_secure(headers: &HeaderMap, uri: &Uri) -> bool {
+pub(crate) fn is_request_secure(headers: &HeaderMap, uri: &Uri) -> bool {
     // Check X-Forwarded-Proto header (common for reverse proxies like nginx, AWS ALB)
     if let Some(proto) = headers
         .get(HTTP_X_FORWARDED_PROTO)
@@ -85,30 +97,69 @@ pub async fn taxii1x_service_handler(
     uri: Uri,
     headers: HeaderMap,
     account: Option<axum::Extension<Account>>,
-    body: String,
+    body: Body,
 ) -> impl IntoResponse {
     // Detect if request is over HTTPS (for proper X-TAXII-Protocol header)
     let is_secure = is_request_secure(&headers, &uri);
-    // Extract TAXII headers
-    let content_type = headers
-        .get(HTTP_X_TAXII_CONTENT_TYPE)
-        .and_then(|v| v.to_str().ok())
-        .unwrap_or("");
+    // Extract TAXII headers, rejecting requests missing mandatory ones
+    let taxii_headers = match TaxiiHeaders::from_header_map(&headers) {
+        Ok(taxii_headers) => taxii_headers,
+        Err(e) => {
+            let version = get_version_from_headers(&headers);
+            return taxii_status_error_response(&e, StatusCode::BAD_REQUEST, version, is_secure);
+        }
+    };
 
-    let services = headers
-        .get(HTTP_X_TAXII_SERVICES)
-        .and_then(|v| v.to_str().ok())
-        .unwrap_or("");
+    // Look up the service first: its properties may override the size limits
+    let service = state.persistence.get_service(&service_id).await;
+    let limits = match &service {
+        Ok(Some(s)) => state.limits.for_service(&s.properties),
+        _ => state.limits,
+    };
 
-    let accept = headers
-        .get(HTTP_X_TAXII_ACCEPT)
+    // Read the body within the size limit, spooling large ones to disk
+    let content_length = headers
+        .get(header::CONTENT_LENGTH)
         .and_then(|v| v.to_str().ok())
-        .map(String::from);
+        .and_then(|v| v.parse::<u64>().ok());
+    let body = match MessageBody::read(body, content_length, &limits).await {
+        Ok(body) => body,
+        Err(e) => {
+            let version = get_version_from_headers(&headers);
+            return match e {
+                Taxii1xError::StatusMessage { .. } => taxii_status_error_response(
+                    &e,
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    version,
+                    is_secure,
+                ),
+                Taxii1xError::InvalidRequest(_) => {
+                    taxii_status_error_response(&e, StatusCode::BAD_REQUEST, version, is_secure)
+                }
+                other => {
+                    error!("TAXII 1.x failed to buffer request body: {:?}", other);
+                    taxii_error_response(
+                        "Failed to read message",
+                        None,
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        version,
+                        is_secure,
+                    )
+                }
+            };
+        }
+    };
 
-    if content_type.is_empty() || services.is_empty() {
+    // Reject DTDs before reading any further
+    if let Err(e) = body.check_document().await {
         let version = get_version_from_headers(&headers);
-        return taxii_error_response(
-            "Missing required TAXII headers",
+        let message = match e {
+            Taxii1xError::StatusMessage { message, .. } => message,
+            other => other.to_string(),
+        };
+        return taxii_status_response(
+            taxii_1x::ST_BAD_MESSAGE,
+            &message,
             None,
             StatusCode::BAD_REQUEST,
             version,
@@ -116,14 +167,26 @@ pub async fn taxii1x_service_handler(
         );
     }
 
-    let taxii_headers = TaxiiHeaders {
-        content_type: content_type.to_string(),
-        services: services.to_string(),
-        accept,
-    };
+    // Check the message structure once, before deserializing it
+    #[cfg(feature = "xsd")]
+    if state.validate_xml {
+        if let Err(violation) = body.validate_schema().await {
+            let version = get_version_from_headers(&headers);
+            return taxii_status_response(
+                taxii_1x::ST_BAD_MESSAGE,
+                &format!(
+                    "Message does not conform to the TAXII message binding schema: {violation}"
+                ),
+                None,
+                StatusCode::BAD_REQUEST,
+                version,
+                is_secure,
+            );
+        }
+    }
 
     // Parse the message
-    let message = match get_message_from_xml(&body) {
+    let message = match body.parse().await {
         Ok(msg) => msg,
         Err(e) => {
             let version = get_version_from_headers(&headers);
@@ -140,8 +203,24 @@ pub async fn taxii1x_service_handler(
     // Get message version for error responses
     let msg_version = message.version();
 
-    // Get service info from database
-    let service = match state.persistence.get_service(&service_id).await {
+    // Check the headers agree with the message
+    if let Err(e) = taxii_headers.validate(msg_version, Some(message.message_id())) {
+        return taxii_status_error_response(&e, StatusCode::BAD_REQUEST, msg_version, is_secure);
+    }
+
+    // Reject content blocks over the size limit, naming the first one
+    if let Err(e) = check_content_blocks(&message, limits.max_content_block_size) {
+        return taxii_status_error_response(
+            &e,
+            StatusCode::PAYLOAD_TOO_LARGE,
+            msg_version,
+            is_secure,
+        );
+    }
+    drop(body);
+
+    // Check the service was found
+    let service = match service {
         Ok(Some(s)) => s,
         Ok(None) => {
             return taxii_error_response(
@@ -180,12 +259,19 @@ pub async fn taxii1x_service_handler(
                 .get("description")
                 .and_then(|v| v.as_str())
                 .map(String::from),
-            protocol_bindings: vec![taxii_1x::VID_TAXII_HTTP_10.to_string()],
+            protocol_bindings: vec![if is_secure {
+                VID_TAXII_HTTPS_10.to_string()
+            } else {
+                VID_TAXII_HTTP_10.to_string()
+            }],
             message_bindings: vec![VID_TAXII_XML_10.to_string(), VID_TAXII_XML_11.to_string()],
             available: true,
             authentication_required: false,
             properties: service.properties,
         },
+        domain: state.domain.clone(),
+        limits,
+        push: state.push,
         hooks: state.hooks.clone(),
     };
 
@@ -208,58 +294,18 @@ pub async fn taxii1x_service_handler(
 
     // Process based on version
     let response_xml = match message {
-        TaxiiMessage::V10(ref msg) => {
-            // Validate headers for 1.0
-            if let Err(e) = taxii_headers.validate_10(Some(msg.message_id())) {
-                return taxii_error_response(
-                    &e.to_string(),
-                    Some(msg.message_id()),
-                    StatusCode::BAD_REQUEST,
-                    VID_TAXII_XML_10,
-                    is_secure,
-                );
-            }
-
-            match handler.handle_10(&ctx, &taxii_headers, msg).await {
-                Ok(response) => response.to_xml(),
-                Err(e) => {
-                    error!("TAXII 1.0 handler error: {:?}", e);
-                    return taxii_error_response(
-                        "Processing error occurred",
-                        Some(msg.message_id()),
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        VID_TAXII_XML_10,
-                        is_secure,
-                    );
-                }
+        TaxiiMessage::V10(ref msg) => match handler.handle_10(&ctx, &taxii_headers, msg).await {
+            Ok(response) => response.to_xml(),
+            Err(e) => {
+                return handler_error_response(&e, msg.message_id(), VID_TAXII_XML_10, is_secure);
             }
-        }
-        TaxiiMessage::V11(ref msg) => {
-            // Validate headers for 1.1
-            if let Err(e) = taxii_headers.validate_11(Some(msg.message_id())) {
-                return taxii_error_response(
-                    &e.to_string(),
-                    Some(msg.message_id()),
-                    StatusCode::BAD_REQUEST,
-                    VID_TAXII_XML_11,
-                    is_secure,
-                );
-            }
-
-            match handler.handle_11(&ctx, &taxii_headers, msg).await {
-                Ok(response) => response.to_xml(),
-                Err(e) => {
-                    error!("TAXII 1.1 handler error: {:?}", e);
-                    return taxii_error_response(
-                        "Processing error occurred",
-                        Some(msg.message_id()),
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        VID_TAXII_XML_11,
-                        is_secure,
-                    );
-                }
+        },
+        TaxiiMessage::V11(ref msg) => match handler.handle_11(&ctx, &taxii_headers, msg).await {
+            Ok(response) => response.to_xml(),
+            Err(e) => {
+                return handler_error_response(&e, msg.message_id(), VID_TAXII_XML_11, is_secure);
             }
-        }
+        },
     };
 
     // Serialize response
@@ -337,37 +383,124 @@ pub async fn taxii1x_options_handler(
 }
 
 /// Create a TAXII error response with proper XML StatusMessage.
-fn taxii_error_response(
+pub(crate) fn taxii_error_response(
     message: &str,
     in_response_to: Option<&str>,
     status: StatusCode,
     version: &str,
     is_secure: bool,
 ) -> Response {
-    let message_id = taxii_1x::http::generate_message_id();
-    let protocol_binding = get_protocol_binding(is_secure);
+    taxii_status_response(
+        taxii_1x::ST_FAILURE,
+        message,
+        in_response_to,
+        status,
+        version,
+        is_secure,
+    )
+}
 
-    // Determine if this is TAXII 1.0 or 1.1 based on version
-    let is_10 = version == VID_TAXII_XML_10 || version == taxii_1x::VID_TAXII_SERVICES_10;
+/// Create a TAXII response with an XML StatusMessage of the given status type.
+pub(crate) fn taxii_status_response(
+    status_type: &str,
+    message: &str,
+    in_response_to: Option<&str>,
+    status: StatusCode,
+    version: &str,
+    is_secure: bool,
+) -> Response {
+    let message_id = taxii_1x::http::generate_message_id();
 
-    let (xml_result, services_value, content_type_value) = if is_10 {
+    let xml_result = if is_taxii_10(version) {
         // TAXII 1.0 StatusMessage
-        let status_msg = tm10::StatusMessage::failure(
+        let mut status_msg = tm10::StatusMessage::failure(
             message_id,
             in_response_to.map(String::from),
             Some(message.to_string()),
         );
-        let xml = tm10::Taxii10Message::StatusMessage(status_msg).to_xml();
-        (xml, taxii_1x::VID_TAXII_SERVICES_10, VID_TAXII_XML_10)
+        status_msg.status_type = status_type.to_string();
+        tm10::Taxii10Message::StatusMessage(status_msg).to_xml()
     } else {
         // TAXII 1.1 StatusMessage (default)
-        let status_msg = tm11::StatusMessage::failure(
+        let mut status_msg = tm11::StatusMessage::failure(
             message_id,
             in_response_to.map(String::from),
             Some(message.to_string()),
         );
-        let xml = tm11::Taxii11Message::StatusMessage(status_msg).to_xml();
-        (xml, taxii_1x::VID_TAXII_SERVICES_11, VID_TAXII_XML_11)
+        status_msg.status_type = status_type.to_string();
+        tm11::Taxii11Message::StatusMessage(status_msg).to_xml()
+    };
+
+    status_xml_response(xml_result, message, status, version, is_secure)
+}
+
+/// Create a TAXII response for an error returned by a message handler.
+///
+/// Status messages are sent as they are; other errors are logged and
+/// reported as a generic failure. The HTTP status follows
+/// [`crate::error::taxii1x_status`].
+fn handler_error_response(
+    error: &Taxii1xError,
+    in_response_to: &str,
+    version: &str,
+    is_secure: bool,
+) -> Response {
+    let status = taxii1x_status(error);
+    if matches!(error, Taxii1xError::StatusMessage { .. }) {
+        return taxii_status_error_response(error, status, version, is_secure);
+    }
+
+    error!("TAXII 1.x handler error: {:?}", error);
+    taxii_error_response(
+        "Processing error occurred",
+        Some(in_response_to),
+        status,
+        version,
+        is_secure,
+    )
+}
+
+/// Create a TAXII response with an XML StatusMessage reporting an error.
+///
+/// Status errors keep their status type and detail; see
+/// [`Taxii1xError::to_status_message_11`].
+pub(crate) fn taxii_status_error_response(
+    error: &Taxii1xError,
+    status: StatusCode,
+    version: &str,
+    is_secure: bool,
+) -> Response {
+    let message_id = taxii_1x::http::generate_message_id();
+
+    let xml_result = if is_taxii_10(version) {
+        tm10::Taxii10Message::StatusMessage(error.to_status_message_10(message_id)).to_xml()
+    } else {
+        tm11::Taxii11Message::StatusMessage(error.to_status_message_11(message_id)).to_xml()
+    };
+
+    status_xml_response(xml_result, &error.to_string(), status, version, is_secure)
+}
+
+/// Whether a message or services binding is TAXII 1.0.
+fn is_taxii_10(version: &str) -> bool {
+    version == VID_TAXII_XML_10 || version == taxii_1x::VID_TAXII_SERVICES_10
+}
+
+/// Wrap a serialized StatusMessage with the TAXII headers of its version.
+///
+/// Falls back to a plain text `message` if serialization failed.
+fn status_xml_response(
+    xml_result: Result<String, Taxii1xError>,
+    message: &str,
+    status: StatusCode,
+    version: &str,
+    is_secure: bool,
+) -> Response {
+    let protocol_binding = get_protocol_binding(is_secure);
+    let (services_value, content_type_value) = if is_taxii_10(version) {
+        (taxii_1x::VID_TAXII_SERVICES_10, VID_TAXII_XML_10)
+    } else {
+        (taxii_1x::VID_TAXII_SERVICES_11, VID_TAXII_XML_11)
     };
 
     match xml_result {
@@ -391,7 +524,7 @@ fn taxii_error_response(
 /// Get version from request headers for error responses.
 ///
 /// Checks X-TAXII-Accept first, then X-TAXII-Content-Type. Defaults to TAXII 1.1.
-fn get_version_from_headers(headers: &HeaderMap) -> &'static str {
+pub(crate) fn get_version_from_headers(headers: &HeaderMap) -> &'static str {
     // First try X-TAXII-Accept
     if let Some(accept) = headers
         .get(HTTP_X_TAXII_ACCEPT)
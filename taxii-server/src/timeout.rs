@@ -0,0 +1,161 @@
+//! Per-request deadlines for TAXII handlers.
+//!
+//! Handlers that do not complete within the deadline of their route class are
+//! aborted (the handler future is dropped, cancelling its database queries)
+//! and the client receives `503 Service Unavailable` with a TAXII error body.
+
+use std::time::Duration;
+
+use axum::extract::{Request, State};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use tracing::warn;
+
+use taxii_2x::Taxii2Error;
+
+use crate::taxii1x_routes::{get_version_from_headers, is_request_secure, taxii_error_response};
+
+/// Deadline per route class. `None` disables the deadline.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RequestTimeouts {
+    /// TAXII 2.x discovery, API root, collection and status endpoints.
+    pub discovery: Option<Duration>,
+    /// TAXII 2.x object, manifest and version endpoints.
+    pub objects: Option<Duration>,
+    /// TAXII 1.x services.
+    pub taxii1: Option<Duration>,
+}
+
+impl RequestTimeouts {
+    /// Build from seconds per route class, where 0 disables the deadline.
+    pub fn from_secs(discovery: u64, objects: u64, taxii1: u64) -> Self {
+        let deadline = |secs| (secs > 0).then(|| Duration::from_secs(secs));
+        Self {
+            discovery: deadline(discovery),
+            objects: deadline(objects),
+            taxii1: deadline(taxii1),
+        }
+    }
+}
+
+/// Middleware aborting TAXII 2.x requests that exceed the deadline.
+pub async fn taxii2_timeout(State(limit): State<Duration>, req: Request, next: Next) -> Response {
+    let path = req.uri().path().to_string();
+
+    match tokio::time::timeout(limit, next.run(req)).await {
+        Ok(response) => response,
+        Err(_) => {
+            warn!(path = %path, limit_ms = limit.as_millis(), "TAXII 2.x request timed out");
+            Taxii2Error::Timeout.into_response()
+        }
+    }
+}
+
+/// Middleware aborting TAXII 1.x requests that exceed the deadline.
+///
+/// The response is a failure Status Message in the version of the request.
+pub async fn taxii1x_timeout(State(limit): State<Duration>, req: Request, next: Next) -> Response {
+    let path = req.uri().path().to_string();
+    let version = get_version_from_headers(req.headers());
+    let is_secure = is_request_secure(req.headers(), req.uri());
+
+    match tokio::time::timeout(limit, next.run(req)).await {
+        Ok(response) => response,
+        Err(_) => {
+            warn!(path = %path, limit_ms = limit.as_millis(), "TAXII 1.x request timed out");
+            taxii_error_response(
+                "Request timed out",
+                None,
+                StatusCode::SERVICE_UNAVAILABLE,
+                version,
+                is_secure,
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::Router;
+    use axum::body::{Body, to_bytes};
+    use axum::middleware::from_fn_with_state;
+    use axum::routing::{get, post};
+    use tower::ServiceExt;
+
+    type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+    const LIMIT: Duration = Duration::from_millis(50);
+
+    async fn slow_handler() -> &'static str {
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        "done"
+    }
+
+    async fn fast_handler() -> &'static str {
+        "done"
+    }
+
+    fn taxii2_app() -> Router {
+        Router::new()
+            .route("/slow/", get(slow_handler))
+            .route("/fast/", get(fast_handler))
+            .layer(from_fn_with_state(LIMIT, taxii2_timeout))
+    }
+
+    #[tokio::test]
+    async fn test_taxii2_slow_handler_times_out() -> TestResult {
+        let request = Request::builder().uri("/slow/").body(Body::empty())?;
+        let response = taxii2_app().oneshot(request).await?;
+
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body: serde_json::Value =
+            serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await?)?;
+        assert_eq!(body["title"], "Service Unavailable");
+        assert_eq!(body["http_status"], 503);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_taxii2_fast_handler_completes() -> TestResult {
+        let request = Request::builder().uri("/fast/").body(Body::empty())?;
+        let response = taxii2_app().oneshot(request).await?;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(to_bytes(response.into_body(), usize::MAX).await?, "done");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_taxii1x_slow_handler_times_out() -> TestResult {
+        let app = Router::new()
+            .route("/services/poll/", post(slow_handler))
+            .layer(from_fn_with_state(LIMIT, taxii1x_timeout));
+
+        let request = Request::builder()
+            .method("POST")
+            .uri("/services/poll/")
+            .body(Body::empty())?;
+        let response = app.oneshot(request).await?;
+
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = to_bytes(response.into_body(), usize::MAX).await?;
+        let body = std::str::from_utf8(&body)?;
+        assert!(body.contains("Status_Message"));
+        assert!(body.contains("FAILURE"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_zero_disables_deadline() {
+        let timeouts = RequestTimeouts::from_secs(10, 0, 30);
+
+        assert_eq!(timeouts.discovery, Some(Duration::from_secs(10)));
+        assert_eq!(timeouts.objects, None);
+        assert_eq!(timeouts.taxii1, Some(Duration::from_secs(30)));
+    }
+}